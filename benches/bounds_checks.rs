@@ -0,0 +1,49 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use wasmer::*;
+
+/// A loop copying 32 bytes at a time, accessing each block at decreasing offsets so that only
+/// the first access to each block is checked with redundant bounds checks elided.
+static COPY_WAT: &str = r#"(module
+    (memory (export "memory") 4)
+    (func (export "copy") (param $dst i32) (param $src i32) (param $blocks i32)
+        (block $done
+            (loop $loop
+                (br_if $done (i32.eqz (local.get $blocks)))
+                (i64.store offset=24 (local.get $dst) (i64.load offset=24 (local.get $src)))
+                (i64.store offset=16 (local.get $dst) (i64.load offset=16 (local.get $src)))
+                (i64.store offset=8 (local.get $dst) (i64.load offset=8 (local.get $src)))
+                (i64.store (local.get $dst) (i64.load (local.get $src)))
+                (local.set $dst (i32.add (local.get $dst) (i32.const 32)))
+                (local.set $src (i32.add (local.get $src) (i32.const 32)))
+                (local.set $blocks (i32.sub (local.get $blocks) (i32.const 1)))
+                (br $loop)))))
+"#;
+
+fn copy_loop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bounds_checks");
+    for elide in [false, true] {
+        let mut compiler = Singlepass::new();
+        compiler.redundant_bounds_check_elimination(elide);
+        let store = Store::new(&Universal::new(compiler).engine());
+        let module = Module::new(&store, COPY_WAT).unwrap();
+        let instance = Instance::new(&module, &imports! {}).unwrap();
+        let copy = instance
+            .get_native_function::<(i32, i32, i32), ()>("copy")
+            .unwrap();
+        let name = if elide { "elided" } else { "checked" };
+        group.bench_function(BenchmarkId::new("copy_128KiB", name), |b| {
+            b.iter(|| {
+                copy.call(black_box(0), black_box(131072), black_box(4096))
+                    .unwrap()
+            })
+        });
+    }
+}
+
+criterion_group! {
+    name = bounds_checks;
+    config = Criterion::default();
+    targets = copy_loop
+}
+
+criterion_main!(bounds_checks);
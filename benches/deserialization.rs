@@ -0,0 +1,198 @@
+use criterion::measurement::{Measurement, ValueFormatter};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use wasmer::*;
+
+/// The number of functions of the module serialized and loaded.
+const FUNCTIONS: usize = 20_000;
+
+/// Bytes currently allocated on the heap.
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+/// The most bytes allocated on the heap at once since the last reset.
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// The system allocator, counting what is allocated through it.
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+            let allocated = ALLOCATED.fetch_add(new_size, Ordering::Relaxed) + new_size;
+            PEAK.fetch_max(allocated, Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// The heap high-water mark of a run, above what was allocated when it started.
+///
+/// Code memory is mapped rather than allocated on the heap, so that this only counts the
+/// copies of the serialized module and what loading it keeps.
+struct PeakHeap;
+
+impl Measurement for PeakHeap {
+    type Intermediate = usize;
+    type Value = usize;
+
+    fn start(&self) -> usize {
+        let allocated = ALLOCATED.load(Ordering::Relaxed);
+        PEAK.store(allocated, Ordering::Relaxed);
+        allocated
+    }
+
+    fn end(&self, start: usize) -> usize {
+        PEAK.load(Ordering::Relaxed).saturating_sub(start)
+    }
+
+    fn add(&self, v1: &usize, v2: &usize) -> usize {
+        v1 + v2
+    }
+
+    fn zero(&self) -> usize {
+        0
+    }
+
+    fn to_f64(&self, value: &usize) -> f64 {
+        *value as f64
+    }
+
+    fn formatter(&self) -> &dyn ValueFormatter {
+        &BytesFormatter
+    }
+}
+
+struct BytesFormatter;
+
+impl ValueFormatter for BytesFormatter {
+    fn scale_values(&self, _typical_value: f64, values: &mut [f64]) -> &'static str {
+        for value in values {
+            *value /= 1024.0;
+        }
+        "KiB"
+    }
+
+    fn scale_throughputs(
+        &self,
+        _typical_value: f64,
+        _throughput: &Throughput,
+        values: &mut [f64],
+    ) -> &'static str {
+        self.scale_values(0.0, values)
+    }
+
+    fn scale_for_machines(&self, _values: &mut [f64]) -> &'static str {
+        "B"
+    }
+}
+
+/// A module of `count` small functions, every other one calling the next.
+fn many_functions(count: usize) -> String {
+    let mut wat = String::from("(module\n");
+    for i in 0..count {
+        let body = if i % 2 == 0 && i + 1 < count {
+            format!(
+                "(call $f{} (i32.add (local.get 0) (i32.const {})))",
+                i + 1,
+                i
+            )
+        } else {
+            format!("(i32.mul (local.get 0) (i32.const {}))", i)
+        };
+        wat += &format!(
+            "(func $f{} (export \"f{}\") (param i32) (result i32) {})\n",
+            i, i, body
+        );
+    }
+    wat += ")";
+    wat
+}
+
+/// Load the module serialized into the file at `path`, reading the file into memory first or
+/// mapping it.
+fn load(store: &Store, path: &Path, mapped: bool) -> Module {
+    unsafe {
+        if mapped {
+            Module::deserialize_from_file(store, path).unwrap()
+        } else {
+            Module::deserialize(store, &std::fs::read(path).unwrap()).unwrap()
+        }
+    }
+}
+
+/// A store, and a file holding a large module serialized with it.
+fn serialized_module() -> (Store, tempfile::NamedTempFile) {
+    let store = Store::new(&Universal::new(Singlepass::new()).engine());
+    let module = Module::new(&store, many_functions(FUNCTIONS)).unwrap();
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), module.serialize().unwrap()).unwrap();
+    (store, file)
+}
+
+fn load_time(c: &mut Criterion) {
+    let (store, file) = serialized_module();
+    let mut group = c.benchmark_group("deserialize_time");
+    group.sample_size(10);
+    for &mapped in &[false, true] {
+        let name = if mapped { "mapped" } else { "read" };
+        group.bench_function(BenchmarkId::new("20k_functions", name), |b| {
+            b.iter(|| load(&store, file.path(), mapped))
+        });
+    }
+}
+
+fn peak_heap(c: &mut Criterion<PeakHeap>) {
+    let (store, file) = serialized_module();
+    let mut group = c.benchmark_group("deserialize_peak_heap");
+    group.sample_size(10);
+    for &mapped in &[false, true] {
+        let name = if mapped { "mapped" } else { "read" };
+        group.bench_function(BenchmarkId::new("20k_functions", name), |b| {
+            // Each load is measured on its own, so that the mean is the peak of one load.
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| {
+                        let start = PeakHeap.start();
+                        drop(load(&store, file.path(), mapped));
+                        PeakHeap.end(start)
+                    })
+                    .sum()
+            })
+        });
+    }
+}
+
+criterion_group! {
+    name = time;
+    config = Criterion::default().measurement_time(Duration::from_secs(10));
+    targets = load_time
+}
+
+criterion_group! {
+    name = heap;
+    config = Criterion::default().with_measurement(PeakHeap);
+    targets = peak_heap
+}
+
+criterion_main!(time, heap);
@@ -0,0 +1,70 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use wasmer::*;
+
+/// Many calls to small functions, each loading and storing memory, so that the diagnostics
+/// checks on function entry and memory accesses make up a large share of the work.
+static KERNEL_WAT: &str = r#"(module
+    (memory 1)
+    (func $bump (param $address i32) (result i32)
+        (i32.store (local.get $address)
+            (i32.add (i32.load (local.get $address)) (i32.const 1)))
+        (i32.load (local.get $address)))
+    (func (export "kernel") (param $n i32) (result i32)
+        (local $sum i32)
+        (block $done
+            (loop $loop
+                (br_if $done (i32.eqz (local.get $n)))
+                (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                (local.set $sum
+                    (i32.add (local.get $sum)
+                        (call $bump (i32.and (local.get $n) (i32.const 0xffc)))))
+                (br $loop)))
+        (local.get $sum)))
+"#;
+
+/// Calls from the host into a function doing nothing but an addition.
+static ADD_WAT: &str = r#"(module
+    (func (export "add") (param i32 i32) (result i32)
+        (i32.add (local.get 0) (local.get 1))))
+"#;
+
+/// Instantiate `wat` with diagnostics compiled in or not, and left off in any case.
+fn instantiate(wat: &str, diagnostics: bool) -> (Instance, &'static str) {
+    let mut compiler = Singlepass::new();
+    compiler.diagnostics(diagnostics);
+    let store = Store::new(&Universal::new(compiler).engine());
+    let module = Module::new(&store, wat).unwrap();
+    let instance = Instance::new(&module, &imports! {}).unwrap();
+    let name = if diagnostics {
+        "diagnostics_off"
+    } else {
+        "no_diagnostics"
+    };
+    (instance, name)
+}
+
+fn call_heavy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("call_heavy");
+    for diagnostics in [false, true] {
+        let (instance, name) = instantiate(ADD_WAT, diagnostics);
+        let add = instance
+            .get_native_function::<(i32, i32), i32>("add")
+            .unwrap();
+        group.bench_function(BenchmarkId::new("add", name), |b| {
+            b.iter(|| black_box(add.call(black_box(4), black_box(6)).unwrap()))
+        });
+        let (instance, name) = instantiate(KERNEL_WAT, diagnostics);
+        let kernel = instance.get_native_function::<i32, i32>("kernel").unwrap();
+        group.bench_function(BenchmarkId::new("kernel", name), |b| {
+            b.iter(|| black_box(kernel.call(black_box(10_000)).unwrap()))
+        });
+    }
+}
+
+criterion_group! {
+    name = diagnostics;
+    config = Criterion::default();
+    targets = call_heavy
+}
+
+criterion_main!(diagnostics);
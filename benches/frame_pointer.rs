@@ -0,0 +1,66 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use wasmer::*;
+
+/// A loop keeping eight values alive across each iteration, more than singlepass has registers
+/// for, so that every freed register saves spills.
+static KERNEL_WAT: &str = r#"(module
+    (func (export "kernel") (param $n i32) (param $seed i64) (result i64)
+        (local $a i64) (local $b i64) (local $c i64) (local $d i64)
+        (local $e i64) (local $f i64) (local $g i64) (local $h i64)
+        (local.set $a (local.get $seed))
+        (local.set $b (i64.add (local.get $seed) (i64.const 1)))
+        (local.set $c (i64.mul (local.get $seed) (i64.const 3)))
+        (local.set $d (i64.xor (local.get $seed) (i64.const 0x5555)))
+        (block $done
+            (loop $loop
+                (br_if $done (i32.eqz (local.get $n)))
+                (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                (local.set $e (i64.add (local.get $a) (local.get $b)))
+                (local.set $f (i64.mul (local.get $c) (local.get $d)))
+                (local.set $g (i64.rotl (local.get $e) (local.get $f)))
+                (local.set $h (i64.xor (local.get $g) (local.get $e)))
+                (local.set $a
+                    (i64.add
+                        (i64.add
+                            (i64.add (i64.mul (local.get $a) (local.get $e)) (local.get $b))
+                            (i64.add (i64.mul (local.get $c) (local.get $f)) (local.get $d)))
+                        (i64.add
+                            (i64.add (i64.mul (local.get $e) (local.get $g)) (local.get $f))
+                            (i64.add (i64.mul (local.get $g) (local.get $h)) (local.get $h)))))
+                (local.set $b (i64.xor (local.get $b) (local.get $h)))
+                (local.set $c (i64.sub (local.get $c) (local.get $g)))
+                (local.set $d (i64.rotr (local.get $d) (local.get $a)))
+                (br $loop)))
+        (i64.add (local.get $a)
+            (i64.add (local.get $b) (i64.add (local.get $c) (local.get $d))))))
+"#;
+
+fn register_pressure(c: &mut Criterion) {
+    let mut group = c.benchmark_group("register_pressure");
+    for frame_pointer in [true, false] {
+        let mut compiler = Singlepass::new();
+        compiler.frame_pointer(frame_pointer);
+        let store = Store::new(&Universal::new(compiler).engine());
+        let module = Module::new(&store, KERNEL_WAT).unwrap();
+        let instance = Instance::new(&module, &imports! {}).unwrap();
+        let kernel = instance
+            .get_native_function::<(i32, i64), i64>("kernel")
+            .unwrap();
+        let name = if frame_pointer {
+            "frame_pointer"
+        } else {
+            "no_frame_pointer"
+        };
+        group.bench_function(BenchmarkId::new("kernel", name), |b| {
+            b.iter(|| black_box(kernel.call(black_box(10_000), black_box(42)).unwrap()))
+        });
+    }
+}
+
+criterion_group! {
+    name = frame_pointer;
+    config = Criterion::default();
+    targets = register_pressure
+}
+
+criterion_main!(frame_pointer);
@@ -0,0 +1,210 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use wasmer::*;
+
+/// Counts the allocations made by the benchmarks.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+static WAT: &str = r#"(module
+    (memory 1)
+    (global $g (mut i32) (i32.const 0))
+    (table 4 funcref)
+    (func $add (export "add") (param i32 i32) (result i32)
+        (i32.add (local.get 0) (local.get 1)))
+    (elem (i32.const 0) $add))
+"#;
+
+/// Writes a byte to every 4 KiB of its 16 MiB of memory.
+static SWEEP_WAT: &str = r#"(module
+    (memory 256)
+    (func (export "sweep")
+        (local $addr i32)
+        (loop $next
+            (i32.store8 (local.get $addr) (i32.const 1))
+            (local.set $addr (i32.add (local.get $addr) (i32.const 4096)))
+            (br_if $next (i32.lt_u (local.get $addr) (i32.const 16777216))))))
+"#;
+
+/// Return the number of allocations made by `f`, averaged over a few runs.
+fn allocations_per_run(mut f: impl FnMut()) -> usize {
+    const RUNS: usize = 100;
+    f();
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..RUNS {
+        f();
+    }
+    (ALLOCATIONS.load(Ordering::Relaxed) - before) / RUNS
+}
+
+fn instantiate(c: &mut Criterion) {
+    let store = Store::new(&Universal::new(Singlepass::new()).engine());
+    let module = Module::new(&store, WAT).unwrap();
+    let scope = InstanceScope::new();
+
+    let owned = || {
+        let instance = Instance::new(&module, &imports! {}).unwrap();
+        let add = instance
+            .get_native_function::<(i32, i32), i32>("add")
+            .unwrap();
+        black_box(add.call(1, 2).unwrap());
+    };
+    let scoped = || {
+        let instance = module
+            .instantiate_scoped(&imports! {}, InstanceConfig::default(), &scope)
+            .unwrap();
+        let add = instance
+            .get_native_function::<(i32, i32), i32>("add")
+            .unwrap();
+        black_box(add.call(1, 2).unwrap());
+    };
+    println!(
+        "allocations per instantiation: owned {}, scoped {}",
+        allocations_per_run(owned),
+        allocations_per_run(scoped)
+    );
+
+    let mut group = c.benchmark_group("instantiate");
+    group.bench_function("owned", |b| b.iter(owned));
+    group.bench_function("scoped", |b| b.iter(scoped));
+}
+
+/// Time the first call into fresh instances, which faults in the pages of their memory unless
+/// they were pretouched.
+fn first_call(c: &mut Criterion) {
+    let store = Store::new(&Universal::new(Singlepass::new()).engine());
+    let module = Module::new(&store, SWEEP_WAT).unwrap();
+
+    let mut group = c.benchmark_group("first_call");
+    for (name, mode) in [
+        ("cold", PretouchMode::None),
+        ("pretouched", PretouchMode::AllMinimumPages),
+    ] {
+        let policy = PretouchPolicy {
+            memory: mode,
+            tables: true,
+        };
+        group.bench_function(name, |b| {
+            b.iter_custom(|iters| {
+                let mut elapsed = Duration::default();
+                for _ in 0..iters {
+                    let config = InstanceConfig::default().with_pretouch(policy);
+                    let instance =
+                        Instance::new_with_config(&module, config, &imports! {}).unwrap();
+                    let sweep = instance.get_native_function::<(), ()>("sweep").unwrap();
+                    let started = Instant::now();
+                    sweep.call().unwrap();
+                    elapsed += started.elapsed();
+                }
+                elapsed
+            })
+        });
+    }
+}
+
+/// Time instantiating a module with 16 MiB of data segments, copied into each instance or
+/// mapped copy-on-write.
+fn data_segments(c: &mut Criterion) {
+    let data = "\\ff".repeat(65536);
+    let wat = format!(
+        "(module (memory 256) {})",
+        (0..256)
+            .map(|page| format!("(data (i32.const {}) \"{}\")", page * 65536, data))
+            .collect::<String>()
+    );
+    let engine = Universal::new(Singlepass::new()).engine();
+
+    let mut group = c.benchmark_group("data_segments");
+    for (name, copy_on_write) in [("copied", false), ("copy_on_write", true)] {
+        let tunables = BaseTunables {
+            copy_on_write_memories: copy_on_write,
+            ..BaseTunables::for_target(engine.target())
+        };
+        let store = Store::new_with_tunables(&engine, tunables);
+        let module = Module::new(&store, &wat).unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(Instance::new(&module, &imports! {}).unwrap()))
+        });
+    }
+}
+
+/// Time bringing an instance of a module with 1 MiB of data segments back to its initial
+/// state, by resetting it or instantiating the module again.
+fn reset(c: &mut Criterion) {
+    let data = "\\ff".repeat(65536);
+    let wat = format!(
+        "(module (memory 16) (global (mut i32) (i32.const 0)) {})",
+        (0..16)
+            .map(|page| format!("(data (i32.const {}) \"{}\")", page * 65536, data))
+            .collect::<String>()
+    );
+    let engine = Universal::new(Singlepass::new()).engine();
+
+    let mut group = c.benchmark_group("reset");
+    for (name, copy_on_write) in [("copied", false), ("copy_on_write", true)] {
+        let tunables = BaseTunables {
+            copy_on_write_memories: copy_on_write,
+            ..BaseTunables::for_target(engine.target())
+        };
+        let store = Store::new_with_tunables(&engine, tunables);
+        let module = Module::new(&store, &wat).unwrap();
+        group.bench_function(format!("instantiate/{}", name), |b| {
+            b.iter(|| black_box(Instance::new(&module, &imports! {}).unwrap()))
+        });
+        let instance = Instance::new(&module, &imports! {}).unwrap();
+        group.bench_function(format!("reset/{}", name), |b| {
+            b.iter(|| instance.reset(false).unwrap())
+        });
+    }
+}
+
+/// Time instantiating a module with 64 MiB of data segments in 64 KiB pieces, which are
+/// merged and copied at once with non-temporal stores when they follow each other, and copied
+/// one by one otherwise.
+fn large_data_segments(c: &mut Criterion) {
+    let data = "w".repeat(65536);
+    let segment = |page: usize| format!("(data (i32.const {}) \"{}\")", page * 65536, data);
+    let engine = Universal::new(Singlepass::new()).engine();
+    let store = Store::new(&engine);
+
+    let mut group = c.benchmark_group("large_data_segments");
+    group.sample_size(10);
+    for (name, pages) in [
+        ("merged", (0..1024).collect::<Vec<_>>()),
+        ("unmerged", (0..1024).rev().collect()),
+    ] {
+        let wat = format!(
+            "(module (memory 1024) {})",
+            pages.into_iter().map(segment).collect::<String>()
+        );
+        let module = Module::new(&store, &wat).unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(Instance::new(&module, &imports! {}).unwrap()))
+        });
+    }
+}
+
+criterion_group! {
+    name = instantiation;
+    config = Criterion::default();
+    targets = instantiate, first_call, data_segments, reset, large_data_segments
+}
+
+criterion_main!(instantiation);
@@ -0,0 +1,59 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use wasmer::*;
+
+/// The number of functions of the module compiled and loaded.
+const FUNCTIONS: usize = 150_000;
+
+/// A module of `count` small functions, every other one calling the next.
+fn many_functions(count: usize) -> Vec<u8> {
+    let mut wat = String::from("(module\n");
+    for i in 0..count {
+        let body = if i % 2 == 0 && i + 1 < count {
+            format!(
+                "(call $f{} (i32.add (local.get 0) (i32.const {})))",
+                i + 1,
+                i
+            )
+        } else {
+            format!("(i32.mul (local.get 0) (i32.const {}))", i)
+        };
+        wat += &format!(
+            "(func $f{} (export \"f{}\") (param i32) (result i32) {})\n",
+            i, i, body
+        );
+    }
+    wat += ")";
+    wat::parse_str(wat).unwrap()
+}
+
+fn compile_and_load(c: &mut Criterion) {
+    let binary = many_functions(FUNCTIONS);
+    let mut group = c.benchmark_group("compile_and_load");
+    group.sample_size(10);
+    for &pipelined in &[false, true] {
+        let engine = Universal::new(Singlepass::new())
+            .pipelined_loading(pipelined)
+            .engine();
+        let store = Store::new(&engine);
+        let name = if pipelined { "pipelined" } else { "serial" };
+        group.bench_function(BenchmarkId::new("150k_functions", name), |b| {
+            b.iter(|| {
+                drop(
+                    engine
+                        .compile_and_load_universal(&binary, store.tunables())
+                        .unwrap(),
+                );
+                // Release the code of the artifact right away, as each iteration maps more.
+                engine.reclaim(usize::MAX, ReclaimPriority::Unused);
+            })
+        });
+    }
+}
+
+criterion_group! {
+    name = pipelined_loading;
+    config = Criterion::default();
+    targets = compile_and_load
+}
+
+criterion_main!(pipelined_loading);
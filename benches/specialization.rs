@@ -0,0 +1,56 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use wasmer::*;
+
+/// A loop bounded by an immutable imported global, read on every iteration.
+static LOOP_WAT: &str = r#"(module
+    (import "env" "max_items" (global $max_items i32))
+    (func (export "sum") (result i32)
+        (local $i i32) (local $sum i32)
+        (block $done
+            (loop $loop
+                (br_if $done (i32.ge_u (local.get $i) (global.get $max_items)))
+                (local.set $sum (i32.add (local.get $sum) (local.get $i)))
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br $loop)))
+        (local.get $sum)))
+"#;
+
+/// Instantiate `LOOP_WAT`, specialized for its bound or not.
+fn instantiate(store: &Store, specialized: bool) -> (Instance, &'static str) {
+    let mut module = Module::new(store, LOOP_WAT).unwrap();
+    if specialized {
+        module = module
+            .specialize(&[("env", "max_items", Value::I32(10_000))])
+            .unwrap();
+    }
+    let imports = imports! {
+        "env" => { "max_items" => Global::new(store, Value::I32(10_000)) },
+    };
+    let instance = Instance::new(&module, &imports).unwrap();
+    let name = if specialized {
+        "specialized"
+    } else {
+        "generic"
+    };
+    (instance, name)
+}
+
+fn bounded_loop(c: &mut Criterion) {
+    let store = Store::new(&Universal::new(Singlepass::new()).engine());
+    let mut group = c.benchmark_group("bounded_loop");
+    for specialized in [false, true] {
+        let (instance, name) = instantiate(&store, specialized);
+        let sum = instance.get_native_function::<(), i32>("sum").unwrap();
+        group.bench_function(BenchmarkId::new("sum", name), |b| {
+            b.iter(|| black_box(sum.call().unwrap()))
+        });
+    }
+}
+
+criterion_group! {
+    name = specialization;
+    config = Criterion::default();
+    targets = bounded_loop
+}
+
+criterion_main!(specialization);
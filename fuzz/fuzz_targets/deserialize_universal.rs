@@ -0,0 +1,23 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wasmer_engine_universal::{DeserializeLimits, UniversalExecutableRef};
+
+fuzz_target!(|data: &[u8]| {
+    // Small limits, so that inputs claiming more than the limits allow are explored too.
+    let limits = DeserializeLimits {
+        max_total_size: 1 << 20,
+        max_functions: 1024,
+        max_relocations: 1 << 16,
+        max_custom_metadata: 16,
+    };
+    let executable = match unsafe { UniversalExecutableRef::deserialize_with_limits(data, &limits) }
+    {
+        Ok(executable) => executable,
+        Err(_) => return,
+    };
+    // Everything the deserializer checked can be read without going out of `data`.
+    let _ = executable.function_hashes();
+    let _ = executable.signature_block().map(|block| block.len());
+    let _ = executable.provenance();
+});
@@ -302,7 +302,16 @@
 //! - `dylib`
 #![cfg_attr(feature = "dylib", doc = "(enabled),")]
 #![cfg_attr(not(feature = "dylib"), doc = "(disabled),")]
-//!   enables [the Dylib engine][`wasmer-engine-dylib`].
+//!   enables [the Dylib engine][`wasmer-engine-dylib`],
+//! - `rpc`
+#![cfg_attr(feature = "rpc", doc = "(enabled),")]
+#![cfg_attr(not(feature = "rpc"), doc = "(disabled),")]
+//!   enables conversions between values and JSON for RPC layers, in the `rpc` module,
+//! - `conformance`
+#![cfg_attr(feature = "conformance", doc = "(enabled),")]
+#![cfg_attr(not(feature = "conformance"), doc = "(disabled),")]
+//!   enables randomized checking of engines against a reference evaluator, in the
+//!   `conformance` module.
 //!
 //! The features that set defaults come in sets that are mutually exclusive.
 //!
@@ -0,0 +1,385 @@
+//! Checking a corpus of modules against a proposed set of host imports, without instantiating
+//! or compiling them.
+//!
+//! ```
+//! # use wasmer::*;
+//! # use wasmer::abi_check::*;
+//! let gas = FunctionType::new(vec![Type::I64], vec![]);
+//! let imports = [ImportDescriptor::new("env", "gas", ExternType::Function(gas))];
+//! let ok = wat2wasm(br#"(module (import "env" "gas" (func (param i64))))"#).unwrap();
+//! let old = wat2wasm(br#"(module (import "env" "gas" (func (param i32))))"#).unwrap();
+//! let corpus = vec![ModuleSource::wasm("ok", ok), ModuleSource::wasm("old", old)];
+//! let report = check_corpus(corpus.into_iter(), &imports, &CorpusOptions::default());
+//! assert_eq!(report.compatible, ["ok"]);
+//! assert_eq!(report.broken[0].modules, ["old"]);
+//! ```
+
+use crate::sys::module::split_header;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use wasmer_compiler::ModuleEnvironment;
+use wasmer_engine::{is_compatible_memory, is_compatible_table};
+use wasmer_engine_universal::UniversalExecutableRef;
+use wasmer_types::{ExternType, FunctionType, GlobalType, MemoryType, TableType};
+
+/// An import the host proposes to provide.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ImportDescriptor {
+    /// The namespace of the import.
+    pub module: String,
+    /// The name of the import in its namespace.
+    pub name: String,
+    /// The type of what the host provides.
+    pub ty: ExternType,
+}
+
+impl ImportDescriptor {
+    /// Describe the import `module`.`name`, of type `ty`.
+    pub fn new(module: &str, name: &str, ty: ExternType) -> Self {
+        Self {
+            module: module.to_string(),
+            name: name.to_string(),
+            ty,
+        }
+    }
+}
+
+/// A module of the corpus given to [`check_corpus`], with the identifier it is reported
+/// under.
+#[derive(Clone, Debug)]
+pub struct ModuleSource {
+    id: String,
+    kind: SourceKind,
+}
+
+#[derive(Clone, Debug)]
+enum SourceKind {
+    Wasm(Vec<u8>),
+    Serialized(Vec<u8>),
+}
+
+impl ModuleSource {
+    /// A module given as its WebAssembly binary, whose sections are parsed but whose code is
+    /// not compiled.
+    pub fn wasm(id: impl Into<String>, bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            id: id.into(),
+            kind: SourceKind::Wasm(bytes.into()),
+        }
+    }
+
+    /// A module given as [serialized](crate::Module::serialize), whose imports are read from
+    /// the metadata of its executable without loading its code.
+    ///
+    /// # Safety
+    ///
+    /// See [`Module::deserialize`](crate::Module::deserialize): damage to the parts of the
+    /// executable that are checked is reported as corruption, the others must be intact.
+    pub unsafe fn serialized(id: impl Into<String>, bytes: impl Into<Vec<u8>>) -> Self {
+        Self {
+            id: id.into(),
+            kind: SourceKind::Serialized(bytes.into()),
+        }
+    }
+
+    /// The identifier of the module.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The namespaces, names and types of the imports of the module.
+    fn imports(&self) -> Result<Vec<(String, String, ExternType)>, String> {
+        match &self.kind {
+            SourceKind::Wasm(bytes) => {
+                let environ = ModuleEnvironment::new()
+                    .translate(bytes)
+                    .map_err(|e| e.to_string())?;
+                Ok(environ
+                    .module
+                    .imports()
+                    .map(|(module, name, ty)| (module.to_string(), name.to_string(), ty))
+                    .collect())
+            }
+            SourceKind::Serialized(bytes) => {
+                let (_, executable) = split_header(bytes).map_err(|e| e.to_string())?;
+                UniversalExecutableRef::with_aligned(executable, |executable| {
+                    // The caller vouched for the bytes, see `ModuleSource::serialized`.
+                    let executable = unsafe { UniversalExecutableRef::deserialize(executable) }
+                        .map_err(|e| e.to_string())?;
+                    Ok(executable.imports())
+                })
+            }
+        }
+    }
+}
+
+/// How [`check_corpus`] checks the corpus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CorpusOptions {
+    /// How many modules are checked at once, on as many threads. Defaults to 4.
+    pub threads: usize,
+}
+
+impl Default for CorpusOptions {
+    fn default() -> Self {
+        Self { threads: 4 }
+    }
+}
+
+/// Why an import of a module is not satisfied by the proposed imports.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IncompatibilityKind {
+    /// Nothing is proposed under its name.
+    Missing(ExternType),
+    /// Something of another kind than the import is proposed, such as a global for a function.
+    Kind {
+        /// The type the module imports.
+        required: ExternType,
+        /// The type proposed.
+        provided: ExternType,
+    },
+    /// A function of another signature is proposed.
+    Signature {
+        /// The signature the module imports.
+        required: FunctionType,
+        /// The signature proposed.
+        provided: FunctionType,
+    },
+    /// A memory whose limits or sharing do not fit the import is proposed.
+    Memory {
+        /// The memory type the module imports.
+        required: MemoryType,
+        /// The memory type proposed.
+        provided: MemoryType,
+    },
+    /// A table whose element type or limits do not fit the import is proposed.
+    Table {
+        /// The table type the module imports.
+        required: TableType,
+        /// The table type proposed.
+        provided: TableType,
+    },
+    /// A global of another type or mutability is proposed.
+    Global {
+        /// The global type the module imports.
+        required: GlobalType,
+        /// The global type proposed.
+        provided: GlobalType,
+    },
+}
+
+/// An import modules have that the proposed imports do not satisfy.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Incompatibility {
+    /// The namespace of the import.
+    pub module: String,
+    /// The name of the import in its namespace.
+    pub name: String,
+    /// Why it is not satisfied.
+    pub kind: IncompatibilityKind,
+}
+
+impl fmt::Display for Incompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}: ", self.module, self.name)?;
+        match &self.kind {
+            IncompatibilityKind::Missing(required) => write!(f, "missing {:?}", required),
+            IncompatibilityKind::Kind { required, provided } => {
+                write!(f, "expected {:?}, provided {:?}", required, provided)
+            }
+            IncompatibilityKind::Signature { required, provided } => {
+                write!(f, "expected function {}, provided {}", required, provided)
+            }
+            IncompatibilityKind::Memory { required, provided } => {
+                write!(f, "expected memory {}, provided {}", required, provided)
+            }
+            IncompatibilityKind::Table { required, provided } => {
+                write!(f, "expected table {}, provided {}", required, provided)
+            }
+            IncompatibilityKind::Global { required, provided } => {
+                write!(f, "expected global {}, provided {}", required, provided)
+            }
+        }
+    }
+}
+
+/// The modules sharing an [`Incompatibility`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncompatibilityGroup {
+    /// The unsatisfied import.
+    pub incompatibility: Incompatibility,
+    /// The identifiers of the modules with it, sorted.
+    pub modules: Vec<String>,
+}
+
+/// A module [`check_corpus`] could not read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CorruptModule {
+    /// The identifier of the module.
+    pub id: String,
+    /// Why it could not be read.
+    pub error: String,
+}
+
+/// What [`check_corpus`] found.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CorpusReport {
+    /// The identifiers of the modules whose imports are all satisfied, sorted.
+    pub compatible: Vec<String>,
+    /// The unsatisfied imports, each with the modules that have it, sorted by namespace, name
+    /// and description. A module with several unsatisfied imports is in several groups.
+    pub broken: Vec<IncompatibilityGroup>,
+    /// The modules that could not be read, sorted by identifier.
+    pub corrupt: Vec<CorruptModule>,
+}
+
+impl CorpusReport {
+    /// Whether every module of the corpus links against the proposed imports.
+    pub fn is_compatible(&self) -> bool {
+        self.broken.is_empty() && self.corrupt.is_empty()
+    }
+}
+
+/// Check which modules of `sources` would fail to link against `imports`, by the types of
+/// their imports alone.
+///
+/// The modules are neither compiled nor instantiated, and are read on
+/// [`CorpusOptions::threads`] threads as `sources` yields them: only the few being checked
+/// are held in memory at once. Modules that cannot be read are reported, not fatal.
+pub fn check_corpus(
+    sources: impl Iterator<Item = ModuleSource>,
+    imports: &[ImportDescriptor],
+    options: &CorpusOptions,
+) -> CorpusReport {
+    let provided: Arc<HashMap<(String, String), ExternType>> = Arc::new(
+        imports
+            .iter()
+            .map(|import| {
+                (
+                    (import.module.clone(), import.name.clone()),
+                    import.ty.clone(),
+                )
+            })
+            .collect(),
+    );
+    let threads = options.threads.max(1);
+    let (source_sender, source_receiver) = mpsc::sync_channel::<ModuleSource>(threads);
+    let source_receiver = Arc::new(Mutex::new(source_receiver));
+    let (result_sender, result_receiver) = mpsc::channel();
+    let workers = (0..threads)
+        .map(|_| {
+            let provided = provided.clone();
+            let sources = source_receiver.clone();
+            let results = result_sender.clone();
+            thread::spawn(move || loop {
+                let source = match sources.lock().unwrap().recv() {
+                    Ok(source) => source,
+                    Err(_) => break,
+                };
+                let result = source
+                    .imports()
+                    .map(|required| incompatibilities(&provided, required));
+                if results.send((source.id, result)).is_err() {
+                    break;
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(result_sender);
+
+    let mut report = CorpusReport::default();
+    let mut groups: HashMap<Incompatibility, Vec<String>> = HashMap::new();
+    let mut record = |(id, result): (String, Result<Vec<Incompatibility>, String>)| match result {
+        Ok(found) if found.is_empty() => report.compatible.push(id),
+        Ok(found) => {
+            for incompatibility in found {
+                groups.entry(incompatibility).or_default().push(id.clone());
+            }
+        }
+        Err(error) => report.corrupt.push(CorruptModule { id, error }),
+    };
+    for source in sources {
+        // Record what is ready while waiting for a free worker, so that results do not pile up.
+        while let Ok(result) = result_receiver.try_recv() {
+            record(result);
+        }
+        if source_sender.send(source).is_err() {
+            break;
+        }
+    }
+    drop(source_sender);
+    for result in result_receiver {
+        record(result);
+    }
+    for worker in workers {
+        worker.join().unwrap();
+    }
+
+    report.compatible.sort();
+    report.corrupt.sort_by(|a, b| a.id.cmp(&b.id));
+    let mut broken = groups
+        .into_iter()
+        .map(|(incompatibility, mut modules)| {
+            modules.sort();
+            IncompatibilityGroup {
+                incompatibility,
+                modules,
+            }
+        })
+        .collect::<Vec<_>>();
+    broken.sort_by_cached_key(|group| group.incompatibility.to_string());
+    report.broken = broken;
+    report
+}
+
+/// The imports of `required` that `provided` does not satisfy.
+fn incompatibilities(
+    provided: &HashMap<(String, String), ExternType>,
+    required: Vec<(String, String, ExternType)>,
+) -> Vec<Incompatibility> {
+    required
+        .into_iter()
+        .filter_map(|(module, name, required)| {
+            let kind = match provided.get(&(module.clone(), name.clone())) {
+                None => IncompatibilityKind::Missing(required),
+                Some(provided) => incompatibility(required, provided.clone())?,
+            };
+            Some(Incompatibility { module, name, kind })
+        })
+        .collect()
+}
+
+/// Why `provided` does not satisfy an import of type `required`, if it does not.
+///
+/// These are the checks `wasmer_engine::resolve_imports` makes when linking.
+fn incompatibility(required: ExternType, provided: ExternType) -> Option<IncompatibilityKind> {
+    match (required, provided) {
+        (ExternType::Function(required), ExternType::Function(provided)) => {
+            if required == provided {
+                return None;
+            }
+            Some(IncompatibilityKind::Signature { required, provided })
+        }
+        (ExternType::Memory(required), ExternType::Memory(provided)) => {
+            if is_compatible_memory(&provided, &required) {
+                return None;
+            }
+            Some(IncompatibilityKind::Memory { required, provided })
+        }
+        (ExternType::Table(required), ExternType::Table(provided)) => {
+            if is_compatible_table(&provided, &required) && provided.ty == required.ty {
+                return None;
+            }
+            Some(IncompatibilityKind::Table { required, provided })
+        }
+        (ExternType::Global(required), ExternType::Global(provided)) => {
+            if required == provided {
+                return None;
+            }
+            Some(IncompatibilityKind::Global { required, provided })
+        }
+        (required, provided) => Some(IncompatibilityKind::Kind { required, provided }),
+    }
+}
@@ -0,0 +1,353 @@
+use crate::sys::instance::Instance;
+use crate::sys::types::Val;
+use crate::sys::FunctionType;
+use std::ffi::c_void;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use thiserror::Error;
+use wasmer_types::{ErrorCode, HasErrorCode, MemoryIndex, Type};
+
+/// A rule on the arguments of a host function, see [`ArgPolicy`].
+///
+/// Arguments are numbered from 0, and rules only apply to `i32` and `i64` arguments. `i32`
+/// pointers and lengths are unsigned.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ArgRule {
+    /// The `len` bytes at `ptr` are within the memory of the calling instance.
+    PtrLenPair {
+        /// The index of the pointer argument.
+        ptr: usize,
+        /// The index of the length argument.
+        len: usize,
+    },
+    /// The argument is within `range`.
+    EnumRange {
+        /// The index of the argument.
+        arg: usize,
+        /// The values the argument may take.
+        range: RangeInclusive<i64>,
+    },
+    /// The argument is not zero.
+    NonZero {
+        /// The index of the argument.
+        arg: usize,
+    },
+}
+
+impl ArgRule {
+    /// Return the index of the argument the rule is about, the pointer for pairs.
+    pub fn arg(&self) -> usize {
+        match *self {
+            Self::PtrLenPair { ptr, .. } => ptr,
+            Self::EnumRange { arg, .. } | Self::NonZero { arg } => arg,
+        }
+    }
+
+    fn args(&self) -> Vec<usize> {
+        match *self {
+            Self::PtrLenPair { ptr, len } => vec![ptr, len],
+            Self::EnumRange { arg, .. } | Self::NonZero { arg } => vec![arg],
+        }
+    }
+}
+
+impl fmt::Display for ArgRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PtrLenPair { ptr, len } => write!(f, "ptr_len_pair({}, {})", ptr, len),
+            Self::EnumRange { arg, range } => write!(
+                f,
+                "enum_range({}, {}..={})",
+                arg,
+                range.start(),
+                range.end()
+            ),
+            Self::NonZero { arg } => write!(f, "nonzero({})", arg),
+        }
+    }
+}
+
+/// Rules the arguments of a host function are checked against before it runs, attached with
+/// [`Function::with_arg_policy`].
+///
+/// A call breaking a rule fails with a [`RuntimeError`] wrapping an
+/// [`ArgPolicyError::Violation`], naming the import, the argument and the rule, and the host
+/// function does not run. Pointers are checked against the size the memory of the calling
+/// instance has at the time of the call.
+///
+/// ```
+/// # use wasmer::{ArgPolicy, ArgRule};
+/// let policy = ArgPolicy::builder()
+///     .ptr_len_pair(0, 1)
+///     .enum_range(2, 0..=4)
+///     .nonzero(3)
+///     .build();
+/// assert_eq!(policy.rules()[2], ArgRule::NonZero { arg: 3 });
+/// assert_eq!(policy.to_string(), "ptr_len_pair(0, 1), enum_range(2, 0..=4), nonzero(3)");
+/// ```
+///
+/// [`Function::with_arg_policy`]: crate::Function::with_arg_policy
+/// [`RuntimeError`]: crate::RuntimeError
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ArgPolicy {
+    rules: Vec<ArgRule>,
+}
+
+impl ArgPolicy {
+    /// Start a policy without rules.
+    pub fn builder() -> ArgPolicyBuilder {
+        ArgPolicyBuilder::default()
+    }
+
+    /// Return the rules of the policy, in the order they are checked.
+    pub fn rules(&self) -> &[ArgRule] {
+        &self.rules
+    }
+
+    /// Check that the rules only refer to integer arguments of a function of type `ty`.
+    fn resolve(self, ty: &FunctionType) -> Result<Checks, ArgPolicyError> {
+        let params = ty.params();
+        for rule in &self.rules {
+            for arg in rule.args() {
+                match params.get(arg) {
+                    Some(Type::I32) | Some(Type::I64) => {}
+                    Some(other) => {
+                        return Err(ArgPolicyError::Invalid(format!(
+                            "`{}` refers to argument {}, of type {}",
+                            rule, arg, other
+                        )))
+                    }
+                    None => {
+                        return Err(ArgPolicyError::Invalid(format!(
+                            "`{}` refers to argument {} of a function taking {}",
+                            rule,
+                            arg,
+                            params.len()
+                        )))
+                    }
+                }
+            }
+        }
+        Ok(Checks {
+            wide: params.iter().map(|param| *param == Type::I64).collect(),
+            policy: self,
+        })
+    }
+}
+
+impl fmt::Display for ArgPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, rule) in self.rules.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+/// Builds an [`ArgPolicy`], one rule at a time.
+#[derive(Clone, Debug, Default)]
+pub struct ArgPolicyBuilder {
+    rules: Vec<ArgRule>,
+}
+
+impl ArgPolicyBuilder {
+    /// Require the `len` bytes at `ptr` to be within the memory of the calling instance.
+    pub fn ptr_len_pair(mut self, ptr: usize, len: usize) -> Self {
+        self.rules.push(ArgRule::PtrLenPair { ptr, len });
+        self
+    }
+
+    /// Require the argument `arg` to be within `range`.
+    pub fn enum_range(mut self, arg: usize, range: RangeInclusive<i64>) -> Self {
+        self.rules.push(ArgRule::EnumRange { arg, range });
+        self
+    }
+
+    /// Require the argument `arg` not to be zero.
+    pub fn nonzero(mut self, arg: usize) -> Self {
+        self.rules.push(ArgRule::NonZero { arg });
+        self
+    }
+
+    /// Finish the policy.
+    pub fn build(self) -> ArgPolicy {
+        ArgPolicy { rules: self.rules }
+    }
+}
+
+/// An error raised by an [`ArgPolicy`].
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ArgPolicyError {
+    /// The function is not a host function with an environment to hold the policy.
+    #[error(
+        "argument policies only apply to host functions with an environment [{}]",
+        ErrorCode::ArgPolicyNotHostFunction
+    )]
+    NotHostFunction,
+    /// The policy refers to arguments the function does not have, or that are not integers.
+    #[error("invalid argument policy: {0} [{}]", ErrorCode::ArgPolicyInvalid)]
+    Invalid(String),
+    /// A call broke a rule of the policy.
+    #[error(
+        "argument {index} of import {import} violates `{rule}` [{}]",
+        ErrorCode::ArgPolicyViolation
+    )]
+    Violation {
+        /// The import, as `"module"."field"`, or `host` for calls from the host.
+        import: String,
+        /// The index of the argument, the pointer for pairs.
+        index: usize,
+        /// The rule broken.
+        rule: ArgRule,
+    },
+}
+
+impl HasErrorCode for ArgPolicyError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotHostFunction => ErrorCode::ArgPolicyNotHostFunction,
+            Self::Invalid(_) => ErrorCode::ArgPolicyInvalid,
+            Self::Violation { .. } => ErrorCode::ArgPolicyViolation,
+        }
+    }
+}
+
+/// A policy checked against the type of the function it is attached to.
+struct Checks {
+    policy: ArgPolicy,
+    /// Whether each argument is an `i64`.
+    wide: Vec<bool>,
+}
+
+impl Checks {
+    /// Return the argument `arg` as unsigned, zero-extending `i32`s.
+    fn unsigned(&self, args: &[i128], arg: usize) -> u64 {
+        if self.wide[arg] {
+            args[arg] as u64
+        } else {
+            args[arg] as u32 as u64
+        }
+    }
+}
+
+/// The instance calling an imported host function.
+#[derive(Clone)]
+struct Caller {
+    import: String,
+    memory: Option<Arc<dyn wasmer_vm::Memory>>,
+}
+
+/// The argument policy of a host function and what it checks against, stored first in the
+/// environment of the function so that it is found without knowing the type of the rest.
+///
+/// The environment of the function is cloned for every instance importing it, and the clone
+/// initialized with the caller.
+#[derive(Clone, Default)]
+pub(crate) struct ArgGuard {
+    checks: Option<Arc<Checks>>,
+    caller: Option<Caller>,
+}
+
+impl ArgGuard {
+    pub(crate) fn policy(&self) -> Option<&ArgPolicy> {
+        self.checks.as_ref().map(|checks| &checks.policy)
+    }
+
+    /// Check `policy` against the type `ty` of the function and attach it.
+    pub(crate) fn set_policy(
+        &mut self,
+        policy: ArgPolicy,
+        ty: &FunctionType,
+    ) -> Result<(), ArgPolicyError> {
+        self.checks = Some(Arc::new(policy.resolve(ty)?));
+        Ok(())
+    }
+
+    /// Record `instance` as the caller of the import whose host environment is `host_env`.
+    pub(crate) fn init(&mut self, instance: &Instance, host_env: *mut c_void) {
+        if self.checks.is_some() {
+            self.caller = Some(Caller {
+                import: instance
+                    .function_import_name(host_env)
+                    .unwrap_or_else(|| "host".to_string()),
+                memory: instance.memory(MemoryIndex::from_u32(0)),
+            });
+        }
+    }
+
+    /// Check the arguments of a typed function, in their binary representation.
+    pub(crate) fn check(&self, args: &[i128]) -> Result<(), ArgPolicyError> {
+        let checks = match &self.checks {
+            Some(checks) => checks,
+            None => return Ok(()),
+        };
+        for rule in checks.policy.rules() {
+            let violated = match *rule {
+                ArgRule::PtrLenPair { ptr, len } => {
+                    // Ranges wrapping around the address space are out of bounds.
+                    match checks
+                        .unsigned(args, ptr)
+                        .checked_add(checks.unsigned(args, len))
+                    {
+                        Some(end) => end > self.memory_size(),
+                        None => true,
+                    }
+                }
+                ArgRule::EnumRange { arg, ref range } => !range.contains(&(args[arg] as i64)),
+                ArgRule::NonZero { arg } => args[arg] == 0,
+            };
+            if violated {
+                return Err(ArgPolicyError::Violation {
+                    import: self
+                        .caller
+                        .as_ref()
+                        .map_or("host", |caller| &caller.import)
+                        .to_string(),
+                    index: rule.arg(),
+                    rule: rule.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the arguments of a dynamic function.
+    pub(crate) fn check_values(&self, args: &[Val]) -> Result<(), ArgPolicyError> {
+        if self.checks.is_none() {
+            return Ok(());
+        }
+        let args = args
+            .iter()
+            .map(|arg| match *arg {
+                Val::I32(value) => value.into(),
+                Val::I64(value) => value.into(),
+                _ => 0,
+            })
+            .collect::<Vec<i128>>();
+        self.check(&args)
+    }
+
+    /// The size of the memory of the caller, 0 for callers without memory and the host.
+    fn memory_size(&self) -> u64 {
+        match self
+            .caller
+            .as_ref()
+            .and_then(|caller| caller.memory.as_ref())
+        {
+            Some(memory) => unsafe { memory.vmmemory().as_ref().current_length as u64 },
+            None => 0,
+        }
+    }
+}
+
+/// The environment of a typed host function: its argument guard, then what the user gave.
+#[repr(C)]
+#[derive(Clone)]
+pub(crate) struct Guarded<Env> {
+    pub(crate) guard: ArgGuard,
+    pub(crate) env: Env,
+}
@@ -0,0 +1,190 @@
+use crate::sys::exports::ExportError;
+use crate::sys::externals::{Extern, Function, Global, Memory, Table, WasmTypeList};
+use crate::sys::instance::Instance;
+use crate::sys::native::NativeFunc;
+use std::fmt;
+use thiserror::Error;
+use wasmer_types::{ErrorCode, HasErrorCode};
+
+/// An export of an [`Instance`] that can be bound to a field of a [`bind_exports!`] struct.
+///
+/// This is implemented for [`Function`], [`NativeFunc`], [`Memory`], [`Global`] and [`Table`].
+pub trait BindableExport: Sized {
+    /// Look up the export `name` of `instance`, checking that it has the expected type.
+    fn bind_export(instance: &Instance, name: &str) -> Result<Self, ExportError>;
+}
+
+/// A set of exports looked up and type checked at once.
+///
+/// Implement it with the [`bind_exports!`] macro.
+pub trait BindExports: Sized {
+    /// Look up and type check every export of the set in `instance`.
+    ///
+    /// The bound handles keep the instance alive.
+    fn bind(instance: &Instance) -> Result<Self, BindExportsError>;
+}
+
+/// The exports that could not be bound by [`BindExports::bind`].
+#[derive(Error, Debug)]
+pub struct BindExportsError {
+    /// Every export that is missing or has the wrong type, with the reason, in field order.
+    pub errors: Vec<(String, ExportError)>,
+}
+
+impl fmt::Display for BindExportsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "could not bind exports")?;
+        for (i, (name, error)) in self.errors.iter().enumerate() {
+            let separator = if i == 0 { ": " } else { ", " };
+            write!(f, "{}`{}` ({})", separator, name, error)?;
+        }
+        write!(f, " [{}]", self.code())
+    }
+}
+
+impl HasErrorCode for BindExportsError {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::BindExports
+    }
+}
+
+fn bind_extern(instance: &Instance, name: &str) -> Result<Extern, ExportError> {
+    let export = instance
+        .lookup(name)
+        .ok_or_else(|| ExportError::Missing(name.to_string()))?;
+    Ok(Extern::from_vm_export(instance.store(), export))
+}
+
+impl BindableExport for Function {
+    fn bind_export(instance: &Instance, name: &str) -> Result<Self, ExportError> {
+        match bind_extern(instance, name)? {
+            Extern::Function(f) => Ok(f),
+            _ => Err(ExportError::IncompatibleType),
+        }
+    }
+}
+
+impl<Args, Rets> BindableExport for NativeFunc<Args, Rets>
+where
+    Args: WasmTypeList,
+    Rets: WasmTypeList,
+{
+    fn bind_export(instance: &Instance, name: &str) -> Result<Self, ExportError> {
+        Function::bind_export(instance, name)?
+            .native()
+            .map_err(|_| ExportError::IncompatibleType)
+    }
+}
+
+impl BindableExport for Memory {
+    fn bind_export(instance: &Instance, name: &str) -> Result<Self, ExportError> {
+        match bind_extern(instance, name)? {
+            Extern::Memory(m) => Ok(m),
+            _ => Err(ExportError::IncompatibleType),
+        }
+    }
+}
+
+impl BindableExport for Global {
+    fn bind_export(instance: &Instance, name: &str) -> Result<Self, ExportError> {
+        match bind_extern(instance, name)? {
+            Extern::Global(g) => Ok(g),
+            _ => Err(ExportError::IncompatibleType),
+        }
+    }
+}
+
+impl BindableExport for Table {
+    fn bind_export(instance: &Instance, name: &str) -> Result<Self, ExportError> {
+        match bind_extern(instance, name)? {
+            Extern::Table(t) => Ok(t),
+            _ => Err(ExportError::IncompatibleType),
+        }
+    }
+}
+
+/// Declare a struct of typed export handles and implement [`BindExports`] for it.
+///
+/// Every field is annotated with the name of the export it is bound to, and its type must
+/// implement [`BindableExport`]. [`BindExports::bind`] looks up and type checks all the exports
+/// in one go and reports every missing or mismatched export at once.
+///
+/// # Usage
+///
+/// ```
+/// # use wasmer::{imports, Instance, Module, Store};
+/// # fn main() -> anyhow::Result<()> {
+/// use wasmer::{bind_exports, BindExports, Memory, NativeFunc};
+///
+/// bind_exports! {
+///     struct Contract {
+///         memory: Memory = "memory",
+///         add: NativeFunc<(i32, i32), i32> = "add",
+///     }
+/// }
+///
+/// let store = Store::default();
+/// let module = Module::new(&store, r#"
+///     (module
+///         (memory (export "memory") 1)
+///         (func (export "add") (param i32 i32) (result i32)
+///             (i32.add (local.get 0) (local.get 1))))
+/// "#)?;
+/// let instance = Instance::new(&module, &imports! {})?;
+/// let contract = Contract::bind(&instance)?;
+/// assert_eq!(contract.add.call(1, 2)?, 3);
+/// assert_eq!(contract.memory.size().0, 1);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Fields must have a type that can be bound:
+///
+/// ```compile_fail
+/// wasmer::bind_exports! {
+///     struct Contract {
+///         counter: i32 = "counter",
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! bind_exports {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_attr:meta])*
+                $field_vis:vis $field:ident : $ty:ty = $export:expr
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $(
+                $(#[$field_attr])*
+                $field_vis $field: $ty,
+            )*
+        }
+
+        impl $crate::BindExports for $name {
+            fn bind(instance: &$crate::Instance) -> ::std::result::Result<Self, $crate::BindExportsError> {
+                let mut errors = ::std::vec::Vec::new();
+                $(
+                    let $field = match <$ty as $crate::BindableExport>::bind_export(instance, $export) {
+                        ::std::result::Result::Ok(export) => ::std::option::Option::Some(export),
+                        ::std::result::Result::Err(error) => {
+                            errors.push((::std::string::String::from($export), error));
+                            ::std::option::Option::None
+                        }
+                    };
+                )*
+                if !errors.is_empty() {
+                    return ::std::result::Result::Err($crate::BindExportsError { errors });
+                }
+                ::std::result::Result::Ok(Self {
+                    $($field: $field.unwrap(),)*
+                })
+            }
+        }
+    };
+}
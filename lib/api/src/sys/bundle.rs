@@ -0,0 +1,448 @@
+//! Bundles of modules instantiated together, the imports of each namespace of a module being
+//! satisfied by the exports of another one.
+
+use crate::sys::instance::{Instance, InstantiationError};
+use crate::sys::module::Module;
+use crate::sys::store::Store;
+use std::collections::HashMap;
+use std::fmt;
+use thiserror::Error;
+use wasmer_compiler::CompileError;
+use wasmer_engine::DeserializeError;
+use wasmer_engine_universal::UniversalExecutableRef;
+use wasmer_types::{ErrorCode, HasErrorCode};
+use wasmer_vm::{Export, NamedResolver};
+
+/// The code of a slot of a [`BundleManifest`].
+#[derive(Clone)]
+pub struct BundleModule(ModuleSource);
+
+#[derive(Clone)]
+enum ModuleSource {
+    Wasm(Vec<u8>),
+    Precompiled(Vec<u8>),
+    Module(Module),
+}
+
+impl BundleModule {
+    /// A module to compile from its binary, or its text if the `wat` feature is enabled.
+    pub fn wasm(bytes: impl Into<Vec<u8>>) -> Self {
+        Self(ModuleSource::Wasm(bytes.into()))
+    }
+
+    /// A module to load from an executable serialized by the universal engine.
+    ///
+    /// # Safety
+    ///
+    /// The same as [`UniversalExecutableRef::deserialize`]: the bytes must come from a
+    /// trusted serialization, as their code is run as it is.
+    pub unsafe fn precompiled(bytes: impl Into<Vec<u8>>) -> Self {
+        Self(ModuleSource::Precompiled(bytes.into()))
+    }
+
+    /// A module already compiled, in the store the bundle will be instantiated in.
+    pub fn module(module: Module) -> Self {
+        Self(ModuleSource::Module(module))
+    }
+
+    fn load(&self, store: &Store, slot: &str) -> Result<Module, BundleError> {
+        let compile = |source| BundleError::Compile {
+            slot: slot.to_string(),
+            source,
+        };
+        match &self.0 {
+            ModuleSource::Wasm(bytes) => Module::new(store, bytes).map_err(compile),
+            ModuleSource::Precompiled(bytes) => {
+                UniversalExecutableRef::with_aligned(bytes, |bytes| {
+                    // Safety: guaranteed by the caller of `BundleModule::precompiled`.
+                    let executable = unsafe { UniversalExecutableRef::deserialize(bytes) }
+                        .map_err(|source| BundleError::Deserialize {
+                            slot: slot.to_string(),
+                            source,
+                        })?;
+                    Module::from_executable(store, &executable).map_err(compile)
+                })
+            }
+            ModuleSource::Module(module) => Ok(module.clone()),
+        }
+    }
+}
+
+/// A link of a [`BundleManifest`]: the imports of `slot` from `namespace` are satisfied by
+/// the exports of `provider`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BundleLink {
+    /// The importing slot.
+    pub slot: String,
+    /// The module name of the imports.
+    pub namespace: String,
+    /// The exporting slot.
+    pub provider: String,
+}
+
+/// The modules of an [`InstanceBundle`], in named slots, and the links between them.
+///
+/// The imports of a slot from namespaces without a link are resolved by the imports given to
+/// [`InstanceBundle::instantiate`].
+#[derive(Clone)]
+pub struct BundleManifest {
+    main: String,
+    slots: Vec<(String, BundleModule)>,
+    links: Vec<BundleLink>,
+}
+
+impl BundleManifest {
+    /// Start a manifest whose main instance is that of the slot `main`.
+    pub fn new(main: impl Into<String>) -> Self {
+        Self {
+            main: main.into(),
+            slots: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+
+    /// Add the slot `name`, holding `module`.
+    pub fn slot(mut self, name: impl Into<String>, module: BundleModule) -> Self {
+        self.slots.push((name.into(), module));
+        self
+    }
+
+    /// Satisfy the imports of `slot` from `namespace` with the exports of `provider`.
+    pub fn link(
+        mut self,
+        slot: impl Into<String>,
+        namespace: impl Into<String>,
+        provider: impl Into<String>,
+    ) -> Self {
+        self.links.push(BundleLink {
+            slot: slot.into(),
+            namespace: namespace.into(),
+            provider: provider.into(),
+        });
+        self
+    }
+
+    /// Return the links of the manifest.
+    pub fn links(&self) -> &[BundleLink] {
+        &self.links
+    }
+}
+
+/// Modules instantiated together from a [`BundleManifest`], each after the slots providing
+/// its imports.
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// # let store = Store::default();
+/// let bundle = InstanceBundle::new(
+///     BundleManifest::new("main")
+///         .slot("main", BundleModule::wasm(r#"
+///             (module
+///                 (import "lib" "answer" (func $answer (result i32)))
+///                 (func (export "run") (result i32) (call $answer)))
+///         "#))
+///         .slot("lib", BundleModule::wasm(r#"
+///             (module (func (export "answer") (result i32) (i32.const 42)))
+///         "#))
+///         .link("main", "lib", "lib"),
+/// )?;
+/// assert_eq!(bundle.order().collect::<Vec<_>>(), ["lib", "main"]);
+/// let instances = bundle.instantiate(&store, &imports! {})?;
+/// let run = instances.main().get_native_function::<(), i32>("run")?;
+/// assert_eq!(run.call()?, 42);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct InstanceBundle {
+    manifest: BundleManifest,
+    /// The indices of the slots, providers first.
+    order: Vec<usize>,
+    keep_partial: bool,
+}
+
+impl InstanceBundle {
+    /// Check `manifest` and order its slots.
+    ///
+    /// Fails with [`BundleError::Cycle`] if slots import from each other in a cycle.
+    pub fn new(manifest: BundleManifest) -> Result<Self, BundleError> {
+        let mut indices = HashMap::new();
+        for (index, (name, _)) in manifest.slots.iter().enumerate() {
+            if indices.insert(name.as_str(), index).is_some() {
+                return Err(BundleError::Manifest(format!(
+                    "slot `{}` is defined twice",
+                    name
+                )));
+            }
+        }
+        if !indices.contains_key(manifest.main.as_str()) {
+            return Err(BundleError::Manifest(format!(
+                "the main slot `{}` is not defined",
+                manifest.main
+            )));
+        }
+        let mut providers = vec![Vec::new(); manifest.slots.len()];
+        for (index, link) in manifest.links.iter().enumerate() {
+            let slot = *indices.get(link.slot.as_str()).ok_or_else(|| {
+                BundleError::Manifest(format!("link from undefined slot `{}`", link.slot))
+            })?;
+            let provider = *indices.get(link.provider.as_str()).ok_or_else(|| {
+                BundleError::Manifest(format!("link to undefined slot `{}`", link.provider))
+            })?;
+            if manifest.links[..index]
+                .iter()
+                .any(|other| other.slot == link.slot && other.namespace == link.namespace)
+            {
+                return Err(BundleError::Manifest(format!(
+                    "namespace `{}` of slot `{}` is linked twice",
+                    link.namespace, link.slot
+                )));
+            }
+            providers[slot].push(provider);
+        }
+        let order = order(&providers).map_err(|cycle| {
+            BundleError::Cycle(
+                cycle
+                    .into_iter()
+                    .map(|index| manifest.slots[index].0.clone())
+                    .collect(),
+            )
+        })?;
+        Ok(Self {
+            manifest,
+            order,
+            keep_partial: false,
+        })
+    }
+
+    /// Return the instances created before an instantiation failed in the error, instead of
+    /// dropping them, to inspect them.
+    pub fn keep_partial_on_failure(mut self, keep: bool) -> Self {
+        self.keep_partial = keep;
+        self
+    }
+
+    /// Return the names of the slots, in the order they are instantiated.
+    pub fn order(&self) -> impl Iterator<Item = &str> + '_ {
+        self.order
+            .iter()
+            .map(move |&index| self.manifest.slots[index].0.as_str())
+    }
+
+    /// Instantiate the slots in `store`, providers first, resolving the imports from the
+    /// namespaces without a link with `imports`.
+    ///
+    /// If a slot fails to compile or instantiate, the instances already created are dropped
+    /// unless [`InstanceBundle::keep_partial_on_failure`] was set.
+    pub fn instantiate(
+        &self,
+        store: &Store,
+        imports: &dyn NamedResolver,
+    ) -> Result<BundleInstances, BundleError> {
+        let mut instances = BundleInstances {
+            main: self.manifest.main.clone(),
+            instances: Vec::with_capacity(self.order.len()),
+        };
+        for &index in &self.order {
+            let (name, module) = &self.manifest.slots[index];
+            let result = module.load(store, name).and_then(|module| {
+                let resolver = SlotImports {
+                    slot: name,
+                    links: &self.manifest.links,
+                    instances: &instances,
+                    imports,
+                };
+                Instance::new(&module, &resolver).map_err(|source| BundleError::Instantiation {
+                    slot: name.clone(),
+                    source: Box::new(source),
+                    partial: None,
+                })
+            });
+            match result {
+                Ok(instance) => instances.instances.push((name.clone(), instance)),
+                Err(BundleError::Instantiation { slot, source, .. }) if self.keep_partial => {
+                    return Err(BundleError::Instantiation {
+                        slot,
+                        source,
+                        partial: Some(instances),
+                    })
+                }
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(instances)
+    }
+}
+
+/// Order the slots whose providers are given by index so that providers come first, or
+/// return a cycle, as a path starting and ending with the same slot.
+fn order(providers: &[Vec<usize>]) -> Result<Vec<usize>, Vec<usize>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        New,
+        Visiting,
+        Done,
+    }
+    fn visit(
+        slot: usize,
+        providers: &[Vec<usize>],
+        states: &mut [State],
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), Vec<usize>> {
+        match states[slot] {
+            State::Done => return Ok(()),
+            State::Visiting => {
+                let start = path.iter().position(|&other| other == slot).unwrap();
+                let mut cycle = path[start..].to_vec();
+                cycle.push(slot);
+                return Err(cycle);
+            }
+            State::New => {}
+        }
+        states[slot] = State::Visiting;
+        path.push(slot);
+        for &provider in &providers[slot] {
+            visit(provider, providers, states, path, order)?;
+        }
+        path.pop();
+        states[slot] = State::Done;
+        order.push(slot);
+        Ok(())
+    }
+    let mut states = vec![State::New; providers.len()];
+    let mut order = Vec::with_capacity(providers.len());
+    for slot in 0..providers.len() {
+        visit(slot, providers, &mut states, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Resolves the imports of a slot from the instances of the slots it is linked to, then from
+/// the imports of the bundle.
+struct SlotImports<'a> {
+    slot: &'a str,
+    links: &'a [BundleLink],
+    instances: &'a BundleInstances,
+    imports: &'a dyn NamedResolver,
+}
+
+impl NamedResolver for SlotImports<'_> {
+    fn resolve_by_name(&self, module: &str, field: &str) -> Option<Export> {
+        match self
+            .links
+            .iter()
+            .find(|link| link.slot == self.slot && link.namespace == module)
+        {
+            Some(link) => self.instances.get(&link.provider)?.lookup(field),
+            None => self.imports.resolve_by_name(module, field),
+        }
+    }
+}
+
+/// The instances of the slots of an [`InstanceBundle`].
+#[derive(Clone)]
+pub struct BundleInstances {
+    main: String,
+    instances: Vec<(String, Instance)>,
+}
+
+impl BundleInstances {
+    /// Return the instance of the main slot.
+    ///
+    /// # Panics
+    ///
+    /// If the main slot was not instantiated, which only happens to the instances returned
+    /// by a failed instantiation.
+    pub fn main(&self) -> &Instance {
+        self.get(&self.main)
+            .expect("the main slot was not instantiated")
+    }
+
+    /// Return the instance of the slot `name`, if it was instantiated.
+    pub fn get(&self, name: &str) -> Option<&Instance> {
+        self.instances
+            .iter()
+            .find(|(slot, _)| slot == name)
+            .map(|(_, instance)| instance)
+    }
+
+    /// Iterate over the slots and their instances, in the order they were instantiated.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Instance)> + '_ {
+        self.instances
+            .iter()
+            .map(|(slot, instance)| (slot.as_str(), instance))
+    }
+
+    /// Return the number of instances.
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// Return whether there are no instances.
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+}
+
+impl fmt::Debug for BundleInstances {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BundleInstances")
+            .field("main", &self.main)
+            .field(
+                "slots",
+                &self.iter().map(|(slot, _)| slot).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// An error raised by an [`InstanceBundle`].
+#[derive(Error, Debug)]
+pub enum BundleError {
+    /// The manifest is inconsistent, such as when a link refers to an undefined slot.
+    #[error("invalid bundle manifest: {0} [{}]", ErrorCode::BundleManifest)]
+    Manifest(String),
+    /// Slots import from each other in a cycle.
+    #[error("bundle slots import from each other in a cycle: {} [{}]", .0.join(" -> "), ErrorCode::BundleCycle)]
+    Cycle(Vec<String>),
+    /// The module of a slot does not compile.
+    #[error("slot `{slot}` does not compile: {source}")]
+    Compile {
+        /// The name of the slot.
+        slot: String,
+        /// The compilation error.
+        source: CompileError,
+    },
+    /// The precompiled module of a slot cannot be deserialized.
+    #[error("slot `{slot}` cannot be deserialized: {source}")]
+    Deserialize {
+        /// The name of the slot.
+        slot: String,
+        /// The deserialization error.
+        source: DeserializeError,
+    },
+    /// The module of a slot cannot be instantiated.
+    #[error("slot `{slot}` cannot be instantiated: {source}")]
+    Instantiation {
+        /// The name of the slot.
+        slot: String,
+        /// The instantiation error.
+        source: Box<InstantiationError>,
+        /// The instances created before, if the bundle keeps them on failure.
+        partial: Option<BundleInstances>,
+    },
+}
+
+impl HasErrorCode for BundleError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Manifest(_) => ErrorCode::BundleManifest,
+            Self::Cycle(_) => ErrorCode::BundleCycle,
+            Self::Compile { source, .. } => source.code(),
+            Self::Deserialize { source, .. } => source.code(),
+            Self::Instantiation { source, .. } => source.code(),
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::sys::store::Store;
+use std::sync::Arc;
+use wasmer_types::Extensions;
+use wasmer_vm::{Trap, WeakOrStrongInstanceRef};
+
+/// The context of the WebAssembly call a host function is running in.
+///
+/// This gives host functions access to the extensions of the instance that
+/// called them (see [`InstanceConfig::with_extension`] and
+/// [`Instance::insert_extension`]) without threading an env through every
+/// function.
+///
+/// ## Locking model
+///
+/// The extensions of an instance are read-mostly: every call into the
+/// instance takes a snapshot of them, so calls never block each other, and
+/// inserting an extension only affects the calls started afterwards.
+///
+/// [`InstanceConfig::with_extension`]: crate::InstanceConfig::with_extension
+/// [`Instance::insert_extension`]: crate::Instance::insert_extension
+///
+/// ```
+/// # use wasmer::{imports, CallContext, Function, Instance, InstanceConfig, Module, Store};
+/// # fn main() -> anyhow::Result<()> {
+/// struct RequestId(u64);
+///
+/// let store = Store::default();
+/// let module = Module::new(&store, r#"
+///     (module
+///         (import "env" "log" (func $log))
+///         (func (export "run") call $log))
+/// "#)?;
+/// let log = Function::new_native(&store, || {
+///     let context = CallContext::current().unwrap();
+///     assert_eq!(context.extension::<RequestId>().unwrap().0, 42);
+/// });
+/// let config = InstanceConfig::default().with_extension(RequestId(42));
+/// let instance = Instance::new_with_config(&module, config, &imports! {
+///     "env" => { "log" => log },
+/// })?;
+/// instance.lookup_function("run").unwrap().call(&[])?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct CallContext {
+    extensions: Arc<Extensions>,
+}
+
+impl CallContext {
+    /// Return the context of the innermost WebAssembly call running on this thread, if any.
+    pub fn current() -> Option<Self> {
+        Some(Self {
+            extensions: wasmer_vm::current_extensions()?,
+        })
+    }
+
+    /// Get the extension of type `T` of the calling instance, if it has one.
+    pub fn extension<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.extensions.get::<T>()
+    }
+}
+
+/// Run `closure` as a call from the host into the instance behind `instance_ref`, making its
+/// extensions available to the host functions it calls, and raising the lightweight traps of
+/// `store` as such and the others with a backtrace as its policy says.
+///
+/// Calls into an instance poisoned by a failed restore trap without running `closure`.
+pub(crate) fn enter_instance<R>(
+    store: &Store,
+    instance_ref: Option<&WeakOrStrongInstanceRef>,
+    closure: impl FnOnce() -> Result<R, Trap>,
+) -> Result<R, Trap> {
+    store.with_lightweight_traps(|| match instance_ref {
+        Some(instance_ref) => instance_ref.enter(closure),
+        None => closure(),
+    })
+}
@@ -0,0 +1,75 @@
+//! Cancelling single guarded calls into an instance while they run.
+
+use crate::sys::execution_plan::{with_gas_guard, Guards};
+use crate::sys::exports::ExportError;
+use crate::sys::instance::Instance;
+use crate::sys::{Export, RuntimeError, Val};
+use std::sync::Arc;
+use std::time::Instant;
+use wasmer_vm::CallCancellation;
+
+/// Cancels a call made with [`Instance::guarded_call_handle`], from any thread.
+#[derive(Clone, Debug)]
+pub struct CallHandle(Arc<CallCancellation>);
+
+impl CallHandle {
+    /// Cancel the call, returning whether it had not finished nor been cancelled yet.
+    ///
+    /// A running call fails with a [`TrapCode::Cancelled`] trap at the next check of its
+    /// compiled code, and a call yet to start fails the same way without starting. Cancelling
+    /// a call that finished does nothing.
+    ///
+    /// [`TrapCode::Cancelled`]: crate::TrapCode::Cancelled
+    pub fn cancel(&self) -> bool {
+        self.0.cancel()
+    }
+
+    /// Return whether the call was cancelled before it finished.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
+}
+
+/// A call prepared by [`Instance::guarded_call_handle`], made by calling it.
+pub type GuardedCall = Box<dyn FnOnce() -> Result<Box<[Val]>, RuntimeError>>;
+
+/// Finishes a call once the closure making it is dropped, whether it was called or not.
+struct Finish(Arc<CallCancellation>);
+
+impl Drop for Finish {
+    fn drop(&mut self) {
+        self.0.finish();
+    }
+}
+
+/// See [`Instance::guarded_call_handle`].
+pub(crate) fn guarded_call_handle(
+    instance: &Instance,
+    entry: &str,
+    params: &[Val],
+    guards: Guards,
+) -> Result<(CallHandle, GuardedCall), ExportError> {
+    let function = match instance.lookup(entry) {
+        Some(Export::Function(_)) => instance.lookup_function(entry).unwrap(),
+        Some(_) => return Err(ExportError::IncompatibleType),
+        None => return Err(ExportError::Missing(entry.to_string())),
+    };
+    let cancellation = instance.vm_handle().new_call_cancellation();
+    let handle = CallHandle(Arc::clone(&cancellation));
+    let finish = Finish(cancellation);
+    let instance = instance.clone();
+    let params = params.to_vec();
+    let call = move || -> Result<Box<[Val]>, RuntimeError> {
+        let finish = finish;
+        if guards
+            .deadline
+            .map_or(false, |deadline| Instant::now() >= deadline)
+        {
+            return Err(RuntimeError::new("the deadline of the call passed"));
+        }
+        finish
+            .0
+            .run(|| with_gas_guard(&instance, guards.gas, || function.call(&params)))?
+    };
+    Ok((handle, Box::new(call)))
+}
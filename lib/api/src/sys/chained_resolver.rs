@@ -0,0 +1,319 @@
+//! Resolving imports from layers of import objects, with explicit precedence rules.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use thiserror::Error;
+use wasmer_types::{Classify, ErrorCode, FailureKind, HasErrorCode};
+use wasmer_vm::{Export, NamedResolver, Resolver};
+
+/// A namespace of imports, that is the module name of an import, as in `"env"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Namespace<'a>(pub &'a str);
+
+/// Which layer of a [`ChainedResolver`] an import comes from when several define it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Precedence {
+    /// The import comes from the earliest layer defining it.
+    FirstMatch,
+    /// The import must be defined by one layer at most: instantiating a module importing
+    /// an import several layers define fails with [`ChainedResolverError::Ambiguous`].
+    RequireUnique,
+}
+
+impl Default for Precedence {
+    fn default() -> Self {
+        Self::FirstMatch
+    }
+}
+
+/// The namespaces a layer of a [`ChainedResolver`] may provide imports for.
+///
+/// A pattern is either a namespace, or a prefix followed by `*` matching every namespace
+/// starting with it, as in `"core_*"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NamespaceFilter {
+    /// Only the namespaces matching one of the patterns.
+    Allow(Vec<String>),
+    /// Every namespace but those matching one of the patterns.
+    Deny(Vec<String>),
+}
+
+impl NamespaceFilter {
+    /// Allow only the namespaces matching one of `patterns`.
+    pub fn allow<S: Into<String>>(patterns: impl IntoIterator<Item = S>) -> Self {
+        Self::Allow(patterns.into_iter().map(Into::into).collect())
+    }
+
+    /// Allow every namespace but those matching one of `patterns`.
+    pub fn deny<S: Into<String>>(patterns: impl IntoIterator<Item = S>) -> Self {
+        Self::Deny(patterns.into_iter().map(Into::into).collect())
+    }
+
+    /// Return whether the filter allows `namespace`.
+    pub fn allows(&self, namespace: &str) -> bool {
+        let matches = |patterns: &[String]| {
+            patterns
+                .iter()
+                .any(|pattern| match pattern.strip_suffix('*') {
+                    Some(prefix) => namespace.starts_with(prefix),
+                    None => namespace == pattern,
+                })
+        };
+        match self {
+            Self::Allow(patterns) => matches(patterns),
+            Self::Deny(patterns) => !matches(patterns),
+        }
+    }
+}
+
+/// An error resolving an import with a [`ChainedResolver`], failing the instantiation with
+/// an [`ImportError::Rejected`](crate::ImportError::Rejected) carrying it.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ChainedResolverError {
+    /// Several layers define an import of a namespace requiring unique definitions.
+    #[error(
+        "{module}.{field} is defined by the layers {} [{}]",
+        .layers.join(", "),
+        ErrorCode::ChainedResolverAmbiguous
+    )]
+    Ambiguous {
+        /// The module name of the import.
+        module: String,
+        /// The field name of the import.
+        field: String,
+        /// The names of the layers defining the import, in the order of the chain.
+        layers: Vec<String>,
+    },
+    /// A layer defines an import of a namespace its filter does not allow.
+    #[error(
+        "the layer {layer} may not provide {module}.{field} [{}]",
+        ErrorCode::ChainedResolverNamespaceViolation
+    )]
+    NamespaceViolation {
+        /// The name of the layer.
+        layer: String,
+        /// The module name of the import.
+        module: String,
+        /// The field name of the import.
+        field: String,
+    },
+}
+
+impl HasErrorCode for ChainedResolverError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Ambiguous { .. } => ErrorCode::ChainedResolverAmbiguous,
+            Self::NamespaceViolation { .. } => ErrorCode::ChainedResolverNamespaceViolation,
+        }
+    }
+}
+
+impl Classify for ChainedResolverError {
+    fn failure_kind(&self) -> FailureKind {
+        FailureKind::Permanent
+    }
+}
+
+/// An import a [`ChainedResolver`] resolved, and the layer it comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedImport {
+    /// The module name of the import.
+    pub module: String,
+    /// The field name of the import.
+    pub field: String,
+    /// The name of the layer the import comes from, or `None` if no layer defines it.
+    pub layer: Option<String>,
+}
+
+struct Layer {
+    name: String,
+    resolver: Box<dyn NamedResolver + Send + Sync>,
+    filter: Option<NamespaceFilter>,
+}
+
+/// Resolves imports from layers of resolvers, such as import objects, with a
+/// [`Precedence`] for each namespace.
+///
+/// Every layer is asked for every import, in the order they were added:
+///
+/// * a layer defining an import of a namespace its [`NamespaceFilter`] does not allow fails
+///   the instantiation with [`ChainedResolverError::NamespaceViolation`], even when an
+///   earlier layer defines the import too;
+/// * an import defined by several layers comes from the earliest of them if its namespace
+///   has the [`Precedence::FirstMatch`] policy, the default, and fails the instantiation
+///   with [`ChainedResolverError::Ambiguous`] if it has the [`Precedence::RequireUnique`]
+///   one.
+///
+/// ```
+/// # use wasmer::{imports, ChainedResolver, Namespace, NamespaceFilter, Precedence};
+/// let resolver = ChainedResolver::builder()
+///     .layer("core", imports! {})
+///     .layer_filtered("tenant", imports! {}, NamespaceFilter::deny(["core_*"]))
+///     .policy(Namespace("debug"), Precedence::RequireUnique)
+///     .build();
+/// ```
+pub struct ChainedResolver {
+    layers: Vec<Layer>,
+    policies: HashMap<String, Precedence>,
+    trace: Option<Mutex<Vec<ResolvedImport>>>,
+}
+
+impl ChainedResolver {
+    /// Start building a resolver with no layers.
+    pub fn builder() -> ChainedResolverBuilder {
+        ChainedResolverBuilder::default()
+    }
+
+    /// Return the imports resolved so far, in the order they were, if the resolver records
+    /// them, see [`ChainedResolverBuilder::record_trace`].
+    pub fn trace(&self) -> Option<Vec<ResolvedImport>> {
+        self.trace
+            .as_ref()
+            .map(|trace| trace.lock().unwrap().clone())
+    }
+
+    /// Return the precedence of the imports of `namespace`.
+    pub fn precedence(&self, namespace: &str) -> Precedence {
+        self.policies.get(namespace).copied().unwrap_or_default()
+    }
+
+    fn resolve_in_layers(
+        &self,
+        module: &str,
+        field: &str,
+    ) -> Result<Option<Export>, ChainedResolverError> {
+        let mut resolved = None;
+        let mut layers = Vec::new();
+        for layer in &self.layers {
+            let export = match layer.resolver.resolve_by_name(module, field) {
+                Some(export) => export,
+                None => continue,
+            };
+            if let Some(filter) = &layer.filter {
+                if !filter.allows(module) {
+                    return Err(ChainedResolverError::NamespaceViolation {
+                        layer: layer.name.clone(),
+                        module: module.to_string(),
+                        field: field.to_string(),
+                    });
+                }
+            }
+            layers.push(layer.name.clone());
+            resolved.get_or_insert(export);
+        }
+        if layers.len() > 1 && self.precedence(module) == Precedence::RequireUnique {
+            return Err(ChainedResolverError::Ambiguous {
+                module: module.to_string(),
+                field: field.to_string(),
+                layers,
+            });
+        }
+        if let Some(trace) = &self.trace {
+            trace.lock().unwrap().push(ResolvedImport {
+                module: module.to_string(),
+                field: field.to_string(),
+                layer: layers.into_iter().next(),
+            });
+        }
+        Ok(resolved)
+    }
+}
+
+impl Resolver for ChainedResolver {
+    fn resolve(&self, _index: u32, module: &str, field: &str) -> Option<Export> {
+        self.resolve_in_layers(module, field).ok().flatten()
+    }
+
+    fn try_resolve(
+        &self,
+        _index: u32,
+        module: &str,
+        field: &str,
+    ) -> Result<Option<Export>, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self.resolve_in_layers(module, field)?)
+    }
+}
+
+impl fmt::Debug for ChainedResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChainedResolver")
+            .field(
+                "layers",
+                &self
+                    .layers
+                    .iter()
+                    .map(|layer| (&layer.name, &layer.filter))
+                    .collect::<Vec<_>>(),
+            )
+            .field("policies", &self.policies)
+            .finish()
+    }
+}
+
+/// Builds a [`ChainedResolver`], see [`ChainedResolver::builder`].
+#[derive(Default)]
+pub struct ChainedResolverBuilder {
+    layers: Vec<Layer>,
+    policies: HashMap<String, Precedence>,
+    record_trace: bool,
+}
+
+impl ChainedResolverBuilder {
+    /// Add a layer named `name` resolving imports with `resolver`, after the layers added
+    /// so far.
+    pub fn layer(
+        mut self,
+        name: impl Into<String>,
+        resolver: impl NamedResolver + Send + Sync + 'static,
+    ) -> Self {
+        self.layers.push(Layer {
+            name: name.into(),
+            resolver: Box::new(resolver),
+            filter: None,
+        });
+        self
+    }
+
+    /// Add a layer like [`Self::layer`], which may only provide the imports of the
+    /// namespaces `filter` allows.
+    pub fn layer_filtered(
+        mut self,
+        name: impl Into<String>,
+        resolver: impl NamedResolver + Send + Sync + 'static,
+        filter: NamespaceFilter,
+    ) -> Self {
+        self.layers.push(Layer {
+            name: name.into(),
+            resolver: Box::new(resolver),
+            filter: Some(filter),
+        });
+        self
+    }
+
+    /// Resolve the imports of `namespace` with `precedence` rather than
+    /// [`Precedence::FirstMatch`].
+    pub fn policy(mut self, namespace: Namespace<'_>, precedence: Precedence) -> Self {
+        self.policies.insert(namespace.0.to_string(), precedence);
+        self
+    }
+
+    /// Record the layer each import comes from, see [`ChainedResolver::trace`].
+    pub fn record_trace(mut self) -> Self {
+        self.record_trace = true;
+        self
+    }
+
+    /// Build the resolver.
+    pub fn build(self) -> ChainedResolver {
+        ChainedResolver {
+            layers: self.layers,
+            policies: self.policies,
+            trace: if self.record_trace {
+                Some(Mutex::new(Vec::new()))
+            } else {
+                None
+            },
+        }
+    }
+}
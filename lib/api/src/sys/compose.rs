@@ -0,0 +1,1309 @@
+//! Static composition of modules into one, resolving the imports they provide each other at
+//! compile time.
+
+use crate::sys::store::Store;
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+use wasmer_compiler::wasmparser::{
+    BinaryReaderError, Data, DataKind, Element, ElementItem, ElementKind, ExternalKind, FuncType,
+    FunctionBody, GlobalType, ImportSectionEntryType, InitExpr, MemoryType, Name,
+    NameSectionReader, Operator, OperatorsReader, Parser, Payload, ResizableLimits, TableType,
+    Type, TypeDef, TypeOrFuncType,
+};
+use wasmer_compiler::CompileError;
+use wasmer_types::{ErrorCode, HasErrorCode};
+
+/// How [`compose_modules`] merges modules.
+#[derive(Clone, Debug)]
+pub struct ComposeOptions {
+    names: Vec<String>,
+    dedup_functions: bool,
+}
+
+impl ComposeOptions {
+    /// Compose modules named `names`, given in the same order as the modules.
+    ///
+    /// The imports of a module whose module name is the name of another module are satisfied
+    /// by the exports of that module. The others are kept as imports of the composed module.
+    pub fn new<I>(names: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self {
+            names: names.into_iter().map(Into::into).collect(),
+            dedup_functions: false,
+        }
+    }
+
+    /// Merge the functions whose bodies are identical once their indices are renumbered,
+    /// keeping the first one.
+    pub fn with_function_dedup(mut self, dedup: bool) -> Self {
+        self.dedup_functions = dedup;
+        self
+    }
+}
+
+/// An error raised by [`compose_modules`].
+#[derive(Error, Debug)]
+pub enum ComposeError {
+    /// The options do not describe the modules, such as when there are not as many names as
+    /// modules.
+    #[error("invalid composition options: {0} [{}]", ErrorCode::ComposeOptions)]
+    Options(String),
+    /// A module does not validate.
+    #[error("module `{module}` is invalid: {source}")]
+    InvalidModule {
+        /// The name of the module.
+        module: String,
+        /// The validation error.
+        source: CompileError,
+    },
+    /// A module uses a feature composition does not support.
+    #[error(
+        "module `{module}` uses {feature}, which cannot be composed [{}]",
+        ErrorCode::ComposeUnsupported
+    )]
+    Unsupported {
+        /// The name of the module.
+        module: String,
+        /// The feature.
+        feature: &'static str,
+    },
+    /// A module imports something another module does not export.
+    #[error("module `{module}` imports `{import_module}.{field}`, which `{import_module}` does not export [{}]", ErrorCode::ComposeMissingExport)]
+    MissingExport {
+        /// The name of the importing module.
+        module: String,
+        /// The name of the module the import is from.
+        import_module: String,
+        /// The name of the import.
+        field: String,
+    },
+    /// A module imports an export of another module with another type.
+    #[error("module `{module}` imports `{import_module}.{field}` as {expected}, but it is {actual} [{}]", ErrorCode::ComposeIncompatibleImport)]
+    IncompatibleImport {
+        /// The name of the importing module.
+        module: String,
+        /// The name of the module the import is from.
+        import_module: String,
+        /// The name of the import.
+        field: String,
+        /// The type of the import.
+        expected: String,
+        /// The type of the export.
+        actual: String,
+    },
+    /// Imports re-exported from module to module lead back to themselves.
+    #[error(
+        "the import `{import_module}.{field}` of module `{module}` is re-exported in a cycle [{}]",
+        ErrorCode::ComposeCyclicImport
+    )]
+    CyclicImport {
+        /// The name of the importing module.
+        module: String,
+        /// The name of the module the import is from.
+        import_module: String,
+        /// The name of the import.
+        field: String,
+    },
+    /// The composed module would have more than one memory.
+    #[error("the composed module would have several memories, from {} [{}]", owners.join(", "), ErrorCode::ComposeConflictingMemories)]
+    ConflictingMemories {
+        /// The modules defining a memory, and the imports of memories kept for the host as
+        /// `module.field`.
+        owners: Vec<String>,
+    },
+    /// The composed module does not validate, such as when it has several tables without
+    /// the reference types proposal.
+    #[error("the composed module is invalid: {0}")]
+    InvalidComposition(CompileError),
+}
+
+impl HasErrorCode for ComposeError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Options(_) => ErrorCode::ComposeOptions,
+            Self::InvalidModule { source, .. } => source.code(),
+            Self::Unsupported { .. } => ErrorCode::ComposeUnsupported,
+            Self::MissingExport { .. } => ErrorCode::ComposeMissingExport,
+            Self::IncompatibleImport { .. } => ErrorCode::ComposeIncompatibleImport,
+            Self::CyclicImport { .. } => ErrorCode::ComposeCyclicImport,
+            Self::ConflictingMemories { .. } => ErrorCode::ComposeConflictingMemories,
+            Self::InvalidComposition(error) => error.code(),
+        }
+    }
+}
+
+/// Compose `modules` into a single module, in which the imports the modules provide each
+/// other are satisfied directly.
+///
+/// The modules are named by `options`, and an import whose module name is the name of one of
+/// the modules refers to its export of the same name: calls to imported functions become
+/// direct calls, and imported globals, tables and memories become those of the exporting
+/// module. The other imports are kept for the host, merging the identical ones.
+///
+/// The composed module:
+/// * numbers the imports first, then the functions, tables, memories and globals defined by
+///   each module in the order of `modules`;
+/// * exports what the first module exports;
+/// * runs the start functions of the modules in the reverse order of `modules`, so that the
+///   modules listed after the ones importing from them start first;
+/// * names the functions `module::function` after the name sections of the modules, and
+///   drops the other custom sections.
+///
+/// There must be at most one memory once the imports are resolved: two modules may not both
+/// define a memory, unless one of them imports the memory of the other.
+pub fn compose_modules(
+    store: &Store,
+    modules: &[&[u8]],
+    options: ComposeOptions,
+) -> Result<Vec<u8>, ComposeError> {
+    if modules.is_empty() {
+        return Err(ComposeError::Options("no modules to compose".to_string()));
+    }
+    if options.names.len() != modules.len() {
+        return Err(ComposeError::Options(format!(
+            "{} names were given for {} modules",
+            options.names.len(),
+            modules.len()
+        )));
+    }
+    let mut by_name = HashMap::new();
+    for (index, name) in options.names.iter().enumerate() {
+        if by_name.insert(name.as_str(), index).is_some() {
+            return Err(ComposeError::Options(format!(
+                "two modules are named `{}`",
+                name
+            )));
+        }
+    }
+    let mut parsed = Vec::with_capacity(modules.len());
+    for (bytes, name) in modules.iter().zip(&options.names) {
+        store
+            .engine()
+            .validate(bytes)
+            .map_err(|source| ComposeError::InvalidModule {
+                module: name.clone(),
+                source,
+            })?;
+        parsed.push(ParsedModule::parse(name, bytes)?);
+    }
+
+    let layout = Layout::new(&parsed, &by_name, options.dedup_functions)?;
+    let composed = layout.encode(&parsed)?;
+    store
+        .engine()
+        .validate(&composed)
+        .map_err(ComposeError::InvalidComposition)?;
+    Ok(composed)
+}
+
+/// The kinds of entities imports can refer to, as indices into per-kind arrays.
+const FUNC: usize = 0;
+const TABLE: usize = 1;
+const MEMORY: usize = 2;
+const GLOBAL: usize = 3;
+
+fn kind_of(kind: ExternalKind) -> Option<usize> {
+    match kind {
+        ExternalKind::Function => Some(FUNC),
+        ExternalKind::Table => Some(TABLE),
+        ExternalKind::Memory => Some(MEMORY),
+        ExternalKind::Global => Some(GLOBAL),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum ImportType {
+    Func(u32),
+    Table(TableType),
+    Memory(MemoryType),
+    Global(GlobalType),
+}
+
+impl ImportType {
+    fn kind(&self) -> usize {
+        match self {
+            Self::Func(_) => FUNC,
+            Self::Table(_) => TABLE,
+            Self::Memory(_) => MEMORY,
+            Self::Global(_) => GLOBAL,
+        }
+    }
+}
+
+struct Import<'a> {
+    module: &'a str,
+    field: &'a str,
+    ty: ImportType,
+}
+
+/// The parts of a module composition needs.
+struct ParsedModule<'a> {
+    name: &'a str,
+    bytes: &'a [u8],
+    types: Vec<FuncType>,
+    imports: Vec<Import<'a>>,
+    /// The imports of every kind, by index in the index space of their kind.
+    imports_by_kind: [Vec<usize>; 4],
+    functions: Vec<u32>,
+    tables: Vec<TableType>,
+    memories: Vec<MemoryType>,
+    globals: Vec<(GlobalType, InitExpr<'a>)>,
+    exports: Vec<(&'a str, usize, u32)>,
+    start: Option<u32>,
+    elements: Vec<Element<'a>>,
+    data: Vec<Data<'a>>,
+    has_data_count: bool,
+    bodies: Vec<FunctionBody<'a>>,
+    function_names: Vec<(u32, &'a str)>,
+    local_names: Vec<(u32, Vec<(u32, &'a str)>)>,
+}
+
+impl<'a> ParsedModule<'a> {
+    fn parse(name: &'a str, bytes: &'a [u8]) -> Result<Self, ComposeError> {
+        let mut module = Self {
+            name,
+            bytes,
+            types: Vec::new(),
+            imports: Vec::new(),
+            imports_by_kind: Default::default(),
+            functions: Vec::new(),
+            tables: Vec::new(),
+            memories: Vec::new(),
+            globals: Vec::new(),
+            exports: Vec::new(),
+            start: None,
+            elements: Vec::new(),
+            data: Vec::new(),
+            has_data_count: false,
+            bodies: Vec::new(),
+            function_names: Vec::new(),
+            local_names: Vec::new(),
+        };
+        module
+            .parse_payloads()
+            .map_err(|error| ComposeError::InvalidModule {
+                module: name.to_string(),
+                source: error.into(),
+            })??;
+        Ok(module)
+    }
+
+    /// Parse the sections, failing with the outer error for malformed bytes and the inner one
+    /// for unsupported features.
+    fn parse_payloads(&mut self) -> Result<Result<(), ComposeError>, BinaryReaderError> {
+        let module = self.name;
+        let unsupported = |feature| {
+            Ok(Err(ComposeError::Unsupported {
+                module: module.to_string(),
+                feature,
+            }))
+        };
+        for payload in Parser::new(0).parse_all(self.bytes) {
+            match payload? {
+                Payload::TypeSection(types) => {
+                    for ty in types {
+                        match ty? {
+                            TypeDef::Func(ty) => self.types.push(ty),
+                            _ => return unsupported("module linking"),
+                        }
+                    }
+                }
+                Payload::ImportSection(imports) => {
+                    for import in imports {
+                        let import = import?;
+                        let ty = match import.ty {
+                            ImportSectionEntryType::Function(ty) => ImportType::Func(ty),
+                            ImportSectionEntryType::Table(ty) => ImportType::Table(ty),
+                            ImportSectionEntryType::Memory(ty) => ImportType::Memory(ty),
+                            ImportSectionEntryType::Global(ty) => ImportType::Global(ty),
+                            ImportSectionEntryType::Event(_) => {
+                                return unsupported("exception handling")
+                            }
+                            _ => return unsupported("module linking"),
+                        };
+                        let field = match import.field {
+                            Some(field) => field,
+                            None => return unsupported("module linking"),
+                        };
+                        self.imports_by_kind[ty.kind()].push(self.imports.len());
+                        self.imports.push(Import {
+                            module: import.module,
+                            field,
+                            ty,
+                        });
+                    }
+                }
+                Payload::FunctionSection(functions) => {
+                    for ty in functions {
+                        self.functions.push(ty?);
+                    }
+                }
+                Payload::TableSection(tables) => {
+                    for table in tables {
+                        self.tables.push(table?);
+                    }
+                }
+                Payload::MemorySection(memories) => {
+                    for memory in memories {
+                        self.memories.push(memory?);
+                    }
+                }
+                Payload::GlobalSection(globals) => {
+                    for global in globals {
+                        let global = global?;
+                        self.globals.push((global.ty, global.init_expr));
+                    }
+                }
+                Payload::ExportSection(exports) => {
+                    for export in exports {
+                        let export = export?;
+                        match kind_of(export.kind) {
+                            Some(kind) => self.exports.push((export.field, kind, export.index)),
+                            None => return unsupported("module linking"),
+                        }
+                    }
+                }
+                Payload::StartSection { func, .. } => self.start = Some(func),
+                Payload::ElementSection(elements) => {
+                    for element in elements {
+                        self.elements.push(element?);
+                    }
+                }
+                Payload::DataCountSection { .. } => self.has_data_count = true,
+                Payload::DataSection(data) => {
+                    for data in data {
+                        self.data.push(data?);
+                    }
+                }
+                Payload::CodeSectionEntry(body) => self.bodies.push(body),
+                Payload::CustomSection {
+                    name: "name",
+                    data,
+                    data_offset,
+                    ..
+                } => self.parse_names(data, data_offset)?,
+                Payload::EventSection(_) => return unsupported("exception handling"),
+                Payload::AliasSection(_)
+                | Payload::InstanceSection(_)
+                | Payload::ModuleSectionStart { .. } => return unsupported("module linking"),
+                _ => {}
+            }
+        }
+        Ok(Ok(()))
+    }
+
+    fn parse_names(&mut self, data: &'a [u8], offset: usize) -> Result<(), BinaryReaderError> {
+        let mut names = NameSectionReader::new(data, offset)?;
+        while !names.eof() {
+            match names.read()? {
+                Name::Function(functions) => {
+                    let mut map = functions.get_map()?;
+                    for _ in 0..map.get_count() {
+                        let naming = map.read()?;
+                        self.function_names.push((naming.index, naming.name));
+                    }
+                }
+                Name::Local(locals) => {
+                    let mut functions = locals.get_function_local_reader()?;
+                    for _ in 0..functions.get_count() {
+                        let function = functions.read()?;
+                        let mut map = function.get_map()?;
+                        let mut names = Vec::new();
+                        for _ in 0..map.get_count() {
+                            let naming = map.read()?;
+                            names.push((naming.index, naming.name));
+                        }
+                        self.local_names.push((function.func_index, names));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn import_count(&self, kind: usize) -> u32 {
+        self.imports_by_kind[kind].len() as u32
+    }
+
+    fn defined_count(&self, kind: usize) -> u32 {
+        (match kind {
+            FUNC => self.functions.len(),
+            TABLE => self.tables.len(),
+            MEMORY => self.memories.len(),
+            _ => self.globals.len(),
+        }) as u32
+    }
+
+    fn export(&self, field: &str, kind: usize) -> Option<u32> {
+        self.exports
+            .iter()
+            .find(|(name, export_kind, _)| *name == field && *export_kind == kind)
+            .map(|(_, _, index)| *index)
+    }
+
+    /// The type of the entity of `kind` at `index`, as it would be imported.
+    fn entity_type(&self, kind: usize, index: u32) -> ImportType {
+        let imports = self.import_count(kind);
+        if index < imports {
+            return self.imports[self.imports_by_kind[kind][index as usize]].ty;
+        }
+        let defined = (index - imports) as usize;
+        match kind {
+            FUNC => ImportType::Func(self.functions[defined]),
+            TABLE => ImportType::Table(self.tables[defined]),
+            MEMORY => ImportType::Memory(self.memories[defined]),
+            _ => ImportType::Global(self.globals[defined].0),
+        }
+    }
+
+    fn describe(&self, ty: &ImportType) -> String {
+        match ty {
+            ImportType::Func(index) => {
+                let ty = &self.types[*index as usize];
+                format!("a function {:?} -> {:?}", ty.params, ty.returns)
+            }
+            ImportType::Table(ty) => format!("a table {:?}", ty),
+            ImportType::Memory(ty) => format!("a memory {:?}", ty),
+            ImportType::Global(ty) => format!("a global {:?}", ty),
+        }
+    }
+}
+
+/// Whether limits `actual` can be imported with limits `expected`.
+fn limits_match(actual: &ResizableLimits, expected: &ResizableLimits) -> bool {
+    actual.initial >= expected.initial
+        && match (actual.maximum, expected.maximum) {
+            (_, None) => true,
+            (Some(actual), Some(expected)) => actual <= expected,
+            (None, Some(_)) => false,
+        }
+}
+
+/// Where an import is resolved.
+#[derive(Clone, Copy)]
+enum Resolution {
+    /// An import of the composed module, by index in the index space of its kind.
+    Host(u32),
+    /// The entity at this index of another module.
+    Module(usize, u32),
+}
+
+/// The index spaces of the composed module.
+struct Layout {
+    types: Vec<FuncType>,
+    /// The composed type of every type of every module.
+    type_maps: Vec<Vec<u32>>,
+    /// The composed index of every entity of every module, by kind.
+    maps: Vec<[Vec<u32>; 4]>,
+    /// The imports kept for the host, by module and index in its import section.
+    host_imports: Vec<(usize, usize)>,
+    /// The defined functions kept after deduplication, by module and defined index.
+    functions: Vec<(usize, usize)>,
+    element_bases: Vec<u32>,
+    data_bases: Vec<u32>,
+}
+
+impl Layout {
+    fn new(
+        modules: &[ParsedModule],
+        by_name: &HashMap<&str, usize>,
+        dedup_functions: bool,
+    ) -> Result<Self, ComposeError> {
+        let mut types = Vec::new();
+        let mut known_types = HashMap::new();
+        let type_maps = modules
+            .iter()
+            .map(|module| {
+                module
+                    .types
+                    .iter()
+                    .map(|ty| {
+                        *known_types.entry(ty.clone()).or_insert_with(|| {
+                            types.push(ty.clone());
+                            types.len() as u32 - 1
+                        })
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // Find the export satisfying each import, keeping the others for the host.
+        let mut resolutions = Vec::with_capacity(modules.len());
+        let mut host_imports = Vec::new();
+        let mut host_keys = HashMap::new();
+        let mut host_counts = [0; 4];
+        for (index, module) in modules.iter().enumerate() {
+            let mut module_resolutions = Vec::with_capacity(module.imports.len());
+            for (import_index, import) in module.imports.iter().enumerate() {
+                let kind = import.ty.kind();
+                let resolution = match by_name.get(import.module) {
+                    Some(&provider) => {
+                        let target = modules[provider].export(import.field, kind);
+                        let target = match target {
+                            Some(target) if provider != index => target,
+                            Some(_) => {
+                                return Err(ComposeError::CyclicImport {
+                                    module: module.name.to_string(),
+                                    import_module: import.module.to_string(),
+                                    field: import.field.to_string(),
+                                })
+                            }
+                            None => {
+                                return Err(ComposeError::MissingExport {
+                                    module: module.name.to_string(),
+                                    import_module: import.module.to_string(),
+                                    field: import.field.to_string(),
+                                })
+                            }
+                        };
+                        let provided = modules[provider].entity_type(kind, target);
+                        let matches = match (&import.ty, &provided) {
+                            (ImportType::Func(expected), ImportType::Func(actual)) => {
+                                type_maps[index][*expected as usize]
+                                    == type_maps[provider][*actual as usize]
+                            }
+                            (ImportType::Table(expected), ImportType::Table(actual)) => {
+                                expected.element_type == actual.element_type
+                                    && limits_match(&actual.limits, &expected.limits)
+                            }
+                            (
+                                ImportType::Memory(MemoryType::M32 {
+                                    limits: expected,
+                                    shared: expected_shared,
+                                }),
+                                ImportType::Memory(MemoryType::M32 {
+                                    limits: actual,
+                                    shared: actual_shared,
+                                }),
+                            ) => expected_shared == actual_shared && limits_match(actual, expected),
+                            (ImportType::Global(expected), ImportType::Global(actual)) => {
+                                expected == actual
+                            }
+                            _ => false,
+                        };
+                        if !matches {
+                            return Err(ComposeError::IncompatibleImport {
+                                module: module.name.to_string(),
+                                import_module: import.module.to_string(),
+                                field: import.field.to_string(),
+                                expected: module.describe(&import.ty),
+                                actual: modules[provider].describe(&provided),
+                            });
+                        }
+                        Resolution::Module(provider, target)
+                    }
+                    None => {
+                        let ty = match import.ty {
+                            ImportType::Func(ty) => ImportType::Func(type_maps[index][ty as usize]),
+                            ty => ty,
+                        };
+                        let key = (import.module, import.field, ty);
+                        let next = &mut host_counts[kind];
+                        let host = *host_keys.entry(key).or_insert_with(|| {
+                            host_imports.push((index, import_index));
+                            *next += 1;
+                            *next - 1
+                        });
+                        Resolution::Host(host)
+                    }
+                };
+                module_resolutions.push(resolution);
+            }
+            resolutions.push(module_resolutions);
+        }
+
+        // Number the defined entities after the imports, module after module.
+        let mut bases = Vec::with_capacity(modules.len());
+        let mut next = host_counts;
+        for module in modules {
+            bases.push(next);
+            for (kind, next) in next.iter_mut().enumerate() {
+                *next += module.defined_count(kind);
+            }
+        }
+        let memories = next[MEMORY];
+        if memories > 1 {
+            let mut owners = host_imports
+                .iter()
+                .map(|&(module, import)| &modules[module].imports[import])
+                .filter(|import| import.ty.kind() == MEMORY)
+                .map(|import| format!("`{}.{}`", import.module, import.field))
+                .collect::<Vec<_>>();
+            owners.extend(
+                modules
+                    .iter()
+                    .filter(|module| !module.memories.is_empty())
+                    .map(|module| format!("`{}`", module.name)),
+            );
+            return Err(ComposeError::ConflictingMemories { owners });
+        }
+
+        let mut maps = Vec::with_capacity(modules.len());
+        for (index, module) in modules.iter().enumerate() {
+            let mut map: [Vec<u32>; 4] = Default::default();
+            for (kind, map) in map.iter_mut().enumerate() {
+                let total = module.import_count(kind) + module.defined_count(kind);
+                for entity in 0..total {
+                    map.push(resolve(
+                        modules,
+                        &resolutions,
+                        &bases,
+                        (index, kind, entity),
+                        &mut Vec::new(),
+                    )?);
+                }
+            }
+            maps.push(map);
+        }
+
+        let mut layout = Self {
+            types,
+            type_maps,
+            maps,
+            host_imports,
+            functions: Vec::new(),
+            element_bases: Vec::new(),
+            data_bases: Vec::new(),
+        };
+        let (mut elements, mut data) = (0, 0);
+        for module in modules {
+            layout.element_bases.push(elements);
+            layout.data_bases.push(data);
+            elements += module.elements.len() as u32;
+            data += module.data.len() as u32;
+        }
+
+        // Merge the identical functions, comparing their bodies as numbered without merging:
+        // the bodies identical then are still identical once renumbered.
+        let mut functions = Vec::new();
+        let mut renumbering = (0..next[FUNC]).collect::<Vec<u32>>();
+        let mut known_bodies = HashMap::new();
+        let mut kept = host_counts[FUNC];
+        for (index, module) in modules.iter().enumerate() {
+            for (defined, body) in module.bodies.iter().enumerate() {
+                let original = bases[index][FUNC] + defined as u32;
+                let ty = layout.type_maps[index][module.functions[defined] as usize];
+                let canonical = if dedup_functions {
+                    let body = layout.encode_body(modules, index, body)?;
+                    *known_bodies.entry((ty, body)).or_insert(original)
+                } else {
+                    original
+                };
+                if canonical == original {
+                    renumbering[original as usize] = kept;
+                    kept += 1;
+                    functions.push((index, defined));
+                } else {
+                    renumbering[original as usize] = renumbering[canonical as usize];
+                }
+            }
+        }
+        for map in &mut layout.maps {
+            for function in &mut map[FUNC] {
+                *function = renumbering[*function as usize];
+            }
+        }
+        layout.functions = functions;
+        Ok(layout)
+    }
+
+    fn encode(&self, modules: &[ParsedModule]) -> Result<Vec<u8>, ComposeError> {
+        let first = &modules[0];
+        let mut types = self.types.clone();
+
+        let mut imports = Vec::new();
+        for &(module, import) in &self.host_imports {
+            let import = &modules[module].imports[import];
+            name(&mut imports, import.module);
+            name(&mut imports, import.field);
+            match import.ty {
+                ImportType::Func(ty) => {
+                    imports.push(0x00);
+                    leb(&mut imports, self.type_maps[module][ty as usize]);
+                }
+                ImportType::Table(ty) => {
+                    imports.push(0x01);
+                    table_type(&mut imports, &ty, first)?;
+                }
+                ImportType::Memory(ty) => {
+                    imports.push(0x02);
+                    memory_type(&mut imports, &ty);
+                }
+                ImportType::Global(ty) => {
+                    imports.push(0x03);
+                    global_type(&mut imports, &ty, first)?;
+                }
+            }
+        }
+
+        let mut functions = Vec::new();
+        for &(module, defined) in &self.functions {
+            let ty = modules[module].functions[defined];
+            leb(&mut functions, self.type_maps[module][ty as usize]);
+        }
+        let (mut tables, mut memories, mut globals) = (Vec::new(), Vec::new(), Vec::new());
+        let (mut table_count, mut memory_count, mut global_count) = (0, 0, 0);
+        for (index, module) in modules.iter().enumerate() {
+            for ty in &module.tables {
+                table_type(&mut tables, ty, module)?;
+                table_count += 1;
+            }
+            for ty in &module.memories {
+                memory_type(&mut memories, ty);
+                memory_count += 1;
+            }
+            for (ty, init) in &module.globals {
+                global_type(&mut globals, ty, module)?;
+                self.encode_expr(modules, index, init, &mut globals)?;
+                global_count += 1;
+            }
+        }
+
+        let mut exports = Vec::new();
+        for &(field, kind, index) in &first.exports {
+            name(&mut exports, field);
+            exports.push(kind as u8);
+            leb(&mut exports, self.maps[0][kind][index as usize]);
+        }
+
+        let mut elements = Vec::new();
+        let mut element_count = 0;
+        for (index, module) in modules.iter().enumerate() {
+            for element in &module.elements {
+                self.encode_element(modules, index, element, &mut elements)?;
+                element_count += 1;
+            }
+        }
+
+        let mut code = Vec::new();
+        for &(module, defined) in &self.functions {
+            let body = self.encode_body(modules, module, &modules[module].bodies[defined])?;
+            leb(&mut code, body.len() as u32);
+            code.extend(body);
+        }
+        let mut function_count = self.functions.len() as u32;
+
+        // Several start functions are called in turn by a new one, the modules listed last
+        // starting first.
+        let starts = modules
+            .iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(index, module)| Some(self.maps[index][FUNC][module.start? as usize]))
+            .collect::<Vec<_>>();
+        let start = match starts[..] {
+            [] => None,
+            [start] => Some(start),
+            _ => {
+                let empty = FuncType {
+                    params: Box::new([]),
+                    returns: Box::new([]),
+                };
+                let ty = match types.iter().position(|ty| *ty == empty) {
+                    Some(ty) => ty,
+                    None => {
+                        types.push(empty);
+                        types.len() - 1
+                    }
+                };
+                leb(&mut functions, ty as u32);
+                let mut body = vec![0x00];
+                for start in starts {
+                    op(&mut body, &[0x10], &[start]);
+                }
+                body.push(0x0B);
+                leb(&mut code, body.len() as u32);
+                code.extend(body);
+                function_count += 1;
+                Some(self.host_imports_of(modules, FUNC) + function_count - 1)
+            }
+        };
+
+        let mut data = Vec::new();
+        let mut data_count = 0;
+        for (index, module) in modules.iter().enumerate() {
+            for segment in &module.data {
+                match &segment.kind {
+                    DataKind::Passive => data.push(0x01),
+                    DataKind::Active { init_expr, .. } => {
+                        data.push(0x00);
+                        self.encode_expr(modules, index, init_expr, &mut data)?;
+                    }
+                }
+                leb(&mut data, segment.data.len() as u32);
+                data.extend_from_slice(segment.data);
+                data_count += 1;
+            }
+        }
+
+        let mut type_section = Vec::new();
+        for ty in &types {
+            type_section.push(0x60);
+            leb(&mut type_section, ty.params.len() as u32);
+            for param in ty.params.iter() {
+                type_section.push(value_type(*param, first)?);
+            }
+            leb(&mut type_section, ty.returns.len() as u32);
+            for result in ty.returns.iter() {
+                type_section.push(value_type(*result, first)?);
+            }
+        }
+
+        let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+        section(&mut bytes, 1, types.len() as u32, &type_section);
+        section(&mut bytes, 2, self.host_imports.len() as u32, &imports);
+        section(&mut bytes, 3, function_count, &functions);
+        section(&mut bytes, 4, table_count, &tables);
+        section(&mut bytes, 5, memory_count, &memories);
+        section(&mut bytes, 6, global_count, &globals);
+        section(&mut bytes, 7, first.exports.len() as u32, &exports);
+        if let Some(start) = start {
+            let mut contents = Vec::new();
+            leb(&mut contents, start);
+            write_section(&mut bytes, 8, &contents);
+        }
+        section(&mut bytes, 9, element_count, &elements);
+        if modules.iter().any(|module| module.has_data_count) {
+            let mut contents = Vec::new();
+            leb(&mut contents, data_count);
+            write_section(&mut bytes, 12, &contents);
+        }
+        section(&mut bytes, 10, function_count, &code);
+        section(&mut bytes, 11, data_count, &data);
+        self.encode_names(modules, &mut bytes);
+        Ok(bytes)
+    }
+
+    fn host_imports_of(&self, modules: &[ParsedModule], kind: usize) -> u32 {
+        self.host_imports
+            .iter()
+            .filter(|&&(module, import)| modules[module].imports[import].ty.kind() == kind)
+            .count() as u32
+    }
+
+    /// Append a `name` section naming the functions `module::function`.
+    fn encode_names(&self, modules: &[ParsedModule], bytes: &mut Vec<u8>) {
+        let mut functions = BTreeMap::new();
+        let mut locals = BTreeMap::new();
+        for (index, module) in modules.iter().enumerate() {
+            let imports = module.import_count(FUNC);
+            let map = &self.maps[index][FUNC];
+            let is_kept = |function: u32| {
+                if function < imports {
+                    let import = &module.imports[module.imports_by_kind[FUNC][function as usize]];
+                    !modules.iter().any(|other| other.name == import.module)
+                } else {
+                    let defined = (function - imports) as usize;
+                    self.functions.contains(&(index, defined))
+                }
+            };
+            for &(function, function_name) in &module.function_names {
+                if (function as usize) < map.len() && is_kept(function) {
+                    functions
+                        .entry(map[function as usize])
+                        .or_insert_with(|| format!("{}::{}", module.name, function_name));
+                }
+            }
+            for (function, names) in &module.local_names {
+                if (*function as usize) < map.len() && *function >= imports && is_kept(*function) {
+                    locals.entry(map[*function as usize]).or_insert(names);
+                }
+            }
+        }
+        if functions.is_empty() && locals.is_empty() {
+            return;
+        }
+
+        let mut section = Vec::new();
+        name(&mut section, "name");
+        let mut subsection = Vec::new();
+        leb(&mut subsection, functions.len() as u32);
+        for (function, function_name) in &functions {
+            leb(&mut subsection, *function);
+            name(&mut subsection, function_name);
+        }
+        section.push(1);
+        leb(&mut section, subsection.len() as u32);
+        section.extend(subsection);
+        let mut subsection = Vec::new();
+        leb(&mut subsection, locals.len() as u32);
+        for (function, names) in &locals {
+            leb(&mut subsection, *function);
+            leb(&mut subsection, names.len() as u32);
+            for (local, local_name) in names.iter() {
+                leb(&mut subsection, *local);
+                name(&mut subsection, local_name);
+            }
+        }
+        section.push(2);
+        leb(&mut section, subsection.len() as u32);
+        section.extend(subsection);
+
+        write_section(bytes, 0, &section);
+    }
+
+    fn encode_element(
+        &self,
+        modules: &[ParsedModule],
+        module: usize,
+        element: &Element,
+        out: &mut Vec<u8>,
+    ) -> Result<(), ComposeError> {
+        let malformed = |error: BinaryReaderError| invalid(&modules[module], error);
+        let mut items = Vec::new();
+        let mut reader = element.items.get_items_reader().map_err(malformed)?;
+        for _ in 0..reader.get_count() {
+            items.push(reader.read().map_err(malformed)?);
+        }
+        let functions = element.ty == Type::FuncRef
+            && items
+                .iter()
+                .all(|item| matches!(item, ElementItem::Func(_)));
+        let (flags, table, offset) = match &element.kind {
+            ElementKind::Passive => (0x01, None, None),
+            ElementKind::Declared => (0x03, None, None),
+            ElementKind::Active {
+                table_index,
+                init_expr,
+            } => {
+                let table = self.maps[module][TABLE][*table_index as usize];
+                if table == 0 && functions {
+                    (0x00, None, Some(init_expr))
+                } else {
+                    (0x02, Some(table), Some(init_expr))
+                }
+            }
+        };
+        out.push(if functions || flags == 0x00 {
+            flags
+        } else {
+            flags | 0x04
+        });
+        if let Some(table) = table {
+            leb(out, table);
+        }
+        if let Some(offset) = offset {
+            self.encode_expr(modules, module, offset, out)?;
+        }
+        if flags != 0x00 {
+            if functions {
+                out.push(0x00);
+            } else {
+                out.push(value_type(element.ty, &modules[module])?);
+            }
+        }
+        leb(out, items.len() as u32);
+        for item in items {
+            match item {
+                ElementItem::Func(function) => {
+                    let function = self.maps[module][FUNC][function as usize];
+                    if !functions {
+                        out.push(0xD2);
+                    }
+                    leb(out, function);
+                    if !functions {
+                        out.push(0x0B);
+                    }
+                }
+                ElementItem::Null(ty) => {
+                    out.push(0xD0);
+                    out.push(value_type(ty, &modules[module])?);
+                    out.push(0x0B);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn encode_expr(
+        &self,
+        modules: &[ParsedModule],
+        module: usize,
+        expr: &InitExpr,
+        out: &mut Vec<u8>,
+    ) -> Result<(), ComposeError> {
+        self.encode_operators(modules, module, expr.get_operators_reader(), out)
+            .map_err(|error| invalid(&modules[module], error))
+    }
+
+    /// Encode a function body with the indices of the composed module.
+    fn encode_body(
+        &self,
+        modules: &[ParsedModule],
+        module: usize,
+        body: &FunctionBody,
+    ) -> Result<Vec<u8>, ComposeError> {
+        let parsed = &modules[module];
+        let encode = || {
+            let operators = body.get_operators_reader()?;
+            // The locals do not refer to any index.
+            let mut out = parsed.bytes[body.range().start..operators.original_position()].to_vec();
+            self.encode_operators(modules, module, operators, &mut out)?;
+            Ok(out)
+        };
+        encode().map_err(|error| invalid(parsed, error))
+    }
+
+    /// Copy operators, renumbering the indices they refer to.
+    fn encode_operators(
+        &self,
+        modules: &[ParsedModule],
+        module: usize,
+        mut operators: OperatorsReader,
+        out: &mut Vec<u8>,
+    ) -> Result<(), BinaryReaderError> {
+        let bytes = modules[module].bytes;
+        let map = &self.maps[module];
+        let types = &self.type_maps[module];
+        let (elements, data) = (self.element_bases[module], self.data_bases[module]);
+        while !operators.eof() {
+            let (operator, start) = operators.read_with_offset()?;
+            let end = operators.original_position();
+            match operator {
+                Operator::Block {
+                    ty: TypeOrFuncType::FuncType(ty),
+                }
+                | Operator::Loop {
+                    ty: TypeOrFuncType::FuncType(ty),
+                }
+                | Operator::If {
+                    ty: TypeOrFuncType::FuncType(ty),
+                }
+                | Operator::Try {
+                    ty: TypeOrFuncType::FuncType(ty),
+                } => {
+                    out.push(bytes[start]);
+                    sleb(out, i64::from(types[ty as usize]));
+                }
+                Operator::Call { function_index } => {
+                    op(out, &[0x10], &[map[FUNC][function_index as usize]])
+                }
+                Operator::ReturnCall { function_index } => {
+                    op(out, &[0x12], &[map[FUNC][function_index as usize]])
+                }
+                Operator::RefFunc { function_index } => {
+                    op(out, &[0xD2], &[map[FUNC][function_index as usize]])
+                }
+                Operator::CallIndirect { index, table_index } => op(
+                    out,
+                    &[0x11],
+                    &[types[index as usize], map[TABLE][table_index as usize]],
+                ),
+                Operator::ReturnCallIndirect { index, table_index } => op(
+                    out,
+                    &[0x13],
+                    &[types[index as usize], map[TABLE][table_index as usize]],
+                ),
+                Operator::GlobalGet { global_index } => {
+                    op(out, &[0x23], &[map[GLOBAL][global_index as usize]])
+                }
+                Operator::GlobalSet { global_index } => {
+                    op(out, &[0x24], &[map[GLOBAL][global_index as usize]])
+                }
+                Operator::TableGet { table } => op(out, &[0x25], &[map[TABLE][table as usize]]),
+                Operator::TableSet { table } => op(out, &[0x26], &[map[TABLE][table as usize]]),
+                Operator::MemoryInit { segment, mem } => {
+                    op(out, &[0xFC, 0x08], &[data + segment, mem])
+                }
+                Operator::DataDrop { segment } => op(out, &[0xFC, 0x09], &[data + segment]),
+                Operator::TableInit { segment, table } => op(
+                    out,
+                    &[0xFC, 0x0C],
+                    &[elements + segment, map[TABLE][table as usize]],
+                ),
+                Operator::ElemDrop { segment } => op(out, &[0xFC, 0x0D], &[elements + segment]),
+                Operator::TableCopy {
+                    dst_table,
+                    src_table,
+                } => op(
+                    out,
+                    &[0xFC, 0x0E],
+                    &[
+                        map[TABLE][dst_table as usize],
+                        map[TABLE][src_table as usize],
+                    ],
+                ),
+                Operator::TableGrow { table } => {
+                    op(out, &[0xFC, 0x0F], &[map[TABLE][table as usize]])
+                }
+                Operator::TableSize { table } => {
+                    op(out, &[0xFC, 0x10], &[map[TABLE][table as usize]])
+                }
+                Operator::TableFill { table } => {
+                    op(out, &[0xFC, 0x11], &[map[TABLE][table as usize]])
+                }
+                _ => out.extend_from_slice(&bytes[start..end]),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The composed index of the entity `(module, kind, index)`, following the imports from
+/// module to module.
+fn resolve(
+    modules: &[ParsedModule],
+    resolutions: &[Vec<Resolution>],
+    bases: &[[u32; 4]],
+    (module, kind, index): (usize, usize, u32),
+    visiting: &mut Vec<(usize, usize, u32)>,
+) -> Result<u32, ComposeError> {
+    let parsed = &modules[module];
+    let imports = parsed.import_count(kind);
+    if index >= imports {
+        return Ok(bases[module][kind] + index - imports);
+    }
+    let import = parsed.imports_by_kind[kind][index as usize];
+    match resolutions[module][import] {
+        Resolution::Host(host) => Ok(host),
+        Resolution::Module(provider, target) => {
+            if visiting.contains(&(module, kind, index)) {
+                let import = &parsed.imports[import];
+                return Err(ComposeError::CyclicImport {
+                    module: parsed.name.to_string(),
+                    import_module: import.module.to_string(),
+                    field: import.field.to_string(),
+                });
+            }
+            visiting.push((module, kind, index));
+            resolve(
+                modules,
+                resolutions,
+                bases,
+                (provider, kind, target),
+                visiting,
+            )
+        }
+    }
+}
+
+fn invalid(module: &ParsedModule, error: BinaryReaderError) -> ComposeError {
+    ComposeError::InvalidModule {
+        module: module.name.to_string(),
+        source: error.into(),
+    }
+}
+
+fn op(out: &mut Vec<u8>, opcode: &[u8], immediates: &[u32]) {
+    out.extend_from_slice(opcode);
+    for immediate in immediates {
+        leb(out, *immediate);
+    }
+}
+
+fn value_type(ty: Type, module: &ParsedModule) -> Result<u8, ComposeError> {
+    Ok(match ty {
+        Type::I32 => 0x7F,
+        Type::I64 => 0x7E,
+        Type::F32 => 0x7D,
+        Type::F64 => 0x7C,
+        Type::V128 => 0x7B,
+        Type::FuncRef => 0x70,
+        Type::ExternRef => 0x6F,
+        _ => {
+            return Err(ComposeError::Unsupported {
+                module: module.name.to_string(),
+                feature: "exception handling",
+            })
+        }
+    })
+}
+
+fn limits(out: &mut Vec<u8>, flags: u8, limits: &ResizableLimits) {
+    out.push(flags | u8::from(limits.maximum.is_some()));
+    leb(out, limits.initial);
+    if let Some(maximum) = limits.maximum {
+        leb(out, maximum);
+    }
+}
+
+fn table_type(
+    out: &mut Vec<u8>,
+    ty: &TableType,
+    module: &ParsedModule,
+) -> Result<(), ComposeError> {
+    out.push(value_type(ty.element_type, module)?);
+    limits(out, 0, &ty.limits);
+    Ok(())
+}
+
+fn memory_type(out: &mut Vec<u8>, ty: &MemoryType) {
+    match ty {
+        MemoryType::M32 { limits: l, shared } => limits(out, u8::from(*shared) << 1, l),
+        MemoryType::M64 { limits, shared } => {
+            out.push(0x04 | u8::from(*shared) << 1 | u8::from(limits.maximum.is_some()));
+            leb64(out, limits.initial);
+            if let Some(maximum) = limits.maximum {
+                leb64(out, maximum);
+            }
+        }
+    }
+}
+
+fn global_type(
+    out: &mut Vec<u8>,
+    ty: &GlobalType,
+    module: &ParsedModule,
+) -> Result<(), ComposeError> {
+    out.push(value_type(ty.content_type, module)?);
+    out.push(u8::from(ty.mutable));
+    Ok(())
+}
+
+/// Append a section holding `count` entries, unless it would be empty.
+fn section(bytes: &mut Vec<u8>, id: u8, count: u32, entries: &[u8]) {
+    if count == 0 {
+        return;
+    }
+    let mut contents = Vec::new();
+    leb(&mut contents, count);
+    contents.extend_from_slice(entries);
+    write_section(bytes, id, &contents);
+}
+
+fn write_section(bytes: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    bytes.push(id);
+    leb(bytes, contents.len() as u32);
+    bytes.extend_from_slice(contents);
+}
+
+/// Append the unsigned LEB128 encoding of `value`.
+fn leb(bytes: &mut Vec<u8>, value: u32) {
+    leb64(bytes, u64::from(value))
+}
+
+fn leb64(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Append the signed LEB128 encoding of `value`.
+fn sleb(bytes: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn name(bytes: &mut Vec<u8>, name: &str) {
+    leb(bytes, name.len() as u32);
+    bytes.extend_from_slice(name.as_bytes());
+}
@@ -0,0 +1,160 @@
+//! Deterministic generation of conformance cases from a seed.
+//!
+//! Operands are drawn from distributions heavy in the values implementations get wrong:
+//! integer extremes, powers of two and their neighbours, shift counts around the width of
+//! the operand, signed zeros, subnormals, NaNs with arbitrary payloads, and the floats
+//! bounding the ranges of the integer types.
+
+use super::{Case, Number, NumericOp, Placement};
+use crate::ValType;
+
+/// A SplitMix64 generator, so that cases only depend on the seed, whatever the platform.
+pub(super) struct Rng(u64);
+
+impl Rng {
+    /// The generator of the case at `index` of the run seeded with `seed`.
+    pub(super) fn for_case(seed: u64, index: u64) -> Self {
+        Self(mix(seed ^ mix(index)))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.0)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u64) as usize]
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.below(100) < percent
+    }
+}
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Generate the case at `index` of the run seeded with `seed`, exercising one of `ops`.
+pub(super) fn case(seed: u64, index: u64, ops: &[NumericOp]) -> Case {
+    let mut rng = Rng::for_case(seed, index);
+    let op = rng.pick(ops);
+    let operands = op
+        .params()
+        .iter()
+        .map(|ty| operand(&mut rng, *ty))
+        .collect();
+    let placement = rng.pick(&[
+        Placement::Params,
+        Placement::Constants,
+        Placement::LastConstant,
+    ]);
+    Case::new(op, operands, placement)
+}
+
+fn operand(rng: &mut Rng, ty: ValType) -> Number {
+    match ty {
+        ValType::I32 => Number::I32(integer(rng, 32) as u32 as i32),
+        ValType::I64 => Number::I64(integer(rng, 64) as i64),
+        ValType::F32 => Number::F32(float(rng, 23, 8) as u32),
+        ValType::F64 => Number::F64(float(rng, 52, 11)),
+        _ => unreachable!("numeric operators only take numbers"),
+    }
+}
+
+/// The bits of an integer of `width` bits.
+fn integer(rng: &mut Rng, width: u32) -> u64 {
+    let mask = u64::MAX >> (64 - width);
+    let min = 1 << (width - 1);
+    let bits = match rng.below(4) {
+        0 => rng.pick(&[0, 1, mask, min, min - 1, min + 1, min - 2, 2, 3]),
+        // A power of two, or one of its neighbours.
+        1 => {
+            let power = 1u64 << rng.below(u64::from(width));
+            power.wrapping_add(rng.pick(&[0, 1, u64::MAX]))
+        }
+        // A shift count around the width, or a small value.
+        2 => {
+            let width = u64::from(width);
+            let small = rng.below(3 * width);
+            let count = rng.pick(&[width - 1, width, width + 1, 2 * width, small]);
+            if rng.chance(25) {
+                count.wrapping_neg()
+            } else {
+                count
+            }
+        }
+        _ => rng.next(),
+    };
+    bits & mask
+}
+
+/// The bits of a float with `mantissa` bits of mantissa and `exponent` bits of exponent.
+fn float(rng: &mut Rng, mantissa: u32, exponent: u32) -> u64 {
+    let sign = if rng.chance(50) {
+        1 << (mantissa + exponent)
+    } else {
+        0
+    };
+    let bias = (1u64 << (exponent - 1)) - 1;
+    let max_exponent = (1u64 << exponent) - 1;
+    let quiet = 1u64 << (mantissa - 1);
+    let mantissa_mask = (1u64 << mantissa) - 1;
+    let power_of_two = |exponent: u64| (exponent + bias) << mantissa;
+    let magnitude = match rng.below(7) {
+        // Zero, infinity, one, a half, and the extremes of the normal and subnormal ranges.
+        0 => rng.pick(&[
+            0,
+            max_exponent << mantissa,
+            power_of_two(0),
+            (bias - 1) << mantissa,
+            (max_exponent << mantissa) - 1,
+            1 << mantissa,
+            mantissa_mask,
+            1,
+        ]),
+        // A NaN: canonical, quiet with a payload, or signaling.
+        1 => {
+            let payload = rng.next() & mantissa_mask;
+            let payload = match rng.below(3) {
+                0 => quiet,
+                1 => payload | quiet,
+                _ => (payload & !quiet).max(1),
+            };
+            (max_exponent << mantissa) | payload
+        }
+        // A subnormal.
+        2 => rng.next() & mantissa_mask,
+        // A bound of the range of an integer type, or one of its neighbours.
+        3 => {
+            let bound = power_of_two(rng.pick(&[31, 32, 63, 64]));
+            bound.wrapping_add(rng.pick(&[0, 1, u64::MAX]))
+        }
+        // A power of two, or one of its neighbours.
+        4 => {
+            let power = (rng.below(max_exponent - 1) + 1) << mantissa;
+            power.wrapping_add(rng.pick(&[0, 1, u64::MAX]))
+        }
+        // An integer or the half between two, to round.
+        5 => {
+            let value = rng.below(16);
+            let half = rng.chance(50);
+            if value == 0 && !half {
+                0
+            } else {
+                let scaled = value * 2 + u64::from(half);
+                let shift = 63 - scaled.leading_zeros();
+                let fraction = (scaled << (mantissa - shift)) & mantissa_mask;
+                (power_of_two(u64::from(shift)) - (1 << mantissa)) | fraction
+            }
+        }
+        _ => rng.next() & (mantissa_mask | (max_exponent << mantissa)),
+    };
+    sign | magnitude
+}
@@ -0,0 +1,509 @@
+//! Randomized checking of engines against a reference evaluator of the numeric operators.
+//!
+//! A [`ConformanceRunner`] generates [`Case`]s from a seed, each a module applying a single
+//! [`NumericOp`] to operands drawn from distributions heavy in corner cases, runs them on
+//! every [`EngineConfig`] it is given, and compares the [`Outcome`]s against those of a
+//! reference evaluator written from the specification alone. Each [`Divergence`] is reported
+//! on one line with the seed and index of its case, its operands and the text of its module,
+//! so that it can be reproduced from a log line alone, either with [`ConformanceRunner::case`]
+//! or by hand.
+//!
+//! Cases only depend on the seed, their index, and the operators of the runner, so a short
+//! run with a fixed seed is deterministic enough for a test suite, while long runs with
+//! fresh seeds explore further.
+//!
+//! ```
+//! use wasmer::conformance::{ConformanceRunner, EngineConfig};
+//! use wasmer::Store;
+//!
+//! let engines = [EngineConfig::new("default", Store::default())];
+//! let report = ConformanceRunner::new().run(42, 100, &engines);
+//! assert!(report.is_conformant(), "{}", report);
+//! ```
+
+mod generate;
+mod operators;
+mod reference;
+
+pub use self::operators::NumericOp;
+
+use crate::{ImportObject, Instance, Module, Store, TrapCode, Val, ValType};
+use std::fmt;
+
+/// An operand or result of a numeric operator.
+///
+/// Floats are kept as their bits, so that the payloads of NaNs and the signs of zeros are
+/// neither lost nor compared loosely.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Number {
+    /// An `i32`.
+    I32(i32),
+    /// An `i64`.
+    I64(i64),
+    /// The bits of an `f32`.
+    F32(u32),
+    /// The bits of an `f64`.
+    F64(u64),
+}
+
+impl Number {
+    /// The type of the number.
+    pub fn ty(self) -> ValType {
+        match self {
+            Self::I32(_) => ValType::I32,
+            Self::I64(_) => ValType::I64,
+            Self::F32(_) => ValType::F32,
+            Self::F64(_) => ValType::F64,
+        }
+    }
+
+    /// The bits of the number, zero-extended.
+    pub fn bits(self) -> u64 {
+        match self {
+            Self::I32(value) => u64::from(value as u32),
+            Self::I64(value) => value as u64,
+            Self::F32(bits) => u64::from(bits),
+            Self::F64(bits) => bits,
+        }
+    }
+
+    /// The number as a value to call a function with.
+    pub fn to_val(self) -> Val {
+        match self {
+            Self::I32(value) => Val::I32(value),
+            Self::I64(value) => Val::I64(value),
+            Self::F32(bits) => Val::F32(f32::from_bits(bits)),
+            Self::F64(bits) => Val::F64(f64::from_bits(bits)),
+        }
+    }
+
+    /// The number held by `val`, if it holds one.
+    pub fn from_val(val: &Val) -> Option<Self> {
+        match val {
+            Val::I32(value) => Some(Self::I32(*value)),
+            Val::I64(value) => Some(Self::I64(*value)),
+            Val::F32(value) => Some(Self::F32(value.to_bits())),
+            Val::F64(value) => Some(Self::F64(value.to_bits())),
+            _ => None,
+        }
+    }
+
+    fn type_name(self) -> &'static str {
+        match self {
+            Self::I32(_) => "i32",
+            Self::I64(_) => "i64",
+            Self::F32(_) => "f32",
+            Self::F64(_) => "f64",
+        }
+    }
+}
+
+/// Write the float with the given bits in the text format, exactly: as a hexadecimal
+/// significand and a binary exponent, or a NaN with its payload.
+fn write_float(f: &mut fmt::Formatter<'_>, bits: u64, mantissa: u32, exponent: u32) -> fmt::Result {
+    if bits >> (mantissa + exponent) != 0 {
+        f.write_str("-")?;
+    }
+    let biased = (bits >> mantissa) & ((1 << exponent) - 1);
+    let fraction = bits & ((1 << mantissa) - 1);
+    if biased == (1 << exponent) - 1 {
+        return match fraction {
+            0 => f.write_str("inf"),
+            payload => write!(f, "nan:0x{:x}", payload),
+        };
+    }
+    let (leading, exponent) = match biased {
+        0 if fraction == 0 => return f.write_str("0x0p+0"),
+        0 => (0, 2 - (1 << (exponent - 1)) as i64),
+        biased => (1, biased as i64 - ((1 << (exponent - 1)) - 1)),
+    };
+    write!(f, "0x{}", leading)?;
+    if fraction != 0 {
+        // Pad the fraction to whole hexadecimal digits.
+        let padding = (4 - mantissa % 4) % 4;
+        let digits = ((mantissa + padding) / 4) as usize;
+        let hex = format!("{:0width$x}", fraction << padding, width = digits);
+        write!(f, ".{}", hex.trim_end_matches('0'))?;
+    }
+    write!(f, "p{:+}", exponent)
+}
+
+/// Numbers are written as constant instructions of the text format.
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}.const ", self.type_name())?;
+        match *self {
+            Self::I32(value) => write!(f, "{}", value)?,
+            Self::I64(value) => write!(f, "{}", value)?,
+            Self::F32(bits) => write_float(f, u64::from(bits), 23, 8)?,
+            Self::F64(bits) => write_float(f, bits, 52, 11)?,
+        }
+        f.write_str(")")
+    }
+}
+
+/// What running a case resulted in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The operator returned a number.
+    Value(Number),
+    /// The operator trapped.
+    Trap(TrapCode),
+    /// The case could not be run: its module failed to compile or instantiate, or the call
+    /// failed other than by trapping.
+    Failed(String),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(number) => write!(f, "{}", number),
+            Self::Trap(code) => write!(f, "trap ({})", code),
+            Self::Failed(message) => write!(f, "failure ({})", message),
+        }
+    }
+}
+
+/// Where the operands of a case come from, as compilers take different paths for each.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Placement {
+    /// Every operand is a parameter of the function.
+    Params,
+    /// Every operand is a constant instruction, so that it may be an immediate.
+    Constants,
+    /// The last operand, such as a shift count or a divisor, is a constant instruction, and
+    /// the others are parameters.
+    LastConstant,
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Params => "params",
+            Self::Constants => "constants",
+            Self::LastConstant => "last-constant",
+        })
+    }
+}
+
+/// A module applying one operator to given operands, and returning its result.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Case {
+    /// The operator.
+    pub op: NumericOp,
+    /// The operands of the operator.
+    pub operands: Vec<Number>,
+    /// Where the operands come from.
+    pub placement: Placement,
+}
+
+impl Case {
+    /// Create a case applying `op` to `operands`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the operands are not of the types `op` takes.
+    pub fn new(op: NumericOp, operands: Vec<Number>, placement: Placement) -> Self {
+        let types = operands
+            .iter()
+            .map(|operand| operand.ty())
+            .collect::<Vec<_>>();
+        assert_eq!(types, op.params(), "the operands of {}", op.name());
+        Self {
+            op,
+            operands,
+            placement,
+        }
+    }
+
+    /// The number of operands passed as parameters, the first ones.
+    fn param_count(&self) -> usize {
+        match self.placement {
+            Placement::Params => self.operands.len(),
+            Placement::Constants => 0,
+            Placement::LastConstant => self.operands.len() - 1,
+        }
+    }
+
+    /// The values to call the function of the module with.
+    pub fn params(&self) -> Vec<Val> {
+        self.operands[..self.param_count()]
+            .iter()
+            .map(|operand| operand.to_val())
+            .collect()
+    }
+
+    /// The module of the case in the text format, on one line, exporting its function as
+    /// `run`.
+    pub fn wat(&self) -> String {
+        let param_count = self.param_count();
+        let mut wat = String::from(r#"(module (func (export "run")"#);
+        if param_count > 0 {
+            wat.push_str(" (param");
+            for operand in &self.operands[..param_count] {
+                wat.push(' ');
+                wat.push_str(operand.type_name());
+            }
+            wat.push(')');
+        }
+        let result = self.op.result().to_string().to_lowercase();
+        wat.push_str(&format!(" (result {}) ({}", result, self.op.name()));
+        for (index, operand) in self.operands.iter().enumerate() {
+            if index < param_count {
+                wat.push_str(&format!(" (local.get {})", index));
+            } else {
+                wat.push_str(&format!(" {}", operand));
+            }
+        }
+        wat.push_str(")))");
+        wat
+    }
+
+    /// The outcome of the case according to the reference evaluator.
+    pub fn expected(&self) -> Outcome {
+        reference::evaluate(self.op, &self.operands)
+    }
+}
+
+/// An engine to check, and how it is configured to behave where the specification allows
+/// several behaviors.
+#[derive(Clone)]
+pub struct EngineConfig {
+    name: String,
+    store: Store,
+    canonical_nans: bool,
+}
+
+impl EngineConfig {
+    /// Check the engine of `store`, reporting its divergences under `name`.
+    pub fn new(name: impl Into<String>, store: Store) -> Self {
+        Self {
+            name: name.into(),
+            store,
+            canonical_nans: false,
+        }
+    }
+
+    /// Declare that the engine canonicalizes NaNs, so that every NaN its arithmetic returns
+    /// must be the positive canonical one.
+    pub fn canonical_nans(mut self, canonical_nans: bool) -> Self {
+        self.canonical_nans = canonical_nans;
+        self
+    }
+
+    /// The name divergences of the engine are reported under.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Run `case` on the engine.
+    pub fn execute(&self, case: &Case) -> Outcome {
+        let module = match Module::new(&self.store, case.wat()) {
+            Ok(module) => module,
+            Err(error) => return Outcome::Failed(format!("compilation: {}", error)),
+        };
+        let instance = match Instance::new(&module, &ImportObject::new()) {
+            Ok(instance) => instance,
+            Err(error) => return Outcome::Failed(format!("instantiation: {}", error)),
+        };
+        let run = match instance.lookup_function("run") {
+            Some(run) => run,
+            None => return Outcome::Failed("no `run` export".to_string()),
+        };
+        match run.call(&case.params()) {
+            Ok(results) => match results.first().and_then(Number::from_val) {
+                Some(number) if results.len() == 1 => Outcome::Value(number),
+                _ => Outcome::Failed(format!("unexpected results {:?}", results)),
+            },
+            Err(error) => {
+                let message = error.message();
+                error
+                    .to_trap()
+                    .map_or(Outcome::Failed(message), Outcome::Trap)
+            }
+        }
+    }
+}
+
+/// A case whose outcome on an engine is not one the specification allows.
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    /// The seed of the run that generated the case and its index in the run, if it was
+    /// generated.
+    pub origin: Option<(u64, u64)>,
+    /// The name of the engine.
+    pub engine: String,
+    /// The case.
+    pub case: Case,
+    /// The outcome according to the reference evaluator, with NaNs canonicalized if the
+    /// engine canonicalizes them. Where the specification allows several NaNs, this is one of
+    /// them.
+    pub expected: Outcome,
+    /// The outcome on the engine.
+    pub actual: Outcome,
+}
+
+/// Divergences are written on one line holding all that is needed to reproduce them.
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.origin {
+            Some((seed, index)) => write!(f, "seed={} case={}", seed, index)?,
+            None => f.write_str("fixed case")?,
+        }
+        write!(
+            f,
+            " engine={} op={} placement={} operands=[",
+            self.engine,
+            self.case.op.name(),
+            self.case.placement
+        )?;
+        for (index, operand) in self.case.operands.iter().enumerate() {
+            if index > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", operand)?;
+        }
+        write!(
+            f,
+            "] expected={} actual={} wat={}",
+            self.expected,
+            self.actual,
+            self.case.wat()
+        )
+    }
+}
+
+/// The result of a run of a [`ConformanceRunner`].
+#[derive(Clone, Debug)]
+pub struct ConformanceReport {
+    /// The seed of the run.
+    pub seed: u64,
+    /// The cases run, on every engine.
+    pub cases: u64,
+    /// The divergences found, in the order of their cases.
+    pub divergences: Vec<Divergence>,
+}
+
+impl ConformanceReport {
+    /// Whether every engine agreed with the reference evaluator on every case.
+    pub fn is_conformant(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "seed={}: {} cases, {} divergences",
+            self.seed,
+            self.cases,
+            self.divergences.len()
+        )?;
+        for divergence in &self.divergences {
+            write!(f, "\n{}", divergence)?;
+        }
+        Ok(())
+    }
+}
+
+/// Generates conformance cases from seeds and checks engines against them.
+#[derive(Clone, Debug)]
+pub struct ConformanceRunner {
+    ops: Vec<NumericOp>,
+    stop_after: usize,
+}
+
+impl Default for ConformanceRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConformanceRunner {
+    /// Create a runner exercising every numeric operator, stopping a run after 16
+    /// divergences.
+    pub fn new() -> Self {
+        Self {
+            ops: NumericOp::ALL.to_vec(),
+            stop_after: 16,
+        }
+    }
+
+    /// Only exercise `ops`.
+    ///
+    /// The operators are part of what cases are generated from: a case is only reproduced
+    /// from its seed and index by a runner exercising the same operators.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ops` is empty.
+    pub fn with_operators(mut self, ops: &[NumericOp]) -> Self {
+        assert!(!ops.is_empty(), "a runner needs operators to exercise");
+        self.ops = ops.to_vec();
+        self
+    }
+
+    /// Stop runs once `divergences` were found, as systematic divergences otherwise make
+    /// long runs report the same bug over and over.
+    pub fn stop_after(mut self, divergences: usize) -> Self {
+        self.stop_after = divergences;
+        self
+    }
+
+    /// The case at `index` of runs seeded with `seed`.
+    pub fn case(&self, seed: u64, index: u64) -> Case {
+        generate::case(seed, index, &self.ops)
+    }
+
+    /// Run the first `iterations` cases seeded with `seed` on each of `engines`.
+    pub fn run(&self, seed: u64, iterations: u64, engines: &[EngineConfig]) -> ConformanceReport {
+        let mut report = ConformanceReport {
+            seed,
+            cases: 0,
+            divergences: Vec::new(),
+        };
+        for index in 0..iterations {
+            if report.divergences.len() >= self.stop_after {
+                break;
+            }
+            let case = self.case(seed, index);
+            for mut divergence in self.check(&case, engines) {
+                divergence.origin = Some((seed, index));
+                report.divergences.push(divergence);
+            }
+            report.cases += 1;
+        }
+        report
+    }
+
+    /// Run `case` on each of `engines`, returning the divergences found.
+    pub fn check(&self, case: &Case, engines: &[EngineConfig]) -> Vec<Divergence> {
+        let reference = case.expected();
+        engines
+            .iter()
+            .filter_map(|engine| {
+                let expected = if engine.canonical_nans {
+                    reference::canonicalize(case.op, &reference)
+                } else {
+                    reference.clone()
+                };
+                let actual = engine.execute(case);
+                let conforms = reference::conforms(
+                    case.op,
+                    &case.operands,
+                    &expected,
+                    &actual,
+                    engine.canonical_nans,
+                );
+                (!conforms).then(|| Divergence {
+                    origin: None,
+                    engine: engine.name.clone(),
+                    case: case.clone(),
+                    expected,
+                    actual,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,380 @@
+//! A reference evaluator of the numeric operators, written from the specification alone so
+//! that it shares no code, nor bugs, with the compilers.
+//!
+//! Floats are computed with the IEEE 754 arithmetic of Rust, which rounds to nearest, ties
+//! to even, and keeps subnormals, as WebAssembly requires. The operators whose WebAssembly
+//! semantics differ from those of Rust (`min`, `max`, `nearest` and the trapping
+//! truncations) are spelt out. Which NaN an operator returns is nondeterministic in
+//! WebAssembly, so results are checked against the rules of the specification by
+//! [`conforms`] rather than compared bit for bit.
+
+use super::{Number, NumericOp, Outcome};
+use crate::TrapCode;
+
+/// The canonical NaNs, whose payload only has the quiet bit set.
+const CANONICAL_NAN32: u32 = 0x7fc0_0000;
+const CANONICAL_NAN64: u64 = 0x7ff8_0000_0000_0000;
+
+/// Evaluate `op` on `operands`, which are of the types it takes.
+pub(super) fn evaluate(op: NumericOp, operands: &[Number]) -> Outcome {
+    use Number::{F32, F64, I32, I64};
+    use NumericOp::*;
+
+    let i32_at = |index: usize| match operands[index] {
+        I32(value) => value,
+        operand => panic!("{} is not an i32", operand),
+    };
+    let i64_at = |index: usize| match operands[index] {
+        I64(value) => value,
+        operand => panic!("{} is not an i64", operand),
+    };
+    let f32_at = |index: usize| match operands[index] {
+        F32(bits) => f32::from_bits(bits),
+        operand => panic!("{} is not an f32", operand),
+    };
+    let f64_at = |index: usize| match operands[index] {
+        F64(bits) => f64::from_bits(bits),
+        operand => panic!("{} is not an f64", operand),
+    };
+    let value = |number| Outcome::Value(number);
+    let boolean = |condition: bool| Outcome::Value(I32(condition as i32));
+    let int32 = |value: i32| Outcome::Value(I32(value));
+    let int64 = |value: i64| Outcome::Value(I64(value));
+    let float32 = |value: f32| Outcome::Value(F32(value.to_bits()));
+    let float64 = |value: f64| Outcome::Value(F64(value.to_bits()));
+    let trap = Outcome::Trap;
+
+    match op {
+        I32Eqz => boolean(i32_at(0) == 0),
+        I32Eq => boolean(i32_at(0) == i32_at(1)),
+        I32Ne => boolean(i32_at(0) != i32_at(1)),
+        I32LtS => boolean(i32_at(0) < i32_at(1)),
+        I32LtU => boolean((i32_at(0) as u32) < i32_at(1) as u32),
+        I32GtS => boolean(i32_at(0) > i32_at(1)),
+        I32GtU => boolean(i32_at(0) as u32 > i32_at(1) as u32),
+        I32LeS => boolean(i32_at(0) <= i32_at(1)),
+        I32LeU => boolean(i32_at(0) as u32 <= i32_at(1) as u32),
+        I32GeS => boolean(i32_at(0) >= i32_at(1)),
+        I32GeU => boolean(i32_at(0) as u32 >= i32_at(1) as u32),
+        I64Eqz => boolean(i64_at(0) == 0),
+        I64Eq => boolean(i64_at(0) == i64_at(1)),
+        I64Ne => boolean(i64_at(0) != i64_at(1)),
+        I64LtS => boolean(i64_at(0) < i64_at(1)),
+        I64LtU => boolean((i64_at(0) as u64) < i64_at(1) as u64),
+        I64GtS => boolean(i64_at(0) > i64_at(1)),
+        I64GtU => boolean(i64_at(0) as u64 > i64_at(1) as u64),
+        I64LeS => boolean(i64_at(0) <= i64_at(1)),
+        I64LeU => boolean(i64_at(0) as u64 <= i64_at(1) as u64),
+        I64GeS => boolean(i64_at(0) >= i64_at(1)),
+        I64GeU => boolean(i64_at(0) as u64 >= i64_at(1) as u64),
+        F32Eq => boolean(f32_at(0) == f32_at(1)),
+        F32Ne => boolean(f32_at(0) != f32_at(1)),
+        F32Lt => boolean(f32_at(0) < f32_at(1)),
+        F32Gt => boolean(f32_at(0) > f32_at(1)),
+        F32Le => boolean(f32_at(0) <= f32_at(1)),
+        F32Ge => boolean(f32_at(0) >= f32_at(1)),
+        F64Eq => boolean(f64_at(0) == f64_at(1)),
+        F64Ne => boolean(f64_at(0) != f64_at(1)),
+        F64Lt => boolean(f64_at(0) < f64_at(1)),
+        F64Gt => boolean(f64_at(0) > f64_at(1)),
+        F64Le => boolean(f64_at(0) <= f64_at(1)),
+        F64Ge => boolean(f64_at(0) >= f64_at(1)),
+        I32Clz => int32(i32_at(0).leading_zeros() as i32),
+        I32Ctz => int32(i32_at(0).trailing_zeros() as i32),
+        I32Popcnt => int32(i32_at(0).count_ones() as i32),
+        I32Add => int32(i32_at(0).wrapping_add(i32_at(1))),
+        I32Sub => int32(i32_at(0).wrapping_sub(i32_at(1))),
+        I32Mul => int32(i32_at(0).wrapping_mul(i32_at(1))),
+        I32DivS => match (i32_at(0), i32_at(1)) {
+            (_, 0) => trap(TrapCode::IntegerDivisionByZero),
+            (i32::MIN, -1) => trap(TrapCode::IntegerOverflow),
+            (a, b) => int32(a / b),
+        },
+        I32DivU => match (i32_at(0) as u32, i32_at(1) as u32) {
+            (_, 0) => trap(TrapCode::IntegerDivisionByZero),
+            (a, b) => int32((a / b) as i32),
+        },
+        I32RemS => match (i32_at(0), i32_at(1)) {
+            (_, 0) => trap(TrapCode::IntegerDivisionByZero),
+            // The quotient overflows, but not the remainder.
+            (a, b) => int32(a.wrapping_rem(b)),
+        },
+        I32RemU => match (i32_at(0) as u32, i32_at(1) as u32) {
+            (_, 0) => trap(TrapCode::IntegerDivisionByZero),
+            (a, b) => int32((a % b) as i32),
+        },
+        I32And => int32(i32_at(0) & i32_at(1)),
+        I32Or => int32(i32_at(0) | i32_at(1)),
+        I32Xor => int32(i32_at(0) ^ i32_at(1)),
+        // Shift and rotation counts are taken modulo the width.
+        I32Shl => int32(i32_at(0) << (i32_at(1) & 31)),
+        I32ShrS => int32(i32_at(0) >> (i32_at(1) & 31)),
+        I32ShrU => int32((i32_at(0) as u32 >> (i32_at(1) & 31)) as i32),
+        I32Rotl => int32(i32_at(0).rotate_left(i32_at(1) as u32 % 32)),
+        I32Rotr => int32(i32_at(0).rotate_right(i32_at(1) as u32 % 32)),
+        I64Clz => int64(i64::from(i64_at(0).leading_zeros())),
+        I64Ctz => int64(i64::from(i64_at(0).trailing_zeros())),
+        I64Popcnt => int64(i64::from(i64_at(0).count_ones())),
+        I64Add => int64(i64_at(0).wrapping_add(i64_at(1))),
+        I64Sub => int64(i64_at(0).wrapping_sub(i64_at(1))),
+        I64Mul => int64(i64_at(0).wrapping_mul(i64_at(1))),
+        I64DivS => match (i64_at(0), i64_at(1)) {
+            (_, 0) => trap(TrapCode::IntegerDivisionByZero),
+            (i64::MIN, -1) => trap(TrapCode::IntegerOverflow),
+            (a, b) => int64(a / b),
+        },
+        I64DivU => match (i64_at(0) as u64, i64_at(1) as u64) {
+            (_, 0) => trap(TrapCode::IntegerDivisionByZero),
+            (a, b) => int64((a / b) as i64),
+        },
+        I64RemS => match (i64_at(0), i64_at(1)) {
+            (_, 0) => trap(TrapCode::IntegerDivisionByZero),
+            (a, b) => int64(a.wrapping_rem(b)),
+        },
+        I64RemU => match (i64_at(0) as u64, i64_at(1) as u64) {
+            (_, 0) => trap(TrapCode::IntegerDivisionByZero),
+            (a, b) => int64((a % b) as i64),
+        },
+        I64And => int64(i64_at(0) & i64_at(1)),
+        I64Or => int64(i64_at(0) | i64_at(1)),
+        I64Xor => int64(i64_at(0) ^ i64_at(1)),
+        I64Shl => int64(i64_at(0) << (i64_at(1) & 63)),
+        I64ShrS => int64(i64_at(0) >> (i64_at(1) & 63)),
+        I64ShrU => int64((i64_at(0) as u64 >> (i64_at(1) & 63)) as i64),
+        I64Rotl => int64(i64_at(0).rotate_left((i64_at(1) as u64 % 64) as u32)),
+        I64Rotr => int64(i64_at(0).rotate_right((i64_at(1) as u64 % 64) as u32)),
+        F32Abs => value(F32(operands[0].bits() as u32 & !(1 << 31))),
+        F32Neg => value(F32(operands[0].bits() as u32 ^ (1 << 31))),
+        F32Ceil => float32(f32_at(0).ceil()),
+        F32Floor => float32(f32_at(0).floor()),
+        F32Trunc => float32(f32_at(0).trunc()),
+        F32Nearest => float32(f32_nearest(f32_at(0))),
+        F32Sqrt => float32(f32_at(0).sqrt()),
+        F32Add => float32(f32_at(0) + f32_at(1)),
+        F32Sub => float32(f32_at(0) - f32_at(1)),
+        F32Mul => float32(f32_at(0) * f32_at(1)),
+        F32Div => float32(f32_at(0) / f32_at(1)),
+        F32Min => float32(f32_min(f32_at(0), f32_at(1))),
+        F32Max => float32(f32_max(f32_at(0), f32_at(1))),
+        F32Copysign => {
+            let sign = 1 << 31;
+            let (a, b) = (operands[0].bits() as u32, operands[1].bits() as u32);
+            value(F32((a & !sign) | (b & sign)))
+        }
+        F64Abs => value(F64(operands[0].bits() & !(1 << 63))),
+        F64Neg => value(F64(operands[0].bits() ^ (1 << 63))),
+        F64Ceil => float64(f64_at(0).ceil()),
+        F64Floor => float64(f64_at(0).floor()),
+        F64Trunc => float64(f64_at(0).trunc()),
+        F64Nearest => float64(f64_nearest(f64_at(0))),
+        F64Sqrt => float64(f64_at(0).sqrt()),
+        F64Add => float64(f64_at(0) + f64_at(1)),
+        F64Sub => float64(f64_at(0) - f64_at(1)),
+        F64Mul => float64(f64_at(0) * f64_at(1)),
+        F64Div => float64(f64_at(0) / f64_at(1)),
+        F64Min => float64(f64_min(f64_at(0), f64_at(1))),
+        F64Max => float64(f64_max(f64_at(0), f64_at(1))),
+        F64Copysign => {
+            let sign = 1 << 63;
+            let (a, b) = (operands[0].bits(), operands[1].bits());
+            value(F64((a & !sign) | (b & sign)))
+        }
+        I32WrapI64 => int32(i64_at(0) as i32),
+        I32TruncF32S => truncate(f64::from(f32_at(0)), -2f64.powi(31), 2f64.powi(31))
+            .map_or_else(trap, |t| int32(t as i32)),
+        I32TruncF32U => truncate(f64::from(f32_at(0)), 0.0, 2f64.powi(32))
+            .map_or_else(trap, |t| int32(t as u32 as i32)),
+        I32TruncF64S => truncate(f64_at(0), -2f64.powi(31), 2f64.powi(31))
+            .map_or_else(trap, |t| int32(t as i32)),
+        I32TruncF64U => {
+            truncate(f64_at(0), 0.0, 2f64.powi(32)).map_or_else(trap, |t| int32(t as u32 as i32))
+        }
+        I64ExtendI32S => int64(i64::from(i32_at(0))),
+        I64ExtendI32U => int64(i64::from(i32_at(0) as u32)),
+        I64TruncF32S => truncate(f64::from(f32_at(0)), -2f64.powi(63), 2f64.powi(63))
+            .map_or_else(trap, |t| int64(t as i64)),
+        I64TruncF32U => truncate(f64::from(f32_at(0)), 0.0, 2f64.powi(64))
+            .map_or_else(trap, |t| int64(t as u64 as i64)),
+        I64TruncF64S => truncate(f64_at(0), -2f64.powi(63), 2f64.powi(63))
+            .map_or_else(trap, |t| int64(t as i64)),
+        I64TruncF64U => {
+            truncate(f64_at(0), 0.0, 2f64.powi(64)).map_or_else(trap, |t| int64(t as u64 as i64))
+        }
+        // Casts between integers and floats round to nearest, ties to even, like WebAssembly.
+        F32ConvertI32S => float32(i32_at(0) as f32),
+        F32ConvertI32U => float32(i32_at(0) as u32 as f32),
+        F32ConvertI64S => float32(i64_at(0) as f32),
+        F32ConvertI64U => float32(i64_at(0) as u64 as f32),
+        F32DemoteF64 => float32(f64_at(0) as f32),
+        F64ConvertI32S => float64(f64::from(i32_at(0))),
+        F64ConvertI32U => float64(f64::from(i32_at(0) as u32)),
+        F64ConvertI64S => float64(i64_at(0) as f64),
+        F64ConvertI64U => float64(i64_at(0) as u64 as f64),
+        F64PromoteF32 => float64(f64::from(f32_at(0))),
+        I32ReinterpretF32 => int32(operands[0].bits() as u32 as i32),
+        I64ReinterpretF64 => int64(operands[0].bits() as i64),
+        F32ReinterpretI32 => value(F32(i32_at(0) as u32)),
+        F64ReinterpretI64 => value(F64(i64_at(0) as u64)),
+        I32Extend8S => int32(i32::from(i32_at(0) as i8)),
+        I32Extend16S => int32(i32::from(i32_at(0) as i16)),
+        I64Extend8S => int64(i64::from(i64_at(0) as i8)),
+        I64Extend16S => int64(i64::from(i64_at(0) as i16)),
+        I64Extend32S => int64(i64::from(i64_at(0) as i32)),
+        // Casts from floats to integers saturate, and turn NaNs into zero, like WebAssembly.
+        I32TruncSatF32S => int32(f32_at(0) as i32),
+        I32TruncSatF32U => int32(f32_at(0) as u32 as i32),
+        I32TruncSatF64S => int32(f64_at(0) as i32),
+        I32TruncSatF64U => int32(f64_at(0) as u32 as i32),
+        I64TruncSatF32S => int64(f32_at(0) as i64),
+        I64TruncSatF32U => int64(f32_at(0) as u64 as i64),
+        I64TruncSatF64S => int64(f64_at(0) as i64),
+        I64TruncSatF64U => int64(f64_at(0) as u64 as i64),
+    }
+}
+
+/// Truncate `value` towards zero, trapping unless the result is within `min..max`.
+fn truncate(value: f64, min: f64, max: f64) -> Result<f64, TrapCode> {
+    if value.is_nan() {
+        return Err(TrapCode::BadConversionToInteger);
+    }
+    let truncated = value.trunc();
+    if truncated < min || truncated >= max {
+        return Err(TrapCode::IntegerOverflow);
+    }
+    Ok(truncated)
+}
+
+macro_rules! float_operators {
+    ($float:ty, $min:ident, $max:ident, $nearest:ident) => {
+        /// `min`, which returns a NaN if either operand is one, and orders -0 below +0.
+        fn $min(a: $float, b: $float) -> $float {
+            if a.is_nan() || b.is_nan() {
+                a + b
+            } else if a == b {
+                if a.is_sign_negative() {
+                    a
+                } else {
+                    b
+                }
+            } else if a < b {
+                a
+            } else {
+                b
+            }
+        }
+
+        /// `max`, which returns a NaN if either operand is one, and orders -0 below +0.
+        fn $max(a: $float, b: $float) -> $float {
+            if a.is_nan() || b.is_nan() {
+                a + b
+            } else if a == b {
+                if a.is_sign_positive() {
+                    a
+                } else {
+                    b
+                }
+            } else if a > b {
+                a
+            } else {
+                b
+            }
+        }
+
+        /// `nearest`, which rounds to the nearest integer, ties to even, keeping the sign.
+        fn $nearest(value: $float) -> $float {
+            if !value.is_finite() {
+                return value + value;
+            }
+            let truncated = value.trunc();
+            // Exact, as the fraction fits in the mantissa of `value`.
+            let fraction = (value - truncated).abs();
+            let rounded = if fraction > 0.5 || (fraction == 0.5 && truncated % 2.0 != 0.0) {
+                truncated + value.signum()
+            } else {
+                truncated
+            };
+            rounded.copysign(value)
+        }
+    };
+}
+
+float_operators!(f32, f32_min, f32_max, f32_nearest);
+float_operators!(f64, f64_min, f64_max, f64_nearest);
+
+/// Whether `actual` is an outcome of `op` on `operands` the specification allows, `expected`
+/// being the one the reference evaluator computed.
+///
+/// The specification lets an operator returning a NaN return any of a set of them: a
+/// canonical NaN if none of its operands is a NaN other than a canonical one, any NaN with
+/// the quiet bit set otherwise. Only the operators that move the bits of floats around
+/// return a NaN determined bit for bit. An engine canonicalizing NaNs is held to the
+/// outcome [`canonicalize`] computes instead, bit for bit.
+pub(super) fn conforms(
+    op: NumericOp,
+    operands: &[Number],
+    expected: &Outcome,
+    actual: &Outcome,
+    canonical_nans: bool,
+) -> bool {
+    let (expected, actual) = match (expected, actual) {
+        (Outcome::Value(expected), Outcome::Value(actual)) => (*expected, *actual),
+        (expected, actual) => return expected == actual,
+    };
+    if canonical_nans || !expected.is_nan() || op.is_bitwise() {
+        return actual == expected;
+    }
+    if expected.ty() != actual.ty() || !actual.is_nan() {
+        return false;
+    }
+    if operands
+        .iter()
+        .all(|operand| !operand.is_nan() || operand.is_canonical_nan())
+    {
+        actual.is_canonical_nan()
+    } else {
+        actual.is_arithmetic_nan()
+    }
+}
+
+/// The outcome of `op` on an engine canonicalizing NaNs, `expected` being the one the
+/// reference evaluator computed: the NaNs arithmetic returns are the positive canonical NaN,
+/// while those only moved around keep their bits.
+pub(super) fn canonicalize(op: NumericOp, expected: &Outcome) -> Outcome {
+    match *expected {
+        Outcome::Value(number) if number.is_nan() && !op.is_bitwise() => {
+            Outcome::Value(match number {
+                Number::F32(_) => Number::F32(CANONICAL_NAN32),
+                _ => Number::F64(CANONICAL_NAN64),
+            })
+        }
+        _ => expected.clone(),
+    }
+}
+
+impl Number {
+    fn is_nan(self) -> bool {
+        match self {
+            Self::F32(bits) => f32::from_bits(bits).is_nan(),
+            Self::F64(bits) => f64::from_bits(bits).is_nan(),
+            Self::I32(_) | Self::I64(_) => false,
+        }
+    }
+
+    /// Whether the number is a NaN whose payload only has the quiet bit set, of either sign.
+    fn is_canonical_nan(self) -> bool {
+        match self {
+            Self::F32(bits) => bits & !(1 << 31) == CANONICAL_NAN32,
+            Self::F64(bits) => bits & !(1 << 63) == CANONICAL_NAN64,
+            Self::I32(_) | Self::I64(_) => false,
+        }
+    }
+
+    /// Whether the number is a NaN with the quiet bit set.
+    fn is_arithmetic_nan(self) -> bool {
+        match self {
+            Self::F32(bits) => bits & CANONICAL_NAN32 == CANONICAL_NAN32,
+            Self::F64(bits) => bits & CANONICAL_NAN64 == CANONICAL_NAN64,
+            Self::I32(_) | Self::I64(_) => false,
+        }
+    }
+}
@@ -1,14 +1,29 @@
 use crate::sys::{ExportError, Instance};
 use thiserror::Error;
+use wasmer_types::{ErrorCode, HasErrorCode};
 
 /// An error while initializing the user supplied host env with the `WasmerEnv` trait.
 #[derive(Error, Debug)]
-#[error("Host env initialization error")]
 pub enum HostEnvInitError {
     /// An error occurred when accessing an export
+    #[error("Host env initialization error [{}]", ErrorCode::HostEnvExport)]
     Export(ExportError),
     /// Incorrect gas metering config
+    #[error("Host env initialization error [{}]", ErrorCode::HostEnvGasMetering)]
     IncorrectGasMeteringConfig,
+    /// The environment expects a memory export the instance does not have
+    #[error("Host env initialization error [{}]", ErrorCode::HostEnvMissingMemory)]
+    MissingMemory(String),
+}
+
+impl HasErrorCode for HostEnvInitError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Export(_) => ErrorCode::HostEnvExport,
+            Self::IncorrectGasMeteringConfig => ErrorCode::HostEnvGasMetering,
+            Self::MissingMemory(_) => ErrorCode::HostEnvMissingMemory,
+        }
+    }
 }
 
 impl From<ExportError> for HostEnvInitError {
@@ -29,7 +44,7 @@ impl From<ExportError> for HostEnvInitError {
 ///
 /// This trait may also be implemented manually:
 /// ```
-/// # use wasmer::{WasmerEnv, LazyInit, Memory, Instance, HostEnvInitError};
+/// # use wasmer::{Exportable, WasmerEnv, LazyInit, Memory, Instance, HostEnvInitError};
 /// #[derive(Clone)]
 /// pub struct MyEnv {
 ///    memory: LazyInit<Memory>,
@@ -37,14 +52,21 @@ impl From<ExportError> for HostEnvInitError {
 ///
 /// impl WasmerEnv for MyEnv {
 ///     fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
-///         todo!()
+///         let mut memory = instance
+///             .lookup_memory("memory")
+///             .ok_or_else(|| HostEnvInitError::MissingMemory("memory".into()))?;
+///         memory.into_weak_instance_ref();
+///         self.memory.initialize(memory);
+///         Ok(())
 ///     }
 /// }
 /// ```
 ///
-/// When implementing the trait manually, it's important to get a "weak" export to
-/// prevent a cyclic reference leaking memory. You can access a "weak" export with
-/// a method like `get_with_generics_weak`.
+/// When implementing the trait manually, it's important to make the exports kept "weak"
+/// with [`Exportable::into_weak_instance_ref`], to prevent a cyclic reference leaking the
+/// instance and the environment.
+///
+/// [`Exportable::into_weak_instance_ref`]: crate::Exportable::into_weak_instance_ref
 pub trait WasmerEnv: Clone + Send + Sync {
     /// The function that Wasmer will call on your type to let it finish
     /// setting up the environment with data from the `Instance`.
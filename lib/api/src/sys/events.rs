@@ -0,0 +1,311 @@
+use crate::sys::env::{HostEnvInitError, LazyInit, WasmerEnv};
+use crate::sys::externals::{Function, Memory};
+use crate::sys::instance::Instance;
+use crate::sys::store::Store;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use wasmer_types::{ErrorCode, HasErrorCode};
+
+/// An event emitted by WebAssembly code through an [`EventSink`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// The topic of the event, accepted by the [`EventSchema`] of the sink.
+    pub topic: String,
+    /// The payload of the event.
+    pub payload: Vec<u8>,
+}
+
+/// The topics an [`EventSink`] accepts.
+///
+/// A topic is accepted if it is no longer than the maximum length and is either one of the
+/// allowed topics or starts with one of the allowed prefixes. A schema allowing neither
+/// topics nor prefixes accepts every topic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventSchema {
+    max_topic_len: usize,
+    topics: BTreeSet<String>,
+    prefixes: Vec<String>,
+}
+
+impl EventSchema {
+    /// Create a schema accepting every topic of at most `max_topic_len` bytes.
+    pub fn new(max_topic_len: usize) -> Self {
+        Self {
+            max_topic_len,
+            topics: BTreeSet::new(),
+            prefixes: Vec::new(),
+        }
+    }
+
+    /// Allow the topic `topic`.
+    pub fn with_topic(mut self, topic: &str) -> Self {
+        self.topics.insert(topic.to_string());
+        self
+    }
+
+    /// Allow the topics starting with `prefix`.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefixes.push(prefix.to_string());
+        self
+    }
+
+    fn allows(&self, topic: &str) -> bool {
+        (self.topics.is_empty() && self.prefixes.is_empty())
+            || self.topics.contains(topic)
+            || self.prefixes.iter().any(|prefix| topic.starts_with(prefix))
+    }
+}
+
+/// Limits on the events held by an [`EventSink`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventCaps {
+    /// The maximum size of the payload of an event, in bytes.
+    pub max_payload_len: usize,
+    /// The maximum number of events held by the sink until [`EventSink::take_events`].
+    pub max_events: usize,
+}
+
+impl Default for EventCaps {
+    fn default() -> Self {
+        Self {
+            max_payload_len: 64 * 1024,
+            max_events: 1024,
+        }
+    }
+}
+
+/// The rule of the [`EventSchema`] or the [`EventCaps`] violated by a rejected event.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EventError {
+    /// A buffer of the event is not within the memory of the instance.
+    #[error(
+        "event buffer 0x{ptr:x}+0x{len:x} is out of the bounds of the memory [{}]",
+        ErrorCode::EventOutOfBounds
+    )]
+    OutOfBounds {
+        /// The guest pointer to the buffer.
+        ptr: u32,
+        /// The length of the buffer.
+        len: u32,
+    },
+    /// The topic is longer than the schema allows.
+    #[error(
+        "event topic is {len} bytes long, the maximum is {max} [{}]",
+        ErrorCode::EventTopicTooLong
+    )]
+    TopicTooLong {
+        /// The length of the topic.
+        len: usize,
+        /// The maximum length allowed by the schema.
+        max: usize,
+    },
+    /// The topic is not valid UTF-8.
+    #[error("event topic is not valid UTF-8 [{}]", ErrorCode::EventTopicNotUtf8)]
+    TopicNotUtf8,
+    /// The topic is neither allowed nor has an allowed prefix.
+    #[error(
+        "event topic '{0}' is not allowed by the schema [{}]",
+        ErrorCode::EventTopicNotAllowed
+    )]
+    TopicNotAllowed(String),
+    /// The payload is larger than the caps allow.
+    #[error(
+        "event payload is {len} bytes long, the maximum is {max} [{}]",
+        ErrorCode::EventPayloadTooLarge
+    )]
+    PayloadTooLarge {
+        /// The length of the payload.
+        len: usize,
+        /// The maximum length allowed by the caps.
+        max: usize,
+    },
+    /// The sink already holds as many events as the caps allow.
+    #[error("too many events, the maximum is {max} [{}]", ErrorCode::EventTooMany)]
+    TooManyEvents {
+        /// The maximum number of events allowed by the caps.
+        max: usize,
+    },
+}
+
+impl HasErrorCode for EventError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::OutOfBounds { .. } => ErrorCode::EventOutOfBounds,
+            Self::TopicTooLong { .. } => ErrorCode::EventTopicTooLong,
+            Self::TopicNotUtf8 => ErrorCode::EventTopicNotUtf8,
+            Self::TopicNotAllowed(_) => ErrorCode::EventTopicNotAllowed,
+            Self::PayloadTooLarge { .. } => ErrorCode::EventPayloadTooLarge,
+            Self::TooManyEvents { .. } => ErrorCode::EventTooMany,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct EventLog {
+    events: Mutex<Vec<Event>>,
+    rejected: AtomicU64,
+}
+
+/// Host side of the conventional `emit_event(topic_ptr, topic_len, payload_ptr, payload_len)`
+/// import, validating the events emitted by WebAssembly code and recording them in order.
+///
+/// The events of every instance importing [`EventSink::function`] are appended to a single
+/// log, in the order they are emitted, including when they are emitted by nested calls.
+/// Invalid events make the import fail with a [`RuntimeError`] wrapping the [`EventError`],
+/// unless the sink is [lenient](EventSink::lenient).
+///
+/// [`RuntimeError`]: crate::RuntimeError
+///
+/// ```
+/// # use wasmer::{imports, EventCaps, EventSchema, EventSink, Instance, Module, Store};
+/// # fn main() -> anyhow::Result<()> {
+/// let store = Store::default();
+/// let module = Module::new(&store, r#"
+///     (module
+///         (import "env" "emit_event" (func $emit (param i32 i32 i32 i32)))
+///         (memory (export "memory") 1)
+///         (data (i32.const 0) "transfer42")
+///         (func (export "run")
+///             (call $emit (i32.const 0) (i32.const 8) (i32.const 8) (i32.const 2))))
+/// "#)?;
+/// let sink = EventSink::new(EventSchema::new(32).with_topic("transfer"), EventCaps::default());
+/// let instance = Instance::new(&module, &imports! {
+///     "env" => { "emit_event" => sink.function(&store) },
+/// })?;
+/// instance.lookup_function("run").unwrap().call(&[])?;
+/// let events = sink.take_events();
+/// assert_eq!(events[0].topic, "transfer");
+/// assert_eq!(events[0].payload, b"42");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct EventSink {
+    schema: Arc<EventSchema>,
+    caps: EventCaps,
+    lenient: bool,
+    log: Arc<EventLog>,
+}
+
+impl EventSink {
+    /// Create a sink accepting the events allowed by `schema` and `caps`.
+    pub fn new(schema: EventSchema, caps: EventCaps) -> Self {
+        Self {
+            schema: Arc::new(schema),
+            caps,
+            lenient: false,
+            log: Arc::default(),
+        }
+    }
+
+    /// Drop invalid events instead of failing, counting them in [`EventSink::rejected`].
+    pub fn lenient(mut self) -> Self {
+        self.lenient = true;
+        self
+    }
+
+    /// Create the host function to provide as the `emit_event` import.
+    ///
+    /// The instances importing it must export their memory as `memory`.
+    pub fn function(&self, store: &Store) -> Function {
+        let env = EmitEnv {
+            sink: self.clone(),
+            memory: LazyInit::new(),
+        };
+        Function::new_native_with_env(store, env, emit_event)
+    }
+
+    /// Take the events accepted so far, in the order they were emitted.
+    pub fn take_events(&self) -> Vec<Event> {
+        std::mem::take(&mut *self.log.events.lock().unwrap())
+    }
+
+    /// Return the number of events dropped by a lenient sink.
+    pub fn rejected(&self) -> u64 {
+        self.log.rejected.load(Ordering::SeqCst)
+    }
+
+    fn emit(
+        &self,
+        memory: &Memory,
+        topic: (u32, u32),
+        payload: (u32, u32),
+    ) -> Result<(), EventError> {
+        let max = self.schema.max_topic_len;
+        if topic.1 as usize > max {
+            return Err(EventError::TopicTooLong {
+                len: topic.1 as usize,
+                max,
+            });
+        }
+        let topic =
+            String::from_utf8(read_buffer(memory, topic)?).map_err(|_| EventError::TopicNotUtf8)?;
+        if !self.schema.allows(&topic) {
+            return Err(EventError::TopicNotAllowed(topic));
+        }
+        let max = self.caps.max_payload_len;
+        if payload.1 as usize > max {
+            return Err(EventError::PayloadTooLarge {
+                len: payload.1 as usize,
+                max,
+            });
+        }
+        let payload = read_buffer(memory, payload)?;
+
+        let mut events = self.log.events.lock().unwrap();
+        if events.len() >= self.caps.max_events {
+            return Err(EventError::TooManyEvents {
+                max: self.caps.max_events,
+            });
+        }
+        events.push(Event { topic, payload });
+        Ok(())
+    }
+}
+
+fn read_buffer(memory: &Memory, (ptr, len): (u32, u32)) -> Result<Vec<u8>, EventError> {
+    let start = ptr as usize;
+    let end = start + len as usize;
+    if end as u64 > memory.data_size() {
+        return Err(EventError::OutOfBounds { ptr, len });
+    }
+    Ok(unsafe { memory.data_unchecked() }[start..end].to_vec())
+}
+
+#[derive(Clone)]
+struct EmitEnv {
+    sink: EventSink,
+    memory: LazyInit<Memory>,
+}
+
+impl WasmerEnv for EmitEnv {
+    fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
+        let memory = instance
+            .lookup_memory("memory")
+            .ok_or_else(|| HostEnvInitError::MissingMemory("memory".into()))?;
+        self.memory.initialize(memory);
+        Ok(())
+    }
+}
+
+fn emit_event(
+    env: &EmitEnv,
+    topic_ptr: u32,
+    topic_len: u32,
+    payload_ptr: u32,
+    payload_len: u32,
+) -> Result<(), EventError> {
+    let memory = env.memory.get_ref().expect("the memory is initialized");
+    let result = env
+        .sink
+        .emit(memory, (topic_ptr, topic_len), (payload_ptr, payload_len));
+    match result {
+        Err(_) if env.sink.lenient => {
+            env.sink.log.rejected.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+        result => result,
+    }
+}
@@ -0,0 +1,331 @@
+//! Running a fixed sequence of calls into an instance, with the failed ones rolled back.
+
+use crate::sys::exports::ExportError;
+use crate::sys::instance::Instance;
+use crate::sys::{Export, RuntimeError, Val};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use wasmer_types::{Classify, ErrorCode, FailureKind, HasErrorCode};
+use wasmer_vm::{SnapshotError, SnapshotOptions};
+
+/// What an [`ExecutionPlan`] does with the state an entry leaves behind when it fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IsolationMode {
+    /// Keep it: the entries are run one after the other, as plain calls.
+    None,
+    /// Restore the memories, tables and globals of the instance to what they were before
+    /// the entry, from a snapshot, so that the next entry runs against the state the setup
+    /// and the entries that succeeded left.
+    ///
+    /// See [`Instance::snapshot_to`] for what snapshots cover. Gas burnt by the failed entry
+    /// stays burnt.
+    SnapshotRollback,
+}
+
+/// Limits on the calls of an [`ExecutionPlan`].
+///
+/// Plan-wide guards, given to [`Instance::run_plan`], and the guards of an entry, given to
+/// [`ExecutionPlanBuilder::entry_with_guards`], both apply to the entry: the tighter wins.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Guards {
+    /// The most gas the calls may burn. The limit of the gas counter of the instance still
+    /// applies. A call exceeding either traps with [`TrapCode::GasExceeded`].
+    ///
+    /// [`TrapCode::GasExceeded`]: crate::TrapCode::GasExceeded
+    pub gas: Option<u64>,
+    /// The time after which the calls are not started anymore. A running call is not
+    /// interrupted.
+    pub deadline: Option<Instant>,
+}
+
+/// A call of an [`ExecutionPlan`].
+#[derive(Clone, Debug)]
+struct PlanCall {
+    name: String,
+    args: Vec<Val>,
+    guards: Guards,
+}
+
+/// A setup call then independent entry calls into an instance, run with
+/// [`Instance::run_plan`].
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// # let store = Store::default();
+/// let module = Module::new(&store, r#"
+///     (module
+///         (global $total (mut i32) (i32.const 0))
+///         (func (export "init") (global.set $total (i32.const 100)))
+///         (func (export "spend") (param i32) (result i32)
+///             (global.set $total (i32.sub (global.get $total) (local.get 0)))
+///             (if (i32.lt_s (global.get $total) (i32.const 0)) (then unreachable))
+///             (global.get $total)))
+/// "#)?;
+/// let instance = Instance::new(&module, &imports! {})?;
+/// let plan = ExecutionPlan::builder()
+///     .setup("init", vec![])
+///     .entry("spend", vec![Val::I32(30)])
+///     .entry("spend", vec![Val::I32(500)])
+///     .entry("spend", vec![Val::I32(30)])
+///     .isolation(IsolationMode::SnapshotRollback)
+///     .build();
+/// let report = instance.run_plan(&plan, Guards::default())?;
+/// assert_eq!(**report.entries[0].result.as_ref().unwrap(), [Val::I32(70)]);
+/// assert!(report.entries[1].rolled_back);
+/// // The failed entry left no trace.
+/// assert_eq!(**report.entries[2].result.as_ref().unwrap(), [Val::I32(40)]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ExecutionPlan {
+    setup: Option<PlanCall>,
+    entries: Vec<PlanCall>,
+    isolation: IsolationMode,
+}
+
+impl ExecutionPlan {
+    /// Start building a plan, with no setup, no entries and no isolation.
+    pub fn builder() -> ExecutionPlanBuilder {
+        ExecutionPlanBuilder {
+            plan: Self {
+                setup: None,
+                entries: Vec::new(),
+                isolation: IsolationMode::None,
+            },
+        }
+    }
+}
+
+/// Builds an [`ExecutionPlan`].
+#[derive(Clone, Debug)]
+pub struct ExecutionPlanBuilder {
+    plan: ExecutionPlan,
+}
+
+impl ExecutionPlanBuilder {
+    /// Call the exported function `name` with `args` before the entries, replacing any
+    /// previous setup. The plan fails if the setup does.
+    pub fn setup(mut self, name: &str, args: Vec<Val>) -> Self {
+        self.plan.setup = Some(PlanCall {
+            name: name.to_string(),
+            args,
+            guards: Guards::default(),
+        });
+        self
+    }
+
+    /// Call the exported function `name` with `args` after the entries added so far.
+    pub fn entry(self, name: &str, args: Vec<Val>) -> Self {
+        self.entry_with_guards(name, args, Guards::default())
+    }
+
+    /// As [`entry`](Self::entry), with guards of the entry alone.
+    pub fn entry_with_guards(mut self, name: &str, args: Vec<Val>, guards: Guards) -> Self {
+        self.plan.entries.push(PlanCall {
+            name: name.to_string(),
+            args,
+            guards,
+        });
+        self
+    }
+
+    /// Set what to do with the state failed entries leave behind.
+    pub fn isolation(mut self, isolation: IsolationMode) -> Self {
+        self.plan.isolation = isolation;
+        self
+    }
+
+    /// Finish building the plan.
+    pub fn build(self) -> ExecutionPlan {
+        self.plan
+    }
+}
+
+/// How a call of an [`ExecutionPlan`] went.
+#[derive(Debug)]
+pub struct CallOutcome {
+    /// The name of the function called.
+    pub name: String,
+    /// What the call returned, or its error. Calls not started because of their deadline
+    /// fail with a [`RuntimeError`] saying so.
+    pub result: Result<Box<[Val]>, RuntimeError>,
+    /// The gas the call burnt.
+    pub gas_used: u64,
+    /// How long the call took.
+    pub duration: Duration,
+    /// Whether the state the call left was rolled back, see
+    /// [`IsolationMode::SnapshotRollback`].
+    pub rolled_back: bool,
+}
+
+/// How an [`ExecutionPlan`] went, returned by [`Instance::run_plan`].
+#[derive(Debug)]
+pub struct PlanReport {
+    /// How the setup went, if the plan has one. It succeeded, or the plan would have failed.
+    pub setup: Option<CallOutcome>,
+    /// How the entries went, in order.
+    pub entries: Vec<CallOutcome>,
+}
+
+impl PlanReport {
+    /// The gas burnt by the setup and the entries together.
+    pub fn gas_used(&self) -> u64 {
+        self.setup
+            .iter()
+            .chain(&self.entries)
+            .map(|outcome| outcome.gas_used)
+            .sum()
+    }
+}
+
+/// An error running an [`ExecutionPlan`], rather than one of its entries.
+#[derive(Error, Debug)]
+pub enum PlanError {
+    /// The instance does not export a function the plan calls.
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    /// The setup failed.
+    #[error(transparent)]
+    Setup(#[from] RuntimeError),
+    /// The state of the instance could not be snapshotted or restored.
+    #[error(transparent)]
+    Snapshot(#[from] SnapshotError),
+}
+
+impl HasErrorCode for PlanError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Export(e) => e.code(),
+            Self::Setup(e) => e.code(),
+            Self::Snapshot(e) => e.code(),
+        }
+    }
+}
+
+impl Classify for PlanError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::Export(_) => FailureKind::Permanent,
+            Self::Setup(e) => e.failure_kind(),
+            Self::Snapshot(e) => e.failure_kind(),
+        }
+    }
+}
+
+/// Run `call` with the gas counter of `instance` limited to `gas` more than it burnt so far, on
+/// top of its own limit.
+pub(crate) fn with_gas_guard<R>(
+    instance: &Instance,
+    gas: Option<u64>,
+    call: impl FnOnce() -> R,
+) -> R {
+    let counter = instance.gas_counter();
+    // The counter outlives the instance, see `InstanceConfig::with_counter`.
+    let original_limit = unsafe { (*counter).gas_limit };
+    if let Some(gas) = gas {
+        let burnt = unsafe { (*counter).burnt_gas };
+        unsafe { (*counter).gas_limit = original_limit.min(burnt.saturating_add(gas)) };
+    }
+    let result = call();
+    unsafe { (*counter).gas_limit = original_limit };
+    result
+}
+
+/// See [`Instance::run_plan`].
+pub(crate) fn run_plan(
+    instance: &Instance,
+    plan: &ExecutionPlan,
+    guards: Guards,
+) -> Result<PlanReport, PlanError> {
+    // Every function is looked up before anything runs.
+    let lookup = |call: &PlanCall| match instance.lookup(&call.name) {
+        Some(Export::Function(_)) => Ok(instance.lookup_function(&call.name).unwrap()),
+        Some(_) => Err(ExportError::IncompatibleType),
+        None => Err(ExportError::Missing(call.name.clone())),
+    };
+    let setup_function = plan.setup.as_ref().map(lookup).transpose()?;
+    let functions = plan
+        .entries
+        .iter()
+        .map(lookup)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let counter = instance.gas_counter();
+    // The counter outlives the instance, see `InstanceConfig::with_counter`.
+    let burnt = || unsafe { (*counter).burnt_gas };
+    let plan_start = burnt();
+    // Returns whether the call was started, along with how it went.
+    let run = |call: &PlanCall, function: &crate::Function| {
+        let deadline = match (guards.deadline, call.guards.deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let start = burnt();
+        if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            let outcome = CallOutcome {
+                name: call.name.clone(),
+                result: Err(RuntimeError::new("the deadline of the call passed")),
+                gas_used: 0,
+                duration: Duration::default(),
+                rolled_back: false,
+            };
+            return (false, outcome);
+        }
+        let plan_left = guards
+            .gas
+            .map(|gas| plan_start.saturating_add(gas).saturating_sub(start));
+        let limit = match (plan_left, call.guards.gas) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let now = Instant::now();
+        let result = with_gas_guard(instance, limit, || function.call(&call.args));
+        let duration = now.elapsed();
+        let outcome = CallOutcome {
+            name: call.name.clone(),
+            result,
+            gas_used: burnt().saturating_sub(start),
+            duration,
+            rolled_back: false,
+        };
+        (true, outcome)
+    };
+
+    let setup = match (&plan.setup, setup_function) {
+        (Some(call), Some(function)) => {
+            let (_, outcome) = run(call, &function);
+            match outcome.result {
+                Ok(_) => Some(outcome),
+                Err(e) => return Err(PlanError::Setup(e)),
+            }
+        }
+        _ => None,
+    };
+    let mut snapshot = Vec::new();
+    let take_snapshot = |snapshot: &mut Vec<u8>| -> Result<(), SnapshotError> {
+        snapshot.clear();
+        instance.snapshot_to(&mut *snapshot, SnapshotOptions::default())?;
+        Ok(())
+    };
+    let isolated = plan.isolation == IsolationMode::SnapshotRollback;
+    if isolated {
+        take_snapshot(&mut snapshot)?;
+    }
+    let mut entries = Vec::with_capacity(plan.entries.len());
+    for (index, (call, function)) in plan.entries.iter().zip(&functions).enumerate() {
+        let (started, mut outcome) = run(call, function);
+        let last = index + 1 == plan.entries.len();
+        if isolated && started {
+            if outcome.result.is_err() {
+                instance.restore_from(&snapshot[..])?;
+                outcome.rolled_back = true;
+            } else if !last {
+                take_snapshot(&mut snapshot)?;
+            }
+        }
+        entries.push(outcome);
+    }
+    Ok(PlanReport { setup, entries })
+}
@@ -3,6 +3,7 @@ use crate::sys::import_object::LikeNamespace;
 use indexmap::IndexMap;
 use std::sync::Arc;
 use thiserror::Error;
+use wasmer_types::{ErrorCode, HasErrorCode};
 use wasmer_vm::Export;
 
 /// The `ExportError` can happen when trying to get a specific
@@ -46,13 +47,22 @@ use wasmer_vm::Export;
 pub enum ExportError {
     /// An error than occurs when the exported type and the expected type
     /// are incompatible.
-    #[error("Incompatible Export Type")]
+    #[error("Incompatible Export Type [{}]", ErrorCode::ExportIncompatibleType)]
     IncompatibleType,
     /// This error arises when an export is missing
-    #[error("Missing export {0}")]
+    #[error("Missing export {0} [{}]", ErrorCode::ExportMissing)]
     Missing(String),
 }
 
+impl HasErrorCode for ExportError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::IncompatibleType => ErrorCode::ExportIncompatibleType,
+            Self::Missing(_) => ErrorCode::ExportMissing,
+        }
+    }
+}
+
 /// Exports is a special kind of map that allows easily unwrapping
 /// the types of instances.
 ///
@@ -102,4 +112,13 @@ pub trait Exportable<'a>: Sized {
     ///
     /// [`Module`]: crate::Module
     fn to_export(&self) -> Export;
+
+    /// Make the extern hold a weak reference to the instance it is exported from, so that it
+    /// does not keep the instance alive. This prevents cycles, such as when an extern of an
+    /// instance is kept in the [`WasmerEnv`] of one of its imports.
+    ///
+    /// Clones of the extern hold a strong reference again, if the instance is still alive.
+    ///
+    /// [`WasmerEnv`]: crate::WasmerEnv
+    fn into_weak_instance_ref(&mut self);
 }
@@ -1,23 +1,57 @@
+use crate::sys::arg_policy::{ArgGuard, ArgPolicy, ArgPolicyError, Guarded};
+use crate::sys::call_context::enter_instance;
 use crate::sys::exports::Exportable;
+use crate::sys::non_send::{NonSendFunction, SingleThreaded};
 use crate::sys::store::Store;
 use crate::sys::types::{Val, ValFuncRef};
 use crate::sys::FunctionType;
 use crate::sys::NativeFunc;
 use crate::sys::RuntimeError;
-use crate::sys::WasmerEnv;
+use crate::sys::{HostEnvInitError, WasmerEnv};
 pub use inner::{FromToNativeWasmType, HostFunction, WasmTypeList, WithEnv, WithoutEnv};
 
+use std::cell::Cell;
 use std::cmp::max;
 use std::ffi::c_void;
 use std::fmt;
+use std::marker::PhantomData;
 use std::sync::Arc;
+use thiserror::Error;
+use wasmer_types::{ErrorCode, HasErrorCode};
 use wasmer_vm::{
     raise_user_trap, resume_panic, wasmer_call_trampoline, Export, ExportFunction,
-    ExportFunctionMetadata, ImportInitializerFuncPtr, TableElement, VMCallerCheckedAnyfunc,
+    ExportFunctionMetadata, ImportInitializerFuncPtr, TableElement, Trap, VMCallerCheckedAnyfunc,
     VMDynamicFunctionContext, VMFuncRef, VMFunction, VMFunctionBody, VMFunctionEnvironment,
     VMFunctionKind, VMTrampoline,
 };
 
+/// The number of values of the calls passing them on the stack.
+const INLINE_VALUES: usize = 16;
+
+thread_local! {
+    /// The values of the calls of the current thread with too many to pass on the stack.
+    static SCRATCH_VALUES: Cell<Vec<i128>> = Cell::new(Vec::new());
+}
+
+/// The results given to [`Function::call_into`] cannot hold the results of the function.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error(
+    "the function has {expected} results but only {given} were given to hold them [{}]",
+    ErrorCode::CallResultArity
+)]
+pub struct ResultArityError {
+    /// The number of results of the function.
+    pub expected: usize,
+    /// The number of results given.
+    pub given: usize,
+}
+
+impl HasErrorCode for ResultArityError {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::CallResultArity
+    }
+}
+
 /// A WebAssembly `function` instance.
 ///
 /// A function instance is the runtime representation of a function.
@@ -195,6 +229,43 @@ impl Function {
         Self::new_with_env(store, ty, WithoutEnv, wrapped_func)
     }
 
+    /// Creates a new host `Function` (dynamic) with the provided signature, whose closure may
+    /// mutate what it captures without locks.
+    ///
+    /// Unlike [`Function::new`], the closure is neither required to be `Send` nor `Sync`, so
+    /// the function is bound to the thread that created it: it can only be imported through
+    /// [`NonSendImports`](crate::NonSendImports), by a [`NonSendInstance`](crate::NonSendInstance)
+    /// that cannot leave the thread either.
+    ///
+    /// Calling the function from another thread, or while it is running through an export
+    /// it calls, raises a trap.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmer::{Function, FunctionType, Type, Store, Value};
+    /// # let store = Store::default();
+    /// #
+    /// let signature = FunctionType::new(vec![Type::I32], vec![Type::I32]);
+    ///
+    /// let mut total = 0;
+    /// let f = Function::new_st(&store, &signature, move |args| {
+    ///     total += args[0].unwrap_i32();
+    ///     Ok(vec![Value::I32(total)])
+    /// });
+    /// ```
+    pub fn new_st<FT, F>(store: &Store, ty: FT, func: F) -> NonSendFunction
+    where
+        FT: Into<FunctionType>,
+        F: FnMut(&[Val]) -> Result<Vec<Val>, RuntimeError> + 'static,
+    {
+        let func = SingleThreaded::new(func);
+        NonSendFunction {
+            function: Self::new(store, ty, move |args| func.call(args)),
+            _not_send: PhantomData,
+        }
+    }
+
     /// Creates a new host `Function` (dynamic) with the provided signature and environment.
     ///
     /// If you know the signature of the host function at compile time,
@@ -251,6 +322,7 @@ impl Function {
         let ty: FunctionType = ty.into();
         let dynamic_ctx: VMDynamicFunctionContext<DynamicFunction<Env>> =
             VMDynamicFunctionContext::from_context(DynamicFunction {
+                guard: ArgGuard::default(),
                 env: Box::new(env),
                 func: Arc::new(func),
                 store: store.clone(),
@@ -260,6 +332,8 @@ impl Function {
         let import_init_function_ptr: for<'a> fn(&'a mut _, &'a _) -> Result<(), _> =
             |env: &mut VMDynamicFunctionContext<DynamicFunction<Env>>,
              instance: &crate::Instance| {
+                let host_env = env as *mut VMDynamicFunctionContext<_> as *mut c_void;
+                env.ctx.guard.init(instance, host_env);
                 Env::init_with_instance(&mut *env.ctx.env, instance)
             };
 
@@ -341,7 +415,7 @@ impl Function {
                     vmctx,
                     signature,
                     kind: VMFunctionKind::Static,
-                    call_trampoline: None,
+                    call_trampoline: store.engine().lookup_function_call_trampoline(signature),
                     instance_ref: None,
                 },
             },
@@ -385,8 +459,18 @@ impl Function {
         let function = inner::Function::<Args, Rets>::new(func);
         let address = function.address();
 
+        let env = Guarded {
+            guard: ArgGuard::default(),
+            env,
+        };
+        let import_init_function_ptr: for<'a> fn(&'a mut _, &'a _) -> Result<(), _> =
+            |env: &mut Guarded<Env>, instance: &crate::Instance| {
+                let host_env = env as *mut Guarded<Env> as *mut c_void;
+                env.guard.init(instance, host_env);
+                Env::init_with_instance(&mut env.env, instance)
+            };
         let (host_env, metadata) =
-            build_export_function_metadata::<Env>(env, Env::init_with_instance);
+            build_export_function_metadata::<Guarded<Env>>(env, import_init_function_ptr);
 
         let vmctx = VMFunctionEnvironment { host_env };
         let signature = store.engine().register_signature((&function.ty()).into());
@@ -399,13 +483,178 @@ impl Function {
                     kind: VMFunctionKind::Static,
                     vmctx,
                     signature,
-                    call_trampoline: None,
+                    call_trampoline: store.engine().lookup_function_call_trampoline(signature),
                     instance_ref: None,
                 },
             },
         }
     }
 
+    /// Attach `policy` to this host function, checking its arguments before it runs.
+    ///
+    /// Calls breaking a rule of the policy fail with a [`RuntimeError`] wrapping an
+    /// [`ArgPolicyError::Violation`], without running the function. The function is returned
+    /// with the policy, replacing the policy it had, and is otherwise unchanged.
+    ///
+    /// Pointers are checked against the first memory of the instance importing the function.
+    /// Calls from the host, and from instances without memory, only pass with empty ranges at
+    /// offset 0.
+    ///
+    /// # Errors
+    ///
+    /// Policies only apply to functions created with an environment, by
+    /// [`Function::new`], [`Function::new_with_env`] and [`Function::new_native_with_env`]:
+    /// other functions fail with [`ArgPolicyError::NotHostFunction`]. Policies referring to
+    /// arguments that the function does not have, or that are not `i32` or `i64`, fail with
+    /// [`ArgPolicyError::Invalid`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{imports, ArgPolicy, ArgPolicyError, Function, Instance, Module, Store, WasmerEnv};
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// #[derive(Clone)]
+    /// struct Env;
+    /// impl WasmerEnv for Env {}
+    ///
+    /// fn log(_env: &Env, _ptr: u32, _len: u32) {}
+    ///
+    /// let policy = ArgPolicy::builder().ptr_len_pair(0, 1).build();
+    /// let log = Function::new_native_with_env(&store, Env, log).with_arg_policy(policy)?;
+    /// let module = Module::new(&store, r#"
+    ///     (module
+    ///         (import "env" "log" (func $log (param i32 i32)))
+    ///         (memory 1)
+    ///         (func (export "run") (param i32 i32) (call $log (local.get 0) (local.get 1))))
+    /// "#)?;
+    /// let instance = Instance::new(&module, &imports! { "env" => { "log" => log } })?;
+    /// let run = instance.get_native_function::<(u32, u32), ()>("run")?;
+    /// run.call(0xfff0, 0x10)?;
+    /// let error = run.call(0xfff0, 0x11).unwrap_err();
+    /// assert_eq!(
+    ///     error.message(),
+    ///     r#"argument 0 of import "env"."log" violates `ptr_len_pair(0, 1)` [W0692]"#,
+    /// );
+    /// assert!(matches!(error.downcast::<ArgPolicyError>()?, ArgPolicyError::Violation { .. }));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_arg_policy(self, policy: ArgPolicy) -> Result<Self, ArgPolicyError> {
+        let metadata = match (
+            &self.exported.metadata,
+            &self.exported.vm_function.instance_ref,
+        ) {
+            (Some(metadata), None) => metadata,
+            _ => return Err(ArgPolicyError::NotHostFunction),
+        };
+        let ty = self.ty();
+        // The environment may be shared with clones of this function, which keep their policy.
+        let host_env =
+            (metadata.host_env_clone_fn)(unsafe { self.exported.vm_function.vmctx.host_env });
+        let metadata = ExportFunctionMetadata {
+            host_env,
+            import_init_function_ptr: metadata.import_init_function_ptr,
+            host_env_clone_fn: metadata.host_env_clone_fn,
+            host_env_drop_fn: metadata.host_env_drop_fn,
+        };
+        // # Safety
+        // The new environment is a clone of that of this function, of the same kind.
+        unsafe { &mut *self.arg_guard(host_env) }.set_policy(policy, &ty)?;
+        Ok(Self {
+            store: self.store,
+            exported: ExportFunction {
+                metadata: Some(Arc::new(metadata)),
+                vm_function: VMFunction {
+                    vmctx: VMFunctionEnvironment { host_env },
+                    ..self.exported.vm_function
+                },
+            },
+        })
+    }
+
+    /// Returns this function with an environment of its own, initialized for `instance` as
+    /// if `instance` imported it, for host functions called on behalf of `instance` without
+    /// being imported by it.
+    ///
+    /// Functions without an environment are returned as they are.
+    pub(crate) fn bind_to_instance(
+        &self,
+        instance: &crate::Instance,
+    ) -> Result<Self, HostEnvInitError> {
+        let metadata = match (
+            &self.exported.metadata,
+            &self.exported.vm_function.instance_ref,
+        ) {
+            (Some(metadata), None) => metadata,
+            _ => return Ok(self.clone()),
+        };
+        let host_env =
+            (metadata.host_env_clone_fn)(unsafe { self.exported.vm_function.vmctx.host_env });
+        // Owning the clone, so that it is dropped with the function, or here if `init` fails.
+        let bound_metadata = Arc::new(ExportFunctionMetadata {
+            host_env,
+            import_init_function_ptr: None,
+            host_env_clone_fn: metadata.host_env_clone_fn,
+            host_env_drop_fn: metadata.host_env_drop_fn,
+        });
+        if let Some(init) = metadata.import_init_function_ptr {
+            // # Safety
+            // The initializers of host functions return `HostEnvInitError`s and take the
+            // instance as an `Instance`, see `wasmer_vm::initialize_host_envs`.
+            unsafe {
+                let init = std::mem::transmute::<
+                    ImportInitializerFuncPtr,
+                    ImportInitializerFuncPtr<HostEnvInitError>,
+                >(init);
+                init(host_env, instance as *const _ as *const c_void)?;
+            }
+        }
+        Ok(Self {
+            store: self.store.clone(),
+            exported: ExportFunction {
+                metadata: Some(bound_metadata),
+                vm_function: VMFunction {
+                    vmctx: VMFunctionEnvironment { host_env },
+                    ..self.exported.vm_function.clone()
+                },
+            },
+        })
+    }
+
+    /// Returns the argument policy of this host function, if it has one.
+    ///
+    /// See [`Function::with_arg_policy`].
+    pub fn arg_policy(&self) -> Option<&ArgPolicy> {
+        match (
+            &self.exported.metadata,
+            &self.exported.vm_function.instance_ref,
+        ) {
+            // # Safety
+            // The environment of host functions is owned by their metadata.
+            (Some(_), None) => unsafe {
+                (*self.arg_guard(self.exported.vm_function.vmctx.host_env)).policy()
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the argument guard of the host environment `host_env` of this function.
+    ///
+    /// # Safety
+    ///
+    /// `host_env` must be an environment of this host function, or a clone of it.
+    unsafe fn arg_guard(&self, host_env: *mut c_void) -> *mut ArgGuard {
+        match self.exported.vm_function.kind {
+            VMFunctionKind::Dynamic => {
+                type VMContextWithEnv = VMDynamicFunctionContext<DynamicFunction<c_void>>;
+                &mut (*(host_env as *mut VMContextWithEnv)).ctx.guard
+            }
+            // `Guarded` starts with the guard.
+            VMFunctionKind::Static => host_env as *mut ArgGuard,
+        }
+    }
+
     /// Returns the [`FunctionType`] of the `Function`.
     ///
     /// # Example
@@ -440,67 +689,86 @@ impl Function {
         trampoline: VMTrampoline,
         params: &[Val],
         results: &mut [Val],
-    ) -> Result<(), RuntimeError> {
-        let format_types_for_error_message = |items: &[Val]| {
-            items
+    ) -> Result<usize, RuntimeError> {
+        let signature = self
+            .store
+            .engine()
+            .lookup_shared_signature(self.exported.vm_function.signature)
+            .expect("Could not resolve VMSharedFunctionIndex! Mixing engines?");
+        if signature.params().len() != params.len()
+            || params
+                .iter()
+                .zip(signature.params())
+                .any(|(arg, ty)| arg.ty() != *ty)
+        {
+            let param_types = params
                 .iter()
                 .map(|param| param.ty().to_string())
                 .collect::<Vec<String>>()
-                .join(", ")
-        };
-        let signature = self.ty();
-        if signature.params().len() != params.len() {
+                .join(", ");
             return Err(RuntimeError::new(format!(
                 "Parameters of type [{}] did not match signature {}",
-                format_types_for_error_message(params),
-                &signature
+                param_types, &signature,
             )));
         }
-        if signature.results().len() != results.len() {
-            return Err(RuntimeError::new(format!(
-                "Results of type [{}] did not match signature {}",
-                format_types_for_error_message(results),
-                &signature,
-            )));
+        let arity = signature.results().len();
+        if results.len() < arity {
+            let error = ResultArityError {
+                expected: arity,
+                given: results.len(),
+            };
+            return Err(RuntimeError::from_trap(Trap::User(Box::new(error))));
         }
 
-        let mut values_vec = vec![0; max(params.len(), results.len())];
+        // Small signatures have their values on the stack, the others in a buffer reused by
+        // the calls of the thread.
+        let len = max(params.len(), arity);
+        if len <= INLINE_VALUES {
+            let mut values = [0; INLINE_VALUES];
+            self.call_trampoline(trampoline, &signature, params, results, &mut values)?;
+        } else {
+            let mut values = SCRATCH_VALUES.with(Cell::take);
+            values.clear();
+            values.resize(len, 0);
+            let result = self.call_trampoline(trampoline, &signature, params, results, &mut values);
+            SCRATCH_VALUES.with(|scratch| scratch.set(values));
+            result?;
+        }
+        Ok(arity)
+    }
 
-        // Store the argument values into `values_vec`.
-        let param_tys = signature.params().iter();
-        for ((arg, slot), ty) in params.iter().zip(&mut values_vec).zip(param_tys) {
-            if arg.ty() != *ty {
-                let param_types = format_types_for_error_message(params);
-                return Err(RuntimeError::new(format!(
-                    "Parameters of type [{}] did not match signature {}",
-                    param_types, &signature,
-                )));
-            }
+    /// Call the trampoline with `params` stored in `values`, and load the results of
+    /// `signature` out of it into `results`.
+    fn call_trampoline(
+        &self,
+        trampoline: VMTrampoline,
+        signature: &FunctionType,
+        params: &[Val],
+        results: &mut [Val],
+        values: &mut [i128],
+    ) -> Result<(), RuntimeError> {
+        for (arg, slot) in params.iter().zip(values.iter_mut()) {
             unsafe {
                 arg.write_value_to(slot);
             }
         }
 
-        // Call the trampoline.
-        if let Err(error) = unsafe {
+        let instance_ref = self.exported.vm_function.instance_ref.as_ref();
+        if let Err(error) = enter_instance(&self.store, instance_ref, || unsafe {
             wasmer_call_trampoline(
                 self.exported.vm_function.vmctx,
                 trampoline,
                 self.exported.vm_function.address,
-                values_vec.as_mut_ptr() as *mut u8,
+                values.as_mut_ptr() as *mut u8,
             )
-        } {
-            return Err(RuntimeError::from_trap(error));
+        }) {
+            return Err(self.store.runtime_error_from_trap(error));
         }
 
-        // Load the return values out of `values_vec`.
-        for (index, &value_type) in signature.results().iter().enumerate() {
-            unsafe {
-                let ptr = values_vec.as_ptr().add(index);
-                results[index] = Val::read_value_from(&self.store, ptr, value_type);
-            }
+        let result_types = signature.results().iter();
+        for ((result, value), ty) in results.iter_mut().zip(values.iter()).zip(result_types) {
+            *result = unsafe { Val::read_value_from(&self.store, value, *ty) };
         }
-
         Ok(())
     }
 
@@ -574,25 +842,94 @@ impl Function {
     /// assert_eq!(sum.call(&[Value::I32(1), Value::I32(2)]).unwrap().to_vec(), vec![Value::I32(3)]);
     /// ```
     pub fn call(&self, params: &[Val]) -> Result<Box<[Val]>, RuntimeError> {
-        // If it's a function defined in the Wasm, it will always have a call_trampoline
+        if let Some(results) = self.call_dynamic(params) {
+            return Ok(results?.into_boxed_slice());
+        }
+        let mut results = vec![Val::null(); self.result_arity()].into_boxed_slice();
+        self.call_into(params, &mut results)?;
+        Ok(results)
+    }
+
+    /// Call the `Function` function like [`Function::call`], writing its results at the start
+    /// of `results` and returning how many there are.
+    ///
+    /// Unlike [`Function::call`], this does not allocate for calls of functions defined in the
+    /// Wasm nor of native host functions, whatever their signature: the values are passed on
+    /// the stack or in a buffer reused by the calls of the thread.
+    ///
+    /// Calls fail with a [`RuntimeError`] wrapping a [`ResultArityError`] if `results` is too
+    /// short for the results of the function, without calling it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmer::{imports, wat2wasm, Function, Instance, Module, Store, Type, Value};
+    /// # let store = Store::default();
+    /// # let wasm_bytes = wat2wasm(r#"
+    /// # (module
+    /// #   (func (export "sum") (param $x i32) (param $y i32) (result i32)
+    /// #     local.get $x
+    /// #     local.get $y
+    /// #     i32.add
+    /// #   ))
+    /// # "#.as_bytes()).unwrap();
+    /// # let module = Module::new(&store, wasm_bytes).unwrap();
+    /// # let import_object = imports! {};
+    /// # let instance = Instance::new(&module, &import_object).unwrap();
+    /// #
+    /// let sum = instance.lookup_function("sum").unwrap();
+    /// let mut results = [Value::I32(0)];
+    ///
+    /// assert_eq!(sum.call_into(&[Value::I32(1), Value::I32(2)], &mut results).unwrap(), 1);
+    /// assert_eq!(results, [Value::I32(3)]);
+    /// ```
+    pub fn call_into(&self, params: &[Val], results: &mut [Val]) -> Result<usize, RuntimeError> {
+        // Functions defined in the Wasm always have a call_trampoline, and host functions
+        // have one if it was installed in the engine
         if let Some(trampoline) = self.exported.vm_function.call_trampoline {
-            let mut results = vec![Val::null(); self.result_arity()];
-            self.call_wasm(trampoline, params, &mut results)?;
-            return Ok(results.into_boxed_slice());
+            return self.call_wasm(trampoline, params, results);
         }
 
         // If it's a function defined in the host
-        match self.exported.vm_function.kind {
-            VMFunctionKind::Dynamic => unsafe {
+        if let Some(values) = self.call_dynamic(params) {
+            let values = values?;
+            if results.len() < values.len() {
+                let error = ResultArityError {
+                    expected: values.len(),
+                    given: results.len(),
+                };
+                return Err(RuntimeError::from_trap(Trap::User(Box::new(error))));
+            }
+            results[..values.len()].clone_from_slice(&values);
+            return Ok(values.len());
+        }
+
+        // Host functions created before the trampoline of their signature was installed, and
+        // functions taken out of tables, have no trampoline yet.
+        let signature = self.exported.vm_function.signature;
+        let trampoline = self
+            .store
+            .engine()
+            .function_call_trampoline(signature)
+            .ok_or_else(|| {
+                RuntimeError::new(format!(
+                    "no trampoline is available to call functions of signature {}",
+                    self.ty()
+                ))
+            })?;
+        self.call_wasm(trampoline, params, results)
+    }
+
+    /// Call the function if it is a dynamic host function without a trampoline.
+    fn call_dynamic(&self, params: &[Val]) -> Option<Result<Vec<Val>, RuntimeError>> {
+        let vm_function = &self.exported.vm_function;
+        match (vm_function.call_trampoline, &vm_function.kind) {
+            (None, VMFunctionKind::Dynamic) => unsafe {
                 type VMContextWithEnv = VMDynamicFunctionContext<DynamicFunction<std::ffi::c_void>>;
-                let ctx = self.exported.vm_function.vmctx.host_env as *mut VMContextWithEnv;
-                Ok((*ctx).ctx.call(&params)?.into_boxed_slice())
+                let ctx = vm_function.vmctx.host_env as *mut VMContextWithEnv;
+                Some((*ctx).ctx.call(params))
             },
-            VMFunctionKind::Static => {
-                unimplemented!(
-                    "Native function definitions can't be directly called from the host yet"
-                );
-            }
+            _ => None,
         }
     }
 
@@ -740,6 +1077,12 @@ impl<'a> Exportable<'a> for Function {
     fn to_export(&self) -> Export {
         self.exported.clone().into()
     }
+
+    fn into_weak_instance_ref(&mut self) {
+        if let Some(instance_ref) = &mut self.exported.vm_function.instance_ref {
+            *instance_ref = instance_ref.downgrade();
+        }
+    }
 }
 
 impl Clone for Function {
@@ -774,6 +1117,7 @@ pub(crate) struct DynamicFunction<Env>
 where
     Env: Sized + 'static + Send + Sync,
 {
+    guard: ArgGuard,
     function_type: FunctionType,
     #[allow(clippy::type_complexity)]
     func: Arc<dyn Fn(&Env, &[Val]) -> Result<Vec<Val>, RuntimeError> + 'static + Send + Sync>,
@@ -784,6 +1128,7 @@ where
 impl<Env: Sized + Clone + 'static + Send + Sync> Clone for DynamicFunction<Env> {
     fn clone(&self) -> Self {
         Self {
+            guard: self.guard.clone(),
             env: self.env.clone(),
             function_type: self.function_type.clone(),
             store: self.store.clone(),
@@ -797,6 +1142,9 @@ where
     Env: Sized + 'static + Send + Sync,
 {
     fn call(&self, args: &[Val]) -> Result<Vec<Val>, RuntimeError> {
+        self.guard
+            .check_values(args)
+            .map_err(|error| RuntimeError::from_trap(Trap::User(Box::new(error))))?;
         (*self.func)(&*self.env, &args)
     }
     fn function_type(&self) -> &FunctionType {
@@ -872,6 +1220,7 @@ impl<T: VMDynamicFunction> VMDynamicFunctionCall<T> for VMDynamicFunctionContext
 /// This private inner module contains the low-level implementation
 /// for `Function` and its siblings.
 mod inner {
+    use crate::sys::arg_policy::Guarded;
     use std::array::TryFromSliceError;
     use std::convert::{Infallible, TryInto};
     use std::error::Error;
@@ -1359,7 +1708,7 @@ mod inner {
                     /// This is a function that wraps the real host
                     /// function. Its address will be used inside the
                     /// runtime.
-                    extern fn func_wrapper<$( $x, )* Rets, RetsAsResult, Env, Func>( env: &Env, $( $x: $x::Native, )* ) -> Rets::CStruct
+                    extern "C" fn func_wrapper<$( $x, )* Rets, RetsAsResult, Env, Func>( env: &Guarded<Env>, $( $x: $x::Native, )* ) -> Rets::CStruct
                     where
                         $( $x: FromToNativeWasmType, )*
                         Rets: WasmTypeList,
@@ -1369,8 +1718,17 @@ mod inner {
                     {
                         let func: &Func = unsafe { &*(&() as *const () as *const Func) };
 
+                        // The arguments go through their binary representation, which all
+                        // native types round-trip through, to be checked by the policy.
+                        let args: [i128; count_idents!( $( $x ),* )] = [ $( $x.to_binary() ),* ];
+                        if let Err(error) = env.guard.check(&args) {
+                            unsafe { raise_user_trap(Box::new(error)) }
+                        }
+                        #[allow(unused_variables)]
+                        let [ $( $x ),* ] = args;
+
                         let result = panic::catch_unwind(AssertUnwindSafe(|| {
-                            func(env, $( FromToNativeWasmType::from_native($x) ),* ).into_result()
+                            func(&env.env, $( FromToNativeWasmType::from_native(NativeWasmType::from_binary($x)) ),* ).into_result()
                         }));
 
                         match result {
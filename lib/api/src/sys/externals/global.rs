@@ -147,6 +147,10 @@ impl Global {
     ///
     /// # Errors
     ///
+    /// The error carries a [`GlobalError`], retrieved with [`RuntimeError::downcast`]:
+    /// [`GlobalError::ImmutableGlobalCannotBeSet`] for immutable globals, and
+    /// [`GlobalError::IncorrectType`] for values of another type than the global.
+    ///
     /// Trying to mutate a immutable global will raise an error:
     ///
     /// ```should_panic
@@ -169,6 +173,10 @@ impl Global {
     /// // This results in an error: `RuntimeError`.
     /// g.set(Value::I64(2)).unwrap();
     /// ```
+    ///
+    /// [`GlobalError`]: crate::GlobalError
+    /// [`GlobalError::ImmutableGlobalCannotBeSet`]: crate::GlobalError::ImmutableGlobalCannotBeSet
+    /// [`GlobalError::IncorrectType`]: crate::GlobalError::IncorrectType
     pub fn set(&self, val: Val) -> Result<(), RuntimeError> {
         if !val.comes_from_same_store(&self.store) {
             return Err(RuntimeError::new("cross-`Store` values are not supported"));
@@ -177,7 +185,7 @@ impl Global {
             self.vm_global
                 .from
                 .set(val)
-                .map_err(|e| RuntimeError::new(format!("{}", e)))?;
+                .map_err(|e| RuntimeError::user(Box::new(e)))?;
         }
         Ok(())
     }
@@ -216,4 +224,10 @@ impl<'a> Exportable<'a> for Global {
     fn to_export(&self) -> Export {
         self.vm_global.clone().into()
     }
+
+    fn into_weak_instance_ref(&mut self) {
+        if let Some(instance_ref) = &mut self.vm_global.instance_ref {
+            *instance_ref = instance_ref.downgrade();
+        }
+    }
 }
@@ -4,7 +4,7 @@ use crate::sys::{MemoryType, MemoryView};
 use std::convert::TryInto;
 use std::slice;
 use wasmer_types::{Pages, ValueType};
-use wasmer_vm::{Export, MemoryError, VMMemory};
+use wasmer_vm::{BaseStability, Export, MemoryError, MemoryStyle, ModuleStyleHints, VMMemory};
 
 /// A WebAssembly `memory` instance.
 ///
@@ -42,7 +42,8 @@ impl Memory {
     /// ```
     pub fn new(store: &Store, ty: MemoryType) -> Result<Self, MemoryError> {
         let tunables = store.tunables();
-        let style = tunables.memory_style(&ty);
+        // Host memories are meant to be imported, so they get the style of an import.
+        let style = tunables.memory_style_for(&ty, &ModuleStyleHints::new(&ty, true));
         let memory = tunables.create_host_memory(&ty, &style)?;
 
         Ok(Self {
@@ -81,6 +82,44 @@ impl Memory {
         self.vm_memory.from.ty()
     }
 
+    /// Returns the style the `Memory` was created with, as chosen by the [`Tunables`] of its
+    /// store.
+    ///
+    /// [`Tunables`]: crate::Tunables
+    pub fn style(&self) -> &MemoryStyle {
+        self.vm_memory.from.style()
+    }
+
+    /// Returns whether the base of the `Memory`, see [`Memory::data_ptr`], may move when it
+    /// grows, which follows from the style it was created with.
+    ///
+    /// Static memories are reserved up to their maximum size and never move. Dynamic ones
+    /// move when they grow, unless their store reserves their address space up to their
+    /// maximum, see [`dynamic_memory_reservation`]. Pointers into a memory that may move
+    /// must not be kept across anything that may grow it, such as calls into WebAssembly,
+    /// unless the memory is pinned with [`Memory::pin`].
+    ///
+    /// [`dynamic_memory_reservation`]: crate::BaseTunables::dynamic_memory_reservation
+    pub fn base_stability(&self) -> BaseStability {
+        self.vm_memory.from.base_stability()
+    }
+
+    /// Returns why the address space of the `Memory` could not be reserved up to its maximum
+    /// size when its store asked for it, leaving it to move when it grows.
+    pub fn reservation_fallback(&self) -> Option<MemoryError> {
+        self.vm_memory.from.reservation_fallback()
+    }
+
+    /// Keep the base of the `Memory` where it is for as long as the returned pin lives.
+    ///
+    /// Meanwhile, growing a memory that may move past the address space reserved for it
+    /// fails with [`MemoryError::BasePinned`], and `memory.grow` returns -1 in WebAssembly.
+    /// Pinning a memory that never moves blocks nothing.
+    pub fn pin(&self) -> Result<MemoryPin<'_>, MemoryError> {
+        self.vm_memory.from.pin_base()?;
+        Ok(MemoryPin { memory: self })
+    }
+
     /// Returns the [`Store`] where the `Memory` belongs.
     ///
     /// # Example
@@ -154,17 +193,85 @@ impl Memory {
         self.vm_memory.from.size()
     }
 
+    /// Grow the memory by `delta` pages, returning its previous size as `memory.grow` does.
+    ///
+    /// The memory cannot grow past the maximum of its type, nor past what WebAssembly can
+    /// index: it fails with [`MemoryError::CouldNotGrow`] and keeps its size then. A static
+    /// memory grows within the bound of its style, which the tunables chose to cover its
+    /// maximum. A memory that may move when it grows, see [`Memory::base_stability`], fails
+    /// with [`MemoryError::BasePinned`] instead of moving while it is pinned, and views
+    /// taken before it grew must be taken anew.
+    ///
+    /// The memory must not grow while WebAssembly code using it runs on another thread.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{Memory, MemoryError, MemoryType, Pages, Store};
+    /// # let store = Store::default();
+    /// #
+    /// let m = Memory::new(&store, MemoryType::new(1, Some(3), false)).unwrap();
+    /// assert_eq!(m.grow(Pages(2)).unwrap(), Pages(1));
+    /// assert_eq!(m.size(), Pages(3));
+    ///
+    /// // The maximum is reached.
+    /// assert!(matches!(m.grow(Pages(1)), Err(MemoryError::CouldNotGrow { .. })));
+    /// assert_eq!(m.size(), Pages(3));
+    /// ```
+    pub fn grow(&self, delta: Pages) -> Result<Pages, MemoryError> {
+        self.vm_memory.from.grow(delta)
+    }
+
+    /// Give the physical memory of the `len` bytes at `offset` back to the operating system.
+    /// The bytes stay accessible and read as zeros until written again.
+    ///
+    /// The range must be made of whole pages of the host, which WebAssembly pages always are,
+    /// or [`MemoryError::InvalidRange`] is returned. WebAssembly may keep accessing the memory
+    /// meanwhile: it reads either the old contents of the range or zeros.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{Memory, MemoryType, Store, WASM_PAGE_SIZE};
+    /// # let store = Store::default();
+    /// #
+    /// let m = Memory::new(&store, MemoryType::new(1, None, false)).unwrap();
+    /// m.view::<u8>()[0].set(42);
+    /// m.discard_range(0, WASM_PAGE_SIZE as u64).unwrap();
+    ///
+    /// assert_eq!(m.view::<u8>()[0].get(), 0);
+    /// ```
+    pub fn discard_range(&self, offset: u64, len: u64) -> Result<(), MemoryError> {
+        let invalid_range = MemoryError::InvalidRange { offset, len };
+        let offset = offset.try_into().map_err(|_| invalid_range.clone())?;
+        let len = len.try_into().map_err(|_| invalid_range)?;
+        self.vm_memory.from.discard(offset, len)
+    }
+
+    /// Returns the number of bytes of the `Memory` resident in physical memory, in whole
+    /// native pages. Where the operating system does not tell, all of the memory is assumed
+    /// to be.
+    pub fn resident_bytes(&self) -> u64 {
+        self.vm_memory.from.resident_bytes() as u64
+    }
+
     /// Return a "view" of the currently accessible memory. By
     /// default, the view is unsynchronized, using regular memory
     /// accesses. You can force a memory view to use atomic accesses
     /// by calling the [`MemoryView::atomically`] method.
     ///
+    /// The view covers the memory as it is when it is taken. If the memory may move when it
+    /// grows, see [`Memory::base_stability`], take a view anew after anything that may grow
+    /// it, or take it from a [`MemoryPin`].
+    ///
     /// # Notes:
     ///
     /// This method is safe (as in, it won't cause the host to crash or have UB),
     /// but it doesn't obey rust's rules involving data races, especially concurrent ones.
     /// Therefore, if this memory is shared between multiple threads, a single memory
     /// location can be mutated concurrently without synchronization.
+    /// Views read from several threads at once are fine as long as nothing writes the memory
+    /// or grows it meanwhile, such as when no WebAssembly code using it runs.
     ///
     /// # Usage:
     ///
@@ -213,6 +320,33 @@ impl Memory {
     }
 }
 
+/// A pin keeping the base of a [`Memory`] where it is, returned by [`Memory::pin`].
+///
+/// Views taken from the pin borrow it, so that they cannot outlive it.
+#[derive(Debug)]
+pub struct MemoryPin<'a> {
+    memory: &'a Memory,
+}
+
+impl<'a> MemoryPin<'a> {
+    /// Returns the pointer to the raw bytes of the pinned memory, which does not change while
+    /// the pin lives.
+    pub fn data_ptr(&self) -> *mut u8 {
+        self.memory.data_ptr()
+    }
+
+    /// Return a view of the currently accessible memory, valid for as long as the pin lives.
+    pub fn view<T: ValueType>(&self) -> MemoryView<'_, T> {
+        self.memory.view()
+    }
+}
+
+impl<'a> Drop for MemoryPin<'a> {
+    fn drop(&mut self) {
+        self.memory.vm_memory.from.unpin_base();
+    }
+}
+
 impl Clone for Memory {
     fn clone(&self) -> Self {
         let mut vm_memory = self.vm_memory.clone();
@@ -229,4 +363,10 @@ impl<'a> Exportable<'a> for Memory {
     fn to_export(&self) -> Export {
         self.vm_memory.clone().into()
     }
+
+    fn into_weak_instance_ref(&mut self) {
+        if let Some(instance_ref) = &mut self.vm_memory.instance_ref {
+            *instance_ref = instance_ref.downgrade();
+        }
+    }
 }
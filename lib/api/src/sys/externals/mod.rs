@@ -4,11 +4,12 @@ mod memory;
 mod table;
 
 pub use self::function::{
-    FromToNativeWasmType, Function, HostFunction, WasmTypeList, WithEnv, WithoutEnv,
+    FromToNativeWasmType, Function, HostFunction, ResultArityError, WasmTypeList, WithEnv,
+    WithoutEnv,
 };
 
 pub use self::global::Global;
-pub use self::memory::Memory;
+pub use self::memory::{Memory, MemoryPin};
 pub use self::table::Table;
 
 use crate::sys::exports::Exportable;
@@ -53,6 +54,15 @@ impl<'a> Exportable<'a> for Extern {
             Self::Table(t) => t.to_export(),
         }
     }
+
+    fn into_weak_instance_ref(&mut self) {
+        match self {
+            Self::Function(f) => f.into_weak_instance_ref(),
+            Self::Global(g) => g.into_weak_instance_ref(),
+            Self::Memory(m) => m.into_weak_instance_ref(),
+            Self::Table(t) => t.into_weak_instance_ref(),
+        }
+    }
 }
 
 impl StoreObject for Extern {
@@ -4,7 +4,7 @@ use crate::sys::types::{Val, ValFuncRef};
 use crate::sys::RuntimeError;
 use crate::sys::TableType;
 use std::sync::Arc;
-use wasmer_vm::{Export, Table as RuntimeTable, TableElement, VMTable};
+use wasmer_vm::{Export, FuncOrigin, Table as RuntimeTable, TableElement, VMTable};
 
 /// A WebAssembly `table` instance.
 ///
@@ -28,6 +28,19 @@ fn set_table_item(
     table.set(item_index, item).map_err(|e| e.into())
 }
 
+/// Converts `val` into an element of a table of type `ty`, checking that the table accepts
+/// it.
+fn table_element(store: &Store, ty: &TableType, val: &Val) -> Result<TableElement, RuntimeError> {
+    if val.ty() != ty.ty {
+        return Err(RuntimeError::new(format!(
+            "a table of {} cannot hold a value of type {}",
+            ty.ty,
+            val.ty()
+        )));
+    }
+    val.into_table_reference(store)
+}
+
 impl Table {
     /// Creates a new `Table` with the provided [`TableType`] definition.
     ///
@@ -36,7 +49,7 @@ impl Table {
     /// This function will construct the `Table` using the store
     /// [`BaseTunables`][crate::sys::BaseTunables].
     pub fn new(store: &Store, ty: TableType, init: Val) -> Result<Self, RuntimeError> {
-        let item = init.into_table_reference(store)?;
+        let item = table_element(store, &ty, &init)?;
         let tunables = store.tunables();
         let style = tunables.table_style(&ty);
         let table = tunables
@@ -72,6 +85,60 @@ impl Table {
         self.vm_table.from.size()
     }
 
+    /// Retrieves the element of the `Table` at `index`, or `None` if the index is out of
+    /// bounds.
+    ///
+    /// Functions retrieved from the table must not be called once the instances they come
+    /// from are dropped.
+    pub fn get(&self, index: u32) -> Option<Val> {
+        let item = self.vm_table.from.get(index)?;
+        Some(unsafe { ValFuncRef::from_table_reference(item, &self.store) })
+    }
+
+    /// Sets the element of the `Table` at `index` to `val`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with a [`TrapCode::TableAccessOutOfBounds`] trap if the index is out of bounds,
+    /// and with an error if `val` does not have the type of the elements of the table or
+    /// is a function from another store.
+    ///
+    /// [`TrapCode::TableAccessOutOfBounds`]: crate::TrapCode::TableAccessOutOfBounds
+    pub fn set(&self, index: u32, val: Val) -> Result<(), RuntimeError> {
+        let item = table_element(&self.store, self.ty(), &val)?;
+        set_table_item(self.vm_table.from.as_ref(), index, item)
+    }
+
+    /// Grows the `Table` by `delta` elements, all set to `init`, returning its previous
+    /// size.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the table would grow past its maximum size, and like [`Table::set`] for
+    /// values it does not accept.
+    pub fn grow(&self, delta: u32, init: Val) -> Result<u32, RuntimeError> {
+        let item = table_element(&self.store, self.ty(), &init)?;
+        self.vm_table.from.grow(delta, item).ok_or_else(|| {
+            RuntimeError::new(format!(
+                "failed to grow the table of {} elements by {}",
+                self.size(),
+                delta
+            ))
+        })
+    }
+
+    /// Returns where the function reference at `index` comes from, or `None` if the index is
+    /// out of bounds or the element is null or not a function reference.
+    ///
+    /// The origin belongs to the reference itself, so it follows the reference through
+    /// `table.set`, `table.copy` and element segments, whichever table they write to.
+    pub fn origin(&self, index: u32) -> Option<FuncOrigin> {
+        match self.vm_table.from.get(index)? {
+            TableElement::FuncRef(funcref) => FuncOrigin::of(funcref),
+            TableElement::ExternRef(_) => None,
+        }
+    }
+
     pub(crate) fn from_vm_export(store: &Store, vm_table: VMTable) -> Self {
         Self {
             store: store.clone(),
@@ -113,4 +180,10 @@ impl<'a> Exportable<'a> for Table {
     fn to_export(&self) -> Export {
         self.vm_table.clone().into()
     }
+
+    fn into_weak_instance_ref(&mut self) {
+        if let Some(instance_ref) = &mut self.vm_table.instance_ref {
+            *instance_ref = instance_ref.downgrade();
+        }
+    }
 }
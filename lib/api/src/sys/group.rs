@@ -0,0 +1,442 @@
+//! Groups of instances wired together, moved between threads as one unit.
+
+use crate::sys::instance::{Instance, InstantiationError};
+use crate::sys::module::Module;
+use crate::sys::{Extern, ExternType};
+use std::cell::RefCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{Arc, Weak};
+use thiserror::Error;
+use wasmer_types::{Classify, ErrorCode, FailureKind, HasErrorCode};
+use wasmer_vm::{Export, Resolver, WeakInstanceRef, WeakOrStrongInstanceRef};
+
+/// An import of a member of an [`InstanceGroup`] resolved to something the group moves along
+/// with its members: an export of another member, or a memory given by the host.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupReference {
+    /// The importing member.
+    pub importer: usize,
+    /// The module name of the import.
+    pub module: String,
+    /// The field name of the import.
+    pub field: String,
+    /// The member exporting the import, or `None` for a memory given by the host.
+    pub provider: Option<usize>,
+    /// The type of the import.
+    pub ty: ExternType,
+}
+
+/// A handle held outside of an [`InstanceGroup`], keeping it from moving.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GroupOffender {
+    /// Clones of the [`Instance`] of a member.
+    Instance {
+        /// The member.
+        member: usize,
+        /// The number of clones.
+        handles: usize,
+    },
+    /// Exports looked up from a member, such as [`Function`](crate::Function)s, including
+    /// those held by import objects.
+    Exports {
+        /// The member.
+        member: usize,
+        /// The number of exports.
+        handles: usize,
+    },
+    /// Handles to a memory the host gave to members.
+    Memory {
+        /// The module name of the first import of the memory.
+        module: String,
+        /// The field name of the first import of the memory.
+        field: String,
+        /// The number of handles.
+        handles: usize,
+    },
+}
+
+impl fmt::Display for GroupOffender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Instance { member, handles } => {
+                write!(f, "{} handle(s) to member {}", handles, member)
+            }
+            Self::Exports { member, handles } => {
+                write!(f, "{} export(s) of member {}", handles, member)
+            }
+            Self::Memory {
+                module,
+                field,
+                handles,
+            } => write!(
+                f,
+                "{} handle(s) to the memory imported as {}.{}",
+                handles, module, field
+            ),
+        }
+    }
+}
+
+/// An error preparing an [`InstanceGroup`] to move to another thread.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum GroupMoveError {
+    /// Calls into members are running.
+    #[error(
+        "calls into members {members:?} of the group are running [{}]",
+        ErrorCode::GroupCallInFlight
+    )]
+    CallInFlight {
+        /// The members being called.
+        members: Vec<usize>,
+    },
+    /// Handles to members or to their exports are held outside of the group.
+    #[error(
+        "handles escape the group: {} [{}]",
+        .offenders.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "),
+        ErrorCode::GroupEscaped
+    )]
+    Escaped {
+        /// The handles held outside of the group.
+        offenders: Vec<GroupOffender>,
+    },
+}
+
+impl HasErrorCode for GroupMoveError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::CallInFlight { .. } => ErrorCode::GroupCallInFlight,
+            Self::Escaped { .. } => ErrorCode::GroupEscaped,
+        }
+    }
+}
+
+impl Classify for GroupMoveError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            // The move succeeds once the calls return.
+            Self::CallInFlight { .. } => FailureKind::TransientInterrupted,
+            Self::Escaped { .. } => FailureKind::Permanent,
+        }
+    }
+}
+
+#[derive(Default)]
+struct GroupState {
+    members: Vec<Instance>,
+    references: Vec<GroupReference>,
+    host_memories: Vec<HostMemory>,
+}
+
+/// A memory imported by members but not defined by any.
+struct HostMemory {
+    memory: Weak<dyn wasmer_vm::Memory>,
+    /// The number of imports of the memory by members, each holding a reference to it.
+    imports: usize,
+    /// The names of the first import of the memory.
+    module: String,
+    field: String,
+}
+
+/// Instances wired together, through a memory they share or functions they import from each
+/// other, that move between threads as one unit.
+///
+/// The group is bound to its thread. Moving it takes [`InstanceGroup::prepare_move`], which
+/// checks that no call into a member is running and that nothing outside of the group refers
+/// to the members, then [`MovableGroup::activate`] on the destination thread.
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let store = Store::default();
+/// let counter = Module::new(&store, r#"
+///     (module
+///         (global $n (mut i32) (i32.const 0))
+///         (func (export "next") (result i32)
+///             (global.set $n (i32.add (global.get $n) (i32.const 1)))
+///             (global.get $n)))
+/// "#)?;
+/// let user = Module::new(&store, r#"
+///     (module
+///         (import "counter" "next" (func $next (result i32)))
+///         (func (export "run") (result i32) (call $next)))
+/// "#)?;
+/// let mut group = InstanceGroup::new();
+/// let counter = group.instantiate(&counter, &imports! {})?;
+/// let imports = imports! {
+///     "counter" => { "next" => group.instance(counter).lookup_function("next").unwrap() },
+/// };
+/// let user = group.instantiate(&user, &imports)?;
+/// drop(imports);
+///
+/// let movable = group.prepare_move()?;
+/// std::thread::spawn(move || {
+///     let group = movable.activate();
+///     let run = group.instance(user).get_native_function::<(), i32>("run").unwrap();
+///     assert_eq!(run.call().unwrap(), 1);
+/// })
+/// .join()
+/// .unwrap();
+/// # Ok(())
+/// # }
+/// ```
+pub struct InstanceGroup {
+    state: GroupState,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl InstanceGroup {
+    /// Create an empty group.
+    pub fn new() -> Self {
+        Self::from_state(GroupState::default())
+    }
+
+    fn from_state(state: GroupState) -> Self {
+        Self {
+            state,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Instantiate `module` as a new member of the group, resolving its imports with
+    /// `resolver`, and return the index of the member.
+    ///
+    /// The imports resolved to an export of another member, or to a memory, are recorded as
+    /// the [`references`](Self::references) of the group. Memories given by the host are
+    /// moved along with the group, and must only be imported by its members.
+    pub fn instantiate(
+        &mut self,
+        module: &Module,
+        resolver: &dyn Resolver,
+    ) -> Result<usize, InstantiationError> {
+        let importer = self.state.members.len();
+        let members = self
+            .state
+            .members
+            .iter()
+            .map(|member| member.vm_handle().downgrade())
+            .collect::<Vec<_>>();
+        let tracer = TracingResolver {
+            module,
+            resolver,
+            members: &members,
+            importer,
+            traced: Default::default(),
+        };
+        let instance = Instance::new(module, &tracer)?;
+        for (reference, memory) in tracer.traced.into_inner() {
+            if let Some(memory) = memory {
+                match self.state.host_memories.iter_mut().find(|other| {
+                    other.memory.as_ptr() as *const u8 == memory.as_ptr() as *const u8
+                }) {
+                    Some(other) => other.imports += 1,
+                    None => self.state.host_memories.push(HostMemory {
+                        memory,
+                        imports: 1,
+                        module: reference.module.clone(),
+                        field: reference.field.clone(),
+                    }),
+                }
+            }
+            self.state.references.push(reference);
+        }
+        self.state.members.push(instance);
+        Ok(importer)
+    }
+
+    /// Return the instance of `member`.
+    ///
+    /// # Panics
+    ///
+    /// If `member` is not a member of the group.
+    pub fn instance(&self, member: usize) -> &Instance {
+        &self.state.members[member]
+    }
+
+    /// Return the number of members.
+    pub fn len(&self) -> usize {
+        self.state.members.len()
+    }
+
+    /// Return whether the group has no members.
+    pub fn is_empty(&self) -> bool {
+        self.state.members.is_empty()
+    }
+
+    /// Return the imports of the members resolved to an export of another member, or to a
+    /// memory given by the host, in the order they were resolved.
+    pub fn references(&self) -> &[GroupReference] {
+        &self.state.references
+    }
+
+    /// Check that the group can move to another thread and return it in a form that can.
+    ///
+    /// On success, the members move to the returned group and this one is left empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GroupMoveError::CallInFlight`] if a call into a member is running, and
+    /// [`GroupMoveError::Escaped`] if clones of the instance of a member, exports of a member
+    /// or handles to a memory given by the host are held outside of the group. The group is
+    /// left as is then.
+    pub fn prepare_move(&mut self) -> Result<MovableGroup, GroupMoveError> {
+        let mut calling = Vec::new();
+        let mut offenders = Vec::new();
+        for (member, instance) in self.state.members.iter().enumerate() {
+            let handle = instance.vm_handle();
+            if handle.usage().in_call {
+                calling.push(member);
+            }
+            if instance.handle_count() > 1 {
+                offenders.push(GroupOffender::Instance {
+                    member,
+                    handles: instance.handle_count() - 1,
+                });
+            }
+            if handle.strong_count() > 1 {
+                offenders.push(GroupOffender::Exports {
+                    member,
+                    handles: handle.strong_count() - 1,
+                });
+            }
+        }
+        for memory in &self.state.host_memories {
+            let handles = memory.memory.strong_count().saturating_sub(memory.imports);
+            if handles > 0 {
+                offenders.push(GroupOffender::Memory {
+                    module: memory.module.clone(),
+                    field: memory.field.clone(),
+                    handles,
+                });
+            }
+        }
+        if !calling.is_empty() {
+            return Err(GroupMoveError::CallInFlight { members: calling });
+        }
+        if !offenders.is_empty() {
+            return Err(GroupMoveError::Escaped { offenders });
+        }
+        Ok(MovableGroup {
+            state: std::mem::take(&mut self.state),
+        })
+    }
+}
+
+impl Default for InstanceGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for InstanceGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstanceGroup")
+            .field("members", &self.state.members.len())
+            .field("references", &self.state.references)
+            .finish()
+    }
+}
+
+/// An [`InstanceGroup`] on its way to another thread, see [`InstanceGroup::prepare_move`].
+pub struct MovableGroup {
+    state: GroupState,
+}
+
+impl MovableGroup {
+    /// Bind the group to the current thread.
+    ///
+    /// The runtime keeps no state on a thread between calls: what a call needs on its thread,
+    /// such as where to unwind to when it traps or the values too many to pass on the stack,
+    /// is set up when the call starts. Calls into the members on the current thread are thus
+    /// ready to run.
+    pub fn activate(self) -> InstanceGroup {
+        InstanceGroup::from_state(self.state)
+    }
+
+    /// Return the number of members.
+    pub fn len(&self) -> usize {
+        self.state.members.len()
+    }
+
+    /// Return whether the group has no members.
+    pub fn is_empty(&self) -> bool {
+        self.state.members.is_empty()
+    }
+}
+
+impl fmt::Debug for MovableGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MovableGroup")
+            .field("members", &self.state.members.len())
+            .finish()
+    }
+}
+
+/// A traced import, with the memory it resolved to if no other member exports it.
+type TracedImport = (GroupReference, Option<Weak<dyn wasmer_vm::Memory>>);
+
+/// Resolves the imports of a new member with the resolver of the user, recording those
+/// resolved to the exports of other members and to memories.
+struct TracingResolver<'a> {
+    module: &'a Module,
+    resolver: &'a dyn Resolver,
+    members: &'a [WeakInstanceRef],
+    importer: usize,
+    traced: RefCell<Vec<TracedImport>>,
+}
+
+impl TracingResolver<'_> {
+    fn trace(&self, module: &str, field: &str, export: Export) -> Export {
+        let (instance_ref, memory) = match &export {
+            Export::Function(f) => (&f.vm_function.instance_ref, None),
+            Export::Table(t) => (&t.instance_ref, None),
+            Export::Memory(m) => (&m.instance_ref, Some(Arc::downgrade(&m.from))),
+            Export::Global(g) => (&g.instance_ref, None),
+        };
+        let provider = instance_ref.as_ref().and_then(|instance_ref| {
+            let instance_ref = WeakInstanceRef::from(WeakOrStrongInstanceRef::clone(instance_ref));
+            self.members
+                .iter()
+                .position(|member| *member == instance_ref)
+        });
+        let memory = match (provider, memory) {
+            (Some(_), _) => None,
+            (None, Some(memory)) => Some(memory),
+            (None, None) => return export,
+        };
+        let ty = match Extern::from_vm_export(self.module.store(), export.clone()) {
+            Extern::Function(function) => ExternType::Function(function.ty()),
+            Extern::Memory(memory) => ExternType::Memory(memory.ty()),
+            Extern::Global(global) => ExternType::Global(*global.ty()),
+            Extern::Table(table) => ExternType::Table(*table.ty()),
+        };
+        self.traced.borrow_mut().push((
+            GroupReference {
+                importer: self.importer,
+                module: module.to_string(),
+                field: field.to_string(),
+                provider,
+                ty,
+            },
+            memory,
+        ));
+        export
+    }
+}
+
+impl Resolver for TracingResolver<'_> {
+    fn resolve(&self, index: u32, module: &str, field: &str) -> Option<Export> {
+        let export = self.resolver.resolve(index, module, field)?;
+        Some(self.trace(module, field, export))
+    }
+
+    fn try_resolve(
+        &self,
+        index: u32,
+        module: &str,
+        field: &str,
+    ) -> Result<Option<Export>, Box<dyn std::error::Error + Send + Sync>> {
+        let export = self.resolver.try_resolve(index, module, field)?;
+        Ok(export.map(|export| self.trace(module, field, export)))
+    }
+}
@@ -0,0 +1,426 @@
+//! Reading and writing Rust values in guest memory with the layout of the guest's structs.
+
+use crate::sys::ptr::WasmPtr;
+use std::cell::Cell;
+use std::convert::TryInto;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Range;
+use thiserror::Error;
+use wasmer_types::{ErrorCode, HasErrorCode, MemoryView};
+
+/// An error raised by [`GuestType`] when reading or writing guest memory.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum GuestTypeError {
+    /// The offset is not aligned for the type stored there.
+    #[error(
+        "guest offset 0x{offset:x} is not aligned to {align} bytes [{}]",
+        ErrorCode::GuestMisaligned
+    )]
+    Misaligned {
+        /// The offset in guest memory.
+        offset: u64,
+        /// The alignment required by the type.
+        align: usize,
+    },
+    /// The accessed bytes are out of the bounds of the memory.
+    #[error("guest range 0x{offset:x}+0x{len:x} is out of the bounds of the memory (0x{size:x} bytes) [{}]", ErrorCode::GuestOutOfBounds)]
+    OutOfBounds {
+        /// The offset in guest memory.
+        offset: u64,
+        /// The number of bytes accessed.
+        len: u64,
+        /// The size of the memory in bytes.
+        size: u64,
+    },
+    /// A buffer is shorter than the value it should hold.
+    #[error(
+        "a buffer of {len} bytes cannot hold {needed} bytes [{}]",
+        ErrorCode::GuestShortBuffer
+    )]
+    ShortBuffer {
+        /// The number of bytes needed.
+        needed: u64,
+        /// The number of bytes of the buffer.
+        len: u64,
+    },
+    /// The bytes do not encode a value of the type, such as a `bool` other than 0 or 1.
+    #[error("invalid {ty} in guest memory [{}]", ErrorCode::GuestInvalidValue)]
+    InvalidValue {
+        /// The type of the value.
+        ty: &'static str,
+    },
+    /// A [`GuestString`] is not valid UTF-8.
+    #[error(
+        "guest string at 0x{offset:x} is not valid UTF-8 [{}]",
+        ErrorCode::GuestInvalidUtf8
+    )]
+    InvalidUtf8 {
+        /// The offset of the string in guest memory.
+        offset: u64,
+    },
+}
+
+impl HasErrorCode for GuestTypeError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Misaligned { .. } => ErrorCode::GuestMisaligned,
+            Self::OutOfBounds { .. } => ErrorCode::GuestOutOfBounds,
+            Self::ShortBuffer { .. } => ErrorCode::GuestShortBuffer,
+            Self::InvalidValue { .. } => ErrorCode::GuestInvalidValue,
+            Self::InvalidUtf8 { .. } => ErrorCode::GuestInvalidUtf8,
+        }
+    }
+}
+
+impl GuestTypeError {
+    /// Return the first `size` bytes of `bytes`, or a [`GuestTypeError::ShortBuffer`] if
+    /// there are fewer.
+    pub fn check_len(bytes: &[u8], size: usize) -> Result<&[u8], Self> {
+        bytes.get(..size).ok_or(Self::ShortBuffer {
+            needed: size as u64,
+            len: bytes.len() as u64,
+        })
+    }
+
+    /// As [`check_len`](Self::check_len), for a buffer to write to.
+    pub fn check_len_mut(bytes: &mut [u8], size: usize) -> Result<&mut [u8], Self> {
+        let len = bytes.len() as u64;
+        bytes.get_mut(..size).ok_or(Self::ShortBuffer {
+            needed: size as u64,
+            len,
+        })
+    }
+}
+
+/// A type stored in guest memory, with an explicit layout: scalars are little-endian, and
+/// structs are laid out like the C structs of the guest.
+///
+/// It is implemented for the integer and floating point types, `bool`, arrays, [`WasmPtr`],
+/// [`GuestVec`] and [`GuestString`], and derived for structs whose fields implement it:
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// # let store = Store::default();
+/// # let memory = Memory::new(&store, MemoryType::new(1, None, false))?;
+/// #[derive(GuestType, Debug, PartialEq)]
+/// struct Transfer {
+///     kind: u8,
+///     amount: u64,
+///     receiver: [u8; 4],
+/// }
+///
+/// // The C layout pads `kind` to the alignment of `amount`.
+/// assert_eq!((Transfer::SIZE, Transfer::ALIGN), (24, 8));
+/// let transfer = Transfer { kind: 1, amount: 500, receiver: *b"bob!" };
+/// let view = memory.view::<u8>();
+/// transfer.write_to(&view, 0x100)?;
+/// assert_eq!(Transfer::read_from(&view, 0x100)?, transfer);
+/// assert_eq!(
+///     Transfer::read_from(&view, 0x104).unwrap_err().to_string(),
+///     "guest offset 0x104 is not aligned to 8 bytes [W0650]",
+/// );
+/// # Ok(())
+/// # }
+/// ```
+///
+/// `#[guest(packed)]` lays the fields out without padding, like a packed C struct, with an
+/// alignment of 1. `#[guest(layout = "C")]` states the default layout explicitly.
+///
+/// References cannot be stored in guest memory, guest pointers are [`WasmPtr`]s:
+///
+/// ```compile_fail
+/// # use wasmer::*;
+/// #[derive(GuestType)]
+/// struct Borrowed<'a> {
+///     name: &'a str,
+/// }
+/// ```
+pub trait GuestType: Sized {
+    /// The size of the value in guest memory, in bytes.
+    const SIZE: usize;
+    /// The alignment of the value in guest memory, in bytes.
+    const ALIGN: usize;
+
+    /// Decode a value from the first [`SIZE`](Self::SIZE) bytes of `bytes`.
+    fn decode(bytes: &[u8]) -> Result<Self, GuestTypeError>;
+
+    /// Encode the value into the first [`SIZE`](Self::SIZE) bytes of `bytes`.
+    fn encode(&self, bytes: &mut [u8]) -> Result<(), GuestTypeError>;
+
+    /// Read the value at `offset`, which must be aligned to [`ALIGN`](Self::ALIGN).
+    fn read_from(view: &MemoryView<u8>, offset: u32) -> Result<Self, GuestTypeError> {
+        let cells = cells(view, offset, Self::SIZE, Self::ALIGN)?;
+        let bytes = cells.iter().map(Cell::get).collect::<Vec<_>>();
+        Self::decode(&bytes)
+    }
+
+    /// Write the value at `offset`, which must be aligned to [`ALIGN`](Self::ALIGN).
+    ///
+    /// Nothing is written if the value cannot be written entirely.
+    fn write_to(&self, view: &MemoryView<u8>, offset: u32) -> Result<(), GuestTypeError> {
+        let cells = cells(view, offset, Self::SIZE, Self::ALIGN)?;
+        let mut bytes = vec![0; Self::SIZE];
+        self.encode(&mut bytes)?;
+        for (cell, byte) in cells.iter().zip(bytes) {
+            cell.set(byte);
+        }
+        Ok(())
+    }
+}
+
+/// Return the range of `len` bytes at `offset`, checking that it is aligned to `align` and
+/// within the bounds of the memory.
+fn range(
+    view: &MemoryView<u8>,
+    offset: u32,
+    len: u64,
+    align: usize,
+) -> Result<Range<usize>, GuestTypeError> {
+    if offset as usize % align.max(1) != 0 {
+        return Err(GuestTypeError::Misaligned {
+            offset: offset.into(),
+            align,
+        });
+    }
+    let size = view.len() as u64;
+    match u64::from(offset).checked_add(len) {
+        Some(end) if end <= size => Ok(offset as usize..end as usize),
+        _ => Err(GuestTypeError::OutOfBounds {
+            offset: offset.into(),
+            len,
+            size,
+        }),
+    }
+}
+
+pub(crate) fn cells<'a>(
+    view: &'a MemoryView<u8>,
+    offset: u32,
+    len: usize,
+    align: usize,
+) -> Result<&'a [Cell<u8>], GuestTypeError> {
+    Ok(&view[range(view, offset, len as u64, align)?])
+}
+
+macro_rules! scalar {
+    ($($ty:ty),*) => {
+        $(
+            impl GuestType for $ty {
+                const SIZE: usize = std::mem::size_of::<$ty>();
+                const ALIGN: usize = std::mem::size_of::<$ty>();
+
+                fn decode(bytes: &[u8]) -> Result<Self, GuestTypeError> {
+                    let bytes = GuestTypeError::check_len(bytes, Self::SIZE)?;
+                    Ok(Self::from_le_bytes(bytes.try_into().unwrap()))
+                }
+
+                fn encode(&self, bytes: &mut [u8]) -> Result<(), GuestTypeError> {
+                    let bytes = GuestTypeError::check_len_mut(bytes, Self::SIZE)?;
+                    bytes.copy_from_slice(&self.to_le_bytes());
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+scalar!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+
+impl GuestType for bool {
+    const SIZE: usize = 1;
+    const ALIGN: usize = 1;
+
+    fn decode(bytes: &[u8]) -> Result<Self, GuestTypeError> {
+        match u8::decode(bytes)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(GuestTypeError::InvalidValue { ty: "bool" }),
+        }
+    }
+
+    fn encode(&self, bytes: &mut [u8]) -> Result<(), GuestTypeError> {
+        u8::from(*self).encode(bytes)
+    }
+}
+
+impl<T: GuestType, const N: usize> GuestType for [T; N] {
+    const SIZE: usize = T::SIZE * N;
+    const ALIGN: usize = T::ALIGN;
+
+    fn decode(bytes: &[u8]) -> Result<Self, GuestTypeError> {
+        let bytes = GuestTypeError::check_len(bytes, Self::SIZE)?;
+        let items = (0..N)
+            .map(|index| T::decode(&bytes[index * T::SIZE..]))
+            .collect::<Result<Vec<_>, _>>()?;
+        match items.try_into() {
+            Ok(items) => Ok(items),
+            Err(_) => unreachable!("{} items were decoded", N),
+        }
+    }
+
+    fn encode(&self, bytes: &mut [u8]) -> Result<(), GuestTypeError> {
+        let bytes = GuestTypeError::check_len_mut(bytes, Self::SIZE)?;
+        for (index, item) in self.iter().enumerate() {
+            item.encode(&mut bytes[index * T::SIZE..])?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Copy, Ty> GuestType for WasmPtr<T, Ty> {
+    const SIZE: usize = 4;
+    const ALIGN: usize = 4;
+
+    fn decode(bytes: &[u8]) -> Result<Self, GuestTypeError> {
+        Ok(Self::new(u32::decode(bytes)?))
+    }
+
+    fn encode(&self, bytes: &mut [u8]) -> Result<(), GuestTypeError> {
+        self.offset().encode(bytes)
+    }
+}
+
+/// A guest pointer to `len` values of type `T`, stored in guest memory as the pointer then the
+/// length, both as `u32`.
+pub struct GuestVec<T> {
+    /// The offset of the first value in guest memory.
+    pub ptr: u32,
+    /// The number of values.
+    pub len: u32,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: GuestType> GuestVec<T> {
+    /// Create a pointer to the `len` values at `ptr`.
+    pub fn new(ptr: u32, len: u32) -> Self {
+        Self {
+            ptr,
+            len,
+            _phantom: PhantomData,
+        }
+    }
+
+    fn range(&self, view: &MemoryView<u8>) -> Result<Range<usize>, GuestTypeError> {
+        let len = (T::SIZE as u64).checked_mul(self.len.into());
+        range(view, self.ptr, len.unwrap_or(u64::MAX), T::ALIGN)
+    }
+
+    /// Read the values.
+    pub fn read(&self, view: &MemoryView<u8>) -> Result<Vec<T>, GuestTypeError> {
+        let range = self.range(view)?;
+        let bytes = view[range].iter().map(Cell::get).collect::<Vec<_>>();
+        (0..self.len as usize)
+            .map(|index| T::decode(&bytes[index * T::SIZE..]))
+            .collect()
+    }
+
+    /// Write `values` at the start of the vector, which must hold at least as many.
+    ///
+    /// Nothing is written if the values cannot be written entirely.
+    pub fn write(&self, view: &MemoryView<u8>, values: &[T]) -> Result<(), GuestTypeError> {
+        let mut range = self.range(view)?;
+        let len = values.len() * T::SIZE;
+        if len > range.len() {
+            return Err(GuestTypeError::ShortBuffer {
+                needed: len as u64,
+                len: range.len() as u64,
+            });
+        }
+        range.end = range.start + len;
+        let mut bytes = vec![0; len];
+        for (index, value) in values.iter().enumerate() {
+            value.encode(&mut bytes[index * T::SIZE..])?;
+        }
+        for (cell, byte) in view[range].iter().zip(bytes) {
+            cell.set(byte);
+        }
+        Ok(())
+    }
+}
+
+impl<T> Clone for GuestVec<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for GuestVec<T> {}
+
+impl<T> PartialEq for GuestVec<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.ptr, self.len) == (other.ptr, other.len)
+    }
+}
+
+impl<T> Eq for GuestVec<T> {}
+
+impl<T> fmt::Debug for GuestVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GuestVec")
+            .field("ptr", &self.ptr)
+            .field("len", &self.len)
+            .finish()
+    }
+}
+
+impl<T: GuestType> GuestType for GuestVec<T> {
+    const SIZE: usize = 8;
+    const ALIGN: usize = 4;
+
+    fn decode(bytes: &[u8]) -> Result<Self, GuestTypeError> {
+        let bytes = GuestTypeError::check_len(bytes, Self::SIZE)?;
+        Ok(Self::new(u32::decode(bytes)?, u32::decode(&bytes[4..])?))
+    }
+
+    fn encode(&self, bytes: &mut [u8]) -> Result<(), GuestTypeError> {
+        let bytes = GuestTypeError::check_len_mut(bytes, Self::SIZE)?;
+        self.ptr.encode(bytes)?;
+        self.len.encode(&mut bytes[4..])
+    }
+}
+
+/// A guest pointer to a UTF-8 string of `len` bytes, stored in guest memory as the pointer
+/// then the length, both as `u32`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GuestString {
+    /// The offset of the string in guest memory.
+    pub ptr: u32,
+    /// The length of the string in bytes.
+    pub len: u32,
+}
+
+impl GuestString {
+    /// Create a pointer to the string of `len` bytes at `ptr`.
+    pub fn new(ptr: u32, len: u32) -> Self {
+        Self { ptr, len }
+    }
+
+    /// Read the string, which must be valid UTF-8.
+    pub fn read(&self, view: &MemoryView<u8>) -> Result<String, GuestTypeError> {
+        let bytes = GuestVec::<u8>::new(self.ptr, self.len).read(view)?;
+        String::from_utf8(bytes).map_err(|_| GuestTypeError::InvalidUtf8 {
+            offset: self.ptr.into(),
+        })
+    }
+
+    /// Write `string` at the start of this string, which must be at least as long.
+    pub fn write(&self, view: &MemoryView<u8>, string: &str) -> Result<(), GuestTypeError> {
+        GuestVec::<u8>::new(self.ptr, self.len).write(view, string.as_bytes())
+    }
+}
+
+impl GuestType for GuestString {
+    const SIZE: usize = 8;
+    const ALIGN: usize = 4;
+
+    fn decode(bytes: &[u8]) -> Result<Self, GuestTypeError> {
+        let vec = GuestVec::<u8>::decode(bytes)?;
+        Ok(Self::new(vec.ptr, vec.len))
+    }
+
+    fn encode(&self, bytes: &mut [u8]) -> Result<(), GuestTypeError> {
+        GuestVec::<u8>::new(self.ptr, self.len).encode(bytes)
+    }
+}
@@ -1,10 +1,24 @@
+use crate::sys::cancellation::{self, CallHandle, GuardedCall};
+use crate::sys::execution_plan::{ExecutionPlan, Guards, PlanReport};
 use crate::sys::module::Module;
-use crate::sys::{HostEnvInitError, LinkError, RuntimeError};
+use crate::sys::overrides::{self, OverridableResolver};
+use crate::sys::store::Store;
+use crate::sys::{
+    ExternalDataError, Function, HostEnvInitError, LinkError, OverrideError, PlanError,
+    RuntimeError, Val,
+};
 use crate::{ExportError, NativeFunc, WasmTypeList};
-use std::sync::{Arc, Mutex};
+use std::ffi::c_void;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
 use thiserror::Error;
-use wasmer_types::InstanceConfig;
-use wasmer_vm::{InstanceHandle, Resolver};
+use wasmer_types::{
+    Classify, ErrorCode, FailureKind, FastGasCounter, HasErrorCode, InstanceConfig, MemoryIndex,
+};
+use wasmer_vm::{
+    DiagnosticsLevel, InstanceHandle, InstanceId, InstanceLayout, RebindError, Resolver,
+    SnapshotError, SnapshotOptions, SnapshotStats,
+};
 
 /// A WebAssembly Instance is a stateful, executable
 /// instance of a WebAssembly [`Module`].
@@ -54,12 +68,99 @@ pub enum InstantiationError {
 
     /// The module was compiled with a CPU feature that is not available on
     /// the current host.
-    #[error("missing requires CPU features: {0:?}")]
+    #[error(
+        "missing requires CPU features: {0:?} [{}]",
+        ErrorCode::InstantiationCpuFeature
+    )]
     CpuFeature(String),
 
     /// Error occurred when initializing the host environment.
     #[error(transparent)]
     HostEnvInitialization(HostEnvInitError),
+
+    /// The contents of an externalized data segment could not be obtained.
+    #[error(transparent)]
+    ExternalData(ExternalDataError),
+
+    /// The module cannot be instantiated with read-only memories.
+    #[error(
+        "module cannot be instantiated read-only: {0} [{}]",
+        ErrorCode::InstantiationReadOnlyUnsupported
+    )]
+    ReadOnlyUnsupported(String),
+
+    /// The module was compiled for another memory style than the tunables of its store
+    /// choose, such as when it was deserialized into a store with other tunables.
+    #[error(
+        "module compiled for another memory style: {0} [{}]",
+        ErrorCode::InstantiationMemoryStyleMismatch
+    )]
+    MemoryStyleMismatch(String),
+
+    /// An imported global differs from the value the module was specialized for, see
+    /// [`Module::specialize`](crate::Module::specialize).
+    #[error(
+        "module specialized for another value: {0} [{}]",
+        ErrorCode::InstantiationSpecializationMismatch
+    )]
+    SpecializationMismatch(String),
+
+    /// The instance would allocate more than the limit of its configuration, see
+    /// [`InstanceConfig::with_max_instance_overhead_bytes`].
+    #[error(
+        "instance needs {} bytes, more than the limit of {} bytes [{}]",
+        .layout.total_bytes(),
+        .limit,
+        ErrorCode::InstantiationOverheadExceeded
+    )]
+    OverheadExceeded {
+        /// What the instance would allocate.
+        layout: InstanceLayout,
+        /// The limit of the configuration.
+        limit: usize,
+    },
+
+    /// The module was instantiated into a store whose engine is not the one that loaded it,
+    /// nor a clone of it, see [`Module::instantiate_in`](crate::Module::instantiate_in).
+    #[error(
+        "module loaded by another engine than the one of the store [{}]",
+        ErrorCode::InstantiationDifferentEngine
+    )]
+    DifferentEngine,
+}
+
+impl HasErrorCode for InstantiationError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Link(e) => e.code(),
+            Self::Start(e) => e.code(),
+            Self::CpuFeature(_) => ErrorCode::InstantiationCpuFeature,
+            Self::HostEnvInitialization(e) => e.code(),
+            Self::ExternalData(e) => e.code(),
+            Self::ReadOnlyUnsupported(_) => ErrorCode::InstantiationReadOnlyUnsupported,
+            Self::MemoryStyleMismatch(_) => ErrorCode::InstantiationMemoryStyleMismatch,
+            Self::SpecializationMismatch(_) => ErrorCode::InstantiationSpecializationMismatch,
+            Self::OverheadExceeded { .. } => ErrorCode::InstantiationOverheadExceeded,
+            Self::DifferentEngine => ErrorCode::InstantiationDifferentEngine,
+        }
+    }
+}
+
+impl Classify for InstantiationError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::Link(e) => e.failure_kind(),
+            Self::Start(e) => e.failure_kind(),
+            Self::CpuFeature(_)
+            | Self::HostEnvInitialization(_)
+            | Self::ReadOnlyUnsupported(_)
+            | Self::MemoryStyleMismatch(_)
+            | Self::SpecializationMismatch(_)
+            | Self::OverheadExceeded { .. }
+            | Self::DifferentEngine => FailureKind::Permanent,
+            Self::ExternalData(e) => e.failure_kind(),
+        }
+    }
 }
 
 impl From<wasmer_engine::InstantiationError> for InstantiationError {
@@ -68,6 +169,19 @@ impl From<wasmer_engine::InstantiationError> for InstantiationError {
             wasmer_engine::InstantiationError::Link(e) => Self::Link(e),
             wasmer_engine::InstantiationError::Start(e) => Self::Start(e),
             wasmer_engine::InstantiationError::CpuFeature(e) => Self::CpuFeature(e),
+            wasmer_engine::InstantiationError::ExternalData(e) => Self::ExternalData(e),
+            wasmer_engine::InstantiationError::ReadOnlyUnsupported(e) => {
+                Self::ReadOnlyUnsupported(e)
+            }
+            wasmer_engine::InstantiationError::MemoryStyleMismatch(e) => {
+                Self::MemoryStyleMismatch(e)
+            }
+            wasmer_engine::InstantiationError::SpecializationMismatch(e) => {
+                Self::SpecializationMismatch(e)
+            }
+            wasmer_engine::InstantiationError::OverheadExceeded { layout, limit } => {
+                Self::OverheadExceeded { layout, limit }
+            }
         }
     }
 }
@@ -78,6 +192,19 @@ impl From<HostEnvInitError> for InstantiationError {
     }
 }
 
+/// Check that `config` can be used to instantiate a module.
+pub(crate) fn check_config(config: &InstanceConfig) -> Result<(), InstantiationError> {
+    unsafe {
+        if (*config.gas_counter).opcode_cost > i32::MAX as u64 {
+            // Fast gas counter logic assumes that individual opcode cost is not too big.
+            return Err(InstantiationError::HostEnvInitialization(
+                HostEnvInitError::IncorrectGasMeteringConfig,
+            ));
+        }
+    }
+    Ok(())
+}
+
 impl Instance {
     /// Creates a new `Instance` from a WebAssembly [`Module`] and a
     /// set of imports resolved by the [`Resolver`].
@@ -117,20 +244,62 @@ impl Instance {
     }
 
     /// New instance with config.
+    ///
+    /// The imported functions declared overridable with
+    /// [`InstanceConfig::with_overridable_import`] can be overridden for a single call with
+    /// [`Instance::guarded_call_with_overrides`].
     pub fn new_with_config(
         module: &Module,
-        config: InstanceConfig,
+        mut config: InstanceConfig,
         resolver: &dyn Resolver,
     ) -> Result<Self, InstantiationError> {
-        unsafe {
-            if (*config.gas_counter).opcode_cost > i32::MAX as u64 {
-                // Fast gas counter logic assumes that individual opcode cost is not too big.
-                return Err(InstantiationError::HostEnvInitialization(
-                    HostEnvInitError::IncorrectGasMeteringConfig,
-                ));
-            }
-        }
-        let handle = module.instantiate(resolver, config)?;
+        check_config(&config)?;
+        let resolver = OverridableResolver::new(module, &mut config, resolver);
+        Self::from_handle(module, module.instantiate(&resolver, config)?)
+    }
+
+    /// Creates a new read-only `Instance` from a WebAssembly [`Module`] and a set of imports
+    /// resolved by the [`Resolver`].
+    ///
+    /// The memory of read-only instances cannot be modified. All the read-only instances of a
+    /// module share a single image of it, captured right after its data segments are applied,
+    /// so that many of them can serve calls that only read their state concurrently, cheaply.
+    /// Their tables and globals are their own, and their start function is run as usual.
+    ///
+    /// WebAssembly code writing to memory traps with [`TrapCode::ReadOnlyInstance`], and
+    /// `memory.grow` fails. Read-only instances are only supported on Linux.
+    ///
+    /// ## Errors
+    ///
+    /// Besides the errors of [`Instance::new`], the function returns
+    /// [`InstantiationError::ReadOnlyUnsupported`] for modules importing a memory or placing a
+    /// data segment with a global.
+    ///
+    /// [`TrapCode::ReadOnlyInstance`]: crate::TrapCode::ReadOnlyInstance
+    pub fn new_readonly(
+        module: &Module,
+        resolver: &dyn Resolver,
+    ) -> Result<Self, InstantiationError> {
+        Instance::new_readonly_with_config(module, InstanceConfig::default(), resolver)
+    }
+
+    /// New read-only instance with config.
+    ///
+    /// See [`Instance::new_readonly`].
+    pub fn new_readonly_with_config(
+        module: &Module,
+        mut config: InstanceConfig,
+        resolver: &dyn Resolver,
+    ) -> Result<Self, InstantiationError> {
+        check_config(&config)?;
+        let resolver = OverridableResolver::new(module, &mut config, resolver);
+        Self::from_handle(module, module.instantiate_readonly(&resolver, config)?)
+    }
+
+    pub(crate) fn from_handle(
+        module: &Module,
+        handle: InstanceHandle,
+    ) -> Result<Self, InstantiationError> {
         let instance = Self {
             handle: Arc::new(Mutex::new(handle)),
             module: module.clone(),
@@ -154,6 +323,296 @@ impl Instance {
         Ok(instance)
     }
 
+    /// Attach an extension value to this instance, returning the value of the same type it
+    /// replaces.
+    ///
+    /// Host functions called by this instance can read it via [`CallContext::extension`].
+    /// Calls already in progress keep observing the previous value.
+    ///
+    /// [`CallContext::extension`]: crate::CallContext::extension
+    pub fn insert_extension<T: Send + Sync + 'static>(&self, value: T) -> Option<Arc<T>> {
+        self.handle.lock().unwrap().insert_extension(value)
+    }
+
+    /// Return the number of calls the host made into this instance, which is also the call
+    /// sequence number observed by the call in progress, if any.
+    ///
+    /// See [`InstanceConfig::with_call_sequence_global`].
+    pub fn call_sequence(&self) -> u64 {
+        self.handle.lock().unwrap().call_sequence()
+    }
+
+    /// Return the identifier of this instance, which tables report as the origin of the
+    /// functions it defines.
+    ///
+    /// See [`Table::origin`](crate::Table::origin).
+    pub fn id(&self) -> InstanceId {
+        self.handle.lock().unwrap().id()
+    }
+
+    /// Return the diagnostics level of this instance.
+    pub fn diagnostics_level(&self) -> DiagnosticsLevel {
+        self.handle.lock().unwrap().diagnostics_level()
+    }
+
+    /// Change how much this instance reports to the diagnostics sink of its engine.
+    ///
+    /// This takes effect at the next function entry or memory access, including in calls
+    /// already running, and leaves the other instances alone. New instances start at the
+    /// default level of the engine, see `UniversalEngine::set_default_diagnostics_level`.
+    pub fn set_diagnostics_level(&self, level: DiagnosticsLevel) {
+        self.handle.lock().unwrap().set_diagnostics_level(level)
+    }
+
+    /// Switch this instance to the code of `new_module`, keeping the contents of its
+    /// memories, tables and globals.
+    ///
+    /// This lets a long-lived instance run the code of the same binary compiled anew, such as
+    /// by a fixed compiler or with another engine configuration, without losing its state.
+    /// The instance stays in the store it was created in. Tables and globals holding its
+    /// functions, and the functions looked up afterwards, run the new code. Functions looked
+    /// up before keep running the code of the previous module, which stays loaded as long as
+    /// the instance. Calls starting on other threads wait for the rebind to complete.
+    ///
+    /// # Errors
+    ///
+    /// * [`RebindError::WasmMismatch`] if `new_module` is not compiled from the same binary,
+    ///   or not specialized for the same bindings;
+    /// * [`RebindError::CallInFlight`] if a call into the instance is running, such as when
+    ///   rebinding from a host function it called;
+    /// * [`RebindError::LayoutMismatch`] if `new_module` is compiled for another layout of the
+    ///   instance, or for memories or tables of other styles.
+    pub fn rebind(&self, new_module: &Module) -> Result<(), RebindError> {
+        self.module
+            .artifact()
+            .check_rebind_target(new_module.artifact())?;
+        let artifact = Arc::clone(new_module.artifact());
+        // SAFETY: the artifacts are compiled from the same binary, for the same styles.
+        unsafe { self.handle.lock().unwrap().rebind(artifact) }
+    }
+
+    /// Write a snapshot of the memories, tables and globals of this instance to `writer`.
+    ///
+    /// The snapshot leaves out the memory pages only holding zeros, so that it is as large
+    /// as the pages the instance wrote to, and carries checksums of the pages and a hash of
+    /// the whole. It is written through a buffer of the size given in `options`, which
+    /// bounds the memory taken on top of that of the instance. Imported memories, tables and
+    /// globals are left to the snapshots of the instances defining them.
+    ///
+    /// Calls into the instance on other threads wait for the snapshot to complete.
+    ///
+    /// # Errors
+    ///
+    /// * [`SnapshotError::CallInFlight`] if a call into the instance is running, such as
+    ///   when snapshotting from a host function it called;
+    /// * [`SnapshotError::Poisoned`] if a restore of the instance failed;
+    /// * [`SnapshotError::Unsupported`] if a table or global holds an extern reference or a
+    ///   function of another instance;
+    /// * [`SnapshotError::Io`] if writing fails.
+    pub fn snapshot_to(
+        &self,
+        writer: impl Write,
+        options: SnapshotOptions,
+    ) -> Result<SnapshotStats, SnapshotError> {
+        self.handle.lock().unwrap().snapshot_to(writer, &options)
+    }
+
+    /// Restore the memories, tables and globals of this instance from a snapshot of an
+    /// instance of the same module, read from `reader`.
+    ///
+    /// Memory pages are read straight into the memories, without buffering, and checked
+    /// against their checksums as they are. Readers slow on small reads, such as files, are
+    /// best wrapped in a [`BufReader`](std::io::BufReader). Memories and tables grow to the
+    /// size they had in the snapshot.
+    ///
+    /// # Errors
+    ///
+    /// [`SnapshotError::CallInFlight`], and the [`SnapshotError::Format`],
+    /// [`SnapshotError::Mismatch`] and [`SnapshotError::Unsupported`] errors about the header
+    /// of the snapshot, leave the instance as it was. Any other error, such as a
+    /// [`SnapshotError::CorruptPage`] naming the page that does not match its checksum,
+    /// leaves the instance poisoned: calls into it trap with [`TrapCode::PoisonedInstance`]
+    /// and snapshots of it fail until a restore succeeds.
+    ///
+    /// [`TrapCode::PoisonedInstance`]: crate::TrapCode::PoisonedInstance
+    pub fn restore_from(&self, reader: impl Read) -> Result<(), SnapshotError> {
+        self.handle.lock().unwrap().restore_from(reader)
+    }
+
+    /// Bring the memories, tables and globals of this instance back to the state they had
+    /// when it was created, running its start function again if `run_start` is set, to reuse
+    /// it without instantiating the module again.
+    ///
+    /// Memories and tables shrink back to their minimum size. Memories mapping the image of
+    /// their module copy-on-write, see [`BaseTunables::copy_on_write_memories`], map it
+    /// again, which is cheaper than zeroing them and applying the data segments again.
+    /// Imported memories, tables and globals are left alone, apart from the segments of the
+    /// module being applied to them again.
+    ///
+    /// # Errors
+    ///
+    /// A [`ResetError`], retrieved with [`RuntimeError::downcast`], or the error of the start
+    /// function. [`ResetError::CallInFlight`] leaves the instance as it was, while a
+    /// [`ResetError::Memory`] leaves it poisoned until a reset or a restore succeeds.
+    ///
+    /// [`BaseTunables::copy_on_write_memories`]: crate::BaseTunables::copy_on_write_memories
+    /// [`ResetError`]: crate::ResetError
+    /// [`ResetError::CallInFlight`]: crate::ResetError::CallInFlight
+    /// [`ResetError::Memory`]: crate::ResetError::Memory
+    pub fn reset(&self, run_start: bool) -> Result<(), RuntimeError> {
+        let handle = self.handle.lock().unwrap();
+        self.store()
+            .with_backtrace_policy(|| handle.reset(run_start))
+            .map_err(|trap| self.store().runtime_error_from_trap(trap))
+    }
+
+    /// Return whether a failed restore or reset left this instance poisoned, see
+    /// [`Instance::restore_from`] and [`Instance::reset`].
+    pub fn is_poisoned(&self) -> bool {
+        self.handle.lock().unwrap().is_poisoned()
+    }
+
+    /// Return the gas left to this instance, that is the limit of its gas counter minus the
+    /// gas burnt so far. The instance traps with `GasExceeded` once it has none left.
+    pub fn remaining_gas(&self) -> u64 {
+        self.handle.lock().unwrap().remaining_gas()
+    }
+
+    /// Leave `remaining` gas to this instance by moving the limit of its gas counter. The gas
+    /// burnt so far is left as it is.
+    pub fn set_remaining_gas(&self, remaining: u64) {
+        self.handle.lock().unwrap().set_remaining_gas(remaining)
+    }
+
+    /// Run the setup then the entries of `plan`, within `guards` and those of the entries.
+    ///
+    /// The entries are run whether the ones before succeeded or not, and the report tells
+    /// how each went. Under [`SnapshotRollback`](crate::IsolationMode::SnapshotRollback)
+    /// isolation, the state of the instance is snapshotted after the setup and after each
+    /// entry that succeeded, and restored after each entry that failed.
+    ///
+    /// The gas of the calls is read from the gas counter of the instance, whose limit is
+    /// lowered for the duration of the calls with a gas guard. Calls into the instance on
+    /// other threads while the plan runs are charged to the calls of the plan.
+    ///
+    /// # Errors
+    ///
+    /// * [`PlanError::Export`] if the instance does not export a function the plan calls,
+    ///   before anything runs;
+    /// * [`PlanError::Setup`] if the setup fails, before any entry runs;
+    /// * [`PlanError::Snapshot`] if the state of the instance cannot be snapshotted, such as
+    ///   when a table holds an extern reference, or restored.
+    pub fn run_plan(&self, plan: &ExecutionPlan, guards: Guards) -> Result<PlanReport, PlanError> {
+        crate::sys::execution_plan::run_plan(self, plan, guards)
+    }
+
+    /// Call the exported function `entry` with `params`, within `guards`, with the imported
+    /// functions in `overrides`, by module and field name, replaced by the given functions.
+    ///
+    /// The imports must have been declared overridable with
+    /// [`InstanceConfig::with_overridable_import`] when the instance was created. The
+    /// overrides apply to this call only, including the calls into this instance the host
+    /// functions it calls make, and to no other call, such as the calls into this instance
+    /// on other threads. An override of a nested call wins over the one of the call it is
+    /// nested in. The environments of the overrides are initialized for this instance, see
+    /// [`WasmerEnv::init_with_instance`](crate::WasmerEnv::init_with_instance).
+    ///
+    /// # Errors
+    ///
+    /// Before anything runs:
+    ///
+    /// * [`OverrideError::Export`] if the instance does not export the function `entry`;
+    /// * [`OverrideError::NotOverridable`] if an import was not declared overridable;
+    /// * [`OverrideError::SignatureMismatch`] if an override does not have the signature of
+    ///   the import it overrides;
+    /// * [`OverrideError::HostEnvInitialization`] if the environment of an override cannot
+    ///   be initialized.
+    ///
+    /// Then [`OverrideError::Call`] if the deadline of `guards` passed or the call fails.
+    pub fn guarded_call_with_overrides(
+        &self,
+        entry: &str,
+        params: &[Val],
+        guards: Guards,
+        overrides: &[(&str, &str, Function)],
+    ) -> Result<Box<[Val]>, OverrideError> {
+        let extensions = self.handle.lock().unwrap().extensions();
+        overrides::guarded_call_with_overrides(self, &extensions, entry, params, guards, overrides)
+    }
+
+    /// Prepare a call of the exported function `entry` with `params`, within `guards`, that
+    /// can be cancelled through the returned [`CallHandle`], from any thread.
+    ///
+    /// The call runs when the returned closure is called, on the thread calling it. Once
+    /// cancelled, it fails with a [`TrapCode::Cancelled`] trap, whose failure kind is
+    /// [`FailureKind::TransientInterrupted`]:
+    ///
+    /// * without starting, if it had not started yet;
+    /// * at the next check of the compiled code of the instance, on function entry and at
+    ///   the start of every loop iteration, if it is running. A call blocked in a host
+    ///   function thus stops once the host function returns, and the calls into other
+    ///   instances it makes run to completion.
+    ///
+    /// Cancelling a call that finished does nothing. Other calls into the instance, such as
+    /// concurrent calls on other threads, are not affected. Code compiled without the checks,
+    /// as it is by default, see `Singlepass::cancellation`, runs to completion once started.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ExportError`] if the instance does not export the function `entry`. The
+    /// closure returns the error of the call, or a [`RuntimeError`] saying so if the deadline
+    /// of `guards` passed before the call started.
+    ///
+    /// [`TrapCode::Cancelled`]: crate::TrapCode::Cancelled
+    pub fn guarded_call_handle(
+        &self,
+        entry: &str,
+        params: &[Val],
+        guards: Guards,
+    ) -> Result<(CallHandle, GuardedCall), ExportError> {
+        cancellation::guarded_call_handle(self, entry, params, guards)
+    }
+
+    /// Return the gas counter of this instance, which outlives it.
+    pub(crate) fn gas_counter(&self) -> *mut FastGasCounter {
+        self.handle.lock().unwrap().gas_counter()
+    }
+
+    /// Return the number of handles to this instance, including this one.
+    pub(crate) fn handle_count(&self) -> usize {
+        Arc::strong_count(&self.handle)
+    }
+
+    /// Return the handle of the VM to this instance.
+    pub(crate) fn vm_handle(&self) -> MutexGuard<'_, InstanceHandle> {
+        self.handle.lock().unwrap()
+    }
+
+    pub(crate) fn store(&self) -> &Store {
+        self.module.store()
+    }
+
+    pub(crate) fn module(&self) -> &Module {
+        &self.module
+    }
+
+    /// Return the name of the function import whose host environment is `host_env`, as
+    /// `"module"."field"`.
+    pub(crate) fn function_import_name(&self, host_env: *mut c_void) -> Option<String> {
+        let index = self
+            .handle
+            .lock()
+            .unwrap()
+            .imported_function_with_env(host_env)?;
+        let (module, field) = self.module.artifact().function_import(index)?;
+        Some(format!("{:?}.{:?}", module, field))
+    }
+
+    /// Return the memory of the given index, without keeping this instance alive.
+    pub(crate) fn memory(&self, index: MemoryIndex) -> Option<Arc<dyn wasmer_vm::Memory>> {
+        self.handle.lock().unwrap().memory(index)
+    }
+
     /// Lookup an exported entity by its name.
     pub fn lookup(&self, field: &str) -> Option<crate::Export> {
         let vmextern = self.handle.lock().unwrap().lookup(field)?;
@@ -169,7 +628,29 @@ impl Instance {
         }
     }
 
+    /// Lookup an exported memory by its name.
+    pub fn lookup_memory(&self, field: &str) -> Option<crate::Memory> {
+        if let crate::Export::Memory(m) = self.lookup(field)? {
+            Some(crate::Memory::from_vm_export(self.module.store(), m))
+        } else {
+            None
+        }
+    }
+
+    /// Lookup an exported table by its name.
+    pub fn lookup_table(&self, field: &str) -> Option<crate::Table> {
+        if let crate::Export::Table(t) = self.lookup(field)? {
+            Some(crate::Table::from_vm_export(self.module.store(), t))
+        } else {
+            None
+        }
+    }
+
     /// Get an export as a `NativeFunc`.
+    ///
+    /// The signature of the function is checked against `Args` and `Rets` once, here, so
+    /// that calling it needs no checks, boxing or allocation. Fails with
+    /// [`ExportError::IncompatibleType`] if the export is not a function of this signature.
     pub fn get_native_function<Args, Rets>(
         &self,
         name: &str,
@@ -185,7 +666,7 @@ impl Instance {
                     .map_err(|_| ExportError::IncompatibleType)
             }
             Some(_) => Err(ExportError::IncompatibleType),
-            None => Err(ExportError::Missing("not found".into())),
+            None => Err(ExportError::Missing(name.to_string())),
         }
     }
 }
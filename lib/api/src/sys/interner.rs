@@ -0,0 +1,231 @@
+//! Interning of the strings guests pass to host functions.
+
+use crate::sys::guest_type::{cells, GuestTypeError};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use wasmer_types::MemoryView;
+
+/// A string read from guest memory by a [`StringInterner`], shared with its cache.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InternedStr(Arc<str>);
+
+impl InternedStr {
+    /// Whether `a` and `b` are the same interned string, rather than equal ones.
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Arc::ptr_eq(&a.0, &b.0)
+    }
+
+    /// The shared string.
+    pub fn into_arc(self) -> Arc<str> {
+        self.0
+    }
+}
+
+impl Deref for InternedStr {
+    type Target = str;
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for InternedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// What a [`StringInterner`] did so far, see [`StringInterner::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InternerStats {
+    /// Strings found in the cache.
+    pub hits: u64,
+    /// Strings read from guest memory, including invalid ones.
+    pub misses: u64,
+    /// Strings in the cache.
+    pub strings: usize,
+    /// Bytes of the strings in the cache.
+    pub bytes: usize,
+}
+
+/// Reads strings from guest memory, keeping the most recently read ones so that reading
+/// them again neither validates their UTF-8 nor allocates.
+///
+/// Guests passing the same short strings to host functions again and again, such as the
+/// prefixes of storage keys, only pay for hashing and comparing their bytes. The guest may
+/// change its memory between calls, so every hit is compared with the bytes in guest
+/// memory: a string that is not there anymore is never returned.
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// # let store = Store::default();
+/// # let memory = Memory::new(&store, MemoryType::new(1, None, false))?;
+/// let interner = StringInterner::new(64);
+/// let view = memory.view::<u8>();
+/// GuestString::new(0x100, 5).write(&view, "alice")?;
+/// let first = interner.get(&view, 0x100, 5)?;
+/// let second = interner.get(&view, 0x100, 5)?;
+/// assert_eq!(&*second, "alice");
+/// assert!(InternedStr::ptr_eq(&first, &second));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Attach it to an instance with [`Instance::insert_extension`] so that the host functions
+/// the instance calls find it with [`CallContext::extension`].
+///
+/// [`Instance::insert_extension`]: crate::Instance::insert_extension
+/// [`CallContext::extension`]: crate::CallContext::extension
+pub struct StringInterner {
+    cache: Mutex<Cache>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct Cache {
+    capacity: usize,
+    max_bytes: usize,
+    /// The cached strings, by the hash of their bytes.
+    strings: HashMap<u64, Vec<Entry>>,
+    /// The hash of the cached strings, by when they were last used.
+    lru: BTreeMap<u64, u64>,
+    clock: u64,
+    len: usize,
+    bytes: usize,
+}
+
+struct Entry {
+    string: Arc<str>,
+    used: u64,
+}
+
+impl StringInterner {
+    /// Create an interner keeping the `capacity` most recently read strings, whatever their
+    /// size.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_max_bytes(capacity, usize::MAX)
+    }
+
+    /// Create an interner keeping the `capacity` most recently read strings, as long as
+    /// they are `max_bytes` long together.
+    ///
+    /// The least recently read strings are evicted first. Strings longer than `max_bytes`
+    /// are read without being kept.
+    pub fn with_max_bytes(capacity: usize, max_bytes: usize) -> Self {
+        Self {
+            cache: Mutex::new(Cache {
+                capacity,
+                max_bytes,
+                strings: HashMap::new(),
+                lru: BTreeMap::new(),
+                clock: 0,
+                len: 0,
+                bytes: 0,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Read the UTF-8 string of `len` bytes at `ptr` in `view`.
+    ///
+    /// Fails with [`GuestTypeError::OutOfBounds`] if the string is not within the memory,
+    /// and with [`GuestTypeError::InvalidUtf8`] if it is not valid UTF-8.
+    pub fn get(
+        &self,
+        view: &MemoryView<u8>,
+        ptr: u32,
+        len: u32,
+    ) -> Result<InternedStr, GuestTypeError> {
+        let bytes = cells(view, ptr, len as usize, 1)?;
+        let hash = hash(bytes);
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(string) = cache.find(hash, bytes) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(InternedStr(string));
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let bytes = bytes.iter().map(Cell::get).collect::<Vec<u8>>();
+        let string: Arc<str> = String::from_utf8(bytes)
+            .map_err(|_| GuestTypeError::InvalidUtf8 { offset: ptr.into() })?
+            .into();
+        cache.insert(hash, string.clone());
+        Ok(InternedStr(string))
+    }
+
+    /// The hits and misses so far, and what the cache holds.
+    pub fn stats(&self) -> InternerStats {
+        let cache = self.cache.lock().unwrap();
+        InternerStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            strings: cache.len,
+            bytes: cache.bytes,
+        }
+    }
+}
+
+impl fmt::Debug for StringInterner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StringInterner")
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+impl Cache {
+    /// The cached string with the bytes `bytes` of hash `hash`, marked as used.
+    fn find(&mut self, hash: u64, bytes: &[Cell<u8>]) -> Option<Arc<str>> {
+        let entry = self
+            .strings
+            .get_mut(&hash)?
+            .iter_mut()
+            .find(|entry| entry.string.bytes().eq(bytes.iter().map(Cell::get)))?;
+        self.lru.remove(&entry.used);
+        self.clock += 1;
+        entry.used = self.clock;
+        self.lru.insert(self.clock, hash);
+        Some(entry.string.clone())
+    }
+
+    /// Keep `string`, of hash `hash`, evicting the least recently used strings to make room.
+    fn insert(&mut self, hash: u64, string: Arc<str>) {
+        if self.capacity == 0 || string.len() > self.max_bytes {
+            return;
+        }
+        self.clock += 1;
+        self.len += 1;
+        self.bytes += string.len();
+        self.lru.insert(self.clock, hash);
+        self.strings.entry(hash).or_default().push(Entry {
+            string,
+            used: self.clock,
+        });
+        while self.len > self.capacity || self.bytes > self.max_bytes {
+            let (used, hash) = match self.lru.iter().next() {
+                Some((&used, &hash)) => (used, hash),
+                None => break,
+            };
+            self.lru.remove(&used);
+            let entries = self.strings.get_mut(&hash).unwrap();
+            let index = entries.iter().position(|entry| entry.used == used).unwrap();
+            let evicted = entries.swap_remove(index);
+            if entries.is_empty() {
+                self.strings.remove(&hash);
+            }
+            self.len -= 1;
+            self.bytes -= evicted.string.len();
+        }
+    }
+}
+
+/// The FNV-1a hash of `bytes`, read in place.
+fn hash(bytes: &[Cell<u8>]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte.get())).wrapping_mul(0x0100_0000_01b3)
+    })
+}
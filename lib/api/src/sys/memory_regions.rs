@@ -0,0 +1,385 @@
+use crate::sys::externals::Memory;
+use crate::sys::ptr::{Array, WasmPtr};
+use std::fmt;
+use std::ops::Range;
+use std::sync::{Mutex, MutexGuard};
+use thiserror::Error;
+use wasmer_types::{ErrorCode, HasErrorCode, ValueType};
+
+/// What the host may do with the guest pointers into a [`MemoryRegion`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RegionPolicy {
+    /// Whether the host may read from the region.
+    pub read: bool,
+    /// Whether the host may write into the region.
+    pub write: bool,
+}
+
+impl RegionPolicy {
+    /// A region the host may only read from.
+    pub const READ_ONLY: Self = Self {
+        read: true,
+        write: false,
+    };
+    /// A region the host may read from and write into.
+    pub const READ_WRITE: Self = Self {
+        read: true,
+        write: true,
+    };
+
+    fn allows(&self, access: RegionAccess) -> bool {
+        match access {
+            RegionAccess::Read => self.read,
+            RegionAccess::Write => self.write,
+            RegionAccess::ReadWrite => self.read && self.write,
+        }
+    }
+}
+
+impl fmt::Display for RegionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match (self.read, self.write) {
+            (true, true) => "read-write",
+            (true, false) => "read-only",
+            (false, true) => "write-only",
+            (false, false) => "no access",
+        })
+    }
+}
+
+/// The kind of access rejected by a [`RegionPolicy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RegionAccess {
+    /// Reading through [`MemoryRegions::read`].
+    Read,
+    /// Writing through [`MemoryRegions::write`].
+    Write,
+    /// Reading and writing through a [`WasmPtr`] from [`MemoryRegions::wasm_ptr`].
+    ReadWrite,
+}
+
+impl fmt::Display for RegionAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Read => "read",
+            Self::Write => "write",
+            Self::ReadWrite => "read-write access",
+        })
+    }
+}
+
+/// A named range of linear memory with the policy applying to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryRegion {
+    /// The name of the region, used to look it up and in error messages.
+    pub name: String,
+    /// The offsets of the region in linear memory.
+    pub range: Range<u64>,
+    /// What the host may do in the region.
+    pub policy: RegionPolicy,
+}
+
+/// An error raised by [`MemoryRegions`].
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RegionError {
+    /// No region has the given name.
+    #[error("unknown memory region '{0}' [{}]", ErrorCode::RegionUnknown)]
+    UnknownRegion(String),
+    /// The accessed bytes are not all within the region.
+    #[error("range 0x{ptr:x}+0x{len:x} is not within '{region}' (0x{:x}..0x{:x}) [{}]", range.start, range.end, ErrorCode::RegionOutOfRegion)]
+    OutOfRegion {
+        /// The guest pointer.
+        ptr: u64,
+        /// The number of bytes accessed.
+        len: u64,
+        /// The name of the region.
+        region: String,
+        /// The range of the region.
+        range: Range<u64>,
+    },
+    /// The policy of the region does not allow the access.
+    #[error(
+        "pointer 0x{ptr:x} is in '{region}' ({policy}), {access} rejected [{}]",
+        ErrorCode::RegionPolicyViolation
+    )]
+    PolicyViolation {
+        /// The guest pointer.
+        ptr: u64,
+        /// The name of the region.
+        region: String,
+        /// The policy of the region.
+        policy: RegionPolicy,
+        /// The rejected access.
+        access: RegionAccess,
+    },
+    /// The accessed bytes are within the region, but not within the memory.
+    #[error("range 0x{ptr:x}+0x{len:x} of '{region}' is out of the bounds of the memory (0x{size:x} bytes) [{}]", ErrorCode::RegionOutOfBounds)]
+    OutOfBounds {
+        /// The guest pointer.
+        ptr: u64,
+        /// The number of bytes accessed.
+        len: u64,
+        /// The name of the region.
+        region: String,
+        /// The size of the memory in bytes.
+        size: u64,
+    },
+    /// The pointer is not aligned for the type it points to.
+    #[error(
+        "pointer 0x{ptr:x} in '{region}' is not aligned to {align} bytes [{}]",
+        ErrorCode::RegionUnaligned
+    )]
+    Unaligned {
+        /// The guest pointer.
+        ptr: u64,
+        /// The name of the region.
+        region: String,
+        /// The alignment required by the type.
+        align: u64,
+    },
+    /// The regions are reversed, overlap or have duplicate names.
+    #[error("invalid memory regions: {0} [{}]", ErrorCode::RegionInvalid)]
+    InvalidRegions(String),
+}
+
+impl HasErrorCode for RegionError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnknownRegion(_) => ErrorCode::RegionUnknown,
+            Self::OutOfRegion { .. } => ErrorCode::RegionOutOfRegion,
+            Self::PolicyViolation { .. } => ErrorCode::RegionPolicyViolation,
+            Self::OutOfBounds { .. } => ErrorCode::RegionOutOfBounds,
+            Self::Unaligned { .. } => ErrorCode::RegionUnaligned,
+            Self::InvalidRegions(_) => ErrorCode::RegionInvalid,
+        }
+    }
+}
+
+type UpdateFn = dyn Fn(u64, &mut [MemoryRegion]) + Send + Sync;
+
+struct RegionsState {
+    regions: Vec<MemoryRegion>,
+    /// The memory size the regions were last updated for.
+    memory_size: Option<u64>,
+}
+
+/// A partition of linear memory into named regions, checking the guest pointers given to host
+/// functions against the region they are expected to be in.
+///
+/// Attach it to an instance with [`InstanceConfig::with_extension`] and get it back in host
+/// functions through [`CallContext::extension`]. The memory is passed to every accessor rather
+/// than stored, as storing it would keep the instance alive.
+///
+/// [`InstanceConfig::with_extension`]: crate::InstanceConfig::with_extension
+/// [`CallContext::extension`]: crate::CallContext::extension
+///
+/// ```
+/// # use wasmer::{Memory, MemoryRegions, MemoryType, RegionPolicy, Store};
+/// # fn main() -> anyhow::Result<()> {
+/// # let store = Store::default();
+/// # let memory = Memory::new(&store, MemoryType::new(1, None, false))?;
+/// let regions = MemoryRegions::new(&[
+///     ("input", 0..0x1000, RegionPolicy::READ_ONLY),
+///     ("heap", 0x1000..0x10000, RegionPolicy::READ_WRITE),
+/// ])?;
+/// regions.write(&memory, "heap", 0x1000, b"hello")?;
+/// assert_eq!(regions.read(&memory, "heap", 0x1000, 5)?, b"hello");
+/// assert_eq!(
+///     regions.write(&memory, "input", 0x10, b"hello").unwrap_err().to_string(),
+///     "pointer 0x10 is in 'input' (read-only), write rejected [W0622]",
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub struct MemoryRegions {
+    state: Mutex<RegionsState>,
+    update: Option<Box<UpdateFn>>,
+}
+
+impl fmt::Debug for MemoryRegions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("MemoryRegions")
+            .field("regions", &state.regions)
+            .finish()
+    }
+}
+
+impl MemoryRegions {
+    /// Create the regions from their name, range and policy.
+    ///
+    /// The regions must not overlap and their names must be unique.
+    pub fn new(regions: &[(&str, Range<u64>, RegionPolicy)]) -> Result<Self, RegionError> {
+        let regions = regions
+            .iter()
+            .map(|(name, range, policy)| MemoryRegion {
+                name: name.to_string(),
+                range: range.clone(),
+                policy: *policy,
+            })
+            .collect::<Vec<_>>();
+        validate(&regions)?;
+        Ok(Self {
+            state: Mutex::new(RegionsState {
+                regions,
+                memory_size: None,
+            }),
+            update: None,
+        })
+    }
+
+    /// Recompute the ranges of the regions with `update` whenever the size of the memory (in
+    /// bytes) changes, for example for a heap extending to the end of the memory.
+    ///
+    /// `update` is called before the first access and may not add or remove regions. It runs
+    /// under the lock of the regions and must not access them.
+    pub fn with_update(
+        mut self,
+        update: impl Fn(u64, &mut [MemoryRegion]) + Send + Sync + 'static,
+    ) -> Self {
+        self.update = Some(Box::new(update));
+        self
+    }
+
+    /// Get the regions, as updated for the current size of `memory`.
+    pub fn regions(&self, memory: &Memory) -> Result<Vec<MemoryRegion>, RegionError> {
+        Ok(self.state(memory)?.regions.clone())
+    }
+
+    /// Copy the `len` bytes at `ptr`, which must be within the region `name`.
+    pub fn read(
+        &self,
+        memory: &Memory,
+        name: &str,
+        ptr: u64,
+        len: u64,
+    ) -> Result<Vec<u8>, RegionError> {
+        let range = self.check(memory, name, ptr, len, RegionAccess::Read)?;
+        let data = unsafe { memory.data_unchecked() };
+        Ok(data[range].to_vec())
+    }
+
+    /// Copy `data` to `ptr`, which must be within the region `name`.
+    pub fn write(
+        &self,
+        memory: &Memory,
+        name: &str,
+        ptr: u64,
+        data: &[u8],
+    ) -> Result<(), RegionError> {
+        let len = data.len() as u64;
+        let range = self.check(memory, name, ptr, len, RegionAccess::Write)?;
+        unsafe { memory.data_unchecked_mut()[range].copy_from_slice(data) };
+        Ok(())
+    }
+
+    /// Check that the `len` values of type `T` at `ptr` are aligned and within the region
+    /// `name`, which must allow both reads and writes, and return a pointer to them.
+    ///
+    /// Read-only regions can be accessed through [`MemoryRegions::read`].
+    pub fn wasm_ptr<T: Copy + ValueType>(
+        &self,
+        memory: &Memory,
+        name: &str,
+        ptr: u32,
+        len: u32,
+    ) -> Result<WasmPtr<T, Array>, RegionError> {
+        let size = std::mem::size_of::<T>() as u64 * u64::from(len);
+        self.check(memory, name, ptr.into(), size, RegionAccess::ReadWrite)?;
+        let align = std::mem::align_of::<T>() as u64;
+        if u64::from(ptr) % align != 0 {
+            return Err(RegionError::Unaligned {
+                ptr: ptr.into(),
+                region: name.to_string(),
+                align,
+            });
+        }
+        Ok(WasmPtr::new(ptr))
+    }
+
+    fn state(&self, memory: &Memory) -> Result<MutexGuard<'_, RegionsState>, RegionError> {
+        let mut state = self.state.lock().unwrap();
+        let size = memory.data_size();
+        if state.memory_size != Some(size) {
+            if let Some(update) = &self.update {
+                let mut regions = state.regions.clone();
+                update(size, &mut regions);
+                validate(&regions)?;
+                state.regions = regions;
+            }
+            state.memory_size = Some(size);
+        }
+        Ok(state)
+    }
+
+    fn check(
+        &self,
+        memory: &Memory,
+        name: &str,
+        ptr: u64,
+        len: u64,
+        access: RegionAccess,
+    ) -> Result<Range<usize>, RegionError> {
+        let state = self.state(memory)?;
+        let region = state
+            .regions
+            .iter()
+            .find(|region| region.name == name)
+            .ok_or_else(|| RegionError::UnknownRegion(name.to_string()))?;
+        let end = match ptr.checked_add(len) {
+            Some(end) if region.range.start <= ptr && end <= region.range.end => end,
+            _ => {
+                return Err(RegionError::OutOfRegion {
+                    ptr,
+                    len,
+                    region: region.name.clone(),
+                    range: region.range.clone(),
+                })
+            }
+        };
+        if !region.policy.allows(access) {
+            return Err(RegionError::PolicyViolation {
+                ptr,
+                region: region.name.clone(),
+                policy: region.policy,
+                access,
+            });
+        }
+        let size = memory.data_size();
+        if end > size {
+            return Err(RegionError::OutOfBounds {
+                ptr,
+                len,
+                region: region.name.clone(),
+                size,
+            });
+        }
+        Ok(ptr as usize..end as usize)
+    }
+}
+
+fn validate(regions: &[MemoryRegion]) -> Result<(), RegionError> {
+    for (i, region) in regions.iter().enumerate() {
+        if region.range.start > region.range.end {
+            return Err(RegionError::InvalidRegions(format!(
+                "'{}' ends before it starts",
+                region.name
+            )));
+        }
+        for other in &regions[..i] {
+            if other.name == region.name {
+                return Err(RegionError::InvalidRegions(format!(
+                    "'{}' is defined twice",
+                    region.name
+                )));
+            }
+            if other.range.start < region.range.end && region.range.start < other.range.end {
+                return Err(RegionError::InvalidRegions(format!(
+                    "'{}' overlaps '{}'",
+                    region.name, other.name
+                )));
+            }
+        }
+    }
+    Ok(())
+}
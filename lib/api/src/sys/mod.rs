@@ -1,15 +1,40 @@
+#[cfg(feature = "compiler")]
+pub mod abi_check;
+mod arg_policy;
+mod bind_exports;
+mod bundle;
+mod call_context;
+mod cancellation;
 mod cell;
+mod chained_resolver;
+#[cfg(feature = "compiler")]
+mod compose;
+#[cfg(feature = "conformance")]
+pub mod conformance;
 mod env;
+mod events;
+mod execution_plan;
 mod exports;
 mod externals;
+mod group;
+mod guest_type;
 mod import_object;
 mod instance;
+mod interner;
+mod memory_regions;
 mod module;
 mod native;
+mod non_send;
+mod overrides;
 mod ptr;
+mod replay;
+#[cfg(feature = "rpc")]
+pub mod rpc;
+mod scoped;
 mod store;
 mod tunables;
 mod types;
+mod view_cache;
 
 #[doc(hidden)]
 pub mod internals {
@@ -21,37 +46,96 @@ pub mod internals {
     pub use crate::sys::externals::{WithEnv, WithoutEnv};
 }
 
+pub use crate::sys::arg_policy::{ArgPolicy, ArgPolicyBuilder, ArgPolicyError, ArgRule};
+pub use crate::sys::bind_exports::{BindExports, BindExportsError, BindableExport};
+pub use crate::sys::bundle::{
+    BundleError, BundleInstances, BundleLink, BundleManifest, BundleModule, InstanceBundle,
+};
+pub use crate::sys::call_context::CallContext;
+pub use crate::sys::cancellation::{CallHandle, GuardedCall};
 pub use crate::sys::cell::WasmCell;
+pub use crate::sys::chained_resolver::{
+    ChainedResolver, ChainedResolverBuilder, ChainedResolverError, Namespace, NamespaceFilter,
+    Precedence, ResolvedImport,
+};
+#[cfg(feature = "compiler")]
+pub use crate::sys::compose::{compose_modules, ComposeError, ComposeOptions};
 pub use crate::sys::env::{HostEnvInitError, LazyInit, WasmerEnv};
+pub use crate::sys::events::{Event, EventCaps, EventError, EventSchema, EventSink};
+pub use crate::sys::execution_plan::{
+    CallOutcome, ExecutionPlan, ExecutionPlanBuilder, Guards, IsolationMode, PlanError, PlanReport,
+};
 pub use crate::sys::exports::{ExportError, Exportable, Exports};
 pub use crate::sys::externals::{
-    Extern, FromToNativeWasmType, Function, Global, HostFunction, Memory, Table, WasmTypeList,
+    Extern, FromToNativeWasmType, Function, Global, HostFunction, Memory, MemoryPin,
+    ResultArityError, Table, WasmTypeList,
+};
+pub use crate::sys::group::{
+    GroupMoveError, GroupOffender, GroupReference, InstanceGroup, MovableGroup,
 };
+pub use crate::sys::guest_type::{GuestString, GuestType, GuestTypeError, GuestVec};
 pub use crate::sys::import_object::{ImportObject, ImportObjectIterator, LikeNamespace};
 pub use crate::sys::instance::{Instance, InstantiationError};
-pub use crate::sys::module::Module;
+pub use crate::sys::interner::{InternedStr, InternerStats, StringInterner};
+pub use crate::sys::memory_regions::{
+    MemoryRegion, MemoryRegions, RegionAccess, RegionError, RegionPolicy,
+};
+pub use crate::sys::module::{
+    IoCompileError, Module, ModuleStats, SerializeError, UnusedItemsReport,
+};
 pub use crate::sys::native::NativeFunc;
+pub use crate::sys::non_send::{
+    NonSendFunction, NonSendImports, NonSendInstance, NonSendNativeFunc,
+};
+pub use crate::sys::overrides::OverrideError;
 pub use crate::sys::ptr::{Array, Item, WasmPtr};
+pub use crate::sys::replay::{
+    DivergenceField, ReplayDivergence, ReplayLog, ReplayLogError, ReplayPlayer, ReplayRecorder,
+};
+pub use crate::sys::scoped::{InstanceScope, ScopedInstance, ScopedMemory, ScopedNativeFunc};
 pub use crate::sys::store::{Store, StoreObject};
-pub use crate::sys::tunables::BaseTunables;
+pub use crate::sys::tunables::{BaseTunables, ThresholdTunables};
 pub use crate::sys::types::{
-    ExportType, ExternType, FunctionType, GlobalType, MemoryType, Mutability, TableType, Val,
-    ValType,
+    ExportType, ExportsIterator, ExternType, FunctionType, GlobalType, ImportsIterator, MemoryType,
+    Mutability, TableType, Val, ValType,
 };
 pub use crate::sys::types::{Val as Value, ValType as Type};
+pub use crate::sys::view_cache::{
+    Nondeterminism, ViewCallCache, ViewCallCacheStats, ViewCallError,
+};
 pub use target_lexicon::{Architecture, CallingConvention, OperatingSystem, Triple, HOST};
 #[cfg(feature = "compiler")]
-pub use wasmer_compiler::{wasmparser, CompilerConfig};
 pub use wasmer_compiler::{
-    CompileError, CpuFeature, Features, ParseCpuFeatureError, Target, WasmError, WasmResult,
+    wasmparser, CompilerConfig, OperatorCosts, ScheduleVersion, ValidationReport,
+};
+pub use wasmer_compiler::{
+    CompileError, CpuFeature, DisabledFeatureUse, Features, Location, ParseCpuFeatureError,
+    SectionKind, SourceLocation, SubprocessError, Target, WasmError, WasmResult,
+};
+pub use wasmer_derive::GuestType;
+pub use wasmer_engine::{
+    BacktraceCapture, BacktracePolicy, DeserializeError, ElidedFrames, Engine, ExternalDataError,
+    ExternalDataErrorKind, FrameInfo, ImportError, LinkError, RuntimeError, SymbolicationCache,
+    DEFAULT_SYMBOLICATION_CACHE_SIZE,
 };
-pub use wasmer_engine::{DeserializeError, Engine, FrameInfo, LinkError, RuntimeError};
 pub use wasmer_types::{
-    Atomically, Bytes, ExportIndex, ExternRef, GlobalInit, LocalFunctionIndex, MemoryView, Pages,
-    ValueType, WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
+    Atomically, Bytes, Classify, DataError, DataProvider, DeferredTeardown, DynamicGasCosts,
+    ErrorCode, ExportIndex, Extensions, ExternRef, FailureKind, FunctionIndex, GlobalIndex,
+    GlobalInit, HasErrorCode, InstanceConfig, LocalFunctionIndex, MemoryView, Pages, PretouchMode,
+    PretouchPolicy, Quota, QuotaDimension, TableProvenancePolicy, TenantId, ValueType, WasmFeature,
+    WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
 };
 pub use wasmer_vm::{
-    ChainableNamedResolver, Export, NamedResolver, NamedResolverChain, Resolver, Tunables,
+    AtomicMetricsSink, BaseStability, Counter, DiagnosticEvent, DiagnosticsLevel, DiagnosticsSink,
+    FairShareArbiter, FuncOrigin, Gauge, GlobalError, GrowDecision, GrowRequest, InstanceId,
+    InstanceLayout, InstanceUsage, MemoryArbiter, MemoryRelease, MetricsSink, MetricsSnapshot,
+    PageAllocator, PageProtection, RebindError, ReclaimPriority, ReclaimReport, Reclaimable,
+    ReclaimedComponent, ResetError, SnapshotError, SnapshotOptions, SnapshotStats,
+    SystemPageAllocator, TeardownQueue, Timer, TrapCode, TrapCodeCollision,
+};
+pub use wasmer_vm::{
+    ChainableNamedResolver, Export, ModuleStyleHints, NamedResolver, NamedResolverChain, Resolver,
+    Tunables,
 };
 
 // TODO: should those be moved into wasmer::vm as well?
@@ -71,8 +155,13 @@ pub use wat::parse_bytes as wat2wasm;
 #[cfg(feature = "singlepass")]
 pub use wasmer_compiler_singlepass::Singlepass;
 
+pub use wasmer_engine_universal::{
+    DeserializeLimits, InterfaceFormat, PinMode, PinReport, TenantUsage,
+};
 #[cfg(feature = "universal")]
-pub use wasmer_engine_universal::{Universal, UniversalArtifact, UniversalEngine};
+pub use wasmer_engine_universal::{
+    InstanceSnapshotInfo, TrampolineSetExecutable, Universal, UniversalArtifact, UniversalEngine,
+};
 
 #[cfg(feature = "dylib")]
 pub use wasmer_engine_dylib::{Dylib, DylibArtifact, DylibEngine};
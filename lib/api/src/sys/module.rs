@@ -1,27 +1,151 @@
+use crate::sys::instance::{check_config, Instance};
+use crate::sys::scoped::{InstanceScope, ScopedInstance};
 use crate::sys::store::Store;
+use crate::sys::types::Val;
 use crate::sys::InstantiationError;
 use std::fmt;
 use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
 use std::sync::Arc;
 use thiserror::Error;
 use wasmer_compiler::CompileError;
 #[cfg(feature = "wat")]
 use wasmer_compiler::WasmError;
-use wasmer_engine::RuntimeError;
-use wasmer_engine_universal::UniversalArtifact;
-use wasmer_types::InstanceConfig;
-use wasmer_vm::{InstanceHandle, Instantiatable, Resolver};
+#[cfg(feature = "compiler")]
+use wasmer_compiler::{Features, ValidationReport};
+#[cfg(feature = "compiler")]
+use wasmer_engine::Engine;
+use wasmer_engine::{DeserializeError, Executable};
+#[cfg(feature = "compiler")]
+use wasmer_engine_universal::UniversalEngine;
+use wasmer_engine_universal::{
+    DeserializeLimits, InterfaceFormat, PinMode, PinReport, UniversalArtifact,
+    UniversalExecutableRef,
+};
+use wasmer_types::entity::EntityRef;
+use wasmer_types::{
+    Classify, ErrorCode, ExportsIterator, ExternType, FailureKind, FunctionIndex, GlobalIndex,
+    HasErrorCode, ImportIndex, ImportsIterator, InstanceConfig, LocalFunctionIndex, MemoryType,
+    TableType,
+};
+#[cfg(feature = "compiler")]
+use wasmer_types::{GlobalInit, TenantId};
+use wasmer_vm::{InstanceHandle, InstanceLayout, Instantiatable, Mmap, Resolver};
 
+/// An error reading and compiling a module, see [`Module::from_file`].
 #[derive(Error, Debug)]
 pub enum IoCompileError {
     /// An IO error
-    #[error(transparent)]
+    #[error("{0} [{}]", ErrorCode::CompileIo)]
     Io(#[from] io::Error),
     /// A compilation error
     #[error(transparent)]
     Compile(#[from] CompileError),
 }
 
+impl HasErrorCode for IoCompileError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Io(_) => ErrorCode::CompileIo,
+            Self::Compile(e) => e.code(),
+        }
+    }
+}
+
+impl Classify for IoCompileError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::Io(e) => e.failure_kind(),
+            Self::Compile(e) => e.failure_kind(),
+        }
+    }
+}
+
+/// An error serializing a [`Module`].
+#[derive(Error, Debug)]
+pub enum SerializeError {
+    /// The module was not compiled from a binary by this crate, so it cannot be compiled
+    /// again to be serialized.
+    #[error(
+        "the binary of the module is not available to serialize it [{}]",
+        ErrorCode::SerializeBinaryUnavailable
+    )]
+    BinaryUnavailable,
+    /// Compiling the module again failed.
+    #[error(transparent)]
+    Compile(#[from] CompileError),
+    /// The compiled module could not be serialized.
+    #[error("could not serialize the module: {0} [{}]", ErrorCode::Serialize)]
+    Generic(String),
+}
+
+impl HasErrorCode for SerializeError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::BinaryUnavailable => ErrorCode::SerializeBinaryUnavailable,
+            Self::Generic(_) => ErrorCode::Serialize,
+            Self::Compile(e) => e.code(),
+        }
+    }
+}
+
+impl Classify for SerializeError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::BinaryUnavailable | Self::Generic(_) => FailureKind::Permanent,
+            Self::Compile(e) => e.failure_kind(),
+        }
+    }
+}
+
+/// The start of serialized modules.
+const MODULE_MAGIC: [u8; 16] = *b"\0wasmer-module\0\0";
+/// The version of the format of serialized modules, bumped whenever it changes.
+const MODULE_FORMAT_VERSION: u32 = 2;
+
+/// Counts and sizes declared by a module, read without instantiating it, see [`Module::info`].
+///
+/// Imported functions, tables, memories and globals are counted along with those the module
+/// defines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleStats {
+    /// The number of functions.
+    pub functions: usize,
+    /// The number of imported functions.
+    pub imported_functions: usize,
+    /// The number of globals.
+    pub globals: usize,
+    /// The number of memories.
+    pub memories: usize,
+    /// The largest minimum size of the memories, in pages, or 0 without memories.
+    pub max_memory_pages: u32,
+    /// The minimum sizes of the tables, in elements, by table index.
+    pub table_minimums: Vec<u32>,
+    /// The total length of the data segments, in bytes.
+    pub data_size: usize,
+}
+
+/// The imported functions and the globals the code of a module never uses, found when
+/// compiling it, see [`Module::unused_items`].
+///
+/// Code is reachable from the exported functions, the start function and the functions placed
+/// in tables or globals, through the functions it calls or takes a reference to. Globals are
+/// used by reachable code, by exports, and by the initializers of other globals and segments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnusedItemsReport {
+    /// The imported functions reachable code never calls nor takes a reference to, by module
+    /// and field name.
+    pub functions: Vec<(String, String)>,
+    /// The unused imported globals, by module and field name.
+    pub imported_globals: Vec<(String, String)>,
+    /// The unused globals the module defines, by index.
+    pub globals: Vec<GlobalIndex>,
+    /// Whether the unused imported functions were stripped from the imports instances must
+    /// be given, see [`CompilerConfig::strip_unused_imports`](crate::CompilerConfig::strip_unused_imports).
+    pub imports_stripped: bool,
+}
+
 /// A WebAssembly Module contains stateless WebAssembly
 /// code that has already been compiled and can be instantiated
 /// multiple times.
@@ -34,6 +158,8 @@ pub enum IoCompileError {
 pub struct Module {
     store: Store,
     artifact: Arc<wasmer_engine_universal::UniversalArtifact>,
+    /// The binary the module was compiled from, kept to specialize it.
+    binary: Option<Arc<[u8]>>,
 }
 
 impl Module {
@@ -109,6 +235,129 @@ impl Module {
         Self::from_binary(store, bytes.as_ref())
     }
 
+    /// Creates a new WebAssembly module on behalf of `tenant`, accounting the compilation and
+    /// the loaded module to the tenant until the module is dropped.
+    ///
+    /// Fails with [`CompileError::QuotaExceeded`] if that would exceed the quota of the
+    /// tenant, see [`UniversalEngine::set_tenant_quota`](crate::UniversalEngine::set_tenant_quota),
+    /// and with [`CompileError::EngineDowncast`] if the engine of `store` is not a
+    /// `UniversalEngine`. Otherwise, this is the same as [`Module::new`].
+    #[cfg(feature = "compiler")]
+    pub fn new_for_tenant(
+        store: &Store,
+        tenant: TenantId,
+        bytes: impl AsRef<[u8]>,
+    ) -> Result<Self, CompileError> {
+        #[cfg(feature = "wat")]
+        let bytes = wat::parse_bytes(bytes.as_ref()).map_err(|e| {
+            CompileError::Wasm(WasmError::Generic(format!(
+                "Error when converting wat: {}",
+                e
+            )))
+        })?;
+        let binary = bytes.as_ref();
+        let engine: &dyn Engine = &**store.engine();
+        let engine = engine
+            .downcast_ref::<UniversalEngine>()
+            .ok_or(CompileError::EngineDowncast)?;
+        engine.validate(binary)?;
+        let executable = engine.compile_universal_as(tenant, binary, store.tunables())?;
+        let artifact = engine.load_universal_executable_as(tenant, &executable)?;
+        Ok(Self {
+            store: store.clone(),
+            artifact: Arc::new(artifact),
+            binary: Some(binary.into()),
+        })
+    }
+
+    /// Creates a new WebAssembly module from a file path, named after the absolute path of
+    /// the file.
+    pub fn from_file(store: &Store, file: impl AsRef<Path>) -> Result<Self, IoCompileError> {
+        let file = file.as_ref();
+        let canonical = file.canonicalize()?;
+        let wasm_bytes = std::fs::read(file)?;
+        let mut module = Self::new(store, &wasm_bytes)?;
+        // Set the module name to the absolute path of the filename.
+        // This is useful for debugging the stack traces.
+        module.set_name(&canonical.to_string_lossy());
+        Ok(module)
+    }
+
+    /// Validates a WebAssembly binary against `features` rather than those of a store, telling
+    /// where and why it is invalid if it is.
+    ///
+    /// Deployment pipelines can thus check modules against the features of production without
+    /// building the store it uses. The WebAssembly text format is not accepted.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use wasmer::*;
+    /// // (module (func (result i32) (i64.const 0)))
+    /// let bytes = [
+    ///     0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x01, 0x05, 0x01, 0x60,
+    ///     0x00, 0x01, 0x7f, 0x03, 0x02, 0x01, 0x00, 0x0a, 0x06, 0x01, 0x04, 0x00,
+    ///     0x42, 0x00, 0x0b,
+    /// ];
+    /// let report = Module::validate_with_features(&bytes, &Features::default()).unwrap_err();
+    /// assert_eq!(report.section, Some(SectionKind::Code));
+    /// ```
+    #[cfg(feature = "compiler")]
+    pub fn validate_with_features(
+        binary: &[u8],
+        features: &Features,
+    ) -> Result<(), ValidationReport> {
+        wasmer_compiler::validate_module_with_report(features, binary)
+    }
+
+    /// Creates a new WebAssembly module from a binary read from `reader`, such as a network
+    /// connection, without waiting for all of it to validate it.
+    ///
+    /// Each section of the binary is validated as soon as it is read, and so is each function
+    /// body while the rest of the code section is still being read: an invalid module fails
+    /// as soon as the invalid part of it is read. Once `reader` reaches its end, the module is
+    /// compiled as [`Module::new`] would. Unlike [`Module::new`], the WebAssembly text format
+    /// is not accepted.
+    ///
+    /// A binary cut short fails with [`CompileError::Wasm`], and errors reading from `reader`
+    /// with [`IoCompileError::Io`].
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let bytes: &[u8] = b"\0asm\x01\0\0\0";
+    /// let module = Module::from_reader(&store, bytes)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "compiler")]
+    pub fn from_reader(store: &Store, mut reader: impl io::Read) -> Result<Self, IoCompileError> {
+        let engine: &dyn Engine = &**store.engine();
+        let engine = engine
+            .downcast_ref::<UniversalEngine>()
+            .ok_or(CompileError::EngineDowncast)?;
+        let mut validator = engine.streaming_validator();
+        let mut chunk = [0; 16 * 1024];
+        loop {
+            match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(len) => validator.feed(&chunk[..len])?,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let binary = validator.finish()?;
+        let executable = engine.compile_universal(&binary, store.tunables())?;
+        let module = Self::from_executable(store, &executable)?;
+        Ok(Self {
+            binary: Some(binary.into()),
+            ..module
+        })
+    }
+
     /// Creates a new WebAssembly module from a binary.
     ///
     /// Opposed to [`Module::new`], this function is not compatible with
@@ -116,21 +365,261 @@ impl Module {
     /// this crate).
     pub(crate) fn from_binary(store: &Store, binary: &[u8]) -> Result<Self, CompileError> {
         store.engine().validate(binary)?;
-        let module = {
-            let executable = store.engine().compile(binary, store.tunables())?;
-            let artifact = store.engine().load(&*executable)?;
-            match artifact.downcast_arc::<UniversalArtifact>() {
-                Ok(universal) => Self {
-                    store: store.clone(),
-                    artifact: universal,
-                },
-                // We're are probably given an externally defined artifact type
-                // which I imagine we don't care about for now since this entire crate
-                // is only used for tests and this crate only defines universal engine.
-                Err(_) => panic!("unhandled artifact type"),
+        let artifact = store
+            .engine()
+            .compile_and_load(binary, store.tunables())?
+            .downcast_arc::<UniversalArtifact>()
+            .map_err(|_| CompileError::EngineDowncast)?;
+        Ok(Self {
+            store: store.clone(),
+            artifact,
+            binary: Some(binary.into()),
+        })
+    }
+
+    /// Creates a new WebAssembly module from an already compiled executable.
+    ///
+    /// This is useful when the executable was produced outside of
+    /// [`Module::new`], for instance by
+    /// [`UniversalEngine::compile_universal_deduped`](crate::UniversalEngine::compile_universal_deduped).
+    ///
+    /// Modules hold the artifacts of the Universal engine: this fails with
+    /// [`CompileError::EngineDowncast`] if the engine of `store` loads other artifacts.
+    pub fn from_executable(
+        store: &Store,
+        executable: &dyn Executable,
+    ) -> Result<Self, CompileError> {
+        let artifact = store.engine().load(executable)?;
+        let artifact = artifact
+            .downcast_arc::<UniversalArtifact>()
+            .map_err(|_| CompileError::EngineDowncast)?;
+        Ok(Self {
+            store: store.clone(),
+            artifact,
+            binary: None,
+        })
+    }
+
+    /// Serializes this module, to be loaded with [`Module::deserialize`] without compiling it.
+    ///
+    /// The module only keeps the loaded code, so this compiles its binary again, with the
+    /// bindings it is specialized for if any: serialize modules once, ahead of time. Only
+    /// modules compiled from a binary by this crate can be serialized.
+    ///
+    /// The serialized module starts with a header naming the format and engine versions and
+    /// the CPU features the code was compiled for, which [`Module::deserialize`] checks. The
+    /// header is padded so that the executable that follows is as aligned as the bytes are.
+    #[cfg(feature = "compiler")]
+    pub fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        let binary = self
+            .binary
+            .as_ref()
+            .ok_or(SerializeError::BinaryUnavailable)?;
+        let engine = self.artifact.engine();
+        let tunables = self.store.tunables();
+        let executable = match self.artifact.specialization_hash() {
+            Some(_) => {
+                let bindings = self.artifact.global_bindings();
+                engine.compile_universal_specialized(binary, tunables, &bindings)?
             }
+            None => engine.compile_universal(binary, tunables)?,
         };
-        Ok(module)
+        let code = executable
+            .serialize()
+            .map_err(|e| SerializeError::Generic(e.to_string()))?;
+        let mut out = MODULE_MAGIC.to_vec();
+        out.extend(&MODULE_FORMAT_VERSION.to_le_bytes());
+        put_str(&mut out, wasmer_engine_universal::VERSION);
+        let cpu_features = executable
+            .cpu_features()
+            .iter()
+            .map(|feature| format!("{:?}", feature))
+            .collect::<Vec<_>>()
+            .join(",");
+        put_str(&mut out, &cpu_features);
+        // The executable is accessed in place, so it starts as aligned as the buffer is.
+        out.resize(header_len(out.len()), 0);
+        out.extend(code);
+        Ok(out)
+    }
+
+    /// Loads a module serialized with [`Module::serialize`].
+    ///
+    /// Fails with [`DeserializeError::Incompatible`] if the module was serialized by another
+    /// version of the engine or in another format, or if its code uses CPU features the
+    /// target of the engine of `store` does not have.
+    ///
+    /// # Safety
+    ///
+    /// The code of the module is validated to be well-formed but run as it is: the bytes must
+    /// come from [`Module::serialize`]. See [`UniversalExecutableRef::deserialize`].
+    pub unsafe fn deserialize(store: &Store, bytes: &[u8]) -> Result<Self, DeserializeError> {
+        Self::deserialize_with_limits(store, bytes, &DeserializeLimits::default())
+    }
+
+    /// Loads a module [serialized](Module::serialize) into `bytes`, failing with
+    /// [`DeserializeError::LimitExceeded`] if it is larger than `limits` allow.
+    ///
+    /// The limits are checked before anything is allocated for the module. Fails as
+    /// [`Module::deserialize`] does otherwise.
+    ///
+    /// # Safety
+    ///
+    /// See [`Module::deserialize`] and [`UniversalExecutableRef::deserialize_with_limits`].
+    pub unsafe fn deserialize_with_limits(
+        store: &Store,
+        bytes: &[u8],
+        limits: &DeserializeLimits,
+    ) -> Result<Self, DeserializeError> {
+        let (cpu_features, executable) = split_header(bytes)?;
+        let host = store
+            .engine()
+            .target()
+            .cpu_features()
+            .iter()
+            .map(|feature| format!("{:?}", feature))
+            .collect::<Vec<_>>();
+        let missing = cpu_features
+            .split(',')
+            .filter(|feature| !feature.is_empty() && !host.iter().any(|have| have == feature))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(DeserializeError::Incompatible(format!(
+                "the module was compiled for CPU features the target lacks: {}",
+                missing.join(", ")
+            )));
+        }
+        UniversalExecutableRef::with_aligned(executable, |executable| {
+            let executable = UniversalExecutableRef::deserialize_with_limits(executable, limits)?;
+            Self::from_executable(store, &executable).map_err(DeserializeError::Compiler)
+        })
+    }
+
+    /// Loads a module [serialized](Module::serialize) into the file at `path`, without reading
+    /// the file into memory.
+    ///
+    /// The file is mapped, and only the code and what the module keeps are copied out of it,
+    /// so loading a large module does not take a second copy of its code. Fails as
+    /// [`Module::deserialize`] does.
+    ///
+    /// # Safety
+    ///
+    /// See [`Module::deserialize`]. The file must not be modified while it is loaded.
+    pub unsafe fn deserialize_from_file(
+        store: &Store,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, DeserializeError> {
+        let mapping = Mmap::map_file(&std::fs::File::open(path)?)?;
+        Self::deserialize(store, mapping.as_slice())
+    }
+
+    /// Compiles this module again, specialized for the given values of some of its imported
+    /// globals.
+    ///
+    /// Each binding names an immutable imported global by module and field, and gives it a
+    /// value of its numeric type. The compiler uses these values as constants in place of
+    /// reading the globals, so the specialized module runs faster where it reads them often,
+    /// such as in the bounds of loops. Its instances must be given globals with exactly these
+    /// values, and fail with [`InstantiationError::SpecializationMismatch`] otherwise, unless
+    /// [`InstanceConfig::with_bound_globals_provided`] has the runtime provide these globals.
+    ///
+    /// Specializing costs a full compilation. The engine reuses the result for the same binary
+    /// and bindings for as long as a module specialized with them is alive.
+    ///
+    /// Only modules compiled from a binary by this crate can be specialized.
+    #[cfg(feature = "compiler")]
+    pub fn specialize(&self, bindings: &[(&str, &str, Val)]) -> Result<Self, CompileError> {
+        let binary = self.binary.as_ref().ok_or_else(|| {
+            CompileError::Specialization("the binary of the module is not available".to_string())
+        })?;
+        let bindings = bindings
+            .iter()
+            .map(|(module, field, value)| {
+                let value = match *value {
+                    Val::I32(value) => GlobalInit::I32Const(value),
+                    Val::I64(value) => GlobalInit::I64Const(value),
+                    Val::F32(value) => GlobalInit::F32Const(value),
+                    Val::F64(value) => GlobalInit::F64Const(value),
+                    _ => {
+                        return Err(CompileError::Specialization(format!(
+                            "{}.{} cannot be bound to {:?}",
+                            module, field, value
+                        )))
+                    }
+                };
+                Ok((*module, *field, value))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let artifact =
+            self.artifact
+                .engine()
+                .specialize(binary, self.store.tunables(), &bindings)?;
+        Ok(Self {
+            store: self.store.clone(),
+            artifact,
+            binary: Some(Arc::clone(binary)),
+        })
+    }
+
+    /// Returns the values of the imported globals this module is specialized for, with the
+    /// module and field names of these globals.
+    ///
+    /// This is empty for modules that are not specialized.
+    pub fn global_bindings(&self) -> Vec<(&str, &str, Val)> {
+        self.artifact
+            .global_bindings()
+            .into_iter()
+            .map(|(module, field, value)| (module, field, value.to_value()))
+            .collect()
+    }
+
+    /// Returns the hash identifying the binary and the bindings this module is specialized
+    /// for, or `None` if it is not specialized.
+    ///
+    /// Modules specialized for different values have different hashes.
+    pub fn specialization_hash(&self) -> Option<[u8; 32]> {
+        self.artifact.specialization_hash()
+    }
+
+    pub(crate) fn artifact(&self) -> &Arc<wasmer_engine_universal::UniversalArtifact> {
+        &self.artifact
+    }
+
+    /// Creates an instance of this module in `store` rather than in the store of the module,
+    /// with the imports resolved by `resolver`.
+    ///
+    /// This lets a module be compiled once and instantiated into many stores, such as one per
+    /// request, as long as their engine is the one that loaded the module or a clone of it.
+    /// The instance uses the tunables of `store`, and its exports belong to `store`.
+    /// Instantiation otherwise proceeds as with [`Instance::new_with_config`], and fails with
+    /// the same errors.
+    ///
+    /// ## Errors
+    ///
+    /// Besides those of [`Instance::new_with_config`], this fails with
+    /// [`InstantiationError::DifferentEngine`] if the engine of `store` is another engine.
+    pub fn instantiate_in(
+        &self,
+        store: &Store,
+        resolver: &dyn Resolver,
+        config: InstanceConfig,
+    ) -> Result<Instance, InstantiationError> {
+        let engine: &dyn wasmer_engine::Engine = &**store.engine();
+        let same_engine = engine
+            .downcast_ref::<wasmer_engine_universal::UniversalEngine>()
+            .map_or(false, |engine| {
+                engine.is_same_engine(self.artifact.engine())
+            });
+        if !same_engine {
+            return Err(InstantiationError::DifferentEngine);
+        }
+        check_config(&config)?;
+        let module = Self {
+            store: store.clone(),
+            artifact: Arc::clone(&self.artifact),
+            binary: self.binary.clone(),
+        };
+        Instance::from_handle(&module, module.instantiate(resolver, config)?)
     }
 
     pub(crate) fn instantiate(
@@ -151,18 +640,395 @@ impl Module {
             // of this steps traps, we still need to keep the instance alive
             // as some of the Instance elements may have placed in other
             // instance tables.
-            instance_handle
-                .finish_instantiation()
-                .map_err(|t| InstantiationError::Start(RuntimeError::from_trap(t)))?;
+            self.store
+                .with_backtrace_policy(|| instance_handle.finish_instantiation())
+                .map_err(|t| InstantiationError::Start(self.store.runtime_error_from_trap(t)))?;
 
             Ok(instance_handle)
         }
     }
 
+    pub(crate) fn instantiate_readonly(
+        &self,
+        resolver: &dyn Resolver,
+        config: InstanceConfig,
+    ) -> Result<InstanceHandle, InstantiationError> {
+        unsafe {
+            let instance_handle = Arc::clone(&self.artifact).instantiate_readonly(
+                self.store.tunables(),
+                resolver,
+                Box::new((self.store.clone(), Arc::clone(&self.artifact))),
+                config,
+            )?;
+            // As in `instantiate`, the instance is kept alive if the start function traps.
+            self.store
+                .with_backtrace_policy(|| instance_handle.finish_readonly_instantiation())
+                .map_err(|t| InstantiationError::Start(self.store.runtime_error_from_trap(t)))?;
+            Ok(instance_handle)
+        }
+    }
+
+    /// Creates an instance of this module that borrows it and stores its `VMContext` in
+    /// `scope`, see [`ScopedInstance`].
+    ///
+    /// Scoped instances are meant for hot paths creating many short-lived instances: they
+    /// do not keep the module alive, and reuse the storage of the instances of `scope` that
+    /// were dropped. Instantiation otherwise proceeds as with [`Instance::new_with_config`],
+    /// and fails with the same errors.
+    pub fn instantiate_scoped<'a>(
+        &'a self,
+        resolver: &dyn Resolver,
+        config: InstanceConfig,
+        scope: &'a InstanceScope,
+    ) -> Result<ScopedInstance<'a>, InstantiationError> {
+        check_config(&config)?;
+        let handle = unsafe {
+            // The borrow of `self` keeps the store and the artifact alive instead of the host
+            // state, which is left empty.
+            let instance_handle = Arc::clone(&self.artifact).instantiate_in(
+                &scope.arena,
+                self.store.tunables(),
+                resolver,
+                Box::new(()),
+                config,
+            )?;
+            // As in `instantiate`, the instance is kept alive if the start function traps.
+            self.store
+                .with_backtrace_policy(|| instance_handle.finish_instantiation())
+                .map_err(|t| InstantiationError::Start(self.store.runtime_error_from_trap(t)))?;
+            instance_handle
+        };
+        Ok(ScopedInstance {
+            instance: Instance::from_handle(self, handle)?,
+            _scope: PhantomData,
+        })
+    }
+
+    /// Returns the name of the module, if it has one.
+    ///
+    /// The name comes from the name section of the binary, from [`Module::from_file`] or
+    /// from [`Module::set_name`].
+    pub fn name(&self) -> Option<&str> {
+        self.artifact.name()
+    }
+
+    /// Returns the name of the function `index`, from the name section of the binary.
+    ///
+    /// Traces of the traps the module raises show functions by these names.
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # let store = Store::default();
+    /// let module = Module::new(&store, "(module (func $add_one) (func))").unwrap();
+    /// assert_eq!(module.function_name(FunctionIndex::from_u32(0)), Some("add_one"));
+    /// assert_eq!(module.function_name(FunctionIndex::from_u32(1)), None);
+    /// ```
+    pub fn function_name(&self, index: FunctionIndex) -> Option<&str> {
+        self.artifact.function_name(index)
+    }
+
+    /// Sets the name of the module, as reported in the traces of the traps it raises.
+    ///
+    /// The module can only be renamed before it is cloned or instantiated, as long as it is
+    /// the only owner of its code. Returns whether it was renamed.
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # let store = Store::default();
+    /// let mut module = Module::new(&store, "(module $old)").unwrap();
+    /// assert!(module.set_name("new"));
+    /// assert_eq!(module.name(), Some("new"));
+    /// let clone = module.clone();
+    /// assert!(!module.set_name("newer"));
+    /// # drop(clone);
+    /// ```
+    pub fn set_name(&mut self, name: &str) -> bool {
+        match Arc::get_mut(&mut self.artifact) {
+            Some(artifact) => {
+                artifact.set_name(name.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Returns the [`Store`] where the `Instance` belongs.
     pub fn store(&self) -> &Store {
         &self.store
     }
+
+    /// Returns the names and types of the exports of this module, in declaration order.
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let wat = r#"(module
+    ///     (func (export "run"))
+    ///     (memory (export "memory") 1))"#;
+    /// let module = Module::new(&store, wat)?;
+    /// let functions = module.exports().functions().map(|(name, _)| name);
+    /// assert_eq!(functions.collect::<Vec<_>>(), vec!["run"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn exports(
+        &self,
+    ) -> ExportsIterator<impl ExactSizeIterator<Item = (&str, ExternType)> + '_> {
+        self.artifact.interface().exports()
+    }
+
+    /// Returns the namespaces, names and types of the imports of this module, in declaration
+    /// order.
+    ///
+    /// The imports can be checked against what the embedder provides before instantiating
+    /// the module:
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let wat = r#"(module (import "env" "gas" (func (param i64))))"#;
+    /// let module = Module::new(&store, wat)?;
+    /// assert!(module.imports().all(|(namespace, _, _)| namespace == "env"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn imports(
+        &self,
+    ) -> ImportsIterator<impl ExactSizeIterator<Item = (&str, &str, ExternType)> + '_> {
+        self.artifact.interface().imports()
+    }
+
+    /// Returns the contents of the custom sections named `name`, in the order they appear in
+    /// the binary this module was compiled from.
+    ///
+    /// Every custom section is kept with the module, whatever its size, including in
+    /// serialized modules.
+    pub fn custom_sections<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Box<[u8]>> + 'a {
+        self.artifact
+            .interface()
+            .custom_sections(name)
+            .map(|data| Box::from(&*data))
+    }
+
+    /// Returns the content hashes of the functions defined by this module.
+    ///
+    /// Identical functions compiled as part of different modules have identical hashes, which
+    /// lets embedders deduplicate stored code at function granularity.
+    pub fn function_body_hashes(&self) -> Vec<(LocalFunctionIndex, [u8; 32])> {
+        self.artifact.function_hashes()
+    }
+
+    /// Returns the SHA-256 hash of the binary this module was compiled from, the same for
+    /// every compilation of the binary.
+    pub fn wasm_hash(&self) -> [u8; 32] {
+        self.artifact.wasm_hash()
+    }
+
+    /// Returns a stable identifier of this module, to cache it under: the same for every
+    /// compilation of the same binary by engines of the same
+    /// [`fingerprint`](crate::Engine::fingerprint).
+    ///
+    /// See [`UniversalExecutable::artifact_id`](wasmer_engine_universal::UniversalExecutable::artifact_id).
+    pub fn artifact_id(&self) -> [u8; 32] {
+        self.artifact.artifact_id()
+    }
+
+    /// Returns a hash of everything that determines what the instances of this module
+    /// compute, given the same imports.
+    ///
+    /// See [`UniversalArtifact::consensus_hash`].
+    pub fn consensus_hash(&self) -> [u8; 32] {
+        self.artifact.consensus_hash()
+    }
+
+    /// Describes the imports and exports of this module, with their types, in `format`.
+    ///
+    /// Functions and their parameters are named after the name section of the binary, and
+    /// after their position when it does not name them. The description starts with a
+    /// comment giving the name of the module and a hash of the rest of the description, which
+    /// lists the imports then the exports by index: it is the same for every compilation of
+    /// the same binary.
+    pub fn interface_text(&self, format: InterfaceFormat) -> String {
+        self.artifact.interface_text(format)
+    }
+
+    /// Returns what instantiating this module allocates for the `VMContext` of the instance
+    /// and its function references, which is the same for all its instances.
+    ///
+    /// See [`InstanceConfig::with_max_instance_overhead_bytes`] to cap it.
+    pub fn instance_layout(&self) -> InstanceLayout {
+        self.artifact.instance_layout()
+    }
+
+    /// Returns the numbers of functions, globals and memories of this module, the sizes of
+    /// its memories and tables and of its data, to check them against limits before
+    /// instantiating it.
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let wat = r#"(module (memory 3) (data (i32.const 0) "abc"))"#;
+    /// let info = Module::new(&store, wat)?.info();
+    /// assert_eq!((info.max_memory_pages, info.data_size), (3, 3));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn info(&self) -> ModuleStats {
+        let interface = self.artifact.interface();
+        ModuleStats {
+            functions: interface.functions.len(),
+            imported_functions: interface.import_counts.functions as usize,
+            globals: interface.globals.len(),
+            memories: interface.memories.len(),
+            max_memory_pages: interface
+                .memories
+                .values()
+                .map(|memory| memory.minimum.0)
+                .max()
+                .unwrap_or(0),
+            table_minimums: interface
+                .tables
+                .values()
+                .map(|table| table.minimum)
+                .collect(),
+            data_size: self.artifact.data_size(),
+        }
+    }
+
+    /// Returns the imported functions and the globals the code of this module never uses.
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let module = Module::new(&store, r#"
+    ///     (module
+    ///         (import "env" "used" (func $used))
+    ///         (import "env" "unused" (func))
+    ///         (func (export "run") (call $used)))
+    /// "#)?;
+    /// let report = module.unused_items();
+    /// assert_eq!(report.functions, [("env".to_string(), "unused".to_string())]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn unused_items(&self) -> UnusedItemsReport {
+        let interface = self.artifact.interface();
+        let unused = self.artifact.unused_items();
+        let import_name = |wanted: ImportIndex| {
+            interface
+                .imports
+                .iter()
+                .find(|(_, index)| **index == wanted)
+                .map(|((module, field, _), _)| (module.clone(), field.clone()))
+        };
+        let imported_globals = interface.import_counts.globals as usize;
+        UnusedItemsReport {
+            functions: unused
+                .functions
+                .iter()
+                .filter_map(|index| import_name(ImportIndex::Function(*index)))
+                .collect(),
+            imported_globals: unused
+                .globals
+                .iter()
+                .filter_map(|index| import_name(ImportIndex::Global(*index)))
+                .collect(),
+            globals: unused
+                .globals
+                .iter()
+                .filter(|index| index.index() >= imported_globals)
+                .copied()
+                .collect(),
+            imports_stripped: unused.imports_stripped,
+        }
+    }
+
+    /// Returns the types of the memories of this module, imported or defined, by index.
+    pub fn memory_types(&self) -> Vec<MemoryType> {
+        self.artifact
+            .interface()
+            .memories
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the types of the tables of this module, imported or defined, by index.
+    pub fn table_types(&self) -> Vec<TableType> {
+        self.artifact.interface().tables.values().cloned().collect()
+    }
+
+    /// Keeps the code of the hot functions of this module resident, as requested by `mode`.
+    ///
+    /// See [`UniversalArtifact::pin_hot_code`].
+    pub fn pin_hot_code(&self, mode: PinMode) -> PinReport {
+        self.artifact.pin_hot_code(mode)
+    }
+}
+
+#[cfg(feature = "compiler")]
+fn put_str(out: &mut Vec<u8>, text: &str) {
+    out.extend(&(text.len() as u32).to_le_bytes());
+    out.extend(text.as_bytes());
+}
+
+/// Split a module [serialized](Module::serialize) into `bytes` into the CPU features its code
+/// was compiled for and its executable, failing if it was serialized in another format or by
+/// another version of the engine.
+pub(crate) fn split_header(bytes: &[u8]) -> Result<(&str, &[u8]), DeserializeError> {
+    if !bytes.starts_with(&MODULE_MAGIC) {
+        return Err(DeserializeError::Incompatible(
+            "the bytes are not a serialized module".to_string(),
+        ));
+    }
+    let mut rest = &bytes[MODULE_MAGIC.len()..];
+    let mut version = [0; 4];
+    version.copy_from_slice(take(&mut rest, 4)?);
+    let format = u32::from_le_bytes(version);
+    let engine = take_str(&mut rest)?;
+    if format != MODULE_FORMAT_VERSION || engine != wasmer_engine_universal::VERSION {
+        return Err(DeserializeError::Incompatible(format!(
+            "the module was serialized in format {} by engine {}, this is format {} and engine {}",
+            format,
+            engine,
+            MODULE_FORMAT_VERSION,
+            wasmer_engine_universal::VERSION
+        )));
+    }
+    let cpu_features = take_str(&mut rest)?;
+    let len = bytes.len() - rest.len();
+    take(&mut rest, header_len(len) - len)?;
+    Ok((cpu_features, rest))
+}
+
+/// The length of a header of `len` bytes, padded to the alignment of executables.
+fn header_len(len: usize) -> usize {
+    let alignment = UniversalExecutableRef::ALIGNMENT;
+    (len + alignment - 1) & !(alignment - 1)
+}
+
+/// Split `len` bytes off the front of `bytes`.
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], DeserializeError> {
+    if bytes.len() < len {
+        return Err(DeserializeError::CorruptedBinary(
+            "the header of the module is truncated".to_string(),
+        ));
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(taken)
+}
+
+fn take_str<'a>(bytes: &mut &'a [u8]) -> Result<&'a str, DeserializeError> {
+    let mut len = [0; 4];
+    len.copy_from_slice(take(bytes, 4)?);
+    std::str::from_utf8(take(bytes, u32::from_le_bytes(len) as usize)?).map_err(|_| {
+        DeserializeError::CorruptedBinary("the header of the module is malformed".to_string())
+    })
 }
 
 impl fmt::Debug for Module {
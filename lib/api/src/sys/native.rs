@@ -9,7 +9,10 @@
 //! ```
 use std::marker::PhantomData;
 
+use crate::sys::call_context::enter_instance;
 use crate::sys::externals::function::{DynamicFunction, VMDynamicFunction};
+use crate::sys::non_send::NonSendNativeFunc;
+use crate::sys::scoped::ScopedNativeFunc;
 use crate::sys::{FromToNativeWasmType, Function, RuntimeError, Store, WasmTypeList};
 use std::panic::{catch_unwind, AssertUnwindSafe};
 use wasmer_types::NativeWasmType;
@@ -142,14 +145,15 @@ macro_rules! impl_native_traits {
                         }
                         rets_list.as_mut()
                     };
-                    unsafe {
+                    let instance_ref = self.exported.vm_function.instance_ref.as_ref();
+                    enter_instance(&self.store, instance_ref, || unsafe {
                         wasmer_vm::wasmer_call_trampoline(
                             self.vmctx(),
                             trampoline,
                             self.address(),
                             args_rets.as_mut_ptr() as *mut u8,
                         )
-                    }?;
+                    }).map_err(|trap| self.store.runtime_error_from_trap(trap))?;
                     let num_rets = rets_list.len();
                     if !using_rets_array && num_rets > 0 {
                         let src_pointer = params_list.as_ptr();
@@ -209,6 +213,30 @@ macro_rules! impl_native_traits {
             }
 
         }
+
+        #[allow(unused_parens, non_snake_case)]
+        impl<$( $x , )* Rets> ScopedNativeFunc<'_, ( $( $x ),* ), Rets>
+        where
+            $( $x: FromToNativeWasmType, )*
+            Rets: WasmTypeList,
+        {
+            /// Call the typed func and return results.
+            pub fn call(&self, $( $x: $x, )* ) -> Result<Rets, RuntimeError> {
+                self.func.call($( $x, )*)
+            }
+        }
+
+        #[allow(unused_parens, non_snake_case)]
+        impl<$( $x , )* Rets> NonSendNativeFunc<'_, ( $( $x ),* ), Rets>
+        where
+            $( $x: FromToNativeWasmType, )*
+            Rets: WasmTypeList,
+        {
+            /// Call the typed func and return results.
+            pub fn call(&self, $( $x: $x, )* ) -> Result<Rets, RuntimeError> {
+                self.func.call($( $x, )*)
+            }
+        }
     };
 }
 
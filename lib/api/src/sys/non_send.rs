@@ -0,0 +1,265 @@
+//! Host functions and instances bound to the thread that created them.
+//!
+//! See [`Function::new_st`](crate::Function::new_st).
+
+use crate::sys::exports::{ExportError, Exportable};
+use crate::sys::import_object::ImportObject;
+use crate::sys::instance::{Instance, InstantiationError};
+use crate::sys::module::Module;
+use crate::sys::native::NativeFunc;
+use crate::sys::{Function, FunctionType, Memory, RuntimeError, Val, WasmTypeList};
+use std::cell::{Cell, UnsafeCell};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::thread::ThreadId;
+use wasmer_types::InstanceConfig;
+use wasmer_vm::{Export, NamedResolver};
+
+thread_local! {
+    static THREAD_ID: ThreadId = std::thread::current().id();
+}
+
+/// The identifier of the current thread, or `None` if the thread is being torn down.
+fn current_thread() -> Option<ThreadId> {
+    THREAD_ID.try_with(|id| *id).ok()
+}
+
+/// The closure of a single-threaded host function.
+///
+/// The closure is only ever called and dropped on the thread that created it: the types
+/// holding it are not `Send`, and this holder checks the thread anyway, as instances can
+/// reach their imports in ways the types do not track.
+pub(crate) struct SingleThreaded<F> {
+    owner: ThreadId,
+    running: Cell<bool>,
+    func: ManuallyDrop<UnsafeCell<F>>,
+}
+
+// # Safety
+// `running` and `func` are only accessed on the `owner` thread.
+unsafe impl<F> Send for SingleThreaded<F> {}
+unsafe impl<F> Sync for SingleThreaded<F> {}
+
+impl<F> SingleThreaded<F>
+where
+    F: FnMut(&[Val]) -> Result<Vec<Val>, RuntimeError>,
+{
+    pub(crate) fn new(func: F) -> Self {
+        Self {
+            owner: std::thread::current().id(),
+            running: Cell::new(false),
+            func: ManuallyDrop::new(UnsafeCell::new(func)),
+        }
+    }
+
+    pub(crate) fn call(&self, args: &[Val]) -> Result<Vec<Val>, RuntimeError> {
+        if current_thread() != Some(self.owner) {
+            return Err(RuntimeError::new(
+                "single-threaded host function called from another thread",
+            ));
+        }
+        if self.running.replace(true) {
+            return Err(RuntimeError::new(
+                "single-threaded host function called while it is running",
+            ));
+        }
+        struct Running<'a>(&'a Cell<bool>);
+        impl Drop for Running<'_> {
+            fn drop(&mut self) {
+                self.0.set(false);
+            }
+        }
+        let _running = Running(&self.running);
+        // Safety: on the owner thread and not running, this is the only reference to `func`.
+        let func = unsafe { &mut *self.func.get() };
+        func(args)
+    }
+}
+
+impl<F> Drop for SingleThreaded<F> {
+    fn drop(&mut self) {
+        // Dropping the closure on another thread would drop what it captured there, so it is
+        // leaked instead.
+        if current_thread() == Some(self.owner) {
+            unsafe { ManuallyDrop::drop(&mut self.func) }
+        }
+    }
+}
+
+/// A host function created with [`Function::new_st`], bound to the thread that created it.
+///
+/// It can be imported through [`NonSendImports`] only, by instances that are bound to the
+/// same thread.
+#[derive(Debug)]
+pub struct NonSendFunction {
+    pub(crate) function: Function,
+    pub(crate) _not_send: PhantomData<*const ()>,
+}
+
+impl NonSendFunction {
+    /// Returns the [`FunctionType`] of the function.
+    pub fn ty(&self) -> FunctionType {
+        self.function.ty()
+    }
+
+    /// Call the function with `params`.
+    pub fn call(&self, params: &[Val]) -> Result<Box<[Val]>, RuntimeError> {
+        self.function.call(params)
+    }
+}
+
+/// The imports of a [`NonSendInstance`]: an [`ImportObject`] and [`NonSendFunction`]s, which
+/// take precedence over the imports of the same name in the object.
+pub struct NonSendImports {
+    imports: ImportObject,
+    functions: HashMap<(String, String), NonSendFunction>,
+}
+
+impl NonSendImports {
+    /// Create imports providing those of `imports`.
+    pub fn new(imports: ImportObject) -> Self {
+        Self {
+            imports,
+            functions: HashMap::new(),
+        }
+    }
+
+    /// Provide `function` as the import `name` of `module`, returning the function it
+    /// replaces.
+    pub fn define(
+        &mut self,
+        module: &str,
+        name: &str,
+        function: NonSendFunction,
+    ) -> Option<NonSendFunction> {
+        self.functions
+            .insert((module.to_string(), name.to_string()), function)
+    }
+}
+
+/// Resolves the imports of a [`NonSendInstance`], without letting `NonSendImports` resolve
+/// those of a [`Send`] instance.
+struct NonSendResolver<'a>(&'a NonSendImports);
+
+impl NamedResolver for NonSendResolver<'_> {
+    fn resolve_by_name(&self, module: &str, name: &str) -> Option<Export> {
+        match self
+            .0
+            .functions
+            .get(&(module.to_string(), name.to_string()))
+        {
+            Some(function) => Some(function.function.to_export()),
+            None => self.0.imports.resolve_by_name(module, name),
+        }
+    }
+}
+
+/// An instance importing [`NonSendFunction`]s, bound to the thread that created it.
+///
+/// Its exported functions are only reachable through handles borrowing it, which cannot leave
+/// the thread either.
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let store = Store::default();
+/// let module = Module::new(&store, r#"
+///     (module
+///         (import "env" "next" (func $next (result i32)))
+///         (func (export "next") (result i32) (call $next)))
+/// "#)?;
+/// let mut count = 0;
+/// let next = Function::new_st(&store, ([], [Type::I32]), move |_| {
+///     count += 1;
+///     Ok(vec![Value::I32(count)])
+/// });
+/// let mut imports = NonSendImports::new(imports! {});
+/// imports.define("env", "next", next);
+/// let instance = NonSendInstance::new(&module, &imports)?;
+/// let next = instance.get_native_function::<(), i32>("next")?;
+/// assert_eq!(next.call()?, 1);
+/// assert_eq!(next.call()?, 2);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The instance cannot be sent to another thread:
+///
+/// ```compile_fail
+/// # use wasmer::*;
+/// # let store = Store::default();
+/// # let module = Module::new(&store, "(module)").unwrap();
+/// fn assert_send<T: Send>(_: &T) {}
+/// let instance = NonSendInstance::new(&module, &NonSendImports::new(imports! {})).unwrap();
+/// assert_send(&instance);
+/// ```
+///
+/// Nor can its functions:
+///
+/// ```compile_fail
+/// # use wasmer::*;
+/// # let store = Store::default();
+/// # let module = Module::new(&store, r#"(module (func (export "f")))"#).unwrap();
+/// fn assert_send<T: Send>(_: &T) {}
+/// let instance = NonSendInstance::new(&module, &NonSendImports::new(imports! {})).unwrap();
+/// let f = instance.get_native_function::<(), ()>("f").unwrap();
+/// assert_send(&f);
+/// ```
+pub struct NonSendInstance {
+    instance: Instance,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl NonSendInstance {
+    /// Creates a new instance of `module` with `imports`.
+    ///
+    /// See [`Instance::new`].
+    pub fn new(module: &Module, imports: &NonSendImports) -> Result<Self, InstantiationError> {
+        Self::new_with_config(module, InstanceConfig::default(), imports)
+    }
+
+    /// New instance with config.
+    pub fn new_with_config(
+        module: &Module,
+        config: InstanceConfig,
+        imports: &NonSendImports,
+    ) -> Result<Self, InstantiationError> {
+        Ok(Self {
+            instance: Instance::new_with_config(module, config, &NonSendResolver(imports))?,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Get an exported function as a [`NonSendNativeFunc`].
+    pub fn get_native_function<Args, Rets>(
+        &self,
+        name: &str,
+    ) -> Result<NonSendNativeFunc<'_, Args, Rets>, ExportError>
+    where
+        Args: WasmTypeList,
+        Rets: WasmTypeList,
+    {
+        Ok(NonSendNativeFunc {
+            func: self.instance.get_native_function(name)?,
+            _instance: PhantomData,
+        })
+    }
+
+    /// Lookup an exported memory by its name.
+    pub fn lookup_memory(&self, name: &str) -> Option<Memory> {
+        self.instance.lookup_memory(name)
+    }
+
+    /// Return the number of calls the host made into this instance.
+    pub fn call_sequence(&self) -> u64 {
+        self.instance.call_sequence()
+    }
+}
+
+/// A function exported by a [`NonSendInstance`], callable with the native ABI like a
+/// [`NativeFunc`].
+pub struct NonSendNativeFunc<'s, Args = (), Rets = ()> {
+    pub(crate) func: NativeFunc<Args, Rets>,
+    _instance: PhantomData<&'s NonSendInstance>,
+}
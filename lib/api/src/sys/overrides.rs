@@ -0,0 +1,269 @@
+//! Overriding imported host functions for the duration of a single call.
+
+use crate::sys::execution_plan::{with_gas_guard, Guards};
+use crate::sys::exports::{ExportError, Exportable};
+use crate::sys::instance::Instance;
+use crate::sys::module::Module;
+use crate::sys::{Function, HostEnvInitError, RuntimeError, Val, WasmerEnv};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use thiserror::Error;
+use wasmer_types::{
+    Classify, ErrorCode, Extensions, ExternType, FailureKind, FunctionType, HasErrorCode,
+    InstanceConfig,
+};
+use wasmer_vm::{Export, Resolver};
+
+/// An error calling an instance with [`Instance::guarded_call_with_overrides`], rather than
+/// one of the call itself.
+#[derive(Error, Debug)]
+pub enum OverrideError {
+    /// The instance does not export the function called.
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    /// The import was not declared overridable with
+    /// [`InstanceConfig::with_overridable_import`], or is not an imported function.
+    #[error(
+        "the import {module}.{field} is not overridable [{}]",
+        ErrorCode::OverrideNotOverridable
+    )]
+    NotOverridable {
+        /// The module name of the import.
+        module: String,
+        /// The field name of the import.
+        field: String,
+    },
+    /// The override does not have the signature of the import.
+    #[error(
+        "the override of {module}.{field} has the signature {given}, not {expected} [{}]",
+        ErrorCode::OverrideSignatureMismatch
+    )]
+    SignatureMismatch {
+        /// The module name of the import.
+        module: String,
+        /// The field name of the import.
+        field: String,
+        /// The signature of the import.
+        expected: FunctionType,
+        /// The signature of the override.
+        given: FunctionType,
+    },
+    /// The environment of an override could not be initialized for the instance.
+    #[error(transparent)]
+    HostEnvInitialization(#[from] HostEnvInitError),
+    /// The call failed, or was not started because of its deadline.
+    #[error(transparent)]
+    Call(#[from] RuntimeError),
+}
+
+impl HasErrorCode for OverrideError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Export(e) => e.code(),
+            Self::NotOverridable { .. } => ErrorCode::OverrideNotOverridable,
+            Self::SignatureMismatch { .. } => ErrorCode::OverrideSignatureMismatch,
+            Self::HostEnvInitialization(e) => e.code(),
+            Self::Call(e) => e.code(),
+        }
+    }
+}
+
+impl Classify for OverrideError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::Export(_)
+            | Self::NotOverridable { .. }
+            | Self::SignatureMismatch { .. }
+            | Self::HostEnvInitialization(_) => FailureKind::Permanent,
+            Self::Call(e) => e.failure_kind(),
+        }
+    }
+}
+
+/// The slot of each overridable import of an instance, by module and field name, along with
+/// its signature. Kept as an extension of the instance.
+struct OverrideSlots(HashMap<(String, String), (u64, FunctionType)>);
+
+/// The source of the slots, unique across instances.
+static NEXT_SLOT: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// The overrides of the calls running on this thread, by slot, innermost call last.
+    static ACTIVE_OVERRIDES: RefCell<Vec<HashMap<u64, Function>>> = RefCell::new(Vec::new());
+}
+
+/// Resolves the overridable imports of an instance to host functions calling the override of
+/// the running call if there is one, and the import the inner resolver gives otherwise.
+pub(crate) struct OverridableResolver<'a> {
+    inner: &'a dyn Resolver,
+    module: &'a Module,
+    slots: HashMap<(String, String), u64>,
+}
+
+impl<'a> OverridableResolver<'a> {
+    /// Resolve the imports of an instance of `module` with `inner`, giving the overridable
+    /// imports of `config` a slot, recorded in the extensions of `config`.
+    pub(crate) fn new(
+        module: &'a Module,
+        config: &mut InstanceConfig,
+        inner: &'a dyn Resolver,
+    ) -> Self {
+        let mut slots = HashMap::new();
+        for (module_name, field, ty) in module.imports() {
+            let ty = match ty {
+                ExternType::Function(ty) => ty,
+                _ => continue,
+            };
+            let declared = config
+                .overridable_imports
+                .iter()
+                .any(|(m, f)| m == module_name && f == field);
+            if declared {
+                let slot = NEXT_SLOT.fetch_add(1, Ordering::Relaxed);
+                slots.insert((module_name.to_string(), field.to_string()), (slot, ty));
+            }
+        }
+        let resolver = Self {
+            inner,
+            module,
+            slots: slots
+                .iter()
+                .map(|(name, (slot, _))| (name.clone(), *slot))
+                .collect(),
+        };
+        if !slots.is_empty() {
+            config.extensions.insert(OverrideSlots(slots));
+        }
+        resolver
+    }
+}
+
+impl OverridableResolver<'_> {
+    /// Wrap `export` in a shim if it resolves an overridable import.
+    fn shim(&self, module: &str, field: &str, export: Export) -> Export {
+        let slot = match self.slots.get(&(module.to_string(), field.to_string())) {
+            Some(slot) => *slot,
+            None => return export,
+        };
+        let original = match export {
+            Export::Function(f) => Function::from_vm_export(self.module.store(), f),
+            other => return other,
+        };
+        let shim = Shim {
+            slot,
+            original: original.clone(),
+            bound: None,
+        };
+        let function = Function::new_with_env(self.module.store(), original.ty(), shim, call_shim);
+        function.to_export()
+    }
+}
+
+impl<'a> Resolver for OverridableResolver<'a> {
+    fn resolve(&self, index: u32, module: &str, field: &str) -> Option<Export> {
+        let export = self.inner.resolve(index, module, field)?;
+        Some(self.shim(module, field, export))
+    }
+
+    fn try_resolve(
+        &self,
+        index: u32,
+        module: &str,
+        field: &str,
+    ) -> Result<Option<Export>, Box<dyn std::error::Error + Send + Sync>> {
+        let export = self.inner.try_resolve(index, module, field)?;
+        Ok(export.map(|export| self.shim(module, field, export)))
+    }
+}
+
+/// The environment of the host function an overridable import resolves to.
+#[derive(Clone)]
+struct Shim {
+    slot: u64,
+    /// The function the import resolved to.
+    original: Function,
+    /// The function the import resolved to, with its environment initialized for the
+    /// instance.
+    bound: Option<Function>,
+}
+
+impl WasmerEnv for Shim {
+    fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
+        self.bound = Some(self.original.bind_to_instance(instance)?);
+        Ok(())
+    }
+}
+
+fn call_shim(shim: &Shim, args: &[Val]) -> Result<Vec<Val>, RuntimeError> {
+    let active = ACTIVE_OVERRIDES.with(|active| {
+        let active = active.borrow();
+        active
+            .iter()
+            .rev()
+            .find_map(|overrides| overrides.get(&shim.slot).cloned())
+    });
+    let function = match (&active, &shim.bound) {
+        (Some(function), _) | (None, Some(function)) => function,
+        (None, None) => &shim.original,
+    };
+    Ok(function.call(args)?.into_vec())
+}
+
+/// Pops the overrides of a call once it returns, or unwinds.
+struct ActiveGuard;
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        ACTIVE_OVERRIDES.with(|active| active.borrow_mut().pop());
+    }
+}
+
+/// See [`Instance::guarded_call_with_overrides`].
+pub(crate) fn guarded_call_with_overrides(
+    instance: &Instance,
+    extensions: &Extensions,
+    entry: &str,
+    params: &[Val],
+    guards: Guards,
+    overrides: &[(&str, &str, Function)],
+) -> Result<Box<[Val]>, OverrideError> {
+    let function = match instance.lookup(entry) {
+        Some(Export::Function(_)) => instance.lookup_function(entry).unwrap(),
+        Some(_) => return Err(ExportError::IncompatibleType.into()),
+        None => return Err(ExportError::Missing(entry.to_string()).into()),
+    };
+    let slots = extensions.get::<OverrideSlots>();
+    let mut active = HashMap::with_capacity(overrides.len());
+    for (module, field, function) in overrides {
+        let slot = slots.and_then(|slots| slots.0.get(&(module.to_string(), field.to_string())));
+        let (slot, expected) = slot.ok_or_else(|| OverrideError::NotOverridable {
+            module: module.to_string(),
+            field: field.to_string(),
+        })?;
+        let given = function.ty();
+        if given != *expected {
+            return Err(OverrideError::SignatureMismatch {
+                module: module.to_string(),
+                field: field.to_string(),
+                expected: expected.clone(),
+                given,
+            });
+        }
+        active.insert(*slot, function.bind_to_instance(instance)?);
+    }
+    if guards
+        .deadline
+        .map_or(false, |deadline| Instant::now() >= deadline)
+    {
+        return Err(RuntimeError::new("the deadline of the call passed").into());
+    }
+    // Nested calls into the instance from the host functions it calls see the overrides too,
+    // unless they override the same imports, and calls on other threads do not.
+    ACTIVE_OVERRIDES.with(|stack| stack.borrow_mut().push(active));
+    let _guard = ActiveGuard;
+    Ok(with_gas_guard(instance, guards.gas, || {
+        function.call(params)
+    })?)
+}
@@ -0,0 +1,1026 @@
+//! Recording the import calls of an execution and replaying them without the host.
+//!
+//! A [`ReplayRecorder`] stands between an instance and its imports and logs every call the
+//! instance makes to an imported function: its arguments, its results or the error it raised,
+//! and the bytes it wrote to the memory of the instance. A [`ReplayPlayer`] answers the same
+//! calls from the [`ReplayLog`] alone, so that an execution can be reproduced away from the
+//! host that made it, and reports where the replayed execution stops matching the recorded one.
+//!
+//! Only function imports are recorded: memories, tables and globals are shared with the host
+//! rather than called, and the player leaves them to the resolvers chained after it. Calls the
+//! host makes back into the instance from an import are not replayed, only their effect on the
+//! memory is.
+
+use crate::sys::env::{HostEnvInitError, WasmerEnv};
+use crate::sys::exports::Exportable;
+use crate::sys::externals::{Extern, Function, Memory};
+use crate::sys::import_object::ImportObject;
+use crate::sys::instance::{Instance, InstantiationError};
+use crate::sys::module::Module;
+use crate::sys::store::Store;
+use crate::sys::types::{decode_values, encode_values};
+use crate::sys::{ExternType, FunctionType, RuntimeError, Val, ValType};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use wasmer_types::{Classify, ErrorCode, FailureKind, HasErrorCode, Mutability, Pages};
+use wasmer_vm::{Export, ExportFunction, NamedResolver, Trap};
+
+/// The magic bytes starting a serialized [`ReplayLog`].
+const MAGIC: &[u8; 4] = b"wrpl";
+
+/// The version of the format of serialized [`ReplayLog`]s.
+const VERSION: u8 = 1;
+
+/// An error reading a serialized [`ReplayLog`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ReplayLogError {
+    /// The log was written in a format this version cannot read.
+    #[error(
+        "replay log version {0} is not supported [{}]",
+        ErrorCode::ReplayLogVersion
+    )]
+    UnsupportedVersion(u8),
+    /// The log is truncated or damaged.
+    #[error(
+        "replay log is corrupted at byte {offset} [{}]",
+        ErrorCode::ReplayLogCorrupted
+    )]
+    Corrupted {
+        /// The offset of the first byte that could not be read.
+        offset: usize,
+    },
+}
+
+impl HasErrorCode for ReplayLogError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnsupportedVersion(_) => ErrorCode::ReplayLogVersion,
+            Self::Corrupted { .. } => ErrorCode::ReplayLogCorrupted,
+        }
+    }
+}
+
+impl Classify for ReplayLogError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::UnsupportedVersion(_) => FailureKind::Permanent,
+            Self::Corrupted { .. } => FailureKind::Corrupt,
+        }
+    }
+}
+
+/// What a [`ReplayDivergence`] found to differ from the log.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DivergenceField {
+    /// Another import was called, or an import was called after the end of the log, or the
+    /// execution ended before the end of the log.
+    Import,
+    /// The import was called with other arguments.
+    Arguments,
+    /// The memory of the instance cannot hold what the import wrote to it.
+    Memory,
+}
+
+impl fmt::Display for DivergenceField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Import => "import",
+            Self::Arguments => "arguments",
+            Self::Memory => "memory",
+        })
+    }
+}
+
+/// The first difference between a replayed execution and the recorded one.
+///
+/// The imports of a [`ReplayPlayer`] raise it as a user error, see
+/// [`RuntimeError::downcast`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error(
+    "replay diverged at call {call_index}: expected {field} {expected}, found {actual} [{}]",
+    ErrorCode::ReplayDivergence
+)]
+pub struct ReplayDivergence {
+    /// The index of the call in the log, from 0.
+    pub call_index: u64,
+    /// What differs.
+    pub field: DivergenceField,
+    /// What the log recorded.
+    pub expected: String,
+    /// What the replayed execution did.
+    pub actual: String,
+}
+
+impl HasErrorCode for ReplayDivergence {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::ReplayDivergence
+    }
+}
+
+impl Classify for ReplayDivergence {
+    fn failure_kind(&self) -> FailureKind {
+        FailureKind::Permanent
+    }
+}
+
+/// An imported function, as resolved while recording.
+#[derive(Clone, Debug)]
+struct RecordedImport {
+    module: String,
+    field: String,
+    ty: FunctionType,
+}
+
+/// A call to an imported function.
+#[derive(Clone, Debug)]
+struct RecordedCall {
+    import: u32,
+    /// The arguments of the call, encoded.
+    args: Vec<u8>,
+    /// The encoded results of the call, or the message of the error it raised.
+    outcome: Result<Vec<u8>, String>,
+    /// What the call did to the memory, if the instance exports one.
+    memory: Option<MemoryEffect>,
+}
+
+/// The size of the memory after a call and the runs of bytes the call changed.
+#[derive(Clone, Debug)]
+struct MemoryEffect {
+    pages: u32,
+    writes: Vec<(u32, Vec<u8>)>,
+}
+
+/// The import calls of an execution, in the order they started.
+///
+/// See [`ReplayRecorder`] and [`ReplayPlayer`].
+#[derive(Clone, Debug, Default)]
+pub struct ReplayLog {
+    imports: Vec<RecordedImport>,
+    calls: Vec<RecordedCall>,
+}
+
+impl ReplayLog {
+    /// The number of calls in the log.
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    /// Whether the log has no calls.
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// Serialize the log.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        put_len(&mut bytes, self.imports.len());
+        for import in &self.imports {
+            put_bytes(&mut bytes, import.module.as_bytes());
+            put_bytes(&mut bytes, import.field.as_bytes());
+            put_types(&mut bytes, import.ty.params());
+            put_types(&mut bytes, import.ty.results());
+        }
+        put_len(&mut bytes, self.calls.len());
+        for call in &self.calls {
+            put_u32(&mut bytes, call.import);
+            put_bytes(&mut bytes, &call.args);
+            match &call.outcome {
+                Ok(values) => {
+                    bytes.push(0);
+                    put_bytes(&mut bytes, values);
+                }
+                Err(message) => {
+                    bytes.push(1);
+                    put_bytes(&mut bytes, message.as_bytes());
+                }
+            }
+            match &call.memory {
+                None => bytes.push(0),
+                Some(effect) => {
+                    bytes.push(1);
+                    put_u32(&mut bytes, effect.pages);
+                    put_len(&mut bytes, effect.writes.len());
+                    for (offset, written) in &effect.writes {
+                        put_u32(&mut bytes, *offset);
+                        put_bytes(&mut bytes, written);
+                    }
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Deserialize a log serialized with [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayLogError> {
+        let mut reader = Reader { bytes, offset: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(ReplayLogError::Corrupted { offset: 0 });
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(ReplayLogError::UnsupportedVersion(version));
+        }
+        let mut log = Self::default();
+        for _ in 0..reader.u32()? {
+            let module = reader.string()?;
+            let field = reader.string()?;
+            let params = reader.types()?;
+            let results = reader.types()?;
+            log.imports.push(RecordedImport {
+                module,
+                field,
+                ty: FunctionType::new(params, results),
+            });
+        }
+        for _ in 0..reader.u32()? {
+            let offset = reader.offset;
+            let import = reader.u32()?;
+            if import as usize >= log.imports.len() {
+                return Err(ReplayLogError::Corrupted { offset });
+            }
+            let args = reader.values()?;
+            let outcome = match reader.u8()? {
+                0 => Ok(reader.values()?),
+                1 => Err(reader.string()?),
+                _ => return Err(reader.corrupted_before(1)),
+            };
+            let memory = match reader.u8()? {
+                0 => None,
+                1 => {
+                    let pages = reader.u32()?;
+                    let mut writes = Vec::new();
+                    for _ in 0..reader.u32()? {
+                        let offset = reader.u32()?;
+                        writes.push((offset, reader.bytes()?.to_vec()));
+                    }
+                    Some(MemoryEffect { pages, writes })
+                }
+                _ => return Err(reader.corrupted_before(1)),
+            };
+            log.calls.push(RecordedCall {
+                import,
+                args,
+                outcome,
+                memory,
+            });
+        }
+        if reader.offset != bytes.len() {
+            return Err(reader.corrupted_before(0));
+        }
+        Ok(log)
+    }
+
+    /// The name of the import `import`, as `module.field`.
+    fn import_name(&self, import: u32) -> String {
+        let import = &self.imports[import as usize];
+        format!("{}.{}", import.module, import.field)
+    }
+
+    /// The index of the import `field` of `module` with type `ty`, added if it is new.
+    fn import_index(&mut self, module: &str, field: &str, ty: &FunctionType) -> u32 {
+        let index =
+            match self.imports.iter().position(|import| {
+                import.module == module && import.field == field && import.ty == *ty
+            }) {
+                Some(index) => index,
+                None => {
+                    self.imports.push(RecordedImport {
+                        module: module.to_string(),
+                        field: field.to_string(),
+                        ty: ty.clone(),
+                    });
+                    self.imports.len() - 1
+                }
+            };
+        index as u32
+    }
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_len(bytes: &mut Vec<u8>, len: usize) {
+    put_u32(
+        bytes,
+        len.try_into().expect("replay logs hold less than 4 GiB"),
+    );
+}
+
+fn put_bytes(bytes: &mut Vec<u8>, data: &[u8]) {
+    put_len(bytes, data.len());
+    bytes.extend_from_slice(data);
+}
+
+fn put_types(bytes: &mut Vec<u8>, types: &[ValType]) {
+    put_len(bytes, types.len());
+    bytes.extend(types.iter().map(|ty| type_byte(*ty)));
+}
+
+/// Reads a serialized [`ReplayLog`].
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// The error for a value that started `len` bytes before the current offset.
+    fn corrupted_before(&self, len: usize) -> ReplayLogError {
+        ReplayLogError::Corrupted {
+            offset: self.offset - len,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ReplayLogError> {
+        let bytes = self.bytes[self.offset..]
+            .get(..len)
+            .ok_or(ReplayLogError::Corrupted {
+                offset: self.offset,
+            })?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, ReplayLogError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, ReplayLogError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], ReplayLogError> {
+        let len = self.u32()?;
+        self.take(len as usize)
+    }
+
+    fn string(&mut self) -> Result<String, ReplayLogError> {
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| self.corrupted_before(bytes.len()))
+    }
+
+    fn types(&mut self) -> Result<Vec<ValType>, ReplayLogError> {
+        let bytes = self.bytes()?;
+        bytes
+            .iter()
+            .map(|byte| byte_type(*byte))
+            .collect::<Option<_>>()
+            .ok_or_else(|| self.corrupted_before(bytes.len()))
+    }
+
+    /// Encoded values, which are checked to decode.
+    fn values(&mut self) -> Result<Vec<u8>, ReplayLogError> {
+        let bytes = self.bytes()?;
+        match decode_values(bytes) {
+            Some(_) => Ok(bytes.to_vec()),
+            None => Err(self.corrupted_before(bytes.len())),
+        }
+    }
+}
+
+/// The byte encoding `ty` in WebAssembly modules.
+fn type_byte(ty: ValType) -> u8 {
+    match ty {
+        ValType::I32 => 0x7F,
+        ValType::I64 => 0x7E,
+        ValType::F32 => 0x7D,
+        ValType::F64 => 0x7C,
+        ValType::V128 => 0x7B,
+        ValType::FuncRef => 0x70,
+        ValType::ExternRef => 0x6F,
+    }
+}
+
+fn byte_type(byte: u8) -> Option<ValType> {
+    Some(match byte {
+        0x7F => ValType::I32,
+        0x7E => ValType::I64,
+        0x7D => ValType::F32,
+        0x7C => ValType::F64,
+        0x7B => ValType::V128,
+        0x70 => ValType::FuncRef,
+        0x6F => ValType::ExternRef,
+        _ => return None,
+    })
+}
+
+/// Resolves imports like an [`ImportObject`] while logging the calls to its functions.
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let store = Store::default();
+/// let module = Module::new(&store, r#"
+///     (module
+///         (import "env" "random" (func $random (result i64)))
+///         (func (export "roll") (result i64)
+///             (i64.rem_u (call $random) (i64.const 6))))
+/// "#)?;
+/// let random = Function::new_native(&store, || -> i64 { 1234 });
+/// let recorder = ReplayRecorder::wrap(&store, &imports! { "env" => { "random" => random } });
+/// let instance = Instance::new(&module, &recorder)?;
+/// let roll = instance.get_native_function::<(), i64>("roll")?;
+/// assert_eq!(roll.call()?, 4);
+///
+/// // Replaying needs nothing from the host.
+/// let log = ReplayLog::from_bytes(&recorder.log().to_bytes())?;
+/// let player = ReplayPlayer::from_log(&store, log);
+/// let instance = Instance::new(&module, &player)?;
+/// let roll = instance.get_native_function::<(), i64>("roll")?;
+/// assert_eq!(roll.call()?, 4);
+/// player.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Host functions run as they would without the recorder, with their environments initialized
+/// against an instance exporting what the recorded instance does, except that imports called
+/// while the instance is being started, before the environments are initialized, fail.
+/// Calls with reference arguments or results cannot be recorded and fail as well.
+pub struct ReplayRecorder {
+    store: Store,
+    imports: ImportObject,
+    recording: Arc<Mutex<Recording>>,
+}
+
+struct Recording {
+    log: ReplayLog,
+    /// The compiled forwarders, by their bytes.
+    forwarders: HashMap<Vec<u8>, Module>,
+}
+
+impl ReplayRecorder {
+    /// Record the calls to the functions of `imports`, which must belong to `store`.
+    pub fn wrap(store: &Store, imports: &ImportObject) -> Self {
+        Self {
+            store: store.clone(),
+            imports: imports.clone(),
+            recording: Arc::new(Mutex::new(Recording {
+                log: ReplayLog::default(),
+                forwarders: HashMap::new(),
+            })),
+        }
+    }
+
+    /// The calls recorded so far.
+    pub fn log(&self) -> ReplayLog {
+        self.recording.lock().unwrap().log.clone()
+    }
+}
+
+impl NamedResolver for ReplayRecorder {
+    fn resolve_by_name(&self, module: &str, field: &str) -> Option<Export> {
+        let function = match self.imports.resolve_by_name(module, field)? {
+            Export::Function(function) => function,
+            other => return Some(other),
+        };
+        let ty = Function::from_vm_export(&self.store, function.clone()).ty();
+        let import = self
+            .recording
+            .lock()
+            .unwrap()
+            .log
+            .import_index(module, field, &ty);
+        let env = RecordEnv {
+            recording: self.recording.clone(),
+            store: self.store.clone(),
+            import,
+            host: function,
+            ty: ty.clone(),
+            linked: None,
+        };
+        Some(Function::new_with_env(&self.store, ty, env, record).to_export())
+    }
+}
+
+/// The environment of the function standing for an import while recording.
+///
+/// The import cannot be called directly: host functions can only be called from WebAssembly,
+/// and their environments are initialized against the instance importing them. Each recorded
+/// instance therefore gets a forwarder, a module importing the function and re-exporting the
+/// exports of the instance, with a function calling the import on behalf of the recorder.
+#[derive(Clone)]
+struct RecordEnv {
+    recording: Arc<Mutex<Recording>>,
+    store: Store,
+    import: u32,
+    host: ExportFunction,
+    ty: FunctionType,
+    linked: Option<Linked>,
+}
+
+/// What [`RecordEnv`] sets up once the recorded instance exists.
+#[derive(Clone)]
+struct Linked {
+    memory: Option<Memory>,
+    /// The function of the forwarder calling the import.
+    call: Function,
+}
+
+impl WasmerEnv for RecordEnv {
+    fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
+        let exports = weak_exports(instance);
+        let memory = first_memory(&self.store, &exports);
+        let forwarded = forwarded(&self.store, exports);
+        let (bytes, wrapper) = encode_forwarder(&self.ty, &forwarded);
+        let module = {
+            let mut recording = self.recording.lock().unwrap();
+            let store = &self.store;
+            recording
+                .forwarders
+                .entry(bytes)
+                .or_insert_with_key(|bytes| {
+                    Module::new(store, bytes).expect("forwarders are valid modules")
+                })
+                .clone()
+        };
+        let resolver = ForwarderImports {
+            host: Export::Function(self.host.clone()),
+            guest: &forwarded,
+        };
+        let forwarder = match Instance::new(&module, &resolver) {
+            Ok(forwarder) => forwarder,
+            Err(InstantiationError::HostEnvInitialization(error)) => return Err(error),
+            Err(error) => panic!(
+                "could not instantiate the forwarder of an import: {}",
+                error
+            ),
+        };
+        let call = forwarder
+            .lookup_function(&wrapper)
+            .expect("forwarders export their wrapper");
+        self.linked = Some(Linked { memory, call });
+        Ok(())
+    }
+}
+
+fn record(env: &RecordEnv, args: &[Val]) -> Result<Vec<Val>, RuntimeError> {
+    let linked = env.linked.as_ref().ok_or_else(|| {
+        RuntimeError::new("imports cannot be recorded before the instance is initialized")
+    })?;
+    let encoded_args = encode_values(args)
+        .ok_or_else(|| RuntimeError::new("calls with reference arguments cannot be recorded"))?;
+    // The call takes its place in the log before it runs, so that the calls it makes back
+    // into the instance come after it.
+    let index = {
+        let mut recording = env.recording.lock().unwrap();
+        recording.log.calls.push(RecordedCall {
+            import: env.import,
+            args: encoded_args,
+            outcome: Ok(Vec::new()),
+            memory: None,
+        });
+        recording.log.calls.len() - 1
+    };
+    let before = linked
+        .memory
+        .as_ref()
+        .map(|memory| unsafe { memory.data_unchecked() }.to_vec());
+    let result = linked.call.call(args).and_then(|values| {
+        let encoded = encode_values(&values)
+            .ok_or_else(|| RuntimeError::new("calls with reference results cannot be recorded"))?;
+        Ok((values.into_vec(), encoded))
+    });
+    let memory = linked
+        .memory
+        .as_ref()
+        .zip(before)
+        .map(|(memory, before)| MemoryEffect {
+            pages: memory.size().0,
+            writes: diff(&before, unsafe { memory.data_unchecked() }),
+        });
+    let mut recording = env.recording.lock().unwrap();
+    let call = &mut recording.log.calls[index];
+    call.memory = memory;
+    match result {
+        Ok((values, encoded)) => {
+            call.outcome = Ok(encoded);
+            Ok(values)
+        }
+        Err(error) => {
+            call.outcome = Err(error.message());
+            Err(error)
+        }
+    }
+}
+
+/// The runs of bytes of `after` that differ from `before`, which reads as zeros past its end.
+fn diff(before: &[u8], after: &[u8]) -> Vec<(u32, Vec<u8>)> {
+    let mut writes = Vec::new();
+    let mut start = None;
+    for (offset, byte) in after.iter().enumerate() {
+        let changed = *byte != before.get(offset).copied().unwrap_or(0);
+        match (changed, start) {
+            (true, None) => start = Some(offset),
+            (false, Some(run)) => {
+                writes.push((run as u32, after[run..offset].to_vec()));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(run) = start {
+        writes.push((run as u32, after[run..].to_vec()));
+    }
+    writes
+}
+
+/// The exports of `instance`, referring to it weakly so that the environments of its imports
+/// do not keep it alive.
+fn weak_exports(instance: &Instance) -> Vec<(String, Export)> {
+    instance
+        .module()
+        .exports()
+        .filter_map(|(name, _)| {
+            let export = match instance.lookup(name)? {
+                Export::Function(mut function) => {
+                    function.vm_function.instance_ref = function
+                        .vm_function
+                        .instance_ref
+                        .map(|instance_ref| instance_ref.downgrade());
+                    Export::Function(function)
+                }
+                Export::Table(mut table) => {
+                    table.instance_ref = table.instance_ref.map(|r| r.downgrade());
+                    Export::Table(table)
+                }
+                Export::Memory(mut memory) => {
+                    memory.instance_ref = memory.instance_ref.map(|r| r.downgrade());
+                    Export::Memory(memory)
+                }
+                Export::Global(mut global) => {
+                    global.instance_ref = global.instance_ref.map(|r| r.downgrade());
+                    Export::Global(global)
+                }
+            };
+            Some((name.to_string(), export))
+        })
+        .collect()
+}
+
+/// The memory the log records the writes to: the first one the instance exports.
+fn first_memory(store: &Store, exports: &[(String, Export)]) -> Option<Memory> {
+    exports.iter().find_map(|(_, export)| match export {
+        Export::Memory(memory) => Some(Memory::from_vm_export(store, memory.clone())),
+        _ => None,
+    })
+}
+
+/// The exports a forwarder re-exports, with their types.
+///
+/// Host functions look up the memory, functions and globals of the instance calling them, so
+/// tables, other memories, which a module can only import one of, and functions with several
+/// results, which the compiler may not support, are left out.
+fn forwarded(store: &Store, exports: Vec<(String, Export)>) -> Vec<(String, Export, ExternType)> {
+    let mut memory = false;
+    exports
+        .into_iter()
+        .filter_map(|(name, export)| {
+            let ty = match Extern::from_vm_export(store, export.clone()) {
+                Extern::Function(function) => ExternType::Function(function.ty()),
+                Extern::Memory(memory) => ExternType::Memory(memory.ty()),
+                Extern::Global(global) => ExternType::Global(*global.ty()),
+                Extern::Table(table) => ExternType::Table(*table.ty()),
+            };
+            let forwarded = match &ty {
+                ExternType::Function(ty) => ty.results().len() <= 1,
+                ExternType::Memory(_) => !std::mem::replace(&mut memory, true),
+                ExternType::Global(_) => true,
+                ExternType::Table(_) => false,
+            };
+            if forwarded {
+                Some((name, export, ty))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Resolves the imports of a forwarder: the import `f` of `host` and the exports of the
+/// recorded instance in `guest`.
+struct ForwarderImports<'a> {
+    host: Export,
+    guest: &'a [(String, Export, ExternType)],
+}
+
+impl NamedResolver for ForwarderImports<'_> {
+    fn resolve_by_name(&self, module: &str, field: &str) -> Option<Export> {
+        match module {
+            "host" if field == "f" => Some(self.host.clone()),
+            "guest" => self
+                .guest
+                .iter()
+                .find(|(name, _, _)| name == field)
+                .map(|(_, export, _)| export.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// The binary of a forwarder calling an import of type `host` and re-exporting `guest`, with
+/// the name of the function calling the import.
+fn encode_forwarder(
+    host: &FunctionType,
+    guest: &[(String, Export, ExternType)],
+) -> (Vec<u8>, String) {
+    let mut wrapper = "call".to_string();
+    while guest.iter().any(|(name, _, _)| *name == wrapper) {
+        wrapper.insert(0, '_');
+    }
+
+    let mut types = vec![host];
+    let mut imports = Vec::new();
+    leb(&mut imports, 1 + guest.len() as u32);
+    name(&mut imports, "host");
+    name(&mut imports, "f");
+    imports.extend_from_slice(&[0x00, 0x00]);
+    let mut exports = Vec::new();
+    let (mut functions, mut memories, mut globals) = (1, 0, 0);
+    for (field, _, ty) in guest {
+        name(&mut imports, "guest");
+        name(&mut imports, field);
+        let (kind, index) = match ty {
+            ExternType::Function(ty) => {
+                let index = match types.iter().position(|known| *known == ty) {
+                    Some(index) => index,
+                    None => {
+                        types.push(ty);
+                        types.len() - 1
+                    }
+                };
+                imports.push(0x00);
+                leb(&mut imports, index as u32);
+                functions += 1;
+                (0x00, functions - 1)
+            }
+            ExternType::Memory(ty) => {
+                imports.push(0x02);
+                let flags = u8::from(ty.maximum.is_some()) | u8::from(ty.shared) << 1;
+                imports.push(flags);
+                leb(&mut imports, ty.minimum.0);
+                if let Some(maximum) = ty.maximum {
+                    leb(&mut imports, maximum.0);
+                }
+                memories += 1;
+                (0x02, memories - 1)
+            }
+            ExternType::Global(ty) => {
+                imports.push(0x03);
+                imports.push(type_byte(ty.ty));
+                imports.push(u8::from(ty.mutability == Mutability::Var));
+                globals += 1;
+                (0x03, globals - 1)
+            }
+            ExternType::Table(_) => unreachable!("tables are not forwarded"),
+        };
+        exports.push((field.as_str(), kind, index));
+    }
+
+    let mut type_section = Vec::new();
+    leb(&mut type_section, types.len() as u32);
+    for ty in types {
+        type_section.push(0x60);
+        leb(&mut type_section, ty.params().len() as u32);
+        type_section.extend(ty.params().iter().map(|ty| type_byte(*ty)));
+        leb(&mut type_section, ty.results().len() as u32);
+        type_section.extend(ty.results().iter().map(|ty| type_byte(*ty)));
+    }
+    let mut export_section = Vec::new();
+    leb(&mut export_section, 1 + exports.len() as u32);
+    name(&mut export_section, &wrapper);
+    export_section.push(0x00);
+    leb(&mut export_section, functions);
+    for (field, kind, index) in exports {
+        name(&mut export_section, field);
+        export_section.push(kind);
+        leb(&mut export_section, index);
+    }
+    // The wrapper passes its parameters to the import and returns what it returns.
+    let mut body = vec![0x00];
+    for param in 0..host.params().len() {
+        body.push(0x20);
+        leb(&mut body, param as u32);
+    }
+    body.extend_from_slice(&[0x10, 0x00, 0x0B]);
+    let mut code_section = vec![0x01];
+    leb(&mut code_section, body.len() as u32);
+    code_section.extend(body);
+
+    let mut bytes = b"\0asm\x01\0\0\0".to_vec();
+    for (id, section) in [
+        (1, type_section),
+        (2, imports),
+        (3, vec![0x01, 0x00]),
+        (7, export_section),
+        (10, code_section),
+    ] {
+        bytes.push(id);
+        leb(&mut bytes, section.len() as u32);
+        bytes.extend(section);
+    }
+    (bytes, wrapper)
+}
+
+/// Append the unsigned LEB128 encoding of `value`.
+fn leb(bytes: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn name(bytes: &mut Vec<u8>, name: &str) {
+    leb(bytes, name.len() as u32);
+    bytes.extend_from_slice(name.as_bytes());
+}
+
+/// Resolves the function imports of an instance from a [`ReplayLog`], without calling the
+/// host.
+///
+/// Each call to an import must be the next call of the log, with the same arguments: the
+/// player then writes what the recorded call wrote to the memory of the instance and returns
+/// its results, or raises its error with the same message. Otherwise the call raises a
+/// [`ReplayDivergence`], as do all the calls after it. Only the imports found in the log
+/// are resolved, other resolvers can be chained for the others.
+pub struct ReplayPlayer {
+    store: Store,
+    replay: Arc<Mutex<Replay>>,
+}
+
+struct Replay {
+    log: ReplayLog,
+    /// The index of the next call.
+    next: usize,
+    divergence: Option<ReplayDivergence>,
+}
+
+impl Replay {
+    /// Record a divergence at the next call, unless there is one already, and return the
+    /// error reporting it.
+    fn diverge(
+        &mut self,
+        field: DivergenceField,
+        expected: String,
+        actual: String,
+    ) -> RuntimeError {
+        let divergence = self.divergence.get_or_insert(ReplayDivergence {
+            call_index: self.next as u64,
+            field,
+            expected,
+            actual,
+        });
+        RuntimeError::from_trap(Trap::User(Box::new(divergence.clone())))
+    }
+}
+
+impl ReplayPlayer {
+    /// Replay the calls of `log`, with functions belonging to `store`.
+    pub fn from_log(store: &Store, log: ReplayLog) -> Self {
+        Self {
+            store: store.clone(),
+            replay: Arc::new(Mutex::new(Replay {
+                log,
+                next: 0,
+                divergence: None,
+            })),
+        }
+    }
+
+    /// The first divergence found so far, if any.
+    pub fn divergence(&self) -> Option<ReplayDivergence> {
+        self.replay.lock().unwrap().divergence.clone()
+    }
+
+    /// Check that the replayed execution made all the calls of the log and nothing else.
+    pub fn finish(&self) -> Result<(), ReplayDivergence> {
+        let replay = self.replay.lock().unwrap();
+        if let Some(divergence) = &replay.divergence {
+            return Err(divergence.clone());
+        }
+        match replay.log.calls.get(replay.next) {
+            Some(call) => Err(ReplayDivergence {
+                call_index: replay.next as u64,
+                field: DivergenceField::Import,
+                expected: replay.log.import_name(call.import),
+                actual: "the end of the execution".to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl NamedResolver for ReplayPlayer {
+    fn resolve_by_name(&self, module: &str, field: &str) -> Option<Export> {
+        let (import, ty) = {
+            let replay = self.replay.lock().unwrap();
+            let (import, recorded) = replay
+                .log
+                .imports
+                .iter()
+                .enumerate()
+                .find(|(_, import)| import.module == module && import.field == field)?;
+            (import as u32, recorded.ty.clone())
+        };
+        let env = PlayEnv {
+            replay: self.replay.clone(),
+            import,
+            memory: None,
+        };
+        Some(Function::new_with_env(&self.store, ty, env, play).to_export())
+    }
+}
+
+/// The environment of the functions of a [`ReplayPlayer`].
+#[derive(Clone)]
+struct PlayEnv {
+    replay: Arc<Mutex<Replay>>,
+    import: u32,
+    memory: Option<Memory>,
+}
+
+impl WasmerEnv for PlayEnv {
+    fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
+        self.memory = first_memory(instance.store(), &weak_exports(instance));
+        Ok(())
+    }
+}
+
+fn play(env: &PlayEnv, args: &[Val]) -> Result<Vec<Val>, RuntimeError> {
+    let mut replay = env.replay.lock().unwrap();
+    if let Some(divergence) = &replay.divergence {
+        return Err(RuntimeError::from_trap(Trap::User(Box::new(
+            divergence.clone(),
+        ))));
+    }
+    let call = match replay.log.calls.get(replay.next) {
+        Some(call) => call.clone(),
+        None => {
+            let actual = replay.log.import_name(env.import);
+            return Err(replay.diverge(
+                DivergenceField::Import,
+                "the end of the log".to_string(),
+                actual,
+            ));
+        }
+    };
+    if call.import != env.import {
+        let expected = replay.log.import_name(call.import);
+        let actual = replay.log.import_name(env.import);
+        return Err(replay.diverge(DivergenceField::Import, expected, actual));
+    }
+    if encode_values(args).as_ref() != Some(&call.args) {
+        return Err(replay.diverge(
+            DivergenceField::Arguments,
+            format!("{:?}", decoded(&call.args)),
+            format!("{:?}", args),
+        ));
+    }
+    if let Some(effect) = &call.memory {
+        if let Err(actual) = apply(effect, env.memory.as_ref()) {
+            let expected = format!("{} pages", effect.pages);
+            return Err(replay.diverge(DivergenceField::Memory, expected, actual));
+        }
+    }
+    replay.next += 1;
+    match call.outcome {
+        Ok(values) => Ok(decoded(&values)),
+        Err(message) => Err(RuntimeError::new(message)),
+    }
+}
+
+/// Values of a log, which [`ReplayLog::from_bytes`] checked to decode.
+fn decoded(bytes: &[u8]) -> Vec<Val> {
+    decode_values(bytes).expect("logs hold valid values")
+}
+
+/// Make `memory` what `effect` recorded, or describe why it cannot be.
+fn apply(effect: &MemoryEffect, memory: Option<&Memory>) -> Result<(), String> {
+    let memory = memory.ok_or_else(|| "no memory".to_string())?;
+    let pages = memory.size().0;
+    if pages > effect.pages {
+        return Err(format!("{} pages", pages));
+    }
+    if pages < effect.pages {
+        memory
+            .grow(Pages(effect.pages - pages))
+            .map_err(|error| format!("{} pages that cannot grow: {}", pages, error))?;
+    }
+    let data = unsafe { memory.data_unchecked_mut() };
+    for (offset, written) in &effect.writes {
+        let start = *offset as usize;
+        data.get_mut(start..start + written.len())
+            .ok_or_else(|| format!("{} pages written past their end", effect.pages))?
+            .copy_from_slice(written);
+    }
+    Ok(())
+}
@@ -0,0 +1,667 @@
+//! Conversions between WebAssembly values and JSON, for RPC layers.
+//!
+//! JSON numbers are doubles for most consumers, so integers beyond ±(2^53 - 1) cannot be
+//! exchanged as plain numbers without loss: [`I64Encoding`] picks how `i64`s are written, and
+//! decoding accepts any of the encodings. Non-finite floats are written as the string
+//! sentinels `"NaN"`, `"Infinity"` and `"-Infinity"`, and NaNs other than the canonical one as
+//! `"NaN:0x"` followed by their bits in hexadecimal, so that their payload is preserved. `v128`
+//! values are strings of 32 hexadecimal digits, most significant first.
+//!
+//! Decoding is driven by the expected [`Type`], never by the shape of the JSON alone, and
+//! fails rather than silently losing precision. References cannot be converted.
+
+use crate::{FunctionType, Val, ValType as Type};
+use serde_json::Value as Json;
+use std::convert::TryFrom;
+use thiserror::Error;
+use wasmer_types::{ErrorCode, HasErrorCode};
+
+/// The largest magnitude up to which all integers are exactly representable as doubles.
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+/// The bits of the NaN written as plain `"NaN"`.
+const CANONICAL_NAN32: u32 = 0x7fc0_0000;
+const CANONICAL_NAN64: u64 = 0x7ff8_0000_0000_0000;
+
+/// How `i64` values are written to JSON.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum I64Encoding {
+    /// A string holding the decimal value, such as `"-42"`.
+    String,
+    /// A number. Values beyond ±(2^53 - 1) cannot be encoded this way.
+    Number,
+    /// An object holding the high and low 32 bits of the two's complement value as unsigned
+    /// numbers, such as `{"hi": 4294967295, "lo": 4294967254}` for -42.
+    SplitHiLo,
+}
+
+/// An error converting between a WebAssembly value and JSON.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConvertError {
+    /// The JSON value is of a kind that cannot represent the expected type.
+    #[error(
+        "expected {expected}, found {found} [{}]",
+        ErrorCode::ConvertUnexpectedJson
+    )]
+    UnexpectedJson {
+        /// The expected type.
+        expected: Type,
+        /// The kind of JSON value found instead.
+        found: &'static str,
+    },
+    /// The value does not fit the expected type, or its encoding, without loss.
+    #[error(
+        "{value} cannot be represented as {expected} without loss [{}]",
+        ErrorCode::ConvertOutOfRange
+    )]
+    OutOfRange {
+        /// The expected type.
+        expected: Type,
+        /// The offending value.
+        value: String,
+    },
+    /// The string or object is not a valid encoding of a value of the expected type.
+    #[error(
+        "{value} is not a valid encoding of {expected} [{}]",
+        ErrorCode::ConvertInvalidEncoding
+    )]
+    InvalidEncoding {
+        /// The expected type.
+        expected: Type,
+        /// The offending JSON value.
+        value: String,
+    },
+    /// Values of this type cannot be converted.
+    #[error(
+        "values of type {0} cannot be converted to or from JSON [{}]",
+        ErrorCode::ConvertUnsupported
+    )]
+    Unsupported(Type),
+    /// The parameters are not given as an array.
+    #[error(
+        "expected an array of parameters, found {0} [{}]",
+        ErrorCode::ConvertNotAnArray
+    )]
+    NotAnArray(&'static str),
+    /// The number of parameters does not match the function type.
+    #[error(
+        "expected {expected} parameters, found {found} [{}]",
+        ErrorCode::ConvertArity
+    )]
+    Arity {
+        /// The number of parameters of the function.
+        expected: usize,
+        /// The number of parameters given.
+        found: usize,
+    },
+    /// A parameter could not be converted.
+    #[error("parameter {index}: {error}")]
+    Param {
+        /// The position of the parameter.
+        index: usize,
+        /// Why it could not be converted.
+        error: Box<ConvertError>,
+    },
+}
+
+impl HasErrorCode for ConvertError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnexpectedJson { .. } => ErrorCode::ConvertUnexpectedJson,
+            Self::OutOfRange { .. } => ErrorCode::ConvertOutOfRange,
+            Self::InvalidEncoding { .. } => ErrorCode::ConvertInvalidEncoding,
+            Self::Unsupported(_) => ErrorCode::ConvertUnsupported,
+            Self::NotAnArray(_) => ErrorCode::ConvertNotAnArray,
+            Self::Arity { .. } => ErrorCode::ConvertArity,
+            Self::Param { error, .. } => error.code(),
+        }
+    }
+}
+
+/// Write `value` as JSON, encoding `i64`s as `i64_encoding` says.
+///
+/// Fails for references, and for `i64`s beyond ±(2^53 - 1) with [`I64Encoding::Number`].
+pub fn value_to_json(value: &Val, i64_encoding: I64Encoding) -> Result<Json, ConvertError> {
+    Ok(match *value {
+        Val::I32(v) => Json::from(v),
+        Val::I64(v) => match i64_encoding {
+            I64Encoding::String => Json::String(v.to_string()),
+            I64Encoding::Number if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&v) => {
+                Json::from(v)
+            }
+            I64Encoding::Number => {
+                return Err(ConvertError::OutOfRange {
+                    expected: Type::I64,
+                    value: v.to_string(),
+                })
+            }
+            I64Encoding::SplitHiLo => {
+                let bits = v as u64;
+                serde_json::json!({ "hi": bits >> 32, "lo": bits & 0xffff_ffff })
+            }
+        },
+        Val::F32(v) if v.is_nan() => match v.to_bits() {
+            CANONICAL_NAN32 => Json::from("NaN"),
+            bits => Json::String(format!("NaN:0x{:08x}", bits)),
+        },
+        Val::F32(v) => float_to_json(f64::from(v)),
+        Val::F64(v) if v.is_nan() => match v.to_bits() {
+            CANONICAL_NAN64 => Json::from("NaN"),
+            bits => Json::String(format!("NaN:0x{:016x}", bits)),
+        },
+        Val::F64(v) => float_to_json(v),
+        Val::V128(v) => Json::String(format!("{:032x}", v)),
+        Val::ExternRef(_) => return Err(ConvertError::Unsupported(Type::ExternRef)),
+        Val::FuncRef(_) => return Err(ConvertError::Unsupported(Type::FuncRef)),
+    })
+}
+
+/// Write a non-NaN float.
+fn float_to_json(v: f64) -> Json {
+    if v == f64::INFINITY {
+        Json::from("Infinity")
+    } else if v == f64::NEG_INFINITY {
+        Json::from("-Infinity")
+    } else {
+        Json::from(v)
+    }
+}
+
+/// Read a value of type `ty` from JSON.
+///
+/// Integers may be given as numbers or as decimal strings, and `i64`s additionally in any
+/// [`I64Encoding`]. Unsigned values are accepted and wrapped, so that `4294967295` is the
+/// `i32` -1. Numbers with a fractional part, integers out of range, and `i64` numbers beyond
+/// ±(2^53 - 1), which the sender may already have rounded, are rejected. Floats are rounded
+/// to the nearest `f32` as needed, but must not overflow it.
+pub fn json_to_value(json: &Json, ty: &Type) -> Result<Val, ConvertError> {
+    let ty = *ty;
+    match ty {
+        Type::I32 => {
+            let v = json_to_integer(json, ty, i128::from(i32::MIN), i128::from(u32::MAX))?;
+            Ok(Val::I32(v as u32 as i32))
+        }
+        Type::I64 => {
+            if let Json::Object(fields) = json {
+                return hi_lo_to_i64(fields)
+                    .map(Val::I64)
+                    .ok_or_else(|| invalid(json, ty));
+            }
+            let (min, max) = match json {
+                Json::Number(_) => (i128::from(-MAX_SAFE_INTEGER), i128::from(MAX_SAFE_INTEGER)),
+                _ => (i128::from(i64::MIN), i128::from(u64::MAX)),
+            };
+            let v = json_to_integer(json, ty, min, max)?;
+            Ok(Val::I64(v as u64 as i64))
+        }
+        Type::F32 => {
+            if let Json::String(s) = json {
+                return match parse_sentinel(s) {
+                    Some(Sentinel::Nan(None)) => Ok(Val::F32(f32::from_bits(CANONICAL_NAN32))),
+                    Some(Sentinel::Nan(Some(bits))) => u32::try_from(bits)
+                        .ok()
+                        .map(f32::from_bits)
+                        .filter(|v| v.is_nan())
+                        .map(Val::F32)
+                        .ok_or_else(|| invalid(json, ty)),
+                    Some(Sentinel::Infinity) => Ok(Val::F32(f32::INFINITY)),
+                    Some(Sentinel::NegInfinity) => Ok(Val::F32(f32::NEG_INFINITY)),
+                    None => Err(invalid(json, ty)),
+                };
+            }
+            let rounded = json_to_f64(json, ty)? as f32;
+            if rounded.is_infinite() {
+                return Err(ConvertError::OutOfRange {
+                    expected: ty,
+                    value: json.to_string(),
+                });
+            }
+            Ok(Val::F32(rounded))
+        }
+        Type::F64 => {
+            if let Json::String(s) = json {
+                return match parse_sentinel(s) {
+                    Some(Sentinel::Nan(None)) => Ok(Val::F64(f64::from_bits(CANONICAL_NAN64))),
+                    Some(Sentinel::Nan(Some(bits))) => Some(f64::from_bits(bits))
+                        .filter(|v| v.is_nan())
+                        .map(Val::F64)
+                        .ok_or_else(|| invalid(json, ty)),
+                    Some(Sentinel::Infinity) => Ok(Val::F64(f64::INFINITY)),
+                    Some(Sentinel::NegInfinity) => Ok(Val::F64(f64::NEG_INFINITY)),
+                    None => Err(invalid(json, ty)),
+                };
+            }
+            json_to_f64(json, ty).map(Val::F64)
+        }
+        Type::V128 => match json {
+            Json::String(s) if s.len() == 32 && s.bytes().all(|b| b.is_ascii_hexdigit()) => {
+                u128::from_str_radix(s, 16)
+                    .map(Val::V128)
+                    .map_err(|_| invalid(json, ty))
+            }
+            Json::String(_) => Err(invalid(json, ty)),
+            _ => Err(unexpected(json, ty)),
+        },
+        Type::ExternRef | Type::FuncRef => Err(ConvertError::Unsupported(ty)),
+    }
+}
+
+/// Read the parameters of a function of type `ty` from a JSON array.
+pub fn params_from_json(ty: &FunctionType, json: &Json) -> Result<Vec<Val>, ConvertError> {
+    let params = match json {
+        Json::Array(params) => params,
+        _ => return Err(ConvertError::NotAnArray(kind(json))),
+    };
+    if params.len() != ty.params().len() {
+        return Err(ConvertError::Arity {
+            expected: ty.params().len(),
+            found: params.len(),
+        });
+    }
+    params
+        .iter()
+        .zip(ty.params())
+        .enumerate()
+        .map(|(index, (param, ty))| {
+            json_to_value(param, ty).map_err(|error| ConvertError::Param {
+                index,
+                error: Box::new(error),
+            })
+        })
+        .collect()
+}
+
+/// Read an integer within `min..=max` from a number or a decimal string.
+fn json_to_integer(json: &Json, ty: Type, min: i128, max: i128) -> Result<i128, ConvertError> {
+    let out_of_range = || ConvertError::OutOfRange {
+        expected: ty,
+        value: json.to_string(),
+    };
+    let v = match json {
+        Json::Number(n) => {
+            if let Some(v) = n.as_i64() {
+                i128::from(v)
+            } else if let Some(v) = n.as_u64() {
+                i128::from(v)
+            } else {
+                // Doubles beyond the 64-bit range or with a fractional part are never valid.
+                let v = n.as_f64().ok_or_else(out_of_range)?;
+                if v.fract() != 0.0 || v.abs() >= 18_446_744_073_709_551_616.0 {
+                    return Err(out_of_range());
+                }
+                v as i128
+            }
+        }
+        Json::String(s) => s.parse::<i128>().map_err(|_| invalid(json, ty))?,
+        _ => return Err(unexpected(json, ty)),
+    };
+    if v < min || v > max {
+        return Err(out_of_range());
+    }
+    Ok(v)
+}
+
+/// Read an `i64` split as by [`I64Encoding::SplitHiLo`].
+fn hi_lo_to_i64(fields: &serde_json::Map<String, Json>) -> Option<i64> {
+    if fields.len() != 2 {
+        return None;
+    }
+    let half = |name| {
+        fields
+            .get(name)
+            .and_then(Json::as_u64)
+            .filter(|&v| v <= u64::from(u32::MAX))
+    };
+    Some((half("hi")? << 32 | half("lo")?) as i64)
+}
+
+fn json_to_f64(json: &Json, ty: Type) -> Result<f64, ConvertError> {
+    match json {
+        Json::Number(n) => n.as_f64().ok_or_else(|| ConvertError::OutOfRange {
+            expected: ty,
+            value: n.to_string(),
+        }),
+        _ => Err(unexpected(json, ty)),
+    }
+}
+
+enum Sentinel {
+    /// The canonical NaN, or one with the given bits.
+    Nan(Option<u64>),
+    Infinity,
+    NegInfinity,
+}
+
+fn parse_sentinel(s: &str) -> Option<Sentinel> {
+    match s {
+        "NaN" => Some(Sentinel::Nan(None)),
+        "Infinity" => Some(Sentinel::Infinity),
+        "-Infinity" => Some(Sentinel::NegInfinity),
+        _ => {
+            let hex = s.strip_prefix("NaN:0x")?;
+            if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            u64::from_str_radix(hex, 16)
+                .ok()
+                .map(|bits| Sentinel::Nan(Some(bits)))
+        }
+    }
+}
+
+fn kind(json: &Json) -> &'static str {
+    match json {
+        Json::Null => "null",
+        Json::Bool(_) => "a boolean",
+        Json::Number(_) => "a number",
+        Json::String(_) => "a string",
+        Json::Array(_) => "an array",
+        Json::Object(_) => "an object",
+    }
+}
+
+fn unexpected(json: &Json, expected: Type) -> ConvertError {
+    ConvertError::UnexpectedJson {
+        expected,
+        found: kind(json),
+    }
+}
+
+fn invalid(json: &Json, expected: Type) -> ConvertError {
+    ConvertError::InvalidEncoding {
+        expected,
+        value: json.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const ENCODINGS: [I64Encoding; 3] = [
+        I64Encoding::String,
+        I64Encoding::Number,
+        I64Encoding::SplitHiLo,
+    ];
+
+    /// Compare values bit for bit, so that NaNs and signed zeros are told apart.
+    fn bits(value: &Val) -> (Type, u128) {
+        match *value {
+            Val::I32(v) => (Type::I32, u128::from(v as u32)),
+            Val::I64(v) => (Type::I64, u128::from(v as u64)),
+            Val::F32(v) => (Type::F32, u128::from(v.to_bits())),
+            Val::F64(v) => (Type::F64, u128::from(v.to_bits())),
+            Val::V128(v) => (Type::V128, v),
+            _ => panic!("unexpected reference"),
+        }
+    }
+
+    fn round_trip(value: Val, encoding: I64Encoding) {
+        let json = value_to_json(&value, encoding).unwrap();
+        // Also go through the textual form, as an RPC layer would.
+        let json: Json = serde_json::from_str(&json.to_string()).unwrap();
+        let decoded = json_to_value(&json, &value.ty()).unwrap();
+        assert_eq!(bits(&decoded), bits(&value), "{} via {:?}", json, encoding);
+    }
+
+    #[test]
+    fn round_trips() {
+        let mut values = vec![];
+        for &v in &[0, 1, -1, i32::MIN, i32::MAX] {
+            values.push(Val::I32(v));
+        }
+        for &v in &[0, -1, MAX_SAFE_INTEGER, -MAX_SAFE_INTEGER] {
+            values.push(Val::I64(v));
+        }
+        for &v in &[
+            0.0,
+            -0.0,
+            0.1,
+            f32::MIN,
+            f32::MAX,
+            f32::MIN_POSITIVE,
+            f32::EPSILON,
+        ] {
+            values.push(Val::F32(v));
+        }
+        for &bits in &[
+            CANONICAL_NAN32,
+            0xffc0_0000,
+            0x7f80_0001,
+            0x7fff_ffff,
+            0xff80_0000,
+        ] {
+            values.push(Val::F32(f32::from_bits(bits)));
+        }
+        values.push(Val::F32(f32::INFINITY));
+        for &v in &[
+            0.0,
+            -0.0,
+            0.1,
+            f64::MIN,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            5e-324,
+        ] {
+            values.push(Val::F64(v));
+        }
+        for &bits in &[
+            CANONICAL_NAN64,
+            0xfff8_0000_0000_0000,
+            0x7ff0_0000_0000_0001,
+        ] {
+            values.push(Val::F64(f64::from_bits(bits)));
+        }
+        values.push(Val::F64(f64::NEG_INFINITY));
+        for &v in &[0, 1, u128::MAX, 1 << 127] {
+            values.push(Val::V128(v));
+        }
+        for value in values {
+            for &encoding in &ENCODINGS {
+                round_trip(value.clone(), encoding);
+            }
+        }
+
+        for &v in &[
+            i64::MIN,
+            i64::MAX,
+            MAX_SAFE_INTEGER + 1,
+            -MAX_SAFE_INTEGER - 1,
+        ] {
+            round_trip(Val::I64(v), I64Encoding::String);
+            round_trip(Val::I64(v), I64Encoding::SplitHiLo);
+        }
+    }
+
+    #[test]
+    fn encodings() {
+        let encode = |value, encoding| value_to_json(&value, encoding).unwrap();
+        assert_eq!(encode(Val::I64(-42), I64Encoding::String), json!("-42"));
+        assert_eq!(encode(Val::I64(-42), I64Encoding::Number), json!(-42));
+        assert_eq!(
+            encode(Val::I64(-42), I64Encoding::SplitHiLo),
+            json!({ "hi": 0xffff_ffffu32, "lo": 0xffff_ffd6u32 })
+        );
+        assert_eq!(
+            encode(Val::F32(f32::NAN), I64Encoding::String),
+            json!("NaN")
+        );
+        assert_eq!(
+            encode(Val::F32(f32::from_bits(0xffc0_0000)), I64Encoding::String),
+            json!("NaN:0xffc00000")
+        );
+        assert_eq!(
+            encode(Val::F64(f64::INFINITY), I64Encoding::String),
+            json!("Infinity")
+        );
+        assert_eq!(
+            encode(Val::V128(0x0102), I64Encoding::String),
+            json!("00000000000000000000000000000102")
+        );
+    }
+
+    #[test]
+    fn coercions() {
+        let decode = |json: Json, ty| bits(&json_to_value(&json, &ty).unwrap());
+        // Unsigned values wrap.
+        assert_eq!(
+            decode(json!(4_294_967_295u32), Type::I32),
+            bits(&Val::I32(-1))
+        );
+        assert_eq!(
+            decode(json!(2_147_483_648u32), Type::I32),
+            bits(&Val::I32(i32::MIN))
+        );
+        assert_eq!(
+            decode(json!(u64::MAX.to_string()), Type::I64),
+            bits(&Val::I64(-1))
+        );
+        // Integral doubles and decimal strings are integers.
+        assert_eq!(decode(json!(3.0), Type::I32), bits(&Val::I32(3)));
+        assert_eq!(decode(json!("-7"), Type::I32), bits(&Val::I32(-7)));
+        assert_eq!(decode(json!(-7), Type::I64), bits(&Val::I64(-7)));
+        // Integers are floats, rounded to the nearest `f32` as needed.
+        assert_eq!(decode(json!(1), Type::F64), bits(&Val::F64(1.0)));
+        assert_eq!(decode(json!(0.1), Type::F32), bits(&Val::F32(0.1)));
+        assert_eq!(
+            decode(json!(16_777_217), Type::F32),
+            bits(&Val::F32(16_777_216.0))
+        );
+        assert_eq!(
+            decode(json!("NaN:0x7ff4000000000000"), Type::F64),
+            bits(&Val::F64(f64::from_bits(0x7ff4_0000_0000_0000)))
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let decode = |json: Json, ty| json_to_value(&json, &ty).unwrap_err();
+        let out_of_range = |expected, value: &str| ConvertError::OutOfRange {
+            expected,
+            value: value.to_string(),
+        };
+        let invalid = |expected, json: Json| ConvertError::InvalidEncoding {
+            expected,
+            value: json.to_string(),
+        };
+
+        assert_eq!(
+            decode(json!(4_294_967_296u64), Type::I32),
+            out_of_range(Type::I32, "4294967296")
+        );
+        assert_eq!(
+            decode(json!(-2_147_483_649i64), Type::I32),
+            out_of_range(Type::I32, "-2147483649")
+        );
+        assert_eq!(
+            decode(json!(1.5), Type::I64),
+            out_of_range(Type::I64, "1.5")
+        );
+        assert_eq!(
+            decode(json!(1e300), Type::I64),
+            out_of_range(Type::I64, &json!(1e300).to_string())
+        );
+        assert_eq!(
+            decode(json!(MAX_SAFE_INTEGER + 1), Type::I64),
+            out_of_range(Type::I64, "9007199254740992")
+        );
+        assert_eq!(
+            decode(json!(-MAX_SAFE_INTEGER - 1), Type::I64),
+            out_of_range(Type::I64, "-9007199254740992")
+        );
+        assert_eq!(
+            decode(json!("18446744073709551616"), Type::I64),
+            out_of_range(Type::I64, "\"18446744073709551616\"")
+        );
+        assert_eq!(
+            decode(json!("0x10"), Type::I64),
+            invalid(Type::I64, json!("0x10"))
+        );
+        assert_eq!(
+            decode(json!({ "hi": 1, "lo": 4_294_967_296u64 }), Type::I64),
+            invalid(Type::I64, json!({ "hi": 1, "lo": 4_294_967_296u64 }))
+        );
+        assert_eq!(
+            decode(json!(true), Type::I32),
+            ConvertError::UnexpectedJson {
+                expected: Type::I32,
+                found: "a boolean"
+            }
+        );
+        assert_eq!(
+            decode(json!(1e39), Type::F32),
+            out_of_range(Type::F32, &json!(1e39).to_string())
+        );
+        assert_eq!(
+            decode(json!("nan"), Type::F64),
+            invalid(Type::F64, json!("nan"))
+        );
+        assert_eq!(
+            decode(json!("1.5"), Type::F64),
+            invalid(Type::F64, json!("1.5"))
+        );
+        // Not a NaN.
+        assert_eq!(
+            decode(json!("NaN:0x7f800000"), Type::F32),
+            invalid(Type::F32, json!("NaN:0x7f800000"))
+        );
+        assert_eq!(
+            decode(json!("NaN:0x7ff8000000000000"), Type::F32),
+            invalid(Type::F32, json!("NaN:0x7ff8000000000000"))
+        );
+        assert_eq!(
+            decode(json!("0102"), Type::V128),
+            invalid(Type::V128, json!("0102"))
+        );
+        assert_eq!(
+            decode(json!(null), Type::FuncRef),
+            ConvertError::Unsupported(Type::FuncRef)
+        );
+
+        assert_eq!(
+            value_to_json(&Val::I64(i64::MAX), I64Encoding::Number).unwrap_err(),
+            out_of_range(Type::I64, "9223372036854775807")
+        );
+        assert_eq!(
+            value_to_json(&Val::FuncRef(None), I64Encoding::String).unwrap_err(),
+            ConvertError::Unsupported(Type::FuncRef)
+        );
+    }
+
+    #[test]
+    fn params() {
+        let ty = FunctionType::new(vec![Type::I32, Type::I64, Type::F64], vec![]);
+        let params = params_from_json(&ty, &json!([-1, "-1", "Infinity"])).unwrap();
+        assert_eq!(
+            params.iter().map(bits).collect::<Vec<_>>(),
+            vec![
+                bits(&Val::I32(-1)),
+                bits(&Val::I64(-1)),
+                bits(&Val::F64(f64::INFINITY))
+            ]
+        );
+
+        assert_eq!(
+            params_from_json(&ty, &json!({})).unwrap_err(),
+            ConvertError::NotAnArray("an object")
+        );
+        assert_eq!(
+            params_from_json(&ty, &json!([1, 2])).unwrap_err(),
+            ConvertError::Arity {
+                expected: 3,
+                found: 2
+            }
+        );
+        assert_eq!(
+            params_from_json(&ty, &json!([1, 2, "3"])).unwrap_err(),
+            ConvertError::Param {
+                index: 2,
+                error: Box::new(ConvertError::InvalidEncoding {
+                    expected: Type::F64,
+                    value: "\"3\"".to_string()
+                })
+            }
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! Short-lived instances borrowing their module and storage.
+//!
+//! See [`Module::instantiate_scoped`](crate::Module::instantiate_scoped).
+
+use crate::sys::instance::Instance;
+use crate::sys::native::NativeFunc;
+use crate::sys::{ExportError, Memory, WasmTypeList};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use wasmer_types::{MemoryView, Pages, ValueType};
+use wasmer_vm::{InstanceArena, Reclaimable};
+
+/// Storage reused by the [`ScopedInstance`]s created in it.
+///
+/// The `VMContext` of a scoped instance is returned to its scope when the instance is
+/// dropped, and handed to the next instance of the same module rather than freed. Reusing a
+/// single scope for a stream of short-lived instances thus saves their largest allocation.
+#[derive(Debug, Default)]
+pub struct InstanceScope {
+    pub(crate) arena: Arc<InstanceArena>,
+}
+
+impl InstanceScope {
+    /// Create an empty scope.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Free the storage kept for reuse.
+    ///
+    /// No instance of the scope may be alive:
+    ///
+    /// ```compile_fail
+    /// # use wasmer::*;
+    /// # let store = Store::default();
+    /// # let module = Module::new(&store, "(module)").unwrap();
+    /// let mut scope = InstanceScope::new();
+    /// let instance = module
+    ///     .instantiate_scoped(&imports! {}, InstanceConfig::default(), &scope)
+    ///     .unwrap();
+    /// scope.reset();
+    /// drop(instance);
+    /// ```
+    pub fn reset(&mut self) {
+        self.arena.clear();
+    }
+
+    /// The storage kept for reuse, to register with
+    /// [`UniversalEngine::register_reclaimable`](crate::UniversalEngine::register_reclaimable).
+    ///
+    /// Unlike [`reset`](Self::reset), reclaiming it is possible while instances of the scope
+    /// are alive, as it only frees the storage no instance uses.
+    pub fn reclaimable(&self) -> Arc<dyn Reclaimable> {
+        self.arena.clone()
+    }
+}
+
+/// An instance that cannot outlive the [`Module`](crate::Module) it was created from, nor the
+/// [`InstanceScope`] holding its storage.
+///
+/// Its exports are only reachable through handles borrowing it, so that nothing refers to the
+/// instance once it is dropped. It cannot provide imports to other instances either.
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let store = Store::default();
+/// let module = Module::new(&store, r#"(module (func (export "answer") (result i32) i32.const 42))"#)?;
+/// let scope = InstanceScope::new();
+/// for _ in 0..3 {
+///     let instance = module.instantiate_scoped(&imports! {}, InstanceConfig::default(), &scope)?;
+///     let answer = instance.get_native_function::<(), i32>("answer")?;
+///     assert_eq!(answer.call()?, 42);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Handles cannot escape the instance:
+///
+/// ```compile_fail
+/// # use wasmer::*;
+/// # let store = Store::default();
+/// # let module = Module::new(&store, r#"(module (func (export "f")))"#).unwrap();
+/// let scope = InstanceScope::new();
+/// let f = {
+///     let instance = module
+///         .instantiate_scoped(&imports! {}, InstanceConfig::default(), &scope)
+///         .unwrap();
+///     instance.get_native_function::<(), ()>("f").unwrap()
+/// };
+/// f.call().unwrap();
+/// ```
+///
+/// Nor can the instance outlive its module:
+///
+/// ```compile_fail
+/// # use wasmer::*;
+/// # let store = Store::default();
+/// let module = Module::new(&store, "(module)").unwrap();
+/// let scope = InstanceScope::new();
+/// let instance = module
+///     .instantiate_scoped(&imports! {}, InstanceConfig::default(), &scope)
+///     .unwrap();
+/// drop(module);
+/// drop(instance);
+/// ```
+///
+/// Nor be used to resolve the imports of another instance:
+///
+/// ```compile_fail
+/// # use wasmer::*;
+/// # let store = Store::default();
+/// # let module = Module::new(&store, "(module)").unwrap();
+/// let scope = InstanceScope::new();
+/// let instance = module
+///     .instantiate_scoped(&imports! {}, InstanceConfig::default(), &scope)
+///     .unwrap();
+/// Instance::new(&module, &instance).unwrap();
+/// ```
+pub struct ScopedInstance<'a> {
+    pub(crate) instance: Instance,
+    pub(crate) _scope: PhantomData<&'a InstanceScope>,
+}
+
+impl ScopedInstance<'_> {
+    /// Get an exported function as a [`ScopedNativeFunc`].
+    pub fn get_native_function<Args, Rets>(
+        &self,
+        name: &str,
+    ) -> Result<ScopedNativeFunc<'_, Args, Rets>, ExportError>
+    where
+        Args: WasmTypeList,
+        Rets: WasmTypeList,
+    {
+        Ok(ScopedNativeFunc {
+            func: self.instance.get_native_function(name)?,
+            _instance: PhantomData,
+        })
+    }
+
+    /// Lookup an exported memory by its name.
+    pub fn lookup_memory(&self, name: &str) -> Option<ScopedMemory<'_>> {
+        Some(ScopedMemory {
+            memory: self.instance.lookup_memory(name)?,
+            _instance: PhantomData,
+        })
+    }
+
+    /// Return the number of calls the host made into this instance.
+    pub fn call_sequence(&self) -> u64 {
+        self.instance.call_sequence()
+    }
+}
+
+/// A function exported by a [`ScopedInstance`], callable with the native ABI like a
+/// [`NativeFunc`].
+pub struct ScopedNativeFunc<'s, Args = (), Rets = ()> {
+    pub(crate) func: NativeFunc<Args, Rets>,
+    _instance: PhantomData<&'s ScopedInstance<'s>>,
+}
+
+/// A memory exported by a [`ScopedInstance`].
+pub struct ScopedMemory<'s> {
+    memory: Memory,
+    _instance: PhantomData<&'s ScopedInstance<'s>>,
+}
+
+impl ScopedMemory<'_> {
+    /// Returns the size, in pages, of the memory.
+    pub fn size(&self) -> Pages {
+        self.memory.size()
+    }
+
+    /// Returns the size, in bytes, of the memory.
+    pub fn data_size(&self) -> u64 {
+        self.memory.data_size()
+    }
+
+    /// Return a view of the memory as values of type `T`.
+    ///
+    /// See [`Memory::view`].
+    pub fn view<T: ValueType>(&self) -> MemoryView<'_, T> {
+        self.memory.view()
+    }
+}
@@ -3,8 +3,11 @@ use std::fmt;
 use std::sync::Arc;
 #[cfg(all(feature = "compiler", feature = "engine"))]
 use wasmer_compiler::CompilerConfig;
-use wasmer_engine::Engine;
-use wasmer_vm::Tunables;
+use wasmer_engine::{
+    BacktracePolicy, Engine, LightweightTraps, RuntimeError, SharedBacktracePolicy,
+    SymbolicationCache,
+};
+use wasmer_vm::{Trap, TrapCode, Tunables};
 
 /// The store represents all global state that can be manipulated by
 /// WebAssembly programs. It consists of the runtime representation
@@ -20,6 +23,9 @@ use wasmer_vm::Tunables;
 pub struct Store {
     engine: Arc<dyn Engine + Send + Sync>,
     tunables: Arc<dyn Tunables + Send + Sync>,
+    lightweight_traps: Arc<LightweightTraps>,
+    symbolication_cache: Arc<SymbolicationCache>,
+    backtrace_policy: Arc<SharedBacktracePolicy>,
 }
 
 impl Store {
@@ -39,6 +45,9 @@ impl Store {
         Self {
             engine: engine.cloned(),
             tunables: Arc::new(tunables),
+            lightweight_traps: Arc::default(),
+            symbolication_cache: Arc::default(),
+            backtrace_policy: Arc::default(),
         }
     }
 
@@ -52,6 +61,128 @@ impl Store {
         &self.engine
     }
 
+    /// Raise the traps of the given codes as lightweight traps in the calls made through this
+    /// store and its clones, and no others.
+    ///
+    /// Lightweight traps are for the traps expected often, such as running out of gas. They
+    /// skip capturing a backtrace and reuse the preallocated payload of an error no longer
+    /// held, so that raising them does not allocate. Their [`RuntimeError::trace`] is only
+    /// looked up when first asked for, such as to display the error, and only has the frame
+    /// the trap was raised in.
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// store.set_lightweight_traps(&[TrapCode::GasExceeded, TrapCode::UnreachableCodeReached]);
+    /// let module = Module::new(&store, r#"(module (func (export "run") unreachable))"#)?;
+    /// let instance = Instance::new(&module, &imports! {})?;
+    /// let error = instance.lookup_function("run").unwrap().call(&[]).unwrap_err();
+    /// assert_eq!(error.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    /// assert_eq!(error.trace().len(), 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_lightweight_traps(&self, codes: &[TrapCode]) {
+        self.lightweight_traps.set_codes(codes);
+    }
+
+    /// Keep up to `entries` symbolicated frames in the symbolication cache of this store and
+    /// its clones, [`DEFAULT_SYMBOLICATION_CACHE_SIZE`] by default.
+    ///
+    /// The frames of the backtraces of the traps raised in the calls made through the store
+    /// are looked up in the cache before being symbolicated, so that a module trapping at the
+    /// same instruction over and over is not symbolicated each time. Hits and misses are
+    /// reported to the metrics sink of the engine as [`Counter::SymbolicationCacheHits`] and
+    /// [`Counter::SymbolicationCacheMisses`]. A size of 0 disables the cache.
+    ///
+    /// [`DEFAULT_SYMBOLICATION_CACHE_SIZE`]: crate::DEFAULT_SYMBOLICATION_CACHE_SIZE
+    /// [`Counter::SymbolicationCacheHits`]: crate::Counter::SymbolicationCacheHits
+    /// [`Counter::SymbolicationCacheMisses`]: crate::Counter::SymbolicationCacheMisses
+    pub fn set_symbolication_cache_size(&self, entries: usize) {
+        self.symbolication_cache.set_capacity(entries);
+    }
+
+    /// Returns the symbolication cache of this store and its clones, see
+    /// [`Store::set_symbolication_cache_size`].
+    pub fn symbolication_cache(&self) -> &SymbolicationCache {
+        &self.symbolication_cache
+    }
+
+    /// Keep the backtraces of the traps raised in the calls made through this store and its
+    /// clones as `policy` says, capturing all of them by default.
+    ///
+    /// This applies to the traps of the start functions run when instantiating modules too.
+    /// Lightweight traps, see [`Store::set_lightweight_traps`], never capture a backtrace.
+    ///
+    /// ```
+    /// # use wasmer::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// store.set_backtrace_policy(BacktracePolicy {
+    ///     max_frames: Some(64),
+    ///     capture: BacktraceCapture::Always,
+    ///     summarize_recursion: true,
+    /// });
+    /// let module = Module::new(&store, r#"(module (func (export "run") unreachable))"#)?;
+    /// let instance = Instance::new(&module, &imports! {})?;
+    /// let error = instance.lookup_function("run").unwrap().call(&[]).unwrap_err();
+    /// assert_eq!(error.trace().len(), 1);
+    /// assert_eq!(error.elided_frames(), None);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_backtrace_policy(&self, policy: BacktracePolicy) {
+        self.backtrace_policy.set(policy);
+    }
+
+    /// Returns the backtrace policy of this store and its clones, see
+    /// [`Store::set_backtrace_policy`].
+    pub fn backtrace_policy(&self) -> BacktracePolicy {
+        self.backtrace_policy.get()
+    }
+
+    /// Run `closure`, a call into WebAssembly code, raising the lightweight traps of this
+    /// store as such, and capturing the backtraces of the others as its policy says.
+    pub(crate) fn with_lightweight_traps<R>(&self, closure: impl FnOnce() -> R) -> R {
+        wasmer_vm::with_lightweight_traps(self.lightweight_traps.mask(), || {
+            self.with_backtrace_policy(closure)
+        })
+    }
+
+    /// Run `closure`, a call into WebAssembly code, capturing the backtraces of its traps as
+    /// the policy of this store says.
+    pub(crate) fn with_backtrace_policy<R>(&self, closure: impl FnOnce() -> R) -> R {
+        wasmer_vm::with_backtraces(self.backtrace_policy.captures(), closure)
+    }
+
+    /// Turn a trap raised while running WebAssembly code into a [`RuntimeError`], its
+    /// backtrace kept as the policy of the store says and its frames symbolicated through the
+    /// symbolication cache, reporting it to the metrics sink of the engine.
+    pub(crate) fn runtime_error_from_trap(&self, trap: Trap) -> RuntimeError {
+        let sink = self.engine.metrics_sink();
+        let error = match trap {
+            Trap::Lightweight { trap_code, pc } => {
+                self.lightweight_traps.runtime_error(trap_code, pc)
+            }
+            trap => {
+                let policy = self.backtrace_policy.get();
+                RuntimeError::from_trap_cached(
+                    trap,
+                    &self.symbolication_cache,
+                    sink.as_deref(),
+                    &policy,
+                )
+            }
+        };
+        if let Some(code) = error.trap_code() {
+            if let Some(sink) = &sink {
+                wasmer_vm::record_trap(&**sink, code);
+            }
+        }
+        error
+    }
+
     /// Checks whether two stores are identical. A store is considered
     /// equal to another store if both have the same engine. The
     /// tunables are excluded from the logic.
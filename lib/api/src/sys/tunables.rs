@@ -5,8 +5,8 @@ use target_lexicon::PointerWidth;
 use wasmer_compiler::Target;
 use wasmer_vm::MemoryError;
 use wasmer_vm::{
-    LinearMemory, LinearTable, Memory, MemoryStyle, Table, TableStyle, Tunables,
-    VMMemoryDefinition, VMTableDefinition,
+    LinearMemory, LinearTable, Memory, MemoryImage, MemoryStyle, ModuleStyleHints, Table,
+    TableStyle, Tunables, VMMemoryDefinition, VMTableDefinition,
 };
 
 /// Tunable parameters for WebAssembly compilation.
@@ -27,6 +27,26 @@ pub struct BaseTunables {
 
     /// The size in bytes of the offset guard for dynamic heaps.
     pub dynamic_memory_offset_guard_size: u64,
+
+    /// Reserve the address space of dynamic heaps up to their maximum, as long as it is at
+    /// most this many wasm pages, so that they never move when they grow.
+    ///
+    /// The address space is committed as the heaps grow. Heaps whose maximum is larger, and
+    /// heaps whose address space cannot be reserved, may still move, see
+    /// [`Memory::reservation_fallback`](crate::Memory::reservation_fallback). Dynamic heaps
+    /// are reserved up to their current size only by default.
+    pub dynamic_memory_reservation: Option<Pages>,
+
+    /// Map a memory image of the module copy-on-write as the local memories of instances,
+    /// instead of copying the data segments into each of them.
+    ///
+    /// The image is captured from the data segments by the first instance, and shared by
+    /// the read-only instances of the module. Modules importing a memory, placing a data
+    /// segment with a global or fetching data segments from a [`DataProvider`], and
+    /// platforms other than Linux, keep copying the data segments. Disabled by default.
+    ///
+    /// [`DataProvider`]: crate::DataProvider
+    pub copy_on_write_memories: bool,
 }
 
 impl BaseTunables {
@@ -61,6 +81,8 @@ impl BaseTunables {
             static_memory_bound,
             static_memory_offset_guard_size,
             dynamic_memory_offset_guard_size,
+            dynamic_memory_reservation: None,
+            copy_on_write_memories: false,
         }
     }
 }
@@ -97,7 +119,10 @@ impl Tunables for BaseTunables {
         ty: &MemoryType,
         style: &MemoryStyle,
     ) -> Result<Arc<dyn Memory>, MemoryError> {
-        Ok(Arc::new(LinearMemory::new(&ty, &style)?))
+        Ok(Arc::new(match self.dynamic_memory_reservation {
+            Some(limit) => LinearMemory::new_reserved(ty, style, limit)?,
+            None => LinearMemory::new(ty, style)?,
+        }))
     }
 
     /// Create a memory owned by the VM given a [`MemoryType`] and a [`MemoryStyle`].
@@ -111,9 +136,36 @@ impl Tunables for BaseTunables {
         style: &MemoryStyle,
         vm_definition_location: NonNull<VMMemoryDefinition>,
     ) -> Result<Arc<dyn Memory>, MemoryError> {
-        Ok(Arc::new(LinearMemory::from_definition(
-            &ty,
-            &style,
+        Ok(Arc::new(match self.dynamic_memory_reservation {
+            Some(limit) => {
+                LinearMemory::from_definition_reserved(ty, style, limit, vm_definition_location)?
+            }
+            None => LinearMemory::from_definition(ty, style, vm_definition_location)?,
+        }))
+    }
+
+    fn copy_on_write_memories(&self) -> bool {
+        self.copy_on_write_memories
+    }
+
+    /// Create a memory owned by the VM mapping `image` copy-on-write, reserved as
+    /// [`BaseTunables::create_vm_memory`] does.
+    ///
+    /// # Safety
+    /// - `vm_definition_location` must point to a valid, owned `VMMemoryDefinition`,
+    ///   for example in `VMContext`.
+    unsafe fn create_vm_memory_from_image(
+        &self,
+        image: Arc<MemoryImage>,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<Arc<dyn Memory>, MemoryError> {
+        Ok(Arc::new(LinearMemory::from_definition_with_image(
+            image,
+            ty,
+            style,
+            self.dynamic_memory_reservation,
             vm_definition_location,
         )?))
     }
@@ -146,6 +198,110 @@ impl Tunables for BaseTunables {
     }
 }
 
+/// Tunables choosing the style of each memory from the maximum size its module declares.
+///
+/// A memory defined by its module with a declared maximum of at most `static_threshold` is
+/// reserved in full up front, followed by guard pages, in the static style. Any other memory,
+/// including one without a maximum, is dynamic. So are imported memories, which may have been
+/// created with any style: host memories, created with [`Memory::new`](crate::Memory::new),
+/// thus always are.
+///
+/// Everything else is delegated to `base`.
+#[derive(Clone)]
+pub struct ThresholdTunables {
+    /// The largest declared maximum of a static memory.
+    pub static_threshold: Pages,
+
+    /// The tunables providing the offset guards, tables and memories.
+    pub base: BaseTunables,
+}
+
+impl ThresholdTunables {
+    /// Get the `ThresholdTunables` for a specific Target, with a threshold of 64 MiB.
+    pub fn for_target(target: &Target) -> Self {
+        Self {
+            static_threshold: Pages(1024),
+            base: BaseTunables::for_target(target),
+        }
+    }
+}
+
+impl Tunables for ThresholdTunables {
+    /// Get the `MemoryStyle` of a memory defined by a module.
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.memory_style_for(memory, &ModuleStyleHints::new(memory, false))
+    }
+
+    /// Get the `MemoryStyle` of a memory given what its module declares.
+    fn memory_style_for(&self, _memory: &MemoryType, hints: &ModuleStyleHints) -> MemoryStyle {
+        match hints.maximum {
+            Some(maximum) if maximum <= self.static_threshold && !hints.imported => {
+                MemoryStyle::Static {
+                    bound: maximum,
+                    offset_guard_size: self.base.static_memory_offset_guard_size,
+                }
+            }
+            _ => MemoryStyle::Dynamic {
+                offset_guard_size: self.base.dynamic_memory_offset_guard_size,
+            },
+        }
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.base.table_style(table)
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<Arc<dyn Memory>, MemoryError> {
+        self.base.create_host_memory(ty, style)
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<Arc<dyn Memory>, MemoryError> {
+        self.base
+            .create_vm_memory(ty, style, vm_definition_location)
+    }
+
+    fn copy_on_write_memories(&self) -> bool {
+        self.base.copy_on_write_memories()
+    }
+
+    unsafe fn create_vm_memory_from_image(
+        &self,
+        image: Arc<MemoryImage>,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<Arc<dyn Memory>, MemoryError> {
+        self.base
+            .create_vm_memory_from_image(image, ty, style, vm_definition_location)
+    }
+
+    fn create_host_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+    ) -> Result<Arc<dyn Table>, String> {
+        self.base.create_host_table(ty, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<Arc<dyn Table>, String> {
+        self.base.create_vm_table(ty, style, vm_definition_location)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -156,6 +312,8 @@ mod tests {
             static_memory_bound: Pages(2048),
             static_memory_offset_guard_size: 128,
             dynamic_memory_offset_guard_size: 256,
+            dynamic_memory_reservation: None,
+            copy_on_write_memories: false,
         };
 
         // No maximum
@@ -188,4 +346,36 @@ mod tests {
             s => panic!("Unexpected memory style: {:?}", s),
         }
     }
+
+    #[test]
+    fn threshold_memory_style() {
+        let tunables = ThresholdTunables {
+            static_threshold: Pages(1024),
+            base: BaseTunables {
+                static_memory_bound: Pages(2048),
+                static_memory_offset_guard_size: 128,
+                dynamic_memory_offset_guard_size: 256,
+                dynamic_memory_reservation: None,
+                copy_on_write_memories: false,
+            },
+        };
+        let style = |maximum, imported| {
+            let memory = MemoryType::new(3, maximum, false);
+            tunables.memory_style_for(&memory, &ModuleStyleHints::new(&memory, imported))
+        };
+        let dynamic = MemoryStyle::Dynamic {
+            offset_guard_size: 256,
+        };
+
+        assert_eq!(
+            style(Some(1024), false),
+            MemoryStyle::Static {
+                bound: Pages(1024),
+                offset_guard_size: 128,
+            }
+        );
+        assert_eq!(style(Some(1025), false), dynamic);
+        assert_eq!(style(None, false), dynamic);
+        assert_eq!(style(Some(16), true), dynamic);
+    }
 }
@@ -1,10 +1,11 @@
 use crate::sys::externals::Function;
 use crate::sys::store::{Store, StoreObject};
 use crate::sys::RuntimeError;
+use std::convert::TryInto;
 use wasmer_types::Value;
 pub use wasmer_types::{
-    ExportType, ExternType, FunctionType, GlobalType, MemoryType, Mutability, TableType,
-    Type as ValType,
+    ExportType, ExportsIterator, ExternType, FunctionType, GlobalType, ImportsIterator, MemoryType,
+    Mutability, TableType, Type as ValType,
 };
 use wasmer_vm::VMFuncRef;
 
@@ -90,3 +91,62 @@ impl ValFuncRef for Val {
         }
     }
 }
+
+/// The canonical encoding of `values`: for each, a tag followed by the little-endian bits of
+/// the value, or `None` if one of them is a reference.
+pub(crate) fn encode_values(values: &[Val]) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for value in values {
+        match value {
+            Val::I32(value) => {
+                bytes.push(0);
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            Val::I64(value) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            Val::F32(value) => {
+                bytes.push(2);
+                bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+            }
+            Val::F64(value) => {
+                bytes.push(3);
+                bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+            }
+            Val::V128(value) => {
+                bytes.push(4);
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            Val::ExternRef(_) | Val::FuncRef(_) => return None,
+        }
+    }
+    Some(bytes)
+}
+
+/// Decode values encoded by [`encode_values`], or `None` if `bytes` is not such an encoding.
+pub(crate) fn decode_values(mut bytes: &[u8]) -> Option<Vec<Val>> {
+    fn array<const N: usize>(bytes: &[u8]) -> Option<[u8; N]> {
+        bytes.get(1..1 + N)?.try_into().ok()
+    }
+    let mut values = Vec::new();
+    while let Some(tag) = bytes.first() {
+        let (value, len) = match tag {
+            0 => (Val::I32(i32::from_le_bytes(array(bytes)?)), 5),
+            1 => (Val::I64(i64::from_le_bytes(array(bytes)?)), 9),
+            2 => (
+                Val::F32(f32::from_bits(u32::from_le_bytes(array(bytes)?))),
+                5,
+            ),
+            3 => (
+                Val::F64(f64::from_bits(u64::from_le_bytes(array(bytes)?))),
+                9,
+            ),
+            4 => (Val::V128(u128::from_le_bytes(array(bytes)?)), 17),
+            _ => return None,
+        };
+        values.push(value);
+        bytes = &bytes[len..];
+    }
+    Some(values)
+}
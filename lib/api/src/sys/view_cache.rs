@@ -0,0 +1,391 @@
+//! Memoization of the results of view calls.
+
+use crate::sys::exports::ExportError;
+use crate::sys::instance::{Instance, InstantiationError};
+use crate::sys::module::Module;
+use crate::sys::types::encode_values;
+use crate::sys::{Export, RuntimeError, Val};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use wasmer_types::{Classify, ErrorCode, FailureKind, HasErrorCode, InstanceConfig};
+use wasmer_vm::{Counter, ReclaimPriority, Reclaimable, Resolver, TrapCode};
+
+/// An error making a view call through a [`ViewCallCache`].
+#[derive(Error, Debug)]
+pub enum ViewCallError {
+    /// The module could not be instantiated.
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+    /// The module does not export the entry point as a function.
+    #[error(transparent)]
+    Export(#[from] ExportError),
+    /// The call failed.
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+}
+
+impl HasErrorCode for ViewCallError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Instantiation(e) => e.code(),
+            Self::Export(e) => e.code(),
+            Self::Runtime(e) => e.code(),
+        }
+    }
+}
+
+impl Classify for ViewCallError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::Instantiation(e) => e.failure_kind(),
+            Self::Export(_) => FailureKind::Permanent,
+            Self::Runtime(e) => e.failure_kind(),
+        }
+    }
+}
+
+/// Lets the host functions called during a [`ViewCallCache`] call report that its result
+/// depends on more than the state generation, such as on the time, so that it is not cached.
+///
+/// The cache provides it as an extension of the instances it creates:
+///
+/// ```
+/// # use wasmer::*;
+/// fn now(_: &[Value]) -> Result<Vec<Value>, RuntimeError> {
+///     if let Some(context) = CallContext::current() {
+///         if let Some(nondeterminism) = context.extension::<Nondeterminism>() {
+///             nondeterminism.record();
+///         }
+///     }
+///     Ok(vec![Value::I64(0)])
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Nondeterminism {
+    recorded: Arc<AtomicBool>,
+}
+
+impl Nondeterminism {
+    /// Record that the call in progress is nondeterministic.
+    pub fn record(&self) {
+        self.recorded.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the call was recorded as nondeterministic.
+    pub fn is_recorded(&self) -> bool {
+        self.recorded.load(Ordering::Relaxed)
+    }
+}
+
+/// Statistics of a [`ViewCallCache`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ViewCallCacheStats {
+    /// Calls answered from the cache.
+    pub hits: u64,
+    /// Calls that were executed.
+    pub misses: u64,
+    /// Executed calls that were not cached because they were nondeterministic.
+    pub nondeterministic: u64,
+    /// Entries evicted to make room for others, or to reclaim memory.
+    pub evictions: u64,
+    /// Entries in the cache.
+    pub entries: usize,
+    /// Bytes of the entries in the cache.
+    pub bytes: usize,
+}
+
+/// What a view call result is cached under.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Key {
+    module: [u8; 32],
+    state_generation: u64,
+    entry: String,
+    args: Vec<u8>,
+}
+
+struct Entry {
+    result: Result<Box<[Val]>, RuntimeError>,
+    bytes: usize,
+    last_use: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_key: HashMap<Key, Entry>,
+    by_last_use: BTreeMap<u64, Key>,
+    clock: u64,
+    stats: ViewCallCacheStats,
+}
+
+impl Entries {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn get(&mut self, key: &Key) -> Option<Result<Box<[Val]>, RuntimeError>> {
+        let now = self.tick();
+        let entry = self.by_key.get_mut(key)?;
+        let key = self.by_last_use.remove(&entry.last_use)?;
+        entry.last_use = now;
+        self.by_last_use.insert(now, key);
+        Some(entry.result.clone())
+    }
+
+    fn insert(&mut self, key: Key, entry: Entry) {
+        self.remove(&key);
+        self.stats.entries += 1;
+        self.stats.bytes += entry.bytes;
+        self.by_last_use.insert(entry.last_use, key.clone());
+        self.by_key.insert(key, entry);
+    }
+
+    fn remove(&mut self, key: &Key) -> Option<Entry> {
+        let entry = self.by_key.remove(key)?;
+        self.by_last_use.remove(&entry.last_use);
+        self.stats.entries -= 1;
+        self.stats.bytes -= entry.bytes;
+        Some(entry)
+    }
+
+    fn evict_least_recently_used(&mut self) {
+        let key = match self.by_last_use.values().next() {
+            Some(key) => key.clone(),
+            None => return,
+        };
+        self.remove(&key);
+        self.stats.evictions += 1;
+    }
+}
+
+/// Caches the results of view calls: calls that only read the state of a contract, so that
+/// identical calls made against the same state return the same result.
+///
+/// Results are cached under the [consensus hash](Module::consensus_hash) of the module, the
+/// state generation, the entry point and the arguments. The generation is supplied by the
+/// embedder, which bumps it whenever the state the imports give access to changes: the
+/// results of older generations are then never returned again, and are evicted as the cache
+/// fills up. Entries are sized by the bytes of their key and result, and the least recently
+/// used are evicted first.
+///
+/// Calls are not cached when they are nondeterministic, that is when a host function records
+/// [`Nondeterminism`] or when a result is a NaN, whose bits WebAssembly does not specify.
+/// Such calls are counted as [`Counter::NondeterministicViewCalls`] by the metrics sink of
+/// the engine. Calls taking or returning references are not cached either, as references
+/// only mean something to the instance they come from.
+///
+/// ```
+/// # use wasmer::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let store = Store::default();
+/// let module = Module::new(&store, r#"
+///     (module
+///         (func (export "double") (param i32) (result i32)
+///             (i32.mul (local.get 0) (i32.const 2))))
+/// "#)?;
+/// let cache = ViewCallCache::new(1 << 20);
+/// let result = cache.call(&module, &imports! {}, 1, "double", &[Value::I32(21)])?;
+/// assert_eq!(result[..], [Value::I32(42)]);
+/// cache.call(&module, &imports! {}, 1, "double", &[Value::I32(21)])?;
+/// assert_eq!(cache.stats().hits, 1);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ViewCallCache {
+    capacity_bytes: usize,
+    cache_traps: bool,
+    readonly_instances: bool,
+    entries: Mutex<Entries>,
+}
+
+/// The cached results never hold references, which are what keeps values from being sent
+/// between threads, as the calls taking or returning them are not cached.
+unsafe impl Send for ViewCallCache {}
+unsafe impl Sync for ViewCallCache {}
+
+impl ViewCallCache {
+    /// Create a cache holding up to `capacity_bytes` of keys and results.
+    ///
+    /// Traps are not cached, and calls are made on regular instances.
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self {
+            capacity_bytes,
+            cache_traps: false,
+            readonly_instances: false,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Whether calls ending with a trap are cached, as long as the trap does not depend on
+    /// the [`InstanceConfig`] of the call: running out of gas or stack is never cached.
+    ///
+    /// Errors raised by host functions are never cached.
+    pub fn with_trap_caching(mut self, cache_traps: bool) -> Self {
+        self.cache_traps = cache_traps;
+        self
+    }
+
+    /// Whether calls are made on read-only instances, see [`Instance::new_readonly`].
+    ///
+    /// Read-only instances are cheaper to create, but the calls writing to memory trap.
+    pub fn with_readonly_instances(mut self, readonly: bool) -> Self {
+        self.readonly_instances = readonly;
+        self
+    }
+
+    /// Call `entry` with `args` on a new instance of `module`, or return the result of the
+    /// same call against the same `state_generation` if it is cached.
+    pub fn call(
+        &self,
+        module: &Module,
+        resolver: &dyn Resolver,
+        state_generation: u64,
+        entry: &str,
+        args: &[Val],
+    ) -> Result<Box<[Val]>, ViewCallError> {
+        self.call_with_config(
+            module,
+            InstanceConfig::default(),
+            resolver,
+            state_generation,
+            entry,
+            args,
+        )
+    }
+
+    /// As [`call`](Self::call), instantiating `module` with `config` if the call is not
+    /// cached.
+    pub fn call_with_config(
+        &self,
+        module: &Module,
+        config: InstanceConfig,
+        resolver: &dyn Resolver,
+        state_generation: u64,
+        entry: &str,
+        args: &[Val],
+    ) -> Result<Box<[Val]>, ViewCallError> {
+        let key = encode_values(args).map(|args| Key {
+            module: module.consensus_hash(),
+            state_generation,
+            entry: entry.to_string(),
+            args,
+        });
+        {
+            let mut entries = self.entries.lock().unwrap();
+            if let Some(result) = key.as_ref().and_then(|key| entries.get(key)) {
+                entries.stats.hits += 1;
+                return Ok(result?);
+            }
+            entries.stats.misses += 1;
+        }
+
+        let nondeterminism = Nondeterminism::default();
+        let config = config.with_extension(nondeterminism.clone());
+        let instance = if self.readonly_instances {
+            Instance::new_readonly_with_config(module, config, resolver)?
+        } else {
+            Instance::new_with_config(module, config, resolver)?
+        };
+        let function = match instance.lookup(entry) {
+            Some(Export::Function(_)) => instance.lookup_function(entry).unwrap(),
+            Some(_) => return Err(ExportError::IncompatibleType.into()),
+            None => return Err(ExportError::Missing(entry.to_string()).into()),
+        };
+        let result = function.call(args);
+
+        let key = match key {
+            Some(key) => key,
+            None => return Ok(result?),
+        };
+        let has_nan = match &result {
+            Ok(values) => values.iter().any(is_nan),
+            Err(_) => false,
+        };
+        if nondeterminism.is_recorded() || has_nan {
+            self.entries.lock().unwrap().stats.nondeterministic += 1;
+            if let Some(sink) = module.store().engine().metrics_sink() {
+                sink.increment(Counter::NondeterministicViewCalls, 1);
+            }
+            return Ok(result?);
+        }
+        let bytes = match &result {
+            Ok(values) => encode_values(values).map(|bytes| bytes.len()),
+            Err(error) if self.cache_traps && trap_is_cacheable(error) => {
+                Some(error.message().len())
+            }
+            Err(_) => None,
+        };
+        let bytes = match bytes {
+            Some(bytes) => bytes + key.entry.len() + key.args.len(),
+            None => return Ok(result?),
+        };
+        if bytes <= self.capacity_bytes {
+            let mut entries = self.entries.lock().unwrap();
+            entries.remove(&key);
+            while entries.stats.bytes + bytes > self.capacity_bytes {
+                entries.evict_least_recently_used();
+            }
+            let last_use = entries.tick();
+            entries.insert(
+                key,
+                Entry {
+                    result: result.clone(),
+                    bytes,
+                    last_use,
+                },
+            );
+        }
+        Ok(result?)
+    }
+
+    /// Return the statistics of this cache.
+    pub fn stats(&self) -> ViewCallCacheStats {
+        self.entries.lock().unwrap().stats
+    }
+}
+
+/// Evicts the least recently used entries, as counted by [`ViewCallCacheStats::bytes`].
+/// Calls that got their result from an evicted entry keep it.
+impl Reclaimable for ViewCallCache {
+    fn name(&self) -> &str {
+        "view call cache"
+    }
+
+    fn priority(&self) -> ReclaimPriority {
+        ReclaimPriority::Cached
+    }
+
+    fn reclaimable_bytes(&self) -> usize {
+        self.stats().bytes
+    }
+
+    fn reclaim(&self, target_bytes: usize) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.stats.bytes;
+        while before - entries.stats.bytes < target_bytes && entries.stats.entries > 0 {
+            entries.evict_least_recently_used();
+        }
+        before - entries.stats.bytes
+    }
+}
+
+/// Whether the trap ending a call would end it again with any [`InstanceConfig`].
+fn trap_is_cacheable(error: &RuntimeError) -> bool {
+    match error.trap_code() {
+        Some(TrapCode::GasExceeded)
+        | Some(TrapCode::StackOverflow)
+        | Some(TrapCode::Cancelled)
+        | None => false,
+        Some(_) => true,
+    }
+}
+
+fn is_nan(value: &Val) -> bool {
+    match value {
+        Val::F32(value) => value.is_nan(),
+        Val::F64(value) => value.is_nan(),
+        _ => false,
+    }
+}
@@ -1,10 +1,12 @@
 use crate::address_map::get_function_address_map;
 use crate::config::IntrinsicKind;
+use crate::unwind::UnwindOp;
 use crate::{config::Singlepass, emitter_x64::*, machine::Machine, x64_decl::*};
-use dynasmrt::{x64::X64Relocation, AssemblyOffset, DynamicLabel, DynasmApi, VecAssembler};
+use dynasmrt::{AssemblyOffset, DynamicLabel, DynasmApi};
 use memoffset::offset_of;
 use smallvec::{smallvec, SmallVec};
 use std::cmp::max;
+use std::collections::BTreeMap;
 use std::iter;
 use wasmer_compiler::wasmparser::{
     MemoryImmediate, Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType,
@@ -20,12 +22,20 @@ use wasmer_types::{
     FastGasCounter, FunctionType,
 };
 use wasmer_types::{
-    FunctionIndex, GlobalIndex, LocalFunctionIndex, LocalMemoryIndex, MemoryIndex, ModuleInfo,
-    SignatureIndex, TableIndex, Type,
+    FunctionIndex, GlobalIndex, GlobalInit, LocalFunctionIndex, LocalMemoryIndex, MemoryIndex,
+    ModuleInfo, SignatureIndex, TableIndex, Type,
 };
-use wasmer_vm::{TableStyle, TrapCode, VMBuiltinFunctionIndex, VMOffsets};
+use wasmer_vm::{DiagnosticsLevel, TableStyle, TrapCode, VMBuiltinFunctionIndex, VMOffsets};
 
-type Assembler = VecAssembler<X64Relocation>;
+/// Size of the area reserved below the frame by the prologue, as a "red zone" for the platforms
+/// without one.
+const RED_ZONE_SIZE: u32 = 32;
+
+/// The 8-byte stack slots every frame takes besides its locals and operand stack, whatever the
+/// calling convention, so that the stack limit of an instance is hit at the same depth
+/// everywhere: the return address, the saved RBP, the vmctx register and, under the Windows
+/// ABI, RDI and RSI saved by `Machine::init_locals`, then the red zone.
+const FRAME_OVERHEAD_SLOTS: usize = 2 + 1 + 2 + RED_ZONE_SIZE as usize / 8;
 
 /// The singlepass per-function code generator.
 pub(crate) struct FuncGen<'a> {
@@ -50,6 +60,13 @@ pub(crate) struct FuncGen<'a> {
     /// Function signature.
     signature: FunctionType,
 
+    /// Label of the first instruction of the function, by whose address the diagnostics tell
+    /// the function apart without its index being part of the code.
+    entry: DynamicLabel,
+
+    /// Values of the imported globals the module is specialized for.
+    global_bindings: &'a BTreeMap<GlobalIndex, GlobalInit>,
+
     // Working storage.
     /// The assembler.
     ///
@@ -84,31 +101,79 @@ pub(crate) struct FuncGen<'a> {
     /// Relocation information.
     relocations: Vec<Relocation>,
 
-    /// A set of special labels for trapping.
-    special_labels: SpecialLabelSet,
+    /// Trap sequences to emit at the end of the function, in emission order.
+    trap_stubs: Vec<TrapStub>,
+
+    /// Diagnostic and cancellation calls to emit at the end of the function, in emission order.
+    diagnostic_stubs: Vec<DiagnosticStub>,
 
     /// The source location for the current operator.
     src_loc: u32,
 
+    /// Offset of the first native instruction emitted for the current operator.
+    src_loc_begin: usize,
+
     /// Map from byte offset into wasm function to range of native instructions.
     ///
-    // Ordered by increasing InstructionAddressMap::srcloc.
+    // Ordered by increasing InstructionAddressMap::code_offset.
     instructions_address_map: Vec<InstructionAddressMap>,
 
     /// Calling convention to use.
     calling_convention: CallingConvention,
+
+    /// The locals the values at the top of the value stack were read from, for the values
+    /// pushed by the latest operators that leave the rest of the stack untouched.
+    address_origins: Vec<Option<u32>>,
+
+    /// For each local, the end of the largest access at its value whose bounds were checked
+    /// since the start of the current straight-line run of code.
+    checked_bounds: BTreeMap<u32, u64>,
+
+    /// Whether the bounds check of the current memory access is implied by an earlier one.
+    elide_bounds_check: bool,
+}
+
+/// The state of the machine to preserve around a call into native code.
+struct CallContext {
+    used_gprs: Vec<GPR>,
+    used_xmms: Vec<XMM>,
+    stack_offset: usize,
+}
+
+impl CallContext {
+    fn of(machine: &Machine) -> Self {
+        Self {
+            used_gprs: machine.get_used_gprs(),
+            used_xmms: machine.get_used_xmms(),
+            stack_offset: machine.get_stack_offset(),
+        }
+    }
 }
 
-struct SpecialLabelSet {
-    integer_division_by_zero: DynamicLabel,
-    integer_overflow: DynamicLabel,
-    bad_conversion_to_integer: DynamicLabel,
-    heap_access_oob: DynamicLabel,
-    table_access_oob: DynamicLabel,
-    indirect_call_null: DynamicLabel,
-    bad_signature: DynamicLabel,
-    gas_limit_exceeded: DynamicLabel,
-    stack_overflow: DynamicLabel,
+/// A call reporting a diagnostic, or checking for the cancellation of the running call,
+/// emitted out of line so that the code checking whether it is needed falls through when it is
+/// not.
+struct DiagnosticStub {
+    builtin: VMBuiltinFunctionIndex,
+    params: SmallVec<[Location; 4]>,
+    /// Label whose address is passed after the `params`, if any.
+    address: Option<DynamicLabel>,
+    context: CallContext,
+    srcloc: u32,
+    label: DynamicLabel,
+    resume: DynamicLabel,
+}
+
+/// A trap raised on behalf of a single operator.
+///
+/// Every operator gets its own trap sequences, so that the address of the trap maps back to
+/// the exact operator that trapped.
+struct TrapStub {
+    code: TrapCode,
+    srcloc: u32,
+    /// The stack depth of the jumps to the stub, when compiling without a frame pointer.
+    cfa_offset: Option<i32>,
+    label: DynamicLabel,
 }
 
 /// Metadata about a floating-point value.
@@ -258,8 +323,35 @@ struct I2O1 {
 
 impl<'a> FuncGen<'a> {
     /// Set the source location of the Wasm to the given offset.
+    ///
+    /// The code emitted since the previous call is attributed to the previous source location.
     pub(crate) fn set_srcloc(&mut self, offset: u32) {
+        self.mark_instruction_address_end(self.src_loc_begin);
         self.src_loc = offset;
+        self.src_loc_begin = self.assembler.get_offset().0;
+    }
+
+    /// Get the label raising a trap with `code` on behalf of the current operator.
+    fn trap_label(&mut self, code: TrapCode) -> DynamicLabel {
+        let src_loc = self.src_loc;
+        let cfa_offset = self.assembler.cfa_offset();
+        let existing = self
+            .trap_stubs
+            .iter()
+            .rev()
+            .take_while(|stub| stub.srcloc == src_loc)
+            .find(|stub| stub.code == code && stub.cfa_offset == cfa_offset);
+        if let Some(stub) = existing {
+            return stub.label;
+        }
+        let label = self.assembler.get_label();
+        self.trap_stubs.push(TrapStub {
+            code,
+            srcloc: src_loc,
+            cfa_offset,
+            label,
+        });
+        label
     }
 
     fn get_location_released(&mut self, loc: Location) -> Location {
@@ -397,18 +489,19 @@ impl<'a> FuncGen<'a> {
         });
         match intrinsic.map(|i| &i.kind) {
             Some(IntrinsicKind::Gas) => self.emit_gas(params[0]),
+            Some(IntrinsicKind::Trap(code)) => {
+                self.emit_trap(TrapCode::Custom(*code));
+                // The call does not return.
+                self.unreachable_depth = 1;
+            }
             None => return false,
         }
         return true;
     }
 
     fn emit_gas(&mut self, count_location: Location) {
-        let counter_offset = offset_of!(FastGasCounter, burnt_gas) as i32;
-        let gas_limit_offset = offset_of!(FastGasCounter, gas_limit) as i32;
         let opcode_cost_offset = offset_of!(FastGasCounter, opcode_cost) as i32;
         // Recheck offsets, to make sure offsets will never change.
-        assert_eq!(counter_offset, 0);
-        assert_eq!(gas_limit_offset, 8);
         assert_eq!(opcode_cost_offset, 16);
         let base_reg = self.machine.acquire_temp_gpr().unwrap();
         // Load gas counter base.
@@ -420,13 +513,6 @@ impl<'a> FuncGen<'a> {
             ),
             Location::GPR(base_reg),
         );
-        let current_burnt_reg = self.machine.acquire_temp_gpr().unwrap();
-        // Read current gas counter.
-        self.assembler.emit_mov(
-            Size::S64,
-            Location::Memory(base_reg, counter_offset),
-            Location::GPR(current_burnt_reg),
-        );
         // Read opcode cost.
         let count_reg = self.machine.acquire_temp_gpr().unwrap();
         self.assembler.emit_mov(
@@ -439,14 +525,57 @@ impl<'a> FuncGen<'a> {
             Location::Imm32(imm) => self.assembler.emit_imul_imm32_gpr64(imm, count_reg),
             _ => assert!(false),
         }
+        self.emit_burn_gas(base_reg, count_reg);
+        self.machine.release_temp_gpr(base_reg);
+        self.machine.release_temp_gpr(count_reg);
+    }
+
+    /// Charge the gas counter with `cost`, the cost of the basic block starting here, see
+    /// `Singlepass::metering`.
+    pub(crate) fn emit_metering(&mut self, cost: u64) {
+        if self.unreachable_depth > 0 || cost == 0 {
+            return;
+        }
+        let base_reg = self.machine.acquire_temp_gpr().unwrap();
+        self.assembler.emit_mov(
+            Size::S64,
+            Location::Memory(
+                Machine::get_vmctx_reg(),
+                self.vmoffsets.vmctx_gas_limiter_pointer() as i32,
+            ),
+            Location::GPR(base_reg),
+        );
+        let count_reg = self.machine.acquire_temp_gpr().unwrap();
+        self.assembler
+            .emit_mov(Size::S64, Location::Imm64(cost), Location::GPR(count_reg));
+        self.emit_burn_gas(base_reg, count_reg);
+        self.machine.release_temp_gpr(base_reg);
+        self.machine.release_temp_gpr(count_reg);
+    }
+
+    /// Add the gas in `count_reg` to the gas counter at `base_reg`, trapping with
+    /// `GasExceeded` once it reaches its limit.
+    fn emit_burn_gas(&mut self, base_reg: GPR, count_reg: GPR) {
+        let counter_offset = offset_of!(FastGasCounter, burnt_gas) as i32;
+        let gas_limit_offset = offset_of!(FastGasCounter, gas_limit) as i32;
+        // Recheck offsets, to make sure offsets will never change.
+        assert_eq!(counter_offset, 0);
+        assert_eq!(gas_limit_offset, 8);
+        let current_burnt_reg = self.machine.acquire_temp_gpr().unwrap();
+        // Read current gas counter.
+        self.assembler.emit_mov(
+            Size::S64,
+            Location::Memory(base_reg, counter_offset),
+            Location::GPR(current_burnt_reg),
+        );
         // Compute new cost.
         self.assembler.emit_add(
             Size::S64,
             Location::GPR(count_reg),
             Location::GPR(current_burnt_reg),
         );
-        self.assembler
-            .emit_jmp(Condition::Overflow, self.special_labels.integer_overflow);
+        let trap = self.trap_label(TrapCode::IntegerOverflow);
+        self.assembler.emit_jmp(Condition::Overflow, trap);
         // Compare with the limit.
         self.assembler.emit_cmp(
             Size::S64,
@@ -459,13 +588,9 @@ impl<'a> FuncGen<'a> {
             Location::GPR(current_burnt_reg),
             Location::Memory(base_reg, counter_offset),
         );
-        self.assembler.emit_jmp(
-            Condition::BelowEqual,
-            self.special_labels.gas_limit_exceeded,
-        );
-        self.machine.release_temp_gpr(base_reg);
+        let trap = self.trap_label(TrapCode::GasExceeded);
+        self.assembler.emit_jmp(Condition::BelowEqual, trap);
         self.machine.release_temp_gpr(current_burnt_reg);
-        self.machine.release_temp_gpr(count_reg);
     }
 
     fn emit_trap(&mut self, code: TrapCode) {
@@ -477,18 +602,146 @@ impl<'a> FuncGen<'a> {
         );
         self.assembler.emit_mov(
             Size::S32,
-            Location::Imm32(code as u32),
+            Location::Imm32(code.to_raw()),
             Machine::get_param_location(1, self.calling_convention),
         );
-        // Align stack.
-        self.assembler.emit_and(
-            Size::S64,
-            Location::Imm32(0xfffffff0),
-            Location::GPR(GPR::RSP),
-        );
+        // Align stack. Without a frame pointer, the depth of the stack is kept known to
+        // unwinders.
+        match self.assembler.cfa_offset() {
+            Some(cfa_offset) => {
+                let padding = (16 - cfa_offset % 16) % 16;
+                if padding != 0 {
+                    self.assembler.emit_sub(
+                        Size::S64,
+                        Location::Imm32(padding as u32),
+                        Location::GPR(GPR::RSP),
+                    );
+                }
+            }
+            None => self.assembler.emit_and(
+                Size::S64,
+                Location::Imm32(0xfffffff0),
+                Location::GPR(GPR::RSP),
+            ),
+        }
         let offset = self.vmoffsets.vmctx_trap_handler();
         self.assembler
             .emit_call_location(Location::Memory(Machine::get_vmctx_reg(), offset as i32));
+        // The trap handler does not return.
+        self.assembler.set_cfa_offset(None);
+    }
+
+    /// Call the `builtin` reporting a diagnostic with `params`, followed by the address of
+    /// `address` if any, if the diagnostics level of the instance is at least `level`.
+    ///
+    /// Below that level, which is the common case, this costs a compare and a branch that is
+    /// not taken.
+    fn emit_diagnostic(
+        &mut self,
+        level: DiagnosticsLevel,
+        builtin: VMBuiltinFunctionIndex,
+        params: &[Location],
+        address: Option<DynamicLabel>,
+    ) {
+        let label = self.assembler.get_label();
+        let resume = self.assembler.get_label();
+        self.assembler.emit_cmp(
+            Size::S8,
+            Location::Imm8(level as u8),
+            Location::Memory(
+                Machine::get_vmctx_reg(),
+                self.vmoffsets.vmctx_diagnostics_level() as i32,
+            ),
+        );
+        self.assembler.emit_jmp(Condition::AboveEqual, label);
+        self.assembler.emit_label(resume);
+        self.diagnostic_stubs.push(DiagnosticStub {
+            builtin,
+            params: params.iter().copied().collect(),
+            address,
+            context: CallContext::of(&self.machine),
+            srcloc: self.src_loc,
+            label,
+            resume,
+        });
+    }
+
+    /// Call the builtin trapping if the running call was cancelled, if a call into the
+    /// instance was.
+    ///
+    /// While no call into the instance is cancelled, this costs a compare and a branch that is
+    /// not taken.
+    fn emit_cancellation_check(&mut self) {
+        let label = self.assembler.get_label();
+        let resume = self.assembler.get_label();
+        self.assembler.emit_cmp(
+            Size::S32,
+            Location::Imm32(0),
+            Location::Memory(
+                Machine::get_vmctx_reg(),
+                self.vmoffsets.vmctx_cancellations_pending() as i32,
+            ),
+        );
+        self.assembler.emit_jmp(Condition::NotEqual, label);
+        self.assembler.emit_label(resume);
+        self.diagnostic_stubs.push(DiagnosticStub {
+            builtin: VMBuiltinFunctionIndex::get_check_cancellation_index(),
+            params: SmallVec::new(),
+            address: None,
+            context: CallContext::of(&self.machine),
+            srcloc: self.src_loc,
+            label,
+            resume,
+        });
+    }
+
+    /// Emit the call of a diagnostic stub, returning to where the level was checked.
+    fn emit_diagnostic_stub(&mut self, stub: DiagnosticStub) -> Result<(), CodegenError> {
+        self.assembler.emit_label(stub.label);
+        self.assembler.emit_mov(
+            Size::S64,
+            Location::Memory(
+                Machine::get_vmctx_reg(),
+                self.vmoffsets.vmctx_builtin_function(stub.builtin) as i32,
+            ),
+            Location::GPR(GPR::RAX),
+        );
+        // The address is computed once the parameters before it are in place.
+        let address = stub.address.map(|label| {
+            let location =
+                Machine::get_param_location(1 + stub.params.len(), self.calling_convention);
+            (label, location)
+        });
+        self.emit_call_native_in(
+            &stub.context,
+            |this| {
+                if let Some((label, location)) = address {
+                    this.assembler.emit_lea_label(label, location);
+                }
+                this.assembler.emit_call_register(GPR::RAX);
+            },
+            stub.params.into_iter(),
+        )?;
+        self.assembler.emit_jmp(Condition::None, stub.resume);
+        Ok(())
+    }
+
+    /// Report the load or store of `size` bytes about to be made by the current operator,
+    /// whose address is `depth` values below the top of the stack.
+    fn emit_memory_diagnostic(&mut self, depth: usize, offset: u32, size: u8) {
+        let address = self.value_stack[self.value_stack.len() - 1 - depth];
+        self.emit_diagnostic(
+            DiagnosticsLevel::Verbose,
+            VMBuiltinFunctionIndex::get_diagnostics_memory_index(),
+            // [vmctx, address, offset, size, store]
+            &[
+                address,
+                Location::Imm32(offset),
+                Location::Imm32(size.into()),
+                Location::Imm32(depth as u32),
+            ],
+            None,
+        );
     }
 
     /// Canonicalizes the floating point value at `input` into `output`.
@@ -541,10 +794,8 @@ impl<'a> FuncGen<'a> {
     /// Moves `loc` to a valid location for `div`/`idiv`.
     fn emit_relaxed_xdiv(&mut self, signed: bool, sz: Size, loc: Location) {
         self.assembler.emit_cmp(sz, Location::Imm32(0), loc);
-        self.assembler.emit_jmp(
-            Condition::Equal,
-            self.special_labels.integer_division_by_zero,
-        );
+        let trap = self.trap_label(TrapCode::IntegerDivisionByZero);
+        self.assembler.emit_jmp(Condition::Equal, trap);
 
         // Boundary checks for integer overflow. It clearly doesn't make sense for
         // unsigned division, as numerator is of same size as the actual result, and divisor is
@@ -578,8 +829,8 @@ impl<'a> FuncGen<'a> {
                 _ => assert!(false),
             }
             self.assembler.emit_jmp(Condition::NotEqual, end);
-            self.assembler
-                .emit_jmp(Condition::None, self.special_labels.integer_overflow);
+            let trap = self.trap_label(TrapCode::IntegerOverflow);
+            self.assembler.emit_jmp(Condition::None, trap);
             self.assembler.emit_label(end);
         }
 
@@ -1144,17 +1395,29 @@ impl<'a> FuncGen<'a> {
         &mut self,
         cb: F,
         params: I,
+    ) -> Result<(), CodegenError> {
+        let context = CallContext::of(&self.machine);
+        self.emit_call_native_in(&context, cb, params)
+    }
+
+    /// Emits a System V call sequence preserving the registers used as of `context`, which
+    /// may be another point of the function than the current machine state.
+    fn emit_call_native_in<I: Iterator<Item = Location>, F: FnOnce(&mut Self)>(
+        &mut self,
+        context: &CallContext,
+        cb: F,
+        params: I,
     ) -> Result<(), CodegenError> {
         let params: Vec<_> = params.collect();
 
         // Save used GPRs.
-        let used_gprs = self.machine.get_used_gprs();
+        let used_gprs = &context.used_gprs;
         for r in used_gprs.iter() {
             self.assembler.emit_push(Size::S64, Location::GPR(*r));
         }
 
         // Save used XMM registers.
-        let used_xmms = self.machine.get_used_xmms();
+        let used_xmms = &context.used_xmms;
         if used_xmms.len() > 0 {
             self.assembler.emit_sub(
                 Size::S64,
@@ -1187,11 +1450,7 @@ impl<'a> FuncGen<'a> {
         }
 
         // Align stack to 16 bytes.
-        if (self.machine.get_stack_offset()
-            + used_gprs.len() * 8
-            + used_xmms.len() * 8
-            + stack_offset)
-            % 16
+        if (context.stack_offset + used_gprs.len() * 8 + used_xmms.len() * 8 + stack_offset) % 16
             != 0
         {
             self.assembler
@@ -1212,7 +1471,7 @@ impl<'a> FuncGen<'a> {
                         Location::GPR(_) => {}
                         Location::XMM(_) => {}
                         Location::Memory(reg, _) => {
-                            if reg != GPR::RBP {
+                            if reg != GPR::FrameBase {
                                 return Err(CodegenError {
                                     message: "emit_call_native loc param: unreachable code"
                                         .to_string(),
@@ -1341,6 +1600,70 @@ impl<'a> FuncGen<'a> {
         Ok(())
     }
 
+    /// Decide whether the bounds check of `op`, if it accesses memory, is implied by an
+    /// earlier check, and update what is known of the checks and of the addresses for the next
+    /// operators.
+    ///
+    /// A check is implied when the address is the value of a local that was already checked
+    /// for an access ending at the same or a larger offset, in the same straight-line run of
+    /// code. Writing the local forgets its checks, and branches, labels, calls and
+    /// `memory.grow` forget all of them.
+    fn track_bounds_checks(&mut self, op: &Operator) {
+        let mut loads = false;
+        if let Some((offset, size, store)) = memory_access(op) {
+            // Stores take their address below the value to store.
+            let depth = store as usize + 1;
+            let origin = match self.address_origins.len().checked_sub(depth) {
+                Some(index) => self.address_origins[index],
+                None => None,
+            };
+            if let Some(local) = origin {
+                let end = u64::from(offset) + u64::from(size);
+                let checked = self.checked_bounds.entry(local).or_insert(0);
+                self.elide_bounds_check = *checked >= end;
+                *checked = (*checked).max(end);
+            }
+            loads = !store;
+        }
+
+        match *op {
+            Operator::LocalGet { local_index } => self.address_origins.push(Some(local_index)),
+            Operator::I32Const { .. }
+            | Operator::I64Const { .. }
+            | Operator::F32Const { .. }
+            | Operator::F64Const { .. }
+            | Operator::GlobalGet { .. } => self.address_origins.push(None),
+            // Loads replace their address with the loaded value.
+            _ if loads => {
+                if self.address_origins.pop().is_some() {
+                    self.address_origins.push(None);
+                }
+            }
+            Operator::LocalSet { local_index } | Operator::LocalTee { local_index } => {
+                // Values read from the local before are no longer its value.
+                self.address_origins.clear();
+                self.checked_bounds.remove(&local_index);
+            }
+            Operator::Block { .. }
+            | Operator::Loop { .. }
+            | Operator::If { .. }
+            | Operator::Else
+            | Operator::End
+            | Operator::Br { .. }
+            | Operator::BrIf { .. }
+            | Operator::BrTable { .. }
+            | Operator::Return
+            | Operator::Unreachable
+            | Operator::Call { .. }
+            | Operator::CallIndirect { .. }
+            | Operator::MemoryGrow { .. } => {
+                self.address_origins.clear();
+                self.checked_bounds.clear();
+            }
+            _ => self.address_origins.clear(),
+        }
+    }
+
     /// Emits a memory operation.
     fn emit_memory_op<F: FnOnce(&mut Self, GPR) -> Result<(), CodegenError>>(
         &mut self,
@@ -1350,7 +1673,7 @@ impl<'a> FuncGen<'a> {
         value_size: usize,
         cb: F,
     ) -> Result<(), CodegenError> {
-        let need_check = true;
+        let need_check = !std::mem::replace(&mut self.elide_bounds_check, false);
         let tmp_addr = self.machine.acquire_temp_gpr().unwrap();
 
         // Reusing `tmp_addr` for temporary indirection here, since it's not used before the last reference to `{base,bound}_loc`.
@@ -1417,9 +1740,12 @@ impl<'a> FuncGen<'a> {
                 Location::GPR(tmp_addr),
             );
 
-            // Trap if offset calculation overflowed.
-            self.assembler
-                .emit_jmp(Condition::Carry, self.special_labels.heap_access_oob);
+            // Trap if offset calculation overflowed. An earlier check of a larger offset
+            // implies it did not.
+            if need_check {
+                let trap = self.trap_label(TrapCode::HeapAccessOutOfBounds);
+                self.assembler.emit_jmp(Condition::Carry, trap);
+            }
         }
 
         // Wasm linear memory -> real memory
@@ -1432,8 +1758,8 @@ impl<'a> FuncGen<'a> {
                 .emit_cmp(Size::S64, Location::GPR(tmp_bound), Location::GPR(tmp_addr));
 
             // `tmp_bound` is inclusive. So trap only if `tmp_addr > tmp_bound`.
-            self.assembler
-                .emit_jmp(Condition::Above, self.special_labels.heap_access_oob);
+            let trap = self.trap_label(TrapCode::HeapAccessOutOfBounds);
+            self.assembler.emit_jmp(Condition::Above, trap);
         }
 
         self.machine.release_temp_gpr(tmp_bound);
@@ -1452,8 +1778,8 @@ impl<'a> FuncGen<'a> {
                 Location::Imm32((align - 1).into()),
                 Location::GPR(tmp_aligncheck),
             );
-            self.assembler
-                .emit_jmp(Condition::NotEqual, self.special_labels.heap_access_oob);
+            let trap = self.trap_label(TrapCode::HeapAccessOutOfBounds);
+            self.assembler.emit_jmp(Condition::NotEqual, trap);
             self.machine.release_temp_gpr(tmp_aligncheck);
         }
 
@@ -1581,8 +1907,8 @@ impl<'a> FuncGen<'a> {
 
     // Checks for underflow/overflow/nan before IxxTrunc{U/S}F32.
     fn emit_f32_int_conv_check_trap(&mut self, reg: XMM, lower_bound: f32, upper_bound: f32) {
-        let trap_overflow = self.special_labels.integer_overflow;
-        let trap_badconv = self.special_labels.bad_conversion_to_integer;
+        let trap_overflow = self.trap_label(TrapCode::IntegerOverflow);
+        let trap_badconv = self.trap_label(TrapCode::BadConversionToInteger);
         let end = self.assembler.get_label();
 
         self.emit_f32_int_conv_check(
@@ -1717,8 +2043,8 @@ impl<'a> FuncGen<'a> {
 
     // Checks for underflow/overflow/nan before IxxTrunc{U/S}F64.
     fn emit_f64_int_conv_check_trap(&mut self, reg: XMM, lower_bound: f64, upper_bound: f64) {
-        let trap_overflow = self.special_labels.integer_overflow;
-        let trap_badconv = self.special_labels.bad_conversion_to_integer;
+        let trap_overflow = self.trap_label(TrapCode::IntegerOverflow);
+        let trap_badconv = self.trap_label(TrapCode::BadConversionToInteger);
         let end = self.assembler.get_label();
 
         self.emit_f64_int_conv_check(
@@ -1806,8 +2132,8 @@ impl<'a> FuncGen<'a> {
             // TODO: make it cleaner, now we assume instruction with 32-bit immediate at the end.
             // Recheck offsets, if change above instruction to anything else.
             self.stack_check_offset = AssemblyOffset(self.assembler.offset().0 - 4);
-            self.assembler
-                .emit_jmp(Condition::Signed, self.special_labels.stack_overflow);
+            let trap = self.trap_label(TrapCode::StackOverflow);
+            self.assembler.emit_jmp(Condition::Signed, trap);
         } else {
             {
                 // Patch earlier stack checker with now known max stack depth.
@@ -1829,23 +2155,26 @@ impl<'a> FuncGen<'a> {
     }
 
     fn emit_function_stack_check(&mut self, enter: bool) {
-        // `local_types` include parameters as well.
-        let depth = self.local_count() as usize
-            + self.max_stack_depth
-            // we add 4 to ensure that deep recursion is prohibited even for local and argument free
-            // functions, as they still use stack space for the saved frame base and return address,
-            // along with spill area for callee-saved registers.
-            + 4;
+        // `local_types` include parameters as well. The locals held in registers are counted
+        // by the space saving these registers.
+        let depth = self.local_count() as usize + self.max_stack_depth + FRAME_OVERHEAD_SLOTS;
         self.emit_stack_check(enter, depth);
     }
 
     pub(crate) fn emit_head(&mut self) -> Result<(), CodegenError> {
         // TODO: Patchpoint is not emitted for now, and ARM trampoline is not prepended.
 
-        // Normal x86 entry prologue.
+        self.assembler.emit_label(self.entry);
+
+        // Normal x86 entry prologue. Without a frame pointer, RBP is still saved at the same
+        // place, as it is used as a general purpose register.
         self.assembler.emit_push(Size::S64, Location::GPR(GPR::RBP));
-        self.assembler
-            .emit_mov(Size::S64, Location::GPR(GPR::RSP), Location::GPR(GPR::RBP));
+        if self.config.frame_pointer {
+            self.assembler
+                .emit_mov(Size::S64, Location::GPR(GPR::RSP), Location::GPR(GPR::RBP));
+        } else {
+            self.assembler.mark_unwind(UnwindOp::SaveFramePointer);
+        }
 
         // Initialize locals.
         let local_count = self.local_count();
@@ -1858,8 +2187,24 @@ impl<'a> FuncGen<'a> {
 
         self.emit_function_stack_check(true);
 
-        self.assembler
-            .emit_sub(Size::S64, Location::Imm32(32), Location::GPR(GPR::RSP)); // simulate "red zone" if not supported by the platform
+        self.assembler.emit_sub(
+            Size::S64,
+            Location::Imm32(RED_ZONE_SIZE),
+            Location::GPR(GPR::RSP),
+        ); // simulate "red zone" if not supported by the platform
+
+        if self.config.diagnostics {
+            self.emit_diagnostic(
+                DiagnosticsLevel::Calls,
+                VMBuiltinFunctionIndex::get_diagnostics_call_index(),
+                // [vmctx, function]
+                &[],
+                Some(self.entry),
+            );
+        }
+        if self.config.cancellation {
+            self.emit_cancellation_check();
+        }
 
         self.control_stack.push(ControlFrame {
             label: self.assembler.get_label(),
@@ -1881,10 +2226,14 @@ impl<'a> FuncGen<'a> {
     /// Pushes the instruction to the address map, calculating the offset from a
     /// provided beginning address.
     fn mark_instruction_address_end(&mut self, begin: usize) {
+        let code_len = self.assembler.get_offset().0 - begin;
+        if code_len == 0 {
+            return;
+        }
         self.instructions_address_map.push(InstructionAddressMap {
             srcloc: SourceLoc::new(self.src_loc),
             code_offset: begin,
-            code_len: self.assembler.get_offset().0 - begin,
+            code_len,
         });
     }
 
@@ -1894,6 +2243,7 @@ impl<'a> FuncGen<'a> {
         config: &'a Singlepass,
         vmoffsets: &'a VMOffsets,
         _table_styles: &'a PrimaryMap<TableIndex, TableStyle>,
+        global_bindings: &'a BTreeMap<GlobalIndex, GlobalInit>,
         local_func_index: LocalFunctionIndex,
         calling_convention: CallingConvention,
     ) -> Result<FuncGen<'a>, CodegenError> {
@@ -1901,18 +2251,12 @@ impl<'a> FuncGen<'a> {
         let sig_index = module.functions[func_index];
         let signature = module.signatures[sig_index].clone();
 
-        let mut assembler = Assembler::new(0);
-        let special_labels = SpecialLabelSet {
-            integer_division_by_zero: assembler.get_label(),
-            integer_overflow: assembler.get_label(),
-            bad_conversion_to_integer: assembler.get_label(),
-            heap_access_oob: assembler.get_label(),
-            table_access_oob: assembler.get_label(),
-            indirect_call_null: assembler.get_label(),
-            bad_signature: assembler.get_label(),
-            gas_limit_exceeded: assembler.get_label(),
-            stack_overflow: assembler.get_label(),
+        let mut assembler = if config.frame_pointer {
+            Assembler::new(0)
+        } else {
+            Assembler::without_frame_pointer(0)
         };
+        let entry = assembler.get_label();
 
         let mut fg = FuncGen {
             module,
@@ -1926,14 +2270,21 @@ impl<'a> FuncGen<'a> {
             stack_check_offset: AssemblyOffset(0),
             fp_stack: vec![],
             control_stack: vec![],
-            machine: Machine::new(),
+            machine: Machine::new(config.frame_pointer),
             unreachable_depth: 0,
             relocations: vec![],
-            special_labels,
+            trap_stubs: vec![],
+            diagnostic_stubs: vec![],
             src_loc: 0,
+            src_loc_begin: 0,
             instructions_address_map: vec![],
             calling_convention,
             signature,
+            entry,
+            global_bindings,
+            address_origins: vec![],
+            checked_bounds: BTreeMap::new(),
+            elide_bounds_check: false,
         };
         for param in module.signatures[sig_index].params() {
             fg.feed_local(1, type_to_wp_type(*param));
@@ -2011,7 +2362,60 @@ impl<'a> FuncGen<'a> {
             was_unreachable = false;
         }
 
+        // Without a frame pointer, the stack depth between operators follows the machine state.
+        // After unreachable code, the machine state may still include the values left by the
+        // last branch, so the depth of the labels bound next takes precedence.
+        if !self.config.frame_pointer {
+            let cfa_offset = 16 + RED_ZONE_SIZE as i32 + self.machine.get_stack_offset() as i32;
+            if was_unreachable {
+                self.assembler.assume_cfa_offset(cfa_offset);
+            } else {
+                debug_assert_eq!(self.assembler.cfa_offset(), Some(cfa_offset));
+            }
+        }
+
+        if self.config.diagnostics {
+            if let Some((offset, size, store)) = memory_access(&op) {
+                // Stores take their address below the value to store.
+                self.emit_memory_diagnostic(store as usize, offset, size);
+            }
+        }
+
+        if self.config.redundant_bounds_check_elimination {
+            self.track_bounds_checks(&op);
+        }
+
         match op {
+            // Bound globals are immutable, and folded like the equivalent `const`.
+            Operator::GlobalGet { global_index }
+                if self
+                    .global_bindings
+                    .contains_key(&GlobalIndex::from_u32(global_index)) =>
+            {
+                match self.global_bindings[&GlobalIndex::from_u32(global_index)] {
+                    GlobalInit::I32Const(value) => {
+                        self.value_stack.push(Location::Imm32(value as u32));
+                    }
+                    GlobalInit::I64Const(value) => {
+                        self.value_stack.push(Location::Imm64(value as u64));
+                    }
+                    GlobalInit::F32Const(value) => {
+                        self.value_stack.push(Location::Imm32(value.to_bits()));
+                        self.fp_stack
+                            .push(FloatValue::new(self.value_stack.len() - 1));
+                    }
+                    GlobalInit::F64Const(value) => {
+                        self.value_stack.push(Location::Imm64(value.to_bits()));
+                        self.fp_stack
+                            .push(FloatValue::new(self.value_stack.len() - 1));
+                    }
+                    value => {
+                        return Err(CodegenError {
+                            message: format!("cannot fold {:?}", value),
+                        })
+                    }
+                }
+            }
             Operator::GlobalGet { global_index } => {
                 let global_index = GlobalIndex::from_u32(global_index);
 
@@ -5224,8 +5628,8 @@ impl<'a> FuncGen<'a> {
 
                 self.assembler
                     .emit_cmp(Size::S32, func_index, Location::GPR(table_count));
-                self.assembler
-                    .emit_jmp(Condition::BelowEqual, self.special_labels.table_access_oob);
+                let trap = self.trap_label(TrapCode::TableAccessOutOfBounds);
+                self.assembler.emit_jmp(Condition::BelowEqual, trap);
                 self.assembler
                     .emit_mov(Size::S32, func_index, Location::GPR(table_count));
                 self.assembler
@@ -5245,8 +5649,8 @@ impl<'a> FuncGen<'a> {
                 // Trap if the FuncRef is null
                 self.assembler
                     .emit_cmp(Size::S64, Location::Imm32(0), Location::GPR(table_count));
-                self.assembler
-                    .emit_jmp(Condition::Equal, self.special_labels.indirect_call_null);
+                let trap = self.trap_label(TrapCode::IndirectCallToNull);
+                self.assembler.emit_jmp(Condition::Equal, trap);
                 self.assembler.emit_mov(
                     Size::S64,
                     Location::Memory(
@@ -5265,8 +5669,8 @@ impl<'a> FuncGen<'a> {
                         (self.vmoffsets.vmcaller_checked_anyfunc_type_index() as usize) as i32,
                     ),
                 );
-                self.assembler
-                    .emit_jmp(Condition::NotEqual, self.special_labels.bad_signature);
+                let trap = self.trap_label(TrapCode::BadSignature);
+                self.assembler.emit_jmp(Condition::NotEqual, trap);
 
                 self.machine.release_temp_gpr(sigidx);
                 self.machine.release_temp_gpr(table_count);
@@ -5533,7 +5937,9 @@ impl<'a> FuncGen<'a> {
                 });
                 self.assembler.emit_label(label);
 
-                // TODO: Re-enable interrupt signal check without branching
+                if self.config.cancellation {
+                    self.emit_cancellation_check();
+                }
             }
             Operator::Nop => {}
             Operator::MemorySize { mem, mem_byte: _ } => {
@@ -6163,9 +6569,7 @@ impl<'a> FuncGen<'a> {
                 })?;
             }
             Operator::Unreachable => {
-                let offset = self.assembler.get_offset().0;
                 self.emit_trap(TrapCode::UnreachableCodeReached);
-                self.mark_instruction_address_end(offset);
                 self.unreachable_depth = 1;
             }
             Operator::Return => {
@@ -6353,10 +6757,13 @@ impl<'a> FuncGen<'a> {
                     Location::GPR(GPR::RCX),
                     Location::GPR(GPR::RDX),
                 );
+                // The cases and the jump table are reached through the indirect jump.
+                let cfa_offset = self.assembler.cfa_offset();
                 self.assembler.emit_jmp_location(Location::GPR(GPR::RDX));
 
                 for (target, _) in targets.iter() {
                     let label = self.assembler.get_label();
+                    self.assembler.set_cfa_offset(cfa_offset);
                     self.assembler.emit_label(label);
                     table.push(label);
                     let frame =
@@ -6460,6 +6867,7 @@ impl<'a> FuncGen<'a> {
 
                 self.assembler.emit_label(table_label);
                 for x in table {
+                    self.assembler.set_cfa_offset(cfa_offset);
                     self.assembler.emit_jmp(Condition::None, x);
                 }
                 self.unreachable_depth = 1;
@@ -6520,12 +6928,17 @@ impl<'a> FuncGen<'a> {
                         self.calling_convention,
                         local_count,
                     );
-                    self.assembler.emit_mov(
-                        Size::S64,
-                        Location::GPR(GPR::RBP),
-                        Location::GPR(GPR::RSP),
-                    );
+                    if self.config.frame_pointer {
+                        self.assembler.emit_mov(
+                            Size::S64,
+                            Location::GPR(GPR::RBP),
+                            Location::GPR(GPR::RSP),
+                        );
+                    }
                     self.assembler.emit_pop(Size::S64, Location::GPR(GPR::RBP));
+                    if !self.config.frame_pointer {
+                        self.assembler.mark_unwind(UnwindOp::RestoreFramePointer);
+                    }
 
                     // Make a copy of the return value in XMM0, as required by the SysV CC.
                     match self.signature.results() {
@@ -8370,42 +8783,39 @@ impl<'a> FuncGen<'a> {
         Ok(())
     }
 
-    pub(crate) fn finalize(mut self, data: &FunctionBodyData) -> CompiledFunction {
-        // Generate actual code for special labels.
-        self.assembler
-            .emit_label(self.special_labels.integer_division_by_zero);
-        self.emit_trap(TrapCode::IntegerDivisionByZero);
-
-        self.assembler
-            .emit_label(self.special_labels.integer_overflow);
-        self.emit_trap(TrapCode::IntegerOverflow);
-
-        self.assembler
-            .emit_label(self.special_labels.bad_conversion_to_integer);
-        self.emit_trap(TrapCode::BadConversionToInteger);
-
-        self.assembler
-            .emit_label(self.special_labels.heap_access_oob);
-        self.emit_trap(TrapCode::HeapAccessOutOfBounds);
-
-        self.assembler
-            .emit_label(self.special_labels.table_access_oob);
-        self.emit_trap(TrapCode::TableAccessOutOfBounds);
-
-        self.assembler
-            .emit_label(self.special_labels.indirect_call_null);
-        self.emit_trap(TrapCode::IndirectCallToNull);
-
-        self.assembler.emit_label(self.special_labels.bad_signature);
-        self.emit_trap(TrapCode::BadSignature);
+    /// Finish the function, returning it along with its unwind table when compiled without a
+    /// frame pointer.
+    pub(crate) fn finalize(
+        mut self,
+        data: &FunctionBodyData,
+    ) -> (CompiledFunction, Vec<(u32, UnwindOp)>) {
+        // Attribute the code of the last operator.
+        self.mark_instruction_address_end(self.src_loc_begin);
+
+        // The diagnostic and cancellation calls and trap sequences follow the epilogue, but run within the frame.
+        if !self.config.frame_pointer
+            && !(self.trap_stubs.is_empty() && self.diagnostic_stubs.is_empty())
+        {
+            self.assembler.mark_unwind(UnwindOp::SaveFramePointer);
+        }
 
-        self.assembler
-            .emit_label(self.special_labels.gas_limit_exceeded);
-        self.emit_trap(TrapCode::GasExceeded);
+        // Generate the diagnostic and cancellation calls, which return to the body of the
+        // function.
+        for stub in std::mem::take(&mut self.diagnostic_stubs) {
+            self.src_loc = stub.srcloc;
+            let begin = self.assembler.get_offset().0;
+            self.emit_diagnostic_stub(stub).unwrap();
+            self.mark_instruction_address_end(begin);
+        }
 
-        self.assembler
-            .emit_label(self.special_labels.stack_overflow);
-        self.emit_trap(TrapCode::StackOverflow);
+        // Generate actual code for the trap sequences.
+        for stub in std::mem::take(&mut self.trap_stubs) {
+            self.assembler.emit_label(stub.label);
+            self.src_loc = stub.srcloc;
+            let begin = self.assembler.get_offset().0;
+            self.emit_trap(stub.code);
+            self.mark_instruction_address_end(begin);
+        }
 
         // Notify the assembler backend to generate necessary code at end of function.
         self.assembler.finalize_function();
@@ -8413,9 +8823,10 @@ impl<'a> FuncGen<'a> {
         let body_len = self.assembler.get_offset().0;
         let instructions_address_map = self.instructions_address_map;
         let address_map = get_function_address_map(instructions_address_map, data, body_len);
+        let unwind_ops = self.assembler.take_unwind_ops();
         let body = self.assembler.finalize().unwrap().to_vec();
 
-        CompiledFunction {
+        let function = CompiledFunction {
             body: FunctionBody {
                 body,
                 unwind_info: None,
@@ -8426,10 +8837,39 @@ impl<'a> FuncGen<'a> {
                 traps: vec![],
                 address_map,
             },
-        }
+        };
+        (function, unwind_ops)
     }
 }
 
+/// Return the offset, the size in bytes, and whether it writes, of the access to linear
+/// memory made by `op`, if it is a load or a store.
+fn memory_access(op: &Operator) -> Option<(u32, u8, bool)> {
+    let (memarg, size, store) = match op {
+        Operator::I32Load8S { memarg }
+        | Operator::I32Load8U { memarg }
+        | Operator::I64Load8S { memarg }
+        | Operator::I64Load8U { memarg } => (memarg, 1, false),
+        Operator::I32Load16S { memarg }
+        | Operator::I32Load16U { memarg }
+        | Operator::I64Load16S { memarg }
+        | Operator::I64Load16U { memarg } => (memarg, 2, false),
+        Operator::I32Load { memarg }
+        | Operator::F32Load { memarg }
+        | Operator::I64Load32S { memarg }
+        | Operator::I64Load32U { memarg } => (memarg, 4, false),
+        Operator::I64Load { memarg } | Operator::F64Load { memarg } => (memarg, 8, false),
+        Operator::I32Store8 { memarg } | Operator::I64Store8 { memarg } => (memarg, 1, true),
+        Operator::I32Store16 { memarg } | Operator::I64Store16 { memarg } => (memarg, 2, true),
+        Operator::I32Store { memarg }
+        | Operator::F32Store { memarg }
+        | Operator::I64Store32 { memarg } => (memarg, 4, true),
+        Operator::I64Store { memarg } | Operator::F64Store { memarg } => (memarg, 8, true),
+        _ => return None,
+    };
+    Some((memarg.offset, size, store))
+}
+
 fn type_to_wp_type(ty: Type) -> WpType {
     match ty {
         Type::I32 => WpType::I32,
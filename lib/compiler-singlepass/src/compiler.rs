@@ -6,18 +6,21 @@ use crate::codegen_x64::{
     gen_import_call_trampoline, gen_std_dynamic_import_trampoline, gen_std_trampoline,
     CodegenError, FuncGen,
 };
-use crate::config::Singlepass;
+use crate::config::{IntrinsicKind, Singlepass};
+use crate::unwind::create_eh_frame;
 #[cfg(feature = "rayon")]
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 use std::sync::Arc;
 use wasmer_compiler::{
     Architecture, CallingConvention, Compilation, CompileError, CompileModuleInfo,
-    CompiledFunction, Compiler, CompilerConfig, CpuFeature, FunctionBody, FunctionBodyData,
-    ModuleTranslationState, OperatingSystem, SectionIndex, Target, TrapInformation,
+    CompiledFunction, CompiledFunctionUnwindInfo, Compiler, CompilerConfig, CompilerProvenance,
+    CpuFeature, Dwarf, FunctionBody, FunctionBodyData, ModuleTranslationState, OperatingSystem,
+    OperatorCosts, ScheduleVersion, SectionIndex, Target, TrapInformation,
 };
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
-    FunctionIndex, FunctionType, LocalFunctionIndex, MemoryIndex, ModuleInfo, TableIndex,
+    FunctionIndex, FunctionType, ImportIndex, LocalFunctionIndex, MemoryIndex, ModuleInfo,
+    TableIndex,
 };
 use wasmer_vm::{TrapCode, VMOffsets};
 
@@ -37,9 +40,93 @@ impl SinglepassCompiler {
     fn config(&self) -> &Singlepass {
         &self.config
     }
+
+    /// The cost table of the metering schedule `version`.
+    fn schedule(&self, version: ScheduleVersion) -> Result<&OperatorCosts, CompileError> {
+        self.config
+            .metering_schedules
+            .iter()
+            .find(|(schedule, _)| *schedule == version)
+            .map(|(_, costs)| costs)
+            .ok_or_else(|| {
+                CompileError::UnsupportedFeature(format!("metering schedule {}", version))
+            })
+    }
+
+    /// The provenance of the code compiled with the `metering` middleware, if any.
+    fn provenance_with(&self, metering: Option<String>) -> CompilerProvenance {
+        let config = self.config();
+        CompilerProvenance {
+            name: "singlepass".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            flags: vec![
+                format!("canonicalize_nans={}", config.enable_nan_canonicalization),
+                format!("stack_check={}", config.enable_stack_check),
+                format!("frame_pointer={}", config.frame_pointer),
+                format!("diagnostics={}", config.diagnostics),
+                format!("cancellation={}", config.cancellation),
+                format!(
+                    "redundant_bounds_check_elimination={}",
+                    config.redundant_bounds_check_elimination
+                ),
+                format!("strip_unused_imports={}", config.strip_unused_imports),
+            ],
+            middlewares: config
+                .intrinsics
+                .iter()
+                .map(|intrinsic| match intrinsic.kind {
+                    IntrinsicKind::Gas => format!("intrinsic:{}", intrinsic.name),
+                    IntrinsicKind::Trap(code) => {
+                        format!("intrinsic:{}:trap{}", intrinsic.name, code)
+                    }
+                })
+                .chain(metering)
+                .collect(),
+        }
+    }
 }
 
 impl Compiler for SinglepassCompiler {
+    fn provenance(&self) -> CompilerProvenance {
+        let metering = self.config.metering.as_ref();
+        self.provenance_with(metering.map(|costs| format!("metering:{}", costs.name())))
+    }
+
+    fn schedule_provenance(
+        &self,
+        version: ScheduleVersion,
+    ) -> Result<CompilerProvenance, CompileError> {
+        let costs = self.schedule(version)?;
+        Ok(self.provenance_with(Some(format!("metering:{}@{}", costs.name(), version))))
+    }
+
+    fn trap_imports(&self, module: &ModuleInfo) -> Vec<(FunctionIndex, TrapCode)> {
+        // As `FuncGen::try_intrinsic` finds the intrinsics of calls.
+        module
+            .imports
+            .iter()
+            .filter_map(|((_, field, _), index)| match index {
+                ImportIndex::Function(function) => Some((field, *function)),
+                _ => None,
+            })
+            .filter_map(|(field, function)| {
+                let signature = &module.signatures[module.functions[function]];
+                self.config.intrinsics.iter().find_map(|intrinsic| match intrinsic.kind {
+                    IntrinsicKind::Trap(code)
+                        if intrinsic.name == *field && intrinsic.signature == *signature =>
+                    {
+                        Some((function, TrapCode::Custom(code)))
+                    }
+                    _ => None,
+                })
+            })
+            .collect()
+    }
+
+    fn strips_unused_imports(&self) -> bool {
+        self.config.strip_unused_imports
+    }
+
     /// Compile the module using Singlepass, producing a compilation result with
     /// associated relocations.
     fn compile_module(
@@ -49,31 +136,48 @@ impl Compiler for SinglepassCompiler {
         module_translation: &ModuleTranslationState,
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
     ) -> Result<Compilation, CompileError> {
-        /*if target.triple().operating_system == OperatingSystem::Windows {
-            return Err(CompileError::UnsupportedTarget(
-                OperatingSystem::Windows.to_string(),
-            ));
-        }*/
-        if target.triple().architecture != Architecture::X86_64 {
-            return Err(CompileError::UnsupportedTarget(
-                target.triple().architecture.to_string(),
-            ));
-        }
-        if !target.cpu_features().contains(CpuFeature::AVX) {
-            return Err(CompileError::UnsupportedTarget(
-                "x86_64 without AVX".to_string(),
-            ));
-        }
+        self.compile_module_streaming(
+            target,
+            compile_info,
+            module_translation,
+            function_body_inputs,
+            &|_, _| {},
+        )
+    }
+
+    /// Compile the module using Singlepass, handing each function to `sink` from the thread
+    /// that compiled it.
+    fn compile_module_streaming(
+        &self,
+        target: &Target,
+        compile_info: &CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+        sink: &(dyn Fn(LocalFunctionIndex, &CompiledFunction) + Sync),
+    ) -> Result<Compilation, CompileError> {
+        let calling_convention = check_target(target)?;
         if compile_info.features.multi_value {
             return Err(CompileError::UnsupportedFeature("multivalue".to_string()));
         }
-        let calling_convention = match target.triple().default_calling_convention() {
-            Ok(CallingConvention::WindowsFastcall) => CallingConvention::WindowsFastcall,
-            Ok(CallingConvention::SystemV) => CallingConvention::SystemV,
-            //Ok(CallingConvention::AppleAarch64) => AppleAarch64,
-            _ => panic!("Unsupported Calling convention for Singlepass compiler"),
-        };
+        if compile_info.features.exceptions {
+            return Err(CompileError::UnsupportedFeature("exceptions".to_string()));
+        }
+        if let Some(limit) = self.config.max_function_body_size {
+            for (index, input) in function_body_inputs.iter() {
+                if input.data.len() > limit {
+                    return Err(CompileError::CodeTooLarge {
+                        index,
+                        size: input.data.len(),
+                        limit,
+                    });
+                }
+            }
+        }
 
+        let metering = match compile_info.metering_schedule {
+            Some(version) => Some(self.schedule(version)?),
+            None => self.config.metering.as_ref(),
+        };
         let table_styles = &compile_info.table_styles;
         let module = &compile_info.module;
         let pointer_width = target
@@ -84,10 +188,10 @@ impl Compiler for SinglepassCompiler {
             })?
             .bytes();
         let vmoffsets = VMOffsets::new(pointer_width).with_module_info(&module);
-        let import_idxs = 0..module.import_counts.functions as usize;
-        let import_trampolines: PrimaryMap<SectionIndex, _> = import_idxs
-            .into_par_iter_if_rayon()
-            .map(|i| {
+        let parallel = self.config.parallel_compilation;
+        let import_idxs = (0..module.import_counts.functions as usize).collect::<Vec<_>>();
+        let mut custom_sections: PrimaryMap<SectionIndex, _> =
+            map_in_order(parallel, import_idxs, |i| {
                 let i = FunctionIndex::new(i);
                 gen_import_call_trampoline(
                     &vmoffsets,
@@ -96,81 +200,143 @@ impl Compiler for SinglepassCompiler {
                     calling_convention,
                 )
             })
-            .collect::<Vec<_>>()
             .into_iter()
             .collect();
-        let functions = function_body_inputs
+        // Without a frame pointer, unwinders need the unwind tables of the functions.
+        let dwarf = !self.config.frame_pointer && calling_convention == CallingConvention::SystemV;
+        let inputs = function_body_inputs
             .iter()
-            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
-            .into_par_iter_if_rayon()
-            .map(|(i, input)| {
-                let reader = wasmer_compiler::FunctionReader::new(input.module_offset, input.data);
-
-                let mut generator = FuncGen::new(
-                    module,
-                    module_translation,
-                    &self.config,
-                    &vmoffsets,
-                    &table_styles,
-                    i,
-                    calling_convention,
-                )
-                .map_err(to_compile_error)?;
-
-                let mut local_reader = reader.get_locals_reader()?;
-                for _ in 0..local_reader.get_count() {
-                    let (count, ty) = local_reader.read()?;
-                    // Overflows feeding a local here have most likely already been caught by the
-                    // validator, but it is possible that the validator hasn't been run at all, or
-                    // that the validator does not impose any limits on the number of locals.
-                    generator.feed_local(count, ty);
-                }
+            .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>();
+        let functions = map_in_order(parallel, inputs, |(i, input)| {
+            let reader = wasmer_compiler::FunctionReader::new(input.module_offset, input.data);
 
-                generator.emit_head().map_err(to_compile_error)?;
+            let mut generator = FuncGen::new(
+                module,
+                module_translation,
+                &self.config,
+                &vmoffsets,
+                &table_styles,
+                &compile_info.global_bindings,
+                i,
+                calling_convention,
+            )
+            .map_err(to_compile_error)?;
 
-                let mut operator_reader = reader.get_operators_reader()?.into_iter_with_offsets();
-                while generator.has_control_frames() {
-                    let (op, pos) = operator_reader.next().unwrap()?;
-                    generator.set_srcloc(pos as u32);
-                    generator.feed_operator(op).map_err(to_compile_error)?;
+            let mut local_reader = reader.get_locals_reader()?;
+            for _ in 0..local_reader.get_count() {
+                let (count, ty) = local_reader.read()?;
+                // Overflows feeding a local here have most likely already been caught by the
+                // validator, but it is possible that the validator hasn't been run at all, or
+                // that the validator does not impose any limits on the number of locals.
+                generator.feed_local(count, ty);
+            }
+
+            // The prologue, and its stack check, is attributed to the start of the body.
+            generator.set_srcloc(input.module_offset as u32);
+            generator.emit_head().map_err(to_compile_error)?;
+
+            let blocks = match metering {
+                Some(costs) => costs.basic_blocks(&reader)?,
+                None => Vec::new(),
+            };
+            let mut blocks = blocks.into_iter().peekable();
+            let mut operator_reader = reader.get_operators_reader()?.into_iter_with_offsets();
+            let mut index = 0;
+            while generator.has_control_frames() {
+                let (op, pos) = operator_reader.next().unwrap()?;
+                generator.set_srcloc(pos as u32);
+                if let Some((_, cost)) = blocks.next_if(|&(start, _)| start == index) {
+                    generator.emit_metering(cost);
                 }
+                generator.feed_operator(op).map_err(to_compile_error)?;
+                index += 1;
+            }
 
-                Ok(generator.finalize(&input))
-            })
-            .collect::<Result<Vec<CompiledFunction>, CompileError>>()?
+            let (mut function, unwind_ops) = generator.finalize(&input);
+            if dwarf {
+                function.body.unwind_info = Some(CompiledFunctionUnwindInfo::Dwarf);
+            }
+            sink(i, &function);
+            Ok((function, unwind_ops))
+        })
+        .into_iter()
+        .collect::<Result<Vec<_>, CompileError>>()?;
+
+        let mut debug = None;
+        if dwarf {
+            let eh_frame = create_eh_frame(functions.iter().enumerate().map(
+                |(i, (function, unwind_ops))| {
+                    let index = LocalFunctionIndex::new(i);
+                    (index, function.body.body.len(), &unwind_ops[..])
+                },
+            ));
+            debug = Some(Dwarf::new(custom_sections.push(eh_frame)));
+        }
+        let functions = functions
             .into_iter()
+            .map(|(function, _)| function)
             .collect::<PrimaryMap<LocalFunctionIndex, CompiledFunction>>();
 
-        let function_call_trampolines = module
-            .signatures
-            .values()
-            .collect::<Vec<_>>()
-            .into_par_iter_if_rayon()
-            .map(|func_type| gen_std_trampoline(&func_type, calling_convention))
-            .collect::<Vec<_>>()
-            .into_iter()
-            .collect::<PrimaryMap<_, _>>();
-
-        let dynamic_function_trampolines = module
-            .imported_function_types()
-            .collect::<Vec<_>>()
-            .into_par_iter_if_rayon()
-            .map(|func_type| {
-                gen_std_dynamic_import_trampoline(&vmoffsets, &func_type, calling_convention)
-            })
-            .collect::<Vec<_>>()
-            .into_iter()
-            .collect::<PrimaryMap<FunctionIndex, FunctionBody>>();
+        let signatures = module.signatures.values().collect::<Vec<_>>();
+        let function_call_trampolines = map_in_order(parallel, signatures, |func_type| {
+            gen_std_trampoline(&func_type, calling_convention)
+        })
+        .into_iter()
+        .collect::<PrimaryMap<_, _>>();
+
+        let imported_types = module.imported_function_types().collect::<Vec<_>>();
+        let dynamic_function_trampolines = map_in_order(parallel, imported_types, |func_type| {
+            gen_std_dynamic_import_trampoline(&vmoffsets, &func_type, calling_convention)
+        })
+        .into_iter()
+        .collect::<PrimaryMap<FunctionIndex, FunctionBody>>();
 
         Ok(Compilation::new(
             functions,
-            import_trampolines,
+            custom_sections,
             function_call_trampolines,
             dynamic_function_trampolines,
-            None,
+            debug,
             None,
         ))
     }
+
+    fn compile_function_call_trampolines(
+        &self,
+        target: &Target,
+        signatures: &[FunctionType],
+    ) -> Result<Vec<FunctionBody>, CompileError> {
+        let calling_convention = check_target(target)?;
+        Ok(signatures
+            .iter()
+            .map(|func_type| gen_std_trampoline(func_type, calling_convention))
+            .collect())
+    }
+}
+
+/// Check that Singlepass can compile for `target`, returning its calling convention.
+fn check_target(target: &Target) -> Result<CallingConvention, CompileError> {
+    /*if target.triple().operating_system == OperatingSystem::Windows {
+        return Err(CompileError::UnsupportedTarget(
+            OperatingSystem::Windows.to_string(),
+        ));
+    }*/
+    if target.triple().architecture != Architecture::X86_64 {
+        return Err(CompileError::UnsupportedTarget(
+            target.triple().architecture.to_string(),
+        ));
+    }
+    if !target.cpu_features().contains(CpuFeature::AVX) {
+        return Err(CompileError::UnsupportedTarget(
+            "x86_64 without AVX".to_string(),
+        ));
+    }
+    match target.triple().default_calling_convention() {
+        Ok(CallingConvention::WindowsFastcall) => Ok(CallingConvention::WindowsFastcall),
+        Ok(CallingConvention::SystemV) => Ok(CallingConvention::SystemV),
+        //Ok(CallingConvention::AppleAarch64) => AppleAarch64,
+        _ => panic!("Unsupported Calling convention for Singlepass compiler"),
+    }
 }
 
 trait ToCompileError {
@@ -187,25 +353,29 @@ fn to_compile_error<T: ToCompileError>(x: T) -> CompileError {
     x.to_compile_error()
 }
 
-trait IntoParIterIfRayon {
-    type Output;
-    fn into_par_iter_if_rayon(self) -> Self::Output;
-}
-
+/// Map `f` over `items`, on the rayon thread pool if `parallel`, keeping the order of `items`
+/// in the results either way.
 #[cfg(feature = "rayon")]
-impl<T: IntoParallelIterator + IntoIterator> IntoParIterIfRayon for T {
-    type Output = <T as IntoParallelIterator>::Iter;
-    fn into_par_iter_if_rayon(self) -> Self::Output {
-        return self.into_par_iter();
+fn map_in_order<T, R, F>(parallel: bool, items: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync + Send,
+{
+    if parallel {
+        items.into_par_iter().map(f).collect()
+    } else {
+        items.into_iter().map(f).collect()
     }
 }
 
+/// Map `f` over `items` in order, as there is no thread pool without the `rayon` feature.
 #[cfg(not(feature = "rayon"))]
-impl<T: IntoIterator> IntoParIterIfRayon for T {
-    type Output = <T as IntoIterator>::IntoIter;
-    fn into_par_iter_if_rayon(self) -> Self::Output {
-        return self.into_iter();
-    }
+fn map_in_order<T, R, F>(_parallel: bool, items: Vec<T>, f: F) -> Vec<R>
+where
+    F: Fn(T) -> R,
+{
+    items.into_iter().map(f).collect()
 }
 
 #[cfg(test)]
@@ -226,6 +396,8 @@ mod tests {
             module: Arc::new(ModuleInfo::new()),
             memory_styles: PrimaryMap::<MemoryIndex, MemoryStyle>::new(),
             table_styles: PrimaryMap::<TableIndex, TableStyle>::new(),
+            global_bindings: Default::default(),
+            metering_schedule: None,
         };
         let module_translation = ModuleTranslationState::new();
         let function_body_inputs = PrimaryMap::<LocalFunctionIndex, FunctionBodyData<'_>>::new();
@@ -5,12 +5,17 @@ use crate::compiler::SinglepassCompiler;
 use crate::emitter_x64::Location;
 use smallvec::SmallVec;
 use std::sync::Arc;
-use wasmer_compiler::{Compiler, CompilerConfig, CpuFeature, Target};
+use wasmer_compiler::{
+    Compiler, CompilerConfig, CpuFeature, OperatorCosts, ScheduleVersion, Target,
+};
 use wasmer_types::{Features, FunctionType, Type};
+use wasmer_vm::{TrapCode, TrapCodeCollision};
 
 #[derive(Debug, Clone)]
 pub(crate) enum IntrinsicKind {
     Gas,
+    /// Raise the custom trap with the code.
+    Trap(u16),
 }
 
 #[derive(Debug, Clone)]
@@ -24,6 +29,15 @@ pub(crate) struct Intrinsic {
 pub struct Singlepass {
     pub(crate) enable_nan_canonicalization: bool,
     pub(crate) enable_stack_check: bool,
+    pub(crate) frame_pointer: bool,
+    pub(crate) diagnostics: bool,
+    pub(crate) cancellation: bool,
+    pub(crate) redundant_bounds_check_elimination: bool,
+    pub(crate) parallel_compilation: bool,
+    pub(crate) max_function_body_size: Option<usize>,
+    pub(crate) metering: Option<OperatorCosts>,
+    pub(crate) metering_schedules: Vec<(ScheduleVersion, OperatorCosts)>,
+    pub(crate) strip_unused_imports: bool,
     /// Compiler intrinsics.
     pub(crate) intrinsics: Vec<Intrinsic>,
 }
@@ -35,6 +49,15 @@ impl Singlepass {
         Self {
             enable_nan_canonicalization: true,
             enable_stack_check: false,
+            frame_pointer: true,
+            diagnostics: false,
+            cancellation: false,
+            redundant_bounds_check_elimination: false,
+            parallel_compilation: true,
+            max_function_body_size: None,
+            metering: None,
+            metering_schedules: Vec::new(),
+            strip_unused_imports: false,
             intrinsics: vec![Intrinsic {
                 kind: IntrinsicKind::Gas,
                 name: "gas".to_string(),
@@ -55,6 +78,139 @@ impl Singlepass {
         self
     }
 
+    /// Keep a frame pointer.
+    ///
+    /// When disabled, functions address their frame relative to RSP and RBP is
+    /// available as a general purpose register. On System V targets, unwinders
+    /// (for trap backtraces and profilers) then rely on the `.eh_frame` unwind
+    /// tables emitted for the module instead of the chain of frame pointers.
+    ///
+    /// Enabled by default.
+    pub fn frame_pointer(&mut self, enable: bool) -> &mut Self {
+        self.frame_pointer = enable;
+        self
+    }
+
+    /// Check the diagnostics level of the instance on function entry and before each load and
+    /// store, so that diagnostics can be turned on for an instance while it runs.
+    ///
+    /// Instances whose diagnostics are off only pay for a compare and a branch at each of
+    /// these points. In `benches/diagnostics.rs`, this is lost in the noise for calls from the
+    /// host, but costs 10 to 30% on a loop calling a function made of nothing but three memory
+    /// accesses. When disabled, diagnostics levels have no effect on the compiled code.
+    ///
+    /// Disabled by default.
+    pub fn diagnostics(&mut self, enable: bool) -> &mut Self {
+        self.diagnostics = enable;
+        self
+    }
+
+    /// Check for the cancellation of the running call on function entry and at the start of
+    /// every loop iteration, so that calls can be cancelled through their handle while they
+    /// run.
+    ///
+    /// Each check is a compare and a branch that is not taken unless a call into the instance
+    /// was cancelled. When disabled, cancelling a call only keeps it from starting.
+    ///
+    /// Disabled by default.
+    pub fn cancellation(&mut self, enable: bool) -> &mut Self {
+        self.cancellation = enable;
+        self
+    }
+
+    /// Skip the bounds checks of memory accesses that an earlier check already covers.
+    ///
+    /// Within a straight-line run of code, an access at the value of a local is not checked
+    /// again if an access at the same value, ending at the same or a larger offset, was
+    /// checked already. Any write to the local, branch, label, call or `memory.grow` ends the
+    /// run, so that out-of-bounds accesses trap exactly as they do with every access checked.
+    ///
+    /// This favors code accessing memory at decreasing offsets from a cursor, or several
+    /// times at the same offsets. In `benches/bounds_checks.rs`, it removes three quarters of
+    /// the checks of a copy loop.
+    ///
+    /// Disabled by default.
+    pub fn redundant_bounds_check_elimination(&mut self, enable: bool) -> &mut Self {
+        self.redundant_bounds_check_elimination = enable;
+        self
+    }
+
+    /// Compile the functions and trampolines of a module on the rayon thread pool.
+    ///
+    /// The compiled module is the same either way, byte for byte: disable this to compile
+    /// on the calling thread alone. It has no effect when the `rayon` feature is disabled.
+    ///
+    /// Enabled by default.
+    pub fn parallel_compilation(&mut self, enable: bool) -> &mut Self {
+        self.parallel_compilation = enable;
+        self
+    }
+
+    /// Fail to compile modules with a function whose body is larger than `limit` bytes of
+    /// WebAssembly, see [`CompilerConfig::set_max_function_body_size`].
+    ///
+    /// Unlimited by default.
+    pub fn max_function_body_size(&mut self, limit: usize) -> &mut Self {
+        self.max_function_body_size = Some(limit);
+        self
+    }
+
+    /// Charge the gas counter of the instances with the cost of every basic block of their
+    /// code, from `costs`, see [`CompilerConfig::set_metering`].
+    ///
+    /// Each block costs a load, an add, a compare and a store of the counter. The name of
+    /// the cost table is part of the provenance of the compiled modules.
+    ///
+    /// Disabled by default.
+    pub fn metering(&mut self, costs: OperatorCosts) -> &mut Self {
+        self.metering = Some(costs);
+        self
+    }
+
+    /// Meter the modules compiled with a metering schedule with the cost table of their
+    /// version in `schedules`, see [`CompilerConfig::set_metering_schedules`].
+    ///
+    /// The version and the name of the cost table are part of the provenance of the modules
+    /// compiled with a schedule. Compiling a module with a version missing from `schedules`
+    /// fails.
+    pub fn metering_schedules(
+        &mut self,
+        schedules: Vec<(ScheduleVersion, OperatorCosts)>,
+    ) -> &mut Self {
+        self.metering_schedules = schedules;
+        self
+    }
+
+    /// Drop the imported functions the code of a module never uses from the imports its
+    /// instances must be given, see [`CompilerConfig::strip_unused_imports`].
+    ///
+    /// Disabled by default.
+    pub fn strip_unused_imports(&mut self, enable: bool) -> &mut Self {
+        self.strip_unused_imports = enable;
+        self
+    }
+
+    /// Compile calls to the imported functions named `name`, taking and returning nothing,
+    /// into raising the trap [`TrapCode::Custom`] with `code` instead.
+    ///
+    /// This lets code instrumenting modules, such as a metering pass, raise traps the host
+    /// tells apart from the others with `RuntimeError::trap_code`, and whose message is
+    /// `name`. The code is registered with its name for the whole process, see
+    /// [`TrapCode::register_custom`], which fails if it is registered with another name
+    /// already. The code and name are part of the provenance of the compiled modules.
+    pub fn custom_trap(&mut self, name: &str, code: u16) -> Result<&mut Self, TrapCodeCollision> {
+        TrapCode::register_custom(code, name)?;
+        self.intrinsics.retain(|intrinsic| {
+            intrinsic.name != name || !matches!(intrinsic.kind, IntrinsicKind::Trap(_))
+        });
+        self.intrinsics.push(Intrinsic {
+            kind: IntrinsicKind::Trap(code),
+            name: name.to_string(),
+            signature: ([], []).into(),
+        });
+        Ok(self)
+    }
+
     fn enable_nan_canonicalization(&mut self) {
         self.enable_nan_canonicalization = true;
     }
@@ -71,6 +227,26 @@ impl CompilerConfig for Singlepass {
         // PIC code.
     }
 
+    fn enable_parallel_compilation(&mut self, enable: bool) {
+        self.parallel_compilation = enable;
+    }
+
+    fn set_max_function_body_size(&mut self, limit: usize) {
+        self.max_function_body_size(limit);
+    }
+
+    fn set_metering(&mut self, costs: OperatorCosts) {
+        self.metering(costs);
+    }
+
+    fn set_metering_schedules(&mut self, schedules: Vec<(ScheduleVersion, OperatorCosts)>) {
+        self.metering_schedules(schedules);
+    }
+
+    fn strip_unused_imports(&mut self, enable: bool) {
+        self.strip_unused_imports(enable);
+    }
+
     /// Transform it into the compiler
     fn compiler(self: Box<Self>) -> Box<dyn Compiler> {
         Box::new(SinglepassCompiler::new(*self))
@@ -97,6 +273,7 @@ impl Intrinsic {
                 Location::Imm32(value) => value < i32::MAX as u32,
                 _ => false,
             },
+            IntrinsicKind::Trap(_) => true,
         }
     }
 }
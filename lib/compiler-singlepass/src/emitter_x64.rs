@@ -1,10 +1,11 @@
+use crate::unwind::UnwindOp;
 pub(crate) use crate::x64_decl::{GPR, XMM};
 use dynasm::dynasm;
 use dynasmrt::{
-    x64::X64Relocation, AssemblyOffset, DynamicLabel, DynasmApi, DynasmLabelApi, VecAssembler,
+    x64::X64Relocation, AssemblyOffset, DynamicLabel, DynasmApi, DynasmError, DynasmLabelApi,
+    UncommittedModifier, VecAssembler,
 };
-
-type Assembler = VecAssembler<X64Relocation>;
+use std::collections::HashMap;
 
 /// Force `dynasm!` to use the correct arch (x64) when cross-compiling.
 /// `dynasm!` proc-macro tries to auto-detect it by default by looking at the
@@ -378,6 +379,9 @@ macro_rules! binop_imm32_mem {
             (Size::S64, Location::Imm32(src), Location::Memory(dst, disp)) => {
                 dynasm!($assembler ; $ins QWORD [Rq(dst as u8) + disp], src as i32);
             },
+            (Size::S8, Location::Imm8(src), Location::Memory(dst, disp)) => {
+                dynasm!($assembler ; $ins BYTE [Rq(dst as u8) + disp], src as i8);
+            },
             _ => $otherwise
         }
     };
@@ -635,7 +639,7 @@ macro_rules! avx_round_fn {
     }
 }
 
-impl Emitter for Assembler {
+impl Emitter for VecAssembler<X64Relocation> {
     type Label = DynamicLabel;
     type Offset = AssemblyOffset;
 
@@ -1423,3 +1427,523 @@ impl Emitter for Assembler {
         2
     }
 }
+
+/// How the assembler addresses the frame of the function.
+#[derive(Copy, Clone)]
+enum FrameAddressing {
+    /// Relative to RBP, which points to the frame base.
+    FramePointer,
+    /// Relative to RSP, for a CFA this many bytes above RSP, if known.
+    StackPointer(Option<i32>),
+}
+
+/// An operand of an instruction, which may address the frame of the function.
+trait FrameOperand: Copy {
+    /// Rewrite the addresses of the operand relative to [`GPR::FrameBase`] to be relative to
+    /// an actual register.
+    fn rebase(self, _addressing: FrameAddressing) -> Self {
+        self
+    }
+}
+
+impl FrameOperand for u8 {}
+impl FrameOperand for u32 {}
+impl FrameOperand for usize {}
+impl FrameOperand for Size {}
+impl FrameOperand for Condition {}
+impl FrameOperand for XMM {}
+impl FrameOperand for DynamicLabel {}
+
+impl FrameOperand for GPR {
+    fn rebase(self, _addressing: FrameAddressing) -> Self {
+        assert_ne!(self, GPR::FrameBase, "the frame base is not a register");
+        self
+    }
+}
+
+/// Compute the register and displacement addressing `disp` bytes from the frame base.
+fn rebase_frame_address(disp: i32, addressing: FrameAddressing) -> (GPR, i32) {
+    match addressing {
+        FrameAddressing::FramePointer => (GPR::RBP, disp),
+        FrameAddressing::StackPointer(cfa_offset) => {
+            // The frame base is 16 bytes below the CFA, right below the return address and the
+            // caller's RBP.
+            let cfa_offset = cfa_offset.expect("the frame is addressed at an unknown stack depth");
+            (GPR::RSP, disp + cfa_offset - 16)
+        }
+    }
+}
+
+impl FrameOperand for Location {
+    fn rebase(self, addressing: FrameAddressing) -> Self {
+        match self {
+            Location::GPR(gpr) => Location::GPR(gpr.rebase(addressing)),
+            Location::Memory(GPR::FrameBase, disp) => {
+                let (base, disp) = rebase_frame_address(disp, addressing);
+                Location::Memory(base, disp)
+            }
+            Location::MemoryAddTriple(GPR::FrameBase, ..)
+            | Location::MemoryAddTriple(_, GPR::FrameBase, _) => {
+                panic!("singlepass can't address the frame with {:?}", self)
+            }
+            _ => self,
+        }
+    }
+}
+
+impl FrameOperand for XMMOrMemory {
+    fn rebase(self, addressing: FrameAddressing) -> Self {
+        match self {
+            XMMOrMemory::Memory(GPR::FrameBase, disp) => {
+                let (base, disp) = rebase_frame_address(disp, addressing);
+                XMMOrMemory::Memory(base, disp)
+            }
+            _ => self,
+        }
+    }
+}
+
+impl FrameOperand for GPROrMemory {
+    fn rebase(self, addressing: FrameAddressing) -> Self {
+        match self {
+            GPROrMemory::GPR(gpr) => GPROrMemory::GPR(gpr.rebase(addressing)),
+            GPROrMemory::Memory(GPR::FrameBase, disp) => {
+                let (base, disp) = rebase_frame_address(disp, addressing);
+                GPROrMemory::Memory(base, disp)
+            }
+            _ => self,
+        }
+    }
+}
+
+/// The stack depth tracked when compiling without a frame pointer.
+struct FrameTracker {
+    /// How many bytes the CFA (the value of RSP before the call) is above RSP, unless the code
+    /// at the current position is only reachable through a label that is not yet bound.
+    cfa_offset: Option<i32>,
+    /// Whether `cfa_offset` is only assumed, as the code at the current position may only be
+    /// reachable through a label that is not yet bound.
+    assumed: bool,
+    /// The CFA offset at the labels bound or jumped to so far.
+    labels: HashMap<DynamicLabel, i32>,
+    /// The changes of the unwind rules, by code offset.
+    unwind_ops: Vec<(u32, UnwindOp)>,
+    /// The CFA offset described by `unwind_ops` at the current position.
+    unwind_cfa_offset: i32,
+}
+
+/// The assembler of the functions compiled by singlepass.
+///
+/// Code generation addresses the frame relative to [`GPR::FrameBase`], which the assembler
+/// rewrites to RBP for functions compiled with a frame pointer. Without a frame pointer, RBP is
+/// a general register: the assembler tracks how far RSP is below the CFA, rewrites the frame
+/// addresses to be relative to RSP instead, and records the unwind table of the function.
+pub(crate) struct Assembler {
+    inner: VecAssembler<X64Relocation>,
+    frame: Option<FrameTracker>,
+}
+
+impl Assembler {
+    /// Create an assembler for code with a frame pointer, or without a frame.
+    pub(crate) fn new(baseaddr: usize) -> Self {
+        Self {
+            inner: VecAssembler::new(baseaddr),
+            frame: None,
+        }
+    }
+
+    /// Create an assembler for a function without a frame pointer, positioned at its entry.
+    pub(crate) fn without_frame_pointer(baseaddr: usize) -> Self {
+        Self {
+            inner: VecAssembler::new(baseaddr),
+            frame: Some(FrameTracker {
+                cfa_offset: Some(8),
+                assumed: false,
+                labels: HashMap::new(),
+                unwind_ops: Vec::new(),
+                unwind_cfa_offset: 8,
+            }),
+        }
+    }
+
+    pub(crate) fn offset(&self) -> AssemblyOffset {
+        self.inner.offset()
+    }
+
+    pub(crate) fn alter(&mut self) -> UncommittedModifier<'_> {
+        self.inner.alter()
+    }
+
+    pub(crate) fn finalize(self) -> Result<Vec<u8>, DynasmError> {
+        self.inner.finalize()
+    }
+
+    /// Get how many bytes the CFA is above RSP at the current position, if tracked and known.
+    pub(crate) fn cfa_offset(&self) -> Option<i32> {
+        self.frame.as_ref().and_then(|frame| frame.cfa_offset)
+    }
+
+    /// Set how many bytes the CFA is above RSP at the current position, if tracked.
+    pub(crate) fn set_cfa_offset(&mut self, cfa_offset: Option<i32>) {
+        let offset = self.inner.offset().0 as u32;
+        if let Some(frame) = &mut self.frame {
+            frame.cfa_offset = cfa_offset;
+            frame.assumed &= cfa_offset.is_some();
+            match cfa_offset {
+                Some(cfa_offset) if cfa_offset != frame.unwind_cfa_offset => {
+                    frame.unwind_cfa_offset = cfa_offset;
+                    let op = UnwindOp::CfaOffset(cfa_offset as u32);
+                    match frame.unwind_ops.last_mut() {
+                        Some(last @ (_, UnwindOp::CfaOffset(_))) if last.0 == offset => {
+                            *last = (offset, op)
+                        }
+                        _ => frame.unwind_ops.push((offset, op)),
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Set how many bytes the CFA is assumed to be above RSP at a position that may only be
+    /// reachable through a label that is not yet bound, if tracked.
+    ///
+    /// Binding a label jumped to at a different stack depth overrides the assumption.
+    pub(crate) fn assume_cfa_offset(&mut self, cfa_offset: i32) {
+        self.set_cfa_offset(Some(cfa_offset));
+        if let Some(frame) = &mut self.frame {
+            frame.assumed = true;
+        }
+    }
+
+    /// Record a change of the unwind rules at the current position, if tracked.
+    pub(crate) fn mark_unwind(&mut self, op: UnwindOp) {
+        let offset = self.inner.offset().0 as u32;
+        if let Some(frame) = &mut self.frame {
+            frame.unwind_ops.push((offset, op));
+        }
+    }
+
+    /// Take the unwind table recorded so far, as code offsets and the rule changes taking
+    /// effect there.
+    pub(crate) fn take_unwind_ops(&mut self) -> Vec<(u32, UnwindOp)> {
+        self.frame
+            .as_mut()
+            .map(|frame| std::mem::take(&mut frame.unwind_ops))
+            .unwrap_or_default()
+    }
+
+    fn rebase<T: FrameOperand>(&self, operand: T) -> T {
+        operand.rebase(match &self.frame {
+            Some(frame) => FrameAddressing::StackPointer(frame.cfa_offset),
+            None => FrameAddressing::FramePointer,
+        })
+    }
+
+    /// Move the CFA `delta` bytes further from RSP, if it is known.
+    fn adjust_cfa_offset(&mut self, delta: i32) {
+        let cfa_offset = self.cfa_offset().map(|cfa_offset| cfa_offset + delta);
+        self.set_cfa_offset(cfa_offset);
+    }
+}
+
+/// Forward the methods of [`Emitter`] to the inner assembler, rebasing their operands.
+macro_rules! forward {
+    ($(fn $name:ident(&mut self $(, $arg:ident: $ty:ty)*);)*) => {
+        $(
+            fn $name(&mut self $(, $arg: $ty)*) {
+                $(let $arg = self.rebase($arg);)*
+                self.inner.$name($($arg),*)
+            }
+        )*
+    };
+}
+
+impl Emitter for Assembler {
+    type Label = DynamicLabel;
+    type Offset = AssemblyOffset;
+
+    fn get_label(&mut self) -> DynamicLabel {
+        self.inner.get_label()
+    }
+
+    fn get_offset(&self) -> AssemblyOffset {
+        self.inner.get_offset()
+    }
+
+    fn get_jmp_instr_size(&self) -> u8 {
+        self.inner.get_jmp_instr_size()
+    }
+
+    fn finalize_function(&mut self) {
+        self.inner.finalize_function()
+    }
+
+    fn emit_u64(&mut self, x: u64) {
+        self.inner.emit_u64(x)
+    }
+
+    fn emit_bytes(&mut self, bytes: &[u8]) {
+        self.inner.emit_bytes(bytes)
+    }
+
+    fn emit_label(&mut self, label: DynamicLabel) {
+        if let Some(frame) = &mut self.frame {
+            let assumed = std::mem::replace(&mut frame.assumed, false);
+            match (frame.cfa_offset, frame.labels.get(&label).copied()) {
+                (None, Some(cfa_offset)) => self.set_cfa_offset(Some(cfa_offset)),
+                (Some(_), Some(cfa_offset)) if assumed => self.set_cfa_offset(Some(cfa_offset)),
+                (Some(cfa_offset), Some(expected)) => assert_eq!(
+                    cfa_offset, expected,
+                    "label bound at a different stack depth than jumped to"
+                ),
+                (Some(cfa_offset), None) => {
+                    frame.labels.insert(label, cfa_offset);
+                }
+                (None, None) => {}
+            }
+        }
+        self.inner.emit_label(label)
+    }
+
+    fn emit_jmp(&mut self, condition: Condition, label: DynamicLabel) {
+        if let Some(frame) = &mut self.frame {
+            if let Some(cfa_offset) = frame.cfa_offset {
+                let expected = *frame.labels.entry(label).or_insert(cfa_offset);
+                assert_eq!(
+                    cfa_offset, expected,
+                    "label jumped to at a different stack depth than bound"
+                );
+            }
+        }
+        self.inner.emit_jmp(condition, label);
+        if condition == Condition::None {
+            self.set_cfa_offset(None);
+        }
+    }
+
+    fn emit_jmp_location(&mut self, loc: Location) {
+        let loc = self.rebase(loc);
+        self.inner.emit_jmp_location(loc);
+        self.set_cfa_offset(None);
+    }
+
+    fn emit_ret(&mut self) {
+        self.inner.emit_ret();
+        self.set_cfa_offset(None);
+    }
+
+    fn emit_host_redirection(&mut self, target: GPR) {
+        let target = self.rebase(target);
+        self.inner.emit_host_redirection(target);
+        self.set_cfa_offset(None);
+    }
+
+    fn emit_push(&mut self, sz: Size, src: Location) {
+        let src = self.rebase(src);
+        self.inner.emit_push(sz, src);
+        self.adjust_cfa_offset(8);
+    }
+
+    fn emit_pop(&mut self, sz: Size, dst: Location) {
+        // The address of a memory destination is computed after RSP is incremented.
+        let cfa_offset = self.cfa_offset().map(|cfa_offset| cfa_offset - 8);
+        let dst = match &self.frame {
+            Some(_) => dst.rebase(FrameAddressing::StackPointer(cfa_offset)),
+            None => dst.rebase(FrameAddressing::FramePointer),
+        };
+        self.inner.emit_pop(sz, dst);
+        self.set_cfa_offset(cfa_offset);
+    }
+
+    fn emit_add(&mut self, sz: Size, src: Location, dst: Location) {
+        let (src, dst) = (self.rebase(src), self.rebase(dst));
+        self.inner.emit_add(sz, src, dst);
+        if dst == Location::GPR(GPR::RSP) {
+            match src {
+                Location::Imm32(delta) => self.adjust_cfa_offset(-(delta as i32)),
+                _ => self.set_cfa_offset(None),
+            }
+        }
+    }
+
+    fn emit_sub(&mut self, sz: Size, src: Location, dst: Location) {
+        let (src, dst) = (self.rebase(src), self.rebase(dst));
+        self.inner.emit_sub(sz, src, dst);
+        if dst == Location::GPR(GPR::RSP) {
+            match src {
+                Location::Imm32(delta) => self.adjust_cfa_offset(delta as i32),
+                _ => self.set_cfa_offset(None),
+            }
+        }
+    }
+
+    fn emit_lea(&mut self, sz: Size, src: Location, dst: Location) {
+        let (src, dst) = (self.rebase(src), self.rebase(dst));
+        self.inner.emit_lea(sz, src, dst);
+        if dst == Location::GPR(GPR::RSP) {
+            match src {
+                Location::Memory(GPR::RSP, disp) => self.adjust_cfa_offset(-disp),
+                _ => self.set_cfa_offset(None),
+            }
+        }
+    }
+
+    fn emit_mov(&mut self, sz: Size, src: Location, dst: Location) {
+        let (src, dst) = (self.rebase(src), self.rebase(dst));
+        self.inner.emit_mov(sz, src, dst);
+        if dst == Location::GPR(GPR::RSP) {
+            self.set_cfa_offset(None);
+        }
+    }
+
+    fn emit_and(&mut self, sz: Size, src: Location, dst: Location) {
+        let (src, dst) = (self.rebase(src), self.rebase(dst));
+        self.inner.emit_and(sz, src, dst);
+        if dst == Location::GPR(GPR::RSP) {
+            self.set_cfa_offset(None);
+        }
+    }
+
+    fn arch_mov64_imm_offset(&self) -> usize {
+        self.inner.arch_mov64_imm_offset()
+    }
+
+    forward! {
+        fn emit_nop(&mut self);
+        fn emit_nop_n(&mut self, n: usize);
+        fn emit_lea_label(&mut self, label: DynamicLabel, dst: Location);
+        fn emit_cdq(&mut self);
+        fn emit_cqo(&mut self);
+        fn emit_xor(&mut self, sz: Size, src: Location, dst: Location);
+        fn emit_set(&mut self, condition: Condition, dst: GPR);
+        fn emit_cmp(&mut self, sz: Size, left: Location, right: Location);
+        fn emit_neg(&mut self, sz: Size, value: Location);
+        fn emit_imul(&mut self, sz: Size, src: Location, dst: Location);
+        fn emit_imul_imm32_gpr64(&mut self, src: u32, dst: GPR);
+        fn emit_div(&mut self, sz: Size, divisor: Location);
+        fn emit_idiv(&mut self, sz: Size, divisor: Location);
+        fn emit_shl(&mut self, sz: Size, src: Location, dst: Location);
+        fn emit_shr(&mut self, sz: Size, src: Location, dst: Location);
+        fn emit_sar(&mut self, sz: Size, src: Location, dst: Location);
+        fn emit_rol(&mut self, sz: Size, src: Location, dst: Location);
+        fn emit_ror(&mut self, sz: Size, src: Location, dst: Location);
+        fn emit_or(&mut self, sz: Size, src: Location, dst: Location);
+        fn emit_bsr(&mut self, sz: Size, src: Location, dst: Location);
+        fn emit_bsf(&mut self, sz: Size, src: Location, dst: Location);
+        fn emit_popcnt(&mut self, sz: Size, src: Location, dst: Location);
+        fn emit_movzx(&mut self, sz_src: Size, src: Location, sz_dst: Size, dst: Location);
+        fn emit_movsx(&mut self, sz_src: Size, src: Location, sz_dst: Size, dst: Location);
+        fn emit_xchg(&mut self, sz: Size, src: Location, dst: Location);
+        fn emit_lock_xadd(&mut self, sz: Size, src: Location, dst: Location);
+        fn emit_lock_cmpxchg(&mut self, sz: Size, src: Location, dst: Location);
+        fn emit_rep_stosq(&mut self);
+        fn emit_btc_gpr_imm8_32(&mut self, src: u8, dst: GPR);
+        fn emit_btc_gpr_imm8_64(&mut self, src: u8, dst: GPR);
+        fn emit_cmovae_gpr_32(&mut self, src: GPR, dst: GPR);
+        fn emit_cmovae_gpr_64(&mut self, src: GPR, dst: GPR);
+        fn emit_vmovaps(&mut self, src: XMMOrMemory, dst: XMMOrMemory);
+        fn emit_vmovapd(&mut self, src: XMMOrMemory, dst: XMMOrMemory);
+        fn emit_vxorps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vxorpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vaddss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vaddsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vsubss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vsubsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vmulss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vmulsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vdivss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vdivsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vmaxss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vmaxsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vminss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vminsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpeqss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpeqsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpneqss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpneqsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpltss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpltsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpless(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmplesd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpgtss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpgtsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpgess(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpgesd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpunordss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpunordsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpordss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcmpordsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vsqrtss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vsqrtsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vroundss_nearest(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vroundss_floor(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vroundss_ceil(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vroundss_trunc(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vroundsd_nearest(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vroundsd_floor(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vroundsd_ceil(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vroundsd_trunc(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcvtss2sd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_vcvtsd2ss(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+        fn emit_ucomiss(&mut self, src: XMMOrMemory, dst: XMM);
+        fn emit_ucomisd(&mut self, src: XMMOrMemory, dst: XMM);
+        fn emit_cvttss2si_32(&mut self, src: XMMOrMemory, dst: GPR);
+        fn emit_cvttss2si_64(&mut self, src: XMMOrMemory, dst: GPR);
+        fn emit_cvttsd2si_32(&mut self, src: XMMOrMemory, dst: GPR);
+        fn emit_cvttsd2si_64(&mut self, src: XMMOrMemory, dst: GPR);
+        fn emit_vcvtsi2ss_32(&mut self, src1: XMM, src2: GPROrMemory, dst: XMM);
+        fn emit_vcvtsi2ss_64(&mut self, src1: XMM, src2: GPROrMemory, dst: XMM);
+        fn emit_vcvtsi2sd_32(&mut self, src1: XMM, src2: GPROrMemory, dst: XMM);
+        fn emit_vcvtsi2sd_64(&mut self, src1: XMM, src2: GPROrMemory, dst: XMM);
+        fn emit_vblendvps(&mut self, src1: XMM, src2: XMMOrMemory, mask: XMM, dst: XMM);
+        fn emit_vblendvpd(&mut self, src1: XMM, src2: XMMOrMemory, mask: XMM, dst: XMM);
+        fn emit_test_gpr_64(&mut self, reg: GPR);
+        fn emit_ud2(&mut self);
+        fn emit_call_label(&mut self, label: DynamicLabel);
+        fn emit_call_location(&mut self, loc: Location);
+        fn emit_call_register(&mut self, reg: GPR);
+        fn emit_bkpt(&mut self);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Assemble a `lea` of `src` into RAX, without a frame pointer unless `cfa_offset` is none.
+    fn lea_rax(src: Location, cfa_offset: Option<i32>) -> Vec<u8> {
+        let mut assembler = match cfa_offset {
+            Some(cfa_offset) => {
+                let mut assembler = Assembler::without_frame_pointer(0);
+                assembler.set_cfa_offset(Some(cfa_offset));
+                assembler
+            }
+            None => Assembler::new(0),
+        };
+        assembler.emit_lea(Size::S64, src, Location::GPR(GPR::RAX));
+        assembler.finalize().unwrap()
+    }
+
+    #[test]
+    fn test_rebase_frame_slots() {
+        let slot = Location::Memory(GPR::FrameBase, -8);
+        assert_eq!(
+            lea_rax(slot, None),
+            lea_rax(Location::Memory(GPR::RBP, -8), None)
+        );
+        assert_eq!(
+            lea_rax(slot, Some(40)),
+            lea_rax(Location::Memory(GPR::RSP, 16), None)
+        );
+    }
+
+    #[test]
+    fn test_rebase_keeps_rbp_operands() {
+        // Without a frame pointer, RBP may hold any value, such as the address of a table.
+        let operand = Location::Memory(GPR::RBP, 8);
+        assert_eq!(lea_rax(operand, Some(40)), lea_rax(operand, None));
+        let operand = Location::MemoryAddTriple(GPR::RBP, GPR::RCX, 8);
+        assert_eq!(lea_rax(operand, Some(40)), lea_rax(operand, None));
+    }
+}
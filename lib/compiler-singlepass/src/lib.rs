@@ -14,6 +14,7 @@ mod compiler;
 mod config;
 mod emitter_x64;
 mod machine;
+mod unwind;
 mod x64_decl;
 
 pub use crate::compiler::SinglepassCompiler;
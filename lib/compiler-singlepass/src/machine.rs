@@ -18,16 +18,19 @@ pub(crate) struct Machine {
     ///
     /// Populated in `init_locals`.
     locals_offset: MachineStackOffset,
+    /// Whether RBP is reserved for the frame pointer.
+    frame_pointer: bool,
 }
 
 impl Machine {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(frame_pointer: bool) -> Self {
         Machine {
             used_gprs: HashSet::new(),
             used_xmms: HashSet::new(),
             stack_offset: MachineStackOffset(0),
             save_area_offset: None,
             locals_offset: MachineStackOffset(0),
+            frame_pointer,
         }
     }
 
@@ -57,7 +60,9 @@ impl Machine {
     pub(crate) fn pick_gpr(&self) -> Option<GPR> {
         use GPR::*;
         static REGS: &[GPR] = &[RSI, RDI, R8, R9, R10, R11];
-        for r in REGS {
+        // Without a frame pointer, RBP is available too.
+        let rbp: &[GPR] = if self.frame_pointer { &[] } else { &[RBP] };
+        for r in REGS.iter().chain(rbp) {
             if !self.used_gprs.contains(r) {
                 return Some(*r);
             }
@@ -168,7 +173,7 @@ impl Machine {
             } else {
                 self.stack_offset.0 += 8;
                 delta_stack_offset += 8;
-                Location::Memory(GPR::RBP, -(self.stack_offset.0 as i32))
+                Location::Memory(GPR::FrameBase, -(self.stack_offset.0 as i32))
             };
             if let Location::GPR(x) = loc {
                 self.used_gprs.insert(x);
@@ -205,7 +210,7 @@ impl Machine {
                 Location::XMM(ref x) => {
                     assert_eq!(self.used_xmms.remove(x), true);
                 }
-                Location::Memory(GPR::RBP, x) => {
+                Location::Memory(GPR::FrameBase, x) => {
                     if x >= 0 {
                         unreachable!();
                     }
@@ -251,7 +256,7 @@ impl Machine {
         let mut delta_stack_offset: usize = 0;
 
         for loc in locs.iter().rev() {
-            if let Location::Memory(GPR::RBP, x) = *loc {
+            if let Location::Memory(GPR::FrameBase, x) = *loc {
                 if x >= 0 {
                     unreachable!();
                 }
@@ -282,7 +287,7 @@ impl Machine {
         let mut stack_offset = self.stack_offset.0;
 
         for loc in locs.iter().rev() {
-            if let Location::Memory(GPR::RBP, x) = *loc {
+            if let Location::Memory(GPR::FrameBase, x) = *loc {
                 if x >= 0 {
                     unreachable!();
                 }
@@ -324,7 +329,7 @@ impl Machine {
                     .unwrap()
                     .wrapping_mul(8);
                 Location::Memory(
-                    GPR::RBP,
+                    GPR::FrameBase,
                     (local_offset.wrapping_add(self.locals_offset.0 as u32) as i32).wrapping_neg(),
                 )
             })
@@ -371,7 +376,7 @@ impl Machine {
             a.emit_mov(
                 Size::S64,
                 Location::GPR(*local_reg),
-                Location::Memory(GPR::RBP, -(self.stack_offset.0 as i32)),
+                Location::Memory(GPR::FrameBase, -(self.stack_offset.0 as i32)),
             );
         }
 
@@ -380,7 +385,7 @@ impl Machine {
         a.emit_mov(
             Size::S64,
             Location::GPR(GPR::R15),
-            Location::Memory(GPR::RBP, -(self.stack_offset.0 as i32)),
+            Location::Memory(GPR::FrameBase, -(self.stack_offset.0 as i32)),
         );
 
         if calling_convention == CallingConvention::WindowsFastcall {
@@ -389,7 +394,7 @@ impl Machine {
                 a.emit_mov(
                     Size::S64,
                     Location::GPR(reg),
-                    Location::Memory(GPR::RBP, -(self.stack_offset.0 as i32)),
+                    Location::Memory(GPR::FrameBase, -(self.stack_offset.0 as i32)),
                 );
             }
         }
@@ -483,7 +488,7 @@ impl Machine {
         a.emit_lea(
             Size::S64,
             Location::Memory(
-                GPR::RBP,
+                GPR::FrameBase,
                 -(self.save_area_offset.as_ref().unwrap().0 as i32),
             ),
             Location::GPR(GPR::RSP),
@@ -517,7 +522,7 @@ impl Machine {
                 1 => Location::GPR(GPR::RDX),
                 2 => Location::GPR(GPR::R8),
                 3 => Location::GPR(GPR::R9),
-                _ => Location::Memory(GPR::RBP, (16 + 32 + (idx - 4) * 8) as i32),
+                _ => Location::Memory(GPR::FrameBase, (16 + 32 + (idx - 4) * 8) as i32),
             },
             _ => match idx {
                 0 => Location::GPR(GPR::RDI),
@@ -526,7 +531,7 @@ impl Machine {
                 3 => Location::GPR(GPR::RCX),
                 4 => Location::GPR(GPR::R8),
                 5 => Location::GPR(GPR::R9),
-                _ => Location::Memory(GPR::RBP, (16 + (idx - 6) * 8) as i32),
+                _ => Location::Memory(GPR::FrameBase, (16 + (idx - 6) * 8) as i32),
             },
         }
     }
@@ -535,13 +540,11 @@ impl Machine {
 #[cfg(test)]
 mod test {
     use super::*;
-    use dynasmrt::x64::X64Relocation;
-    use dynasmrt::VecAssembler;
-    type Assembler = VecAssembler<X64Relocation>;
+    use crate::emitter_x64::Assembler;
 
     #[test]
     fn test_release_locations_keep_state_nopanic() {
-        let mut machine = Machine::new();
+        let mut machine = Machine::new(true);
         let mut assembler = Assembler::new(0);
         let locs = machine.acquire_locations(
             &mut assembler,
@@ -551,4 +554,15 @@ mod test {
 
         machine.release_locations_keep_state(&mut assembler, &locs);
     }
+
+    #[test]
+    fn test_pick_gpr_frame_pointer() {
+        use GPR::*;
+        for frame_pointer in [true, false] {
+            let mut machine = Machine::new(frame_pointer);
+            machine.used_gprs.extend(&[RSI, RDI, R8, R9, R10, R11]);
+            let expected = if frame_pointer { None } else { Some(RBP) };
+            assert_eq!(machine.pick_gpr(), expected);
+        }
+    }
 }
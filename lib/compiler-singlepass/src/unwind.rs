@@ -0,0 +1,213 @@
+//! Unwind tables of the functions compiled without a frame pointer.
+//!
+//! Without a frame pointer, unwinders can no longer walk the chain of saved RBP values, so the
+//! position of the return address is described by a `.eh_frame` section instead, registered by
+//! the engine when the module is loaded.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use wasmer_compiler::{
+    CustomSection, CustomSectionProtection, Relocation, RelocationKind, RelocationTarget,
+    SectionBody,
+};
+use wasmer_types::LocalFunctionIndex;
+
+/// A change of the unwind rules of a function, taking effect at a code offset.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum UnwindOp {
+    /// The canonical frame address (CFA, the value of RSP before the call) is this many bytes
+    /// above RSP.
+    CfaOffset(u32),
+    /// The caller's RBP is saved 16 bytes below the CFA.
+    SaveFramePointer,
+    /// RBP holds the caller's value again.
+    RestoreFramePointer,
+}
+
+const DW_CFA_ADVANCE_LOC: u8 = 0x40;
+const DW_CFA_OFFSET: u8 = 0x80;
+const DW_CFA_RESTORE: u8 = 0xc0;
+const DW_CFA_NOP: u8 = 0x00;
+const DW_CFA_ADVANCE_LOC1: u8 = 0x02;
+const DW_CFA_ADVANCE_LOC2: u8 = 0x03;
+const DW_CFA_ADVANCE_LOC4: u8 = 0x04;
+const DW_CFA_DEF_CFA: u8 = 0x0c;
+const DW_CFA_DEF_CFA_OFFSET: u8 = 0x0e;
+const DW_EH_PE_ABSPTR: u8 = 0x00;
+
+/// DWARF numbers of the x86-64 registers.
+const DW_REG_RBP: u8 = 6;
+const DW_REG_RSP: u8 = 7;
+const DW_REG_RA: u8 = 16;
+
+/// The data alignment factor, by which the offsets of saved registers are multiplied.
+const DATA_ALIGNMENT: i64 = -8;
+
+/// Build the `.eh_frame` section describing `functions`, as their index, code length and
+/// unwind operations.
+///
+/// The section has a single CIE, describing the state at function entry, and an FDE per
+/// function, whose start address is relocated against the function. It ends with a zero
+/// terminator, as expected by `__register_frame` on libgcc.
+pub(crate) fn create_eh_frame<'a>(
+    functions: impl Iterator<Item = (LocalFunctionIndex, usize, &'a [(u32, UnwindOp)])>,
+) -> CustomSection {
+    let mut bytes = Vec::new();
+    let mut relocations = Vec::new();
+
+    let cie_offset = bytes.len();
+    write_entry(&mut bytes, |bytes| {
+        // CIE id, version and augmentation.
+        bytes.write_u32::<LittleEndian>(0).unwrap();
+        bytes.push(1);
+        bytes.extend_from_slice(b"zR\0");
+        write_uleb128(bytes, 1);
+        write_sleb128(bytes, DATA_ALIGNMENT);
+        write_uleb128(bytes, DW_REG_RA.into());
+        // Augmentation data: the encoding of the FDE addresses.
+        write_uleb128(bytes, 1);
+        bytes.push(DW_EH_PE_ABSPTR);
+        // At entry, the return address is at the top of the stack.
+        bytes.push(DW_CFA_DEF_CFA);
+        write_uleb128(bytes, DW_REG_RSP.into());
+        write_uleb128(bytes, 8);
+        bytes.push(DW_CFA_OFFSET | DW_REG_RA);
+        write_uleb128(bytes, 1);
+    });
+
+    for (index, len, ops) in functions {
+        let fde_offset = bytes.len();
+        write_entry(&mut bytes, |bytes| {
+            // The CIE pointer is relative to its own position, right after the length.
+            let cie_pointer = fde_offset + 4 - cie_offset;
+            bytes.write_u32::<LittleEndian>(cie_pointer as u32).unwrap();
+            relocations.push(Relocation {
+                kind: RelocationKind::Abs8,
+                reloc_target: RelocationTarget::LocalFunc(index),
+                offset: (fde_offset + 4 + bytes.len()) as u32,
+                addend: 0,
+            });
+            bytes.write_u64::<LittleEndian>(0).unwrap();
+            bytes.write_u64::<LittleEndian>(len as u64).unwrap();
+            // No augmentation data.
+            write_uleb128(bytes, 0);
+            write_instructions(bytes, ops);
+        });
+    }
+
+    bytes.write_u32::<LittleEndian>(0).unwrap();
+    CustomSection {
+        protection: CustomSectionProtection::Read,
+        bytes: SectionBody::new_with_vec(bytes),
+        relocations,
+    }
+}
+
+/// Write an entry of the section, prefixed by its length and padded to the pointer size.
+fn write_entry(bytes: &mut Vec<u8>, contents: impl FnOnce(&mut Vec<u8>)) {
+    let mut entry = Vec::new();
+    contents(&mut entry);
+    while (entry.len() + 4) % 8 != 0 {
+        entry.push(DW_CFA_NOP);
+    }
+    bytes.write_u32::<LittleEndian>(entry.len() as u32).unwrap();
+    bytes.extend_from_slice(&entry);
+}
+
+fn write_instructions(bytes: &mut Vec<u8>, ops: &[(u32, UnwindOp)]) {
+    let mut location = 0;
+    for &(offset, op) in ops {
+        let delta = offset - location;
+        location = offset;
+        if delta < 0x40 {
+            if delta > 0 {
+                bytes.push(DW_CFA_ADVANCE_LOC | delta as u8);
+            }
+        } else if delta <= u8::MAX.into() {
+            bytes.push(DW_CFA_ADVANCE_LOC1);
+            bytes.push(delta as u8);
+        } else if delta <= u16::MAX.into() {
+            bytes.push(DW_CFA_ADVANCE_LOC2);
+            bytes.write_u16::<LittleEndian>(delta as u16).unwrap();
+        } else {
+            bytes.push(DW_CFA_ADVANCE_LOC4);
+            bytes.write_u32::<LittleEndian>(delta).unwrap();
+        }
+        match op {
+            UnwindOp::CfaOffset(cfa_offset) => {
+                bytes.push(DW_CFA_DEF_CFA_OFFSET);
+                write_uleb128(bytes, cfa_offset.into());
+            }
+            UnwindOp::SaveFramePointer => {
+                bytes.push(DW_CFA_OFFSET | DW_REG_RBP);
+                write_uleb128(bytes, (-16 / DATA_ALIGNMENT) as u64);
+            }
+            UnwindOp::RestoreFramePointer => bytes.push(DW_CFA_RESTORE | DW_REG_RBP),
+        }
+    }
+}
+
+fn write_uleb128(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+fn write_sleb128(bytes: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            bytes.push(byte);
+            return;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eh_frame_layout() {
+        let ops = [
+            (1, UnwindOp::CfaOffset(16)),
+            (1, UnwindOp::SaveFramePointer),
+            (0x1234, UnwindOp::CfaOffset(0x90)),
+        ];
+        let section = create_eh_frame(std::iter::once((
+            LocalFunctionIndex::from_u32(3),
+            0x2000,
+            &ops[..],
+        )));
+        let bytes = section.bytes.as_slice();
+
+        // CIE, then the FDE pointing back at it, then the terminator.
+        let cie_len = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+        assert_eq!((cie_len + 4) % 8, 0);
+        let fde = &bytes[cie_len + 4..];
+        let fde_len = u32::from_le_bytes([fde[0], fde[1], fde[2], fde[3]]) as usize;
+        assert_eq!(&fde[4..8], &((cie_len + 8) as u32).to_le_bytes());
+        assert_eq!(&fde[16..24], &0x2000u64.to_le_bytes());
+        assert_eq!(
+            &fde[24..33],
+            &[0, 0x41, 0x0e, 16, 0x86, 2, 0x03, 0x33, 0x12][..]
+        );
+        assert_eq!(&fde[33..36], &[0x0e, 0x90, 0x01]);
+        assert_eq!(&fde[fde_len + 4..], &[0, 0, 0, 0]);
+
+        assert_eq!(section.relocations.len(), 1);
+        let relocation = &section.relocations[0];
+        assert_eq!(relocation.offset as usize, cie_len + 4 + 8);
+        assert_eq!(
+            relocation.reloc_target,
+            RelocationTarget::LocalFunc(LocalFunctionIndex::from_u32(3))
+        );
+    }
+}
@@ -38,6 +38,11 @@ pub(crate) enum GPR {
     R14,
     /// R15 register
     R15,
+    /// The base of the frame of the function, which is not a register of its own.
+    ///
+    /// Frame slots are addressed relative to it, and the assembler rewrites their addresses to
+    /// be relative to RBP with a frame pointer, or to RSP without.
+    FrameBase,
 }
 
 /// XMM registers.
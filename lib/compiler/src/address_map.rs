@@ -6,6 +6,7 @@ use crate::sourceloc::SourceLoc;
 
 /// Single source location to generated address mapping.
 #[derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Debug, Clone, PartialEq, Eq)]
+#[archive(check_bytes)]
 pub struct InstructionAddressMap {
     /// Original source location.
     pub srcloc: SourceLoc,
@@ -21,6 +22,7 @@ pub struct InstructionAddressMap {
 #[derive(
     rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Debug, Clone, PartialEq, Eq, Default,
 )]
+#[archive(check_bytes)]
 pub struct FunctionAddressMap {
     /// Instructions maps.
     /// The array is sorted by the InstructionAddressMap::code_offset field.
@@ -2,15 +2,22 @@
 //! compilers will need to implement.
 
 use crate::error::CompileError;
-use crate::function::Compilation;
+use crate::function::{Compilation, CompiledFunction, FunctionBody};
 use crate::lib::std::boxed::Box;
+use crate::lib::std::string::{String, ToString};
+use crate::lib::std::vec::Vec;
+use crate::metering::{OperatorCosts, ScheduleVersion};
 use crate::module::CompileModuleInfo;
 use crate::target::Target;
+use crate::translator::disabled_features_error;
 use crate::FunctionBodyData;
 use crate::ModuleTranslationState;
 use crate::SectionIndex;
-use wasmer_types::entity::PrimaryMap;
-use wasmer_types::{Features, FunctionIndex, LocalFunctionIndex, SignatureIndex};
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{
+    Features, FunctionIndex, FunctionType, LocalFunctionIndex, ModuleInfo, SignatureIndex,
+};
+use wasmer_vm::TrapCode;
 use wasmparser::{Validator, WasmFeatures};
 
 /// The compiler configuration options.
@@ -53,6 +60,56 @@ pub trait CompilerConfig {
         // in case they create an IR that they can verify.
     }
 
+    /// Compile the functions of a module on several threads.
+    ///
+    /// Compilers doing so produce the same output either way, in the same order, so that
+    /// disabling it only trades compilation time for running on the calling thread alone.
+    fn enable_parallel_compilation(&mut self, _enable: bool) {
+        // By default we do nothing, each backend compiling in parallel will need to
+        // customize this.
+    }
+
+    /// Fail to compile functions whose body is larger than `limit` bytes of WebAssembly,
+    /// with [`CompileError::CodeTooLarge`], before compiling any of them.
+    ///
+    /// This bounds the time compiling a single function takes. Bodies of any size are
+    /// compiled by default.
+    fn set_max_function_body_size(&mut self, _limit: usize) {
+        // By default we do nothing, each backend will need to customize this.
+    }
+
+    /// Charge the gas counter of the instances for the code they run, with the cost of each
+    /// operator in `costs`.
+    ///
+    /// The cost of every basic block, see [`OperatorCosts::basic_blocks`], is added to the
+    /// burnt gas when the block starts, and the code traps with `GasExceeded` once the burnt
+    /// gas reaches the limit of the counter. Code is not metered by default.
+    fn set_metering(&mut self, _costs: OperatorCosts) {
+        // By default we do nothing, each backend will need to customize this.
+    }
+
+    /// Make the cost tables of `schedules` available to meter modules with, each under its
+    /// version.
+    ///
+    /// Modules compiled with a [`ScheduleVersion`] in their
+    /// [`CompileModuleInfo::metering_schedule`] are metered with the costs of that schedule
+    /// instead of those given to [`CompilerConfig::set_metering`], so that a single compiler
+    /// keeps compiling every module with the costs it was deployed with. The version is part
+    /// of the provenance of the executables.
+    fn set_metering_schedules(&mut self, _schedules: Vec<(ScheduleVersion, OperatorCosts)>) {
+        // By default we do nothing, each backend will need to customize this.
+    }
+
+    /// Drop the imported functions the code of a module never uses, see [`UnusedItems`](crate::UnusedItems), from
+    /// the imports its instances must be given.
+    ///
+    /// The module still declares them, but instantiating it no longer fails when the resolver
+    /// does not provide them. The setting is recorded in the provenance of the executables,
+    /// and so in their identifier. Imports are kept by default.
+    fn strip_unused_imports(&mut self, _enable: bool) {
+        // By default we do nothing, each backend will need to customize this.
+    }
+
     /// Gets the custom compiler config
     fn compiler(self: Box<Self>) -> Box<dyn Compiler>;
 
@@ -71,8 +128,57 @@ where
     }
 }
 
+/// What a compiler records about itself in the provenance of the executables it compiles.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompilerProvenance {
+    /// The name of the compiler.
+    pub name: String,
+    /// The version of the compiler.
+    pub version: String,
+    /// The settings changing the code the compiler generates, as `name=value`.
+    pub flags: Vec<String>,
+    /// The passes instrumenting the generated code, such as gas metering, in the order they
+    /// apply.
+    pub middlewares: Vec<String>,
+}
+
 /// An implementation of a Compiler from parsed WebAssembly module to Compiled native code.
 pub trait Compiler: Send {
+    /// Describes this compiler and its configuration, to be recorded with the executables it
+    /// compiles.
+    fn provenance(&self) -> CompilerProvenance {
+        CompilerProvenance::default()
+    }
+
+    /// Describes this compiler compiling with the metering schedule `version`, see
+    /// [`CompilerConfig::set_metering_schedules`].
+    ///
+    /// Fails if the compiler has no schedule of that version.
+    fn schedule_provenance(
+        &self,
+        version: ScheduleVersion,
+    ) -> Result<CompilerProvenance, CompileError> {
+        Err(CompileError::UnsupportedFeature(format!(
+            "metering schedule {}",
+            version
+        )))
+    }
+
+    /// Whether the executables this compiler compiles drop the unused imported functions from
+    /// the imports their instances must be given, see [`CompilerConfig::strip_unused_imports`].
+    fn strips_unused_imports(&self) -> bool {
+        false
+    }
+
+    /// The imported functions of `module` whose calls this compiler compiles into raising a
+    /// trap, with the code of the trap.
+    ///
+    /// Instances of the module need not be given these functions: those missing from their
+    /// imports raise the trap as well when called through a table or an export.
+    fn trap_imports(&self, _module: &ModuleInfo) -> Vec<(FunctionIndex, TrapCode)> {
+        Vec::new()
+    }
+
     /// Validates a module.
     ///
     /// It returns the a succesful Result in case is valid, `CompileError` in case is not.
@@ -82,23 +188,11 @@ pub trait Compiler: Send {
         data: &'data [u8],
     ) -> Result<(), CompileError> {
         let mut validator = Validator::new();
-        let wasm_features = WasmFeatures {
-            bulk_memory: features.bulk_memory,
-            threads: features.threads,
-            reference_types: features.reference_types,
-            multi_value: features.multi_value,
-            simd: features.simd,
-            tail_call: features.tail_call,
-            module_linking: features.module_linking,
-            multi_memory: features.multi_memory,
-            memory64: features.memory64,
-            exceptions: features.exceptions,
-            deterministic_only: false,
-        };
-        validator.wasm_features(wasm_features);
-        validator
-            .validate_all(data)
-            .map_err(|e| CompileError::Validate(format!("{}", e)))?;
+        validator.wasm_features(wasm_features(features));
+        validator.validate_all(data).map_err(|e| {
+            disabled_features_error(features, data, &e, true)
+                .unwrap_or_else(|| CompileError::Validate(format!("{}", e)))
+        })?;
         Ok(())
     }
 
@@ -114,6 +208,41 @@ pub trait Compiler: Send {
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
     ) -> Result<Compilation, CompileError>;
 
+    /// Compiles a parsed module as [`Compiler::compile_module`] does, handing each function
+    /// to `sink` as soon as it is compiled.
+    ///
+    /// The functions are handed over exactly as they end up in the [`Compilation`], possibly
+    /// from several threads at once and in any order. By default, they are handed over in
+    /// order once all of them are compiled.
+    fn compile_module_streaming<'data, 'module>(
+        &self,
+        target: &Target,
+        module: &'module CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+        sink: &(dyn Fn(LocalFunctionIndex, &CompiledFunction) + Sync),
+    ) -> Result<Compilation, CompileError> {
+        let compilation =
+            self.compile_module(target, module, module_translation, function_body_inputs)?;
+        for index in 0..compilation.len() {
+            let index = LocalFunctionIndex::new(index);
+            sink(index, compilation.get(index));
+        }
+        Ok(compilation)
+    }
+
+    /// Compiles the trampolines calling functions of the given signatures from the host, as
+    /// [`Compiler::compile_module`] does for the signatures of a module, in the same order.
+    fn compile_function_call_trampolines(
+        &self,
+        _target: &Target,
+        _signatures: &[FunctionType],
+    ) -> Result<Vec<FunctionBody>, CompileError> {
+        Err(CompileError::UnsupportedFeature(
+            "standalone trampolines".to_string(),
+        ))
+    }
+
     /// Compiles a module into a native object file.
     ///
     /// It returns the bytes as a `&[u8]` or a [`CompileError`].
@@ -158,3 +287,20 @@ pub trait SymbolRegistry: Send + Sync {
     /// This function is the inverse of [`SymbolRegistry::symbol_to_name`]
     fn name_to_symbol(&self, name: &str) -> Option<Symbol>;
 }
+
+/// The features of wasmparser matching `features`, to validate modules with.
+pub(crate) fn wasm_features(features: &Features) -> WasmFeatures {
+    WasmFeatures {
+        bulk_memory: features.bulk_memory,
+        threads: features.threads,
+        reference_types: features.reference_types,
+        multi_value: features.multi_value,
+        simd: features.simd,
+        tail_call: features.tail_call,
+        module_linking: features.module_linking,
+        multi_memory: features.multi_memory,
+        memory64: features.memory64,
+        exceptions: features.exceptions,
+        deterministic_only: false,
+    }
+}
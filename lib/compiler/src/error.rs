@@ -1,6 +1,13 @@
+use crate::lib::std::fmt;
 use crate::lib::std::string::String;
+use crate::lib::std::vec::Vec;
 #[cfg(feature = "std")]
 use thiserror::Error;
+use wasmer_types::entity::EntityRef;
+use wasmer_types::{
+    Classify, ErrorCode, FailureKind, HasErrorCode, LocalFunctionIndex, QuotaDimension, TenantId,
+    WasmFeature,
+};
 
 // Compilation Errors
 //
@@ -21,32 +28,274 @@ pub enum CompileError {
     Wasm(WasmError),
 
     /// A compilation error occured.
-    #[cfg_attr(feature = "std", error("Compilation error: {0}"))]
+    #[cfg_attr(
+        feature = "std",
+        error("Compilation error: {0} [{}]", ErrorCode::CompileCodegen)
+    )]
     Codegen(String),
 
     /// The module did not pass validation.
-    #[cfg_attr(feature = "std", error("Validation error: {0}"))]
+    #[cfg_attr(
+        feature = "std",
+        error("Validation error: {0} [{}]", ErrorCode::CompileValidate)
+    )]
     Validate(String),
 
     /// The compiler doesn't support a Wasm feature
-    #[cfg_attr(feature = "std", error("Feature {0} is not yet supported"))]
+    #[cfg_attr(
+        feature = "std",
+        error(
+            "Feature {0} is not yet supported [{}]",
+            ErrorCode::CompileUnsupportedFeature
+        )
+    )]
     UnsupportedFeature(String),
 
     /// The compiler cannot compile for the given target.
     /// This can refer to the OS, the chipset or any other aspect of the target system.
-    #[cfg_attr(feature = "std", error("The target {0} is not yet supported (see https://docs.wasmer.io/ecosystem/wasmer/wasmer-features)"))]
+    #[cfg_attr(feature = "std", error("The target {0} is not yet supported (see https://docs.wasmer.io/ecosystem/wasmer/wasmer-features) [{}]", ErrorCode::CompileUnsupportedTarget))]
     UnsupportedTarget(String),
 
     /// Insufficient resources available for execution.
-    #[cfg_attr(feature = "std", error("Insufficient resources: {0}"))]
+    #[cfg_attr(
+        feature = "std",
+        error("Insufficient resources: {0} [{}]", ErrorCode::CompileResource)
+    )]
     Resource(String),
 
     /// Cannot downcast the engine to a specific type.
     #[cfg_attr(
         feature = "std",
-        error("cannot downcast the engine to a specific type")
+        error(
+            "cannot downcast the engine to a specific type [{}]",
+            ErrorCode::CompileEngineDowncast
+        )
     )]
     EngineDowncast,
+
+    /// The module cannot be specialized with the given bindings.
+    #[cfg_attr(
+        feature = "std",
+        error("Specialization error: {0} [{}]", ErrorCode::CompileSpecialization)
+    )]
+    Specialization(String),
+
+    /// There is no code memory left for the module, even after releasing the code of
+    /// dropped modules.
+    #[cfg_attr(
+        feature = "std",
+        error(
+            "out of code memory: {requested} bytes requested, {in_use} bytes in use, largest free block of {largest_free_block} bytes [{}]",
+            ErrorCode::CompileCodeMemoryExhausted
+        )
+    )]
+    CodeMemoryExhausted {
+        /// The number of bytes the module needs.
+        requested: usize,
+        /// The number of bytes still used by loaded modules.
+        in_use: usize,
+        /// The largest allocation that could have succeeded, zero if the operating system
+        /// refused to map more memory.
+        largest_free_block: usize,
+    },
+
+    /// Compiling or loading the module would exceed the quota of the tenant it is for.
+    #[cfg_attr(
+        feature = "std",
+        error(
+            "{tenant} is over its quota of {limit} {dimension}: {used} would be used [{}]",
+            ErrorCode::CompileQuotaExceeded
+        )
+    )]
+    QuotaExceeded {
+        /// The tenant the module is for.
+        tenant: TenantId,
+        /// The resource the quota limits.
+        dimension: QuotaDimension,
+        /// The most of the resource the tenant may use.
+        limit: u64,
+        /// How much of the resource the tenant would use.
+        used: u64,
+    },
+
+    /// The process compiling the module on our behalf failed.
+    #[cfg_attr(feature = "std", error("Compilation subprocess failed: {0}"))]
+    Subprocess(SubprocessError),
+
+    /// The module uses a proposal the engine does not enable.
+    #[cfg_attr(
+        feature = "std",
+        error(
+            "the {construct} at {first_use} needs the {feature} feature: {hint}{} [{}]",
+            and_others(.others),
+            ErrorCode::CompileFeatureDisabled
+        )
+    )]
+    FeatureDisabled {
+        /// The proposal the module uses first.
+        feature: WasmFeature,
+        /// Where the module first uses it.
+        first_use: Location,
+        /// What the module uses it for, such as `SIMD instruction`.
+        construct: &'static str,
+        /// How to either enable the proposal or build the module without it.
+        hint: &'static str,
+        /// The first uses of the other disabled proposals the module uses, in the order of
+        /// their first use. There is at most one per proposal.
+        others: Vec<DisabledFeatureUse>,
+    },
+
+    /// The body of a function is larger than the compiler is configured to compile.
+    #[cfg_attr(
+        feature = "std",
+        error(
+            "the body of function {} is {size} bytes, more than the limit of {limit} [{}]",
+            .index.index(),
+            ErrorCode::CompileCodeTooLarge
+        )
+    )]
+    CodeTooLarge {
+        /// The function whose body is too large.
+        index: LocalFunctionIndex,
+        /// The size of the body, in bytes of WebAssembly.
+        size: usize,
+        /// The largest body the compiler compiles.
+        limit: usize,
+    },
+}
+
+/// The other disabled proposals in [`CompileError::FeatureDisabled`], to append to its message.
+#[cfg(feature = "std")]
+fn and_others(others: &[DisabledFeatureUse]) -> String {
+    others.iter().map(|other| format!("; {}", other)).collect()
+}
+
+/// A section of a WebAssembly module.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SectionKind {
+    /// The type section.
+    Type,
+    /// The import section.
+    Import,
+    /// The function section, declaring the types of the functions the module defines.
+    Function,
+    /// The table section.
+    Table,
+    /// The memory section.
+    Memory,
+    /// The global section.
+    Global,
+    /// The export section.
+    Export,
+    /// The start section.
+    Start,
+    /// The element section.
+    Element,
+    /// The data count section.
+    DataCount,
+    /// The code section, with the bodies of the functions the module defines.
+    Code,
+    /// The data section.
+    Data,
+    /// A custom section, such as the name section.
+    Custom,
+    /// A section of a proposal this crate does not support, such as module linking.
+    Unsupported,
+}
+
+/// A place in a WebAssembly binary.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    /// The section of the place.
+    pub section: SectionKind,
+    /// The function whose body the place is in, if any.
+    pub function: Option<LocalFunctionIndex>,
+    /// The offset of the place in the binary.
+    pub offset: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.function {
+            Some(function) => write!(f, "the body of function {}", function.index())?,
+            None => write!(f, "the {:?} section", self.section)?,
+        }
+        write!(f, ", offset {}", self.offset)
+    }
+}
+
+/// The first use of a proposal a module uses while it is disabled, see
+/// [`CompileError::FeatureDisabled`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisabledFeatureUse {
+    /// The proposal used.
+    pub feature: WasmFeature,
+    /// Where the module first uses it.
+    pub first_use: Location,
+    /// What the module uses it for.
+    pub construct: &'static str,
+    /// How to either enable the proposal or build the module without it.
+    pub hint: &'static str,
+}
+
+impl fmt::Display for DisabledFeatureUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the {} at {} needs the {} feature: {}",
+            self.construct, self.first_use, self.feature, self.hint
+        )
+    }
+}
+
+/// The ways out-of-process compilation can fail, besides the module failing to compile.
+#[derive(Debug)]
+#[cfg_attr(feature = "std", derive(Error))]
+pub enum SubprocessError {
+    /// The helper process could not be started.
+    #[cfg_attr(
+        feature = "std",
+        error("could not spawn the helper: {0} [{}]", ErrorCode::SubprocessSpawn)
+    )]
+    Spawn(String),
+
+    /// Communicating with the helper process failed.
+    #[cfg_attr(
+        feature = "std",
+        error(
+            "could not communicate with the helper: {0} [{}]",
+            ErrorCode::SubprocessIo
+        )
+    )]
+    Io(String),
+
+    /// The helper did not answer in time and was killed.
+    #[cfg_attr(
+        feature = "std",
+        error("the helper timed out [{}]", ErrorCode::SubprocessTimeout)
+    )]
+    Timeout,
+
+    /// The helper exited without answering.
+    #[cfg_attr(
+        feature = "std",
+        error("the helper crashed: {0} [{}]", ErrorCode::SubprocessCrashed)
+    )]
+    Crashed(String),
+
+    /// The helper refused the module or ran out of resources because of a limit.
+    #[cfg_attr(
+        feature = "std",
+        error("limit exceeded: {0} [{}]", ErrorCode::SubprocessLimitExceeded)
+    )]
+    LimitExceeded(String),
+
+    /// The helper answered something that is not a valid response.
+    #[cfg_attr(
+        feature = "std",
+        error("malformed response: {0} [{}]", ErrorCode::SubprocessMalformedResponse)
+    )]
+    MalformedResponse(String),
 }
 
 impl From<WasmError> for CompileError {
@@ -58,7 +307,10 @@ impl From<WasmError> for CompileError {
 /// A error in the middleware.
 #[derive(Debug)]
 #[cfg_attr(feature = "std", derive(Error))]
-#[cfg_attr(feature = "std", error("Error in middleware {name}: {message}"))]
+#[cfg_attr(
+    feature = "std",
+    error("Error in middleware {name}: {message} [{}]", ErrorCode::Middleware)
+)]
 pub struct MiddlewareError {
     /// The name of the middleware where the error was created
     pub name: String,
@@ -89,7 +341,10 @@ pub enum WasmError {
     /// code. This should never happen for validated WebAssembly code.
     #[cfg_attr(
         feature = "std",
-        error("Invalid input WebAssembly code at offset {offset}: {message}")
+        error(
+            "Invalid input WebAssembly code at offset {offset}: {message} [{}]",
+            ErrorCode::WasmInvalid
+        )
     )]
     InvalidWebAssembly {
         /// A string describing the validation error.
@@ -101,11 +356,17 @@ pub enum WasmError {
     /// A feature used by the WebAssembly code is not supported by the embedding environment.
     ///
     /// Embedding environments may have their own limitations and feature restrictions.
-    #[cfg_attr(feature = "std", error("Unsupported feature: {0}"))]
+    #[cfg_attr(
+        feature = "std",
+        error("Unsupported feature: {0} [{}]", ErrorCode::WasmUnsupported)
+    )]
     Unsupported(String),
 
     /// An implementation limit was exceeded.
-    #[cfg_attr(feature = "std", error("Implementation limit exceeded"))]
+    #[cfg_attr(
+        feature = "std",
+        error("Implementation limit exceeded [{}]", ErrorCode::WasmImplLimitExceeded)
+    )]
     ImplLimitExceeded,
 
     /// An error from the middleware error.
@@ -113,7 +374,7 @@ pub enum WasmError {
     Middleware(MiddlewareError),
 
     /// A generic error.
-    #[cfg_attr(feature = "std", error("{0}"))]
+    #[cfg_attr(feature = "std", error("{0} [{}]", ErrorCode::WasmGeneric))]
     Generic(String),
 }
 
@@ -123,16 +384,124 @@ impl From<MiddlewareError> for WasmError {
     }
 }
 
+impl Classify for CompileError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::Wasm(e) => e.failure_kind(),
+            Self::Subprocess(e) => e.failure_kind(),
+            Self::Resource(_) | Self::CodeMemoryExhausted { .. } | Self::QuotaExceeded { .. } => {
+                FailureKind::TransientResource
+            }
+            Self::Codegen(_)
+            | Self::Validate(_)
+            | Self::UnsupportedFeature(_)
+            | Self::UnsupportedTarget(_)
+            | Self::EngineDowncast
+            | Self::Specialization(_)
+            | Self::FeatureDisabled { .. }
+            | Self::CodeTooLarge { .. } => FailureKind::Permanent,
+        }
+    }
+}
+
+impl Classify for SubprocessError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            // Spawning fails when the host is out of processes or memory, and the helper is
+            // killed when it runs out of memory.
+            Self::Spawn(_) | Self::Io(_) | Self::Crashed(_) => FailureKind::TransientResource,
+            Self::Timeout => FailureKind::TransientInterrupted,
+            // The limits apply to the module, which exceeds them again when retried.
+            Self::LimitExceeded(_) => FailureKind::Permanent,
+            Self::MalformedResponse(_) => FailureKind::Corrupt,
+        }
+    }
+}
+
+impl Classify for WasmError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::InvalidWebAssembly { .. }
+            | Self::Unsupported(_)
+            | Self::ImplLimitExceeded
+            | Self::Middleware(_)
+            | Self::Generic(_) => FailureKind::Permanent,
+        }
+    }
+}
+
+impl HasErrorCode for CompileError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Wasm(e) => e.code(),
+            Self::Codegen(_) => ErrorCode::CompileCodegen,
+            Self::Validate(_) => ErrorCode::CompileValidate,
+            Self::UnsupportedFeature(_) => ErrorCode::CompileUnsupportedFeature,
+            Self::UnsupportedTarget(_) => ErrorCode::CompileUnsupportedTarget,
+            Self::Resource(_) => ErrorCode::CompileResource,
+            Self::EngineDowncast => ErrorCode::CompileEngineDowncast,
+            Self::Specialization(_) => ErrorCode::CompileSpecialization,
+            Self::CodeMemoryExhausted { .. } => ErrorCode::CompileCodeMemoryExhausted,
+            Self::QuotaExceeded { .. } => ErrorCode::CompileQuotaExceeded,
+            Self::Subprocess(e) => e.code(),
+            Self::FeatureDisabled { .. } => ErrorCode::CompileFeatureDisabled,
+            Self::CodeTooLarge { .. } => ErrorCode::CompileCodeTooLarge,
+        }
+    }
+}
+
+impl HasErrorCode for SubprocessError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Spawn(_) => ErrorCode::SubprocessSpawn,
+            Self::Io(_) => ErrorCode::SubprocessIo,
+            Self::Timeout => ErrorCode::SubprocessTimeout,
+            Self::Crashed(_) => ErrorCode::SubprocessCrashed,
+            Self::LimitExceeded(_) => ErrorCode::SubprocessLimitExceeded,
+            Self::MalformedResponse(_) => ErrorCode::SubprocessMalformedResponse,
+        }
+    }
+}
+
+impl HasErrorCode for MiddlewareError {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::Middleware
+    }
+}
+
+impl HasErrorCode for WasmError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidWebAssembly { .. } => ErrorCode::WasmInvalid,
+            Self::Unsupported(_) => ErrorCode::WasmUnsupported,
+            Self::ImplLimitExceeded => ErrorCode::WasmImplLimitExceeded,
+            Self::Middleware(e) => e.code(),
+            Self::Generic(_) => ErrorCode::WasmGeneric,
+        }
+    }
+}
+
 /// The error that can happen while parsing a `str`
 /// to retrieve a [`CpuFeature`](crate::target::CpuFeature).
 #[derive(Debug)]
 #[cfg_attr(feature = "std", derive(Error))]
 pub enum ParseCpuFeatureError {
     /// The provided string feature doesn't exist
-    #[cfg_attr(feature = "std", error("CpuFeature {0} not recognized"))]
+    #[cfg_attr(
+        feature = "std",
+        error("CpuFeature {0} not recognized [{}]", ErrorCode::UnknownCpuFeature)
+    )]
     Missing(String),
 }
 
+impl HasErrorCode for ParseCpuFeatureError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Missing(_) => ErrorCode::UnknownCpuFeature,
+        }
+    }
+}
+
 /// A convenient alias for a `Result` that uses `WasmError` as the error type.
 pub type WasmResult<T> = Result<T, WasmError>;
 
@@ -159,4 +528,48 @@ mod tests {
             err => panic!("Unexpected error: {:?}", err),
         }
     }
+
+    #[test]
+    fn failure_kinds() {
+        let kind = |e: CompileError| e.failure_kind();
+        assert_eq!(
+            kind(CompileError::Validate("type mismatch".into())),
+            FailureKind::Permanent
+        );
+        assert_eq!(
+            kind(CompileError::Resource("mmap".into())),
+            FailureKind::TransientResource
+        );
+        assert_eq!(
+            kind(CompileError::CodeMemoryExhausted {
+                requested: 4096,
+                in_use: 0,
+                largest_free_block: 0,
+            }),
+            FailureKind::TransientResource
+        );
+        assert_eq!(
+            kind(CompileError::QuotaExceeded {
+                tenant: TenantId(7),
+                dimension: QuotaDimension::Artifacts,
+                limit: 1,
+                used: 2,
+            }),
+            FailureKind::TransientResource
+        );
+        assert_eq!(
+            kind(WasmError::ImplLimitExceeded.into()),
+            FailureKind::Permanent
+        );
+        assert_eq!(
+            kind(CompileError::Subprocess(SubprocessError::Timeout)),
+            FailureKind::TransientInterrupted
+        );
+        assert_eq!(
+            kind(CompileError::Subprocess(
+                SubprocessError::MalformedResponse("garbage".into())
+            )),
+            FailureKind::Corrupt
+        );
+    }
 }
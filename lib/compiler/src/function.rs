@@ -21,6 +21,7 @@ use wasmer_types::{FunctionIndex, LocalFunctionIndex, SignatureIndex};
 #[derive(
     rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Debug, Clone, PartialEq, Eq, Default,
 )]
+#[archive(check_bytes)]
 pub struct CompiledFunctionFrameInfo {
     /// The traps (in the function body).
     ///
@@ -33,6 +34,7 @@ pub struct CompiledFunctionFrameInfo {
 
 /// The function body.
 #[derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Debug, Clone, PartialEq, Eq)]
+#[archive(check_bytes)]
 pub struct FunctionBody {
     /// The function body bytes.
     pub body: Vec<u8>,
@@ -74,6 +76,7 @@ impl<'a> From<&'a ArchivedFunctionBody> for FunctionBodyRef<'a> {
 /// (function bytecode body, relocations, traps, jump tables
 /// and unwind information).
 #[derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Debug, Clone, PartialEq, Eq)]
+#[archive(check_bytes)]
 pub struct CompiledFunction {
     /// The function body.
     pub body: FunctionBody,
@@ -101,6 +104,7 @@ pub type CustomSections = PrimaryMap<SectionIndex, CustomSection>;
 /// In the future this structure may also hold other information useful
 /// for debugging.
 #[derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Debug, PartialEq, Eq, Clone)]
+#[archive(check_bytes)]
 pub struct Dwarf {
     /// The section index in the [`Compilation`] that corresponds to the exception frames.
     /// [Learn
@@ -117,6 +121,7 @@ impl Dwarf {
 
 /// Trampolines section used by ARM short jump (26bits)
 #[derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Debug, PartialEq, Eq, Clone)]
+#[archive(check_bytes)]
 pub struct TrampolinesSection {
     /// SectionIndex for the actual Trampolines code
     pub section_index: SectionIndex,
@@ -55,33 +55,41 @@ mod compiler;
 mod error;
 mod function;
 mod jump_table;
+#[cfg(feature = "translator")]
+mod metering;
 mod module;
 mod relocation;
 mod target;
 mod trap;
+mod unused;
 mod unwind;
 #[cfg(feature = "translator")]
 #[macro_use]
 mod translator;
 mod section;
+mod source_map;
 mod sourceloc;
 
 pub use crate::address_map::{FunctionAddressMap, InstructionAddressMap};
 #[cfg(feature = "translator")]
-pub use crate::compiler::{Compiler, CompilerConfig, Symbol, SymbolRegistry};
+pub use crate::compiler::{Compiler, CompilerConfig, CompilerProvenance, Symbol, SymbolRegistry};
 pub use crate::error::{
-    CompileError, MiddlewareError, ParseCpuFeatureError, WasmError, WasmResult,
+    CompileError, DisabledFeatureUse, Location, MiddlewareError, ParseCpuFeatureError, SectionKind,
+    SubprocessError, WasmError, WasmResult,
 };
 pub use crate::function::{
     Compilation, CompiledFunction, CompiledFunctionFrameInfo, CustomSections, Dwarf, FunctionBody,
     FunctionBodyRef, Functions, TrampolinesSection,
 };
 pub use crate::jump_table::{JumpTable, JumpTableOffsets};
+#[cfg(feature = "translator")]
+pub use crate::metering::{OperatorCosts, ScheduleVersion};
 pub use crate::module::CompileModuleInfo;
 pub use crate::relocation::{Relocation, RelocationKind, RelocationTarget, Relocations};
 pub use crate::section::{
     CustomSection, CustomSectionProtection, CustomSectionRef, SectionBody, SectionIndex,
 };
+pub use crate::source_map::{SourceLocation, SourceMap};
 pub use crate::sourceloc::SourceLoc;
 pub use crate::target::{
     Architecture, BinaryFormat, CallingConvention, CpuFeature, Endianness, OperatingSystem,
@@ -89,10 +97,12 @@ pub use crate::target::{
 };
 #[cfg(feature = "translator")]
 pub use crate::translator::{
-    translate_module, wptype_to_type, FunctionBodyData, FunctionReader, ModuleEnvironment,
-    ModuleTranslationState,
+    translate_module, validate_module_with_report, wptype_to_type, FunctionBodyData,
+    FunctionReader, ModuleEnvironment, ModuleTranslationState, StreamingValidator,
+    ValidationReport,
 };
 pub use crate::trap::TrapInformation;
+pub use crate::unused::UnusedItems;
 pub use crate::unwind::{CompiledFunctionUnwindInfo, CompiledFunctionUnwindInfoRef};
 
 pub use wasmer_types::Features;
@@ -0,0 +1,125 @@
+//! Costs of the WebAssembly operators, for the gas metering compilers insert.
+
+use crate::lib::std::fmt;
+use crate::lib::std::string::{String, ToString};
+use crate::lib::std::sync::Arc;
+use crate::lib::std::vec::Vec;
+use crate::translator::FunctionReader;
+use crate::WasmResult;
+use wasmparser::Operator;
+
+/// The version of a cost table, identifying it among the schedules a compiler meters with,
+/// see [`CompilerConfig::set_metering_schedules`].
+///
+/// Contracts keep being compiled with the schedule they were deployed with, whichever
+/// schedules are added after it.
+///
+/// [`CompilerConfig::set_metering_schedules`]: crate::CompilerConfig::set_metering_schedules
+#[derive(
+    rkyv::Serialize,
+    rkyv::Deserialize,
+    rkyv::Archive,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+#[archive(check_bytes)]
+pub struct ScheduleVersion(pub u32);
+
+impl fmt::Display for ScheduleVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+/// The gas cost of each WebAssembly operator.
+///
+/// Compilers metering the code they compile, see [`CompilerConfig::set_metering`], charge the
+/// cost of every basic block to the gas counter of the instance when the block starts, so
+/// that a module burns the same gas whichever compiler compiled it.
+///
+/// [`CompilerConfig::set_metering`]: crate::CompilerConfig::set_metering
+#[derive(Clone)]
+pub struct OperatorCosts {
+    name: String,
+    cost: Arc<dyn Fn(&Operator) -> u64 + Send + Sync>,
+}
+
+impl OperatorCosts {
+    /// Create a cost table from the cost of each operator.
+    ///
+    /// `name` identifies the table in the provenance of the compiled modules: change it
+    /// whenever the costs change, so that modules compiled with the old costs are not
+    /// mistaken for modules compiled with the new ones.
+    pub fn new<F>(name: &str, cost: F) -> Self
+    where
+        F: Fn(&Operator) -> u64 + Send + Sync + 'static,
+    {
+        Self {
+            name: name.to_string(),
+            cost: Arc::new(cost),
+        }
+    }
+
+    /// Create a cost table charging `cost` for every operator.
+    pub fn uniform(cost: u64) -> Self {
+        Self::new(&format!("uniform:{}", cost), move |_| cost)
+    }
+
+    /// The name of the cost table.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The cost of `operator`.
+    pub fn cost(&self, operator: &Operator) -> u64 {
+        (self.cost)(operator)
+    }
+
+    /// The basic blocks of the function body `reader`, as the index of the operator each
+    /// starts at and the cost of the operators it is made of, in order.
+    ///
+    /// A block ends after a block, loop, if, branch, return or `unreachable` operator. The
+    /// `else` and `end` operators belong to the block they close, so that the cost of a block
+    /// is charged after the label the branches to its end jump to.
+    pub fn basic_blocks(&self, reader: &FunctionReader) -> WasmResult<Vec<(usize, u64)>> {
+        let mut blocks: Vec<(usize, u64)> = Vec::new();
+        let mut ended = true;
+        for (index, operator) in reader.get_operators_reader()?.into_iter().enumerate() {
+            let operator = operator?;
+            let closes = matches!(operator, Operator::Else | Operator::End);
+            if ended && (!closes || blocks.is_empty()) {
+                blocks.push((index, 0));
+            }
+            let block = blocks.last_mut().unwrap();
+            block.1 = block.1.saturating_add(self.cost(&operator));
+            ended = matches!(
+                operator,
+                Operator::Block { .. }
+                    | Operator::Loop { .. }
+                    | Operator::If { .. }
+                    | Operator::Else
+                    | Operator::End
+                    | Operator::Br { .. }
+                    | Operator::BrIf { .. }
+                    | Operator::BrTable { .. }
+                    | Operator::Return
+                    | Operator::Unreachable
+            );
+        }
+        Ok(blocks)
+    }
+}
+
+impl fmt::Debug for OperatorCosts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OperatorCosts")
+            .field("name", &self.name)
+            .finish()
+    }
+}
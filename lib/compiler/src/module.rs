@@ -1,6 +1,8 @@
+use crate::lib::std::collections::BTreeMap;
 use crate::lib::std::sync::Arc;
+use crate::metering::ScheduleVersion;
 use wasmer_types::entity::PrimaryMap;
-use wasmer_types::{Features, MemoryIndex, ModuleInfo, TableIndex};
+use wasmer_types::{Features, GlobalIndex, GlobalInit, MemoryIndex, ModuleInfo, TableIndex};
 use wasmer_vm::{MemoryStyle, TableStyle};
 
 /// The required info for compiling a module.
@@ -9,6 +11,7 @@ use wasmer_vm::{MemoryStyle, TableStyle};
 /// possible after translation (such as the features used for compiling,
 /// or the `MemoryStyle` and `TableStyle`).
 #[derive(Debug, PartialEq, Eq, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
 pub struct CompileModuleInfo {
     /// The features used for compiling the module
     pub features: Features,
@@ -21,4 +24,13 @@ pub struct CompileModuleInfo {
     pub memory_styles: PrimaryMap<MemoryIndex, MemoryStyle>,
     /// The table plans used for compiling.
     pub table_styles: PrimaryMap<TableIndex, TableStyle>,
+    /// The values of the imported globals the module is specialized for.
+    ///
+    /// The compiler may use these values in place of reading the globals, so instances
+    /// must be given globals with exactly these values.
+    pub global_bindings: BTreeMap<GlobalIndex, GlobalInit>,
+    /// The metering schedule the code is compiled with, see
+    /// [`CompilerConfig::set_metering_schedules`](crate::CompilerConfig::set_metering_schedules),
+    /// or `None` for the metering the compiler is configured with otherwise.
+    pub metering_schedule: Option<ScheduleVersion>,
 }
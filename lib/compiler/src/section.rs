@@ -11,6 +11,7 @@ use wasmer_types::entity::entity_impl;
 
 /// Index type of a Section defined inside a WebAssembly `Compilation`.
 #[derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
 #[archive_attr(derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug))]
 #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
 pub struct SectionIndex(u32);
@@ -23,6 +24,7 @@ entity_impl!(ArchivedSectionIndex);
 ///
 /// Determines how a custom section may be used.
 #[derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Debug, Copy, Clone, PartialEq, Eq)]
+#[archive(check_bytes)]
 pub enum CustomSectionProtection {
     /// A custom section with read permission.
     Read,
@@ -36,6 +38,7 @@ pub enum CustomSectionProtection {
 /// This is used so compilers can store arbitrary information
 /// in the emitted module.
 #[derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Debug, Clone, PartialEq, Eq)]
+#[archive(check_bytes)]
 pub struct CustomSection {
     /// Memory protection that applies to this section.
     pub protection: CustomSectionProtection,
@@ -84,10 +87,28 @@ impl<'a> From<&'a ArchivedCustomSection> for CustomSectionRef<'a> {
     }
 }
 
+impl ArchivedSectionBody {
+    /// Returns a raw pointer to the section's buffer, without reading it.
+    pub fn as_ptr(&self) -> *const u8 {
+        self.0.as_ptr()
+    }
+
+    /// Returns the length of this section in bytes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns whether or not the section body is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
 /// The bytes in the section.
 #[derive(
     rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Debug, Clone, PartialEq, Eq, Default,
 )]
+#[archive(check_bytes)]
 pub struct SectionBody(Vec<u8>);
 
 impl SectionBody {
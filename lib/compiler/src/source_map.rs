@@ -0,0 +1,144 @@
+//! Mapping of offsets in a WebAssembly module back to the source it was
+//! compiled from.
+
+use crate::lib::std::fmt;
+use crate::lib::std::string::String;
+use crate::lib::std::vec::Vec;
+
+/// A location in the source a WebAssembly module was compiled from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    file: String,
+    line: u32,
+    column: Option<u32>,
+}
+
+impl SourceLocation {
+    /// Path of the source file, as recorded in the module.
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// Line in the source file, starting at 1.
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// Column in the line, starting at 1, if known.
+    pub fn column(&self) -> Option<u32> {
+        self.column
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)?;
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+        Ok(())
+    }
+}
+
+/// Marks the rows covering offsets that have no source location.
+const NO_FILE: u32 = u32::MAX;
+
+#[derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Debug, Clone, Copy, PartialEq, Eq)]
+#[archive(check_bytes)]
+struct SourceMapRow {
+    offset: u32,
+    file: u32,
+    line: u32,
+    column: u32,
+}
+
+/// A table mapping offsets in a WebAssembly module to [`SourceLocation`]s.
+///
+/// Each row covers the offsets from its own up to the one of the next row.
+#[derive(
+    rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Debug, Clone, PartialEq, Eq, Default,
+)]
+#[archive(check_bytes)]
+pub struct SourceMap {
+    files: Vec<String>,
+    /// Sorted by offset once the map is finished.
+    rows: Vec<SourceMapRow>,
+}
+
+impl SourceMap {
+    /// Create an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a source file, returning the index rows refer to it by.
+    pub fn add_file(&mut self, file: String) -> u32 {
+        self.files.push(file);
+        (self.files.len() - 1) as u32
+    }
+
+    /// Map the offsets from `offset` on to a location in the file at `file`.
+    ///
+    /// `line` and `column` start at 1, and a `column` of 0 means that it is unknown. Rows with
+    /// a `line` of 0, or an unknown `file`, have no location.
+    pub fn push(&mut self, offset: u32, file: u32, line: u32, column: u32) {
+        let file = if (file as usize) < self.files.len() && line != 0 {
+            file
+        } else {
+            NO_FILE
+        };
+        self.rows.push(SourceMapRow {
+            offset,
+            file,
+            line,
+            column,
+        });
+    }
+
+    /// Leave the offsets from `offset` on without a location.
+    pub fn push_unmapped(&mut self, offset: u32) {
+        self.push(offset, NO_FILE, 0, 0)
+    }
+
+    /// Sort the rows, keeping the last one pushed for each offset.
+    pub fn finish(mut self) -> Self {
+        // The sort is stable, so the last row pushed for an offset remains last.
+        self.rows.sort_by_key(|row| row.offset);
+        self.rows.reverse();
+        self.rows.dedup_by_key(|row| row.offset);
+        self.rows.reverse();
+        self
+    }
+
+    /// Return the number of rows in this map.
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Return whether this map has no rows.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Approximate number of bytes this map takes in memory.
+    pub fn size(&self) -> usize {
+        let files: usize = self.files.iter().map(|f| f.len()).sum();
+        files + self.rows.len() * core::mem::size_of::<SourceMapRow>()
+    }
+
+    /// Find the source location of the byte at `offset` in the module.
+    pub fn lookup(&self, offset: u32) -> Option<SourceLocation> {
+        let index = self.rows.partition_point(|row| row.offset <= offset);
+        let row = self.rows.get(index.checked_sub(1)?)?;
+        let file = self.files.get(row.file as usize)?;
+        Some(SourceLocation {
+            file: file.clone(),
+            line: row.line,
+            column: if row.column == 0 {
+                None
+            } else {
+                Some(row.column)
+            },
+        })
+    }
+}
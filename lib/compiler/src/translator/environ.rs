@@ -44,6 +44,11 @@ pub struct ModuleEnvironment<'data> {
 
     /// The decoded Wasm types for the module.
     pub module_translation_state: Option<ModuleTranslationState>,
+
+    /// Offset of the contents of the code section in the module, if it has one.
+    ///
+    /// DWARF debug information addresses code relative to this offset.
+    pub code_section_offset: Option<usize>,
 }
 
 impl<'data> ModuleEnvironment<'data> {
@@ -54,6 +59,7 @@ impl<'data> ModuleEnvironment<'data> {
             function_body_inputs: PrimaryMap::new(),
             data_initializers: Vec::new(),
             module_translation_state: None,
+            code_section_offset: None,
         }
     }
 
@@ -400,6 +406,26 @@ impl<'data> ModuleEnvironment<'data> {
         Ok(())
     }
 
+    /// Declares the name of a local of a function, which is only kept for its parameters.
+    pub(crate) fn declare_local_name(
+        &mut self,
+        func_index: FunctionIndex,
+        local_index: u32,
+        name: &'data str,
+    ) -> WasmResult<()> {
+        let params = self
+            .module
+            .functions
+            .get(func_index)
+            .map_or(0, |sig| self.module.signatures[*sig].params().len());
+        if (local_index as usize) < params {
+            self.module
+                .param_names
+                .insert((func_index, local_index), name.to_string());
+        }
+        Ok(())
+    }
+
     /// Provides the number of imports up front. By default this does nothing, but
     /// implementations can use this to preallocate memory if desired.
     pub(crate) fn reserve_imports(&mut self, _num: u32) -> WasmResult<()> {
@@ -418,7 +444,7 @@ impl<'data> ModuleEnvironment<'data> {
         );
         self.module
             .custom_sections
-            .insert(String::from(name), custom_section);
+            .push((String::from(name), custom_section));
         self.module.custom_sections_data.push(Arc::from(data));
         Ok(())
     }
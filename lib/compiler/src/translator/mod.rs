@@ -11,8 +11,13 @@ mod state;
 #[macro_use]
 mod error;
 mod sections;
+mod stream;
+mod validation;
 
 pub use self::environ::{FunctionBodyData, FunctionReader, ModuleEnvironment};
 pub use self::module::translate_module;
 pub use self::sections::wptype_to_type;
 pub use self::state::ModuleTranslationState;
+pub use self::stream::StreamingValidator;
+pub(crate) use self::validation::disabled_features_error;
+pub use self::validation::{validate_module_with_report, ValidationReport};
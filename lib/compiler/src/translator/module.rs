@@ -10,7 +10,7 @@ use super::sections::{
     parse_start_section, parse_table_section, parse_type_section,
 };
 use super::state::ModuleTranslationState;
-use crate::WasmResult;
+use crate::{WasmError, WasmResult};
 use wasmparser::{NameSectionReader, Parser, Payload};
 
 /// Translate a sequence of bytes forming a valid Wasm binary into a
@@ -61,7 +61,9 @@ pub fn translate_module<'data>(
                 parse_element_section(elements, environ)?;
             }
 
-            Payload::CodeSectionStart { .. } => {}
+            Payload::CodeSectionStart { range, .. } => {
+                environ.code_section_offset = Some(range.start);
+            }
             Payload::CodeSectionEntry(code) => {
                 let mut code = code.get_binary_reader();
                 let size = code.bytes_remaining();
@@ -81,9 +83,14 @@ pub fn translate_module<'data>(
                 environ.reserve_passive_data(count)?;
             }
 
+            Payload::EventSection(_) => {
+                return Err(WasmError::Unsupported(
+                    "exception handling is not supported".to_string(),
+                ));
+            }
+
             Payload::InstanceSection(_)
             | Payload::AliasSection(_)
-            | Payload::EventSection(_)
             | Payload::ModuleSectionStart { .. }
             | Payload::ModuleSectionEntry { .. } => {
                 unimplemented!("module linking not implemented yet")
@@ -27,9 +27,10 @@ use wasmer_types::{
 use wasmparser::{
     self, Data, DataKind, DataSectionReader, Element, ElementItem, ElementItems, ElementKind,
     ElementSectionReader, Export, ExportSectionReader, ExternalKind, FuncType as WPFunctionType,
-    FunctionSectionReader, GlobalSectionReader, GlobalType as WPGlobalType, ImportSectionEntryType,
-    ImportSectionReader, MemorySectionReader, MemoryType as WPMemoryType, NameSectionReader,
-    Naming, NamingReader, Operator, TableSectionReader, TypeDef, TypeSectionReader,
+    FunctionLocalReader, FunctionSectionReader, GlobalSectionReader, GlobalType as WPGlobalType,
+    ImportSectionEntryType, ImportSectionReader, MemorySectionReader, MemoryType as WPMemoryType,
+    NameSectionReader, Naming, NamingReader, Operator, TableSectionReader, TypeDef,
+    TypeSectionReader,
 };
 
 /// Helper function translating wasmparser types to Wasm Type.
@@ -437,12 +438,8 @@ pub fn parse_name_section<'data>(
     while let Ok(subsection) = names.read() {
         match subsection {
             wasmparser::Name::Function(function_subsection) => {
-                if let Some(function_names) = function_subsection
-                    .get_map()
-                    .ok()
-                    .and_then(parse_function_name_subsection)
-                {
-                    for (index, name) in function_names {
+                if let Ok(naming_reader) = function_subsection.get_map() {
+                    for (index, name) in parse_function_name_subsection(naming_reader) {
                         environ.declare_function_name(index, name)?;
                     }
                 }
@@ -452,33 +449,58 @@ pub fn parse_name_section<'data>(
                     environ.declare_module_name(name)?;
                 }
             }
-            wasmparser::Name::Local(_) => {}
+            wasmparser::Name::Local(local_subsection) => {
+                if let Some(local_names) = local_subsection
+                    .get_function_local_reader()
+                    .ok()
+                    .and_then(parse_local_name_subsection)
+                {
+                    for (func_index, local_index, name) in local_names {
+                        environ.declare_local_name(func_index, local_index, name)?;
+                    }
+                }
+            }
             wasmparser::Name::Unknown { .. } => {}
         };
     }
     Ok(())
 }
 
+/// The names of the function name subsection, as far as it can be read.
+///
+/// The names are only debug info, so a damaged subsection is not an error: the names before
+/// a truncation are kept, and the first name of a function given several wins.
 fn parse_function_name_subsection(
     mut naming_reader: NamingReader<'_>,
-) -> Option<HashMap<FunctionIndex, &str>> {
+) -> HashMap<FunctionIndex, &str> {
     let mut function_names = HashMap::new();
     for _ in 0..naming_reader.get_count() {
-        let Naming { index, name } = naming_reader.read().ok()?;
+        let Naming { index, name } = match naming_reader.read() {
+            Ok(naming) => naming,
+            Err(_) => break,
+        };
         if index == std::u32::MAX {
             // We reserve `u32::MAX` for our own use.
-            return None;
+            continue;
         }
+        function_names
+            .entry(FunctionIndex::from_u32(index))
+            .or_insert(name);
+    }
+    function_names
+}
 
-        if function_names
-            .insert(FunctionIndex::from_u32(index), name)
-            .is_some()
-        {
-            // If the function index has been previously seen, then we
-            // break out of the loop and early return `None`, because these
-            // should be unique.
-            return None;
+fn parse_local_name_subsection(
+    mut reader: FunctionLocalReader<'_>,
+) -> Option<Vec<(FunctionIndex, u32, &str)>> {
+    let mut local_names = Vec::new();
+    for _ in 0..reader.get_count() {
+        let function = reader.read().ok()?;
+        let mut naming_reader = function.get_map().ok()?;
+        for _ in 0..naming_reader.get_count() {
+            let Naming { index, name } = naming_reader.read().ok()?;
+            local_names.push((FunctionIndex::from_u32(function.func_index), index, name));
         }
     }
-    Some(function_names)
+    Some(local_names)
 }
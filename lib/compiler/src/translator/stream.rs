@@ -0,0 +1,119 @@
+//! Validation of WebAssembly modules received in chunks, such as from the network.
+
+use super::validation::disabled_features_error;
+use crate::compiler::wasm_features;
+use crate::lib::std::string::ToString;
+use crate::lib::std::vec::Vec;
+use crate::{CompileError, WasmError};
+use wasmer_types::Features;
+use wasmparser::{BinaryReaderError, Chunk, Parser, Payload, ValidPayload, Validator};
+
+/// Validates a WebAssembly binary as its bytes arrive, rather than once all of them did.
+///
+/// Each section is validated as soon as it is complete, and each function body as soon as it
+/// is, while the rest of the code section is still arriving. Once the last bytes are in,
+/// [`StreamingValidator::finish`] returns the binary, to be compiled without validating it
+/// again.
+///
+/// ```
+/// # use wasmer_compiler::{Features, StreamingValidator};
+/// let binary = b"\0asm\x01\0\0\0";
+/// let mut validator = StreamingValidator::new(&Features::default());
+/// for byte in binary.chunks(1) {
+///     validator.feed(byte).unwrap();
+/// }
+/// assert_eq!(validator.finish().unwrap(), binary);
+/// ```
+pub struct StreamingValidator {
+    parser: Parser,
+    validator: Validator,
+    /// All the bytes received so far.
+    binary: Vec<u8>,
+    /// How many of the bytes received so far were parsed.
+    parsed: usize,
+    /// Whether the end of the module was parsed.
+    ended: bool,
+    /// The features the binary is validated against.
+    features: Features,
+}
+
+impl StreamingValidator {
+    /// Start validating a binary against `features`, as `Compiler::validate_module` would.
+    pub fn new(features: &Features) -> Self {
+        let mut validator = Validator::new();
+        validator.wasm_features(wasm_features(features));
+        Self {
+            parser: Parser::new(0),
+            validator,
+            binary: Vec::new(),
+            parsed: 0,
+            ended: false,
+            features: features.clone(),
+        }
+    }
+
+    /// The number of bytes received so far.
+    pub fn received(&self) -> usize {
+        self.binary.len()
+    }
+
+    /// Receive the next bytes of the binary, validating what they complete.
+    ///
+    /// Fails as soon as what was received is invalid, with [`CompileError::Wasm`] if the
+    /// binary is malformed and with [`CompileError::Validate`] if it does not validate.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<(), CompileError> {
+        self.binary.extend_from_slice(bytes);
+        self.advance(false)
+    }
+
+    /// Finish validating the binary now that all its bytes were received, and return it.
+    ///
+    /// A binary missing bytes fails with [`CompileError::Wasm`].
+    pub fn finish(mut self) -> Result<Vec<u8>, CompileError> {
+        self.advance(true)?;
+        if !self.ended {
+            return Err(truncated(self.binary.len()));
+        }
+        Ok(self.binary)
+    }
+
+    /// Parse and validate as much of the bytes received as possible.
+    fn advance(&mut self, eof: bool) -> Result<(), CompileError> {
+        while !self.ended {
+            let remaining = &self.binary[self.parsed..];
+            let (consumed, payload) = match self.parser.parse(remaining, eof)? {
+                Chunk::NeedMoreData(_) if eof => return Err(truncated(self.binary.len())),
+                Chunk::NeedMoreData(_) => return Ok(()),
+                Chunk::Parsed { consumed, payload } => (consumed, payload),
+            };
+            let received = &self.binary[..];
+            let features = &self.features;
+            let invalid = |e: BinaryReaderError| {
+                disabled_features_error(features, received, &e, eof)
+                    .unwrap_or_else(|| CompileError::Validate(format!("{}", e)))
+            };
+            match self.validator.payload(&payload).map_err(invalid)? {
+                ValidPayload::Ok => {}
+                ValidPayload::Func(mut validator, body) => {
+                    validator.validate(&body).map_err(invalid)?;
+                }
+                ValidPayload::Submodule(_) => {
+                    return Err(CompileError::Wasm(WasmError::Unsupported(
+                        "module linking is not supported".to_string(),
+                    )));
+                }
+            }
+            self.ended = matches!(payload, Payload::End);
+            self.parsed += consumed;
+        }
+        Ok(())
+    }
+}
+
+/// The error of a binary ending after `len` bytes, before the end of the module.
+fn truncated(len: usize) -> CompileError {
+    CompileError::Wasm(WasmError::InvalidWebAssembly {
+        message: "unexpected end of the module".to_string(),
+        offset: len,
+    })
+}
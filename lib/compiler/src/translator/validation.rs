@@ -0,0 +1,400 @@
+//! Validation of WebAssembly modules telling where and why they are invalid.
+
+use crate::compiler::wasm_features;
+use crate::lib::std::fmt;
+use crate::lib::std::string::{String, ToString};
+use crate::lib::std::vec::Vec;
+use crate::{CompileError, DisabledFeatureUse, Location, SectionKind};
+use wasmer_types::entity::EntityRef;
+use wasmer_types::{Features, LocalFunctionIndex, WasmFeature};
+use wasmparser::{
+    BinaryReaderError, Chunk, DataKind, ElementKind, FunctionBody, ImportSectionEntryType,
+    MemoryType, Operator, Parser, Payload, Type, TypeDef, ValidPayload, Validator,
+};
+
+impl SectionKind {
+    fn of(payload: &Payload) -> Option<Self> {
+        Some(match payload {
+            Payload::Version { .. } | Payload::End => return None,
+            Payload::TypeSection(_) => Self::Type,
+            Payload::ImportSection(_) => Self::Import,
+            Payload::FunctionSection(_) => Self::Function,
+            Payload::TableSection(_) => Self::Table,
+            Payload::MemorySection(_) => Self::Memory,
+            Payload::GlobalSection(_) => Self::Global,
+            Payload::ExportSection(_) => Self::Export,
+            Payload::StartSection { .. } => Self::Start,
+            Payload::ElementSection(_) => Self::Element,
+            Payload::DataCountSection { .. } => Self::DataCount,
+            Payload::CodeSectionStart { .. } | Payload::CodeSectionEntry(_) => Self::Code,
+            Payload::DataSection(_) => Self::Data,
+            Payload::CustomSection { .. } => Self::Custom,
+            Payload::EventSection(_)
+            | Payload::InstanceSection(_)
+            | Payload::AliasSection(_)
+            | Payload::ModuleSectionStart { .. }
+            | Payload::ModuleSectionEntry { .. }
+            | Payload::UnknownSection { .. } => Self::Unsupported,
+        })
+    }
+}
+
+/// Where and why a WebAssembly module is invalid, see [`validate_module_with_report`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Why the module is invalid, as wasmparser puts it.
+    pub message: String,
+    /// The offset in the binary of what is invalid.
+    pub offset: usize,
+    /// The section that is invalid, or `None` if the module is invalid before any section,
+    /// such as when its header is.
+    pub section: Option<SectionKind>,
+    /// The function whose body is invalid, if any.
+    pub function: Option<LocalFunctionIndex>,
+    /// The name of the operator that is invalid, as wasmparser names it, such as `I32Add`,
+    /// when the operator could be read.
+    pub operator: Option<String>,
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)?;
+        if let Some(operator) = &self.operator {
+            write!(f, ", in operator {}", operator)?;
+        }
+        if let Some(function) = self.function {
+            write!(f, ", in the body of function {}", function.index())?;
+        } else if let Some(section) = self.section {
+            write!(f, ", in the {:?} section", section)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ValidationReport {}
+
+/// Validate a WebAssembly binary against `features`, as `Compiler::validate_module` does,
+/// telling where it is invalid if it is.
+///
+/// The function bodies are validated operator by operator, so that the report names the
+/// operator that is invalid.
+pub fn validate_module_with_report(
+    features: &Features,
+    data: &[u8],
+) -> Result<(), ValidationReport> {
+    let mut validator = Validator::new();
+    validator.wasm_features(wasm_features(features));
+    let mut section = None;
+    let mut functions = 0;
+    let report = |error: BinaryReaderError,
+                  section: Option<SectionKind>,
+                  function: Option<LocalFunctionIndex>,
+                  operator: Option<String>| ValidationReport {
+        message: error.message().to_string(),
+        offset: error.offset(),
+        section,
+        function,
+        operator,
+    };
+    for payload in Parser::new(0).parse_all(data) {
+        let payload = payload.map_err(|e| report(e, section, None, None))?;
+        section = SectionKind::of(&payload).or(section);
+        match validator.payload(&payload) {
+            Ok(ValidPayload::Func(mut validator, body)) => {
+                let function = LocalFunctionIndex::new(functions);
+                functions += 1;
+                validate_body(&mut validator, &body)
+                    .map_err(|(e, operator)| report(e, section, Some(function), operator))?;
+            }
+            Ok(_) => {}
+            Err(e) => return Err(report(e, section, None, None)),
+        }
+    }
+    Ok(())
+}
+
+/// Validate a function body, returning the name of the operator that is invalid with the
+/// error, if it could be read.
+fn validate_body(
+    validator: &mut wasmparser::FuncValidator<wasmparser::ValidatorResources>,
+    body: &FunctionBody,
+) -> Result<(), (BinaryReaderError, Option<String>)> {
+    let mut locals = body.get_locals_reader().map_err(|e| (e, None))?;
+    for _ in 0..locals.get_count() {
+        let offset = locals.original_position();
+        let (count, ty) = locals.read().map_err(|e| (e, None))?;
+        validator
+            .define_locals(offset, count, ty)
+            .map_err(|e| (e, None))?;
+    }
+    let mut operators = body.get_operators_reader().map_err(|e| (e, None))?;
+    while !operators.eof() {
+        let (operator, offset) = operators.read_with_offset().map_err(|e| (e, None))?;
+        validator
+            .op(offset, &operator)
+            .map_err(|e| (e, Some(operator_name(&operator))))?;
+    }
+    validator
+        .finish(operators.original_position())
+        .map_err(|e| (e, None))
+}
+
+/// The name of the variant of `operator`, such as `I32Add`.
+fn operator_name(operator: &Operator) -> String {
+    let debug = format!("{:?}", operator);
+    debug
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Return the error of a module using proposals that `features` disables, if its validation
+/// failed with `error` because of one of them, so that failing validation tells which proposals
+/// to enable. Return `None` if `error` is another one, to be reported as it is.
+///
+/// The error is caused by a disabled proposal if validating the module again with the
+/// proposals it uses enabled gets past it. `eof` tells whether `data` is the whole module,
+/// rather than the part of it received so far.
+///
+/// Validation stops at the first error, but the whole module is scanned here, so that every
+/// disabled proposal the module uses is reported, where it is first used. The scan stops at
+/// the first malformed part of the module, reporting the proposals used before it.
+pub(crate) fn disabled_features_error(
+    features: &Features,
+    data: &[u8],
+    error: &BinaryReaderError,
+    eof: bool,
+) -> Option<CompileError> {
+    let mut scan = FeatureScan {
+        features,
+        uses: Vec::new(),
+    };
+    // Malformed modules fail validation anyway: report what was found until then.
+    let _ = scan.module(data);
+    if scan.uses.is_empty() {
+        return None;
+    }
+
+    let mut enabled = features.clone();
+    for feature_use in &scan.uses {
+        enable(&mut enabled, feature_use.feature);
+    }
+    if let Err(again) = validate_prefix(&enabled, data, eof) {
+        if again.offset() == error.offset() && again.message() == error.message() {
+            return None;
+        }
+    }
+    let mut uses = scan.uses.into_iter();
+    let first = uses.next()?;
+    Some(CompileError::FeatureDisabled {
+        feature: first.feature,
+        first_use: first.first_use,
+        construct: first.construct,
+        hint: first.hint,
+        others: uses.collect(),
+    })
+}
+
+fn enable(features: &mut Features, feature: WasmFeature) {
+    match feature {
+        WasmFeature::Threads => features.threads(true),
+        WasmFeature::ReferenceTypes => features.reference_types(true),
+        WasmFeature::Simd => features.simd(true),
+        WasmFeature::BulkMemory => features.bulk_memory(true),
+        WasmFeature::MultiValue => features.multi_value(true),
+    };
+}
+
+/// Validate `data` against `features`, only as far as it goes when it is not the whole module.
+fn validate_prefix(features: &Features, data: &[u8], eof: bool) -> Result<(), BinaryReaderError> {
+    let mut validator = Validator::new();
+    validator.wasm_features(wasm_features(features));
+    let mut parser = Parser::new(0);
+    let mut parsed = 0;
+    loop {
+        let (consumed, payload) = match parser.parse(&data[parsed..], eof)? {
+            Chunk::NeedMoreData(_) => return Ok(()),
+            Chunk::Parsed { consumed, payload } => (consumed, payload),
+        };
+        match validator.payload(&payload)? {
+            ValidPayload::Func(mut validator, body) => validator.validate(&body)?,
+            ValidPayload::Ok | ValidPayload::Submodule(_) => {}
+        }
+        if let Payload::End = payload {
+            return Ok(());
+        }
+        parsed += consumed;
+    }
+}
+
+/// The first uses of the disabled proposals found in a module so far.
+struct FeatureScan<'a> {
+    features: &'a Features,
+    uses: Vec<DisabledFeatureUse>,
+}
+
+impl FeatureScan<'_> {
+    fn module(&mut self, data: &[u8]) -> Result<(), BinaryReaderError> {
+        let mut functions = 0;
+        for payload in Parser::new(0).parse_all(data) {
+            match payload? {
+                Payload::TypeSection(mut types) => {
+                    for _ in 0..types.get_count() {
+                        let at = location(SectionKind::Type, None, types.original_position());
+                        let ty = match types.read()? {
+                            TypeDef::Func(ty) => ty,
+                            _ => continue,
+                        };
+                        if ty.returns.len() > 1 {
+                            let construct = "function type with several results";
+                            self.found(WasmFeature::MultiValue, at, construct);
+                        }
+                        for ty in ty.params.iter().chain(ty.returns.iter()) {
+                            self.value_type(*ty, at);
+                        }
+                    }
+                }
+                Payload::ImportSection(mut imports) => {
+                    for _ in 0..imports.get_count() {
+                        let at = location(SectionKind::Import, None, imports.original_position());
+                        if let ImportSectionEntryType::Memory(memory) = imports.read()?.ty {
+                            self.memory(memory, at);
+                        }
+                    }
+                }
+                Payload::MemorySection(mut memories) => {
+                    for _ in 0..memories.get_count() {
+                        let at = location(SectionKind::Memory, None, memories.original_position());
+                        self.memory(memories.read()?, at);
+                    }
+                }
+                Payload::ElementSection(mut elements) => {
+                    for _ in 0..elements.get_count() {
+                        let at = location(SectionKind::Element, None, elements.original_position());
+                        if let ElementKind::Passive = elements.read()?.kind {
+                            let construct = "passive element segment";
+                            self.found(WasmFeature::BulkMemory, at, construct);
+                        }
+                    }
+                }
+                Payload::DataCountSection { range, .. } => {
+                    let at = location(SectionKind::DataCount, None, range.start);
+                    self.found(WasmFeature::BulkMemory, at, "data count section");
+                }
+                Payload::DataSection(mut data) => {
+                    for _ in 0..data.get_count() {
+                        let at = location(SectionKind::Data, None, data.original_position());
+                        if let DataKind::Passive = data.read()?.kind {
+                            let construct = "passive data segment";
+                            self.found(WasmFeature::BulkMemory, at, construct);
+                        }
+                    }
+                }
+                Payload::CodeSectionEntry(body) => {
+                    let function = Some(LocalFunctionIndex::new(functions));
+                    functions += 1;
+                    let mut locals = body.get_locals_reader()?;
+                    for _ in 0..locals.get_count() {
+                        let at = location(SectionKind::Code, function, locals.original_position());
+                        let (_, ty) = locals.read()?;
+                        self.value_type(ty, at);
+                    }
+                    let mut operators = body.get_operators_reader()?;
+                    while !operators.eof() {
+                        let (operator, offset) = operators.read_with_offset()?;
+                        if let Some((feature, construct)) = operator_feature(&operator) {
+                            let at = location(SectionKind::Code, function, offset);
+                            self.found(feature, at, construct);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn value_type(&mut self, ty: Type, at: Location) {
+        match ty {
+            Type::V128 => self.found(WasmFeature::Simd, at, "v128 value"),
+            Type::FuncRef | Type::ExternRef => {
+                self.found(WasmFeature::ReferenceTypes, at, "reference value")
+            }
+            _ => {}
+        }
+    }
+
+    fn memory(&mut self, memory: MemoryType, at: Location) {
+        if let MemoryType::M32 { shared: true, .. } | MemoryType::M64 { shared: true, .. } = memory
+        {
+            self.found(WasmFeature::Threads, at, "shared memory");
+        }
+    }
+
+    /// Record that the module uses `feature` at `first_use`, if it is disabled and was not
+    /// found used earlier.
+    fn found(&mut self, feature: WasmFeature, first_use: Location, construct: &'static str) {
+        if feature.is_enabled(self.features) || self.uses.iter().any(|u| u.feature == feature) {
+            return;
+        }
+        self.uses.push(DisabledFeatureUse {
+            feature,
+            first_use,
+            construct,
+            hint: hint(feature),
+        });
+    }
+}
+
+fn location(section: SectionKind, function: Option<LocalFunctionIndex>, offset: usize) -> Location {
+    Location {
+        section,
+        function,
+        offset,
+    }
+}
+
+/// The proposal `operator` belongs to, and what it is, if it belongs to one `Features` may
+/// disable.
+fn operator_feature(operator: &Operator) -> Option<(WasmFeature, &'static str)> {
+    const SIMD_PREFIXES: &[&str] = &["V128", "I8x16", "I16x8", "I32x4", "I64x2", "F32x4", "F64x2"];
+    let name = operator_name(operator);
+    if SIMD_PREFIXES.iter().any(|prefix| name.starts_with(prefix)) {
+        return Some((WasmFeature::Simd, "SIMD instruction"));
+    }
+    if name.contains("Atomic") {
+        return Some((WasmFeature::Threads, "atomic instruction"));
+    }
+    match name.as_str() {
+        "MemoryInit" | "DataDrop" | "MemoryCopy" | "MemoryFill" | "TableInit" | "ElemDrop"
+        | "TableCopy" => Some((WasmFeature::BulkMemory, "bulk memory instruction")),
+        "RefNull" | "RefIsNull" | "RefFunc" | "TypedSelect" | "TableGet" | "TableSet"
+        | "TableGrow" | "TableSize" | "TableFill" => {
+            Some((WasmFeature::ReferenceTypes, "reference types instruction"))
+        }
+        _ => None,
+    }
+}
+
+/// How to either enable `feature` or build modules without it.
+fn hint(feature: WasmFeature) -> &'static str {
+    match feature {
+        WasmFeature::Threads => {
+            "enable `threads` in the features of the engine, or build the module without atomics, such as with `-C target-feature=-atomics`"
+        }
+        WasmFeature::ReferenceTypes => {
+            "enable `reference_types` in the features of the engine, or build the module without them, such as with `-C target-feature=-reference-types`"
+        }
+        WasmFeature::Simd => {
+            "enable `simd` in the features of the engine, or build the module without SIMD, such as with `-C target-feature=-simd128`"
+        }
+        WasmFeature::BulkMemory => {
+            "enable `bulk_memory` in the features of the engine, or build the module without it, such as with `-C target-feature=-bulk-memory`"
+        }
+        WasmFeature::MultiValue => {
+            "enable `multi_value` in the features of the engine, or build the module without it, such as with `-C target-feature=-multivalue`"
+        }
+    }
+}
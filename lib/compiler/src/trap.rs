@@ -3,6 +3,7 @@ use wasmer_vm::TrapCode;
 
 /// Information about trap.
 #[derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Clone, Debug, PartialEq, Eq)]
+#[archive(check_bytes)]
 pub struct TrapInformation {
     /// The offset of the trapping instruction in native code. It is relative to the beginning of the function.
     pub code_offset: CodeOffset,
@@ -0,0 +1,127 @@
+//! Imports and globals a module never uses, found when compiling it.
+
+#[cfg(feature = "translator")]
+use crate::lib::std::collections::BTreeSet;
+use crate::lib::std::vec::Vec;
+#[cfg(feature = "translator")]
+use crate::translator::{FunctionReader, ModuleEnvironment};
+#[cfg(feature = "translator")]
+use crate::WasmResult;
+#[cfg(feature = "translator")]
+use wasmer_types::entity::EntityRef;
+#[cfg(feature = "translator")]
+use wasmer_types::{ExportIndex, GlobalInit};
+use wasmer_types::{FunctionIndex, GlobalIndex};
+#[cfg(feature = "translator")]
+use wasmparser::Operator;
+
+/// The imported functions and the globals of a module that none of its reachable code uses.
+///
+/// Code is reachable from the exported functions, the start function, and the functions
+/// placed in tables or globals, through the functions it calls or takes a reference to. A
+/// global is used when reachable code reads or writes it, or when it is exported or gives
+/// the initial value of another global or the offset of a data or element segment.
+#[derive(
+    Clone, Debug, Default, PartialEq, Eq, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive,
+)]
+#[archive(check_bytes)]
+pub struct UnusedItems {
+    /// The unused imported functions, in order.
+    pub functions: Vec<FunctionIndex>,
+    /// The unused globals, imported or defined, in order.
+    pub globals: Vec<GlobalIndex>,
+    /// Whether the unused imported functions were dropped from the imports instances must be
+    /// given, see [`CompilerConfig::strip_unused_imports`](crate::CompilerConfig::strip_unused_imports).
+    pub imports_stripped: bool,
+}
+
+#[cfg(feature = "translator")]
+impl UnusedItems {
+    /// Find the unused items of the module `translation` is the translation of.
+    pub fn find(translation: &ModuleEnvironment) -> WasmResult<Self> {
+        let module = &translation.module;
+        let mut functions = BTreeSet::new();
+        let mut globals = BTreeSet::new();
+        let mut pending = Vec::new();
+        let mut reach = |index: FunctionIndex, pending: &mut Vec<FunctionIndex>| {
+            // Null elements are the reserved value, beyond the functions.
+            if index.index() < module.functions.len() && functions.insert(index) {
+                pending.push(index);
+            }
+        };
+
+        for export in module.exports.values() {
+            match *export {
+                ExportIndex::Function(index) => reach(index, &mut pending),
+                ExportIndex::Global(index) => {
+                    globals.insert(index);
+                }
+                _ => {}
+            }
+        }
+        if let Some(index) = module.start_function {
+            reach(index, &mut pending);
+        }
+        for initializer in &module.table_initializers {
+            globals.extend(initializer.base);
+            for index in initializer.elements.iter() {
+                reach(*index, &mut pending);
+            }
+        }
+        for elements in module.passive_elements.values() {
+            for index in elements.iter() {
+                reach(*index, &mut pending);
+            }
+        }
+        for init in module.global_initializers.values() {
+            match *init {
+                GlobalInit::RefFunc(index) => reach(index, &mut pending),
+                GlobalInit::GetGlobal(index) => {
+                    globals.insert(index);
+                }
+                _ => {}
+            }
+        }
+        globals.extend(
+            translation
+                .data_initializers
+                .iter()
+                .filter_map(|initializer| initializer.location.base),
+        );
+
+        while let Some(index) = pending.pop() {
+            let local = match module.local_func_index(index) {
+                Some(local) => local,
+                None => continue,
+            };
+            let body = &translation.function_body_inputs[local];
+            let reader = FunctionReader::new(body.module_offset, body.data);
+            for operator in reader.get_operators_reader()? {
+                match operator? {
+                    Operator::Call { function_index }
+                    | Operator::ReturnCall { function_index }
+                    | Operator::RefFunc { function_index } => {
+                        reach(FunctionIndex::from_u32(function_index), &mut pending)
+                    }
+                    Operator::GlobalGet { global_index } | Operator::GlobalSet { global_index } => {
+                        globals.insert(GlobalIndex::from_u32(global_index));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(Self {
+            functions: (0..module.import_counts.functions as usize)
+                .map(FunctionIndex::new)
+                .filter(|index| !functions.contains(index))
+                .collect(),
+            globals: module
+                .globals
+                .keys()
+                .filter(|index| !globals.contains(index))
+                .collect(),
+            imports_stripped: false,
+        })
+    }
+}
@@ -15,12 +15,20 @@ use crate::lib::std::vec::Vec;
 ///
 /// [unwind info]: https://docs.microsoft.com/en-us/cpp/build/exception-handling-x64?view=vs-2019
 #[derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Debug, Clone, PartialEq, Eq)]
+#[archive(check_bytes)]
 pub enum CompiledFunctionUnwindInfo {
     /// Windows UNWIND_INFO.
     WindowsX64(Vec<u8>),
 
     /// The unwind info is added to the Dwarf section in `Compilation`.
     Dwarf,
+
+    /// The entry of a function in the Dwarf section of the module it was compiled in, from
+    /// the end of its address range on, for the function to be moved to another module.
+    ///
+    /// Compilers do not emit this: the rest of the entry only depends on where the entry and
+    /// the function are, so that the engine writes it again in the section of the other module.
+    DwarfEntry(Vec<u8>),
 }
 
 /// See [`CompiledFunctionUnwindInfo`].
@@ -30,6 +38,8 @@ pub enum CompiledFunctionUnwindInfoRef<'a> {
     WindowsX64(&'a [u8]),
     /// Unwind info is added to the Dwarf section in `Compilation`.
     Dwarf,
+    /// The entry of a function in the Dwarf section of another module.
+    DwarfEntry(&'a [u8]),
 }
 
 impl<'a> From<&'a CompiledFunctionUnwindInfo> for CompiledFunctionUnwindInfoRef<'a> {
@@ -39,6 +49,9 @@ impl<'a> From<&'a CompiledFunctionUnwindInfo> for CompiledFunctionUnwindInfoRef<
                 CompiledFunctionUnwindInfoRef::WindowsX64(d)
             }
             CompiledFunctionUnwindInfo::Dwarf => CompiledFunctionUnwindInfoRef::Dwarf,
+            CompiledFunctionUnwindInfo::DwarfEntry(d) => {
+                CompiledFunctionUnwindInfoRef::DwarfEntry(d)
+            }
         }
     }
 }
@@ -50,6 +63,9 @@ impl<'a> From<&'a ArchivedCompiledFunctionUnwindInfo> for CompiledFunctionUnwind
                 CompiledFunctionUnwindInfoRef::WindowsX64(d)
             }
             ArchivedCompiledFunctionUnwindInfo::Dwarf => CompiledFunctionUnwindInfoRef::Dwarf,
+            ArchivedCompiledFunctionUnwindInfo::DwarfEntry(d) => {
+                CompiledFunctionUnwindInfoRef::DwarfEntry(d)
+            }
         }
     }
 }
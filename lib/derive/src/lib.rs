@@ -0,0 +1,202 @@
+//! Derive macros of the `wasmer` crate, which re-exports them.
+
+#![deny(missing_docs, trivial_numeric_casts, unused_extern_crates)]
+#![warn(unused_import_braces)]
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, Lit, Meta, NestedMeta, Type,
+};
+
+/// Implement `wasmer::GuestType` for a struct whose fields all implement it.
+///
+/// Fields are laid out in declaration order, each aligned as it would be in a C struct, and
+/// the size of the struct is rounded up to its alignment. `#[guest(packed)]` lays the fields
+/// out without padding, with an alignment of 1. `#[guest(layout = "C")]` states the default
+/// layout explicitly.
+#[proc_macro_derive(GuestType, attributes(guest))]
+pub fn derive_guest_type(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match guest_type(input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn guest_type(input: DeriveInput) -> Result<TokenStream2, Error> {
+    let packed = parse_layout(&input)?;
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new(
+                input.ident.span(),
+                "GuestType can only be derived for structs",
+            ))
+        }
+    };
+    for field in fields {
+        check_field_type(&field.ty)?;
+    }
+
+    let name = &input.ident;
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    let mut generics = input.generics.clone();
+    {
+        let where_clause = generics.make_where_clause();
+        for ty in &types {
+            where_clause
+                .predicates
+                .push(parse_quote!(#ty: ::wasmer::GuestType));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // The offset of each field, from the end of the previous one.
+    let offsets = types
+        .iter()
+        .map(|ty| {
+            if packed {
+                quote!(end)
+            } else {
+                quote! {
+                    end + (<#ty as ::wasmer::GuestType>::ALIGN
+                        - end % <#ty as ::wasmer::GuestType>::ALIGN)
+                        % <#ty as ::wasmer::GuestType>::ALIGN
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    let align = if packed {
+        quote!(1)
+    } else {
+        quote! {{
+            let mut align = 1;
+            #(
+                if <#types as ::wasmer::GuestType>::ALIGN > align {
+                    align = <#types as ::wasmer::GuestType>::ALIGN;
+                }
+            )*
+            align
+        }}
+    };
+
+    let bindings = (0..types.len())
+        .map(|index| format_ident!("field{}", index))
+        .collect::<Vec<_>>();
+    // Builds the struct from the bindings, and binds its fields.
+    let pattern = match fields {
+        Fields::Named(_) => {
+            let names = fields.iter().map(|field| &field.ident);
+            quote!(Self { #(#names: #bindings),* })
+        }
+        Fields::Unnamed(_) => quote!(Self(#(#bindings),*)),
+        Fields::Unit => quote!(Self),
+    };
+    let decode = types
+        .iter()
+        .zip(&offsets)
+        .zip(&bindings)
+        .map(|((ty, offset), binding)| {
+            quote_spanned! {ty.span()=>
+                let offset = #offset;
+                end = offset + <#ty as ::wasmer::GuestType>::SIZE;
+                let #binding = <#ty as ::wasmer::GuestType>::decode(&bytes[offset..end])?;
+            }
+        });
+    let encode = types
+        .iter()
+        .zip(&offsets)
+        .zip(&bindings)
+        .map(|((ty, offset), binding)| {
+            quote_spanned! {ty.span()=>
+                let offset = #offset;
+                end = offset + <#ty as ::wasmer::GuestType>::SIZE;
+                <#ty as ::wasmer::GuestType>::encode(#binding, &mut bytes[offset..end])?;
+            }
+        });
+
+    Ok(quote! {
+        impl #impl_generics ::wasmer::GuestType for #name #ty_generics #where_clause {
+            const SIZE: usize = {
+                let mut end = 0;
+                #(end = #offsets + <#types as ::wasmer::GuestType>::SIZE;)*
+                end + (Self::ALIGN - end % Self::ALIGN) % Self::ALIGN
+            };
+            const ALIGN: usize = #align;
+
+            #[allow(unused_assignments, unused_mut, unused_variables)]
+            fn decode(bytes: &[u8]) -> ::std::result::Result<Self, ::wasmer::GuestTypeError> {
+                let bytes = ::wasmer::GuestTypeError::check_len(bytes, Self::SIZE)?;
+                let mut end = 0;
+                #(#decode)*
+                Ok(#pattern)
+            }
+
+            #[allow(unused_assignments, unused_mut, unused_variables)]
+            fn encode(
+                &self,
+                bytes: &mut [u8],
+            ) -> ::std::result::Result<(), ::wasmer::GuestTypeError> {
+                let bytes = ::wasmer::GuestTypeError::check_len_mut(bytes, Self::SIZE)?;
+                let #pattern = self;
+                let mut end = 0;
+                #(#encode)*
+                Ok(())
+            }
+        }
+    })
+}
+
+/// Parse the `#[guest(...)]` attributes, returning whether the struct is packed.
+fn parse_layout(input: &DeriveInput) -> Result<bool, Error> {
+    let mut packed = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("guest"))
+    {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new_spanned(meta, "expected #[guest(...)]")),
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("packed") => packed = true,
+                NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("layout") => {
+                    match &pair.lit {
+                        Lit::Str(layout) if layout.value() == "C" => {}
+                        lit => {
+                            return Err(Error::new_spanned(lit, "the only layout is \"C\""));
+                        }
+                    }
+                }
+                nested => {
+                    return Err(Error::new_spanned(
+                        nested,
+                        "expected `layout = \"C\"` or `packed`",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(packed)
+}
+
+/// Reject the field types that can never be in guest memory, with a clearer error than the
+/// missing trait implementation.
+fn check_field_type(ty: &Type) -> Result<(), Error> {
+    match ty {
+        Type::Reference(_) | Type::Ptr(_) => Err(Error::new_spanned(
+            ty,
+            "references and pointers cannot be stored in guest memory, use `WasmPtr` or \
+             `GuestVec` for guest pointers",
+        )),
+        Type::Array(array) => check_field_type(&array.elem),
+        Type::Group(group) => check_field_type(&group.elem),
+        Type::Paren(paren) => check_field_type(&paren.elem),
+        _ => Ok(()),
+    }
+}
@@ -1,20 +1,34 @@
 //! Define `UniversalArtifact` to allow compiling and instantiating to be
 //! done as separate steps.
 
+use crate::engine::CodeMemoryLease;
+use crate::hot_code::{PinMode, PinReport};
+use crate::interface::InterfaceFormat;
+use crate::provenance::Provenance;
+use crate::specialization::value_bits;
+use sha2::{Digest, Sha256};
 use std::collections::BTreeMap;
 use std::convert::TryFrom;
-use std::sync::Arc;
-use wasmer_engine::InstantiationError;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use wasmer_compiler::{ScheduleVersion, SourceLocation, SourceMap, UnusedItems};
+use wasmer_engine::{
+    ExternalDataError, ExternalDataErrorKind, GlobalFrameInfoRegistration, InstantiationError,
+    RuntimeError,
+};
 use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{
-    DataIndex, ElemIndex, FunctionIndex, GlobalInit, GlobalType, ImportCounts, LocalFunctionIndex,
-    LocalGlobalIndex, MemoryType, OwnedDataInitializer, OwnedTableInitializer, SignatureIndex,
-    TableType,
+    DataIndex, ElemIndex, ExternalDataInitializer, FunctionIndex, GlobalIndex, GlobalInit,
+    GlobalType, ImportCounts, InstanceConfig, LocalFunctionIndex, LocalGlobalIndex, MemoryType,
+    ModuleInfo, Mutability, OwnedDataInitializer, OwnedTableInitializer, SignatureIndex, TableType,
+    Type,
 };
 use wasmer_vm::{
-    Artifact, FunctionBodyPtr, FunctionExtent, InstanceHandle, Instantiatable, MemoryStyle,
-    Resolver, TableStyle, Tunables, VMImport, VMImportType, VMLocalFunction, VMOffsets,
-    VMSharedSignatureIndex,
+    Artifact, Counter, Export, ExportFunction, FunctionBodyPtr, FunctionExtent, Gauge, Imports,
+    InstanceArena, InstanceHandle, InstanceLayout, Instantiatable, MemoryImage, MemoryStyle,
+    MetricsSink, ModuleStyleHints, ReadOnlyMemory, RebindError, Resolver, TableStyle, Trap,
+    TrapCode, Tunables, VMFunction, VMFunctionBody, VMFunctionEnvironment, VMFunctionKind,
+    VMGlobal, VMImport, VMImportType, VMLocalFunction, VMOffsets, VMSharedSignatureIndex,
 };
 
 /// A compiled wasm module, containing everything necessary for instantiation.
@@ -31,12 +45,57 @@ pub struct UniversalArtifact {
     pub(crate) signatures: BoxedSlice<SignatureIndex, VMSharedSignatureIndex>,
     pub(crate) local_memories: Vec<(MemoryType, MemoryStyle)>,
     pub(crate) data_segments: Vec<OwnedDataInitializer>,
+    pub(crate) external_data_segments: Vec<ExternalDataInitializer>,
     pub(crate) passive_data: BTreeMap<DataIndex, Arc<[u8]>>,
     pub(crate) local_tables: Vec<(TableType, TableStyle)>,
     pub(crate) element_segments: Vec<OwnedTableInitializer>,
     // TODO: does this need to be a BTreeMap? Can it be a plain vector?
     pub(crate) passive_elements: BTreeMap<ElemIndex, Box<[FunctionIndex]>>,
     pub(crate) local_globals: Vec<(GlobalType, GlobalInit)>,
+    pub(crate) function_hashes: BoxedSlice<LocalFunctionIndex, [u8; 32]>,
+    pub(crate) wasm_hash: [u8; 32],
+    pub(crate) global_bindings: BTreeMap<GlobalIndex, GlobalInit>,
+    pub(crate) metering_schedule: Option<ScheduleVersion>,
+    pub(crate) specialization_hash: Option<[u8; 32]>,
+    pub(crate) source_map: Option<Arc<SourceMap>>,
+    pub(crate) unused_items: UnusedItems,
+    pub(crate) trap_imports: Vec<(FunctionIndex, TrapCode)>,
+    /// At least the parts of the module info describing the imports, exports and custom
+    /// sections.
+    pub(crate) interface: Arc<ModuleInfo>,
+    // Keeps the trap and backtrace information of the functions registered while alive.
+    pub(crate) frame_info_registration: Option<GlobalFrameInfoRegistration>,
+    // Keeps the code of the functions from being released while alive.
+    pub(crate) _code_memory_lease: Arc<CodeMemoryLease>,
+    /// Where to report the unloading of this artifact and the lifetime of its instances.
+    pub(crate) metrics_sink: Option<Arc<dyn MetricsSink>>,
+    /// The images of the local memories shared by the read-only instances, captured by the
+    /// first of them.
+    pub(crate) memory_images: Mutex<Option<Vec<Arc<MemoryImage>>>>,
+    /// The consensus hash of the artifact, computed when it is first asked for.
+    pub(crate) consensus_hash: Mutex<Option<[u8; 32]>>,
+    /// How the executable the artifact was loaded from was produced.
+    pub(crate) provenance: Provenance,
+    /// The start and length of the code of the hot functions, if there are any.
+    pub(crate) hot_code: Option<(FunctionBodyPtr, usize)>,
+    /// The number of bytes of the hot code locked in memory.
+    pub(crate) hot_code_locked: Mutex<usize>,
+}
+
+impl Drop for UniversalArtifact {
+    fn drop(&mut self) {
+        // The code memory outlives the artifact until it is reclaimed, so unlock it now.
+        let locked_bytes = *self.hot_code_locked.get_mut().unwrap();
+        if let (Some((start, _)), true) = (self.hot_code, locked_bytes > 0) {
+            crate::hot_code::unlock(start.0 as *const u8, locked_bytes);
+            if let Some(sink) = &self.metrics_sink {
+                sink.adjust(Gauge::PinnedCodeBytes, -(locked_bytes as i64));
+            }
+        }
+        if let Some(sink) = &self.metrics_sink {
+            sink.increment(Counter::ArtifactsUnloaded, 1);
+        }
+    }
 }
 
 impl UniversalArtifact {
@@ -49,31 +108,626 @@ impl UniversalArtifact {
         })
     }
 
-    /// Return the engine instance this artifact is loaded into.
-    pub fn engine(&self) -> &crate::UniversalEngine {
-        &self.engine
+    /// The extent of the code of the hot functions, which starts on a page boundary, or `None`
+    /// if the artifact has no hot functions.
+    ///
+    /// See [`Universal::hot_functions`](crate::Universal::hot_functions).
+    pub fn hot_code(&self) -> Option<FunctionExtent> {
+        self.hot_code
+            .map(|(address, length)| FunctionExtent { address, length })
     }
-}
 
-impl Instantiatable for UniversalArtifact {
-    type Error = InstantiationError;
+    /// Keep the code of the hot functions resident, as requested by `mode`.
+    ///
+    /// The pages holding the hot code are pinned, up to the end of the last of them. Failures
+    /// are reported in the [`PinReport`] and leave the code as it was. Locked pages stay locked
+    /// until the artifact is dropped, and are reported to the metrics sink as
+    /// [`Gauge::PinnedCodeBytes`].
+    pub fn pin_hot_code(&self, mode: PinMode) -> PinReport {
+        let (start, length) = match self.hot_code {
+            Some((start, length)) => (start.0 as *const u8, length),
+            None => (std::ptr::null(), 0),
+        };
+        let mut locked_bytes = self.hot_code_locked.lock().unwrap();
+        let report = crate::hot_code::pin(start, length, mode, *locked_bytes > 0);
+        if *locked_bytes == 0 && report.locked_bytes() > 0 {
+            *locked_bytes = report.locked_bytes();
+            if let Some(sink) = &self.metrics_sink {
+                sink.adjust(Gauge::PinnedCodeBytes, *locked_bytes as i64);
+            }
+        }
+        report
+    }
 
-    unsafe fn instantiate(
+    /// Return the exports of this artifact, by name.
+    pub fn exports(&self) -> &BTreeMap<String, wasmer_types::ExportIndex> {
+        &self.exports
+    }
+
+    /// Content hashes of the local functions in this artifact.
+    ///
+    /// See [`UniversalExecutable::function_hashes`](crate::UniversalExecutable::function_hashes).
+    pub fn function_hashes(&self) -> Vec<(LocalFunctionIndex, [u8; 32])> {
+        self.function_hashes
+            .iter()
+            .map(|(idx, hash)| (idx, *hash))
+            .collect()
+    }
+
+    /// The values of the imported globals this artifact is specialized for, with the module
+    /// and field names of these globals.
+    pub fn global_bindings(&self) -> Vec<(&str, &str, GlobalInit)> {
+        self.global_imports()
+            .filter_map(|(index, import)| {
+                let value = self.global_bindings.get(&index)?;
+                Some((&*import.module, &*import.field, *value))
+            })
+            .collect()
+    }
+
+    /// The hash identifying the binary and the bindings this artifact was specialized for, or
+    /// `None` if it is not specialized.
+    ///
+    /// See [`UniversalExecutable::specialization_hash`](crate::UniversalExecutable::specialization_hash).
+    pub fn specialization_hash(&self) -> Option<[u8; 32]> {
+        self.specialization_hash
+    }
+
+    /// The metering schedule the code of this artifact was compiled with, or `None` if it was
+    /// compiled without one.
+    ///
+    /// See [`UniversalEngine::compile_universal_with_schedule`](crate::UniversalEngine::compile_universal_with_schedule).
+    pub fn metering_schedule(&self) -> Option<ScheduleVersion> {
+        self.metering_schedule
+    }
+
+    /// The imported functions and the globals the code of this artifact never uses.
+    ///
+    /// See [`UniversalExecutable::unused_items`](crate::UniversalExecutable::unused_items).
+    pub fn unused_items(&self) -> &UnusedItems {
+        &self.unused_items
+    }
+
+    /// The hash of the binary this artifact was compiled from.
+    ///
+    /// See [`UniversalExecutable::wasm_hash`](crate::UniversalExecutable::wasm_hash).
+    pub fn wasm_hash(&self) -> [u8; 32] {
+        self.wasm_hash
+    }
+
+    /// A stable identifier of the executable this artifact was loaded from.
+    ///
+    /// See [`UniversalExecutable::artifact_id`](crate::UniversalExecutable::artifact_id).
+    pub fn artifact_id(&self) -> [u8; 32] {
+        crate::provenance::artifact_id(
+            &self.wasm_hash,
+            self.specialization_hash.as_ref(),
+            &self.provenance.engine_id,
+        )
+    }
+
+    /// Check that the instances of this artifact can run the code of `target` instead, with
+    /// `InstanceHandle::rebind`.
+    ///
+    /// `target` must be compiled from the same binary, specialized for the same bindings if
+    /// any, and for memories and tables of the same styles.
+    pub fn check_rebind_target(&self, target: &Self) -> Result<(), RebindError> {
+        if target.wasm_hash != self.wasm_hash
+            || target.specialization_hash != self.specialization_hash
+        {
+            return Err(RebindError::WasmMismatch);
+        }
+        let memory_styles = |artifact: &Self| {
+            let imported = artifact
+                .imports
+                .iter()
+                .filter_map(|import| match &import.ty {
+                    VMImportType::Memory(_, style) => Some(style.clone()),
+                    _ => None,
+                });
+            let local = artifact
+                .local_memories
+                .iter()
+                .map(|(_, style)| style.clone());
+            imported.chain(local).collect::<Vec<_>>()
+        };
+        let (expected, actual) = (memory_styles(self), memory_styles(target));
+        if actual != expected {
+            return Err(RebindError::LayoutMismatch(format!(
+                "memories compiled for {:?} instead of {:?}",
+                actual, expected
+            )));
+        }
+        let table_styles = |artifact: &Self| {
+            artifact
+                .local_tables
+                .iter()
+                .map(|(_, style)| style.clone())
+                .collect::<Vec<_>>()
+        };
+        let (expected, actual) = (table_styles(self), table_styles(target));
+        if actual != expected {
+            return Err(RebindError::LayoutMismatch(format!(
+                "tables compiled for {:?} instead of {:?}",
+                actual, expected
+            )));
+        }
+        Ok(())
+    }
+
+    /// A hash of everything that determines what the instances of this artifact compute: the
+    /// code of its functions, their signatures, its imports, exports and start function, and
+    /// the initial contents of its memories, tables and globals.
+    ///
+    /// Instances of artifacts with the same consensus hash, given the same imports, compute
+    /// the same results, whichever binary or serialized executable they were loaded from.
+    /// Names that only appear in traces and the [`provenance`](Self::provenance) are not
+    /// covered.
+    pub fn consensus_hash(&self) -> [u8; 32] {
+        *self
+            .consensus_hash
+            .lock()
+            .unwrap()
+            .get_or_insert_with(|| self.compute_consensus_hash())
+    }
+
+    fn compute_consensus_hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        let mut field = |bytes: &[u8]| {
+            hasher.update((bytes.len() as u64).to_le_bytes());
+            hasher.update(bytes);
+        };
+        for hash in self.function_hashes.values() {
+            field(hash);
+        }
+        for signature in self.interface.signatures.values() {
+            field(format!("{:?}", signature).as_bytes());
+        }
+        for signature in self.interface.functions.values() {
+            field(&signature.as_u32().to_le_bytes());
+        }
+        field(format!("{:?}", self.start_function).as_bytes());
+        field(format!("{:?}", self.exports).as_bytes());
+        field(format!("{:?}", self.interface.imports).as_bytes());
+        field(format!("{:?}", self.local_memories).as_bytes());
+        field(format!("{:?}", self.local_tables).as_bytes());
+        for (ty, init) in self.local_globals.iter() {
+            field(format!("{:?} {:?}", ty, init).as_bytes());
+            field(&value_bits(init).unwrap_or_default().to_le_bytes());
+        }
+        for (index, init) in self.global_bindings.iter() {
+            field(&index.as_u32().to_le_bytes());
+            field(&value_bits(init).unwrap_or_default().to_le_bytes());
+        }
+        for segment in self.data_segments.iter() {
+            field(format!("{:?}", segment.location).as_bytes());
+            field(&segment.data);
+        }
+        field(format!("{:?}", self.external_data_segments).as_bytes());
+        for (index, data) in self.passive_data.iter() {
+            field(&index.as_u32().to_le_bytes());
+            field(data);
+        }
+        field(format!("{:?}", self.element_segments).as_bytes());
+        field(format!("{:?}", self.passive_elements).as_bytes());
+        hasher.finalize().into()
+    }
+
+    /// How the executable this artifact was loaded from was produced.
+    pub fn provenance(&self) -> &Provenance {
+        &self.provenance
+    }
+
+    /// The name of the module, if it has one.
+    pub fn name(&self) -> Option<&str> {
+        self.interface.name.as_deref()
+    }
+
+    /// The name of the function `index`, from the name section of the module, if it has one.
+    pub fn function_name(&self, index: FunctionIndex) -> Option<&str> {
+        self.interface
+            .function_names
+            .get(&index)
+            .map(String::as_str)
+    }
+
+    /// Rename the module, in traces and interface descriptions.
+    pub fn set_name(&mut self, name: String) {
+        if let Some(registration) = &self.frame_info_registration {
+            registration.set_module_name(name.clone());
+        }
+        Arc::make_mut(&mut self.interface).name = Some(name);
+    }
+
+    /// The module info of this artifact, describing at least its imports, exports and custom
+    /// sections.
+    pub fn interface(&self) -> &ModuleInfo {
+        &self.interface
+    }
+
+    /// Describes the imports and exports of this artifact, with their types, in `format`.
+    ///
+    /// See [`UniversalExecutable::interface_text`](crate::UniversalExecutable::interface_text).
+    pub fn interface_text(&self, format: InterfaceFormat) -> String {
+        crate::interface::interface_text(&self.interface, format)
+    }
+
+    /// What instantiating this artifact allocates for the `VMContext` of the instance and
+    /// its function references, computed without instantiating it.
+    pub fn instance_layout(&self) -> InstanceLayout {
+        InstanceLayout::new(
+            &self.vmoffsets,
+            self.import_counts.functions as usize + self.functions.len(),
+        )
+    }
+
+    /// The total length of the data segments of this artifact, active and passive, including
+    /// the externalized ones.
+    pub fn data_size(&self) -> usize {
+        let inline: usize = self.data_segments.iter().map(|s| s.data.len()).sum();
+        let external: usize = self.external_data_segments.iter().map(|s| s.len).sum();
+        let passive: usize = self.passive_data.values().map(|data| data.len()).sum();
+        inline + external + passive
+    }
+
+    /// The module and field names of the imported function of the given index, if it is
+    /// imported.
+    pub fn function_import(&self, index: FunctionIndex) -> Option<(&str, &str)> {
+        self.imports
+            .iter()
+            .filter(|import| matches!(import.ty, VMImportType::Function { .. }))
+            .nth(index.index())
+            .map(|import| (&*import.module, &*import.field))
+    }
+
+    /// The import numbers and signatures of the imported functions the instances need not be
+    /// given, with the code of the trap they raise if they are not: those stripped from the
+    /// imports, and those the code raises a trap for instead of calling them.
+    fn stand_in_imports(&self) -> Vec<(u32, VMSharedSignatureIndex, TrapCode)> {
+        let functions = self
+            .imports
+            .iter()
+            .filter(|import| matches!(import.ty, VMImportType::Function { .. }))
+            .collect::<Vec<_>>();
+        let stripped = if self.unused_items.imports_stripped {
+            &self.unused_items.functions[..]
+        } else {
+            &[]
+        };
+        stripped
+            .iter()
+            .map(|index| (*index, TrapCode::UnreachableCodeReached))
+            .chain(self.trap_imports.iter().copied())
+            .filter_map(|(index, code)| {
+                let import = functions.get(index.index())?;
+                match import.ty {
+                    VMImportType::Function { sig, .. } => Some((import.import_no, sig, code)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// The imports of globals, with the indices of the globals.
+    fn global_imports(&self) -> impl Iterator<Item = (GlobalIndex, &VMImport)> {
+        self.imports
+            .iter()
+            .filter(|import| matches!(import.ty, VMImportType::Global(_)))
+            .enumerate()
+            .map(|(index, import)| (GlobalIndex::new(index), import))
+    }
+
+    /// Create the globals provided in place of the imports bound by specialization.
+    fn bound_globals(&self) -> Vec<(&VMImport, Arc<wasmer_vm::Global>)> {
+        self.global_imports()
+            .filter_map(|(index, import)| {
+                let value = self.global_bindings.get(&index)?;
+                let ty = match import.ty {
+                    VMImportType::Global(ty) => ty,
+                    _ => unreachable!(),
+                };
+                let global = Arc::new(wasmer_vm::Global::new(ty));
+                // SAFETY: nothing else has access to the new global yet.
+                unsafe {
+                    let definition = global.vmglobal().as_mut();
+                    match *value {
+                        GlobalInit::I32Const(value) => *definition.as_i32_mut() = value,
+                        GlobalInit::I64Const(value) => *definition.as_i64_mut() = value,
+                        GlobalInit::F32Const(value) => *definition.as_f32_mut() = value,
+                        GlobalInit::F64Const(value) => *definition.as_f64_mut() = value,
+                        value => unreachable!("globals cannot be bound to {:?}", value),
+                    }
+                }
+                Some((import, global))
+            })
+            .collect()
+    }
+
+    /// Check that the imported globals this artifact is specialized for have the values it
+    /// was compiled with.
+    fn check_global_bindings(&self, imports: &Imports) -> Result<(), InstantiationError> {
+        for (index, import) in self.global_imports() {
+            let bound = match self.global_bindings.get(&index) {
+                Some(bound) => bound,
+                None => continue,
+            };
+            let global = &imports.globals[index].from;
+            // SAFETY: bound globals are immutable.
+            let definition = unsafe { global.vmglobal().as_ref() };
+            let given = match global.ty().ty {
+                Type::I32 => GlobalInit::I32Const(definition.to_i32()),
+                Type::I64 => GlobalInit::I64Const(definition.to_i64()),
+                Type::F32 => GlobalInit::F32Const(definition.to_f32()),
+                Type::F64 => GlobalInit::F64Const(definition.to_f64()),
+                ty => unreachable!("{} globals cannot be bound", ty),
+            };
+            if value_bits(&given) != value_bits(bound) {
+                return Err(InstantiationError::SpecializationMismatch(format!(
+                    "{}.{} is bound to {:?}, but was given {:?}",
+                    import.module, import.field, bound, given
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the source location of the byte at `wasm_offset` in the module.
+    ///
+    /// Returns `None` if no source map was retained, see
+    /// [`Universal::retain_source_maps`](crate::Universal::retain_source_maps), or if it does
+    /// not cover the offset.
+    pub fn resolve_source(&self, wasm_offset: usize) -> Option<SourceLocation> {
+        self.source_map
+            .as_ref()?
+            .lookup(u32::try_from(wasm_offset).ok()?)
+    }
+
+    /// Obtain and verify the contents of the external data segments from the configured
+    /// data provider.
+    fn fetch_external_data(
+        &self,
+        config: &InstanceConfig,
+    ) -> Result<Vec<Vec<u8>>, InstantiationError> {
+        self.external_data_segments
+            .iter()
+            .map(|init| {
+                let error = |kind| {
+                    InstantiationError::ExternalData(ExternalDataError {
+                        segment_index: init.segment_index,
+                        hash: init.hash,
+                        kind,
+                    })
+                };
+                let provider = config
+                    .data_provider
+                    .as_ref()
+                    .ok_or_else(|| error(ExternalDataErrorKind::MissingProvider))?;
+                let data = provider
+                    .fetch(&init.hash)
+                    .map_err(|e| error(ExternalDataErrorKind::Fetch(e)))?;
+                if data.len() != init.len || <[u8; 32]>::from(Sha256::digest(&data)) != init.hash {
+                    return Err(error(ExternalDataErrorKind::HashMismatch));
+                }
+                Ok(data.into_owned())
+            })
+            .collect()
+    }
+
+    /// Check that `tunables` choose the styles the local memories were compiled for, as the
+    /// compiled code and the memories created by `tunables` would disagree otherwise.
+    fn check_memory_styles(&self, tunables: &dyn Tunables) -> Result<(), InstantiationError> {
+        for (index, (ty, compiled)) in (self.import_counts.memories..).zip(&self.local_memories) {
+            let chosen = tunables.memory_style_for(ty, &ModuleStyleHints::new(ty, false));
+            if chosen != *compiled {
+                return Err(InstantiationError::MemoryStyleMismatch(format!(
+                    "memory {} was compiled for {:?}, but the tunables choose {:?}",
+                    index, compiled, chosen
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the images of the local memories right after the data segments are applied,
+    /// capturing them on first use.
+    fn memory_images(
+        self: &Arc<Self>,
+        config: &InstanceConfig,
+    ) -> Result<Vec<Arc<MemoryImage>>, InstantiationError> {
+        let mut cached = self.memory_images.lock().unwrap();
+        if let Some(images) = &*cached {
+            return Ok(images.clone());
+        }
+        if self.import_counts.memories != 0 {
+            return Err(InstantiationError::ReadOnlyUnsupported(
+                "the module imports a memory".to_string(),
+            ));
+        }
+
+        // Interleave both kinds of segments back into module order, as later segments may
+        // overwrite earlier ones.
+        let external_data = self.fetch_external_data(config)?;
+        let mut inline_segments = self.data_segments.iter();
+        let mut external_segments = self
+            .external_data_segments
+            .iter()
+            .zip(external_data.iter())
+            .peekable();
+        let mut segments = Vec::new();
+        for segment_index in 0.. {
+            let segment = match external_segments.peek() {
+                Some((init, _)) if init.segment_index == segment_index => external_segments
+                    .next()
+                    .map(|(init, data)| (&init.location, &data[..])),
+                _ => inline_segments
+                    .next()
+                    .map(|init| (&init.location, &init.data[..])),
+            };
+            match segment {
+                Some(segment) => segments.push(segment),
+                None => break,
+            }
+        }
+
+        let mut images = Vec::with_capacity(self.local_memories.len());
+        for (index, (ty, _)) in self.local_memories.iter().enumerate() {
+            let len = ty.minimum.bytes().0;
+            let mut image_segments = Vec::new();
+            for (location, data) in segments.iter() {
+                if location.memory_index.index() != index {
+                    continue;
+                }
+                if location.base.is_some() {
+                    return Err(InstantiationError::ReadOnlyUnsupported(
+                        "a data segment is placed by a global".to_string(),
+                    ));
+                }
+                if location
+                    .offset
+                    .checked_add(data.len())
+                    .map_or(true, |end| end > len)
+                {
+                    return Err(InstantiationError::Start(RuntimeError::from_trap(
+                        Trap::lib(TrapCode::HeapAccessOutOfBounds),
+                    )));
+                }
+                image_segments.push((location.offset, *data));
+            }
+            let image = MemoryImage::new(ty.minimum, image_segments)
+                .map_err(|e| InstantiationError::Link(wasmer_engine::LinkError::Memory(e)))?;
+            images.push(Arc::new(image));
+        }
+        *cached = Some(images.clone());
+        let artifact = Arc::downgrade(self);
+        let mut engine = self.engine.inner_mut();
+        engine
+            .image_artifacts
+            .retain(|other| other.strong_count() > 0 && !other.ptr_eq(&artifact));
+        engine.image_artifacts.push(artifact);
+        Ok(images)
+    }
+
+    /// The bytes of the memory images captured for the read-only and copy-on-write instances,
+    /// if none uses them.
+    pub(crate) fn unused_memory_image_bytes(&self) -> usize {
+        image_bytes_if_unused(&self.memory_images.lock().unwrap())
+    }
+
+    /// Release the memory images captured for the read-only and copy-on-write instances if none
+    /// uses them, returning their bytes. The next such instance captures them again.
+    pub(crate) fn release_memory_images(&self) -> usize {
+        let mut cached = self.memory_images.lock().unwrap();
+        let bytes = image_bytes_if_unused(&cached);
+        if bytes > 0 {
+            *cached = None;
+        }
+        bytes
+    }
+
+    /// Create a read-only instance of this artifact.
+    ///
+    /// The local memories of all the read-only instances of an artifact map the same image
+    /// without write access, captured from the data segments by the first of them. The data
+    /// segments are thus neither applied nor, for external ones, fetched again by the later
+    /// instances. WebAssembly code writing to memory traps with
+    /// [`TrapCode::ReadOnlyInstance`], and `memory.grow` fails.
+    ///
+    /// Modules importing a memory, or placing a data segment with a global, are rejected.
+    /// Instantiation is finished by
+    /// [`InstanceHandle::finish_readonly_instantiation`].
+    ///
+    /// # Safety
+    ///
+    /// See [`Instantiatable::instantiate`].
+    pub unsafe fn instantiate_readonly(
         self: Arc<Self>,
         tunables: &dyn Tunables,
         resolver: &dyn Resolver,
         host_state: Box<dyn std::any::Any>,
-        config: wasmer_types::InstanceConfig,
-    ) -> Result<InstanceHandle, Self::Error> {
+        config: InstanceConfig,
+    ) -> Result<InstanceHandle, InstantiationError> {
+        let images = self.memory_images(&config)?;
+        self.instantiate_with(tunables, resolver, host_state, config, Some(images), None)
+    }
+
+    /// Create an instance of this artifact whose `VMContext` is allocated in `arena`.
+    ///
+    /// Otherwise the same as [`Instantiatable::instantiate`], whose safety requirements apply.
+    pub unsafe fn instantiate_in(
+        self: Arc<Self>,
+        arena: &Arc<InstanceArena>,
+        tunables: &dyn Tunables,
+        resolver: &dyn Resolver,
+        host_state: Box<dyn std::any::Any>,
+        config: InstanceConfig,
+    ) -> Result<InstanceHandle, InstantiationError> {
+        self.instantiate_with(tunables, resolver, host_state, config, None, Some(arena))
+    }
+
+    unsafe fn instantiate_with(
+        self: Arc<Self>,
+        tunables: &dyn Tunables,
+        resolver: &dyn Resolver,
+        host_state: Box<dyn std::any::Any>,
+        config: InstanceConfig,
+        memory_images: Option<Vec<Arc<MemoryImage>>>,
+        arena: Option<&Arc<InstanceArena>>,
+    ) -> Result<InstanceHandle, InstantiationError> {
+        let layout = self.instance_layout();
+        if let Some(limit) = config.max_instance_overhead_bytes {
+            if layout.total_bytes() > limit {
+                return Err(InstantiationError::OverheadExceeded { layout, limit });
+            }
+        }
+        self.check_memory_styles(tunables)?;
+        // Memories are mapped copy-on-write from the images of the read-only instances when
+        // the tunables ask for it and the images can be captured, and filled by copying the
+        // data segments otherwise.
+        let read_only = memory_images.is_some();
+        let memory_images = match memory_images {
+            None if tunables.copy_on_write_memories() && self.external_data_segments.is_empty() => {
+                self.memory_images(&config).ok()
+            }
+            images => images,
+        };
+        let call_sequence_global = config.call_sequence_global.as_ref().map(|_| {
+            Arc::new(wasmer_vm::Global::new(GlobalType::new(
+                Type::I64,
+                Mutability::Const,
+            )))
+        });
+        let bound_globals = if config.provide_bound_globals {
+            self.bound_globals()
+        } else {
+            Vec::new()
+        };
         let (imports, import_function_envs) = {
+            let mut provided = bound_globals
+                .iter()
+                .map(|(import, global)| (&*import.module, &*import.field, global))
+                .collect::<Vec<_>>();
+            if let (Some((module, field)), Some(global)) =
+                (&config.call_sequence_global, &call_sequence_global)
+            {
+                provided.push((module, field, global));
+            }
+            let resolver = StandInImportsResolver {
+                stand_ins: self.stand_in_imports(),
+                resolver,
+            };
+            let resolver = ProvidedGlobalsResolver {
+                globals: provided,
+                resolver: &resolver,
+            };
             let mut imports = wasmer_engine::resolve_imports(
                 &self.engine,
-                resolver,
+                &resolver,
                 &self.import_counts,
                 &self.imports,
                 &self.dynamic_function_trampolines,
             )
             .map_err(InstantiationError::Link)?;
+            self.check_global_bindings(&imports)?;
 
             // Get the `WasmerEnv::init_with_instance` function pointers and the pointers
             // to the envs to call it on.
@@ -82,21 +736,38 @@ impl Instantiatable for UniversalArtifact {
             (imports, import_function_envs)
         };
 
-        let (allocator, memory_definition_locations, table_definition_locations) =
-            wasmer_vm::InstanceAllocator::new(self.vmoffsets.clone());
+        let (allocator, memory_definition_locations, table_definition_locations) = match arena {
+            Some(arena) => wasmer_vm::InstanceAllocator::new_in(self.vmoffsets.clone(), arena),
+            None => wasmer_vm::InstanceAllocator::new(self.vmoffsets.clone()),
+        };
 
         // Memories
         let mut memories: PrimaryMap<wasmer_types::LocalMemoryIndex, _> =
             PrimaryMap::with_capacity(self.local_memories.len());
         for (idx, (ty, style)) in (self.import_counts.memories..).zip(self.local_memories.iter()) {
-            let memory = tunables
-                .create_vm_memory(&ty, &style, memory_definition_locations[idx as usize])
-                .map_err(|e| {
-                    InstantiationError::Link(wasmer_engine::LinkError::Resource(format!(
-                        "Failed to create memory: {}",
-                        e
-                    )))
-                })?;
+            let location = memory_definition_locations[idx as usize];
+            let memory: Arc<dyn wasmer_vm::Memory> = match &memory_images {
+                Some(images) if read_only => Arc::new(
+                    ReadOnlyMemory::from_definition(
+                        Arc::clone(&images[memories.len()]),
+                        ty,
+                        style,
+                        location,
+                    )
+                    .map_err(|e| InstantiationError::Link(wasmer_engine::LinkError::Memory(e)))?,
+                ),
+                Some(images) => tunables
+                    .create_vm_memory_from_image(
+                        Arc::clone(&images[memories.len()]),
+                        &ty,
+                        &style,
+                        location,
+                    )
+                    .map_err(|e| InstantiationError::Link(wasmer_engine::LinkError::Memory(e)))?,
+                None => tunables
+                    .create_vm_memory(&ty, &style, location)
+                    .map_err(|e| InstantiationError::Link(wasmer_engine::LinkError::Memory(e)))?,
+            };
             memories.push(memory);
         }
 
@@ -117,8 +788,24 @@ impl Instantiatable for UniversalArtifact {
             globals.push(Arc::new(wasmer_vm::Global::new(*ty)));
         }
 
+        // The images already hold the data segments.
+        let external_data = match memory_images {
+            Some(_) => Vec::new(),
+            None => self.fetch_external_data(&config)?,
+        };
         let passive_data = self.passive_data.clone();
-        Ok(InstanceHandle::new(
+        let metrics_sink = self.metrics_sink.clone();
+        let (diagnostics_sink, diagnostics_level, memory_arbiter, instance_registry) = {
+            let engine = self.engine.inner();
+            (
+                engine.diagnostics_sink.clone(),
+                engine.default_diagnostics_level,
+                engine.memory_arbiter.clone(),
+                engine.instance_registry.clone(),
+            )
+        };
+        let artifact = Arc::downgrade(&self);
+        let handle = InstanceHandle::new(
             self,
             allocator,
             memories.into_boxed_slice(),
@@ -126,13 +813,156 @@ impl Instantiatable for UniversalArtifact {
             globals.into_boxed_slice(),
             imports,
             passive_data,
+            external_data,
+            metrics_sink,
+            diagnostics_sink,
+            diagnostics_level,
+            memory_arbiter,
+            call_sequence_global,
             host_state,
             import_function_envs,
             config,
-        ))
+        );
+        debug_assert_eq!(handle.allocated_bytes(), layout.total_bytes());
+        if memory_images.is_some() {
+            // Safety: the images were captured after applying the data segments.
+            handle.skip_data_segments();
+        }
+        if let Some(registry) = instance_registry {
+            registry.register(&handle, artifact);
+        }
+        Ok(handle)
+    }
+
+    /// Return the engine instance this artifact is loaded into.
+    pub fn engine(&self) -> &crate::UniversalEngine {
+        &self.engine
+    }
+}
+
+impl Instantiatable for UniversalArtifact {
+    type Error = InstantiationError;
+
+    unsafe fn instantiate(
+        self: Arc<Self>,
+        tunables: &dyn Tunables,
+        resolver: &dyn Resolver,
+        host_state: Box<dyn std::any::Any>,
+        config: InstanceConfig,
+    ) -> Result<InstanceHandle, Self::Error> {
+        self.instantiate_with(tunables, resolver, host_state, config, None, None)
+    }
+}
+
+/// The bytes of the memory images `cached`, or 0 if an instance uses them.
+///
+/// Images are only shared under the lock of the cache, so that none can start being used while
+/// it is held.
+fn image_bytes_if_unused(cached: &Option<Vec<Arc<MemoryImage>>>) -> usize {
+    match cached {
+        Some(images) if images.iter().all(|image| Arc::strong_count(image) == 1) => {
+            images.iter().map(|image| image.size().bytes().0).sum()
+        }
+        _ => 0,
+    }
+}
+
+/// Provides the globals created by the runtime for an instance, such as its call sequence
+/// global, falling back to the resolver of the user for the other imports.
+struct ProvidedGlobalsResolver<'a> {
+    globals: Vec<(&'a str, &'a str, &'a Arc<wasmer_vm::Global>)>,
+    resolver: &'a dyn Resolver,
+}
+
+impl ProvidedGlobalsResolver<'_> {
+    fn provided(&self, module: &str, field: &str) -> Option<Export> {
+        let (_, _, global) = self
+            .globals
+            .iter()
+            .rev()
+            .find(|(m, f, _)| *m == module && *f == field)?;
+        Some(Export::Global(VMGlobal {
+            from: Arc::clone(global),
+            instance_ref: None,
+        }))
+    }
+}
+
+impl Resolver for ProvidedGlobalsResolver<'_> {
+    fn resolve(&self, index: u32, module: &str, field: &str) -> Option<Export> {
+        self.provided(module, field)
+            .or_else(|| self.resolver.resolve(index, module, field))
+    }
+
+    fn try_resolve(
+        &self,
+        index: u32,
+        module: &str,
+        field: &str,
+    ) -> Result<Option<Export>, Box<dyn Error + Send + Sync>> {
+        match self.provided(module, field) {
+            Some(export) => Ok(Some(export)),
+            None => self.resolver.try_resolve(index, module, field),
+        }
+    }
+}
+
+/// Resolves the imported functions an artifact does not need to a function raising a trap when
+/// called, when the resolver does not provide them.
+struct StandInImportsResolver<'a> {
+    stand_ins: Vec<(u32, VMSharedSignatureIndex, TrapCode)>,
+    resolver: &'a dyn Resolver,
+}
+
+impl StandInImportsResolver<'_> {
+    fn stand_in(&self, index: u32) -> Option<Export> {
+        let (_, signature, code) = self.stand_ins.iter().find(|(no, ..)| *no == index)?;
+        Some(Export::Function(ExportFunction {
+            vm_function: VMFunction {
+                address: stand_in_import as *const () as *const VMFunctionBody,
+                // The environment of the stand-in is the raw code of its trap, not a pointer.
+                vmctx: VMFunctionEnvironment {
+                    host_env: code.to_raw() as usize as *mut std::ffi::c_void,
+                },
+                signature: *signature,
+                kind: VMFunctionKind::Static,
+                call_trampoline: None,
+                instance_ref: None,
+            },
+            metadata: None,
+        }))
     }
 }
 
+impl Resolver for StandInImportsResolver<'_> {
+    fn resolve(&self, index: u32, module: &str, field: &str) -> Option<Export> {
+        self.resolver
+            .resolve(index, module, field)
+            .or_else(|| self.stand_in(index))
+    }
+
+    fn try_resolve(
+        &self,
+        index: u32,
+        module: &str,
+        field: &str,
+    ) -> Result<Option<Export>, Box<dyn Error + Send + Sync>> {
+        Ok(self
+            .resolver
+            .try_resolve(index, module, field)?
+            .or_else(|| self.stand_in(index)))
+    }
+}
+
+/// The body of the imports an artifact does not need and no function was given for. The code
+/// of the artifact never calls them, so this only stands in for a function that must not run
+/// when it is called through a table or an export, and raises the trap whose raw code
+/// `env` is.
+extern "C" fn stand_in_import(env: *mut std::ffi::c_void) {
+    let code = TrapCode::from_raw(env as usize as u32).expect("invalid trap code");
+    unsafe { wasmer_vm::raise_lib_trap(Trap::lib(code)) }
+}
+
 impl Artifact for UniversalArtifact {
     fn offsets(&self) -> &wasmer_vm::VMOffsets {
         &self.vmoffsets
@@ -158,6 +988,10 @@ impl Artifact for UniversalArtifact {
         &self.data_segments[..]
     }
 
+    fn external_data_segments(&self) -> &[ExternalDataInitializer] {
+        &self.external_data_segments[..]
+    }
+
     fn globals(&self) -> &[(GlobalType, GlobalInit)] {
         &self.local_globals[..]
     }
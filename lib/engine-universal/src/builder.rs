@@ -7,8 +7,16 @@ pub struct Universal {
     compiler_config: Option<Box<dyn CompilerConfig>>,
     target: Option<Target>,
     features: Option<Features>,
+    externalize_data_segments: Option<usize>,
+    retain_source_maps: bool,
+    source_map_size_limit: usize,
+    hot_functions: Vec<String>,
+    pipelined_loading: bool,
 }
 
+/// The default for [`Universal::source_map_size_limit`].
+const DEFAULT_SOURCE_MAP_SIZE_LIMIT: usize = 1 << 20;
+
 impl Universal {
     /// Create a new Universal
     pub fn new<T>(compiler_config: T) -> Self
@@ -19,6 +27,11 @@ impl Universal {
             compiler_config: Some(compiler_config.into()),
             target: None,
             features: None,
+            externalize_data_segments: None,
+            retain_source_maps: false,
+            source_map_size_limit: DEFAULT_SOURCE_MAP_SIZE_LIMIT,
+            hot_functions: Vec::new(),
+            pipelined_loading: false,
         }
     }
 
@@ -28,6 +41,11 @@ impl Universal {
             compiler_config: None,
             target: None,
             features: None,
+            externalize_data_segments: None,
+            retain_source_maps: false,
+            source_map_size_limit: DEFAULT_SOURCE_MAP_SIZE_LIMIT,
+            hot_functions: Vec::new(),
+            pipelined_loading: false,
         }
     }
 
@@ -43,6 +61,67 @@ impl Universal {
         self
     }
 
+    /// Keep active data segments of at least `min_size` bytes out of compiled executables.
+    ///
+    /// Only the hash, length and location of such segments are recorded, and their contents
+    /// must be supplied by a [`DataProvider`](wasmer_types::DataProvider) when the module is
+    /// instantiated.
+    pub fn externalize_data_segments(mut self, min_size: usize) -> Self {
+        self.externalize_data_segments = Some(min_size);
+        self
+    }
+
+    /// Keep a table mapping module offsets to source locations in compiled executables.
+    ///
+    /// The table is built from the debug information embedded in the module: a source map
+    /// referenced by a `sourceMappingURL` section, if it is embedded as a `data:` URL and the
+    /// `source-map-json` feature is enabled, or else the DWARF line tables. It lets [`FrameInfo::source_location`] and
+    /// [`UniversalArtifact::resolve_source`] report where in the source a trap happened.
+    ///
+    /// [`FrameInfo::source_location`]: wasmer_engine::FrameInfo::source_location
+    /// [`UniversalArtifact::resolve_source`]: crate::UniversalArtifact::resolve_source
+    pub fn retain_source_maps(mut self, retain: bool) -> Self {
+        self.retain_source_maps = retain;
+        self
+    }
+
+    /// Drop the source maps that would take more than `size` bytes, 1 MiB by default.
+    ///
+    /// See [`Universal::retain_source_maps`].
+    pub fn source_map_size_limit(mut self, size: usize) -> Self {
+        self.source_map_size_limit = size;
+        self
+    }
+
+    /// Place the code of the given functions first in the code memory of the artifacts, next
+    /// to each other and in this order, so that
+    /// [`UniversalArtifact::pin_hot_code`](crate::UniversalArtifact::pin_hot_code) can pin
+    /// just that code.
+    ///
+    /// Functions are given by export name or, if not exported, by function index. Functions a
+    /// module does not have are ignored, so that one engine can be used for all modules. The
+    /// layout is recorded in compiled executables and kept when they are deserialized.
+    pub fn hot_functions(mut self, functions: Vec<String>) -> Self {
+        self.hot_functions = functions;
+        self
+    }
+
+    /// Place the functions of modules into code memory as soon as they are compiled, while
+    /// the others are still compiling, when compiling and loading them at once.
+    ///
+    /// This overlaps compiling and loading large modules, see
+    /// [`UniversalEngine::compile_and_load_universal`](crate::UniversalEngine::compile_and_load_universal).
+    /// Code memory is reserved for the functions before they are compiled, in proportion to
+    /// the size of the binary, and what they do not use is given back. Modules are loaded
+    /// once compiled if there is not enough code memory to reserve, or too little reserved,
+    /// or if hot functions are set, as they are placed before the others.
+    ///
+    /// This is disabled by default: the loader only overlaps compiling on a spare core.
+    pub fn pipelined_loading(mut self, enable: bool) -> Self {
+        self.pipelined_loading = enable;
+        self
+    }
+
     /// Build the `UniversalEngine` for this configuration
     #[cfg(feature = "compiler")]
     pub fn engine(self) -> UniversalEngine {
@@ -52,7 +131,16 @@ impl Universal {
                 .features
                 .unwrap_or_else(|| compiler_config.default_features_for_target(&target));
             let compiler = compiler_config.compiler();
-            UniversalEngine::new(compiler, target, features)
+            let engine = UniversalEngine::new(compiler, target, features);
+            let mut inner = engine.inner_mut();
+            inner.externalize_data_segments = self.externalize_data_segments;
+            if self.retain_source_maps {
+                inner.source_map_size_limit = Some(self.source_map_size_limit);
+            }
+            inner.hot_functions = self.hot_functions;
+            inner.pipelined_loading = self.pipelined_loading;
+            drop(inner);
+            engine
         } else {
             UniversalEngine::headless()
         }
@@ -3,8 +3,9 @@
 
 //! Memory management for executable code.
 use crate::unwind::UnwindRegistry;
+use std::sync::Arc;
 use wasmer_compiler::{CompiledFunctionUnwindInfoRef, CustomSectionRef, FunctionBodyRef};
-use wasmer_vm::{Mmap, VMFunctionBody};
+use wasmer_vm::{PageAllocator, Pages, SystemPageAllocator, VMFunctionBody};
 
 /// The optimal alignment for functions.
 ///
@@ -20,8 +21,10 @@ const DATA_SECTION_ALIGNMENT: usize = 64;
 /// Memory manager for executable code.
 pub struct CodeMemory {
     unwind_registry: UnwindRegistry,
-    mmap: Mmap,
+    pages: Pages,
     start_of_nonexecutable_pages: usize,
+    /// The number of bytes taken by the functions placed with [`CodeMemory::append_function`].
+    appended_len: usize,
 }
 
 impl CodeMemory {
@@ -29,17 +32,73 @@ impl CodeMemory {
     pub fn new() -> Self {
         Self {
             unwind_registry: UnwindRegistry::new(),
-            mmap: Mmap::new(),
+            pages: Pages::empty(Arc::new(SystemPageAllocator::default())),
             start_of_nonexecutable_pages: 0,
+            appended_len: 0,
         }
     }
 
+    /// Create a `CodeMemory` with at least `len` bytes already mapped, which
+    /// [`CodeMemory::allocate`] uses if they are enough.
+    pub fn with_capacity(len: usize) -> Result<Self, String> {
+        Self::with_allocator(Arc::new(SystemPageAllocator::default()), len)
+    }
+
+    /// Create a `CodeMemory` with at least `len` bytes already allocated by `allocator`, which
+    /// also allocates the pages [`CodeMemory::allocate`] needs if they are not enough.
+    pub fn with_allocator(allocator: Arc<dyn PageAllocator>, len: usize) -> Result<Self, String> {
+        Ok(Self {
+            unwind_registry: UnwindRegistry::new(),
+            pages: Pages::executable(allocator, len)?,
+            start_of_nonexecutable_pages: 0,
+            appended_len: 0,
+        })
+    }
+
+    /// The number of bytes mapped for the code and sections.
+    pub fn mapped_len(&self) -> usize {
+        self.pages.len()
+    }
+
     /// Mutably get the UnwindRegistry.
     pub fn unwind_registry_mut(&mut self) -> &mut UnwindRegistry {
         &mut self.unwind_registry
     }
 
+    /// Copy `func` right after the functions appended so far, if the mapped pages have room
+    /// for it.
+    ///
+    /// This places functions one at a time, as they become available, before
+    /// [`CodeMemory::allocate`] places the rest of the code after them.
+    pub fn append_function(&mut self, func: FunctionBodyRef<'_>) -> Option<&mut [VMFunctionBody]> {
+        let len = round_up(
+            Self::function_allocation_size(func),
+            ARCH_FUNCTION_ALIGNMENT,
+        );
+        let end = self.appended_len.checked_add(len)?;
+        if end > self.pages.len() {
+            return None;
+        }
+        let buf = &mut self.pages.as_mut_slice()[self.appended_len..end];
+        self.appended_len = end;
+        Some(Self::copy_function(&mut self.unwind_registry, func, buf))
+    }
+
+    /// The number of bytes taken by the functions placed with
+    /// [`CodeMemory::append_function`].
+    pub fn appended_len(&self) -> usize {
+        self.appended_len
+    }
+
+    /// Give back the mapped pages past the first `len` bytes, if the page allocator can.
+    pub fn shrink(&mut self, len: usize) {
+        self.pages.shrink(len);
+    }
+
     /// Allocate a single contiguous block of memory for the functions and custom sections, and copy the data in place.
+    ///
+    /// The block starts after the functions appended with [`CodeMemory::append_function`],
+    /// if any, in which case the mapped pages must have room for it.
     pub fn allocate(
         &mut self,
         functions: &[FunctionBodyRef<'_>],
@@ -50,41 +109,32 @@ impl CodeMemory {
         let mut data_section_result = vec![];
         let mut executable_section_result = vec![];
 
-        let page_size = region::page::size();
+        let page_size = self.pages.allocator().page_size();
 
-        // 1. Calculate the total size, that is:
-        // - function body size, including all trampolines
-        // -- windows unwind info
-        // -- padding between functions
-        // - executable section body
-        // -- padding between executable sections
-        // - padding until a new page to change page permissions
-        // - data section body size
-        // -- padding between data sections
-
-        let total_len = round_up(
-            functions.iter().fold(0, |acc, func| {
-                round_up(
-                    acc + Self::function_allocation_size(*func),
-                    ARCH_FUNCTION_ALIGNMENT,
-                )
-            }) + executable_sections.iter().fold(0, |acc, exec| {
-                round_up(acc + exec.bytes.len(), ARCH_FUNCTION_ALIGNMENT)
-            }),
+        // 1. Calculate the total size.
+
+        let total_len = Self::allocation_size_from(
+            self.appended_len,
+            functions,
+            executable_sections,
+            data_sections,
             page_size,
-        ) + data_sections.iter().fold(0, |acc, data| {
-            round_up(acc + data.bytes.len(), DATA_SECTION_ALIGNMENT)
-        });
+        );
 
-        // 2. Allocate the pages. Mark them all read-write.
+        // 2. Allocate the pages, unless enough are mapped already. Mark them all read-write.
 
-        self.mmap = Mmap::with_at_least(total_len)?;
+        if self.pages.len() < total_len {
+            if self.appended_len > 0 {
+                return Err("no room left after the appended functions".to_string());
+            }
+            self.pages = Pages::executable(self.pages.allocator().clone(), total_len)?;
+        }
 
         // 3. Determine where the pointers to each function, executable section
         // or data section are. Copy the functions. Collect the addresses of each and return them.
 
-        let mut bytes = 0;
-        let mut buf = self.mmap.as_mut_slice();
+        let mut bytes = self.appended_len;
+        let mut buf = &mut self.pages.as_mut_slice()[bytes..];
         for func in functions {
             let len = round_up(
                 Self::function_allocation_size(*func),
@@ -135,20 +185,88 @@ impl CodeMemory {
         ))
     }
 
+    /// The number of bytes [`CodeMemory::allocate`] maps for the given functions and custom
+    /// sections.
+    ///
+    /// This covers the function bodies, including all trampolines and Windows unwind
+    /// information, and the executable sections, each padded to the function alignment, then
+    /// padding until a new page to change page permissions, then the data sections padded to
+    /// their alignment, all rounded up to whole pages.
+    pub fn allocation_size(
+        functions: &[FunctionBodyRef<'_>],
+        executable_sections: &[CustomSectionRef<'_>],
+        data_sections: &[CustomSectionRef<'_>],
+    ) -> usize {
+        let page_size = region::page::size();
+        Self::allocation_size_with_page_size(
+            functions,
+            executable_sections,
+            data_sections,
+            page_size,
+        )
+    }
+
+    /// [`CodeMemory::allocation_size`] for pages of `page_size` bytes.
+    pub fn allocation_size_with_page_size(
+        functions: &[FunctionBodyRef<'_>],
+        executable_sections: &[CustomSectionRef<'_>],
+        data_sections: &[CustomSectionRef<'_>],
+        page_size: usize,
+    ) -> usize {
+        Self::allocation_size_from(0, functions, executable_sections, data_sections, page_size)
+    }
+
+    /// The number of bytes [`CodeMemory::allocate`] needs to place the given functions and
+    /// custom sections after the functions already appended, counting those.
+    pub fn allocation_size_after_appended(
+        &self,
+        functions: &[FunctionBodyRef<'_>],
+        executable_sections: &[CustomSectionRef<'_>],
+        data_sections: &[CustomSectionRef<'_>],
+    ) -> usize {
+        let page_size = self.pages.allocator().page_size();
+        Self::allocation_size_from(
+            self.appended_len,
+            functions,
+            executable_sections,
+            data_sections,
+            page_size,
+        )
+    }
+
+    /// [`CodeMemory::allocation_size_with_page_size`] for code starting `start` bytes in.
+    fn allocation_size_from(
+        start: usize,
+        functions: &[FunctionBodyRef<'_>],
+        executable_sections: &[CustomSectionRef<'_>],
+        data_sections: &[CustomSectionRef<'_>],
+        page_size: usize,
+    ) -> usize {
+        let len = round_up(
+            functions.iter().fold(start, |acc, func| {
+                round_up(
+                    acc + Self::function_allocation_size(*func),
+                    ARCH_FUNCTION_ALIGNMENT,
+                )
+            }) + executable_sections.iter().fold(0, |acc, exec| {
+                round_up(acc + exec.bytes.len(), ARCH_FUNCTION_ALIGNMENT)
+            }),
+            page_size,
+        ) + data_sections.iter().fold(0, |acc, data| {
+            round_up(acc + data.bytes.len(), DATA_SECTION_ALIGNMENT)
+        });
+        round_up(len, page_size)
+    }
+
     /// Apply the page permissions.
     pub fn publish(&mut self) {
-        if self.mmap.is_empty() || self.start_of_nonexecutable_pages == 0 {
+        if self.pages.is_empty() || self.start_of_nonexecutable_pages == 0 {
             return;
         }
-        assert!(self.mmap.len() >= self.start_of_nonexecutable_pages);
-        unsafe {
-            region::protect(
-                self.mmap.as_mut_ptr(),
-                self.start_of_nonexecutable_pages,
-                region::Protection::READ_EXECUTE,
-            )
-        }
-        .expect("unable to make memory readonly and executable");
+        let page_size = self.pages.allocator().page_size();
+        self.pages
+            .make_executable(round_up(self.start_of_nonexecutable_pages, page_size))
+            .expect("unable to make memory readonly and executable");
     }
 
     /// Calculates the allocation size of the given compiled function.
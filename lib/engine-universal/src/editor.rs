@@ -0,0 +1,295 @@
+//! Editing the metadata of serialized executables without recompiling them.
+//!
+//! The metadata of an executable is what the engine does not compile: the name of the module,
+//! the names its functions are exported under, the signature and the custom metadata attached
+//! by the embedder. Everything else, starting with the code, stays as it was compiled, which
+//! [`UniversalExecutable::code_hash`] lets anyone check.
+
+use crate::executable::UniversalExecutableRef;
+use crate::UniversalExecutable;
+use rkyv::ser::serializers::AllocSerializer;
+use rkyv::with::Inline;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use wasmer_compiler::{
+    CompiledFunctionFrameInfo, CustomSection, Dwarf, Features, FunctionBody, JumpTableOffsets,
+    Relocation, SectionIndex, TrampolinesSection,
+};
+use wasmer_engine::{DeserializeError, Executable};
+use wasmer_types::entity::PrimaryMap;
+use wasmer_types::{
+    Classify, ErrorCode, ExportIndex, FailureKind, FunctionIndex, FunctionType, GlobalIndex,
+    GlobalInit, HasErrorCode, LocalFunctionIndex, MemoryIndex, SignatureIndex, TableIndex,
+};
+use wasmer_vm::{MemoryStyle, TableStyle};
+
+/// An error editing the metadata of an executable.
+#[derive(thiserror::Error, Debug)]
+pub enum ArtifactEditError {
+    /// The bytes are not a serialized executable.
+    #[error("could not open the executable: {0}")]
+    Deserialize(#[from] DeserializeError),
+
+    /// An entry point refers to a function the executable does not have.
+    #[error(
+        "entry point `{name}` refers to function {} which does not exist [{}]",
+        index.as_u32(),
+        ErrorCode::ArtifactEditUnknownFunction
+    )]
+    UnknownFunction {
+        /// The name of the entry point.
+        name: String,
+        /// The function it refers to.
+        index: FunctionIndex,
+    },
+
+    /// An entry point does not have the signature recorded for its function.
+    #[error(
+        "entry point `{name}` expects {expected} but its function has {recorded} [{}]",
+        ErrorCode::ArtifactEditSignatureMismatch
+    )]
+    SignatureMismatch {
+        /// The name of the entry point.
+        name: String,
+        /// The signature given for the entry point.
+        expected: FunctionType,
+        /// The signature recorded for the function.
+        recorded: FunctionType,
+    },
+
+    /// Two exports of the edited executable have the same name.
+    #[error(
+        "`{0}` is exported more than once [{}]",
+        ErrorCode::ArtifactEditDuplicateExport
+    )]
+    DuplicateExport(String),
+
+    /// The edit would change the code of the executable.
+    #[error(
+        "the edit would change the code of the executable [{}]",
+        ErrorCode::ArtifactEditCodeChanged
+    )]
+    CodeChanged,
+
+    /// The edited executable could not be serialized.
+    #[error(
+        "could not serialize the edited executable: {0} [{}]",
+        ErrorCode::Serialize
+    )]
+    Serialize(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl HasErrorCode for ArtifactEditError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Deserialize(error) => error.code(),
+            Self::UnknownFunction { .. } => ErrorCode::ArtifactEditUnknownFunction,
+            Self::SignatureMismatch { .. } => ErrorCode::ArtifactEditSignatureMismatch,
+            Self::DuplicateExport(_) => ErrorCode::ArtifactEditDuplicateExport,
+            Self::CodeChanged => ErrorCode::ArtifactEditCodeChanged,
+            Self::Serialize(_) => ErrorCode::Serialize,
+        }
+    }
+}
+
+impl Classify for ArtifactEditError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::Deserialize(error) => error.failure_kind(),
+            Self::UnknownFunction { .. }
+            | Self::SignatureMismatch { .. }
+            | Self::DuplicateExport(_)
+            | Self::CodeChanged
+            | Self::Serialize(_) => FailureKind::Permanent,
+        }
+    }
+}
+
+/// Edits the metadata of a serialized executable, leaving its code as it was compiled.
+///
+/// Each edit is checked against the executable, and [`finish`](Self::finish) checks that the
+/// [`code_hash`](UniversalExecutable::code_hash) did not change before serializing the edited
+/// executable, which loads like any other. There is no way to edit the code, relocations or
+/// target of the executable.
+pub struct ArtifactEditor {
+    executable: UniversalExecutable,
+    // Kept out of `executable` so that it is not covered by the signing digest.
+    signature_block: Option<Vec<u8>>,
+    code_hash: [u8; 32],
+}
+
+impl ArtifactEditor {
+    /// Open the serialized executable `bytes` for editing.
+    ///
+    /// # Safety
+    ///
+    /// See [`UniversalExecutableRef::deserialize`].
+    pub unsafe fn open(bytes: &[u8]) -> Result<Self, ArtifactEditError> {
+        let mut executable = UniversalExecutableRef::with_aligned(bytes, |bytes| {
+            UniversalExecutableRef::deserialize(bytes)?.to_owned()
+        })?;
+        let signature_block = executable.signature_block.take();
+        let code_hash = executable.code_hash();
+        Ok(Self {
+            executable,
+            signature_block,
+            code_hash,
+        })
+    }
+
+    /// The executable being edited.
+    pub fn executable(&self) -> &UniversalExecutable {
+        &self.executable
+    }
+
+    /// Set the name of the module, as reported in traces and interface descriptions.
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        Arc::make_mut(&mut self.executable.compile_info.module).name = Some(name.into());
+    }
+
+    /// Replace the functions exported by the executable with `entry_points`, each a name, a
+    /// function and the signature the embedder expects it to have.
+    ///
+    /// Exports of tables, memories and globals are kept. The executable is left as it was if
+    /// a function does not exist, does not have the expected signature, or if two exports
+    /// would have the same name.
+    pub fn set_entry_points<I>(&mut self, entry_points: I) -> Result<(), ArtifactEditError>
+    where
+        I: IntoIterator<Item = (String, FunctionIndex, FunctionType)>,
+    {
+        let module = &self.executable.compile_info.module;
+        let mut exports = module
+            .exports
+            .iter()
+            .filter(|(_, index)| !matches!(index, ExportIndex::Function(_)))
+            .map(|(name, index)| (name.clone(), index.clone()))
+            .collect::<Vec<_>>();
+        for (name, index, expected) in entry_points {
+            let signature = match module.functions.get(index) {
+                Some(signature) => *signature,
+                None => return Err(ArtifactEditError::UnknownFunction { name, index }),
+            };
+            let recorded = &module.signatures[signature];
+            if *recorded != expected {
+                return Err(ArtifactEditError::SignatureMismatch {
+                    name,
+                    expected,
+                    recorded: recorded.clone(),
+                });
+            }
+            exports.push((name, ExportIndex::Function(index)));
+        }
+        let mut names = HashSet::new();
+        for (name, _) in exports.iter() {
+            if !names.insert(name) {
+                return Err(ArtifactEditError::DuplicateExport(name.clone()));
+            }
+        }
+        Arc::make_mut(&mut self.executable.compile_info.module).exports =
+            exports.into_iter().collect();
+        Ok(())
+    }
+
+    /// The signature attached to the executable, if any.
+    pub fn signature_block(&self) -> Option<&[u8]> {
+        self.signature_block.as_deref()
+    }
+
+    /// Attach `block` as the signature of the executable, returning the previous one.
+    ///
+    /// The engine does not interpret the signature. Signers sign the
+    /// [`signing_digest`](Self::signing_digest) of the executable, which covers everything but
+    /// the signature, so signing is the last edit.
+    pub fn replace_signature_block(&mut self, block: Vec<u8>) -> Option<Vec<u8>> {
+        self.signature_block.replace(block)
+    }
+
+    /// Attach `bytes` to the executable under `key`, replacing what was attached under it.
+    pub fn add_custom_metadata(&mut self, key: impl Into<String>, bytes: Vec<u8>) {
+        self.executable.custom_metadata.insert(key.into(), bytes);
+    }
+
+    /// A hash of the edited executable without its signature.
+    ///
+    /// To verify a signature, open the signed executable and check its
+    /// [`signature_block`](Self::signature_block) against this digest.
+    pub fn signing_digest(&self) -> Result<[u8; 32], ArtifactEditError> {
+        let bytes = self
+            .executable
+            .serialize()
+            .map_err(ArtifactEditError::Serialize)?;
+        Ok(Sha256::digest(&bytes).into())
+    }
+
+    /// Serialize the edited executable.
+    pub fn finish(mut self) -> Result<Vec<u8>, ArtifactEditError> {
+        if self.executable.code_hash() != self.code_hash {
+            return Err(ArtifactEditError::CodeChanged);
+        }
+        self.executable.signature_block = self.signature_block;
+        self.executable
+            .serialize()
+            .map_err(ArtifactEditError::Serialize)
+    }
+}
+
+/// What [`code_hash`] covers, serialized to be hashed.
+#[derive(rkyv::Archive, rkyv::Serialize)]
+#[archive(check_bytes)]
+struct Code<'a> {
+    #[with(Inline)]
+    function_bodies: &'a PrimaryMap<LocalFunctionIndex, FunctionBody>,
+    #[with(Inline)]
+    function_relocations: &'a PrimaryMap<LocalFunctionIndex, Vec<Relocation>>,
+    #[with(Inline)]
+    function_jt_offsets: &'a PrimaryMap<LocalFunctionIndex, JumpTableOffsets>,
+    #[with(Inline)]
+    function_frame_info: &'a PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>,
+    #[with(Inline)]
+    function_call_trampolines: &'a PrimaryMap<SignatureIndex, FunctionBody>,
+    #[with(Inline)]
+    dynamic_function_trampolines: &'a PrimaryMap<FunctionIndex, FunctionBody>,
+    #[with(Inline)]
+    custom_sections: &'a PrimaryMap<SectionIndex, CustomSection>,
+    #[with(Inline)]
+    custom_section_relocations: &'a PrimaryMap<SectionIndex, Vec<Relocation>>,
+    #[with(Inline)]
+    debug: &'a Option<Dwarf>,
+    #[with(Inline)]
+    trampolines: &'a Option<TrampolinesSection>,
+    #[with(Inline)]
+    features: &'a Features,
+    #[with(Inline)]
+    memory_styles: &'a PrimaryMap<MemoryIndex, MemoryStyle>,
+    #[with(Inline)]
+    table_styles: &'a PrimaryMap<TableIndex, TableStyle>,
+    #[with(Inline)]
+    global_bindings: &'a BTreeMap<GlobalIndex, GlobalInit>,
+    cpu_features: u64,
+}
+
+/// See [`UniversalExecutable::code_hash`].
+pub(crate) fn code_hash(executable: &UniversalExecutable) -> [u8; 32] {
+    let code = Code {
+        function_bodies: &executable.function_bodies,
+        function_relocations: &executable.function_relocations,
+        function_jt_offsets: &executable.function_jt_offsets,
+        function_frame_info: &executable.function_frame_info,
+        function_call_trampolines: &executable.function_call_trampolines,
+        dynamic_function_trampolines: &executable.dynamic_function_trampolines,
+        custom_sections: &executable.custom_sections,
+        custom_section_relocations: &executable.custom_section_relocations,
+        debug: &executable.debug,
+        trampolines: &executable.trampolines,
+        features: &executable.compile_info.features,
+        memory_styles: &executable.compile_info.memory_styles,
+        table_styles: &executable.compile_info.table_styles,
+        global_bindings: &executable.compile_info.global_bindings,
+        cpu_features: executable.cpu_features,
+    };
+    let mut serializer = AllocSerializer::<1024>::default();
+    rkyv::ser::Serializer::serialize_value(&mut serializer, &code)
+        .expect("serializing into memory cannot fail");
+    Sha256::digest(serializer.into_serializer().into_inner().as_slice()).into()
+}
@@ -0,0 +1,112 @@
+//! Moving the entries of functions in `.eh_frame` sections between modules, along with the
+//! code of the functions.
+//!
+//! Compilers that describe the unwinding of functions in an `.eh_frame` section, such as
+//! Singlepass without frame pointers, emit it for the whole module. An entry (FDE) starts with
+//! its length, the distance to its CIE, and the address range of its function, whose start is
+//! relocated against the function; only what follows depends on the code of the function.
+
+#[cfg(feature = "compiler")]
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use wasmer_compiler::{Relocation, RelocationKind, RelocationTarget};
+use wasmer_types::LocalFunctionIndex;
+
+/// Size of the length and the CIE pointer of an entry, which precede its address range.
+const HEADER_SIZE: usize = 8;
+
+/// Size of the start address and the length of the address range of an FDE.
+const ADDRESS_RANGE_SIZE: usize = 16;
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// The offset of the FDE of `function` in the section, as told by the relocation of its start
+/// address.
+fn entry_offset(relocations: &[Relocation], function: LocalFunctionIndex) -> Option<usize> {
+    relocations
+        .iter()
+        .find(|relocation| {
+            relocation.kind == RelocationKind::Abs8
+                && relocation.reloc_target == RelocationTarget::LocalFunc(function)
+        })
+        .and_then(|relocation| (relocation.offset as usize).checked_sub(HEADER_SIZE))
+}
+
+/// Return the part of the FDE of `function` in the `.eh_frame` section `bytes` that follows
+/// its address range, if the section has one.
+pub(crate) fn function_entry(
+    bytes: &[u8],
+    relocations: &[Relocation],
+    function: LocalFunctionIndex,
+) -> Option<Vec<u8>> {
+    let offset = entry_offset(relocations, function)?;
+    let end = (offset + 4).checked_add(read_u32(bytes, offset)? as usize)?;
+    let contents = bytes.get(offset + HEADER_SIZE + ADDRESS_RANGE_SIZE..end)?;
+    Some(contents.to_vec())
+}
+
+/// Rewrite the `.eh_frame` section `bytes` with its `relocations`, with the FDEs of the
+/// functions in `entries` replaced by the given ones, as returned by [`function_entry`], for
+/// code of the given length.
+///
+/// The entries after a replaced one move along with their relocations, and keep pointing to
+/// their CIE.
+#[cfg(feature = "compiler")]
+pub(crate) fn replace_function_entries(
+    bytes: &[u8],
+    relocations: &[Relocation],
+    entries: &BTreeMap<LocalFunctionIndex, (&[u8], usize)>,
+) -> (Vec<u8>, Vec<Relocation>) {
+    let replaced = entries
+        .iter()
+        .filter_map(|(&function, &entry)| Some((entry_offset(relocations, function)?, entry)))
+        .collect::<BTreeMap<_, _>>();
+    let mut output = Vec::with_capacity(bytes.len());
+    // The old and new offsets of the entries, in order.
+    let mut moves: Vec<(usize, usize)> = Vec::new();
+    let mut offset = 0;
+    while let Some(length) = read_u32(bytes, offset).filter(|&length| length != 0) {
+        let end = offset + 4 + length as usize;
+        let new_offset = output.len();
+        match replaced.get(&offset) {
+            Some((contents, code_length)) => {
+                let length = HEADER_SIZE - 4 + ADDRESS_RANGE_SIZE + contents.len();
+                output.extend_from_slice(&(length as u32).to_le_bytes());
+                output.extend_from_slice(&bytes[offset + 4..offset + HEADER_SIZE]);
+                output.extend_from_slice(&0u64.to_le_bytes());
+                output.extend_from_slice(&(*code_length as u64).to_le_bytes());
+                output.extend_from_slice(contents);
+            }
+            None => output.extend_from_slice(&bytes[offset..end]),
+        }
+        // The CIE pointer of an FDE is the distance back from itself to its CIE, which is
+        // zero for CIEs.
+        let cie_pointer = read_u32(bytes, offset + 4).unwrap() as usize;
+        if cie_pointer != 0 {
+            let cie = offset + 4 - cie_pointer;
+            let new_cie = moves[moves.binary_search_by_key(&cie, |&(old, _)| old).unwrap()].1;
+            let new_pointer = (new_offset + 4 - new_cie) as u32;
+            output[new_offset + 4..new_offset + HEADER_SIZE]
+                .copy_from_slice(&new_pointer.to_le_bytes());
+        }
+        moves.push((offset, new_offset));
+        offset = end;
+    }
+    output.extend_from_slice(&bytes[offset..]);
+
+    let relocations = relocations
+        .iter()
+        .map(|relocation| {
+            let old = relocation.offset as usize;
+            let (entry, new_entry) = moves[moves.partition_point(|&(start, _)| start <= old) - 1];
+            Relocation {
+                offset: (new_entry + (old - entry)) as u32,
+                ..relocation.clone()
+            }
+        })
+        .collect();
+    (output, relocations)
+}
@@ -1,29 +1,51 @@
 //! Universal compilation.
 
 use crate::executable::{unrkyv, UniversalExecutableRef};
+use crate::instance_registry::{InstanceRegistry, InstanceSnapshotInfo};
+use crate::link::is_local_relocation;
+#[cfg(feature = "compiler")]
+use crate::pipeline::Loader;
+use crate::pipeline::StreamedCode;
+use crate::reclaim::{DroppedCode, MemoryImages};
+use crate::tenant::{TenantCharge, TenantLedger, TenantUsage};
+use crate::trampolines::TrampolineSetExecutable;
 use crate::{CodeMemory, UniversalArtifact, UniversalExecutable};
 use rkyv::de::deserializers::SharedDeserializeMap;
-use std::collections::BTreeMap;
+#[cfg(feature = "compiler")]
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::convert::TryFrom;
-use std::sync::{Arc, Mutex};
+use std::fs::File;
+use std::path::Path;
+use std::sync::{Arc, Mutex, Weak};
 #[cfg(feature = "compiler")]
-use wasmer_compiler::Compiler;
+use std::time::Instant;
 use wasmer_compiler::{
     CompileError, CustomSectionProtection, CustomSectionRef, FunctionBodyRef, JumpTable,
-    SectionIndex, Target,
+    Relocation, SectionIndex, SourceMap, Target,
+};
+#[cfg(feature = "compiler")]
+use wasmer_compiler::{
+    CompiledFunction, CompiledFunctionUnwindInfo, Compiler, ScheduleVersion, SectionBody,
 };
-use wasmer_engine::{Engine, EngineId};
-use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_engine::{DeserializeError, Engine, EngineId};
+use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{
-    DataInitializer, ExportIndex, Features, FunctionIndex, FunctionType, FunctionTypeRef,
-    GlobalInit, GlobalType, ImportCounts, ImportIndex, LocalFunctionIndex, LocalGlobalIndex,
-    MemoryIndex, SignatureIndex, TableIndex,
+    ArchivableIndexMap, DataInitializer, ExportIndex, Features, FunctionIndex, FunctionType,
+    FunctionTypeRef, GlobalInit, GlobalType, ImportCounts, ImportIndex, LocalFunctionIndex,
+    LocalGlobalIndex, MemoryIndex, ModuleInfo, Quota, SignatureIndex, TableIndex, TenantId,
 };
+#[cfg(feature = "compiler")]
+use wasmer_types::{ExternalDataInitializer, OwnedDataInitializer};
 use wasmer_vm::{
-    FuncDataRegistry, FunctionBodyPtr, SectionBodyPtr, SignatureRegistry, Tunables,
+    Counter, DiagnosticsLevel, DiagnosticsSink, FuncDataRegistry, FunctionBodyPtr, FunctionExtent,
+    Gauge, MemoryArbiter, MetricsSink, Mmap, PageAllocator, ReclaimPriority, ReclaimReport,
+    Reclaimable, ReclaimedComponent, SectionBodyPtr, SignatureRegistry, Tunables,
     VMCallerCheckedAnyfunc, VMFuncRef, VMFunctionBody, VMImportType, VMLocalFunction, VMOffsets,
     VMSharedSignatureIndex, VMTrampoline,
 };
+#[cfg(feature = "compiler")]
+use wasmer_vm::{ModuleStyleHints, Timer};
 
 /// A WebAssembly `Universal` Engine.
 #[derive(Clone)]
@@ -42,9 +64,26 @@ impl UniversalEngine {
             inner: Arc::new(Mutex::new(UniversalEngineInner {
                 compiler: Some(compiler),
                 code_memory: vec![],
+                code_memory_limit: None,
                 signatures: SignatureRegistry::new(),
                 func_data: Arc::new(FuncDataRegistry::new()),
                 features,
+                externalize_data_segments: None,
+                source_map_size_limit: None,
+                hot_functions: Vec::new(),
+                pipelined_loading: false,
+                metrics_sink: None,
+                diagnostics_sink: None,
+                default_diagnostics_level: DiagnosticsLevel::Off,
+                specializations: HashMap::new(),
+                instance_registry: None,
+                reclaimables: Vec::new(),
+                image_artifacts: Vec::new(),
+                installed_trampolines: HashMap::new(),
+                trampoline_leases: Vec::new(),
+                tenants: Arc::new(TenantLedger::default()),
+                memory_arbiter: None,
+                page_allocator: None,
             })),
             target: Arc::new(target),
             engine_id: EngineId::default(),
@@ -70,15 +109,41 @@ impl UniversalEngine {
                 #[cfg(feature = "compiler")]
                 compiler: None,
                 code_memory: vec![],
+                code_memory_limit: None,
                 signatures: SignatureRegistry::new(),
                 func_data: Arc::new(FuncDataRegistry::new()),
                 features: Features::default(),
+                externalize_data_segments: None,
+                source_map_size_limit: None,
+                hot_functions: Vec::new(),
+                pipelined_loading: false,
+                metrics_sink: None,
+                diagnostics_sink: None,
+                default_diagnostics_level: DiagnosticsLevel::Off,
+                specializations: HashMap::new(),
+                instance_registry: None,
+                reclaimables: Vec::new(),
+                image_artifacts: Vec::new(),
+                installed_trampolines: HashMap::new(),
+                trampoline_leases: Vec::new(),
+                tenants: Arc::new(TenantLedger::default()),
+                memory_arbiter: None,
+                page_allocator: None,
             })),
             target: Arc::new(Target::default()),
             engine_id: EngineId::default(),
         }
     }
 
+    /// Whether `other` is this engine or a clone of it, sharing its code memory and
+    /// signatures.
+    ///
+    /// Clones have their own [`Engine::id`], so that this is the way to tell whether the
+    /// artifacts of one can be instantiated with the other.
+    pub fn is_same_engine(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
     pub(crate) fn inner(&self) -> std::sync::MutexGuard<'_, UniversalEngineInner> {
         self.inner.lock().unwrap()
     }
@@ -87,24 +152,451 @@ impl UniversalEngine {
         self.inner.lock().unwrap()
     }
 
+    /// Report the metrics of this engine to `sink`, replacing the previous sink if any.
+    ///
+    /// Artifacts, and the instances created from them, keep reporting to the sink that was set
+    /// when they were loaded.
+    pub fn set_metrics_sink(&self, sink: Arc<dyn MetricsSink>) {
+        self.inner_mut().metrics_sink = Some(sink);
+    }
+
+    /// Report the diagnostics of the instances of this engine to `sink`, replacing the
+    /// previous sink if any.
+    ///
+    /// Instances keep reporting to the sink that was set when they were created. Only code
+    /// compiled with diagnostics, such as by Singlepass with `diagnostics(true)`, reports to it.
+    pub fn set_diagnostics_sink(&self, sink: Arc<dyn DiagnosticsSink>) {
+        self.inner_mut().diagnostics_sink = Some(sink);
+    }
+
+    /// Keep track of the instances created from now on, so that [`UniversalEngine::instances`]
+    /// lists them.
+    ///
+    /// The engine only holds weak references to the instances, and does nothing when they are
+    /// dropped: their entries are removed the next time instances are registered or listed.
+    pub fn enable_instance_registry(&self) {
+        let mut inner = self.inner_mut();
+        if inner.instance_registry.is_none() {
+            inner.instance_registry = Some(Arc::new(InstanceRegistry::default()));
+        }
+    }
+
+    /// Return the live instances created since [`UniversalEngine::enable_instance_registry`]
+    /// was called, by ascending identifier, or nothing if it was not.
+    pub fn instances(&self) -> Vec<InstanceSnapshotInfo> {
+        let registry = self.inner().instance_registry.clone();
+        registry.map_or_else(Vec::new, |registry| registry.instances())
+    }
+
+    /// Let [`UniversalEngine::reclaim`] ask `component` to release memory.
+    ///
+    /// The engine only holds a weak reference to the component, which is unregistered once
+    /// dropped. The code of dropped artifacts and the images of the read-only instances are
+    /// always reclaimable, without registration.
+    pub fn register_reclaimable(&self, component: Arc<dyn Reclaimable>) {
+        let mut inner = self.inner_mut();
+        inner
+            .reclaimables
+            .retain(|component| component.strong_count() > 0);
+        inner.reclaimables.push(Arc::downgrade(&component));
+    }
+
+    /// Ask the reclaimable components to release `target_bytes` of memory, from the cheapest
+    /// to release up to those of `max_priority`, and report what each released.
+    ///
+    /// Components of the same priority are asked in the order they were registered, after the
+    /// engine's own, and none is asked once the target is reached. Only memory no one uses is
+    /// released, so that reclaiming is safe while the engine is in use.
+    pub fn reclaim(&self, target_bytes: usize, max_priority: ReclaimPriority) -> ReclaimReport {
+        let mut components: Vec<Arc<dyn Reclaimable>> = vec![
+            Arc::new(DroppedCode::new(self.clone())),
+            Arc::new(MemoryImages::new(self.clone())),
+        ];
+        components.extend(self.inner().reclaimables.iter().filter_map(Weak::upgrade));
+        // The sort is stable, keeping the registration order within priorities.
+        components.sort_by_key(|component| component.priority());
+
+        let mut report = ReclaimReport {
+            target_bytes,
+            components: Vec::new(),
+        };
+        let mut released = 0;
+        for component in components {
+            if released >= target_bytes || component.priority() > max_priority {
+                break;
+            }
+            let reclaimable_bytes = component.reclaimable_bytes();
+            if reclaimable_bytes == 0 {
+                continue;
+            }
+            let released_bytes = component.reclaim(target_bytes - released);
+            released += released_bytes;
+            report.components.push(ReclaimedComponent {
+                name: component.name().to_string(),
+                priority: component.priority(),
+                reclaimable_bytes,
+                released_bytes,
+            });
+        }
+        report
+    }
+
+    /// Limit the memory mapped for the code of loaded artifacts to `limit` bytes, or lift the
+    /// limit with `None`.
+    ///
+    /// When loading an artifact would exceed the limit, or the operating system refuses to map
+    /// its code, the engine first releases the code of the artifacts that were dropped. If
+    /// that is not enough, loading fails with [`CompileError::CodeMemoryExhausted`].
+    pub fn set_code_memory_limit(&self, limit: Option<usize>) {
+        self.inner_mut().code_memory_limit = limit;
+    }
+
+    /// Limit what the engine uses for `tenant` to `quota`, replacing its previous quota.
+    ///
+    /// Only the work attributed to the tenant, by
+    /// [`compile_universal_as`](Self::compile_universal_as) and
+    /// [`load_universal_executable_as`](Self::load_universal_executable_as), counts. What the
+    /// tenant already uses is kept even if it exceeds the new quota, but no more is admitted
+    /// until it fits again.
+    pub fn set_tenant_quota(&self, tenant: TenantId, quota: Quota) {
+        self.inner().tenants.set_quota(tenant, quota);
+    }
+
+    /// What this engine did and holds for `tenant`.
+    pub fn tenant_usage(&self, tenant: TenantId) -> TenantUsage {
+        let inner = self.inner();
+        let mut usage = inner.tenants.usage(tenant);
+        if let Some(arbiter) = &inner.memory_arbiter {
+            usage.memory_bytes = arbiter.tenant_bytes(tenant);
+        }
+        usage
+    }
+
+    /// Have the memories of the instances created from now on ask `arbiter` before growing,
+    /// replacing the previous arbiter if any.
+    ///
+    /// Every growth of the memories these instances define is arbitrated, whether by
+    /// `memory.grow` or by the host, on behalf of the tenant of their `InstanceConfig`.
+    /// Memories keep asking the arbiter that was set when their instance was created.
+    pub fn set_memory_arbiter(&self, arbiter: Arc<dyn MemoryArbiter>) {
+        self.inner_mut().memory_arbiter = Some(arbiter);
+    }
+
+    /// Have the code of the artifacts loaded from now on placed in pages from `allocator`,
+    /// rather than mapped from the operating system.
+    ///
+    /// This is for embedders without `mmap`, such as enclaves, that load precompiled artifacts.
+    pub fn set_page_allocator(&self, allocator: Arc<dyn PageAllocator>) {
+        self.inner_mut().page_allocator = Some(allocator);
+    }
+
+    /// Set the diagnostics level of the instances created from now on.
+    ///
+    /// The level of each instance can then be changed with
+    /// `InstanceHandle::set_diagnostics_level`. It is [`DiagnosticsLevel::Off`] by default.
+    pub fn set_default_diagnostics_level(&self, level: DiagnosticsLevel) {
+        self.inner_mut().default_diagnostics_level = level;
+    }
+
+    /// Start validating a WebAssembly binary received in chunks against the features of the
+    /// engine, see [`StreamingValidator`](wasmer_compiler::StreamingValidator).
+    #[cfg(feature = "compiler")]
+    pub fn streaming_validator(&self) -> wasmer_compiler::StreamingValidator {
+        wasmer_compiler::StreamingValidator::new(self.inner().features())
+    }
+
     /// Compile a WebAssembly binary
     #[cfg(feature = "compiler")]
     pub fn compile_universal(
         &self,
         binary: &[u8],
         tunables: &dyn Tunables,
+    ) -> Result<crate::UniversalExecutable, CompileError> {
+        self.compile_observed(binary, tunables, None, CompileOptions::default())
+    }
+
+    /// Compile a WebAssembly binary on behalf of `tenant`, accounting the time spent to it.
+    ///
+    /// Fails with [`CompileError::QuotaExceeded`] without compiling if the tenant already
+    /// spent its compile time for the last minute. See [`UniversalEngine::set_tenant_quota`].
+    #[cfg(feature = "compiler")]
+    pub fn compile_universal_as(
+        &self,
+        tenant: TenantId,
+        binary: &[u8],
+        tunables: &dyn Tunables,
+    ) -> Result<crate::UniversalExecutable, CompileError> {
+        self.compile_observed(binary, tunables, Some(tenant), CompileOptions::default())
+    }
+
+    /// Compile a WebAssembly binary metered with the cost table of the metering schedule
+    /// `version`, see [`CompilerConfig::set_metering_schedules`].
+    ///
+    /// The version is recorded in the executable, see
+    /// [`UniversalArtifact::metering_schedule`], and is part of its
+    /// [`engine_id`](crate::Provenance::engine_id), so that executables compiled from the same
+    /// binary under different schedules have different identifiers. Fails if the compiler has
+    /// no schedule of that version.
+    ///
+    /// [`CompilerConfig::set_metering_schedules`]: wasmer_compiler::CompilerConfig::set_metering_schedules
+    #[cfg(feature = "compiler")]
+    pub fn compile_universal_with_schedule(
+        &self,
+        binary: &[u8],
+        tunables: &dyn Tunables,
+        version: ScheduleVersion,
+    ) -> Result<crate::UniversalExecutable, CompileError> {
+        let options = CompileOptions {
+            metering_schedule: Some(version),
+            ..CompileOptions::default()
+        };
+        self.compile_observed(binary, tunables, None, options)
+    }
+
+    /// Compile a WebAssembly binary specialized for the given values of some of its imported
+    /// globals.
+    ///
+    /// Each binding names an immutable imported global of numeric type, and the value it is
+    /// bound to. The compiler may use these values in place of reading the globals, which lets
+    /// it fold them into the code. The executable records the bindings, and instances of it
+    /// must be given globals with exactly these values, unless
+    /// [`InstanceConfig::with_bound_globals_provided`](wasmer_types::InstanceConfig::with_bound_globals_provided)
+    /// has the runtime provide them.
+    ///
+    /// The executable has a
+    /// [`specialization_hash`](crate::UniversalExecutable::specialization_hash) covering the
+    /// binary and the bindings. Compiling is as expensive as for the unspecialized module, see
+    /// [`UniversalEngine::specialize`] to reuse specialized artifacts.
+    #[cfg(feature = "compiler")]
+    pub fn compile_universal_specialized(
+        &self,
+        binary: &[u8],
+        tunables: &dyn Tunables,
+        bindings: &[(&str, &str, GlobalInit)],
+    ) -> Result<crate::UniversalExecutable, CompileError> {
+        let options = CompileOptions {
+            bindings,
+            specialization_hash: Some(crate::specialization::specialization_hash(binary, bindings)),
+            ..CompileOptions::default()
+        };
+        self.compile_observed(binary, tunables, None, options)
+    }
+
+    /// Load the module in `binary` specialized for `bindings`, reusing the artifact of a
+    /// previous call with the same binary and bindings if it is still alive.
+    ///
+    /// The order of the bindings does not matter. Otherwise, this is the same as loading the
+    /// result of [`UniversalEngine::compile_universal_specialized`].
+    #[cfg(feature = "compiler")]
+    pub fn specialize(
+        &self,
+        binary: &[u8],
+        tunables: &dyn Tunables,
+        bindings: &[(&str, &str, GlobalInit)],
+    ) -> Result<Arc<UniversalArtifact>, CompileError> {
+        let hash = crate::specialization::specialization_hash(binary, bindings);
+        let cached = self.inner().specializations.get(&hash).map(Weak::upgrade);
+        if let Some(Some(artifact)) = cached {
+            return Ok(artifact);
+        }
+        let options = CompileOptions {
+            bindings,
+            specialization_hash: Some(hash),
+            ..CompileOptions::default()
+        };
+        let executable = self.compile_observed(binary, tunables, None, options)?;
+        let artifact = Arc::new(self.load_universal_executable(&executable)?);
+        let mut inner = self.inner_mut();
+        inner
+            .specializations
+            .retain(|_, artifact| artifact.strong_count() > 0);
+        inner
+            .specializations
+            .insert(hash, Arc::downgrade(&artifact));
+        Ok(artifact)
+    }
+
+    /// Compile the trampolines calling functions of `signatures` from the host.
+    ///
+    /// The set can be serialized, and installed in this engine or in any other, including
+    /// headless ones, running on the same target.
+    #[cfg(feature = "compiler")]
+    pub fn compile_trampoline_set(
+        &self,
+        signatures: &[FunctionType],
+    ) -> Result<TrampolineSetExecutable, CompileError> {
+        let bodies = self
+            .inner()
+            .compiler()?
+            .compile_function_call_trampolines(&self.target, signatures)?;
+        Ok(TrampolineSetExecutable {
+            signatures: signatures.to_vec(),
+            bodies,
+            cpu_features: self.target.cpu_features().as_u64(),
+        })
+    }
+
+    /// Load the trampolines of `set` into code memory, for the engine to use them rather than
+    /// compile its own.
+    ///
+    /// Host functions created from now on, the loaded artifacts, and calls through
+    /// `Function::call` then use the installed trampolines of their signatures. Trampolines
+    /// already installed for a signature are kept. The code of a set stays loaded until the
+    /// engine is dropped.
+    pub fn install_trampoline_set(
+        &self,
+        set: &TrampolineSetExecutable,
+    ) -> Result<(), CompileError> {
+        if !set.cpu_features().is_subset(*self.target.cpu_features()) {
+            return Err(CompileError::UnsupportedTarget(format!(
+                "the trampolines were compiled for the CPU features {:?}",
+                set.cpu_features()
+            )));
+        }
+        if set.bodies.len() != set.signatures.len() {
+            return Err(CompileError::Validate(format!(
+                "the trampoline set has {} trampolines for {} signatures",
+                set.bodies.len(),
+                set.signatures.len()
+            )));
+        }
+        let mut inner = self.inner_mut();
+        let mut missing = Vec::new();
+        for (signature, body) in set.signatures.iter().zip(&set.bodies) {
+            let index = inner.signatures.register(signature.into());
+            if !inner.installed_trampolines.contains_key(&index)
+                && !missing.iter().any(|(other, _)| *other == index)
+            {
+                missing.push((index, body));
+            }
+        }
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let (_, trampolines, _, _, lease) = inner.allocate(
+            std::iter::empty(),
+            &[],
+            missing.iter().map(|(_, body)| FunctionBodyRef::from(*body)),
+            std::iter::empty(),
+            std::iter::empty(),
+            |_| unreachable!("trampoline sets have no functions"),
+            None,
+            None,
+        )?;
+        inner.publish_compiled_code();
+        for ((index, _), trampoline) in missing.iter().zip(trampolines.values()) {
+            inner.installed_trampolines.insert(*index, *trampoline);
+        }
+        inner.trampoline_leases.push(lease);
+        Ok(())
+    }
+
+    /// Compile a WebAssembly binary, reporting the compilation to the metrics sink and
+    /// accounting it to `tenant`, if any.
+    #[cfg(feature = "compiler")]
+    fn compile_observed(
+        &self,
+        binary: &[u8],
+        tunables: &dyn Tunables,
+        tenant: Option<TenantId>,
+        options: CompileOptions,
+    ) -> Result<crate::UniversalExecutable, CompileError> {
+        self.observe_compilation(tenant, || {
+            self.compile_executable(binary, tunables, options)
+        })
+    }
+
+    /// Run `compile`, reporting it to the metrics sink as a compilation and accounting it to
+    /// `tenant`, if any.
+    #[cfg(feature = "compiler")]
+    fn observe_compilation<T>(
+        &self,
+        tenant: Option<TenantId>,
+        compile: impl FnOnce() -> Result<T, CompileError>,
+    ) -> Result<T, CompileError> {
+        let (sink, tenants) = {
+            let inner = self.inner();
+            (inner.metrics_sink.clone(), Arc::clone(&inner.tenants))
+        };
+        if let Some(tenant) = tenant {
+            tenants.admit_compilation(tenant)?;
+        }
+        let started = Instant::now();
+        if let Some(sink) = &sink {
+            sink.increment(Counter::CompilationsStarted, 1);
+        }
+        let result = compile();
+        if let Some(tenant) = tenant {
+            tenants.record_compilation(tenant, started.elapsed());
+        }
+        if let Some(sink) = sink {
+            sink.observe(Timer::Compilation, started.elapsed());
+            let counter = match result {
+                Ok(_) => Counter::CompilationsSucceeded,
+                Err(_) => Counter::CompilationsFailed,
+            };
+            sink.increment(counter, 1);
+        }
+        result
+    }
+
+    #[cfg(feature = "compiler")]
+    fn compile_executable(
+        &self,
+        binary: &[u8],
+        tunables: &dyn Tunables,
+        options: CompileOptions,
     ) -> Result<crate::UniversalExecutable, CompileError> {
         let inner_engine = self.inner_mut();
+        self.compile_executable_in(&inner_engine, binary, tunables, options, &|_, _| {})
+    }
+
+    /// Compile a WebAssembly binary, handing each function to `sink` as soon as it is
+    /// compiled, see [`Compiler::compile_module_streaming`].
+    #[cfg(feature = "compiler")]
+    fn compile_executable_in(
+        &self,
+        inner_engine: &UniversalEngineInner,
+        binary: &[u8],
+        tunables: &dyn Tunables,
+        options: CompileOptions,
+        sink: &(dyn Fn(LocalFunctionIndex, &CompiledFunction) + Sync),
+    ) -> Result<crate::UniversalExecutable, CompileError> {
+        let CompileOptions {
+            bindings,
+            specialization_hash,
+            code_index,
+            metering_schedule,
+        } = options;
         let features = inner_engine.features();
         let compiler = inner_engine.compiler()?;
+        let compiler_provenance = match metering_schedule {
+            Some(version) => compiler.schedule_provenance(version)?,
+            None => compiler.provenance(),
+        };
+        let provenance = crate::provenance::Provenance::for_compilation(
+            compiler_provenance,
+            self.target(),
+            features,
+        );
         let environ = wasmer_compiler::ModuleEnvironment::new();
         let translation = environ.translate(binary).map_err(CompileError::Wasm)?;
+        let global_bindings = crate::specialization::bind_globals(&translation.module, bindings)?;
+        let unused_items = wasmer_compiler::UnusedItems {
+            imports_stripped: compiler.strips_unused_imports(),
+            ..wasmer_compiler::UnusedItems::find(&translation).map_err(CompileError::Wasm)?
+        };
+        let trap_imports = compiler.trap_imports(&translation.module);
 
+        let imported_memories = translation.module.import_counts.memories as usize;
         let memory_styles: PrimaryMap<wasmer_types::MemoryIndex, _> = translation
             .module
             .memories
-            .values()
-            .map(|memory_type| tunables.memory_style(memory_type))
+            .iter()
+            .map(|(index, memory_type)| {
+                let hints = ModuleStyleHints::new(memory_type, index.index() < imported_memories);
+                tunables.memory_style_for(memory_type, &hints)
+            })
             .collect();
         let table_styles: PrimaryMap<wasmer_types::TableIndex, _> = translation
             .module
@@ -119,47 +611,248 @@ impl UniversalEngine {
             features: features.clone(),
             memory_styles,
             table_styles,
+            global_bindings,
+            metering_schedule,
         };
-        let compilation = compiler.compile_module(
+        // SAFETY: Calling `unwrap` is correct since
+        // `environ.translate()` above will write some data into
+        // `module_translation_state`.
+        let module_translation_state = translation.module_translation_state.as_ref().unwrap();
+        let mut function_body_inputs = translation.function_body_inputs;
+        let function_source_hashes = crate::function_hash::source_hashes(
+            &provenance.engine_id,
+            &compile_info,
+            module_translation_state,
+            &function_body_inputs,
+        )
+        .map_err(CompileError::Wasm)?;
+
+        // The functions the index has code for are compiled as a trap, and replaced below.
+        let mut reused = Vec::new();
+        if let Some(code_index) = code_index {
+            for (idx, input) in function_body_inputs.iter_mut() {
+                let mut function = match code_index.lookup(&function_source_hashes[idx]) {
+                    Some(function) => function,
+                    None => continue,
+                };
+                // Unwind tables are emitted for the whole module by the compiler, so functions
+                // that do not come with their entry in them cannot be taken out of theirs.
+                if let Some(CompiledFunctionUnwindInfo::Dwarf) = function.body.unwind_info {
+                    continue;
+                }
+                crate::function_hash::rebase(&mut function, input.module_offset);
+                // No locals, `unreachable`, `end`.
+                input.data = &[0x00, 0x00, 0x0b];
+                reused.push((idx, function));
+            }
+        }
+        let compilation = compiler.compile_module_streaming(
             &self.target(),
             &compile_info,
-            // SAFETY: Calling `unwrap` is correct since
-            // `environ.translate()` above will write some data into
-            // `module_translation_state`.
-            translation.module_translation_state.as_ref().unwrap(),
-            translation.function_body_inputs,
+            module_translation_state,
+            function_body_inputs,
+            sink,
         )?;
         let function_call_trampolines = compilation.get_function_call_trampolines();
         let dynamic_function_trampolines = compilation.get_dynamic_function_trampolines();
-        let data_initializers = translation
-            .data_initializers
-            .iter()
-            .map(wasmer_types::OwnedDataInitializer::new)
-            .collect();
+        let mut data_initializers = Vec::new();
+        let mut external_data_initializers = Vec::new();
+        let mut external_data = Vec::new();
+        for (segment_index, init) in translation.data_initializers.iter().enumerate() {
+            match inner_engine.externalize_data_segments {
+                Some(min_size) if init.data.len() >= min_size => {
+                    external_data_initializers.push(ExternalDataInitializer {
+                        location: init.location.clone(),
+                        segment_index: segment_index as u32,
+                        hash: Sha256::digest(init.data).into(),
+                        len: init.data.len(),
+                    });
+                    external_data.push(init.data.to_vec());
+                }
+                _ => data_initializers.push(OwnedDataInitializer::new(init)),
+            }
+        }
+        // External segments are found by their position among all the segments.
+        if external_data_initializers.is_empty() {
+            data_initializers = coalesce_data_initializers(&compile_info.module, data_initializers);
+        }
 
-        let frame_infos = compilation.get_frame_info();
+        let code_section_offset = translation.code_section_offset;
+        let source_map = inner_engine.source_map_size_limit.and_then(|limit| {
+            crate::source_map::extract(&compile_info.module, code_section_offset, limit)
+        });
+
+        let mut frame_infos = compilation.get_frame_info();
+        let mut function_bodies = compilation.get_function_bodies();
+        let mut function_relocations = compilation.get_relocations();
+        let mut function_jt_offsets = compilation.get_jt_offsets();
+        let mut custom_sections = compilation.get_custom_sections();
+        let mut custom_section_relocations = compilation.get_custom_section_relocations();
+        let debug = compilation.get_debug();
+        // The entries of the functions in the Dwarf section are those of the trap they were
+        // compiled as, and take the ones of their code instead.
+        let unwind_entries = reused
+            .iter()
+            .filter_map(|(idx, function)| match &function.body.unwind_info {
+                Some(CompiledFunctionUnwindInfo::DwarfEntry(entry)) => {
+                    Some((*idx, (&entry[..], function.body.body.len())))
+                }
+                _ => None,
+            })
+            .collect::<BTreeMap<_, _>>();
+        if let (Some(debug), false) = (&debug, unwind_entries.is_empty()) {
+            let section = &mut custom_sections[debug.eh_frame];
+            let (bytes, relocations) = crate::eh_frame::replace_function_entries(
+                section.bytes.as_slice(),
+                &section.relocations,
+                &unwind_entries,
+            );
+            section.bytes = SectionBody::new_with_vec(bytes);
+            section.relocations = relocations.clone();
+            custom_section_relocations[debug.eh_frame] = relocations;
+        }
+        for (idx, mut function) in reused {
+            if let Some(CompiledFunctionUnwindInfo::DwarfEntry(_)) = function.body.unwind_info {
+                function.body.unwind_info = Some(CompiledFunctionUnwindInfo::Dwarf);
+            }
+            function_bodies[idx] = function.body;
+            function_relocations[idx] = function.relocations;
+            function_jt_offsets[idx] = function.jt_offsets;
+            frame_infos[idx] = function.frame_info;
+        }
+        let hot_functions =
+            crate::hot_code::resolve(&compile_info.module, &inner_engine.hot_functions);
+        let function_hashes = crate::function_hash::function_hashes(
+            &function_bodies,
+            &function_relocations,
+            &frame_infos,
+        );
         Ok(crate::UniversalExecutable {
-            function_bodies: compilation.get_function_bodies(),
-            function_relocations: compilation.get_relocations(),
-            function_jt_offsets: compilation.get_jt_offsets(),
+            function_bodies,
+            function_relocations,
+            function_jt_offsets,
             function_frame_info: frame_infos,
             function_call_trampolines,
             dynamic_function_trampolines,
-            custom_sections: compilation.get_custom_sections(),
-            custom_section_relocations: compilation.get_custom_section_relocations(),
-            debug: compilation.get_debug(),
+            custom_sections,
+            custom_section_relocations,
+            debug,
             trampolines: compilation.get_trampolines(),
             compile_info,
             data_initializers,
+            external_data_initializers,
+            external_data,
             cpu_features: self.target().cpu_features().as_u64(),
+            function_hashes,
+            function_source_hashes,
+            wasm_hash: Sha256::digest(binary).into(),
+            hot_functions,
+            source_map,
+            specialization_hash,
+            unused_items,
+            trap_imports,
+            signature_block: None,
+            custom_metadata: BTreeMap::new(),
+            provenance: provenance.encode(),
         })
     }
 
+    /// Compile a WebAssembly binary, reusing function code the embedder already has.
+    ///
+    /// The code of every local function whose
+    /// [source hash](crate::UniversalExecutable::function_source_hashes) is known to
+    /// `code_index` is taken from the index rather than generated, so that the resulting
+    /// executable references the same code as previously stored executables. The index is
+    /// trusted to return the code compiled from the source with the hash it is asked for.
+    ///
+    /// Functions whose unwind tables the compiler emits for the whole module, as Singlepass
+    /// does without frame pointers, are only taken from the index if they come with their
+    /// entry in them, as given by
+    /// [`UniversalExecutable::compiled_function`](crate::UniversalExecutable::compiled_function).
+    #[cfg(feature = "compiler")]
+    pub fn compile_universal_deduped(
+        &self,
+        binary: &[u8],
+        tunables: &dyn Tunables,
+        code_index: &dyn crate::FunctionCodeIndex,
+    ) -> Result<crate::UniversalExecutable, CompileError> {
+        let options = CompileOptions {
+            code_index: Some(code_index),
+            ..CompileOptions::default()
+        };
+        self.compile_observed(binary, tunables, None, options)
+    }
+
+    /// Compile a WebAssembly binary and load it with this engine.
+    ///
+    /// This is the same as loading the result of [`UniversalEngine::compile_universal`], and
+    /// returns the same executable. With [`Universal::pipelined_loading`] though, the
+    /// functions are placed into code memory as soon as they are compiled, on a thread of
+    /// their own, rather than once all of them are.
+    ///
+    /// [`Universal::pipelined_loading`]: crate::Universal::pipelined_loading
+    #[cfg(feature = "compiler")]
+    pub fn compile_and_load_universal(
+        &self,
+        binary: &[u8],
+        tunables: &dyn Tunables,
+    ) -> Result<(crate::UniversalExecutable, UniversalArtifact), CompileError> {
+        let (executable, streamed) = self.observe_compilation(None, || {
+            let inner_engine = self.inner_mut();
+            let loader = inner_engine.start_loader(binary.len());
+            let options = CompileOptions::default();
+            let result = match &loader {
+                Some(loader) => self.compile_executable_in(
+                    &inner_engine,
+                    binary,
+                    tunables,
+                    options,
+                    &|index, function| loader.send(index, function),
+                ),
+                None => {
+                    self.compile_executable_in(&inner_engine, binary, tunables, options, &|_, _| {})
+                }
+            };
+            // If compiling failed, this releases the code memory of the streamed functions.
+            let streamed = loader.and_then(Loader::finish);
+            Ok((result?, streamed))
+        })?;
+        let artifact = self.load_executable(&executable, None, streamed)?;
+        Ok((executable, artifact))
+    }
+
     /// Load a [`UniversalExecutable`](crate::UniversalExecutable) with this engine.
     pub fn load_universal_executable(
         &self,
         executable: &UniversalExecutable,
     ) -> Result<UniversalArtifact, CompileError> {
+        self.load_executable(executable, None, None)
+    }
+
+    /// Load a [`UniversalExecutable`](crate::UniversalExecutable) with this engine on behalf of
+    /// `tenant`, charging the artifact and its code memory to the tenant until it is dropped.
+    ///
+    /// Fails with [`CompileError::QuotaExceeded`] without mapping any code memory if that
+    /// would exceed the quota of the tenant. See [`UniversalEngine::set_tenant_quota`].
+    pub fn load_universal_executable_as(
+        &self,
+        tenant: TenantId,
+        executable: &UniversalExecutable,
+    ) -> Result<UniversalArtifact, CompileError> {
+        self.load_executable(executable, Some(tenant), None)
+    }
+
+    /// Load `executable`, whose local functions are in the code memory of `streamed` already
+    /// if any.
+    fn load_executable(
+        &self,
+        executable: &UniversalExecutable,
+        tenant: Option<TenantId>,
+        streamed: Option<StreamedCode>,
+    ) -> Result<UniversalArtifact, CompileError> {
+        let provenance = executable
+            .provenance()
+            .map_err(|e| CompileError::Validate(e.to_string()))?;
         let info = &executable.compile_info;
         let module = &info.module;
         let local_memories = (module.import_counts.memories as usize..module.memories.len())
@@ -189,24 +882,55 @@ impl UniversalEngine {
         let local_functions = executable.function_bodies.iter().map(|(_, b)| b.into());
         let function_call_trampolines = &executable.function_call_trampolines;
         let dynamic_function_trampolines = &executable.dynamic_function_trampolines;
+        // The rest of the code goes after the streamed functions, if there is room for it.
+        let streamed = streamed.filter(|code| {
+            let trampolines = function_call_trampolines
+                .values()
+                .chain(dynamic_function_trampolines.values())
+                .map(FunctionBodyRef::from)
+                .collect::<Vec<_>>();
+            let (executable_sections, data_sections): (Vec<CustomSectionRef>, _) = executable
+                .custom_sections
+                .values()
+                .map(CustomSectionRef::from)
+                .partition(|section| section.protection == CustomSectionProtection::ReadExecute);
+            let size = code.memory.allocation_size_after_appended(
+                &trampolines,
+                &executable_sections,
+                &data_sections,
+            );
+            executable.hot_functions.is_empty()
+                && code.functions.len() == executable.function_bodies.len()
+                && size <= code.memory.mapped_len()
+        });
+        let linked_locally = streamed.is_some();
         let signatures = module
             .signatures
             .iter()
             .map(|(_, sig)| inner_engine.signatures.register(sig.into()))
             .collect::<PrimaryMap<SignatureIndex, _>>()
             .into_boxed_slice();
-        let (functions, trampolines, dynamic_trampolines, custom_sections) = inner_engine
-            .allocate(
-                local_functions,
-                function_call_trampolines.iter().map(|(_, b)| b.into()),
-                dynamic_function_trampolines.iter().map(|(_, b)| b.into()),
-                executable.custom_sections.iter().map(|(_, s)| s.into()),
-                |idx: LocalFunctionIndex| {
-                    let func_idx = module.import_counts.function_index(idx);
-                    let sig_idx = module.functions[func_idx];
-                    (sig_idx, signatures[sig_idx])
-                },
-            )?;
+        let (
+            mut functions,
+            mut trampolines,
+            dynamic_trampolines,
+            custom_sections,
+            code_memory_lease,
+        ) = inner_engine.allocate(
+            local_functions,
+            &executable.hot_functions,
+            function_call_trampolines.iter().map(|(_, b)| b.into()),
+            dynamic_function_trampolines.iter().map(|(_, b)| b.into()),
+            executable.custom_sections.iter().map(|(_, s)| s.into()),
+            |idx: LocalFunctionIndex| {
+                let func_idx = module.import_counts.function_index(idx);
+                let sig_idx = module.functions[func_idx];
+                (sig_idx, signatures[sig_idx])
+            },
+            streamed,
+            tenant,
+        )?;
+        inner_engine.link_installed_trampolines(&signatures, &mut trampolines, &mut functions);
         let imports = module
             .imports
             .iter()
@@ -234,10 +958,13 @@ impl UniversalEngine {
 
         let function_relocations = executable.function_relocations.iter();
         let section_relocations = executable.custom_section_relocations.iter();
+        // The streamed functions only miss the relocations to other code.
+        let deferred = |i, r: &&Relocation| !linked_locally || !is_local_relocation(i, r);
         crate::link_module(
             &functions,
             |func_idx, jt_idx| executable.function_jt_offsets[func_idx][jt_idx],
-            function_relocations.map(|(i, rs)| (i, rs.iter().cloned())),
+            function_relocations
+                .map(|(i, rs)| (i, rs.iter().filter(move |r| deferred(i, r)).cloned())),
             &custom_sections,
             section_relocations.map(|(i, rs)| (i, rs.iter().cloned())),
             &executable.trampolines,
@@ -259,6 +986,25 @@ impl UniversalEngine {
             .iter()
             .map(|(s, i)| (s.clone(), i.clone()))
             .collect::<BTreeMap<String, ExportIndex>>();
+        let metrics_sink = inner_engine.loaded_artifact_sink();
+        match &metrics_sink {
+            Some(sink) if linked_locally => sink.increment(Counter::PipelinedLoads, 1),
+            _ => {}
+        }
+        let source_map = executable.source_map.clone().map(Arc::new);
+        let frame_info_registration = wasmer_engine::register_frame_info(
+            module.name(),
+            module
+                .function_names
+                .iter()
+                .map(|(idx, name)| (*idx, name.clone()))
+                .collect(),
+            module.import_counts,
+            &function_extents(&functions),
+            executable.function_frame_info.clone(),
+            source_map.clone(),
+        );
+        let hot_code = hot_code(&functions, &executable.hot_functions);
 
         Ok(UniversalArtifact {
             engine: self.clone(),
@@ -272,11 +1018,29 @@ impl UniversalEngine {
             signatures,
             local_memories,
             data_segments: executable.data_initializers.clone(),
+            external_data_segments: executable.external_data_initializers.clone(),
             passive_data: module.passive_data.clone(),
             local_tables,
             element_segments: module.table_initializers.clone(),
             passive_elements: module.passive_elements.clone(),
             local_globals,
+            function_hashes: executable.function_hashes.clone().into_boxed_slice(),
+            wasm_hash: executable.wasm_hash,
+            global_bindings: info.global_bindings.clone(),
+            metering_schedule: info.metering_schedule,
+            specialization_hash: executable.specialization_hash,
+            source_map,
+            unused_items: executable.unused_items.clone(),
+            trap_imports: executable.trap_imports.clone(),
+            interface: Arc::clone(module),
+            frame_info_registration,
+            _code_memory_lease: code_memory_lease,
+            metrics_sink,
+            memory_images: Mutex::new(None),
+            consensus_hash: Mutex::new(None),
+            provenance,
+            hot_code,
+            hot_code_locked: Mutex::new(0),
         })
     }
 
@@ -285,6 +1049,58 @@ impl UniversalEngine {
         &self,
         executable: &UniversalExecutableRef,
     ) -> Result<UniversalArtifact, CompileError> {
+        self.load_executable_ref(executable, None)
+    }
+
+    /// Load a [`UniversalExecutableRef`](crate::UniversalExecutableRef) with this engine on
+    /// behalf of `tenant`.
+    ///
+    /// See [`UniversalEngine::load_universal_executable_as`].
+    pub fn load_universal_executable_ref_as(
+        &self,
+        tenant: TenantId,
+        executable: &UniversalExecutableRef,
+    ) -> Result<UniversalArtifact, CompileError> {
+        self.load_executable_ref(executable, Some(tenant))
+    }
+
+    /// Load a [`UniversalExecutable`](crate::UniversalExecutable) serialized into the file at
+    /// `path` with this engine, without reading the file into memory.
+    ///
+    /// The file is mapped, and only the code and what the artifact keeps are copied out of it.
+    /// Fails with [`DeserializeError::Incompatible`] if the file is not a serialized executable
+    /// or its code uses CPU features the target of the engine lacks, and with
+    /// [`DeserializeError::CorruptedBinary`] if it was truncated.
+    ///
+    /// # Safety
+    ///
+    /// See [`UniversalExecutableRef::deserialize`]. The file must not be modified while it is
+    /// loaded.
+    pub unsafe fn load_universal_executable_from_file(
+        &self,
+        path: &Path,
+    ) -> Result<UniversalArtifact, DeserializeError> {
+        let mapping = Mmap::map_file(&File::open(path)?)?;
+        let executable = UniversalExecutableRef::deserialize(mapping.as_slice())?;
+        let cpu_features = wasmer_engine::Executable::cpu_features(&executable);
+        if !cpu_features.is_subset(*self.target.cpu_features()) {
+            return Err(DeserializeError::Incompatible(format!(
+                "the executable was compiled for the CPU features {:?}",
+                cpu_features
+            )));
+        }
+        self.load_universal_executable_ref(&executable)
+            .map_err(DeserializeError::Compiler)
+    }
+
+    fn load_executable_ref(
+        &self,
+        executable: &UniversalExecutableRef,
+        tenant: Option<TenantId>,
+    ) -> Result<UniversalArtifact, CompileError> {
+        let provenance = executable
+            .provenance()
+            .map_err(|e| CompileError::Validate(e.to_string()))?;
         let info = &executable.compile_info;
         let module = &info.module;
         let import_counts: ImportCounts = unrkyv(&module.import_counts);
@@ -336,18 +1152,27 @@ impl UniversalEngine {
             .map(|sig| inner_engine.signatures.register(sig.into()))
             .collect::<PrimaryMap<SignatureIndex, _>>()
             .into_boxed_slice();
-        let (functions, trampolines, dynamic_trampolines, custom_sections) = inner_engine
-            .allocate(
-                local_functions,
-                call_trampolines.map(|(_, b)| b.into()),
-                dynamic_trampolines.map(|(_, b)| b.into()),
-                executable.custom_sections.iter().map(|(_, s)| s.into()),
-                |idx: LocalFunctionIndex| {
-                    let func_idx = import_counts.function_index(idx);
-                    let sig_idx = module.functions[&func_idx];
-                    (sig_idx, signatures[sig_idx])
-                },
-            )?;
+        let (
+            mut functions,
+            mut trampolines,
+            dynamic_trampolines,
+            custom_sections,
+            code_memory_lease,
+        ) = inner_engine.allocate(
+            local_functions,
+            &unrkyv::<Vec<LocalFunctionIndex>>(&executable.hot_functions),
+            call_trampolines.map(|(_, b)| b.into()),
+            dynamic_trampolines.map(|(_, b)| b.into()),
+            executable.custom_sections.iter().map(|(_, s)| s.into()),
+            |idx: LocalFunctionIndex| {
+                let func_idx = import_counts.function_index(idx);
+                let sig_idx = module.functions[&func_idx];
+                (sig_idx, signatures[sig_idx])
+            },
+            None,
+            tenant,
+        )?;
+        inner_engine.link_installed_trampolines(&signatures, &mut trampolines, &mut functions);
         let imports = {
             module
                 .imports
@@ -407,6 +1232,18 @@ impl UniversalEngine {
             .iter()
             .map(|(s, i)| (unrkyv(s), unrkyv(i)))
             .collect::<BTreeMap<String, ExportIndex>>();
+        let metrics_sink = inner_engine.loaded_artifact_sink();
+        let module_name: Option<String> = unrkyv(&module.name);
+        let source_map = unrkyv::<Option<SourceMap>>(&executable.source_map).map(Arc::new);
+        let frame_info_registration = wasmer_engine::register_frame_info(
+            module_name.unwrap_or_else(|| "<module>".to_string()),
+            unrkyv(&module.function_names),
+            import_counts,
+            &function_extents(&functions),
+            unrkyv(&executable.function_frame_info),
+            source_map.clone(),
+        );
+        let hot_code = hot_code(&functions, &unrkyv::<Vec<_>>(&executable.hot_functions));
         Ok(UniversalArtifact {
             engine: self.clone(),
             import_counts,
@@ -419,15 +1256,155 @@ impl UniversalEngine {
             signatures,
             local_memories,
             data_segments,
+            external_data_segments: unrkyv(&executable.external_data_initializers),
             passive_data,
             local_tables,
             element_segments,
             passive_elements,
             local_globals,
+            function_hashes: executable
+                .function_hashes
+                .iter()
+                .map(|(_, hash)| *hash)
+                .collect::<PrimaryMap<LocalFunctionIndex, _>>()
+                .into_boxed_slice(),
+            wasm_hash: executable.wasm_hash,
+            global_bindings: unrkyv(&info.global_bindings),
+            metering_schedule: unrkyv(&info.metering_schedule),
+            specialization_hash: unrkyv(&executable.specialization_hash),
+            source_map,
+            unused_items: unrkyv(&executable.unused_items),
+            trap_imports: unrkyv(&executable.trap_imports),
+            interface: Arc::new(archived_interface(module)),
+            frame_info_registration,
+            _code_memory_lease: code_memory_lease,
+            metrics_sink,
+            memory_images: Mutex::new(None),
+            consensus_hash: Mutex::new(None),
+            provenance,
+            hot_code,
+            hot_code_locked: Mutex::new(0),
         })
     }
 }
 
+/// The parts of an archived module info describing the imports, exports and custom sections of
+/// the module.
+///
+/// The rest, and in particular the data, is left out.
+fn archived_interface(module: &rkyv::Archived<ModuleInfo>) -> ModuleInfo {
+    ModuleInfo {
+        name: unrkyv(&module.name),
+        imports: unrkyv::<ArchivableIndexMap<_, _>>(&module.imports).into(),
+        exports: unrkyv::<ArchivableIndexMap<_, _>>(&module.exports).into(),
+        function_names: unrkyv::<BTreeMap<_, _>>(&module.function_names)
+            .into_iter()
+            .collect(),
+        param_names: unrkyv::<BTreeMap<_, _>>(&module.param_names)
+            .into_iter()
+            .collect(),
+        signatures: module
+            .signatures
+            .values()
+            .map(|sig| {
+                let sig = FunctionTypeRef::from(sig);
+                FunctionType::new(sig.params(), sig.results())
+            })
+            .collect(),
+        functions: unrkyv(&module.functions),
+        tables: unrkyv(&module.tables),
+        memories: unrkyv(&module.memories),
+        globals: unrkyv(&module.globals),
+        custom_sections: unrkyv(&module.custom_sections),
+        custom_sections_data: module
+            .custom_sections_data
+            .values()
+            .map(|data| Arc::from(&data[..]))
+            .collect(),
+        import_counts: unrkyv(&module.import_counts),
+        ..ModuleInfo::default()
+    }
+}
+
+/// How a binary is compiled, beyond the configuration of the engine.
+#[cfg(feature = "compiler")]
+#[derive(Default)]
+struct CompileOptions<'a> {
+    /// The values of the imported globals to specialize the module for.
+    bindings: &'a [(&'a str, &'a str, GlobalInit)],
+    /// The hash of `bindings`, for specialized modules.
+    specialization_hash: Option<[u8; 32]>,
+    /// Where to take the code of the functions the embedder already has.
+    code_index: Option<&'a dyn crate::FunctionCodeIndex>,
+    /// The metering schedule to meter the code with.
+    metering_schedule: Option<ScheduleVersion>,
+}
+
+/// Merge the runs of data segments written right after one another into a memory, at
+/// constant offsets, so that instantiating the module copies each run at once.
+///
+/// Only segments ending within the minimum size of their memory, which it has when the
+/// segments are applied, are merged: none of them can be out of bounds, so that the segments
+/// applied before an out-of-bounds one stay the same.
+#[cfg(feature = "compiler")]
+fn coalesce_data_initializers(
+    module: &ModuleInfo,
+    initializers: Vec<OwnedDataInitializer>,
+) -> Vec<OwnedDataInitializer> {
+    let within_minimum = |init: &OwnedDataInitializer| {
+        let minimum = module.memories[init.location.memory_index].minimum;
+        init.location.base.is_none()
+            && init
+                .location
+                .offset
+                .checked_add(init.data.len())
+                .map_or(false, |end| end <= minimum.bytes().0)
+    };
+    let mut coalesced: Vec<OwnedDataInitializer> = Vec::with_capacity(initializers.len());
+    for init in initializers {
+        if let Some(last) = coalesced.last_mut() {
+            if last.location.memory_index == init.location.memory_index
+                && last.location.offset + last.data.len() == init.location.offset
+                && within_minimum(last)
+                && within_minimum(&init)
+            {
+                last.data.extend_from_slice(&init.data);
+                continue;
+            }
+        }
+        coalesced.push(init);
+    }
+    coalesced
+}
+
+/// Extents of the loaded local functions, for frame info registration.
+fn function_extents(
+    functions: &PrimaryMap<LocalFunctionIndex, VMLocalFunction>,
+) -> BoxedSlice<LocalFunctionIndex, FunctionExtent> {
+    functions
+        .values()
+        .map(|f| FunctionExtent {
+            address: f.body,
+            length: usize::try_from(f.length).unwrap(),
+        })
+        .collect::<PrimaryMap<LocalFunctionIndex, _>>()
+        .into_boxed_slice()
+}
+
+/// The start and length of the code of the `hot` functions, which `allocate` placed next to
+/// each other.
+fn hot_code(
+    functions: &PrimaryMap<LocalFunctionIndex, VMLocalFunction>,
+    hot: &[LocalFunctionIndex],
+) -> Option<(FunctionBodyPtr, usize)> {
+    let bodies = hot.iter().filter_map(|index| functions.get(*index));
+    let start = bodies.clone().map(|f| f.body.0 as usize).min()?;
+    let end = bodies
+        .map(|f| f.body.0 as usize + f.length as usize)
+        .max()?;
+    Some((FunctionBodyPtr(start as *const VMFunctionBody), end - start))
+}
+
 impl Engine for UniversalEngine {
     /// The target
     fn target(&self) -> &Target {
@@ -448,6 +1425,49 @@ impl Engine for UniversalEngine {
         self.inner().signatures.lookup(sig).cloned()
     }
 
+    fn lookup_shared_signature(&self, sig: VMSharedSignatureIndex) -> Option<Arc<FunctionType>> {
+        self.inner().signatures.lookup_shared(sig)
+    }
+
+    fn lookup_function_call_trampoline(&self, sig: VMSharedSignatureIndex) -> Option<VMTrampoline> {
+        self.inner().installed_trampolines.get(&sig).copied()
+    }
+
+    /// Compiles and installs the trampoline if none is installed and the engine has a
+    /// compiler, counting it as [`Counter::TrampolinesCompiled`].
+    fn function_call_trampoline(&self, sig: VMSharedSignatureIndex) -> Option<VMTrampoline> {
+        if let Some(trampoline) = self.lookup_function_call_trampoline(sig) {
+            return Some(trampoline);
+        }
+        #[cfg(feature = "compiler")]
+        {
+            let signature = self.lookup_signature(sig)?;
+            let set = self.compile_trampoline_set(&[signature]).ok()?;
+            if let Some(sink) = self.metrics_sink() {
+                sink.increment(Counter::TrampolinesCompiled, 1);
+            }
+            self.install_trampoline_set(&set).ok()?;
+            self.lookup_function_call_trampoline(sig)
+        }
+        #[cfg(not(feature = "compiler"))]
+        None
+    }
+
+    /// The [`Provenance::engine_id`](crate::Provenance::engine_id) of the executables this
+    /// engine compiles.
+    fn fingerprint(&self) -> [u8; 32] {
+        let inner = self.inner();
+        #[cfg(feature = "compiler")]
+        let compiler = inner
+            .compiler
+            .as_ref()
+            .map(|compiler| compiler.provenance())
+            .unwrap_or_default();
+        #[cfg(not(feature = "compiler"))]
+        let compiler = wasmer_compiler::CompilerProvenance::default();
+        crate::provenance::engine_id(&compiler, &self.target, inner.features())
+    }
+
     /// Validates a WebAssembly module
     fn validate(&self, binary: &[u8]) -> Result<(), CompileError> {
         self.inner().validate(binary)
@@ -483,6 +1503,17 @@ impl Engine for UniversalEngine {
         executable.load(self)
     }
 
+    /// Compile a WebAssembly binary and load it
+    #[cfg(feature = "compiler")]
+    fn compile_and_load(
+        &self,
+        binary: &[u8],
+        tunables: &dyn Tunables,
+    ) -> Result<Arc<dyn wasmer_vm::Artifact>, CompileError> {
+        let (_, artifact) = self.compile_and_load_universal(binary, tunables)?;
+        Ok(Arc::new(artifact))
+    }
+
     fn id(&self) -> &EngineId {
         &self.engine_id
     }
@@ -490,6 +1521,10 @@ impl Engine for UniversalEngine {
     fn cloned(&self) -> Arc<dyn Engine + Send + Sync> {
         Arc::new(self.clone())
     }
+
+    fn metrics_sink(&self) -> Option<Arc<dyn MetricsSink>> {
+        self.inner().metrics_sink.clone()
+    }
 }
 
 /// The inner contents of `UniversalEngine`
@@ -501,7 +1536,9 @@ pub struct UniversalEngineInner {
     features: Features,
     /// The code memory is responsible of publishing the compiled
     /// functions to memory.
-    code_memory: Vec<CodeMemory>,
+    code_memory: Vec<LoadedCode>,
+    /// The most bytes of code memory to map, if limited.
+    code_memory_limit: Option<usize>,
     /// The signature registry is used mainly to operate with trampolines
     /// performantly.
     pub(crate) signatures: SignatureRegistry,
@@ -509,6 +1546,39 @@ pub struct UniversalEngineInner {
     /// functions with the same `VMCallerCheckedAnyfunc` will have the same `VMFuncRef`.
     /// It also guarantees that the `VMFuncRef`s stay valid until the engine is dropped.
     func_data: Arc<FuncDataRegistry>,
+    /// The minimum size of the data segments to keep out of compiled executables.
+    pub(crate) externalize_data_segments: Option<usize>,
+    /// The size limit of the source maps to keep in compiled executables, if they are kept.
+    pub(crate) source_map_size_limit: Option<usize>,
+    /// The functions to place first in code memory, by export name or function index.
+    pub(crate) hot_functions: Vec<String>,
+    /// Whether to place functions into code memory as soon as they are compiled.
+    pub(crate) pipelined_loading: bool,
+    /// Where to report the metrics of this engine.
+    pub(crate) metrics_sink: Option<Arc<dyn MetricsSink>>,
+    /// Where instances report their diagnostics.
+    pub(crate) diagnostics_sink: Option<Arc<dyn DiagnosticsSink>>,
+    /// The diagnostics level of new instances.
+    pub(crate) default_diagnostics_level: DiagnosticsLevel,
+    /// The specialized artifacts loaded by `UniversalEngine::specialize`, by specialization
+    /// hash.
+    specializations: HashMap<[u8; 32], Weak<UniversalArtifact>>,
+    /// The live instances, if they are tracked.
+    pub(crate) instance_registry: Option<Arc<InstanceRegistry>>,
+    /// The components registered by `UniversalEngine::register_reclaimable`.
+    reclaimables: Vec<Weak<dyn Reclaimable>>,
+    /// The artifacts that captured memory images for their read-only instances.
+    pub(crate) image_artifacts: Vec<Weak<UniversalArtifact>>,
+    /// The trampolines installed by `UniversalEngine::install_trampoline_set`, by signature.
+    installed_trampolines: HashMap<VMSharedSignatureIndex, VMTrampoline>,
+    /// Keeps the code of the installed trampolines loaded.
+    trampoline_leases: Vec<Arc<CodeMemoryLease>>,
+    /// The usage and quotas of the tenants work is attributed to.
+    tenants: Arc<TenantLedger>,
+    /// What the memories of new instances ask before growing, if anything.
+    pub(crate) memory_arbiter: Option<Arc<dyn MemoryArbiter>>,
+    /// Where the code of new artifacts is placed, if not in pages mapped from the system.
+    page_allocator: Option<Arc<dyn PageAllocator>>,
 }
 
 impl UniversalEngineInner {
@@ -541,31 +1611,73 @@ impl UniversalEngineInner {
         &self.features
     }
 
-    /// Allocate compiled functions into memory
-    #[allow(clippy::type_complexity)]
+    /// Count an artifact as loaded, returning the sink it should report to.
+    fn loaded_artifact_sink(&self) -> Option<Arc<dyn MetricsSink>> {
+        let sink = self.metrics_sink.clone();
+        if let Some(sink) = &sink {
+            sink.increment(Counter::ArtifactsLoaded, 1);
+        }
+        sink
+    }
+
+    /// Allocate compiled functions into memory, the `hot_functions` first
+    ///
+    /// With `streamed` code, the local functions are already in its code memory, which must
+    /// have room for the rest after them, and there must be no hot functions.
+    #[allow(clippy::type_complexity, clippy::too_many_arguments)]
     pub(crate) fn allocate<'a>(
         &mut self,
         local_functions: impl ExactSizeIterator<Item = FunctionBodyRef<'a>>,
+        hot_functions: &[LocalFunctionIndex],
         call_trampolines: impl ExactSizeIterator<Item = FunctionBodyRef<'a>>,
         dynamic_trampolines: impl ExactSizeIterator<Item = FunctionBodyRef<'a>>,
         custom_sections: impl ExactSizeIterator<Item = CustomSectionRef<'a>>,
         function_signature: impl Fn(LocalFunctionIndex) -> (SignatureIndex, VMSharedSignatureIndex),
+        streamed: Option<StreamedCode>,
+        tenant: Option<TenantId>,
     ) -> Result<
         (
             PrimaryMap<LocalFunctionIndex, VMLocalFunction>,
             PrimaryMap<SignatureIndex, VMTrampoline>,
             PrimaryMap<FunctionIndex, FunctionBodyPtr>,
             PrimaryMap<SectionIndex, SectionBodyPtr>,
+            Arc<CodeMemoryLease>,
         ),
         CompileError,
     > {
-        let code_memory = &mut self.code_memory;
+        let local_functions = local_functions.collect::<Vec<_>>();
         let function_count = local_functions.len();
         let call_trampoline_count = call_trampolines.len();
-        let function_bodies = call_trampolines
-            .chain(local_functions)
-            .chain(dynamic_trampolines)
+        // The hot functions come first, then the trampolines and the other functions, so that
+        // the hot code is contiguous and starts on a page boundary.
+        let mut is_hot = vec![false; function_count];
+        let hot = hot_functions
+            .iter()
+            .map(|index| index.index())
+            .filter(|&index| index < function_count && !std::mem::replace(&mut is_hot[index], true))
             .collect::<Vec<_>>();
+        let cold = (0..function_count)
+            .filter(|&index| !is_hot[index])
+            .collect::<Vec<_>>();
+        let (streamed_memory, streamed_functions) = match streamed {
+            Some(StreamedCode { memory, functions }) => {
+                assert!(hot.is_empty() && functions.len() == function_count);
+                (Some(memory), Some(functions))
+            }
+            None => (None, None),
+        };
+        let function_bodies = match streamed_functions {
+            Some(_) => call_trampolines
+                .chain(dynamic_trampolines)
+                .collect::<Vec<_>>(),
+            None => hot
+                .iter()
+                .map(|&index| local_functions[index])
+                .chain(call_trampolines)
+                .chain(cold.iter().map(|&index| local_functions[index]))
+                .chain(dynamic_trampolines)
+                .collect::<Vec<_>>(),
+        };
 
         // TOOD: this shouldn't be necessary....
         let mut section_types = Vec::with_capacity(custom_sections.len());
@@ -579,10 +1691,46 @@ impl UniversalEngineInner {
             }
             section_types.push(section.protection);
         }
-        code_memory.push(CodeMemory::new());
-        let code_memory = self.code_memory.last_mut().expect("infallible");
+        let size = match (&streamed_memory, &self.page_allocator) {
+            (Some(memory), _) => memory.allocation_size_after_appended(
+                &function_bodies,
+                &executable_sections,
+                &data_sections,
+            ),
+            (None, Some(allocator)) => CodeMemory::allocation_size_with_page_size(
+                &function_bodies,
+                &executable_sections,
+                &data_sections,
+                allocator.page_size(),
+            ),
+            (None, None) => {
+                CodeMemory::allocation_size(&function_bodies, &executable_sections, &data_sections)
+            }
+        };
+        // Charge the tenant first, so that its quota is enforced before any memory is mapped.
+        let tenant_charge = tenant
+            .map(|tenant| self.tenants.charge_artifact(tenant, size))
+            .transpose()?;
+        let memory = match streamed_memory {
+            Some(mut memory) => {
+                memory.shrink(size);
+                self.adopt_code_memory(memory)?
+            }
+            None => self.map_code_memory(size)?,
+        };
+        if let Some(sink) = &self.metrics_sink {
+            sink.adjust(Gauge::CodeBytes, memory.mapped_len() as i64);
+        }
+        let lease = Arc::new(CodeMemoryLease {
+            _tenant_charge: tenant_charge,
+        });
+        self.code_memory.push(LoadedCode {
+            memory,
+            lease: Arc::downgrade(&lease),
+        });
+        let code_memory = &mut self.code_memory.last_mut().expect("infallible").memory;
 
-        let (mut allocated_functions, allocated_executable_sections, allocated_data_sections) =
+        let (allocated_functions, allocated_executable_sections, allocated_data_sections) =
             code_memory
                 .allocate(
                     function_bodies.as_slice(),
@@ -596,10 +1744,25 @@ impl UniversalEngineInner {
                     ))
                 })?;
 
+        let mut allocated_functions = allocated_functions.into_iter();
+        let mut local_bodies = match &streamed_functions {
+            Some(functions) => functions
+                .iter()
+                .map(|&(address, len)| Some((address as *const VMFunctionBody, len)))
+                .collect(),
+            None => vec![None; function_count],
+        };
+        let mut place = |index: usize, slice: Option<&mut [VMFunctionBody]>| {
+            local_bodies[index] = slice.map(|slice| (slice.as_ptr() as *const _, slice.len()));
+        };
+        for &index in &hot {
+            place(index, allocated_functions.next());
+        }
         let mut allocated_function_call_trampolines: PrimaryMap<SignatureIndex, VMTrampoline> =
             PrimaryMap::new();
         for ptr in allocated_functions
-            .drain(0..call_trampoline_count)
+            .by_ref()
+            .take(call_trampoline_count)
             .map(|slice| slice.as_ptr())
         {
             // TODO: What in damnation have you done?! – Bannon
@@ -608,15 +1771,22 @@ impl UniversalEngineInner {
             allocated_function_call_trampolines.push(trampoline);
         }
 
-        let allocated_functions_result = allocated_functions
-            .drain(0..function_count)
+        if streamed_functions.is_none() {
+            for &index in &cold {
+                place(index, allocated_functions.next());
+            }
+        }
+
+        let allocated_functions_result = local_bodies
+            .into_iter()
             .enumerate()
-            .map(|(index, slice)| -> Result<_, CompileError> {
+            .map(|(index, body)| -> Result<_, CompileError> {
+                let (body, len) = body.expect("every local function is allocated");
                 let index = LocalFunctionIndex::new(index);
                 let (sig_idx, sig) = function_signature(index);
                 Ok(VMLocalFunction {
-                    body: FunctionBodyPtr(slice.as_ptr()),
-                    length: u32::try_from(slice.len()).map_err(|_| {
+                    body: FunctionBodyPtr(body),
+                    length: u32::try_from(len).map_err(|_| {
                         CompileError::Codegen("function body length exceeds 4GiB".into())
                     })?,
                     signature: sig,
@@ -626,7 +1796,6 @@ impl UniversalEngineInner {
             .collect::<Result<PrimaryMap<LocalFunctionIndex, _>, _>>()?;
 
         let allocated_dynamic_function_trampolines = allocated_functions
-            .drain(..)
             .map(|slice| FunctionBodyPtr(slice.as_ptr()))
             .collect::<PrimaryMap<FunctionIndex, _>>();
 
@@ -652,12 +1821,140 @@ impl UniversalEngineInner {
             allocated_function_call_trampolines,
             allocated_dynamic_function_trampolines,
             allocated_custom_sections,
+            lease,
         ))
     }
 
+    /// Start placing the functions of a binary of `binary_len` bytes into code memory as they
+    /// are compiled, if the engine does so and can reserve enough code memory for them.
+    #[cfg(feature = "compiler")]
+    fn start_loader(&self, binary_len: usize) -> Option<Loader> {
+        // Hot functions are placed first, before they are all compiled.
+        if !self.pipelined_loading || !self.hot_functions.is_empty() {
+            return None;
+        }
+        let reservation = crate::pipeline::reservation_size(binary_len);
+        Loader::start(self.try_map_code_memory(reservation).ok()?)
+    }
+
+    /// Map `len` bytes of code memory, releasing the code of dropped artifacts first if the
+    /// limit or the operating system would refuse them otherwise.
+    fn map_code_memory(&mut self, len: usize) -> Result<CodeMemory, CompileError> {
+        let mut result = self.try_map_code_memory(len);
+        if result.is_err() && self.reclaim_code_memory(usize::MAX) > 0 {
+            result = self.try_map_code_memory(len);
+        }
+        result.map_err(|largest_free_block| self.code_memory_exhausted(len, largest_free_block))
+    }
+
+    /// Take `memory`, already mapped, as code memory of this engine, releasing the code of
+    /// dropped artifacts first if the limit would refuse it otherwise.
+    fn adopt_code_memory(&mut self, memory: CodeMemory) -> Result<CodeMemory, CompileError> {
+        let len = memory.mapped_len();
+        let available = |inner: &Self| match inner.code_memory_limit {
+            Some(limit) => limit.saturating_sub(inner.code_memory_in_use()),
+            None => usize::MAX,
+        };
+        if available(self) < len {
+            self.reclaim_code_memory(usize::MAX);
+        }
+        match available(self) {
+            available if available < len => Err(self.code_memory_exhausted(len, available)),
+            _ => Ok(memory),
+        }
+    }
+
+    /// Report that `len` bytes of code memory were refused.
+    fn code_memory_exhausted(&self, len: usize, largest_free_block: usize) -> CompileError {
+        if let Some(sink) = &self.metrics_sink {
+            sink.increment(Counter::CodeMemoryExhausted, 1);
+        }
+        CompileError::CodeMemoryExhausted {
+            requested: len,
+            in_use: self.code_memory_in_use(),
+            largest_free_block,
+        }
+    }
+
+    /// Map `len` bytes of code memory, or return the most that could be mapped instead.
+    fn try_map_code_memory(&self, len: usize) -> Result<CodeMemory, usize> {
+        if let Some(limit) = self.code_memory_limit {
+            let available = limit.saturating_sub(self.code_memory_in_use());
+            if available < len {
+                return Err(available);
+            }
+        }
+        match &self.page_allocator {
+            Some(allocator) => CodeMemory::with_allocator(allocator.clone(), len),
+            None => CodeMemory::with_capacity(len),
+        }
+        .map_err(|_| 0)
+    }
+
+    /// Release the code memory of the artifacts that were dropped, until at least
+    /// `target_bytes` are released, returning the number of bytes released.
+    pub(crate) fn reclaim_code_memory(&mut self, target_bytes: usize) -> usize {
+        let (mut released, mut count) = (0, 0);
+        self.code_memory.retain(|code| {
+            if released >= target_bytes || code.lease.strong_count() > 0 {
+                return true;
+            }
+            released += code.memory.mapped_len();
+            count += 1;
+            false
+        });
+        if count > 0 {
+            if let Some(sink) = &self.metrics_sink {
+                sink.increment(Counter::CodeMemoryReclaimed, count);
+                sink.adjust(Gauge::CodeBytes, -(released as i64));
+            }
+        }
+        released
+    }
+
+    /// The number of bytes of code memory of the artifacts that were dropped.
+    pub(crate) fn dropped_code_memory(&self) -> usize {
+        self.code_memory
+            .iter()
+            .filter(|code| code.lease.strong_count() == 0)
+            .map(|code| code.memory.mapped_len())
+            .sum()
+    }
+
+    /// The number of bytes of code memory mapped.
+    fn code_memory_in_use(&self) -> usize {
+        self.code_memory
+            .iter()
+            .map(|code| code.memory.mapped_len())
+            .sum()
+    }
+
+    /// Have an artifact use the installed trampolines rather than its own, so that all the
+    /// artifacts of the engine share them.
+    fn link_installed_trampolines(
+        &self,
+        signatures: &BoxedSlice<SignatureIndex, VMSharedSignatureIndex>,
+        trampolines: &mut PrimaryMap<SignatureIndex, VMTrampoline>,
+        functions: &mut PrimaryMap<LocalFunctionIndex, VMLocalFunction>,
+    ) {
+        if self.installed_trampolines.is_empty() {
+            return;
+        }
+        for (index, trampoline) in trampolines.iter_mut() {
+            if let Some(installed) = self.installed_trampolines.get(&signatures[index]) {
+                *trampoline = *installed;
+            }
+        }
+        for function in functions.values_mut() {
+            if let Some(installed) = self.installed_trampolines.get(&function.signature) {
+                function.trampoline = *installed;
+            }
+        }
+    }
+
     /// Make memory containing compiled code executable.
     pub(crate) fn publish_compiled_code(&mut self) {
-        self.code_memory.last_mut().unwrap().publish();
+        self.code_memory.last_mut().unwrap().memory.publish();
     }
 
     /// Register DWARF-type exception handling information associated with the code.
@@ -665,6 +1962,7 @@ impl UniversalEngineInner {
         self.code_memory
             .last_mut()
             .unwrap()
+            .memory
             .unwind_registry_mut()
             .publish(eh_frame)
             .map_err(|e| {
@@ -678,3 +1976,27 @@ impl UniversalEngineInner {
         &self.func_data
     }
 }
+
+/// The code memory of an artifact.
+struct LoadedCode {
+    memory: CodeMemory,
+    /// Dead once the artifact is dropped, after which the memory can be released.
+    lease: Weak<CodeMemoryLease>,
+}
+
+/// Keeps the code memory of an artifact from being released while alive.
+///
+/// The engine only releases the code of dropped artifacts when it runs out of code memory,
+/// so that dropping an artifact never waits on the engine. The tenant the artifact was loaded
+/// for, if any, is credited back as soon as the artifact is dropped though.
+pub(crate) struct CodeMemoryLease {
+    _tenant_charge: Option<TenantCharge>,
+}
+
+impl Drop for UniversalEngineInner {
+    fn drop(&mut self) {
+        if let Some(sink) = &self.metrics_sink {
+            sink.adjust(Gauge::CodeBytes, -(self.code_memory_in_use() as i64));
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::provenance::{Provenance, SerializeOptions};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use enumset::EnumSet;
@@ -6,16 +8,18 @@ use rkyv::ser::serializers::{
     AllocScratchError, AllocSerializer, CompositeSerializerError, SharedSerializeMapError,
 };
 use wasmer_compiler::{
-    CompileError, CompileModuleInfo, CompiledFunctionFrameInfo, CpuFeature, CustomSection, Dwarf,
-    Features, FunctionBody, JumpTableOffsets, Relocation, SectionIndex, TrampolinesSection,
+    CompileError, CompileModuleInfo, CompiledFunction, CompiledFunctionFrameInfo,
+    CompiledFunctionUnwindInfo, CpuFeature, CustomSection, Dwarf, Features, FunctionBody,
+    JumpTableOffsets, Relocation, SectionIndex, SourceMap, TrampolinesSection, UnusedItems,
 };
 use wasmer_engine::{DeserializeError, Engine};
-use wasmer_types::entity::PrimaryMap;
+use wasmer_types::entity::{ArchivedPrimaryMap, PrimaryMap};
 use wasmer_types::{
-    ExportIndex, FunctionIndex, ImportIndex, LocalFunctionIndex, OwnedDataInitializer,
+    ErrorCode, ExportIndex, ExternType, ExternalDataInitializer, FunctionIndex, FunctionType,
+    FunctionTypeRef, HasErrorCode, ImportIndex, LocalFunctionIndex, OwnedDataInitializer,
     SignatureIndex,
 };
-use wasmer_vm::Artifact;
+use wasmer_vm::{Artifact, TrapCode};
 
 const MAGIC_HEADER: [u8; 32] = {
     let value = *b"\0wasmer-universal\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF";
@@ -23,6 +27,32 @@ const MAGIC_HEADER: [u8; 32] = {
     value
 };
 
+/// Limits on the executables [`UniversalExecutableRef::deserialize_with_limits`] accepts.
+///
+/// The defaults are well above what compiling any module the validator accepts produces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeserializeLimits {
+    /// Largest serialized executable, in bytes.
+    pub max_total_size: usize,
+    /// Most local functions.
+    pub max_functions: usize,
+    /// Most relocations, of the functions and custom sections together.
+    pub max_relocations: usize,
+    /// Most entries of custom metadata, see [`ArtifactEditor`](crate::ArtifactEditor).
+    pub max_custom_metadata: usize,
+}
+
+impl Default for DeserializeLimits {
+    fn default() -> Self {
+        Self {
+            max_total_size: 1 << 30,
+            max_functions: 1_000_000,
+            max_relocations: 1 << 26,
+            max_custom_metadata: 1024,
+        }
+    }
+}
+
 /// A 0-copy view of the encoded `UniversalExecutable` payload.
 #[derive(Clone, Copy)]
 pub struct UniversalExecutableRef<'a> {
@@ -49,37 +79,177 @@ impl<'a> UniversalExecutableRef<'a> {
         let (remaining, position) = data.split_at(data.len() - 8);
         let mut position_value = [0u8; 8];
         position_value.copy_from_slice(position);
-        if u64::from_le_bytes(position_value) > remaining.len() as u64 {
+        // The root of the archive is written last, so it ends the payload: this also catches
+        // truncated buffers, whose last bytes are then unlikely to describe such a root.
+        let payload_len = (remaining.len() - MAGIC_HEADER.len()) as u64;
+        let root_len = std::mem::size_of::<ArchivedUniversalExecutable>() as u64;
+        if u64::from_le_bytes(position_value).checked_add(root_len) != Some(payload_len) {
             return Err("the buffer is malformed");
         }
-        // TODO(0-copy): bytecheck too.
+        // The rest is validated by `deserialize_with_limits`.
         Ok(())
     }
 
+    /// The alignment of the buffers executables are deserialized from, which their contents
+    /// are accessed in place at.
+    pub const ALIGNMENT: usize = rkyv::AlignedVec::ALIGNMENT;
+
+    /// Call `f` with `data`, copied to a buffer aligned to [`UniversalExecutableRef::ALIGNMENT`]
+    /// if it is not already.
+    pub fn with_aligned<R>(data: &[u8], f: impl FnOnce(&[u8]) -> R) -> R {
+        if data.as_ptr() as usize & (Self::ALIGNMENT - 1) == 0 {
+            return f(data);
+        }
+        let mut aligned = rkyv::AlignedVec::with_capacity(data.len());
+        aligned.extend_from_slice(data);
+        f(&aligned)
+    }
+
+    /// Deserialize an executable within the default [`DeserializeLimits`].
+    ///
     /// # Safety
     ///
-    /// This method is unsafe since it deserializes data directly
-    /// from memory.
-    /// Right now we are not doing any extra work for validation, but
-    /// `rkyv` has an option to do bytecheck on the serialized data before
-    /// serializing (via `rkyv::check_archived_value`).
+    /// See [`UniversalExecutableRef::deserialize_with_limits`].
     pub unsafe fn deserialize(
         data: &'a [u8],
     ) -> Result<UniversalExecutableRef<'a>, DeserializeError> {
-        Self::verify_serialized(data).map_err(|e| DeserializeError::Incompatible(e.to_string()))?;
+        Self::deserialize_with_limits(data, &DeserializeLimits::default())
+    }
+
+    /// Deserialize an executable without copying it, failing with
+    /// [`DeserializeError::LimitExceeded`] if it is larger than `limits` allow.
+    ///
+    /// Everything the executable holds, from its functions and relocations to its module info,
+    /// frame info, jump tables and source map, is validated before it is used: each value must
+    /// lie within the buffer, be aligned and have a valid bit pattern, and no two values may
+    /// claim the same bytes. Damaged executables fail with
+    /// [`DeserializeError::CorruptedBinary`]. Validation takes no allocation proportional to
+    /// the lengths it reads: a length claiming more than the buffer holds fails without being
+    /// trusted.
+    ///
+    /// `data` must be aligned to [`UniversalExecutableRef::ALIGNMENT`], or this fails with
+    /// [`DeserializeError::Generic`]; see [`UniversalExecutableRef::with_aligned`].
+    ///
+    /// # Safety
+    ///
+    /// The code of the executable is run as it is, so that the bytes must still come from
+    /// [`UniversalExecutable::serialize`] of a trusted engine.
+    pub unsafe fn deserialize_with_limits(
+        data: &'a [u8],
+        limits: &DeserializeLimits,
+    ) -> Result<UniversalExecutableRef<'a>, DeserializeError> {
+        if data.len() > limits.max_total_size {
+            return Err(exceeded("bytes", data.len(), limits.max_total_size));
+        }
+        if data.as_ptr() as usize & (Self::ALIGNMENT - 1) != 0 {
+            return Err(DeserializeError::Generic(format!(
+                "the buffer of the executable is not aligned to {} bytes",
+                Self::ALIGNMENT
+            )));
+        }
+        Self::verify_serialized(data).map_err(|e| {
+            // Past the header, the data was serialized by us and got damaged since.
+            if data.starts_with(&MAGIC_HEADER) {
+                DeserializeError::CorruptedBinary(e.to_string())
+            } else {
+                DeserializeError::Incompatible(e.to_string())
+            }
+        })?;
         let (archive, position) = data.split_at(data.len() - 8);
         let mut position_value = [0u8; 8];
         position_value.copy_from_slice(position);
-        let (_, data) = archive.split_at(MAGIC_HEADER.len());
+        let (_, payload) = archive.split_at(MAGIC_HEADER.len());
+        let archive = rkyv::check_archived_value::<UniversalExecutable>(
+            payload,
+            u64::from_le_bytes(position_value) as usize,
+        )
+        .map_err(|e| {
+            DeserializeError::CorruptedBinary(format!("the executable is malformed: {}", e))
+        })?;
+        check_archive(archive, limits)?;
         Ok(UniversalExecutableRef {
             buffer: data,
-            archive: rkyv::archived_value::<UniversalExecutable>(
-                data,
-                u64::from_le_bytes(position_value) as usize,
-            ),
+            archive,
         })
     }
 
+    /// The signature attached to this executable, if any.
+    ///
+    /// See [`UniversalExecutable::signature_block`].
+    pub fn signature_block(&self) -> Option<&[u8]> {
+        self.archive
+            .signature_block
+            .as_ref()
+            .map(|block| &block[..])
+    }
+
+    /// The metadata attached to this executable under `key`, if any.
+    ///
+    /// See [`UniversalExecutable::custom_metadata`].
+    pub fn custom_metadata(&self, key: &str) -> Option<&[u8]> {
+        self.archive
+            .custom_metadata
+            .get(key)
+            .map(|bytes| &bytes[..])
+    }
+
+    /// How this executable was produced.
+    ///
+    /// See [`UniversalExecutable::provenance`].
+    pub fn provenance(&self) -> Result<Provenance, DeserializeError> {
+        Provenance::decode(&self.archive.provenance)
+    }
+
+    /// Content hashes of the local functions in this executable.
+    pub fn function_hashes(&self) -> Vec<(LocalFunctionIndex, [u8; 32])> {
+        self.archive
+            .function_hashes
+            .iter()
+            .map(|(idx, hash)| (idx, *hash))
+            .collect()
+    }
+
+    /// Source hashes of the local functions in this executable.
+    ///
+    /// See [`UniversalExecutable::function_source_hashes`].
+    pub fn function_source_hashes(&self) -> Vec<(LocalFunctionIndex, [u8; 32])> {
+        self.archive
+            .function_source_hashes
+            .iter()
+            .map(|(idx, hash)| (idx, *hash))
+            .collect()
+    }
+
+    /// The namespaces, names and types of the imports of this executable, in declaration
+    /// order, read from the archive without loading its code.
+    pub fn imports(&self) -> Vec<(String, String, ExternType)> {
+        let module = &self.archive.compile_info.module;
+        module
+            .imports
+            .iter()
+            .map(|((namespace, name, _), index)| {
+                let ty = match index {
+                    ImportIndex::Function(i) => {
+                        let signature = &module.signatures[&module.functions[i]];
+                        let signature = FunctionTypeRef::from(signature);
+                        ExternType::Function(FunctionType::new(
+                            signature.params(),
+                            signature.results(),
+                        ))
+                    }
+                    ImportIndex::Table(i) => ExternType::Table(unrkyv(&module.tables[i])),
+                    ImportIndex::Memory(i) => ExternType::Memory(unrkyv(&module.memories[i])),
+                    ImportIndex::Global(i) => ExternType::Global(unrkyv(&module.globals[i])),
+                };
+                (
+                    namespace.as_str().to_string(),
+                    name.as_str().to_string(),
+                    ty,
+                )
+            })
+            .collect()
+    }
+
     // TODO(0-copy): this should never fail.
     /// Convert this reference to an owned `UniversalExecutable` value.
     pub fn to_owned(self) -> Result<UniversalExecutable, DeserializeError> {
@@ -89,12 +259,56 @@ impl<'a> UniversalExecutableRef<'a> {
     }
 }
 
+/// The error of a deserialized executable with `count` `what`, more than `max`.
+fn exceeded(what: &str, count: usize, max: usize) -> DeserializeError {
+    DeserializeError::LimitExceeded(format!(
+        "{} {}, more than the limit of {}",
+        count, what, max
+    ))
+}
+
+/// The number of relocations in a map of relocations.
+fn count_relocations<K: rkyv::Archive + wasmer_types::entity::EntityRef>(
+    relocations: &ArchivedPrimaryMap<K, Vec<Relocation>>,
+) -> usize {
+    relocations.values().fold(0usize, |count, relocations| {
+        count.saturating_add(relocations.len())
+    })
+}
+
+/// Check `archive`, once validated, against `limits`, see
+/// [`UniversalExecutableRef::deserialize_with_limits`].
+fn check_archive(
+    archive: &ArchivedUniversalExecutable,
+    limits: &DeserializeLimits,
+) -> Result<(), DeserializeError> {
+    let functions = archive.function_bodies.len();
+    if functions > limits.max_functions {
+        return Err(exceeded("functions", functions, limits.max_functions));
+    }
+    let relocations = count_relocations(&archive.function_relocations)
+        .saturating_add(count_relocations(&archive.custom_section_relocations));
+    if relocations > limits.max_relocations {
+        return Err(exceeded("relocations", relocations, limits.max_relocations));
+    }
+    let metadata = archive.custom_metadata.len();
+    if metadata > limits.max_custom_metadata {
+        return Err(exceeded(
+            "custom metadata entries",
+            metadata,
+            limits.max_custom_metadata,
+        ));
+    }
+    Ok(())
+}
+
 /// A wasm module compiled to some shape, ready to be loaded with `UniversalEngine` to produce an
 /// `UniversalArtifact`.
 ///
 /// This is the result obtained after validating and compiling a WASM module with any of the
 /// supported compilers. This type falls in-between a module and [`Artifact`](crate::Artifact).
 #[derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize)]
+#[archive(check_bytes)]
 pub struct UniversalExecutable {
     pub(crate) function_bodies: PrimaryMap<LocalFunctionIndex, FunctionBody>,
     pub(crate) function_relocations: PrimaryMap<LocalFunctionIndex, Vec<Relocation>>,
@@ -110,12 +324,231 @@ pub struct UniversalExecutable {
     pub(crate) trampolines: Option<TrampolinesSection>,
     pub(crate) compile_info: CompileModuleInfo,
     pub(crate) data_initializers: Vec<OwnedDataInitializer>,
+    pub(crate) external_data_initializers: Vec<ExternalDataInitializer>,
+    // Contents of the external data segments, only available right after compilation.
+    #[with(rkyv::with::Skip)]
+    pub(crate) external_data: Vec<Vec<u8>>,
     pub(crate) cpu_features: u64,
+    pub(crate) function_hashes: PrimaryMap<LocalFunctionIndex, [u8; 32]>,
+    // Keys of the local functions in a `FunctionCodeIndex`.
+    pub(crate) function_source_hashes: PrimaryMap<LocalFunctionIndex, [u8; 32]>,
+    // Hash of the binary this executable was compiled from.
+    pub(crate) wasm_hash: [u8; 32],
+    // Local functions placed first in code memory, in this order.
+    pub(crate) hot_functions: Vec<LocalFunctionIndex>,
+    // Source locations of the module offsets, if retained.
+    pub(crate) source_map: Option<SourceMap>,
+    // Identifies the binary and bindings of specialized executables.
+    pub(crate) specialization_hash: Option<[u8; 32]>,
+    // Imported functions and globals the code never uses.
+    pub(crate) unused_items: UnusedItems,
+    // Imported functions whose calls the code raises a trap for instead, with its code.
+    pub(crate) trap_imports: Vec<(FunctionIndex, TrapCode)>,
+    // Signature attached by the embedder, opaque to the engine.
+    pub(crate) signature_block: Option<Vec<u8>>,
+    // Metadata attached by the embedder, by key.
+    pub(crate) custom_metadata: BTreeMap<String, Vec<u8>>,
+    // How the executable was produced, encoded by `Provenance::encode`.
+    pub(crate) provenance: Vec<u8>,
+}
+
+impl UniversalExecutable {
+    /// The table mapping module offsets to source locations, if one was retained.
+    ///
+    /// See [`Universal::retain_source_maps`](crate::Universal::retain_source_maps).
+    pub fn source_map(&self) -> Option<&SourceMap> {
+        self.source_map.as_ref()
+    }
+
+    /// Machine code of the local functions in this executable.
+    pub fn function_bodies(&self) -> &PrimaryMap<LocalFunctionIndex, FunctionBody> {
+        &self.function_bodies
+    }
+
+    /// The local functions whose code is placed first in code memory, in order.
+    ///
+    /// See [`Universal::hot_functions`](crate::Universal::hot_functions).
+    pub fn hot_functions(&self) -> &[LocalFunctionIndex] {
+        &self.hot_functions
+    }
+
+    /// Data segments whose contents are kept out of this executable.
+    ///
+    /// See [`Universal::externalize_data_segments`](crate::Universal::externalize_data_segments).
+    pub fn external_data_segments(&self) -> &[ExternalDataInitializer] {
+        &self.external_data_initializers
+    }
+
+    /// Hashes and contents of the data segments kept out of this executable, to be stored
+    /// where the [`DataProvider`](wasmer_types::DataProvider) used for instantiation can find
+    /// them.
+    ///
+    /// The contents are not serialized, so this is empty for deserialized executables.
+    pub fn external_data(&self) -> impl Iterator<Item = (&[u8; 32], &[u8])> {
+        self.external_data_initializers
+            .iter()
+            .zip(self.external_data.iter())
+            .map(|(init, data)| (&init.hash, &data[..]))
+    }
+
+    /// The hash identifying the binary and the bindings this executable was specialized for,
+    /// or `None` if it is not specialized.
+    ///
+    /// See [`UniversalEngine::compile_universal_specialized`](crate::UniversalEngine::compile_universal_specialized).
+    pub fn specialization_hash(&self) -> Option<[u8; 32]> {
+        self.specialization_hash
+    }
+
+    /// The imported functions and the globals the code of this executable never uses, and
+    /// whether the functions were stripped from the imports its instances must be given.
+    ///
+    /// See [`CompilerConfig::strip_unused_imports`](wasmer_compiler::CompilerConfig::strip_unused_imports).
+    pub fn unused_items(&self) -> &UnusedItems {
+        &self.unused_items
+    }
+
+    /// The signature attached to this executable with
+    /// [`ArtifactEditor::replace_signature_block`](crate::ArtifactEditor::replace_signature_block),
+    /// if any.
+    ///
+    /// The engine does not interpret the signature.
+    pub fn signature_block(&self) -> Option<&[u8]> {
+        self.signature_block.as_deref()
+    }
+
+    /// The metadata attached to this executable under `key` with
+    /// [`ArtifactEditor::add_custom_metadata`](crate::ArtifactEditor::add_custom_metadata), if
+    /// any.
+    pub fn custom_metadata(&self, key: &str) -> Option<&[u8]> {
+        self.custom_metadata.get(key).map(|bytes| &bytes[..])
+    }
+
+    /// How this executable was produced, see [`Provenance`].
+    ///
+    /// Fails if the provenance block of a deserialized executable was damaged.
+    pub fn provenance(&self) -> Result<Provenance, DeserializeError> {
+        Provenance::decode(&self.provenance)
+    }
+
+    /// Serialize this executable, first recording the build time and the embedder entries of
+    /// `options` in its provenance.
+    ///
+    /// The executable keeps them, so serializing it again gives the same bytes.
+    /// [`serialize`](wasmer_engine::Executable::serialize) records neither, so that compiling
+    /// the same binary the same way always gives the same bytes.
+    pub fn serialize_with(
+        &mut self,
+        options: &SerializeOptions,
+    ) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut provenance = self.provenance()?;
+        options.apply(&mut provenance);
+        self.provenance = provenance.encode();
+        wasmer_engine::Executable::serialize(self)
+    }
+
+    /// A hash of the code of this executable.
+    ///
+    /// The hash covers the machine code with its relocations, unwind and trap metadata,
+    /// the trampolines, the custom sections, and what the code was compiled for: the
+    /// features, CPU features, memory and table styles and global bindings. Editing the
+    /// metadata of an executable with [`ArtifactEditor`](crate::ArtifactEditor) does not
+    /// change it.
+    pub fn code_hash(&self) -> [u8; 32] {
+        crate::editor::code_hash(self)
+    }
+
+    /// The SHA-256 hash of the binary this executable was compiled from.
+    ///
+    /// Executables compiled from the same binary have the same hash, whatever the compiler
+    /// and engine configuration.
+    pub fn wasm_hash(&self) -> [u8; 32] {
+        self.wasm_hash
+    }
+
+    /// A stable identifier of this executable, to cache it under.
+    ///
+    /// The identifier is a hash of the [`wasm_hash`](Self::wasm_hash) of the binary, the
+    /// [`engine_id`](Provenance::engine_id) of the engine that compiled it, which is the
+    /// [`fingerprint`](Engine::fingerprint) of that engine, and the
+    /// [`specialization_hash`](Self::specialization_hash) if any. Compiling the same binary
+    /// with engines of the same fingerprint always gives executables with the same identifier.
+    ///
+    /// Fails if the provenance block of a deserialized executable was damaged.
+    pub fn artifact_id(&self) -> Result<[u8; 32], DeserializeError> {
+        Ok(crate::provenance::artifact_id(
+            &self.wasm_hash,
+            self.specialization_hash.as_ref(),
+            &self.provenance()?.engine_id,
+        ))
+    }
+
+    /// Content hashes of the local functions in this executable.
+    ///
+    /// A hash covers the function's machine code, with relocation sites replaced by their
+    /// symbolic targets, the shape of its trap and address map metadata, and the code of the
+    /// local functions it calls, transitively. It does not depend on the location of the
+    /// function or its callees in memory or in the wasm binary, so identical functions compiled
+    /// as part of different modules hash identically, unless the compiler put their index in
+    /// their code, as Singlepass does for diagnostics.
+    pub fn function_hashes(&self) -> Vec<(LocalFunctionIndex, [u8; 32])> {
+        self.function_hashes
+            .iter()
+            .map(|(idx, hash)| (idx, *hash))
+            .collect()
+    }
+
+    /// Source hashes of the local functions in this executable.
+    ///
+    /// A source hash covers what the compiler generated the code of the function from: the
+    /// compiler and its configuration, the target and the features, the function's index and
+    /// body, and the parts of the module its code depends on. Functions with the same source
+    /// hash compile to the same code, which is what a
+    /// [`FunctionCodeIndex`](crate::FunctionCodeIndex) is keyed by.
+    pub fn function_source_hashes(&self) -> Vec<(LocalFunctionIndex, [u8; 32])> {
+        self.function_source_hashes
+            .iter()
+            .map(|(idx, hash)| (idx, *hash))
+            .collect()
+    }
+
+    /// The code of a local function of this executable, with its relocations and metadata.
+    ///
+    /// If the unwind info of the function is in the Dwarf section of the executable, the
+    /// function comes with its entry in the section, as
+    /// [`CompiledFunctionUnwindInfo::DwarfEntry`].
+    pub fn compiled_function(&self, index: LocalFunctionIndex) -> CompiledFunction {
+        let mut body = self.function_bodies[index].clone();
+        if let (Some(CompiledFunctionUnwindInfo::Dwarf), Some(debug)) =
+            (&body.unwind_info, &self.debug)
+        {
+            if let Some(entry) = crate::eh_frame::function_entry(
+                self.custom_sections[debug.eh_frame].bytes.as_slice(),
+                &self.custom_section_relocations[debug.eh_frame],
+                index,
+            ) {
+                body.unwind_info = Some(CompiledFunctionUnwindInfo::DwarfEntry(entry));
+            }
+        }
+        CompiledFunction {
+            body,
+            relocations: self.function_relocations[index].clone(),
+            jt_offsets: self.function_jt_offsets[index].clone(),
+            frame_info: self.function_frame_info[index].clone(),
+        }
+    }
+
+    /// Describes the imports and exports of this executable, with their types, in `format`.
+    ///
+    /// The description starts with a comment naming the module and giving a hash of the rest
+    /// of the description, which lists the imports then the exports by index.
+    pub fn interface_text(&self, format: crate::InterfaceFormat) -> String {
+        crate::interface::interface_text(&self.compile_info.module, format)
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
 pub enum ExecutableSerializeError {
-    #[error("could not serialize the executable data")]
+    #[error("could not serialize the executable data [{}]", ErrorCode::Serialize)]
     Executable(
         #[source]
         CompositeSerializerError<
@@ -126,6 +559,14 @@ pub enum ExecutableSerializeError {
     ),
 }
 
+impl HasErrorCode for ExecutableSerializeError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Executable(_) => ErrorCode::Serialize,
+        }
+    }
+}
+
 impl wasmer_engine::Executable for UniversalExecutable {
     fn load(
         &self,
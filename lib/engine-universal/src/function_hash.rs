@@ -0,0 +1,404 @@
+//! Content hashes of compiled function bodies, and the source hashes functions are compiled from.
+//!
+//! A function hash covers the function's machine code and unwind info with
+//! every relocation site masked out and replaced by its symbolic target, plus
+//! the shape of its trap and address-map metadata, and the code of the local
+//! functions it calls. Nothing in the hash depends on where the code is loaded,
+//! nor on the position of the function or its callees in the wasm binary, so two
+//! modules that contain the same function compiled to the same code will report
+//! the same hash for it.
+//!
+//! A source hash covers what the compiler generates the code of a function from,
+//! so it is known before compiling, and lets
+//! [`UniversalEngine::compile_universal_deduped`](crate::UniversalEngine::compile_universal_deduped)
+//! skip the codegen of the functions the embedder already has.
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use wasmer_compiler::wasmparser::{Operator, TypeOrFuncType};
+use wasmer_compiler::{
+    CompileModuleInfo, CompiledFunction, CompiledFunctionFrameInfo, CompiledFunctionUnwindInfoRef,
+    FunctionBody, FunctionBodyData, FunctionBodyRef, FunctionReader, ModuleTranslationState,
+    Relocation, RelocationKind, RelocationTarget, SourceLoc, WasmError,
+};
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{FunctionIndex, LocalFunctionIndex, SignatureIndex};
+
+/// A lookup table of function code the embedder already has, keyed by the
+/// source hashes reported by
+/// [`UniversalExecutable::function_source_hashes`](crate::UniversalExecutable::function_source_hashes).
+///
+/// See [`UniversalEngine::compile_universal_deduped`](crate::UniversalEngine::compile_universal_deduped).
+pub trait FunctionCodeIndex {
+    /// Return the function previously compiled from the source with this hash, if any, as
+    /// given by [`UniversalExecutable::compiled_function`](crate::UniversalExecutable::compiled_function).
+    fn lookup(&self, source_hash: &[u8; 32]) -> Option<CompiledFunction>;
+}
+
+/// Number of bytes patched in at the relocation site for the given kind.
+fn relocation_width(kind: RelocationKind) -> usize {
+    match kind {
+        RelocationKind::Abs8 | RelocationKind::X86PCRel8 => 8,
+        _ => 4,
+    }
+}
+
+/// A tag for the relocation kind, fixed here so that hashes do not change with the
+/// declaration or the `Debug` output of `RelocationKind`.
+fn relocation_kind_tag(kind: RelocationKind) -> u8 {
+    match kind {
+        RelocationKind::Abs4 => 0,
+        RelocationKind::Abs8 => 1,
+        RelocationKind::X86PCRel4 => 2,
+        RelocationKind::X86PCRel8 => 3,
+        RelocationKind::X86PCRelRodata4 => 4,
+        RelocationKind::X86CallPCRel4 => 5,
+        RelocationKind::X86CallPLTRel4 => 6,
+        RelocationKind::X86GOTPCRel4 => 7,
+        RelocationKind::Arm32Call => 8,
+        RelocationKind::Arm64Call => 9,
+        RelocationKind::Arm64Movw0 => 10,
+        RelocationKind::Arm64Movw1 => 11,
+        RelocationKind::Arm64Movw2 => 12,
+        RelocationKind::Arm64Movw3 => 13,
+        RelocationKind::ElfX86_64TlsGd => 14,
+    }
+}
+
+fn hash_relocation(hasher: &mut Sha256, relocation: &Relocation) {
+    hasher.update(relocation.offset.to_le_bytes());
+    hasher.update(relocation.addend.to_le_bytes());
+    hasher.update([relocation_kind_tag(relocation.kind)]);
+    match relocation.reloc_target {
+        RelocationTarget::LocalFunc(_) => {
+            // The callee is covered by the hashes of the call graph, see `function_hashes`.
+            hasher.update([0]);
+        }
+        RelocationTarget::LibCall(libcall) => {
+            hasher.update([1]);
+            hasher.update(libcall.to_function_name().as_bytes());
+        }
+        RelocationTarget::JumpTable(_, jt) => {
+            // The owning function is always the function being hashed.
+            hasher.update([2]);
+            hasher.update(jt.as_u32().to_le_bytes());
+        }
+        RelocationTarget::CustomSection(idx) => {
+            hasher.update([3]);
+            hasher.update(idx.as_u32().to_le_bytes());
+        }
+    }
+}
+
+/// The relocations of a function, by offset.
+fn sorted(relocations: &[Relocation]) -> Vec<&Relocation> {
+    let mut relocations = relocations.iter().collect::<Vec<_>>();
+    relocations.sort_by_key(|r| r.offset);
+    relocations
+}
+
+/// Compute the hash of the code of a single compiled function, leaving out its callees.
+fn code_hash(
+    body: FunctionBodyRef<'_>,
+    relocations: &[Relocation],
+    frame_info: &CompiledFunctionFrameInfo,
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    let mut code = body.body.to_vec();
+    let relocations = sorted(relocations);
+    for relocation in relocations.iter() {
+        let start = relocation.offset as usize;
+        let end = (start + relocation_width(relocation.kind)).min(code.len());
+        if start < end {
+            code[start..end].iter_mut().for_each(|b| *b = 0);
+        }
+    }
+    hasher.update((code.len() as u64).to_le_bytes());
+    hasher.update(&code);
+    match body.unwind_info {
+        None => hasher.update([0]),
+        Some(CompiledFunctionUnwindInfoRef::WindowsX64(info)) => {
+            hasher.update([1]);
+            hasher.update((info.len() as u64).to_le_bytes());
+            hasher.update(info);
+        }
+        // The entry of the function in the Dwarf section of its module is not hashed.
+        Some(CompiledFunctionUnwindInfoRef::Dwarf)
+        | Some(CompiledFunctionUnwindInfoRef::DwarfEntry(_)) => hasher.update([2]),
+    }
+    hasher.update((relocations.len() as u64).to_le_bytes());
+    for relocation in relocations {
+        hash_relocation(&mut hasher, relocation);
+    }
+    hasher.update((frame_info.traps.len() as u64).to_le_bytes());
+    for trap in frame_info.traps.iter() {
+        hasher.update(trap.code_offset.to_le_bytes());
+        hasher.update(trap.trap_code.to_raw().to_le_bytes());
+    }
+    // Source locations are absolute offsets into the wasm binary, so only
+    // their position relative to the function start is part of the shape.
+    let address_map = &frame_info.address_map;
+    let start = address_map.start_srcloc.bits();
+    hasher.update((address_map.instructions.len() as u64).to_le_bytes());
+    for instruction in address_map.instructions.iter() {
+        hasher.update(instruction.srcloc.bits().wrapping_sub(start).to_le_bytes());
+        hasher.update((instruction.code_offset as u64).to_le_bytes());
+        hasher.update((instruction.code_len as u64).to_le_bytes());
+    }
+    hasher.update(
+        address_map
+            .end_srcloc
+            .bits()
+            .wrapping_sub(start)
+            .to_le_bytes(),
+    );
+    hasher.update((address_map.body_len as u64).to_le_bytes());
+    hasher.finalize().into()
+}
+
+/// Compute the content hashes of the local functions of a module.
+///
+/// The hash of a function covers its own code and the code of the local functions it calls,
+/// transitively. Within a strongly connected component of the call graph, callees are
+/// numbered in the order a traversal of the calls from the function reaches them, rather than
+/// identified by their index in the module; calls leaving the component are covered by the
+/// hash of the callee, computed first.
+pub(crate) fn function_hashes(
+    bodies: &PrimaryMap<LocalFunctionIndex, FunctionBody>,
+    relocations: &PrimaryMap<LocalFunctionIndex, Vec<Relocation>>,
+    frame_infos: &PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>,
+) -> PrimaryMap<LocalFunctionIndex, [u8; 32]> {
+    let code = bodies
+        .iter()
+        .map(|(idx, body)| code_hash(body.into(), &relocations[idx], &frame_infos[idx]))
+        .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+    let callees = relocations
+        .values()
+        .map(|relocations| {
+            sorted(relocations)
+                .into_iter()
+                .filter_map(|relocation| match relocation.reloc_target {
+                    RelocationTarget::LocalFunc(callee) => Some(callee),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+
+    let components = call_graph_components(&callees);
+    let mut component_of = vec![0; callees.len()];
+    for (component, functions) in components.iter().enumerate() {
+        for function in functions {
+            component_of[function.index()] = component;
+        }
+    }
+    let mut hashes: Vec<Option<[u8; 32]>> = vec![None; callees.len()];
+    for functions in components.iter() {
+        for &root in functions {
+            let mut hasher = Sha256::new();
+            let mut order = vec![root];
+            let mut numbers = HashMap::new();
+            numbers.insert(root, 0u32);
+            let mut next = 0;
+            while let Some(&function) = order.get(next) {
+                next += 1;
+                hasher.update(code[function]);
+                hasher.update((callees[function].len() as u64).to_le_bytes());
+                for &callee in callees[function].iter() {
+                    if component_of[callee.index()] == component_of[function.index()] {
+                        let number = numbers.len() as u32;
+                        let number = *numbers.entry(callee).or_insert_with(|| {
+                            order.push(callee);
+                            number
+                        });
+                        hasher.update([0]);
+                        hasher.update(number.to_le_bytes());
+                    } else {
+                        hasher.update([1]);
+                        hasher.update(hashes[callee.index()].unwrap());
+                    }
+                }
+            }
+            hashes[root.index()] = Some(hasher.finalize().into());
+        }
+    }
+    hashes.into_iter().map(Option::unwrap).collect()
+}
+
+/// The strongly connected components of the call graph, each after the components it calls
+/// into, as found by Tarjan's algorithm.
+fn call_graph_components(
+    callees: &PrimaryMap<LocalFunctionIndex, Vec<LocalFunctionIndex>>,
+) -> Vec<Vec<LocalFunctionIndex>> {
+    const UNVISITED: usize = usize::MAX;
+    let mut index = vec![UNVISITED; callees.len()];
+    let mut lowlink = vec![0; callees.len()];
+    let mut on_stack = vec![false; callees.len()];
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+    let mut next_index = 0;
+    for root in callees.keys() {
+        if index[root.index()] != UNVISITED {
+            continue;
+        }
+        index[root.index()] = next_index;
+        lowlink[root.index()] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root.index()] = true;
+        // The functions being visited, with the position of their next callee to look at.
+        let mut visiting = vec![(root, 0)];
+        while let Some((function, position)) = visiting.last_mut() {
+            let function = *function;
+            if let Some(&callee) = callees[function].get(*position) {
+                *position += 1;
+                if index[callee.index()] == UNVISITED {
+                    index[callee.index()] = next_index;
+                    lowlink[callee.index()] = next_index;
+                    next_index += 1;
+                    stack.push(callee);
+                    on_stack[callee.index()] = true;
+                    visiting.push((callee, 0));
+                } else if on_stack[callee.index()] {
+                    lowlink[function.index()] =
+                        lowlink[function.index()].min(index[callee.index()]);
+                }
+                continue;
+            }
+            visiting.pop();
+            if let Some((caller, _)) = visiting.last() {
+                lowlink[caller.index()] = lowlink[caller.index()].min(lowlink[function.index()]);
+            }
+            if lowlink[function.index()] == index[function.index()] {
+                let mut component = Vec::new();
+                loop {
+                    let member = stack.pop().unwrap();
+                    on_stack[member.index()] = false;
+                    component.push(member);
+                    if member == function {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+    components
+}
+
+fn hash_field(hasher: &mut Sha256, bytes: &[u8]) {
+    hasher.update((bytes.len() as u64).to_le_bytes());
+    hasher.update(bytes);
+}
+
+/// Compute the source hashes of the local functions of a module, the keys of
+/// [`FunctionCodeIndex`].
+///
+/// A source hash covers everything the compiler generates the code of the function from: the
+/// engine id, which covers the compiler and its configuration, the target and the features;
+/// the layout of the instance; the types and styles of the globals, memories and tables, and
+/// the bound globals; the signature and body of the function; and the signatures of the
+/// functions and types its body refers to, along with the names of the imported functions,
+/// which the compiler may replace with intrinsics.
+pub(crate) fn source_hashes(
+    engine_id: &[u8; 32],
+    info: &CompileModuleInfo,
+    translation: &ModuleTranslationState,
+    inputs: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+) -> Result<PrimaryMap<LocalFunctionIndex, [u8; 32]>, WasmError> {
+    let module = &info.module;
+    let mut shared = Sha256::new();
+    shared.update(engine_id);
+    let counts = &module.import_counts;
+    for count in [
+        counts.functions,
+        counts.tables,
+        counts.memories,
+        counts.globals,
+    ]
+    .iter()
+    {
+        shared.update(count.to_le_bytes());
+    }
+    for count in [
+        module.signatures.len(),
+        module.tables.len(),
+        module.memories.len(),
+        module.globals.len(),
+    ]
+    .iter()
+    {
+        shared.update((*count as u64).to_le_bytes());
+    }
+    hash_field(&mut shared, format!("{:?}", module.globals).as_bytes());
+    hash_field(&mut shared, format!("{:?}", module.memories).as_bytes());
+    hash_field(&mut shared, format!("{:?}", module.tables).as_bytes());
+    hash_field(&mut shared, format!("{:?}", info.memory_styles).as_bytes());
+    hash_field(&mut shared, format!("{:?}", info.table_styles).as_bytes());
+    hash_field(
+        &mut shared,
+        format!("{:?}", info.global_bindings).as_bytes(),
+    );
+
+    let signature = |hasher: &mut Sha256, index: SignatureIndex| {
+        hash_field(hasher, format!("{:?}", module.signatures[index]).as_bytes());
+    };
+    inputs
+        .iter()
+        .map(|(local, input)| {
+            let mut hasher = shared.clone();
+            signature(&mut hasher, module.functions[module.func_index(local)]);
+            hash_field(&mut hasher, input.data);
+            let reader = FunctionReader::new(input.module_offset, input.data);
+            for operator in reader.get_operators_reader()? {
+                match operator? {
+                    Operator::Call { function_index }
+                    | Operator::ReturnCall { function_index }
+                    | Operator::RefFunc { function_index } => {
+                        let function = FunctionIndex::from_u32(function_index);
+                        signature(&mut hasher, module.functions[function]);
+                        match translation.import_map.get(&function) {
+                            Some(name) => {
+                                hasher.update([1]);
+                                hash_field(&mut hasher, name.as_bytes());
+                            }
+                            None => hasher.update([0]),
+                        }
+                    }
+                    Operator::CallIndirect { index, .. }
+                    | Operator::ReturnCallIndirect { index, .. }
+                    | Operator::Block {
+                        ty: TypeOrFuncType::FuncType(index),
+                    }
+                    | Operator::Loop {
+                        ty: TypeOrFuncType::FuncType(index),
+                    }
+                    | Operator::If {
+                        ty: TypeOrFuncType::FuncType(index),
+                    } => signature(&mut hasher, SignatureIndex::from_u32(index)),
+                    _ => {}
+                }
+            }
+            Ok(hasher.finalize().into())
+        })
+        .collect()
+}
+
+/// Move the source locations of a function compiled as part of another module to
+/// `module_offset`, the offset of its body in the module being compiled.
+pub(crate) fn rebase(function: &mut CompiledFunction, module_offset: usize) {
+    let address_map = &mut function.frame_info.address_map;
+    let delta = (module_offset as u32).wrapping_sub(address_map.start_srcloc.bits());
+    let shift = |srcloc: SourceLoc| {
+        if srcloc.is_default() {
+            srcloc
+        } else {
+            SourceLoc::new(srcloc.bits().wrapping_add(delta))
+        }
+    };
+    address_map.start_srcloc = shift(address_map.start_srcloc);
+    address_map.end_srcloc = shift(address_map.end_srcloc);
+    for instruction in address_map.instructions.iter_mut() {
+        instruction.srcloc = shift(instruction.srcloc);
+    }
+}
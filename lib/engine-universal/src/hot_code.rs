@@ -0,0 +1,152 @@
+//! Placing the code of hot functions first in code memory, and pinning it there.
+
+use std::io;
+#[cfg(feature = "compiler")]
+use wasmer_types::{ExportIndex, FunctionIndex, LocalFunctionIndex, ModuleInfo};
+
+/// How [`UniversalArtifact::pin_hot_code`](crate::UniversalArtifact::pin_hot_code) keeps the
+/// code of the hot functions resident.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PinMode {
+    /// Lock the pages in memory with `mlock`, so that they are never evicted.
+    Mlock,
+    /// Ask for the pages to be backed by transparent huge pages with `madvise`, so that
+    /// they take fewer iTLB entries.
+    HugePage,
+    /// Both of the above.
+    Both,
+}
+
+impl PinMode {
+    fn mlock(self) -> bool {
+        matches!(self, Self::Mlock | Self::Both)
+    }
+
+    fn huge_pages(self) -> bool {
+        matches!(self, Self::HugePage | Self::Both)
+    }
+}
+
+/// The outcome of [`UniversalArtifact::pin_hot_code`](crate::UniversalArtifact::pin_hot_code),
+/// for each of the requested modes.
+///
+/// Failing to pin the code is not an error: the code runs the same, only with the latency
+/// pinning was meant to avoid.
+#[derive(Debug)]
+pub struct PinReport {
+    /// The number of bytes covered: the hot code rounded up to whole pages.
+    pub bytes: usize,
+    /// The result of locking the pages, if requested. Fails with `EPERM` or `ENOMEM` when
+    /// `RLIMIT_MEMLOCK` is too low.
+    pub mlock: Option<io::Result<()>>,
+    /// The result of asking for huge pages, if requested. Fails with `EINVAL` when the kernel
+    /// has no transparent huge pages.
+    pub huge_pages: Option<io::Result<()>>,
+}
+
+impl PinReport {
+    /// The number of bytes locked in memory.
+    pub fn locked_bytes(&self) -> usize {
+        match self.mlock {
+            Some(Ok(())) => self.bytes,
+            _ => 0,
+        }
+    }
+}
+
+/// The local functions named by `names`, in order, skipping imported, unknown and repeated
+/// functions.
+///
+/// Names are export names or, for functions that are not exported, function indices.
+#[cfg(feature = "compiler")]
+pub(crate) fn resolve(module: &ModuleInfo, names: &[String]) -> Vec<LocalFunctionIndex> {
+    let mut hot = Vec::new();
+    for name in names {
+        let index = match module.exports.get(name) {
+            Some(ExportIndex::Function(index)) => *index,
+            Some(_) => continue,
+            None => match name.parse() {
+                Ok(index) => FunctionIndex::from_u32(index),
+                Err(_) => continue,
+            },
+        };
+        if index.as_u32() as usize >= module.functions.len() {
+            continue;
+        }
+        if let Some(local) = module.local_func_index(index) {
+            if !hot.contains(&local) {
+                hot.push(local);
+            }
+        }
+    }
+    hot
+}
+
+/// Pin the `len` bytes of code at `start`, which must be page-aligned.
+pub(crate) fn pin(start: *const u8, len: usize, mode: PinMode, locked: bool) -> PinReport {
+    let bytes = round_up(len, region::page::size());
+    let skip = bytes == 0;
+    PinReport {
+        bytes,
+        mlock: if !mode.mlock() {
+            None
+        } else if skip || locked {
+            Some(Ok(()))
+        } else {
+            Some(mlock(start, bytes))
+        },
+        huge_pages: if !mode.huge_pages() {
+            None
+        } else if skip {
+            Some(Ok(()))
+        } else {
+            Some(madvise_huge_pages(start, bytes))
+        },
+    }
+}
+
+/// Undo the locking of the `bytes` reported by [`pin`].
+pub(crate) fn unlock(start: *const u8, bytes: usize) {
+    #[cfg(unix)]
+    unsafe {
+        libc::munlock(start as *const libc::c_void, bytes);
+    }
+    #[cfg(not(unix))]
+    let _ = (start, bytes);
+}
+
+#[cfg(unix)]
+fn mlock(start: *const u8, bytes: usize) -> io::Result<()> {
+    if unsafe { libc::mlock(start as *const libc::c_void, bytes) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn mlock(_start: *const u8, _bytes: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "locking code in memory is only supported on Unix",
+    ))
+}
+
+#[cfg(target_os = "linux")]
+fn madvise_huge_pages(start: *const u8, bytes: usize) -> io::Result<()> {
+    if unsafe { libc::madvise(start as *mut libc::c_void, bytes, libc::MADV_HUGEPAGE) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn madvise_huge_pages(_start: *const u8, _bytes: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "huge pages for code are only supported on Linux",
+    ))
+}
+
+fn round_up(size: usize, multiple: usize) -> usize {
+    (size + (multiple - 1)) & !(multiple - 1)
+}
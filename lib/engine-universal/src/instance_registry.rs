@@ -0,0 +1,99 @@
+//! Keeping track of the live instances of an engine, for debugging.
+
+use crate::UniversalArtifact;
+use std::collections::HashMap;
+use std::sync::{Mutex, Weak};
+use std::time::SystemTime;
+use wasmer_vm::{InstanceHandle, InstanceId, InstanceUsage, WeakInstanceRef};
+
+/// A live instance of a [`UniversalEngine`](crate::UniversalEngine), as listed by
+/// [`UniversalEngine::instances`](crate::UniversalEngine::instances).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstanceSnapshotInfo {
+    /// The identifier of the instance.
+    pub id: InstanceId,
+    /// The name of the module the instance was created from, if it has one.
+    pub module_name: Option<String>,
+    /// The [consensus hash](UniversalArtifact::consensus_hash) of the module the instance was
+    /// created from.
+    pub module_hash: [u8; 32],
+    /// When the instance was created.
+    pub created: SystemTime,
+    /// The resources the instance used when the snapshot was taken.
+    pub usage: InstanceUsage,
+}
+
+struct Entry {
+    instance: WeakInstanceRef,
+    artifact: Weak<UniversalArtifact>,
+    created: SystemTime,
+}
+
+#[derive(Default)]
+struct Entries {
+    by_id: HashMap<InstanceId, Entry>,
+    /// The number of entries above which the next registration prunes the dead ones.
+    prune_above: usize,
+}
+
+/// The instances of an engine, held weakly so that the registry never keeps them alive.
+///
+/// Dead entries are only removed while registering and listing, so that dropping an instance
+/// does not involve the registry.
+#[derive(Default)]
+pub(crate) struct InstanceRegistry {
+    entries: Mutex<Entries>,
+}
+
+impl InstanceRegistry {
+    /// Register the instance `handle` of `artifact`.
+    pub(crate) fn register(&self, handle: &InstanceHandle, artifact: Weak<UniversalArtifact>) {
+        let entry = Entry {
+            instance: handle.downgrade(),
+            artifact,
+            created: SystemTime::now(),
+        };
+        let mut entries = self.entries.lock().unwrap();
+        // Pruning once the live entries doubled keeps registrations amortized constant time.
+        if entries.by_id.len() >= entries.prune_above {
+            entries
+                .by_id
+                .retain(|_, entry| entry.instance.upgrade().is_some());
+            entries.prune_above = (2 * entries.by_id.len()).max(16);
+        }
+        entries.by_id.insert(handle.id(), entry);
+    }
+
+    /// Return the live instances, by ascending identifier.
+    pub(crate) fn instances(&self) -> Vec<InstanceSnapshotInfo> {
+        // The instances are kept alive while the snapshot is taken, outside of the lock.
+        let live = {
+            let mut entries = self.entries.lock().unwrap();
+            let mut live = Vec::with_capacity(entries.by_id.len());
+            entries.by_id.retain(|id, entry| {
+                let instance = entry.instance.upgrade();
+                let artifact = entry.artifact.upgrade();
+                match (instance, artifact) {
+                    (Some(instance), Some(artifact)) => {
+                        live.push((*id, instance, artifact, entry.created));
+                        true
+                    }
+                    _ => false,
+                }
+            });
+            live
+        };
+        let mut snapshot = live
+            .into_iter()
+            .map(|(id, instance, artifact, created)| InstanceSnapshotInfo {
+                id,
+                module_name: artifact.interface.name.clone(),
+                module_hash: artifact.consensus_hash(),
+                created,
+                usage: instance.usage(),
+            })
+            .collect::<Vec<_>>();
+        snapshot.sort_by_key(|info| info.id);
+        snapshot
+    }
+}
@@ -0,0 +1,316 @@
+//! Text descriptions of the interface of a module: its imports and exports, with their types.
+//!
+//! The description is derived from the module info alone, so it is the same for every
+//! compilation of a binary. Items are listed by index, and the header carries a hash of the
+//! listing so that documentation pipelines can tell when an interface changed.
+
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use wasmer_types::{
+    ExportIndex, FunctionIndex, GlobalType, ImportIndex, MemoryType, ModuleInfo, Mutability,
+    TableType, Type,
+};
+
+/// The syntax of the description of the interface of a module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InterfaceFormat {
+    /// The `(import ...)` and `(export ...)` declarations of the WebAssembly text format,
+    /// with the types of the imported and exported items.
+    Wat,
+    /// A simplified WIT-like listing: the imports grouped by module, then the exports.
+    WitLike,
+}
+
+/// An imported or exported item, with the names of the function and its parameters.
+enum Item {
+    Func {
+        name: String,
+        params: Vec<(String, Type)>,
+        results: Vec<Type>,
+    },
+    Table(TableType),
+    Memory(MemoryType),
+    Global(GlobalType),
+}
+
+impl Item {
+    fn new(module: &ModuleInfo, index: &ExportIndex) -> Self {
+        match *index {
+            ExportIndex::Function(index) => function(module, index),
+            ExportIndex::Table(index) => Self::Table(module.tables[index]),
+            ExportIndex::Memory(index) => Self::Memory(module.memories[index]),
+            ExportIndex::Global(index) => Self::Global(module.globals[index]),
+        }
+    }
+}
+
+/// Describes the function, using positional names for what the name section does not name.
+fn function(module: &ModuleInfo, index: FunctionIndex) -> Item {
+    let signature = &module.signatures[module.functions[index]];
+    let name = module
+        .function_names
+        .get(&index)
+        .map_or_else(|| format!("f{}", index.as_u32()), |name| identifier(name));
+    let params = signature
+        .params()
+        .iter()
+        .enumerate()
+        .map(|(position, ty)| {
+            let name = module
+                .param_names
+                .get(&(index, position as u32))
+                .map_or_else(|| format!("p{}", position), |name| identifier(name));
+            (name, *ty)
+        })
+        .collect();
+    Item::Func {
+        name,
+        params,
+        results: signature.results().to_vec(),
+    }
+}
+
+/// Describes the imports and exports of `module`, see [`InterfaceFormat`].
+pub(crate) fn interface_text(module: &ModuleInfo, format: InterfaceFormat) -> String {
+    let mut imports = module.imports.iter().collect::<Vec<_>>();
+    imports.sort_by_key(|((_, _, import_no), _)| *import_no);
+    let imports = imports
+        .into_iter()
+        .map(|((module_name, field, _), index)| {
+            let index = match *index {
+                ImportIndex::Function(index) => ExportIndex::Function(index),
+                ImportIndex::Table(index) => ExportIndex::Table(index),
+                ImportIndex::Memory(index) => ExportIndex::Memory(index),
+                ImportIndex::Global(index) => ExportIndex::Global(index),
+            };
+            (&**module_name, &**field, Item::new(module, &index))
+        })
+        .collect::<Vec<_>>();
+    let mut exports = module.exports.iter().collect::<Vec<_>>();
+    exports.sort_by_key(|(name, index)| (export_order(index), *name));
+    let exports = exports
+        .into_iter()
+        .map(|(name, index)| (&**name, Item::new(module, index)))
+        .collect::<Vec<_>>();
+
+    let (comment, body) = match format {
+        InterfaceFormat::Wat => (";;", wat(&imports, &exports)),
+        InterfaceFormat::WitLike => ("//", wit_like(&imports, &exports)),
+    };
+    let hash: String = Sha256::digest(body.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!(
+        "{} module {}\n{} interface sha256:{}\n{}",
+        comment,
+        module.name(),
+        comment,
+        hash,
+        body
+    )
+}
+
+/// Orders exports by kind, then by index, as the sections of the binary would.
+fn export_order(index: &ExportIndex) -> (u8, u32) {
+    match *index {
+        ExportIndex::Function(index) => (0, index.as_u32()),
+        ExportIndex::Table(index) => (1, index.as_u32()),
+        ExportIndex::Memory(index) => (2, index.as_u32()),
+        ExportIndex::Global(index) => (3, index.as_u32()),
+    }
+}
+
+fn wat(imports: &[(&str, &str, Item)], exports: &[(&str, Item)]) -> String {
+    let mut out = String::new();
+    for (module, field, item) in imports {
+        let _ = writeln!(
+            out,
+            "(import {} {} {})",
+            wat_string(module),
+            wat_string(field),
+            wat_item(item)
+        );
+    }
+    for (name, item) in exports {
+        let _ = writeln!(out, "(export {} {})", wat_string(name), wat_item(item));
+    }
+    out
+}
+
+fn wat_item(item: &Item) -> String {
+    match item {
+        Item::Func {
+            name,
+            params,
+            results,
+        } => {
+            let mut out = format!("(func ${}", name);
+            for (name, ty) in params {
+                let _ = write!(out, " (param ${} {})", name, type_name(*ty));
+            }
+            if !results.is_empty() {
+                out.push_str(" (result");
+                for ty in results {
+                    let _ = write!(out, " {}", type_name(*ty));
+                }
+                out.push(')');
+            }
+            out.push(')');
+            out
+        }
+        Item::Table(table) => match table.maximum {
+            Some(maximum) => format!(
+                "(table {} {} {})",
+                table.minimum,
+                maximum,
+                type_name(table.ty)
+            ),
+            None => format!("(table {} {})", table.minimum, type_name(table.ty)),
+        },
+        Item::Memory(memory) => {
+            let mut out = format!("(memory {}", memory.minimum.0);
+            if let Some(maximum) = memory.maximum {
+                let _ = write!(out, " {}", maximum.0);
+            }
+            if memory.shared {
+                out.push_str(" shared");
+            }
+            out.push(')');
+            out
+        }
+        Item::Global(global) => match global.mutability {
+            Mutability::Const => format!("(global {})", type_name(global.ty)),
+            Mutability::Var => format!("(global (mut {}))", type_name(global.ty)),
+        },
+    }
+}
+
+/// Quotes `s` as a string of the WebAssembly text format.
+fn wat_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for byte in s.bytes() {
+        match byte {
+            b'"' | b'\\' => {
+                out.push('\\');
+                out.push(byte as char);
+            }
+            0x20..=0x7e => out.push(byte as char),
+            _ => {
+                let _ = write!(out, "\\{:02x}", byte);
+            }
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn wit_like(imports: &[(&str, &str, Item)], exports: &[(&str, Item)]) -> String {
+    // Import modules are listed in the order of their first import.
+    let mut modules: Vec<&str> = vec![];
+    for (module, _, _) in imports {
+        if !modules.contains(module) {
+            modules.push(module);
+        }
+    }
+    let mut out = String::new();
+    for module in modules {
+        let _ = writeln!(out, "\nimport {} {{", wit_key(module));
+        for (_, field, item) in imports.iter().filter(|(m, _, _)| *m == module) {
+            let _ = writeln!(out, "  {}: {}", wit_key(field), wit_item(item));
+        }
+        out.push_str("}\n");
+    }
+    if !exports.is_empty() {
+        out.push_str("\nexport {\n");
+        for (name, item) in exports {
+            let _ = writeln!(out, "  {}: {}", wit_key(name), wit_item(item));
+        }
+        out.push_str("}\n");
+    }
+    out
+}
+
+fn wit_item(item: &Item) -> String {
+    match item {
+        Item::Func {
+            params, results, ..
+        } => {
+            let params = params
+                .iter()
+                .map(|(name, ty)| format!("{}: {}", name, type_name(*ty)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let results = results.iter().map(|ty| type_name(*ty)).collect::<Vec<_>>();
+            match results.len() {
+                0 => format!("func({})", params),
+                1 => format!("func({}) -> {}", params, results[0]),
+                _ => format!("func({}) -> ({})", params, results.join(", ")),
+            }
+        }
+        Item::Table(table) => match table.maximum {
+            Some(maximum) => format!(
+                "table({}, {}..{})",
+                type_name(table.ty),
+                table.minimum,
+                maximum
+            ),
+            None => format!("table({}, {}..)", type_name(table.ty), table.minimum),
+        },
+        Item::Memory(memory) => {
+            let mut out = format!("memory({}..", memory.minimum.0);
+            if let Some(maximum) = memory.maximum {
+                let _ = write!(out, "{}", maximum.0);
+            }
+            if memory.shared {
+                out.push_str(", shared");
+            }
+            out.push(')');
+            out
+        }
+        Item::Global(global) => match global.mutability {
+            Mutability::Const => format!("global({})", type_name(global.ty)),
+            Mutability::Var => format!("global(mut {})", type_name(global.ty)),
+        },
+    }
+}
+
+/// Keeps plain names as they are and quotes the others.
+fn wit_key(name: &str) -> String {
+    let plain = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if plain {
+        name.to_string()
+    } else {
+        format!("{:?}", name)
+    }
+}
+
+/// Replaces the characters a text format identifier cannot have.
+fn identifier(name: &str) -> String {
+    if name.is_empty() {
+        return "_".to_string();
+    }
+    name.chars()
+        .map(|c| match c {
+            '0'..='9' | 'a'..='z' | 'A'..='Z' => c,
+            '!' | '#' | '$' | '%' | '&' | '\'' | '*' | '+' | '-' | '.' | '/' | ':' | '<' | '='
+            | '>' | '?' | '@' | '\\' | '^' | '_' | '`' | '|' | '~' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+fn type_name(ty: Type) -> &'static str {
+    match ty {
+        Type::I32 => "i32",
+        Type::I64 => "i64",
+        Type::F32 => "f32",
+        Type::F64 => "f64",
+        Type::V128 => "v128",
+        Type::ExternRef => "externref",
+        Type::FuncRef => "funcref",
+    }
+}
@@ -27,17 +27,47 @@
 mod artifact;
 mod builder;
 mod code_memory;
+mod editor;
+mod eh_frame;
 mod engine;
 mod executable;
+mod function_hash;
+mod hot_code;
+mod instance_registry;
+mod interface;
 mod link;
+mod pipeline;
+#[cfg(all(feature = "pressure-watcher", target_os = "linux"))]
+mod pressure;
+mod provenance;
+mod reclaim;
+#[cfg(feature = "compiler")]
+mod source_map;
+mod specialization;
+mod tenant;
+mod trampolines;
+#[cfg(unix)]
+mod subprocess;
 mod unwind;
 
 pub use crate::artifact::UniversalArtifact;
 pub use crate::builder::Universal;
 pub use crate::code_memory::CodeMemory;
+pub use crate::editor::{ArtifactEditError, ArtifactEditor};
 pub use crate::engine::UniversalEngine;
-pub use crate::executable::{UniversalExecutable, UniversalExecutableRef};
+pub use crate::executable::{DeserializeLimits, UniversalExecutable, UniversalExecutableRef};
+pub use crate::function_hash::FunctionCodeIndex;
+pub use crate::hot_code::{PinMode, PinReport};
+pub use crate::instance_registry::InstanceSnapshotInfo;
+pub use crate::interface::InterfaceFormat;
 pub use crate::link::link_module;
+#[cfg(all(feature = "pressure-watcher", target_os = "linux"))]
+pub use crate::pressure::{PressureWatcher, PressureWatcherConfig};
+pub use crate::provenance::{Provenance, SerializeOptions};
+#[cfg(unix)]
+pub use crate::subprocess::{serve_compile_request, SubprocessCompiler, SubprocessLimits};
+pub use crate::tenant::TenantUsage;
+pub use crate::trampolines::TrampolineSetExecutable;
 
 /// Version number of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -3,7 +3,8 @@
 use std::collections::HashMap;
 use std::ptr::{read_unaligned, write_unaligned};
 use wasmer_compiler::{
-    JumpTable, Relocation, RelocationKind, RelocationTarget, SectionIndex, TrampolinesSection,
+    JumpTable, JumpTableOffsets, Relocation, RelocationKind, RelocationTarget, SectionIndex,
+    TrampolinesSection,
 };
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::LocalFunctionIndex;
@@ -87,7 +88,25 @@ fn apply_relocation(
             *allocated_functions[func_index].body as usize + offset as usize
         }
     };
+    patch_relocation(
+        body,
+        r,
+        target_func_address,
+        allocated_sections,
+        trampolines,
+        trampolines_map,
+    );
+}
 
+/// Patch the code at `body` for the relocation `r` to reach `target_func_address`.
+fn patch_relocation(
+    body: usize,
+    r: &Relocation,
+    target_func_address: usize,
+    allocated_sections: &PrimaryMap<SectionIndex, SectionBodyPtr>,
+    trampolines: &Option<TrampolinesSection>,
+    trampolines_map: &mut HashMap<usize, usize>,
+) {
     match r.kind {
         #[cfg(target_pointer_width = "64")]
         RelocationKind::Abs8 => unsafe {
@@ -166,6 +185,46 @@ fn apply_relocation(
     }
 }
 
+/// Whether the relocation `r` of the function `index` only depends on where that function
+/// is, so that it can be applied before the other functions and the custom sections are
+/// placed.
+pub(crate) fn is_local_relocation(index: LocalFunctionIndex, r: &Relocation) -> bool {
+    let local_target = match r.reloc_target {
+        RelocationTarget::LibCall(_) => true,
+        RelocationTarget::JumpTable(func_index, _) => func_index == index,
+        RelocationTarget::LocalFunc(_) | RelocationTarget::CustomSection(_) => false,
+    };
+    // Calls out of reach go through the trampolines section, which is placed last.
+    local_target && r.kind != RelocationKind::Arm64Call
+}
+
+/// Apply the relocations of the function `index` placed at `body` that
+/// [`is_local_relocation`] finds, leaving the others to [`link_module`].
+pub(crate) fn link_function_locally(
+    body: usize,
+    index: LocalFunctionIndex,
+    relocations: &[Relocation],
+    jt_offsets: &JumpTableOffsets,
+) {
+    let no_sections = PrimaryMap::new();
+    let mut no_trampolines = HashMap::new();
+    for r in relocations.iter().filter(|r| is_local_relocation(index, r)) {
+        let target_func_address = match r.reloc_target {
+            RelocationTarget::LibCall(libcall) => libcall.function_pointer(),
+            RelocationTarget::JumpTable(_, jt) => body + jt_offsets[jt] as usize,
+            _ => unreachable!("not a local relocation"),
+        };
+        patch_relocation(
+            body,
+            r,
+            target_func_address,
+            &no_sections,
+            &None,
+            &mut no_trampolines,
+        );
+    }
+}
+
 /// Links a module, patching the allocated functions with the
 /// required relocations and jump tables.
 pub fn link_module(
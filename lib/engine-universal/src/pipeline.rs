@@ -0,0 +1,157 @@
+//! Loading the functions of a module into code memory while the others are compiling.
+
+#[cfg(feature = "compiler")]
+use crate::link::link_function_locally;
+use crate::CodeMemory;
+#[cfg(feature = "compiler")]
+use std::sync::mpsc::{sync_channel, SyncSender};
+#[cfg(feature = "compiler")]
+use std::sync::Mutex;
+#[cfg(feature = "compiler")]
+use std::thread::JoinHandle;
+#[cfg(feature = "compiler")]
+use wasmer_compiler::{CompiledFunction, FunctionBody, JumpTableOffsets, Relocation};
+#[cfg(feature = "compiler")]
+use wasmer_types::entity::EntityRef;
+#[cfg(feature = "compiler")]
+use wasmer_types::LocalFunctionIndex;
+
+/// The number of compiled functions handed over to the loader at once, so that it is not
+/// woken up for each function.
+#[cfg(feature = "compiler")]
+const FUNCTIONS_PER_BATCH: usize = 32;
+
+/// The most batches of compiled functions waiting for the loader, which bounds the memory
+/// they take.
+#[cfg(feature = "compiler")]
+const BATCHES_IN_FLIGHT: usize = 4;
+
+/// The bytes of code memory to reserve for each byte of the binary.
+///
+/// Singlepass emits around ten bytes of code for each byte of function body: modules that
+/// need more than this are loaded once compiled instead.
+#[cfg(feature = "compiler")]
+const RESERVED_BYTES_PER_BINARY_BYTE: usize = 32;
+
+/// The bytes of code memory to reserve for any binary, for the smallest functions.
+#[cfg(feature = "compiler")]
+const RESERVED_BYTES: usize = 1 << 16;
+
+/// The bytes of code memory to reserve for the functions of a binary of `binary_len` bytes,
+/// and the rest of its code.
+#[cfg(feature = "compiler")]
+pub(crate) fn reservation_size(binary_len: usize) -> usize {
+    binary_len
+        .saturating_mul(RESERVED_BYTES_PER_BINARY_BYTE)
+        .saturating_add(RESERVED_BYTES)
+}
+
+/// A compiled function on its way to the loader.
+#[cfg(feature = "compiler")]
+struct StreamedFunction {
+    index: LocalFunctionIndex,
+    body: FunctionBody,
+    relocations: Vec<Relocation>,
+    jt_offsets: JumpTableOffsets,
+}
+
+/// Places the functions the compiler hands over into code memory, on a thread of its own.
+///
+/// Each function is copied after the previous ones as soon as it is compiled, and the
+/// relocations that only depend on where it is are applied. The others, to the other
+/// functions and the custom sections, are left for when the whole module is loaded.
+#[cfg(feature = "compiler")]
+pub(crate) struct Loader {
+    sender: SyncSender<Vec<StreamedFunction>>,
+    /// The functions compiled since the last batch was sent.
+    batch: Mutex<Vec<StreamedFunction>>,
+    thread: JoinHandle<Option<StreamedCode>>,
+}
+
+/// The functions of a module placed into code memory by a [`Loader`].
+pub(crate) struct StreamedCode {
+    /// The code memory, with the functions appended.
+    pub(crate) memory: CodeMemory,
+    /// The address and length of the code of each function, by local function index.
+    pub(crate) functions: Vec<(usize, usize)>,
+}
+
+#[cfg(feature = "compiler")]
+impl Loader {
+    /// Start placing functions into `memory`, or return `None` if no thread can be spawned.
+    pub(crate) fn start(mut memory: CodeMemory) -> Option<Self> {
+        let (sender, receiver) = sync_channel::<Vec<StreamedFunction>>(BATCHES_IN_FLIGHT);
+        let thread = std::thread::Builder::new()
+            .name("wasmer-loader".to_string())
+            .spawn(move || {
+                let mut functions = Vec::new();
+                let mut overflowed = false;
+                for function in receiver.into_iter().flatten() {
+                    // Keep receiving, so that the compiler is never blocked.
+                    if overflowed {
+                        continue;
+                    }
+                    let body = match memory.append_function((&function.body).into()) {
+                        Some(body) => body,
+                        None => {
+                            overflowed = true;
+                            continue;
+                        }
+                    };
+                    let (address, len) = (body.as_ptr() as usize, body.len());
+                    link_function_locally(
+                        address,
+                        function.index,
+                        &function.relocations,
+                        &function.jt_offsets,
+                    );
+                    let index = function.index.index();
+                    if functions.len() <= index {
+                        functions.resize(index + 1, None);
+                    }
+                    functions[index] = Some((address, len));
+                }
+                if overflowed {
+                    return None;
+                }
+                let functions = functions.into_iter().collect::<Option<Vec<_>>>()?;
+                Some(StreamedCode { memory, functions })
+            })
+            .ok()?;
+        Some(Self {
+            sender,
+            batch: Mutex::new(Vec::with_capacity(FUNCTIONS_PER_BATCH)),
+            thread,
+        })
+    }
+
+    /// Hand the compiled function `index` over to the loader, waiting if too many are in
+    /// flight already.
+    pub(crate) fn send(&self, index: LocalFunctionIndex, function: &CompiledFunction) {
+        let function = StreamedFunction {
+            index,
+            body: function.body.clone(),
+            relocations: function.relocations.clone(),
+            jt_offsets: function.jt_offsets.clone(),
+        };
+        let full = {
+            let mut batch = self.batch.lock().unwrap();
+            batch.push(function);
+            if batch.len() < FUNCTIONS_PER_BATCH {
+                return;
+            }
+            std::mem::replace(&mut *batch, Vec::with_capacity(FUNCTIONS_PER_BATCH))
+        };
+        // The loader only stops receiving if it panicked, and `finish` reports that.
+        let _ = self.sender.send(full);
+    }
+
+    /// Wait for the loader to place the functions handed over, returning them unless the
+    /// reserved code memory was too small for them or the loader panicked.
+    pub(crate) fn finish(self) -> Option<StreamedCode> {
+        let last = self.batch.into_inner().unwrap();
+        let _ = self.sender.send(last);
+        drop(self.sender);
+        self.thread.join().ok().flatten()
+    }
+}
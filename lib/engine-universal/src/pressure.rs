@@ -0,0 +1,199 @@
+//! Reclaiming memory automatically when Linux reports memory pressure.
+
+use crate::UniversalEngine;
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use wasmer_vm::{ReclaimPriority, ReclaimReport};
+
+type ReclaimHandler = dyn Fn(&ReclaimReport) + Send + Sync;
+
+/// When a [`PressureWatcher`] reclaims memory, and how much.
+///
+/// Nothing is watched by default: at least one of the thresholds must be set.
+#[derive(Clone)]
+pub struct PressureWatcherConfig {
+    interval: Duration,
+    max_priority: ReclaimPriority,
+    psi_path: PathBuf,
+    psi_threshold: Option<(f64, usize)>,
+    cgroup_dir: PathBuf,
+    cgroup_headroom: Option<u64>,
+    on_reclaim: Option<Arc<ReclaimHandler>>,
+}
+
+impl PressureWatcherConfig {
+    /// Watch every second, reclaiming memory of up to `max_priority`.
+    pub fn new(max_priority: ReclaimPriority) -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            max_priority,
+            psi_path: PathBuf::from("/proc/pressure/memory"),
+            psi_threshold: None,
+            cgroup_dir: PathBuf::from("/sys/fs/cgroup"),
+            cgroup_headroom: None,
+            on_reclaim: None,
+        }
+    }
+
+    /// Check the pressure every `interval`.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Reclaim `target_bytes` whenever some tasks were stalled waiting for memory during at
+    /// least `some_avg10` percent of the last 10 seconds, according to the pressure stall
+    /// information of the kernel.
+    pub fn with_psi_threshold(mut self, some_avg10: f64, target_bytes: usize) -> Self {
+        self.psi_threshold = Some((some_avg10, target_bytes));
+        self
+    }
+
+    /// Read the pressure stall information from `path` rather than `/proc/pressure/memory`,
+    /// such as the `memory.pressure` file of a cgroup.
+    pub fn with_psi_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.psi_path = path.into();
+        self
+    }
+
+    /// Reclaim memory whenever the memory used by the cgroup comes within `headroom_bytes` of
+    /// its limit, as much as needed to restore the headroom.
+    ///
+    /// Cgroups without a memory limit are never under pressure.
+    pub fn with_cgroup_headroom(mut self, headroom_bytes: u64) -> Self {
+        self.cgroup_headroom = Some(headroom_bytes);
+        self
+    }
+
+    /// Read the `memory.current` and `memory.max` files of the cgroup in `dir` rather than in
+    /// `/sys/fs/cgroup`.
+    pub fn with_cgroup_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cgroup_dir = dir.into();
+        self
+    }
+
+    /// Call `on_reclaim` with the report of every reclamation.
+    pub fn with_reclaim_handler(
+        mut self,
+        on_reclaim: impl Fn(&ReclaimReport) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_reclaim = Some(Arc::new(on_reclaim));
+        self
+    }
+
+    /// The number of bytes to reclaim given the current pressure, if any.
+    ///
+    /// Files that cannot be read or parsed are taken as no pressure.
+    fn target_bytes(&self) -> Option<usize> {
+        let psi = self.psi_threshold.and_then(|(threshold, target)| {
+            let avg10 = read_psi_some_avg10(&self.psi_path)?;
+            if avg10 >= threshold {
+                Some(target)
+            } else {
+                None
+            }
+        });
+        let cgroup = self.cgroup_headroom.and_then(|headroom| {
+            let (current, max) = read_cgroup_memory(&self.cgroup_dir)?;
+            let available = max.saturating_sub(current);
+            if available < headroom {
+                usize::try_from(headroom - available).ok()
+            } else {
+                None
+            }
+        });
+        match (psi, cgroup) {
+            (Some(psi), Some(cgroup)) => Some(psi.max(cgroup)),
+            (psi, cgroup) => psi.or(cgroup),
+        }
+    }
+}
+
+impl fmt::Debug for PressureWatcherConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PressureWatcherConfig")
+            .field("interval", &self.interval)
+            .field("max_priority", &self.max_priority)
+            .field("psi_path", &self.psi_path)
+            .field("psi_threshold", &self.psi_threshold)
+            .field("cgroup_dir", &self.cgroup_dir)
+            .field("cgroup_headroom", &self.cgroup_headroom)
+            .finish()
+    }
+}
+
+/// A thread calling [`UniversalEngine::reclaim`] whenever the memory pressure crosses the
+/// thresholds of its [`PressureWatcherConfig`].
+///
+/// The thread keeps the engine alive, and is stopped when the watcher is dropped.
+pub struct PressureWatcher {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PressureWatcher {
+    /// Start watching the memory pressure on behalf of `engine`.
+    pub fn start(engine: &UniversalEngine, config: PressureWatcherConfig) -> io::Result<Self> {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let engine = engine.clone();
+        let thread = thread::Builder::new()
+            .name("wasmer-pressure-watcher".to_string())
+            .spawn(move || loop {
+                if let Some(target_bytes) = config.target_bytes() {
+                    let report = engine.reclaim(target_bytes, config.max_priority);
+                    if let Some(on_reclaim) = &config.on_reclaim {
+                        on_reclaim(&report);
+                    }
+                }
+                match stopped.recv_timeout(config.interval) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Ok(()) | Err(RecvTimeoutError::Disconnected) => break,
+                }
+            })?;
+        Ok(Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl fmt::Debug for PressureWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PressureWatcher").finish()
+    }
+}
+
+impl Drop for PressureWatcher {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the thread up.
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Read the `avg10` of the `some` line of a pressure stall information file.
+fn read_psi_some_avg10(path: &Path) -> Option<f64> {
+    let contents = fs::read_to_string(path).ok()?;
+    let line = contents.lines().find(|line| line.starts_with("some "))?;
+    let avg10 = line
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("avg10="))?;
+    avg10.parse().ok()
+}
+
+/// Read the memory used by the cgroup in `dir` and its limit, if it has one.
+fn read_cgroup_memory(dir: &Path) -> Option<(u64, u64)> {
+    let read = |name: &str| fs::read_to_string(dir.join(name)).ok();
+    let current = read("memory.current")?.trim().parse().ok()?;
+    let max = read("memory.max")?.trim().parse().ok()?;
+    Some((current, max))
+}
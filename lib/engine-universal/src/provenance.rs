@@ -0,0 +1,297 @@
+//! How an executable was produced, recorded alongside it.
+//!
+//! The provenance is kept as a block of little-endian bytes in the metadata of the executable,
+//! so that it can be read on any host without loading the executable.
+
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+use wasmer_compiler::{CompilerProvenance, Features, Target};
+use wasmer_engine::DeserializeError;
+
+const MAGIC: [u8; 4] = *b"WPRV";
+const VERSION: u32 = 1;
+
+/// How an executable was produced: by which engine and compiler, with which settings, when,
+/// and whatever the embedder recorded about it.
+///
+/// The engine records the provenance when compiling, and the build time and embedder entries
+/// when serializing with [`SerializeOptions`]. The provenance is covered by the
+/// [`signing_digest`](crate::ArtifactEditor::signing_digest) of the executable, but not by the
+/// [`code_hash`](crate::UniversalExecutable::code_hash) nor the
+/// [`consensus_hash`](crate::UniversalArtifact::consensus_hash): executables compiled from the
+/// same binary with the same configuration compute the same results whoever compiled them and
+/// whenever they did.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Provenance {
+    /// The name and version of the engine.
+    pub engine: String,
+    /// A hash of the engine, target and compiler configuration the executable was compiled
+    /// with: the [`fingerprint`](wasmer_engine::Engine::fingerprint) of the engine. Engines
+    /// configured the same way have the same id.
+    pub engine_id: [u8; 32],
+    /// The compiler and its configuration.
+    pub compiler: CompilerProvenance,
+    /// The WebAssembly features enabled.
+    pub features: Vec<String>,
+    /// When the executable was serialized with [`SerializeOptions`], in seconds since the Unix
+    /// epoch.
+    pub built_at: Option<u64>,
+    /// The entries recorded by the embedder, in the order they were given.
+    pub custom: Vec<(String, String)>,
+}
+
+impl Provenance {
+    /// The provenance of executables compiled for `target` with `features` by `compiler`.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn for_compilation(
+        compiler: CompilerProvenance,
+        target: &Target,
+        features: &Features,
+    ) -> Self {
+        Self {
+            engine: engine_name(),
+            engine_id: engine_id(&compiler, target, features),
+            compiler,
+            features: feature_names(features),
+            built_at: None,
+            custom: Vec::new(),
+        }
+    }
+
+    /// The value of the embedder entry `key`, the last one if it was given several times.
+    pub fn custom(&self, key: &str) -> Option<&str> {
+        self.custom
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| &value[..])
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend(&VERSION.to_le_bytes());
+        put_str(&mut out, &self.engine);
+        out.extend(&self.engine_id);
+        put_str(&mut out, &self.compiler.name);
+        put_str(&mut out, &self.compiler.version);
+        put_list(&mut out, &self.compiler.flags);
+        put_list(&mut out, &self.compiler.middlewares);
+        put_list(&mut out, &self.features);
+        match self.built_at {
+            Some(time) => {
+                out.push(1);
+                out.extend(&time.to_le_bytes());
+            }
+            None => out.push(0),
+        }
+        out.extend(&(self.custom.len() as u32).to_le_bytes());
+        for (key, value) in self.custom.iter() {
+            put_str(&mut out, key);
+            put_str(&mut out, value);
+        }
+        out
+    }
+
+    pub(crate) fn decode(bytes: &[u8]) -> Result<Self, DeserializeError> {
+        let mut reader = Reader(bytes);
+        if reader.take(4)? != MAGIC || reader.u32()? != VERSION {
+            return Err(malformed());
+        }
+        let engine = reader.string()?;
+        let mut engine_id = [0; 32];
+        engine_id.copy_from_slice(reader.take(32)?);
+        let compiler = CompilerProvenance {
+            name: reader.string()?,
+            version: reader.string()?,
+            flags: reader.list()?,
+            middlewares: reader.list()?,
+        };
+        let features = reader.list()?;
+        let built_at = match reader.take(1)? {
+            [0] => None,
+            [1] => Some(reader.u64()?),
+            _ => return Err(malformed()),
+        };
+        let mut custom = Vec::new();
+        for _ in 0..reader.u32()? {
+            custom.push((reader.string()?, reader.string()?));
+        }
+        if !reader.0.is_empty() {
+            return Err(malformed());
+        }
+        Ok(Self {
+            engine,
+            engine_id,
+            compiler,
+            features,
+            built_at,
+            custom,
+        })
+    }
+}
+
+/// Options for serializing an executable with
+/// [`UniversalExecutable::serialize_with`](crate::UniversalExecutable::serialize_with).
+///
+/// ```
+/// # use wasmer_engine_universal::SerializeOptions;
+/// let options = SerializeOptions::new()
+///     .provenance(&[("requested_by", "alice"), ("ticket", "OPS-12")])
+///     .built_at(1_700_000_000);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SerializeOptions {
+    pub(crate) provenance: Vec<(String, String)>,
+    pub(crate) built_at: Option<u64>,
+}
+
+impl SerializeOptions {
+    /// Options recording no embedder entries, with the current time as the build time.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `entries` in the provenance of the executable, after those already recorded.
+    pub fn provenance(mut self, entries: &[(&str, &str)]) -> Self {
+        self.provenance.extend(
+            entries
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string())),
+        );
+        self
+    }
+
+    /// Record `seconds` since the Unix epoch as the build time rather than the current time,
+    /// for reproducible builds.
+    pub fn built_at(mut self, seconds: u64) -> Self {
+        self.built_at = Some(seconds);
+        self
+    }
+
+    /// Record the build time and embedder entries in `provenance`.
+    pub(crate) fn apply(&self, provenance: &mut Provenance) {
+        provenance.built_at = Some(self.built_at.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs())
+        }));
+        provenance.custom.extend(self.provenance.iter().cloned());
+    }
+}
+
+/// The [`Provenance::engine_id`] of executables compiled for `target` with `features` by
+/// `compiler`.
+pub(crate) fn engine_id(
+    compiler: &CompilerProvenance,
+    target: &Target,
+    features: &Features,
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    let mut field = |bytes: &[u8]| {
+        hasher.update((bytes.len() as u64).to_le_bytes());
+        hasher.update(bytes);
+    };
+    field(engine_name().as_bytes());
+    field(target.triple().to_string().as_bytes());
+    field(&target.cpu_features().as_u64().to_le_bytes());
+    for text in [&compiler.name, &compiler.version]
+        .iter()
+        .copied()
+        .chain(&compiler.flags)
+        .chain(&compiler.middlewares)
+        .chain(&feature_names(features))
+    {
+        field(text.as_bytes());
+    }
+    hasher.finalize().into()
+}
+
+/// The identifier of the executables compiled from the binary of SHA-256 hash `wasm_hash`,
+/// specialized for the bindings of hash `specialization_hash` if any, by engines of
+/// fingerprint `engine_id`.
+pub(crate) fn artifact_id(
+    wasm_hash: &[u8; 32],
+    specialization_hash: Option<&[u8; 32]>,
+    engine_id: &[u8; 32],
+) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(wasm_hash);
+    hasher.update(engine_id);
+    if let Some(hash) = specialization_hash {
+        hasher.update(hash);
+    }
+    hasher.finalize().into()
+}
+
+fn engine_name() -> String {
+    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+fn feature_names(features: &Features) -> Vec<String> {
+    [
+        ("threads", features.threads),
+        ("reference_types", features.reference_types),
+        ("simd", features.simd),
+        ("bulk_memory", features.bulk_memory),
+        ("multi_value", features.multi_value),
+        ("tail_call", features.tail_call),
+        ("module_linking", features.module_linking),
+        ("multi_memory", features.multi_memory),
+        ("memory64", features.memory64),
+        ("exceptions", features.exceptions),
+    ]
+    .iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| name.to_string())
+    .collect()
+}
+
+fn put_str(out: &mut Vec<u8>, text: &str) {
+    out.extend(&(text.len() as u32).to_le_bytes());
+    out.extend(text.as_bytes());
+}
+
+fn put_list(out: &mut Vec<u8>, texts: &[String]) {
+    out.extend(&(texts.len() as u32).to_le_bytes());
+    for text in texts {
+        put_str(out, text);
+    }
+}
+
+fn malformed() -> DeserializeError {
+    DeserializeError::CorruptedBinary("the provenance block is malformed".to_string())
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DeserializeError> {
+        if self.0.len() < len {
+            return Err(malformed());
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, DeserializeError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn u64(&mut self) -> Result<u64, DeserializeError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn string(&mut self) -> Result<String, DeserializeError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| malformed())
+    }
+
+    fn list(&mut self) -> Result<Vec<String>, DeserializeError> {
+        (0..self.u32()?).map(|_| self.string()).collect()
+    }
+}
@@ -0,0 +1,84 @@
+//! The memory an engine can release by itself when asked to reclaim memory.
+
+use crate::{UniversalArtifact, UniversalEngine};
+use std::sync::{Arc, Weak};
+use wasmer_vm::{ReclaimPriority, Reclaimable};
+
+/// The code memory of the artifacts that were dropped, which the engine otherwise only
+/// releases when it runs out of code memory.
+pub(crate) struct DroppedCode {
+    engine: UniversalEngine,
+}
+
+impl DroppedCode {
+    pub(crate) fn new(engine: UniversalEngine) -> Self {
+        Self { engine }
+    }
+}
+
+impl Reclaimable for DroppedCode {
+    fn name(&self) -> &str {
+        "code memory"
+    }
+
+    fn priority(&self) -> ReclaimPriority {
+        ReclaimPriority::Unused
+    }
+
+    fn reclaimable_bytes(&self) -> usize {
+        self.engine.inner().dropped_code_memory()
+    }
+
+    fn reclaim(&self, target_bytes: usize) -> usize {
+        self.engine.inner_mut().reclaim_code_memory(target_bytes)
+    }
+}
+
+/// The memory images captured by the artifacts for their read-only instances.
+///
+/// The images of an artifact are only released when no read-only instance uses them, as the
+/// next one would capture them again while the previous ones are still mapped.
+pub(crate) struct MemoryImages {
+    engine: UniversalEngine,
+}
+
+impl MemoryImages {
+    pub(crate) fn new(engine: UniversalEngine) -> Self {
+        Self { engine }
+    }
+
+    /// The live artifacts that captured images, upgraded outside of the lock of the engine, as
+    /// the artifacts take their own lock before the engine's.
+    fn artifacts(&self) -> Vec<Arc<UniversalArtifact>> {
+        let artifacts = self.engine.inner().image_artifacts.clone();
+        artifacts.iter().filter_map(Weak::upgrade).collect()
+    }
+}
+
+impl Reclaimable for MemoryImages {
+    fn name(&self) -> &str {
+        "memory images"
+    }
+
+    fn priority(&self) -> ReclaimPriority {
+        ReclaimPriority::Expensive
+    }
+
+    fn reclaimable_bytes(&self) -> usize {
+        self.artifacts()
+            .iter()
+            .map(|artifact| artifact.unused_memory_image_bytes())
+            .sum()
+    }
+
+    fn reclaim(&self, target_bytes: usize) -> usize {
+        let mut released = 0;
+        for artifact in self.artifacts() {
+            if released >= target_bytes {
+                break;
+            }
+            released += artifact.release_memory_images();
+        }
+        released
+    }
+}
@@ -0,0 +1,528 @@
+//! Extraction of the source maps embedded in wasm modules.
+//!
+//! Two kinds of debug information are understood:
+//!
+//! * A `sourceMappingURL` custom section holding a `data:` URL, which is how AssemblyScript and
+//!   Emscripten embed a [source map] in the module. Maps referenced by any other URL are not
+//!   fetched. Source maps are JSON, and are only read with the `source-map-json` feature.
+//! * DWARF line tables, in the `.debug_line` custom section, as emitted by LLVM.
+//!
+//! [source map]: https://sourcemaps.info/spec.html
+
+use std::convert::TryFrom;
+use wasmer_compiler::SourceMap;
+use wasmer_types::ModuleInfo;
+
+/// Build the source map of `module`, or `None` if it has no debug information this module
+/// understands, or if the map would take more than `size_limit` bytes.
+///
+/// `code_section_offset` is the offset of the contents of the code section in the module,
+/// which DWARF addresses are relative to.
+pub(crate) fn extract(
+    module: &ModuleInfo,
+    code_section_offset: Option<usize>,
+    size_limit: usize,
+) -> Option<SourceMap> {
+    let section = |name| module.custom_sections(name).last();
+    let map = match section("sourceMappingURL") {
+        #[cfg(feature = "source-map-json")]
+        Some(url) => from_source_mapping_url(&url)?,
+        _ => {
+            let debug_line = section(".debug_line")?;
+            let strings = DwarfStrings {
+                debug_line_str: section(".debug_line_str"),
+                debug_str: section(".debug_str"),
+            };
+            from_debug_line(&debug_line, &strings, code_section_offset?)?
+        }
+    };
+    if map.is_empty() || map.size() > size_limit {
+        return None;
+    }
+    Some(map)
+}
+
+/// A cursor over little-endian binary data.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.data.len() {
+            return None;
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(bytes)
+    }
+
+    fn uint(&mut self, len: usize) -> Option<u64> {
+        let bytes = self.bytes(len)?;
+        if len > 8 {
+            return None;
+        }
+        let mut value = [0; 8];
+        value[..len].copy_from_slice(bytes);
+        Some(u64::from_le_bytes(value))
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.bytes(1)?[0])
+    }
+
+    fn uleb(&mut self) -> Option<u64> {
+        leb128::read::unsigned(&mut self.data).ok()
+    }
+
+    fn sleb(&mut self) -> Option<i64> {
+        leb128::read::signed(&mut self.data).ok()
+    }
+
+    /// Read a string terminated by a NUL byte.
+    fn cstr(&mut self) -> Option<&'a str> {
+        let len = self.data.iter().position(|b| *b == 0)?;
+        let string = std::str::from_utf8(self.bytes(len)?).ok()?;
+        self.bytes(1)?;
+        Some(string)
+    }
+}
+
+/// Parse the contents of a `sourceMappingURL` custom section.
+#[cfg(feature = "source-map-json")]
+fn from_source_mapping_url(section: &[u8]) -> Option<SourceMap> {
+    let mut reader = Reader::new(section);
+    let len = usize::try_from(reader.uleb()?).ok()?;
+    let url = std::str::from_utf8(reader.bytes(len)?).ok()?;
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let json = if header.ends_with(";base64") {
+        decode_base64(data)?
+    } else {
+        decode_percent(data)?
+    };
+    from_source_map_json(&json)
+}
+
+#[cfg(feature = "source-map-json")]
+fn base64_digit(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "source-map-json")]
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(data.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut nbits = 0;
+    for c in data.trim_end_matches('=').bytes() {
+        bits = bits << 6 | u32::from(base64_digit(c)?);
+        nbits += 6;
+        if nbits >= 8 {
+            nbits -= 8;
+            decoded.push((bits >> nbits) as u8);
+        }
+    }
+    Some(decoded)
+}
+
+#[cfg(feature = "source-map-json")]
+fn decode_percent(data: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut bytes = data.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            decoded.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            decoded.push(b);
+        }
+    }
+    Some(decoded)
+}
+
+/// Parse a version 3 source map.
+#[cfg(feature = "source-map-json")]
+fn from_source_map_json(json: &[u8]) -> Option<SourceMap> {
+    let json: serde_json::Value = serde_json::from_slice(json).ok()?;
+    if json.get("version")?.as_u64()? != 3 {
+        return None;
+    }
+    let root = json
+        .get("sourceRoot")
+        .and_then(|r| r.as_str())
+        .unwrap_or("");
+    let mut map = SourceMap::new();
+    let sources = json.get("sources")?.as_array()?;
+    for source in sources {
+        let source = source.as_str().unwrap_or("");
+        let file = if root.is_empty() || source.starts_with('/') || source.contains("://") {
+            source.to_string()
+        } else {
+            format!("{}/{}", root.trim_end_matches('/'), source)
+        };
+        map.add_file(file);
+    }
+
+    // Wasm source maps have a single generated line, whose columns are module offsets.
+    let mappings = json.get("mappings")?.as_str()?;
+    let line = mappings.split(';').next().unwrap_or("");
+    let (mut offset, mut file, mut source_line, mut column) = (0i64, 0i64, 0i64, 0i64);
+    for segment in line.split(',').filter(|s| !s.is_empty()) {
+        let fields = decode_vlq(segment)?;
+        offset = offset.checked_add(fields[0])?;
+        let offset = u32::try_from(offset).ok()?;
+        match fields.len() {
+            1 => map.push_unmapped(offset),
+            4 | 5 => {
+                file = file.checked_add(fields[1])?;
+                source_line = source_line.checked_add(fields[2])?;
+                column = column.checked_add(fields[3])?;
+                map.push(
+                    offset,
+                    u32::try_from(file).ok()?,
+                    u32::try_from(source_line.checked_add(1)?).ok()?,
+                    u32::try_from(column.checked_add(1)?).ok()?,
+                );
+            }
+            _ => return None,
+        }
+    }
+    Some(map.finish())
+}
+
+/// Decode the base64 VLQ fields of a source map segment.
+#[cfg(feature = "source-map-json")]
+fn decode_vlq(segment: &str) -> Option<Vec<i64>> {
+    let mut fields = Vec::new();
+    let (mut value, mut shift) = (0u64, 0);
+    for c in segment.bytes() {
+        let digit = u64::from(base64_digit(c)?);
+        if shift > 60 {
+            return None;
+        }
+        value |= (digit & 0x1f) << shift;
+        shift += 5;
+        if digit & 0x20 == 0 {
+            let magnitude = (value >> 1) as i64;
+            fields.push(if value & 1 == 1 {
+                -magnitude
+            } else {
+                magnitude
+            });
+            value = 0;
+            shift = 0;
+        }
+    }
+    if shift != 0 {
+        return None;
+    }
+    Some(fields)
+}
+
+/// Sections holding the strings referred to by DWARF 5 line table headers.
+struct DwarfStrings {
+    debug_line_str: Option<std::sync::Arc<[u8]>>,
+    debug_str: Option<std::sync::Arc<[u8]>>,
+}
+
+const DW_LNCT_PATH: u64 = 1;
+const DW_LNCT_DIRECTORY_INDEX: u64 = 2;
+
+const DW_FORM_BLOCK: u64 = 0x09;
+const DW_FORM_DATA1: u64 = 0x0b;
+const DW_FORM_DATA2: u64 = 0x05;
+const DW_FORM_DATA4: u64 = 0x06;
+const DW_FORM_DATA8: u64 = 0x07;
+const DW_FORM_DATA16: u64 = 0x1e;
+const DW_FORM_LINE_STRP: u64 = 0x1f;
+const DW_FORM_STRING: u64 = 0x08;
+const DW_FORM_STRP: u64 = 0x0e;
+const DW_FORM_UDATA: u64 = 0x0f;
+
+/// A value of a DWARF 5 directory or file entry.
+enum FormValue<'a> {
+    Str(&'a str),
+    Uint(u64),
+    Other,
+}
+
+/// The header of a line table unit.
+struct LineUnit<'a> {
+    version: u16,
+    offset_size: usize,
+    strings: &'a DwarfStrings,
+    directories: Vec<String>,
+    /// Indices in the `SourceMap` of the files of this unit, by DWARF file number.
+    files: Vec<u32>,
+}
+
+impl<'a> LineUnit<'a> {
+    fn string_at(section: Option<&'a std::sync::Arc<[u8]>>, offset: u64) -> Option<&'a str> {
+        let section = section?;
+        let mut reader = Reader::new(section.get(usize::try_from(offset).ok()?..)?);
+        reader.cstr()
+    }
+
+    fn form_value(&self, reader: &mut Reader<'a>, form: u64) -> Option<FormValue<'a>> {
+        Some(match form {
+            DW_FORM_STRING => FormValue::Str(reader.cstr()?),
+            DW_FORM_LINE_STRP | DW_FORM_STRP => {
+                let offset = reader.uint(self.offset_size)?;
+                let section = if form == DW_FORM_LINE_STRP {
+                    self.strings.debug_line_str.as_ref()
+                } else {
+                    self.strings.debug_str.as_ref()
+                };
+                FormValue::Str(Self::string_at(section, offset)?)
+            }
+            DW_FORM_UDATA => FormValue::Uint(reader.uleb()?),
+            DW_FORM_DATA1 => FormValue::Uint(reader.uint(1)?),
+            DW_FORM_DATA2 => FormValue::Uint(reader.uint(2)?),
+            DW_FORM_DATA4 => FormValue::Uint(reader.uint(4)?),
+            DW_FORM_DATA8 => FormValue::Uint(reader.uint(8)?),
+            DW_FORM_DATA16 => {
+                reader.bytes(16)?;
+                FormValue::Other
+            }
+            DW_FORM_BLOCK => {
+                let len = usize::try_from(reader.uleb()?).ok()?;
+                reader.bytes(len)?;
+                FormValue::Other
+            }
+            // Other forms, such as `strx`, need sections that are not worth supporting here.
+            _ => return None,
+        })
+    }
+
+    /// Read a DWARF 5 directory or file entry list, as (path, directory index) pairs.
+    fn entries(&self, reader: &mut Reader<'a>) -> Option<Vec<(&'a str, u64)>> {
+        let format_count = reader.u8()?;
+        let mut format = Vec::new();
+        for _ in 0..format_count {
+            format.push((reader.uleb()?, reader.uleb()?));
+        }
+        let count = reader.uleb()?;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let (mut path, mut directory) = ("", 0);
+            for (content_type, form) in &format {
+                match (*content_type, self.form_value(reader, *form)?) {
+                    (DW_LNCT_PATH, FormValue::Str(s)) => path = s,
+                    (DW_LNCT_DIRECTORY_INDEX, FormValue::Uint(i)) => directory = i,
+                    _ => {}
+                }
+            }
+            entries.push((path, directory));
+        }
+        Some(entries)
+    }
+
+    /// Add the file at `path` in the directory with DWARF index `directory` to `map`.
+    fn add_file(&mut self, map: &mut SourceMap, path: &str, directory: u64) {
+        // Before DWARF 5, directory 0 is the compilation directory, which is not listed.
+        let directory = match self.version {
+            5 => usize::try_from(directory).ok(),
+            _ => usize::try_from(directory)
+                .ok()
+                .and_then(|d| d.checked_sub(1)),
+        };
+        let directory = directory.and_then(|d| self.directories.get(d));
+        let file = match directory {
+            Some(dir) if !dir.is_empty() && !path.starts_with('/') => {
+                format!("{}/{}", dir.trim_end_matches('/'), path)
+            }
+            _ => path.to_string(),
+        };
+        self.files.push(map.add_file(file));
+    }
+
+    /// Return the index in `map` of the file with DWARF number `file`.
+    fn file(&self, file: u64) -> u32 {
+        // Before DWARF 5, file numbers start at 1.
+        let index = match self.version {
+            5 => usize::try_from(file).ok(),
+            _ => usize::try_from(file).ok().and_then(|f| f.checked_sub(1)),
+        };
+        index
+            .and_then(|i| self.files.get(i).copied())
+            .unwrap_or(u32::MAX)
+    }
+}
+
+/// Parse the line tables of a `.debug_line` section.
+fn from_debug_line(
+    debug_line: &[u8],
+    strings: &DwarfStrings,
+    code_section_offset: usize,
+) -> Option<SourceMap> {
+    let mut map = SourceMap::new();
+    let mut section = Reader::new(debug_line);
+    while !section.is_empty() {
+        let (offset_size, unit_length) = match section.uint(4)? {
+            0xffff_ffff => (8, section.uint(8)?),
+            length => (4, length),
+        };
+        let mut unit = Reader::new(section.bytes(usize::try_from(unit_length).ok()?)?);
+        let version = u16::try_from(unit.uint(2)?).ok()?;
+        if !(2..=5).contains(&version) {
+            return None;
+        }
+        if version == 5 {
+            // The address and segment selector sizes.
+            unit.bytes(2)?;
+        }
+        let header_length = usize::try_from(unit.uint(offset_size)?).ok()?;
+        let mut program = unit;
+        let mut header = Reader::new(program.bytes(header_length)?);
+        let minimum_instruction_length = u64::from(header.u8()?);
+        if version >= 4 {
+            // The maximum number of operations per instruction, only relevant to VLIW.
+            header.u8()?;
+        }
+        // Whether rows are statements by default, which does not matter here.
+        header.u8()?;
+        let line_base = i64::from(header.u8()? as i8);
+        let line_range = u64::from(header.u8()?);
+        let opcode_base = header.u8()?;
+        if line_range == 0 || opcode_base == 0 {
+            return None;
+        }
+        let standard_opcode_lengths = header.bytes(usize::from(opcode_base - 1))?;
+
+        let mut line_unit = LineUnit {
+            version,
+            offset_size,
+            strings,
+            directories: Vec::new(),
+            files: Vec::new(),
+        };
+        if version == 5 {
+            let directories = line_unit.entries(&mut header)?;
+            line_unit.directories = directories.iter().map(|(p, _)| p.to_string()).collect();
+            for (path, directory) in line_unit.entries(&mut header)? {
+                line_unit.add_file(&mut map, path, directory);
+            }
+        } else {
+            loop {
+                let directory = header.cstr()?;
+                if directory.is_empty() {
+                    break;
+                }
+                line_unit.directories.push(directory.to_string());
+            }
+            loop {
+                let path = header.cstr()?;
+                if path.is_empty() {
+                    break;
+                }
+                let directory = header.uleb()?;
+                // The modification time and length of the file.
+                header.uleb()?;
+                header.uleb()?;
+                line_unit.add_file(&mut map, path, directory);
+            }
+        }
+
+        let mut address = 0u64;
+        let mut file = 1u64;
+        let mut line = 1i64;
+        let mut column = 0u64;
+        // Dead code is given tombstone addresses that fall past the module.
+        let emit = |map: &mut SourceMap, unit: &LineUnit, address: u64, row: Option<_>| {
+            let offset = u64::try_from(code_section_offset)
+                .ok()
+                .and_then(|o| o.checked_add(address))
+                .and_then(|o| u32::try_from(o).ok());
+            let offset = match offset {
+                Some(offset) => offset,
+                None => return,
+            };
+            match row {
+                Some((file, line, column)) => {
+                    let line = u32::try_from(line).unwrap_or(0);
+                    let column = u32::try_from(column).unwrap_or(0);
+                    map.push(offset, unit.file(file), line, column)
+                }
+                None => map.push_unmapped(offset),
+            }
+        };
+        while !program.is_empty() {
+            let opcode = program.u8()?;
+            if opcode >= opcode_base {
+                let adjusted = u64::from(opcode - opcode_base);
+                address = address.wrapping_add(adjusted / line_range * minimum_instruction_length);
+                line += line_base + (adjusted % line_range) as i64;
+                emit(&mut map, &line_unit, address, Some((file, line, column)));
+                continue;
+            }
+            match opcode {
+                // Extended opcodes.
+                0 => {
+                    let len = usize::try_from(program.uleb()?).ok()?;
+                    let mut operands = Reader::new(program.bytes(len)?);
+                    match operands.u8()? {
+                        // DW_LNE_end_sequence
+                        1 => {
+                            emit(&mut map, &line_unit, address, None);
+                            address = 0;
+                            file = 1;
+                            line = 1;
+                            column = 0;
+                        }
+                        // DW_LNE_set_address
+                        2 => address = operands.uint(len - 1)?,
+                        // DW_LNE_define_file
+                        3 => {
+                            let path = operands.cstr()?;
+                            let directory = operands.uleb()?;
+                            line_unit.add_file(&mut map, path, directory);
+                        }
+                        _ => {}
+                    }
+                }
+                // DW_LNS_copy
+                1 => emit(&mut map, &line_unit, address, Some((file, line, column))),
+                // DW_LNS_advance_pc
+                2 => {
+                    let advance = program.uleb()?.wrapping_mul(minimum_instruction_length);
+                    address = address.wrapping_add(advance);
+                }
+                // DW_LNS_advance_line
+                3 => line = line.wrapping_add(program.sleb()?),
+                // DW_LNS_set_file
+                4 => file = program.uleb()?,
+                // DW_LNS_set_column
+                5 => column = program.uleb()?,
+                // DW_LNS_const_add_pc
+                8 => {
+                    let adjusted = u64::from(255 - opcode_base);
+                    address =
+                        address.wrapping_add(adjusted / line_range * minimum_instruction_length);
+                }
+                // DW_LNS_fixed_advance_pc
+                9 => address = address.wrapping_add(program.uint(2)?),
+                // Skip the operands of the other opcodes.
+                _ => {
+                    for _ in 0..standard_opcode_lengths[usize::from(opcode - 1)] {
+                        program.uleb()?;
+                    }
+                }
+            }
+        }
+    }
+    Some(map.finish())
+}
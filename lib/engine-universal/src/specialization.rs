@@ -0,0 +1,96 @@
+//! Specialization of modules for the values of some of their imported globals.
+//!
+//! A binding names an immutable imported global, by module and field, and the value it will
+//! have. The bindings of an executable are resolved to global indices when it is compiled, and
+//! recorded with it, so that compilers may read the values in place of the globals and
+//! instantiation can check that the globals it is given have these values.
+
+#[cfg(feature = "compiler")]
+use sha2::{Digest, Sha256};
+#[cfg(feature = "compiler")]
+use std::collections::BTreeMap;
+#[cfg(feature = "compiler")]
+use wasmer_compiler::CompileError;
+use wasmer_types::GlobalInit;
+#[cfg(feature = "compiler")]
+use wasmer_types::{GlobalIndex, ImportIndex, ModuleInfo, Mutability, Type};
+
+/// The type of the globals `value` can be bound to, if any.
+#[cfg(feature = "compiler")]
+fn value_type(value: &GlobalInit) -> Option<Type> {
+    match value {
+        GlobalInit::I32Const(_) => Some(Type::I32),
+        GlobalInit::I64Const(_) => Some(Type::I64),
+        GlobalInit::F32Const(_) => Some(Type::F32),
+        GlobalInit::F64Const(_) => Some(Type::F64),
+        _ => None,
+    }
+}
+
+/// The bits of `value`, which compare floats by representation rather than numerically.
+pub(crate) fn value_bits(value: &GlobalInit) -> Option<u64> {
+    match *value {
+        GlobalInit::I32Const(value) => Some(u64::from(value as u32)),
+        GlobalInit::I64Const(value) => Some(value as u64),
+        GlobalInit::F32Const(value) => Some(u64::from(value.to_bits())),
+        GlobalInit::F64Const(value) => Some(value.to_bits()),
+        _ => None,
+    }
+}
+
+/// Resolve `bindings` to the indices of the imported globals of `module` they name.
+#[cfg(feature = "compiler")]
+pub(crate) fn bind_globals(
+    module: &ModuleInfo,
+    bindings: &[(&str, &str, GlobalInit)],
+) -> Result<BTreeMap<GlobalIndex, GlobalInit>, CompileError> {
+    let mut bound = BTreeMap::new();
+    for &(module_name, field, value) in bindings {
+        let error = |reason: String| {
+            CompileError::Specialization(format!("{}.{} {}", module_name, field, reason))
+        };
+        let index = module
+            .imports
+            .iter()
+            .find_map(|((m, f, _), index)| match index {
+                ImportIndex::Global(index) if m == module_name && f == field => Some(*index),
+                _ => None,
+            })
+            .ok_or_else(|| error("is not an imported global".to_string()))?;
+        let ty = module.globals[index];
+        if ty.mutability != Mutability::Const {
+            return Err(error("is mutable".to_string()));
+        }
+        if value_type(&value) != Some(ty.ty) {
+            return Err(error(format!("is of type {}, not {:?}", ty.ty, value)));
+        }
+        if bound.insert(index, value).is_some() {
+            return Err(error("is bound more than once".to_string()));
+        }
+    }
+    Ok(bound)
+}
+
+/// Compute the hash identifying the specialization of `binary` for `bindings`.
+///
+/// The hash does not depend on the order of the bindings.
+#[cfg(feature = "compiler")]
+pub(crate) fn specialization_hash(
+    binary: &[u8],
+    bindings: &[(&str, &str, GlobalInit)],
+) -> [u8; 32] {
+    let mut sorted = bindings.to_vec();
+    sorted.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+    let mut hasher = Sha256::new();
+    hasher.update((binary.len() as u64).to_le_bytes());
+    hasher.update(binary);
+    for (module, field, value) in sorted {
+        hasher.update((module.len() as u64).to_le_bytes());
+        hasher.update(module.as_bytes());
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field.as_bytes());
+        hasher.update(format!("{:?}", value_type(&value)).as_bytes());
+        hasher.update(value_bits(&value).unwrap_or_default().to_le_bytes());
+    }
+    hasher.finalize().into()
+}
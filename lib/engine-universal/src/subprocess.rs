@@ -0,0 +1,608 @@
+//! Compilation in a separate, sandboxed process.
+//!
+//! The parent spawns a helper executable, streams the WebAssembly module to its standard input
+//! and reads the serialized [`UniversalExecutable`] back from its standard output. A compiler
+//! bug triggered by a hostile module can then at worst take down the helper.
+//!
+//! Note that the parent still runs the machine code produced by the helper, so this protects
+//! against memory corruption in the compiler, not against a miscompilation.
+
+use crate::{UniversalExecutable, UniversalExecutableRef};
+use std::convert::TryFrom;
+use std::ffi::OsString;
+use std::io::{self, Read, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use wasmer_compiler::{CompileError, SubprocessError, WasmError};
+use wasmer_engine::Executable;
+
+const RESPONSE_EXECUTABLE: u8 = 0;
+const RESPONSE_COMPILE_ERROR: u8 = 1;
+const RESPONSE_LIMIT_EXCEEDED: u8 = 2;
+
+/// Exit code of the helper when it could not even start serving the request.
+const HELPER_SETUP_FAILURE: i32 = 2;
+
+/// The restrictions a compilation subprocess runs under.
+#[derive(Clone, Debug)]
+pub struct SubprocessLimits {
+    /// Wall-clock time after which the parent kills the helper.
+    pub timeout: Duration,
+    /// Largest module the helper accepts, in bytes.
+    pub max_wasm_size: Option<usize>,
+    /// Largest address space of the helper, in bytes (`RLIMIT_AS`).
+    ///
+    /// The compiler threads reserve address space for their stacks and allocators, so this
+    /// must be well above the memory actually needed for the compilation.
+    pub max_memory: Option<u64>,
+    /// CPU time the helper may use, rounded up to whole seconds (`RLIMIT_CPU`).
+    pub max_cpu_time: Option<Duration>,
+    /// Largest serialized executable the parent accepts from the helper, in bytes.
+    pub max_response_size: usize,
+    /// Whether the helper forbids itself from spawning processes, using the network or
+    /// modifying files, using seccomp. Only supported on x86_64 Linux.
+    pub seccomp: bool,
+}
+
+impl Default for SubprocessLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(60),
+            max_wasm_size: None,
+            max_memory: None,
+            max_cpu_time: None,
+            max_response_size: 1 << 30,
+            seccomp: cfg!(all(target_os = "linux", target_arch = "x86_64")),
+        }
+    }
+}
+
+impl SubprocessLimits {
+    /// The command line arguments passing the limits the helper enforces itself.
+    fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(size) = self.max_wasm_size {
+            args.push(format!("--max-wasm-size={}", size));
+        }
+        if let Some(memory) = self.max_memory {
+            args.push(format!("--max-memory={}", memory));
+        }
+        if let Some(time) = self.max_cpu_time {
+            let seconds = time.as_secs() + u64::from(time.subsec_nanos() > 0);
+            args.push(format!("--max-cpu-time={}", seconds));
+        }
+        if self.seccomp {
+            args.push("--seccomp".to_string());
+        }
+        args
+    }
+
+    fn from_args(args: impl Iterator<Item = OsString>) -> Result<Self, String> {
+        let mut limits = Self {
+            seccomp: false,
+            ..Self::default()
+        };
+        for arg in args {
+            let arg = arg
+                .into_string()
+                .map_err(|arg| format!("invalid argument {:?}", arg))?;
+            let (name, value) = match arg.find('=') {
+                Some(eq) => (&arg[..eq], Some(&arg[eq + 1..])),
+                None => (&arg[..], None),
+            };
+            let number = || -> Result<u64, String> {
+                value
+                    .and_then(|v| v.parse().ok())
+                    .ok_or_else(|| format!("invalid argument {:?}", arg))
+            };
+            match name {
+                "--max-wasm-size" => limits.max_wasm_size = Some(number()? as usize),
+                "--max-memory" => limits.max_memory = Some(number()?),
+                "--max-cpu-time" => limits.max_cpu_time = Some(Duration::from_secs(number()?)),
+                "--seccomp" if value.is_none() => limits.seccomp = true,
+                _ => return Err(format!("unknown argument {:?}", arg)),
+            }
+        }
+        Ok(limits)
+    }
+}
+
+/// A compiler running every compilation in a fresh helper process.
+///
+/// The helper is an executable calling [`serve_compile_request`], such as the
+/// `wasmer-compile-helper` binary of this workspace. It compiles with its own engine
+/// configuration and default tunables, so it must be configured for the same target and
+/// features as the engine loading the result.
+#[derive(Clone, Debug)]
+pub struct SubprocessCompiler {
+    helper: PathBuf,
+    limits: SubprocessLimits,
+}
+
+impl SubprocessCompiler {
+    /// Create a compiler running `helper` under `limits`.
+    pub fn new(helper: impl Into<PathBuf>, limits: SubprocessLimits) -> Self {
+        Self {
+            helper: helper.into(),
+            limits,
+        }
+    }
+
+    /// The limits the helper runs under.
+    pub fn limits(&self) -> &SubprocessLimits {
+        &self.limits
+    }
+
+    /// Compile a WebAssembly binary in a helper process.
+    ///
+    /// The result can be loaded with any engine, headless ones included, for example with
+    /// [`UniversalEngine::load_universal_executable`](crate::UniversalEngine::load_universal_executable).
+    pub fn compile_universal(&self, binary: &[u8]) -> Result<UniversalExecutable, CompileError> {
+        let response = self.run(binary).map_err(CompileError::Subprocess)?;
+        parse_response(&response)
+    }
+
+    /// Run the helper on `binary`, returning its raw response.
+    fn run(&self, binary: &[u8]) -> Result<Vec<u8>, SubprocessError> {
+        let mut child = Command::new(&self.helper)
+            .args(self.limits.to_args())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| SubprocessError::Spawn(e.to_string()))?;
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let input = binary.to_vec();
+        let writer = thread::spawn(move || {
+            // The helper may exit before reading everything, e.g. if the module is too large.
+            match stdin.write_all(&input) {
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e),
+                _ => Ok(()),
+            }
+        });
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let max_response_size = self.limits.max_response_size as u64;
+        let reader = thread::spawn(move || {
+            let mut response = Vec::new();
+            stdout
+                .take(max_response_size + 1)
+                .read_to_end(&mut response)
+                .map(|_| response)
+        });
+
+        let deadline = Instant::now() + self.limits.timeout;
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(5)),
+                Ok(None) => {
+                    // Killing closes the pipes, which lets the threads finish.
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    let _ = writer.join();
+                    let _ = reader.join();
+                    return Err(SubprocessError::Timeout);
+                }
+                Err(e) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(SubprocessError::Io(e.to_string()));
+                }
+            }
+        };
+        let written = writer.join().expect("writer thread panicked");
+        let response = reader.join().expect("reader thread panicked");
+
+        // A helper whose response was cut short dies writing it, so check the size first.
+        if let Ok(response) = &response {
+            if response.len() as u64 > max_response_size {
+                return Err(SubprocessError::LimitExceeded(format!(
+                    "the response is larger than {} bytes",
+                    max_response_size
+                )));
+            }
+        }
+        check_status(status, &self.limits)?;
+        written.map_err(|e| SubprocessError::Io(e.to_string()))?;
+        response.map_err(|e| SubprocessError::Io(e.to_string()))
+    }
+}
+
+fn check_status(status: ExitStatus, limits: &SubprocessLimits) -> Result<(), SubprocessError> {
+    if status.success() {
+        return Ok(());
+    }
+    match status.signal() {
+        Some(libc::SIGXCPU) if limits.max_cpu_time.is_some() => Err(
+            SubprocessError::LimitExceeded("the CPU time limit was reached".to_string()),
+        ),
+        Some(signal) => Err(SubprocessError::Crashed(format!(
+            "killed by signal {}",
+            signal
+        ))),
+        None => Err(SubprocessError::Crashed(format!(
+            "exited with code {}",
+            status.code().unwrap_or(-1)
+        ))),
+    }
+}
+
+fn malformed(message: &str) -> CompileError {
+    CompileError::Subprocess(SubprocessError::MalformedResponse(message.to_string()))
+}
+
+fn parse_response(response: &[u8]) -> Result<UniversalExecutable, CompileError> {
+    let (kind, payload) = response
+        .split_first()
+        .ok_or_else(|| malformed("the response is empty"))?;
+    match *kind {
+        RESPONSE_EXECUTABLE => {
+            UniversalExecutableRef::verify_serialized(payload).map_err(malformed)?;
+            UniversalExecutableRef::with_aligned(payload, |payload| {
+                // SAFETY: the payload was produced by `UniversalExecutable::serialize` in the
+                // helper.
+                let executable = unsafe { UniversalExecutableRef::deserialize(payload) }
+                    .map_err(|e| malformed(&e.to_string()))?;
+                executable.to_owned().map_err(|e| malformed(&e.to_string()))
+            })
+        }
+        RESPONSE_COMPILE_ERROR => Err(decode_compile_error(payload)?),
+        RESPONSE_LIMIT_EXCEEDED => Err(CompileError::Subprocess(SubprocessError::LimitExceeded(
+            String::from_utf8_lossy(payload).into_owned(),
+        ))),
+        _ => Err(malformed("unknown response kind")),
+    }
+}
+
+fn encode_compile_error(error: &CompileError) -> Vec<u8> {
+    let (tag, offset, message) = match error {
+        CompileError::Wasm(WasmError::InvalidWebAssembly { message, offset }) => {
+            (0, *offset as u64, message.clone())
+        }
+        CompileError::Wasm(WasmError::Unsupported(message)) => (1, 0, message.clone()),
+        CompileError::Wasm(WasmError::ImplLimitExceeded) => (2, 0, String::new()),
+        CompileError::Wasm(error) => (3, 0, error.to_string()),
+        CompileError::Codegen(message) => (4, 0, message.clone()),
+        CompileError::Validate(message) => (5, 0, message.clone()),
+        CompileError::UnsupportedFeature(message) => (6, 0, message.clone()),
+        CompileError::UnsupportedTarget(message) => (7, 0, message.clone()),
+        CompileError::Resource(message) => (8, 0, message.clone()),
+        error => (4, 0, error.to_string()),
+    };
+    let mut encoded = vec![tag];
+    encoded.extend_from_slice(&offset.to_le_bytes());
+    encoded.extend_from_slice(message.as_bytes());
+    encoded
+}
+
+fn decode_compile_error(encoded: &[u8]) -> Result<CompileError, CompileError> {
+    if encoded.len() < 9 {
+        return Err(malformed("truncated compile error"));
+    }
+    let mut offset = [0; 8];
+    offset.copy_from_slice(&encoded[1..9]);
+    let offset = usize::try_from(u64::from_le_bytes(offset))
+        .map_err(|_| malformed("invalid error offset"))?;
+    let message = String::from_utf8_lossy(&encoded[9..]).into_owned();
+    Ok(match encoded[0] {
+        0 => CompileError::Wasm(WasmError::InvalidWebAssembly { message, offset }),
+        1 => CompileError::Wasm(WasmError::Unsupported(message)),
+        2 => CompileError::Wasm(WasmError::ImplLimitExceeded),
+        3 => CompileError::Wasm(WasmError::Generic(message)),
+        4 => CompileError::Codegen(message),
+        5 => CompileError::Validate(message),
+        6 => CompileError::UnsupportedFeature(message),
+        7 => CompileError::UnsupportedTarget(message),
+        8 => CompileError::Resource(message),
+        _ => return Err(malformed("unknown compile error kind")),
+    })
+}
+
+/// Serve one request of a [`SubprocessCompiler`], returning the exit code of the helper.
+///
+/// This is the body of a helper executable: it applies the limits passed on the command line,
+/// reads the module from the standard input, compiles it with `compile` and writes the
+/// response to the standard output.
+///
+/// ```ignore
+/// fn main() {
+///     std::process::exit(serve_compile_request(|wasm| {
+///         let engine = Universal::new(Singlepass::new()).engine();
+///         engine.compile_universal(wasm, &BaseTunables::for_target(engine.target()))
+///     }));
+/// }
+/// ```
+pub fn serve_compile_request(
+    compile: impl FnOnce(&[u8]) -> Result<UniversalExecutable, CompileError>,
+) -> i32 {
+    let limits = match SubprocessLimits::from_args(std::env::args_os().skip(1)) {
+        Ok(limits) => limits,
+        Err(e) => {
+            eprintln!("{}", e);
+            return HELPER_SETUP_FAILURE;
+        }
+    };
+    if let Err(e) = sandbox::enter(&limits) {
+        eprintln!("could not sandbox the helper: {}", e);
+        return HELPER_SETUP_FAILURE;
+    }
+
+    let mut wasm = Vec::new();
+    let max_wasm_size = limits.max_wasm_size.map_or(u64::MAX, |size| size as u64);
+    if let Err(e) = io::stdin()
+        .take(max_wasm_size.saturating_add(1))
+        .read_to_end(&mut wasm)
+    {
+        eprintln!("could not read the module: {}", e);
+        return HELPER_SETUP_FAILURE;
+    }
+
+    let mut response = Vec::new();
+    if wasm.len() as u64 > max_wasm_size {
+        response.push(RESPONSE_LIMIT_EXCEEDED);
+        response.extend_from_slice(
+            format!("the module is larger than {} bytes", max_wasm_size).as_bytes(),
+        );
+    } else {
+        match compile(&wasm).and_then(|executable| {
+            executable
+                .serialize()
+                .map_err(|e| CompileError::Codegen(e.to_string()))
+        }) {
+            Ok(serialized) => {
+                response.push(RESPONSE_EXECUTABLE);
+                response.extend_from_slice(&serialized);
+            }
+            Err(e) => {
+                response.push(RESPONSE_COMPILE_ERROR);
+                response.extend_from_slice(&encode_compile_error(&e));
+            }
+        }
+    }
+    let mut stdout = io::stdout();
+    match stdout.write_all(&response).and_then(|()| stdout.flush()) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("could not write the response: {}", e);
+            HELPER_SETUP_FAILURE
+        }
+    }
+}
+
+mod sandbox {
+    use super::SubprocessLimits;
+    use std::io;
+
+    pub(super) fn enter(limits: &SubprocessLimits) -> io::Result<()> {
+        // The type of the resource argument differs between C libraries.
+        macro_rules! set_rlimit {
+            ($resource:expr, $soft:expr, $hard:expr) => {{
+                let limit = libc::rlimit {
+                    rlim_cur: $soft,
+                    rlim_max: $hard,
+                };
+                // SAFETY: `limit` is a valid `rlimit`.
+                if unsafe { libc::setrlimit($resource, &limit) } != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }};
+        }
+
+        // Hostile input should not leave core dumps behind.
+        set_rlimit!(libc::RLIMIT_CORE, 0, 0);
+        if let Some(memory) = limits.max_memory {
+            set_rlimit!(libc::RLIMIT_AS, memory, memory);
+        }
+        if let Some(time) = limits.max_cpu_time {
+            // Reaching the soft limit raises SIGXCPU, which tells it apart from a crash.
+            let seconds = time.as_secs();
+            set_rlimit!(libc::RLIMIT_CPU, seconds, seconds + 1);
+        }
+        if limits.seccomp {
+            seccomp::install()?;
+        }
+        Ok(())
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    mod seccomp {
+        use std::io;
+
+        // From <linux/filter.h>, <linux/seccomp.h> and <linux/audit.h>.
+        const BPF_LD_W_ABS: u16 = 0x20;
+        const BPF_JMP_JEQ_K: u16 = 0x15;
+        const BPF_JMP_JGE_K: u16 = 0x35;
+        const BPF_JMP_JSET_K: u16 = 0x45;
+        const BPF_RET_K: u16 = 0x06;
+        const SECCOMP_MODE_FILTER: libc::c_ulong = 2;
+        const SECCOMP_RET_KILL_PROCESS: u32 = 0x8000_0000;
+        const SECCOMP_RET_ERRNO: u32 = 0x0005_0000;
+        const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+        const AUDIT_ARCH_X86_64: u32 = 0xc000_003e;
+        const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+        // Offsets in `struct seccomp_data`.
+        const NR: u32 = 0;
+        const ARCH: u32 = 4;
+        const ARG0: u32 = 16;
+        const ARG1: u32 = 24;
+        const ARG2: u32 = 32;
+
+        #[repr(C)]
+        struct SockFilter {
+            code: u16,
+            jt: u8,
+            jf: u8,
+            k: u32,
+        }
+
+        #[repr(C)]
+        struct SockFprog {
+            len: u16,
+            filter: *const SockFilter,
+        }
+
+        const fn stmt(code: u16, k: u32) -> SockFilter {
+            SockFilter {
+                code,
+                jt: 0,
+                jf: 0,
+                k,
+            }
+        }
+
+        const fn jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+            SockFilter { code, jt, jf, k }
+        }
+
+        /// System calls a compiler has no business making.
+        const DENIED: &[libc::c_long] = &[
+            libc::SYS_execve,
+            libc::SYS_execveat,
+            libc::SYS_fork,
+            libc::SYS_vfork,
+            libc::SYS_ptrace,
+            libc::SYS_process_vm_readv,
+            libc::SYS_process_vm_writev,
+            libc::SYS_kill,
+            libc::SYS_socket,
+            libc::SYS_socketpair,
+            libc::SYS_connect,
+            libc::SYS_accept,
+            libc::SYS_accept4,
+            libc::SYS_bind,
+            libc::SYS_listen,
+            libc::SYS_sendto,
+            libc::SYS_sendmsg,
+            libc::SYS_sendmmsg,
+            libc::SYS_recvfrom,
+            libc::SYS_recvmsg,
+            libc::SYS_recvmmsg,
+            libc::SYS_creat,
+            libc::SYS_truncate,
+            libc::SYS_unlink,
+            libc::SYS_unlinkat,
+            libc::SYS_rename,
+            libc::SYS_renameat,
+            libc::SYS_renameat2,
+            libc::SYS_mkdir,
+            libc::SYS_mkdirat,
+            libc::SYS_rmdir,
+            libc::SYS_link,
+            libc::SYS_linkat,
+            libc::SYS_symlink,
+            libc::SYS_symlinkat,
+            libc::SYS_chmod,
+            libc::SYS_fchmod,
+            libc::SYS_fchmodat,
+            libc::SYS_chown,
+            libc::SYS_fchown,
+            libc::SYS_lchown,
+            libc::SYS_fchownat,
+            libc::SYS_mount,
+            libc::SYS_umount2,
+            libc::SYS_chroot,
+            libc::SYS_pivot_root,
+            libc::SYS_unshare,
+            libc::SYS_setns,
+            libc::SYS_setuid,
+            libc::SYS_setgid,
+            libc::SYS_setreuid,
+            libc::SYS_setregid,
+            libc::SYS_setresuid,
+            libc::SYS_setresgid,
+            libc::SYS_init_module,
+            libc::SYS_finit_module,
+            libc::SYS_delete_module,
+            libc::SYS_kexec_load,
+            libc::SYS_reboot,
+            libc::SYS_bpf,
+            libc::SYS_perf_event_open,
+            libc::SYS_userfaultfd,
+            libc::SYS_keyctl,
+            libc::SYS_add_key,
+            libc::SYS_request_key,
+            libc::SYS_ioperm,
+            libc::SYS_iopl,
+            libc::SYS_personality,
+        ];
+
+        fn filter() -> Vec<SockFilter> {
+            let deny = SECCOMP_RET_ERRNO | libc::EPERM as u32;
+            let write_flags =
+                (libc::O_WRONLY | libc::O_RDWR | libc::O_CREAT | libc::O_TRUNC) as u32;
+            let mut filter = vec![
+                stmt(BPF_LD_W_ABS, ARCH),
+                jump(BPF_JMP_JEQ_K, AUDIT_ARCH_X86_64, 1, 0),
+                stmt(BPF_RET_K, SECCOMP_RET_KILL_PROCESS),
+                stmt(BPF_LD_W_ABS, NR),
+                jump(BPF_JMP_JGE_K, X32_SYSCALL_BIT, 0, 1),
+                stmt(BPF_RET_K, deny),
+            ];
+            for nr in DENIED {
+                filter.push(jump(BPF_JMP_JEQ_K, *nr as u32, 0, 1));
+                filter.push(stmt(BPF_RET_K, deny));
+            }
+            // Threads are fine, new processes are not. `clone3` reports ENOSYS so that the C
+            // library falls back to `clone`, whose flags can be inspected.
+            filter.push(jump(BPF_JMP_JEQ_K, libc::SYS_clone3 as u32, 0, 1));
+            filter.push(stmt(BPF_RET_K, SECCOMP_RET_ERRNO | libc::ENOSYS as u32));
+            for (nr, arg, mask) in [
+                (libc::SYS_clone, ARG0, libc::CLONE_THREAD as u32),
+                (libc::SYS_open, ARG1, write_flags),
+                (libc::SYS_openat, ARG2, write_flags),
+            ] {
+                let (if_set, if_unset) = if nr == libc::SYS_clone {
+                    (SECCOMP_RET_ALLOW, deny)
+                } else {
+                    (deny, SECCOMP_RET_ALLOW)
+                };
+                filter.push(jump(BPF_JMP_JEQ_K, nr as u32, 0, 4));
+                filter.push(stmt(BPF_LD_W_ABS, arg));
+                filter.push(jump(BPF_JMP_JSET_K, mask, 0, 1));
+                filter.push(stmt(BPF_RET_K, if_set));
+                filter.push(stmt(BPF_RET_K, if_unset));
+            }
+            filter.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+            filter
+        }
+
+        pub(super) fn install() -> io::Result<()> {
+            let filter = filter();
+            let program = SockFprog {
+                len: filter.len() as u16,
+                filter: filter.as_ptr(),
+            };
+            // SAFETY: `program` points to a valid filter that outlives the calls.
+            unsafe {
+                if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                if libc::prctl(
+                    libc::PR_SET_SECCOMP,
+                    SECCOMP_MODE_FILTER,
+                    &program as *const SockFprog,
+                ) != 0
+                {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(not(all(target_os = "linux", target_arch = "x86_64")))]
+    mod seccomp {
+        use std::io;
+
+        pub(super) fn install() -> io::Result<()> {
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                "seccomp is only supported on x86_64 Linux",
+            ))
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Accounting of the resources an engine uses for each tenant, and enforcement of their
+//! quotas.
+
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use wasmer_compiler::CompileError;
+use wasmer_types::{Quota, QuotaDimension, TenantId};
+
+/// The window over which [`Quota::max_compile_ms_per_minute`] is enforced.
+const COMPILE_WINDOW: Duration = Duration::from_secs(60);
+
+/// What an engine did and holds for a tenant.
+///
+/// See [`UniversalEngine::tenant_usage`](crate::UniversalEngine::tenant_usage).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TenantUsage {
+    /// The bytes of code memory used by the live artifacts loaded for the tenant.
+    pub code_bytes: usize,
+    /// The live artifacts loaded for the tenant.
+    pub artifacts_loaded: usize,
+    /// The time spent compiling for the tenant, in total.
+    pub compile_time: Duration,
+    /// The compilations submitted for the tenant, including those its quota refused.
+    pub compilations_submitted: u64,
+    /// The bytes the memory arbiter of the engine granted to the memories of the instances of
+    /// the tenant and that were not released yet, if it keeps track of them.
+    pub memory_bytes: Option<u64>,
+}
+
+#[derive(Default)]
+struct Tenant {
+    quota: Quota,
+    usage: TenantUsage,
+    /// When the recent compilations ended and how long they took, oldest first.
+    recent_compilations: VecDeque<(Instant, Duration)>,
+}
+
+impl Tenant {
+    /// The time spent compiling over the window ending `now`, forgetting older compilations.
+    fn recent_compile_time(&mut self, now: Instant) -> Duration {
+        while let Some((ended, _)) = self.recent_compilations.front() {
+            if now.duration_since(*ended) < COMPILE_WINDOW {
+                break;
+            }
+            self.recent_compilations.pop_front();
+        }
+        self.recent_compilations.iter().map(|(_, time)| *time).sum()
+    }
+}
+
+/// The usage and quotas of the tenants of an engine.
+#[derive(Default)]
+pub(crate) struct TenantLedger {
+    tenants: Mutex<HashMap<TenantId, Tenant>>,
+}
+
+impl TenantLedger {
+    pub(crate) fn set_quota(&self, tenant: TenantId, quota: Quota) {
+        self.tenants
+            .lock()
+            .unwrap()
+            .entry(tenant)
+            .or_default()
+            .quota = quota;
+    }
+
+    pub(crate) fn usage(&self, tenant: TenantId) -> TenantUsage {
+        let tenants = self.tenants.lock().unwrap();
+        tenants
+            .get(&tenant)
+            .map(|tenant| tenant.usage.clone())
+            .unwrap_or_default()
+    }
+
+    /// Count a compilation as submitted for `tenant`, failing if the tenant already spent its
+    /// compile time for the last minute.
+    pub(crate) fn admit_compilation(&self, tenant: TenantId) -> Result<(), CompileError> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let state = tenants.entry(tenant).or_default();
+        state.usage.compilations_submitted += 1;
+        if let Some(limit) = state.quota.max_compile_ms_per_minute {
+            let used = state.recent_compile_time(Instant::now());
+            if used >= Duration::from_millis(limit) {
+                return Err(CompileError::QuotaExceeded {
+                    tenant,
+                    dimension: QuotaDimension::CompileMsPerMinute,
+                    limit,
+                    used: u64::try_from(used.as_millis()).unwrap_or(u64::MAX),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Account for `time` spent compiling for `tenant`.
+    pub(crate) fn record_compilation(&self, tenant: TenantId, time: Duration) {
+        let mut tenants = self.tenants.lock().unwrap();
+        let state = tenants.entry(tenant).or_default();
+        state.usage.compile_time += time;
+        state.recent_compilations.push_back((Instant::now(), time));
+    }
+
+    /// Charge `tenant` for an artifact using `code_bytes` of code memory, failing if that
+    /// would exceed its quota. The tenant is credited back when the charge is dropped.
+    pub(crate) fn charge_artifact(
+        self: &Arc<Self>,
+        tenant: TenantId,
+        code_bytes: usize,
+    ) -> Result<TenantCharge, CompileError> {
+        let mut tenants = self.tenants.lock().unwrap();
+        let state = tenants.entry(tenant).or_default();
+        let exceeded = |dimension, limit: usize, used: usize| CompileError::QuotaExceeded {
+            tenant,
+            dimension,
+            limit: limit as u64,
+            used: used as u64,
+        };
+        let artifacts = state.usage.artifacts_loaded + 1;
+        if let Some(limit) = state.quota.max_artifacts.filter(|&limit| artifacts > limit) {
+            return Err(exceeded(QuotaDimension::Artifacts, limit, artifacts));
+        }
+        let bytes = state.usage.code_bytes.saturating_add(code_bytes);
+        if let Some(limit) = state.quota.max_code_bytes.filter(|&limit| bytes > limit) {
+            return Err(exceeded(QuotaDimension::CodeBytes, limit, bytes));
+        }
+        state.usage.artifacts_loaded = artifacts;
+        state.usage.code_bytes = bytes;
+        Ok(TenantCharge {
+            ledger: Arc::clone(self),
+            tenant,
+            code_bytes,
+        })
+    }
+}
+
+/// An artifact charged to a tenant, credited back once dropped.
+pub(crate) struct TenantCharge {
+    ledger: Arc<TenantLedger>,
+    tenant: TenantId,
+    code_bytes: usize,
+}
+
+impl Drop for TenantCharge {
+    fn drop(&mut self) {
+        let mut tenants = self.ledger.tenants.lock().unwrap();
+        if let Some(state) = tenants.get_mut(&self.tenant) {
+            state.usage.artifacts_loaded -= 1;
+            state.usage.code_bytes -= self.code_bytes;
+        }
+    }
+}
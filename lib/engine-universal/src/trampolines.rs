@@ -0,0 +1,94 @@
+//! Trampolines compiled ahead of time, shared by the modules and host functions of an engine.
+
+use crate::executable::{ExecutableSerializeError, UniversalExecutableRef};
+use enumset::EnumSet;
+use rkyv::de::deserializers::SharedDeserializeMap;
+use rkyv::ser::serializers::AllocSerializer;
+use wasmer_compiler::{CpuFeature, FunctionBody};
+use wasmer_engine::DeserializeError;
+use wasmer_types::FunctionType;
+
+const MAGIC_HEADER: [u8; 32] = {
+    let value = *b"\0wasmer-trampolines\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF";
+    let _length_must_be_multiple_of_16: bool = [true][value.len() % 16];
+    value
+};
+
+/// The trampolines calling functions of a set of signatures from the host, compiled by
+/// [`UniversalEngine::compile_trampoline_set`](crate::UniversalEngine::compile_trampoline_set)
+/// and installed with
+/// [`UniversalEngine::install_trampoline_set`](crate::UniversalEngine::install_trampoline_set).
+///
+/// It only depends on the target it was compiled for, so that processes that never compile
+/// WebAssembly can load the same serialized set rather than each compiling the trampolines
+/// their host functions need.
+#[derive(rkyv::Archive, rkyv::Deserialize, rkyv::Serialize)]
+#[archive(check_bytes)]
+pub struct TrampolineSetExecutable {
+    pub(crate) signatures: Vec<FunctionType>,
+    pub(crate) bodies: Vec<FunctionBody>,
+    pub(crate) cpu_features: u64,
+}
+
+impl TrampolineSetExecutable {
+    /// The signatures of the functions the trampolines call.
+    pub fn signatures(&self) -> &[FunctionType] {
+        &self.signatures
+    }
+
+    /// The CPU features the trampolines were compiled for.
+    pub fn cpu_features(&self) -> EnumSet<CpuFeature> {
+        EnumSet::from_u64(self.cpu_features)
+    }
+
+    /// Serialize the set, in the same framing as a serialized `UniversalExecutable` but with
+    /// its own header.
+    pub fn serialize(&self) -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut serializer = AllocSerializer::<1024>::default();
+        let pos = rkyv::ser::Serializer::serialize_value(&mut serializer, self)
+            .map_err(ExecutableSerializeError::Executable)? as u64;
+        let data = serializer.into_serializer().into_inner();
+        let mut out = Vec::with_capacity(MAGIC_HEADER.len() + data.len() + 8);
+        out.extend(&MAGIC_HEADER);
+        out.extend(data.as_slice());
+        out.extend(&pos.to_le_bytes());
+        Ok(out)
+    }
+
+    /// Deserialize a set serialized by [`TrampolineSetExecutable::serialize`].
+    ///
+    /// # Safety
+    ///
+    /// As for `UniversalExecutableRef::deserialize`, the data is validated to be well-formed,
+    /// but its code is run as it is, so that it must come from a trusted source.
+    pub unsafe fn deserialize(data: &[u8]) -> Result<Self, DeserializeError> {
+        if !data.starts_with(&MAGIC_HEADER) {
+            return Err(DeserializeError::Incompatible(
+                "the provided bytes are not a wasmer trampoline set".to_string(),
+            ));
+        }
+        if data.len() < MAGIC_HEADER.len() + 8 {
+            return Err(DeserializeError::CorruptedBinary(
+                "the data buffer is too small to be valid".to_string(),
+            ));
+        }
+        let (archive, position) = data.split_at(data.len() - 8);
+        let mut position_value = [0u8; 8];
+        position_value.copy_from_slice(position);
+        let position = u64::from_le_bytes(position_value);
+        let (_, payload) = archive.split_at(MAGIC_HEADER.len());
+        if position > payload.len() as u64 {
+            return Err(DeserializeError::CorruptedBinary(
+                "the buffer is malformed".to_string(),
+            ));
+        }
+        UniversalExecutableRef::with_aligned(payload, |payload| {
+            let archive =
+                rkyv::check_archived_value::<Self>(payload, position as usize).map_err(|e| {
+                    DeserializeError::CorruptedBinary(format!("the set is malformed: {}", e))
+                })?;
+            rkyv::Deserialize::deserialize(archive, &mut SharedDeserializeMap::new())
+                .map_err(|e| DeserializeError::CorruptedBinary(format!("{:?}", e)))
+        })
+    }
+}
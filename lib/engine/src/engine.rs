@@ -4,7 +4,10 @@ use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 use std::sync::Arc;
 use wasmer_compiler::{CompileError, Target};
 use wasmer_types::{FunctionType, FunctionTypeRef};
-use wasmer_vm::{Artifact, Tunables, VMCallerCheckedAnyfunc, VMFuncRef, VMSharedSignatureIndex};
+use wasmer_vm::{
+    Artifact, MetricsSink, Tunables, VMCallerCheckedAnyfunc, VMFuncRef, VMSharedSignatureIndex,
+    VMTrampoline,
+};
 
 mod private {
     pub struct Internal(pub(super) ());
@@ -29,6 +32,31 @@ pub trait Engine {
     /// Lookup a signature
     fn lookup_signature(&self, sig: VMSharedSignatureIndex) -> Option<FunctionType>;
 
+    /// Lookup a signature, without copying it if the engine can share it.
+    fn lookup_shared_signature(&self, sig: VMSharedSignatureIndex) -> Option<Arc<FunctionType>> {
+        self.lookup_signature(sig).map(Arc::new)
+    }
+
+    /// The trampoline installed to call functions of signature `sig` from the host, if any.
+    fn lookup_function_call_trampoline(
+        &self,
+        _sig: VMSharedSignatureIndex,
+    ) -> Option<VMTrampoline> {
+        None
+    }
+
+    /// The trampoline calling functions of signature `sig` from the host, compiling it if
+    /// the engine can and none is installed.
+    fn function_call_trampoline(&self, sig: VMSharedSignatureIndex) -> Option<VMTrampoline> {
+        self.lookup_function_call_trampoline(sig)
+    }
+
+    /// A hash of the configuration this engine compiles with: its version, target and CPU
+    /// features, compiler and compiler settings, and WebAssembly features.
+    ///
+    /// Engines with the same fingerprint compile the same binary to the same code.
+    fn fingerprint(&self) -> [u8; 32];
+
     /// Validates a WebAssembly module
     fn validate(&self, binary: &[u8]) -> Result<(), CompileError>;
 
@@ -43,6 +71,18 @@ pub trait Engine {
     fn load(&self, executable: &(dyn crate::Executable))
         -> Result<Arc<dyn Artifact>, CompileError>;
 
+    /// Compile a WebAssembly binary and load it with this engine.
+    ///
+    /// Engines may overlap the two, the default compiles the binary then loads it.
+    fn compile_and_load(
+        &self,
+        binary: &[u8],
+        tunables: &dyn Tunables,
+    ) -> Result<Arc<dyn Artifact>, CompileError> {
+        let executable = self.compile(binary, tunables)?;
+        self.load(&*executable)
+    }
+
     /// A unique identifier for this object.
     ///
     /// This exists to allow us to compare two Engines for equality. Otherwise,
@@ -53,6 +93,11 @@ pub trait Engine {
     /// Clone the engine
     fn cloned(&self) -> Arc<dyn Engine + Send + Sync>;
 
+    /// The sink metrics of this engine are reported to, if any.
+    fn metrics_sink(&self) -> Option<Arc<dyn MetricsSink>> {
+        None
+    }
+
     /// Internal: support for downcasting `Engine`s.
     #[doc(hidden)]
     fn type_id(&self, _: private::Internal) -> std::any::TypeId
@@ -3,24 +3,28 @@ use crate::trap::RuntimeError;
 use std::io;
 use thiserror::Error;
 use wasmer_compiler::CompileError;
-use wasmer_types::ExternType;
+use wasmer_types::{Classify, DataError, ErrorCode, ExternType, FailureKind, HasErrorCode};
+use wasmer_vm::{InstanceLayout, MemoryError};
 
 /// The Deserialize error can occur when loading a
 /// compiled Module from a binary.
 #[derive(Error, Debug)]
 pub enum DeserializeError {
     /// An IO error
-    #[error(transparent)]
+    #[error("{0} [{}]", ErrorCode::DeserializeIo)]
     Io(#[from] io::Error),
     /// A generic deserialization error
-    #[error("{0}")]
+    #[error("{0} [{}]", ErrorCode::DeserializeGeneric)]
     Generic(String),
     /// Incompatible serialized binary
-    #[error("incompatible binary: {0}")]
+    #[error("incompatible binary: {0} [{}]", ErrorCode::DeserializeIncompatible)]
     Incompatible(String),
     /// The provided binary is corrupted
-    #[error("corrupted binary: {0}")]
+    #[error("corrupted binary: {0} [{}]", ErrorCode::DeserializeCorrupted)]
     CorruptedBinary(String),
+    /// The binary is larger than the limits of the deserializer allow
+    #[error("binary too large: {0} [{}]", ErrorCode::DeserializeLimitExceeded)]
+    LimitExceeded(String),
     /// The binary was valid, but we got an error when
     /// trying to allocate the required resources.
     #[error(transparent)]
@@ -35,13 +39,29 @@ pub enum DeserializeError {
 pub enum ImportError {
     /// Incompatible Import Type.
     /// This error occurs when the import types mismatch.
-    #[error("incompatible import type. Expected {0:?} but received {1:?}")]
+    ///
+    /// Linking reports these with [`LinkError::Imports`], along with the other imports that
+    /// cannot be linked.
+    #[error(
+        "incompatible import type. Expected {0:?} but received {1:?} [{}]",
+        ErrorCode::ImportIncompatibleType
+    )]
     IncompatibleType(ExternType, ExternType),
 
     /// Unknown Import.
     /// This error occurs when an import was expected but not provided.
-    #[error("unknown import. Expected {0:?}")]
+    ///
+    /// Linking reports these with [`LinkError::Imports`], along with the other imports that
+    /// cannot be linked.
+    #[error("unknown import. Expected {0:?} [{}]", ErrorCode::ImportUnknown)]
     UnknownImport(ExternType),
+
+    /// Rejected Import.
+    /// This error occurs when the resolver rejects an import, see
+    /// [`Resolver::try_resolve`](wasmer_vm::Resolver::try_resolve). The error of the
+    /// resolver can be retrieved by downcasting.
+    #[error("import rejected: {0} [{}]", ErrorCode::ImportRejected)]
+    Rejected(Box<dyn std::error::Error + Send + Sync>),
 }
 
 /// The WebAssembly.LinkError object indicates an error during
@@ -57,13 +77,24 @@ pub enum LinkError {
     #[error("Error while importing {0:?}.{1:?}: {2}")]
     Import(String, String, ImportError),
 
+    /// Imports are missing or do not have the expected type, with their module and field
+    /// names and either [`ImportError::UnknownImport`] or [`ImportError::IncompatibleType`].
+    /// Every import is resolved before linking fails, so that all of them are reported at
+    /// once, in the order of the module.
+    #[error("{} [{}]", describe_imports(.0), ErrorCode::LinkImports)]
+    Imports(Vec<(String, String, ImportError)>),
+
     /// A trap ocurred during linking.
     #[error("RuntimeError occurred during linking: {0}")]
     Trap(#[source] RuntimeError),
 
     /// Insufficient resources available for linking.
-    #[error("Insufficient resources: {0}")]
+    #[error("Insufficient resources: {0} [{}]", ErrorCode::LinkResource)]
     Resource(String),
+
+    /// A memory of the instance could not be created.
+    #[error("Insufficient resources: Failed to create memory: {0}")]
+    Memory(MemoryError),
 }
 
 /// An error while instantiating a module.
@@ -81,10 +112,232 @@ pub enum InstantiationError {
 
     /// The module was compiled with a CPU feature that is not available on
     /// the current host.
-    #[error("module compiled with CPU feature that is missing from host")]
+    #[error(
+        "module compiled with CPU feature that is missing from host [{}]",
+        ErrorCode::InstantiationCpuFeature
+    )]
     CpuFeature(String),
 
     /// A runtime error occured while invoking the start function
     #[error(transparent)]
     Start(RuntimeError),
+
+    /// The contents of an externalized data segment could not be obtained.
+    #[error(transparent)]
+    ExternalData(ExternalDataError),
+
+    /// The module cannot be instantiated with read-only memories.
+    #[error(
+        "module cannot be instantiated read-only: {0} [{}]",
+        ErrorCode::InstantiationReadOnlyUnsupported
+    )]
+    ReadOnlyUnsupported(String),
+
+    /// The module was compiled for another memory style than the tunables used to instantiate
+    /// it choose.
+    #[error(
+        "module compiled for another memory style: {0} [{}]",
+        ErrorCode::InstantiationMemoryStyleMismatch
+    )]
+    MemoryStyleMismatch(String),
+
+    /// An imported global differs from the value the module was specialized for.
+    #[error(
+        "module specialized for another value: {0} [{}]",
+        ErrorCode::InstantiationSpecializationMismatch
+    )]
+    SpecializationMismatch(String),
+
+    /// The instance would allocate more than the limit of its configuration, see
+    /// `InstanceConfig::with_max_instance_overhead_bytes`.
+    #[error(
+        "instance needs {} bytes, more than the limit of {} bytes [{}]",
+        .layout.total_bytes(),
+        .limit,
+        ErrorCode::InstantiationOverheadExceeded
+    )]
+    OverheadExceeded {
+        /// What the instance would allocate.
+        layout: InstanceLayout,
+        /// The limit of the configuration.
+        limit: usize,
+    },
+}
+
+/// An error while obtaining the contents of an externalized data segment.
+#[derive(Error, Debug)]
+#[error("data segment {segment_index} with hash {} is unavailable: {kind}", hex(.hash))]
+pub struct ExternalDataError {
+    /// The position of the segment among the active data segments of the module.
+    pub segment_index: u32,
+    /// The expected SHA-256 hash of the segment contents.
+    pub hash: [u8; 32],
+    /// What went wrong.
+    pub kind: ExternalDataErrorKind,
+}
+
+/// The reason an externalized data segment is unavailable.
+#[derive(Error, Debug)]
+pub enum ExternalDataErrorKind {
+    /// The instance was configured without a data provider.
+    #[error(
+        "no data provider is configured [{}]",
+        ErrorCode::ExternalDataMissingProvider
+    )]
+    MissingProvider,
+    /// The data provider failed to supply the data.
+    #[error(transparent)]
+    Fetch(DataError),
+    /// The supplied data does not have the expected length or hash.
+    #[error(
+        "the supplied data does not match the hash [{}]",
+        ErrorCode::ExternalDataHashMismatch
+    )]
+    HashMismatch,
+}
+
+impl Classify for DeserializeError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::Io(e) => e.failure_kind(),
+            Self::Generic(_) | Self::Incompatible(_) | Self::LimitExceeded(_) => {
+                FailureKind::Permanent
+            }
+            Self::CorruptedBinary(_) => FailureKind::Corrupt,
+            Self::Compiler(e) => e.failure_kind(),
+        }
+    }
+}
+
+impl Classify for ImportError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::IncompatibleType(..) | Self::UnknownImport(_) | Self::Rejected(_) => {
+                FailureKind::Permanent
+            }
+        }
+    }
+}
+
+impl Classify for LinkError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::Import(_, _, e) => e.failure_kind(),
+            Self::Imports(_) => FailureKind::Permanent,
+            Self::Trap(e) => e.failure_kind(),
+            Self::Resource(_) => FailureKind::TransientResource,
+            Self::Memory(e) => e.failure_kind(),
+        }
+    }
+}
+
+impl Classify for InstantiationError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::Link(e) => e.failure_kind(),
+            Self::CpuFeature(_) => FailureKind::Permanent,
+            Self::Start(e) => e.failure_kind(),
+            Self::ExternalData(e) => e.failure_kind(),
+            Self::ReadOnlyUnsupported(_) => FailureKind::Permanent,
+            Self::MemoryStyleMismatch(_) => FailureKind::Permanent,
+            Self::SpecializationMismatch(_) => FailureKind::Permanent,
+            Self::OverheadExceeded { .. } => FailureKind::Permanent,
+        }
+    }
+}
+
+impl Classify for ExternalDataError {
+    fn failure_kind(&self) -> FailureKind {
+        match &self.kind {
+            ExternalDataErrorKind::MissingProvider => FailureKind::Permanent,
+            ExternalDataErrorKind::Fetch(e) => e.failure_kind(),
+            ExternalDataErrorKind::HashMismatch => FailureKind::Corrupt,
+        }
+    }
+}
+
+impl HasErrorCode for DeserializeError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Io(_) => ErrorCode::DeserializeIo,
+            Self::Generic(_) => ErrorCode::DeserializeGeneric,
+            Self::Incompatible(_) => ErrorCode::DeserializeIncompatible,
+            Self::CorruptedBinary(_) => ErrorCode::DeserializeCorrupted,
+            Self::LimitExceeded(_) => ErrorCode::DeserializeLimitExceeded,
+            Self::Compiler(e) => e.code(),
+        }
+    }
+}
+
+impl HasErrorCode for ImportError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::IncompatibleType(..) => ErrorCode::ImportIncompatibleType,
+            Self::UnknownImport(_) => ErrorCode::ImportUnknown,
+            Self::Rejected(_) => ErrorCode::ImportRejected,
+        }
+    }
+}
+
+impl HasErrorCode for LinkError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Import(_, _, e) => e.code(),
+            Self::Imports(_) => ErrorCode::LinkImports,
+            Self::Trap(e) => e.code(),
+            Self::Resource(_) => ErrorCode::LinkResource,
+            Self::Memory(e) => e.code(),
+        }
+    }
+}
+
+impl HasErrorCode for InstantiationError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Link(e) => e.code(),
+            Self::CpuFeature(_) => ErrorCode::InstantiationCpuFeature,
+            Self::Start(e) => e.code(),
+            Self::ExternalData(e) => e.code(),
+            Self::ReadOnlyUnsupported(_) => ErrorCode::InstantiationReadOnlyUnsupported,
+            Self::MemoryStyleMismatch(_) => ErrorCode::InstantiationMemoryStyleMismatch,
+            Self::SpecializationMismatch(_) => ErrorCode::InstantiationSpecializationMismatch,
+            Self::OverheadExceeded { .. } => ErrorCode::InstantiationOverheadExceeded,
+        }
+    }
+}
+
+impl HasErrorCode for ExternalDataError {
+    fn code(&self) -> ErrorCode {
+        self.kind.code()
+    }
+}
+
+impl HasErrorCode for ExternalDataErrorKind {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::MissingProvider => ErrorCode::ExternalDataMissingProvider,
+            Self::Fetch(e) => e.code(),
+            Self::HashMismatch => ErrorCode::ExternalDataHashMismatch,
+        }
+    }
+}
+
+/// List the `imports` that cannot be linked, in the words of the single [`ImportError`]s that
+/// `assert_unlinkable` tests expect, as in
+/// `2 imports cannot be linked: "env"."f": unknown import. Expected ...; "env"."g": ...`.
+fn describe_imports(imports: &[(String, String, ImportError)]) -> String {
+    let problems = imports
+        .iter()
+        .map(|(module, field, error)| format!("{:?}.{:?}: {}", module, field, error))
+        .collect::<Vec<_>>();
+    format!(
+        "{} import{} cannot be linked: {}",
+        problems.len(),
+        if problems.len() == 1 { "" } else { "s" },
+        problems.join("; ")
+    )
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
 }
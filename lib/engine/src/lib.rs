@@ -27,9 +27,12 @@ mod resolver;
 mod trap;
 
 pub use crate::engine::{Engine, EngineId};
-pub use crate::error::{DeserializeError, ImportError, InstantiationError, LinkError};
+pub use crate::error::{
+    DeserializeError, ExternalDataError, ExternalDataErrorKind, ImportError, InstantiationError,
+    LinkError,
+};
 pub use crate::executable::Executable;
-pub use crate::resolver::resolve_imports;
+pub use crate::resolver::{is_compatible_memory, is_compatible_table, resolve_imports};
 pub use crate::trap::*;
 
 /// Version number of this crate.
@@ -7,29 +7,101 @@ use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{ExternType, FunctionIndex, ImportCounts, MemoryType, TableType};
 
 use wasmer_vm::{
-    Export, ExportFunctionMetadata, FunctionBodyPtr, ImportFunctionEnv, Imports, MemoryStyle,
-    Resolver, VMFunctionBody, VMFunctionEnvironment, VMFunctionImport, VMFunctionKind,
-    VMGlobalImport, VMImport, VMImportType, VMMemoryImport, VMTableImport,
+    Export, ExportFunction, ExportFunctionMetadata, FunctionBodyPtr, ImportFunctionEnv, Imports,
+    MemoryStyle, Resolver, VMFunctionBody, VMFunctionEnvironment, VMFunctionImport, VMFunctionKind,
+    VMGlobal, VMGlobalImport, VMImport, VMImportType, VMMemory, VMMemoryImport,
+    VMSharedSignatureIndex, VMTable, VMTableImport, VMTrampoline,
 };
 
-fn is_compatible_table(ex: &TableType, im: &TableType) -> bool {
+/// Whether a table of type `ex` satisfies an import of a table of type `im`.
+pub fn is_compatible_table(ex: &TableType, im: &TableType) -> bool {
     (ex.ty == wasmer_types::Type::FuncRef || ex.ty == im.ty)
         && im.minimum <= ex.minimum
         && (im.maximum.is_none()
             || (!ex.maximum.is_none() && im.maximum.unwrap() >= ex.maximum.unwrap()))
 }
 
-fn is_compatible_memory(ex: &MemoryType, im: &MemoryType) -> bool {
+/// Whether a memory of type `ex` satisfies an import of a memory of type `im`.
+pub fn is_compatible_memory(ex: &MemoryType, im: &MemoryType) -> bool {
     im.minimum <= ex.minimum
         && (im.maximum.is_none()
             || (!ex.maximum.is_none() && im.maximum.unwrap() >= ex.maximum.unwrap()))
         && ex.shared == im.shared
 }
 
+/// The type of an import of type `ty`.
+fn import_extern(engine: &dyn Engine, ty: &VMImportType) -> ExternType {
+    match ty {
+        VMImportType::Table(t) => ExternType::Table(*t),
+        VMImportType::Memory(t, _) => ExternType::Memory(*t),
+        VMImportType::Global(t) => ExternType::Global(*t),
+        VMImportType::Function {
+            sig,
+            static_trampoline: _,
+        } => ExternType::Function(
+            engine
+                .lookup_signature(*sig)
+                .expect("VMSharedSignatureIndex is not valid?"),
+        ),
+    }
+}
+
+/// The type of `export`.
+fn export_extern(engine: &dyn Engine, export: &Export) -> ExternType {
+    match export {
+        Export::Function(f) => ExternType::Function(
+            engine
+                .lookup_signature(f.vm_function.signature)
+                .expect("VMSharedSignatureIndex not registered with engine (wrong engine?)"),
+        ),
+        Export::Table(t) => ExternType::Table(*t.ty()),
+        Export::Memory(m) => ExternType::Memory(m.ty()),
+        Export::Global(g) => ExternType::Global(*g.from.ty()),
+    }
+}
+
+/// An export satisfying an import, with what the import tells of how to use it.
+enum Linked {
+    Function(ExportFunction, VMSharedSignatureIndex, VMTrampoline),
+    Table(VMTable),
+    Memory(VMMemory, MemoryStyle),
+    Global(VMGlobal),
+}
+
+/// Check that `export` satisfies an import of type `ty`, giving it back if it does not.
+fn link(export: Export, ty: &VMImportType) -> Result<Linked, Export> {
+    match (export, ty) {
+        (
+            Export::Function(ex),
+            VMImportType::Function {
+                sig,
+                static_trampoline,
+            },
+        ) if ex.vm_function.signature == *sig => Ok(Linked::Function(ex, *sig, *static_trampoline)),
+        (Export::Table(ex), VMImportType::Table(im))
+            if is_compatible_table(ex.ty(), im) && ex.from.ty().ty == im.ty =>
+        {
+            Ok(Linked::Table(ex))
+        }
+        (Export::Memory(ex), VMImportType::Memory(im, style))
+            if is_compatible_memory(&ex.ty(), im) =>
+        {
+            Ok(Linked::Memory(ex, style.clone()))
+        }
+        (Export::Global(ex), VMImportType::Global(im)) if ex.from.ty() == im => {
+            Ok(Linked::Global(ex))
+        }
+        (export, _) => Err(export),
+    }
+}
+
 /// This function allows to match all imports of a `ModuleInfo` with concrete definitions provided by
 /// a `Resolver`.
 ///
 /// If all imports are satisfied returns an `Imports` instance required for a module instantiation.
+/// Otherwise, every import is resolved before failing with [`LinkError::Imports`], which lists
+/// all the imports that are missing or do not have the expected type. An import the resolver
+/// rejects fails the resolution right away, with [`ImportError::Rejected`].
 pub fn resolve_imports(
     engine: &dyn Engine,
     resolver: &dyn Resolver,
@@ -37,12 +109,8 @@ pub fn resolve_imports(
     imports: &[VMImport],
     finished_dynamic_function_trampolines: &BoxedSlice<FunctionIndex, FunctionBodyPtr>,
 ) -> Result<Imports, LinkError> {
-    let mut function_imports = PrimaryMap::with_capacity(import_counts.functions as _);
-    let mut host_function_env_initializers =
-        PrimaryMap::with_capacity(import_counts.functions as _);
-    let mut table_imports = PrimaryMap::with_capacity(import_counts.tables as _);
-    let mut memory_imports = PrimaryMap::with_capacity(import_counts.memories as _);
-    let mut global_imports = PrimaryMap::with_capacity(import_counts.globals as _);
+    let mut linked_imports = Vec::with_capacity(imports.len());
+    let mut unlinked = Vec::new();
     for VMImport {
         import_no,
         module,
@@ -50,52 +118,41 @@ pub fn resolve_imports(
         ty,
     } in imports
     {
-        let resolved = resolver.resolve(*import_no, module, field);
-        let import_extern = || match ty {
-            &VMImportType::Table(t) => ExternType::Table(t),
-            &VMImportType::Memory(t, _) => ExternType::Memory(t),
-            &VMImportType::Global(t) => ExternType::Global(t),
-            &VMImportType::Function {
-                sig,
-                static_trampoline: _,
-            } => ExternType::Function(
-                engine
-                    .lookup_signature(sig)
-                    .expect("VMSharedSignatureIndex is not valid?"),
-            ),
-        };
-        let resolved = match resolved {
-            Some(r) => r,
-            None => {
-                return Err(LinkError::Import(
+        let resolved = resolver
+            .try_resolve(*import_no, module, field)
+            .map_err(|error| {
+                LinkError::Import(
                     module.to_string(),
                     field.to_string(),
-                    ImportError::UnknownImport(import_extern()),
-                ));
+                    ImportError::Rejected(error),
+                )
+            })?;
+        let error = match resolved.map(|export| link(export, ty)) {
+            Some(Ok(linked)) => {
+                linked_imports.push(linked);
+                continue;
             }
-        };
-        let export_extern = || match resolved {
-            Export::Function(ref f) => ExternType::Function(
-                engine
-                    .lookup_signature(f.vm_function.signature)
-                    .expect("VMSharedSignatureIndex not registered with engine (wrong engine?)")
-                    .clone(),
+            Some(Err(export)) => ImportError::IncompatibleType(
+                import_extern(engine, ty),
+                export_extern(engine, &export),
             ),
-            Export::Table(ref t) => ExternType::Table(*t.ty()),
-            Export::Memory(ref m) => ExternType::Memory(m.ty()),
-            Export::Global(ref g) => {
-                let global = g.from.ty();
-                ExternType::Global(*global)
-            }
+            None => ImportError::UnknownImport(import_extern(engine, ty)),
         };
-        match (&resolved, ty) {
-            (
-                Export::Function(ex),
-                VMImportType::Function {
-                    sig,
-                    static_trampoline,
-                },
-            ) if ex.vm_function.signature == *sig => {
+        unlinked.push((module.to_string(), field.to_string(), error));
+    }
+    if !unlinked.is_empty() {
+        return Err(LinkError::Imports(unlinked));
+    }
+
+    let mut function_imports = PrimaryMap::with_capacity(import_counts.functions as _);
+    let mut host_function_env_initializers =
+        PrimaryMap::with_capacity(import_counts.functions as _);
+    let mut table_imports = PrimaryMap::with_capacity(import_counts.tables as _);
+    let mut memory_imports = PrimaryMap::with_capacity(import_counts.memories as _);
+    let mut global_imports = PrimaryMap::with_capacity(import_counts.globals as _);
+    for linked in linked_imports {
+        match linked {
+            Linked::Function(ex, sig, static_trampoline) => {
                 let address = match ex.vm_function.kind {
                     VMFunctionKind::Dynamic => {
                         // If this is a dynamic imported function,
@@ -134,7 +191,7 @@ pub fn resolve_imports(
                     Some(t)
                 } else if let VMFunctionKind::Static = ex.vm_function.kind {
                     // Look up a trampoline by finding one by the signature and fill it in.
-                    Some(*static_trampoline)
+                    Some(static_trampoline)
                 } else {
                     // FIXME: remove this possibility entirely.
                     None
@@ -142,7 +199,7 @@ pub fn resolve_imports(
 
                 function_imports.push(VMFunctionImport {
                     body: FunctionBodyPtr(address),
-                    signature: *sig,
+                    signature: sig,
                     environment: VMFunctionEnvironment { host_env: env },
                     trampoline,
                 });
@@ -167,23 +224,13 @@ pub fn resolve_imports(
 
                 host_function_env_initializers.push(import_function_env);
             }
-            (Export::Table(ex), VMImportType::Table(im)) if is_compatible_table(ex.ty(), im) => {
-                let import_table_ty = ex.from.ty();
-                if import_table_ty.ty != im.ty {
-                    return Err(LinkError::Import(
-                        module.to_string(),
-                        field.to_string(),
-                        ImportError::IncompatibleType(import_extern(), export_extern()),
-                    ));
-                }
+            Linked::Table(ex) => {
                 table_imports.push(VMTableImport {
                     definition: ex.from.vmtable(),
                     from: ex.from.clone(),
                 });
             }
-            (Export::Memory(ex), VMImportType::Memory(im, import_memory_style))
-                if is_compatible_memory(&ex.ty(), im) =>
-            {
+            Linked::Memory(ex, import_memory_style) => {
                 // Sanity-check: Ensure that the imported memory has at least
                 // guard-page protections the importing module expects it to have.
                 let export_memory_style = ex.style();
@@ -207,19 +254,12 @@ pub fn resolve_imports(
                 });
             }
 
-            (Export::Global(ex), VMImportType::Global(im)) if ex.from.ty() == im => {
+            Linked::Global(ex) => {
                 global_imports.push(VMGlobalImport {
                     definition: ex.from.vmglobal(),
                     from: ex.from.clone(),
                 });
             }
-            _ => {
-                return Err(LinkError::Import(
-                    module.to_string(),
-                    field.to_string(),
-                    ImportError::IncompatibleType(import_extern(), export_extern()),
-                ));
-            }
         }
     }
     Ok(Imports::new(
@@ -0,0 +1,128 @@
+//! How much of the backtraces of traps is captured and kept.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// When the backtrace of a trap is captured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BacktraceCapture {
+    /// Capture the backtrace when the trap is raised.
+    Always,
+    /// Only keep where the trap was raised, and look its frame up when
+    /// [`RuntimeError::trace`] is first called, as for lightweight traps.
+    ///
+    /// [`RuntimeError::trace`]: crate::RuntimeError::trace
+    OnlyIfRequested,
+    /// Never capture a backtrace: the trace of the error is empty.
+    Never,
+}
+
+/// How much of the backtraces of traps is captured and kept in their [`RuntimeError`].
+///
+/// Deeply recursive code trapping has a backtrace of tens of thousands of frames, long to
+/// capture and symbolicate and large to hold.
+///
+/// [`RuntimeError`]: crate::RuntimeError
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BacktracePolicy {
+    /// Keep at most this many frames of the trace: the innermost half, rounded up, and the
+    /// outermost half. The frames in between are elided, see
+    /// [`RuntimeError::elided_frames`](crate::RuntimeError::elided_frames).
+    pub max_frames: Option<usize>,
+    /// When the backtrace is captured.
+    pub capture: BacktraceCapture,
+    /// Collapse runs of identical frames, as recursive calls make, into one frame, see
+    /// [`FrameInfo::repeats`](crate::FrameInfo::repeats). This happens before `max_frames`
+    /// applies.
+    pub summarize_recursion: bool,
+}
+
+impl Default for BacktracePolicy {
+    /// Capture the whole backtrace, as it is.
+    fn default() -> Self {
+        Self {
+            max_frames: None,
+            capture: BacktraceCapture::Always,
+            summarize_recursion: false,
+        }
+    }
+}
+
+/// Frames elided from the trace of a [`RuntimeError`] by [`BacktracePolicy::max_frames`].
+///
+/// [`RuntimeError`]: crate::RuntimeError
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ElidedFrames {
+    /// The index in the trace of the first frame kept after the elided ones.
+    pub index: usize,
+    /// How many frames were elided, counting the repeats of summarized frames.
+    pub count: usize,
+}
+
+/// The [`BacktracePolicy`] of a store and its clones.
+#[derive(Default)]
+pub struct SharedBacktracePolicy {
+    policy: Mutex<BacktracePolicy>,
+    /// Whether `policy` skips capturing backtraces when traps are raised, read on every call.
+    skip_capture: AtomicBool,
+}
+
+impl SharedBacktracePolicy {
+    /// Apply `policy` to the traps raised from now on.
+    pub fn set(&self, policy: BacktracePolicy) {
+        let mut current = self.policy.lock().unwrap();
+        *current = policy;
+        self.skip_capture
+            .store(policy.capture != BacktraceCapture::Always, Ordering::SeqCst);
+    }
+
+    /// Returns the policy.
+    pub fn get(&self) -> BacktracePolicy {
+        *self.policy.lock().unwrap()
+    }
+
+    /// Returns whether traps capture a backtrace when raised, as taken by
+    /// [`with_backtraces`](wasmer_vm::with_backtraces).
+    pub fn captures(&self) -> bool {
+        !self.skip_capture.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Debug for SharedBacktracePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedBacktracePolicy")
+            .field(&self.get())
+            .finish()
+    }
+}
+
+/// Program counters of frames, each with how many times it repeats.
+type Frames = Vec<(usize, usize)>;
+
+/// Summarize and truncate the program counters of the frames of a trace as `policy` says:
+/// returns the innermost frames kept, the outermost ones, each with how many times it
+/// repeats, and how many frames were elided in between.
+pub(super) fn apply(
+    policy: &BacktracePolicy,
+    pcs: impl IntoIterator<Item = usize>,
+) -> (Frames, Frames, usize) {
+    let mut frames: Frames = Vec::new();
+    for pc in pcs {
+        match frames.last_mut() {
+            Some((last, repeats)) if policy.summarize_recursion && *last == pc => *repeats += 1,
+            _ => frames.push((pc, 1)),
+        }
+    }
+    match policy.max_frames {
+        Some(max) if frames.len() > max => {
+            let outermost = frames.split_off(frames.len() - max / 2);
+            let elided = frames
+                .drain(max - max / 2..)
+                .map(|(_, repeats)| repeats)
+                .sum();
+            (frames, outermost, elided)
+        }
+        _ => (frames, Vec::new(), 0),
+    }
+}
@@ -1,9 +1,13 @@
+use super::backtrace_policy::{self, BacktraceCapture, BacktracePolicy, ElidedFrames};
 use super::frame_info::{FrameInfo, GlobalFrameInfo, FRAME_INFO};
+use super::symbolication::SymbolicationCache;
 use backtrace::Backtrace;
+use once_cell::sync::OnceCell;
 use std::error::Error;
 use std::fmt;
 use std::sync::Arc;
-use wasmer_vm::{raise_user_trap, Trap, TrapCode};
+use wasmer_types::{Classify, ErrorCode, FailureKind, HasErrorCode};
+use wasmer_vm::{raise_user_trap, MetricsSink, Trap, TrapCode};
 
 /// A struct representing an aborted instruction execution, with a message
 /// indicating the cause.
@@ -14,7 +18,7 @@ pub struct RuntimeError {
 
 /// The source of the `RuntimeError`.
 #[derive(Debug)]
-enum RuntimeErrorSource {
+pub(super) enum RuntimeErrorSource {
     Generic(String),
     OOM,
     User(Box<dyn Error + Send + Sync>),
@@ -27,20 +31,52 @@ impl fmt::Display for RuntimeErrorSource {
             Self::Generic(s) => write!(f, "{}", s),
             Self::User(s) => write!(f, "{}", s),
             Self::OOM => write!(f, "Wasmer VM out of memory"),
+            Self::Trap(code @ TrapCode::Custom(number)) => {
+                write!(f, "{} (custom trap {})", code.message(), number)
+            }
             Self::Trap(s) => write!(f, "{}", s.message()),
         }
     }
 }
 
-struct RuntimeErrorInner {
+pub(super) struct RuntimeErrorInner {
     /// The source error (this can be a custom user `Error` or a [`TrapCode`])
-    source: RuntimeErrorSource,
-    /// The reconstructed Wasm trace (from the native trace and the `GlobalFrameInfo`).
-    wasm_trace: Vec<FrameInfo>,
-    /// The native backtrace
-    native_trace: Backtrace,
+    pub(super) source: RuntimeErrorSource,
+    /// The reconstructed Wasm trace (from the native trace and the `GlobalFrameInfo`), only
+    /// computed when first asked for by lightweight traps and deferred backtraces.
+    pub(super) wasm_trace: OnceCell<Vec<FrameInfo>>,
+    /// The program counter a lightweight trap or a trap deferring its backtrace was raised
+    /// at, its trace being the frame there.
+    pub(super) deferred_pc: Option<usize>,
+    /// The frames elided from `wasm_trace` by the backtrace policy, if any.
+    pub(super) elided: Option<ElidedFrames>,
+    /// The native backtrace, empty for lightweight traps, for traps that did not capture it
+    /// and for traps that elided frames from their trace.
+    pub(super) native_trace: Backtrace,
+}
+
+impl RuntimeErrorInner {
+    /// The payload of a lightweight trap, which skips the backtrace.
+    pub(super) fn lightweight(trap_code: TrapCode, pc: Option<usize>) -> Self {
+        Self::deferred(RuntimeErrorSource::Trap(trap_code), pc)
+    }
+
+    /// The payload of an error without a backtrace, its trace being the frame at `pc` if
+    /// known, looked up when first asked for.
+    fn deferred(source: RuntimeErrorSource, pc: Option<usize>) -> Self {
+        Self {
+            source,
+            wasm_trace: OnceCell::new(),
+            deferred_pc: pc,
+            elided: None,
+            native_trace: Vec::new().into(),
+        }
+    }
 }
 
+/// Symbolicates the frame at a program counter, as [`GlobalFrameInfo::lookup_frame_info`].
+type Symbolicate<'a> = dyn Fn(&GlobalFrameInfo, usize) -> Option<FrameInfo> + 'a;
+
 fn _assert_trap_is_sync_and_send(t: &Trap) -> (&dyn Sync, &dyn Send) {
     (t, t)
 }
@@ -58,6 +94,8 @@ impl RuntimeError {
         let msg = message.into();
         Self::new_with_trace(
             &info,
+            &|info, pc| info.lookup_frame_info(pc),
+            &BacktracePolicy::default(),
             None,
             RuntimeErrorSource::Generic(msg),
             Backtrace::new_unresolved(),
@@ -66,6 +104,26 @@ impl RuntimeError {
 
     /// Create a new RuntimeError from a Trap.
     pub fn from_trap(trap: Trap) -> Self {
+        Self::from_trap_with(
+            trap,
+            &|info, pc| info.lookup_frame_info(pc),
+            &BacktracePolicy::default(),
+        )
+    }
+
+    /// Create a new RuntimeError from a Trap, getting the frames of its backtrace from `cache`
+    /// when they are there and caching them otherwise. Whether they were there is reported
+    /// to `sink`, if any. Its backtrace is kept as `policy` says.
+    pub fn from_trap_cached(
+        trap: Trap,
+        cache: &SymbolicationCache,
+        sink: Option<&dyn MetricsSink>,
+        policy: &BacktracePolicy,
+    ) -> Self {
+        Self::from_trap_with(trap, &|info, pc| cache.lookup(info, pc, sink), policy)
+    }
+
+    fn from_trap_with(trap: Trap, symbolicate: &Symbolicate, policy: &BacktracePolicy) -> Self {
         let info = FRAME_INFO.read().unwrap();
         match trap {
             // A user error
@@ -75,16 +133,26 @@ impl RuntimeError {
                     Ok(runtime_error) => *runtime_error,
                     Err(e) => Self::new_with_trace(
                         &info,
+                        symbolicate,
+                        policy,
                         None,
                         RuntimeErrorSource::User(e),
-                        Backtrace::new_unresolved(),
+                        match policy.capture {
+                            BacktraceCapture::Always => Backtrace::new_unresolved(),
+                            _ => Vec::new().into(),
+                        },
                     ),
                 }
             }
             // A trap caused by the VM being Out of Memory
-            Trap::OOM { backtrace } => {
-                Self::new_with_trace(&info, None, RuntimeErrorSource::OOM, backtrace)
-            }
+            Trap::OOM { backtrace } => Self::new_with_trace(
+                &info,
+                symbolicate,
+                policy,
+                None,
+                RuntimeErrorSource::OOM,
+                backtrace,
+            ),
             // A trap caused by an error on the generated machine code for a Wasm function
             Trap::Wasm {
                 pc,
@@ -96,16 +164,51 @@ impl RuntimeError {
                     .map_or(signal_trap.unwrap_or(TrapCode::StackOverflow), |info| {
                         info.trap_code
                     });
-                Self::new_with_trace(&info, Some(pc), RuntimeErrorSource::Trap(code), backtrace)
+                Self::new_with_trace(
+                    &info,
+                    symbolicate,
+                    policy,
+                    Some(pc),
+                    RuntimeErrorSource::Trap(code),
+                    backtrace,
+                )
             }
             // A trap triggered manually from the Wasmer runtime
             Trap::Lib {
                 trap_code,
                 backtrace,
-            } => Self::new_with_trace(&info, None, RuntimeErrorSource::Trap(trap_code), backtrace),
+            } => Self::new_with_trace(
+                &info,
+                symbolicate,
+                policy,
+                None,
+                RuntimeErrorSource::Trap(trap_code),
+                backtrace,
+            ),
+            // A trap raised without a backtrace
+            Trap::Lightweight { trap_code, pc } => Self {
+                inner: Arc::new(RuntimeErrorInner::lightweight(trap_code, pc)),
+            },
         }
     }
 
+    /// Creates a `RuntimeError` carrying the custom user error `error`, which
+    /// [`RuntimeError::downcast`] gives back.
+    ///
+    /// This is what host functions returning `Err(RuntimeError)` use to abort the WebAssembly
+    /// calling them with an error of their own, rather than panicking. A `RuntimeError` given
+    /// as `error` is returned as it is.
+    ///
+    /// # Example
+    /// ```
+    /// # use wasmer_engine::RuntimeError;
+    /// let error = RuntimeError::user(Box::new(std::fmt::Error));
+    /// assert!(error.downcast::<std::fmt::Error>().is_ok());
+    /// ```
+    pub fn user(error: Box<dyn Error + Send + Sync>) -> Self {
+        Self::from_trap(Trap::User(error))
+    }
+
     /// Raises a custom user Error
     pub fn raise(error: Box<dyn Error + Send + Sync>) -> ! {
         unsafe { raise_user_trap(error) }
@@ -113,11 +216,26 @@ impl RuntimeError {
 
     fn new_with_trace(
         info: &GlobalFrameInfo,
+        symbolicate: &Symbolicate,
+        policy: &BacktracePolicy,
         trap_pc: Option<usize>,
         source: RuntimeErrorSource,
         native_trace: Backtrace,
     ) -> Self {
-        let frames: Vec<usize> = native_trace
+        match policy.capture {
+            BacktraceCapture::Always => {}
+            // Best effort: only traps raised by the generated code know where they were.
+            BacktraceCapture::OnlyIfRequested => {
+                return Self::from_inner(Arc::new(RuntimeErrorInner::deferred(source, trap_pc)))
+            }
+            BacktraceCapture::Never => {
+                let inner = RuntimeErrorInner::deferred(source, None);
+                let _ = inner.wasm_trace.set(Vec::new());
+                return Self::from_inner(Arc::new(inner));
+            }
+        }
+
+        let frames = native_trace
             .frames()
             .iter()
             .filter_map(|frame| {
@@ -139,19 +257,40 @@ impl RuntimeError {
                     Some(pc_to_lookup)
                 }
             })
-            .collect();
+            .filter(|&pc| info.is_wasm_frame(pc));
 
-        // Let's construct the trace
-        let wasm_trace = frames
-            .into_iter()
-            .filter_map(|pc| info.lookup_frame_info(pc))
-            .collect::<Vec<_>>();
+        // Only the frames kept are symbolicated.
+        let (innermost, outermost, elided) = backtrace_policy::apply(policy, frames);
+        let symbolicate_all = |frames: Vec<(usize, usize)>| {
+            frames.into_iter().filter_map(move |(pc, repeats)| {
+                let mut frame = symbolicate(info, pc)?;
+                frame.set_repeats(repeats);
+                Some(frame)
+            })
+        };
+        let mut wasm_trace = symbolicate_all(innermost).collect::<Vec<_>>();
+        let elided = if elided > 0 {
+            Some(ElidedFrames {
+                index: wasm_trace.len(),
+                count: elided,
+            })
+        } else {
+            None
+        };
+        wasm_trace.extend(symbolicate_all(outermost));
 
         Self {
             inner: Arc::new(RuntimeErrorInner {
                 source,
-                wasm_trace,
-                native_trace,
+                wasm_trace: OnceCell::with_value(wasm_trace),
+                deferred_pc: None,
+                elided,
+                // Not holding on to the frames elided.
+                native_trace: if elided.is_some() {
+                    Vec::new().into()
+                } else {
+                    native_trace
+                },
             }),
         }
     }
@@ -163,8 +302,34 @@ impl RuntimeError {
 
     /// Returns a list of function frames in WebAssembly code that led to this
     /// trap happening.
+    ///
+    /// Lightweight traps, see `Store::set_lightweight_traps`, and traps deferring their
+    /// backtrace, see [`BacktraceCapture::OnlyIfRequested`], only have the frame they were
+    /// raised in, looked up on the first call.
     pub fn trace(&self) -> &[FrameInfo] {
-        &self.inner.wasm_trace
+        self.inner.wasm_trace.get_or_init(|| {
+            let info = FRAME_INFO.read().unwrap();
+            self.inner
+                .deferred_pc
+                .and_then(|pc| info.lookup_frame_info(pc))
+                .into_iter()
+                .collect()
+        })
+    }
+
+    /// Returns whether the trace is yet to be looked up, which [`RuntimeError::trace`] does
+    /// when first called.
+    pub fn is_trace_deferred(&self) -> bool {
+        self.inner.wasm_trace.get().is_none()
+    }
+
+    /// Returns the frames elided from the trace by [`BacktracePolicy::max_frames`], if any.
+    pub fn elided_frames(&self) -> Option<ElidedFrames> {
+        self.inner.elided
+    }
+
+    pub(super) fn from_inner(inner: Arc<RuntimeErrorInner>) -> Self {
+        Self { inner }
     }
 
     /// Attempts to downcast the `RuntimeError` to a concrete type.
@@ -191,6 +356,15 @@ impl RuntimeError {
         }
     }
 
+    /// Returns the trap code without consuming the error, if it's a Trap
+    pub fn trap_code(&self) -> Option<TrapCode> {
+        if let RuntimeErrorSource::Trap(trap_code) = self.inner.source {
+            Some(trap_code)
+        } else {
+            None
+        }
+    }
+
     /// Returns true if the `RuntimeError` is the same as T
     pub fn is<T: Error + 'static>(&self) -> bool {
         match &self.inner.source {
@@ -204,7 +378,8 @@ impl fmt::Debug for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("RuntimeError")
             .field("source", &self.inner.source)
-            .field("wasm_trace", &self.inner.wasm_trace)
+            .field("wasm_trace", &self.trace())
+            .field("elided_frames", &self.inner.elided)
             .field("native_trace", &self.inner.native_trace)
             .finish()
     }
@@ -212,12 +387,16 @@ impl fmt::Debug for RuntimeError {
 
 impl fmt::Display for RuntimeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "RuntimeError: {}", self.message())?;
+        write!(f, "RuntimeError: {} [{}]", self.message(), self.code())?;
         let trace = self.trace();
         if trace.is_empty() {
             return Ok(());
         }
-        for frame in self.trace().iter() {
+        for (index, frame) in trace.iter().enumerate() {
+            if let Some(elided) = self.inner.elided.filter(|elided| elided.index == index) {
+                writeln!(f)?;
+                write!(f, "    ... {} frames elided ...", elided.count)?;
+            }
             let name = frame.module_name();
             let func_index = frame.func_index();
             writeln!(f)?;
@@ -236,6 +415,20 @@ impl fmt::Display for RuntimeError {
                 func_index,
                 frame.module_offset()
             )?;
+            if let Some(location) = frame.source_location() {
+                write!(f, " at {}", location)?;
+            }
+            if frame.repeats() > 1 {
+                write!(f, " \u{d7} {}", frame.repeats())?;
+            }
+        }
+        if let Some(elided) = self
+            .inner
+            .elided
+            .filter(|elided| elided.index == trace.len())
+        {
+            writeln!(f)?;
+            write!(f, "    ... {} frames elided ...", elided.count)?;
         }
         Ok(())
     }
@@ -251,6 +444,31 @@ impl std::error::Error for RuntimeError {
     }
 }
 
+impl Classify for RuntimeError {
+    fn failure_kind(&self) -> FailureKind {
+        match &self.inner.source {
+            RuntimeErrorSource::OOM => FailureKind::TransientResource,
+            // Cancelled calls were cut short, the same call may complete if made again.
+            RuntimeErrorSource::Trap(TrapCode::Cancelled) => FailureKind::TransientInterrupted,
+            // Traps are deterministic, and so are the host functions as far as we know.
+            RuntimeErrorSource::Generic(_)
+            | RuntimeErrorSource::User(_)
+            | RuntimeErrorSource::Trap(_) => FailureKind::Permanent,
+        }
+    }
+}
+
+impl HasErrorCode for RuntimeError {
+    fn code(&self) -> ErrorCode {
+        match &self.inner.source {
+            RuntimeErrorSource::Generic(_) => ErrorCode::RuntimeGeneric,
+            RuntimeErrorSource::OOM => ErrorCode::RuntimeOutOfMemory,
+            RuntimeErrorSource::User(_) => ErrorCode::RuntimeUser,
+            RuntimeErrorSource::Trap(trap_code) => trap_code.code(),
+        }
+    }
+}
+
 impl From<Trap> for RuntimeError {
     fn from(trap: Trap) -> Self {
         Self::from_trap(trap)
@@ -5,17 +5,20 @@
 //!
 //! # Example
 //! ```ignore
-//! use wasmer_vm::{FRAME_INFO};
-//! use wasmer_types::ModuleInfo;
+//! use wasmer_engine::register_frame_info;
 //!
-//! let module: ModuleInfo = ...;
-//! FRAME_INFO.register(module, compiled_functions);
+//! let registration = register_frame_info(name, function_names, import_counts, &functions, frame_infos, None);
 //! ```
+use std::cmp;
 use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, RwLock};
-use wasmer_compiler::{CompiledFunctionFrameInfo, SourceLoc, TrapInformation};
-use wasmer_types::entity::{EntityRef, PrimaryMap};
-use wasmer_types::{LocalFunctionIndex, ModuleInfo};
+use wasmer_compiler::{
+    CompiledFunctionFrameInfo, SourceLoc, SourceLocation, SourceMap, TrapInformation,
+};
+use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
+use wasmer_types::{FunctionIndex, ImportCounts, LocalFunctionIndex};
+use wasmer_vm::FunctionExtent;
 
 lazy_static::lazy_static! {
     /// This is a global cache of backtrace frame information for all active
@@ -26,6 +29,9 @@ lazy_static::lazy_static! {
     pub static ref FRAME_INFO: RwLock<GlobalFrameInfo> = Default::default();
 }
 
+/// The source of the serials of the registrations, never reused.
+static NEXT_SERIAL: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Default)]
 pub struct GlobalFrameInfo {
     /// An internal map that keeps track of backtrace frame information for
@@ -46,14 +52,21 @@ pub struct GlobalFrameInfoRegistration {
     /// The key that will be removed from the global `ranges` map when this is
     /// dropped.
     key: usize,
+    /// The serial of the registration, whose symbolicated frames are purged when this is
+    /// dropped.
+    serial: u64,
 }
 
 #[derive(Debug)]
 struct ModuleInfoFrameInfo {
     start: usize,
+    serial: u64,
     functions: BTreeMap<usize, FunctionInfo>,
-    module: Arc<ModuleInfo>,
+    module_name: String,
+    function_names: BTreeMap<FunctionIndex, String>,
+    import_counts: ImportCounts,
     frame_infos: PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>,
+    source_map: Option<Arc<SourceMap>>,
 }
 
 impl ModuleInfoFrameInfo {
@@ -126,16 +139,37 @@ impl GlobalFrameInfo {
             // start offset of the function.
             None => instr_map.start_srcloc,
         };
-        let func_index = module.module.func_index(func.local_index);
+        let func_index = module.import_counts.function_index(func.local_index);
+        let source_location = match &module.source_map {
+            Some(map) if !instr.is_default() => map.lookup(instr.bits()),
+            _ => None,
+        };
         Some(FrameInfo {
-            module_name: module.module.name(),
+            module_name: module.module_name.clone(),
             func_index: func_index.index() as u32,
-            function_name: module.module.function_names.get(&func_index).cloned(),
+            function_name: module.function_names.get(&func_index).cloned(),
             instr,
             func_start: instr_map.start_srcloc,
+            source_location,
+            repeats: 1,
         })
     }
 
+    /// Identifies the code at a program counter across loads of modules: returns the serial
+    /// of the registration of the module and the offset of `pc` in its code, if `pc` is known
+    /// to some registered module.
+    pub(super) fn locate(&self, pc: usize) -> Option<(u64, usize)> {
+        let module = self.module_info(pc)?;
+        Some((module.serial, pc - module.start))
+    }
+
+    /// Returns whether a program counter is in the code of a function of a registered module,
+    /// without symbolicating it.
+    pub(super) fn is_wasm_frame(&self, pc: usize) -> bool {
+        self.module_info(pc)
+            .map_or(false, |module| module.function_info(pc).is_some())
+    }
+
     /// Fetches trap information about a program counter in a backtrace.
     pub fn lookup_trap_info(&self, pc: usize) -> Option<&TrapInformation> {
         let module = self.module_info(pc)?;
@@ -158,11 +192,90 @@ impl GlobalFrameInfo {
     }
 }
 
+/// Register the frame information of the local functions of a loaded module.
+///
+/// `functions` are the extents of the loaded function bodies. The returned
+/// registration keeps the information available to [`RuntimeError`]s until it
+/// is dropped, which must happen before the code is unmapped.
+///
+/// `source_map`, if any, gives the source locations of the frames.
+///
+/// Returns `None` if the module has no function code, or if its code overlaps
+/// with the code of a module that is still registered.
+///
+/// [`RuntimeError`]: crate::RuntimeError
+pub fn register_frame_info(
+    module_name: String,
+    function_names: BTreeMap<FunctionIndex, String>,
+    import_counts: ImportCounts,
+    functions: &BoxedSlice<LocalFunctionIndex, FunctionExtent>,
+    frame_infos: PrimaryMap<LocalFunctionIndex, CompiledFunctionFrameInfo>,
+    source_map: Option<Arc<SourceMap>>,
+) -> Option<GlobalFrameInfoRegistration> {
+    let mut min = usize::MAX;
+    let mut max = 0;
+    let mut function_infos = BTreeMap::new();
+    for (local_index, extent) in functions.iter() {
+        if extent.length == 0 {
+            continue;
+        }
+        let start = *extent.address as usize;
+        // Ranges are keyed by their last byte, as lookups include the end.
+        let end = start + extent.length - 1;
+        min = cmp::min(min, start);
+        max = cmp::max(max, end);
+        let func = FunctionInfo { start, local_index };
+        if function_infos.insert(end, func).is_some() {
+            return None;
+        }
+    }
+    if function_infos.is_empty() {
+        return None;
+    }
+
+    let mut info = FRAME_INFO.write().unwrap();
+    if let Some((_, next)) = info.ranges.range(min..).next() {
+        if next.start <= max {
+            return None;
+        }
+    }
+    let serial = NEXT_SERIAL.fetch_add(1, Ordering::Relaxed);
+    info.ranges.insert(
+        max,
+        ModuleInfoFrameInfo {
+            start: min,
+            serial,
+            functions: function_infos,
+            module_name,
+            function_names,
+            import_counts,
+            frame_infos,
+            source_map,
+        },
+    );
+    Some(GlobalFrameInfoRegistration { key: max, serial })
+}
+
+impl GlobalFrameInfoRegistration {
+    /// Name the frames of the registered module `module_name` from now on.
+    pub fn set_module_name(&self, module_name: String) {
+        let mut info = FRAME_INFO.write().unwrap();
+        if let Some(module) = info.ranges.get_mut(&self.key) {
+            module.module_name = module_name;
+        }
+        drop(info);
+        // The frames cached so far have the previous name.
+        super::symbolication::purge(self.serial);
+    }
+}
+
 impl Drop for GlobalFrameInfoRegistration {
     fn drop(&mut self) {
         if let Ok(mut info) = FRAME_INFO.write() {
             info.ranges.remove(&self.key);
         }
+        // Frames of the module can no longer be symbolicated, so none is cached from now on.
+        super::symbolication::purge(self.serial);
     }
 }
 
@@ -181,6 +294,8 @@ pub struct FrameInfo {
     function_name: Option<String>,
     func_start: SourceLoc,
     instr: SourceLoc,
+    source_location: Option<SourceLocation>,
+    repeats: usize,
 }
 
 impl FrameInfo {
@@ -227,7 +342,11 @@ impl FrameInfo {
     /// counter was at.
     ///
     /// The offset here is the offset from the beginning of the original wasm
-    /// module to the instruction that this frame points to.
+    /// module to the instruction that this frame points to. For the innermost
+    /// frame of a trap, this is the operator that trapped (see [`TrapCode`]);
+    /// for the other frames, it is the call operator.
+    ///
+    /// [`TrapCode`]: wasmer_vm::TrapCode
     pub fn module_offset(&self) -> usize {
         self.instr.bits() as usize
     }
@@ -241,4 +360,24 @@ impl FrameInfo {
     pub fn func_offset(&self) -> usize {
         (self.instr.bits() - self.func_start.bits()) as usize
     }
+
+    /// Returns the location in the source of the module of the instruction
+    /// this frame points to.
+    ///
+    /// This is only available if the module embeds debug information and the
+    /// engine was configured to retain it, and returns `None` otherwise.
+    pub fn source_location(&self) -> Option<&SourceLocation> {
+        self.source_location.as_ref()
+    }
+
+    /// Returns how many times this frame repeats in a row in the backtrace, more than once
+    /// only for the frames of recursive calls summarized as one, see
+    /// [`BacktracePolicy::summarize_recursion`](crate::BacktracePolicy::summarize_recursion).
+    pub fn repeats(&self) -> usize {
+        self.repeats
+    }
+
+    pub(super) fn set_repeats(&mut self, repeats: usize) {
+        self.repeats = repeats;
+    }
 }
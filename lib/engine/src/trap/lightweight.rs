@@ -0,0 +1,81 @@
+use super::error::{RuntimeError, RuntimeErrorInner};
+use once_cell::sync::OnceCell;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use wasmer_vm::TrapCode;
+
+/// How many errors of lightweight traps can be alive at the same time without allocating.
+const PAYLOADS: usize = 64;
+
+/// The codes of the traps raised as lightweight traps, and the payloads of the
+/// [`RuntimeError`]s for them.
+///
+/// A lightweight trap skips capturing a backtrace, and its error reuses a preallocated payload
+/// no other error holds anymore, so that raising it does not allocate. Its trace is looked up
+/// when first asked for, and only has the frame the trap was raised in. When all the payloads
+/// are held, errors are allocated as usual.
+#[derive(Default)]
+pub struct LightweightTraps {
+    /// The codes, as bits `1 << code`.
+    codes: AtomicU32,
+    /// Allocated when codes are first set.
+    payloads: OnceCell<Box<[Mutex<Arc<RuntimeErrorInner>>]>>,
+    /// Where to start looking for a free payload, so that consecutive traps do not contend.
+    next: AtomicUsize,
+}
+
+impl LightweightTraps {
+    /// Raise the traps of the given codes as lightweight traps, and no others.
+    ///
+    /// Traps with [`TrapCode::Custom`] codes are never lightweight.
+    pub fn set_codes(&self, codes: &[TrapCode]) {
+        let mask = codes.iter().fold(0, |mask, &code| {
+            mask | 1u32.checked_shl(code.to_raw()).unwrap_or(0)
+        });
+        if mask != 0 {
+            self.payloads.get_or_init(|| {
+                (0..PAYLOADS)
+                    .map(|_| {
+                        let payload = RuntimeErrorInner::lightweight(TrapCode::GasExceeded, None);
+                        Mutex::new(Arc::new(payload))
+                    })
+                    .collect()
+            });
+        }
+        self.codes.store(mask, Ordering::SeqCst);
+    }
+
+    /// Return the codes, as bits `1 << code`, as taken by
+    /// [`with_lightweight_traps`](wasmer_vm::with_lightweight_traps).
+    pub fn mask(&self) -> u32 {
+        self.codes.load(Ordering::Relaxed)
+    }
+
+    /// Return the error of a lightweight trap, raised at `pc` in generated code if it is
+    /// known.
+    pub fn runtime_error(&self, trap_code: TrapCode, pc: Option<usize>) -> RuntimeError {
+        if let Some(payloads) = self.payloads.get() {
+            let start = self.next.fetch_add(1, Ordering::Relaxed);
+            for offset in 0..payloads.len() {
+                let payload = &payloads[(start + offset) % payloads.len()];
+                if let Ok(mut payload) = payload.try_lock() {
+                    // Only payloads no error holds anymore can be reused.
+                    if let Some(inner) = Arc::get_mut(&mut payload) {
+                        *inner = RuntimeErrorInner::lightweight(trap_code, pc);
+                        return RuntimeError::from_inner(Arc::clone(&payload));
+                    }
+                }
+            }
+        }
+        RuntimeError::from_inner(Arc::new(RuntimeErrorInner::lightweight(trap_code, pc)))
+    }
+}
+
+impl fmt::Debug for LightweightTraps {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LightweightTraps")
+            .field("codes", &format_args!("{:#x}", self.mask()))
+            .finish()
+    }
+}
@@ -1,4 +1,12 @@
+mod backtrace_policy;
 mod error;
 mod frame_info;
+mod lightweight;
+mod symbolication;
+pub use backtrace_policy::{
+    BacktraceCapture, BacktracePolicy, ElidedFrames, SharedBacktracePolicy,
+};
 pub use error::RuntimeError;
-pub use frame_info::{FrameInfo, GlobalFrameInfoRegistration};
+pub use frame_info::{register_frame_info, FrameInfo, GlobalFrameInfoRegistration};
+pub use lightweight::LightweightTraps;
+pub use symbolication::{SymbolicationCache, DEFAULT_SYMBOLICATION_CACHE_SIZE};
@@ -0,0 +1,173 @@
+//! A cache of the frames symbolicated for the backtraces of traps.
+
+use super::frame_info::{FrameInfo, GlobalFrameInfo};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex, Weak};
+use wasmer_vm::{Counter, MetricsSink};
+
+/// How many frames a [`SymbolicationCache`] keeps by default.
+pub const DEFAULT_SYMBOLICATION_CACHE_SIZE: usize = 1024;
+
+lazy_static::lazy_static! {
+    /// The state of the caches alive, purged of the frames of the modules unregistered.
+    static ref CACHES: Mutex<Vec<Weak<Mutex<CacheState>>>> = Default::default();
+}
+
+/// A cache of the frames of the backtraces of [`RuntimeError`]s, keyed by the code they are
+/// in and their offset in it, that evicts the least recently used frames.
+///
+/// Symbolicating a frame looks up the frame information of its module, then its function
+/// name and source location. A module trapping at the same instruction over and over gets
+/// its frames from the cache instead.
+///
+/// The cache holds no reference to the modules: the frames of a module are dropped when its
+/// frame information is unregistered, that is when its artifact is unloaded.
+///
+/// [`RuntimeError`]: crate::RuntimeError
+pub struct SymbolicationCache {
+    state: Arc<Mutex<CacheState>>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    capacity: usize,
+    /// The frames, along with when they were last used.
+    entries: HashMap<(u64, usize), (FrameInfo, u64)>,
+    /// The keys of the entries, by when they were last used.
+    recency: BTreeMap<u64, (u64, usize)>,
+    clock: u64,
+}
+
+impl CacheState {
+    fn get(&mut self, key: (u64, usize)) -> Option<FrameInfo> {
+        let (frame, used) = self.entries.get_mut(&key)?;
+        self.recency.remove(used);
+        self.clock += 1;
+        *used = self.clock;
+        self.recency.insert(self.clock, key);
+        Some(frame.clone())
+    }
+
+    fn insert(&mut self, key: (u64, usize), frame: FrameInfo) {
+        self.clock += 1;
+        if let Some((_, used)) = self.entries.insert(key, (frame, self.clock)) {
+            self.recency.remove(&used);
+        }
+        self.recency.insert(self.clock, key);
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.entries.len() > self.capacity {
+            let (used, key) = match self.recency.iter().next() {
+                Some((used, key)) => (*used, *key),
+                None => break,
+            };
+            self.recency.remove(&used);
+            self.entries.remove(&key);
+        }
+    }
+
+    fn purge(&mut self, serial: u64) {
+        self.entries.retain(|key, _| key.0 != serial);
+        self.recency.retain(|_, key| key.0 != serial);
+    }
+}
+
+impl SymbolicationCache {
+    /// Create a cache keeping up to `capacity` frames. A cache of no frames symbolicates
+    /// every frame.
+    pub fn new(capacity: usize) -> Self {
+        let state = Arc::new(Mutex::new(CacheState {
+            capacity,
+            ..CacheState::default()
+        }));
+        let mut caches = CACHES.lock().unwrap();
+        caches.retain(|cache| cache.strong_count() > 0);
+        caches.push(Arc::downgrade(&state));
+        Self { state }
+    }
+
+    /// Keep up to `capacity` frames from now on, evicting the least recently used ones.
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.capacity = capacity;
+        state.evict();
+    }
+
+    /// Returns how many frames the cache keeps at most.
+    pub fn capacity(&self) -> usize {
+        self.state.lock().unwrap().capacity
+    }
+
+    /// Returns how many frames the cache currently holds.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    /// Returns whether the cache currently holds no frames.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Symbolicate the frame at `pc` as [`GlobalFrameInfo::lookup_frame_info`] does, through
+    /// the cache, reporting whether it was found there to `sink`.
+    ///
+    /// The caller holds the frame information until the frame is cached, so that the frames
+    /// of a module being unregistered are not cached after being purged.
+    pub(super) fn lookup(
+        &self,
+        info: &GlobalFrameInfo,
+        pc: usize,
+        sink: Option<&dyn MetricsSink>,
+    ) -> Option<FrameInfo> {
+        let key = info.locate(pc)?;
+        if let Some(frame) = self.state.lock().unwrap().get(key) {
+            if let Some(sink) = sink {
+                sink.increment(Counter::SymbolicationCacheHits, 1);
+            }
+            return Some(frame);
+        }
+        // Other traps are not held up while this frame is symbolicated.
+        let frame = info.lookup_frame_info(pc)?;
+        let mut state = self.state.lock().unwrap();
+        if state.capacity == 0 {
+            return Some(frame);
+        }
+        if let Some(sink) = sink {
+            sink.increment(Counter::SymbolicationCacheMisses, 1);
+        }
+        state.insert(key, frame.clone());
+        Some(frame)
+    }
+}
+
+impl Default for SymbolicationCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_SYMBOLICATION_CACHE_SIZE)
+    }
+}
+
+impl fmt::Debug for SymbolicationCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("SymbolicationCache")
+            .field("capacity", &state.capacity)
+            .field("len", &state.entries.len())
+            .finish()
+    }
+}
+
+/// Drop the frames of the module registered with `serial` from every cache.
+pub(super) fn purge(serial: u64) {
+    let caches = match CACHES.lock() {
+        Ok(caches) => caches,
+        Err(_) => return,
+    };
+    for cache in caches.iter().filter_map(Weak::upgrade) {
+        if let Ok(mut state) = cache.lock() {
+            state.purge(serial);
+        }
+    }
+}
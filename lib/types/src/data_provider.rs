@@ -0,0 +1,48 @@
+//! Supplying the contents of externalized data segments.
+
+use crate::error_code::{ErrorCode, HasErrorCode};
+use crate::failure::{Classify, FailureKind};
+use std::borrow::Cow;
+use thiserror::Error;
+
+/// A source of data segment contents, keyed by their SHA-256 hash.
+///
+/// Modules compiled with data segment externalization only record the hash
+/// and length of their large data segments. The provider configured with
+/// [`InstanceConfig::with_data_provider`](crate::InstanceConfig::with_data_provider)
+/// supplies the bytes when such a module is instantiated, and they are
+/// verified against the hash before being copied into memory.
+pub trait DataProvider: Send + Sync {
+    /// Return the bytes whose SHA-256 hash is `hash`.
+    fn fetch(&self, hash: &[u8; 32]) -> Result<Cow<'_, [u8]>, DataError>;
+}
+
+/// An error returned by a [`DataProvider`].
+#[derive(Error, Debug)]
+pub enum DataError {
+    /// The provider has no data for the requested hash.
+    #[error("no data is available for this hash [{}]", ErrorCode::DataNotFound)]
+    NotFound,
+    /// The provider failed to retrieve the data.
+    #[error("{0} [{}]", ErrorCode::DataOther)]
+    Other(String),
+}
+
+impl HasErrorCode for DataError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotFound => ErrorCode::DataNotFound,
+            Self::Other(_) => ErrorCode::DataOther,
+        }
+    }
+}
+
+impl Classify for DataError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::NotFound => FailureKind::Permanent,
+            // Providers fetching the data remotely fail this way when the network does.
+            Self::Other(_) => FailureKind::TransientResource,
+        }
+    }
+}
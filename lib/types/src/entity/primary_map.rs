@@ -31,6 +31,7 @@ use crate::lib::std::vec::Vec;
 /// plain slice would make it easier to use incorrectly. To make a slice of a `PrimaryMap`, use
 /// `into_boxed_slice`.
 #[derive(Debug, Clone, Hash, PartialEq, Eq, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
 pub struct PrimaryMap<K, V>
 where
     K: EntityRef,
@@ -250,6 +251,11 @@ where
         self.elems.iter()
     }
 
+    /// A pointer to the values of this map, to check where they lie before reading them.
+    pub fn as_ptr(&self) -> *const rkyv::Archived<V> {
+        self.elems.as_ptr()
+    }
+
     /// Iterate over all the keys and values in this map.
     pub fn iter(&self) -> Iter<K, rkyv::Archived<V>> {
         Iter::new(self.elems.iter())
@@ -22,6 +22,7 @@ use rkyv::Archive;
 /// The map does not track if an entry for a key has been inserted or not. Instead it behaves as if
 /// all keys have a default entry from the beginning.
 #[derive(Debug, Clone, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
 pub struct SecondaryMap<K, V>
 where
     K: EntityRef,
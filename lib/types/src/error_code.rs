@@ -0,0 +1,391 @@
+//! Stable codes identifying errors independently of their messages.
+
+use crate::lib::std::fmt;
+
+macro_rules! error_codes {
+    ($($(#[doc = $doc:literal])* $name:ident = $code:literal,)*) => {
+        /// A stable code identifying the kind of an error, such as `W0102` for a module that
+        /// does not validate.
+        ///
+        /// Messages may be reworded from one release to the next, codes are not: a code keeps
+        /// its meaning forever and is never reused. Errors append their code to their message
+        /// between brackets, as in `Validation error: type mismatch [W0102]`, and [`as_u16`]
+        /// gives it as an integer for interfaces that cannot carry strings.
+        ///
+        /// Errors wrapping another error, such as a `LinkError` caused by a trap, have the
+        /// code of the error they wrap. Codes are grouped by the stage that fails:
+        ///
+        /// * `W01xx`: compilation;
+        /// * `W02xx`: serialization and deserialization of compiled modules;
+        /// * `W03xx`: linking and instantiation;
+        /// * `W04xx`: execution, with `W0400` to `W0417` matching the trap codes;
+        /// * `W05xx`: memories and globals;
+        /// * `W06xx`: the helpers of the API, such as exports, events, memory regions, JSON
+        ///   conversions, replay logs, guest types, module composition, the rebinding of
+        ///   instances, their snapshots and the argument policies of host functions;
+        /// * `W07xx`: bundles of modules instantiated together, and calls into instances.
+        ///
+        /// This enum is the table of all the codes.
+        ///
+        /// [`as_u16`]: ErrorCode::as_u16
+        #[non_exhaustive]
+        #[repr(u16)]
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+        pub enum ErrorCode {
+            $($(#[doc = $doc])* $name = $code,)*
+        }
+
+        impl ErrorCode {
+            /// Every code, in increasing order.
+            pub const ALL: &'static [Self] = &[$(Self::$name,)*];
+        }
+    };
+}
+
+error_codes! {
+    /// `CompileError::Codegen`: the compiler failed to generate code.
+    CompileCodegen = 101,
+    /// `CompileError::Validate`: the module did not pass validation.
+    CompileValidate = 102,
+    /// `CompileError::UnsupportedFeature`: the compiler does not support a feature.
+    CompileUnsupportedFeature = 103,
+    /// `CompileError::UnsupportedTarget`: the compiler cannot compile for the target.
+    CompileUnsupportedTarget = 104,
+    /// `CompileError::Resource`: the host ran out of resources while compiling.
+    CompileResource = 105,
+    /// `CompileError::EngineDowncast`: the engine is not of the expected type.
+    CompileEngineDowncast = 106,
+    /// `CompileError::Specialization`: the module cannot be specialized with the given
+    /// bindings.
+    CompileSpecialization = 107,
+    /// `CompileError::CodeMemoryExhausted`: there is no code memory left for the module, even
+    /// after releasing the code of dropped modules.
+    CompileCodeMemoryExhausted = 108,
+    /// `CompileError::QuotaExceeded`: compiling or loading the module would exceed the quota
+    /// of the tenant it is for.
+    CompileQuotaExceeded = 109,
+    /// `WasmError::InvalidWebAssembly`: the module could not be decoded.
+    WasmInvalid = 110,
+    /// `WasmError::Unsupported`: the module uses an unsupported feature.
+    WasmUnsupported = 111,
+    /// `WasmError::ImplLimitExceeded`: the module exceeds an implementation limit.
+    WasmImplLimitExceeded = 112,
+    /// `WasmError::Generic`: the module could not be translated.
+    WasmGeneric = 113,
+    /// `MiddlewareError`: a middleware rejected the module.
+    Middleware = 114,
+    /// `CompileError::FeatureDisabled`: the module uses a proposal the engine does not enable.
+    CompileFeatureDisabled = 115,
+    /// `CompileError::CodeTooLarge`: the body of a function is larger than the compiler
+    /// compiles.
+    CompileCodeTooLarge = 116,
+    /// `ParseCpuFeatureError::Missing`: the CPU feature name is not known.
+    UnknownCpuFeature = 120,
+    /// `TrapCodeCollision`: a custom trap code is registered with two names.
+    TrapCodeCollision = 121,
+    /// `SubprocessError::Spawn`: the compilation helper could not be started.
+    SubprocessSpawn = 130,
+    /// `SubprocessError::Io`: communicating with the compilation helper failed.
+    SubprocessIo = 131,
+    /// `SubprocessError::Timeout`: the compilation helper timed out.
+    SubprocessTimeout = 132,
+    /// `SubprocessError::Crashed`: the compilation helper exited without answering.
+    SubprocessCrashed = 133,
+    /// `SubprocessError::LimitExceeded`: the compilation helper hit a limit.
+    SubprocessLimitExceeded = 134,
+    /// `SubprocessError::MalformedResponse`: the compilation helper answered garbage.
+    SubprocessMalformedResponse = 135,
+    /// `IoCompileError::Io`: the module could not be read.
+    CompileIo = 150,
+    /// `ExecutableSerializeError`, `ArtifactEditError::Serialize` and
+    /// `SerializeError::Generic`: the compiled module could not be serialized.
+    Serialize = 201,
+    /// `SerializeError::BinaryUnavailable`: the module cannot be compiled again to be
+    /// serialized.
+    SerializeBinaryUnavailable = 202,
+    /// `DeserializeError::Io`: the serialized module could not be read.
+    DeserializeIo = 210,
+    /// `DeserializeError::Generic`: the serialized module could not be loaded.
+    DeserializeGeneric = 211,
+    /// `DeserializeError::Incompatible`: the serialized module is for another version or
+    /// engine.
+    DeserializeIncompatible = 212,
+    /// `DeserializeError::CorruptedBinary`: the serialized module is damaged.
+    DeserializeCorrupted = 213,
+    /// `DeserializeError::LimitExceeded`: the serialized module exceeds the limits of the
+    /// deserializer.
+    DeserializeLimitExceeded = 214,
+    /// `ArtifactEditError::UnknownFunction`: an entry point refers to a function the
+    /// executable does not have.
+    ArtifactEditUnknownFunction = 220,
+    /// `ArtifactEditError::SignatureMismatch`: an entry point does not have the signature
+    /// recorded for its function.
+    ArtifactEditSignatureMismatch = 221,
+    /// `ArtifactEditError::DuplicateExport`: two exports of the edited executable have the
+    /// same name.
+    ArtifactEditDuplicateExport = 222,
+    /// `ArtifactEditError::CodeChanged`: the edit would change the code of the executable.
+    ArtifactEditCodeChanged = 223,
+    /// `ImportError::IncompatibleType`: an import does not have the expected type.
+    ImportIncompatibleType = 301,
+    /// `ImportError::UnknownImport`: an import is missing.
+    ImportUnknown = 302,
+    /// `ImportError::Rejected`: the resolver rejected an import.
+    ImportRejected = 303,
+    /// `LinkError::Resource`: the host ran out of resources while linking.
+    LinkResource = 310,
+    /// `LinkError::Imports`: imports are missing or do not have the expected type.
+    LinkImports = 311,
+    /// `InstantiationError::CpuFeature`: the module needs a CPU feature the host lacks.
+    InstantiationCpuFeature = 320,
+    /// `InstantiationError::ReadOnlyUnsupported`: the module cannot run read-only.
+    InstantiationReadOnlyUnsupported = 321,
+    /// `InstantiationError::MemoryStyleMismatch`: the module was compiled for another memory
+    /// style than the tunables choose.
+    InstantiationMemoryStyleMismatch = 322,
+    /// `InstantiationError::SpecializationMismatch`: an imported global differs from the
+    /// value the module was specialized for.
+    InstantiationSpecializationMismatch = 323,
+    /// `InstantiationError::OverheadExceeded`: the instance would allocate more than
+    /// its configuration allows.
+    InstantiationOverheadExceeded = 324,
+    /// `InstantiationError::DifferentEngine`: the store has another engine than the module.
+    InstantiationDifferentEngine = 325,
+    /// `HostEnvInitError::Export`: a host environment could not find an export.
+    HostEnvExport = 330,
+    /// `HostEnvInitError::IncorrectGasMeteringConfig`: the gas metering configuration is
+    /// wrong.
+    HostEnvGasMetering = 331,
+    /// `HostEnvInitError::MissingMemory`: a host environment needs a memory export.
+    HostEnvMissingMemory = 332,
+    /// `ExternalDataErrorKind::MissingProvider`: no data provider is configured.
+    ExternalDataMissingProvider = 340,
+    /// `ExternalDataErrorKind::HashMismatch`: the provided data does not match its hash.
+    ExternalDataHashMismatch = 341,
+    /// `DataError::NotFound`: the data provider has no data for a hash.
+    DataNotFound = 342,
+    /// `DataError::Other`: the data provider failed.
+    DataOther = 343,
+    /// Trap `StackOverflow`.
+    TrapStackOverflow = 400,
+    /// Trap `HeapAccessOutOfBounds`.
+    TrapHeapAccessOutOfBounds = 401,
+    /// Trap `HeapMisaligned`.
+    TrapHeapMisaligned = 402,
+    /// Trap `TableAccessOutOfBounds`.
+    TrapTableAccessOutOfBounds = 403,
+    /// Trap `OutOfBounds`.
+    TrapOutOfBounds = 404,
+    /// Trap `IndirectCallToNull`.
+    TrapIndirectCallToNull = 405,
+    /// Trap `BadSignature`.
+    TrapBadSignature = 406,
+    /// Trap `IntegerOverflow`.
+    TrapIntegerOverflow = 407,
+    /// Trap `IntegerDivisionByZero`.
+    TrapIntegerDivisionByZero = 408,
+    /// Trap `BadConversionToInteger`.
+    TrapBadConversionToInteger = 409,
+    /// Trap `UnreachableCodeReached`.
+    TrapUnreachableCodeReached = 410,
+    /// Trap `UnalignedAtomic`.
+    TrapUnalignedAtomic = 411,
+    /// Trap `GasExceeded`.
+    TrapGasExceeded = 412,
+    /// Trap `ReadOnlyInstance`.
+    TrapReadOnlyInstance = 413,
+    /// Trap `ForeignFuncref`.
+    TrapForeignFuncref = 414,
+    /// Trap `PoisonedInstance`.
+    TrapPoisonedInstance = 415,
+    /// Trap `Custom`, whatever its code.
+    TrapCustom = 416,
+    /// Trap `Cancelled`.
+    TrapCancelled = 417,
+    /// A `RuntimeError` raised because the VM ran out of memory.
+    RuntimeOutOfMemory = 480,
+    /// A `RuntimeError` created with a message by the host.
+    RuntimeGeneric = 490,
+    /// A `RuntimeError` wrapping an error returned by a host function.
+    RuntimeUser = 491,
+    /// `MemoryError::Region`: the memory could not be mapped.
+    MemoryRegion = 501,
+    /// `MemoryError::CouldNotGrow`: the memory cannot grow that much.
+    MemoryCouldNotGrow = 502,
+    /// `MemoryError::InvalidMemory`: the memory is invalid.
+    MemoryInvalid = 503,
+    /// `MemoryError::MinimumMemoryTooLarge`: the minimum size is larger than allowed.
+    MemoryMinimumTooLarge = 504,
+    /// `MemoryError::MaximumMemoryTooLarge`: the maximum size is larger than allowed.
+    MemoryMaximumTooLarge = 505,
+    /// `MemoryError::GasExceeded`: growing the memory exceeds the gas limit.
+    MemoryGasExceeded = 506,
+    /// `MemoryError::Generic`: a user-defined memory error.
+    MemoryGeneric = 507,
+    /// `MemoryError::InvalidRange`: the range is not made of whole pages of the memory.
+    MemoryInvalidRange = 508,
+    /// `MemoryError::BasePinned`: the memory would move while its base is pinned.
+    MemoryBasePinned = 509,
+    /// `GlobalError::ImmutableGlobalCannotBeSet`: the global is immutable.
+    GlobalImmutable = 520,
+    /// `GlobalError::IncorrectType`: the global has another type.
+    GlobalIncorrectType = 521,
+    /// `partial_sum_map::Error::Overflow`: a partial sum overflowed.
+    PartialSumOverflow = 530,
+    /// `ExportError::IncompatibleType`: an export does not have the expected type.
+    ExportIncompatibleType = 601,
+    /// `ExportError::Missing`: an export is missing.
+    ExportMissing = 602,
+    /// `BindExportsError`: a set of exports could not be bound.
+    BindExports = 603,
+    /// `EventError::OutOfBounds`: an event buffer is out of the bounds of the memory.
+    EventOutOfBounds = 610,
+    /// `EventError::TopicTooLong`: an event topic is too long.
+    EventTopicTooLong = 611,
+    /// `EventError::TopicNotUtf8`: an event topic is not valid UTF-8.
+    EventTopicNotUtf8 = 612,
+    /// `EventError::TopicNotAllowed`: an event topic is not allowed.
+    EventTopicNotAllowed = 613,
+    /// `EventError::PayloadTooLarge`: an event payload is too large.
+    EventPayloadTooLarge = 614,
+    /// `EventError::TooManyEvents`: too many events were emitted.
+    EventTooMany = 615,
+    /// `RegionError::UnknownRegion`: no memory region has the name.
+    RegionUnknown = 620,
+    /// `RegionError::OutOfRegion`: the access is not within the memory region.
+    RegionOutOfRegion = 621,
+    /// `RegionError::PolicyViolation`: the memory region does not allow the access.
+    RegionPolicyViolation = 622,
+    /// `RegionError::OutOfBounds`: the access is out of the bounds of the memory.
+    RegionOutOfBounds = 623,
+    /// `RegionError::Unaligned`: the pointer is not aligned.
+    RegionUnaligned = 624,
+    /// `RegionError::InvalidRegions`: the memory regions are inconsistent.
+    RegionInvalid = 625,
+    /// `ConvertError::UnexpectedJson`: the JSON value is of the wrong kind.
+    ConvertUnexpectedJson = 630,
+    /// `ConvertError::OutOfRange`: the value does not fit the type.
+    ConvertOutOfRange = 631,
+    /// `ConvertError::InvalidEncoding`: the value is not validly encoded.
+    ConvertInvalidEncoding = 632,
+    /// `ConvertError::Unsupported`: values of the type cannot be converted.
+    ConvertUnsupported = 633,
+    /// `ConvertError::NotAnArray`: the parameters are not an array.
+    ConvertNotAnArray = 634,
+    /// `ConvertError::Arity`: the number of parameters is wrong.
+    ConvertArity = 635,
+    /// `ReplayLogError::UnsupportedVersion`: the replay log has a format this version cannot
+    /// read.
+    ReplayLogVersion = 640,
+    /// `ReplayLogError::Corrupted`: the replay log is truncated or damaged.
+    ReplayLogCorrupted = 641,
+    /// `ReplayDivergence`: a replayed execution made other import calls than the recorded one.
+    ReplayDivergence = 642,
+    /// `GuestTypeError::Misaligned`: a guest offset is not aligned for the type stored there.
+    GuestMisaligned = 650,
+    /// `GuestTypeError::OutOfBounds`: a guest value is out of the bounds of the memory.
+    GuestOutOfBounds = 651,
+    /// `GuestTypeError::ShortBuffer`: a buffer is shorter than the value it should hold.
+    GuestShortBuffer = 652,
+    /// `GuestTypeError::InvalidValue`: guest bytes do not encode a value of the type.
+    GuestInvalidValue = 653,
+    /// `GuestTypeError::InvalidUtf8`: a guest string is not valid UTF-8.
+    GuestInvalidUtf8 = 654,
+    /// `ComposeError::Options`: the composition options do not describe the modules.
+    ComposeOptions = 660,
+    /// `ComposeError::Unsupported`: a module uses a feature composition does not support.
+    ComposeUnsupported = 661,
+    /// `ComposeError::MissingExport`: a module imports what another module does not export.
+    ComposeMissingExport = 662,
+    /// `ComposeError::IncompatibleImport`: a module imports an export of another module with
+    /// another type.
+    ComposeIncompatibleImport = 663,
+    /// `ComposeError::CyclicImport`: imports re-exported from module to module lead back to
+    /// themselves.
+    ComposeCyclicImport = 664,
+    /// `ComposeError::ConflictingMemories`: the composed module would have several memories.
+    ComposeConflictingMemories = 665,
+    /// `RebindError::WasmMismatch`: the new module was not compiled from the same binary.
+    RebindWasmMismatch = 670,
+    /// `RebindError::CallInFlight`: a call into the instance is running.
+    RebindCallInFlight = 671,
+    /// `RebindError::LayoutMismatch`: the new code expects another layout of the instance.
+    RebindLayoutMismatch = 672,
+    /// `SnapshotError::Io`: reading or writing the snapshot failed.
+    SnapshotIo = 680,
+    /// `SnapshotError::CallInFlight`: a call into the instance is running.
+    SnapshotCallInFlight = 681,
+    /// `SnapshotError::Poisoned`: the instance was left poisoned by a failed restore.
+    SnapshotPoisoned = 682,
+    /// `SnapshotError::Unsupported`: the instance holds state snapshots cannot represent.
+    SnapshotUnsupported = 683,
+    /// `SnapshotError::Format`: the bytes are not a snapshot of a supported version.
+    SnapshotFormat = 684,
+    /// `SnapshotError::Mismatch`: the snapshot does not fit the instance.
+    SnapshotMismatch = 685,
+    /// `SnapshotError::CorruptPage`: a memory page does not match its checksum.
+    SnapshotCorruptPage = 686,
+    /// `SnapshotError::Corrupt`: the snapshot does not match its hash.
+    SnapshotCorrupt = 687,
+    /// `ArgPolicyError::NotHostFunction`: argument policies only apply to host functions
+    /// with an environment.
+    ArgPolicyNotHostFunction = 690,
+    /// `ArgPolicyError::Invalid`: the policy refers to arguments the function does not have,
+    /// or that are not integers.
+    ArgPolicyInvalid = 691,
+    /// `ArgPolicyError::Violation`: a call broke a rule of the argument policy of a host
+    /// function.
+    ArgPolicyViolation = 692,
+    /// `BundleError::Manifest`: the bundle manifest is inconsistent.
+    BundleManifest = 700,
+    /// `BundleError::Cycle`: the slots of a bundle import from each other in a cycle.
+    BundleCycle = 701,
+    /// `ResultArityError`: the results given to `Function::call_into` cannot hold the results
+    /// of the function.
+    CallResultArity = 710,
+    /// `OverrideError::NotOverridable`: the import was not declared overridable, or is not
+    /// an imported function.
+    OverrideNotOverridable = 720,
+    /// `OverrideError::SignatureMismatch`: the override does not have the signature of the
+    /// import.
+    OverrideSignatureMismatch = 721,
+    /// `GroupMoveError::CallInFlight`: calls into members of the instance group are running.
+    GroupCallInFlight = 730,
+    /// `GroupMoveError::Escaped`: handles to members of the instance group or to their
+    /// exports are held outside of it.
+    GroupEscaped = 731,
+    /// `ResetError::CallInFlight`: a call into the instance is running.
+    ResetCallInFlight = 740,
+    /// `ResetError::Memory`: a memory the instance defines could not be reset.
+    ResetMemory = 741,
+    /// `ChainedResolverError::Ambiguous`: several layers define an import that must be
+    /// unique.
+    ChainedResolverAmbiguous = 750,
+    /// `ChainedResolverError::NamespaceViolation`: a layer defines an import of a namespace
+    /// it may not provide.
+    ChainedResolverNamespaceViolation = 751,
+}
+
+impl ErrorCode {
+    /// Return the code as an integer, such as 102 for `W0102`.
+    pub fn as_u16(self) -> u16 {
+        self as u16
+    }
+
+    /// Return the code with the given integer value, if there is one.
+    pub fn from_u16(code: u16) -> Option<Self> {
+        Self::ALL.iter().copied().find(|c| c.as_u16() == code)
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "W{:04}", self.as_u16())
+    }
+}
+
+/// An error with a stable [`ErrorCode`].
+pub trait HasErrorCode {
+    /// Return the code of this error.
+    fn code(&self) -> ErrorCode;
+}
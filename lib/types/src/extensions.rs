@@ -0,0 +1,85 @@
+//! Typed values attached to an instance by the embedder.
+
+use std::any::{Any, TypeId};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A map holding at most one value of every type, keyed by the value's `TypeId`.
+///
+/// Values are stored behind an `Arc` so that cloning the map is cheap and so
+/// that a snapshot of the map stays valid while a newer version is installed.
+#[derive(Clone, Default)]
+pub struct Extensions {
+    map: BTreeMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    /// Create an empty map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Insert a value, returning the value of the same type it replaces, if any.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) -> Option<Arc<T>> {
+        self.map
+            .insert(TypeId::of::<T>(), Arc::new(value))
+            .map(|old| {
+                old.downcast::<T>()
+                    .expect("extension keyed by the wrong type")
+            })
+    }
+
+    /// Get the value of type `T`, if any.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.map.get(&TypeId::of::<T>())?.downcast_ref::<T>()
+    }
+
+    /// Remove the value of type `T`, returning it.
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<Arc<T>> {
+        self.map.remove(&TypeId::of::<T>()).map(|old| {
+            old.downcast::<T>()
+                .expect("extension keyed by the wrong type")
+        })
+    }
+
+    /// Return the number of values in the map.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Whether the map holds no value.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.map.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_get_replace() {
+        let mut extensions = Extensions::new();
+        assert!(extensions.get::<u32>().is_none());
+        assert!(extensions.insert(1u32).is_none());
+        assert!(extensions.insert(String::from("a")).is_none());
+        assert_eq!(extensions.get::<u32>(), Some(&1));
+        assert_eq!(extensions.insert(2u32).as_deref(), Some(&1));
+        assert_eq!(extensions.get::<u32>(), Some(&2));
+        assert_eq!(extensions.get::<String>().map(|s| &**s), Some("a"));
+        assert_eq!(
+            extensions.remove::<String>().as_deref().map(|s| &**s),
+            Some("a")
+        );
+        assert_eq!(extensions.len(), 1);
+    }
+}
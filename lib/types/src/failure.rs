@@ -0,0 +1,45 @@
+//! Classification of failures by whether retrying may succeed.
+
+/// Whether an operation that failed may succeed if it is retried.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FailureKind {
+    /// The operation fails again when retried with the same inputs: the module is invalid,
+    /// an import does not match, the WebAssembly code trapped, and so on.
+    Permanent,
+    /// The host ran out of a resource, such as memory or address space, that may be
+    /// available again later.
+    TransientResource,
+    /// The operation was interrupted, by a deadline for instance, before it could complete.
+    TransientInterrupted,
+    /// An input is damaged, such as a serialized module or data that does not match its
+    /// hash. Retrying with the same input fails again, but fetching it anew may succeed.
+    Corrupt,
+}
+
+impl FailureKind {
+    /// Return whether retrying the failed operation as is may succeed.
+    pub fn is_transient(self) -> bool {
+        match self {
+            Self::TransientResource | Self::TransientInterrupted => true,
+            Self::Permanent | Self::Corrupt => false,
+        }
+    }
+}
+
+/// An error whose [`FailureKind`] is known.
+pub trait Classify {
+    /// Return whether retrying the operation that failed with this error may succeed.
+    fn failure_kind(&self) -> FailureKind;
+}
+
+#[cfg(feature = "std")]
+impl Classify for std::io::Error {
+    fn failure_kind(&self) -> FailureKind {
+        use std::io::ErrorKind;
+        match self.kind() {
+            ErrorKind::Interrupted | ErrorKind::TimedOut => FailureKind::TransientInterrupted,
+            ErrorKind::OutOfMemory => FailureKind::TransientResource,
+            _ => FailureKind::Permanent,
+        }
+    }
+}
@@ -1,8 +1,11 @@
+use crate::lib::std::fmt;
+
 /// Controls which experimental features will be enabled.
 /// Features usually have a corresponding [WebAssembly proposal].
 ///
 /// [WebAssembly proposal]: https://github.com/WebAssembly/proposals
 #[derive(Clone, Debug, Eq, PartialEq, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
 pub struct Features {
     /// Threads proposal should be enabled
     pub threads: bool,
@@ -227,6 +230,51 @@ impl Default for Features {
     }
 }
 
+/// One of the proposals [`Features`] enables, that modules may use while it is disabled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WasmFeature {
+    /// The threads proposal, see [`Features::threads`].
+    Threads,
+    /// The reference types proposal, see [`Features::reference_types`].
+    ReferenceTypes,
+    /// The SIMD proposal, see [`Features::simd`].
+    Simd,
+    /// The bulk memory operations proposal, see [`Features::bulk_memory`].
+    BulkMemory,
+    /// The multi-value proposal, see [`Features::multi_value`].
+    MultiValue,
+}
+
+impl WasmFeature {
+    /// The name of the field of [`Features`] enabling the proposal, such as `bulk_memory`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Threads => "threads",
+            Self::ReferenceTypes => "reference_types",
+            Self::Simd => "simd",
+            Self::BulkMemory => "bulk_memory",
+            Self::MultiValue => "multi_value",
+        }
+    }
+
+    /// Whether `features` enables the proposal.
+    pub fn is_enabled(self, features: &Features) -> bool {
+        match self {
+            Self::Threads => features.threads,
+            Self::ReferenceTypes => features.reference_types,
+            Self::Simd => features.simd,
+            Self::BulkMemory => features.bulk_memory,
+            Self::MultiValue => features.multi_value,
+        }
+    }
+}
+
+impl fmt::Display for WasmFeature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
 #[cfg(test)]
 mod test_features {
     use super::*;
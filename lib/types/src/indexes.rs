@@ -16,6 +16,8 @@ use core::u32;
     rkyv::Deserialize,
     rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 #[repr(transparent)]
 pub struct LocalFunctionIndex(u32);
@@ -45,6 +47,8 @@ entity_impl!(LocalMemoryIndex);
     rkyv::Deserialize,
     rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 #[repr(transparent)]
 pub struct LocalGlobalIndex(u32);
@@ -64,6 +68,8 @@ entity_impl!(LocalGlobalIndex);
     rkyv::Deserialize,
     rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 #[repr(transparent)]
 pub struct FunctionIndex(u32);
@@ -83,6 +89,8 @@ entity_impl!(FunctionIndex);
     rkyv::Deserialize,
     rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 #[repr(transparent)]
 pub struct TableIndex(u32);
@@ -102,6 +110,8 @@ entity_impl!(TableIndex);
     rkyv::Deserialize,
     rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 #[repr(transparent)]
 pub struct GlobalIndex(u32);
@@ -121,6 +131,8 @@ entity_impl!(GlobalIndex);
     rkyv::Deserialize,
     rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 #[repr(transparent)]
 pub struct MemoryIndex(u32);
@@ -140,6 +152,8 @@ entity_impl!(MemoryIndex);
     rkyv::Deserialize,
     rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 #[repr(transparent)]
 pub struct SignatureIndex(u32);
@@ -159,6 +173,8 @@ entity_impl!(SignatureIndex);
     rkyv::Deserialize,
     rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 #[repr(transparent)]
 pub struct DataIndex(u32);
@@ -178,6 +194,8 @@ entity_impl!(DataIndex);
     rkyv::Deserialize,
     rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 #[repr(transparent)]
 pub struct ElemIndex(u32);
@@ -197,6 +215,8 @@ entity_impl!(ElemIndex);
     rkyv::Deserialize,
     rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 #[repr(transparent)]
 pub struct CustomSectionIndex(u32);
@@ -215,6 +235,8 @@ entity_impl!(CustomSectionIndex);
     rkyv::Deserialize,
     rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 #[repr(u8)]
 pub enum ExportIndex {
@@ -241,6 +263,8 @@ pub enum ExportIndex {
     rkyv::Deserialize,
     rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 #[repr(u8)]
 pub enum ImportIndex {
@@ -3,6 +3,7 @@ use crate::lib::std::boxed::Box;
 
 /// A WebAssembly table initializer.
 #[derive(Clone, Debug, Hash, PartialEq, Eq, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
 pub struct OwnedTableInitializer {
     /// The index of a table to initialize.
     pub table_index: TableIndex,
@@ -17,6 +18,7 @@ pub struct OwnedTableInitializer {
 /// A memory index and offset within that memory where a data initialization
 /// should be performed.
 #[derive(Clone, Debug, PartialEq, Eq, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
 pub struct DataInitializerLocation {
     /// The index of the memory to initialize.
     pub memory_index: MemoryIndex,
@@ -41,6 +43,7 @@ pub struct DataInitializer<'data> {
 /// As `DataInitializer` but owning the data rather than
 /// holding a reference to it
 #[derive(Debug, Clone, PartialEq, Eq, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
 pub struct OwnedDataInitializer {
     /// The location where the initialization is to be performed.
     pub location: DataInitializerLocation,
@@ -86,3 +89,21 @@ impl<'a> From<DataInitializer<'a>> for OwnedDataInitializer {
         }
     }
 }
+
+/// A data initializer whose contents are stored outside of the artifact and
+/// supplied by a [`DataProvider`](crate::DataProvider) at instantiation time.
+#[derive(Debug, Clone, PartialEq, Eq, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
+pub struct ExternalDataInitializer {
+    /// The location where the initialization is to be performed.
+    pub location: DataInitializerLocation,
+
+    /// The position of this segment among all the active data segments of the module.
+    pub segment_index: u32,
+
+    /// The SHA-256 hash of the initialization data.
+    pub hash: [u8; 32],
+
+    /// The length of the initialization data.
+    pub len: usize,
+}
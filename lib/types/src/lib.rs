@@ -55,7 +55,11 @@ pub mod lib {
 }
 
 mod archives;
+mod data_provider;
+mod error_code;
+mod extensions;
 mod extern_ref;
+mod failure;
 mod features;
 mod indexes;
 mod initializers;
@@ -63,32 +67,42 @@ mod memory_view;
 mod module;
 mod native;
 pub mod partial_sum_map;
+mod teardown;
+mod tenant;
 mod types;
 mod units;
 mod values;
 
 /// The entity module, with common helpers for Rust structures
 pub mod entity;
+pub use crate::data_provider::{DataError, DataProvider};
+pub use crate::error_code::{ErrorCode, HasErrorCode};
+pub use crate::extensions::Extensions;
 pub use crate::extern_ref::{ExternRef, VMExternRef};
-pub use crate::features::Features;
+pub use crate::failure::{Classify, FailureKind};
+pub use crate::features::{Features, WasmFeature};
 pub use crate::indexes::{
     CustomSectionIndex, DataIndex, ElemIndex, ExportIndex, FunctionIndex, GlobalIndex, ImportIndex,
     LocalFunctionIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex,
     SignatureIndex, TableIndex,
 };
 pub use crate::initializers::{
-    DataInitializer, DataInitializerLocation, OwnedDataInitializer, OwnedTableInitializer,
+    DataInitializer, DataInitializerLocation, ExternalDataInitializer, OwnedDataInitializer,
+    OwnedTableInitializer,
 };
 pub use crate::memory_view::{Atomically, MemoryView};
-pub use crate::module::{ImportCounts, ModuleInfo};
+pub use crate::module::{ExportsIterator, ImportCounts, ImportsIterator, ModuleInfo};
 pub use crate::native::{NativeWasmType, ValueType};
+pub use crate::teardown::DeferredTeardown;
+pub use crate::tenant::{Quota, QuotaDimension, TenantId};
 pub use crate::units::{
     Bytes, PageCountOutOfRange, Pages, WASM_MAX_PAGES, WASM_MIN_PAGES, WASM_PAGE_SIZE,
 };
 pub use crate::values::{Value, WasmValueType};
 pub use types::{
-    ExportType, ExternType, FastGasCounter, FunctionType, FunctionTypeRef, GlobalInit, GlobalType,
-    Import, InstanceConfig, MemoryType, Mutability, TableType, Type, V128,
+    DynamicGasCosts, ExportType, ExternType, FastGasCounter, FunctionType, FunctionTypeRef,
+    GlobalInit, GlobalType, Import, InstanceConfig, MemoryType, Mutability, PretouchMode,
+    PretouchPolicy, TableProvenancePolicy, TableType, Type, V128,
 };
 
 pub use archives::ArchivableIndexMap;
@@ -7,7 +7,7 @@
 use crate::entity::{EntityRef, PrimaryMap};
 use crate::ArchivableIndexMap;
 use crate::{
-    CustomSectionIndex, DataIndex, ElemIndex, ExportIndex, FunctionIndex, FunctionType,
+    CustomSectionIndex, DataIndex, ElemIndex, ExportIndex, ExternType, FunctionIndex, FunctionType,
     GlobalIndex, GlobalInit, GlobalType, ImportIndex, LocalFunctionIndex, LocalGlobalIndex,
     LocalMemoryIndex, LocalTableIndex, MemoryIndex, MemoryType, OwnedTableInitializer,
     SignatureIndex, TableIndex, TableType,
@@ -24,6 +24,7 @@ use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 use std::sync::Arc;
 
 #[derive(Debug, Clone, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
 pub struct ModuleId {
     id: usize,
 }
@@ -47,6 +48,8 @@ impl Default for ModuleId {
 #[derive(
     Debug, Copy, Clone, Default, PartialEq, Eq, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 pub struct ImportCounts {
     /// Number of imported functions in the module.
@@ -164,6 +167,9 @@ pub struct ModuleInfo {
     /// WebAssembly function names.
     pub function_names: HashMap<FunctionIndex, String>,
 
+    /// WebAssembly function parameter names, by function and parameter index.
+    pub param_names: HashMap<(FunctionIndex, u32), String>,
+
     /// WebAssembly function signatures.
     pub signatures: PrimaryMap<SignatureIndex, FunctionType>,
 
@@ -179,8 +185,10 @@ pub struct ModuleInfo {
     /// WebAssembly global variables (imported and local).
     pub globals: PrimaryMap<GlobalIndex, GlobalType>,
 
-    /// Custom sections in the module.
-    pub custom_sections: IndexMap<String, CustomSectionIndex>,
+    /// Custom sections in the module, by name, in the order they appear in the module.
+    ///
+    /// Several sections may have the same name.
+    pub custom_sections: Vec<(String, CustomSectionIndex)>,
 
     /// The data for each CustomSection in the module.
     pub custom_sections_data: PrimaryMap<CustomSectionIndex, Arc<[u8]>>,
@@ -191,6 +199,7 @@ pub struct ModuleInfo {
 
 /// Mirror version of ModuleInfo that can derive rkyv traits
 #[derive(rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
 pub struct ArchivableModuleInfo {
     pub name: Option<String>,
     pub imports: ArchivableIndexMap<(String, String, u32), ImportIndex>,
@@ -201,12 +210,13 @@ pub struct ArchivableModuleInfo {
     pub passive_data: BTreeMap<DataIndex, Arc<[u8]>>,
     pub global_initializers: PrimaryMap<LocalGlobalIndex, GlobalInit>,
     pub function_names: BTreeMap<FunctionIndex, String>,
+    pub param_names: BTreeMap<(FunctionIndex, u32), String>,
     pub signatures: PrimaryMap<SignatureIndex, FunctionType>,
     pub functions: PrimaryMap<FunctionIndex, SignatureIndex>,
     pub tables: PrimaryMap<TableIndex, TableType>,
     pub memories: PrimaryMap<MemoryIndex, MemoryType>,
     pub globals: PrimaryMap<GlobalIndex, GlobalType>,
-    pub custom_sections: ArchivableIndexMap<String, CustomSectionIndex>,
+    pub custom_sections: Vec<(String, CustomSectionIndex)>,
     pub custom_sections_data: PrimaryMap<CustomSectionIndex, Arc<[u8]>>,
     pub import_counts: ImportCounts,
 }
@@ -223,12 +233,13 @@ impl From<ModuleInfo> for ArchivableModuleInfo {
             passive_data: it.passive_data.into_iter().collect(),
             global_initializers: it.global_initializers,
             function_names: it.function_names.into_iter().collect(),
+            param_names: it.param_names.into_iter().collect(),
             signatures: it.signatures,
             functions: it.functions,
             tables: it.tables,
             memories: it.memories,
             globals: it.globals,
-            custom_sections: ArchivableIndexMap::from(it.custom_sections),
+            custom_sections: it.custom_sections,
             custom_sections_data: it.custom_sections_data,
             import_counts: it.import_counts,
         }
@@ -248,12 +259,13 @@ impl From<ArchivableModuleInfo> for ModuleInfo {
             passive_data: it.passive_data.into_iter().collect(),
             global_initializers: it.global_initializers,
             function_names: it.function_names.into_iter().collect(),
+            param_names: it.param_names.into_iter().collect(),
             signatures: it.signatures,
             functions: it.functions,
             tables: it.tables,
             memories: it.memories,
             globals: it.globals,
-            custom_sections: it.custom_sections.into(),
+            custom_sections: it.custom_sections,
             custom_sections_data: it.custom_sections_data,
             import_counts: it.import_counts,
         }
@@ -305,6 +317,7 @@ impl PartialEq for ModuleInfo {
             && self.passive_data == other.passive_data
             && self.global_initializers == other.global_initializers
             && self.function_names == other.function_names
+            && self.param_names == other.param_names
             && self.signatures == other.signatures
             && self.functions == other.functions
             && self.tables == other.tables
@@ -344,7 +357,51 @@ impl ModuleInfo {
             .collect::<Vec<FunctionType>>()
     }
 
-    /// Get the custom sections of the module given a `name`.
+    /// Get the names and types of the exports of the module, in declaration order.
+    pub fn exports(
+        &self,
+    ) -> ExportsIterator<impl ExactSizeIterator<Item = (&str, ExternType)> + '_> {
+        let iter = self
+            .exports
+            .iter()
+            .map(move |(name, index)| (&name[..], self.export_type(index)));
+        ExportsIterator { iter }
+    }
+
+    /// Get the namespaces, names and types of the imports of the module, in declaration order.
+    pub fn imports(
+        &self,
+    ) -> ImportsIterator<impl ExactSizeIterator<Item = (&str, &str, ExternType)> + '_> {
+        let iter = self
+            .imports
+            .iter()
+            .map(move |((namespace, name, _), index)| {
+                let ty = match index {
+                    ImportIndex::Function(i) => self.function_type(*i),
+                    ImportIndex::Table(i) => ExternType::Table(self.tables[*i]),
+                    ImportIndex::Memory(i) => ExternType::Memory(self.memories[*i]),
+                    ImportIndex::Global(i) => ExternType::Global(self.globals[*i]),
+                };
+                (&namespace[..], &name[..], ty)
+            });
+        ImportsIterator { iter }
+    }
+
+    fn export_type(&self, index: &ExportIndex) -> ExternType {
+        match index {
+            ExportIndex::Function(i) => self.function_type(*i),
+            ExportIndex::Table(i) => ExternType::Table(self.tables[*i]),
+            ExportIndex::Memory(i) => ExternType::Memory(self.memories[*i]),
+            ExportIndex::Global(i) => ExternType::Global(self.globals[*i]),
+        }
+    }
+
+    fn function_type(&self, index: FunctionIndex) -> ExternType {
+        ExternType::Function(self.signatures[self.functions[index]].clone())
+    }
+
+    /// Get the custom sections of the module given a `name`, in the order they appear in the
+    /// module.
     pub fn custom_sections<'a>(&'a self, name: &'a str) -> impl Iterator<Item = Arc<[u8]>> + 'a {
         self.custom_sections
             .iter()
@@ -442,3 +499,111 @@ impl fmt::Display for ModuleInfo {
         write!(f, "{}", self.name())
     }
 }
+
+/// An iterator over the names and types of the exports of a module, see
+/// [`ModuleInfo::exports`].
+pub struct ExportsIterator<I> {
+    iter: I,
+}
+
+impl<'a, I: Iterator<Item = (&'a str, ExternType)>> ExportsIterator<I> {
+    /// Get only the functions.
+    pub fn functions(self) -> impl Iterator<Item = (&'a str, FunctionType)> {
+        self.iter.filter_map(|(name, ty)| match ty {
+            ExternType::Function(ty) => Some((name, ty)),
+            _ => None,
+        })
+    }
+
+    /// Get only the memories.
+    pub fn memories(self) -> impl Iterator<Item = (&'a str, MemoryType)> {
+        self.iter.filter_map(|(name, ty)| match ty {
+            ExternType::Memory(ty) => Some((name, ty)),
+            _ => None,
+        })
+    }
+
+    /// Get only the tables.
+    pub fn tables(self) -> impl Iterator<Item = (&'a str, TableType)> {
+        self.iter.filter_map(|(name, ty)| match ty {
+            ExternType::Table(ty) => Some((name, ty)),
+            _ => None,
+        })
+    }
+
+    /// Get only the globals.
+    pub fn globals(self) -> impl Iterator<Item = (&'a str, GlobalType)> {
+        self.iter.filter_map(|(name, ty)| match ty {
+            ExternType::Global(ty) => Some((name, ty)),
+            _ => None,
+        })
+    }
+}
+
+impl<I: Iterator> Iterator for ExportsIterator<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<I: ExactSizeIterator> ExactSizeIterator for ExportsIterator<I> {}
+
+/// An iterator over the namespaces, names and types of the imports of a module, see
+/// [`ModuleInfo::imports`].
+pub struct ImportsIterator<I> {
+    iter: I,
+}
+
+impl<'a, I: Iterator<Item = (&'a str, &'a str, ExternType)>> ImportsIterator<I> {
+    /// Get only the functions.
+    pub fn functions(self) -> impl Iterator<Item = (&'a str, &'a str, FunctionType)> {
+        self.iter.filter_map(|(namespace, name, ty)| match ty {
+            ExternType::Function(ty) => Some((namespace, name, ty)),
+            _ => None,
+        })
+    }
+
+    /// Get only the memories.
+    pub fn memories(self) -> impl Iterator<Item = (&'a str, &'a str, MemoryType)> {
+        self.iter.filter_map(|(namespace, name, ty)| match ty {
+            ExternType::Memory(ty) => Some((namespace, name, ty)),
+            _ => None,
+        })
+    }
+
+    /// Get only the tables.
+    pub fn tables(self) -> impl Iterator<Item = (&'a str, &'a str, TableType)> {
+        self.iter.filter_map(|(namespace, name, ty)| match ty {
+            ExternType::Table(ty) => Some((namespace, name, ty)),
+            _ => None,
+        })
+    }
+
+    /// Get only the globals.
+    pub fn globals(self) -> impl Iterator<Item = (&'a str, &'a str, GlobalType)> {
+        self.iter.filter_map(|(namespace, name, ty)| match ty {
+            ExternType::Global(ty) => Some((namespace, name, ty)),
+            _ => None,
+        })
+    }
+}
+
+impl<I: Iterator> Iterator for ImportsIterator<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<I: ExactSizeIterator> ExactSizeIterator for ImportsIterator<I> {}
@@ -14,6 +14,8 @@
 //! The type of a given index can be quickly found with a binary search over the partial sum
 //! field.
 
+use crate::error_code::{ErrorCode, HasErrorCode};
+
 /// A Map from keys to values that is able to efficiently store repeating occurences of the value.
 ///
 /// This map can only be appended to.
@@ -95,7 +97,16 @@ impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(match self {
             Self::Overflow => "partial sum overflow",
-        })
+        })?;
+        write!(f, " [{}]", self.code())
+    }
+}
+
+impl HasErrorCode for Error {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Overflow => ErrorCode::PartialSumOverflow,
+        }
     }
 }
 
@@ -0,0 +1,14 @@
+//! Releasing the resources of dropped instances off the thread dropping them.
+
+/// Where the memories, tables and artifact of dropped instances go to be released later.
+///
+/// Instances configured with
+/// [`InstanceConfig::with_deferred_teardown`](crate::InstanceConfig::with_deferred_teardown)
+/// hand them over when dropped, after their host environments and host state are dropped.
+/// `TeardownQueue` in wasmer-vm releases them on a background thread.
+pub trait DeferredTeardown: Send + Sync {
+    /// Take `resources`, keeping `bytes` of memory mapped, to drop them later.
+    ///
+    /// They are given back if they can't be taken, and should then be dropped by the caller.
+    fn defer(&self, bytes: usize, resources: Box<dyn Send>) -> Result<(), Box<dyn Send>>;
+}
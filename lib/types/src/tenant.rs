@@ -0,0 +1,50 @@
+//! Attribution of the work of an engine to the tenants it is done for, and their quotas.
+
+use crate::lib::std::fmt;
+
+/// Identifies the tenant an engine compiles and loads modules for, so that the engine can
+/// account for the resources each tenant uses and enforce its [`Quota`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TenantId(pub u64);
+
+impl fmt::Display for TenantId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "tenant {}", self.0)
+    }
+}
+
+/// The most of each resource of an engine a tenant may use, `None` for no limit.
+///
+/// Quotas are checked before the work is admitted: a compilation or load that would exceed
+/// one fails with `CompileError::QuotaExceeded` without using any of the resource.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Quota {
+    /// The most bytes of code memory the artifacts loaded for the tenant may use.
+    pub max_code_bytes: Option<usize>,
+    /// The most artifacts loaded for the tenant that may be alive at once.
+    pub max_artifacts: Option<usize>,
+    /// The most milliseconds spent compiling for the tenant over the last minute, past which
+    /// its compilations are refused until older ones leave the window.
+    pub max_compile_ms_per_minute: Option<u64>,
+}
+
+/// A resource limited by a [`Quota`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum QuotaDimension {
+    /// See [`Quota::max_code_bytes`].
+    CodeBytes,
+    /// See [`Quota::max_artifacts`].
+    Artifacts,
+    /// See [`Quota::max_compile_ms_per_minute`].
+    CompileMsPerMinute,
+}
+
+impl fmt::Display for QuotaDimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::CodeBytes => "code bytes",
+            Self::Artifacts => "artifacts",
+            Self::CompileMsPerMinute => "compile milliseconds per minute",
+        })
+    }
+}
@@ -1,8 +1,12 @@
+use crate::data_provider::DataProvider;
+use crate::extensions::Extensions;
 use crate::indexes::{FunctionIndex, GlobalIndex};
 use crate::lib::std::fmt;
 use crate::lib::std::format;
 use crate::lib::std::string::{String, ToString};
 use crate::lib::std::vec::Vec;
+use crate::teardown::DeferredTeardown;
+use crate::tenant::TenantId;
 use crate::units::Pages;
 use crate::values::{Value, WasmValueType};
 use std::cell::UnsafeCell;
@@ -17,6 +21,9 @@ use std::sync::Arc;
 #[derive(
     Copy, Debug, Clone, Eq, PartialEq, Hash, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
+#[repr(u8)]
 #[archive(as = "Self")]
 pub enum Type {
     /// Signed 32 bit integer.
@@ -60,6 +67,8 @@ impl fmt::Display for Type {
 #[derive(
     Copy, Clone, Debug, Eq, PartialEq, Hash, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 /// The WebAssembly V128 type
 pub struct V128(pub(crate) [u8; 16]);
@@ -159,6 +168,7 @@ impl ExternType {
 ///
 /// WebAssembly functions can have 0 or more parameters and results.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
 pub struct FunctionType {
     /// The parameters of the function
     params: Arc<[Type]>,
@@ -285,6 +295,9 @@ impl<'a> From<&'a ArchivedFunctionType> for FunctionTypeRef<'a> {
 #[derive(
     Debug, Clone, Copy, PartialEq, Eq, Hash, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
+#[repr(u8)]
 #[archive(as = "Self")]
 pub enum Mutability {
     /// The global is constant and its value does not change
@@ -307,6 +320,8 @@ impl Mutability {
 #[derive(
     Debug, Clone, Copy, PartialEq, Eq, Hash, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 pub struct GlobalType {
     /// The type of the value stored in the global.
@@ -350,6 +365,9 @@ impl fmt::Display for GlobalType {
 
 /// Globals are initialized via the `const` operators or by referring to another import.
 #[derive(Debug, Clone, Copy, PartialEq, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
+#[repr(u8)]
 #[archive(as = "Self")]
 pub enum GlobalInit {
     /// An `i32.const`.
@@ -408,6 +426,7 @@ impl GlobalInit {
 #[derive(
     Debug, Clone, Copy, PartialEq, Eq, Hash, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive,
 )]
+#[archive(check_bytes)]
 pub struct TableType {
     /// The type of data stored in elements of the table.
     pub ty: Type,
@@ -448,6 +467,7 @@ impl fmt::Display for TableType {
 #[derive(
     Debug, Clone, Copy, PartialEq, Eq, Hash, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive,
 )]
+#[archive(check_bytes)]
 pub struct MemoryType {
     /// The minimum number of pages in the memory.
     pub minimum: Pages,
@@ -616,6 +636,91 @@ impl fmt::Display for FastGasCounter {
     }
 }
 
+/// Gas charged by the runtime for the operations whose work scales with their operands, on top
+/// of what the compiled code charges through its gas counter.
+///
+/// Each operation costs its base plus its rate for every page, byte or element it covers, and
+/// is charged before it does anything. All costs are zero by default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DynamicGasCosts {
+    /// Base cost of `memory.grow`.
+    pub memory_grow_base: u64,
+    /// Cost of every page requested by `memory.grow`.
+    pub memory_grow_per_page: u64,
+    /// Base cost of `memory.copy`, `memory.fill` and `memory.init`.
+    pub bulk_memory_base: u64,
+    /// Cost of every byte written by `memory.copy`, `memory.fill` and `memory.init`.
+    pub bulk_memory_per_byte: u64,
+    /// Base cost of `table.grow`.
+    pub table_grow_base: u64,
+    /// Cost of every element requested by `table.grow`.
+    pub table_grow_per_element: u64,
+    /// Base cost of `table.copy`, `table.fill` and `table.init`.
+    pub bulk_table_base: u64,
+    /// Cost of every element written by `table.copy`, `table.fill` and `table.init`.
+    pub bulk_table_per_element: u64,
+}
+
+/// What an instance does when a function reference it neither defines nor imports is
+/// written to one of its tables, by `table.set`, `table.grow`, `table.fill` or `table.copy`.
+///
+/// Such a reference lets the instance call into another instance or into a host function it
+/// was never given, which is usually a capability leaking through a table. Checking costs a
+/// lookup on every table write, but nothing on `call_indirect`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableProvenancePolicy {
+    /// Install foreign references without checking them.
+    Allow,
+    /// Install foreign references, and count them in the `ForeignFuncrefsInstalled` metric
+    /// of the engine.
+    Log,
+    /// Trap with `ForeignFuncref` instead of installing a foreign reference, leaving the
+    /// table untouched.
+    Reject,
+}
+
+impl Default for TableProvenancePolicy {
+    fn default() -> Self {
+        Self::Allow
+    }
+}
+
+/// Which pages of its memories an instance touches once instantiated, before running its
+/// start function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PretouchMode {
+    /// Touch nothing, leaving the pages to be faulted in by the first accesses to them.
+    None,
+    /// Read the pages covered by the active data segments, which writing the segments usually
+    /// faulted in already, unless the memory is a read-only image holding them.
+    WrittenPagesOnly,
+    /// Touch every page of the initial size of the memories the instance defines, by writing
+    /// back one byte of each, or only reading it for read-only memories.
+    AllMinimumPages,
+}
+
+impl Default for PretouchMode {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+/// What an instance touches once instantiated, so that its first calls do not pay for faulting
+/// in the pages of its memories and tables.
+///
+/// The pages touched are counted in the `PagesPretouched` metric of the engine and the time
+/// spent in its `Pretouch` timer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PretouchPolicy {
+    /// Which pages of the memories to touch.
+    pub memory: PretouchMode,
+    /// Whether to touch every page of the elements of the tables the instance defines.
+    ///
+    /// The element segments are always written to the tables at instantiation, so this only
+    /// matters for the elements they do not cover.
+    pub tables: bool,
+}
+
 /// External configuration of execution environment for Instance.
 #[derive(Clone)]
 pub struct InstanceConfig {
@@ -624,6 +729,34 @@ pub struct InstanceConfig {
     default_gas_counter: Option<Rc<UnsafeCell<FastGasCounter>>>,
     /// Stack limit, in 8-byte slots.
     pub stack_limit: i32,
+    /// Typed values made available to host functions called from the instance.
+    pub extensions: Extensions,
+    /// Source of the contents of externalized data segments.
+    pub data_provider: Option<Arc<dyn DataProvider>>,
+    /// Import (module and field names) through which the instance is provided its call
+    /// sequence number as an immutable `i64` global.
+    pub call_sequence_global: Option<(String, String)>,
+    /// Gas charged for growing memories and tables and for bulk memory and table operations.
+    pub dynamic_gas_costs: DynamicGasCosts,
+    /// Queue releasing the memories, tables and artifact of the instance once it is dropped.
+    pub teardown_queue: Option<Arc<dyn DeferredTeardown>>,
+    /// What to do when a function reference from elsewhere is written to a table.
+    pub table_provenance_policy: TableProvenancePolicy,
+    /// Whether the imported globals a module was specialized for are provided with the values
+    /// they are bound to, rather than by the resolver.
+    pub provide_bound_globals: bool,
+    /// The most bytes that instantiation may allocate for the `VMContext` of the instance and
+    /// what goes with it.
+    pub max_instance_overhead_bytes: Option<usize>,
+    /// What the instance touches once instantiated.
+    pub pretouch: PretouchPolicy,
+    /// The tenant the instance runs for, which the memory arbiter of the engine is told.
+    pub tenant: Option<TenantId>,
+    /// Whether the memory arbiter of the engine may grant fewer pages than `memory.grow`
+    /// requests.
+    pub partial_memory_grants: bool,
+    /// Imported functions (module and field names) that single calls may override.
+    pub overridable_imports: Vec<(String, String)>,
 }
 
 // Default stack limit, in 8-byte stack slots.
@@ -641,6 +774,18 @@ impl InstanceConfig {
             gas_counter: result.get(),
             default_gas_counter: Some(result),
             stack_limit: DEFAULT_STACK_LIMIT,
+            extensions: Extensions::new(),
+            data_provider: None,
+            call_sequence_global: None,
+            dynamic_gas_costs: DynamicGasCosts::default(),
+            teardown_queue: None,
+            table_provenance_policy: TableProvenancePolicy::Allow,
+            provide_bound_globals: false,
+            max_instance_overhead_bytes: None,
+            pretouch: PretouchPolicy::default(),
+            tenant: None,
+            partial_memory_grants: false,
+            overridable_imports: Vec::new(),
         }
     }
 
@@ -653,11 +798,153 @@ impl InstanceConfig {
         self
     }
 
+    /// Create instance configuration with a gas counter of its own, in place of any external
+    /// counter, so that the instance traps with `GasExceeded` once it burnt `limit` gas.
+    ///
+    /// Gas is burnt by the code compiled with metering, see `CompilerConfig::set_metering`,
+    /// and by the operations charged with [`DynamicGasCosts`]. What is left can be read and
+    /// refilled through the instance once it runs.
+    pub fn with_gas_limit(mut self, limit: u64) -> Self {
+        let counter = Rc::new(UnsafeCell::new(FastGasCounter::new(limit, 0)));
+        self.gas_counter = counter.get();
+        self.default_gas_counter = Some(counter);
+        self
+    }
+
     /// Create instance configuration with given stack limit.
     pub unsafe fn with_stack_limit(mut self, stack_limit: i32) -> Self {
         self.stack_limit = stack_limit;
         self
     }
+
+    /// Create instance configuration limiting the stack of the WebAssembly code to `bytes`,
+    /// rounded down to 8-byte slots, over which calls trap with `StackOverflow`.
+    ///
+    /// The compiled code charges each call with the locals and operand stack of the function
+    /// called, plus a fixed overhead covering its frame on every platform, so the limit is
+    /// hit at the same depth whatever the native stack of the thread running the instance.
+    ///
+    /// # Safety
+    ///
+    /// The native stack must have room for `bytes` on top of what the host uses, or it may
+    /// overflow before the limit is hit.
+    pub unsafe fn with_max_wasm_stack(self, bytes: usize) -> Self {
+        self.with_stack_limit((bytes / 8).min(i32::MAX as usize) as i32)
+    }
+
+    /// Create instance configuration with the given extension value, replacing any previous
+    /// value of the same type.
+    pub fn with_extension<T: Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.extensions.insert(value);
+        self
+    }
+
+    /// Create instance configuration with the given provider for externalized data segments.
+    pub fn with_data_provider(mut self, data_provider: Arc<dyn DataProvider>) -> Self {
+        self.data_provider = Some(data_provider);
+        self
+    }
+
+    /// Create instance configuration providing the import `module`.`field` with an immutable
+    /// `i64` global holding the call sequence number of the instance, in place of whatever the
+    /// resolver would provide.
+    ///
+    /// The call sequence number is incremented by the runtime each time the host calls into the
+    /// instance, before any WebAssembly code runs, so that every call observes a distinct value
+    /// that stays the same for the whole call.
+    pub fn with_call_sequence_global(mut self, module: &str, field: &str) -> Self {
+        self.call_sequence_global = Some((module.to_string(), field.to_string()));
+        self
+    }
+
+    /// Create instance configuration charging the given costs to the gas counter for the
+    /// operations whose work scales with their operands.
+    ///
+    /// An operation that would exceed the gas limit traps with `GasExceeded` before doing
+    /// anything, leaving the memories and tables untouched. This includes `memory.grow` and
+    /// `table.grow`, which trap rather than return -1, so that running out of gas cannot be
+    /// mistaken for a memory or table at its maximum size.
+    pub fn with_dynamic_gas_costs(mut self, costs: DynamicGasCosts) -> Self {
+        self.dynamic_gas_costs = costs;
+        self
+    }
+
+    /// Create instance configuration handing the memories, tables and artifact of the
+    /// instance to `queue` when it is dropped, so that unmapping them does not slow the thread
+    /// dropping it down.
+    ///
+    /// Host environments and host state are still dropped by that thread, before the rest is
+    /// queued.
+    pub fn with_deferred_teardown(mut self, queue: Arc<dyn DeferredTeardown>) -> Self {
+        self.teardown_queue = Some(queue);
+        self
+    }
+
+    /// Create instance configuration applying `policy` to the function references written to
+    /// the tables of the instance that it neither defines nor imports.
+    pub fn with_table_provenance_policy(mut self, policy: TableProvenancePolicy) -> Self {
+        self.table_provenance_policy = policy;
+        self
+    }
+
+    /// Create instance configuration providing the imported globals that the module was
+    /// specialized for with the values they are bound to, in place of whatever the resolver
+    /// would provide.
+    ///
+    /// Otherwise, the resolver has to provide these globals, and instantiation fails if their
+    /// values differ from the bound ones.
+    pub fn with_bound_globals_provided(mut self, provided: bool) -> Self {
+        self.provide_bound_globals = provided;
+        self
+    }
+
+    /// Create instance configuration failing instantiation before it allocates anything if the
+    /// buffer holding the `VMContext` and the array of function references of the instance
+    /// would take more than `bytes`.
+    ///
+    /// These only depend on the module, whose `instance_layout` reports them. The memories,
+    /// tables and globals themselves are not counted.
+    pub fn with_max_instance_overhead_bytes(mut self, bytes: usize) -> Self {
+        self.max_instance_overhead_bytes = Some(bytes);
+        self
+    }
+
+    /// Create instance configuration touching the pages of the memories and tables of the
+    /// instance selected by `policy` at instantiation, so that its first calls do not pay for
+    /// faulting them in.
+    pub fn with_pretouch(mut self, policy: PretouchPolicy) -> Self {
+        self.pretouch = policy;
+        self
+    }
+
+    /// Create instance configuration attributing the growth of the memories of the instance
+    /// to `tenant`, for the memory arbiter of the engine to enforce its budget.
+    pub fn with_tenant(mut self, tenant: TenantId) -> Self {
+        self.tenant = Some(tenant);
+        self
+    }
+
+    /// Create instance configuration letting the memory arbiter of the engine grow the
+    /// memories of the instance by fewer pages than requested, rather than not at all, when
+    /// more than one page is requested.
+    ///
+    /// `memory.grow` then returns the previous size as usual, and the guest has to check
+    /// `memory.size` to know how many pages it got.
+    pub fn with_partial_memory_grants(mut self, enabled: bool) -> Self {
+        self.partial_memory_grants = enabled;
+        self
+    }
+
+    /// Create instance configuration letting single calls into the instance override the
+    /// imported function `module`.`field`, see `Instance::guarded_call_with_overrides`.
+    ///
+    /// Calls to an overridable import go through a dynamic host function looking up the
+    /// override of the running call, rather than straight to the imported function.
+    pub fn with_overridable_import(mut self, module: &str, field: &str) -> Self {
+        self.overridable_imports
+            .push((module.to_string(), field.to_string()));
+        self
+    }
 }
 
 #[cfg(test)]
@@ -29,6 +29,8 @@ pub const WASM_MIN_PAGES: u32 = 0x100;
     rkyv::Deserialize,
     rkyv::Archive,
 )]
+#[derive(rkyv::CheckBytes)]
+#[check_bytes(crate = "rkyv::bytecheck")]
 #[archive(as = "Self")]
 #[repr(transparent)]
 pub struct Pages(pub u32);
@@ -0,0 +1,381 @@
+//! Arbitration of the growth of linear memories across the instances sharing a host memory
+//! budget.
+//!
+//! The memories an instance defines are wrapped in an [`ArbitratedMemory`] when its engine
+//! has a [`MemoryArbiter`], so that every growth, whether by `memory.grow` or by the host,
+//! asks the arbiter first. The bytes the arbiter grants are given back to it once the memory
+//! is dropped, or right away if the growth fails after all.
+
+use crate::memory::{BaseStability, Memory, MemoryError, MemoryStyle};
+use crate::provenance::InstanceId;
+use crate::vmcontext::VMMemoryDefinition;
+use std::collections::HashMap;
+use std::fmt;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use wasmer_types::{MemoryType, Pages, TenantId, WASM_PAGE_SIZE};
+
+/// A request to grow a linear memory, see [`MemoryArbiter::request`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GrowRequest {
+    /// The instance defining the memory.
+    pub instance: InstanceId,
+    /// The tenant the instance runs for, if any.
+    pub tenant: Option<TenantId>,
+    /// The size of the memory before growing, in bytes.
+    pub current_bytes: u64,
+    /// The number of pages to grow the memory by.
+    pub delta: Pages,
+    /// Whether [`GrowDecision::GrantPartial`] may be answered: the instance accepts partial
+    /// grants and more than one page is requested.
+    pub partial_allowed: bool,
+}
+
+impl GrowRequest {
+    /// The number of bytes to grow the memory by.
+    pub fn delta_bytes(&self) -> u64 {
+        u64::from(self.delta.0) * WASM_PAGE_SIZE as u64
+    }
+}
+
+/// What a [`MemoryArbiter`] decided about a [`GrowRequest`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GrowDecision {
+    /// Grow the memory by the pages requested.
+    Grant,
+    /// Do not grow the memory: `memory.grow` returns -1, and growing it from the host fails
+    /// with `MemoryError::CouldNotGrow`.
+    Deny,
+    /// Grow the memory by these pages only, fewer than requested but at least one. Only
+    /// answered when [`GrowRequest::partial_allowed`] is set, and treated as
+    /// [`GrowDecision::Deny`] otherwise.
+    GrantPartial(Pages),
+}
+
+/// Bytes of a memory given back to a [`MemoryArbiter`], see [`MemoryArbiter::release`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryRelease {
+    /// The instance that defined the memory.
+    pub instance: InstanceId,
+    /// The tenant the instance ran for, if any.
+    pub tenant: Option<TenantId>,
+    /// The number of bytes given back.
+    pub bytes: u64,
+}
+
+/// Decides whether the linear memories of the instances of an engine may grow, see
+/// `UniversalEngine::set_memory_arbiter`.
+///
+/// Without an arbiter, every growth within the maximum of the memory is attempted.
+///
+/// The arbiter is called on the thread growing the memory, possibly while other instances
+/// grow theirs: it must not block on them.
+pub trait MemoryArbiter: Send + Sync {
+    /// Decide whether the memory of `request` may grow.
+    ///
+    /// The bytes granted are held by the memory until they are [released](Self::release).
+    fn request(&self, request: &GrowRequest) -> GrowDecision;
+
+    /// Take back bytes granted earlier: all those a memory was granted when it is dropped, or
+    /// those of a grant that went unused, because growing the memory failed after all or the
+    /// partial grant was not allowed.
+    fn release(&self, release: &MemoryRelease);
+
+    /// The bytes granted to the memories of the instances of `tenant` and not released yet, if
+    /// the arbiter keeps track of them.
+    fn tenant_bytes(&self, _tenant: TenantId) -> Option<u64> {
+        None
+    }
+}
+
+/// The memory of an instance, growing only as its engine's [`MemoryArbiter`] allows.
+pub(crate) struct ArbitratedMemory {
+    memory: Arc<dyn Memory>,
+    arbiter: Arc<dyn MemoryArbiter>,
+    instance: InstanceId,
+    tenant: Option<TenantId>,
+    partial_grants: bool,
+    /// The bytes granted to the memory, to release once it is dropped.
+    granted: AtomicU64,
+}
+
+impl ArbitratedMemory {
+    pub(crate) fn new(
+        memory: Arc<dyn Memory>,
+        arbiter: Arc<dyn MemoryArbiter>,
+        instance: InstanceId,
+        tenant: Option<TenantId>,
+        partial_grants: bool,
+    ) -> Self {
+        Self {
+            memory,
+            arbiter,
+            instance,
+            tenant,
+            partial_grants,
+            granted: AtomicU64::new(0),
+        }
+    }
+
+    fn release(&self, pages: Pages) {
+        if pages.0 > 0 {
+            self.arbiter.release(&MemoryRelease {
+                instance: self.instance,
+                tenant: self.tenant,
+                bytes: u64::from(pages.0) * WASM_PAGE_SIZE as u64,
+            });
+        }
+    }
+}
+
+impl fmt::Debug for ArbitratedMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArbitratedMemory")
+            .field("memory", &self.memory)
+            .field("instance", &self.instance)
+            .field("tenant", &self.tenant)
+            .field("granted", &self.granted)
+            .finish()
+    }
+}
+
+impl Memory for ArbitratedMemory {
+    fn ty(&self) -> MemoryType {
+        self.memory.ty()
+    }
+
+    fn style(&self) -> &MemoryStyle {
+        self.memory.style()
+    }
+
+    fn size(&self) -> Pages {
+        self.memory.size()
+    }
+
+    fn grow(&self, delta: Pages) -> Result<Pages, MemoryError> {
+        if delta.0 == 0 {
+            return self.memory.grow(delta);
+        }
+        let current = self.memory.size();
+        let request = GrowRequest {
+            instance: self.instance,
+            tenant: self.tenant,
+            current_bytes: current.bytes().0 as u64,
+            delta,
+            partial_allowed: self.partial_grants && delta.0 > 1,
+        };
+        let could_not_grow = MemoryError::CouldNotGrow {
+            current,
+            attempted_delta: delta,
+        };
+        let granted = match self.arbiter.request(&request) {
+            GrowDecision::Grant => delta,
+            GrowDecision::Deny => return Err(could_not_grow),
+            GrowDecision::GrantPartial(pages)
+                if request.partial_allowed && pages.0 > 0 && pages < delta =>
+            {
+                pages
+            }
+            GrowDecision::GrantPartial(pages) => {
+                self.release(pages);
+                return Err(could_not_grow);
+            }
+        };
+        match self.memory.grow(granted) {
+            Ok(previous) => {
+                let bytes = u64::from(granted.0) * WASM_PAGE_SIZE as u64;
+                self.granted.fetch_add(bytes, Ordering::Relaxed);
+                Ok(previous)
+            }
+            Err(error) => {
+                self.release(granted);
+                Err(error)
+            }
+        }
+    }
+
+    fn discard(&self, offset: usize, len: usize) -> Result<(), MemoryError> {
+        self.memory.discard(offset, len)
+    }
+
+    /// Resetting the memory shrinks it back to its minimum size, releasing all that was
+    /// granted.
+    fn reset(&self) -> Result<bool, MemoryError> {
+        let restored = self.memory.reset()?;
+        let bytes = self.granted.swap(0, Ordering::Relaxed);
+        if bytes > 0 {
+            self.arbiter.release(&MemoryRelease {
+                instance: self.instance,
+                tenant: self.tenant,
+                bytes,
+            });
+        }
+        Ok(restored)
+    }
+
+    fn resident_bytes(&self) -> usize {
+        self.memory.resident_bytes()
+    }
+
+    fn base_stability(&self) -> BaseStability {
+        self.memory.base_stability()
+    }
+
+    fn reservation_fallback(&self) -> Option<MemoryError> {
+        self.memory.reservation_fallback()
+    }
+
+    fn pin_base(&self) -> Result<(), MemoryError> {
+        self.memory.pin_base()
+    }
+
+    fn unpin_base(&self) {
+        self.memory.unpin_base()
+    }
+
+    fn vmmemory(&self) -> NonNull<VMMemoryDefinition> {
+        self.memory.vmmemory()
+    }
+}
+
+impl Drop for ArbitratedMemory {
+    fn drop(&mut self) {
+        let bytes = *self.granted.get_mut();
+        if bytes > 0 {
+            self.arbiter.release(&MemoryRelease {
+                instance: self.instance,
+                tenant: self.tenant,
+                bytes,
+            });
+        }
+    }
+}
+
+/// The bytes a tenant was granted, and the most it may be.
+#[derive(Debug)]
+struct TenantShare {
+    used: AtomicU64,
+    limit: AtomicU64,
+}
+
+/// A [`MemoryArbiter`] sharing a global budget among tenants.
+///
+/// The memories of all instances may together grow by at most a hard cap of bytes, and
+/// those of the instances of each tenant by at most the soft limit of the tenant. Soft limits
+/// are only checked when memories grow: lowering one below what the tenant holds takes
+/// nothing back, but no more is granted until the tenant is under it again. Instances that
+/// run for no tenant are only held to the hard cap.
+///
+/// Only growth is arbitrated: the initial sizes of memories are not counted. When an
+/// instance accepts partial grants, a request exceeding what is left is granted the whole
+/// pages that are.
+///
+/// Decisions only update atomic counters, so concurrent growth never waits, except for the
+/// first growth of a tenant without a soft limit, which registers it.
+#[derive(Debug)]
+pub struct FairShareArbiter {
+    hard_cap: u64,
+    used: AtomicU64,
+    tenants: RwLock<HashMap<TenantId, Arc<TenantShare>>>,
+}
+
+impl FairShareArbiter {
+    /// Create an arbiter letting memories grow by at most `hard_cap` bytes in total.
+    pub fn new(hard_cap: u64) -> Self {
+        Self {
+            hard_cap,
+            used: AtomicU64::new(0),
+            tenants: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Limit the growth of the memories of the instances of `tenant` to `limit` bytes,
+    /// replacing its previous limit.
+    pub fn set_tenant_limit(&self, tenant: TenantId, limit: u64) {
+        self.share(tenant).limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// The bytes granted to all memories and not released yet.
+    pub fn used_bytes(&self) -> u64 {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    fn share(&self, tenant: TenantId) -> Arc<TenantShare> {
+        if let Some(share) = self.tenants.read().unwrap().get(&tenant) {
+            return Arc::clone(share);
+        }
+        let mut tenants = self.tenants.write().unwrap();
+        Arc::clone(tenants.entry(tenant).or_insert_with(|| {
+            Arc::new(TenantShare {
+                used: AtomicU64::new(0),
+                limit: AtomicU64::new(u64::MAX),
+            })
+        }))
+    }
+}
+
+/// Take up to `wanted` bytes, in whole pages, out of `counter` without exceeding `limit`,
+/// returning the bytes taken. Takes nothing unless all of `wanted` fits or `partial` is set.
+fn reserve(counter: &AtomicU64, limit: u64, wanted: u64, partial: bool) -> u64 {
+    let page = WASM_PAGE_SIZE as u64;
+    let mut taken = 0;
+    let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+        let available = limit.saturating_sub(used) / page * page;
+        taken = if wanted <= available {
+            wanted
+        } else if partial {
+            available
+        } else {
+            0
+        };
+        match taken {
+            0 => None,
+            taken => Some(used + taken),
+        }
+    });
+    taken
+}
+
+impl MemoryArbiter for FairShareArbiter {
+    fn request(&self, request: &GrowRequest) -> GrowDecision {
+        let wanted = request.delta_bytes();
+        let partial = request.partial_allowed;
+        let mut granted = reserve(&self.used, self.hard_cap, wanted, partial);
+        if granted == 0 {
+            return GrowDecision::Deny;
+        }
+        if let Some(tenant) = request.tenant {
+            let share = self.share(tenant);
+            let limit = share.limit.load(Ordering::Relaxed);
+            let taken = reserve(&share.used, limit, granted, partial);
+            self.used.fetch_sub(granted - taken, Ordering::Relaxed);
+            granted = taken;
+        }
+        if granted == wanted {
+            GrowDecision::Grant
+        } else if granted > 0 {
+            GrowDecision::GrantPartial(Pages((granted / WASM_PAGE_SIZE as u64) as u32))
+        } else {
+            GrowDecision::Deny
+        }
+    }
+
+    fn release(&self, release: &MemoryRelease) {
+        self.used.fetch_sub(release.bytes, Ordering::Relaxed);
+        if let Some(tenant) = release.tenant {
+            self.share(tenant)
+                .used
+                .fetch_sub(release.bytes, Ordering::Relaxed);
+        }
+    }
+
+    fn tenant_bytes(&self, tenant: TenantId) -> Option<u64> {
+        let tenants = self.tenants.read().unwrap();
+        Some(
+            tenants
+                .get(&tenant)
+                .map_or(0, |share| share.used.load(Ordering::Relaxed)),
+        )
+    }
+}
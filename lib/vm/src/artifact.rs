@@ -1,8 +1,8 @@
 use crate::{InstanceHandle, Resolver, Tunables, VMLocalFunction, VMSharedSignatureIndex};
 use std::{any::Any, collections::BTreeMap, sync::Arc};
 use wasmer_types::{
-    entity::BoxedSlice, ElemIndex, FunctionIndex, GlobalInit, GlobalType, ImportCounts,
-    InstanceConfig, LocalFunctionIndex, OwnedDataInitializer, OwnedTableInitializer,
+    entity::BoxedSlice, ElemIndex, ExternalDataInitializer, FunctionIndex, GlobalInit, GlobalType,
+    ImportCounts, InstanceConfig, LocalFunctionIndex, OwnedDataInitializer, OwnedTableInitializer,
 };
 
 mod private {
@@ -66,6 +66,12 @@ pub trait Artifact: Send + Sync {
     /// TODO: consider making it an iterator of `DataInitializer`s instead?
     fn data_segments(&self) -> &[OwnedDataInitializer];
 
+    /// Memory initializers whose contents are supplied at instantiation time, ordered by
+    /// `segment_index`.
+    fn external_data_segments(&self) -> &[ExternalDataInitializer] {
+        &[]
+    }
+
     /// Passive table elements.
     fn globals(&self) -> &[(GlobalType, GlobalInit)];
 
@@ -0,0 +1,112 @@
+//! Cancelling single calls into instances while they run.
+//!
+//! Compiled code checks a counter of the `vmctx` on function entry and at the start of every
+//! loop iteration, and only calls into the runtime when it is not zero, which is the case while
+//! a cancelled call into the instance has not returned yet. The runtime then traps with
+//! [`TrapCode::Cancelled`] if one of the calls running on the current thread is cancelled, and
+//! returns otherwise: the other calls into the instance only pay for the detour.
+//!
+//! [`TrapCode::Cancelled`]: crate::TrapCode::Cancelled
+
+use crate::instance::InstanceRef;
+use crate::trap::{Trap, TrapCode};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+/// The call may still run, and was not cancelled.
+const ACTIVE: u8 = 0;
+/// The call was cancelled, and has not finished yet.
+const CANCELLED: u8 = 1;
+/// The call finished, cancelled or not.
+const FINISHED: u8 = 2;
+
+thread_local! {
+    /// The cancellable calls running on this thread, innermost last.
+    static RUNNING: RefCell<Vec<Arc<CallCancellation>>> = RefCell::new(Vec::new());
+}
+
+/// Return whether a call running on this thread was cancelled.
+pub(crate) fn cancellation_requested() -> bool {
+    RUNNING.with(|running| running.borrow().iter().any(|call| call.is_cancelled()))
+}
+
+/// Whether a single call into an instance was cancelled, shared between the call and whoever
+/// may cancel it.
+#[derive(Debug)]
+pub struct CallCancellation {
+    state: AtomicU8,
+    instance: InstanceRef,
+}
+
+impl CallCancellation {
+    /// Return the state of a call into `instance` that was not cancelled.
+    pub fn new(instance: InstanceRef) -> Arc<Self> {
+        Arc::new(Self {
+            state: AtomicU8::new(ACTIVE),
+            instance,
+        })
+    }
+
+    /// Cancel the call, returning whether it had not finished nor been cancelled yet.
+    ///
+    /// The call traps with [`TrapCode::Cancelled`] at the next check of the compiled code of
+    /// the instance, or without starting if it did not. Calls into other instances made by
+    /// the host functions it calls run to completion.
+    pub fn cancel(&self) -> bool {
+        let cancelled = self
+            .state
+            .compare_exchange(ACTIVE, CANCELLED, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok();
+        if cancelled {
+            // May briefly wrap around if the call finishes first, see `finish`.
+            self.instance
+                .as_ref()
+                .cancellations_pending()
+                .fetch_add(1, Ordering::SeqCst);
+        }
+        cancelled
+    }
+
+    /// Return whether the call was cancelled before it finished.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.load(Ordering::SeqCst) == CANCELLED
+    }
+
+    /// Run `call` as the call this state is about, returning a [`TrapCode::Cancelled`] trap
+    /// without running it if it was already cancelled.
+    ///
+    /// The call finishes when `call` returns: cancelling it afterwards does nothing.
+    pub fn run<R>(self: &Arc<Self>, call: impl FnOnce() -> R) -> Result<R, Trap> {
+        struct Finish<'a>(&'a CallCancellation);
+
+        impl Drop for Finish<'_> {
+            fn drop(&mut self) {
+                RUNNING.with(|running| running.borrow_mut().pop());
+                self.0.finish();
+            }
+        }
+
+        if self.is_cancelled() {
+            self.finish();
+            return Err(Trap::lib(TrapCode::Cancelled));
+        }
+        RUNNING.with(|running| running.borrow_mut().push(Arc::clone(self)));
+        let _finish = Finish(self);
+        Ok(call())
+    }
+
+    /// Mark the call finished, so that the instance stops checking for its cancellation.
+    ///
+    /// [`run`](Self::run) does so. Calls that are never run should be finished this way, or
+    /// the compiled code of the instance keeps calling into the runtime after they are
+    /// cancelled.
+    pub fn finish(&self) {
+        if self.state.swap(FINISHED, Ordering::SeqCst) == CANCELLED {
+            self.instance
+                .as_ref()
+                .cancellations_pending()
+                .fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Diagnostics that can be turned on for single instances while they run.
+//!
+//! Compiled code checks the [`DiagnosticsLevel`] of its instance, a byte of the `vmctx`,
+//! before each function entry and each memory access, and only calls into the runtime to
+//! report a [`DiagnosticEvent`] when the level asks for it. Changing the level of an
+//! instance thus takes effect immediately, without recompiling anything, and instances left
+//! at [`DiagnosticsLevel::Off`] only pay for a compare and a branch that is never taken.
+
+use crate::provenance::InstanceId;
+use wasmer_types::FunctionIndex;
+
+/// How much an instance reports to the diagnostics sink of its engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum DiagnosticsLevel {
+    /// Report nothing.
+    Off = 0,
+    /// Report the entry into every function defined by the instance.
+    Calls = 1,
+    /// Report function entries and every load and store of linear memory.
+    Verbose = 2,
+}
+
+impl DiagnosticsLevel {
+    /// Return the level stored as `byte` in a `vmctx`.
+    pub(crate) fn from_u8(byte: u8) -> Self {
+        match byte {
+            0 => Self::Off,
+            1 => Self::Calls,
+            _ => Self::Verbose,
+        }
+    }
+}
+
+// `#[derive(Default)]` on enums needs `#[default]`, which the pinned Rust 1.56 lacks.
+#[allow(clippy::derivable_impls)]
+impl Default for DiagnosticsLevel {
+    fn default() -> Self {
+        Self::Off
+    }
+}
+
+/// Something an instance did, as reported at [`DiagnosticsLevel::Calls`] and above.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticEvent {
+    /// A function defined by the instance was entered.
+    Call {
+        /// The index of the function in its module.
+        function: FunctionIndex,
+    },
+    /// Linear memory is about to be read.
+    Load {
+        /// The address of the first byte read, offset included.
+        address: u64,
+        /// The number of bytes read.
+        size: u8,
+    },
+    /// Linear memory is about to be written.
+    Store {
+        /// The address of the first byte written, offset included.
+        address: u64,
+        /// The number of bytes written.
+        size: u8,
+    },
+}
+
+/// Receives the diagnostics of the instances of an engine.
+///
+/// Events are reported synchronously, on the thread running the instance, before the
+/// operation they describe. A load or store reported this way may still trap.
+pub trait DiagnosticsSink: Send + Sync {
+    /// Record `event`, which happened in the instance identified by `instance`.
+    fn event(&self, instance: InstanceId, event: DiagnosticEvent);
+}
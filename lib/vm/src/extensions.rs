@@ -0,0 +1,34 @@
+//! Tracking of the extensions of the instance currently executing on this
+//! thread, so that host functions can reach them without an env.
+
+use std::cell::RefCell;
+use std::sync::Arc;
+use wasmer_types::Extensions;
+
+thread_local! {
+    /// Extensions of every instance entered on this thread, innermost last.
+    static CURRENT: RefCell<Vec<Arc<Extensions>>> = RefCell::new(Vec::new());
+}
+
+/// Return the extensions of the innermost instance executing on this thread.
+pub fn current_extensions() -> Option<Arc<Extensions>> {
+    CURRENT.with(|current| current.borrow().last().cloned())
+}
+
+/// Run `closure` with `extensions` registered as the current extensions of this thread.
+///
+/// Calls into WebAssembly made within `closure` see these extensions from host functions,
+/// until a nested call registers another set.
+pub fn with_extensions<R>(extensions: Arc<Extensions>, closure: impl FnOnce() -> R) -> R {
+    struct Reset;
+
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            CURRENT.with(|current| current.borrow_mut().pop());
+        }
+    }
+
+    CURRENT.with(|current| current.borrow_mut().push(extensions));
+    let _reset = Reset;
+    closure()
+}
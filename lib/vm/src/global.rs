@@ -3,7 +3,9 @@ use std::cell::UnsafeCell;
 use std::ptr::NonNull;
 use std::sync::Mutex;
 use thiserror::Error;
-use wasmer_types::{GlobalType, Mutability, Type, Value, WasmValueType};
+use wasmer_types::{
+    ErrorCode, GlobalType, HasErrorCode, Mutability, Type, Value, WasmValueType,
+};
 
 #[derive(Debug)]
 /// A Global instance
@@ -27,12 +29,15 @@ unsafe impl Sync for Global {}
 #[derive(Error, Debug, Clone, PartialEq, Hash)]
 pub enum GlobalError {
     /// The error returned when attempting to set an immutable global.
-    #[error("Attempted to set an immutable global")]
+    #[error("Attempted to set an immutable global [{}]", ErrorCode::GlobalImmutable)]
     ImmutableGlobalCannotBeSet,
 
     /// The error returned when attempting to operate on a global as a specific type
     /// that differs from the global's own type.
-    #[error("Attempted to operate on a global of type {expected} as a global of type {found}")]
+    #[error(
+        "Attempted to operate on a global of type {expected} as a global of type {found} [{}]",
+        ErrorCode::GlobalIncorrectType
+    )]
     IncorrectType {
         /// The type that the global is.
         expected: Type,
@@ -41,6 +46,15 @@ pub enum GlobalError {
     },
 }
 
+impl HasErrorCode for GlobalError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::ImmutableGlobalCannotBeSet => ErrorCode::GlobalImmutable,
+            Self::IncorrectType { .. } => ErrorCode::GlobalIncorrectType,
+        }
+    }
+}
+
 impl Global {
     /// Create a new, zero bit-pattern initialized global from a [`GlobalType`].
     pub fn new(global_type: GlobalType) -> Self {
@@ -1,10 +1,12 @@
 use super::{Instance, InstanceRef};
-use crate::vmcontext::{VMMemoryDefinition, VMTableDefinition};
+use crate::reclaim::{ReclaimPriority, Reclaimable};
+use crate::vmcontext::{VMCallerCheckedAnyfunc, VMMemoryDefinition, VMTableDefinition};
 use crate::VMOffsets;
 use std::alloc::{self, Layout};
 use std::convert::TryFrom;
 use std::mem;
 use std::ptr::{self, NonNull};
+use std::sync::{Arc, Mutex};
 use wasmer_types::entity::EntityRef;
 use wasmer_types::{LocalMemoryIndex, LocalTableIndex};
 
@@ -22,7 +24,7 @@ use wasmer_types::{LocalMemoryIndex, LocalTableIndex};
 /// 1. Define the correct layout for `Instance` (size and alignment),
 /// 2. Allocate it properly.
 ///
-/// The [`InstanceAllocator::instance_layout`] computes the correct
+/// The [`InstanceAllocator::buffer_layout`] computes the correct
 /// layout to represent the wanted [`Instance`].
 ///
 /// Then we use this layout to allocate an empty `Instance` properly.
@@ -41,6 +43,9 @@ pub struct InstanceAllocator {
     /// `instance_ptr` buffer. If it has not when being dropped,
     /// the buffer should be freed.
     consumed: bool,
+
+    /// Where to return the `instance_ptr` buffer instead of freeing it.
+    arena: Option<Arc<InstanceArena>>,
 }
 
 impl Drop for InstanceAllocator {
@@ -48,15 +53,182 @@ impl Drop for InstanceAllocator {
         if !self.consumed {
             // If `consumed` has not been set, then we still have ownership
             // over the buffer and must free it.
-            let instance_ptr = self.instance_ptr.as_ptr();
+            let instance_ptr = self.instance_ptr.cast::<u8>();
 
             unsafe {
-                std::alloc::dealloc(instance_ptr as *mut u8, self.instance_layout);
+                InstanceArena::release(self.arena.as_deref(), instance_ptr, self.instance_layout);
             }
         }
     }
 }
 
+/// The sizes in bytes of what is allocated for an instance, computed from its [`VMOffsets`]
+/// without instantiating it.
+///
+/// An instance is made of one buffer holding its `Instance` header followed by its
+/// `VMContext`, see [`InstanceAllocator`], and of a separate array with the
+/// [`VMCallerCheckedAnyfunc`] of every function it defines or imports. The memories, tables
+/// and globals themselves, and whatever the host state holds, are allocated elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstanceLayout {
+    /// The `Instance` header preceding the `VMContext` in the buffer.
+    pub header: usize,
+    /// The whole `VMContext`, including the areas below.
+    pub vmctx: usize,
+    /// The `VMMemoryDefinition`s in the `VMContext`.
+    pub memory_definitions: usize,
+    /// The `VMTableDefinition`s in the `VMContext`.
+    pub table_definitions: usize,
+    /// The pointers to the definitions of the globals in the `VMContext`.
+    pub globals: usize,
+    /// The builtin functions array in the `VMContext`.
+    pub builtin_functions: usize,
+    /// Padding of the buffer up to its alignment.
+    pub padding: usize,
+    /// The array of `VMCallerCheckedAnyfunc`s.
+    pub anyfuncs: usize,
+}
+
+impl InstanceLayout {
+    /// Compute the layout of an instance with `offsets`, of a module with `num_functions`
+    /// functions, imported ones included.
+    pub fn new(offsets: &VMOffsets, num_functions: usize) -> Self {
+        let size = |area: u32| usize::try_from(area).unwrap();
+        let (buffer, header) = InstanceAllocator::buffer_layout(offsets);
+        let vmctx = size(offsets.size_of_vmctx());
+        Self {
+            header,
+            vmctx,
+            memory_definitions: size(
+                offsets.num_local_memories * u32::from(offsets.size_of_vmmemory_definition()),
+            ),
+            table_definitions: size(
+                offsets.num_local_tables * u32::from(offsets.size_of_vmtable_definition()),
+            ),
+            globals: size(offsets.vmctx_builtin_functions_begin() - offsets.vmctx_globals_begin()),
+            builtin_functions: size(
+                offsets.vmctx_trap_handler_begin() - offsets.vmctx_builtin_functions_begin(),
+            ),
+            padding: buffer.size() - header - vmctx,
+            anyfuncs: num_functions * mem::size_of::<VMCallerCheckedAnyfunc>(),
+        }
+    }
+
+    /// The size of the buffer holding the `Instance` header and the `VMContext`.
+    pub fn buffer_bytes(&self) -> usize {
+        self.header + self.vmctx + self.padding
+    }
+
+    /// The size of everything allocated for the instance.
+    pub fn total_bytes(&self) -> usize {
+        self.buffer_bytes() + self.anyfuncs
+    }
+}
+
+/// Instance buffers kept for reuse.
+///
+/// Instances allocated with [`InstanceAllocator::new_in`] return their buffer, which holds the
+/// `VMContext`, to the arena when they are dropped rather than freeing it, so that the next
+/// instance of the same module does not need to allocate one. Instances keep the arena alive,
+/// and the buffers are freed when the arena is dropped or [cleared](Self::clear).
+#[derive(Debug, Default)]
+pub struct InstanceArena {
+    free: Mutex<Vec<(Layout, NonNull<u8>)>>,
+}
+
+/// The buffers are only accessed through the lock.
+unsafe impl Send for InstanceArena {}
+unsafe impl Sync for InstanceArena {}
+
+impl InstanceArena {
+    /// Create an empty arena.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the number of buffers kept for reuse.
+    pub fn len(&self) -> usize {
+        self.free.lock().unwrap().len()
+    }
+
+    /// Return whether no buffer is kept for reuse.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Free the buffers kept for reuse.
+    pub fn clear(&self) {
+        let free = mem::take(&mut *self.free.lock().unwrap());
+        for (layout, ptr) in free {
+            unsafe { alloc::dealloc(ptr.as_ptr(), layout) };
+        }
+    }
+
+    /// Take a buffer of `layout`, or allocate one.
+    fn take(arena: Option<&Self>, layout: Layout) -> NonNull<u8> {
+        if let Some(arena) = arena {
+            let mut free = arena.free.lock().unwrap();
+            if let Some(index) = free.iter().position(|(l, _)| *l == layout) {
+                return free.swap_remove(index).1;
+            }
+        }
+        match NonNull::new(unsafe { alloc::alloc(layout) }) {
+            Some(ptr) => ptr,
+            None => alloc::handle_alloc_error(layout),
+        }
+    }
+
+    /// Give back a buffer obtained from [`Self::take`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been obtained from `take` with the same `layout`, and not be used after.
+    pub(super) unsafe fn release(arena: Option<&Self>, ptr: NonNull<u8>, layout: Layout) {
+        match arena {
+            Some(arena) => arena.free.lock().unwrap().push((layout, ptr)),
+            None => alloc::dealloc(ptr.as_ptr(), layout),
+        }
+    }
+}
+
+/// The buffers kept for reuse are released first, as releasing them only costs allocations to
+/// the next instances.
+impl Reclaimable for InstanceArena {
+    fn name(&self) -> &str {
+        "instance arena"
+    }
+
+    fn priority(&self) -> ReclaimPriority {
+        ReclaimPriority::Unused
+    }
+
+    fn reclaimable_bytes(&self) -> usize {
+        let free = self.free.lock().unwrap();
+        free.iter().map(|(layout, _)| layout.size()).sum()
+    }
+
+    fn reclaim(&self, target_bytes: usize) -> usize {
+        let mut released = 0;
+        while released < target_bytes {
+            // Buffers are freed outside of the lock, which instantiations take.
+            let buffer = self.free.lock().unwrap().pop();
+            let (layout, ptr) = match buffer {
+                Some(buffer) => buffer,
+                None => break,
+            };
+            unsafe { alloc::dealloc(ptr.as_ptr(), layout) };
+            released += layout.size();
+        }
+        released
+    }
+}
+
+impl Drop for InstanceArena {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
 impl InstanceAllocator {
     /// Allocates instance data for use with [`InstanceHandle::new`].
     ///
@@ -74,22 +246,40 @@ impl InstanceAllocator {
         Vec<NonNull<VMMemoryDefinition>>,
         Vec<NonNull<VMTableDefinition>>,
     ) {
-        let instance_layout = Self::instance_layout(&offsets);
+        Self::allocate(offsets, None)
+    }
 
-        #[allow(clippy::cast_ptr_alignment)]
-        let instance_ptr = unsafe { alloc::alloc(instance_layout) as *mut Instance };
+    /// Like [`InstanceAllocator::new`], but reuses a buffer kept by `arena` if possible, and
+    /// returns the buffer to it when the instance is dropped.
+    pub fn new_in(
+        offsets: VMOffsets,
+        arena: &Arc<InstanceArena>,
+    ) -> (
+        Self,
+        Vec<NonNull<VMMemoryDefinition>>,
+        Vec<NonNull<VMTableDefinition>>,
+    ) {
+        Self::allocate(offsets, Some(Arc::clone(arena)))
+    }
 
-        let instance_ptr = if let Some(ptr) = NonNull::new(instance_ptr) {
-            ptr
-        } else {
-            alloc::handle_alloc_error(instance_layout);
-        };
+    fn allocate(
+        offsets: VMOffsets,
+        arena: Option<Arc<InstanceArena>>,
+    ) -> (
+        Self,
+        Vec<NonNull<VMMemoryDefinition>>,
+        Vec<NonNull<VMTableDefinition>>,
+    ) {
+        let (instance_layout, _) = Self::buffer_layout(&offsets);
+        let instance_ptr =
+            InstanceArena::take(arena.as_deref(), instance_layout).cast::<Instance>();
 
         let allocator = Self {
             instance_ptr,
             instance_layout,
             offsets,
             consumed: false,
+            arena,
         };
 
         // # Safety
@@ -102,19 +292,20 @@ impl InstanceAllocator {
         (allocator, memories, tables)
     }
 
-    /// Calculate the appropriate layout for the [`Instance`].
-    fn instance_layout(offsets: &VMOffsets) -> Layout {
+    /// Calculate the appropriate layout for the [`Instance`], and the offset of its
+    /// `VMContext`.
+    fn buffer_layout(offsets: &VMOffsets) -> (Layout, usize) {
         let vmctx_size = usize::try_from(offsets.size_of_vmctx())
             .expect("Failed to convert the size of `vmctx` to a `usize`");
 
         let instance_vmctx_layout =
             Layout::array::<u8>(vmctx_size).expect("Failed to create a layout for `VMContext`");
 
-        let (instance_layout, _offset) = Layout::new::<Instance>()
+        let (instance_layout, offset) = Layout::new::<Instance>()
             .extend(instance_vmctx_layout)
             .expect("Failed to extend to `Instance` layout to include `VMContext`");
 
-        instance_layout.pad_to_align()
+        (instance_layout.pad_to_align(), offset)
     }
 
     /// Get the locations of where the local [`VMMemoryDefinition`]s should be stored.
@@ -200,9 +391,10 @@ impl InstanceAllocator {
         }
         let instance = self.instance_ptr;
         let instance_layout = self.instance_layout;
+        let arena = self.arena.take();
 
         // This is correct because of the invariants of `Self` and
         // because we write `Instance` to the pointer in this function.
-        unsafe { InstanceRef::new(instance, instance_layout) }
+        unsafe { InstanceRef::new(instance, instance_layout, arena) }
     }
 }
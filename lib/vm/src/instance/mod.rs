@@ -8,15 +8,26 @@
 //! wrapper around an `InstanceRef`.
 
 mod allocator;
+mod pretouch;
 mod r#ref;
+mod reset;
+mod snapshot;
 
-pub use allocator::InstanceAllocator;
+pub use allocator::{InstanceAllocator, InstanceArena, InstanceLayout};
 pub use r#ref::{InstanceRef, WeakInstanceRef, WeakOrStrongInstanceRef};
+pub use reset::ResetError;
+pub use snapshot::{SnapshotError, SnapshotOptions, SnapshotStats};
 
+use crate::arbiter::{ArbitratedMemory, MemoryArbiter};
+use crate::cancellation::CallCancellation;
+use crate::diagnostics::{DiagnosticEvent, DiagnosticsLevel, DiagnosticsSink};
 use crate::func_data_registry::VMFuncRef;
 use crate::global::Global;
 use crate::imports::Imports;
 use crate::memory::{Memory, MemoryError};
+use crate::nontemporal;
+use crate::provenance::{self, InstanceId};
+use crate::readonly_memory::is_read_only;
 use crate::sig_registry::VMSharedSignatureIndex;
 use crate::table::{Table, TableElement};
 use crate::trap::traphandlers::get_trap_handler;
@@ -26,25 +37,29 @@ use crate::vmcontext::{
     VMFunctionEnvironment, VMFunctionImport, VMFunctionKind, VMGlobalDefinition, VMGlobalImport,
     VMLocalFunction, VMMemoryDefinition, VMMemoryImport, VMTableDefinition, VMTableImport,
 };
-use crate::{wasmer_call_trampoline, Artifact, VMOffsets, VMTrampoline};
+use crate::{wasmer_call_trampoline, with_extensions, Artifact, VMOffsets, VMTrampoline};
+use crate::{Counter, MetricsSink};
 use crate::{VMExtern, VMFunction, VMGlobal};
 use memoffset::offset_of;
 use more_asserts::assert_lt;
 use std::any::Any;
 use std::cell::RefCell;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryFrom;
 use std::ffi;
 use std::fmt;
-use std::mem;
+use std::mem::{self, ManuallyDrop};
 use std::ptr::{self, NonNull};
 use std::slice;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
 use wasmer_types::entity::{packed_option::ReservedValue, BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::{
-    DataIndex, DataInitializer, ElemIndex, ExportIndex, FastGasCounter, FunctionIndex, GlobalIndex,
-    GlobalInit, InstanceConfig, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex,
-    OwnedTableInitializer, Pages, TableIndex,
+    Classify, DataIndex, DataInitializer, ElemIndex, ErrorCode, ExportIndex, Extensions,
+    FailureKind, FastGasCounter, FunctionIndex, GlobalIndex, GlobalInit, HasErrorCode,
+    InstanceConfig, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex,
+    OwnedTableInitializer, Pages, TableIndex, TableProvenancePolicy,
 };
 
 /// The function pointer to call with data and an [`Instance`] pointer to
@@ -52,6 +67,10 @@ use wasmer_types::{
 pub type ImportInitializerFuncPtr<ResultErr = *mut ffi::c_void> =
     fn(*mut ffi::c_void, *const ffi::c_void) -> Result<(), ResultErr>;
 
+/// Set in `Instance::active_calls` while the instance is being rebound to another artifact,
+/// snapshotted, restored or reset, which holds off new calls.
+const EXCLUSIVE: usize = 1 << (usize::BITS - 1);
+
 /// A WebAssembly instance.
 ///
 /// The type is dynamically-sized. Indeed, the `vmctx` field can
@@ -60,7 +79,14 @@ pub type ImportInitializerFuncPtr<ResultErr = *mut ffi::c_void> =
 /// the `vmctx` field to learn more.
 #[repr(C)]
 pub(crate) struct Instance {
-    pub(crate) artifact: Arc<dyn Artifact>,
+    /// Only taken out when the instance is dropped.
+    pub(crate) artifact: ManuallyDrop<Arc<dyn Artifact>>,
+
+    /// The artifacts the instance ran the code of before being rebound to `artifact`.
+    ///
+    /// Functions looked up before the rebind, and the instances importing them, still call
+    /// their code.
+    retired_artifacts: Vec<Arc<dyn Artifact>>,
 
     /// External configuration for instance.
     config: InstanceConfig,
@@ -82,14 +108,70 @@ pub(crate) struct Instance {
     /// get removed. A missing entry is considered equivalent to an empty slice.
     passive_data: RefCell<BTreeMap<DataIndex, Arc<[u8]>>>,
 
+    /// The passive data segments as they were before any `data.drop`, for resets.
+    initial_passive_data: BTreeMap<DataIndex, Arc<[u8]>>,
+
+    /// Contents of the artifact's external data segments, in the same order.
+    /// Kept after memories are initialized, for resets.
+    external_data: Vec<Vec<u8>>,
+
+    /// Whether the local memories already hold the data segments, as they map an image
+    /// captured after applying them, so that instantiation does not apply them again.
+    data_segments_applied: AtomicBool,
+
     /// Mapping of function indices to their func ref backing data. `VMFuncRef`s
     /// will point to elements here for functions defined or imported by this
     /// instance.
     funcrefs: BoxedSlice<FunctionIndex, VMCallerCheckedAnyfunc>,
 
+    /// Identifier of this instance, by which the functions it defines are told apart from
+    /// those of other instances.
+    id: InstanceId,
+
+    /// The same functions as `funcrefs`, to check the references written to tables against.
+    /// Only built when the table provenance policy is not `Allow`.
+    own_funcrefs: Option<HashSet<VMCallerCheckedAnyfunc>>,
+
     /// Hosts can store arbitrary per-instance information here.
     host_state: Box<dyn Any>,
 
+    /// Typed values made available to host functions called from this
+    /// instance.
+    ///
+    /// Updates are rare and replace the whole map, while every call into the
+    /// instance only takes a snapshot of it, so calls never wait on each
+    /// other.
+    extensions: RwLock<Arc<Extensions>>,
+
+    /// Where to report the destruction of this instance.
+    metrics_sink: Option<Arc<dyn MetricsSink>>,
+
+    /// Where to report diagnostics, depending on the level stored in the `vmctx`.
+    diagnostics_sink: Option<Arc<dyn DiagnosticsSink>>,
+
+    /// The functions the instance defines by the address of their body, by which compiled
+    /// code tells the diagnostics which function it entered. Only built when the instance has
+    /// a diagnostics sink.
+    function_bodies: Option<HashMap<usize, FunctionIndex>>,
+
+    /// Number of calls the host made into this instance.
+    call_sequence: AtomicU64,
+
+    /// Global through which the instance reads `call_sequence`, if any.
+    call_sequence_global: Option<Arc<Global>>,
+
+    /// Number of calls into this instance currently on the stack, plus [`EXCLUSIVE`] while the
+    /// instance is being rebound, snapshotted, restored or reset.
+    active_calls: AtomicUsize,
+
+    /// Whether a restore of a snapshot or a reset failed halfway, leaving the instance in a
+    /// state calls must not observe until a restore or a reset succeeds.
+    poisoned: AtomicBool,
+
+    /// The resolved imports. The `vmctx` only holds bitwise copies of them, so this keeps
+    /// the imported tables, memories and globals alive for as long as the instance.
+    imports: Imports,
+
     /// Functions to operate on host environments in the imports
     /// and pointers to the environments.
     ///
@@ -104,6 +186,78 @@ pub(crate) struct Instance {
     vmctx: VMContext,
 }
 
+/// The resources an instance uses at some point in time.
+///
+/// Only the memories and tables the instance defines are counted, not those it imports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InstanceUsage {
+    /// Bytes of the memories of the instance.
+    pub memory_bytes: u64,
+    /// Number of tables of the instance.
+    pub tables: usize,
+    /// Number of elements of the tables of the instance.
+    pub table_elements: u64,
+    /// Gas left before the gas counter of the instance reaches its limit.
+    pub gas_remaining: u64,
+    /// Whether a call into the instance is running.
+    pub in_call: bool,
+}
+
+/// An error rebinding an instance to the code of another artifact.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RebindError {
+    /// The new artifact was not compiled from the same binary as the instance's.
+    #[error(
+        "the module was compiled from another binary than the instance's [{}]",
+        ErrorCode::RebindWasmMismatch
+    )]
+    WasmMismatch,
+    /// A call into the instance is running, so its code cannot be swapped.
+    #[error(
+        "{active_calls} call(s) into the instance are running [{}]",
+        ErrorCode::RebindCallInFlight
+    )]
+    CallInFlight {
+        /// The number of calls running, including those nested in host functions.
+        active_calls: usize,
+    },
+    /// The code of the new artifact expects another layout of the instance.
+    #[error(
+        "the module expects another layout of the instance: {0} [{}]",
+        ErrorCode::RebindLayoutMismatch
+    )]
+    LayoutMismatch(String),
+}
+
+impl HasErrorCode for RebindError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::WasmMismatch => ErrorCode::RebindWasmMismatch,
+            Self::CallInFlight { .. } => ErrorCode::RebindCallInFlight,
+            Self::LayoutMismatch(_) => ErrorCode::RebindLayoutMismatch,
+        }
+    }
+}
+
+impl Classify for RebindError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            // The rebind succeeds once the calls return.
+            Self::CallInFlight { .. } => FailureKind::TransientInterrupted,
+            Self::WasmMismatch | Self::LayoutMismatch(_) => FailureKind::Permanent,
+        }
+    }
+}
+
+/// Holds off calls into an instance, see [`Instance::exclusive`].
+struct Exclusive<'a>(&'a AtomicUsize);
+
+impl Drop for Exclusive<'_> {
+    fn drop(&mut self) {
+        self.0.store(0, Ordering::SeqCst);
+    }
+}
+
 /// A collection of data about host envs used by imported functions.
 #[derive(Debug)]
 pub enum ImportFunctionEnv {
@@ -176,6 +330,35 @@ impl Drop for ImportFunctionEnv {
     }
 }
 
+impl Drop for Instance {
+    fn drop(&mut self) {
+        provenance::unregister(self.vmctx_ptr());
+        if let Some(sink) = &self.metrics_sink {
+            sink.increment(Counter::InstancesDestroyed, 1);
+        }
+        // SAFETY: the artifact is not used past this point.
+        let artifact = unsafe { ManuallyDrop::take(&mut self.artifact) };
+        let queue = match &self.config.teardown_queue {
+            Some(queue) => queue,
+            None => return,
+        };
+        // The host environments and host state go first, as they would without the queue:
+        // this also makes the artifact the last reference to itself when only the host state
+        // of the instance held it.
+        self.imported_function_envs = PrimaryMap::new().into_boxed_slice();
+        self.host_state = Box::new(());
+        let memories = mem::replace(&mut self.memories, PrimaryMap::new().into_boxed_slice());
+        let tables = mem::replace(&mut self.tables, PrimaryMap::new().into_boxed_slice());
+        let retired_artifacts = mem::take(&mut self.retired_artifacts);
+        let bytes = memories.values().map(|m| m.size().bytes().0).sum();
+        // Given back when the backlog is full, in which case they are dropped right here.
+        let _ = queue.defer(
+            bytes,
+            Box::new((memories, tables, artifact, retired_artifacts)),
+        );
+    }
+}
+
 impl fmt::Debug for Instance {
     fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
         formatter.debug_struct("Instance").finish()
@@ -367,6 +550,32 @@ impl Instance {
         unsafe { self.vmctx_plus_offset(self.offsets().vmctx_stack_limit_begin()) }
     }
 
+    /// Return the diagnostics level, which compiled code reads while the host may change it.
+    fn diagnostics_level(&self) -> &AtomicU8 {
+        unsafe { &*self.vmctx_plus_offset(self.offsets().vmctx_diagnostics_level()) }
+    }
+
+    /// Return the number of cancelled calls into this instance still running, which compiled
+    /// code checks while the host may change it.
+    pub(crate) fn cancellations_pending(&self) -> &AtomicU32 {
+        unsafe { &*self.vmctx_plus_offset(self.offsets().vmctx_cancellations_pending()) }
+    }
+
+    /// Return the function the instance defines whose body starts at `body`, if any.
+    pub(crate) fn function_at(&self, body: *const VMFunctionBody) -> Option<FunctionIndex> {
+        self.function_bodies
+            .as_ref()?
+            .get(&(body as usize))
+            .copied()
+    }
+
+    /// Report `event` to the diagnostics sink, if any.
+    pub(crate) fn report_diagnostic(&self, event: DiagnosticEvent) {
+        if let Some(sink) = &self.diagnostics_sink {
+            sink.event(self.id, event);
+        }
+    }
+
     /// Invoke the WebAssembly start function of the instance, if one is present.
     fn invoke_start_function(&self) -> Result<(), Trap> {
         let start_index = match self.artifact.start_function() {
@@ -376,14 +585,106 @@ impl Instance {
         let start_funcref = self.funcrefs[start_index];
         // Make the call.
         self.reset_stack_meter();
-        let result = unsafe {
+        self.enter(|| unsafe {
             catch_traps(|| {
                 mem::transmute::<*const VMFunctionBody, unsafe extern "C" fn(VMFunctionEnvironment)>(
                     start_funcref.func_ptr,
                 )(start_funcref.vmctx)
             })
-        };
-        result
+        })
+    }
+
+    /// Run `closure` as a call from the host into this instance.
+    ///
+    /// Outermost calls increment the call sequence number before `closure` runs. Calls nested
+    /// in host functions are part of the outer call and keep its sequence number.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TrapCode::PoisonedInstance`] trap, without running `closure`, if a restore
+    /// of the instance failed.
+    pub(crate) fn enter<R>(&self, closure: impl FnOnce() -> Result<R, Trap>) -> Result<R, Trap> {
+        struct Exit<'a>(&'a AtomicUsize);
+
+        impl Drop for Exit<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+
+        let mut active_calls = self.active_calls.load(Ordering::SeqCst);
+        loop {
+            // Calls wait for a rebind, snapshot or restore of the instance to complete.
+            if active_calls & EXCLUSIVE != 0 {
+                std::hint::spin_loop();
+                active_calls = self.active_calls.load(Ordering::SeqCst);
+                continue;
+            }
+            match self.active_calls.compare_exchange_weak(
+                active_calls,
+                active_calls + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => break,
+                Err(current) => active_calls = current,
+            }
+        }
+        let _exit = Exit(&self.active_calls);
+        if self.poisoned.load(Ordering::SeqCst) {
+            return Err(Trap::lib(TrapCode::PoisonedInstance));
+        }
+        if active_calls == 0 {
+            let sequence = self.call_sequence.fetch_add(1, Ordering::SeqCst) + 1;
+            if let Some(global) = &self.call_sequence_global {
+                unsafe { *global.vmglobal().as_mut().as_i64_mut() = sequence as i64 };
+            }
+        }
+        with_extensions(self.extensions(), closure)
+    }
+
+    /// Hold off calls into this instance until the returned guard is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns the number of calls running, if any.
+    fn exclusive(&self) -> Result<Exclusive<'_>, usize> {
+        self.active_calls
+            .compare_exchange(0, EXCLUSIVE, Ordering::SeqCst, Ordering::SeqCst)?;
+        Ok(Exclusive(&self.active_calls))
+    }
+
+    /// Return a snapshot of the extensions of this instance.
+    pub(crate) fn extensions(&self) -> Arc<Extensions> {
+        Arc::clone(&self.extensions.read().unwrap())
+    }
+
+    /// Return a snapshot of the resources this instance uses.
+    pub(crate) fn usage(&self) -> InstanceUsage {
+        // The counter outlives the instance, see `InstanceConfig::with_counter`. Calls running
+        // on other threads update it without synchronization, so the value may be stale.
+        let counter = unsafe { ptr::read_volatile(self.config.gas_counter) };
+        InstanceUsage {
+            memory_bytes: self
+                .memories
+                .values()
+                .map(|memory| memory.size().bytes().0 as u64)
+                .sum(),
+            tables: self.tables.len(),
+            table_elements: self
+                .tables
+                .values()
+                .map(|table| u64::from(table.size()))
+                .sum(),
+            gas_remaining: counter.gas_limit.saturating_sub(counter.burnt_gas),
+            in_call: self.active_calls.load(Ordering::SeqCst) & !EXCLUSIVE > 0,
+        }
+    }
+
+    /// Insert an extension value, returning the value of the same type it replaces.
+    pub(crate) fn insert_extension<T: Send + Sync + 'static>(&self, value: T) -> Option<Arc<T>> {
+        let mut extensions = self.extensions.write().unwrap();
+        Arc::make_mut(&mut extensions).insert(value)
     }
 
     fn reset_stack_meter(&self) {
@@ -392,6 +693,50 @@ impl Instance {
         }
     }
 
+    /// Charge `base` plus `rate` for each of `units` to the gas counter of this instance.
+    ///
+    /// Like the gas intrinsic of the compiled code, this records the gas as burnt even when
+    /// it exceeds the limit. The charged operation must then not be performed. A charge that
+    /// overflows exceeds any limit.
+    fn charge_gas(&self, base: u64, rate: u64, units: u32) -> Result<(), Trap> {
+        if base == 0 && rate == 0 {
+            return Ok(());
+        }
+        // The counter outlives the instance, see `InstanceConfig::with_counter`.
+        let counter = unsafe { &mut *self.config.gas_counter };
+        let burnt = rate
+            .checked_mul(u64::from(units))
+            .and_then(|gas| gas.checked_add(base))
+            .and_then(|gas| gas.checked_add(counter.burnt_gas));
+        counter.burnt_gas = burnt.unwrap_or(u64::MAX);
+        match burnt {
+            Some(burnt) if burnt <= counter.gas_limit => Ok(()),
+            _ => Err(Trap::lib(TrapCode::GasExceeded)),
+        }
+    }
+
+    fn charge_memory_grow(&self, delta: Pages) -> Result<(), MemoryError> {
+        let costs = &self.config.dynamic_gas_costs;
+        self.charge_gas(costs.memory_grow_base, costs.memory_grow_per_page, delta.0)
+            .map_err(|_| MemoryError::GasExceeded)
+    }
+
+    fn charge_table_grow(&self, delta: u32) -> Result<(), Trap> {
+        let costs = &self.config.dynamic_gas_costs;
+        self.charge_gas(costs.table_grow_base, costs.table_grow_per_element, delta)
+    }
+
+    fn charge_bulk_memory(&self, len: u32) -> Result<(), Trap> {
+        let costs = &self.config.dynamic_gas_costs;
+        self.charge_gas(costs.bulk_memory_base, costs.bulk_memory_per_byte, len)
+    }
+
+    /// Charge the gas of a bulk table operation writing `len` elements.
+    pub(crate) fn charge_bulk_table(&self, len: u32) -> Result<(), Trap> {
+        let costs = &self.config.dynamic_gas_costs;
+        self.charge_gas(costs.bulk_table_base, costs.bulk_table_per_element, len)
+    }
+
     /// Return the offset from the vmctx pointer to its containing `Instance`.
     #[inline]
     pub(crate) fn vmctx_offset() -> isize {
@@ -424,8 +769,8 @@ impl Instance {
 
     /// Grow memory by the specified amount of pages.
     ///
-    /// Returns `None` if memory can't be grown by the specified amount
-    /// of pages.
+    /// Returns an error if memory can't be grown by the specified amount
+    /// of pages, or if doing so exceeds the gas limit.
     pub(crate) fn memory_grow<IntoPages>(
         &self,
         memory_index: LocalMemoryIndex,
@@ -438,13 +783,15 @@ impl Instance {
             .memories
             .get(memory_index)
             .unwrap_or_else(|| panic!("no memory for index {}", memory_index.index()));
-        mem.grow(delta.into())
+        let delta = delta.into();
+        self.charge_memory_grow(delta)?;
+        mem.grow(delta)
     }
 
     /// Grow imported memory by the specified amount of pages.
     ///
-    /// Returns `None` if memory can't be grown by the specified amount
-    /// of pages.
+    /// Returns an error if memory can't be grown by the specified amount
+    /// of pages, or if doing so exceeds the gas limit.
     ///
     /// # Safety
     /// This and `imported_memory_size` are currently unsafe because they
@@ -458,7 +805,9 @@ impl Instance {
         IntoPages: Into<Pages>,
     {
         let import = self.imported_memory(memory_index);
-        import.from.grow(delta.into())
+        let delta = delta.into();
+        self.charge_memory_grow(delta)?;
+        import.from.grow(delta)
     }
 
     /// Returns the number of allocated wasm pages.
@@ -495,23 +844,31 @@ impl Instance {
     ///
     /// Returns `None` if table can't be grown by the specified amount
     /// of elements.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error, without growing the table, if doing so exceeds the gas limit.
     pub(crate) fn table_grow(
         &self,
         table_index: LocalTableIndex,
         delta: u32,
         init_value: TableElement,
-    ) -> Option<u32> {
-        let result = self
+    ) -> Result<Option<u32>, Trap> {
+        let table = self
             .tables
             .get(table_index)
-            .unwrap_or_else(|| panic!("no table for index {}", table_index.index()))
-            .grow(delta, init_value);
-
-        result
+            .unwrap_or_else(|| panic!("no table for index {}", table_index.index()));
+        self.charge_table_grow(delta)?;
+        self.check_provenance(&init_value, delta)?;
+        Ok(table.grow(delta, init_value))
     }
 
     /// Grow table by the specified amount of elements.
     ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error, without growing the table, if doing so exceeds the gas limit.
+    ///
     /// # Safety
     /// `table_index` must be a valid, imported table index.
     pub(crate) unsafe fn imported_table_grow(
@@ -519,9 +876,11 @@ impl Instance {
         table_index: TableIndex,
         delta: u32,
         init_value: TableElement,
-    ) -> Option<u32> {
+    ) -> Result<Option<u32>, Trap> {
         let import = self.imported_table(table_index);
-        import.from.grow(delta, init_value)
+        self.charge_table_grow(delta)?;
+        self.check_provenance(&init_value, delta)?;
+        Ok(import.from.grow(delta, init_value))
     }
 
     /// Get table element by index.
@@ -556,10 +915,14 @@ impl Instance {
         index: u32,
         val: TableElement,
     ) -> Result<(), Trap> {
-        self.tables
+        let table = self
+            .tables
             .get(table_index)
-            .unwrap_or_else(|| panic!("no table for index {}", table_index.index()))
-            .set(index, val)
+            .unwrap_or_else(|| panic!("no table for index {}", table_index.index()));
+        if index < table.size() {
+            self.check_provenance(&val, 1)?;
+        }
+        table.set(index, val)
     }
 
     /// Set table element by index for an imported table.
@@ -573,9 +936,44 @@ impl Instance {
         val: TableElement,
     ) -> Result<(), Trap> {
         let import = self.imported_table(table_index);
+        if index < import.from.size() {
+            self.check_provenance(&val, 1)?;
+        }
         import.from.set(index, val)
     }
 
+    /// Apply the table provenance policy of the instance to `count` copies of `element` about
+    /// to be written to a table.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error if `element` is a function reference the instance neither
+    /// defines nor imports, and the policy rejects them.
+    fn check_provenance(&self, element: &TableElement, count: u32) -> Result<(), Trap> {
+        let own_funcrefs = match &self.own_funcrefs {
+            Some(own_funcrefs) => own_funcrefs,
+            None => return Ok(()),
+        };
+        let funcref = match element {
+            TableElement::FuncRef(funcref) if !funcref.is_null() && count != 0 => funcref,
+            _ => return Ok(()),
+        };
+        // SAFETY: non-null function references point to live anyfuncs.
+        if own_funcrefs.contains(unsafe { &***funcref }) {
+            return Ok(());
+        }
+        match self.config.table_provenance_policy {
+            TableProvenancePolicy::Allow => Ok(()),
+            TableProvenancePolicy::Log => {
+                if let Some(sink) = &self.metrics_sink {
+                    sink.increment(Counter::ForeignFuncrefsInstalled, u64::from(count));
+                }
+                Ok(())
+            }
+            TableProvenancePolicy::Reject => Err(Trap::lib(TrapCode::ForeignFuncref)),
+        }
+    }
+
     pub(crate) fn func_ref(&self, function_index: FunctionIndex) -> Option<VMFuncRef> {
         Some(self.get_vm_funcref(function_index))
     }
@@ -605,6 +1003,7 @@ impl Instance {
     ) -> Result<(), Trap> {
         // https://webassembly.github.io/bulk-memory-operations/core/exec/instructions.html#exec-table-init
 
+        self.charge_bulk_table(len)?;
         let table = self.get_table(table_index);
         let passive_elements = self.passive_elements.borrow();
         let elem = passive_elements
@@ -628,6 +1027,38 @@ impl Instance {
         Ok(())
     }
 
+    /// The `table.copy` operation: copies a portion of a table to another, or to itself.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error when either range is out of bounds, or when the provenance
+    /// policy rejects one of the copied elements, in which case nothing is copied.
+    pub(crate) fn table_copy(
+        &self,
+        dst_table_index: TableIndex,
+        src_table_index: TableIndex,
+        dst: u32,
+        src: u32,
+        len: u32,
+    ) -> Result<(), Trap> {
+        self.charge_bulk_table(len)?;
+        let dst_table = self.get_table(dst_table_index);
+        let src_table = self.get_table(src_table_index);
+        let in_bounds = |table: &dyn Table, start: u32| {
+            start
+                .checked_add(len)
+                .map_or(false, |end| end <= table.size())
+        };
+        // Out of bounds copies are left to `copy` to report.
+        if self.own_funcrefs.is_some() && in_bounds(src_table, src) && in_bounds(dst_table, dst) {
+            for index in src..src + len {
+                let element = src_table.get(index).expect("bounds checked above");
+                self.check_provenance(&element, 1)?;
+            }
+        }
+        dst_table.copy(src_table, dst, src, len)
+    }
+
     /// The `table.fill` operation: fills a portion of a table with a given value.
     ///
     /// # Errors
@@ -642,6 +1073,7 @@ impl Instance {
     ) -> Result<(), Trap> {
         // https://webassembly.github.io/bulk-memory-operations/core/exec/instructions.html#exec-table-init
 
+        self.charge_bulk_table(len)?;
         let table = self.get_table(table_index);
         let table_size = table.size() as usize;
 
@@ -651,6 +1083,7 @@ impl Instance {
         {
             return Err(Trap::lib(TrapCode::TableAccessOutOfBounds));
         }
+        self.check_provenance(&item, len)?;
 
         for i in start_index..(start_index + len) {
             table
@@ -685,6 +1118,7 @@ impl Instance {
         len: u32,
     ) -> Result<(), Trap> {
         // https://webassembly.github.io/reference-types/core/exec/instructions.html#exec-memory-copy
+        self.charge_bulk_memory(len)?;
         let memory = unsafe { self.memory_ptr(memory_index).as_ref() };
         // The following memory copy is not synchronized and is not atomic:
         unsafe { memory.memory_copy(dst, src, len) }
@@ -698,6 +1132,7 @@ impl Instance {
         src: u32,
         len: u32,
     ) -> Result<(), Trap> {
+        self.charge_bulk_memory(len)?;
         let import = self.imported_memory(memory_index);
         // The following memory copy is not synchronized and is not atomic:
         unsafe { import.from.vmmemory().as_ref().memory_copy(dst, src, len) }
@@ -715,6 +1150,7 @@ impl Instance {
         val: u32,
         len: u32,
     ) -> Result<(), Trap> {
+        self.charge_bulk_memory(len)?;
         let memory = unsafe { self.memory_ptr(memory_index).as_ref() };
         // The following memory fill is not synchronized and is not atomic:
         unsafe { memory.memory_fill(dst, val, len) }
@@ -732,6 +1168,7 @@ impl Instance {
         val: u32,
         len: u32,
     ) -> Result<(), Trap> {
+        self.charge_bulk_memory(len)?;
         let import = self.imported_memory(memory_index);
         // The following memory fill is not synchronized and is not atomic:
         unsafe { import.from.vmmemory().as_ref().memory_fill(dst, val, len) }
@@ -754,6 +1191,7 @@ impl Instance {
     ) -> Result<(), Trap> {
         // https://webassembly.github.io/bulk-memory-operations/core/exec/instructions.html#exec-memory-init
 
+        self.charge_bulk_memory(len)?;
         let memory = self.memory_definition(memory_index);
         let passive_data = self.passive_data.borrow();
         let data = passive_data.get(&data_index).map_or(&[][..], |d| &**d);
@@ -768,6 +1206,9 @@ impl Instance {
         if oob_access {
             return Err(Trap::lib(TrapCode::HeapAccessOutOfBounds));
         }
+        if len != 0 && is_read_only(memory.base) {
+            return Err(Trap::lib(TrapCode::ReadOnlyInstance));
+        }
         let src_slice = &data[src as usize..(src + len) as usize];
         unsafe {
             let dst_start = memory.base.add(dst as usize);
@@ -847,31 +1288,74 @@ impl InstanceHandle {
         finished_globals: BoxedSlice<LocalGlobalIndex, Arc<Global>>,
         imports: Imports,
         passive_data: BTreeMap<DataIndex, Arc<[u8]>>,
+        external_data: Vec<Vec<u8>>,
+        metrics_sink: Option<Arc<dyn MetricsSink>>,
+        diagnostics_sink: Option<Arc<dyn DiagnosticsSink>>,
+        diagnostics_level: DiagnosticsLevel,
+        memory_arbiter: Option<Arc<dyn MemoryArbiter>>,
+        call_sequence_global: Option<Arc<Global>>,
         host_state: Box<dyn Any>,
         imported_function_envs: BoxedSlice<FunctionIndex, ImportFunctionEnv>,
-        instance_config: InstanceConfig,
+        mut instance_config: InstanceConfig,
     ) -> Self {
+        let extensions = mem::take(&mut instance_config.extensions);
         let vmctx_globals = finished_globals
             .values()
             .map(|m| m.vmglobal())
             .collect::<PrimaryMap<LocalGlobalIndex, _>>()
             .into_boxed_slice();
+        let initial_passive_data = passive_data.clone();
         let passive_data = RefCell::new(passive_data);
+        if let Some(sink) = &metrics_sink {
+            sink.increment(Counter::InstancesCreated, 1);
+        }
+        let id = InstanceId::next();
+        let finished_memories = match memory_arbiter {
+            Some(arbiter) => finished_memories
+                .values()
+                .map(|memory| -> Arc<dyn Memory> {
+                    Arc::new(ArbitratedMemory::new(
+                        Arc::clone(memory),
+                        Arc::clone(&arbiter),
+                        id,
+                        instance_config.tenant,
+                        instance_config.partial_memory_grants,
+                    ))
+                })
+                .collect::<PrimaryMap<LocalMemoryIndex, _>>()
+                .into_boxed_slice(),
+            None => finished_memories,
+        };
 
         let handle = {
             // use dummy value to create an instance so we can get the vmctx pointer
             let funcrefs = PrimaryMap::new().into_boxed_slice();
             // Create the `Instance`. The unique, the One.
             let instance = Instance {
-                artifact,
+                artifact: ManuallyDrop::new(artifact),
+                retired_artifacts: Vec::new(),
                 config: instance_config.clone(),
                 memories: finished_memories,
                 tables: finished_tables,
                 globals: finished_globals,
                 passive_elements: Default::default(),
                 passive_data,
+                initial_passive_data,
+                external_data,
+                data_segments_applied: AtomicBool::new(false),
                 host_state,
+                extensions: RwLock::new(Arc::new(extensions)),
+                metrics_sink,
+                diagnostics_sink,
+                function_bodies: None,
+                call_sequence: AtomicU64::new(0),
+                call_sequence_global,
+                active_calls: AtomicUsize::new(0),
+                poisoned: AtomicBool::new(false),
                 funcrefs,
+                id,
+                own_funcrefs: None,
+                imports,
                 imported_function_envs,
                 vmctx: VMContext {},
             };
@@ -883,14 +1367,33 @@ impl InstanceHandle {
                 let instance = instance_ref.as_mut().unwrap();
                 let vmctx_ptr = instance.vmctx_ptr();
                 instance.funcrefs = build_funcrefs(
-                    &imports,
+                    &instance.imports,
                     instance.artifact.functions().iter().map(|(_, f)| f),
                     vmctx_ptr,
                 );
+                if instance_config.table_provenance_policy != TableProvenancePolicy::Allow {
+                    instance.own_funcrefs = Some(instance.funcrefs.values().copied().collect());
+                }
+                if instance.diagnostics_sink.is_some() {
+                    let imported = instance.artifact.import_counts().functions as usize;
+                    instance.function_bodies = Some(
+                        instance
+                            .funcrefs
+                            .iter()
+                            .skip(imported)
+                            .map(|(index, funcref)| (funcref.func_ptr as usize, index))
+                            .collect(),
+                    );
+                }
+                provenance::register(vmctx_ptr, instance.id);
                 *(instance.trap_catcher_ptr()) = get_trap_handler();
                 *(instance.gas_counter_ptr()) = instance_config.gas_counter;
                 *(instance.stack_limit_ptr()) = instance_config.stack_limit;
                 *(instance.stack_limit_initial_ptr()) = instance_config.stack_limit;
+                instance
+                    .diagnostics_level()
+                    .store(diagnostics_level as u8, Ordering::Relaxed);
+                instance.cancellations_pending().store(0, Ordering::Relaxed);
             }
 
             Self {
@@ -906,24 +1409,24 @@ impl InstanceHandle {
         );
 
         ptr::copy(
-            imports.functions.values().as_slice().as_ptr(),
+            instance.imports.functions.values().as_slice().as_ptr(),
             instance.imported_functions_ptr() as *mut VMFunctionImport,
-            imports.functions.len(),
+            instance.imports.functions.len(),
         );
         ptr::copy(
-            imports.tables.values().as_slice().as_ptr(),
+            instance.imports.tables.values().as_slice().as_ptr(),
             instance.imported_tables_ptr() as *mut VMTableImport,
-            imports.tables.len(),
+            instance.imports.tables.len(),
         );
         ptr::copy(
-            imports.memories.values().as_slice().as_ptr(),
+            instance.imports.memories.values().as_slice().as_ptr(),
             instance.imported_memories_ptr() as *mut VMMemoryImport,
-            imports.memories.len(),
+            instance.imports.memories.len(),
         );
         ptr::copy(
-            imports.globals.values().as_slice().as_ptr(),
+            instance.imports.globals.values().as_slice().as_ptr(),
             instance.imported_globals_ptr() as *mut VMGlobalImport,
-            imports.globals.len(),
+            instance.imports.globals.len(),
         );
         // these should already be set, add asserts here? for:
         // - instance.tables_ptr() as *mut VMTableDefinition
@@ -960,10 +1463,10 @@ impl InstanceHandle {
 
         // Apply the initializers.
         initialize_tables(instance)?;
-        initialize_memories(
-            instance,
-            instance.artifact.data_segments().iter().map(Into::into),
-        )?;
+        if !instance.data_segments_applied.load(Ordering::SeqCst) {
+            initialize_data_segments(instance)?;
+        }
+        pretouch::pretouch(instance);
 
         // The WebAssembly spec specifies that the start function is
         // invoked automatically at instantiation time.
@@ -971,6 +1474,36 @@ impl InstanceHandle {
         Ok(())
     }
 
+    /// Make [`InstanceHandle::finish_instantiation`] leave the local memories alone instead
+    /// of applying the data segments to them.
+    ///
+    /// # Safety
+    ///
+    /// The local memories must already hold the data segments, for example by mapping an
+    /// image captured after applying them.
+    pub unsafe fn skip_data_segments(&self) {
+        self.instance()
+            .as_ref()
+            .data_segments_applied
+            .store(true, Ordering::SeqCst);
+    }
+
+    /// Finishes the instantiation process started by `Instance::new` for an instance whose
+    /// memories are read-only images that already hold the data segments.
+    ///
+    /// # Safety
+    ///
+    /// Only safe to call immediately after instantiation.
+    pub unsafe fn finish_readonly_instantiation(&self) -> Result<(), Trap> {
+        let instance = self.instance().as_ref();
+        initialize_tables(instance)?;
+        // The images hold the data segments, as `skip_data_segments` requires.
+        instance.data_segments_applied.store(true, Ordering::SeqCst);
+        pretouch::pretouch(instance);
+        instance.invoke_start_function()?;
+        Ok(())
+    }
+
     /// See [`traphandlers::wasmer_call_trampoline`].
     pub unsafe fn invoke_function(
         &self,
@@ -980,11 +1513,160 @@ impl InstanceHandle {
         values_vec: *mut u8,
     ) -> Result<(), Trap> {
         // `vmctx` is always `*mut VMContext` here, as we call to WASM.
+        let instance = self.instance().as_ref();
+        instance.reset_stack_meter();
+        instance.enter(|| wasmer_call_trampoline(vmctx, trampoline, callee, values_vec))
+    }
+
+    /// Return the number of calls the host made into this instance, which is also the call
+    /// sequence number of the call in progress, if any.
+    ///
+    /// See [`InstanceConfig::with_call_sequence_global`].
+    pub fn call_sequence(&self) -> u64 {
+        self.instance()
+            .as_ref()
+            .call_sequence
+            .load(Ordering::SeqCst)
+    }
+
+    /// Return the identifier of this instance, which is the [`FuncOrigin`] of the functions
+    /// it defines.
+    ///
+    /// [`FuncOrigin`]: crate::FuncOrigin
+    pub fn id(&self) -> InstanceId {
+        self.instance().as_ref().id
+    }
+
+    /// Return a snapshot of the resources this instance uses.
+    pub fn usage(&self) -> InstanceUsage {
+        self.instance().as_ref().usage()
+    }
+
+    /// Return the gas counter of this instance, which outlives it, see
+    /// [`InstanceConfig::with_counter`].
+    pub fn gas_counter(&self) -> *mut FastGasCounter {
+        self.instance().as_ref().config.gas_counter
+    }
+
+    /// Return the gas left to this instance, that is the limit of its gas counter minus the
+    /// gas burnt so far. The instance traps with `GasExceeded` once it has none left.
+    pub fn remaining_gas(&self) -> u64 {
+        // The counter outlives the instance, see `InstanceConfig::with_counter`.
+        let counter = unsafe { &*self.gas_counter() };
+        counter.gas_limit.saturating_sub(counter.burnt_gas)
+    }
+
+    /// Leave `remaining` gas to this instance by moving the limit of its gas counter, such as
+    /// to charge the host for each call.
+    ///
+    /// The gas burnt so far is left as it is.
+    pub fn set_remaining_gas(&self, remaining: u64) {
+        let counter = unsafe { &mut *self.gas_counter() };
+        counter.gas_limit = counter.burnt_gas.saturating_add(remaining);
+    }
+
+    /// Switch this instance to the code of `artifact`, keeping its memories, tables and
+    /// globals as they are.
+    ///
+    /// The function references of the instance are updated in place, so that the tables and
+    /// globals holding them, and the functions looked up afterwards, run the new code.
+    /// Functions looked up before, and the instances importing them, keep running the code
+    /// of the previous artifact, which is kept alive with the instance. Calls starting on
+    /// other threads wait for the rebind to complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RebindError::CallInFlight`] if a call into the instance is running, and
+    /// [`RebindError::LayoutMismatch`] if `artifact` lays out the `VMContext` differently.
+    ///
+    /// # Safety
+    ///
+    /// `artifact` must be compiled from the same module as the artifact of the instance, for
+    /// memories and tables of the same styles.
+    pub unsafe fn rebind(&mut self, artifact: Arc<dyn Artifact>) -> Result<(), RebindError> {
+        let instance = self.instance.as_ref();
+        if artifact.offsets() != instance.offsets() {
+            return Err(RebindError::LayoutMismatch(format!(
+                "{:?} instead of {:?}",
+                artifact.offsets(),
+                instance.offsets()
+            )));
+        }
+        if artifact.functions().len() != instance.artifact.functions().len() {
+            return Err(RebindError::LayoutMismatch(format!(
+                "{} local functions instead of {}",
+                artifact.functions().len(),
+                instance.artifact.functions().len()
+            )));
+        }
+        if let Err(active_calls) =
+            instance
+                .active_calls
+                .compare_exchange(0, EXCLUSIVE, Ordering::SeqCst, Ordering::SeqCst)
         {
-            let instance = self.instance().as_ref();
-            instance.reset_stack_meter();
+            return Err(RebindError::CallInFlight { active_calls });
         }
-        wasmer_call_trampoline(vmctx, trampoline, callee, values_vec)
+        let instance = self.instance.as_mut_unchecked();
+        let imported = instance.artifact.import_counts().functions as usize;
+        for (index, function) in artifact.functions().iter() {
+            // The type index is left alone: it was registered with the engine of the
+            // instance, whose signature ids the `VMContext` holds.
+            let function_index = FunctionIndex::new(imported + index.index());
+            instance.funcrefs[function_index].func_ptr = *function.body;
+            // The previous bodies may still be entered through copies of their references.
+            if let Some(function_bodies) = &mut instance.function_bodies {
+                function_bodies.insert(*function.body as usize, function_index);
+            }
+        }
+        // Copies of the previous function references are still the instance's own.
+        if let Some(own_funcrefs) = &mut instance.own_funcrefs {
+            own_funcrefs.extend(instance.funcrefs.values().copied());
+        }
+        let previous = mem::replace(&mut *instance.artifact, artifact);
+        instance.retired_artifacts.push(previous);
+        instance.active_calls.store(0, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Return a reference to this instance that does not keep it alive.
+    pub fn downgrade(&self) -> WeakInstanceRef {
+        self.instance().downgrade()
+    }
+
+    /// Return the number of references keeping this instance alive, including this handle,
+    /// such as those of the exports looked up from it.
+    pub fn strong_count(&self) -> usize {
+        self.instance().strong_count()
+    }
+
+    /// Return the number of bytes allocated for the buffer holding the `VMContext` of this
+    /// instance and for its array of `VMCallerCheckedAnyfunc`s.
+    ///
+    /// See [`InstanceLayout::total_bytes`], which computes the same without instantiating.
+    pub fn allocated_bytes(&self) -> usize {
+        let instance = self.instance();
+        instance.buffer_layout().size()
+            + instance.as_ref().funcrefs.len() * mem::size_of::<VMCallerCheckedAnyfunc>()
+    }
+
+    /// Return the diagnostics level of this instance.
+    pub fn diagnostics_level(&self) -> DiagnosticsLevel {
+        let level = self.instance().as_ref().diagnostics_level();
+        DiagnosticsLevel::from_u8(level.load(Ordering::Relaxed))
+    }
+
+    /// Change the diagnostics level of this instance, taking effect at the next function
+    /// entry or memory access, including in calls already running.
+    pub fn set_diagnostics_level(&self, level: DiagnosticsLevel) {
+        let instance = self.instance().as_ref();
+        instance
+            .diagnostics_level()
+            .store(level as u8, Ordering::Relaxed);
+    }
+
+    /// Return the cancellation state of a new call into this instance.
+    pub fn new_call_cancellation(&self) -> Arc<CallCancellation> {
+        CallCancellation::new(self.instance().clone())
     }
 
     /// Return a reference to the vmctx used by compiled wasm code.
@@ -1012,9 +1694,11 @@ impl InstanceHandle {
             match instance.artifact.import_counts().local_function_index(idx) {
                 Ok(local) => {
                     let func = instance.artifact.functions().get(local)?;
+                    // The signature is that of the engine of the instance, which may not be
+                    // that of the artifact after a rebind.
                     (
                         *(func.body),
-                        func.signature,
+                        instance.funcrefs[idx].type_index,
                         VMFunctionEnvironment {
                             vmctx: instance.vmctx_ptr(),
                         },
@@ -1095,11 +1779,49 @@ impl InstanceHandle {
         })
     }
 
+    /// Return the memory of the given index, exported or not, if the instance has it.
+    ///
+    /// Unlike exports, the memory does not keep the instance alive.
+    pub fn memory(&self, index: MemoryIndex) -> Option<Arc<dyn Memory>> {
+        let instance = self.instance.as_ref();
+        match instance.artifact.import_counts().local_memory_index(index) {
+            Ok(local) => instance.memories.get(local).cloned(),
+            Err(import) => Some(Arc::clone(&instance.imported_memory(import).from)),
+        }
+    }
+
+    /// Return the index of the imported function whose host environment is `host_env`, if
+    /// there is one.
+    pub fn imported_function_with_env(&self, host_env: *mut ffi::c_void) -> Option<FunctionIndex> {
+        let instance = self.instance.as_ref();
+        instance
+            .imported_function_envs
+            .iter()
+            .find_map(|(index, env)| match env {
+                ImportFunctionEnv::Env { env, .. } if *env == host_env => Some(index),
+                _ => None,
+            })
+    }
+
     /// Return a reference to the custom state attached to this instance.
     pub fn host_state(&self) -> &dyn Any {
         self.instance().as_ref().host_state()
     }
 
+    /// Return a snapshot of the extensions attached to this instance.
+    pub fn extensions(&self) -> Arc<Extensions> {
+        self.instance().as_ref().extensions()
+    }
+
+    /// Attach an extension value to this instance, returning the value of the same type it
+    /// replaces.
+    ///
+    /// Calls already in progress keep observing the extensions as they were when the call
+    /// started.
+    pub fn insert_extension<T: Send + Sync + 'static>(&self, value: T) -> Option<Arc<T>> {
+        self.instance().as_ref().insert_extension(value)
+    }
+
     /// Return the memory index for the given `VMMemoryDefinition` in this instance.
     pub fn memory_index(&self, memory: &VMMemoryDefinition) -> LocalMemoryIndex {
         self.instance().as_ref().memory_index(memory)
@@ -1107,8 +1829,8 @@ impl InstanceHandle {
 
     /// Grow memory in this instance by the specified amount of pages.
     ///
-    /// Returns `None` if memory can't be grown by the specified amount
-    /// of pages.
+    /// Returns an error if memory can't be grown by the specified amount
+    /// of pages, or if doing so exceeds the gas limit of the instance.
     pub fn memory_grow<IntoPages>(
         &self,
         memory_index: LocalMemoryIndex,
@@ -1128,7 +1850,7 @@ impl InstanceHandle {
     /// Grow table in this instance by the specified amount of pages.
     ///
     /// Returns `None` if memory can't be grown by the specified amount
-    /// of pages.
+    /// of pages, or if doing so exceeds the gas limit of the instance.
     pub fn table_grow(
         &self,
         table_index: LocalTableIndex,
@@ -1138,6 +1860,7 @@ impl InstanceHandle {
         self.instance()
             .as_ref()
             .table_grow(table_index, delta, init_value)
+            .unwrap_or(None)
     }
 
     /// Get table element reference.
@@ -1288,6 +2011,34 @@ fn initialize_passive_elements(instance: &Instance) {
     );
 }
 
+/// Apply the data segments of the module, inline and external ones, to the memories.
+fn initialize_data_segments(instance: &Instance) -> Result<(), Trap> {
+    let mut inline_segments = instance.artifact.data_segments().iter();
+    let mut external_segments = instance
+        .artifact
+        .external_data_segments()
+        .iter()
+        .zip(instance.external_data.iter())
+        .peekable();
+    // Interleave both kinds of segments back into module order, as later
+    // segments may overwrite earlier ones.
+    let mut segment_index = 0;
+    let data_segments = std::iter::from_fn(|| {
+        let init = match external_segments.peek() {
+            Some((init, _)) if init.segment_index == segment_index => external_segments
+                .next()
+                .map(|(init, data)| DataInitializer {
+                    location: init.location.clone(),
+                    data: &data[..],
+                }),
+            _ => inline_segments.next().map(Into::into),
+        };
+        segment_index += 1;
+        init
+    });
+    initialize_memories(instance, data_segments)
+}
+
 /// Initialize the table memory from the provided initializers.
 fn initialize_memories<'a>(
     instance: &Instance,
@@ -1308,7 +2059,7 @@ fn initialize_memories<'a>(
             let mem_slice = get_memory_slice(&init, instance);
             let end = start + init.data.len();
             let to_init = &mut mem_slice[start..end];
-            to_init.copy_from_slice(init.data);
+            nontemporal::copy(to_init, init.data);
         }
     }
 
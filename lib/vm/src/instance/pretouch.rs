@@ -0,0 +1,129 @@
+//! Touching the pages of the memories and tables of an instance once instantiated, so that its
+//! first calls do not pay for faulting them in.
+
+use super::Instance;
+use crate::readonly_memory::is_read_only;
+use crate::table::RawTableElement;
+use crate::{Counter, Timer};
+use std::convert::TryFrom;
+use std::mem;
+use std::ptr;
+use std::time::Instant;
+use wasmer_types::{DataInitializerLocation, PretouchMode, PretouchPolicy};
+
+/// A range of addresses to touch, and whether its pages may be written to.
+struct Range {
+    start: usize,
+    end: usize,
+    writable: bool,
+}
+
+/// Touch the pages of `instance` its pretouch policy asks for, and report them to its metrics
+/// sink.
+pub(super) fn pretouch(instance: &Instance) {
+    let policy = instance.config.pretouch;
+    if policy == PretouchPolicy::default() {
+        return;
+    }
+    let started = Instant::now();
+    let mut ranges = Vec::new();
+    match policy.memory {
+        PretouchMode::None => {}
+        PretouchMode::WrittenPagesOnly => {
+            let artifact = &instance.artifact;
+            let inline = artifact
+                .data_segments()
+                .iter()
+                .map(|init| (&init.location, init.data.len()));
+            let external = artifact
+                .external_data_segments()
+                .iter()
+                .map(|init| (&init.location, init.len));
+            for (location, len) in inline.chain(external) {
+                ranges.extend(segment_range(instance, location, len));
+            }
+        }
+        PretouchMode::AllMinimumPages => {
+            for memory in instance.memories.values() {
+                let memory = unsafe { memory.vmmemory().as_ref() };
+                let start = memory.base as usize;
+                ranges.push(Range {
+                    start,
+                    end: start + memory.current_length,
+                    writable: !is_read_only(memory.base),
+                });
+            }
+        }
+    }
+    if policy.tables {
+        for table in instance.tables.values() {
+            let table = unsafe { table.vmtable().as_ref() };
+            let start = table.base as usize;
+            let len = table.current_elements as usize * mem::size_of::<RawTableElement>();
+            ranges.push(Range {
+                start,
+                end: start + len,
+                writable: true,
+            });
+        }
+    }
+    let pages = unsafe { touch(ranges) };
+    if let Some(sink) = &instance.metrics_sink {
+        sink.increment(Counter::PagesPretouched, pages);
+        sink.observe(Timer::Pretouch, started.elapsed());
+    }
+}
+
+/// The addresses an active data segment of `len` bytes at `location` was written to, if they
+/// are within its memory.
+fn segment_range(
+    instance: &Instance,
+    location: &DataInitializerLocation,
+    len: usize,
+) -> Option<Range> {
+    let memory = instance.memory_definition(location.memory_index);
+    let mut offset = location.offset;
+    if let Some(base) = location.base {
+        offset += usize::try_from(instance.global(base).to_u32()).unwrap();
+    }
+    let end = offset.checked_add(len)?;
+    if len == 0 || end > memory.current_length {
+        return None;
+    }
+    let start = memory.base as usize;
+    Some(Range {
+        start: start + offset,
+        end: start + end,
+        // Reading is enough to fault in pages the segment was not written to.
+        writable: false,
+    })
+}
+
+/// Touch one byte of every page overlapping `ranges`, writing it back to the pages that may be
+/// written to, and return the number of pages touched.
+///
+/// # Safety
+///
+/// The ranges must be mapped, and those marked writable not be accessed concurrently.
+unsafe fn touch(mut ranges: Vec<Range>) -> u64 {
+    let page_size = region::page::size();
+    ranges.sort_unstable_by_key(|range| range.start);
+    let mut pages = 0;
+    // The first page not touched yet, so that overlapping ranges touch their pages once.
+    let mut next = 0;
+    for range in ranges {
+        let mut page = range.start / page_size * page_size;
+        page = page.max(next);
+        while page < range.end {
+            let byte = page.max(range.start) as *mut u8;
+            let value = ptr::read_volatile(byte);
+            if range.writable {
+                ptr::write_volatile(byte, value);
+            }
+            pages += 1;
+            page += page_size;
+        }
+        next = next.max(page);
+    }
+    pages
+}
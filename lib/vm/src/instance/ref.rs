@@ -1,8 +1,10 @@
-use super::Instance;
+use super::{Instance, InstanceArena, InstanceUsage};
+use crate::trap::Trap;
 use std::alloc::Layout;
 use std::convert::TryFrom;
 use std::ptr::{self, NonNull};
 use std::sync::{Arc, Weak};
+use wasmer_types::Extensions;
 
 /// Dynamic instance allocation.
 ///
@@ -30,6 +32,9 @@ struct InstanceInner {
     /// No one in the code has a copy of the `Instance`'s
     /// pointer. `Self` is the only one.
     instance: NonNull<Instance>,
+
+    /// Where to return the allocation of `instance` instead of freeing it.
+    arena: Option<Arc<InstanceArena>>,
 }
 
 impl InstanceInner {
@@ -43,7 +48,11 @@ impl InstanceInner {
         let instance_ptr = self.instance.as_ptr();
 
         ptr::drop_in_place(instance_ptr);
-        std::alloc::dealloc(instance_ptr as *mut u8, self.instance_layout);
+        InstanceArena::release(
+            self.arena.as_deref(),
+            self.instance.cast(),
+            self.instance_layout,
+        );
     }
 
     /// Get a reference to the `Instance`.
@@ -113,10 +122,15 @@ impl InstanceRef {
     /// and correctly initialized pointer to `Instance`. See
     /// [`InstanceAllocator`] for an example of how to correctly use
     /// this API.
-    pub(super) unsafe fn new(instance: NonNull<Instance>, instance_layout: Layout) -> Self {
+    pub(super) unsafe fn new(
+        instance: NonNull<Instance>,
+        instance_layout: Layout,
+        arena: Option<Arc<InstanceArena>>,
+    ) -> Self {
         Self(Arc::new(InstanceInner {
             instance_layout,
             instance,
+            arena,
         }))
     }
 
@@ -126,6 +140,26 @@ impl InstanceRef {
         (&*self.0).as_ref()
     }
 
+    /// Return a snapshot of the resources the instance uses.
+    pub fn usage(&self) -> InstanceUsage {
+        self.as_ref().usage()
+    }
+
+    /// Return a reference to the instance that does not keep it alive.
+    pub fn downgrade(&self) -> WeakInstanceRef {
+        WeakInstanceRef(Arc::downgrade(&self.0))
+    }
+
+    /// Return the number of references keeping the instance alive, including this one.
+    pub fn strong_count(&self) -> usize {
+        Arc::strong_count(&self.0)
+    }
+
+    /// The layout of the buffer holding the `Instance`.
+    pub(super) fn buffer_layout(&self) -> Layout {
+        self.0.instance_layout
+    }
+
     /// Only succeeds if ref count is 1.
     #[inline]
     pub(super) fn as_mut(&mut self) -> Option<&mut Instance> {
@@ -188,11 +222,41 @@ impl WeakOrStrongInstanceRef {
         }
     }
 
+    /// Run `closure` as a call from the host into the referenced instance, making its
+    /// extensions available to host functions and advancing its call sequence number.
+    ///
+    /// `closure` runs without entering any instance if the instance has already been dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`TrapCode::PoisonedInstance`] trap, without running `closure`, if a restore
+    /// of the instance failed.
+    ///
+    /// [`TrapCode::PoisonedInstance`]: crate::TrapCode::PoisonedInstance
+    pub fn enter<R>(&self, closure: impl FnOnce() -> Result<R, Trap>) -> Result<R, Trap> {
+        match self {
+            Self::Weak(weak) => match weak.upgrade() {
+                Some(strong) => strong.as_ref().enter(closure),
+                None => closure(),
+            },
+            Self::Strong(strong) => strong.as_ref().enter(closure),
+        }
+    }
+
+    /// Return a snapshot of the extensions of the referenced instance, or `None` if the
+    /// instance has already been dropped.
+    pub fn extensions(&self) -> Option<Arc<Extensions>> {
+        match self {
+            Self::Weak(weak) => Some(weak.upgrade()?.as_ref().extensions()),
+            Self::Strong(strong) => Some(strong.as_ref().extensions()),
+        }
+    }
+
     /// Clones self into a weak reference.
     pub fn downgrade(&self) -> Self {
         match self {
             Self::Weak(weak) => Self::Weak(weak.clone()),
-            Self::Strong(strong) => Self::Weak(WeakInstanceRef(Arc::downgrade(&strong.0))),
+            Self::Strong(strong) => Self::Weak(strong.downgrade()),
         }
     }
 }
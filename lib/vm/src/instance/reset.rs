@@ -0,0 +1,129 @@
+//! Resets of instances to the state they had when instantiated, to reuse them for another
+//! run without paying for a new instantiation.
+
+use super::{
+    initialize_data_segments, initialize_globals, initialize_passive_elements, initialize_tables,
+    InstanceHandle,
+};
+use crate::memory::MemoryError;
+use crate::trap::Trap;
+use std::sync::atomic::Ordering;
+use thiserror::Error;
+use wasmer_types::{Classify, ErrorCode, FailureKind, HasErrorCode, Type};
+
+/// An error resetting an instance, carried by the [`Trap::User`] that
+/// [`InstanceHandle::reset`] returns.
+#[derive(Error, Debug)]
+pub enum ResetError {
+    /// A call into the instance is running, which would see its state change under it.
+    #[error(
+        "{active_calls} call(s) into the instance are running [{}]",
+        ErrorCode::ResetCallInFlight
+    )]
+    CallInFlight {
+        /// The number of calls running, including those nested in host functions.
+        active_calls: usize,
+    },
+    /// A memory the instance defines could not be reset.
+    #[error(
+        "memory {memory} of the instance could not be reset: {error} [{}]",
+        ErrorCode::ResetMemory
+    )]
+    Memory {
+        /// The index of the memory among the memories the instance defines.
+        memory: u32,
+        /// Why the memory could not be reset.
+        error: MemoryError,
+    },
+}
+
+impl HasErrorCode for ResetError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::CallInFlight { .. } => ErrorCode::ResetCallInFlight,
+            Self::Memory { .. } => ErrorCode::ResetMemory,
+        }
+    }
+}
+
+impl Classify for ResetError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            // The reset succeeds once the calls return.
+            Self::CallInFlight { .. } => FailureKind::TransientInterrupted,
+            Self::Memory { error, .. } => error.failure_kind(),
+        }
+    }
+}
+
+impl InstanceHandle {
+    /// Bring the memories, tables and globals the instance defines back to the state they
+    /// had once the segments of the module were applied, and run the start function again
+    /// if `run_start` is set.
+    ///
+    /// Memories and tables shrink back to their minimum size. Memories mapping an image
+    /// copy-on-write map it again, others are zeroed and get the data segments applied
+    /// again. Mutable globals get their initializers again, and dropped passive segments are
+    /// back. Segments into imported memories and tables are applied again, as when the
+    /// instance was created, but they are not reset otherwise, and neither are imported
+    /// globals.
+    ///
+    /// Calls into the instance wait for the reset to complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`Trap::User`] holding a [`ResetError::CallInFlight`] if a call into the
+    /// instance is running, which leaves the instance as it was, and the trap of the start
+    /// function if it traps.
+    ///
+    /// Other errors, such as a [`ResetError::Memory`], leave the instance poisoned: calls
+    /// into it trap with [`TrapCode::PoisonedInstance`] until a reset or a restore succeeds.
+    ///
+    /// [`TrapCode::PoisonedInstance`]: crate::TrapCode::PoisonedInstance
+    pub fn reset(&self, run_start: bool) -> Result<(), Trap> {
+        let instance = self.instance().as_ref();
+        let exclusive = instance.exclusive().map_err(|active_calls| {
+            Trap::User(Box::new(ResetError::CallInFlight { active_calls }))
+        })?;
+        instance.poisoned.store(true, Ordering::SeqCst);
+
+        let mut images_restored = true;
+        for (index, memory) in instance.memories.iter() {
+            let restored = memory.reset().map_err(|error| {
+                Trap::User(Box::new(ResetError::Memory {
+                    memory: index.as_u32(),
+                    error,
+                }))
+            })?;
+            images_restored &= restored;
+        }
+        for global in instance.globals.values() {
+            let ty = global.ty();
+            // Constant globals keep the references they were initialized with.
+            if ty.mutability.is_mutable() && ty.ty == Type::ExternRef {
+                // SAFETY: no call into the instance is running.
+                unsafe { global.vmglobal().as_mut().as_externref_mut().ref_drop() };
+            }
+        }
+        initialize_globals(instance);
+        for table in instance.tables.values() {
+            table.reset();
+        }
+        instance.passive_elements.borrow_mut().clear();
+        initialize_passive_elements(instance);
+        *instance.passive_data.borrow_mut() = instance.initial_passive_data.clone();
+        initialize_tables(instance)?;
+        // Memories that mapped their image again hold the data segments already.
+        if !(images_restored && instance.data_segments_applied.load(Ordering::SeqCst)) {
+            initialize_data_segments(instance)?;
+        }
+        instance.poisoned.store(false, Ordering::SeqCst);
+        // The start function enters the instance, which waits for the reset to complete.
+        drop(exclusive);
+
+        if run_start {
+            instance.invoke_start_function()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,656 @@
+//! Snapshots of the memories, tables and globals an instance defines, streamed to and from
+//! bytes.
+//!
+//! A snapshot is laid out as follows, integers being little-endian:
+//!
+//! * a header: the magic `\0wsn`, a version byte, the numbers of memories, tables and globals
+//!   as `u32`s, the size in pages of each memory and in elements of each table as `u32`s, and
+//!   the type of each global as a byte;
+//! * the value of each global, as 16 bytes for numbers and vectors or as a reference;
+//! * the elements of each table, as references;
+//! * for each memory, the pages not made of zeros only, as the `u32` index of the page, the
+//!   SHA-256 of its contents and its contents, followed by `u32::MAX`;
+//! * the SHA-256 of everything before it.
+//!
+//! A reference is the `u32` index of a function of the instance, or `u32::MAX` for null.
+
+use super::{Instance, InstanceHandle};
+use crate::func_data_registry::VMFuncRef;
+use crate::global::Global;
+use crate::nontemporal;
+use crate::readonly_memory::is_read_only;
+use crate::table::TableElement;
+use crate::vmcontext::VMCallerCheckedAnyfunc;
+use sha2::{Digest, Sha256};
+use std::io::{self, BufWriter, Read, Write};
+use std::mem;
+use std::slice;
+use std::sync::atomic::Ordering;
+use thiserror::Error;
+use wasmer_types::entity::EntityRef;
+use wasmer_types::{
+    Classify, ErrorCode, ExternRef, FailureKind, FunctionIndex, HasErrorCode, LocalMemoryIndex,
+    Pages, Type, VMExternRef, WASM_PAGE_SIZE,
+};
+
+const MAGIC: [u8; 4] = *b"\0wsn";
+const VERSION: u8 = 1;
+
+/// Ends the pages of a memory.
+const END_OF_PAGES: u32 = u32::MAX;
+
+/// A null reference.
+const NULL: u32 = u32::MAX;
+
+/// How to write a snapshot of an instance.
+#[derive(Clone, Debug)]
+pub struct SnapshotOptions {
+    buffer_size: usize,
+}
+
+impl SnapshotOptions {
+    /// Write through a buffer of 64 KiB.
+    pub fn new() -> Self {
+        Self {
+            buffer_size: 64 << 10,
+        }
+    }
+
+    /// Write through a buffer of `bytes` bytes, which bounds the memory a snapshot takes on
+    /// top of that of the instance.
+    pub fn with_buffer_size(mut self, bytes: usize) -> Self {
+        self.buffer_size = bytes;
+        self
+    }
+}
+
+impl Default for SnapshotOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What a snapshot of an instance wrote.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SnapshotStats {
+    /// Bytes written, including the header and the hash.
+    pub bytes: u64,
+    /// Memory pages written.
+    pub pages: u64,
+    /// Memory pages left out because they only hold zeros.
+    pub zero_pages: u64,
+}
+
+/// An error writing or restoring a snapshot of an instance.
+#[derive(Error, Debug)]
+pub enum SnapshotError {
+    /// Reading or writing the snapshot failed.
+    #[error("{0} [{}]", ErrorCode::SnapshotIo)]
+    Io(#[from] io::Error),
+    /// A call into the instance is running, which would change its state under the snapshot.
+    #[error(
+        "{active_calls} call(s) into the instance are running [{}]",
+        ErrorCode::SnapshotCallInFlight
+    )]
+    CallInFlight {
+        /// The number of calls running, including those nested in host functions.
+        active_calls: usize,
+    },
+    /// A restore of the instance failed, so it has no state to snapshot until a restore
+    /// succeeds.
+    #[error(
+        "the instance is poisoned by a failed restore [{}]",
+        ErrorCode::SnapshotPoisoned
+    )]
+    Poisoned,
+    /// The instance holds state snapshots cannot represent.
+    #[error("snapshots cannot represent {0} [{}]", ErrorCode::SnapshotUnsupported)]
+    Unsupported(String),
+    /// The bytes are not a snapshot, or one of a version this runtime does not read.
+    #[error("not a snapshot: {0} [{}]", ErrorCode::SnapshotFormat)]
+    Format(String),
+    /// The snapshot is of an instance of another module, or of one that grew larger.
+    #[error(
+        "the snapshot does not fit the instance: {0} [{}]",
+        ErrorCode::SnapshotMismatch
+    )]
+    Mismatch(String),
+    /// A memory page does not match its checksum.
+    #[error(
+        "page {page} of memory {memory} does not match its checksum [{}]",
+        ErrorCode::SnapshotCorruptPage
+    )]
+    CorruptPage {
+        /// The index of the memory among the memories the instance defines.
+        memory: u32,
+        /// The index of the page in the memory.
+        page: u32,
+    },
+    /// The snapshot is truncated, or does not match its hash.
+    #[error("the snapshot is corrupt: {0} [{}]", ErrorCode::SnapshotCorrupt)]
+    Corrupt(String),
+}
+
+impl HasErrorCode for SnapshotError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Io(_) => ErrorCode::SnapshotIo,
+            Self::CallInFlight { .. } => ErrorCode::SnapshotCallInFlight,
+            Self::Poisoned => ErrorCode::SnapshotPoisoned,
+            Self::Unsupported(_) => ErrorCode::SnapshotUnsupported,
+            Self::Format(_) => ErrorCode::SnapshotFormat,
+            Self::Mismatch(_) => ErrorCode::SnapshotMismatch,
+            Self::CorruptPage { .. } => ErrorCode::SnapshotCorruptPage,
+            Self::Corrupt(_) => ErrorCode::SnapshotCorrupt,
+        }
+    }
+}
+
+impl Classify for SnapshotError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::Io(e) => e.failure_kind(),
+            // The snapshot or restore succeeds once the calls return.
+            Self::CallInFlight { .. } => FailureKind::TransientInterrupted,
+            Self::CorruptPage { .. } | Self::Corrupt(_) => FailureKind::Corrupt,
+            Self::Poisoned | Self::Unsupported(_) | Self::Format(_) | Self::Mismatch(_) => {
+                FailureKind::Permanent
+            }
+        }
+    }
+}
+
+/// Hashes the bytes going through it.
+struct Hashing<T> {
+    inner: T,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl<T> Hashing<T> {
+    fn new(inner: T) -> Self {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            bytes: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for Hashing<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Read for Hashing<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+fn read_exact(reader: &mut impl Read, buf: &mut [u8]) -> Result<(), SnapshotError> {
+    reader.read_exact(buf).map_err(|error| match error.kind() {
+        io::ErrorKind::UnexpectedEof => SnapshotError::Corrupt("it is truncated".to_string()),
+        _ => SnapshotError::Io(error),
+    })
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, SnapshotError> {
+    let mut bytes = [0; 4];
+    read_exact(reader, &mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u8(reader: &mut impl Read) -> Result<u8, SnapshotError> {
+    let mut byte = [0];
+    read_exact(reader, &mut byte)?;
+    Ok(byte[0])
+}
+
+fn type_tag(ty: Type) -> u8 {
+    match ty {
+        Type::I32 => 0,
+        Type::I64 => 1,
+        Type::F32 => 2,
+        Type::F64 => 3,
+        Type::V128 => 4,
+        Type::ExternRef => 5,
+        Type::FuncRef => 6,
+    }
+}
+
+/// Whether `bytes` only holds zeros, read a word at a time.
+fn is_zero(bytes: &[u8]) -> bool {
+    // SAFETY: any bit pattern is a valid `u64`.
+    let (prefix, words, suffix) = unsafe { bytes.align_to::<u64>() };
+    prefix.iter().all(|&byte| byte == 0)
+        && words.iter().all(|&word| word == 0)
+        && suffix.iter().all(|&byte| byte == 0)
+}
+
+/// Zero `bytes`, without writing to them if they already are zeros, which keeps the pages of
+/// memory never written to out of the resident set.
+fn zero(bytes: &mut [u8]) {
+    if !is_zero(bytes) {
+        nontemporal::zero(bytes);
+    }
+}
+
+/// The memory sizes and table sizes of a snapshot.
+struct Header {
+    memory_pages: Vec<u32>,
+    table_sizes: Vec<u32>,
+}
+
+impl Instance {
+    /// The contents of the memory `index`.
+    ///
+    /// # Safety
+    ///
+    /// No call into the instance may be running, and the memory must not grow while the
+    /// slice is in use.
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn memory_bytes(&self, index: LocalMemoryIndex) -> &mut [u8] {
+        let definition = self.memories[index].vmmemory().as_ref();
+        slice::from_raw_parts_mut(definition.base, definition.current_length)
+    }
+
+    /// The index of the function `funcref` refers to, as a reference of snapshots, or `None`
+    /// if it is not a function of the instance.
+    fn encode_funcref(&self, funcref: VMFuncRef) -> Option<u32> {
+        if funcref.is_null() {
+            return Some(NULL);
+        }
+        let funcrefs = self.funcrefs.values().as_slice();
+        let offset = (*funcref as usize).wrapping_sub(funcrefs.as_ptr() as usize);
+        let size = mem::size_of::<VMCallerCheckedAnyfunc>();
+        if offset % size == 0 && offset / size < funcrefs.len() {
+            return Some((offset / size) as u32);
+        }
+        // Copies of the references of the instance, such as the exports of other instances
+        // it imports.
+        // SAFETY: non-null function references point to live anyfuncs.
+        let anyfunc = unsafe { &**funcref };
+        let index = funcrefs.iter().position(|funcref| funcref == anyfunc)?;
+        Some(index as u32)
+    }
+
+    fn decode_funcref(&self, reference: u32) -> Result<VMFuncRef, SnapshotError> {
+        if reference == NULL {
+            return Ok(VMFuncRef::null());
+        }
+        let index = FunctionIndex::from_u32(reference);
+        if !self.funcrefs.is_valid(index) {
+            return Err(SnapshotError::Corrupt(format!(
+                "it refers to function {}, which the instance does not have",
+                reference
+            )));
+        }
+        Ok(self.get_vm_funcref(index))
+    }
+
+    fn encode_element(&self, element: TableElement, what: &str) -> Result<u32, SnapshotError> {
+        match element {
+            TableElement::FuncRef(funcref) => self.encode_funcref(funcref).ok_or_else(|| {
+                SnapshotError::Unsupported(format!("a function of another instance in {}", what))
+            }),
+            TableElement::ExternRef(externref) if externref.is_null() => Ok(NULL),
+            TableElement::ExternRef(_) => Err(SnapshotError::Unsupported(format!(
+                "an extern reference in {}",
+                what
+            ))),
+        }
+    }
+
+    fn decode_element(&self, ty: Type, reference: u32) -> Result<TableElement, SnapshotError> {
+        match ty {
+            Type::FuncRef => Ok(TableElement::FuncRef(self.decode_funcref(reference)?)),
+            _ if reference == NULL => Ok(TableElement::ExternRef(ExternRef::null())),
+            _ => Err(SnapshotError::Corrupt(
+                "it holds a non-null extern reference".to_string(),
+            )),
+        }
+    }
+
+    fn write_global(&self, writer: &mut impl Write, global: &Global) -> Result<(), SnapshotError> {
+        // SAFETY: no call into the instance is running.
+        let definition = unsafe { global.vmglobal().as_ref() };
+        let what = "a global";
+        match global.ty().ty {
+            Type::FuncRef => {
+                let element = TableElement::FuncRef(definition.to_funcref());
+                writer.write_all(&self.encode_element(element, what)?.to_le_bytes())?
+            }
+            Type::ExternRef if definition.to_externref().is_null() => {
+                writer.write_all(&NULL.to_le_bytes())?
+            }
+            Type::ExternRef => {
+                return Err(SnapshotError::Unsupported(format!(
+                    "an extern reference in {}",
+                    what
+                )))
+            }
+            _ => writer.write_all(&definition.to_bytes())?,
+        }
+        Ok(())
+    }
+
+    fn read_global(&self, reader: &mut impl Read, global: &Global) -> Result<(), SnapshotError> {
+        // SAFETY: no call into the instance is running.
+        let definition = unsafe { global.vmglobal().as_mut() };
+        match global.ty().ty {
+            Type::FuncRef => {
+                let funcref = self.decode_funcref(read_u32(reader)?)?;
+                unsafe { *definition.as_funcref_mut() = funcref };
+            }
+            ty @ Type::ExternRef => {
+                self.decode_element(ty, read_u32(reader)?)?;
+                unsafe {
+                    let externref = definition.as_externref_mut();
+                    externref.ref_drop();
+                    *externref = VMExternRef::null();
+                }
+            }
+            _ => {
+                let mut bytes = [0; 16];
+                read_exact(reader, &mut bytes)?;
+                unsafe { *definition.as_bytes_mut() = bytes };
+            }
+        }
+        Ok(())
+    }
+
+    fn write_snapshot(
+        &self,
+        writer: &mut impl Write,
+        stats: &mut SnapshotStats,
+    ) -> Result<(), SnapshotError> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        for count in &[self.memories.len(), self.tables.len(), self.globals.len()] {
+            writer.write_all(&(*count as u32).to_le_bytes())?;
+        }
+        for memory in self.memories.values() {
+            writer.write_all(&memory.size().0.to_le_bytes())?;
+        }
+        for table in self.tables.values() {
+            writer.write_all(&table.size().to_le_bytes())?;
+        }
+        for global in self.globals.values() {
+            writer.write_all(&[type_tag(global.ty().ty)])?;
+        }
+
+        for global in self.globals.values() {
+            self.write_global(writer, global)?;
+        }
+        for (index, table) in self.tables.iter() {
+            let what = format!("table {}", index.index());
+            for element in 0..table.size() {
+                let element = table.get(element).expect("element within the table");
+                writer.write_all(&self.encode_element(element, &what)?.to_le_bytes())?;
+            }
+        }
+        for index in self.memories.keys() {
+            // SAFETY: no call into the instance is running.
+            let bytes = unsafe { self.memory_bytes(index) };
+            for (page, contents) in bytes.chunks_exact(WASM_PAGE_SIZE).enumerate() {
+                if is_zero(contents) {
+                    stats.zero_pages += 1;
+                    continue;
+                }
+                writer.write_all(&(page as u32).to_le_bytes())?;
+                writer.write_all(&Sha256::digest(contents))?;
+                writer.write_all(contents)?;
+                stats.pages += 1;
+            }
+            writer.write_all(&END_OF_PAGES.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read the header of a snapshot, checking that it fits the instance.
+    fn read_header(&self, reader: &mut impl Read) -> Result<Header, SnapshotError> {
+        let mut magic = [0; 4];
+        read_exact(reader, &mut magic)?;
+        if magic != MAGIC {
+            return Err(SnapshotError::Format(format!("bad magic {:02x?}", magic)));
+        }
+        let version = read_u8(reader)?;
+        if version != VERSION {
+            return Err(SnapshotError::Format(format!(
+                "version {} is not supported",
+                version
+            )));
+        }
+        let counts = [
+            ("memories", self.memories.len()),
+            ("tables", self.tables.len()),
+            ("globals", self.globals.len()),
+        ];
+        for (what, expected) in &counts {
+            let count = read_u32(reader)?;
+            if count as usize != *expected {
+                return Err(SnapshotError::Mismatch(format!(
+                    "{} {} instead of {}",
+                    count, what, expected
+                )));
+            }
+        }
+
+        let mut header = Header {
+            memory_pages: Vec::with_capacity(self.memories.len()),
+            table_sizes: Vec::with_capacity(self.tables.len()),
+        };
+        for (index, memory) in self.memories.iter() {
+            let pages = read_u32(reader)?;
+            let (current, maximum) = (memory.size().0, memory.ty().maximum);
+            if pages < current || maximum.map_or(false, |maximum| pages > maximum.0) {
+                return Err(SnapshotError::Mismatch(format!(
+                    "memory {} cannot have {} pages, having {} with a maximum of {:?}",
+                    index.index(),
+                    pages,
+                    current,
+                    maximum.map(|maximum| maximum.0)
+                )));
+            }
+            if is_read_only(unsafe { memory.vmmemory().as_ref() }.base) {
+                return Err(SnapshotError::Unsupported(format!(
+                    "writes to the read-only memory {}",
+                    index.index()
+                )));
+            }
+            header.memory_pages.push(pages);
+        }
+        for (index, table) in self.tables.iter() {
+            let size = read_u32(reader)?;
+            let (current, maximum) = (table.size(), table.ty().maximum);
+            if size < current || maximum.map_or(false, |maximum| size > maximum) {
+                return Err(SnapshotError::Mismatch(format!(
+                    "table {} cannot have {} elements, having {} with a maximum of {:?}",
+                    index.index(),
+                    size,
+                    current,
+                    maximum
+                )));
+            }
+            header.table_sizes.push(size);
+        }
+        for (index, global) in self.globals.iter() {
+            let tag = read_u8(reader)?;
+            if tag != type_tag(global.ty().ty) {
+                return Err(SnapshotError::Mismatch(format!(
+                    "global {} is of type {}, not of tag {}",
+                    index.index(),
+                    global.ty().ty,
+                    tag
+                )));
+            }
+        }
+        Ok(header)
+    }
+
+    /// Restore the globals, tables and memories from the part of a snapshot after `header`.
+    fn read_snapshot(&self, reader: &mut impl Read, header: &Header) -> Result<(), SnapshotError> {
+        for global in self.globals.values() {
+            self.read_global(reader, global)?;
+        }
+        for ((index, table), &size) in self.tables.iter().zip(&header.table_sizes) {
+            let ty = table.ty().ty;
+            let null = self.decode_element(ty, NULL)?;
+            if table.grow(size - table.size(), null).is_none() {
+                return Err(SnapshotError::Mismatch(format!(
+                    "table {} cannot grow to {} elements",
+                    index.index(),
+                    size
+                )));
+            }
+            for element in 0..size {
+                let value = self.decode_element(ty, read_u32(reader)?)?;
+                table.set(element, value).expect("element within the table");
+            }
+        }
+        for ((index, memory), &pages) in self.memories.iter().zip(&header.memory_pages) {
+            memory
+                .grow(Pages(pages) - memory.size())
+                .map_err(|error| SnapshotError::Mismatch(error.to_string()))?;
+            // SAFETY: no call into the instance is running, and the memory does not grow
+            // past this point.
+            let bytes = unsafe { self.memory_bytes(index) };
+            let mut next = 0;
+            loop {
+                let page = read_u32(reader)?;
+                let end = if page == END_OF_PAGES { pages } else { page };
+                if end < next || end > pages || (end == pages && page != END_OF_PAGES) {
+                    return Err(SnapshotError::Corrupt(format!(
+                        "page {} of memory {} is out of order",
+                        page,
+                        index.index()
+                    )));
+                }
+                // The pages the snapshot leaves out are zeros.
+                zero(&mut bytes[next as usize * WASM_PAGE_SIZE..end as usize * WASM_PAGE_SIZE]);
+                if page == END_OF_PAGES {
+                    break;
+                }
+                let mut checksum = [0; 32];
+                read_exact(reader, &mut checksum)?;
+                let contents = &mut bytes[page as usize * WASM_PAGE_SIZE..][..WASM_PAGE_SIZE];
+                read_exact(reader, contents)?;
+                if Sha256::digest(&*contents).as_slice() != checksum {
+                    return Err(SnapshotError::CorruptPage {
+                        memory: index.as_u32(),
+                        page,
+                    });
+                }
+                next = page + 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl InstanceHandle {
+    /// Write a snapshot of the memories, tables and globals the instance defines to `writer`.
+    ///
+    /// The memory pages only holding zeros are left out, so that the snapshot of a large
+    /// memory is as large as the pages written to. Each page is followed by its checksum, and
+    /// the snapshot by its hash. The memories, tables and globals the instance imports are
+    /// left to the snapshots of the instances defining them.
+    ///
+    /// Calls into the instance wait for the snapshot to complete, so `writer` must not call
+    /// into it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnapshotError::CallInFlight`] if a call into the instance is running,
+    /// [`SnapshotError::Poisoned`] if a restore of the instance failed, and
+    /// [`SnapshotError::Unsupported`] if a table or global holds an extern reference or a
+    /// function of another instance.
+    pub fn snapshot_to<W: Write>(
+        &self,
+        writer: W,
+        options: &SnapshotOptions,
+    ) -> Result<SnapshotStats, SnapshotError> {
+        let instance = self.instance().as_ref();
+        let _exclusive = instance
+            .exclusive()
+            .map_err(|active_calls| SnapshotError::CallInFlight { active_calls })?;
+        if instance.poisoned.load(Ordering::SeqCst) {
+            return Err(SnapshotError::Poisoned);
+        }
+        let mut writer = Hashing::new(BufWriter::with_capacity(options.buffer_size, writer));
+        let mut stats = SnapshotStats::default();
+        instance.write_snapshot(&mut writer, &mut stats)?;
+        let Hashing {
+            mut inner,
+            hasher,
+            bytes,
+        } = writer;
+        let hash = hasher.finalize();
+        inner.write_all(&hash)?;
+        inner.flush()?;
+        stats.bytes = bytes + hash.len() as u64;
+        Ok(stats)
+    }
+
+    /// Restore the memories, tables and globals the instance defines from a snapshot read
+    /// from `reader`.
+    ///
+    /// Memory pages are read straight into the memories, without buffering the snapshot, and
+    /// checked against their checksum as they are. Readers slow on small reads, such as
+    /// files, are best wrapped in a [`BufReader`](std::io::BufReader). Memories and tables
+    /// grow to the size they had in the snapshot.
+    ///
+    /// Calls into the instance wait for the restore to complete, so `reader` must not call
+    /// into it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SnapshotError::CallInFlight`] if a call into the instance is running, and
+    /// [`SnapshotError::Format`], [`SnapshotError::Mismatch`] or
+    /// [`SnapshotError::Unsupported`] if the header of the snapshot does not fit the instance,
+    /// all of which leave the instance as it was.
+    ///
+    /// Errors found past the header, such as a [`SnapshotError::CorruptPage`], leave the
+    /// instance poisoned: calls into it trap with [`TrapCode::PoisonedInstance`] and
+    /// snapshots of it fail, until a restore succeeds.
+    ///
+    /// [`TrapCode::PoisonedInstance`]: crate::TrapCode::PoisonedInstance
+    pub fn restore_from<R: Read>(&self, reader: R) -> Result<(), SnapshotError> {
+        let instance = self.instance().as_ref();
+        let _exclusive = instance
+            .exclusive()
+            .map_err(|active_calls| SnapshotError::CallInFlight { active_calls })?;
+        let mut reader = Hashing::new(reader);
+        let header = instance.read_header(&mut reader)?;
+        instance.poisoned.store(true, Ordering::SeqCst);
+        instance.read_snapshot(&mut reader, &header)?;
+        let Hashing {
+            mut inner, hasher, ..
+        } = reader;
+        let mut hash = [0; 32];
+        read_exact(&mut inner, &mut hash)?;
+        if hasher.finalize().as_slice() != hash {
+            return Err(SnapshotError::Corrupt(
+                "it does not match its hash".to_string(),
+            ));
+        }
+        instance.poisoned.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Return whether a restore of the instance failed, see [`Self::restore_from`].
+    pub fn is_poisoned(&self) -> bool {
+        self.instance().as_ref().poisoned.load(Ordering::SeqCst)
+    }
+}
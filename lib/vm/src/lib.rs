@@ -21,18 +21,29 @@
     )
 )]
 
+mod arbiter;
 mod artifact;
+mod cancellation;
+mod diagnostics;
 mod export;
+mod extensions;
 mod func_data_registry;
 mod global;
 mod imports;
 mod instance;
 mod memory;
+mod metrics;
 mod mmap;
+mod nontemporal;
+mod page_allocator;
 mod probestack;
+mod provenance;
+mod readonly_memory;
+mod reclaim;
 mod resolver;
 mod sig_registry;
 mod table;
+mod teardown;
 mod trap;
 mod tunables;
 mod vmcontext;
@@ -40,26 +51,42 @@ mod vmoffsets;
 
 pub mod libcalls;
 
+pub use crate::arbiter::{
+    FairShareArbiter, GrowDecision, GrowRequest, MemoryArbiter, MemoryRelease,
+};
 pub use crate::artifact::{Artifact, Instantiatable};
+pub use crate::cancellation::CallCancellation;
+pub use crate::diagnostics::{DiagnosticEvent, DiagnosticsLevel, DiagnosticsSink};
 pub use crate::export::*;
+pub use crate::extensions::{current_extensions, with_extensions};
 pub use crate::func_data_registry::{FuncDataRegistry, VMFuncRef};
 pub use crate::global::*;
 pub use crate::imports::{Imports, VMImport, VMImportType};
 pub use crate::instance::{
     initialize_host_envs, ImportFunctionEnv, ImportInitializerFuncPtr, InstanceAllocator,
-    InstanceHandle, WeakOrStrongInstanceRef,
+    InstanceArena, InstanceHandle, InstanceLayout, InstanceRef, InstanceUsage, RebindError,
+    ResetError, SnapshotError, SnapshotOptions, SnapshotStats, WeakInstanceRef,
+    WeakOrStrongInstanceRef,
+};
+pub use crate::memory::{BaseStability, LinearMemory, Memory, MemoryError, MemoryStyle};
+pub use crate::metrics::{
+    record_trap, AtomicMetricsSink, Counter, Gauge, MetricsSink, MetricsSnapshot, Timer,
 };
-pub use crate::memory::{LinearMemory, Memory, MemoryError, MemoryStyle};
 pub use crate::mmap::Mmap;
+pub use crate::page_allocator::{PageAllocator, PageProtection, Pages, SystemPageAllocator};
 pub use crate::probestack::PROBESTACK;
+pub use crate::provenance::{FuncOrigin, InstanceId};
+pub use crate::readonly_memory::{MemoryImage, ReadOnlyMemory};
+pub use crate::reclaim::{ReclaimPriority, ReclaimReport, Reclaimable, ReclaimedComponent};
 pub use crate::resolver::{
     ChainableNamedResolver, Export, ExportFunction, ExportFunctionMetadata, NamedResolver,
     NamedResolverChain, NullResolver, Resolver,
 };
 pub use crate::sig_registry::{SignatureRegistry, VMSharedSignatureIndex};
 pub use crate::table::{LinearTable, Table, TableElement, TableStyle};
+pub use crate::teardown::TeardownQueue;
 pub use crate::trap::*;
-pub use crate::tunables::Tunables;
+pub use crate::tunables::{ModuleStyleHints, Tunables};
 pub use crate::vmcontext::{
     FunctionBodyPtr, FunctionExtent, SectionBodyPtr, VMBuiltinFunctionIndex,
     VMCallerCheckedAnyfunc, VMContext, VMDynamicFunctionContext, VMFunctionBody,
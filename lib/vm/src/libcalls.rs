@@ -37,11 +37,14 @@
 
 #![allow(missing_docs)] // For some reason lint fails saying that `LibCall` is not documented, when it actually is
 
+use crate::cancellation::cancellation_requested;
+use crate::diagnostics::DiagnosticEvent;
 use crate::func_data_registry::VMFuncRef;
+use crate::memory::MemoryError;
 use crate::probestack::PROBESTACK;
 use crate::table::{RawTableElement, TableElement};
 use crate::trap::{raise_lib_trap, Trap, TrapCode};
-use crate::vmcontext::VMContext;
+use crate::vmcontext::{VMContext, VMFunctionBody};
 use crate::VMExternRef;
 use std::fmt;
 use wasmer_types::{
@@ -153,10 +156,11 @@ pub unsafe extern "C" fn wasmer_vm_memory32_grow(
     let instance = (&*vmctx).instance();
     let memory_index = LocalMemoryIndex::from_u32(memory_index);
 
-    instance
-        .memory_grow(memory_index, delta)
-        .map(|pages| pages.0)
-        .unwrap_or(u32::max_value())
+    match instance.memory_grow(memory_index, delta) {
+        Ok(pages) => pages.0,
+        Err(MemoryError::GasExceeded) => raise_lib_trap(Trap::lib(TrapCode::GasExceeded)),
+        Err(_) => u32::max_value(),
+    }
 }
 
 /// Implementation of memory.grow for imported 32-bit memories.
@@ -173,10 +177,11 @@ pub unsafe extern "C" fn wasmer_vm_imported_memory32_grow(
     let instance = (&*vmctx).instance();
     let memory_index = MemoryIndex::from_u32(memory_index);
 
-    instance
-        .imported_memory_grow(memory_index, delta)
-        .map(|pages| pages.0)
-        .unwrap_or(u32::max_value())
+    match instance.imported_memory_grow(memory_index, delta) {
+        Ok(pages) => pages.0,
+        Err(MemoryError::GasExceeded) => raise_lib_trap(Trap::lib(TrapCode::GasExceeded)),
+        Err(_) => u32::max_value(),
+    }
 }
 
 /// Implementation of memory.size for locally-defined 32-bit memories.
@@ -226,9 +231,7 @@ pub unsafe extern "C" fn wasmer_vm_table_copy(
         let dst_table_index = TableIndex::from_u32(dst_table_index);
         let src_table_index = TableIndex::from_u32(src_table_index);
         let instance = (&*vmctx).instance();
-        let dst_table = instance.get_table(dst_table_index);
-        let src_table = instance.get_table(src_table_index);
-        dst_table.copy(src_table, dst, src, len)
+        instance.table_copy(dst_table_index, src_table_index, dst, src, len)
     };
     if let Err(trap) = result {
         raise_lib_trap(trap);
@@ -441,9 +444,10 @@ pub unsafe extern "C" fn wasmer_vm_table_grow(
         Type::FuncRef => TableElement::FuncRef(init_value.func_ref),
         _ => panic!("Unrecognized table type: does not contain references"),
     };
-    instance
-        .table_grow(table_index, delta, init_value)
-        .unwrap_or(u32::max_value())
+    match instance.table_grow(table_index, delta, init_value) {
+        Ok(size) => size.unwrap_or(u32::max_value()),
+        Err(trap) => raise_lib_trap(trap),
+    }
 }
 
 /// Implementation of `table.grow` for imported tables.
@@ -466,9 +470,10 @@ pub unsafe extern "C" fn wasmer_vm_imported_table_grow(
         _ => panic!("Unrecognized table type: does not contain references"),
     };
 
-    instance
-        .imported_table_grow(table_index, delta, init_value)
-        .unwrap_or(u32::max_value())
+    match instance.imported_table_grow(table_index, delta, init_value) {
+        Ok(size) => size.unwrap_or(u32::max_value()),
+        Err(trap) => raise_lib_trap(trap),
+    }
 }
 
 /// Implementation of `func.ref`.
@@ -653,15 +658,15 @@ pub unsafe extern "C" fn wasmer_vm_data_drop(vmctx: *mut VMContext, data_index:
     instance.data_drop(data_index)
 }
 
-/// Implementation for raising a trap
+/// Implementation for raising a trap, with the [`TrapCode::to_raw`] form of its code
 ///
 /// # Safety
 ///
 /// Only safe to call when wasm code is on the stack, aka `wasmer_call` or
 /// `wasmer_call_trampoline` must have been previously called.
 #[no_mangle]
-pub unsafe extern "C" fn wasmer_vm_raise_trap(trap_code: TrapCode) -> ! {
-    let trap = Trap::lib(trap_code);
+pub unsafe extern "C" fn wasmer_vm_raise_trap(trap_code: u32) -> ! {
+    let trap = Trap::lib(TrapCode::from_raw(trap_code).expect("invalid trap code"));
     raise_lib_trap(trap)
 }
 
@@ -674,12 +679,70 @@ pub unsafe extern "C" fn wasmer_vm_raise_trap(trap_code: TrapCode) -> ! {
 #[no_mangle]
 pub static wasmer_vm_probestack: unsafe extern "C" fn() = PROBESTACK;
 
+/// Report the entry into the function whose body starts at `body` to diagnostics.
+///
+/// The function is told by its address rather than by its index, so that its code does not
+/// depend on where it is in its module.
+///
+/// # Safety
+///
+/// `vmctx` must be dereferenceable.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_vm_diagnostics_call(
+    vmctx: *mut VMContext,
+    body: *const VMFunctionBody,
+) {
+    let instance = (&*vmctx).instance();
+    if let Some(function) = instance.function_at(body) {
+        instance.report_diagnostic(DiagnosticEvent::Call { function });
+    }
+}
+
+/// Report a load or store of `size` bytes at `address + offset` to diagnostics.
+///
+/// # Safety
+///
+/// `vmctx` must be dereferenceable.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_vm_diagnostics_memory(
+    vmctx: *mut VMContext,
+    address: u32,
+    offset: u32,
+    size: u32,
+    store: u32,
+) {
+    let instance = (&*vmctx).instance();
+    let address = u64::from(address) + u64::from(offset);
+    let size = size as u8;
+    instance.report_diagnostic(if store != 0 {
+        DiagnosticEvent::Store { address, size }
+    } else {
+        DiagnosticEvent::Load { address, size }
+    });
+}
+
+/// Trap with `TrapCode::Cancelled` if a call running on this thread was cancelled.
+///
+/// Compiled code only calls this while a call into its instance is cancelled, on any
+/// thread.
+///
+/// # Safety
+///
+/// To be called from compiled code, which catches the trap.
+#[no_mangle]
+pub unsafe extern "C" fn wasmer_vm_check_cancellation(_vmctx: *mut VMContext) {
+    if cancellation_requested() {
+        raise_lib_trap(Trap::lib(TrapCode::Cancelled));
+    }
+}
+
 /// The name of a runtime library routine.
 ///
 /// This list is likely to grow over time.
 #[derive(
     rkyv::Serialize, rkyv::Deserialize, rkyv::Archive, Copy, Clone, Debug, PartialEq, Eq, Hash,
 )]
+#[archive(check_bytes)]
 pub enum LibCall {
     /// ceil.f32
     CeilF32,
@@ -771,6 +834,15 @@ pub enum LibCall {
     /// A custom trap
     RaiseTrap,
 
+    /// Report a function entry to diagnostics
+    DiagnosticsCall,
+
+    /// Report a memory access to diagnostics
+    DiagnosticsMemory,
+
+    /// Trap if the running call was cancelled
+    CheckCancellation,
+
     /// probe for stack overflow. These are emitted for functions which need
     /// when the `enable_probestack` setting is true.
     Probestack,
@@ -811,6 +883,9 @@ impl LibCall {
             Self::DataDrop => wasmer_vm_data_drop as usize,
             Self::Probestack => wasmer_vm_probestack as usize,
             Self::RaiseTrap => wasmer_vm_raise_trap as usize,
+            Self::DiagnosticsCall => wasmer_vm_diagnostics_call as usize,
+            Self::DiagnosticsMemory => wasmer_vm_diagnostics_memory as usize,
+            Self::CheckCancellation => wasmer_vm_check_cancellation as usize,
         }
     }
 
@@ -847,6 +922,9 @@ impl LibCall {
             Self::Memory32Init => "wasmer_vm_memory32_init",
             Self::DataDrop => "wasmer_vm_data_drop",
             Self::RaiseTrap => "wasmer_vm_raise_trap",
+            Self::DiagnosticsCall => "wasmer_vm_diagnostics_call",
+            Self::DiagnosticsMemory => "wasmer_vm_diagnostics_memory",
+            Self::CheckCancellation => "wasmer_vm_check_cancellation",
             // We have to do this because macOS requires a leading `_` and it's not
             // a normal function, it's a static variable, so we have to do it manually.
             #[cfg(target_vendor = "apple")]
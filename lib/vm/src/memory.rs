@@ -6,6 +6,7 @@
 //! `LinearMemory` is to WebAssembly linear memories what `Table` is to WebAssembly tables.
 
 use crate::mmap::Mmap;
+use crate::readonly_memory::{map_image_private, MemoryImage};
 use crate::vmcontext::VMMemoryDefinition;
 use more_asserts::assert_ge;
 use std::borrow::BorrowMut;
@@ -13,19 +14,19 @@ use std::cell::UnsafeCell;
 use std::convert::TryInto;
 use std::fmt;
 use std::ptr::NonNull;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
-use wasmer_types::{Bytes, MemoryType, Pages};
+use wasmer_types::{Bytes, Classify, ErrorCode, FailureKind, HasErrorCode, MemoryType, Pages};
 
 /// Error type describing things that can go wrong when operating on Wasm Memories.
 #[derive(Error, Debug, Clone, PartialEq, Hash)]
 pub enum MemoryError {
     /// Low level error with mmap.
-    #[error("Error when allocating memory: {0}")]
+    #[error("Error when allocating memory: {0} [{}]", ErrorCode::MemoryRegion)]
     Region(String),
     /// The operation would cause the size of the memory to exceed the maximum or would cause
     /// an overflow leading to unindexable memory.
-    #[error("The memory could not grow: current size {} pages, requested increase: {} pages", current.0, attempted_delta.0)]
+    #[error("The memory could not grow: current size {} pages, requested increase: {} pages [{}]", current.0, attempted_delta.0, ErrorCode::MemoryCouldNotGrow)]
     CouldNotGrow {
         /// The current size in pages.
         current: Pages,
@@ -33,13 +34,13 @@ pub enum MemoryError {
         attempted_delta: Pages,
     },
     /// The operation would cause the size of the memory size exceed the maximum.
-    #[error("The memory is invalid because {}", reason)]
+    #[error("The memory is invalid because {} [{}]", reason, ErrorCode::MemoryInvalid)]
     InvalidMemory {
         /// The reason why the provided memory is invalid.
         reason: String,
     },
     /// Caller asked for more minimum memory than we can give them.
-    #[error("The minimum requested ({} pages) memory is greater than the maximum allowed memory ({} pages)", min_requested.0, max_allowed.0)]
+    #[error("The minimum requested ({} pages) memory is greater than the maximum allowed memory ({} pages) [{}]", min_requested.0, max_allowed.0, ErrorCode::MemoryMinimumTooLarge)]
     MinimumMemoryTooLarge {
         /// The number of pages requested as the minimum amount of memory.
         min_requested: Pages,
@@ -47,20 +48,69 @@ pub enum MemoryError {
         max_allowed: Pages,
     },
     /// Caller asked for a maximum memory greater than we can give them.
-    #[error("The maximum requested memory ({} pages) is greater than the maximum allowed memory ({} pages)", max_requested.0, max_allowed.0)]
+    #[error("The maximum requested memory ({} pages) is greater than the maximum allowed memory ({} pages) [{}]", max_requested.0, max_allowed.0, ErrorCode::MemoryMaximumTooLarge)]
     MaximumMemoryTooLarge {
         /// The number of pages requested as the maximum amount of memory.
         max_requested: Pages,
         /// The number of pages requested as the maximum amount of memory.
         max_allowed: Pages,
     },
+    /// Growing the memory would exceed the gas limit of the instance.
+    #[error("gas limit exceeded [{}]", ErrorCode::MemoryGasExceeded)]
+    GasExceeded,
+    /// The range is not aligned to the native page size or not within the memory.
+    #[error("The range of {} bytes at offset {} is not made of whole pages of the memory [{}]", len, offset, ErrorCode::MemoryInvalidRange)]
+    InvalidRange {
+        /// The offset of the range, in bytes.
+        offset: u64,
+        /// The length of the range, in bytes.
+        len: u64,
+    },
+    /// Growing the memory would move it while its base is pinned.
+    #[error(
+        "The memory cannot move while its base is pinned [{}]",
+        ErrorCode::MemoryBasePinned
+    )]
+    BasePinned,
     /// A user defined error value, used for error cases not listed above.
-    #[error("A user-defined error occurred: {0}")]
+    #[error("A user-defined error occurred: {0} [{}]", ErrorCode::MemoryGeneric)]
     Generic(String),
 }
 
+impl Classify for MemoryError {
+    fn failure_kind(&self) -> FailureKind {
+        match self {
+            Self::Region(_) | Self::BasePinned => FailureKind::TransientResource,
+            Self::CouldNotGrow { .. }
+            | Self::InvalidMemory { .. }
+            | Self::MinimumMemoryTooLarge { .. }
+            | Self::MaximumMemoryTooLarge { .. }
+            | Self::GasExceeded
+            | Self::InvalidRange { .. }
+            | Self::Generic(_) => FailureKind::Permanent,
+        }
+    }
+}
+
+impl HasErrorCode for MemoryError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::Region(_) => ErrorCode::MemoryRegion,
+            Self::CouldNotGrow { .. } => ErrorCode::MemoryCouldNotGrow,
+            Self::InvalidMemory { .. } => ErrorCode::MemoryInvalid,
+            Self::MinimumMemoryTooLarge { .. } => ErrorCode::MemoryMinimumTooLarge,
+            Self::MaximumMemoryTooLarge { .. } => ErrorCode::MemoryMaximumTooLarge,
+            Self::GasExceeded => ErrorCode::MemoryGasExceeded,
+            Self::InvalidRange { .. } => ErrorCode::MemoryInvalidRange,
+            Self::BasePinned => ErrorCode::MemoryBasePinned,
+            Self::Generic(_) => ErrorCode::MemoryGeneric,
+        }
+    }
+}
+
 /// Implementation styles for WebAssembly linear memory.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
 pub enum MemoryStyle {
     /// The actual memory can be resized and moved.
     Dynamic {
@@ -94,6 +144,16 @@ impl MemoryStyle {
     }
 }
 
+/// Whether the base of a memory stays where it is when the memory grows, so that pointers
+/// into it stay valid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaseStability {
+    /// The memory never moves: its address space is reserved up to its maximum size.
+    Stable,
+    /// The memory moves when it grows past the address space reserved for it.
+    MayMoveOnGrow,
+}
+
 /// Trait for implementing Wasm Memory used by Wasmer.
 pub trait Memory: fmt::Debug + Send + Sync {
     /// Returns the memory type for this memory.
@@ -108,6 +168,63 @@ pub trait Memory: fmt::Debug + Send + Sync {
     /// Grow memory by the specified amount of wasm pages.
     fn grow(&self, delta: Pages) -> Result<Pages, MemoryError>;
 
+    /// Release the physical memory of the `len` bytes at `offset`, which stay accessible and
+    /// read as zeros afterwards. The range must be made of whole native pages of the memory.
+    ///
+    /// Concurrent accesses to the range read either its old contents or zeros, and never
+    /// fault. Memories that cannot discard their pages fail with
+    /// [`MemoryError::InvalidMemory`].
+    fn discard(&self, _offset: usize, _len: usize) -> Result<(), MemoryError> {
+        Err(MemoryError::InvalidMemory {
+            reason: "its pages cannot be discarded".to_string(),
+        })
+    }
+
+    /// Shrink the memory back to the size it was created with, and bring back the contents
+    /// of the image it maps copy-on-write, or zero it otherwise. Returns whether it brought
+    /// back the contents of an image.
+    ///
+    /// Memories that cannot be reset fail with [`MemoryError::InvalidMemory`].
+    fn reset(&self) -> Result<bool, MemoryError> {
+        Err(MemoryError::InvalidMemory {
+            reason: "it cannot be reset".to_string(),
+        })
+    }
+
+    /// Returns the number of bytes of the memory resident in physical memory, or an estimate
+    /// of it where the operating system does not tell.
+    fn resident_bytes(&self) -> usize {
+        self.size().bytes().0
+    }
+
+    /// Returns whether the base of the memory may move when it grows.
+    fn base_stability(&self) -> BaseStability {
+        BaseStability::MayMoveOnGrow
+    }
+
+    /// Returns why the address space of a memory asked to be stable could not be reserved up
+    /// to its maximum, leaving it to move when it grows.
+    fn reservation_fallback(&self) -> Option<MemoryError> {
+        None
+    }
+
+    /// Keep the base of the memory where it is until [`Memory::unpin_base`] is called as many
+    /// times: growing the memory past what is reserved fails with
+    /// [`MemoryError::BasePinned`] meanwhile.
+    ///
+    /// Memories that cannot count pins only accept them when they are stable.
+    fn pin_base(&self) -> Result<(), MemoryError> {
+        match self.base_stability() {
+            BaseStability::Stable => Ok(()),
+            BaseStability::MayMoveOnGrow => Err(MemoryError::InvalidMemory {
+                reason: "its base cannot be pinned".to_string(),
+            }),
+        }
+    }
+
+    /// Release a pin taken with [`Memory::pin_base`].
+    fn unpin_base(&self) {}
+
     /// Return a [`VMMemoryDefinition`] for exposing the memory to compiled wasm code.
     ///
     /// The pointer returned in [`VMMemoryDefinition`] must be valid for the lifetime of this memory.
@@ -129,6 +246,12 @@ pub struct LinearMemory {
     /// Our chosen implementation style.
     style: MemoryStyle,
 
+    /// Whether the address space is reserved up to the maximum size.
+    base_stability: BaseStability,
+
+    /// Why the address space could not be reserved up to the maximum size, when asked to.
+    reservation_fallback: Option<MemoryError>,
+
     // Size in bytes of extra guard pages after the end to optimize loads and stores with
     // constant offsets.
     offset_guard_size: usize,
@@ -168,6 +291,10 @@ struct WasmMmap {
     alloc: Mmap,
     // The current logical size in wasm pages of this linear memory.
     size: Pages,
+    // The number of pins keeping the allocation from moving.
+    pins: usize,
+    // The image mapped copy-on-write over the first pages of the allocation, until it moves.
+    image: Option<Arc<MemoryImage>>,
 }
 
 impl LinearMemory {
@@ -176,7 +303,21 @@ impl LinearMemory {
     /// This creates a `LinearMemory` with owned metadata: this can be used to create a memory
     /// that will be imported into Wasm modules.
     pub fn new(memory: &MemoryType, style: &MemoryStyle) -> Result<Self, MemoryError> {
-        unsafe { Self::new_internal(memory, style, None) }
+        unsafe { Self::new_internal(memory, style, None, None) }
+    }
+
+    /// Create a new linear memory instance, as [`LinearMemory::new`] does, reserving the
+    /// address space of a dynamic memory up to its maximum size so that it never moves.
+    ///
+    /// The memory falls back to moving when it grows if its maximum, or 4 GiB if it has
+    /// none, is more than `limit`, or if the address space cannot be reserved, as reported
+    /// by [`Memory::reservation_fallback`].
+    pub fn new_reserved(
+        memory: &MemoryType,
+        style: &MemoryStyle,
+        limit: Pages,
+    ) -> Result<Self, MemoryError> {
+        unsafe { Self::new_internal(memory, style, Some(limit), None) }
     }
 
     /// Create a new linear memory instance with specified minimum and maximum number of wasm pages.
@@ -191,13 +332,65 @@ impl LinearMemory {
         style: &MemoryStyle,
         vm_memory_location: NonNull<VMMemoryDefinition>,
     ) -> Result<Self, MemoryError> {
-        Self::new_internal(memory, style, Some(vm_memory_location))
+        Self::new_internal(memory, style, None, Some(vm_memory_location))
+    }
+
+    /// Create a new linear memory instance with metadata owned by a VM, as
+    /// [`LinearMemory::from_definition`] does, reserving its address space as
+    /// [`LinearMemory::new_reserved`] does.
+    ///
+    /// # Safety
+    /// - `vm_memory_location` must point to a valid location in VM memory.
+    pub unsafe fn from_definition_reserved(
+        memory: &MemoryType,
+        style: &MemoryStyle,
+        limit: Pages,
+        vm_memory_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<Self, MemoryError> {
+        Self::new_internal(memory, style, Some(limit), Some(vm_memory_location))
+    }
+
+    /// Create a new linear memory instance with metadata owned by a VM, as
+    /// [`LinearMemory::from_definition`] does, or as [`LinearMemory::from_definition_reserved`]
+    /// does given a `reservation`, whose first pages map `image` copy-on-write.
+    ///
+    /// The memory starts with the contents of the image, and its pages are shared with the
+    /// image, and with the other memories mapping it, until they are written. The image must
+    /// be as large as the minimum of `memory`.
+    ///
+    /// # Safety
+    /// - `vm_memory_location` must point to a valid location in VM memory.
+    pub unsafe fn from_definition_with_image(
+        image: Arc<MemoryImage>,
+        memory: &MemoryType,
+        style: &MemoryStyle,
+        reservation: Option<Pages>,
+        vm_memory_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<Self, MemoryError> {
+        if image.size() != memory.minimum {
+            return Err(MemoryError::InvalidMemory {
+                reason: format!(
+                    "the image ({} pages) is not the size of the memory ({} pages)",
+                    image.size().0,
+                    memory.minimum.0
+                ),
+            });
+        }
+        let memory = Self::new_internal(memory, style, reservation, Some(vm_memory_location))?;
+        {
+            let mut mmap = memory.mmap.lock().unwrap();
+            map_image_private(&image, mmap.alloc.as_mut_ptr())?;
+            mmap.image = Some(image);
+        }
+        Ok(memory)
     }
 
-    /// Build a `LinearMemory` with either self-owned or VM owned metadata.
+    /// Build a `LinearMemory` with either self-owned or VM owned metadata, reserving the
+    /// address space of dynamic memories up to their maximum if it is within `reservation`.
     unsafe fn new_internal(
         memory: &MemoryType,
         style: &MemoryStyle,
+        reservation: Option<Pages>,
         vm_memory_location: Option<NonNull<VMMemoryDefinition>>,
     ) -> Result<Self, MemoryError> {
         if memory.minimum > Pages::max_value() {
@@ -237,11 +430,40 @@ impl LinearMemory {
         let request_bytes = minimum_bytes.checked_add(offset_guard_bytes).unwrap();
         let mapped_pages = memory.minimum;
         let mapped_bytes = mapped_pages.bytes();
+        let maximum = memory.maximum.unwrap_or_else(Pages::max_value);
+
+        let mut reserved = None;
+        let mut reservation_fallback = None;
+        if let (MemoryStyle::Dynamic { .. }, Some(limit)) = (style, reservation) {
+            if maximum > limit {
+                reservation_fallback = Some(MemoryError::MaximumMemoryTooLarge {
+                    max_requested: maximum,
+                    max_allowed: limit,
+                });
+            } else {
+                let request_bytes = maximum.bytes().0.checked_add(offset_guard_bytes).unwrap();
+                match Mmap::accessible_reserved(mapped_bytes.0, request_bytes) {
+                    Ok(alloc) => reserved = Some(alloc),
+                    Err(message) => reservation_fallback = Some(MemoryError::Region(message)),
+                }
+            }
+        }
+        let alloc = match reserved {
+            Some(alloc) => alloc,
+            None => Mmap::accessible_reserved(mapped_bytes.0, request_bytes)
+                .map_err(MemoryError::Region)?,
+        };
+        let base_stability = if alloc.len() - offset_guard_bytes >= maximum.bytes().0 {
+            BaseStability::Stable
+        } else {
+            BaseStability::MayMoveOnGrow
+        };
 
         let mut mmap = WasmMmap {
-            alloc: Mmap::accessible_reserved(mapped_bytes.0, request_bytes)
-                .map_err(MemoryError::Region)?,
+            alloc,
             size: memory.minimum,
+            pins: 0,
+            image: None,
         };
 
         let base_ptr = mmap.alloc.as_mut_ptr();
@@ -268,6 +490,8 @@ impl LinearMemory {
             },
             memory: *memory,
             style: style.clone(),
+            base_stability,
+            reservation_fallback,
         })
     }
 
@@ -358,7 +582,10 @@ impl Memory for LinearMemory {
 
         if new_bytes > mmap.alloc.len() - self.offset_guard_size {
             // If the new size is within the declared maximum, but needs more memory than we
-            // have on hand, it's a dynamic heap and it can move.
+            // have on hand, it's a dynamic heap and it can move, unless its base is pinned.
+            if mmap.pins > 0 {
+                return Err(MemoryError::BasePinned);
+            }
             let guard_bytes = self.offset_guard_size;
             let request_bytes =
                 new_bytes
@@ -375,6 +602,8 @@ impl Memory for LinearMemory {
             new_mmap.as_mut_slice()[..copy_len].copy_from_slice(&mmap.alloc.as_slice()[..copy_len]);
 
             mmap.alloc = new_mmap;
+            // The contents of the image were copied along.
+            mmap.image = None;
         } else if delta_bytes > 0 {
             // Make the newly allocated pages accessible.
             mmap.alloc
@@ -395,6 +624,101 @@ impl Memory for LinearMemory {
         Ok(prev_pages)
     }
 
+    /// Release the physical memory of a range of the memory, which then reads as zeros.
+    ///
+    /// The mapping is locked so that the memory does not move while its pages are discarded.
+    fn discard(&self, offset: usize, len: usize) -> Result<(), MemoryError> {
+        let mut mmap_guard = self.mmap.lock().unwrap();
+        let mmap = mmap_guard.borrow_mut();
+        let page_size = region::page::size();
+        let within = offset
+            .checked_add(len)
+            .map_or(false, |end| end <= mmap.size.bytes().0);
+        if !within || offset % page_size != 0 || len % page_size != 0 {
+            return Err(MemoryError::InvalidRange {
+                offset: offset as u64,
+                len: len as u64,
+            });
+        }
+        match &mmap.image {
+            // Discarding pages mapped from the image would bring its contents back.
+            #[cfg(not(target_os = "windows"))]
+            Some(image) if offset < image.size().bytes().0 => mmap.alloc.remap_zeroed(offset, len),
+            _ => mmap.alloc.discard(offset, len),
+        }
+        .map_err(MemoryError::Region)
+    }
+
+    /// Shrink the memory back to its minimum size, mapping its image again if it still maps
+    /// one, or zeroing it otherwise.
+    ///
+    /// Pages past the minimum size are made inaccessible, and released, as they were before
+    /// the memory grew.
+    fn reset(&self) -> Result<bool, MemoryError> {
+        let mut mmap_guard = self.mmap.lock().unwrap();
+        let mmap = mmap_guard.borrow_mut();
+        let minimum_bytes = self.memory.minimum.bytes().0;
+        let size_bytes = mmap.size.bytes().0;
+        if size_bytes > minimum_bytes {
+            mmap.alloc
+                .make_inaccessible(minimum_bytes, size_bytes - minimum_bytes)
+                .map_err(MemoryError::Region)?;
+        }
+        let base = mmap.alloc.as_mut_ptr();
+        let restored = match &mmap.image {
+            // The image is as large as the minimum size, see `from_definition_with_image`.
+            Some(image) => {
+                unsafe { map_image_private(image, base)? };
+                true
+            }
+            None => {
+                mmap.alloc
+                    .discard(0, minimum_bytes)
+                    .map_err(MemoryError::Region)?;
+                false
+            }
+        };
+        mmap.size = self.memory.minimum;
+
+        unsafe {
+            let mut md_ptr = self.get_vm_memory_definition();
+            let md = md_ptr.as_mut();
+            md.current_length = minimum_bytes;
+        }
+        Ok(restored)
+    }
+
+    /// Returns the number of bytes of the memory resident in physical memory, or its size if
+    /// the operating system does not tell.
+    fn resident_bytes(&self) -> usize {
+        let mmap = self.mmap.lock().unwrap();
+        let size = mmap.size.bytes().0;
+        mmap.alloc.resident_bytes(size).unwrap_or(size)
+    }
+
+    /// Returns whether the address space of the memory is reserved up to its maximum size.
+    fn base_stability(&self) -> BaseStability {
+        self.base_stability
+    }
+
+    /// Returns why the address space of the memory could not be reserved up to its maximum
+    /// size, when asked to.
+    fn reservation_fallback(&self) -> Option<MemoryError> {
+        self.reservation_fallback.clone()
+    }
+
+    /// Count a pin, which growing the memory checks before moving it.
+    fn pin_base(&self) -> Result<(), MemoryError> {
+        self.mmap.lock().unwrap().pins += 1;
+        Ok(())
+    }
+
+    /// Release a pin.
+    fn unpin_base(&self) {
+        let mut mmap = self.mmap.lock().unwrap();
+        mmap.pins = mmap.pins.saturating_sub(1);
+    }
+
     /// Return a `VMMemoryDefinition` for exposing the memory to compiled wasm code.
     fn vmmemory(&self) -> NonNull<VMMemoryDefinition> {
         let _mmap_guard = self.mmap.lock().unwrap();
@@ -0,0 +1,337 @@
+//! Counters and gauges describing the activity of an engine, reported to a
+//! pluggable [`MetricsSink`].
+
+use crate::trap::TrapCode;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// A monotonically increasing metric.
+///
+/// The names returned by [`Counter::name`] are stable and can be used as keys
+/// by dashboards.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Counter {
+    /// A compilation was started.
+    CompilationsStarted,
+    /// A compilation completed successfully.
+    CompilationsSucceeded,
+    /// A compilation failed.
+    CompilationsFailed,
+    /// An artifact was loaded into the engine.
+    ArtifactsLoaded,
+    /// An artifact was loaded into the engine with its functions placed into code memory as
+    /// they were compiled.
+    PipelinedLoads,
+    /// An artifact was dropped.
+    ArtifactsUnloaded,
+    /// An instance was created.
+    InstancesCreated,
+    /// An instance was deallocated.
+    InstancesDestroyed,
+    /// A call into WebAssembly ended with a trap with the given code.
+    Traps(TrapCode),
+    /// A call into WebAssembly ran out of gas.
+    GasExhausted,
+    /// A function reference from another instance or from the host was written to the table
+    /// of an instance logging them.
+    ForeignFuncrefsInstalled,
+    /// The code of a dropped artifact was released to make room for new code.
+    CodeMemoryReclaimed,
+    /// The engine failed to load an artifact for lack of code memory.
+    CodeMemoryExhausted,
+    /// The result of a view call was not cached because the call was nondeterministic.
+    NondeterministicViewCalls,
+    /// A trampoline calling functions from the host was compiled on demand, as none was
+    /// installed for their signature.
+    TrampolinesCompiled,
+    /// A page of the memories or tables of an instance was touched at instantiation, as
+    /// configured by its pretouch policy.
+    PagesPretouched,
+    /// A frame of the backtrace of a trap was found in the symbolication cache of the store.
+    SymbolicationCacheHits,
+    /// A frame of the backtrace of a trap was not found in the symbolication cache of the
+    /// store, and was symbolicated.
+    SymbolicationCacheMisses,
+}
+
+impl Counter {
+    /// The stable name of this counter.
+    ///
+    /// [`Counter::Traps`] shares its name across trap codes, which are told
+    /// apart by the `Display` of the [`TrapCode`].
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CompilationsStarted => "wasmer_compilations_started_total",
+            Self::CompilationsSucceeded => "wasmer_compilations_succeeded_total",
+            Self::CompilationsFailed => "wasmer_compilations_failed_total",
+            Self::ArtifactsLoaded => "wasmer_artifacts_loaded_total",
+            Self::PipelinedLoads => "wasmer_pipelined_loads_total",
+            Self::ArtifactsUnloaded => "wasmer_artifacts_unloaded_total",
+            Self::InstancesCreated => "wasmer_instances_created_total",
+            Self::InstancesDestroyed => "wasmer_instances_destroyed_total",
+            Self::Traps(_) => "wasmer_traps_total",
+            Self::GasExhausted => "wasmer_gas_exhausted_total",
+            Self::ForeignFuncrefsInstalled => "wasmer_foreign_funcrefs_installed_total",
+            Self::CodeMemoryReclaimed => "wasmer_code_memory_reclaimed_total",
+            Self::CodeMemoryExhausted => "wasmer_code_memory_exhausted_total",
+            Self::NondeterministicViewCalls => "wasmer_nondeterministic_view_calls_total",
+            Self::TrampolinesCompiled => "wasmer_trampolines_compiled_total",
+            Self::PagesPretouched => "wasmer_pages_pretouched_total",
+            Self::SymbolicationCacheHits => "wasmer_symbolication_cache_hits_total",
+            Self::SymbolicationCacheMisses => "wasmer_symbolication_cache_misses_total",
+        }
+    }
+}
+
+/// A metric whose value can go up and down.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Gauge {
+    /// Bytes of executable memory mapped by the engine for compiled code.
+    CodeBytes,
+    /// Bytes of compiled code locked in memory by pinning the hot functions of artifacts.
+    PinnedCodeBytes,
+}
+
+impl Gauge {
+    /// The stable name of this gauge.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CodeBytes => "wasmer_code_bytes",
+            Self::PinnedCodeBytes => "wasmer_pinned_code_bytes",
+        }
+    }
+}
+
+/// A metric recording how long an operation took.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Timer {
+    /// Time spent compiling a module, successfully or not.
+    Compilation,
+    /// Time spent touching the pages of an instance at instantiation, as configured by its
+    /// pretouch policy.
+    Pretouch,
+}
+
+impl Timer {
+    /// The stable name of this timer.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Compilation => "wasmer_compilation_seconds",
+            Self::Pretouch => "wasmer_pretouch_seconds",
+        }
+    }
+}
+
+/// A receiver of engine metrics.
+///
+/// Calls are made synchronously from the code paths being measured, so
+/// implementations should be cheap and must not call back into the engine.
+pub trait MetricsSink: Send + Sync {
+    /// Add `value` to `counter`.
+    fn increment(&self, counter: Counter, value: u64);
+
+    /// Add `delta` to `gauge`.
+    fn adjust(&self, gauge: Gauge, delta: i64);
+
+    /// Record one occurrence of `timer` that took `duration`.
+    fn observe(&self, timer: Timer, duration: Duration);
+}
+
+/// Record a trap with `code` into `sink`.
+pub fn record_trap(sink: &dyn MetricsSink, code: TrapCode) {
+    sink.increment(Counter::Traps(code), 1);
+    if code == TrapCode::GasExceeded {
+        sink.increment(Counter::GasExhausted, 1);
+    }
+}
+
+const TRAP_CODES: [TrapCode; 17] = [
+    TrapCode::StackOverflow,
+    TrapCode::HeapAccessOutOfBounds,
+    TrapCode::HeapMisaligned,
+    TrapCode::TableAccessOutOfBounds,
+    TrapCode::OutOfBounds,
+    TrapCode::IndirectCallToNull,
+    TrapCode::BadSignature,
+    TrapCode::IntegerOverflow,
+    TrapCode::IntegerDivisionByZero,
+    TrapCode::BadConversionToInteger,
+    TrapCode::UnreachableCodeReached,
+    TrapCode::UnalignedAtomic,
+    TrapCode::GasExceeded,
+    TrapCode::ReadOnlyInstance,
+    TrapCode::ForeignFuncref,
+    TrapCode::PoisonedInstance,
+    TrapCode::Cancelled,
+];
+
+/// A [`MetricsSink`] accumulating the metrics into atomic counters.
+#[derive(Debug, Default)]
+pub struct AtomicMetricsSink {
+    compilations_started: AtomicU64,
+    compilations_succeeded: AtomicU64,
+    compilations_failed: AtomicU64,
+    compilation_nanos: AtomicU64,
+    artifacts_loaded: AtomicU64,
+    pipelined_loads: AtomicU64,
+    artifacts_unloaded: AtomicU64,
+    instances_created: AtomicU64,
+    instances_destroyed: AtomicU64,
+    traps: [AtomicU64; TRAP_CODES.len()],
+    /// The counts of the [`TrapCode::Custom`] traps, by code.
+    custom_traps: Mutex<HashMap<u16, u64>>,
+    gas_exhausted: AtomicU64,
+    foreign_funcrefs_installed: AtomicU64,
+    code_memory_reclaimed: AtomicU64,
+    code_memory_exhausted: AtomicU64,
+    nondeterministic_view_calls: AtomicU64,
+    trampolines_compiled: AtomicU64,
+    pages_pretouched: AtomicU64,
+    pretouch_nanos: AtomicU64,
+    symbolication_cache_hits: AtomicU64,
+    symbolication_cache_misses: AtomicU64,
+    code_bytes: AtomicI64,
+    pinned_code_bytes: AtomicI64,
+}
+
+impl AtomicMetricsSink {
+    /// Create a sink with every metric at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the current value of every metric.
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        let custom_traps = self.custom_traps.lock().unwrap();
+        let custom_traps = custom_traps
+            .iter()
+            .map(|(code, count)| (TrapCode::Custom(*code), *count));
+        MetricsSnapshot {
+            compilations_started: load(&self.compilations_started),
+            compilations_succeeded: load(&self.compilations_succeeded),
+            compilations_failed: load(&self.compilations_failed),
+            compilation_time: Duration::from_nanos(load(&self.compilation_nanos)),
+            artifacts_loaded: load(&self.artifacts_loaded),
+            pipelined_loads: load(&self.pipelined_loads),
+            artifacts_unloaded: load(&self.artifacts_unloaded),
+            instances_created: load(&self.instances_created),
+            instances_destroyed: load(&self.instances_destroyed),
+            traps: TRAP_CODES
+                .iter()
+                .zip(self.traps.iter())
+                .map(|(code, count)| (*code, load(count)))
+                .filter(|(_, count)| *count != 0)
+                .chain(custom_traps)
+                .collect(),
+            gas_exhausted: load(&self.gas_exhausted),
+            foreign_funcrefs_installed: load(&self.foreign_funcrefs_installed),
+            code_memory_reclaimed: load(&self.code_memory_reclaimed),
+            code_memory_exhausted: load(&self.code_memory_exhausted),
+            nondeterministic_view_calls: load(&self.nondeterministic_view_calls),
+            trampolines_compiled: load(&self.trampolines_compiled),
+            pages_pretouched: load(&self.pages_pretouched),
+            pretouch_time: Duration::from_nanos(load(&self.pretouch_nanos)),
+            symbolication_cache_hits: load(&self.symbolication_cache_hits),
+            symbolication_cache_misses: load(&self.symbolication_cache_misses),
+            code_bytes: self.code_bytes.load(Ordering::Relaxed),
+            pinned_code_bytes: self.pinned_code_bytes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl MetricsSink for AtomicMetricsSink {
+    fn increment(&self, counter: Counter, value: u64) {
+        let counter = match counter {
+            Counter::CompilationsStarted => &self.compilations_started,
+            Counter::CompilationsSucceeded => &self.compilations_succeeded,
+            Counter::CompilationsFailed => &self.compilations_failed,
+            Counter::ArtifactsLoaded => &self.artifacts_loaded,
+            Counter::PipelinedLoads => &self.pipelined_loads,
+            Counter::ArtifactsUnloaded => &self.artifacts_unloaded,
+            Counter::InstancesCreated => &self.instances_created,
+            Counter::InstancesDestroyed => &self.instances_destroyed,
+            Counter::Traps(TrapCode::Custom(code)) => {
+                *self.custom_traps.lock().unwrap().entry(code).or_insert(0) += value;
+                return;
+            }
+            Counter::Traps(code) => &self.traps[code.to_raw() as usize],
+            Counter::GasExhausted => &self.gas_exhausted,
+            Counter::ForeignFuncrefsInstalled => &self.foreign_funcrefs_installed,
+            Counter::CodeMemoryReclaimed => &self.code_memory_reclaimed,
+            Counter::CodeMemoryExhausted => &self.code_memory_exhausted,
+            Counter::NondeterministicViewCalls => &self.nondeterministic_view_calls,
+            Counter::TrampolinesCompiled => &self.trampolines_compiled,
+            Counter::PagesPretouched => &self.pages_pretouched,
+            Counter::SymbolicationCacheHits => &self.symbolication_cache_hits,
+            Counter::SymbolicationCacheMisses => &self.symbolication_cache_misses,
+        };
+        counter.fetch_add(value, Ordering::Relaxed);
+    }
+
+    fn adjust(&self, gauge: Gauge, delta: i64) {
+        match gauge {
+            Gauge::CodeBytes => self.code_bytes.fetch_add(delta, Ordering::Relaxed),
+            Gauge::PinnedCodeBytes => self.pinned_code_bytes.fetch_add(delta, Ordering::Relaxed),
+        };
+    }
+
+    fn observe(&self, timer: Timer, duration: Duration) {
+        let nanos = match timer {
+            Timer::Compilation => &self.compilation_nanos,
+            Timer::Pretouch => &self.pretouch_nanos,
+        };
+        nanos.fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+}
+
+/// The values of the metrics of an [`AtomicMetricsSink`] at some point in time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    /// See [`Counter::CompilationsStarted`].
+    pub compilations_started: u64,
+    /// See [`Counter::CompilationsSucceeded`].
+    pub compilations_succeeded: u64,
+    /// See [`Counter::CompilationsFailed`].
+    pub compilations_failed: u64,
+    /// Total of the [`Timer::Compilation`] durations.
+    pub compilation_time: Duration,
+    /// See [`Counter::ArtifactsLoaded`].
+    pub artifacts_loaded: u64,
+    /// See [`Counter::PipelinedLoads`].
+    pub pipelined_loads: u64,
+    /// See [`Counter::ArtifactsUnloaded`].
+    pub artifacts_unloaded: u64,
+    /// See [`Counter::InstancesCreated`].
+    pub instances_created: u64,
+    /// See [`Counter::InstancesDestroyed`].
+    pub instances_destroyed: u64,
+    /// See [`Counter::Traps`]. Trap codes that never occurred are omitted.
+    pub traps: HashMap<TrapCode, u64>,
+    /// See [`Counter::GasExhausted`].
+    pub gas_exhausted: u64,
+    /// See [`Counter::ForeignFuncrefsInstalled`].
+    pub foreign_funcrefs_installed: u64,
+    /// See [`Counter::CodeMemoryReclaimed`].
+    pub code_memory_reclaimed: u64,
+    /// See [`Counter::CodeMemoryExhausted`].
+    pub code_memory_exhausted: u64,
+    /// See [`Counter::NondeterministicViewCalls`].
+    pub nondeterministic_view_calls: u64,
+    /// See [`Counter::TrampolinesCompiled`].
+    pub trampolines_compiled: u64,
+    /// See [`Counter::PagesPretouched`].
+    pub pages_pretouched: u64,
+    /// Total of the [`Timer::Pretouch`] durations.
+    pub pretouch_time: Duration,
+    /// See [`Counter::SymbolicationCacheHits`].
+    pub symbolication_cache_hits: u64,
+    /// See [`Counter::SymbolicationCacheMisses`].
+    pub symbolication_cache_misses: u64,
+    /// See [`Gauge::CodeBytes`].
+    pub code_bytes: i64,
+    /// See [`Gauge::PinnedCodeBytes`].
+    pub pinned_code_bytes: i64,
+}
@@ -6,6 +6,8 @@
 
 use more_asserts::assert_le;
 use more_asserts::assert_lt;
+use std::convert::TryFrom;
+use std::fs::File;
 use std::io;
 use std::ptr;
 use std::slice;
@@ -227,6 +229,246 @@ impl Mmap {
         Ok(())
     }
 
+    /// Make the memory starting at `start` and extending for `len` bytes inaccessible again,
+    /// releasing its physical memory. `start` and `len` must be native page-size multiples
+    /// and describe a range within `self`'s accessible memory.
+    #[cfg(not(target_os = "windows"))]
+    pub fn make_inaccessible(&mut self, start: usize, len: usize) -> Result<(), String> {
+        let page_size = region::page::size();
+        assert_eq!(start & (page_size - 1), 0);
+        assert_eq!(len & (page_size - 1), 0);
+        assert_le!(len, self.len);
+        assert_le!(start, self.len - len);
+        if len == 0 {
+            return Ok(());
+        }
+
+        // Fresh inaccessible pages release the old ones, and read as zeros once accessible.
+        let ptr = unsafe {
+            libc::mmap(
+                (self.ptr + start) as *mut libc::c_void,
+                len,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_FIXED,
+                -1,
+                0,
+            )
+        };
+        if ptr as isize == -1_isize {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        Ok(())
+    }
+
+    /// Make the memory starting at `start` and extending for `len` bytes inaccessible again,
+    /// decommitting it. `start` and `len` must be native page-size multiples and describe a
+    /// range within `self`'s accessible memory.
+    #[cfg(target_os = "windows")]
+    pub fn make_inaccessible(&mut self, start: usize, len: usize) -> Result<(), String> {
+        use winapi::ctypes::c_void;
+        use winapi::um::memoryapi::VirtualFree;
+        use winapi::um::winnt::MEM_DECOMMIT;
+        let page_size = region::page::size();
+        assert_eq!(start & (page_size - 1), 0);
+        assert_eq!(len & (page_size - 1), 0);
+        assert_le!(len, self.len);
+        assert_le!(start, self.len - len);
+        if len == 0 {
+            return Ok(());
+        }
+
+        let ptr = self.ptr as *const u8;
+        if unsafe { VirtualFree(ptr.add(start) as *mut c_void, len, MEM_DECOMMIT) } == 0 {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        Ok(())
+    }
+
+    /// Release the physical memory of the `len` bytes starting at `start`, which stay
+    /// accessible and read as zeros afterwards. `start` and `len` must be native page-size
+    /// multiples and describe a range within `self`'s accessible memory.
+    ///
+    /// The pages are replaced without ever being inaccessible, so that concurrent accesses
+    /// read either their old contents or zeros, and never fault.
+    #[cfg(target_os = "linux")]
+    pub fn discard(&mut self, start: usize, len: usize) -> Result<(), String> {
+        let page_size = region::page::size();
+        assert_eq!(start & (page_size - 1), 0);
+        assert_eq!(len & (page_size - 1), 0);
+        assert_le!(len, self.len);
+        assert_le!(start, self.len - len);
+        if len == 0 {
+            return Ok(());
+        }
+
+        // Private anonymous pages are zero-filled on their next access.
+        let ptr = (self.ptr + start) as *mut libc::c_void;
+        if unsafe { libc::madvise(ptr, len, libc::MADV_DONTNEED) } != 0 {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        Ok(())
+    }
+
+    /// Release the physical memory of the `len` bytes starting at `start`, which stay
+    /// accessible and read as zeros afterwards. `start` and `len` must be native page-size
+    /// multiples and describe a range within `self`'s accessible memory.
+    ///
+    /// The pages are replaced without ever being inaccessible, so that concurrent accesses
+    /// read either their old contents or zeros, and never fault.
+    #[cfg(all(not(target_os = "linux"), not(target_os = "windows")))]
+    pub fn discard(&mut self, start: usize, len: usize) -> Result<(), String> {
+        // `MADV_DONTNEED` may keep the contents elsewhere, but mapping fresh pages over the
+        // range replaces them atomically.
+        self.remap_zeroed(start, len)
+    }
+
+    /// Replace the `len` bytes starting at `start` with fresh zeroed pages, which stay
+    /// accessible. `start` and `len` must be native page-size multiples and describe a range
+    /// within `self`'s accessible memory.
+    ///
+    /// Unlike [`Mmap::discard`] on Linux, this zeroes pages mapped from a file too, which
+    /// `MADV_DONTNEED` would bring back to the contents of the file. Concurrent accesses
+    /// read either their old contents or zeros, and never fault.
+    #[cfg(not(target_os = "windows"))]
+    pub fn remap_zeroed(&mut self, start: usize, len: usize) -> Result<(), String> {
+        let page_size = region::page::size();
+        assert_eq!(start & (page_size - 1), 0);
+        assert_eq!(len & (page_size - 1), 0);
+        assert_le!(len, self.len);
+        assert_le!(start, self.len - len);
+        if len == 0 {
+            return Ok(());
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                (self.ptr + start) as *mut libc::c_void,
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_FIXED,
+                -1,
+                0,
+            )
+        };
+        if ptr as isize == -1_isize {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        Ok(())
+    }
+
+    /// Unmap the pages past the first `len` bytes, which must be a native page-size multiple
+    /// no larger than the length of the mapping.
+    #[cfg(not(target_os = "windows"))]
+    pub fn truncate(&mut self, len: usize) -> Result<(), String> {
+        let page_size = region::page::size();
+        assert_eq!(len & (page_size - 1), 0);
+        assert_le!(len, self.len);
+        if len == self.len {
+            return Ok(());
+        }
+        let ptr = (self.ptr + len) as *mut libc::c_void;
+        if unsafe { libc::munmap(ptr, self.len - len) } != 0 {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        self.len = len;
+        Ok(())
+    }
+
+    /// Zero the `len` bytes starting at `start`. `start` and `len` must be native page-size
+    /// multiples and describe a range within `self`'s accessible memory.
+    ///
+    /// Decommitting pages would make them fault until committed again, and resetting them
+    /// leaves their contents undefined, so the pages are zeroed in place and keep their
+    /// physical memory.
+    #[cfg(target_os = "windows")]
+    pub fn discard(&mut self, start: usize, len: usize) -> Result<(), String> {
+        let page_size = region::page::size();
+        assert_eq!(start & (page_size - 1), 0);
+        assert_eq!(len & (page_size - 1), 0);
+        assert_le!(len, self.len);
+        assert_le!(start, self.len - len);
+
+        crate::nontemporal::zero(&mut self.as_mut_slice()[start..start + len]);
+        Ok(())
+    }
+
+    /// Return how many of the first `len` bytes of the memory are resident in physical
+    /// memory, in whole native pages. `len` must describe a range within `self`'s accessible
+    /// memory.
+    #[cfg(not(target_os = "windows"))]
+    pub fn resident_bytes(&self, len: usize) -> Result<usize, String> {
+        let page_size = region::page::size();
+        assert_le!(len, self.len);
+        let pages = round_up_to_page_size(len, page_size) / page_size;
+        if pages == 0 {
+            return Ok(0);
+        }
+
+        let mut residency = vec![0; pages];
+        let ptr = self.ptr as *mut libc::c_void;
+        if unsafe { libc::mincore(ptr, len, residency.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error().to_string());
+        }
+        Ok(residency.iter().filter(|page| **page & 1 != 0).count() * page_size)
+    }
+
+    /// Return how many of the first `len` bytes of the memory are resident in physical
+    /// memory, assuming they all are, as residency is not queried on Windows.
+    #[cfg(target_os = "windows")]
+    pub fn resident_bytes(&self, len: usize) -> Result<usize, String> {
+        assert_le!(len, self.len);
+        Ok(len)
+    }
+
+    /// Map the contents of `file` privately and read-only, without copying them.
+    ///
+    /// The file must not be truncated while mapped: accessing the pages past its new end
+    /// would fault.
+    #[cfg(not(target_os = "windows"))]
+    pub fn map_file(file: &File) -> io::Result<Self> {
+        use std::os::unix::io::AsRawFd;
+
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "the file is too large"))?;
+        // Mmap may return EINVAL if the size is zero, so just
+        // special-case that.
+        if len == 0 {
+            return Ok(Self::new());
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr as isize == -1_isize {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            ptr: ptr as usize,
+            len,
+        })
+    }
+
+    /// Read the contents of `file` into new pages, as files are not mapped on Windows.
+    #[cfg(target_os = "windows")]
+    pub fn map_file(file: &File) -> io::Result<Self> {
+        use std::io::Read;
+
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "the file is too large"))?;
+        let mut mmap =
+            Self::with_at_least(len).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let mut file = file;
+        file.read_exact(&mut mmap.as_mut_slice()[..len])?;
+        mmap.len = len;
+        Ok(mmap)
+    }
+
     /// Return the allocated memory as a slice of u8.
     pub fn as_slice(&self) -> &[u8] {
         unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
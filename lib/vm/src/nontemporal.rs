@@ -0,0 +1,166 @@
+//! Copying and zeroing large ranges of memory with non-temporal stores, which bypass the
+//! caches: initializing the memories of an instance then does not evict the data its caller
+//! works on, nor fill the caches with pages the instance may not read soon.
+
+/// The length from which ranges are written with non-temporal stores. Smaller ranges fit in
+/// the caches, and are likely read soon after being written.
+pub(crate) const NON_TEMPORAL_THRESHOLD: usize = 1 << 20;
+
+/// Copy `src` into `dst`, which must be as long.
+pub(crate) fn copy(dst: &mut [u8], src: &[u8]) {
+    assert_eq!(dst.len(), src.len());
+    #[cfg(target_arch = "x86_64")]
+    {
+        if dst.len() >= NON_TEMPORAL_THRESHOLD {
+            // SAFETY: both ranges are `dst.len()` bytes long, and the CPU features used are
+            // checked at runtime or part of the baseline.
+            unsafe {
+                if is_x86_feature_detected!("avx") {
+                    x86_64::copy_avx(dst.as_mut_ptr(), src.as_ptr(), dst.len());
+                } else {
+                    x86_64::copy_sse2(dst.as_mut_ptr(), src.as_ptr(), dst.len());
+                }
+            }
+            return;
+        }
+    }
+    dst.copy_from_slice(src);
+}
+
+/// Zero `dst`.
+pub(crate) fn zero(dst: &mut [u8]) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if dst.len() >= NON_TEMPORAL_THRESHOLD {
+            // SAFETY: as in `copy`.
+            unsafe {
+                if is_x86_feature_detected!("avx") {
+                    x86_64::zero_avx(dst.as_mut_ptr(), dst.len());
+                } else {
+                    x86_64::zero_sse2(dst.as_mut_ptr(), dst.len());
+                }
+            }
+            return;
+        }
+    }
+    dst.fill(0);
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86_64 {
+    use std::arch::x86_64::{
+        __m128i, __m256i, _mm256_loadu_si256, _mm256_setzero_si256, _mm256_stream_si256,
+        _mm_loadu_si128, _mm_setzero_si128, _mm_sfence, _mm_stream_si128,
+    };
+    use std::ptr;
+
+    /// Split the `len` bytes at `dst` into the bytes up to the first `lane`-aligned address,
+    /// which non-temporal stores require, and a number of whole lanes. The remaining bytes
+    /// follow the lanes.
+    fn split(dst: *mut u8, len: usize, lane: usize) -> (usize, usize) {
+        let head = dst.align_offset(lane).min(len);
+        (head, (len - head) / lane)
+    }
+
+    /// Copy the bytes around the `lanes` lanes of `lane` bytes written with non-temporal
+    /// stores, and order these stores before the following ones, as they are not otherwise.
+    unsafe fn finish_copy(dst: *mut u8, src: *const u8, len: usize, head: usize, end: usize) {
+        ptr::copy_nonoverlapping(src, dst, head);
+        ptr::copy_nonoverlapping(src.add(end), dst.add(end), len - end);
+        _mm_sfence();
+    }
+
+    /// Zero the bytes around the lanes zeroed with non-temporal stores, as `finish_copy`.
+    unsafe fn finish_zero(dst: *mut u8, len: usize, head: usize, end: usize) {
+        ptr::write_bytes(dst, 0, head);
+        ptr::write_bytes(dst.add(end), 0, len - end);
+        _mm_sfence();
+    }
+
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn copy_avx(dst: *mut u8, src: *const u8, len: usize) {
+        const LANE: usize = 32;
+        let (head, lanes) = split(dst, len, LANE);
+        for lane in 0..lanes {
+            let offset = head + lane * LANE;
+            let value = _mm256_loadu_si256(src.add(offset) as *const __m256i);
+            _mm256_stream_si256(dst.add(offset) as *mut __m256i, value);
+        }
+        finish_copy(dst, src, len, head, head + lanes * LANE);
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn copy_sse2(dst: *mut u8, src: *const u8, len: usize) {
+        const LANE: usize = 16;
+        let (head, lanes) = split(dst, len, LANE);
+        for lane in 0..lanes {
+            let offset = head + lane * LANE;
+            let value = _mm_loadu_si128(src.add(offset) as *const __m128i);
+            _mm_stream_si128(dst.add(offset) as *mut __m128i, value);
+        }
+        finish_copy(dst, src, len, head, head + lanes * LANE);
+    }
+
+    #[target_feature(enable = "avx")]
+    pub(super) unsafe fn zero_avx(dst: *mut u8, len: usize) {
+        const LANE: usize = 32;
+        let (head, lanes) = split(dst, len, LANE);
+        let zero = _mm256_setzero_si256();
+        for lane in 0..lanes {
+            _mm256_stream_si256(dst.add(head + lane * LANE) as *mut __m256i, zero);
+        }
+        finish_zero(dst, len, head, head + lanes * LANE);
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn zero_sse2(dst: *mut u8, len: usize) {
+        const LANE: usize = 16;
+        let (head, lanes) = split(dst, len, LANE);
+        let zero = _mm_setzero_si128();
+        for lane in 0..lanes {
+            _mm_stream_si128(dst.add(head + lane * LANE) as *mut __m128i, zero);
+        }
+        finish_zero(dst, len, head, head + lanes * LANE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bytes that differ from one position to the next, and from zero.
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8 + 1).collect()
+    }
+
+    #[test]
+    fn test_copy_unaligned_ranges() {
+        let len = NON_TEMPORAL_THRESHOLD + 77;
+        let src = pattern(len + 64);
+        for (dst_offset, src_offset) in [(0, 0), (1, 0), (0, 3), (17, 31), (33, 5)] {
+            let mut dst = vec![0; len + 64];
+            copy(
+                &mut dst[dst_offset..dst_offset + len],
+                &src[src_offset..src_offset + len],
+            );
+            assert!(dst[..dst_offset].iter().all(|&byte| byte == 0));
+            assert_eq!(
+                &dst[dst_offset..dst_offset + len],
+                &src[src_offset..src_offset + len]
+            );
+            assert!(dst[dst_offset + len..].iter().all(|&byte| byte == 0));
+        }
+    }
+
+    #[test]
+    fn test_zero_unaligned_ranges() {
+        let len = NON_TEMPORAL_THRESHOLD + 13;
+        for offset in [0, 1, 15, 31] {
+            let mut dst = pattern(len + 64);
+            zero(&mut dst[offset..offset + len]);
+            assert_eq!(&dst[..offset], &pattern(offset)[..]);
+            assert!(dst[offset..offset + len].iter().all(|&byte| byte == 0));
+            assert_eq!(&dst[offset + len..], &pattern(len + 64)[offset + len..]);
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Allocation of the pages holding compiled code, delegated to the embedder.
+
+use crate::mmap::Mmap;
+use std::collections::HashMap;
+use std::fmt;
+use std::slice;
+use std::sync::{Arc, Mutex};
+
+/// The accesses allowed to pages allocated by a [`PageAllocator`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PageProtection {
+    /// The pages can be read and written.
+    ReadWrite,
+    /// The pages can be read and executed.
+    ReadExecute,
+}
+
+/// Allocates the pages holding the code and custom sections of loaded artifacts.
+///
+/// Each artifact gets a single allocation from [`PageAllocator::alloc_rx`]: the engine writes
+/// its code then its data sections into it, on separate pages, and then protects the pages
+/// holding the code with [`PageProtection::ReadExecute`]. This lets embedders without `mmap`,
+/// such as enclaves, supply pages from their own allocator, see
+/// `UniversalEngine::set_page_allocator`.
+///
+/// # Safety
+///
+/// Allocations must be aligned to [`PageAllocator::page_size`], zeroed, readable and writable
+/// until protected otherwise, and stay valid until given back to [`PageAllocator::free`].
+pub unsafe trait PageAllocator: fmt::Debug + Send + Sync {
+    /// The size of the pages, a power of two that allocations and protections are aligned to.
+    fn page_size(&self) -> usize;
+
+    /// Allocate `len` bytes of read-write pages, some of which are made executable later,
+    /// where `len` is a multiple of the page size.
+    fn alloc_rx(&self, len: usize) -> Result<*mut u8, String>;
+
+    /// Change the accesses allowed to the `len` bytes of pages at `ptr`, within a single
+    /// allocation.
+    ///
+    /// # Safety
+    ///
+    /// The pages must not be accessed in a way `protection` disallows afterwards.
+    unsafe fn protect(
+        &self,
+        ptr: *mut u8,
+        len: usize,
+        protection: PageProtection,
+    ) -> Result<(), String>;
+
+    /// Give back the pages past the first `new_len` bytes of the allocation of `len` bytes at
+    /// `ptr`, where `new_len` is a multiple of the page size, returning whether they were.
+    ///
+    /// Allocations that were shrunk are given back to [`PageAllocator::free`] with their new
+    /// length. This keeps the allocation whole by default.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` and `len` must be those of an allocation of this allocator, whose pages past
+    /// `new_len` are not used afterwards.
+    unsafe fn shrink(&self, _ptr: *mut u8, _len: usize, _new_len: usize) -> bool {
+        false
+    }
+
+    /// Give back the `len` bytes of pages allocated at `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` and `len` must be those of an allocation of this allocator, not used afterwards.
+    unsafe fn free(&self, ptr: *mut u8, len: usize);
+}
+
+/// The [`PageAllocator`] mapping pages from the operating system, used unless the embedder
+/// sets another one.
+#[derive(Debug, Default)]
+pub struct SystemPageAllocator {
+    /// The mappings allocated, by address.
+    mappings: Mutex<HashMap<usize, Mmap>>,
+}
+
+unsafe impl PageAllocator for SystemPageAllocator {
+    fn page_size(&self) -> usize {
+        region::page::size()
+    }
+
+    fn alloc_rx(&self, len: usize) -> Result<*mut u8, String> {
+        let mut mmap = Mmap::with_at_least(len)?;
+        let ptr = mmap.as_mut_ptr();
+        self.mappings.lock().unwrap().insert(ptr as usize, mmap);
+        Ok(ptr)
+    }
+
+    unsafe fn protect(
+        &self,
+        ptr: *mut u8,
+        len: usize,
+        protection: PageProtection,
+    ) -> Result<(), String> {
+        let protection = match protection {
+            PageProtection::ReadWrite => region::Protection::READ_WRITE,
+            PageProtection::ReadExecute => region::Protection::READ_EXECUTE,
+        };
+        region::protect(ptr, len, protection).map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    unsafe fn shrink(&self, ptr: *mut u8, _len: usize, new_len: usize) -> bool {
+        match self.mappings.lock().unwrap().get_mut(&(ptr as usize)) {
+            Some(mmap) => mmap.truncate(new_len).is_ok(),
+            None => false,
+        }
+    }
+
+    unsafe fn free(&self, ptr: *mut u8, _len: usize) {
+        self.mappings.lock().unwrap().remove(&(ptr as usize));
+    }
+}
+
+/// Pages allocated by a [`PageAllocator`], given back when dropped.
+#[derive(Debug)]
+pub struct Pages {
+    allocator: Arc<dyn PageAllocator>,
+    // Stored as a `usize` for `Pages` to be `Send` and `Sync`, like `Mmap`.
+    ptr: usize,
+    len: usize,
+}
+
+impl Pages {
+    /// No pages, from `allocator`.
+    pub fn empty(allocator: Arc<dyn PageAllocator>) -> Self {
+        let empty = Vec::<u8>::new();
+        Self {
+            allocator,
+            ptr: empty.as_ptr() as usize,
+            len: 0,
+        }
+    }
+
+    /// At least `len` bytes of pages, some to be made executable, from `allocator`.
+    pub fn executable(allocator: Arc<dyn PageAllocator>, len: usize) -> Result<Self, String> {
+        let len = round_up(len, allocator.page_size());
+        if len == 0 {
+            return Ok(Self::empty(allocator));
+        }
+        let ptr = allocator.alloc_rx(len)? as usize;
+        Ok(Self {
+            allocator,
+            ptr,
+            len,
+        })
+    }
+
+    /// The allocator of these pages.
+    pub fn allocator(&self) -> &Arc<dyn PageAllocator> {
+        &self.allocator
+    }
+
+    /// The number of bytes of these pages.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether there are no pages.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The start of the pages.
+    pub fn as_mut_ptr(&mut self) -> *mut u8 {
+        self.ptr as *mut u8
+    }
+
+    /// The contents of the pages.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr as *mut u8, self.len) }
+    }
+
+    /// Give back the pages past the first `len` bytes, if the allocator can, returning the
+    /// number of bytes left.
+    pub fn shrink(&mut self, len: usize) -> usize {
+        let len = round_up(len, self.allocator.page_size());
+        if len < self.len && unsafe { self.allocator.shrink(self.ptr as *mut u8, self.len, len) } {
+            self.len = len;
+        }
+        self.len
+    }
+
+    /// Make the first `len` bytes of these pages readable and executable, no longer writable.
+    pub fn make_executable(&mut self, len: usize) -> Result<(), String> {
+        assert!(len <= self.len);
+        let ptr = self.as_mut_ptr();
+        unsafe {
+            self.allocator
+                .protect(ptr, len, PageProtection::ReadExecute)
+        }
+    }
+}
+
+impl Drop for Pages {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            unsafe { self.allocator.free(self.ptr as *mut u8, self.len) }
+        }
+    }
+}
+
+fn round_up(size: usize, page_size: usize) -> usize {
+    (size + (page_size - 1)) & !(page_size - 1)
+}
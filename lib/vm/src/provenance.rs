@@ -0,0 +1,72 @@
+//! Telling which instance, if any, the function references stored in tables come from.
+//!
+//! A function reference carries the `vmctx` of the instance defining it, so its origin is
+//! found by looking that pointer up among the live instances. Nothing is stored in the
+//! references themselves, which keeps their layout, and `call_indirect`, unchanged.
+
+use crate::func_data_registry::VMFuncRef;
+use crate::vmcontext::VMContext;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+lazy_static::lazy_static! {
+    /// The identifier of each live instance, by address of its `vmctx`.
+    static ref INSTANCES: RwLock<HashMap<usize, InstanceId>> = RwLock::new(HashMap::new());
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// An identifier of an instance, unique within the process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct InstanceId(u64);
+
+impl InstanceId {
+    pub(crate) fn next() -> Self {
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Return the identifier as an integer.
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for InstanceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "instance {}", self.0)
+    }
+}
+
+/// Where a function reference comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FuncOrigin {
+    /// A function defined by the instance with this identifier.
+    Instance(InstanceId),
+    /// A host function, or a function of an instance that was dropped since.
+    Host,
+}
+
+impl FuncOrigin {
+    /// Return the origin of `funcref`, or `None` if it is null.
+    pub fn of(funcref: VMFuncRef) -> Option<Self> {
+        if funcref.is_null() {
+            return None;
+        }
+        // SAFETY: non-null function references point to live anyfuncs.
+        let vmctx = unsafe { (**funcref).vmctx.vmctx } as usize;
+        Some(match INSTANCES.read().unwrap().get(&vmctx) {
+            Some(id) => Self::Instance(*id),
+            None => Self::Host,
+        })
+    }
+}
+
+pub(crate) fn register(vmctx: *const VMContext, id: InstanceId) {
+    INSTANCES.write().unwrap().insert(vmctx as usize, id);
+}
+
+pub(crate) fn unregister(vmctx: *const VMContext) {
+    INSTANCES.write().unwrap().remove(&(vmctx as usize));
+}
@@ -0,0 +1,452 @@
+//! Read-only linear memories sharing one memory image.
+//!
+//! A [`MemoryImage`] holds the contents of a linear memory right after its data segments were
+//! applied. Every [`ReadOnlyMemory`] created from an image maps the same physical pages without
+//! write access, so that any number of them cost little more than their page tables. Writes
+//! from WebAssembly code fault, and the fault is turned into a
+//! [`TrapCode::ReadOnlyInstance`](crate::TrapCode::ReadOnlyInstance) trap.
+//!
+//! Images also back the writable memories of
+//! [`LinearMemory::from_definition_with_image`](crate::LinearMemory::from_definition_with_image),
+//! which map them copy-on-write.
+//!
+//! Images are only supported on Linux, where they are backed by sealed memfds.
+
+use crate::memory::{BaseStability, Memory, MemoryError, MemoryStyle};
+use crate::mmap::Mmap;
+use crate::vmcontext::VMMemoryDefinition;
+use std::fmt;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use wasmer_types::{MemoryType, Pages};
+
+#[cfg(target_os = "linux")]
+type ImageFd = std::os::unix::io::RawFd;
+/// Images cannot be created on other platforms.
+#[cfg(not(target_os = "linux"))]
+type ImageFd = std::convert::Infallible;
+
+/// The immutable contents of a linear memory, shared by the [`ReadOnlyMemory`]s created from it.
+pub struct MemoryImage {
+    fd: ImageFd,
+    size: Pages,
+}
+
+impl MemoryImage {
+    /// Create an image of `size` pages, zeroed except for `segments`, given as their offset and
+    /// contents and applied in order.
+    ///
+    /// The segments must be within the image.
+    #[cfg(target_os = "linux")]
+    pub fn new<'a>(
+        size: Pages,
+        segments: impl IntoIterator<Item = (usize, &'a [u8])>,
+    ) -> Result<Self, MemoryError> {
+        use std::io;
+
+        let error = || MemoryError::Region(io::Error::last_os_error().to_string());
+        let len = size.bytes().0;
+        let fd = unsafe {
+            libc::memfd_create(
+                b"wasmer-memory-image\0".as_ptr().cast(),
+                libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+            )
+        };
+        if fd < 0 {
+            return Err(error());
+        }
+        // Own the descriptor right away so that it is closed on errors.
+        let image = Self { fd, size };
+        if unsafe { libc::ftruncate(fd, len as libc::off_t) } != 0 {
+            return Err(error());
+        }
+        for (offset, data) in segments {
+            assert!(
+                offset
+                    .checked_add(data.len())
+                    .map_or(false, |end| end <= len),
+                "data segment out of the bounds of the memory image"
+            );
+            let mut written = 0;
+            while written < data.len() {
+                let result = unsafe {
+                    libc::pwrite(
+                        fd,
+                        data[written..].as_ptr().cast(),
+                        data.len() - written,
+                        (offset + written) as libc::off_t,
+                    )
+                };
+                if result < 0 {
+                    if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    return Err(error());
+                }
+                written += result as usize;
+            }
+        }
+        let seals =
+            libc::F_SEAL_SEAL | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE;
+        if unsafe { libc::fcntl(fd, libc::F_ADD_SEALS, seals) } != 0 {
+            return Err(error());
+        }
+        Ok(image)
+    }
+
+    /// Create an image of `size` pages, zeroed except for `segments`, given as their offset and
+    /// contents and applied in order.
+    ///
+    /// The segments must be within the image.
+    #[cfg(not(target_os = "linux"))]
+    pub fn new<'a>(
+        _size: Pages,
+        _segments: impl IntoIterator<Item = (usize, &'a [u8])>,
+    ) -> Result<Self, MemoryError> {
+        Err(MemoryError::Generic(
+            "read-only memories are only supported on Linux".to_string(),
+        ))
+    }
+
+    /// Returns the size of the image.
+    pub fn size(&self) -> Pages {
+        self.size
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for MemoryImage {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+impl fmt::Debug for MemoryImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryImage")
+            .field("size", &self.size)
+            .finish()
+    }
+}
+
+/// A linear memory mapping a [`MemoryImage`] without write access.
+///
+/// It cannot grow, and WebAssembly code writing to it traps with
+/// [`TrapCode::ReadOnlyInstance`](crate::TrapCode::ReadOnlyInstance). The host must not write to
+/// it either: doing so outside of a call into WebAssembly crashes the process.
+#[derive(Debug)]
+pub struct ReadOnlyMemory {
+    image: Arc<MemoryImage>,
+    /// The reservation the image is mapped at the beginning of.
+    mmap: Mmap,
+    memory: MemoryType,
+    style: MemoryStyle,
+    vm_memory_definition: NonNull<VMMemoryDefinition>,
+}
+
+/// The definition is only written at creation.
+unsafe impl Send for ReadOnlyMemory {}
+unsafe impl Sync for ReadOnlyMemory {}
+
+impl ReadOnlyMemory {
+    /// Map `image` as a local memory of type `memory`, whose definition is stored at
+    /// `vm_memory_location`.
+    ///
+    /// # Safety
+    /// - `vm_memory_location` must point to a valid location in VM memory.
+    pub unsafe fn from_definition(
+        image: Arc<MemoryImage>,
+        memory: &MemoryType,
+        style: &MemoryStyle,
+        mut vm_memory_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<Self, MemoryError> {
+        let len = image.size.bytes().0;
+        let reserved_pages = match style {
+            MemoryStyle::Dynamic { .. } => image.size,
+            MemoryStyle::Static { bound, .. } => std::cmp::max(*bound, image.size),
+        };
+        let request_bytes = reserved_pages
+            .bytes()
+            .0
+            .checked_add(style.offset_guard_size() as usize)
+            .ok_or_else(|| MemoryError::InvalidMemory {
+                reason: "the reservation of the memory overflows".to_string(),
+            })?;
+        let mut mmap = Mmap::accessible_reserved(0, request_bytes).map_err(MemoryError::Region)?;
+        if len != 0 {
+            map_image(&image, mmap.as_mut_ptr(), len)?;
+        }
+
+        let definition = vm_memory_location.as_mut();
+        definition.base = mmap.as_mut_ptr();
+        definition.current_length = len;
+        let mut memory = *memory;
+        memory.minimum = image.size;
+        Ok(Self {
+            image,
+            mmap,
+            memory,
+            style: style.clone(),
+            vm_memory_definition: vm_memory_location,
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn map_image(image: &MemoryImage, base: *mut u8, len: usize) -> Result<(), MemoryError> {
+    let ptr = libc::mmap(
+        base.cast(),
+        len,
+        libc::PROT_READ,
+        libc::MAP_SHARED | libc::MAP_FIXED,
+        image.fd,
+        0,
+    );
+    if ptr == libc::MAP_FAILED {
+        return Err(MemoryError::Region(
+            std::io::Error::last_os_error().to_string(),
+        ));
+    }
+    signals::register(base as usize, len).map_err(|error| {
+        MemoryError::Region(format!("unable to install the SIGSEGV handler: {}", error))
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+unsafe fn map_image(image: &MemoryImage, _base: *mut u8, _len: usize) -> Result<(), MemoryError> {
+    match image.fd {}
+}
+
+/// Map `image` over the accessible pages at `base`, privately and with write access, so that
+/// the pages are shared with the image until written.
+#[cfg(target_os = "linux")]
+pub(crate) unsafe fn map_image_private(
+    image: &MemoryImage,
+    base: *mut u8,
+) -> Result<(), MemoryError> {
+    let len = image.size.bytes().0;
+    if len == 0 {
+        return Ok(());
+    }
+    let ptr = libc::mmap(
+        base.cast(),
+        len,
+        libc::PROT_READ | libc::PROT_WRITE,
+        libc::MAP_PRIVATE | libc::MAP_FIXED,
+        image.fd,
+        0,
+    );
+    if ptr == libc::MAP_FAILED {
+        return Err(MemoryError::Region(
+            std::io::Error::last_os_error().to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) unsafe fn map_image_private(
+    image: &MemoryImage,
+    _base: *mut u8,
+) -> Result<(), MemoryError> {
+    match image.fd {}
+}
+
+impl Drop for ReadOnlyMemory {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        if self.image.size.0 != 0 {
+            signals::unregister(self.mmap.as_ptr() as usize);
+        }
+    }
+}
+
+impl Memory for ReadOnlyMemory {
+    fn ty(&self) -> MemoryType {
+        self.memory
+    }
+
+    fn style(&self) -> &MemoryStyle {
+        &self.style
+    }
+
+    fn size(&self) -> Pages {
+        self.image.size
+    }
+
+    /// Read-only memories never grow.
+    fn grow(&self, delta: Pages) -> Result<Pages, MemoryError> {
+        Err(MemoryError::CouldNotGrow {
+            current: self.image.size,
+            attempted_delta: delta,
+        })
+    }
+
+    /// Read-only memories keep the contents of their image, and never grow.
+    fn reset(&self) -> Result<bool, MemoryError> {
+        Ok(true)
+    }
+
+    /// Read-only memories never grow, hence never move.
+    fn base_stability(&self) -> BaseStability {
+        BaseStability::Stable
+    }
+
+    fn vmmemory(&self) -> NonNull<VMMemoryDefinition> {
+        self.vm_memory_definition
+    }
+}
+
+/// Returns whether `addr` is within a read-only memory.
+#[cfg(target_os = "linux")]
+pub(crate) fn is_read_only(addr: *const u8) -> bool {
+    signals::is_read_only(addr as usize)
+}
+
+/// Returns whether `addr` is within a read-only memory.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn is_read_only(_addr: *const u8) -> bool {
+    false
+}
+
+/// Turning the faults of writes to read-only memories into traps.
+#[cfg(target_os = "linux")]
+mod signals {
+    use crate::trap::traphandlers::unwind_wasm_trap;
+    use crate::trap::TrapCode;
+    use std::collections::BTreeMap;
+    use std::io;
+    use std::mem::{self, MaybeUninit};
+    use std::ptr;
+    use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::thread;
+
+    /// The read-only mappings, as their start and end addresses in order.
+    type Ranges = Vec<(usize, usize)>;
+
+    /// The mappings and whether the handler is installed, updated under the lock.
+    #[derive(Default)]
+    struct Registry {
+        ranges: BTreeMap<usize, usize>,
+        installed: bool,
+    }
+
+    lazy_static::lazy_static! {
+        static ref REGISTRY: Mutex<Registry> = Mutex::new(Registry::default());
+    }
+
+    /// The latest snapshot of the mappings, which the fault handler reads without locking, or
+    /// null if there are none.
+    static SNAPSHOT: AtomicPtr<Ranges> = AtomicPtr::new(ptr::null_mut());
+
+    /// Number of threads reading `SNAPSHOT`, which replaced snapshots outlive.
+    static READERS: AtomicUsize = AtomicUsize::new(0);
+
+    static mut PREVIOUS: MaybeUninit<libc::sigaction> = MaybeUninit::uninit();
+
+    pub(super) fn register(start: usize, len: usize) -> io::Result<()> {
+        let mut registry = REGISTRY.lock().unwrap();
+        if !registry.installed {
+            unsafe { install()? };
+            registry.installed = true;
+        }
+        registry.ranges.insert(start, start + len);
+        publish(&registry);
+        Ok(())
+    }
+
+    pub(super) fn unregister(start: usize) {
+        let mut registry = REGISTRY.lock().unwrap();
+        registry.ranges.remove(&start);
+        publish(&registry);
+    }
+
+    /// Replace the snapshot with the mappings of `registry`, and free the previous one once no
+    /// thread reads it.
+    fn publish(registry: &Registry) {
+        let ranges = if registry.ranges.is_empty() {
+            ptr::null_mut()
+        } else {
+            let ranges: Ranges = registry.ranges.iter().map(|(&s, &e)| (s, e)).collect();
+            Box::into_raw(Box::new(ranges))
+        };
+        let previous = SNAPSHOT.swap(ranges, Ordering::SeqCst);
+        if previous.is_null() {
+            return;
+        }
+        // Readers count themselves before loading the snapshot, so none of those still
+        // counted after the swap can be reading the previous one past this point.
+        while READERS.load(Ordering::SeqCst) != 0 {
+            thread::yield_now();
+        }
+        drop(unsafe { Box::from_raw(previous) });
+    }
+
+    /// Returns whether `addr` is within a read-only mapping.
+    ///
+    /// This is async-signal-safe: it neither locks nor allocates.
+    pub(super) fn is_read_only(addr: usize) -> bool {
+        READERS.fetch_add(1, Ordering::SeqCst);
+        let ranges = SNAPSHOT.load(Ordering::SeqCst);
+        let read_only = !ranges.is_null() && {
+            let ranges = unsafe { &*ranges };
+            let after = ranges.partition_point(|&(start, _)| start <= addr);
+            after != 0 && addr < ranges[after - 1].1
+        };
+        READERS.fetch_sub(1, Ordering::SeqCst);
+        read_only
+    }
+
+    unsafe fn install() -> io::Result<()> {
+        let mut action: libc::sigaction = mem::zeroed();
+        // `SA_NODEFER` keeps the signal unblocked once the handler unwinds out of it.
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_NODEFER | libc::SA_ONSTACK;
+        action.sa_sigaction = handle_fault as usize;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(libc::SIGSEGV, &action, ptr::addr_of_mut!(PREVIOUS).cast()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    unsafe extern "C" fn handle_fault(
+        signal: libc::c_int,
+        info: *mut libc::siginfo_t,
+        context: *mut libc::c_void,
+    ) {
+        if is_read_only((*info).si_addr() as usize) {
+            // Only returns if no WebAssembly code is running on this thread.
+            unwind_wasm_trap(context_pc(context), TrapCode::ReadOnlyInstance);
+        }
+
+        // Not ours: defer to the handler installed before.
+        let previous = &*ptr::addr_of!(PREVIOUS).cast::<libc::sigaction>();
+        if previous.sa_flags & libc::SA_SIGINFO != 0 {
+            let handler = mem::transmute::<
+                usize,
+                extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void),
+            >(previous.sa_sigaction);
+            handler(signal, info, context);
+        } else if previous.sa_sigaction == libc::SIG_DFL || previous.sa_sigaction == libc::SIG_IGN {
+            // Restore the default disposition, which applies when the instruction faults again.
+            libc::sigaction(signal, previous, ptr::null_mut());
+        } else {
+            let handler =
+                mem::transmute::<usize, extern "C" fn(libc::c_int)>(previous.sa_sigaction);
+            handler(signal);
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    unsafe fn context_pc(context: *mut libc::c_void) -> usize {
+        let context = &*context.cast::<libc::ucontext_t>();
+        context.uc_mcontext.gregs[libc::REG_RIP as usize] as usize
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    unsafe fn context_pc(context: *mut libc::c_void) -> usize {
+        let context = &*context.cast::<libc::ucontext_t>();
+        context.uc_mcontext.pc as usize
+    }
+}
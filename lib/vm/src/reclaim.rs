@@ -0,0 +1,74 @@
+//! Releasing the memory held by caches and pools when the host runs short of it.
+
+/// How costly it is to give up some memory, in increasing order.
+///
+/// [`Reclaimable`] components are asked to release memory by increasing priority, so that
+/// nothing costly is released while cheaper memory is available.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ReclaimPriority {
+    /// Memory kept for reuse only, whose release costs an allocation later on.
+    Unused,
+    /// Cached results, recomputed on their next use.
+    Cached,
+    /// Cached state whose recomputation is expensive, such as memory images whose data
+    /// segments may need to be fetched again.
+    Expensive,
+}
+
+/// A component holding memory it can release on demand, such as a cache or a pool.
+///
+/// Components are called concurrently with their regular use, and must only release what no
+/// one is using: users of an entry released by [`Reclaimable::reclaim`] keep it until they are
+/// done with it.
+pub trait Reclaimable: Send + Sync {
+    /// The name of the component in a [`ReclaimReport`].
+    fn name(&self) -> &str;
+
+    /// How costly releasing the memory of this component is.
+    fn priority(&self) -> ReclaimPriority;
+
+    /// The number of bytes [`Reclaimable::reclaim`] could release.
+    fn reclaimable_bytes(&self) -> usize;
+
+    /// Release at least `target_bytes` if possible, returning the number of bytes actually
+    /// released, which may be more or less than asked.
+    fn reclaim(&self, target_bytes: usize) -> usize;
+}
+
+/// What a component released during a reclamation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReclaimedComponent {
+    /// The [name](Reclaimable::name) of the component.
+    pub name: String,
+    /// The [priority](Reclaimable::priority) of the component.
+    pub priority: ReclaimPriority,
+    /// The bytes the component could release before it was asked to.
+    pub reclaimable_bytes: usize,
+    /// The bytes the component released.
+    pub released_bytes: usize,
+}
+
+/// The outcome of a reclamation, listing the components asked to release memory in the order
+/// they were asked.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReclaimReport {
+    /// The number of bytes that was to be released.
+    pub target_bytes: usize,
+    /// The components asked to release memory.
+    pub components: Vec<ReclaimedComponent>,
+}
+
+impl ReclaimReport {
+    /// The total number of bytes released.
+    pub fn released_bytes(&self) -> usize {
+        self.components
+            .iter()
+            .map(|component| component.released_bytes)
+            .sum()
+    }
+
+    /// Whether at least [`ReclaimReport::target_bytes`] were released.
+    pub fn reached_target(&self) -> bool {
+        self.released_bytes() >= self.target_bytes
+    }
+}
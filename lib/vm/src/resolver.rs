@@ -1,3 +1,4 @@
+use std::error::Error;
 use std::sync::Arc;
 
 use crate::{ImportInitializerFuncPtr, VMExtern, VMFunction, VMGlobal, VMMemory, VMTable};
@@ -189,6 +190,20 @@ pub trait Resolver {
     /// )
     /// ```
     fn resolve(&self, _index: u32, module: &str, field: &str) -> Option<Export>;
+
+    /// Resolves an import like [`Resolver::resolve`], or rejects it with an error failing
+    /// the instantiation.
+    ///
+    /// Resolvers wrapping other resolvers forward their rejections by overriding this method
+    /// to call the one of the resolvers they wrap.
+    fn try_resolve(
+        &self,
+        index: u32,
+        module: &str,
+        field: &str,
+    ) -> Result<Option<Export>, Box<dyn Error + Send + Sync>> {
+        Ok(self.resolve(index, module, field))
+    }
 }
 
 /// Import resolver connects imports with available exported values.
@@ -242,6 +257,11 @@ impl Resolver for NullResolver {
 }
 
 /// A [`Resolver`] that links two resolvers together in a chain.
+///
+/// Each import is looked up in the first resolver of the chain, and in the second one only
+/// if the first does not define it: an import both define comes from the first one. Chains
+/// nest, so `base.chain_front(a).chain_front(b)` looks imports up in `b`, then `a`, then
+/// `base`.
 pub struct NamedResolverChain<A: NamedResolver + Send + Sync, B: NamedResolver + Send + Sync> {
     a: A,
     b: B,
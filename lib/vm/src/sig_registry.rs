@@ -6,6 +6,7 @@
 
 use std::collections::{hash_map, HashMap};
 use std::convert::TryFrom;
+use std::sync::Arc;
 use wasmer_types::{FunctionType, FunctionTypeRef};
 
 /// An index into the shared signature registry, usable for checking signatures
@@ -28,7 +29,7 @@ impl VMSharedSignatureIndex {
 #[derive(Debug)]
 pub struct SignatureRegistry {
     type_to_index: HashMap<FunctionType, VMSharedSignatureIndex>,
-    index_to_data: Vec<FunctionType>,
+    index_to_data: Vec<Arc<FunctionType>>,
 }
 
 impl SignatureRegistry {
@@ -60,7 +61,7 @@ impl SignatureRegistry {
                 );
                 let sig_id = VMSharedSignatureIndex::new(u32::try_from(len).unwrap());
                 entry.insert(sig_id);
-                self.index_to_data.push(sig);
+                self.index_to_data.push(Arc::new(sig));
                 sig_id
             }
         }
@@ -71,6 +72,12 @@ impl SignatureRegistry {
     /// Note that for this operation to be semantically correct the `idx` must
     /// have previously come from a call to `register` of this same object.
     pub fn lookup(&self, idx: VMSharedSignatureIndex) -> Option<&FunctionType> {
-        self.index_to_data.get(idx.0 as usize)
+        self.index_to_data.get(idx.0 as usize).map(|sig| &**sig)
+    }
+
+    /// Looks up a shared signature index within this registry, sharing the signature rather
+    /// than copying it.
+    pub fn lookup_shared(&self, idx: VMSharedSignatureIndex) -> Option<Arc<FunctionType>> {
+        self.index_to_data.get(idx.0 as usize).cloned()
     }
 }
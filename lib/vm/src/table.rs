@@ -19,6 +19,7 @@ use wasmer_types::{ExternRef, TableType, Type as ValType};
 
 /// Implementation styles for WebAssembly tables.
 #[derive(Debug, Clone, Hash, PartialEq, Eq, rkyv::Serialize, rkyv::Deserialize, rkyv::Archive)]
+#[archive(check_bytes)]
 pub enum TableStyle {
     /// Signatures are stored in the table and checked in the caller.
     CallerChecksSignature,
@@ -56,6 +57,20 @@ pub trait Table: fmt::Debug + Send + Sync {
     /// Return a `VMTableDefinition` for exposing the table to compiled wasm code.
     fn vmtable(&self) -> NonNull<VMTableDefinition>;
 
+    /// Shrink the table back to the size it was created with, all its elements null.
+    ///
+    /// Tables that cannot shrink null their elements and keep their size.
+    fn reset(&self) {
+        let null = match self.ty().ty {
+            ValType::ExternRef => TableElement::ExternRef(ExternRef::null()),
+            _ => TableElement::FuncRef(VMFuncRef::null()),
+        };
+        for index in 0..self.size() {
+            // The index is within the table.
+            let _ = self.set(index, null.clone());
+        }
+    }
+
     /// Copy `len` elements from `src_table[src_index..]` into `dst_table[dst_index..]`.
     ///
     /// # Errors
@@ -406,4 +421,26 @@ impl Table for LinearTable {
         let _vec_guard = self.vec.lock().unwrap();
         unsafe { self.get_vm_table_definition() }
     }
+
+    /// Shrink the table back to its minimum size, all its elements null.
+    fn reset(&self) {
+        let mut vec_guard = self.vec.lock().unwrap();
+        let vec = vec_guard.borrow_mut();
+        if self.table.ty == ValType::ExternRef {
+            for element in vec.iter_mut() {
+                unsafe { element.extern_ref.ref_drop() };
+            }
+        }
+        vec.truncate(usize::try_from(self.table.minimum).unwrap());
+        for element in vec.iter_mut() {
+            *element = RawTableElement::default();
+        }
+
+        unsafe {
+            let mut td_ptr = self.get_vm_table_definition();
+            let td = td_ptr.as_mut();
+            td.current_elements = self.table.minimum;
+            td.base = vec.as_mut_ptr() as _;
+        }
+    }
 }
@@ -0,0 +1,122 @@
+//! Releasing the resources of dropped instances on a background thread.
+
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use wasmer_types::DeferredTeardown;
+
+/// Resources handed to the worker, with the number of bytes they keep mapped.
+struct Deferred {
+    bytes: usize,
+    resources: Box<dyn Send>,
+}
+
+#[derive(Default)]
+struct Backlog {
+    items: usize,
+    bytes: usize,
+}
+
+#[derive(Default)]
+struct Shared {
+    backlog: Mutex<Backlog>,
+    drained: Condvar,
+}
+
+/// A queue releasing the memories, tables and artifacts of dropped instances on a background
+/// thread.
+///
+/// Unmapping a large memory can take milliseconds. When an instance configured with
+/// [`InstanceConfig::with_deferred_teardown`](wasmer_types::InstanceConfig::with_deferred_teardown)
+/// is dropped, its host environments and host state are still dropped right away, but its
+/// memories, tables and artifact are moved to this queue and released by its worker thread.
+///
+/// The backlog is bounded by the number of bytes of memory waiting to be released: once it
+/// would exceed `max_backlog`, instances are torn down synchronously again.
+///
+/// The queue is a cheap handle, and clones share the same worker, which exits once every
+/// handle is dropped and the backlog is released.
+#[derive(Clone)]
+pub struct TeardownQueue {
+    sender: Arc<Mutex<Sender<Deferred>>>,
+    shared: Arc<Shared>,
+    max_backlog: usize,
+}
+
+impl TeardownQueue {
+    /// Start a queue whose worker thread accepts up to `max_backlog` bytes of memory waiting
+    /// to be released.
+    pub fn new(max_backlog: usize) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let shared = Arc::new(Shared::default());
+        let worker = Arc::clone(&shared);
+        thread::Builder::new()
+            .name("wasmer-teardown".to_string())
+            .spawn(move || Self::work(receiver, &worker))?;
+        Ok(Self {
+            sender: Arc::new(Mutex::new(sender)),
+            shared,
+            max_backlog,
+        })
+    }
+
+    fn work(receiver: Receiver<Deferred>, shared: &Shared) {
+        for deferred in receiver {
+            let bytes = deferred.bytes;
+            // Keep the backlog accurate, and the worker running, if a destructor panics.
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(deferred)));
+            let mut backlog = shared.backlog.lock().unwrap();
+            backlog.items -= 1;
+            backlog.bytes -= bytes;
+            if backlog.items == 0 {
+                shared.drained.notify_all();
+            }
+        }
+    }
+
+    /// Return the number of bytes of memory waiting to be released.
+    pub fn backlog(&self) -> usize {
+        self.shared.backlog.lock().unwrap().bytes
+    }
+
+    /// Block until everything handed to the queue so far has been released.
+    pub fn drain(&self) {
+        let mut backlog = self.shared.backlog.lock().unwrap();
+        while backlog.items != 0 {
+            backlog = self.shared.drained.wait(backlog).unwrap();
+        }
+    }
+}
+
+impl DeferredTeardown for TeardownQueue {
+    /// Hand `resources` to the worker thread to drop, unless the backlog would exceed its
+    /// maximum.
+    fn defer(&self, bytes: usize, resources: Box<dyn Send>) -> Result<(), Box<dyn Send>> {
+        let mut backlog = self.shared.backlog.lock().unwrap();
+        match backlog.bytes.checked_add(bytes) {
+            Some(total) if total <= self.max_backlog => {}
+            _ => return Err(resources),
+        }
+        let deferred = Deferred { bytes, resources };
+        match self.sender.lock().unwrap().send(deferred) {
+            Ok(()) => {
+                backlog.items += 1;
+                backlog.bytes += bytes;
+                Ok(())
+            }
+            // The worker thread is gone.
+            Err(mpsc::SendError(deferred)) => Err(deferred.resources),
+        }
+    }
+}
+
+impl std::fmt::Debug for TeardownQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TeardownQueue")
+            .field("backlog", &self.backlog())
+            .field("max_backlog", &self.max_backlog)
+            .finish()
+    }
+}
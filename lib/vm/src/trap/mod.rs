@@ -6,9 +6,9 @@
 mod trapcode;
 pub mod traphandlers;
 
-pub use trapcode::TrapCode;
+pub use trapcode::{TrapCode, TrapCodeCollision};
 pub use traphandlers::resume_panic;
 pub use traphandlers::{
     catch_traps, catch_traps_with_result, raise_lib_trap, raise_user_trap, wasmer_call_trampoline,
-    TlsRestore, Trap,
+    with_backtraces, with_lightweight_traps, TlsRestore, Trap,
 };
@@ -5,11 +5,20 @@
 
 use core::fmt::{self, Display, Formatter};
 use core::str::FromStr;
+use std::collections::HashMap;
+use std::sync::RwLock;
 use thiserror::Error;
+use wasmer_types::{ErrorCode, HasErrorCode};
 
 /// A trap code describing the reason for a trap.
 ///
 /// All trap instructions have an explicit trap code.
+///
+/// The innermost frame of the trace of a trap reports the byte offset, within the module, of
+/// the operator that trapped: the memory access, `table.get`/`table.set`/`call_indirect`,
+/// division, conversion or `unreachable` operator itself. A stack overflow detected on entry to
+/// a function is attributed to the start of that function's body, and running out of gas to the
+/// operator at which the gas was charged.
 #[derive(
     Clone,
     Copy,
@@ -22,57 +31,184 @@ use thiserror::Error;
     rkyv::Deserialize,
     rkyv::Archive,
 )]
-#[repr(u32)]
+#[archive(check_bytes)]
 pub enum TrapCode {
     /// The current stack space was exhausted.
     ///
     /// On some platforms, a stack overflow may also be indicated by a segmentation fault from the
     /// stack guard page.
-    StackOverflow = 0,
+    StackOverflow,
 
     /// A `heap_addr` instruction detected an out-of-bounds error.
     ///
     /// Note that not all out-of-bounds heap accesses are reported this way;
     /// some are detected by a segmentation fault on the heap unmapped or
     /// offset-guard pages.
-    HeapAccessOutOfBounds = 1,
+    HeapAccessOutOfBounds,
 
     /// A `heap_addr` instruction was misaligned.
-    HeapMisaligned = 2,
+    HeapMisaligned,
 
     /// A `table_addr` instruction detected an out-of-bounds error.
-    TableAccessOutOfBounds = 3,
+    TableAccessOutOfBounds,
 
     /// Other bounds checking error.
-    OutOfBounds = 4,
+    OutOfBounds,
 
     /// Indirect call to a null table entry.
-    IndirectCallToNull = 5,
+    IndirectCallToNull,
 
     /// Signature mismatch on indirect call.
-    BadSignature = 6,
+    BadSignature,
 
     /// An integer arithmetic operation caused an overflow.
-    IntegerOverflow = 7,
+    IntegerOverflow,
 
     /// An integer division by zero.
-    IntegerDivisionByZero = 8,
+    IntegerDivisionByZero,
 
     /// Failed float-to-int conversion.
-    BadConversionToInteger = 9,
+    BadConversionToInteger,
 
     /// Code that was supposed to have been unreachable was reached.
-    UnreachableCodeReached = 10,
+    UnreachableCodeReached,
 
     /// An atomic memory access was attempted with an unaligned pointer.
-    UnalignedAtomic = 11,
+    UnalignedAtomic,
 
     /// Hit the gas limit.
-    GasExceeded = 12,
+    GasExceeded,
+
+    /// A read-only instance attempted to write to its memory.
+    ReadOnlyInstance,
+
+    /// A function reference from another instance or from the host was written to a table
+    /// whose instance rejects them.
+    ForeignFuncref,
+
+    /// A call was made into an instance left poisoned by a failed restore of a snapshot.
+    PoisonedInstance,
+
+    /// The call was cancelled through its handle while it ran.
+    Cancelled,
+
+    /// A trap raised by code the embedder had the compiler generate, with a code registered
+    /// by [`TrapCode::register_custom`].
+    Custom(u16),
+}
+
+/// The raw form of [`TrapCode::Custom`] codes starts there, past those of the other codes.
+const CUSTOM_BASE: u32 = 1 << 16;
+
+lazy_static::lazy_static! {
+    /// The names of the custom trap codes registered in this process.
+    static ref CUSTOM_NAMES: RwLock<HashMap<u16, &'static str>> = RwLock::new(HashMap::new());
+}
+
+/// An error registering a custom trap code, see [`TrapCode::register_custom`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error(
+    "the custom trap code {code} is registered as `{registered}`, not `{requested}` [{}]",
+    ErrorCode::TrapCodeCollision
+)]
+pub struct TrapCodeCollision {
+    /// The code registered twice.
+    pub code: u16,
+    /// The name it was registered with first.
+    pub registered: String,
+    /// The name it was then registered with.
+    pub requested: String,
+}
+
+impl HasErrorCode for TrapCodeCollision {
+    fn code(&self) -> ErrorCode {
+        ErrorCode::TrapCodeCollision
+    }
 }
 
 impl TrapCode {
+    /// Register `name` for the custom trap code `code`, for the whole process.
+    ///
+    /// The name is the [`message`](Self::message) of traps with the code. Registering a code
+    /// again with the same name does nothing, so that the same code generator can be
+    /// configured several times, but registering it with another name fails: each code keeps
+    /// a single meaning in the process.
+    pub fn register_custom(code: u16, name: &str) -> Result<Self, TrapCodeCollision> {
+        let mut names = CUSTOM_NAMES.write().unwrap();
+        match names.get(&code) {
+            Some(registered) if *registered != name => Err(TrapCodeCollision {
+                code,
+                registered: registered.to_string(),
+                requested: name.to_string(),
+            }),
+            Some(_) => Ok(Self::Custom(code)),
+            None => {
+                // Codes are few, and their names live as long as the process.
+                names.insert(code, Box::leak(name.to_string().into_boxed_str()));
+                Ok(Self::Custom(code))
+            }
+        }
+    }
+
+    /// The name registered for the custom trap code `code`, if any.
+    pub fn custom_name(code: u16) -> Option<&'static str> {
+        CUSTOM_NAMES.read().unwrap().get(&code).copied()
+    }
+
+    /// This code as a single integer, as generated code passes it to the trap handler.
+    pub fn to_raw(self) -> u32 {
+        match self {
+            Self::StackOverflow => 0,
+            Self::HeapAccessOutOfBounds => 1,
+            Self::HeapMisaligned => 2,
+            Self::TableAccessOutOfBounds => 3,
+            Self::OutOfBounds => 4,
+            Self::IndirectCallToNull => 5,
+            Self::BadSignature => 6,
+            Self::IntegerOverflow => 7,
+            Self::IntegerDivisionByZero => 8,
+            Self::BadConversionToInteger => 9,
+            Self::UnreachableCodeReached => 10,
+            Self::UnalignedAtomic => 11,
+            Self::GasExceeded => 12,
+            Self::ReadOnlyInstance => 13,
+            Self::ForeignFuncref => 14,
+            Self::PoisonedInstance => 15,
+            Self::Cancelled => 16,
+            Self::Custom(code) => CUSTOM_BASE + u32::from(code),
+        }
+    }
+
+    /// The code whose [`to_raw`](Self::to_raw) form is `raw`, if any.
+    pub fn from_raw(raw: u32) -> Option<Self> {
+        Some(match raw {
+            0 => Self::StackOverflow,
+            1 => Self::HeapAccessOutOfBounds,
+            2 => Self::HeapMisaligned,
+            3 => Self::TableAccessOutOfBounds,
+            4 => Self::OutOfBounds,
+            5 => Self::IndirectCallToNull,
+            6 => Self::BadSignature,
+            7 => Self::IntegerOverflow,
+            8 => Self::IntegerDivisionByZero,
+            9 => Self::BadConversionToInteger,
+            10 => Self::UnreachableCodeReached,
+            11 => Self::UnalignedAtomic,
+            12 => Self::GasExceeded,
+            13 => Self::ReadOnlyInstance,
+            14 => Self::ForeignFuncref,
+            15 => Self::PoisonedInstance,
+            16 => Self::Cancelled,
+            _ if raw >= CUSTOM_BASE && raw - CUSTOM_BASE <= u32::from(u16::MAX) => {
+                Self::Custom((raw - CUSTOM_BASE) as u16)
+            }
+            _ => return None,
+        })
+    }
+
     /// Gets the message for this trap code
+    ///
+    /// The message of a custom code is its registered name.
     pub fn message(&self) -> &str {
         match self {
             Self::StackOverflow => "call stack exhausted",
@@ -88,6 +224,11 @@ impl TrapCode {
             Self::UnreachableCodeReached => "unreachable",
             Self::UnalignedAtomic => "unaligned atomic access",
             Self::GasExceeded => "gas limit exceeded",
+            Self::ReadOnlyInstance => "write to the memory of a read-only instance",
+            Self::ForeignFuncref => "foreign function reference written to a table",
+            Self::PoisonedInstance => "call into an instance poisoned by a failed restore",
+            Self::Cancelled => "call cancelled",
+            Self::Custom(code) => Self::custom_name(*code).unwrap_or("custom trap"),
         }
     }
 }
@@ -95,6 +236,7 @@ impl TrapCode {
 impl Display for TrapCode {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         let identifier = match *self {
+            Self::Custom(code) => return write!(f, "custom{}", code),
             Self::StackOverflow => "stk_ovf",
             Self::HeapAccessOutOfBounds => "heap_get_oob",
             Self::HeapMisaligned => "heap_misaligned",
@@ -108,11 +250,42 @@ impl Display for TrapCode {
             Self::UnreachableCodeReached => "unreachable",
             Self::UnalignedAtomic => "unalign_atom",
             Self::GasExceeded => "out_of_gas",
+            Self::ReadOnlyInstance => "readonly_write",
+            Self::ForeignFuncref => "foreign_funcref",
+            Self::PoisonedInstance => "poisoned",
+            Self::Cancelled => "cancelled",
         };
         f.write_str(identifier)
     }
 }
 
+// The `Display` form of trap codes is an identifier parsed back by `FromStr`, so it does not
+// include the error code.
+impl HasErrorCode for TrapCode {
+    fn code(&self) -> ErrorCode {
+        match self {
+            Self::StackOverflow => ErrorCode::TrapStackOverflow,
+            Self::HeapAccessOutOfBounds => ErrorCode::TrapHeapAccessOutOfBounds,
+            Self::HeapMisaligned => ErrorCode::TrapHeapMisaligned,
+            Self::TableAccessOutOfBounds => ErrorCode::TrapTableAccessOutOfBounds,
+            Self::OutOfBounds => ErrorCode::TrapOutOfBounds,
+            Self::IndirectCallToNull => ErrorCode::TrapIndirectCallToNull,
+            Self::BadSignature => ErrorCode::TrapBadSignature,
+            Self::IntegerOverflow => ErrorCode::TrapIntegerOverflow,
+            Self::IntegerDivisionByZero => ErrorCode::TrapIntegerDivisionByZero,
+            Self::BadConversionToInteger => ErrorCode::TrapBadConversionToInteger,
+            Self::UnreachableCodeReached => ErrorCode::TrapUnreachableCodeReached,
+            Self::UnalignedAtomic => ErrorCode::TrapUnalignedAtomic,
+            Self::GasExceeded => ErrorCode::TrapGasExceeded,
+            Self::ReadOnlyInstance => ErrorCode::TrapReadOnlyInstance,
+            Self::ForeignFuncref => ErrorCode::TrapForeignFuncref,
+            Self::PoisonedInstance => ErrorCode::TrapPoisonedInstance,
+            Self::Cancelled => ErrorCode::TrapCancelled,
+            Self::Custom(_) => ErrorCode::TrapCustom,
+        }
+    }
+}
+
 impl FromStr for TrapCode {
     type Err = ();
 
@@ -130,7 +303,16 @@ impl FromStr for TrapCode {
             "bad_toint" => Ok(Self::BadConversionToInteger),
             "unreachable" => Ok(Self::UnreachableCodeReached),
             "unalign_atom" => Ok(Self::UnalignedAtomic),
-            _ => Err(()),
+            "readonly_write" => Ok(Self::ReadOnlyInstance),
+            "foreign_funcref" => Ok(Self::ForeignFuncref),
+            "poisoned" => Ok(Self::PoisonedInstance),
+            "cancelled" => Ok(Self::Cancelled),
+            _ => match s.strip_prefix("custom") {
+                Some(code) if code.bytes().all(|b| b.is_ascii_digit()) => {
+                    code.parse().map(Self::Custom).map_err(|_| ())
+                }
+                _ => Err(()),
+            },
         }
     }
 }
@@ -140,7 +322,7 @@ mod tests {
     use super::*;
 
     // Everything but user-defined codes.
-    const CODES: [TrapCode; 12] = [
+    const CODES: [TrapCode; 16] = [
         TrapCode::StackOverflow,
         TrapCode::HeapAccessOutOfBounds,
         TrapCode::HeapMisaligned,
@@ -153,6 +335,10 @@ mod tests {
         TrapCode::BadConversionToInteger,
         TrapCode::UnreachableCodeReached,
         TrapCode::UnalignedAtomic,
+        TrapCode::ReadOnlyInstance,
+        TrapCode::ForeignFuncref,
+        TrapCode::PoisonedInstance,
+        TrapCode::Cancelled,
     ];
 
     #[test]
@@ -163,10 +349,25 @@ mod tests {
         }
         assert_eq!("bogus".parse::<TrapCode>(), Err(()));
 
-        // assert_eq!(TrapCode::User(17).to_string(), "user17");
-        // assert_eq!("user22".parse(), Ok(TrapCode::User(22)));
+        assert_eq!(TrapCode::Custom(17).to_string(), "custom17");
+        assert_eq!("custom22".parse(), Ok(TrapCode::Custom(22)));
+        assert_eq!("custom".parse::<TrapCode>(), Err(()));
+        assert_eq!("custom+1".parse::<TrapCode>(), Err(()));
+        assert_eq!("custom65536".parse::<TrapCode>(), Err(()));
         assert_eq!("user".parse::<TrapCode>(), Err(()));
         assert_eq!("user-1".parse::<TrapCode>(), Err(()));
         assert_eq!("users".parse::<TrapCode>(), Err(()));
     }
+
+    #[test]
+    fn raw() {
+        for code in CODES
+            .iter()
+            .chain(&[TrapCode::Custom(0), TrapCode::Custom(u16::MAX)])
+        {
+            assert_eq!(TrapCode::from_raw(code.to_raw()), Some(*code));
+        }
+        assert_eq!(TrapCode::from_raw(17), None);
+        assert_eq!(TrapCode::from_raw(CUSTOM_BASE + 65536), None);
+    }
 }
@@ -101,6 +101,18 @@ pub enum Trap {
         /// Native stack backtrace at the time the OOM occurred
         backtrace: Backtrace,
     },
+
+    /// A trap whose code was made lightweight with [`with_lightweight_traps`], raised from the
+    /// Wasm generated code or a libcall without capturing a backtrace.
+    ///
+    /// Note: this trap is deterministic (assuming a deterministic host implementation)
+    Lightweight {
+        /// Code of the trap.
+        trap_code: TrapCode,
+        /// The program counter in generated code where this trap happened, if it was raised
+        /// by the generated code.
+        pc: Option<usize>,
+    },
 }
 
 impl Trap {
@@ -117,9 +129,16 @@ impl Trap {
 
     /// Construct a new Wasm trap with the given trap code.
     ///
-    /// Internally saves a backtrace when constructed.
+    /// Internally saves a backtrace when constructed, unless the code is lightweight or
+    /// backtraces are skipped, see [`with_backtraces`].
     pub fn lib(trap_code: TrapCode) -> Self {
-        let backtrace = Backtrace::new_unresolved();
+        if is_lightweight(trap_code) {
+            return Self::Lightweight {
+                trap_code,
+                pc: None,
+            };
+        }
+        let backtrace = capture_backtrace();
         Self::Lib {
             trap_code,
             backtrace,
@@ -128,13 +147,75 @@ impl Trap {
 
     /// Construct a new OOM trap with the given source location and trap code.
     ///
-    /// Internally saves a backtrace when constructed.
+    /// Internally saves a backtrace when constructed, unless backtraces are skipped, see
+    /// [`with_backtraces`].
     pub fn oom() -> Self {
-        let backtrace = Backtrace::new_unresolved();
+        let backtrace = capture_backtrace();
         Self::OOM { backtrace }
     }
 }
 
+thread_local! {
+    /// The codes of the traps raised as [`Trap::Lightweight`] on this thread, as bits
+    /// `1 << code`.
+    static LIGHTWEIGHT_TRAPS: Cell<u32> = Cell::new(0);
+
+    /// Whether the traps raised on this thread capture a backtrace.
+    static CAPTURE_BACKTRACES: Cell<bool> = Cell::new(true);
+}
+
+fn is_lightweight(trap_code: TrapCode) -> bool {
+    LIGHTWEIGHT_TRAPS
+        .try_with(|codes| codes.get() & 1u32.checked_shl(trap_code.to_raw()).unwrap_or(0) != 0)
+        .unwrap_or(false)
+}
+
+/// Run `closure` with the traps whose codes have their bit `1 << code` set in `codes` raised
+/// as [`Trap::Lightweight`], which skips capturing a backtrace.
+///
+/// Traps raised while a nested call into WebAssembly sets other codes follow that call.
+pub fn with_lightweight_traps<R>(codes: u32, closure: impl FnOnce() -> R) -> R {
+    struct Restore(u32);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            LIGHTWEIGHT_TRAPS.with(|codes| codes.set(self.0));
+        }
+    }
+
+    let _restore = Restore(LIGHTWEIGHT_TRAPS.with(|previous| previous.replace(codes)));
+    closure()
+}
+
+/// Run `closure` with the traps raised capturing a backtrace if `capture` is set, and an
+/// empty one otherwise.
+///
+/// Traps raised while a nested call into WebAssembly sets otherwise follow that call.
+pub fn with_backtraces<R>(capture: bool, closure: impl FnOnce() -> R) -> R {
+    struct Restore(bool);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CAPTURE_BACKTRACES.with(|capture| capture.set(self.0));
+        }
+    }
+
+    let _restore = Restore(CAPTURE_BACKTRACES.with(|previous| previous.replace(capture)));
+    closure()
+}
+
+/// Capture the backtrace of a trap, unless [`with_backtraces`] skips it.
+fn capture_backtrace() -> Backtrace {
+    if CAPTURE_BACKTRACES
+        .try_with(|capture| capture.get())
+        .unwrap_or(true)
+    {
+        Backtrace::new_unresolved()
+    } else {
+        Vec::new().into()
+    }
+}
+
 /// Call the VM function pointed to by `callee`.
 ///
 /// * `callee_env` - the function environment
@@ -402,22 +483,39 @@ mod tls {
     }
 }
 
-extern "C" fn signal_less_trap_handler(pc: *const u8, trap: TrapCode) {
+/// Takes the [`TrapCode::to_raw`] form of the code, as generated code passes it.
+extern "C" fn signal_less_trap_handler(pc: *const u8, trap: u32) {
+    let trap = TrapCode::from_raw(trap).expect("invalid trap code");
+    unsafe { unwind_wasm_trap(pc as usize, trap) };
+    unreachable!("traps are only raised by WebAssembly code");
+}
+
+/// Unwinds the call into WebAssembly in progress on this thread with a trap raised at `pc`.
+///
+/// Returns if there is no such call.
+///
+/// # Safety
+///
+/// No Rust destructors may be on the stack between the call and this function.
+pub(crate) unsafe fn unwind_wasm_trap(pc: usize, trap: TrapCode) {
     let jmp_buf = tls::with(|info| {
-        let backtrace = Backtrace::new_unresolved();
-        let info = info.unwrap();
-        unsafe {
-            (*info.unwind.get())
-                .as_mut_ptr()
-                .write(UnwindReason::WasmTrap {
-                    backtrace,
-                    signal_trap: Some(trap),
-                    pc: pc as usize,
-                });
-            info.jmp_buf.get()
-        }
+        let info = info?;
+        let reason = if is_lightweight(trap) {
+            UnwindReason::LibTrap(Trap::Lightweight {
+                trap_code: trap,
+                pc: Some(pc),
+            })
+        } else {
+            UnwindReason::WasmTrap {
+                backtrace: capture_backtrace(),
+                signal_trap: Some(trap),
+                pc,
+            }
+        };
+        (*info.unwind.get()).as_mut_ptr().write(reason);
+        Some(info.jmp_buf.get())
     });
-    unsafe {
+    if let Some(jmp_buf) = jmp_buf {
         wasmer_unwind(jmp_buf);
     }
 }
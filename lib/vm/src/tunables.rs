@@ -1,10 +1,33 @@
 use crate::MemoryError;
-use crate::{Memory, Table};
+use crate::{Memory, MemoryImage, Table};
 use crate::{MemoryStyle, TableStyle};
 use crate::{VMMemoryDefinition, VMTableDefinition};
 use std::ptr::NonNull;
 use std::sync::Arc;
-use wasmer_types::{MemoryType, TableType};
+use wasmer_types::{MemoryType, Pages, TableType};
+
+/// What a module declares about one of its memories, for [`Tunables::memory_style_for`] to
+/// pick a style from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModuleStyleHints {
+    /// The declared minimum size.
+    pub minimum: Pages,
+    /// The declared maximum size, if any.
+    pub maximum: Option<Pages>,
+    /// Whether the memory is imported rather than defined by the module.
+    pub imported: bool,
+}
+
+impl ModuleStyleHints {
+    /// The hints for a memory of type `memory`, imported or not.
+    pub fn new(memory: &MemoryType, imported: bool) -> Self {
+        Self {
+            minimum: memory.minimum,
+            maximum: memory.maximum,
+            imported,
+        }
+    }
+}
 
 /// An engine delegates the creation of memories, tables, and globals
 /// to a foreign implementor of this trait.
@@ -12,6 +35,18 @@ pub trait Tunables {
     /// Construct a `MemoryStyle` for the provided `MemoryType`
     fn memory_style(&self, memory: &MemoryType) -> MemoryStyle;
 
+    /// Construct the `MemoryStyle` of a memory of a module, given what the module declares
+    /// about it.
+    ///
+    /// Modules are compiled against the styles chosen here, which are recorded in their
+    /// artifact; instantiation fails if this would choose another style by then. Host
+    /// memories get the style of an imported memory of their type.
+    ///
+    /// Defaults to [`Tunables::memory_style`].
+    fn memory_style_for(&self, memory: &MemoryType, _hints: &ModuleStyleHints) -> MemoryStyle {
+        self.memory_style(memory)
+    }
+
     /// Construct a `TableStyle` for the provided `TableType`
     fn table_style(&self, table: &TableType) -> TableStyle;
 
@@ -33,6 +68,35 @@ pub trait Tunables {
         vm_definition_location: NonNull<VMMemoryDefinition>,
     ) -> Result<Arc<dyn Memory>, MemoryError>;
 
+    /// Whether the local memories of instances should map a memory image of their module
+    /// copy-on-write, see [`Tunables::create_vm_memory_from_image`], rather than have the
+    /// data segments copied into them.
+    ///
+    /// Defaults to `false`.
+    fn copy_on_write_memories(&self) -> bool {
+        false
+    }
+
+    /// Create a memory owned by the VM given a [`MemoryType`] and a [`MemoryStyle`], holding
+    /// the contents of `image` and sharing its pages until they are written.
+    ///
+    /// Only called when [`Tunables::copy_on_write_memories`] is `true`. The default
+    /// implementation fails.
+    ///
+    /// # Safety
+    /// - `vm_definition_location` must point to a valid location in VM memory.
+    unsafe fn create_vm_memory_from_image(
+        &self,
+        _image: Arc<MemoryImage>,
+        _ty: &MemoryType,
+        _style: &MemoryStyle,
+        _vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<Arc<dyn Memory>, MemoryError> {
+        Err(MemoryError::InvalidMemory {
+            reason: "these tunables cannot create copy-on-write memories".to_string(),
+        })
+    }
+
     /// Create a table owned by the host given a [`TableType`] and a [`TableStyle`].
     fn create_host_table(
         &self,
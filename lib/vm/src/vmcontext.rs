@@ -8,6 +8,7 @@ use crate::func_data_registry::VMFuncRef;
 use crate::global::Global;
 use crate::instance::Instance;
 use crate::memory::Memory;
+use crate::readonly_memory::is_read_only;
 use crate::sig_registry::VMSharedSignatureIndex;
 use crate::table::Table;
 use crate::trap::{Trap, TrapCode};
@@ -431,6 +432,9 @@ impl VMMemoryDefinition {
         {
             return Err(Trap::lib(TrapCode::HeapAccessOutOfBounds));
         }
+        if len != 0 && is_read_only(self.base) {
+            return Err(Trap::lib(TrapCode::ReadOnlyInstance));
+        }
 
         let dst = usize::try_from(dst).unwrap();
         let src = usize::try_from(src).unwrap();
@@ -461,6 +465,9 @@ impl VMMemoryDefinition {
         {
             return Err(Trap::lib(TrapCode::HeapAccessOutOfBounds));
         }
+        if len != 0 && is_read_only(self.base) {
+            return Err(Trap::lib(TrapCode::ReadOnlyInstance));
+        }
 
         let dst = isize::try_from(dst).unwrap();
         let val = val as u8;
@@ -995,9 +1002,21 @@ impl VMBuiltinFunctionIndex {
     pub const fn get_externref_dec_index() -> Self {
         Self(25)
     }
+    /// Returns an index for a function reporting the entry into a function to diagnostics.
+    pub const fn get_diagnostics_call_index() -> Self {
+        Self(26)
+    }
+    /// Returns an index for a function reporting a memory access to diagnostics.
+    pub const fn get_diagnostics_memory_index() -> Self {
+        Self(27)
+    }
+    /// Returns an index for a function trapping if the running call was cancelled.
+    pub const fn get_check_cancellation_index() -> Self {
+        Self(28)
+    }
     /// Returns the total number of builtin functions.
     pub const fn builtin_functions_total_number() -> u32 {
-        26
+        29
     }
 
     /// Return the index as an u32 number.
@@ -1079,6 +1098,12 @@ impl VMBuiltinFunctionsArray {
             wasmer_vm_externref_inc as usize;
         ptrs[VMBuiltinFunctionIndex::get_externref_dec_index().index() as usize] =
             wasmer_vm_externref_dec as usize;
+        ptrs[VMBuiltinFunctionIndex::get_diagnostics_call_index().index() as usize] =
+            wasmer_vm_diagnostics_call as usize;
+        ptrs[VMBuiltinFunctionIndex::get_diagnostics_memory_index().index() as usize] =
+            wasmer_vm_diagnostics_memory as usize;
+        ptrs[VMBuiltinFunctionIndex::get_check_cancellation_index().index() as usize] =
+            wasmer_vm_check_cancellation as usize;
 
         debug_assert!(ptrs.iter().cloned().all(|p| p != 0));
 
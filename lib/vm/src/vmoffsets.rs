@@ -32,7 +32,7 @@ const fn align(offset: u32, width: u32) -> u32 {
 /// related structs that JIT code accesses directly.
 ///
 /// [`VMContext`]: crate::vmcontext::VMContext
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct VMOffsets {
     /// The size in bytes of a pointer on the target.
     pub pointer_size: u8,
@@ -512,13 +512,23 @@ impl VMOffsets {
         self.vmctx_stack_limit_begin().checked_add(4).unwrap()
     }
 
+    /// The offset of the diagnostics level, a single byte.
+    pub fn vmctx_diagnostics_level(&self) -> u32 {
+        self.vmctx_stack_limit_initial_begin()
+            .checked_add(4)
+            .unwrap()
+    }
+
+    /// The offset of the number of cancelled calls still running, a 32-bit counter.
+    pub fn vmctx_cancellations_pending(&self) -> u32 {
+        self.vmctx_diagnostics_level().checked_add(4).unwrap()
+    }
+
     /// Return the size of the [`VMContext`] allocation.
     ///
     /// [`VMContext`]: crate::vmcontext::VMContext
     pub fn size_of_vmctx(&self) -> u32 {
-        self.vmctx_stack_limit_initial_begin()
-            .checked_add(4)
-            .unwrap()
+        self.vmctx_cancellations_pending().checked_add(4).unwrap()
     }
 
     /// Return the offset to [`VMSharedSignatureIndex`] index `index`.
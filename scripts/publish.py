@@ -26,6 +26,7 @@ target_version = "2.1.0"
 # TODO: generate this by parsing toml files
 dep_graph = {
     "wasmer-types": set([]),
+    "wasmer-derive": set([]),
     "wasmer-vm": set(["wasmer-types"]),
     "wasmer-compiler": set(["wasmer-vm", "wasmer-types"]),
     "wasmer-object": set(["wasmer-types", "wasmer-compiler"]),
@@ -34,13 +35,14 @@ dep_graph = {
     "wasmer-engine-universal": set(["wasmer-types", "wasmer-vm", "wasmer-compiler", "wasmer-engine"]),
     "wasmer": set(["wasmer-vm", "wasmer-compiler-singlepass",
                    "wasmer-compiler", "wasmer-engine", "wasmer-engine-universal",
-                   "wasmer-types"]),
+                   "wasmer-types", "wasmer-derive"]),
 }
 
 # where each crate is located in the `lib` directory
 # TODO: this could also be generated from the toml files
 location = {
     "wasmer-types": "types",
+    "wasmer-derive": "derive",
     "wasmer-vm": "vm",
     "wasmer-compiler": "compiler",
     "wasmer-object": "object",
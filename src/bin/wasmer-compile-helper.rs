@@ -0,0 +1,20 @@
+//! Compiles the WebAssembly module read from the standard input with Singlepass, on behalf of
+//! a `SubprocessCompiler`.
+
+#[cfg(unix)]
+fn main() {
+    use wasmer::{BaseTunables, Engine, Singlepass};
+    use wasmer_engine_universal::{serve_compile_request, Universal};
+
+    std::process::exit(serve_compile_request(|wasm| {
+        let engine = Universal::new(Singlepass::new()).engine();
+        let tunables = BaseTunables::for_target(engine.target());
+        engine.compile_universal(wasm, &tunables)
+    }));
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("out-of-process compilation is only supported on Unix");
+    std::process::exit(2);
+}
@@ -0,0 +1,117 @@
+use wasmer::abi_check::*;
+use wasmer::*;
+
+fn proposed_imports() -> Vec<ImportDescriptor> {
+    vec![
+        ImportDescriptor::new(
+            "env",
+            "gas",
+            ExternType::Function(FunctionType::new(vec![Type::I64], vec![])),
+        ),
+        ImportDescriptor::new(
+            "env",
+            "memory",
+            ExternType::Memory(MemoryType::new(1, Some(16), false)),
+        ),
+        ImportDescriptor::new(
+            "env",
+            "height",
+            ExternType::Global(GlobalType::new(Type::I64, Mutability::Const)),
+        ),
+    ]
+}
+
+fn wasm(wat: &str) -> Vec<u8> {
+    wat2wasm(wat.as_bytes()).unwrap().into_owned()
+}
+
+#[compiler_test(abi_check)]
+fn corpus_is_grouped_by_incompatibility(config: crate::Config) -> anyhow::Result<()> {
+    let store = config.store();
+    let compatible = r#"(module
+        (import "env" "gas" (func (param i64)))
+        (import "env" "memory" (memory 1 32))
+        (import "env" "height" (global i64)))"#;
+    let serialized = Module::new(&store, compatible)?.serialize()?;
+    let corpus = vec![
+        ModuleSource::wasm("compatible", wasm(compatible)),
+        unsafe { ModuleSource::serialized("cached", serialized) },
+        ModuleSource::wasm(
+            "old-gas-1",
+            wasm(r#"(module (import "env" "gas" (func (param i32))))"#),
+        ),
+        ModuleSource::wasm(
+            "old-gas-2",
+            wasm(r#"(module (import "env" "gas" (func (param i32))) (memory 1))"#),
+        ),
+        ModuleSource::wasm(
+            "small-memory",
+            wasm(r#"(module (import "env" "memory" (memory 1 8)))"#),
+        ),
+        ModuleSource::wasm(
+            "mutable-height",
+            wasm(r#"(module (import "env" "height" (global (mut i64))))"#),
+        ),
+        ModuleSource::wasm(
+            "removed",
+            wasm(r#"(module (import "env" "log" (func (param i32 i32))))"#),
+        ),
+        ModuleSource::wasm("corrupt", b"\0asm\x01\0\0\0\x02\xff".to_vec()),
+    ];
+
+    let report = check_corpus(
+        corpus.into_iter(),
+        &proposed_imports(),
+        &CorpusOptions { threads: 3 },
+    );
+    assert!(!report.is_compatible());
+    assert_eq!(report.compatible, ["cached", "compatible"]);
+    assert_eq!(report.corrupt.len(), 1);
+    assert_eq!(report.corrupt[0].id, "corrupt");
+
+    let broken = &report.broken;
+    assert_eq!(broken.len(), 4);
+    let names = broken
+        .iter()
+        .map(|group| group.incompatibility.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["gas", "height", "log", "memory"]);
+    assert!(matches!(
+        broken[0].incompatibility.kind,
+        IncompatibilityKind::Signature { .. }
+    ));
+    assert_eq!(broken[0].modules, ["old-gas-1", "old-gas-2"]);
+    assert!(matches!(
+        broken[1].incompatibility.kind,
+        IncompatibilityKind::Global { .. }
+    ));
+    assert_eq!(broken[1].modules, ["mutable-height"]);
+    assert!(matches!(
+        broken[2].incompatibility.kind,
+        IncompatibilityKind::Missing(_)
+    ));
+    assert_eq!(broken[2].modules, ["removed"]);
+    assert!(matches!(
+        broken[3].incompatibility.kind,
+        IncompatibilityKind::Memory { .. }
+    ));
+    assert_eq!(broken[3].modules, ["small-memory"]);
+    Ok(())
+}
+
+#[test]
+fn corpus_is_streamed() {
+    // The corpus is produced on demand, far larger than the channels between the threads.
+    let corpus = (0..1000).map(|i| {
+        let wat = format!(
+            r#"(module (import "env" "gas{}" (func (param i64))))"#,
+            i % 2
+        );
+        ModuleSource::wasm(i.to_string(), wasm(&wat))
+    });
+    let report = check_corpus(corpus, &proposed_imports(), &CorpusOptions::default());
+    assert_eq!(report.compatible.len(), 0);
+    assert_eq!(report.broken.len(), 2);
+    assert_eq!(report.broken[0].modules.len(), 500);
+    assert_eq!(report.broken[1].modules.len(), 500);
+}
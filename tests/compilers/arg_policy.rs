@@ -0,0 +1,217 @@
+//! Argument policies checked before host functions run.
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use wasmer::*;
+
+const WAT: &str = r#"
+(module
+    (import "env" "write" (func $write (param i32 i32 i32 i32)))
+    (import "env" "write64" (func $write64 (param i64 i64)))
+    (import "env" "select" (func $select (param i32 i64) (result i32)))
+    (memory 1 2)
+    (func (export "write") (param i32 i32 i32 i32)
+        (call $write (local.get 0) (local.get 1) (local.get 2) (local.get 3)))
+    (func (export "write64") (param i64 i64)
+        (call $write64 (local.get 0) (local.get 1)))
+    (func (export "select") (param i32 i64) (result i32)
+        (call $select (local.get 0) (local.get 1)))
+    (func (export "grow") (param i32) (result i32)
+        (memory.grow (local.get 0))))
+"#;
+
+/// Counts the calls that reach the host functions.
+#[derive(Clone, Default)]
+struct Env {
+    calls: Arc<AtomicUsize>,
+}
+
+impl WasmerEnv for Env {}
+
+fn write(env: &Env, _ptr: u32, _len: u32, _kind: i32, _flags: i32) {
+    env.calls.fetch_add(1, Ordering::SeqCst);
+}
+
+fn write64(env: &Env, _ptr: u64, _len: u64) {
+    env.calls.fetch_add(1, Ordering::SeqCst);
+}
+
+fn instance(store: &Store, env: &Env) -> Result<Instance> {
+    let module = Module::new(store, WAT)?;
+    let write = Function::new_native_with_env(store, env.clone(), write).with_arg_policy(
+        ArgPolicy::builder()
+            .ptr_len_pair(0, 1)
+            .enum_range(2, 0..=4)
+            .nonzero(3)
+            .build(),
+    )?;
+    let write64 = Function::new_native_with_env(store, env.clone(), write64)
+        .with_arg_policy(ArgPolicy::builder().ptr_len_pair(0, 1).build())?;
+    let signature = FunctionType::new(vec![Type::I32, Type::I64], vec![Type::I32]);
+    let calls = Arc::clone(&env.calls);
+    let select = Function::new(store, &signature, move |args| {
+        calls.fetch_add(1, Ordering::SeqCst);
+        Ok(vec![Value::I32(args[0].unwrap_i32())])
+    })
+    .with_arg_policy(
+        ArgPolicy::builder()
+            .enum_range(0, -1..=1)
+            .nonzero(1)
+            .build(),
+    )?;
+    Ok(Instance::new(
+        &module,
+        &imports! {
+            "env" => {
+                "write" => write,
+                "write64" => write64,
+                "select" => select,
+            },
+        },
+    )?)
+}
+
+/// Return the violation `result` failed with, checking its message.
+fn violation<T: std::fmt::Debug>(result: Result<T, RuntimeError>) -> (String, usize, ArgRule) {
+    let error = result.unwrap_err();
+    let message = error.message();
+    match error.downcast::<ArgPolicyError>() {
+        Ok(ArgPolicyError::Violation {
+            import,
+            index,
+            rule,
+        }) => {
+            assert_eq!(
+                message,
+                format!(
+                    "argument {} of import {} violates `{}` [W0692]",
+                    index, import, rule
+                )
+            );
+            (import, index, rule)
+        }
+        other => panic!("not a violation: {:?}", other),
+    }
+}
+
+#[compiler_test(arg_policy)]
+fn pointers_are_checked_against_the_memory(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let env = Env::default();
+    let instance = instance(&store, &env)?;
+    let write = instance.get_native_function::<(u32, u32, i32, i32), ()>("write")?;
+
+    write.call(0, 0x10000, 0, 1)?;
+    write.call(0x10000, 0, 0, 1)?;
+    assert_eq!(env.calls.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        violation(write.call(0xfff0, 0x11, 0, 1)),
+        (
+            r#""env"."write""#.to_string(),
+            0,
+            ArgRule::PtrLenPair { ptr: 0, len: 1 }
+        )
+    );
+    violation(write.call(0x10001, 0, 0, 1));
+    assert_eq!(env.calls.load(Ordering::SeqCst), 2);
+
+    // Pointers are checked against the size of the memory at the time of the call.
+    instance.get_native_function::<i32, i32>("grow")?.call(1)?;
+    write.call(0xfff0, 0x11, 0, 1)?;
+    assert_eq!(env.calls.load(Ordering::SeqCst), 3);
+    Ok(())
+}
+
+#[compiler_test(arg_policy)]
+fn ranges_wrapping_around_are_rejected(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let env = Env::default();
+    let instance = instance(&store, &env)?;
+
+    // Both wrap around to 0x10, within the memory, in the width of the arguments.
+    let write = instance.get_native_function::<(u32, u32, i32, i32), ()>("write")?;
+    violation(write.call(0xffff_fff0, 0x20, 0, 1));
+    let write64 = instance.get_native_function::<(u64, u64), ()>("write64")?;
+    write64.call(0x10, 0x10)?;
+    let (_, index, rule) = violation(write64.call(u64::MAX - 0xf, 0x20));
+    assert_eq!((index, rule), (0, ArgRule::PtrLenPair { ptr: 0, len: 1 }));
+    assert_eq!(env.calls.load(Ordering::SeqCst), 1);
+    Ok(())
+}
+
+#[compiler_test(arg_policy)]
+fn ranges_and_zeroes_are_checked(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let env = Env::default();
+    let instance = instance(&store, &env)?;
+
+    let write = instance.get_native_function::<(u32, u32, i32, i32), ()>("write")?;
+    write.call(0, 0, 4, -1)?;
+    let (_, index, rule) = violation(write.call(0, 0, 5, 1));
+    assert_eq!(
+        (index, rule),
+        (
+            2,
+            ArgRule::EnumRange {
+                arg: 2,
+                range: 0..=4
+            }
+        )
+    );
+    violation(write.call(0, 0, -1, 1));
+    let (_, index, _) = violation(write.call(0, 0, 0, 0));
+    assert_eq!(index, 3);
+    assert_eq!(env.calls.load(Ordering::SeqCst), 1);
+
+    // Dynamic functions are checked the same way.
+    let select = instance.get_native_function::<(i32, i64), i32>("select")?;
+    assert_eq!(select.call(-1, 1 << 40)?, -1);
+    let (import, index, _) = violation(select.call(2, 1));
+    assert_eq!((import.as_str(), index), (r#""env"."select""#, 0));
+    let (_, index, _) = violation(select.call(0, 0));
+    assert_eq!(index, 1);
+    assert_eq!(env.calls.load(Ordering::SeqCst), 2);
+    Ok(())
+}
+
+#[compiler_test(arg_policy)]
+fn policies_only_apply_to_host_functions(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let env = Env::default();
+    let instance = instance(&store, &env)?;
+    let policy = || ArgPolicy::builder().nonzero(0).build();
+
+    let export = instance.lookup_function("write").unwrap();
+    assert!(export.arg_policy().is_none());
+    let error = export.with_arg_policy(policy()).unwrap_err();
+    assert_eq!(error, ArgPolicyError::NotHostFunction);
+    assert_eq!(error.code(), ErrorCode::ArgPolicyNotHostFunction);
+    let error = Function::new_native(&store, |_: i32| {})
+        .with_arg_policy(policy())
+        .unwrap_err();
+    assert_eq!(error, ArgPolicyError::NotHostFunction);
+
+    // Rules must refer to integer arguments of the function.
+    let function = Function::new_native_with_env(&store, env.clone(), write64);
+    let error = function
+        .clone()
+        .with_arg_policy(ArgPolicy::builder().ptr_len_pair(1, 2).build())
+        .unwrap_err();
+    assert!(matches!(error, ArgPolicyError::Invalid(_)), "{}", error);
+    let error = Function::new_native_with_env(&store, env, |_: &Env, _: f64| {})
+        .with_arg_policy(policy())
+        .unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ArgPolicyInvalid);
+
+    // Policies are attached to a copy of the function, the function itself has none.
+    let checked = function
+        .clone()
+        .with_arg_policy(ArgPolicy::builder().ptr_len_pair(0, 1).build())?;
+    assert_eq!(
+        checked.arg_policy().unwrap().rules(),
+        &[ArgRule::PtrLenPair { ptr: 0, len: 1 }]
+    );
+    assert!(function.arg_policy().is_none());
+    Ok(())
+}
@@ -0,0 +1,184 @@
+//! Editing the metadata of serialized executables.
+
+use anyhow::Result;
+use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{
+    ArtifactEditError, ArtifactEditor, Universal, UniversalExecutable, UniversalExecutableRef,
+};
+
+const WAT: &str = r#"
+    (module $contract
+        (memory (export "memory") 1)
+        (func $transfer (export "transfer") (param i32 i64) (result i32)
+            (i32.add (local.get 0) (i32.wrap_i64 (local.get 1))))
+        (func $version (export "version") (result i32)
+            (i32.const 7)))
+"#;
+
+fn transfer() -> FunctionType {
+    FunctionType::new(vec![Type::I32, Type::I64], vec![Type::I32])
+}
+
+fn compile(config: &crate::Config) -> Result<(Store, Vec<u8>)> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let store = Store::new(&engine);
+    let executable = engine.compile_universal(&wat2wasm(WAT.as_bytes())?, store.tunables())?;
+    let bytes = executable.serialize().unwrap();
+    Ok((store, bytes))
+}
+
+fn owned(bytes: &[u8]) -> Result<UniversalExecutable> {
+    Ok(unsafe { UniversalExecutableRef::deserialize(bytes)? }.to_owned()?)
+}
+
+/// A stand-in for a real signature scheme, mixing the digest with a key.
+fn sign(key: &[u8], digest: &[u8; 32]) -> Vec<u8> {
+    digest
+        .iter()
+        .zip(key.iter().cycle())
+        .map(|(d, k)| d ^ k)
+        .collect()
+}
+
+#[compiler_test(artifact_editor)]
+fn edits_round_trip_through_loading(config: crate::Config) -> Result<()> {
+    let (store, original) = compile(&config)?;
+    let mut editor = unsafe { ArtifactEditor::open(&original)? };
+    editor.set_name("token");
+    editor.set_entry_points(vec![
+        ("send".to_string(), FunctionIndex::from_u32(0), transfer()),
+        (
+            "version".to_string(),
+            FunctionIndex::from_u32(1),
+            FunctionType::new(vec![], vec![Type::I32]),
+        ),
+    ])?;
+    editor.add_custom_metadata("build", b"2024-05-01".to_vec());
+    let edited = editor.finish()?;
+    assert_ne!(edited, original);
+
+    let before = owned(&original)?;
+    let after = owned(&edited)?;
+    assert_eq!(after.code_hash(), before.code_hash());
+    assert_eq!(after.function_hashes(), before.function_hashes());
+    for (index, body) in before.function_bodies().iter() {
+        assert_eq!(after.function_bodies()[index].body, body.body);
+    }
+
+    let executable = unsafe { UniversalExecutableRef::deserialize(&edited)? };
+    assert_eq!(
+        executable.custom_metadata("build"),
+        Some(&b"2024-05-01"[..])
+    );
+    assert_eq!(executable.custom_metadata("commit"), None);
+    assert_eq!(executable.signature_block(), None);
+    let module = Module::from_executable(&store, &executable)?;
+    assert!(module
+        .interface_text(InterfaceFormat::Wat)
+        .starts_with(";; module token\n"));
+    let mut exports = module.exports().map(|(name, _)| name).collect::<Vec<_>>();
+    exports.sort_unstable();
+    assert_eq!(exports, ["memory", "send", "version"]);
+
+    let instance = Instance::new(&module, &imports! {})?;
+    let send = instance.get_native_function::<(i32, i64), i32>("send")?;
+    assert_eq!(send.call(40, 2)?, 42);
+    assert!(instance.lookup_function("transfer").is_none());
+    assert!(instance.lookup_memory("memory").is_some());
+    Ok(())
+}
+
+#[compiler_test(artifact_editor)]
+fn entry_points_are_revalidated(config: crate::Config) -> Result<()> {
+    let (store, original) = compile(&config)?;
+    let mut editor = unsafe { ArtifactEditor::open(&original)? };
+
+    // Renaming `transfer` to an entry point the embedder calls with another signature.
+    let expected = FunctionType::new(vec![Type::I32], vec![]);
+    let error = editor
+        .set_entry_points(vec![(
+            "send".to_string(),
+            FunctionIndex::from_u32(0),
+            expected,
+        )])
+        .unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ArtifactEditSignatureMismatch);
+    assert_eq!(error.failure_kind(), FailureKind::Permanent);
+    match error {
+        ArtifactEditError::SignatureMismatch {
+            name,
+            expected,
+            recorded,
+        } => {
+            assert_eq!(name, "send");
+            assert_eq!(expected.params(), [Type::I32]);
+            assert_eq!(recorded, transfer());
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+
+    let error = editor
+        .set_entry_points(vec![(
+            "send".to_string(),
+            FunctionIndex::from_u32(2),
+            transfer(),
+        )])
+        .unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ArtifactEditUnknownFunction);
+    let error = editor
+        .set_entry_points(vec![(
+            "memory".to_string(),
+            FunctionIndex::from_u32(0),
+            transfer(),
+        )])
+        .unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ArtifactEditDuplicateExport);
+    assert_eq!(
+        error.to_string(),
+        "`memory` is exported more than once [W0222]"
+    );
+
+    // Failed edits leave the executable as it was.
+    let module = Module::from_executable(&store, &owned(&editor.finish()?)?)?;
+    let exports = module.exports().map(|(name, _)| name).collect::<Vec<_>>();
+    assert_eq!(exports, ["memory", "transfer", "version"]);
+    Ok(())
+}
+
+#[compiler_test(artifact_editor)]
+fn signatures_verify_after_re_signing(config: crate::Config) -> Result<()> {
+    let (store, original) = compile(&config)?;
+    let key = b"release key";
+    let mut editor = unsafe { ArtifactEditor::open(&original)? };
+    let digest = editor.signing_digest()?;
+    editor.replace_signature_block(sign(key, &digest));
+    let signed = editor.finish()?;
+
+    let mut editor = unsafe { ArtifactEditor::open(&signed)? };
+    assert_eq!(editor.signing_digest()?, digest);
+    assert_eq!(editor.signature_block(), Some(&sign(key, &digest)[..]));
+    editor.set_name("token");
+    // The previous signature does not cover the edit...
+    let edited_digest = editor.signing_digest()?;
+    assert_ne!(edited_digest, digest);
+    // ...until the edited executable is signed again.
+    let previous = editor.replace_signature_block(sign(key, &edited_digest));
+    assert_eq!(previous, Some(sign(key, &digest)));
+    let resigned = editor.finish()?;
+
+    let verifier = unsafe { ArtifactEditor::open(&resigned)? };
+    let digest = verifier.signing_digest()?;
+    assert_eq!(verifier.signature_block(), Some(&sign(key, &digest)[..]));
+    assert_eq!(owned(&resigned)?.code_hash(), owned(&original)?.code_hash());
+    let executable = unsafe { UniversalExecutableRef::deserialize(&resigned)? };
+    assert_eq!(
+        executable.signature_block(),
+        Some(&sign(key, &edited_digest)[..])
+    );
+    let module = Module::from_executable(&store, &executable)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let version = instance.get_native_function::<(), i32>("version")?;
+    assert_eq!(version.call()?, 7);
+    Ok(())
+}
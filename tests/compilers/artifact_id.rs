@@ -0,0 +1,61 @@
+//! Stable identifiers of compiled modules, to cache them under.
+
+use anyhow::Result;
+use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{Universal, UniversalExecutableRef};
+
+const WAT: &str = r#"
+    (module
+        (func (export "add") (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1))))
+"#;
+
+#[compiler_test(artifact_id)]
+fn identical_compilations_have_the_same_id(config: crate::Config) -> Result<()> {
+    let first = Module::new(&config.store(), WAT)?;
+    let store = config.store();
+    let second = Module::new(&store, WAT)?;
+    assert_eq!(first.artifact_id(), second.artifact_id());
+    assert_ne!(first.artifact_id(), first.wasm_hash());
+
+    let other = Module::new(&store, r#"(module (func (export "nop")))"#)?;
+    assert_ne!(other.artifact_id(), first.artifact_id());
+
+    // The executable, and modules loaded from it by a headless engine, have the same id.
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let executable = engine.compile_universal(&wat2wasm(WAT.as_bytes())?, store.tunables())?;
+    assert_eq!(executable.artifact_id()?, first.artifact_id());
+    assert_eq!(
+        executable.provenance()?.engine_id,
+        store.engine().fingerprint()
+    );
+    let bytes = executable.serialize().unwrap();
+    let reference = unsafe { UniversalExecutableRef::deserialize(&bytes)? };
+    assert_eq!(reference.to_owned()?.artifact_id()?, first.artifact_id());
+    let headless = config.headless_store();
+    let loaded = unsafe { Module::deserialize(&headless, &first.serialize()?)? };
+    assert_eq!(loaded.artifact_id(), first.artifact_id());
+    assert_ne!(
+        headless.engine().fingerprint(),
+        store.engine().fingerprint()
+    );
+    Ok(())
+}
+
+#[compiler_test(artifact_id)]
+fn features_change_the_id(mut config: crate::Config) -> Result<()> {
+    let mut features = Features::new();
+    features.multi_value(false);
+    config.set_features(features.clone());
+    let store = config.store();
+    let fingerprint = store.engine().fingerprint();
+    let id = Module::new(&store, WAT)?.artifact_id();
+
+    features.multi_memory(true);
+    config.set_features(features);
+    let store = config.store();
+    assert_ne!(store.engine().fingerprint(), fingerprint);
+    assert_ne!(Module::new(&store, WAT)?.artifact_id(), id);
+    Ok(())
+}
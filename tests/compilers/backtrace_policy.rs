@@ -0,0 +1,209 @@
+//! Budgeting the backtraces captured for traps.
+
+use anyhow::Result;
+use std::thread;
+use wasmer::*;
+use wasmer_types::InstanceConfig;
+
+/// `rec` recurses down to 0, where it traps. `run` calls it, and so does the start function
+/// when the depth in the global is not 0.
+const WAT: &str = r#"
+    (module
+        (global $depth (mut i32) (i32.const 0))
+        (func $rec (param i32)
+            (if (i32.eqz (local.get 0)) (then unreachable))
+            (call $rec (i32.sub (local.get 0) (i32.const 1))))
+        (func $run (export "run") (param i32)
+            (call $rec (local.get 0)))
+        (func $start
+            (if (global.get $depth) (then (call $run (global.get $depth)))))
+        (start $start))
+"#;
+
+const REC: u32 = 0;
+
+fn store(config: &crate::Config, policy: BacktracePolicy) -> Store {
+    let store = config.store();
+    store.set_backtrace_policy(policy);
+    store
+}
+
+/// Trap `depth` calls of `rec` deep, with room on the stack for as many.
+///
+/// `module` must outlive the error for a deferred trace to be looked up.
+fn trap(module: &Module, depth: i32) -> Result<RuntimeError> {
+    let module = module.clone();
+    thread::Builder::new()
+        .stack_size(256 << 20)
+        .spawn(move || -> Result<RuntimeError> {
+            let config = unsafe { InstanceConfig::default().with_max_wasm_stack(128 << 20) };
+            let instance = Instance::new_with_config(&module, config, &imports! {})?;
+            let run = instance.get_native_function::<i32, ()>("run")?;
+            Ok(run.call(depth).unwrap_err())
+        })?
+        .join()
+        .unwrap()
+}
+
+/// The function index of each frame of the trace of `error`, with its repeats.
+fn frames(error: &RuntimeError) -> Vec<(u32, usize)> {
+    error
+        .trace()
+        .iter()
+        .map(|frame| (frame.func_index(), frame.repeats()))
+        .collect()
+}
+
+/// Check that the trace of `error` starts with the frame of `rec` raising the trap, and has
+/// at most `max_frames` frames.
+///
+/// Only the top frame is checked, as Singlepass does not unwind past it.
+fn assert_top_frame(error: &RuntimeError, max_frames: usize) {
+    let frames = frames(error);
+    assert_eq!(frames.first(), Some(&(REC, 1)));
+    assert!(frames.len() <= max_frames, "{:?}", frames);
+}
+
+/// Check that the frames elided from the trace of `error`, if any, are reported where they
+/// were elided from, and on their own line.
+fn assert_elided_are_reported(error: &RuntimeError) {
+    let display = error.to_string();
+    let lines = display.lines().collect::<Vec<_>>();
+    match error.elided_frames() {
+        Some(elided) => {
+            assert!(elided.index <= error.trace().len());
+            assert_eq!(lines.len(), error.trace().len() + 2);
+            let line = format!("    ... {} frames elided ...", elided.count);
+            assert_eq!(lines[elided.index + 1], line);
+        }
+        None => {
+            assert_eq!(lines.len(), error.trace().len() + 1);
+            assert!(!display.contains("elided"));
+        }
+    }
+}
+
+#[compiler_test(backtrace_policy)]
+fn deep_traces_keep_their_ends(config: crate::Config) -> Result<()> {
+    let store = store(
+        &config,
+        BacktracePolicy {
+            max_frames: Some(10),
+            ..BacktracePolicy::default()
+        },
+    );
+    let module = Module::new(&store, WAT)?;
+    let error = trap(&module, 50_000)?;
+    assert_eq!(error.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    assert_top_frame(&error, 10);
+    assert_elided_are_reported(&error);
+    // 50001 frames of `rec` and the one of `run`, of which 10 at most are kept.
+    if let Some(elided) = error.elided_frames() {
+        assert_eq!(elided.count + error.trace().len(), 50_002);
+    }
+
+    // Traces within the budget are kept whole.
+    let error = trap(&module, 3)?;
+    assert_top_frame(&error, 5);
+    assert_eq!(error.elided_frames(), None);
+    assert_elided_are_reported(&error);
+    Ok(())
+}
+
+#[compiler_test(backtrace_policy)]
+fn recursion_is_summarized(config: crate::Config) -> Result<()> {
+    let store = store(
+        &config,
+        BacktracePolicy {
+            summarize_recursion: true,
+            ..BacktracePolicy::default()
+        },
+    );
+    let module = Module::new(&store, WAT)?;
+    // The frame raising the trap is not summarized with its callers, so that the trace is
+    // at most the trap, `rec` repeated 100 times, and `run`.
+    let error = trap(&module, 100)?;
+    assert_top_frame(&error, 3);
+    let display = error.to_string();
+    let lines = display.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), error.trace().len() + 1);
+    assert!(!lines[1].contains('\u{d7}'));
+    for (line, (_, repeats)) in lines[1..].iter().zip(frames(&error)) {
+        assert_eq!(repeats > 1, line.ends_with(&format!(" \u{d7} {}", repeats)));
+    }
+
+    // Summarized frames count as one against the budget.
+    store.set_backtrace_policy(BacktracePolicy {
+        max_frames: Some(2),
+        summarize_recursion: true,
+        ..BacktracePolicy::default()
+    });
+    let error = trap(&module, 100)?;
+    assert_top_frame(&error, 2);
+    assert_elided_are_reported(&error);
+    Ok(())
+}
+
+#[compiler_test(backtrace_policy)]
+fn traces_are_captured_on_request(config: crate::Config) -> Result<()> {
+    let store = store(
+        &config,
+        BacktracePolicy {
+            capture: BacktraceCapture::OnlyIfRequested,
+            ..BacktracePolicy::default()
+        },
+    );
+    let module = Module::new(&store, WAT)?;
+    let error = trap(&module, 50_000)?;
+    assert!(error.is_trace_deferred());
+    // Upgraded to the frame the trap was raised in.
+    assert_eq!(frames(&error), vec![(REC, 1)]);
+    assert!(!error.is_trace_deferred());
+
+    store.set_backtrace_policy(BacktracePolicy {
+        capture: BacktraceCapture::Never,
+        ..BacktracePolicy::default()
+    });
+    let error = trap(&module, 10)?;
+    assert!(!error.is_trace_deferred());
+    assert!(error.trace().is_empty());
+    assert_eq!(error.to_string().lines().count(), 1);
+    Ok(())
+}
+
+#[compiler_test(backtrace_policy)]
+fn start_functions_follow_the_policy(config: crate::Config) -> Result<()> {
+    let wat = WAT.replace("(mut i32) (i32.const 0)", "(mut i32) (i32.const 20)");
+    let start_error = |module: &Module| -> RuntimeError {
+        match Instance::new(module, &imports! {}) {
+            Err(InstantiationError::Start(error)) => error,
+            _ => panic!("the start function did not trap"),
+        }
+    };
+
+    let store = store(
+        &config,
+        BacktracePolicy {
+            max_frames: Some(4),
+            summarize_recursion: false,
+            capture: BacktraceCapture::Always,
+        },
+    );
+    let module = Module::new(&store, &wat)?;
+    // 21 frames of `rec`, then `run` and the start function, of which 4 at most are kept.
+    let error = start_error(&module);
+    assert_top_frame(&error, 4);
+    assert_elided_are_reported(&error);
+    if let Some(elided) = error.elided_frames() {
+        assert_eq!(elided.count + error.trace().len(), 23);
+    }
+
+    store.set_backtrace_policy(BacktracePolicy {
+        capture: BacktraceCapture::OnlyIfRequested,
+        ..BacktracePolicy::default()
+    });
+    let error = start_error(&module);
+    assert!(error.is_trace_deferred());
+    assert_eq!(frames(&error), vec![(REC, 1)]);
+    Ok(())
+}
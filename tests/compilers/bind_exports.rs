@@ -0,0 +1,107 @@
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (memory (export "memory") 1)
+        (global (export "counter") (mut i32) (i32.const 7))
+        (func (export "add") (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1)))
+        (func (export "store") (param i32 i32)
+            (i32.store (local.get 0) (local.get 1))))
+"#;
+
+bind_exports! {
+    struct Contract {
+        memory: Memory = "memory",
+        counter: Global = "counter",
+        add: NativeFunc<(i32, i32), i32> = "add",
+        store: NativeFunc<(i32, i32), ()> = "store",
+        raw_add: Function = "add",
+    }
+}
+
+bind_exports! {
+    struct Extended {
+        add: NativeFunc<(i32, i32), i32> = "add",
+        missing: Function = "missing",
+        table: Table = "table",
+        memory: Memory = "memory",
+    }
+}
+
+bind_exports! {
+    struct Mismatched {
+        add: NativeFunc<i64, i64> = "add",
+        counter: Memory = "counter",
+        store: NativeFunc<(i32, i32), ()> = "store",
+    }
+}
+
+#[compiler_test(bind_exports)]
+fn bind_all_exports(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let contract = Contract::bind(&instance)?;
+    // The handles keep the instance alive.
+    drop(instance);
+    drop(module);
+
+    assert_eq!(contract.add.call(40, 2)?, 42);
+    assert_eq!(
+        contract.raw_add.call(&[Value::I32(1), Value::I32(2)])?[0],
+        Value::I32(3)
+    );
+    contract.store.call(8, 0x1234)?;
+    assert_eq!(contract.memory.view::<u32>()[2].get(), 0x1234);
+    assert_eq!(contract.counter.get(), Value::I32(7));
+    Ok(())
+}
+
+#[compiler_test(bind_exports)]
+fn bind_reports_every_missing_export(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let error = match Extended::bind(&instance) {
+        Ok(_) => panic!("binding should have failed"),
+        Err(error) => error,
+    };
+    let names = error
+        .errors
+        .iter()
+        .map(|(name, _)| &**name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["missing", "table"]);
+    assert!(error
+        .errors
+        .iter()
+        .all(|(_, e)| matches!(e, ExportError::Missing(_))));
+    let message = error.to_string();
+    assert!(message.contains("`missing`"), "{}", message);
+    assert!(message.contains("`table`"), "{}", message);
+    Ok(())
+}
+
+#[compiler_test(bind_exports)]
+fn bind_reports_type_mismatches(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let error = match Mismatched::bind(&instance) {
+        Ok(_) => panic!("binding should have failed"),
+        Err(error) => error,
+    };
+    let names = error
+        .errors
+        .iter()
+        .map(|(name, _)| &**name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["add", "counter"]);
+    assert!(error
+        .errors
+        .iter()
+        .all(|(_, e)| matches!(e, ExportError::IncompatibleType)));
+    Ok(())
+}
@@ -0,0 +1,175 @@
+//! Eliding the bounds checks implied by earlier ones.
+
+use anyhow::Result;
+use wasmer::*;
+use wasmer_engine_universal::Universal;
+
+/// A xorshift generator, so that the corpus is the same on every run.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
+
+/// An offset, mostly small so that accesses overlap, sometimes large so that they trap.
+fn offset(rng: &mut Rng) -> u64 {
+    match rng.below(80) {
+        0 => 65536 - rng.below(16),
+        1 => 0xffff_fff0 + rng.below(16),
+        _ => rng.below(48),
+    }
+}
+
+/// A statement accessing memory through the cursors `$p` and `$q`, moving them, or ending the
+/// straight-line run of code.
+fn statement(rng: &mut Rng, depth: u32) -> String {
+    let cursor = rng.pick(&["$p", "$p", "$q"]);
+    match rng.below(if depth > 2 { 9 } else { 11 }) {
+        0..=2 => format!(
+            "(local.set $acc (i64.xor (local.get $acc) ({} offset={} (local.get {}))))",
+            rng.pick(&["i64.load", "i64.load32_u", "i64.load16_s", "i64.load8_u"]),
+            offset(rng),
+            cursor,
+        ),
+        3..=4 => format!(
+            "({} offset={} (local.get {}) (local.get $acc))",
+            rng.pick(&["i64.store", "i64.store32", "i64.store16", "i64.store8"]),
+            offset(rng),
+            cursor,
+        ),
+        5 => format!(
+            "(i32.store offset={} (local.get {}) (i32.const {}))",
+            offset(rng),
+            cursor,
+            rng.below(1000),
+        ),
+        6 => format!(
+            "(local.set {0} (i32.add (local.get {0}) (i32.const {1})))",
+            cursor,
+            rng.below(80) as i64 - 16,
+        ),
+        7 => format!(
+            "(local.set $acc (i64.add (local.get $acc) (i64.extend_i32_u (i32.load8_u offset={} (local.tee {} (local.get $p))))))",
+            offset(rng),
+            cursor,
+        ),
+        8 => "(drop (memory.grow (i32.const 0)))".to_string(),
+        9 => format!(
+            "(if (i32.wrap_i64 (i64.and (local.get $acc) (i64.const 1))) (then {}) (else {}))",
+            statement(rng, depth + 1),
+            statement(rng, depth + 1),
+        ),
+        _ => format!(
+            "(block $out {} (br_if $out (i64.eqz (local.get $acc))) {})",
+            statement(rng, depth + 1),
+            statement(rng, depth + 1),
+        ),
+    }
+}
+
+fn module(rng: &mut Rng) -> String {
+    let body = (0..40)
+        .map(|_| statement(rng, 0))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!(
+        r#"(module
+            (memory (export "memory") 1)
+            (func (export "run") (param $p i32) (param $q i32) (result i64)
+                (local $acc i64)
+                (local.set $acc (i64.const 0x0123456789abcdef))
+                {}
+                (local.get $acc)))"#,
+        body
+    )
+}
+
+/// The result of `run(p, q)` on a fresh instance and the memory it leaves.
+fn run(store: &Store, wasm: &[u8], p: i32, q: i32) -> Result<(Result<i64, String>, Vec<u8>)> {
+    let instance = Instance::new(&Module::new(store, wasm)?, &imports! {})?;
+    let run = instance.get_native_function::<(i32, i32), i64>("run")?;
+    let result = run.call(p, q).map_err(|error| error.message());
+    let memory = instance.lookup_memory("memory").unwrap();
+    let contents = memory.view::<u8>().iter().map(|cell| cell.get()).collect();
+    Ok((result, contents))
+}
+
+#[compiler_test(bounds_checks)]
+fn elision_preserves_traps(mut config: crate::Config) -> Result<()> {
+    config.set_bounds_check_elimination(false);
+    let checked = config.store();
+    config.set_bounds_check_elimination(true);
+    let elided = config.store();
+
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+    let (mut traps, mut returns) = (0, 0);
+    for _ in 0..60 {
+        let wasm = wat2wasm(module(&mut rng).as_bytes())?.to_vec();
+        for &(p, q) in &[
+            (0, 64),
+            (1000, 0),
+            (65536 - 100, 65536 - 60),
+            (65536 - 8, -1),
+        ] {
+            let expected = run(&checked, &wasm, p, q)?;
+            assert_eq!(run(&elided, &wasm, p, q)?, expected, "run({}, {})", p, q);
+            match expected.0 {
+                Ok(_) => returns += 1,
+                Err(_) => traps += 1,
+            }
+        }
+    }
+    // The corpus exercises both outcomes.
+    assert!(
+        traps > 20 && returns > 20,
+        "{} traps, {} returns",
+        traps,
+        returns
+    );
+    Ok(())
+}
+
+#[compiler_test(bounds_checks)]
+fn implied_checks_are_elided(mut config: crate::Config) -> Result<()> {
+    let wat = r#"(module
+        (memory 1)
+        (func (param $p i32) (result i64)
+            (i64.add
+                (i64.add (i64.load offset=24 (local.get $p)) (i64.load offset=16 (local.get $p)))
+                (i64.add (i64.load offset=8 (local.get $p)) (i64.load (local.get $p))))))"#;
+    let wasm = wat2wasm(wat.as_bytes())?;
+    let mut code_size = |elide| -> Result<usize> {
+        config.set_bounds_check_elimination(elide);
+        let engine = Universal::new(config.compiler_config(false)).engine();
+        let store = Store::new(&engine);
+        let executable = engine.compile_universal(&wasm, store.tunables())?;
+        Ok(executable
+            .function_bodies()
+            .values()
+            .next()
+            .unwrap()
+            .body
+            .len())
+    };
+    let (checked, elided) = (code_size(false)?, code_size(true)?);
+    assert!(
+        elided < checked,
+        "{} bytes, {} with elision",
+        checked,
+        elided
+    );
+    Ok(())
+}
@@ -0,0 +1,217 @@
+//! Bundles of modules instantiated together from a manifest.
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{Universal, UniversalEngine};
+
+/// Logs through the host and exports its memory, which `lib` writes to.
+const BASE: &str = r#"
+(module
+    (import "env" "log" (func $log (param i32)))
+    (memory (export "memory") 1)
+    (func (export "double") (param i32) (result i32)
+        (call $log (i32.const 1))
+        (i32.mul (local.get 0) (i32.const 2))))
+"#;
+
+const LIB: &str = r#"
+(module
+    (import "env" "log" (func $log (param i32)))
+    (import "base" "double" (func $double (param i32) (result i32)))
+    (import "base" "memory" (memory 1))
+    (func (export "quadruple") (param i32) (result i32)
+        (call $log (i32.const 2))
+        (i32.store (i32.const 0) (call $double (call $double (local.get 0))))
+        (i32.load (i32.const 0))))
+"#;
+
+const APP: &str = r#"
+(module
+    (import "env" "log" (func $log (param i32)))
+    (import "lib" "quadruple" (func $quadruple (param i32) (result i32)))
+    (func (export "run") (param i32) (result i32)
+        (call $log (i32.const 3))
+        (i32.add (call $quadruple (local.get 0)) (i32.const 1))))
+"#;
+
+/// Host imports recording the callers of `env.log`.
+fn host(store: &Store) -> (ImportObject, Arc<[AtomicUsize; 4]>) {
+    let calls = Arc::new(<[AtomicUsize; 4]>::default());
+    let log_calls = Arc::clone(&calls);
+    let log = Function::new_native_with_env(
+        store,
+        LogEnv { calls: log_calls },
+        |env: &LogEnv, slot: i32| {
+            env.calls[slot as usize].fetch_add(1, Ordering::SeqCst);
+        },
+    );
+    (imports! { "env" => { "log" => log } }, calls)
+}
+
+#[derive(Clone)]
+struct LogEnv {
+    calls: Arc<[AtomicUsize; 4]>,
+}
+
+impl WasmerEnv for LogEnv {}
+
+fn manifest() -> BundleManifest {
+    BundleManifest::new("app")
+        .slot("app", BundleModule::wasm(APP))
+        .slot("lib", BundleModule::wasm(LIB))
+        .slot("base", BundleModule::wasm(BASE))
+        .link("app", "lib", "lib")
+        .link("lib", "base", "base")
+}
+
+#[compiler_test(bundle)]
+fn acyclic_bundles_run_end_to_end(config: crate::Config) -> Result<()> {
+    let engine: UniversalEngine = Universal::new(config.compiler_config(false)).engine();
+    let store = Store::new(&engine);
+    let executable = engine.compile_universal(&wat2wasm(BASE.as_bytes())?, store.tunables())?;
+    let serialized = executable.serialize().unwrap();
+    let lib = Module::new(&store, LIB)?;
+    let bundle = InstanceBundle::new(
+        BundleManifest::new("app")
+            .slot("app", BundleModule::wasm(APP))
+            .slot("lib", BundleModule::module(lib))
+            .slot("base", unsafe { BundleModule::precompiled(serialized) })
+            .link("app", "lib", "lib")
+            .link("lib", "base", "base"),
+    )?;
+    assert_eq!(bundle.order().collect::<Vec<_>>(), ["base", "lib", "app"]);
+
+    let (imports, calls) = host(&store);
+    let instances = bundle.instantiate(&store, &imports)?;
+    assert_eq!(
+        instances.iter().map(|(slot, _)| slot).collect::<Vec<_>>(),
+        ["base", "lib", "app"]
+    );
+    let run = instances.main().get_native_function::<i32, i32>("run")?;
+    assert_eq!(run.call(5)?, 21);
+
+    // Every slot called the host, and `lib` wrote to the memory of `base`.
+    let calls = calls
+        .iter()
+        .map(|count| count.load(Ordering::SeqCst))
+        .collect::<Vec<_>>();
+    assert_eq!(calls, [0, 2, 1, 1]);
+    let memory = instances
+        .get("base")
+        .unwrap()
+        .lookup_memory("memory")
+        .unwrap();
+    assert_eq!(memory.view::<u32>()[0].get(), 20);
+
+    // Instantiating again gives independent instances.
+    let again = bundle.instantiate(&store, &imports)?;
+    assert!(again.main().id() != instances.main().id());
+    Ok(())
+}
+
+#[compiler_test(bundle)]
+fn cyclic_manifests_are_rejected(_config: crate::Config) -> Result<()> {
+    let error = InstanceBundle::new(
+        manifest()
+            .slot("other", BundleModule::wasm("(module)"))
+            .link("base", "app", "app"),
+    )
+    .err()
+    .unwrap();
+    assert!(
+        matches!(&error, BundleError::Cycle(cycle) if cycle == &["app", "lib", "base", "app"]),
+        "{}",
+        error
+    );
+    assert_eq!(
+        error.to_string(),
+        "bundle slots import from each other in a cycle: app -> lib -> base -> app [W0701]"
+    );
+
+    let error = InstanceBundle::new(manifest().link("base", "self", "base"))
+        .err()
+        .unwrap();
+    assert!(matches!(&error, BundleError::Cycle(cycle) if cycle == &["base", "base"]));
+
+    // Links must refer to defined slots, once per namespace.
+    for manifest in vec![
+        manifest().link("app", "env", "host"),
+        manifest().link("app", "lib", "base"),
+        manifest().slot("lib", BundleModule::wasm("(module)")),
+        BundleManifest::new("app"),
+    ] {
+        let error = InstanceBundle::new(manifest).err().unwrap();
+        assert_eq!(error.code(), ErrorCode::BundleManifest, "{}", error);
+    }
+    Ok(())
+}
+
+#[compiler_test(bundle)]
+fn partial_failures_drop_or_return_instances(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let (imports, _) = host(&store);
+    let failing = || {
+        BundleManifest::new("app")
+            .slot("app", BundleModule::wasm(APP))
+            .slot("lib", BundleModule::wasm(LIB))
+            .slot(
+                "base",
+                BundleModule::wasm(
+                    r#"(module (memory (export "memory") 1) (func $start unreachable) (start $start))"#,
+                ),
+            )
+            .slot("other", BundleModule::wasm(BASE))
+            .link("app", "lib", "lib")
+            .link("lib", "base", "other")
+            .link("app", "base", "base")
+    };
+
+    let bundle = InstanceBundle::new(failing())?;
+    assert_eq!(
+        bundle.order().collect::<Vec<_>>(),
+        ["other", "lib", "base", "app"]
+    );
+    match bundle.instantiate(&store, &imports).err().unwrap() {
+        BundleError::Instantiation {
+            slot,
+            source,
+            partial,
+        } => {
+            assert_eq!(slot, "base");
+            assert!(matches!(*source, InstantiationError::Start(_)));
+            assert!(partial.is_none());
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+
+    let bundle = InstanceBundle::new(failing())?.keep_partial_on_failure(true);
+    match bundle.instantiate(&store, &imports).err().unwrap() {
+        BundleError::Instantiation { partial, .. } => {
+            let partial = partial.unwrap();
+            assert_eq!(
+                partial.iter().map(|(slot, _)| slot).collect::<Vec<_>>(),
+                ["other", "lib"]
+            );
+            let quadruple = partial
+                .get("lib")
+                .unwrap()
+                .get_native_function::<i32, i32>("quadruple")?;
+            assert_eq!(quadruple.call(3)?, 12);
+        }
+        other => panic!("unexpected error: {}", other),
+    }
+
+    // Imports without a link nor a host import fail to link.
+    let bundle = InstanceBundle::new(manifest())?;
+    let error = bundle.instantiate(&store, &imports! {}).err().unwrap();
+    assert!(
+        matches!(&error, BundleError::Instantiation { slot, .. } if slot == "base"),
+        "{}",
+        error
+    );
+    assert_eq!(error.code(), ErrorCode::LinkImports);
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &str = r#"
+(module
+    (import "env" "seq" (global $seq i64))
+    (import "env" "reenter" (func $reenter (result i64)))
+    (func (export "read") (result i64)
+        global.get $seq)
+    ;; Reads the sequence number around a nested call into the instance, returning the
+    ;; number if the three reads agree and -1 otherwise.
+    (func (export "read_around_call") (result i64)
+        (local $first i64)
+        global.get $seq
+        local.set $first
+        (if (i64.ne (call $reenter) (local.get $first))
+            (then (return (i64.const -1))))
+        (if (i64.ne (global.get $seq) (local.get $first))
+            (then (return (i64.const -1))))
+        local.get $first))
+"#;
+
+#[derive(Clone, Default)]
+struct Env {
+    read: LazyInit<NativeFunc<(), i64>>,
+}
+
+impl WasmerEnv for Env {
+    fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
+        self.read
+            .initialize(instance.get_native_function("read").unwrap());
+        Ok(())
+    }
+}
+
+/// Instantiate `WAT`, importing `seq` unless the call sequence global shadows it.
+fn instance(store: &Store, config: InstanceConfig, seq: &Global) -> Result<Instance> {
+    let module = Module::new(store, WAT)?;
+    let imports = imports! {
+        "env" => {
+            "seq" => seq.clone(),
+            "reenter" => Function::new_native_with_env(store, Env::default(), |env: &Env| {
+                env.read.get_ref().unwrap().call().unwrap()
+            }),
+        },
+    };
+    Ok(Instance::new_with_config(&module, config, &imports)?)
+}
+
+#[compiler_test(call_sequence)]
+fn increasing_across_calls(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let config = InstanceConfig::default().with_call_sequence_global("env", "seq");
+    let seq = Global::new(&store, Value::I64(-42));
+    let instance = instance(&store, config, &seq)?;
+    let read = instance.get_native_function::<(), i64>("read")?;
+    assert_eq!(instance.call_sequence(), 0);
+    let values = (0..3).map(|_| read.call()).collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(values, [1, 2, 3]);
+    assert_eq!(instance.call_sequence(), 3);
+
+    // Dynamic calls count the same way.
+    let result = instance.lookup_function("read").unwrap().call(&[])?;
+    assert_eq!(&*result, [Value::I64(4)]);
+    assert_eq!(instance.call_sequence(), 4);
+    Ok(())
+}
+
+#[compiler_test(call_sequence)]
+fn stable_within_call(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let config = InstanceConfig::default().with_call_sequence_global("env", "seq");
+    let seq = Global::new(&store, Value::I64(-42));
+    let instance = instance(&store, config, &seq)?;
+    let read_around_call = instance.get_native_function::<(), i64>("read_around_call")?;
+    assert_eq!(read_around_call.call()?, 1);
+    assert_eq!(read_around_call.call()?, 2);
+    assert_eq!(instance.call_sequence(), 2);
+    Ok(())
+}
+
+#[compiler_test(call_sequence)]
+fn not_configured(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let seq = Global::new(&store, Value::I64(-42));
+    let instance = instance(&store, InstanceConfig::default(), &seq)?;
+    let read = instance.get_native_function::<(), i64>("read")?;
+    assert_eq!(read.call()?, -42);
+    assert_eq!(read.call()?, -42);
+    // The host can still correlate calls without the global.
+    assert_eq!(instance.call_sequence(), 2);
+    Ok(())
+}
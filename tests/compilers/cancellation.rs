@@ -0,0 +1,129 @@
+//! Cancelling single calls into an instance while they run.
+
+use anyhow::Result;
+use std::sync::{mpsc, Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
+use wasmer::*;
+
+/// Blocks in the host until released, then spins, or counts up to its parameter.
+const WAT: &str = r#"
+    (module
+        (import "host" "block" (func $block))
+        (func (export "block_then_spin")
+            (call $block)
+            (loop $spin (br $spin)))
+        (func (export "count") (param $n i32) (result i32) (local $i i32)
+            (loop $count
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br_if $count (i32.lt_u (local.get $i) (local.get $n))))
+            (local.get $i)))
+"#;
+
+/// A store whose compiled code checks for the cancellation of the running call.
+fn store(config: &crate::Config) -> Store {
+    let mut compiler = Singlepass::new();
+    compiler.cancellation(true);
+    compiler.canonicalize_nans(config.canonicalize_nans);
+    compiler.frame_pointer(config.frame_pointer);
+    compiler.redundant_bounds_check_elimination(config.bounds_check_elimination);
+    Store::new(&*config.engine(Box::new(compiler)))
+}
+
+/// An instance whose `block` import waits on `barrier` twice: once when it is entered, and
+/// once to be released.
+fn instance(store: &Store, barrier: Arc<Barrier>) -> Result<Instance> {
+    let block = Function::new(store, FunctionType::new(vec![], vec![]), move |_| {
+        barrier.wait();
+        barrier.wait();
+        Ok(vec![])
+    });
+    let module = Module::new(store, WAT)?;
+    Ok(Instance::new(
+        &module,
+        &imports! { "host" => { "block" => block } },
+    )?)
+}
+
+fn assert_cancelled(error: &RuntimeError) {
+    assert_eq!(error.trap_code(), Some(TrapCode::Cancelled));
+    assert_eq!(error.code(), ErrorCode::TrapCancelled);
+    assert_eq!(error.failure_kind(), FailureKind::TransientInterrupted);
+}
+
+#[compiler_test(cancellation)]
+fn spinning_calls_stop_promptly(config: crate::Config) -> Result<()> {
+    let store = store(&config);
+    let barrier = Arc::new(Barrier::new(2));
+    let instance = instance(&store, barrier.clone())?;
+    let (handle, call) = instance.guarded_call_handle("block_then_spin", &[], Guards::default())?;
+    let canceller = thread::spawn(move || {
+        // Let the call leave the host function and spin for a while.
+        barrier.wait();
+        barrier.wait();
+        thread::sleep(Duration::from_millis(50));
+        assert!(handle.cancel());
+        Instant::now()
+    });
+
+    let error = call().unwrap_err();
+    let returned = Instant::now();
+    let cancelled = canceller.join().unwrap();
+    assert_cancelled(&error);
+    assert!(returned.duration_since(cancelled) < Duration::from_secs(1));
+    Ok(())
+}
+
+#[compiler_test(cancellation)]
+fn cancelling_finished_calls_does_nothing(config: crate::Config) -> Result<()> {
+    let store = store(&config);
+    let instance = instance(&store, Arc::new(Barrier::new(2)))?;
+    let (handle, call) =
+        instance.guarded_call_handle("count", &[Val::I32(10)], Guards::default())?;
+    assert_eq!(call()?.to_vec(), vec![Val::I32(10)]);
+    assert!(!handle.cancel());
+    assert!(!handle.is_cancelled());
+
+    // Calls cancelled before they start do not run.
+    let (handle, call) =
+        instance.guarded_call_handle("count", &[Val::I32(10)], Guards::default())?;
+    assert!(handle.cancel());
+    assert!(handle.is_cancelled());
+    assert!(!handle.cancel());
+    assert_cancelled(&call().unwrap_err());
+
+    let (_, call) = instance.guarded_call_handle("count", &[Val::I32(10)], Guards::default())?;
+    assert_eq!(call()?.to_vec(), vec![Val::I32(10)]);
+    Ok(())
+}
+
+#[compiler_test(cancellation)]
+fn concurrent_calls_are_not_cancelled(config: crate::Config) -> Result<()> {
+    let store = store(&config);
+    let barrier = Arc::new(Barrier::new(2));
+    let instance = instance(&store, barrier.clone())?;
+    let (sender, receiver) = mpsc::channel();
+    let blocked = {
+        let instance = instance.clone();
+        thread::spawn(move || -> Result<RuntimeError> {
+            let (handle, call) =
+                instance.guarded_call_handle("block_then_spin", &[], Guards::default())?;
+            sender.send(handle).unwrap();
+            Ok(call().unwrap_err())
+        })
+    };
+    let handle = receiver.recv().unwrap();
+
+    // Cancelled while it blocks in the host function, the call keeps blocking, and the
+    // instance keeps running other calls to completion meanwhile.
+    barrier.wait();
+    assert!(handle.cancel());
+    let (_, call) =
+        instance.guarded_call_handle("count", &[Val::I32(1_000_000)], Guards::default())?;
+    assert_eq!(call()?.to_vec(), vec![Val::I32(1_000_000)]);
+
+    // Then stops once back in WebAssembly.
+    barrier.wait();
+    assert_cancelled(&blocked.join().unwrap()?);
+    Ok(())
+}
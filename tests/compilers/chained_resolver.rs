@@ -0,0 +1,231 @@
+//! Resolving imports from layers of import objects.
+
+use anyhow::Result;
+use wasmer::*;
+
+/// A function returning `value`, telling which layer an import comes from.
+fn constant(store: &Store, value: i32) -> Function {
+    let ty = FunctionType::new(vec![], vec![Type::I32]);
+    Function::new(store, ty, move |_| Ok(vec![Value::I32(value)]))
+}
+
+/// The host ABI, the extensions of a tenant and debugging shims, all defining `env.level`.
+fn layers(store: &Store) -> (ImportObject, ImportObject, ImportObject) {
+    let core = imports! {
+        "env" => {
+            "level" => constant(store, 1),
+            "abi_version" => constant(store, 7),
+        },
+        "core_io" => { "read" => constant(store, 10) },
+    };
+    let tenant = imports! {
+        "env" => {
+            "level" => constant(store, 2),
+            "quota" => constant(store, 20),
+        },
+        "debug" => { "log" => constant(store, 21) },
+    };
+    let debug = imports! {
+        "env" => { "level" => constant(store, 3) },
+        "debug" => { "trace" => constant(store, 30) },
+    };
+    (core, tenant, debug)
+}
+
+/// A module importing `imports` as functions returning an `i32`, and exporting a function
+/// calling each of them.
+fn module(store: &Store, imports: &[(&str, &str)]) -> Result<Module> {
+    let mut wat = "(module".to_string();
+    for (index, (module, field)) in imports.iter().enumerate() {
+        wat += &format!(
+            r#" (import "{}" "{}" (func $i{} (result i32)))"#,
+            module, field, index
+        );
+    }
+    for index in 0..imports.len() {
+        wat += &format!(
+            r#" (func (export "call{}") (result i32) (call $i{}))"#,
+            index, index
+        );
+    }
+    wat += ")";
+    Ok(Module::new(store, wat)?)
+}
+
+fn call(instance: &Instance, index: usize) -> Result<i32> {
+    let function = instance.get_native_function::<(), i32>(&format!("call{}", index))?;
+    Ok(function.call()?)
+}
+
+/// The error of the chained resolver failing the instantiation of `module`.
+fn rejection(module: &Module, resolver: &ChainedResolver) -> ChainedResolverError {
+    match Instance::new(module, resolver) {
+        Err(InstantiationError::Link(LinkError::Import(_, _, ImportError::Rejected(error)))) => {
+            error
+                .downcast_ref::<ChainedResolverError>()
+                .unwrap()
+                .clone()
+        }
+        Err(error) => panic!("unexpected error: {}", error),
+        Ok(_) => panic!("the instantiation succeeded"),
+    }
+}
+
+#[compiler_test(chained_resolver)]
+fn earlier_layers_take_precedence(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let (core, tenant, debug) = layers(&store);
+    let resolver = ChainedResolver::builder()
+        .layer("core", core)
+        .layer("tenant", tenant)
+        .layer("debug", debug)
+        .build();
+    let module = module(
+        &store,
+        &[("env", "level"), ("env", "quota"), ("debug", "trace")],
+    )?;
+    let instance = Instance::new(&module, &resolver)?;
+    assert_eq!(call(&instance, 0)?, 1);
+    assert_eq!(call(&instance, 1)?, 20);
+    assert_eq!(call(&instance, 2)?, 30);
+
+    // Reordering the layers changes which one wins.
+    let (core, tenant, debug) = layers(&store);
+    let resolver = ChainedResolver::builder()
+        .layer("debug", debug)
+        .layer("tenant", tenant)
+        .layer("core", core)
+        .build();
+    let instance = Instance::new(&module, &resolver)?;
+    assert_eq!(call(&instance, 0)?, 3);
+    Ok(())
+}
+
+#[compiler_test(chained_resolver)]
+fn unique_namespaces_reject_shadowing(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let (core, tenant, debug) = layers(&store);
+    let resolver = ChainedResolver::builder()
+        .layer("core", core)
+        .layer("tenant", tenant)
+        .layer("debug", debug)
+        .policy(Namespace("env"), Precedence::RequireUnique)
+        .build();
+    assert_eq!(resolver.precedence("env"), Precedence::RequireUnique);
+    assert_eq!(resolver.precedence("debug"), Precedence::FirstMatch);
+
+    let error = rejection(&module(&store, &[("env", "level")])?, &resolver);
+    assert_eq!(
+        error,
+        ChainedResolverError::Ambiguous {
+            module: "env".to_string(),
+            field: "level".to_string(),
+            layers: vec![
+                "core".to_string(),
+                "tenant".to_string(),
+                "debug".to_string()
+            ],
+        }
+    );
+    assert_eq!(error.code(), ErrorCode::ChainedResolverAmbiguous);
+
+    // Imports a single layer defines are fine.
+    let module = module(&store, &[("env", "abi_version"), ("env", "quota")])?;
+    let instance = Instance::new(&module, &resolver)?;
+    assert_eq!(call(&instance, 0)?, 7);
+    assert_eq!(call(&instance, 1)?, 20);
+    Ok(())
+}
+
+#[compiler_test(chained_resolver)]
+fn layers_only_provide_their_namespaces(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let (core, mut tenant, debug) = layers(&store);
+    tenant.register("core_io", {
+        let mut exports = Exports::new();
+        exports.insert("read", constant(&store, 22));
+        exports
+    });
+    let resolver = ChainedResolver::builder()
+        .layer("core", core)
+        .layer_filtered("tenant", tenant, NamespaceFilter::deny(["core_*"]))
+        .layer_filtered("debug", debug, NamespaceFilter::allow(["debug"]))
+        .build();
+
+    // Even though the core layer comes first.
+    let error = rejection(&module(&store, &[("core_io", "read")])?, &resolver);
+    assert_eq!(
+        error,
+        ChainedResolverError::NamespaceViolation {
+            layer: "tenant".to_string(),
+            module: "core_io".to_string(),
+            field: "read".to_string(),
+        }
+    );
+    assert_eq!(error.code(), ErrorCode::ChainedResolverNamespaceViolation);
+    let error = rejection(&module(&store, &[("env", "level")])?, &resolver);
+    assert!(matches!(
+        error,
+        ChainedResolverError::NamespaceViolation { layer, .. } if layer == "debug"
+    ));
+
+    let module = module(&store, &[("env", "quota"), ("debug", "trace")])?;
+    Instance::new(&module, &resolver)?;
+    Ok(())
+}
+
+#[compiler_test(chained_resolver)]
+fn traces_tell_the_layer_of_each_import(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let (core, tenant, debug) = layers(&store);
+    let resolver = ChainedResolver::builder()
+        .layer("core", core)
+        .layer("tenant", tenant)
+        .layer("debug", debug)
+        .record_trace()
+        .build();
+    let module = module(
+        &store,
+        &[
+            ("env", "level"),
+            ("core_io", "read"),
+            ("env", "quota"),
+            ("debug", "log"),
+            ("debug", "trace"),
+        ],
+    )?;
+    Instance::new(&module, &resolver)?;
+    let resolved = |module: &str, field: &str, layer: &str| ResolvedImport {
+        module: module.to_string(),
+        field: field.to_string(),
+        layer: Some(layer.to_string()),
+    };
+    assert_eq!(
+        resolver.trace(),
+        Some(vec![
+            resolved("env", "level", "core"),
+            resolved("core_io", "read", "core"),
+            resolved("env", "quota", "tenant"),
+            resolved("debug", "log", "tenant"),
+            resolved("debug", "trace", "debug"),
+        ])
+    );
+
+    // Missing imports are traced too, before failing the instantiation.
+    let missing = self::module(&store, &[("env", "missing")])?;
+    assert!(Instance::new(&missing, &resolver).is_err());
+    let trace = resolver.trace().unwrap();
+    assert_eq!(
+        trace.last(),
+        Some(&ResolvedImport {
+            module: "env".to_string(),
+            field: "missing".to_string(),
+            layer: None,
+        })
+    );
+    assert_eq!(trace.len(), 6);
+
+    let untraced = ChainedResolver::builder().build();
+    assert_eq!(untraced.trace(), None);
+    Ok(())
+}
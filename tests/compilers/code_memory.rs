@@ -0,0 +1,155 @@
+//! Loading modules when the code memory of the engine runs out.
+
+use anyhow::Result;
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{Universal, UniversalEngine, UniversalExecutableRef};
+
+const WAT: &str = r#"
+    (module
+        (func (export "answer") (result i32)
+            i32.const 42))
+"#;
+
+fn engine(config: &crate::Config) -> (UniversalEngine, Store, Arc<AtomicMetricsSink>) {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    let store = Store::new(&engine);
+    (engine, store, sink)
+}
+
+fn answer(module: &Module) -> Result<i32> {
+    let instance = Instance::new(module, &imports! {})?;
+    Ok(instance.get_native_function::<(), i32>("answer")?.call()?)
+}
+
+#[compiler_test(code_memory)]
+fn dropped_modules_are_reclaimed(config: crate::Config) -> Result<()> {
+    let (engine, store, sink) = engine(&config);
+    let first = Module::new(&store, WAT)?;
+    let size = sink.snapshot().code_bytes as usize;
+    engine.set_code_memory_limit(Some(size));
+
+    // An instance keeps the code of its module, even once the module is dropped.
+    let instance = Instance::new(&first, &imports! {})?;
+    drop(first);
+    assert!(matches!(
+        Module::new(&store, WAT),
+        Err(CompileError::CodeMemoryExhausted { .. })
+    ));
+    assert_eq!(
+        instance.get_native_function::<(), i32>("answer")?.call()?,
+        42
+    );
+
+    drop(instance);
+    let second = Module::new(&store, WAT)?;
+    assert_eq!(answer(&second)?, 42);
+    let snapshot = sink.snapshot();
+    assert_eq!(snapshot.code_memory_reclaimed, 1);
+    assert_eq!(snapshot.code_memory_exhausted, 1);
+    assert_eq!(snapshot.code_bytes as usize, size);
+    Ok(())
+}
+
+#[compiler_test(code_memory)]
+fn exhaustion_is_a_transient_error(config: crate::Config) -> Result<()> {
+    let (engine, store, sink) = engine(&config);
+    let module = Module::new(&store, WAT)?;
+    let size = sink.snapshot().code_bytes as usize;
+    engine.set_code_memory_limit(Some(size + size / 2));
+
+    let error = Module::new(&store, WAT).unwrap_err();
+    match error {
+        CompileError::CodeMemoryExhausted {
+            requested,
+            in_use,
+            largest_free_block,
+        } => {
+            assert_eq!(requested, size);
+            assert_eq!(in_use, size);
+            assert_eq!(largest_free_block, size / 2);
+        }
+        ref other => panic!("unexpected error: {:?}", other),
+    }
+    assert_eq!(error.failure_kind(), FailureKind::TransientResource);
+    assert_eq!(error.code(), ErrorCode::CompileCodeMemoryExhausted);
+    assert_eq!(sink.snapshot().code_memory_reclaimed, 0);
+
+    // Lifting the limit lets the same load succeed.
+    engine.set_code_memory_limit(None);
+    let other = Module::new(&store, WAT)?;
+    assert_eq!(answer(&other)?, 42);
+    assert_eq!(answer(&module)?, 42);
+    Ok(())
+}
+
+#[compiler_test(code_memory)]
+fn deserialized_modules_are_limited_too(config: crate::Config) -> Result<()> {
+    let (engine, store, sink) = engine(&config);
+    let executable = engine.compile_universal(&wat2wasm(WAT.as_bytes())?, store.tunables())?;
+    let serialized = executable.serialize().unwrap();
+    let module = Module::from_executable(&store, &executable)?;
+    engine.set_code_memory_limit(Some(sink.snapshot().code_bytes as usize));
+
+    let deserialized = unsafe { UniversalExecutableRef::deserialize(&serialized)? };
+    assert!(matches!(
+        Module::from_executable(&store, &deserialized),
+        Err(CompileError::CodeMemoryExhausted { .. })
+    ));
+    drop(module);
+    let module = Module::from_executable(&store, &deserialized)?;
+    assert_eq!(answer(&module)?, 42);
+    Ok(())
+}
+
+#[compiler_test(code_memory)]
+fn modules_of_a_store_do_not_collide(config: crate::Config) -> Result<()> {
+    let (engine, store, sink) = engine(&config);
+    let modules = (0..32)
+        .map(|answer| {
+            let wat = format!(
+                r#"(module (func (export "answer") (result i32) i32.const {}))"#,
+                answer
+            );
+            Module::new(&store, wat)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    for (expected, module) in modules.iter().enumerate() {
+        assert_eq!(answer(module)?, expected as i32);
+    }
+
+    // The code of dropped modules is reused rather than leaked.
+    let size = sink.snapshot().code_bytes as usize;
+    engine.set_code_memory_limit(Some(size));
+    drop(modules);
+    for _ in 0..64 {
+        let module = Module::new(&store, WAT)?;
+        assert_eq!(answer(&module)?, 42);
+    }
+    assert!(sink.snapshot().code_bytes as usize <= size);
+    Ok(())
+}
+
+#[compiler_test(code_memory)]
+fn dropping_the_store_releases_code_memory(config: crate::Config) -> Result<()> {
+    let (engine, store, sink) = engine(&config);
+    let modules = (0..8)
+        .map(|_| Module::new(&store, WAT))
+        .collect::<Result<Vec<_>, _>>()?;
+    let instance = Instance::new(&modules[0], &imports! {})?;
+    assert!(sink.snapshot().code_bytes > 0);
+
+    // Modules and instances keep the engine, and thus the code memory, alive.
+    drop((engine, store, modules));
+    assert!(sink.snapshot().code_bytes > 0);
+    assert_eq!(
+        instance.get_native_function::<(), i32>("answer")?.call()?,
+        42
+    );
+    drop(instance);
+    assert_eq!(sink.snapshot().code_bytes, 0);
+    Ok(())
+}
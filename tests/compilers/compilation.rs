@@ -9,6 +9,13 @@ fn slow_to_compile_contract(n_fns: usize, n_locals: usize) -> Vec<u8> {
     wat2wasm(wat.as_bytes()).unwrap().to_vec()
 }
 
+/// A contract with a single function whose body is `n_ops` pairs of instructions long.
+fn large_function_contract(n_ops: usize) -> Vec<u8> {
+    let body = "i32.const 0 drop ".repeat(n_ops);
+    let wat = format!(r#"(module (func) (func (export "main") {}))"#, body);
+    wat2wasm(wat.as_bytes()).unwrap().to_vec()
+}
+
 fn compile_uncached<'a>(
     store: &'a Store,
     engine: &'a dyn Engine,
@@ -106,3 +113,62 @@ fn profiling() {
         }
     }
 }
+
+#[test]
+fn parallel_compilation_is_deterministic() {
+    let fns = (0..200)
+        .map(|i| {
+            format!(
+                "(func (export \"f{0}\") (param i32) (result i32) (call $imp (i32.add (local.get 0) (i32.const {0}))))\n",
+                i
+            )
+        })
+        .collect::<String>();
+    let wat = format!(
+        r#"(module (import "env" "imp" (func $imp (param i32) (result i32))) {})"#,
+        fns
+    );
+    let wasm = wat2wasm(wat.as_bytes()).unwrap();
+    let serialized = |parallel: bool| {
+        let mut compiler = Singlepass::default();
+        compiler.parallel_compilation(parallel);
+        let engine = Universal::new(compiler).engine();
+        let store = Store::new(&engine);
+        compile_uncached(&store, &engine, &wasm, false)
+            .unwrap()
+            .serialize()
+            .unwrap()
+    };
+    let serial = serialized(false);
+    assert_eq!(serialized(true), serial);
+    assert_eq!(serialized(true), serial);
+}
+
+#[test]
+fn function_body_size_limit() {
+    let engine = |limit: usize| {
+        let mut compiler = Singlepass::default();
+        CompilerConfig::set_max_function_body_size(&mut compiler, limit);
+        Universal::new(compiler).engine()
+    };
+    let code = large_function_contract(100_000);
+    let engine = engine(64 * 1024);
+    let store = Store::new(&engine);
+    let error = match compile_uncached(&store, &engine, &code, false) {
+        Err(error) => error,
+        Ok(_) => panic!("compiled a function over the limit"),
+    };
+    match &error {
+        CompileError::CodeTooLarge { index, size, limit } => {
+            assert_eq!(*index, LocalFunctionIndex::from_u32(1));
+            assert!(*size > 300_000, "{}", size);
+            assert_eq!(*limit, 64 * 1024);
+        }
+        error => panic!("unexpected error: {}", error),
+    }
+    assert_eq!(error.code(), ErrorCode::CompileCodeTooLarge);
+
+    // Functions within the limit compile as usual.
+    let code = large_function_contract(1000);
+    assert!(compile_uncached(&store, &engine, &code, false).is_ok());
+}
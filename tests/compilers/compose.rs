@@ -0,0 +1,283 @@
+//! Composing modules into one, resolving the imports they provide each other statically.
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use wasmer::wasmparser::{Name, NameSectionReader, Operator, Parser, Payload};
+use wasmer::*;
+
+const MATH: &str = r#"
+    (module
+        (memory (export "memory") 1)
+        (global $counter (export "counter") (mut i32) (i32.const 0))
+        (func $add (export "add") (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1)))
+        (func $mul (export "mul") (param i32 i32) (result i32)
+            (i32.mul (local.get 0) (local.get 1)))
+        (func $init (global.set $counter (i32.const 10)))
+        (start $init))
+"#;
+
+const CONTRACT: &str = r#"
+    (module
+        (type $binop (func (param i32 i32) (result i32)))
+        (import "env" "log" (func $log (param i32)))
+        (import "math" "add" (func $add (type $binop)))
+        (import "math" "mul" (func $mul (type $binop)))
+        (import "math" "memory" (memory 1))
+        (import "math" "counter" (global $counter (mut i32)))
+        (table 2 funcref)
+        (elem (i32.const 0) $add $mul)
+        (export "counter" (global $counter))
+        (export "memory" (memory 0))
+        (func $init
+            (global.set $counter (i32.mul (global.get $counter) (i32.const 2))))
+        (start $init)
+        (func $compute (export "compute") (param i32) (result i32)
+            (local $result i32)
+            (local.set $result
+                (call $add (call $mul (local.get 0) (i32.const 2)) (i32.const 1)))
+            (i32.store (i32.const 0) (local.get $result))
+            (call $log (local.get $result))
+            (call_indirect (type $binop)
+                (local.get $result) (global.get $counter) (i32.const 0))))
+"#;
+
+/// A `log` import recording what it is called with.
+fn log(store: &Store) -> (Function, Arc<Mutex<Vec<i32>>>) {
+    let logged = Arc::new(Mutex::new(Vec::new()));
+    let ty = FunctionType::new(vec![Type::I32], vec![]);
+    let log = {
+        let logged = logged.clone();
+        Function::new(store, ty, move |args| {
+            logged.lock().unwrap().push(args[0].unwrap_i32());
+            Ok(vec![])
+        })
+    };
+    (log, logged)
+}
+
+/// The result of `compute(5)`, the value of `counter`, the first word of the memory and the
+/// logged values of an instance of the contract.
+fn run(
+    store: &Store,
+    instance: &Instance,
+    logged: &Mutex<Vec<i32>>,
+) -> Result<(i32, i32, u32, Vec<i32>)> {
+    let compute = instance.get_native_function::<i32, i32>("compute")?;
+    let result = compute.call(5)?;
+    let counter = match Extern::from_vm_export(store, instance.lookup("counter").unwrap()) {
+        Extern::Global(global) => global.get(),
+        _ => panic!("the contract should export its counter"),
+    };
+    let memory = instance.lookup_memory("memory").unwrap();
+    let word = memory.view::<u32>()[0].get();
+    Ok((
+        result,
+        counter.unwrap_i32(),
+        word,
+        logged.lock().unwrap().clone(),
+    ))
+}
+
+/// The names of the functions of a module and the functions called by each of them, by index.
+fn introspect(bytes: &[u8]) -> Result<(Vec<String>, BTreeMap<u32, String>, Vec<Vec<u32>>)> {
+    let mut imports = Vec::new();
+    let mut names = BTreeMap::new();
+    let mut calls = Vec::new();
+    for payload in Parser::new(0).parse_all(bytes) {
+        match payload? {
+            Payload::ImportSection(section) => {
+                for import in section {
+                    let import = import?;
+                    imports.push(format!("{}.{}", import.module, import.field.unwrap()));
+                }
+            }
+            Payload::CodeSectionEntry(body) => {
+                let mut called = Vec::new();
+                for operator in body.get_operators_reader()? {
+                    if let Operator::Call { function_index } = operator? {
+                        called.push(function_index);
+                    }
+                }
+                calls.push(called);
+            }
+            Payload::CustomSection {
+                name: "name",
+                data,
+                data_offset,
+                ..
+            } => {
+                for name in NameSectionReader::new(data, data_offset)? {
+                    if let Name::Function(functions) = name? {
+                        let mut map = functions.get_map()?;
+                        for _ in 0..map.get_count() {
+                            let naming = map.read()?;
+                            names.insert(naming.index, naming.name.to_string());
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((imports, names, calls))
+}
+
+#[compiler_test(compose)]
+fn composition_matches_dynamic_linking(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let (math, contract) = (wat2wasm(MATH.as_bytes())?, wat2wasm(CONTRACT.as_bytes())?);
+
+    let (log_import, logged) = log(&store);
+    let math_instance = Instance::new(&Module::new(&store, &math)?, &imports! {})?;
+    let math_exports = ["add", "mul", "memory", "counter"]
+        .iter()
+        .map(|name| (name.to_string(), math_instance.lookup(name).unwrap()))
+        .collect::<Vec<_>>();
+    let mut math_namespace = Exports::new();
+    for (name, export) in math_exports {
+        math_namespace.insert(name, Extern::from_vm_export(&store, export));
+    }
+    let imports = imports! {
+        "env" => { "log" => log_import },
+        "math" => math_namespace,
+    };
+    let linked = Instance::new(&Module::new(&store, &contract)?, &imports)?;
+    let expected = run(&store, &linked, &logged)?;
+    assert_eq!(expected, (31, 20, 11, vec![11]));
+
+    let composed = compose_modules(
+        &store,
+        &[&contract, &math],
+        ComposeOptions::new(["contract", "math"]),
+    )?;
+    let (log_import, logged) = log(&store);
+    let module = Module::new(&store, &composed)?;
+    let instance = Instance::new(&module, &imports! { "env" => { "log" => log_import } })?;
+    assert_eq!(run(&store, &instance, &logged)?, expected);
+
+    // Only the import of the host is left, the functions of the contract come next and those
+    // of the library last, followed by the start function calling both start functions.
+    let (imports, names, calls) = introspect(&composed)?;
+    assert_eq!(imports, ["env.log"]);
+    let names = names.values().map(String::as_str).collect::<Vec<_>>();
+    assert_eq!(
+        names,
+        [
+            "contract::log",
+            "contract::init",
+            "contract::compute",
+            "math::add",
+            "math::mul",
+            "math::init",
+        ]
+    );
+    assert_eq!(
+        calls,
+        [vec![], vec![4, 3, 0], vec![], vec![], vec![], vec![5, 1]]
+    );
+    Ok(())
+}
+
+#[compiler_test(compose)]
+fn identical_functions_are_deduplicated(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let library = wat2wasm(
+        br#"
+        (module
+            (func $double (export "double") (param i32) (result i32)
+                (i32.mul (local.get 0) (i32.const 2))))
+        "#,
+    )?;
+    let main = wat2wasm(
+        br#"
+        (module
+            (import "library" "double" (func $imported (param i32) (result i32)))
+            (func $double (param i32) (result i32)
+                (i32.mul (local.get 0) (i32.const 2)))
+            (func $quadruple (export "quadruple") (param i32) (result i32)
+                (call $imported (call $double (local.get 0)))))
+        "#,
+    )?;
+
+    for dedup in [false, true] {
+        let options = ComposeOptions::new(["main", "library"]).with_function_dedup(dedup);
+        let composed = compose_modules(&store, &[&main, &library], options)?;
+        let (imports, names, calls) = introspect(&composed)?;
+        assert!(imports.is_empty());
+        if dedup {
+            assert_eq!(
+                names.values().collect::<Vec<_>>(),
+                ["main::double", "main::quadruple"]
+            );
+            assert_eq!(calls, [vec![], vec![0, 0]]);
+        } else {
+            assert_eq!(calls, [vec![], vec![0, 2], vec![]]);
+        }
+        let instance = Instance::new(&Module::new(&store, &composed)?, &imports! {})?;
+        let quadruple = instance.get_native_function::<i32, i32>("quadruple")?;
+        assert_eq!(quadruple.call(3)?, 12);
+    }
+    Ok(())
+}
+
+#[compiler_test(compose)]
+fn conflicting_memories_are_rejected(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let defines = wat2wasm(b"(module (memory 1))")?;
+    let imports = wat2wasm(br#"(module (import "env" "memory" (memory 1)))"#)?;
+    let options = || ComposeOptions::new(["a", "b"]);
+
+    let error = compose_modules(&store, &[&defines, &defines], options()).unwrap_err();
+    assert!(matches!(
+        &error,
+        ComposeError::ConflictingMemories { owners } if owners[..] == ["`a`", "`b`"]
+    ));
+    assert_eq!(error.code(), ErrorCode::ComposeConflictingMemories);
+    let error = compose_modules(&store, &[&imports, &defines], options()).unwrap_err();
+    assert!(matches!(
+        &error,
+        ComposeError::ConflictingMemories { owners } if owners[..] == ["`env.memory`", "`b`"]
+    ));
+
+    // A memory imported by the other module, or by both from the host, is shared.
+    let shares = wat2wasm(br#"(module (import "b" "memory" (memory 1)))"#)?;
+    let exports = wat2wasm(br#"(module (memory (export "memory") 1))"#)?;
+    compose_modules(&store, &[&shares, &exports], options())?;
+    compose_modules(&store, &[&imports, &imports], options())?;
+    Ok(())
+}
+
+#[compiler_test(compose)]
+fn unresolvable_imports_are_rejected(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let library = wat2wasm(br#"(module (func (export "f") (param i32)))"#)?;
+    let compose = |wat: &str| {
+        let main = wat2wasm(wat.as_bytes()).unwrap();
+        compose_modules(
+            &store,
+            &[&main, &library],
+            ComposeOptions::new(["main", "library"]),
+        )
+    };
+
+    let error = compose(r#"(module (import "library" "g" (func)))"#).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ComposeMissingExport);
+    let error = compose(r#"(module (import "library" "f" (func (param i64))))"#).unwrap_err();
+    assert!(matches!(
+        &error,
+        ComposeError::IncompatibleImport { field, .. } if field == "f"
+    ));
+    let error = compose(r#"(module (import "library" "f" (global i32)))"#).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ComposeMissingExport);
+    let error =
+        compose(r#"(module (import "main" "f" (func)) (export "f" (func 0)))"#).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ComposeCyclicImport);
+
+    let error = compose_modules(&store, &[&library], ComposeOptions::new(["a", "b"])).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ComposeOptions);
+    let error = compose_modules(&store, &[b"\0asm"], ComposeOptions::new(["a"])).unwrap_err();
+    assert!(matches!(error, ComposeError::InvalidModule { module, .. } if module == "a"));
+    Ok(())
+}
@@ -17,6 +17,8 @@ pub struct Config {
     pub engine: Engine,
     pub features: Option<Features>,
     pub canonicalize_nans: bool,
+    pub frame_pointer: bool,
+    pub bounds_check_elimination: bool,
 }
 
 impl Config {
@@ -26,6 +28,11 @@ impl Config {
             engine,
             features: None,
             canonicalize_nans: false,
+            // Run the whole suite without a frame pointer with `make test-no-frame-pointer`.
+            frame_pointer: std::env::var_os("WASMER_TEST_NO_FRAME_POINTER").is_none(),
+            // And with redundant bounds checks elided with `make test-bounds-check-elimination`.
+            bounds_check_elimination: std::env::var_os("WASMER_TEST_BOUNDS_CHECK_ELIMINATION")
+                .is_some(),
         }
     }
 
@@ -37,6 +44,14 @@ impl Config {
         self.canonicalize_nans = canonicalize_nans;
     }
 
+    pub fn set_frame_pointer(&mut self, frame_pointer: bool) {
+        self.frame_pointer = frame_pointer;
+    }
+
+    pub fn set_bounds_check_elimination(&mut self, enable: bool) {
+        self.bounds_check_elimination = enable;
+    }
+
     pub fn store(&self) -> Store {
         let compiler_config = self.compiler_config(self.canonicalize_nans);
         let engine = self.engine(compiler_config);
@@ -86,6 +101,8 @@ impl Config {
             Compiler::Singlepass => {
                 let mut compiler = wasmer_compiler_singlepass::Singlepass::new();
                 compiler.canonicalize_nans(canonicalize_nans);
+                compiler.frame_pointer(self.frame_pointer);
+                compiler.redundant_bounds_check_elimination(self.bounds_check_elimination);
                 compiler.enable_verifier();
                 Box::new(compiler)
             }
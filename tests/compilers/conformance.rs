@@ -0,0 +1,174 @@
+//! Randomized checking of the numeric operators against the reference evaluator.
+
+use anyhow::Result;
+use std::env;
+use std::time::{SystemTime, UNIX_EPOCH};
+use wasmer::conformance::*;
+use wasmer::*;
+
+const SMOKE_SEED: u64 = 0x5eed;
+
+/// The engine of `config`, and the same engine canonicalizing NaNs.
+fn engines(config: &crate::Config) -> Vec<EngineConfig> {
+    let name = format!("{:?}", config.compiler).to_lowercase();
+    let mut canonical = config.clone();
+    canonical.set_nan_canonicalization(true);
+    vec![
+        EngineConfig::new(name.clone(), config.store()),
+        EngineConfig::new(format!("{}-canonical-nans", name), canonical.store())
+            .canonical_nans(true),
+    ]
+}
+
+#[compiler_test(conformance)]
+fn smoke(config: crate::Config) -> Result<()> {
+    let report = ConformanceRunner::new().run(SMOKE_SEED, 500, &engines(&config));
+    assert_eq!(report.cases, 500);
+    assert!(report.is_conformant(), "{}", report);
+    Ok(())
+}
+
+/// A long run, from `WASMER_CONFORMANCE_SEED` or a fresh seed, which a failure reports.
+#[compiler_test(conformance)]
+#[ignore]
+fn nightly(config: crate::Config) -> Result<()> {
+    let seed = match env::var("WASMER_CONFORMANCE_SEED") {
+        Ok(seed) => seed.parse()?,
+        Err(_) => SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64,
+    };
+    let iterations = match env::var("WASMER_CONFORMANCE_ITERATIONS") {
+        Ok(iterations) => iterations.parse()?,
+        Err(_) => 100_000,
+    };
+    let report = ConformanceRunner::new().run(seed, iterations, &engines(&config));
+    assert!(
+        report.is_conformant(),
+        "seed={} iterations={}\n{}",
+        seed,
+        iterations,
+        report
+    );
+    Ok(())
+}
+
+/// Cases that compilers commonly get wrong, with their outcome.
+fn regressions() -> Vec<(NumericOp, Vec<Number>, Outcome)> {
+    use Number::*;
+    vec![
+        // `idiv` faults on x86 when the quotient overflows, although the remainder does not.
+        (
+            NumericOp::I32RemS,
+            vec![I32(i32::MIN), I32(-1)],
+            Outcome::Value(I32(0)),
+        ),
+        (
+            NumericOp::I64RemS,
+            vec![I64(i64::MIN), I64(-1)],
+            Outcome::Value(I64(0)),
+        ),
+        (
+            NumericOp::I32DivS,
+            vec![I32(i32::MIN), I32(-1)],
+            Outcome::Trap(TrapCode::IntegerOverflow),
+        ),
+        // Shift counts are taken modulo the width, immediates included.
+        (
+            NumericOp::I32Shl,
+            vec![I32(1), I32(33)],
+            Outcome::Value(I32(2)),
+        ),
+        (
+            NumericOp::I64ShrU,
+            vec![I64(-1), I64(-1)],
+            Outcome::Value(I64(1)),
+        ),
+        // Rounding to even keeps the sign of zero.
+        (
+            NumericOp::F32Nearest,
+            vec![F32((-0.5f32).to_bits())],
+            Outcome::Value(F32((-0.0f32).to_bits())),
+        ),
+        (
+            NumericOp::F64Min,
+            vec![F64(0.0f64.to_bits()), F64((-0.0f64).to_bits())],
+            Outcome::Value(F64((-0.0f64).to_bits())),
+        ),
+        // Subnormals are not flushed to zero.
+        (
+            NumericOp::F32Mul,
+            vec![F32(1), F32(2.0f32.to_bits())],
+            Outcome::Value(F32(2)),
+        ),
+        // The largest float below 2^64 converts, 2^64 saturates.
+        (
+            NumericOp::I64TruncSatF64U,
+            vec![F64(2f64.powi(64).to_bits())],
+            Outcome::Value(I64(-1)),
+        ),
+        (
+            NumericOp::I64TruncF64U,
+            vec![F64(2f64.powi(64).to_bits() - 1)],
+            Outcome::Value(I64(-2048)),
+        ),
+    ]
+}
+
+#[compiler_test(conformance)]
+fn regressions_conform(config: crate::Config) -> Result<()> {
+    let runner = ConformanceRunner::new();
+    let engines = engines(&config);
+    for (op, operands, outcome) in regressions() {
+        for placement in [
+            Placement::Params,
+            Placement::Constants,
+            Placement::LastConstant,
+        ] {
+            let case = Case::new(op, operands.clone(), placement);
+            assert_eq!(case.expected(), outcome, "{}", case.wat());
+            let divergences = runner.check(&case, &engines);
+            assert!(divergences.is_empty(), "{}", divergences[0]);
+        }
+    }
+    Ok(())
+}
+
+#[compiler_test(conformance)]
+fn divergences_are_reproducible(config: crate::Config) -> Result<()> {
+    // An engine keeping the payloads of NaNs diverges when it is expected to canonicalize
+    // them.
+    let mislabeled = [EngineConfig::new("mislabeled", config.store()).canonical_nans(true)];
+    let signaling = Number::F32(0x7f80_0001);
+    let one = Number::F32(1.0f32.to_bits());
+    let case = Case::new(
+        NumericOp::F32Add,
+        vec![signaling, one],
+        Placement::LastConstant,
+    );
+    let runner = ConformanceRunner::new();
+    let divergence = &runner.check(&case, &mislabeled)[0];
+    assert_eq!(divergence.actual, Outcome::Value(Number::F32(0x7fc0_0001)));
+    assert_eq!(
+        divergence.to_string(),
+        "fixed case engine=mislabeled op=f32.add placement=last-constant \
+         operands=[(f32.const nan:0x1), (f32.const 0x1p+0)] \
+         expected=(f32.const nan:0x400000) actual=(f32.const nan:0x400001) \
+         wat=(module (func (export \"run\") (param f32) (result f32) \
+         (f32.add (local.get 0) (f32.const 0x1p+0))))"
+    );
+
+    let runner = runner
+        .with_operators(&[NumericOp::F32Add, NumericOp::F64Sqrt])
+        .stop_after(3);
+    let report = runner.run(SMOKE_SEED, 1000, &mislabeled);
+    assert_eq!(report.divergences.len(), 3);
+    assert!(report.cases < 1000);
+    for divergence in &report.divergences {
+        let (seed, index) = divergence.origin.unwrap();
+        assert_eq!(seed, SMOKE_SEED);
+        assert_eq!(runner.case(seed, index), divergence.case);
+        assert!(divergence
+            .to_string()
+            .starts_with(&format!("seed={} case={} engine=mislabeled", seed, index)));
+    }
+    Ok(())
+}
@@ -0,0 +1,160 @@
+//! Local memories mapping the image of their module copy-on-write.
+
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (memory (export "memory") 1 4)
+        (data (i32.const 0) "shared")
+        (data (i32.const 2) "AR")
+        (func (export "load") (param i32) (result i32)
+            local.get 0
+            i32.load8_u)
+        (func (export "store") (param i32)
+            local.get 0
+            i32.const 42
+            i32.store8)
+        (func (export "grow") (result i32)
+            i32.const 1
+            memory.grow))
+"#;
+
+fn store(config: &crate::Config, copy_on_write: bool) -> Store {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables {
+        copy_on_write_memories: copy_on_write,
+        ..BaseTunables::for_target(engine.target())
+    };
+    Store::new_with_tunables(&*engine, tunables)
+}
+
+/// Return the inode of the file mapped at `addr`, if any.
+fn mapped_inode(addr: *const u8) -> Option<u64> {
+    let maps = std::fs::read_to_string("/proc/self/maps").ok()?;
+    maps.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let (start, end) = fields.next()?.split_once('-')?;
+        let start = usize::from_str_radix(start, 16).ok()?;
+        let end = usize::from_str_radix(end, 16).ok()?;
+        if !(start..end).contains(&(addr as usize)) {
+            return None;
+        }
+        fields.nth(3)?.parse().ok()
+    })
+}
+
+fn load(instance: &Instance, offset: i32) -> Result<i32> {
+    Ok(instance
+        .get_native_function::<i32, i32>("load")?
+        .call(offset)?)
+}
+
+#[compiler_test(copy_on_write)]
+fn instances_see_the_data_segments(config: crate::Config) -> Result<()> {
+    for copy_on_write in [false, true] {
+        let module = Module::new(&store(&config, copy_on_write), WAT)?;
+        let instance = Instance::new(&module, &imports! {})?;
+        for (offset, byte) in b"shARed".iter().enumerate() {
+            assert_eq!(load(&instance, offset as i32)?, *byte as i32);
+        }
+        assert_eq!(load(&instance, 6)?, 0);
+    }
+    Ok(())
+}
+
+#[compiler_test(copy_on_write)]
+fn writes_stay_private(config: crate::Config) -> Result<()> {
+    let module = Module::new(&store(&config, true), WAT)?;
+    let first = Instance::new(&module, &imports! {})?;
+    let second = Instance::new(&module, &imports! {})?;
+    first.get_native_function::<i32, ()>("store")?.call(0)?;
+    assert_eq!(load(&first, 0)?, 42);
+    assert_eq!(load(&second, 0)?, b's' as i32);
+    assert_eq!(
+        load(&Instance::new(&module, &imports! {})?, 0)?,
+        b's' as i32
+    );
+
+    // Best effort: both memories map the same file, unlike copied ones.
+    let first = first.lookup_memory("memory").unwrap().data_ptr();
+    let second = second.lookup_memory("memory").unwrap().data_ptr();
+    if let Some(inode) = mapped_inode(second) {
+        assert_eq!(mapped_inode(first), Some(inode));
+    }
+    let copied = Module::new(&store(&config, false), WAT)?;
+    let copied = Instance::new(&copied, &imports! {})?;
+    let copied = copied.lookup_memory("memory").unwrap().data_ptr();
+    if let (Some(inode), Some(other)) = (mapped_inode(second), mapped_inode(copied)) {
+        assert_ne!(inode, other);
+    }
+    Ok(())
+}
+
+#[compiler_test(copy_on_write)]
+fn memories_grow_and_discard(config: crate::Config) -> Result<()> {
+    let module = Module::new(&store(&config, true), WAT)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let grow = instance.get_native_function::<(), i32>("grow")?;
+    let store = instance.get_native_function::<i32, ()>("store")?;
+    assert_eq!(grow.call()?, 1);
+    store.call(WASM_PAGE_SIZE as i32)?;
+    assert_eq!(load(&instance, WASM_PAGE_SIZE as i32)?, 42);
+    assert_eq!(load(&instance, 1)?, b'h' as i32);
+
+    // Discarded pages read zeros rather than the contents of the image.
+    let memory = instance.lookup_memory("memory").unwrap();
+    memory.discard_range(0, 2 * WASM_PAGE_SIZE as u64)?;
+    assert_eq!(load(&instance, 1)?, 0);
+    assert_eq!(load(&instance, WASM_PAGE_SIZE as i32)?, 0);
+    store.call(1)?;
+    assert_eq!(load(&instance, 1)?, 42);
+
+    let other = Instance::new(&module, &imports! {})?;
+    assert_eq!(load(&other, 1)?, b'h' as i32);
+    Ok(())
+}
+
+#[compiler_test(copy_on_write)]
+fn unsupported_modules_copy_the_data_segments(config: crate::Config) -> Result<()> {
+    let store = store(&config, true);
+    let imported = Module::new(
+        &store,
+        r#"
+        (module
+            (import "env" "memory" (memory 1))
+            (data (i32.const 0) "imported")
+            (func (export "load") (param i32) (result i32)
+                local.get 0
+                i32.load8_u))
+        "#,
+    )?;
+    let memory = Memory::new(&store, MemoryType::new(1, None, false))?;
+    let imports = imports! { "env" => { "memory" => memory } };
+    let instance = Instance::new(&imported, &imports)?;
+    assert_eq!(load(&instance, 0)?, b'i' as i32);
+
+    let placed = Module::new(
+        &store,
+        r#"
+        (module
+            (import "env" "offset" (global i32))
+            (memory 1)
+            (data (global.get 0) "placed")
+            (func (export "load") (param i32) (result i32)
+                local.get 0
+                i32.load8_u))
+        "#,
+    )?;
+    let imports = imports! { "env" => { "offset" => Global::new(&store, Val::I32(8)) } };
+    let instance = Instance::new(&placed, &imports)?;
+    assert_eq!(load(&instance, 8)?, b'p' as i32);
+
+    // Out of bounds segments still fail the instantiation.
+    let out_of_bounds = Module::new(
+        &store,
+        r#"(module (memory 1) (data (i32.const 65535) "out of bounds"))"#,
+    )?;
+    assert!(Instance::new(&out_of_bounds, &imports! {}).is_err());
+    Ok(())
+}
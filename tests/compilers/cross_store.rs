@@ -0,0 +1,53 @@
+//! Instantiating a module into stores other than the one it was compiled in.
+
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (global $counter (mut i32) (i32.const 0))
+        (func (export "bump") (result i32)
+            (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+            (global.get $counter)))
+"#;
+
+#[compiler_test(cross_store)]
+fn modules_are_instantiated_in_stores_of_the_same_engine(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let module = Module::new(&Store::new(&engine), WAT)?;
+
+    let requests = (0..2).map(|_| Store::new(&engine)).collect::<Vec<_>>();
+    let instances = requests
+        .iter()
+        .map(|store| module.instantiate_in(store, &imports! {}, InstanceConfig::default()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let bump = |instance: &Instance| -> Result<i32> {
+        Ok(instance.get_native_function::<(), i32>("bump")?.call()?)
+    };
+    assert_eq!(bump(&instances[0])?, 1);
+    assert_eq!(bump(&instances[0])?, 2);
+    // Each instance has its own state.
+    assert_eq!(bump(&instances[1])?, 1);
+
+    // The instances outlive the store the module was compiled in.
+    drop(module);
+    assert_eq!(bump(&instances[1])?, 2);
+    Ok(())
+}
+
+#[compiler_test(cross_store)]
+fn modules_are_not_instantiated_in_stores_of_other_engines(config: crate::Config) -> Result<()> {
+    let module = Module::new(&config.store(), WAT)?;
+    let other = Store::new(&Universal::new(config.compiler_config(false)).engine());
+    let error = match module.instantiate_in(&other, &imports! {}, InstanceConfig::default()) {
+        Err(error) => error,
+        Ok(_) => panic!("the module was instantiated"),
+    };
+    assert!(matches!(error, InstantiationError::DifferentEngine));
+    assert_eq!(error.code(), ErrorCode::InstantiationDifferentEngine);
+
+    // A module compiled in the other store instantiates there.
+    let module = Module::new(&other, WAT)?;
+    module.instantiate_in(&other, &imports! {}, InstanceConfig::default())?;
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! Custom sections kept with compiled modules.
+
+use anyhow::Result;
+use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{Universal, UniversalExecutableRef};
+
+/// Append a custom section named `name` holding `data` to `wasm`.
+fn push_custom_section(wasm: &mut Vec<u8>, name: &str, data: &[u8]) {
+    fn leb128(out: &mut Vec<u8>, mut value: usize) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value == 0 {
+                out.push(byte);
+                return;
+            }
+            out.push(byte | 0x80);
+        }
+    }
+    let mut contents = vec![];
+    leb128(&mut contents, name.len());
+    contents.extend(name.as_bytes());
+    contents.extend(data);
+    wasm.push(0);
+    leb128(wasm, contents.len());
+    wasm.extend(contents);
+}
+
+/// A module with two `abi` sections, the second larger than a mebibyte, and a `version`
+/// section between them.
+fn wasm() -> Result<(Vec<u8>, Vec<u8>)> {
+    let mut wasm = wat2wasm(br#"(module (func (export "run")))"#)?.into_owned();
+    let large = (0..(1 << 20) + 1).map(|i| i as u8).collect::<Vec<_>>();
+    push_custom_section(&mut wasm, "abi", b"{\"run\": []}");
+    push_custom_section(&mut wasm, "version", b"1.2.0");
+    push_custom_section(&mut wasm, "abi", &large);
+    Ok((wasm, large))
+}
+
+fn check_sections(module: &Module, large: &[u8]) {
+    let abi = module.custom_sections("abi").collect::<Vec<_>>();
+    assert_eq!(abi.len(), 2);
+    assert_eq!(&*abi[0], b"{\"run\": []}");
+    assert_eq!(&*abi[1], large);
+    let version = module.custom_sections("version").collect::<Vec<_>>();
+    assert_eq!(version, [Box::from(&b"1.2.0"[..])]);
+    assert_eq!(module.custom_sections("name").count(), 0);
+}
+
+#[compiler_test(custom_sections)]
+fn sections_sharing_a_name_are_all_kept(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let (wasm, large) = wasm()?;
+    let module = Module::new(&store, &wasm)?;
+    check_sections(&module, &large);
+    Ok(())
+}
+
+#[compiler_test(custom_sections)]
+fn sections_are_kept_by_serialized_modules(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let store = Store::new(&engine);
+    let (wasm, large) = wasm()?;
+    let executable = engine.compile_universal(&wasm, store.tunables())?;
+    let serialized = executable.serialize().unwrap();
+    let deserialized = unsafe { UniversalExecutableRef::deserialize(&serialized)? };
+    check_sections(&Module::from_executable(&store, &deserialized)?, &large);
+
+    let serialized = Module::new(&store, &wasm)?.serialize().unwrap();
+    let module = unsafe { Module::deserialize(&config.headless_store(), &serialized)? };
+    check_sections(&module, &large);
+    Ok(())
+}
@@ -0,0 +1,123 @@
+//! Traps with codes of the embedder, raised by calls the compiler turns into traps.
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use wasmer::*;
+use wasmer_compiler_singlepass::Singlepass;
+use wasmer_engine_universal::{Universal, UniversalEngine};
+
+/// Stands for a module instrumented to check its call depth, calling `depth_exceeded` when the
+/// check fails.
+const WAT: &str = r#"
+    (module
+        (import "env" "depth_exceeded" (func $depth_exceeded))
+        (func (export "check") (param i32) (result i32)
+            (if (i32.gt_u (local.get 0) (i32.const 10))
+                (then (call $depth_exceeded)))
+            (local.get 0))
+        (export "depth_exceeded" (func $depth_exceeded)))
+"#;
+
+fn engine(config: &crate::Config, compiler: Singlepass) -> UniversalEngine {
+    let mut universal = Universal::new(compiler);
+    if let Some(features) = &config.features {
+        universal = universal.features(features.clone());
+    }
+    universal.engine()
+}
+
+fn assert_custom_trap(error: &RuntimeError) {
+    assert_eq!(error.trap_code(), Some(TrapCode::Custom(7)));
+    assert_eq!(error.code(), ErrorCode::TrapCustom);
+    assert_eq!(error.message(), "depth_exceeded (custom trap 7)");
+    assert!(error
+        .to_string()
+        .starts_with("RuntimeError: depth_exceeded (custom trap 7) [W0416]"));
+}
+
+#[compiler_test(custom_traps)]
+fn custom_traps_are_raised_with_their_code(config: crate::Config) -> Result<()> {
+    let mut compiler = Singlepass::default();
+    compiler.custom_trap("depth_exceeded", 7)?;
+    let store = Store::new(&engine(&config, compiler));
+    let module = Module::new(&store, WAT)?;
+    // The import the calls are compiled away from need not be given.
+    let instance = Instance::new(&module, &imports! {})?;
+    let check = instance.get_native_function::<i32, i32>("check")?;
+    assert_eq!(check.call(3)?, 3);
+    assert_custom_trap(&check.call(11).unwrap_err());
+
+    // Nor does it run when it is, so that the code of the module behaves the same.
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    let exceeded = Function::new_native(&store, || {
+        CALLS.fetch_add(1, SeqCst);
+    });
+    let imports = imports! { "env" => { "depth_exceeded" => exceeded } };
+    let check = Instance::new(&module, &imports)?.get_native_function::<i32, i32>("check")?;
+    assert_custom_trap(&check.call(11).unwrap_err());
+    assert_eq!(CALLS.load(SeqCst), 0);
+    Ok(())
+}
+
+#[compiler_test(custom_traps)]
+fn missing_trap_imports_raise_the_trap_when_exported(config: crate::Config) -> Result<()> {
+    let mut compiler = Singlepass::default();
+    compiler.custom_trap("depth_exceeded", 7)?;
+    let store = Store::new(&engine(&config, compiler));
+    let module = Module::new(&store, WAT)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let exceeded = instance.get_native_function::<(), ()>("depth_exceeded")?;
+    assert_custom_trap(&exceeded.call().unwrap_err());
+    Ok(())
+}
+
+#[compiler_test(custom_traps)]
+fn custom_traps_change_the_engine_id(config: crate::Config) -> Result<()> {
+    let executable = |compiler: Singlepass| -> Result<_> {
+        let engine = engine(&config, compiler);
+        let store = Store::new(&engine);
+        Ok(engine.compile_universal(&wat2wasm(WAT.as_bytes())?, store.tunables())?)
+    };
+    let mut compiler = Singlepass::default();
+    compiler.custom_trap("depth_exceeded", 7)?;
+    let with_trap = executable(compiler)?.provenance()?;
+    assert_eq!(
+        with_trap.compiler.middlewares,
+        ["intrinsic:gas", "intrinsic:depth_exceeded:trap7"]
+    );
+    let without_trap = executable(Singlepass::default())?.provenance()?;
+    assert_ne!(with_trap.engine_id, without_trap.engine_id);
+    Ok(())
+}
+
+#[compiler_test(custom_traps)]
+fn imports_are_called_without_custom_traps(config: crate::Config) -> Result<()> {
+    let store = Store::new(&engine(&config, Singlepass::default()));
+    let module = Module::new(&store, WAT)?;
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+    let exceeded = Function::new_native(&store, || {
+        CALLS.fetch_add(1, SeqCst);
+    });
+    let imports = imports! { "env" => { "depth_exceeded" => exceeded } };
+    let check = Instance::new(&module, &imports)?.get_native_function::<i32, i32>("check")?;
+    assert_eq!(check.call(11)?, 11);
+    assert_eq!(CALLS.load(SeqCst), 1);
+    Ok(())
+}
+
+#[compiler_test(custom_traps)]
+fn custom_trap_codes_collide(_config: crate::Config) -> Result<()> {
+    Singlepass::default().custom_trap("region_violation", 8)?;
+    // Registering a code with the name it already has is fine.
+    Singlepass::default().custom_trap("region_violation", 8)?;
+
+    let error = Singlepass::default()
+        .custom_trap("quota_exceeded", 8)
+        .unwrap_err();
+    assert_eq!(error.code, 8);
+    assert_eq!(error.registered, "region_violation");
+    assert_eq!(error.requested, "quota_exceeded");
+    assert_eq!(error.code(), ErrorCode::TrapCodeCollision);
+    assert_eq!(TrapCode::Custom(8).message(), "region_violation");
+    Ok(())
+}
@@ -0,0 +1,150 @@
+//! Applying the data segments of modules to their memories when instantiating them.
+
+use anyhow::Result;
+use wasmer::*;
+
+fn store(config: &crate::Config, copy_on_write: bool) -> Store {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables {
+        copy_on_write_memories: copy_on_write,
+        ..BaseTunables::for_target(engine.target())
+    };
+    Store::new_with_tunables(&*engine, tunables)
+}
+
+/// `len` letters starting from the `seed`th one, so that segments tell apart.
+fn letters(seed: usize, len: usize) -> String {
+    (0..len)
+        .map(|i| (b'a' + ((seed + i * 7) % 26) as u8) as char)
+        .collect()
+}
+
+/// A segment, as its offset, or `None` for the one at the offset in the imported global,
+/// and its contents.
+type Segment = (Option<usize>, String);
+
+/// Segments next to each other, overlapping, at unaligned offsets, and large enough to be
+/// copied in bulk, alone or once merged with their neighbours.
+fn segments() -> Vec<Segment> {
+    let mut segments = vec![
+        (Some(0), "abcd".to_string()),
+        (Some(4), "efgh".to_string()),
+        (Some(2), "XY".to_string()),
+        (Some(17), "unaligned".to_string()),
+        (Some(26), "next".to_string()),
+        (None, "placed".to_string()),
+        (Some(28), "overlapping".to_string()),
+        (Some(65536 + 5), letters(1, 1536 * 1024)),
+    ];
+    // 2 MiB at an odd offset, in 64 KiB pieces.
+    let start = 1700 * 1024 + 3;
+    for piece in 0..32 {
+        segments.push((Some(start + piece * 65536), letters(piece, 65536)));
+    }
+    segments
+}
+
+/// A module with one memory of `pages` and `segments`.
+fn module(store: &Store, pages: u32, segments: &[Segment]) -> Result<Module> {
+    let mut wat = format!(
+        r#"(module (import "env" "base" (global i32)) (memory (export "memory") {})"#,
+        pages
+    );
+    for (offset, data) in segments {
+        match offset {
+            Some(offset) => wat += &format!(r#" (data (i32.const {}) "{}")"#, offset, data),
+            None => wat += &format!(r#" (data (global.get 0) "{}")"#, data),
+        }
+    }
+    wat += r#"
+        (func (export "scribble")
+            (memory.fill
+                (i32.const 0)
+                (i32.const 0xff)
+                (i32.mul (memory.size) (i32.const 65536)))))
+    "#;
+    Ok(Module::new(store, wat)?)
+}
+
+/// The contents of a memory of `pages` once `segments` are applied, with `base` in the
+/// imported global.
+fn expected(pages: u32, base: usize, segments: &[Segment]) -> Vec<u8> {
+    let mut memory = vec![0; pages as usize * 65536];
+    for (offset, data) in segments {
+        let offset = offset.unwrap_or(base);
+        memory[offset..offset + data.len()].copy_from_slice(data.as_bytes());
+    }
+    memory
+}
+
+/// An FNV-1a hash of `bytes`.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
+fn memory_checksum(instance: &Instance) -> u64 {
+    let memory = instance.lookup_memory("memory").unwrap();
+    checksum(unsafe { memory.data_unchecked() })
+}
+
+#[compiler_test(data_segments)]
+fn segments_are_applied_in_order(config: crate::Config) -> Result<()> {
+    let segments = segments();
+    let expected = checksum(&expected(64, 9, &segments));
+    for copy_on_write in [false, true] {
+        let store = store(&config, copy_on_write);
+        let module = module(&store, 64, &segments)?;
+        let imports = imports! { "env" => { "base" => Global::new(&store, Value::I32(9)) } };
+        let instance = Instance::new(&module, &imports)?;
+        assert_eq!(memory_checksum(&instance), expected);
+    }
+    Ok(())
+}
+
+#[compiler_test(data_segments)]
+fn resets_zero_the_memory_again(config: crate::Config) -> Result<()> {
+    let segments = segments();
+    let expected = checksum(&expected(64, 9, &segments));
+    for copy_on_write in [false, true] {
+        let store = store(&config, copy_on_write);
+        let module = module(&store, 64, &segments)?;
+        let imports = imports! { "env" => { "base" => Global::new(&store, Value::I32(9)) } };
+        let instance = Instance::new(&module, &imports)?;
+        instance.get_native_function::<(), ()>("scribble")?.call()?;
+        assert_ne!(memory_checksum(&instance), expected);
+
+        // The memory is reused, and reads as zeros where no segment writes, as in its last
+        // page.
+        instance.reset(false)?;
+        assert_eq!(memory_checksum(&instance), expected);
+        let memory = instance.lookup_memory("memory").unwrap();
+        let bytes = unsafe { memory.data_unchecked() };
+        assert!(bytes[bytes.len() - 65536..].iter().all(|&byte| byte == 0));
+    }
+    Ok(())
+}
+
+#[compiler_test(data_segments)]
+fn segments_before_an_out_of_bounds_one_are_applied(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (import "env" "memory" (memory 1))
+            (data (i32.const 0) "abcd")
+            (data (i32.const 4) "efgh")
+            (data (i32.const 65534) "oops"))
+        "#,
+    )?;
+    // As large as the module asks for, which is not enough for the last segment.
+    let memory = Memory::new(&store, MemoryType::new(1, None, false))?;
+    let imports = imports! { "env" => { "memory" => memory.clone() } };
+    assert!(Instance::new(&module, &imports).is_err());
+    let bytes = unsafe { memory.data_unchecked() };
+    assert_eq!(&bytes[..8], b"abcdefgh");
+    assert_eq!(&bytes[65534..], &[0, 0]);
+    Ok(())
+}
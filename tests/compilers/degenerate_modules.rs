@@ -0,0 +1,122 @@
+//! Modules without code, memories or exports must go through every public
+//! path without panicking.
+
+use anyhow::Result;
+use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::UniversalExecutableRef;
+
+const EMPTY: &str = "(module)";
+const IMPORTS_ONLY: &str = r#"(module
+    (import "env" "f" (func))
+    (import "env" "g" (global i32)))"#;
+const EXPORTS_ONLY_GLOBALS: &str = r#"(module
+    (global (export "a") i32 (i32.const 1))
+    (global (export "b") (mut i64) (i64.const 2)))"#;
+const MEMORY_ONLY: &str = "(module (memory 1))";
+const START_ONLY: &str = "(module (func $start) (start $start))";
+
+fn resolver(store: &Store) -> ImportObject {
+    imports! {
+        "env" => {
+            "f" => Function::new_native(store, || {}),
+            "g" => Global::new(store, Value::I32(42)),
+        }
+    }
+}
+
+fn check(config: &crate::Config, wat: &str, exports: &[&str]) -> Result<()> {
+    let store = config.store();
+    let wasm = wat2wasm(wat.as_bytes())?;
+
+    // Compile, then round-trip the executable through serialization.
+    let executable = store.engine().compile(&wasm, store.tunables())?;
+    let serialized = executable.serialize().unwrap();
+    let headless = config.headless_store();
+    let deserialized = unsafe { UniversalExecutableRef::deserialize(&serialized)? };
+    assert_eq!(deserialized.serialize().unwrap(), serialized);
+    let modules = vec![
+        Module::new(&store, &wasm)?,
+        Module::from_executable(&store, &*executable)?,
+        Module::from_executable(&headless, &deserialized)?,
+    ];
+
+    for module in modules {
+        let names = module.exports().map(|(name, _)| name).collect::<Vec<_>>();
+        assert_eq!(names, exports);
+        assert_eq!(
+            module.function_body_hashes().len(),
+            wat.matches("(func $").count()
+        );
+        let instance = Instance::new(&module, &resolver(module.store()))?;
+        assert!(instance.lookup("memory").is_none());
+        assert!(instance.lookup_memory("memory").is_none());
+        for name in exports {
+            assert!(instance.lookup(name).is_some());
+        }
+    }
+    Ok(())
+}
+
+#[compiler_test(degenerate_modules)]
+fn empty_module(config: crate::Config) -> Result<()> {
+    check(&config, EMPTY, &[])?;
+    let module = Module::new(&config.store(), EMPTY)?;
+    Instance::new(&module, &imports! {})?;
+    Ok(())
+}
+
+#[compiler_test(degenerate_modules)]
+fn imports_only_module(config: crate::Config) -> Result<()> {
+    check(&config, IMPORTS_ONLY, &[])
+}
+
+#[compiler_test(degenerate_modules)]
+fn exports_only_globals_module(config: crate::Config) -> Result<()> {
+    check(&config, EXPORTS_ONLY_GLOBALS, &["a", "b"])
+}
+
+#[compiler_test(degenerate_modules)]
+fn memory_only_module(config: crate::Config) -> Result<()> {
+    check(&config, MEMORY_ONLY, &[])
+}
+
+#[compiler_test(degenerate_modules)]
+fn start_only_module(config: crate::Config) -> Result<()> {
+    check(&config, START_ONLY, &[])
+}
+
+#[compiler_test(degenerate_modules)]
+fn missing_memory_for_env(config: crate::Config) -> Result<()> {
+    #[derive(Clone, Default)]
+    struct Env {
+        memory: LazyInit<Memory>,
+    }
+
+    impl WasmerEnv for Env {
+        fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
+            let memory = instance
+                .lookup_memory("memory")
+                .ok_or_else(|| HostEnvInitError::MissingMemory("memory".into()))?;
+            self.memory.initialize(memory);
+            Ok(())
+        }
+    }
+
+    let store = config.store();
+    let module = Module::new(&store, IMPORTS_ONLY)?;
+    let imports = imports! {
+        "env" => {
+            "f" => Function::new_native_with_env(&store, Env::default(), |_: &Env| {}),
+            "g" => Global::new(&store, Value::I32(42)),
+        }
+    };
+    match Instance::new(&module, &imports) {
+        Err(InstantiationError::HostEnvInitialization(HostEnvInitError::MissingMemory(name))) => {
+            assert_eq!(name, "memory");
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("instantiation should have failed"),
+    }
+    Ok(())
+}
@@ -0,0 +1,215 @@
+//! Diagnostics turned on and off for single instances while they run.
+
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use wasmer::*;
+use wasmer_engine_universal::{Universal, UniversalEngine};
+
+/// Stores 7 at `address + 4`, calls the host, then loads it back through another function.
+const WAT: &str = r#"
+    (module
+        (import "env" "host" (func $host))
+        (memory 1)
+        (func $load (param i32) (result i32)
+            local.get 0
+            i32.load offset=4)
+        (func (export "run") (param i32) (result i32)
+            local.get 0
+            i32.const 7
+            i32.store offset=4
+            call $host
+            local.get 0
+            call $load))
+"#;
+
+fn call(id: InstanceId, function: u32) -> (InstanceId, DiagnosticEvent) {
+    let function = FunctionIndex::from_u32(function);
+    (id, DiagnosticEvent::Call { function })
+}
+
+#[derive(Default)]
+struct Recorder(Mutex<Vec<(InstanceId, DiagnosticEvent)>>);
+
+impl Recorder {
+    fn take(&self) -> Vec<(InstanceId, DiagnosticEvent)> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl DiagnosticsSink for Recorder {
+    fn event(&self, instance: InstanceId, event: DiagnosticEvent) {
+        self.0.lock().unwrap().push((instance, event));
+    }
+}
+
+/// The instance whose level the host function raises to `Verbose`, if any.
+#[derive(Clone, Default)]
+struct Env {
+    target: Arc<Mutex<Option<Instance>>>,
+}
+
+impl WasmerEnv for Env {}
+
+/// A compiler set up as `config` says, with diagnostics compiled in.
+fn diagnosed(config: &crate::Config) -> Singlepass {
+    let mut compiler = Singlepass::new();
+    compiler.diagnostics(true);
+    compiler.frame_pointer(config.frame_pointer);
+    compiler.redundant_bounds_check_elimination(config.bounds_check_elimination);
+    compiler
+}
+
+fn engine(compiler: impl Into<Box<dyn CompilerConfig>>) -> (UniversalEngine, Arc<Recorder>) {
+    let engine = Universal::new(compiler).engine();
+    let recorder = Arc::new(Recorder::default());
+    engine.set_diagnostics_sink(recorder.clone());
+    (engine, recorder)
+}
+
+fn instantiate(store: &Store, env: &Env) -> Result<(Instance, NativeFunc<i32, i32>)> {
+    let module = Module::new(store, WAT)?;
+    let imports = imports! {
+        "env" => {
+            "host" => Function::new_native_with_env(store, env.clone(), |env: &Env| {
+                if let Some(instance) = &*env.target.lock().unwrap() {
+                    instance.set_diagnostics_level(DiagnosticsLevel::Verbose);
+                }
+            }),
+        },
+    };
+    let instance = Instance::new(&module, &imports)?;
+    let run = instance.get_native_function("run")?;
+    Ok((instance, run))
+}
+
+fn verbose_run(id: InstanceId, address: u64) -> Vec<(InstanceId, DiagnosticEvent)> {
+    vec![
+        call(id, 2),
+        (id, DiagnosticEvent::Store { address, size: 4 }),
+        call(id, 1),
+        (id, DiagnosticEvent::Load { address, size: 4 }),
+    ]
+}
+
+#[compiler_test(diagnostics)]
+fn level_applies_to_one_instance(config: crate::Config) -> Result<()> {
+    let (engine, recorder) = engine(diagnosed(&config));
+    let store = Store::new(&engine);
+    let (a, run_a) = instantiate(&store, &Env::default())?;
+    let (b, run_b) = instantiate(&store, &Env::default())?;
+
+    assert_eq!(a.diagnostics_level(), DiagnosticsLevel::Off);
+    assert_eq!(run_a.call(16)?, 7);
+    assert_eq!(run_b.call(16)?, 7);
+    assert_eq!(recorder.take(), []);
+
+    a.set_diagnostics_level(DiagnosticsLevel::Verbose);
+    assert_eq!(run_a.call(16)?, 7);
+    assert_eq!(run_b.call(16)?, 7);
+    assert_eq!(recorder.take(), verbose_run(a.id(), 20));
+
+    a.set_diagnostics_level(DiagnosticsLevel::Calls);
+    b.set_diagnostics_level(DiagnosticsLevel::Calls);
+    run_a.call(32)?;
+    run_b.call(32)?;
+    assert_eq!(
+        recorder.take(),
+        [
+            call(a.id(), 2),
+            call(a.id(), 1),
+            call(b.id(), 2),
+            call(b.id(), 1)
+        ]
+    );
+
+    a.set_diagnostics_level(DiagnosticsLevel::Off);
+    b.set_diagnostics_level(DiagnosticsLevel::Off);
+    run_a.call(16)?;
+    run_b.call(16)?;
+    assert_eq!(recorder.take(), []);
+    Ok(())
+}
+
+#[compiler_test(diagnostics)]
+fn level_changes_during_a_call(config: crate::Config) -> Result<()> {
+    let (engine, recorder) = engine(diagnosed(&config));
+    let store = Store::new(&engine);
+    let env = Env::default();
+    let (instance, run) = instantiate(&store, &env)?;
+    let (other, run_other) = instantiate(&store, &env)?;
+    *env.target.lock().unwrap() = Some(instance.clone());
+
+    // The host raises the level between the store and the load.
+    assert_eq!(run.call(16)?, 7);
+    let id = instance.id();
+    assert_eq!(
+        recorder.take(),
+        [
+            call(id, 1),
+            (
+                id,
+                DiagnosticEvent::Load {
+                    address: 20,
+                    size: 4
+                }
+            ),
+        ]
+    );
+    assert_eq!(instance.diagnostics_level(), DiagnosticsLevel::Verbose);
+    assert_eq!(run.call(16)?, 7);
+    assert_eq!(recorder.take(), verbose_run(id, 20));
+
+    // Calling the host from another instance raises the level of the first one only.
+    instance.set_diagnostics_level(DiagnosticsLevel::Off);
+    run_other.call(16)?;
+    assert_eq!(recorder.take(), []);
+    assert_eq!(other.diagnostics_level(), DiagnosticsLevel::Off);
+    assert_eq!(instance.diagnostics_level(), DiagnosticsLevel::Verbose);
+
+    *env.target.lock().unwrap() = None;
+    Ok(())
+}
+
+#[compiler_test(diagnostics)]
+fn engine_default_level(config: crate::Config) -> Result<()> {
+    let (engine, recorder) = engine(diagnosed(&config));
+    let store = Store::new(&engine);
+    let (before, _) = instantiate(&store, &Env::default())?;
+    engine.set_default_diagnostics_level(DiagnosticsLevel::Verbose);
+    let (after, run) = instantiate(&store, &Env::default())?;
+
+    assert_eq!(before.diagnostics_level(), DiagnosticsLevel::Off);
+    assert_eq!(after.diagnostics_level(), DiagnosticsLevel::Verbose);
+    run.call(0)?;
+    assert_eq!(recorder.take(), verbose_run(after.id(), 4));
+    Ok(())
+}
+
+#[compiler_test(diagnostics)]
+fn out_of_bounds_accesses_are_reported_before_trapping(config: crate::Config) -> Result<()> {
+    let (engine, recorder) = engine(diagnosed(&config));
+    let store = Store::new(&engine);
+    let (instance, run) = instantiate(&store, &Env::default())?;
+    instance.set_diagnostics_level(DiagnosticsLevel::Verbose);
+
+    let error = run.call(-1).unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::HeapAccessOutOfBounds));
+    let address = u64::from(u32::MAX) + 4;
+    assert_eq!(
+        recorder.take(),
+        verbose_run(instance.id(), address)[..2].to_vec()
+    );
+    Ok(())
+}
+
+#[compiler_test(diagnostics)]
+fn compiled_without_diagnostics(config: crate::Config) -> Result<()> {
+    // Diagnostics are not compiled in by default.
+    let (engine, recorder) = engine(config.compiler_config(false));
+    let store = Store::new(&engine);
+    let (instance, run) = instantiate(&store, &Env::default())?;
+    instance.set_diagnostics_level(DiagnosticsLevel::Verbose);
+    assert_eq!(run.call(16)?, 7);
+    assert_eq!(recorder.take(), []);
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! Calls of functions with values whose types are only known at runtime.
+
+use crate::instance_layout::count_allocations;
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &str = r#"
+(module
+    (import "host" "mul" (func $mul (param i64 i64) (result i64)))
+    (func (export "add") (param i64 i64) (result i64)
+        (i64.add (local.get 0) (local.get 1)))
+    (func (export "mul") (param i64 i64) (result i64)
+        (call $mul (local.get 0) (local.get 1)))
+    (func (export "sum20")
+        (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32
+               i32 i32 i32 i32 i32 i32 i32 i32 i32 i32)
+        (result i32)
+        (i32.add (local.get 0) (local.get 19))))
+"#;
+
+fn instance(store: &Store) -> Result<Instance> {
+    let module = Module::new(store, WAT)?;
+    let mul = Function::new_native(store, |a: i64, b: i64| a * b);
+    Ok(Instance::new(
+        &module,
+        &imports! { "host" => { "mul" => mul } },
+    )?)
+}
+
+#[compiler_test(dynamic_calls)]
+fn call_into_does_not_allocate(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let instance = instance(&store)?;
+    let params = [Value::I64(40), Value::I64(2)];
+    let mut results = [Value::I64(0)];
+    for name in &["add", "mul"] {
+        let function = instance.lookup_function(name).unwrap();
+        function.call_into(&params, &mut results)?;
+        let (allocations, written) =
+            count_allocations(|| function.call_into(&params, &mut results));
+        assert_eq!(allocations, 0, "calling {}", name);
+        assert_eq!(written?, 1);
+    }
+    assert_eq!(results, [Value::I64(80)]);
+
+    // Signatures with too many values for the stack reuse the buffer of the thread.
+    let sum20 = instance.lookup_function("sum20").unwrap();
+    let params = (1..=20).map(Value::I32).collect::<Vec<_>>();
+    let mut results = [Value::I32(0)];
+    sum20.call_into(&params, &mut results)?;
+    let (allocations, written) = count_allocations(|| sum20.call_into(&params, &mut results));
+    assert_eq!(allocations, 0);
+    assert_eq!(written?, 1);
+    assert_eq!(results, [Value::I32(21)]);
+    assert_eq!(sum20.call(&params)?.to_vec(), vec![Value::I32(21)]);
+    Ok(())
+}
+
+#[compiler_test(dynamic_calls)]
+fn call_into_checks_the_results_arity(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let instance = instance(&store)?;
+    let add = instance.lookup_function("add").unwrap();
+    let params = [Value::I64(1), Value::I64(2)];
+
+    let error = add.call_into(&params, &mut []).unwrap_err();
+    assert_eq!(
+        error.downcast::<ResultArityError>()?,
+        ResultArityError {
+            expected: 1,
+            given: 0
+        }
+    );
+
+    // Longer results are written at their start.
+    let mut results = [Value::I64(0), Value::F32(1.5)];
+    assert_eq!(add.call_into(&params, &mut results)?, 1);
+    assert_eq!(results, [Value::I64(3), Value::F32(1.5)]);
+
+    // Parameters are checked as by `call`.
+    let error = add.call_into(&[Value::I64(1)], &mut results).unwrap_err();
+    assert_eq!(
+        error.message(),
+        "Parameters of type [I64] did not match signature [I64, I64] -> [I64]"
+    );
+    Ok(())
+}
+
+#[compiler_test(dynamic_calls)]
+fn call_into_calls_host_functions(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let ty = FunctionType::new(vec![Type::I64, Type::I64], vec![Type::I64]);
+    let dynamic = Function::new(&store, &ty, |params| {
+        Ok(vec![Value::I64(
+            params[0].unwrap_i64() - params[1].unwrap_i64(),
+        )])
+    });
+    let native = Function::new_native(&store, |a: i64, b: i64| a - b);
+    for function in &[dynamic, native] {
+        let mut results = [Value::I64(0)];
+        assert_eq!(
+            function.call_into(&[Value::I64(5), Value::I64(3)], &mut results)?,
+            1
+        );
+        assert_eq!(results, [Value::I64(2)]);
+        let error = function
+            .call_into(&[Value::I64(5), Value::I64(3)], &mut [])
+            .unwrap_err();
+        assert!(error.downcast::<ResultArityError>().is_ok());
+    }
+    Ok(())
+}
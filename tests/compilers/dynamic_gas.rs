@@ -0,0 +1,202 @@
+//! Gas charged by the runtime for growing memories and tables and for bulk operations.
+
+use anyhow::Result;
+use std::cell::UnsafeCell;
+use wasmer::*;
+use wasmer_types::FastGasCounter;
+
+const WAT: &str = r#"
+    (module
+        (memory (export "memory") 32 33)
+        (table $t 1 4 funcref)
+        (data $d "passive")
+        (elem $e func $copy)
+        (func $copy (export "copy") (param i32 i32 i32)
+            local.get 0
+            local.get 1
+            local.get 2
+            memory.copy)
+        (func (export "fill") (param i32 i32 i32)
+            local.get 0
+            local.get 1
+            local.get 2
+            memory.fill)
+        (func (export "init") (param i32 i32 i32)
+            local.get 0
+            local.get 1
+            local.get 2
+            memory.init $d)
+        (func (export "grow") (param i32) (result i32)
+            local.get 0
+            memory.grow)
+        (func (export "table_grow") (param i32) (result i32)
+            ref.null func
+            local.get 0
+            table.grow $t)
+        (func (export "table_fill") (param i32 i32)
+            local.get 0
+            ref.null func
+            local.get 1
+            table.fill $t)
+        (func (export "table_copy") (param i32 i32 i32)
+            local.get 0
+            local.get 1
+            local.get 2
+            table.copy $t $t)
+        (func (export "table_init") (param i32 i32 i32)
+            local.get 0
+            local.get 1
+            local.get 2
+            table.init $t $e))
+"#;
+
+const COSTS: DynamicGasCosts = DynamicGasCosts {
+    memory_grow_base: 1_000,
+    memory_grow_per_page: 100_000,
+    bulk_memory_base: 100,
+    bulk_memory_per_byte: 1,
+    table_grow_base: 500,
+    table_grow_per_element: 50,
+    bulk_table_base: 10,
+    bulk_table_per_element: 5,
+};
+
+/// A gas counter updated by the instances behind our back.
+struct Counter(UnsafeCell<FastGasCounter>);
+
+impl Counter {
+    fn new(limit: u64) -> Self {
+        Self(UnsafeCell::new(FastGasCounter::new(limit, 0)))
+    }
+
+    fn burnt(&self) -> u64 {
+        unsafe { (*self.0.get()).burnt() }
+    }
+}
+
+/// Instantiate the test module charging `costs` to `counter`.
+fn instantiate(store: &Store, counter: &Counter, costs: DynamicGasCosts) -> Result<Instance> {
+    let module = Module::new(store, WAT)?;
+    let config = unsafe { InstanceConfig::default().with_counter(counter.0.get()) }
+        .with_dynamic_gas_costs(costs);
+    Ok(Instance::new_with_config(&module, config, &imports! {})?)
+}
+
+/// Return the gas burnt by `f`.
+fn burnt_by(counter: &Counter, f: impl FnOnce() -> Result<()>) -> Result<u64> {
+    let before = counter.burnt();
+    f()?;
+    Ok(counter.burnt() - before)
+}
+
+#[compiler_test(dynamic_gas)]
+fn bulk_memory_cost_scales_with_length(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let counter = Counter::new(u64::MAX);
+    let instance = instantiate(&store, &counter, COSTS)?;
+    let copy = instance.get_native_function::<(i32, i32, i32), ()>("copy")?;
+    let fill = instance.get_native_function::<(i32, i32, i32), ()>("fill")?;
+    let init = instance.get_native_function::<(i32, i32, i32), ()>("init")?;
+
+    let small = burnt_by(&counter, || Ok(copy.call(0, 0x10_0000, 1024)?))?;
+    let large = burnt_by(&counter, || Ok(copy.call(0, 0x10_0000, 0x10_0000)?))?;
+    assert_eq!(small, 100 + 1024);
+    assert_eq!(large, 100 + 0x10_0000);
+    assert_eq!(
+        burnt_by(&counter, || Ok(fill.call(0, 1, 4096)?))?,
+        100 + 4096
+    );
+    assert_eq!(burnt_by(&counter, || Ok(init.call(0, 0, 7)?))?, 100 + 7);
+    // Empty operations only cost their base.
+    assert_eq!(burnt_by(&counter, || Ok(copy.call(0, 0, 0)?))?, 100);
+    Ok(())
+}
+
+#[compiler_test(dynamic_gas)]
+fn exhaustion_leaves_memory_unmodified(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let counter = Counter::new(2_000);
+    let instance = instantiate(&store, &counter, COSTS)?;
+    let copy = instance.get_native_function::<(i32, i32, i32), ()>("copy")?;
+    let fill = instance.get_native_function::<(i32, i32, i32), ()>("fill")?;
+    let memory = instance.lookup_memory("memory").unwrap();
+    let view = memory.view::<u8>();
+
+    fill.call(0x1000, 0xab, 1024)?;
+    assert_eq!(counter.burnt(), 1_124);
+    let error = copy.call(0, 0x1000, 1024).unwrap_err();
+    assert_eq!(error.message(), "gas limit exceeded");
+    assert_eq!(error.to_trap(), Some(TrapCode::GasExceeded));
+    // The gas is burnt, but nothing was copied.
+    assert_eq!(counter.burnt(), 2_248);
+    assert!(view[0..1024].iter().all(|byte| byte.get() == 0));
+    Ok(())
+}
+
+#[compiler_test(dynamic_gas)]
+fn grow_traps_when_out_of_gas(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let counter = Counter::new(300_000);
+    let instance = instantiate(&store, &counter, COSTS)?;
+    let grow = instance.get_native_function::<i32, i32>("grow")?;
+    let memory = instance.lookup_memory("memory").unwrap();
+
+    assert_eq!(grow.call(1)?, 32);
+    assert_eq!(counter.burnt(), 101_000);
+    // Growing past the maximum fails as usual, but is still charged.
+    assert_eq!(grow.call(1)?, -1);
+    assert_eq!(counter.burnt(), 202_000);
+    let error = grow.call(1).unwrap_err();
+    assert_eq!(error.to_trap(), Some(TrapCode::GasExceeded));
+    assert_eq!(memory.size(), Pages(33));
+    Ok(())
+}
+
+#[compiler_test(dynamic_gas)]
+fn table_operations(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let counter = Counter::new(2_000);
+    let instance = instantiate(&store, &counter, COSTS)?;
+    let grow = instance.get_native_function::<i32, i32>("table_grow")?;
+    let fill = instance.get_native_function::<(i32, i32), ()>("table_fill")?;
+    let copy = instance.get_native_function::<(i32, i32, i32), ()>("table_copy")?;
+    let init = instance.get_native_function::<(i32, i32, i32), ()>("table_init")?;
+
+    assert_eq!(grow.call(3)?, 1);
+    assert_eq!(counter.burnt(), 650);
+    assert_eq!(burnt_by(&counter, || Ok(fill.call(0, 4)?))?, 30);
+    assert_eq!(burnt_by(&counter, || Ok(copy.call(1, 0, 2)?))?, 20);
+    assert_eq!(burnt_by(&counter, || Ok(init.call(3, 0, 1)?))?, 15);
+    // Out of bounds operations trap, but are charged first.
+    assert!(copy.call(3, 0, 2).is_err());
+    assert_eq!(counter.burnt(), 735);
+    assert_eq!(grow.call(0)?, 4);
+    let error = grow.call(100).unwrap_err();
+    assert_eq!(error.to_trap(), Some(TrapCode::GasExceeded));
+    Ok(())
+}
+
+#[compiler_test(dynamic_gas)]
+fn costs_are_per_instance(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let free = Counter::new(u64::MAX);
+    let instance = instantiate(&store, &free, DynamicGasCosts::default())?;
+    let copy = instance.get_native_function::<(i32, i32, i32), ()>("copy")?;
+    let grow = instance.get_native_function::<i32, i32>("grow")?;
+    copy.call(0, 0x10_0000, 0x10_0000)?;
+    assert_eq!(grow.call(1)?, 32);
+    assert_eq!(free.burnt(), 0);
+
+    // Another instance of the same module may use another schedule.
+    let doubled = Counter::new(u64::MAX);
+    let costs = DynamicGasCosts {
+        bulk_memory_base: 200,
+        bulk_memory_per_byte: 2,
+        ..COSTS
+    };
+    let instance = instantiate(&store, &doubled, costs)?;
+    let copy = instance.get_native_function::<(i32, i32, i32), ()>("copy")?;
+    copy.call(0, 0x10_0000, 1024)?;
+    assert_eq!(doubled.burnt(), 2 * (100 + 1024));
+    Ok(())
+}
@@ -0,0 +1,688 @@
+//! Stable error codes: every error variant has one, and they never change.
+//!
+//! `snapshots/error_codes.txt` lists the code of every variant. A change to that file is a
+//! breaking change for whoever aggregates errors by code: codes may be added, never moved.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+#[cfg(feature = "rpc")]
+use wasmer::rpc::ConvertError;
+use wasmer::*;
+use wasmer_compiler::MiddlewareError;
+use wasmer_engine::{ImportError, InstantiationError as EngineInstantiationError};
+use wasmer_engine_universal::ArtifactEditError;
+use wasmer_types::partial_sum_map;
+use wasmer_vm::{GlobalError, Trap};
+
+const SNAPSHOT: &str = include_str!("snapshots/error_codes.txt");
+
+/// One error of every variant that has a code of its own, by name.
+fn leaves() -> Vec<(&'static str, Box<dyn Fn() -> (ErrorCode, String)>)> {
+    fn leaf<E: HasErrorCode + Display + 'static>(
+        name: &'static str,
+        error: impl Fn() -> E + 'static,
+    ) -> (&'static str, Box<dyn Fn() -> (ErrorCode, String)>) {
+        (
+            name,
+            Box::new(move || {
+                let error = error();
+                (error.code(), error.to_string())
+            }),
+        )
+    }
+    let s = || "oops".to_string();
+    let function = || ExternType::Function(FunctionType::new(vec![], vec![]));
+    #[cfg_attr(not(feature = "rpc"), allow(unused_mut))]
+    let mut leaves = vec![
+        leaf("CompileError::Codegen", move || CompileError::Codegen(s())),
+        leaf(
+            "CompileError::Validate",
+            move || CompileError::Validate(s()),
+        ),
+        leaf("CompileError::UnsupportedFeature", move || {
+            CompileError::UnsupportedFeature(s())
+        }),
+        leaf("CompileError::UnsupportedTarget", move || {
+            CompileError::UnsupportedTarget(s())
+        }),
+        leaf(
+            "CompileError::Resource",
+            move || CompileError::Resource(s()),
+        ),
+        leaf("CompileError::EngineDowncast", || {
+            CompileError::EngineDowncast
+        }),
+        leaf("CompileError::Specialization", move || {
+            CompileError::Specialization(s())
+        }),
+        leaf("CompileError::CodeMemoryExhausted", || {
+            CompileError::CodeMemoryExhausted {
+                requested: 8192,
+                in_use: 4096,
+                largest_free_block: 0,
+            }
+        }),
+        leaf("CompileError::QuotaExceeded", || {
+            CompileError::QuotaExceeded {
+                tenant: TenantId(7),
+                dimension: QuotaDimension::CodeBytes,
+                limit: 4096,
+                used: 8192,
+            }
+        }),
+        leaf("CompileError::FeatureDisabled", || {
+            CompileError::FeatureDisabled {
+                feature: WasmFeature::Simd,
+                first_use: Location {
+                    section: SectionKind::Code,
+                    function: None,
+                    offset: 12,
+                },
+                construct: "SIMD instruction",
+                hint: "enable it",
+                others: vec![],
+            }
+        }),
+        leaf("CompileError::CodeTooLarge", || {
+            CompileError::CodeTooLarge {
+                index: LocalFunctionIndex::from_u32(3),
+                size: 4096,
+                limit: 1024,
+            }
+        }),
+        leaf("WasmError::InvalidWebAssembly", move || {
+            WasmError::InvalidWebAssembly {
+                message: s(),
+                offset: 12,
+            }
+        }),
+        leaf(
+            "WasmError::Unsupported",
+            move || WasmError::Unsupported(s()),
+        ),
+        leaf("WasmError::ImplLimitExceeded", || {
+            WasmError::ImplLimitExceeded
+        }),
+        leaf("WasmError::Generic", move || WasmError::Generic(s())),
+        leaf("MiddlewareError", || {
+            MiddlewareError::new("metering", "oops")
+        }),
+        leaf("ParseCpuFeatureError::Missing", move || {
+            ParseCpuFeatureError::Missing(s())
+        }),
+        leaf(
+            "SubprocessError::Spawn",
+            move || SubprocessError::Spawn(s()),
+        ),
+        leaf("SubprocessError::Io", move || SubprocessError::Io(s())),
+        leaf("SubprocessError::Timeout", || SubprocessError::Timeout),
+        leaf("SubprocessError::Crashed", move || {
+            SubprocessError::Crashed(s())
+        }),
+        leaf("SubprocessError::LimitExceeded", move || {
+            SubprocessError::LimitExceeded(s())
+        }),
+        leaf("SubprocessError::MalformedResponse", move || {
+            SubprocessError::MalformedResponse(s())
+        }),
+        leaf("DeserializeError::Io", || {
+            DeserializeError::Io(std::io::ErrorKind::NotFound.into())
+        }),
+        leaf("DeserializeError::Generic", move || {
+            DeserializeError::Generic(s())
+        }),
+        leaf("DeserializeError::Incompatible", move || {
+            DeserializeError::Incompatible(s())
+        }),
+        leaf("DeserializeError::CorruptedBinary", move || {
+            DeserializeError::CorruptedBinary(s())
+        }),
+        leaf("DeserializeError::LimitExceeded", move || {
+            DeserializeError::LimitExceeded(s())
+        }),
+        leaf("ArtifactEditError::Serialize", move || {
+            ArtifactEditError::Serialize(s().into())
+        }),
+        leaf("SerializeError::BinaryUnavailable", || {
+            SerializeError::BinaryUnavailable
+        }),
+        leaf("ArtifactEditError::UnknownFunction", move || {
+            ArtifactEditError::UnknownFunction {
+                name: s(),
+                index: FunctionIndex::from_u32(3),
+            }
+        }),
+        leaf("ArtifactEditError::SignatureMismatch", move || {
+            ArtifactEditError::SignatureMismatch {
+                name: s(),
+                expected: FunctionType::new(vec![Type::I32], vec![]),
+                recorded: FunctionType::new(vec![], vec![]),
+            }
+        }),
+        leaf("ArtifactEditError::DuplicateExport", move || {
+            ArtifactEditError::DuplicateExport(s())
+        }),
+        leaf("ArtifactEditError::CodeChanged", || {
+            ArtifactEditError::CodeChanged
+        }),
+        leaf("ImportError::IncompatibleType", move || {
+            ImportError::IncompatibleType(function(), function())
+        }),
+        leaf("ImportError::UnknownImport", move || {
+            ImportError::UnknownImport(function())
+        }),
+        leaf("ImportError::Rejected", move || {
+            ImportError::Rejected(s().into())
+        }),
+        leaf("LinkError::Resource", move || LinkError::Resource(s())),
+        leaf("LinkError::Imports", move || {
+            LinkError::Imports(vec![(s(), s(), ImportError::UnknownImport(function()))])
+        }),
+        leaf("InstantiationError::CpuFeature", move || {
+            InstantiationError::CpuFeature(s())
+        }),
+        leaf("InstantiationError::ReadOnlyUnsupported", move || {
+            InstantiationError::ReadOnlyUnsupported(s())
+        }),
+        leaf("InstantiationError::MemoryStyleMismatch", move || {
+            InstantiationError::MemoryStyleMismatch(s())
+        }),
+        leaf("InstantiationError::SpecializationMismatch", move || {
+            InstantiationError::SpecializationMismatch(s())
+        }),
+        leaf("InstantiationError::OverheadExceeded", || {
+            InstantiationError::OverheadExceeded {
+                layout: InstanceLayout::new(&wasmer_vm::VMOffsets::for_host(), 0),
+                limit: 0,
+            }
+        }),
+        leaf("InstantiationError::DifferentEngine", || {
+            InstantiationError::DifferentEngine
+        }),
+        leaf("HostEnvInitError::Export", move || {
+            HostEnvInitError::Export(ExportError::Missing(s()))
+        }),
+        leaf("HostEnvInitError::IncorrectGasMeteringConfig", || {
+            HostEnvInitError::IncorrectGasMeteringConfig
+        }),
+        leaf("HostEnvInitError::MissingMemory", move || {
+            HostEnvInitError::MissingMemory(s())
+        }),
+        leaf("ExternalDataErrorKind::MissingProvider", || {
+            ExternalDataErrorKind::MissingProvider
+        }),
+        leaf("ExternalDataErrorKind::HashMismatch", || {
+            ExternalDataErrorKind::HashMismatch
+        }),
+        leaf("DataError::NotFound", || DataError::NotFound),
+        leaf("DataError::Other", move || DataError::Other(s())),
+        leaf("RuntimeError::OOM", || RuntimeError::from_trap(Trap::oom())),
+        leaf("RuntimeError::Generic", || RuntimeError::new("oops")),
+        leaf("RuntimeError::User", || {
+            RuntimeError::from_trap(Trap::User(Box::new(ExportError::IncompatibleType)))
+        }),
+        leaf("MemoryError::Region", move || MemoryError::Region(s())),
+        leaf("MemoryError::CouldNotGrow", || MemoryError::CouldNotGrow {
+            current: Pages(1),
+            attempted_delta: Pages(2),
+        }),
+        leaf("MemoryError::InvalidMemory", move || {
+            MemoryError::InvalidMemory { reason: s() }
+        }),
+        leaf("MemoryError::MinimumMemoryTooLarge", || {
+            MemoryError::MinimumMemoryTooLarge {
+                min_requested: Pages(2),
+                max_allowed: Pages(1),
+            }
+        }),
+        leaf("MemoryError::MaximumMemoryTooLarge", || {
+            MemoryError::MaximumMemoryTooLarge {
+                max_requested: Pages(2),
+                max_allowed: Pages(1),
+            }
+        }),
+        leaf("MemoryError::GasExceeded", || MemoryError::GasExceeded),
+        leaf("MemoryError::Generic", move || MemoryError::Generic(s())),
+        leaf("MemoryError::InvalidRange", || MemoryError::InvalidRange {
+            offset: 1,
+            len: 2,
+        }),
+        leaf("MemoryError::BasePinned", || MemoryError::BasePinned),
+        leaf("GlobalError::ImmutableGlobalCannotBeSet", || {
+            GlobalError::ImmutableGlobalCannotBeSet
+        }),
+        leaf("GlobalError::IncorrectType", || {
+            GlobalError::IncorrectType {
+                expected: Type::I32,
+                found: Type::I64,
+            }
+        }),
+        leaf("partial_sum_map::Error::Overflow", || {
+            partial_sum_map::Error::Overflow
+        }),
+        leaf("ExportError::IncompatibleType", || {
+            ExportError::IncompatibleType
+        }),
+        leaf("ExportError::Missing", move || ExportError::Missing(s())),
+        leaf("BindExportsError", move || BindExportsError {
+            errors: vec![(s(), ExportError::Missing(s()))],
+        }),
+        leaf("EventError::OutOfBounds", || EventError::OutOfBounds {
+            ptr: 1,
+            len: 2,
+        }),
+        leaf("EventError::TopicTooLong", || EventError::TopicTooLong {
+            len: 2,
+            max: 1,
+        }),
+        leaf("EventError::TopicNotUtf8", || EventError::TopicNotUtf8),
+        leaf("EventError::TopicNotAllowed", move || {
+            EventError::TopicNotAllowed(s())
+        }),
+        leaf("EventError::PayloadTooLarge", || {
+            EventError::PayloadTooLarge { len: 2, max: 1 }
+        }),
+        leaf("EventError::TooManyEvents", || EventError::TooManyEvents {
+            max: 1,
+        }),
+        leaf("RegionError::UnknownRegion", move || {
+            RegionError::UnknownRegion(s())
+        }),
+        leaf("RegionError::OutOfRegion", move || {
+            RegionError::OutOfRegion {
+                ptr: 1,
+                len: 2,
+                region: s(),
+                range: 0..2,
+            }
+        }),
+        leaf("RegionError::PolicyViolation", move || {
+            RegionError::PolicyViolation {
+                ptr: 1,
+                region: s(),
+                policy: RegionPolicy::READ_ONLY,
+                access: RegionAccess::Write,
+            }
+        }),
+        leaf("RegionError::OutOfBounds", move || {
+            RegionError::OutOfBounds {
+                ptr: 1,
+                len: 2,
+                region: s(),
+                size: 2,
+            }
+        }),
+        leaf("RegionError::Unaligned", move || RegionError::Unaligned {
+            ptr: 1,
+            region: s(),
+            align: 4,
+        }),
+        leaf("RegionError::InvalidRegions", move || {
+            RegionError::InvalidRegions(s())
+        }),
+        leaf("ReplayLogError::UnsupportedVersion", || {
+            ReplayLogError::UnsupportedVersion(2)
+        }),
+        leaf("ReplayLogError::Corrupted", || ReplayLogError::Corrupted {
+            offset: 4,
+        }),
+        leaf("ReplayDivergence", move || ReplayDivergence {
+            call_index: 1,
+            field: DivergenceField::Arguments,
+            expected: s(),
+            actual: s(),
+        }),
+        leaf("GuestTypeError::Misaligned", || {
+            GuestTypeError::Misaligned {
+                offset: 1,
+                align: 4,
+            }
+        }),
+        leaf("GuestTypeError::OutOfBounds", || {
+            GuestTypeError::OutOfBounds {
+                offset: 0x10000,
+                len: 4,
+                size: 0x10000,
+            }
+        }),
+        leaf("GuestTypeError::ShortBuffer", || {
+            GuestTypeError::ShortBuffer { needed: 8, len: 4 }
+        }),
+        leaf("GuestTypeError::InvalidValue", || {
+            GuestTypeError::InvalidValue { ty: "bool" }
+        }),
+        leaf("GuestTypeError::InvalidUtf8", || {
+            GuestTypeError::InvalidUtf8 { offset: 0 }
+        }),
+        leaf("ComposeError::Options", move || ComposeError::Options(s())),
+        leaf("ComposeError::Unsupported", move || {
+            ComposeError::Unsupported {
+                module: s(),
+                feature: "module linking",
+            }
+        }),
+        leaf("ComposeError::MissingExport", move || {
+            ComposeError::MissingExport {
+                module: s(),
+                import_module: s(),
+                field: s(),
+            }
+        }),
+        leaf("ComposeError::IncompatibleImport", move || {
+            ComposeError::IncompatibleImport {
+                module: s(),
+                import_module: s(),
+                field: s(),
+                expected: s(),
+                actual: s(),
+            }
+        }),
+        leaf("ComposeError::CyclicImport", move || {
+            ComposeError::CyclicImport {
+                module: s(),
+                import_module: s(),
+                field: s(),
+            }
+        }),
+        leaf("ComposeError::ConflictingMemories", move || {
+            ComposeError::ConflictingMemories {
+                owners: vec![s(), s()],
+            }
+        }),
+        leaf("RebindError::WasmMismatch", || RebindError::WasmMismatch),
+        leaf("RebindError::CallInFlight", || RebindError::CallInFlight {
+            active_calls: 1,
+        }),
+        leaf("RebindError::LayoutMismatch", move || {
+            RebindError::LayoutMismatch(s())
+        }),
+        leaf("SnapshotError::Io", || {
+            SnapshotError::Io(std::io::ErrorKind::NotFound.into())
+        }),
+        leaf("SnapshotError::CallInFlight", || {
+            SnapshotError::CallInFlight { active_calls: 1 }
+        }),
+        leaf("SnapshotError::Poisoned", || SnapshotError::Poisoned),
+        leaf("SnapshotError::Unsupported", move || {
+            SnapshotError::Unsupported(s())
+        }),
+        leaf("SnapshotError::Format", move || SnapshotError::Format(s())),
+        leaf("SnapshotError::Mismatch", move || {
+            SnapshotError::Mismatch(s())
+        }),
+        leaf("SnapshotError::CorruptPage", || {
+            SnapshotError::CorruptPage { memory: 0, page: 1 }
+        }),
+        leaf(
+            "SnapshotError::Corrupt",
+            move || SnapshotError::Corrupt(s()),
+        ),
+        leaf("ArgPolicyError::NotHostFunction", || {
+            ArgPolicyError::NotHostFunction
+        }),
+        leaf("ArgPolicyError::Invalid", move || {
+            ArgPolicyError::Invalid(s())
+        }),
+        leaf("ArgPolicyError::Violation", move || {
+            ArgPolicyError::Violation {
+                import: s(),
+                index: 0,
+                rule: ArgRule::NonZero { arg: 0 },
+            }
+        }),
+        leaf("BundleError::Manifest", move || BundleError::Manifest(s())),
+        leaf("BundleError::Cycle", move || {
+            BundleError::Cycle(vec![s(), s()])
+        }),
+        leaf("TrapCodeCollision", move || TrapCodeCollision {
+            code: 0,
+            registered: s(),
+            requested: s(),
+        }),
+        leaf("ResultArityError", || ResultArityError {
+            expected: 2,
+            given: 1,
+        }),
+        leaf("OverrideError::NotOverridable", move || {
+            OverrideError::NotOverridable {
+                module: s(),
+                field: s(),
+            }
+        }),
+        leaf("OverrideError::SignatureMismatch", move || {
+            OverrideError::SignatureMismatch {
+                module: s(),
+                field: s(),
+                expected: FunctionType::new(vec![Type::I32], vec![]),
+                given: FunctionType::new(vec![], vec![]),
+            }
+        }),
+        leaf("GroupMoveError::CallInFlight", || {
+            GroupMoveError::CallInFlight { members: vec![0] }
+        }),
+        leaf("GroupMoveError::Escaped", || GroupMoveError::Escaped {
+            offenders: vec![GroupOffender::Exports {
+                member: 0,
+                handles: 1,
+            }],
+        }),
+        leaf("ResetError::CallInFlight", || ResetError::CallInFlight {
+            active_calls: 1,
+        }),
+        leaf("ResetError::Memory", || ResetError::Memory {
+            memory: 0,
+            error: MemoryError::GasExceeded,
+        }),
+        leaf("ChainedResolverError::Ambiguous", move || {
+            ChainedResolverError::Ambiguous {
+                module: s(),
+                field: s(),
+                layers: vec![s(), s()],
+            }
+        }),
+        leaf("ChainedResolverError::NamespaceViolation", move || {
+            ChainedResolverError::NamespaceViolation {
+                layer: s(),
+                module: s(),
+                field: s(),
+            }
+        }),
+    ];
+    #[cfg(feature = "rpc")]
+    leaves.extend(vec![
+        leaf("ConvertError::UnexpectedJson", || {
+            ConvertError::UnexpectedJson {
+                expected: Type::I32,
+                found: "string",
+            }
+        }),
+        leaf("ConvertError::OutOfRange", move || {
+            ConvertError::OutOfRange {
+                expected: Type::I32,
+                value: s(),
+            }
+        }),
+        leaf("ConvertError::InvalidEncoding", move || {
+            ConvertError::InvalidEncoding {
+                expected: Type::I64,
+                value: s(),
+            }
+        }),
+        leaf("ConvertError::Unsupported", || {
+            ConvertError::Unsupported(Type::ExternRef)
+        }),
+        leaf("ConvertError::NotAnArray", || {
+            ConvertError::NotAnArray("null")
+        }),
+        leaf("ConvertError::Arity", || ConvertError::Arity {
+            expected: 1,
+            found: 2,
+        }),
+    ]);
+    leaves
+}
+
+const TRAP_CODES: &[TrapCode] = &[
+    TrapCode::StackOverflow,
+    TrapCode::HeapAccessOutOfBounds,
+    TrapCode::HeapMisaligned,
+    TrapCode::TableAccessOutOfBounds,
+    TrapCode::OutOfBounds,
+    TrapCode::IndirectCallToNull,
+    TrapCode::BadSignature,
+    TrapCode::IntegerOverflow,
+    TrapCode::IntegerDivisionByZero,
+    TrapCode::BadConversionToInteger,
+    TrapCode::UnreachableCodeReached,
+    TrapCode::UnalignedAtomic,
+    TrapCode::GasExceeded,
+    TrapCode::ReadOnlyInstance,
+    TrapCode::ForeignFuncref,
+    TrapCode::PoisonedInstance,
+    TrapCode::Custom(0),
+    TrapCode::Cancelled,
+];
+
+/// The snapshot of the codes of every variant, one `name code` per line, by code.
+fn snapshot() -> String {
+    let mut lines = vec![];
+    for (name, error) in leaves() {
+        lines.push((error().0, name.to_string()));
+    }
+    for trap in TRAP_CODES {
+        lines.push((trap.code(), format!("TrapCode::{:?}", trap)));
+    }
+    lines.sort_by_key(|(code, _)| code.as_u16());
+    lines
+        .iter()
+        .map(|(code, name)| format!("{} {}\n", name, code))
+        .collect()
+}
+
+#[test]
+fn codes_match_snapshot() {
+    // The snapshot covers the errors of the optional APIs too.
+    let expected: String = SNAPSHOT
+        .lines()
+        .filter(|line| cfg!(feature = "rpc") || !line.starts_with("ConvertError::"))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    assert_eq!(snapshot(), expected);
+}
+
+#[test]
+fn codes_are_unique_and_complete() {
+    let mut names = HashMap::new();
+    for line in SNAPSHOT.lines() {
+        let (name, code) = line.split_at(line.find(' ').unwrap());
+        if let Some(other) = names.insert(code.trim().to_string(), name) {
+            panic!("{} and {} share the code {}", other, name, code);
+        }
+    }
+    // Every code belongs to exactly one variant, except those of the errors that cannot be
+    // constructed from outside their crate.
+    let hidden = [ErrorCode::CompileIo];
+    assert_eq!(names.len(), ErrorCode::ALL.len() - hidden.len());
+    for code in ErrorCode::ALL {
+        assert!(
+            names.contains_key(&code.to_string()) || hidden.contains(code),
+            "{} has no variant",
+            code
+        );
+    }
+}
+
+#[test]
+fn codes_round_trip_through_integers() {
+    let mut previous = 0;
+    for code in ErrorCode::ALL {
+        assert!(code.as_u16() > previous, "{} is out of order", code);
+        previous = code.as_u16();
+        assert_eq!(ErrorCode::from_u16(code.as_u16()), Some(*code));
+        assert_eq!(code.to_string(), format!("W{:04}", code.as_u16()));
+    }
+    assert_eq!(ErrorCode::from_u16(0), None);
+    assert_eq!(ErrorCode::from_u16(9999), None);
+    assert_eq!(ErrorCode::CompileValidate.to_string(), "W0102");
+}
+
+#[test]
+fn messages_end_with_code() {
+    for (name, error) in leaves() {
+        let (code, message) = error();
+        assert!(
+            message.ends_with(&format!(" [{}]", code)),
+            "{}: {}",
+            name,
+            message
+        );
+    }
+    let error = RuntimeError::from_trap(Trap::lib(TrapCode::GasExceeded));
+    assert_eq!(error.code(), ErrorCode::TrapGasExceeded);
+    assert_eq!(
+        error.to_string(),
+        "RuntimeError: gas limit exceeded [W0412]"
+    );
+    assert_eq!(error.message(), "gas limit exceeded");
+}
+
+#[test]
+fn wrappers_have_the_code_of_their_cause() {
+    let validate = || CompileError::Validate("oops".to_string());
+    let error = CompileError::Wasm(WasmError::Middleware(MiddlewareError::new("m", "oops")));
+    assert_eq!(error.code(), ErrorCode::Middleware);
+    assert!(error.to_string().ends_with(" [W0114]"), "{}", error);
+    let error = CompileError::Subprocess(SubprocessError::Timeout);
+    assert_eq!(error.code(), ErrorCode::SubprocessTimeout);
+    assert_eq!(
+        DeserializeError::Compiler(validate()).code(),
+        ErrorCode::CompileValidate
+    );
+    let error = ArtifactEditError::Deserialize(DeserializeError::Incompatible("oops".to_string()));
+    assert_eq!(error.code(), ErrorCode::DeserializeIncompatible);
+    assert!(error.to_string().ends_with(" [W0212]"), "{}", error);
+
+    let unknown = ImportError::UnknownImport(ExternType::Memory(MemoryType::new(1, None, false)));
+    let error = LinkError::Import("env".to_string(), "memory".to_string(), unknown);
+    assert_eq!(error.code(), ErrorCode::ImportUnknown);
+    assert!(error.to_string().ends_with(" [W0302]"), "{}", error);
+    let error = LinkError::Trap(RuntimeError::from_trap(Trap::lib(TrapCode::OutOfBounds)));
+    assert_eq!(error.code(), ErrorCode::TrapOutOfBounds);
+    let error = LinkError::Memory(MemoryError::GasExceeded);
+    assert_eq!(error.code(), ErrorCode::MemoryGasExceeded);
+
+    let error = InstantiationError::Link(LinkError::Resource("oops".to_string()));
+    assert_eq!(error.code(), ErrorCode::LinkResource);
+    let error = InstantiationError::Start(RuntimeError::new("oops"));
+    assert_eq!(error.code(), ErrorCode::RuntimeGeneric);
+    let error = InstantiationError::HostEnvInitialization(HostEnvInitError::MissingMemory(
+        "memory".to_string(),
+    ));
+    assert_eq!(error.code(), ErrorCode::HostEnvMissingMemory);
+    let external = || ExternalDataError {
+        segment_index: 0,
+        hash: [0; 32],
+        kind: ExternalDataErrorKind::Fetch(DataError::NotFound),
+    };
+    assert_eq!(external().code(), ErrorCode::DataNotFound);
+    let error = InstantiationError::ExternalData(external());
+    assert_eq!(error.code(), ErrorCode::DataNotFound);
+    assert!(error.to_string().ends_with(" [W0342]"), "{}", error);
+    let error = EngineInstantiationError::ExternalData(external());
+    assert_eq!(error.code(), ErrorCode::DataNotFound);
+    let error = EngineInstantiationError::CpuFeature("avx".to_string());
+    assert_eq!(error.code(), ErrorCode::InstantiationCpuFeature);
+
+    #[cfg(feature = "rpc")]
+    {
+        let error = ConvertError::Param {
+            index: 1,
+            error: Box::new(ConvertError::Unsupported(Type::FuncRef)),
+        };
+        assert_eq!(error.code(), ErrorCode::ConvertUnsupported);
+        assert!(error.to_string().ends_with(" [W0633]"), "{}", error);
+    }
+    // A host error is a user error, even if it has a code of its own.
+    let error = RuntimeError::from_trap(Trap::User(Box::new(ExportError::IncompatibleType)));
+    assert_eq!(error.code(), ErrorCode::RuntimeUser);
+}
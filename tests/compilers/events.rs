@@ -0,0 +1,169 @@
+use anyhow::Result;
+use wasmer::*;
+
+// Topics in memory: "transfer" at 0, "log.a" at 8, "log.b" at 13, "log.c" at 18, "mint" at 23,
+// invalid UTF-8 at 27.
+const WAT: &str = r#"
+(module
+    (import "env" "emit_event" (func $emit (param i32 i32 i32 i32)))
+    (import "env" "reenter" (func $reenter))
+    (memory (export "memory") 1)
+    (data (i32.const 0) "transferlog.alog.blog.cmint\ff")
+    (func (export "emit") (param i32 i32 i32 i32)
+        (call $emit (local.get 0) (local.get 1) (local.get 2) (local.get 3)))
+    (func (export "outer")
+        (call $emit (i32.const 8) (i32.const 5) (i32.const 0) (i32.const 1))
+        (call $reenter)
+        (call $emit (i32.const 18) (i32.const 5) (i32.const 0) (i32.const 3)))
+    (func (export "inner")
+        (call $emit (i32.const 13) (i32.const 5) (i32.const 0) (i32.const 2))))
+"#;
+
+#[derive(Clone, Default)]
+struct Env {
+    inner: LazyInit<NativeFunc<(), ()>>,
+}
+
+impl WasmerEnv for Env {
+    fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
+        self.inner
+            .initialize(instance.get_native_function("inner").unwrap());
+        Ok(())
+    }
+}
+
+fn instance(store: &Store, sink: &EventSink) -> Result<Instance> {
+    let module = Module::new(store, WAT)?;
+    let imports = imports! {
+        "env" => {
+            "emit_event" => sink.function(store),
+            "reenter" => Function::new_native_with_env(store, Env::default(), |env: &Env| {
+                env.inner.get_ref().unwrap().call().unwrap()
+            }),
+        },
+    };
+    Ok(Instance::new(&module, &imports)?)
+}
+
+fn schema() -> EventSchema {
+    EventSchema::new(8)
+        .with_topic("transfer")
+        .with_prefix("log.")
+}
+
+fn caps() -> EventCaps {
+    EventCaps {
+        max_payload_len: 4,
+        max_events: 3,
+    }
+}
+
+fn event(topic: &str, payload: &[u8]) -> Event {
+    Event {
+        topic: topic.to_string(),
+        payload: payload.to_vec(),
+    }
+}
+
+fn rejection(result: Result<(), RuntimeError>) -> EventError {
+    match result {
+        Err(e) => e.downcast::<EventError>().unwrap(),
+        Ok(()) => panic!("the event should have been rejected"),
+    }
+}
+
+#[compiler_test(events)]
+fn accepted_events(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let sink = EventSink::new(schema(), caps());
+    let instance = instance(&store, &sink)?;
+    let emit = instance.get_native_function::<(i32, i32, i32, i32), ()>("emit")?;
+    emit.call(0, 8, 8, 4)?;
+    emit.call(8, 5, 0, 0)?;
+    assert_eq!(
+        sink.take_events(),
+        [event("transfer", b"log."), event("log.a", b"")]
+    );
+    assert!(sink.take_events().is_empty());
+    Ok(())
+}
+
+#[compiler_test(events)]
+fn rejected_events(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let sink = EventSink::new(schema(), caps());
+    let instance = instance(&store, &sink)?;
+    let emit = instance.get_native_function::<(i32, i32, i32, i32), ()>("emit")?;
+
+    assert_eq!(
+        rejection(emit.call(0, 9, 0, 0)),
+        EventError::TopicTooLong { len: 9, max: 8 }
+    );
+    assert_eq!(
+        rejection(emit.call(23, 4, 0, 0)),
+        EventError::TopicNotAllowed("mint".to_string())
+    );
+    assert_eq!(rejection(emit.call(27, 1, 0, 0)), EventError::TopicNotUtf8);
+    assert_eq!(
+        rejection(emit.call(0, 8, 0, 5)),
+        EventError::PayloadTooLarge { len: 5, max: 4 }
+    );
+    assert_eq!(
+        rejection(emit.call(0, 8, 0xffff, 2)),
+        EventError::OutOfBounds {
+            ptr: 0xffff,
+            len: 2
+        }
+    );
+    let error = emit.call(23, 4, 0, 0).unwrap_err();
+    assert_eq!(
+        error.message(),
+        "event topic 'mint' is not allowed by the schema [W0613]"
+    );
+    assert!(sink.take_events().is_empty());
+
+    for _ in 0..3 {
+        emit.call(0, 8, 0, 0)?;
+    }
+    assert_eq!(
+        rejection(emit.call(0, 8, 0, 0)),
+        EventError::TooManyEvents { max: 3 }
+    );
+    assert_eq!(sink.take_events().len(), 3);
+    emit.call(0, 8, 0, 0)?;
+    assert_eq!(sink.rejected(), 0);
+    Ok(())
+}
+
+#[compiler_test(events)]
+fn lenient_mode(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let sink = EventSink::new(schema(), caps()).lenient();
+    let instance = instance(&store, &sink)?;
+    let emit = instance.get_native_function::<(i32, i32, i32, i32), ()>("emit")?;
+    emit.call(23, 4, 0, 0)?;
+    emit.call(0, 8, 0, 1)?;
+    emit.call(0, 8, 0, 5)?;
+    assert_eq!(sink.rejected(), 2);
+    assert_eq!(sink.take_events(), [event("transfer", b"t")]);
+    Ok(())
+}
+
+#[compiler_test(events)]
+fn reentrant_ordering(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let sink = EventSink::new(schema(), EventCaps::default());
+    let instance = instance(&store, &sink)?;
+    let outer = instance.get_native_function::<(), ()>("outer")?;
+    outer.call()?;
+    outer.call()?;
+    let expected = [
+        event("log.a", b"t"),
+        event("log.b", b"tr"),
+        event("log.c", b"tra"),
+    ];
+    let events = sink.take_events();
+    assert_eq!(events[..3], expected);
+    assert_eq!(events[3..], expected);
+    Ok(())
+}
@@ -0,0 +1,49 @@
+//! Modules using the exception handling proposal, which singlepass does not support.
+
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (tag $e (param i32))
+        (func (export "run") (result i32)
+            try (result i32)
+                i32.const 42
+                throw $e
+            catch $e
+            end))
+"#;
+
+#[compiler_test(exceptions)]
+fn rejected_without_the_feature(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    match Module::new(&store, WAT) {
+        Err(CompileError::Validate(message)) => {
+            assert!(message.starts_with("Exceptions support is not enabled"))
+        }
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    Ok(())
+}
+
+#[compiler_test(exceptions)]
+fn feature_is_unsupported(mut config: crate::Config) -> Result<()> {
+    let mut features = Features::new();
+    features.multi_value(false);
+    features.exceptions = true;
+    config.set_features(features);
+    let store = config.store();
+    // Tags are refused as soon as the module is translated...
+    match Module::new(&store, WAT) {
+        Err(CompileError::Wasm(WasmError::Unsupported(message))) => {
+            assert_eq!(message, "exception handling is not supported")
+        }
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    // ...and modules without any by the compiler.
+    match Module::new(&store, "(module)") {
+        Err(CompileError::UnsupportedFeature(feature)) => assert_eq!(feature, "exceptions"),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    Ok(())
+}
@@ -0,0 +1,183 @@
+//! Running a setup then independent entries into an instance, with failed entries rolled
+//! back.
+
+use anyhow::Result;
+use std::ptr;
+use std::time::{Duration, Instant};
+use wasmer::*;
+use wasmer_types::FastGasCounter;
+
+/// A contract keeping a balance in a global and a log of it in memory. `handle` burns
+/// 10 units of gas per call, and `read` returns both as one value, see [`state`], as
+/// Singlepass does not compile functions with several results.
+const WAT: &str = r#"
+    (module
+        (import "host" "gas" (func $gas (param i32)))
+        (memory (export "memory") 1)
+        (global $balance (mut i32) (i32.const 0))
+        (func (export "init")
+            (call $gas (i32.const 5))
+            (global.set $balance (i32.const 10))
+            (i32.store (i32.const 0) (i32.const 10)))
+        (func (export "handle") (param $amount i32) (result i32)
+            (call $gas (i32.const 10))
+            (global.set $balance (i32.add (global.get $balance) (local.get $amount)))
+            (i32.store (i32.const 0) (global.get $balance))
+            (if (i32.lt_s (local.get $amount) (i32.const 0)) (then unreachable))
+            (global.get $balance))
+        (func (export "read") (result i32)
+            (i32.add
+                (i32.mul (global.get $balance) (i32.const 1000))
+                (i32.load (i32.const 0)))))
+"#;
+
+fn instance(config: &crate::Config, gas_counter: &mut FastGasCounter) -> Result<Instance> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let gas = Function::new_native(&store, |_: i32| {});
+    let instance = Instance::new_with_config(
+        &module,
+        unsafe { InstanceConfig::default().with_counter(ptr::addr_of_mut!(*gas_counter)) },
+        &imports! { "host" => { "gas" => gas } },
+    )?;
+    Ok(instance)
+}
+
+/// A plan whose second entry traps after changing the balance and the log.
+fn plan(isolation: IsolationMode) -> ExecutionPlan {
+    ExecutionPlan::builder()
+        .setup("init", vec![])
+        .entry("read", vec![])
+        .entry("handle", vec![Val::I32(-3)])
+        .entry("read", vec![])
+        .isolation(isolation)
+        .build()
+}
+
+fn values(outcome: &CallOutcome) -> Vec<Val> {
+    outcome.result.as_ref().unwrap().to_vec()
+}
+
+/// What `read` returns for `balance` and the balance `logged` in memory.
+fn state(balance: i32, logged: i32) -> Vec<Val> {
+    vec![Val::I32(balance * 1000 + logged)]
+}
+
+#[compiler_test(execution_plan)]
+fn failed_entries_are_rolled_back(config: crate::Config) -> Result<()> {
+    let mut gas_counter = FastGasCounter::new(u64::MAX, 1);
+    let instance = instance(&config, &mut gas_counter)?;
+    let report = instance.run_plan(&plan(IsolationMode::SnapshotRollback), Guards::default())?;
+    assert_eq!(values(&report.entries[0]), state(10, 10));
+    let error = report.entries[1].result.as_ref().unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    assert!(report.entries[1].rolled_back);
+    // The third entry only observes the state the setup left.
+    assert_eq!(values(&report.entries[2]), state(10, 10));
+    assert!(!report.entries[2].rolled_back);
+    Ok(())
+}
+
+#[compiler_test(execution_plan)]
+fn failed_entries_are_kept_without_isolation(config: crate::Config) -> Result<()> {
+    let mut gas_counter = FastGasCounter::new(u64::MAX, 1);
+    let instance = instance(&config, &mut gas_counter)?;
+    let report = instance.run_plan(&plan(IsolationMode::None), Guards::default())?;
+    assert!(report.entries[1].result.is_err());
+    assert!(!report.entries[1].rolled_back);
+    assert_eq!(values(&report.entries[2]), state(7, 7));
+    Ok(())
+}
+
+#[compiler_test(execution_plan)]
+fn gas_is_accounted_per_entry(config: crate::Config) -> Result<()> {
+    let mut gas_counter = FastGasCounter::new(u64::MAX, 2);
+    let instance = instance(&config, &mut gas_counter)?;
+    let plan = ExecutionPlan::builder()
+        .setup("init", vec![])
+        .entry("handle", vec![Val::I32(1)])
+        .entry("handle", vec![Val::I32(-1)])
+        .entry("read", vec![])
+        .isolation(IsolationMode::SnapshotRollback)
+        .build();
+    let report = instance.run_plan(&plan, Guards::default())?;
+    assert_eq!(report.setup.as_ref().unwrap().gas_used, 10);
+    let gas = report
+        .entries
+        .iter()
+        .map(|entry| entry.gas_used)
+        .collect::<Vec<_>>();
+    // Gas burnt by the rolled back entry stays burnt.
+    assert_eq!(gas, [20, 20, 0]);
+    assert_eq!(report.gas_used(), 50);
+    assert_eq!(gas_counter.burnt(), 50);
+    Ok(())
+}
+
+#[compiler_test(execution_plan)]
+fn guards_apply_to_entries(config: crate::Config) -> Result<()> {
+    let mut gas_counter = FastGasCounter::new(u64::MAX, 1);
+    let instance = instance(&config, &mut gas_counter)?;
+    let guarded = |gas: Option<u64>, deadline: Option<Instant>| Guards { gas, deadline };
+    let plan = ExecutionPlan::builder()
+        .entry("handle", vec![Val::I32(1)])
+        .entry_with_guards("handle", vec![Val::I32(1)], guarded(Some(5), None))
+        .entry("handle", vec![Val::I32(1)])
+        .entry("handle", vec![Val::I32(1)])
+        .entry_with_guards(
+            "handle",
+            vec![Val::I32(1)],
+            guarded(None, Some(Instant::now() - Duration::from_secs(1))),
+        )
+        .isolation(IsolationMode::SnapshotRollback)
+        .build();
+    // 25 units of gas for the whole plan: the third entry burns the last 5.
+    let report = instance.run_plan(&plan, guarded(Some(25), None))?;
+    let traps = report
+        .entries
+        .iter()
+        .map(|entry| {
+            entry
+                .result
+                .as_ref()
+                .err()
+                .and_then(RuntimeError::trap_code)
+        })
+        .collect::<Vec<_>>();
+    let gas_exceeded = Some(TrapCode::GasExceeded);
+    assert_eq!(
+        traps,
+        [None, gas_exceeded, gas_exceeded, gas_exceeded, None]
+    );
+    assert_eq!(values(&report.entries[0]), [Val::I32(1)]);
+    // The entry past its deadline was not started.
+    let skipped = &report.entries[4];
+    assert_eq!(skipped.gas_used, 0);
+    assert!(!skipped.rolled_back);
+    assert!(skipped.result.is_err());
+    // The gas counter has its limit back.
+    assert_eq!(gas_counter.gas_limit, u64::MAX);
+    Ok(())
+}
+
+#[compiler_test(execution_plan)]
+fn plans_fail_before_running_anything(config: crate::Config) -> Result<()> {
+    let mut gas_counter = FastGasCounter::new(u64::MAX, 1);
+    let instance = instance(&config, &mut gas_counter)?;
+    let plan = ExecutionPlan::builder()
+        .setup("init", vec![])
+        .entry("missing", vec![])
+        .build();
+    let error = instance.run_plan(&plan, Guards::default()).unwrap_err();
+    assert!(matches!(error, PlanError::Export(ExportError::Missing(_))));
+    assert_eq!(error.code(), ErrorCode::ExportMissing);
+    assert_eq!(gas_counter.burnt(), 0);
+
+    let plan = ExecutionPlan::builder()
+        .setup("handle", vec![Val::I32(-1)])
+        .entry("read", vec![])
+        .build();
+    let error = instance.run_plan(&plan, Guards::default()).unwrap_err();
+    assert!(matches!(error, PlanError::Setup(_)));
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use anyhow::Result;
+use std::sync::atomic::{AtomicU64, Ordering::SeqCst};
+use std::sync::Arc;
+use wasmer::*;
+
+/// Host functions as they would be defined by one crate of a host ABI.
+mod accounts {
+    use wasmer::*;
+
+    pub struct Caller(pub &'static str);
+
+    pub fn imports(store: &Store) -> Exports {
+        let mut exports = Exports::new();
+        exports.insert(
+            "caller_len",
+            Function::new_native(store, || -> i32 {
+                let context = CallContext::current().unwrap();
+                context
+                    .extension::<Caller>()
+                    .map_or(-1, |c| c.0.len() as i32)
+            }),
+        );
+        exports
+    }
+}
+
+/// Host functions as they would be defined by another, independent crate.
+mod requests {
+    use wasmer::*;
+
+    pub struct RequestId(pub i64);
+
+    pub fn imports(store: &Store) -> Exports {
+        let mut exports = Exports::new();
+        exports.insert(
+            "request_id",
+            Function::new_native(store, || -> i64 {
+                let context = CallContext::current().unwrap();
+                context.extension::<RequestId>().map_or(-1, |r| r.0)
+            }),
+        );
+        exports
+    }
+}
+
+fn instance(store: &Store, config: InstanceConfig) -> Result<Instance> {
+    let module = Module::new(
+        store,
+        r#"
+        (module
+            (import "accounts" "caller_len" (func $caller_len (result i32)))
+            (import "requests" "request_id" (func $request_id (result i64)))
+            (func (export "run") (result i64)
+                call $caller_len
+                i64.extend_i32_s
+                i64.const 1000
+                i64.mul
+                call $request_id
+                i64.add))
+        "#,
+    )?;
+    let mut imports = ImportObject::new();
+    imports.register("accounts", accounts::imports(store));
+    imports.register("requests", requests::imports(store));
+    Ok(Instance::new_with_config(&module, config, &imports)?)
+}
+
+#[compiler_test(extensions)]
+fn host_functions_read_extensions(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let config = InstanceConfig::default()
+        .with_extension(accounts::Caller("alice"))
+        .with_extension(requests::RequestId(7));
+    let instance = instance(&store, config)?;
+    let run = instance.get_native_function::<(), i64>("run")?;
+    assert_eq!(run.call()?, 5007);
+    Ok(())
+}
+
+#[compiler_test(extensions)]
+fn absent_extension_is_none(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let config = InstanceConfig::default().with_extension(requests::RequestId(7));
+    let instance = instance(&store, config)?;
+    let run = instance.lookup_function("run").unwrap();
+    assert_eq!(&*run.call(&[])?, &[Value::I64(-1000 + 7)]);
+    assert!(CallContext::current().is_none());
+    Ok(())
+}
+
+#[compiler_test(extensions)]
+fn replaced_extension_is_observed(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let instance = instance(&store, InstanceConfig::default())?;
+    let run = instance.get_native_function::<(), i64>("run")?;
+    assert_eq!(run.call()?, -1000 - 1);
+
+    assert!(instance.insert_extension(requests::RequestId(1)).is_none());
+    assert_eq!(run.call()?, -1000 + 1);
+    let old = instance.insert_extension(requests::RequestId(2)).unwrap();
+    assert_eq!(old.0, 1);
+    assert_eq!(run.call()?, -1000 + 2);
+    Ok(())
+}
+
+#[compiler_test(extensions)]
+fn start_function_sees_extensions(config: crate::Config) -> Result<()> {
+    struct Seen(AtomicU64);
+
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (import "env" "record" (func $record))
+            (start $record))
+        "#,
+    )?;
+    let seen = Arc::new(Seen(AtomicU64::new(0)));
+    let record = Function::new_native(&store, || {
+        let context = CallContext::current().unwrap();
+        let seen = context.extension::<Arc<Seen>>().unwrap();
+        seen.0.fetch_add(1, SeqCst);
+    });
+    let config = InstanceConfig::default().with_extension(Arc::clone(&seen));
+    Instance::new_with_config(
+        &module,
+        config,
+        &imports! { "env" => { "record" => record } },
+    )?;
+    assert_eq!(seen.0.load(SeqCst), 1);
+    Ok(())
+}
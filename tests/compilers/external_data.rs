@@ -0,0 +1,129 @@
+use anyhow::Result;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{Universal, UniversalExecutable, UniversalExecutableRef};
+
+const WAT: &str = r#"
+    (module
+        (memory (export "memory") 1)
+        (data (i32.const 0) "small")
+        (data (i32.const 16) "a large segment that is kept out of the artifact")
+        (data (i32.const 20) "AB")
+        (func (export "load") (param i32) (result i32)
+            local.get 0
+            i32.load8_u))
+"#;
+
+struct BlobStore(HashMap<[u8; 32], Vec<u8>>);
+
+impl DataProvider for BlobStore {
+    fn fetch(&self, hash: &[u8; 32]) -> Result<Cow<'_, [u8]>, DataError> {
+        self.0
+            .get(hash)
+            .map(|data| Cow::Borrowed(&data[..]))
+            .ok_or(DataError::NotFound)
+    }
+}
+
+fn compile(config: &crate::Config) -> Result<(Store, UniversalExecutable)> {
+    let engine = Universal::new(config.compiler_config(false))
+        .externalize_data_segments(32)
+        .engine();
+    let store = Store::new(&engine);
+    let wasm = wat2wasm(WAT.as_bytes())?;
+    let executable = engine.compile_universal(&wasm, store.tunables())?;
+    Ok((store, executable))
+}
+
+fn instantiate(
+    module: &Module,
+    provider: Option<BlobStore>,
+) -> Result<Instance, InstantiationError> {
+    let mut config = InstanceConfig::default();
+    if let Some(provider) = provider {
+        config = config.with_data_provider(Arc::new(provider));
+    }
+    Instance::new_with_config(module, config, &imports! {})
+}
+
+#[compiler_test(external_data)]
+fn round_trip_external_segment(config: crate::Config) -> Result<()> {
+    let (store, executable) = compile(&config)?;
+    let segments = executable.external_data_segments();
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].segment_index, 1);
+    let blobs = executable
+        .external_data()
+        .map(|(hash, data)| (*hash, data.to_vec()))
+        .collect::<HashMap<_, _>>();
+
+    let serialized = executable.serialize().unwrap();
+    assert!(!serialized.windows(16).any(|w| w == b"a large segment "));
+    let deserialized = unsafe { UniversalExecutableRef::deserialize(&serialized)? };
+    let module = Module::from_executable(&store, &deserialized)?;
+
+    let instance = instantiate(&module, Some(BlobStore(blobs)))?;
+    let load = instance.get_native_function::<i32, i32>("load")?;
+    assert_eq!(load.call(0)?, b's' as i32);
+    assert_eq!(load.call(16)?, b'a' as i32);
+    // The later inline segment still overwrites the external one.
+    assert_eq!(load.call(20)?, b'A' as i32);
+    assert_eq!(load.call(21)?, b'B' as i32);
+    assert_eq!(load.call(22)?, b'e' as i32);
+    Ok(())
+}
+
+#[compiler_test(external_data)]
+fn hash_mismatch_is_rejected(config: crate::Config) -> Result<()> {
+    let (store, executable) = compile(&config)?;
+    let module = Module::from_executable(&store, &executable)?;
+    let hash = executable.external_data_segments()[0].hash;
+    let mut blobs = HashMap::new();
+    blobs.insert(
+        hash,
+        b"a large segment that is kept out of the artifacT".to_vec(),
+    );
+    match instantiate(&module, Some(BlobStore(blobs))) {
+        Err(InstantiationError::ExternalData(e)) => {
+            assert_eq!(e.segment_index, 1);
+            assert_eq!(e.hash, hash);
+            assert!(matches!(e.kind, ExternalDataErrorKind::HashMismatch));
+            assert_eq!(e.failure_kind(), FailureKind::Corrupt);
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("instantiation should have failed"),
+    }
+    match instantiate(&module, Some(BlobStore(HashMap::new()))) {
+        Err(InstantiationError::ExternalData(e)) => {
+            assert!(matches!(
+                e.kind,
+                ExternalDataErrorKind::Fetch(DataError::NotFound)
+            ));
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("instantiation should have failed"),
+    }
+    Ok(())
+}
+
+#[compiler_test(external_data)]
+fn missing_provider_is_an_error(config: crate::Config) -> Result<()> {
+    let (store, executable) = compile(&config)?;
+    let module = Module::from_executable(&store, &executable)?;
+    match instantiate(&module, None) {
+        Err(InstantiationError::ExternalData(e)) => {
+            assert_eq!(e.segment_index, 1);
+            assert!(matches!(e.kind, ExternalDataErrorKind::MissingProvider));
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("instantiation should have failed"),
+    }
+
+    // Modules without external segments do not need a provider.
+    let module = Module::new(&config.store(), WAT)?;
+    instantiate(&module, None)?;
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use anyhow::Result;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use wasmer::vm::{self, MemoryStyle, TableStyle, VMMemoryDefinition, VMTableDefinition};
+use wasmer::*;
+use wasmer_engine_universal::UniversalExecutableRef;
+
+/// Tunables failing to map memories, as when the host runs out of address space.
+struct ExhaustedTunables(BaseTunables);
+
+impl Tunables for ExhaustedTunables {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.0.memory_style(memory)
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.0.table_style(table)
+    }
+
+    fn create_host_memory(
+        &self,
+        _ty: &MemoryType,
+        _style: &MemoryStyle,
+    ) -> Result<Arc<dyn vm::Memory>, MemoryError> {
+        Err(MemoryError::Region("mmap failed: ENOMEM".to_string()))
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        _ty: &MemoryType,
+        _style: &MemoryStyle,
+        _vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<Arc<dyn vm::Memory>, MemoryError> {
+        Err(MemoryError::Region("mmap failed: ENOMEM".to_string()))
+    }
+
+    fn create_host_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+    ) -> Result<Arc<dyn vm::Table>, String> {
+        self.0.create_host_table(ty, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<Arc<dyn vm::Table>, String> {
+        self.0.create_vm_table(ty, style, vm_definition_location)
+    }
+}
+
+#[compiler_test(failure_kind)]
+fn permanent_failures(config: crate::Config) -> Result<()> {
+    let store = config.store();
+
+    let error = Module::new(&store, "(module (func (result i32) (i64.const 0)))").unwrap_err();
+    assert_eq!(error.failure_kind(), FailureKind::Permanent);
+
+    let module = Module::new(
+        &store,
+        r#"(module
+            (import "env" "f" (func))
+            (func (export "run") unreachable))"#,
+    )?;
+    let error = Instance::new(&module, &imports! {}).err().unwrap();
+    assert_eq!(error.failure_kind(), FailureKind::Permanent);
+
+    let imports = imports! {
+        "env" => { "f" => Function::new_native(&store, || {}) },
+    };
+    let instance = Instance::new(&module, &imports)?;
+    let run = instance.get_native_function::<(), ()>("run")?;
+    let error = run.call().unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    assert_eq!(error.failure_kind(), FailureKind::Permanent);
+    Ok(())
+}
+
+#[compiler_test(failure_kind)]
+fn memory_exhaustion(config: crate::Config) -> Result<()> {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = ExhaustedTunables(BaseTunables::for_target(engine.target()));
+    let store = Store::new_with_tunables(&*engine, tunables);
+    let module = Module::new(&store, "(module (memory 1))")?;
+    match Instance::new(&module, &imports! {}) {
+        Err(error @ InstantiationError::Link(LinkError::Memory(_))) => {
+            assert_eq!(error.failure_kind(), FailureKind::TransientResource);
+            assert!(error.failure_kind().is_transient());
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("instantiation should have failed"),
+    }
+    Ok(())
+}
+
+#[compiler_test(failure_kind)]
+fn corrupted_executable(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wasm = wat2wasm(b"(module (func (export \"run\")))")?;
+    let engine = store.engine();
+    let executable = engine.compile(&wasm, store.tunables())?;
+    let serialized = executable.serialize().unwrap();
+
+    let truncated = &serialized[..serialized.len() - 1];
+    let error = unsafe { UniversalExecutableRef::deserialize(truncated) }
+        .err()
+        .unwrap();
+    assert_eq!(error.failure_kind(), FailureKind::Corrupt, "{}", error);
+
+    let error = unsafe { UniversalExecutableRef::deserialize(&wasm) }
+        .err()
+        .unwrap();
+    assert_eq!(error.failure_kind(), FailureKind::Permanent, "{}", error);
+    Ok(())
+}
@@ -3,7 +3,8 @@ use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering::SeqCst;
 use wasmer::*;
 use wasmer_compiler_singlepass::Singlepass;
-use wasmer_engine_universal::Universal;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{Universal, UniversalExecutableRef};
 use wasmer_types::{FastGasCounter, InstanceConfig};
 
 fn get_module_with_start(store: &Store) -> Module {
@@ -288,3 +289,146 @@ fn test_gas_intrinsic_tricky() {
     // Ensure "gas" was called.
     assert_eq!(HITS.load(SeqCst), 2);
 }
+
+fn get_metered_store(costs: OperatorCosts) -> Store {
+    let mut compiler = Singlepass::default();
+    compiler.metering(costs);
+    Store::new(&Universal::new(compiler).engine())
+}
+
+fn get_metered_module(store: &Store) -> Module {
+    let wat = r#"
+        (func (export "add") (param i32 i32) (result i32)
+            local.get 0
+            local.get 1
+            i32.add
+        )
+        (func (export "spin")
+            (loop (br 0))
+        )
+    "#;
+
+    Module::new(&store, &wat).unwrap()
+}
+
+#[test]
+fn test_metering_infinite_loop() {
+    let burnt = (0..2)
+        .map(|_| {
+            let store = get_metered_store(OperatorCosts::uniform(1));
+            let module = get_metered_module(&store);
+            let mut gas_counter = FastGasCounter::new(1000, 0);
+            let instance = Instance::new_with_config(
+                &module,
+                unsafe { InstanceConfig::default().with_counter(ptr::addr_of_mut!(gas_counter)) },
+                &imports! {},
+            )
+            .unwrap();
+            let spin = instance.lookup_function("spin").unwrap();
+            let error = spin.call(&[]).unwrap_err();
+            assert_eq!(error.trap_code(), Some(TrapCode::GasExceeded));
+            assert_eq!(instance.remaining_gas(), 0);
+            gas_counter.burnt()
+        })
+        .collect::<Vec<_>>();
+    // The loop burns 1 on entry, then 3 per iteration, up to the limit.
+    assert_eq!(burnt, [1000, 1000]);
+}
+
+#[test]
+fn test_metering_refill() {
+    let costs = OperatorCosts::new("test", |operator| match operator {
+        wasmparser::Operator::I32Add => 10,
+        _ => 1,
+    });
+    let store = get_metered_store(costs);
+    let module = get_metered_module(&store);
+    let instance = Instance::new_with_config(
+        &module,
+        InstanceConfig::default().with_gas_limit(30),
+        &imports! {},
+    )
+    .unwrap();
+    let add = instance.lookup_function("add").unwrap();
+    let args = [Value::I32(1), Value::I32(2)];
+    // Two local.get, one i32.add and one end.
+    assert_eq!(add.call(&args).unwrap()[0], Value::I32(3));
+    assert_eq!(instance.remaining_gas(), 17);
+    assert_eq!(add.call(&args).unwrap()[0], Value::I32(3));
+    assert_eq!(instance.remaining_gas(), 4);
+    let error = add.call(&args).unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::GasExceeded));
+    assert_eq!(instance.remaining_gas(), 0);
+    // The host charges for the next call.
+    instance.set_remaining_gas(20);
+    assert_eq!(add.call(&args).unwrap()[0], Value::I32(3));
+    assert_eq!(instance.remaining_gas(), 7);
+}
+
+#[test]
+fn test_metering_is_part_of_provenance() {
+    let mut metered = Singlepass::default();
+    metered.metering(OperatorCosts::uniform(2));
+    let provenance = Box::new(metered.clone()).compiler().provenance();
+    assert_eq!(
+        provenance.middlewares,
+        ["intrinsic:gas", "metering:uniform:2"]
+    );
+    // Modules compiled with and without metering are told apart.
+    let fingerprint = |compiler: Singlepass| Universal::new(compiler).engine().fingerprint();
+    assert_ne!(fingerprint(Singlepass::default()), fingerprint(metered));
+}
+
+#[test]
+fn test_metering_schedules() {
+    let mut compiler = Singlepass::default();
+    compiler.metering_schedules(vec![
+        (ScheduleVersion(1), OperatorCosts::uniform(1)),
+        (ScheduleVersion(2), OperatorCosts::uniform(2)),
+    ]);
+    let engine = Universal::new(compiler).engine();
+    let store = Store::new(&engine);
+    let wasm = wat2wasm(
+        br#"(module (func (export "add") (param i32 i32) (result i32)
+            local.get 0
+            local.get 1
+            i32.add))"#,
+    )
+    .unwrap();
+    let burnt = |version: u32| {
+        let executable = engine
+            .compile_universal_with_schedule(&wasm, store.tunables(), ScheduleVersion(version))
+            .unwrap();
+        let serialized = executable.serialize().unwrap();
+        let deserialized = unsafe { UniversalExecutableRef::deserialize(&serialized).unwrap() };
+        let artifact = engine.load_universal_executable_ref(&deserialized).unwrap();
+        assert_eq!(artifact.metering_schedule(), Some(ScheduleVersion(version)));
+        let module = Module::from_executable(&store, &deserialized).unwrap();
+        let instance = Instance::new_with_config(
+            &module,
+            InstanceConfig::default().with_gas_limit(100),
+            &imports! {},
+        )
+        .unwrap();
+        let add = instance.lookup_function("add").unwrap();
+        let args = [Value::I32(1), Value::I32(2)];
+        assert_eq!(add.call(&args).unwrap()[0], Value::I32(3));
+        let burnt = 100 - instance.remaining_gas();
+        // Refilling works whatever the schedule.
+        instance.set_remaining_gas(100);
+        assert_eq!(add.call(&args).unwrap()[0], Value::I32(3));
+        assert_eq!(instance.remaining_gas(), 100 - burnt);
+        (module.artifact_id(), burnt)
+    };
+    let (first_id, first_burnt) = burnt(1);
+    let (second_id, second_burnt) = burnt(2);
+    // Two local.get, one i32.add and one end.
+    assert_eq!((first_burnt, second_burnt), (4, 8));
+    assert_ne!(first_id, second_id);
+    // The same engine still compiles without a schedule, and fails for unknown ones.
+    let module = Module::new(&store, &wasm).unwrap();
+    assert_ne!(module.artifact_id(), first_id);
+    assert!(engine
+        .compile_universal_with_schedule(&wasm, store.tunables(), ScheduleVersion(3))
+        .is_err());
+}
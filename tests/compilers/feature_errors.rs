@@ -0,0 +1,172 @@
+//! Telling which disabled features a module uses, and where.
+
+use anyhow::Result;
+use wasmer::*;
+
+/// Uses SIMD in its first function, atomics in its second, and bulk memory in its third and in
+/// its data section.
+const WAT: &str = r#"
+    (module
+        (memory 1)
+        (func (drop (v128.const i32x4 0 0 0 0)))
+        (func (drop (i32.atomic.load (i32.const 0))))
+        (func (memory.fill (i32.const 0) (i32.const 0) (i32.const 0)))
+        (data "passive"))
+"#;
+
+/// The default features, without multi-value, which Singlepass does not support.
+fn features() -> Features {
+    let mut features = Features::default();
+    features.multi_value(false);
+    features
+}
+
+/// Validate `wat` only, as Singlepass cannot compile SIMD instructions even when they are
+/// enabled.
+fn validate(config: &crate::Config, features: Features, wat: &str) -> Result<(), CompileError> {
+    let engine = Universal::new(config.compiler_config(false))
+        .features(features)
+        .engine();
+    engine.validate(&wat2wasm(wat.as_bytes()).unwrap())
+}
+
+fn disabled(config: &crate::Config, features: Features, wat: &str) -> CompileError {
+    match validate(config, features, wat) {
+        Err(e @ CompileError::FeatureDisabled { .. }) => e,
+        Err(other) => panic!("unexpected error: {}", other),
+        Ok(_) => panic!("the module validated"),
+    }
+}
+
+#[compiler_test(feature_errors)]
+fn simd_use_is_reported(config: crate::Config) -> Result<()> {
+    let wat = "(module (func (drop (v128.const i32x4 0 0 0 0))))";
+    let mut features = features();
+    features.simd(false);
+    match disabled(&config, features.clone(), wat) {
+        CompileError::FeatureDisabled {
+            feature,
+            first_use,
+            construct,
+            hint,
+            others,
+        } => {
+            assert_eq!(feature, WasmFeature::Simd);
+            assert_eq!(first_use.section, SectionKind::Code);
+            assert_eq!(first_use.function, Some(LocalFunctionIndex::from_u32(0)));
+            assert_eq!(wat2wasm(wat.as_bytes())?[first_use.offset], 0xfd);
+            assert_eq!(construct, "SIMD instruction");
+            assert!(hint.contains("simd128"));
+            assert!(others.is_empty());
+        }
+        _ => unreachable!(),
+    }
+
+    features.simd(true);
+    validate(&config, features, wat)?;
+    Ok(())
+}
+
+#[compiler_test(feature_errors)]
+fn shared_memories_are_reported(config: crate::Config) -> Result<()> {
+    let wat = "(module (memory 1 1 shared))";
+    let mut features = features();
+    features.threads(false);
+    let error = disabled(&config, features, wat);
+    assert_eq!(error.code(), ErrorCode::CompileFeatureDisabled);
+    match error {
+        CompileError::FeatureDisabled {
+            feature,
+            first_use,
+            construct,
+            ..
+        } => {
+            assert_eq!(feature, WasmFeature::Threads);
+            assert_eq!(first_use.section, SectionKind::Memory);
+            assert_eq!(first_use.function, None);
+            assert_eq!(construct, "shared memory");
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+#[compiler_test(feature_errors)]
+fn all_disabled_features_are_reported(config: crate::Config) -> Result<()> {
+    let mut features = features();
+    features.simd(false).threads(false).bulk_memory(false);
+    let error = disabled(&config, features.clone(), WAT);
+    match &error {
+        CompileError::FeatureDisabled {
+            feature,
+            first_use,
+            others,
+            ..
+        } => {
+            // Only the first use of bulk memory is reported, not the later passive segment.
+            assert_eq!(*feature, WasmFeature::Simd);
+            assert_eq!(first_use.function, Some(LocalFunctionIndex::from_u32(0)));
+            let others = others
+                .iter()
+                .map(|other| (other.feature, other.first_use.section, other.construct))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                others,
+                vec![
+                    (
+                        WasmFeature::Threads,
+                        SectionKind::Code,
+                        "atomic instruction"
+                    ),
+                    (
+                        WasmFeature::BulkMemory,
+                        SectionKind::Code,
+                        "bulk memory instruction"
+                    ),
+                ]
+            );
+        }
+        _ => unreachable!(),
+    }
+    let message = error.to_string();
+    assert!(message.contains("simd") && message.contains("threads"));
+    assert!(message.contains("bulk_memory"));
+
+    // Only the features still disabled are reported.
+    features.simd(true).threads(true);
+    match disabled(&config, features.clone(), WAT) {
+        CompileError::FeatureDisabled {
+            feature,
+            first_use,
+            others,
+            ..
+        } => {
+            assert_eq!(feature, WasmFeature::BulkMemory);
+            assert_eq!(first_use.function, Some(LocalFunctionIndex::from_u32(2)));
+            assert!(others.is_empty());
+        }
+        _ => unreachable!(),
+    }
+
+    features.bulk_memory(true);
+    validate(&config, features, WAT)?;
+    Ok(())
+}
+
+#[compiler_test(feature_errors)]
+fn other_validation_errors_are_kept(config: crate::Config) -> Result<()> {
+    // The first function is invalid regardless of the features, before SIMD is used.
+    let wat = r#"
+        (module
+            (func (drop (i32.add (i32.const 0) (i64.const 0))))
+            (func (drop (v128.const i32x4 0 0 0 0))))
+    "#;
+    let mut features = features();
+    features.simd(false);
+    match validate(&config, features, wat) {
+        Err(CompileError::Validate(message)) => assert!(message.contains("type mismatch")),
+        Err(other) => panic!("unexpected error: {}", other),
+        Ok(_) => panic!("the module validated"),
+    }
+    Ok(())
+}
@@ -0,0 +1,108 @@
+use anyhow::Result;
+use wasmer::*;
+
+/// Keeps more values alive than there are registers, across calls with stack arguments, loops,
+/// branch tables and floating point operations.
+const KERNEL: &str = r#"
+(module
+    (func $mix (param i64 i64 i64 i64 i64 i64 i64 i64 f64 f64) (result i64)
+        (i64.add
+            (i64.xor (i64.mul (local.get 0) (local.get 7)) (i64.rotl (local.get 1) (local.get 6)))
+            (i64.sub
+                (i64.add (local.get 2) (i64.mul (local.get 3) (local.get 5)))
+                (i64.add (local.get 4) (i64.trunc_f64_s (f64.add (local.get 8) (local.get 9)))))))
+    (func (export "kernel") (param $n i32) (param $seed i64) (result i64)
+        (local $a i64) (local $b i64) (local $c i64) (local $d i64)
+        (local $e i64) (local $f i64) (local $g i64) (local $h i64) (local $x f64)
+        (local.set $a (local.get $seed))
+        (local.set $b (i64.add (local.get $seed) (i64.const 1)))
+        (local.set $c (i64.mul (local.get $seed) (i64.const 3)))
+        (local.set $d (i64.xor (local.get $seed) (i64.const 0x5555)))
+        (local.set $x (f64.convert_i64_s (local.get $seed)))
+        (block $done
+            (loop $loop
+                (br_if $done (i32.eqz (local.get $n)))
+                (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                (local.set $e (i64.add (local.get $a) (local.get $b)))
+                (local.set $f (i64.mul (local.get $c) (local.get $d)))
+                (local.set $g (i64.rotl (local.get $e) (local.get $f)))
+                (local.set $h (i64.xor (local.get $g) (local.get $e)))
+                (local.set $x (f64.mul (f64.add (local.get $x) (f64.const 0.5)) (f64.const 0.75)))
+                (local.set $a
+                    (i64.add
+                        (i64.add
+                            (i64.add (i64.mul (local.get $a) (local.get $e)) (local.get $b))
+                            (i64.add (i64.mul (local.get $c) (local.get $f)) (local.get $d)))
+                        (call $mix (local.get $a) (local.get $b) (local.get $c) (local.get $d)
+                            (local.get $e) (local.get $f) (local.get $g) (local.get $h)
+                            (local.get $x) (f64.const 1.25))))
+                (block $three (block $two (block $one
+                    (br_table $one $two $three (i32.and (local.get $n) (i32.const 3))))
+                    (local.set $b (i64.add (local.get $b) (local.get $h)))
+                    (br $loop))
+                    (local.set $c (i64.sub (local.get $c) (local.get $g)))
+                    (br $loop))
+                (local.set $d (i64.mul (local.get $d) (i64.const 7)))
+                (br $loop)))
+        (i64.add (local.get $a)
+            (i64.add (local.get $b) (i64.add (local.get $c) (local.get $d))))))
+"#;
+
+const TRAPS: &str = r#"
+(module $frames
+    (func (export "run") (param i32) (result i32)
+        (i32.add (i32.const 1) (call $middle (local.get 0))))
+    (func $middle (param i32) (result i32)
+        (local i64 i64 i64 i64 i64)
+        (i32.add (i32.const 2) (call $inner (local.get 0) (i32.const 0))))
+    (func $inner (param i32 i32) (result i32)
+        (if (local.get 0) (then unreachable))
+        (i32.div_u (i32.const 1) (local.get 1))))
+"#;
+
+fn kernel(config: &crate::Config, n: i32, seed: i64) -> Result<i64> {
+    let store = config.store();
+    let module = Module::new(&store, KERNEL)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let kernel = instance.get_native_function::<(i32, i64), i64>("kernel")?;
+    Ok(kernel.call(n, seed)?)
+}
+
+#[compiler_test(frame_pointer)]
+fn register_pressure(mut config: crate::Config) -> Result<()> {
+    for &(n, seed) in &[(0, 0), (1, 1), (17, -3), (100, 0x1234_5678_9abc)] {
+        config.set_frame_pointer(true);
+        let expected = kernel(&config, n, seed)?;
+        config.set_frame_pointer(false);
+        assert_eq!(kernel(&config, n, seed)?, expected);
+    }
+    Ok(())
+}
+
+#[cfg_attr(any(target_env = "musl", windows), ignore)]
+#[compiler_test(frame_pointer)]
+fn trap_trace(mut config: crate::Config) -> Result<()> {
+    config.set_frame_pointer(false);
+    let store = config.store();
+    let module = Module::new(&store, TRAPS)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.get_native_function::<i32, i32>("run")?;
+
+    // Traps raised inline and from the trap sequences at the end of the function.
+    for &(arg, message) in &[(1, "unreachable"), (0, "divide by zero")] {
+        let error = run.call(arg).unwrap_err();
+        assert!(
+            error.message().contains(message),
+            "wrong message: {}",
+            error.message()
+        );
+        let trace = error
+            .trace()
+            .iter()
+            .map(|frame| (frame.module_name().to_string(), frame.func_index()))
+            .collect::<Vec<_>>();
+        let frame = |index| ("frames".to_string(), index);
+        assert_eq!(trace, [frame(2), frame(1), frame(0)]);
+    }
+    Ok(())
+}
@@ -0,0 +1,266 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use wasmer::*;
+use wasmer_compiler::CompiledFunction;
+use wasmer_compiler_singlepass::Singlepass;
+use wasmer_engine::Engine;
+use wasmer_engine_universal::{FunctionCodeIndex, Universal, UniversalExecutable};
+use wasmer_types::LocalFunctionIndex;
+
+const LIBRARY: &str = r#"
+    (func $square (export "square") (param i32) (result i32)
+        local.get 0
+        local.get 0
+        i32.mul)
+"#;
+
+fn module_with(body: &str) -> Vec<u8> {
+    let wat = format!(
+        r#"(module {} (func (export "main") (result i32) {}))"#,
+        LIBRARY, body
+    );
+    wat2wasm(wat.as_bytes()).unwrap().to_vec()
+}
+
+/// The functions of `module_with(body)`, after another one.
+fn moved_module_with(body: &str) -> Vec<u8> {
+    let wat = format!(
+        r#"(module
+            (func (param i32) (result i32) local.get 0)
+            {}
+            (func (export "main") (result i32) {}))"#,
+        LIBRARY, body
+    );
+    wat2wasm(wat.as_bytes()).unwrap().to_vec()
+}
+
+/// A store whose compiler has diagnostics enabled, with the sink they are reported to.
+fn diagnosed_store(config: &crate::Config) -> (Store, Arc<Recorder>) {
+    let mut compiler = Singlepass::default();
+    compiler.diagnostics(true);
+    compiler.frame_pointer(config.frame_pointer);
+    let mut universal = Universal::new(compiler);
+    if let Some(features) = &config.features {
+        universal = universal.features(features.clone());
+    }
+    let engine = universal.engine();
+    let recorder = Arc::new(Recorder::default());
+    engine.set_diagnostics_sink(recorder.clone());
+    (Store::new(&engine), recorder)
+}
+
+#[derive(Default)]
+struct Recorder(Mutex<Vec<DiagnosticEvent>>);
+
+impl DiagnosticsSink for Recorder {
+    fn event(&self, _instance: InstanceId, event: DiagnosticEvent) {
+        self.0.lock().unwrap().push(event);
+    }
+}
+
+fn compile(store: &Store, wasm: &[u8]) -> UniversalExecutable {
+    let engine: &dyn Engine = &**store.engine();
+    engine
+        .downcast_ref::<UniversalEngine>()
+        .unwrap()
+        .compile_universal(wasm, store.tunables())
+        .unwrap()
+}
+
+fn hash_of(executable: &UniversalExecutable, index: u32) -> [u8; 32] {
+    executable
+        .function_hashes()
+        .into_iter()
+        .find(|(idx, _)| *idx == LocalFunctionIndex::from_u32(index))
+        .unwrap()
+        .1
+}
+
+struct Index(HashMap<[u8; 32], CompiledFunction>);
+
+impl Index {
+    fn of(executable: &UniversalExecutable) -> Self {
+        Self(
+            executable
+                .function_source_hashes()
+                .into_iter()
+                .map(|(idx, hash)| (hash, executable.compiled_function(idx)))
+                .collect(),
+        )
+    }
+}
+
+impl FunctionCodeIndex for Index {
+    fn lookup(&self, source_hash: &[u8; 32]) -> Option<CompiledFunction> {
+        self.0.get(source_hash).cloned()
+    }
+}
+
+fn compile_deduped(store: &Store, wasm: &[u8], index: &Index) -> Result<UniversalExecutable> {
+    let engine: &dyn Engine = &**store.engine();
+    Ok(engine
+        .downcast_ref::<UniversalEngine>()
+        .unwrap()
+        .compile_universal_deduped(wasm, store.tunables(), index)?)
+}
+
+#[compiler_test(function_hashes)]
+fn shared_functions_hash_identically(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let first = compile(&store, &module_with("i32.const 3 call $square"));
+    let second = compile(
+        &store,
+        &module_with("i32.const 4 call $square i32.const 1 i32.add"),
+    );
+    assert_eq!(first.function_hashes().len(), 2);
+    assert_eq!(hash_of(&first, 0), hash_of(&second, 0));
+    assert_ne!(hash_of(&first, 1), hash_of(&second, 1));
+
+    let module = Module::new(&store, module_with("i32.const 3 call $square"))?;
+    assert_eq!(module.function_body_hashes(), first.function_hashes());
+    Ok(())
+}
+
+#[compiler_test(function_hashes)]
+fn callees_are_hashed_by_content(config: crate::Config) -> Result<()> {
+    // Diagnostics tell the runtime which function was entered without its index.
+    let (store, _) = diagnosed_store(&config);
+    let first = compile(&store, &module_with("i32.const 3 call $square"));
+    let moved = compile(&store, &moved_module_with("i32.const 3 call $square"));
+    assert_eq!(hash_of(&first, 0), hash_of(&moved, 1));
+    assert_eq!(hash_of(&first, 1), hash_of(&moved, 2));
+    // The same caller, calling other code.
+    let other = wat2wasm(
+        br#"(module
+            (func $square (param i32) (result i32) local.get 0)
+            (func (export "main") (result i32) i32.const 3 call $square))"#,
+    )?
+    .to_vec();
+    let other = compile(&store, &other);
+    assert_ne!(hash_of(&first, 1), hash_of(&other, 1));
+    Ok(())
+}
+
+#[compiler_test(function_hashes)]
+fn deduped_compile_runs_identically(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let library = compile(&store, &module_with("i32.const 3 call $square"));
+    let index = Index::of(&library);
+
+    let wasm = module_with("i32.const 7 call $square i32.const 1 i32.add");
+    let deduped = compile_deduped(&store, &wasm, &index)?;
+    let compiled = compile(&store, &wasm);
+    assert_eq!(deduped.function_hashes(), compiled.function_hashes());
+    assert_eq!(
+        deduped.function_source_hashes(),
+        compiled.function_source_hashes()
+    );
+    assert_eq!(
+        deduped.function_source_hashes()[0],
+        library.function_source_hashes()[0]
+    );
+    assert_ne!(
+        deduped.function_source_hashes()[1],
+        library.function_source_hashes()[1]
+    );
+
+    let expected = Instance::new(&Module::new(&store, &wasm)?, &imports! {})?;
+    let instance = Instance::new(&Module::from_executable(&store, &deduped)?, &imports! {})?;
+    for (name, args) in &[("main", vec![]), ("square", vec![Value::I32(12)])] {
+        let expected = expected.lookup_function(name).unwrap().call(args)?;
+        let result = instance.lookup_function(name).unwrap().call(args)?;
+        assert_eq!(expected, result);
+    }
+    Ok(())
+}
+
+#[compiler_test(function_hashes)]
+fn deduped_compile_takes_the_code_from_the_index(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wasm = module_with("i32.const 3 call $square");
+    let library = compile(&store, &wasm);
+    let doubling = compile(
+        &store,
+        &wat2wasm(
+            br#"(module
+                (func (export "square") (param i32) (result i32)
+                    local.get 0
+                    local.get 0
+                    i32.add))"#,
+        )?,
+    );
+    // File the code doubling its argument under the source hash of `square`.
+    let mut index = Index::of(&library);
+    let (idx, hash) = library.function_source_hashes()[0];
+    index.0.insert(hash, doubling.compiled_function(idx));
+
+    let deduped = compile_deduped(&store, &wasm, &index)?;
+    assert_eq!(hash_of(&deduped, 0), hash_of(&doubling, 0));
+    let instance = Instance::new(&Module::from_executable(&store, &deduped)?, &imports! {})?;
+    let square = instance.lookup_function("square").unwrap();
+    assert_eq!(square.call(&[Value::I32(5)])?.to_vec(), vec![Value::I32(10)]);
+    Ok(())
+}
+
+#[compiler_test(function_hashes)]
+fn deduped_code_is_diagnosed_at_its_index(config: crate::Config) -> Result<()> {
+    let (store, recorder) = diagnosed_store(&config);
+    let library = compile(&store, &module_with("i32.const 3 call $square"));
+    let wasm = moved_module_with("i32.const 3 call $square");
+    let deduped = compile_deduped(&store, &wasm, &Index::of(&library))?;
+    assert_eq!(
+        deduped.function_source_hashes()[1].1,
+        library.function_source_hashes()[0].1
+    );
+    assert_eq!(hash_of(&deduped, 1), hash_of(&library, 0));
+
+    let instance = Instance::new(&Module::from_executable(&store, &deduped)?, &imports! {})?;
+    instance.set_diagnostics_level(DiagnosticsLevel::Calls);
+    let main = instance.lookup_function("main").unwrap();
+    assert_eq!(main.call(&[])?.to_vec(), vec![Value::I32(9)]);
+    let calls = [2, 1].map(|function| DiagnosticEvent::Call {
+        function: FunctionIndex::from_u32(function),
+    });
+    assert_eq!(*recorder.0.lock().unwrap(), calls);
+    Ok(())
+}
+
+#[compiler_test(function_hashes)]
+fn deduped_code_unwinds(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let trapping = |before: &str| {
+        let wat = format!(
+            r#"(module
+                {}
+                (func $trap (param i32) (result i32)
+                    local.get 0
+                    i32.const 0
+                    i32.div_u)
+                (func (export "main") (result i32) i32.const 3 call $trap))"#,
+            before
+        );
+        wat2wasm(wat.as_bytes()).unwrap().to_vec()
+    };
+    let library = compile(&store, &trapping(""));
+    let wasm = trapping("(func (param i32) (result i32) local.get 0)");
+    let deduped = compile_deduped(&store, &wasm, &Index::of(&library))?;
+    assert_eq!(hash_of(&deduped, 1), hash_of(&library, 0));
+
+    let trace = |executable: &UniversalExecutable| -> Result<Vec<u32>> {
+        let module = Module::from_executable(&store, executable)?;
+        let instance = Instance::new(&module, &imports! {})?;
+        let error = instance
+            .lookup_function("main")
+            .unwrap()
+            .call(&[])
+            .unwrap_err();
+        Ok(error
+            .trace()
+            .iter()
+            .map(|frame| frame.func_index())
+            .collect())
+    };
+    assert_eq!(trace(&deduped)?, trace(&compile(&store, &wasm))?);
+    Ok(())
+}
@@ -0,0 +1,208 @@
+//! Reading and writing structs in guest memory with `GuestType`.
+
+use anyhow::Result;
+use std::cell::Cell;
+use wasmer::*;
+
+#[derive(GuestType, Clone, Debug, PartialEq)]
+struct Header {
+    version: u8,
+    flags: u16,
+    length: u32,
+}
+
+#[derive(GuestType, Clone, Debug, PartialEq)]
+#[guest(layout = "C")]
+struct Message {
+    header: Header,
+    id: u64,
+    tags: [u16; 3],
+    urgent: bool,
+    sender: GuestString,
+    amounts: GuestVec<i64>,
+}
+
+#[derive(GuestType, Clone, Debug, PartialEq)]
+#[guest(layout = "C", packed)]
+struct Packed {
+    kind: u8,
+    value: u32,
+    ratio: f32,
+}
+
+#[derive(GuestType, Clone, Copy)]
+struct Pair(i32, WasmPtr<u8, Array>);
+
+fn memory(config: &crate::Config) -> Result<Memory> {
+    let store = config.store();
+    Ok(Memory::new(&store, MemoryType::new(1, None, false))?)
+}
+
+fn bytes(view: &MemoryView<u8>, offset: usize, len: usize) -> Vec<u8> {
+    view[offset..offset + len].iter().map(Cell::get).collect()
+}
+
+fn set_bytes(view: &MemoryView<u8>, offset: usize, bytes: &[u8]) {
+    for (cell, byte) in view[offset..].iter().zip(bytes) {
+        cell.set(*byte);
+    }
+}
+
+#[compiler_test(guest_types)]
+fn nested_structs_have_the_c_layout(config: crate::Config) -> Result<()> {
+    let memory = memory(&config)?;
+    let view = memory.view::<u8>();
+    assert_eq!((Header::SIZE, Header::ALIGN), (8, 4));
+    assert_eq!((Message::SIZE, Message::ALIGN), (40, 8));
+    let message = Message {
+        header: Header {
+            version: 1,
+            flags: 0x0203,
+            length: 0x04050607,
+        },
+        id: 0x08090a0b0c0d0e0f,
+        tags: [0x1011, 0x1213, 0x1415],
+        urgent: true,
+        sender: GuestString::new(0x200, 5),
+        amounts: GuestVec::new(0x300, 2),
+    };
+    message.write_to(&view, 0x100)?;
+    #[rustfmt::skip]
+    assert_eq!(bytes(&view, 0x100, Message::SIZE), [
+        // header: version, padding, flags, length
+        0x01, 0x00, 0x03, 0x02, 0x07, 0x06, 0x05, 0x04,
+        // id
+        0x0f, 0x0e, 0x0d, 0x0c, 0x0b, 0x0a, 0x09, 0x08,
+        // tags, urgent, padding
+        0x11, 0x10, 0x13, 0x12, 0x15, 0x14, 0x01, 0x00,
+        // sender
+        0x00, 0x02, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00,
+        // amounts
+        0x00, 0x03, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00,
+    ]);
+    assert_eq!(Message::read_from(&view, 0x100)?, message);
+
+    set_bytes(&view, 0x200, b"alice");
+    assert_eq!(message.sender.read(&view)?, "alice");
+    message.amounts.write(&view, &[-1, 2])?;
+    assert_eq!(
+        bytes(&view, 0x300, 16),
+        [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 2, 0, 0, 0, 0, 0, 0, 0]
+    );
+    assert_eq!(message.amounts.read(&view)?, [-1, 2]);
+    Ok(())
+}
+
+#[compiler_test(guest_types)]
+fn packed_and_tuple_structs(config: crate::Config) -> Result<()> {
+    let memory = memory(&config)?;
+    let view = memory.view::<u8>();
+    assert_eq!((Packed::SIZE, Packed::ALIGN), (9, 1));
+    let packed = Packed {
+        kind: 7,
+        value: 0x01020304,
+        ratio: 1.5,
+    };
+    // Packed structs may be at any offset.
+    packed.write_to(&view, 0x101)?;
+    assert_eq!(
+        bytes(&view, 0x101, Packed::SIZE),
+        [0x07, 0x04, 0x03, 0x02, 0x01, 0x00, 0x00, 0xc0, 0x3f]
+    );
+    assert_eq!(Packed::read_from(&view, 0x101)?, packed);
+
+    assert_eq!((Pair::SIZE, Pair::ALIGN), (8, 4));
+    let pairs = GuestVec::<Pair>::new(0x200, 2);
+    let values = [Pair(-2, WasmPtr::new(0x10)), Pair(3, WasmPtr::new(0x20))];
+    pairs.write(&view, &values)?;
+    assert_eq!(
+        bytes(&view, 0x200, 16),
+        [0xfe, 0xff, 0xff, 0xff, 0x10, 0, 0, 0, 3, 0, 0, 0, 0x20, 0, 0, 0]
+    );
+    let read = pairs.read(&view)?;
+    let fields = |pair: &Pair| (pair.0, pair.1.offset());
+    assert_eq!(
+        read.iter().map(fields).collect::<Vec<_>>(),
+        [(-2, 0x10), (3, 0x20)]
+    );
+    Ok(())
+}
+
+#[compiler_test(guest_types)]
+fn invalid_accesses_are_errors(config: crate::Config) -> Result<()> {
+    let memory = memory(&config)?;
+    let view = memory.view::<u8>();
+    let size = view.len() as u32;
+
+    let error = Header::read_from(&view, 2).unwrap_err();
+    assert_eq!(
+        error,
+        GuestTypeError::Misaligned {
+            offset: 2,
+            align: 4
+        }
+    );
+    assert_eq!(error.code(), ErrorCode::GuestMisaligned);
+    assert_eq!(
+        Header::read_from(&view, size - 4).unwrap_err(),
+        GuestTypeError::OutOfBounds {
+            offset: u64::from(size - 4),
+            len: 8,
+            size: size.into(),
+        }
+    );
+    let header = Header {
+        version: 1,
+        flags: 2,
+        length: 3,
+    };
+    assert!(header.write_to(&view, u32::MAX - 3).is_err());
+    assert_eq!(
+        Header::decode(&[0; 7]).unwrap_err(),
+        GuestTypeError::ShortBuffer { needed: 8, len: 7 }
+    );
+    assert_eq!(
+        header.encode(&mut [0; 4]).unwrap_err(),
+        GuestTypeError::ShortBuffer { needed: 8, len: 4 }
+    );
+
+    // Invalid values are only detected when reading, and nothing is written on errors.
+    set_bytes(&view, 0x100, &[0xaa; 40]);
+    set_bytes(&view, 0x100 + 22, &[2]);
+    assert_eq!(
+        Message::read_from(&view, 0x100).unwrap_err(),
+        GuestTypeError::InvalidValue { ty: "bool" }
+    );
+    assert!(Message::read_from(&view, size - 32).is_err());
+    assert!(header.write_to(&view, size - 4).is_err());
+    assert_eq!(bytes(&view, size as usize - 4, 4), [0; 4]);
+
+    // Vectors and strings check their bounds, alignment and encoding.
+    assert!(matches!(
+        GuestVec::<u64>::new(size - 8, 2).read(&view),
+        Err(GuestTypeError::OutOfBounds { .. })
+    ));
+    assert!(matches!(
+        GuestVec::<u64>::new(0, u32::MAX).read(&view),
+        Err(GuestTypeError::OutOfBounds { .. })
+    ));
+    assert!(matches!(
+        GuestVec::<u64>::new(4, 1).read(&view),
+        Err(GuestTypeError::Misaligned { align: 8, .. })
+    ));
+    assert_eq!(
+        GuestVec::<u16>::new(0, 1)
+            .write(&view, &[1, 2])
+            .unwrap_err(),
+        GuestTypeError::ShortBuffer { needed: 4, len: 2 }
+    );
+    set_bytes(&view, 0x200, &[0x61, 0xff]);
+    let error = GuestString::new(0x200, 2).read(&view).unwrap_err();
+    assert_eq!(error, GuestTypeError::InvalidUtf8 { offset: 0x200 });
+    assert_eq!(
+        error.to_string(),
+        "guest string at 0x200 is not valid UTF-8 [W0654]"
+    );
+    assert!(GuestString::new(0x200, 2).write(&view, "abc").is_err());
+    Ok(())
+}
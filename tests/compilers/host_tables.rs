@@ -0,0 +1,87 @@
+//! Reading, writing and growing tables from the host.
+
+use anyhow::Result;
+use wasmer::*;
+
+/// Calls whatever the host installs into its table.
+const WAT: &str = r#"
+    (module
+        (type $ret_i32 (func (result i32)))
+        (table $t (export "table") 2 4 funcref)
+        (func $one (result i32) i32.const 1)
+        (elem (i32.const 0) $one)
+        (func (export "call") (param i32) (result i32)
+            local.get 0
+            call_indirect $t (type $ret_i32)))
+"#;
+
+fn plugin(store: &Store) -> Function {
+    Function::new_native(store, || -> i32 { 42 })
+}
+
+#[compiler_test(host_tables)]
+fn host_functions_are_installed_into_tables(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let instance = Instance::new(&Module::new(&store, WAT)?, &imports! {})?;
+    let call = instance.get_native_function::<i32, i32>("call")?;
+    let table = instance.lookup_table("table").unwrap();
+    let plugin = plugin(&store);
+
+    assert!(matches!(table.get(0), Some(Value::FuncRef(Some(_)))));
+    assert!(matches!(table.get(1), Some(Value::FuncRef(None))));
+    assert!(table.get(2).is_none());
+    table.set(1, Value::FuncRef(Some(plugin.clone())))?;
+    assert_eq!(call.call(0)?, 1);
+    assert_eq!(call.call(1)?, 42);
+    match table.get(1) {
+        Some(Value::FuncRef(Some(function))) => {
+            assert_eq!(function.call(&[])?.to_vec(), vec![Value::I32(42)]);
+        }
+        other => panic!("unexpected element: {:?}", other),
+    }
+
+    // Swapping a plugin out.
+    table.set(1, Value::FuncRef(None))?;
+    let error = call.call(1).unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::IndirectCallToNull));
+
+    assert_eq!(table.grow(2, Value::FuncRef(Some(plugin)))?, 2);
+    assert_eq!(table.size(), 4);
+    assert_eq!(call.call(3)?, 42);
+    Ok(())
+}
+
+#[compiler_test(host_tables)]
+fn out_of_bounds_accesses_are_errors(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let instance = Instance::new(&Module::new(&store, WAT)?, &imports! {})?;
+    let table = instance.lookup_table("table").unwrap();
+
+    let error = table.set(2, Value::FuncRef(None)).unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::TableAccessOutOfBounds));
+    // Past the maximum.
+    assert!(table.grow(3, Value::FuncRef(None)).is_err());
+    assert!(table.grow(u32::MAX, Value::FuncRef(None)).is_err());
+    assert_eq!(table.size(), 2);
+    assert_eq!(table.grow(0, Value::FuncRef(None))?, 2);
+    Ok(())
+}
+
+#[compiler_test(host_tables)]
+fn foreign_values_are_rejected(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let instance = Instance::new(&Module::new(&store, WAT)?, &imports! {})?;
+    let call = instance.get_native_function::<i32, i32>("call")?;
+    let table = instance.lookup_table("table").unwrap();
+
+    // A store of another engine.
+    let foreign = plugin(&config.store());
+    assert!(table.set(1, Value::FuncRef(Some(foreign.clone()))).is_err());
+    assert!(table.grow(1, Value::FuncRef(Some(foreign))).is_err());
+    assert!(table.set(1, Value::I32(7)).is_err());
+    assert!(table.set(1, Value::ExternRef(ExternRef::null())).is_err());
+    assert_eq!(table.size(), 2);
+    assert!(matches!(table.get(1), Some(Value::FuncRef(None))));
+    assert_eq!(call.call(0)?, 1);
+    Ok(())
+}
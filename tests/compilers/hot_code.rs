@@ -0,0 +1,133 @@
+//! Hot functions placed first in code memory and pinned there.
+
+use anyhow::Result;
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{Universal, UniversalArtifact, UniversalExecutableRef};
+use wasmer_types::entity::EntityRef;
+use wasmer_vm::Artifact;
+
+/// Pages are at least this large on all supported targets.
+const PAGE_ALIGNMENT: usize = 4096;
+
+const WAT: &str = r#"
+    (module
+        (import "env" "nop" (func))
+        (func $cold (export "cold") (param i32) (result i32)
+            (i32.add (local.get 0) (i32.const 1)))
+        (func $warm (param i32) (result i32)
+            (i32.mul (local.get 0) (i32.const 3)))
+        (func $hot (export "hot") (param i32) (result i32)
+            (call $warm (local.get 0)))
+        (func $other (export "other") (param i32) (result i32)
+            (i32.sub (local.get 0) (i32.const 1))))
+"#;
+
+fn engine(config: &crate::Config) -> UniversalEngine {
+    // `$warm` is function 2, after the import.
+    let hot = vec!["hot".to_string(), "2".to_string(), "missing".to_string()];
+    Universal::new(config.compiler_config(false))
+        .hot_functions(hot)
+        .engine()
+}
+
+/// The offsets of the local functions from the start of the hot code.
+fn layout(artifact: &UniversalArtifact) -> Vec<(usize, isize)> {
+    let hot = artifact.hot_code().unwrap();
+    let start = hot.address.0 as isize;
+    let mut offsets = artifact
+        .functions()
+        .iter()
+        .map(|(index, _)| {
+            let extent = artifact.function_extent(index).unwrap();
+            (index.index(), extent.address.0 as isize - start)
+        })
+        .collect::<Vec<_>>();
+    offsets.sort_by_key(|(_, offset)| *offset);
+    offsets
+}
+
+#[compiler_test(hot_code)]
+fn hot_functions_come_first(config: crate::Config) -> Result<()> {
+    let engine = engine(&config);
+    let store = Store::new(&engine);
+    let executable = engine.compile_universal(&wat2wasm(WAT.as_bytes())?, store.tunables())?;
+    let hot = [LocalFunctionIndex::new(2), LocalFunctionIndex::new(1)];
+    assert_eq!(executable.hot_functions(), hot);
+
+    let artifact = engine.load_universal_executable(&executable)?;
+    let hot_code = artifact.hot_code().unwrap();
+    assert_eq!(hot_code.address.0 as usize % PAGE_ALIGNMENT, 0);
+    let offsets = layout(&artifact);
+    assert_eq!(offsets[0], (2, 0));
+    assert_eq!(offsets[1].0, 1);
+    let warm = artifact.function_extent(hot[1]).unwrap();
+    let end = warm.address.0 as usize + warm.length - hot_code.address.0 as usize;
+    assert_eq!(hot_code.length, end);
+    assert!(offsets[2..]
+        .iter()
+        .all(|(_, offset)| *offset as usize >= hot_code.length));
+
+    // Deserialized executables keep the layout.
+    let serialized = executable.serialize().unwrap();
+    let executable = unsafe { UniversalExecutableRef::deserialize(&serialized)? };
+    let artifact = engine.load_universal_executable_ref(&executable)?;
+    assert_eq!(layout(&artifact), offsets);
+    assert_eq!(artifact.hot_code().unwrap().length, hot_code.length);
+
+    // Without hot functions, the code keeps the order of the module.
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let executable = engine.compile_universal(&wat2wasm(WAT.as_bytes())?, store.tunables())?;
+    assert!(executable.hot_functions().is_empty());
+    assert!(engine
+        .load_universal_executable(&executable)?
+        .hot_code()
+        .is_none());
+    Ok(())
+}
+
+#[compiler_test(hot_code)]
+fn pinning_reports_each_mode(config: crate::Config) -> Result<()> {
+    let engine = engine(&config);
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    let store = Store::new(&engine);
+    let module = Module::new(&store, wat2wasm(WAT.as_bytes())?)?;
+
+    let report = module.pin_hot_code(PinMode::Both);
+    assert!(report.bytes > 0);
+    assert_eq!(report.bytes % PAGE_ALIGNMENT, 0);
+    assert!(report.huge_pages.is_some());
+    // Locking fails when `RLIMIT_MEMLOCK` is too low for unprivileged processes, which only
+    // shows in the report.
+    match report.mlock.as_ref().unwrap() {
+        Ok(()) => assert_eq!(report.locked_bytes(), report.bytes),
+        Err(error) => {
+            assert_eq!(report.locked_bytes(), 0);
+            assert!(error.raw_os_error().is_some());
+        }
+    }
+    assert_eq!(
+        sink.snapshot().pinned_code_bytes,
+        report.locked_bytes() as i64
+    );
+    // Pinning again does not count the locked pages twice.
+    let again = module.pin_hot_code(PinMode::Mlock);
+    assert!(again.huge_pages.is_none());
+    assert_eq!(
+        sink.snapshot().pinned_code_bytes,
+        again.locked_bytes() as i64
+    );
+
+    let nop = Function::new_native(&store, || {});
+    let instance = Instance::new(&module, &imports! { "env" => { "nop" => nop } })?;
+    let hot = instance.get_native_function::<i32, i32>("hot")?;
+    let cold = instance.get_native_function::<i32, i32>("cold")?;
+    let other = instance.get_native_function::<i32, i32>("other")?;
+    assert_eq!((hot.call(7)?, cold.call(7)?, other.call(7)?), (21, 8, 6));
+
+    drop((hot, cold, other, instance, module));
+    assert_eq!(sink.snapshot().pinned_code_bytes, 0);
+    Ok(())
+}
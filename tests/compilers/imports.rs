@@ -393,6 +393,214 @@ fn regression_import_trampolines(config: crate::Config) -> Result<()> {
     Ok(())
 }
 
+#[compiler_test(imports)]
+fn imported_global_outlives_import_object(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wat = r#"(module
+    (import "env" "limit" (global $limit i32))
+    (func (export "limit") (result i32)
+      (global.get $limit))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let imports = imports! {
+        "env" => {
+            "limit" => Global::new(&store, Value::I32(1000)),
+        },
+    };
+    let instance = Instance::new(&module, &imports)?;
+    drop(imports);
+    let limit: NativeFunc<(), i32> = instance.get_native_function("limit")?;
+    assert_eq!(limit.call()?, 1000);
+    Ok(())
+}
+
+#[compiler_test(imports)]
+fn imported_mutable_global_is_shared_with_host(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wat = r#"(module
+    (import "env" "counter" (global $counter (mut i64)))
+    (func (export "bump") (result i64)
+      (global.set $counter (i64.add (global.get $counter) (i64.const 1)))
+      (global.get $counter))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let counter = Global::new_mut(&store, Value::I64(41));
+    let imports = imports! {
+        "env" => {
+            "counter" => counter.clone(),
+        },
+    };
+    let instance = Instance::new(&module, &imports)?;
+    let bump: NativeFunc<(), i64> = instance.get_native_function("bump")?;
+
+    // The host sees what wasm writes, and the other way around.
+    assert_eq!(bump.call()?, 42);
+    assert_eq!(counter.get(), Value::I64(42));
+    counter.set(Value::I64(100))?;
+    assert_eq!(bump.call()?, 101);
+    assert_eq!(counter.get(), Value::I64(101));
+    Ok(())
+}
+
+#[compiler_test(imports)]
+fn host_global_set_errors(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let constant = Global::new(&store, Value::I32(1));
+    let error = constant.set(Value::I32(2)).unwrap_err();
+    assert!(matches!(
+        error.downcast::<GlobalError>(),
+        Ok(GlobalError::ImmutableGlobalCannotBeSet)
+    ));
+
+    let mutable = Global::new_mut(&store, Value::I32(1));
+    let error = mutable.set(Value::F64(2.0)).unwrap_err();
+    assert!(matches!(
+        error.downcast::<GlobalError>(),
+        Ok(GlobalError::IncorrectType {
+            expected: Type::I32,
+            found: Type::F64,
+        })
+    ));
+    assert_eq!(constant.get(), Value::I32(1));
+    assert_eq!(mutable.get(), Value::I32(1));
+    Ok(())
+}
+
+#[compiler_test(imports)]
+fn chained_imports_override_the_base(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wat = r#"(module
+    (import "env" "clock" (func $clock (result i64)))
+    (import "env" "random" (func $random (result i64)))
+    (import "env" "epoch" (global $epoch i64))
+    (func (export "clock") (result i64) (call $clock))
+    (func (export "random") (result i64) (call $random))
+    (func (export "epoch") (result i64) (global.get $epoch))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let base = || {
+        imports! {
+            "env" => {
+                "clock" => Function::new_native(&store, || -> i64 { 1 }),
+                "random" => Function::new_native(&store, || -> i64 { 2 }),
+                "epoch" => Global::new(&store, Value::I64(3)),
+            },
+        }
+    };
+    let overrides = || {
+        imports! {
+            "env" => {
+                "clock" => Function::new_native(&store, || -> i64 { 10 }),
+            },
+        }
+    };
+    let call = |instance: &Instance, name: &str| -> Result<i64> {
+        let function: NativeFunc<(), i64> = instance.get_native_function(name)?;
+        Ok(function.call()?)
+    };
+
+    // Only the overridden function comes from the overrides.
+    let instance = Instance::new(&module, &base().chain_front(overrides()))?;
+    assert_eq!(call(&instance, "clock")?, 10);
+    assert_eq!(call(&instance, "random")?, 2);
+    assert_eq!(call(&instance, "epoch")?, 3);
+
+    // Chained behind, the overrides only provide what the base does not.
+    let instance = Instance::new(&module, &base().chain_back(overrides()))?;
+    assert_eq!(call(&instance, "clock")?, 1);
+
+    // Chains nest, the front of the outermost coming first.
+    let last = imports! {
+        "env" => {
+            "clock" => Function::new_native(&store, || -> i64 { 100 }),
+            "random" => Function::new_native(&store, || -> i64 { 200 }),
+        },
+    };
+    let resolver = base().chain_front(overrides()).chain_front(last);
+    let instance = Instance::new(&module, &resolver)?;
+    assert_eq!(call(&instance, "clock")?, 100);
+    assert_eq!(call(&instance, "random")?, 200);
+    assert_eq!(call(&instance, "epoch")?, 3);
+    Ok(())
+}
+
+#[compiler_test(imports)]
+fn native_function_env_is_initialized_and_dropped_once(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wat = r#"(module
+    (import "host" "sum" (func $sum (param i32 i32) (result i32)))
+    (memory (export "memory") 1)
+    (data (i32.const 16) "\01\02\03\04")
+    (func (export "main") (param i32) (result i32)
+      (call $sum (i32.const 16) (local.get 0)))
+)"#;
+    let module = Module::new(&store, wat)?;
+
+    struct Env {
+        calls: Arc<AtomicUsize>,
+        live: Arc<AtomicUsize>,
+        memory: LazyInit<Memory>,
+    }
+    impl Clone for Env {
+        fn clone(&self) -> Self {
+            self.live.fetch_add(1, SeqCst);
+            Self {
+                calls: self.calls.clone(),
+                live: self.live.clone(),
+                memory: self.memory.clone(),
+            }
+        }
+    }
+    impl Drop for Env {
+        fn drop(&mut self) {
+            self.live.fetch_sub(1, SeqCst);
+        }
+    }
+    impl WasmerEnv for Env {
+        fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
+            let mut memory = instance
+                .lookup_memory("memory")
+                .ok_or_else(|| HostEnvInitError::MissingMemory("memory".into()))?;
+            // The instance keeps this environment, which must not keep the instance.
+            memory.into_weak_instance_ref();
+            self.memory.initialize(memory);
+            Ok(())
+        }
+    }
+    fn sum(env: &Env, ptr: i32, len: i32) -> i32 {
+        env.calls.fetch_add(1, SeqCst);
+        let view = env.memory.get_ref().unwrap().view::<u8>();
+        view[ptr as usize..(ptr + len) as usize]
+            .iter()
+            .map(|byte| i32::from(byte.get()))
+            .sum()
+    }
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let live = Arc::new(AtomicUsize::new(1));
+    let env = Env {
+        calls: calls.clone(),
+        live: live.clone(),
+        memory: LazyInit::new(),
+    };
+    let imports = imports! {
+        "host" => {
+            "sum" => Function::new_native_with_env(&store, env, sum),
+        },
+    };
+    let instance = Instance::new(&module, &imports)?;
+    let main: NativeFunc<i32, i32> = instance.get_native_function("main")?;
+    assert_eq!(main.call(2)?, 3);
+    assert_eq!(main.call(4)?, 10);
+    assert_eq!(calls.load(SeqCst), 2);
+
+    drop(main);
+    drop(instance);
+    drop(imports);
+    assert_eq!(live.load(SeqCst), 0);
+    Ok(())
+}
+
 // TODO(0-copy): no longer possible to get references to exported entities other than functions
 //               (we don't need that functionality)
 // #[compiler_test(imports)]
@@ -0,0 +1,208 @@
+//! Groups of instances moved between threads as one unit.
+
+use anyhow::Result;
+use std::sync::{Arc, Barrier};
+use std::thread;
+use wasmer::*;
+
+/// Defines the shared memory, and a counter kept in it.
+const OWNER: &str = r#"
+    (module
+        (memory (export "memory") 1)
+        (func (export "bump") (result i32)
+            (i32.store (i32.const 0) (i32.add (i32.load (i32.const 0)) (i32.const 1)))
+            (i32.load (i32.const 0))))
+"#;
+
+/// Bumps the counter of the owner, and reads it through the shared memory.
+const USER: &str = r#"
+    (module
+        (import "owner" "memory" (memory 1))
+        (import "owner" "bump" (func $bump (result i32)))
+        (func (export "bump_twice") (result i32)
+            (drop (call $bump))
+            (call $bump))
+        (func (export "read") (result i32)
+            (i32.load (i32.const 0))))
+"#;
+
+/// A group of an instance of `OWNER` and one of `USER`.
+fn group(store: &Store) -> Result<(InstanceGroup, usize, usize)> {
+    let mut group = InstanceGroup::new();
+    let owner = group.instantiate(&Module::new(store, OWNER)?, &imports! {})?;
+    let imports = imports! {
+        "owner" => {
+            "memory" => group.instance(owner).lookup_memory("memory").unwrap(),
+            "bump" => group.instance(owner).lookup_function("bump").unwrap(),
+        },
+    };
+    let user = group.instantiate(&Module::new(store, USER)?, &imports)?;
+    Ok((group, owner, user))
+}
+
+fn call(group: &InstanceGroup, member: usize, name: &str) -> Result<i32> {
+    let function = group
+        .instance(member)
+        .get_native_function::<(), i32>(name)?;
+    Ok(function.call()?)
+}
+
+#[compiler_test(instance_group)]
+fn groups_move_between_calls(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let (mut group, owner, user) = group(&store)?;
+    let memory = |provider| GroupReference {
+        importer: user,
+        module: "owner".to_string(),
+        field: "memory".to_string(),
+        provider,
+        ty: ExternType::Memory(MemoryType::new(1, None, false)),
+    };
+    assert_eq!(group.references().len(), 2);
+    assert_eq!(group.references()[0], memory(Some(owner)));
+    assert_eq!(group.references()[1].provider, Some(owner));
+    assert_eq!(call(&group, user, "bump_twice")?, 2);
+
+    let movable = group.prepare_move()?;
+    assert!(group.is_empty());
+    let group = thread::spawn(move || -> Result<MovableGroup> {
+        let mut group = movable.activate();
+        assert_eq!(call(&group, user, "read")?, 2);
+        assert_eq!(call(&group, owner, "bump")?, 3);
+        assert_eq!(call(&group, user, "bump_twice")?, 5);
+        Ok(group.prepare_move()?)
+    })
+    .join()
+    .unwrap()?
+    .activate();
+    assert_eq!(group.len(), 2);
+    assert_eq!(call(&group, user, "read")?, 5);
+    assert_eq!(call(&group, user, "bump_twice")?, 7);
+    Ok(())
+}
+
+#[compiler_test(instance_group)]
+fn escaped_handles_keep_the_group(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let (mut group, owner, user) = group(&store)?;
+    let bump = group.instance(owner).lookup_function("bump").unwrap();
+    let instance = group.instance(user).clone();
+    let error = group.prepare_move().unwrap_err();
+    assert_eq!(
+        error,
+        GroupMoveError::Escaped {
+            offenders: vec![
+                GroupOffender::Exports {
+                    member: owner,
+                    handles: 1,
+                },
+                GroupOffender::Instance {
+                    member: user,
+                    handles: 1,
+                },
+            ],
+        }
+    );
+    assert_eq!(error.code(), ErrorCode::GroupEscaped);
+    assert_eq!(group.len(), 2);
+
+    // The group is untouched, and moves once the handles are dropped.
+    assert_eq!(bump.call(&[])?[0].unwrap_i32(), 1);
+    drop(bump);
+    drop(instance);
+    let group = group.prepare_move()?.activate();
+    assert_eq!(call(&group, user, "read")?, 1);
+    Ok(())
+}
+
+#[compiler_test(instance_group)]
+fn host_memories_move_with_the_group(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (import "host" "memory" (memory 1))
+            (func (export "read") (result i32)
+                (i32.load (i32.const 0))))
+        "#,
+    )?;
+    let memory = Memory::new(&store, MemoryType::new(1, None, false))?;
+    memory.view::<u32>()[0].set(7);
+    let imports = imports! { "host" => { "memory" => memory.clone() } };
+    let mut group = InstanceGroup::new();
+    let first = group.instantiate(&module, &imports)?;
+    let second = group.instantiate(&module, &imports)?;
+    assert_eq!(group.references().len(), 2);
+    assert!(group
+        .references()
+        .iter()
+        .all(|reference| reference.provider.is_none()));
+
+    drop(imports);
+    let error = group.prepare_move().unwrap_err();
+    assert_eq!(
+        error,
+        GroupMoveError::Escaped {
+            offenders: vec![GroupOffender::Memory {
+                module: "host".to_string(),
+                field: "memory".to_string(),
+                handles: 1,
+            }],
+        }
+    );
+
+    drop(memory);
+    let movable = group.prepare_move()?;
+    let (first, second) = thread::spawn(move || -> Result<(i32, i32)> {
+        let group = movable.activate();
+        Ok((call(&group, first, "read")?, call(&group, second, "read")?))
+    })
+    .join()
+    .unwrap()?;
+    assert_eq!((first, second), (7, 7));
+    Ok(())
+}
+
+#[compiler_test(instance_group)]
+fn running_calls_keep_the_group(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (import "host" "wait" (func $wait))
+            (func (export "run") (call $wait)))
+        "#,
+    )?;
+    let barrier = Arc::new(Barrier::new(2));
+    let wait = {
+        let barrier = barrier.clone();
+        Function::new(&store, FunctionType::new(vec![], vec![]), move |_| {
+            barrier.wait();
+            barrier.wait();
+            Ok(vec![])
+        })
+    };
+    let mut group = InstanceGroup::new();
+    let member = group.instantiate(&module, &imports! { "host" => { "wait" => wait } })?;
+    let run = group
+        .instance(member)
+        .get_native_function::<(), ()>("run")?;
+    let caller = thread::spawn(move || run.call());
+
+    barrier.wait();
+    let error = group.prepare_move().unwrap_err();
+    assert_eq!(
+        error,
+        GroupMoveError::CallInFlight {
+            members: vec![member]
+        }
+    );
+    assert_eq!(error.code(), ErrorCode::GroupCallInFlight);
+    barrier.wait();
+    caller.join().unwrap()?;
+
+    group.prepare_move()?;
+    Ok(())
+}
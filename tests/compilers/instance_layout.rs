@@ -0,0 +1,188 @@
+//! What instantiation allocates for the `VMContext` of an instance, computed beforehand.
+
+use anyhow::Result;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_engine_universal::Universal;
+use wasmer_vm::Instantiatable;
+
+/// Records whether the current thread allocates blocks of the sizes it watches, and how many
+/// blocks it allocates.
+struct RecordingAllocator;
+
+thread_local! {
+    /// The sizes watched by the current thread, and whether they were allocated.
+    static WATCHED: Cell<[(usize, bool); 2]> = Cell::new([(0, false); 2]);
+    /// The number of blocks the current thread allocated.
+    static ALLOCATIONS: Cell<usize> = Cell::new(0);
+}
+
+/// Return how many blocks the current thread allocates running `f`, and what `f` returns.
+pub(crate) fn count_allocations<R>(f: impl FnOnce() -> R) -> (usize, R) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (ALLOCATIONS.with(Cell::get) - before, result)
+}
+
+unsafe impl GlobalAlloc for RecordingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        let _ = WATCHED.try_with(|watched| {
+            let mut sizes = watched.get();
+            for (size, seen) in sizes.iter_mut() {
+                *seen |= *size == layout.size();
+            }
+            watched.set(sizes);
+        });
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: RecordingAllocator = RecordingAllocator;
+
+const ONE_OF_EACH: &str = r#"
+    (module
+        (memory 1)
+        (table 1 funcref)
+        (global (mut i32) (i32.const 0))
+        (func))
+"#;
+
+/// Instantiates `module`, checking that the buffer and the function references of the
+/// instance are allocated with the sizes its layout reports.
+fn check_allocations(config: &crate::Config, wat: &str) -> Result<InstanceLayout> {
+    let mut features = Features::new();
+    features.multi_value(false).multi_memory(true);
+    let engine = Universal::new(config.compiler_config(false))
+        .features(features)
+        .engine();
+    let store = Store::new(&engine);
+    let executable = engine.compile_universal(&wat2wasm(wat.as_bytes())?, store.tunables())?;
+    let artifact = Arc::new(engine.load_universal_executable(&executable)?);
+    let layout = artifact.instance_layout();
+    assert_eq!(
+        layout.buffer_bytes(),
+        layout.header + layout.vmctx + layout.padding
+    );
+    assert!(
+        layout.memory_definitions
+            + layout.table_definitions
+            + layout.globals
+            + layout.builtin_functions
+            <= layout.vmctx
+    );
+
+    let sizes = [(layout.buffer_bytes(), false), (layout.anyfuncs, false)];
+    WATCHED.with(|watched| watched.set(sizes));
+    let handle = unsafe {
+        artifact.instantiate(
+            store.tunables(),
+            &imports! {},
+            Box::new(()),
+            InstanceConfig::default(),
+        )?
+    };
+    let [(_, buffer_seen), (_, anyfuncs_seen)] =
+        WATCHED.with(|watched| watched.replace([(0, false); 2]));
+    assert!(buffer_seen, "no buffer of {} bytes", layout.buffer_bytes());
+    // An empty array of function references is not allocated.
+    assert_eq!(anyfuncs_seen, layout.anyfuncs != 0);
+    assert_eq!(handle.allocated_bytes(), layout.total_bytes());
+    Ok(layout)
+}
+
+#[compiler_test(instance_layout)]
+fn empty_module(config: crate::Config) -> Result<()> {
+    let layout = check_allocations(&config, "(module)")?;
+    assert_eq!(layout.memory_definitions, 0);
+    assert_eq!(layout.table_definitions, 0);
+    assert_eq!(layout.globals, 0);
+    assert_eq!(layout.anyfuncs, 0);
+    assert!(layout.builtin_functions > 0);
+    Ok(())
+}
+
+#[compiler_test(instance_layout)]
+fn one_of_each(config: crate::Config) -> Result<()> {
+    let one = check_allocations(&config, ONE_OF_EACH)?;
+    let empty = check_allocations(&config, "(module)")?;
+    assert_eq!(one.header, empty.header);
+    assert_eq!(one.builtin_functions, empty.builtin_functions);
+    assert!(one.memory_definitions > 0);
+    assert!(one.table_definitions > 0);
+    assert!(one.globals > 0);
+    assert!(one.anyfuncs > 0);
+    Ok(())
+}
+
+#[compiler_test(instance_layout)]
+fn many_of_each(config: crate::Config) -> Result<()> {
+    let one = check_allocations(&config, ONE_OF_EACH)?;
+    let many = check_allocations(
+        &config,
+        r#"
+        (module
+            (memory 1) (memory 2) (memory 0 1)
+            (table 1 funcref) (table 2 funcref) (table 3 externref)
+            (global (mut i32) (i32.const 0)) (global (mut i64) (i64.const 0))
+            (global f32 (f32.const 0)) (global (mut f64) (f64.const 0))
+            (func) (func) (func) (func) (func))
+        "#,
+    )?;
+    assert_eq!(many.memory_definitions, 3 * one.memory_definitions);
+    assert_eq!(many.table_definitions, 3 * one.table_definitions);
+    assert_eq!(many.globals, 4 * one.globals);
+    assert_eq!(many.anyfuncs, 5 * one.anyfuncs);
+    Ok(())
+}
+
+#[compiler_test(instance_layout)]
+fn overhead_cap_is_enforced(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (memory (export "memory") 1)
+            (global (mut i32) (i32.const 0))
+            (func (export "f")))
+        "#,
+    )?;
+    let layout = module.instance_layout();
+    let total = layout.total_bytes();
+
+    let config = InstanceConfig::default().with_max_instance_overhead_bytes(total - 1);
+    let error = match Instance::new_with_config(&module, config, &imports! {}) {
+        Err(error) => error,
+        Ok(_) => panic!("instantiated over the limit"),
+    };
+    assert_eq!(error.code(), ErrorCode::InstantiationOverheadExceeded);
+    assert_eq!(error.failure_kind(), FailureKind::Permanent);
+    assert!(error.to_string().contains(&format!(
+        "needs {} bytes, more than the limit of {}",
+        total,
+        total - 1
+    )));
+    match error {
+        InstantiationError::OverheadExceeded {
+            layout: reported,
+            limit,
+        } => {
+            assert_eq!(reported, layout);
+            assert_eq!(limit, total - 1);
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+
+    let config = InstanceConfig::default().with_max_instance_overhead_bytes(total);
+    let instance = Instance::new_with_config(&module, config, &imports! {})?;
+    instance.get_native_function::<(), ()>("f")?.call()?;
+    Ok(())
+}
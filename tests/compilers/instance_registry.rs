@@ -0,0 +1,141 @@
+//! Listing the live instances of an engine.
+
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+use wasmer::*;
+use wasmer_engine_universal::Universal;
+use wasmer_types::{FastGasCounter, InstanceConfig};
+
+const WAT: &str = r#"
+    (module $registered
+        (import "env" "inspect" (func $inspect))
+        (memory 2)
+        (table 3 funcref)
+        (table 1 funcref)
+        (func (export "run")
+            (call $inspect)))
+"#;
+
+/// Instantiate `module`, with an `inspect` import saving the instances listed by `engine`.
+fn instantiate(
+    module: &Module,
+    engine: &UniversalEngine,
+    config: InstanceConfig,
+    inspected: &Arc<Mutex<Vec<InstanceSnapshotInfo>>>,
+) -> Result<Instance> {
+    let (engine, inspected) = (engine.clone(), inspected.clone());
+    let inspect = Function::new(module.store(), ([], []), move |_| {
+        *inspected.lock().unwrap() = engine.instances();
+        Ok(vec![])
+    });
+    let imports = imports! { "env" => { "inspect" => inspect } };
+    Ok(Instance::new_with_config(module, config, &imports)?)
+}
+
+#[compiler_test(instance_registry)]
+fn live_instances_are_listed(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let store = Store::new(&engine);
+    let module = Module::new(&store, WAT)?;
+    let inspected = Arc::new(Mutex::new(Vec::new()));
+    // Instances created before the registry is enabled are not listed.
+    let unlisted = instantiate(&module, &engine, InstanceConfig::default(), &inspected)?;
+    assert_eq!(engine.instances(), []);
+    engine.enable_instance_registry();
+
+    let before = SystemTime::now();
+    let threads = (0..4)
+        .map(|_| {
+            let (module, engine, inspected) = (module.clone(), engine.clone(), inspected.clone());
+            thread::spawn(move || {
+                instantiate(&module, &engine, InstanceConfig::default(), &inspected)
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut instances = threads
+        .into_iter()
+        .map(|thread| thread.join().unwrap())
+        .collect::<Result<Vec<_>>>()?;
+    let mut counter = Box::new(FastGasCounter {
+        burnt_gas: 100,
+        gas_limit: 1000,
+        opcode_cost: 0,
+    });
+    let metered_config = unsafe { InstanceConfig::default().with_counter(&mut *counter) };
+    let metered = instantiate(&module, &engine, metered_config, &inspected)?;
+
+    let listed = engine.instances();
+    let mut ids = instances.iter().map(Instance::id).collect::<Vec<_>>();
+    ids.push(metered.id());
+    ids.sort();
+    assert_eq!(listed.iter().map(|info| info.id).collect::<Vec<_>>(), ids);
+    for info in &listed {
+        assert_eq!(info.module_name.as_deref(), Some("registered"));
+        assert_eq!(info.module_hash, module.consensus_hash());
+        assert!(info.created >= before);
+        assert_eq!(info.usage.memory_bytes, 2 * 65536);
+        assert_eq!(info.usage.tables, 2);
+        assert_eq!(info.usage.table_elements, 4);
+        assert!(!info.usage.in_call);
+        if info.id != metered.id() {
+            assert_eq!(info.usage.gas_remaining, u64::MAX);
+        }
+    }
+    let metered_info = listed.iter().find(|info| info.id == metered.id()).unwrap();
+    assert_eq!(metered_info.usage.gas_remaining, 900);
+
+    // Only the instance being called is in a call.
+    metered.get_native_function::<(), ()>("run")?.call()?;
+    let inspected = inspected.lock().unwrap().clone();
+    assert_eq!(inspected.len(), listed.len());
+    for info in inspected {
+        assert_eq!(info.usage.in_call, info.id == metered.id());
+    }
+
+    // Dropped instances disappear.
+    let dropped = instances.split_off(2);
+    let dropped_ids = dropped.iter().map(Instance::id).collect::<Vec<_>>();
+    drop(dropped);
+    let listed = engine.instances();
+    assert_eq!(listed.len(), 3);
+    assert!(listed.iter().all(|info| !dropped_ids.contains(&info.id)));
+    drop(instances);
+    drop(metered);
+    drop(unlisted);
+    assert_eq!(engine.instances(), []);
+    Ok(())
+}
+
+#[compiler_test(instance_registry)]
+fn instances_dropped_concurrently_are_skipped(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    engine.enable_instance_registry();
+    let store = Store::new(&engine);
+    let module = Module::new(&store, WAT)?;
+    let inspected = Arc::new(Mutex::new(Vec::new()));
+    let kept = instantiate(&module, &engine, InstanceConfig::default(), &inspected)?;
+    let threads = (0..4)
+        .map(|_| {
+            let (module, engine, inspected) = (module.clone(), engine.clone(), inspected.clone());
+            thread::spawn(move || -> Result<()> {
+                for _ in 0..50 {
+                    instantiate(&module, &engine, InstanceConfig::default(), &inspected)?;
+                }
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    // Listing while other threads create and drop instances only returns live instances.
+    for _ in 0..50 {
+        assert!(engine.instances().iter().any(|info| info.id == kept.id()));
+    }
+    for thread in threads {
+        thread.join().unwrap()?;
+    }
+    let listed = engine.instances();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].id, kept.id());
+    Ok(())
+}
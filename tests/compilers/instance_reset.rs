@@ -0,0 +1,172 @@
+//! Resets of instances to the state they had when created, to reuse them.
+
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (type $nullary (func (result i32)))
+        (memory (export "memory") 1 4)
+        (global $count (mut i32) (i32.const 0))
+        (global $started (mut i32) (i32.const 0))
+        (table $t 2 8 funcref)
+        (elem (i32.const 0) $one)
+        (elem $passive func $two)
+        (data (i32.const 0) "init")
+        (func $one (result i32) (i32.const 1))
+        (func $two (result i32) (i32.const 2))
+        (func $start (global.set $started (i32.add (global.get $started) (i32.const 1))))
+        (start $start)
+        (func (export "run") (result i32)
+            (global.set $count (i32.add (global.get $count) (i32.const 1)))
+            (i32.store8 (i32.const 0) (i32.const 42))
+            (i32.store8 (i32.const 100) (i32.const 42))
+            (table.init $t $passive (i32.const 1) (i32.const 0) (i32.const 1))
+            (elem.drop $passive)
+            (drop (table.grow $t (ref.func $one) (i32.const 2)))
+            (drop (memory.grow (i32.const 1)))
+            (global.get $count))
+        (func (export "started") (result i32) (global.get $started))
+        (func (export "table_size") (result i32) (table.size $t))
+        (func (export "call") (param i32) (result i32)
+            (call_indirect $t (type $nullary) (local.get 0))))
+"#;
+
+fn store(config: &crate::Config, copy_on_write: bool) -> Store {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = BaseTunables {
+        copy_on_write_memories: copy_on_write,
+        ..BaseTunables::for_target(engine.target())
+    };
+    Store::new_with_tunables(&*engine, tunables)
+}
+
+/// The state of an instance, as seen through its exports.
+#[derive(Debug, PartialEq)]
+struct State {
+    memory: Vec<u8>,
+    pages: Pages,
+    table_size: i32,
+    second_element: Option<i32>,
+}
+
+fn state(instance: &Instance) -> Result<State> {
+    let memory = instance.lookup_memory("memory").unwrap();
+    let bytes = memory.view::<u8>();
+    Ok(State {
+        memory: [0, 1, 2, 3, 4, 100]
+            .iter()
+            .map(|offset| bytes[*offset].get())
+            .collect(),
+        pages: memory.size(),
+        table_size: instance
+            .get_native_function::<(), i32>("table_size")?
+            .call()?,
+        second_element: instance
+            .get_native_function::<i32, i32>("call")?
+            .call(1)
+            .ok(),
+    })
+}
+
+#[compiler_test(instance_reset)]
+fn resets_bring_back_the_initial_state(config: crate::Config) -> Result<()> {
+    for copy_on_write in [false, true] {
+        let module = Module::new(&store(&config, copy_on_write), WAT)?;
+        let instance = Instance::new(&module, &imports! {})?;
+        let run = instance.get_native_function::<(), i32>("run")?;
+        let initial = state(&instance)?;
+        assert_eq!(
+            initial,
+            State {
+                memory: b"init\0\0".to_vec(),
+                pages: Pages(1),
+                table_size: 2,
+                second_element: None,
+            }
+        );
+
+        assert_eq!(run.call()?, 1);
+        assert_eq!(
+            state(&instance)?,
+            State {
+                memory: b"*nit\0*".to_vec(),
+                pages: Pages(2),
+                table_size: 4,
+                second_element: Some(2),
+            }
+        );
+
+        // The second run sees what the first one did not, dropped segments included.
+        instance.reset(false)?;
+        assert_eq!(state(&instance)?, initial);
+        assert_eq!(run.call()?, 1);
+        assert_eq!(state(&instance)?.second_element, Some(2));
+        assert!(!instance.is_poisoned());
+    }
+    Ok(())
+}
+
+#[compiler_test(instance_reset)]
+fn start_functions_run_again_on_request(config: crate::Config) -> Result<()> {
+    let module = Module::new(&config.store(), WAT)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let started = instance.get_native_function::<(), i32>("started")?;
+    assert_eq!(started.call()?, 1);
+    instance.reset(false)?;
+    assert_eq!(started.call()?, 0);
+    instance.reset(true)?;
+    assert_eq!(started.call()?, 1);
+    Ok(())
+}
+
+#[compiler_test(instance_reset)]
+fn calls_in_flight_prevent_resets(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (import "env" "tick" (func $tick))
+            (global $count (mut i32) (i32.const 0))
+            (func (export "increment") (result i32)
+                (call $tick)
+                (global.set $count (i32.add (global.get $count) (i32.const 1)))
+                (global.get $count)))
+        "#,
+    )?;
+
+    // The instance resets itself from an import it calls.
+    let slot = Arc::new(Mutex::new(None::<Instance>));
+    let outcome = Arc::new(Mutex::new(None));
+    let tick = {
+        let (slot, outcome) = (slot.clone(), outcome.clone());
+        let ty = FunctionType::new(vec![], vec![]);
+        Function::new(&store, ty, move |_| {
+            if let Some(instance) = &*slot.lock().unwrap() {
+                *outcome.lock().unwrap() = Some(instance.reset(false));
+            }
+            Ok(vec![])
+        })
+    };
+    let instance = Instance::new(&module, &imports! { "env" => { "tick" => tick } })?;
+    let increment = instance.get_native_function::<(), i32>("increment")?;
+    assert_eq!(increment.call()?, 1);
+    *slot.lock().unwrap() = Some(instance.clone());
+    assert_eq!(increment.call()?, 2);
+    let error = outcome.lock().unwrap().take().unwrap().unwrap_err();
+    let error = error.downcast::<ResetError>()?;
+    assert!(matches!(
+        error,
+        ResetError::CallInFlight { active_calls: 1 }
+    ));
+    assert_eq!(error.code(), ErrorCode::ResetCallInFlight);
+    assert!(error.failure_kind().is_transient());
+
+    // Once the call returned, the instance can be reset.
+    slot.lock().unwrap().take();
+    instance.reset(false)?;
+    assert_eq!(increment.call()?, 1);
+    Ok(())
+}
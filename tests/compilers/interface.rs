@@ -0,0 +1,186 @@
+//! Text descriptions of the imports and exports of modules.
+//!
+//! `snapshots/interface.wat` and `snapshots/interface.wit` hold the descriptions of `WAT`.
+
+use anyhow::Result;
+use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{Universal, UniversalExecutableRef};
+
+const WAT: &str = r#"
+    (module $contract
+        (import "env" "log" (func $log (param $ptr i32) (param $len i32)))
+        (import "env" "memory" (memory 1 16))
+        (import "host" "now" (func $now (result i64)))
+        (import "env" "gas_price" (global $gas_price i64))
+        (import "host" "counter" (global $counter (mut i32)))
+        (table $table 2 10 funcref)
+        (global $total (mut i64) (i64.const 0))
+        (global $scale f64 (f64.const 2.5))
+        (func $transfer (export "transfer") (param $to i32) (param $amount i64) (result i32)
+            (global.set $total (i64.add (global.get $total) (local.get $amount)))
+            (local.get $to))
+        (func (export "scaled") (param $x i32) (param f32) (result f32)
+            (local $unused i32)
+            (local.get 1))
+        (export "log" (func $log))
+        (export "total" (global $total))
+        (export "scale" (global $scale))
+        (export "memory" (memory 0))
+        (export "table" (table $table)))
+"#;
+
+const WAT_SNAPSHOT: &str = include_str!("snapshots/interface.wat");
+const WIT_SNAPSHOT: &str = include_str!("snapshots/interface.wit");
+
+#[compiler_test(interface)]
+fn interface_matches_snapshots(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    assert_eq!(module.interface_text(InterfaceFormat::Wat), WAT_SNAPSHOT);
+    assert_eq!(
+        module.interface_text(InterfaceFormat::WitLike),
+        WIT_SNAPSHOT
+    );
+    Ok(())
+}
+
+#[compiler_test(interface)]
+fn interface_does_not_depend_on_loading(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let store = Store::new(&engine);
+    let executable = engine.compile_universal(&wat2wasm(WAT.as_bytes())?, store.tunables())?;
+    let serialized = executable.serialize().unwrap();
+    let deserialized = unsafe { UniversalExecutableRef::deserialize(&serialized)? };
+    for format in &[InterfaceFormat::Wat, InterfaceFormat::WitLike] {
+        let text = Module::new(&store, WAT)?.interface_text(*format);
+        assert_eq!(executable.interface_text(*format), text);
+        let module = Module::from_executable(&store, &executable)?;
+        assert_eq!(module.interface_text(*format), text);
+        let module = Module::from_executable(&store, &deserialized)?;
+        assert_eq!(module.interface_text(*format), text);
+    }
+    Ok(())
+}
+
+#[compiler_test(interface)]
+fn unnamed_functions_and_params_are_positional(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (import "env" "abort" (func (param i32 i32)))
+            (func (export "add") (param i32 i32) (result i32)
+                (i32.add (local.get 0) (local.get 1))))
+        "#,
+    )?;
+    assert_eq!(
+        module.interface_text(InterfaceFormat::Wat),
+        concat!(
+            ";; module <module>\n",
+            ";; interface sha256:",
+            "5d15bd64c369a39e49fc80d799535a4933394944b938c93b7afe1aaac9f3640c\n",
+            "(import \"env\" \"abort\" (func $f0 (param $p0 i32) (param $p1 i32)))\n",
+            "(export \"add\" (func $f1 (param $p0 i32) (param $p1 i32) (result i32)))\n",
+        )
+    );
+    assert_eq!(
+        module.interface_text(InterfaceFormat::WitLike),
+        concat!(
+            "// module <module>\n",
+            "// interface sha256:",
+            "15e2433f80a83e136b83e3214430fd63c8d78880bbb158846dfc845ebaeeb494\n",
+            "\n",
+            "import env {\n",
+            "  abort: func(p0: i32, p1: i32)\n",
+            "}\n",
+            "\n",
+            "export {\n",
+            "  add: func(p0: i32, p1: i32) -> i32\n",
+            "}\n",
+        )
+    );
+    Ok(())
+}
+
+#[compiler_test(interface)]
+fn types_are_listed_in_declaration_order(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let store = Store::new(&engine);
+    let executable = engine.compile_universal(&wat2wasm(WAT.as_bytes())?, store.tunables())?;
+    let serialized = executable.serialize().unwrap();
+    let deserialized = unsafe { UniversalExecutableRef::deserialize(&serialized)? };
+    let modules = vec![
+        Module::new(&store, WAT)?,
+        Module::from_executable(&store, &deserialized)?,
+    ];
+
+    let log = FunctionType::new(vec![Type::I32, Type::I32], vec![]);
+    let memory = MemoryType::new(1, Some(16), false);
+    for module in modules {
+        assert_eq!(module.imports().len(), 5);
+        assert_eq!(
+            module.imports().collect::<Vec<_>>(),
+            vec![
+                ("env", "log", ExternType::Function(log.clone())),
+                ("env", "memory", ExternType::Memory(memory)),
+                (
+                    "host",
+                    "now",
+                    ExternType::Function(FunctionType::new(vec![], vec![Type::I64]))
+                ),
+                (
+                    "env",
+                    "gas_price",
+                    ExternType::Global(GlobalType::new(Type::I64, Mutability::Const))
+                ),
+                (
+                    "host",
+                    "counter",
+                    ExternType::Global(GlobalType::new(Type::I32, Mutability::Var))
+                ),
+            ]
+        );
+        let names = module.exports().map(|(name, _)| name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            ["transfer", "scaled", "log", "total", "scale", "memory", "table"]
+        );
+
+        let functions = module.imports().functions().collect::<Vec<_>>();
+        assert_eq!(functions[0], ("env", "log", log.clone()));
+        assert_eq!(functions[1].1, "now");
+        assert_eq!(functions.len(), 2);
+        let globals = module.imports().globals().map(|(_, name, _)| name);
+        assert_eq!(globals.collect::<Vec<_>>(), ["gas_price", "counter"]);
+        assert_eq!(module.imports().memories().count(), 1);
+        assert_eq!(module.imports().tables().count(), 0);
+
+        let functions = module.exports().functions().collect::<Vec<_>>();
+        assert_eq!(
+            functions[0],
+            (
+                "transfer",
+                FunctionType::new(vec![Type::I32, Type::I64], vec![Type::I32])
+            )
+        );
+        assert_eq!(functions[2], ("log", log.clone()));
+        assert_eq!(functions.len(), 3);
+        let globals = module.exports().globals().map(|(name, ty)| (name, ty.ty));
+        assert_eq!(
+            globals.collect::<Vec<_>>(),
+            [("total", Type::I64), ("scale", Type::F64)]
+        );
+        assert_eq!(
+            module.exports().memories().collect::<Vec<_>>(),
+            [("memory", memory)]
+        );
+        let tables = module.exports().tables().collect::<Vec<_>>();
+        assert_eq!(
+            tables,
+            [("table", TableType::new(Type::FuncRef, 2, Some(10)))]
+        );
+    }
+    Ok(())
+}
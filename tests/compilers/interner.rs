@@ -0,0 +1,158 @@
+//! Interning the strings guests pass to host functions.
+
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (import "env" "memory" (memory 1))
+        (import "env" "key" (func $key (param i32 i32)))
+        (data (i32.const 0) "prefix:alice")
+        (func (export "key") (param i32 i32)
+            (call $key (local.get 0) (local.get 1)))
+        (func (export "overwrite") (param i32 i32)
+            (i32.store8 (local.get 0) (local.get 1))))
+"#;
+
+/// What the `key` import read, with the stats of the interner after reading it.
+type Keys = Arc<Mutex<Vec<(Result<InternedStr, GuestTypeError>, InternerStats)>>>;
+
+/// An instance whose `key` import reads strings through the interner attached to it.
+fn instance(config: &crate::Config) -> Result<(Instance, Keys)> {
+    let store = config.store();
+    let memory = Memory::new(&store, MemoryType::new(1, None, false))?;
+    let keys = Keys::default();
+    let (guest_memory, recorded) = (memory.clone(), keys.clone());
+    let key = Function::new(&store, ([Type::I32, Type::I32], []), move |args| {
+        let context = CallContext::current().unwrap();
+        let interner = context.extension::<StringInterner>().unwrap();
+        let (ptr, len) = (args[0].unwrap_i32() as u32, args[1].unwrap_i32() as u32);
+        let key = interner.get(&guest_memory.view(), ptr, len);
+        recorded.lock().unwrap().push((key, interner.stats()));
+        Ok(vec![])
+    });
+    let module = Module::new(&store, WAT)?;
+    let imports = imports! { "env" => { "memory" => memory, "key" => key } };
+    let instance = Instance::new(&module, &imports)?;
+    instance.insert_extension(StringInterner::new(16));
+    Ok((instance, keys))
+}
+
+#[compiler_test(interner)]
+fn strings_are_interned(config: crate::Config) -> Result<()> {
+    let (instance, keys) = instance(&config)?;
+    let key = instance.get_native_function::<(i32, i32), ()>("key")?;
+    key.call(0, 6)?;
+    key.call(0, 6)?;
+    key.call(7, 5)?;
+
+    let keys = keys.lock().unwrap();
+    let strings = keys
+        .iter()
+        .map(|(key, _)| key.clone().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(&*strings[0], "prefix");
+    assert!(InternedStr::ptr_eq(&strings[0], &strings[1]));
+    assert_eq!(&*strings[2], "alice");
+    let stats = keys.iter().map(|(_, stats)| *stats).collect::<Vec<_>>();
+    assert_eq!((stats[0].hits, stats[0].misses), (0, 1));
+    assert_eq!((stats[1].hits, stats[1].misses), (1, 1));
+    assert_eq!(
+        stats[2],
+        InternerStats {
+            hits: 1,
+            misses: 2,
+            strings: 2,
+            bytes: 11,
+        }
+    );
+    Ok(())
+}
+
+#[compiler_test(interner)]
+fn overwritten_strings_are_read_again(config: crate::Config) -> Result<()> {
+    let (instance, keys) = instance(&config)?;
+    let key = instance.get_native_function::<(i32, i32), ()>("key")?;
+    let overwrite = instance.get_native_function::<(i32, i32), ()>("overwrite")?;
+    key.call(0, 6)?;
+    overwrite.call(0, i32::from(b'P'))?;
+    key.call(0, 6)?;
+    // Once restored, the bytes are those of the string interned first.
+    overwrite.call(0, i32::from(b'p'))?;
+    key.call(0, 6)?;
+
+    let keys = keys.lock().unwrap();
+    assert_eq!(&*keys[0].0.clone()?, "prefix");
+    assert_eq!(&*keys[1].0.clone()?, "Prefix");
+    assert_eq!(keys[1].1.misses, 2);
+    let again = keys[2].0.clone()?;
+    assert!(InternedStr::ptr_eq(&keys[0].0.clone()?, &again));
+    assert_eq!(keys[2].1.hits, 1);
+    Ok(())
+}
+
+#[compiler_test(interner)]
+fn invalid_strings_are_not_interned(config: crate::Config) -> Result<()> {
+    let (instance, keys) = instance(&config)?;
+    let key = instance.get_native_function::<(i32, i32), ()>("key")?;
+    let overwrite = instance.get_native_function::<(i32, i32), ()>("overwrite")?;
+    overwrite.call(1, 0xff)?;
+    key.call(0, 6)?;
+    key.call(0, 6)?;
+    key.call(65530, 16)?;
+
+    let keys = keys.lock().unwrap();
+    for (key, _) in &keys[..2] {
+        let error = key.clone().unwrap_err();
+        assert_eq!(error, GuestTypeError::InvalidUtf8 { offset: 0 });
+        assert_eq!(error.code(), ErrorCode::GuestInvalidUtf8);
+    }
+    assert_eq!(keys[1].1.misses, 2);
+    assert_eq!(keys[1].1.strings, 0);
+    assert!(matches!(
+        keys[2].0,
+        Err(GuestTypeError::OutOfBounds { offset: 65530, .. })
+    ));
+    Ok(())
+}
+
+#[compiler_test(interner)]
+fn least_recently_read_strings_are_evicted(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let memory = Memory::new(&store, MemoryType::new(1, None, false))?;
+    let view = memory.view::<u8>();
+    GuestString::new(0, 14).write(&view, "aaaabbbbccdddd")?;
+    let (a, b, c) = ((0, 4), (4, 4), (8, 2));
+
+    // At most two strings.
+    let interner = StringInterner::new(2);
+    let read = |(ptr, len): (u32, u32)| interner.get(&view, ptr, len).unwrap();
+    let first_a = read(a);
+    read(b);
+    read(a);
+    read(c);
+    // `b` was read least recently.
+    assert!(InternedStr::ptr_eq(&first_a, &read(a)));
+    assert_eq!(interner.stats().hits, 2);
+    read(b);
+    assert_eq!(interner.stats().misses, 4);
+    assert_eq!(interner.stats().strings, 2);
+
+    // At most 8 bytes.
+    let interner = StringInterner::with_max_bytes(16, 8);
+    let read = |(ptr, len): (u32, u32)| interner.get(&view, ptr, len).unwrap();
+    read(a);
+    read(b);
+    read(c);
+    assert_eq!(interner.stats().strings, 2);
+    assert_eq!(interner.stats().bytes, 6);
+    read(b);
+    assert_eq!(interner.stats().hits, 1);
+    // Strings longer than all the bytes are not kept.
+    read((0, 12));
+    read((0, 12));
+    assert_eq!(interner.stats().hits, 1);
+    assert_eq!(interner.stats().strings, 2);
+    Ok(())
+}
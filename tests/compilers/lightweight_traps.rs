@@ -0,0 +1,135 @@
+//! Traps raised without capturing a backtrace nor allocating their error.
+
+use crate::instance_layout::count_allocations;
+use anyhow::Result;
+use std::ptr;
+use std::sync::Arc;
+use std::thread;
+use wasmer::*;
+use wasmer_types::FastGasCounter;
+
+const WAT: &str = r#"
+(module
+    (import "host" "gas" (func $gas (param i32)))
+    (func $burn (export "burn")
+        (call $gas (i32.const 1000000)))
+    (func (export "burn_nested")
+        (call $burn))
+    (func (export "crash")
+        unreachable))
+"#;
+
+/// An instance of `module` with a gas limit of 1000, which `burn` exceeds.
+fn instance(module: &Module) -> Result<(Instance, Box<FastGasCounter>)> {
+    let store = module.store();
+    let mut counter = Box::new(FastGasCounter::new(1000, 1));
+    let gas = Function::new_native(store, |_: i32| {});
+    let instance = Instance::new_with_config(
+        module,
+        unsafe { InstanceConfig::default().with_counter(ptr::addr_of_mut!(*counter)) },
+        &imports! { "host" => { "gas" => gas } },
+    )?;
+    Ok((instance, counter))
+}
+
+#[compiler_test(lightweight_traps)]
+fn gas_exhaustion_does_not_allocate(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let (instance, _counter) = instance(&module)?;
+    let burn = instance.get_native_function::<(), ()>("burn")?;
+    let crash = instance.get_native_function::<(), ()>("crash")?;
+    burn.call().unwrap_err();
+
+    let (allocations, error) = count_allocations(|| burn.call().unwrap_err());
+    assert!(allocations > 0);
+    drop(error);
+
+    store.set_lightweight_traps(&[TrapCode::GasExceeded]);
+    burn.call().unwrap_err();
+    let (allocations, error) = count_allocations(|| burn.call().unwrap_err());
+    assert_eq!(allocations, 0);
+    assert_eq!(error.trap_code(), Some(TrapCode::GasExceeded));
+    assert_eq!(error.code(), ErrorCode::TrapGasExceeded);
+
+    // Other traps are raised as usual.
+    crash.call().unwrap_err();
+    let (allocations, error) = count_allocations(|| crash.call().unwrap_err());
+    assert!(allocations > 0);
+    assert_eq!(error.trap_code(), Some(TrapCode::UnreachableCodeReached));
+
+    // Setting no codes restores the usual traps.
+    store.set_lightweight_traps(&[]);
+    let (allocations, _) = count_allocations(|| burn.call().unwrap_err());
+    assert!(allocations > 0);
+    Ok(())
+}
+
+#[compiler_test(lightweight_traps)]
+fn traces_are_looked_up_when_asked_for(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let (instance, _counter) = instance(&module)?;
+    let burn_nested = instance.get_native_function::<(), ()>("burn_nested")?;
+    let full = burn_nested.call().unwrap_err();
+    assert!(!full.trace().is_empty());
+
+    store.set_lightweight_traps(&[TrapCode::GasExceeded]);
+    let light = burn_nested.call().unwrap_err();
+    let frames = |error: &RuntimeError| {
+        error
+            .trace()
+            .iter()
+            .map(|frame| (frame.func_index(), frame.module_offset()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(frames(&light), frames(&full)[..1]);
+    let display = light.to_string();
+    assert!(
+        display.starts_with("RuntimeError: gas limit exceeded [W0412]\n    at "),
+        "{}",
+        display
+    );
+    assert_eq!(display.lines().count(), 2);
+    Ok(())
+}
+
+#[compiler_test(lightweight_traps)]
+fn concurrent_traps_keep_their_own_payloads(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    store.set_lightweight_traps(&[TrapCode::GasExceeded, TrapCode::UnreachableCodeReached]);
+    let module = Arc::new(Module::new(&store, WAT)?);
+    let threads = (0..8)
+        .map(|_| {
+            let module = Arc::clone(&module);
+            thread::spawn(move || -> Result<()> {
+                let (instance, _counter) = instance(&module)?;
+                let burn = instance.get_native_function::<(), ()>("burn")?;
+                let crash = instance.get_native_function::<(), ()>("crash")?;
+                // More errors than payloads are alive at the same time.
+                let mut errors = Vec::new();
+                for round in 0..100 {
+                    for _ in 0..(round % 3) {
+                        errors.push((TrapCode::GasExceeded, 1, burn.call()));
+                    }
+                    errors.push((TrapCode::UnreachableCodeReached, 3, crash.call()));
+                    if round % 10 == 9 {
+                        errors.truncate(errors.len() / 2);
+                    }
+                }
+                for (code, function, error) in errors {
+                    let error = error.unwrap_err();
+                    assert_eq!(error.trap_code(), Some(code));
+                    let trace = error.trace();
+                    assert_eq!(trace.len(), 1);
+                    assert_eq!(trace[0].func_index(), function);
+                }
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap()?;
+    }
+    Ok(())
+}
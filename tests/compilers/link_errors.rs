@@ -0,0 +1,129 @@
+//! Reporting every import that cannot be linked at once.
+
+use anyhow::Result;
+use wasmer::*;
+
+#[compiler_test(link_errors)]
+fn every_unlinkable_import_is_reported(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (import "env" "present" (func (param i32)))
+            (import "env" "absent" (func (result i64)))
+            (import "env" "global" (func))
+            (import "env" "signature" (func (param i32) (result i32))))
+        "#,
+    )?;
+    let nothing = Function::new(&store, FunctionType::new(vec![], vec![]), |_| Ok(vec![]));
+    let imports = imports! {
+        "env" => {
+            "present" => Function::new(
+                &store,
+                FunctionType::new(vec![Type::I32], vec![]),
+                |_| Ok(vec![]),
+            ),
+            "global" => Global::new(&store, Value::F32(1.0)),
+            "signature" => nothing,
+        }
+    };
+
+    let error = match Instance::new(&module, &imports) {
+        Err(InstantiationError::Link(error)) => error,
+        Err(error) => panic!("unexpected error: {}", error),
+        Ok(_) => panic!("the imports linked"),
+    };
+    let function = |params: &[Type], results: &[Type]| {
+        ExternType::Function(FunctionType::new(params.to_vec(), results.to_vec()))
+    };
+    let imports = match &error {
+        LinkError::Imports(imports) => imports,
+        error => panic!("unexpected error: {}", error),
+    };
+    assert_eq!(imports.len(), 3);
+    for (module, _, _) in imports {
+        assert_eq!(module, "env");
+    }
+    match &imports[0] {
+        (_, field, ImportError::UnknownImport(expected)) => {
+            assert_eq!(field, "absent");
+            assert_eq!(expected, &function(&[], &[Type::I64]));
+        }
+        (_, field, error) => panic!("unexpected error for {}: {}", field, error),
+    }
+    match &imports[1] {
+        (_, field, ImportError::IncompatibleType(expected, found)) => {
+            assert_eq!(field, "global");
+            assert_eq!(expected, &function(&[], &[]));
+            assert_eq!(
+                found,
+                &ExternType::Global(GlobalType::new(Type::F32, Mutability::Const))
+            );
+        }
+        (_, field, error) => panic!("unexpected error for {}: {}", field, error),
+    }
+    match &imports[2] {
+        (_, field, ImportError::IncompatibleType(expected, found)) => {
+            assert_eq!(field, "signature");
+            assert_eq!(expected, &function(&[Type::I32], &[Type::I32]));
+            assert_eq!(found, &function(&[], &[]));
+        }
+        (_, field, error) => panic!("unexpected error for {}: {}", field, error),
+    }
+    assert_eq!(error.code(), ErrorCode::LinkImports);
+    assert_eq!(error.failure_kind(), FailureKind::Permanent);
+    let message = error.to_string();
+    assert!(message.starts_with("3 imports cannot be linked: "));
+    assert!(message.contains(r#""env"."absent": unknown import"#));
+    assert!(message.contains(r#""env"."global": incompatible import type"#));
+    assert!(message.ends_with("[W0311]"));
+    Ok(())
+}
+
+#[compiler_test(link_errors)]
+fn rejected_imports_fail_right_away(config: crate::Config) -> Result<()> {
+    struct Rejecting;
+
+    impl Resolver for Rejecting {
+        fn resolve(&self, _index: u32, _module: &str, _field: &str) -> Option<Export> {
+            None
+        }
+
+        fn try_resolve(
+            &self,
+            _index: u32,
+            _module: &str,
+            field: &str,
+        ) -> Result<Option<Export>, Box<dyn std::error::Error + Send + Sync>> {
+            if field == "rejected" {
+                Err("not for you".into())
+            } else {
+                Ok(None)
+            }
+        }
+    }
+
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (import "env" "absent" (func))
+            (import "env" "rejected" (func)))
+        "#,
+    )?;
+    match Instance::new(&module, &Rejecting) {
+        Err(InstantiationError::Link(LinkError::Import(
+            _,
+            field,
+            ImportError::Rejected(error),
+        ))) => {
+            assert_eq!(field, "rejected");
+            assert_eq!(error.to_string(), "not for you");
+        }
+        Err(error) => panic!("unexpected error: {}", error),
+        Ok(_) => panic!("the imports linked"),
+    }
+    Ok(())
+}
@@ -4,17 +4,96 @@
 #[macro_use]
 extern crate compiler_test_derive;
 
+mod abi_check;
+mod arg_policy;
+mod artifact_editor;
+mod artifact_id;
+mod backtrace_policy;
+mod bind_exports;
+mod bundle;
+mod bounds_checks;
+mod call_sequence;
+mod cancellation;
+mod chained_resolver;
+mod code_memory;
 mod config;
+mod copy_on_write;
+mod cross_store;
+#[cfg(feature = "conformance")]
+mod conformance;
+mod custom_sections;
+mod custom_traps;
+mod data_segments;
+mod degenerate_modules;
 mod deterministic;
+mod diagnostics;
+mod dynamic_calls;
+mod dynamic_gas;
+mod error_codes;
+mod events;
+mod execution_plan;
+mod exceptions;
+mod extensions;
+mod external_data;
+mod failure_kind;
 mod fast_gas_metering;
+mod feature_errors;
+mod frame_pointer;
+mod function_hashes;
+mod guest_types;
+mod host_tables;
+mod hot_code;
 mod imports;
+mod instance_group;
+mod instance_layout;
+mod instance_registry;
+mod instance_reset;
+mod interface;
+mod interner;
 mod issues;
+mod lightweight_traps;
+mod link_errors;
+mod memory_arbiter;
+mod memory_discard;
+mod memory_regions;
+mod memory_styles;
+mod metrics;
+mod module_stats;
 // mod multi_value_imports;
 mod compilation;
+mod compose;
+mod name_section;
 mod native_functions;
+mod non_send;
+mod overrides;
+mod page_allocator;
+mod pipelined_loading;
+mod pretouch;
+mod provenance;
+#[cfg(target_os = "linux")]
+mod readonly_instance;
+mod rebind;
+mod reclaim;
+mod replay;
+mod scoped_instance;
 mod serialize;
+mod snapshot;
+mod source_maps;
+mod specialization;
 mod stack_limiter;
+mod streaming;
+#[cfg(target_os = "linux")]
+mod subprocess;
+mod symbolication;
+mod table_provenance;
+mod teardown;
+mod tenants;
+mod trampoline_sets;
+mod trap_offsets;
 mod traps;
+mod unused_items;
+mod validation;
+mod view_cache;
 mod wast;
 
 pub use crate::config::{Compiler, Config, Engine};
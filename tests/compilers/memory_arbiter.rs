@@ -0,0 +1,140 @@
+//! Arbitration of the growth of memories across the instances of an engine.
+
+use anyhow::Result;
+use std::sync::Arc;
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (memory (export "memory") 1)
+        (func (export "grow") (param i32) (result i32)
+            (memory.grow (local.get 0))))
+"#;
+
+const ALICE: TenantId = TenantId(1);
+const BOB: TenantId = TenantId(2);
+
+const PAGE: u64 = WASM_PAGE_SIZE as u64;
+
+fn setup(
+    config: &crate::Config,
+    arbiter: Arc<FairShareArbiter>,
+) -> Result<(UniversalEngine, Module)> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    engine.set_memory_arbiter(arbiter);
+    let module = Module::new(&Store::new(&engine), WAT)?;
+    Ok((engine, module))
+}
+
+fn instantiate(
+    module: &Module,
+    config: InstanceConfig,
+) -> Result<(Instance, NativeFunc<i32, i32>)> {
+    let instance = Instance::new_with_config(module, config, &imports! {})?;
+    let grow = instance.get_native_function::<i32, i32>("grow")?;
+    Ok((instance, grow))
+}
+
+fn pages(instance: &Instance) -> Result<u32> {
+    Ok(instance.lookup_memory("memory").unwrap().size().0)
+}
+
+#[compiler_test(memory_arbiter)]
+fn tenants_are_held_to_their_soft_limits(config: crate::Config) -> Result<()> {
+    let arbiter = Arc::new(FairShareArbiter::new(16 * PAGE));
+    arbiter.set_tenant_limit(ALICE, 2 * PAGE);
+    arbiter.set_tenant_limit(BOB, 8 * PAGE);
+    let (engine, module) = setup(&config, arbiter.clone())?;
+    let (alice, alice_grow) = instantiate(&module, InstanceConfig::default().with_tenant(ALICE))?;
+    let (bob, bob_grow) = instantiate(&module, InstanceConfig::default().with_tenant(BOB))?;
+
+    assert_eq!(alice_grow.call(2)?, 1);
+    assert_eq!(alice_grow.call(1)?, -1);
+    assert_eq!(pages(&alice)?, 3);
+    assert_eq!(bob_grow.call(4)?, 1);
+    assert_eq!(bob_grow.call(4)?, 5);
+    assert_eq!(pages(&bob)?, 9);
+    assert_eq!(bob_grow.call(1)?, -1);
+
+    assert_eq!(arbiter.used_bytes(), 10 * PAGE);
+    assert_eq!(engine.tenant_usage(ALICE).memory_bytes, Some(2 * PAGE));
+    assert_eq!(engine.tenant_usage(BOB).memory_bytes, Some(8 * PAGE));
+    Ok(())
+}
+
+#[compiler_test(memory_arbiter)]
+fn the_hard_cap_holds_across_tenants(config: crate::Config) -> Result<()> {
+    let arbiter = Arc::new(FairShareArbiter::new(3 * PAGE));
+    let (_engine, module) = setup(&config, arbiter.clone())?;
+    let (_alice, alice_grow) = instantiate(&module, InstanceConfig::default().with_tenant(ALICE))?;
+    let (_untenanted, untenanted_grow) = instantiate(&module, InstanceConfig::default())?;
+
+    assert_eq!(alice_grow.call(2)?, 1);
+    assert_eq!(untenanted_grow.call(2)?, -1);
+    assert_eq!(untenanted_grow.call(1)?, 1);
+    assert_eq!(alice_grow.call(1)?, -1);
+    assert_eq!(arbiter.used_bytes(), 3 * PAGE);
+    Ok(())
+}
+
+#[compiler_test(memory_arbiter)]
+fn partial_grants_are_opt_in(config: crate::Config) -> Result<()> {
+    let arbiter = Arc::new(FairShareArbiter::new(16 * PAGE));
+    arbiter.set_tenant_limit(ALICE, 3 * PAGE);
+    let (_engine, module) = setup(&config, arbiter.clone())?;
+
+    let (strict, strict_grow) = instantiate(&module, InstanceConfig::default().with_tenant(ALICE))?;
+    assert_eq!(strict_grow.call(5)?, -1);
+    assert_eq!(pages(&strict)?, 1);
+    assert_eq!(arbiter.used_bytes(), 0);
+    drop((strict, strict_grow));
+
+    let config = InstanceConfig::default()
+        .with_tenant(ALICE)
+        .with_partial_memory_grants(true);
+    let (partial, partial_grow) = instantiate(&module, config)?;
+    assert_eq!(partial_grow.call(5)?, 1);
+    assert_eq!(pages(&partial)?, 4);
+    assert_eq!(arbiter.used_bytes(), 3 * PAGE);
+    // Nothing is left, and a single page is never granted partially.
+    assert_eq!(partial_grow.call(2)?, -1);
+    assert_eq!(partial_grow.call(1)?, -1);
+    Ok(())
+}
+
+#[compiler_test(memory_arbiter)]
+fn dropped_instances_release_their_bytes(config: crate::Config) -> Result<()> {
+    let arbiter = Arc::new(FairShareArbiter::new(4 * PAGE));
+    arbiter.set_tenant_limit(ALICE, 4 * PAGE);
+    let (engine, module) = setup(&config, arbiter.clone())?;
+
+    let (first, first_grow) = instantiate(&module, InstanceConfig::default().with_tenant(ALICE))?;
+    let (second, second_grow) = instantiate(&module, InstanceConfig::default().with_tenant(ALICE))?;
+    assert_eq!(first_grow.call(3)?, 1);
+    assert_eq!(second_grow.call(1)?, 1);
+    assert_eq!(second_grow.call(1)?, -1);
+    assert_eq!(engine.tenant_usage(ALICE).memory_bytes, Some(4 * PAGE));
+
+    drop((first, first_grow));
+    assert_eq!(arbiter.used_bytes(), PAGE);
+    assert_eq!(engine.tenant_usage(ALICE).memory_bytes, Some(PAGE));
+    assert_eq!(second_grow.call(3)?, 2);
+
+    drop((second, second_grow));
+    assert_eq!(arbiter.used_bytes(), 0);
+    assert_eq!(engine.tenant_usage(ALICE).memory_bytes, Some(0));
+    Ok(())
+}
+
+#[compiler_test(memory_arbiter)]
+fn failed_growth_releases_its_grant(config: crate::Config) -> Result<()> {
+    let arbiter = Arc::new(FairShareArbiter::new(u64::MAX));
+    let (engine, module) = setup(&config, arbiter.clone())?;
+    let (_instance, grow) = instantiate(&module, InstanceConfig::default().with_tenant(ALICE))?;
+
+    // The arbiter grants the pages, but they exceed what a memory may index.
+    assert_eq!(grow.call(65536)?, -1);
+    assert_eq!(arbiter.used_bytes(), 0);
+    assert_eq!(engine.tenant_usage(ALICE).memory_bytes, Some(0));
+    Ok(())
+}
@@ -0,0 +1,96 @@
+//! Giving the physical memory of ranges of linear memories back to the operating system.
+
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (memory (export "memory") 1)
+        (func (export "grow") (param i32) (result i32)
+            (memory.grow (local.get 0)))
+        (func (export "load") (param i32) (result i32)
+            (i32.load8_u (local.get 0)))
+        (func (export "fill") (param i32 i32 i32)
+            (memory.fill (local.get 0) (local.get 1) (local.get 2))))
+"#;
+
+const PAGE: u64 = WASM_PAGE_SIZE as u64;
+
+struct Guest {
+    memory: Memory,
+    grow: NativeFunc<i32, i32>,
+    load: NativeFunc<i32, i32>,
+    fill: NativeFunc<(i32, i32, i32), ()>,
+    _instance: Instance,
+}
+
+fn instantiate(config: &crate::Config) -> Result<Guest> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    Ok(Guest {
+        memory: instance.lookup_memory("memory").unwrap(),
+        grow: instance.get_native_function("grow")?,
+        load: instance.get_native_function("load")?,
+        fill: instance.get_native_function("fill")?,
+        _instance: instance,
+    })
+}
+
+#[compiler_test(memory_discard)]
+fn discarded_ranges_read_as_zeros(config: crate::Config) -> Result<()> {
+    let guest = instantiate(&config)?;
+    assert_eq!(guest.grow.call(3)?, 1);
+    guest.fill.call(0, 0xaa, 4 * PAGE as i32)?;
+    let dirty = guest.memory.resident_bytes();
+    assert!(dirty >= 4 * PAGE);
+
+    guest.memory.discard_range(PAGE, 2 * PAGE)?;
+    // Reading the range back maps the shared zero page, which counts as resident, so the
+    // released pages are counted before.
+    if cfg!(unix) {
+        assert!(guest.memory.resident_bytes() <= dirty - 2 * PAGE);
+    }
+    for offset in &[PAGE, PAGE + 12345, 3 * PAGE - 1] {
+        assert_eq!(guest.load.call(*offset as i32)?, 0);
+    }
+    assert_eq!(guest.load.call(PAGE as i32 - 1)?, 0xaa);
+    assert_eq!(guest.load.call(3 * PAGE as i32)?, 0xaa);
+
+    // The range stays accessible, and is backed by memory again once written.
+    guest.fill.call(PAGE as i32, 0x55, 1)?;
+    assert_eq!(guest.load.call(PAGE as i32)?, 0x55);
+    assert_eq!(guest.memory.size(), Pages(4));
+    Ok(())
+}
+
+#[compiler_test(memory_discard)]
+fn invalid_ranges_are_rejected(config: crate::Config) -> Result<()> {
+    let guest = instantiate(&config)?;
+    assert_eq!(guest.grow.call(1)?, 1);
+    guest.fill.call(0, 0xaa, 2 * PAGE as i32)?;
+
+    // Unaligned, past the end, and overflowing.
+    let ranges = [
+        (1, PAGE),
+        (0, PAGE - 1),
+        (PAGE, 2 * PAGE),
+        (2 * PAGE, PAGE),
+        (PAGE, u64::MAX - PAGE + 1),
+    ];
+    for &(offset, len) in &ranges {
+        assert_eq!(
+            guest.memory.discard_range(offset, len),
+            Err(MemoryError::InvalidRange { offset, len })
+        );
+    }
+    let error = guest.memory.discard_range(1, PAGE).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::MemoryInvalidRange);
+    assert_eq!(error.failure_kind(), FailureKind::Permanent);
+
+    // Rejected ranges are left untouched, and empty ones are fine.
+    assert_eq!(guest.load.call(0)?, 0xaa);
+    assert_eq!(guest.load.call(2 * PAGE as i32 - 1)?, 0xaa);
+    guest.memory.discard_range(2 * PAGE, 0)?;
+    Ok(())
+}
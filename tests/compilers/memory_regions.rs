@@ -0,0 +1,159 @@
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &str = r#"
+(module
+    (import "env" "memory" (memory 2 4))
+    (import "env" "copy" (func $copy (param i32 i32 i32)))
+    (func (export "copy") (param i32 i32 i32)
+        (call $copy (local.get 0) (local.get 1) (local.get 2)))
+    (func (export "grow") (param i32) (result i32)
+        (memory.grow (local.get 0))))
+"#;
+
+#[derive(Clone)]
+struct Env {
+    memory: Memory,
+}
+
+impl WasmerEnv for Env {}
+
+/// Copy `len` bytes from the input region to the heap region.
+fn copy(env: &Env, src: i32, dst: i32, len: i32) -> Result<(), RegionError> {
+    let context = CallContext::current().unwrap();
+    let regions = context.extension::<MemoryRegions>().unwrap();
+    let data = regions.read(&env.memory, "input", src as u64, len as u64)?;
+    regions.write(&env.memory, "heap", dst as u64, &data)
+}
+
+fn memory(store: &Store) -> Result<Memory> {
+    Ok(Memory::new(store, MemoryType::new(2, Some(4), false))?)
+}
+
+fn instance(store: &Store, memory: &Memory, regions: MemoryRegions) -> Result<Instance> {
+    let module = Module::new(store, WAT)?;
+    let env = Env {
+        memory: memory.clone(),
+    };
+    let config = InstanceConfig::default().with_extension(regions);
+    let instance = Instance::new_with_config(
+        &module,
+        config,
+        &imports! {
+            "env" => {
+                "memory" => memory.clone(),
+                "copy" => Function::new_native_with_env(store, env, copy),
+            },
+        },
+    )?;
+    Ok(instance)
+}
+
+fn regions() -> Result<MemoryRegions> {
+    Ok(MemoryRegions::new(&[
+        ("input", 0..0x10000, RegionPolicy::READ_ONLY),
+        ("heap", 0x10000..0x20000, RegionPolicy::READ_WRITE),
+    ])?)
+}
+
+fn region_error(result: Result<(), RuntimeError>) -> RegionError {
+    match result {
+        Err(e) => e.downcast::<RegionError>().unwrap(),
+        Ok(()) => panic!("the copy should have been rejected"),
+    }
+}
+
+#[compiler_test(memory_regions)]
+fn straddling_access(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let memory = memory(&store)?;
+    let instance = instance(&store, &memory, regions()?)?;
+    let copy = instance.get_native_function::<(i32, i32, i32), ()>("copy")?;
+    memory.view::<u8>()[0xfff0].set(42);
+    copy.call(0xfff0, 0x10000, 0x10)?;
+    assert_eq!(memory.view::<u8>()[0x10000].get(), 42);
+
+    let error = region_error(copy.call(0xfff0, 0x10000, 0x11));
+    assert_eq!(
+        error.to_string(),
+        "range 0xfff0+0x11 is not within 'input' (0x0..0x10000) [W0621]"
+    );
+    assert!(matches!(error, RegionError::OutOfRegion { .. }));
+    let error = region_error(copy.call(0, 0xfff8, 0x10));
+    assert!(matches!(error, RegionError::OutOfRegion { ref region, .. } if region == "heap"));
+    let error = region_error(copy.call(0, 0x1fff8, 0x10));
+    assert!(matches!(error, RegionError::OutOfRegion { .. }));
+    Ok(())
+}
+
+#[compiler_test(memory_regions)]
+fn policy_violation(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let memory = memory(&store)?;
+    let regions = regions()?;
+
+    let error = regions.write(&memory, "input", 0x1f40, b"x").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "pointer 0x1f40 is in 'input' (read-only), write rejected [W0622]"
+    );
+    assert_eq!(
+        regions.wasm_ptr::<u32>(&memory, "input", 0x100, 1).err(),
+        Some(RegionError::PolicyViolation {
+            ptr: 0x100,
+            region: "input".to_string(),
+            policy: RegionPolicy::READ_ONLY,
+            access: RegionAccess::ReadWrite,
+        })
+    );
+    let ptr = regions.wasm_ptr::<u32>(&memory, "heap", 0x10100, 2)?;
+    ptr.deref(&memory, 0, 2).unwrap()[1].set(7);
+    assert_eq!(
+        regions.read(&memory, "heap", 0x10104, 4)?,
+        7u32.to_le_bytes()
+    );
+    assert!(matches!(
+        regions.wasm_ptr::<u32>(&memory, "heap", 0x10101, 1),
+        Err(RegionError::Unaligned { align: 4, .. })
+    ));
+    assert_eq!(
+        regions.read(&memory, "stack", 0, 1).unwrap_err(),
+        RegionError::UnknownRegion("stack".to_string())
+    );
+
+    assert!(matches!(
+        MemoryRegions::new(&[
+            ("input", 0..0x100, RegionPolicy::READ_ONLY),
+            ("heap", 0xff..0x200, RegionPolicy::READ_WRITE),
+        ]),
+        Err(RegionError::InvalidRegions(_))
+    ));
+    assert!(matches!(
+        regions.wasm_ptr::<u32>(&memory, "heap", 0x1fffc, 2),
+        Err(RegionError::OutOfRegion { .. })
+    ));
+    Ok(())
+}
+
+#[compiler_test(memory_regions)]
+fn update_on_grow(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    // The heap extends to the end of the memory.
+    let memory = memory(&store)?;
+    let regions = regions()?.with_update(|size, regions| regions[1].range.end = size);
+    assert_eq!(regions.regions(&memory)?[1].range, 0x10000..0x20000);
+    let instance = instance(&store, &memory, regions)?;
+    let copy = instance.get_native_function::<(i32, i32, i32), ()>("copy")?;
+    let grow = instance.get_native_function::<i32, i32>("grow")?;
+    let error = region_error(copy.call(0, 0x20000, 0x10));
+    assert!(matches!(error, RegionError::OutOfRegion { .. }));
+
+    assert_eq!(grow.call(1)?, 2);
+    copy.call(0, 0x20000, 0x10)?;
+    let error = region_error(copy.call(0, 0x30000, 0x10));
+    assert!(matches!(
+        error,
+        RegionError::OutOfRegion { range, .. } if range == (0x10000..0x30000)
+    ));
+    Ok(())
+}
@@ -0,0 +1,268 @@
+//! Memory styles chosen from the maximum size declared by modules.
+
+use anyhow::Result;
+use wasmer::vm::MemoryStyle;
+use wasmer::*;
+
+/// A module whose memory may grow to `maximum` pages, with accessors for its last byte.
+fn module_wat(maximum: &str) -> String {
+    format!(
+        r#"(module
+            (memory (export "memory") 1 {})
+            (func (export "load") (param i32) (result i32)
+                local.get 0
+                i32.load8_u)
+            (func (export "store") (param i32)
+                local.get 0
+                i32.const 42
+                i32.store8)
+            (func (export "grow") (param i32) (result i32)
+                local.get 0
+                memory.grow))"#,
+        maximum
+    )
+}
+
+fn threshold_store(config: &crate::Config) -> Store {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = ThresholdTunables::for_target(engine.target());
+    Store::new_with_tunables(&*engine, tunables)
+}
+
+#[compiler_test(memory_styles)]
+fn styles_follow_declared_maximum(config: crate::Config) -> Result<()> {
+    let store = threshold_store(&config);
+    let style = |maximum: &str| -> Result<MemoryStyle> {
+        let module = Module::new(&store, module_wat(maximum))?;
+        let instance = Instance::new(&module, &imports! {})?;
+        Ok(instance.lookup_memory("memory").unwrap().style().clone())
+    };
+
+    // 1024 pages are 64 MiB.
+    assert!(matches!(
+        style("1024")?,
+        MemoryStyle::Static {
+            bound: Pages(1024),
+            ..
+        }
+    ));
+    assert!(matches!(style("1025")?, MemoryStyle::Dynamic { .. }));
+    assert!(matches!(style("")?, MemoryStyle::Dynamic { .. }));
+
+    // Host memories are meant to be imported, and are dynamic whatever their maximum.
+    let memory = Memory::new(&store, MemoryType::new(1, Some(16), false))?;
+    assert!(matches!(memory.style(), MemoryStyle::Dynamic { .. }));
+    Ok(())
+}
+
+#[compiler_test(memory_styles)]
+fn mismatched_artifact_is_rejected(config: crate::Config) -> Result<()> {
+    let threshold = threshold_store(&config);
+    let base = config.store();
+    let wat = module_wat("2048");
+    let wasm = wat2wasm(wat.as_bytes())?;
+
+    // Dynamic for the threshold tunables, but static for the base tunables.
+    let executable = threshold.engine().compile(&wasm, threshold.tunables())?;
+    let module = Module::from_executable(&base, &*executable)?;
+    match Instance::new(&module, &imports! {}) {
+        Err(error @ InstantiationError::MemoryStyleMismatch(_)) => {
+            let message = error.to_string();
+            assert!(message.starts_with("module compiled for another memory style: memory 0"));
+            assert!(message.ends_with("[W0322]"));
+            assert_eq!(error.code(), ErrorCode::InstantiationMemoryStyleMismatch);
+        }
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+
+    // The same executable instantiates fine with the tunables it was compiled with.
+    let module = Module::from_executable(&threshold, &*executable)?;
+    Instance::new(&module, &imports! {})?;
+    Ok(())
+}
+
+#[compiler_test(memory_styles)]
+fn out_of_bounds_accesses_trap_under_both_styles(config: crate::Config) -> Result<()> {
+    let store = threshold_store(&config);
+    for maximum in &["16", "2048"] {
+        let module = Module::new(&store, module_wat(maximum))?;
+        let instance = Instance::new(&module, &imports! {})?;
+        let load = instance.get_native_function::<i32, i32>("load")?;
+        let store = instance.get_native_function::<i32, ()>("store")?;
+        let grow = instance.get_native_function::<i32, i32>("grow")?;
+        let end = WASM_PAGE_SIZE as i32;
+
+        store.call(end - 1)?;
+        assert_eq!(load.call(end - 1)?, 42);
+        for result in &[
+            load.call(end).map(drop),
+            store.call(end),
+            load.call(-1).map(drop),
+        ] {
+            let error = result.as_ref().unwrap_err();
+            assert_eq!(error.trap_code(), Some(TrapCode::HeapAccessOutOfBounds));
+        }
+
+        // Growing makes the next page accessible, and only it.
+        assert_eq!(grow.call(1)?, 1);
+        store.call(end)?;
+        assert_eq!(load.call(end)?, 42);
+        let error = load.call(2 * end).unwrap_err();
+        assert_eq!(error.trap_code(), Some(TrapCode::HeapAccessOutOfBounds));
+    }
+    Ok(())
+}
+
+#[compiler_test(memory_styles)]
+fn host_growth_under_both_styles(config: crate::Config) -> Result<()> {
+    let store = threshold_store(&config);
+    for maximum in &["3", "2048"] {
+        let module = Module::new(&store, module_wat(maximum))?;
+        let instance = Instance::new(&module, &imports! {})?;
+        let memory = instance.lookup_memory("memory").unwrap();
+        let load = instance.get_native_function::<i32, i32>("load")?;
+        let grow = instance.get_native_function::<i32, i32>("grow")?;
+        let end = WASM_PAGE_SIZE as i32;
+
+        // The host grows the memory as `memory.grow` does, and both see the new pages.
+        assert_eq!(memory.grow(Pages(1))?, Pages(1));
+        assert_eq!(memory.size(), Pages(2));
+        assert_eq!(memory.data_size(), 2 * WASM_PAGE_SIZE as u64);
+        assert_eq!(grow.call(0)?, 2);
+        memory.view::<u8>()[end as usize].set(7);
+        assert_eq!(load.call(end)?, 7);
+
+        let readers = (0..4)
+            .map(|_| {
+                let memory = memory.clone();
+                std::thread::spawn(move || memory.view::<u8>()[WASM_PAGE_SIZE].get())
+            })
+            .collect::<Vec<_>>();
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), 7);
+        }
+    }
+
+    // Growing past the maximum fails and leaves the memory as it was.
+    let module = Module::new(&store, module_wat("3"))?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let memory = instance.lookup_memory("memory").unwrap();
+    assert_eq!(memory.grow(Pages(2))?, Pages(1));
+    match memory.grow(Pages(1)) {
+        Err(MemoryError::CouldNotGrow {
+            current,
+            attempted_delta,
+        }) => {
+            assert_eq!(current, Pages(3));
+            assert_eq!(attempted_delta, Pages(1));
+        }
+        result => panic!("unexpected result: {:?}", result),
+    }
+    assert_eq!(memory.size(), Pages(3));
+    assert_eq!(
+        instance.get_native_function::<i32, i32>("grow")?.call(0)?,
+        3
+    );
+    Ok(())
+}
+
+/// A store whose dynamic memories are reserved up to their maximum, if it is at most `limit`.
+fn reserving_store(config: &crate::Config, limit: u32) -> Store {
+    let engine = config.engine(config.compiler_config(false));
+    let mut tunables = ThresholdTunables::for_target(engine.target());
+    tunables.base.dynamic_memory_reservation = Some(Pages(limit));
+    Store::new_with_tunables(&*engine, tunables)
+}
+
+#[compiler_test(memory_styles)]
+fn reserved_dynamic_memories_never_move(config: crate::Config) -> Result<()> {
+    let store = reserving_store(&config, 4096);
+    let module = Module::new(&store, module_wat("2048"))?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let memory = instance.lookup_memory("memory").unwrap();
+    assert!(matches!(memory.style(), MemoryStyle::Dynamic { .. }));
+    assert_eq!(memory.base_stability(), BaseStability::Stable);
+    assert_eq!(memory.reservation_fallback(), None);
+
+    let load = instance.get_native_function::<i32, i32>("load")?;
+    let store = instance.get_native_function::<i32, ()>("store")?;
+    let grow = instance.get_native_function::<i32, i32>("grow")?;
+    let base = memory.data_ptr();
+    for pages in 1..200 {
+        assert_eq!(grow.call(1)?, pages);
+        assert_eq!(memory.data_ptr(), base);
+        let end = pages * WASM_PAGE_SIZE as i32;
+        store.call(end)?;
+        assert_eq!(load.call(end)?, 42);
+    }
+    assert_eq!(grow.call(2048 - 200)?, 200);
+    assert_eq!(memory.data_ptr(), base);
+    assert_eq!(grow.call(1)?, -1);
+    Ok(())
+}
+
+#[compiler_test(memory_styles)]
+fn reservation_falls_back_to_moving(config: crate::Config) -> Result<()> {
+    // The instance is kept alive along with its memory.
+    let memory = |store: &Store, maximum: &str| -> Result<(Memory, Instance)> {
+        let module = Module::new(store, module_wat(maximum))?;
+        let instance = Instance::new(&module, &imports! {})?;
+        Ok((instance.lookup_memory("memory").unwrap(), instance))
+    };
+    let store = reserving_store(&config, 1500);
+    let (reserved, _instance) = memory(&store, "1500")?;
+    assert_eq!(reserved.base_stability(), BaseStability::Stable);
+
+    let (unreserved, _instance) = memory(&store, "2048")?;
+    assert_eq!(unreserved.base_stability(), BaseStability::MayMoveOnGrow);
+    let fallback = unreserved.reservation_fallback().unwrap();
+    assert_eq!(
+        fallback,
+        MemoryError::MaximumMemoryTooLarge {
+            max_requested: Pages(2048),
+            max_allowed: Pages(1500),
+        }
+    );
+    let (unbounded, _instance) = memory(&store, "")?;
+    assert_eq!(unbounded.base_stability(), BaseStability::MayMoveOnGrow);
+    assert!(unbounded.reservation_fallback().is_some());
+
+    // Stores that do not reserve report no fallback, and static memories are stable.
+    let store = threshold_store(&config);
+    let (dynamic, _instance) = memory(&store, "2048")?;
+    assert_eq!(dynamic.base_stability(), BaseStability::MayMoveOnGrow);
+    assert_eq!(dynamic.reservation_fallback(), None);
+    let (fixed, _instance) = memory(&store, "16")?;
+    assert_eq!(fixed.base_stability(), BaseStability::Stable);
+    Ok(())
+}
+
+#[compiler_test(memory_styles)]
+fn pins_keep_memories_from_moving(config: crate::Config) -> Result<()> {
+    let store = threshold_store(&config);
+    let module = Module::new(&store, module_wat(""))?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let memory = instance.lookup_memory("memory").unwrap();
+    assert_eq!(memory.base_stability(), BaseStability::MayMoveOnGrow);
+    let load = instance.get_native_function::<i32, i32>("load")?;
+    let grow = instance.get_native_function::<i32, i32>("grow")?;
+
+    {
+        let pin = memory.pin()?;
+        let second = memory.pin()?;
+        let base = pin.data_ptr();
+        pin.view::<u8>()[7].set(42);
+        // Growing would move the memory.
+        assert_eq!(grow.call(1)?, -1);
+        drop(second);
+        assert_eq!(grow.call(1)?, -1);
+        assert_eq!(memory.size(), Pages(1));
+        assert_eq!(pin.data_ptr(), base);
+        assert_eq!(pin.view::<u8>()[7].get(), 42);
+    }
+
+    assert_eq!(grow.call(1)?, 1);
+    assert_eq!(memory.size(), Pages(2));
+    assert_eq!(load.call(7)?, 42);
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use anyhow::Result;
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_engine_universal::Universal;
+
+fn engine_with_sink(config: &crate::Config) -> (UniversalEngine, Arc<AtomicMetricsSink>) {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    (engine, sink)
+}
+
+#[compiler_test(metrics)]
+fn lifecycle_counters(config: crate::Config) -> Result<()> {
+    let (engine, sink) = engine_with_sink(&config);
+    let store = Store::new(&engine);
+
+    let module = Module::new(&store, r#"(module (func (export "run") unreachable))"#)?;
+    let snapshot = sink.snapshot();
+    assert_eq!(snapshot.compilations_started, 1);
+    assert_eq!(snapshot.compilations_succeeded, 1);
+    assert_eq!(snapshot.compilations_failed, 0);
+    assert_eq!(snapshot.artifacts_loaded, 1);
+    assert!(snapshot.code_bytes > 0);
+
+    assert!(engine
+        .compile_universal(b"\0asm\x01\0\0\0\xff", store.tunables())
+        .is_err());
+    let snapshot = sink.snapshot();
+    assert_eq!(snapshot.compilations_started, 2);
+    assert_eq!(snapshot.compilations_succeeded, 1);
+    assert_eq!(snapshot.compilations_failed, 1);
+    assert_eq!(snapshot.artifacts_loaded, 1);
+
+    let instance = Instance::new(&module, &imports! {})?;
+    assert_eq!(sink.snapshot().instances_created, 1);
+    let run = instance.get_native_function::<(), ()>("run")?;
+    run.call().expect_err("run should trap");
+    instance
+        .lookup_function("run")
+        .unwrap()
+        .call(&[])
+        .expect_err("run should trap");
+    let snapshot = sink.snapshot();
+    assert_eq!(snapshot.traps.len(), 1);
+    assert_eq!(snapshot.traps[&TrapCode::UnreachableCodeReached], 2);
+    assert_eq!(snapshot.gas_exhausted, 0);
+
+    drop(run);
+    drop(instance);
+    assert_eq!(sink.snapshot().instances_destroyed, 1);
+    assert_eq!(sink.snapshot().artifacts_unloaded, 0);
+    drop(module);
+    let snapshot = sink.snapshot();
+    assert_eq!(snapshot.artifacts_unloaded, 1);
+    assert_eq!(snapshot.instances_created, 1);
+
+    // Code memory is only released with the engine.
+    assert!(snapshot.code_bytes > 0);
+    drop(store);
+    drop(engine);
+    assert_eq!(sink.snapshot().code_bytes, 0);
+    Ok(())
+}
+
+#[compiler_test(metrics)]
+fn start_function_trap(config: crate::Config) -> Result<()> {
+    let (engine, sink) = engine_with_sink(&config);
+    let store = Store::new(&engine);
+    let module = Module::new(
+        &store,
+        r#"(module (func $start unreachable) (start $start))"#,
+    )?;
+    match Instance::new(&module, &imports! {}) {
+        Err(InstantiationError::Start(_)) => {}
+        _ => panic!("the start function should trap"),
+    }
+    let snapshot = sink.snapshot();
+    assert_eq!(snapshot.instances_created, 1);
+    assert_eq!(snapshot.instances_destroyed, 1);
+    assert_eq!(snapshot.traps[&TrapCode::UnreachableCodeReached], 1);
+    Ok(())
+}
+
+#[compiler_test(metrics)]
+fn no_sink(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let store = Store::new(&engine);
+    let module = Module::new(&store, r#"(module (func (export "run") unreachable))"#)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.get_native_function::<(), ()>("run")?;
+    run.call().expect_err("run should trap");
+
+    // Setting a sink later only affects artifacts loaded afterwards.
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    drop(run);
+    drop(instance);
+    drop(module);
+    let snapshot = sink.snapshot();
+    assert_eq!(snapshot.instances_destroyed, 0);
+    assert_eq!(snapshot.artifacts_unloaded, 0);
+    Ok(())
+}
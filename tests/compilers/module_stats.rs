@@ -0,0 +1,69 @@
+//! Counts and sizes declared by modules, read before instantiating them.
+
+use anyhow::Result;
+use wasmer::*;
+
+/// Two tables, a memory of 2 pages, and 70000 bytes of active data and 3 of passive data.
+fn wat() -> String {
+    format!(
+        r#"
+        (module
+            (import "env" "log" (func (param i32)))
+            (import "env" "table" (table 3 funcref))
+            (table 10 20 externref)
+            (memory 2 4)
+            (global i32 (i32.const 0))
+            (global (mut i64) (i64.const 0))
+            (func)
+            (func)
+            (data (i32.const 0) "{}")
+            (data "abc"))
+        "#,
+        "a".repeat(70000)
+    )
+}
+
+fn check(module: &Module) {
+    assert_eq!(
+        module.info(),
+        ModuleStats {
+            functions: 3,
+            imported_functions: 1,
+            globals: 2,
+            memories: 1,
+            max_memory_pages: 2,
+            table_minimums: vec![3, 10],
+            data_size: 70003,
+        }
+    );
+    assert_eq!(module.memory_types(), [MemoryType::new(2, Some(4), false)]);
+    assert_eq!(
+        module.table_types(),
+        [
+            TableType::new(Type::FuncRef, 3, None),
+            TableType::new(Type::ExternRef, 10, Some(20)),
+        ]
+    );
+}
+
+#[compiler_test(module_stats)]
+fn stats_are_read_from_modules(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, wat())?;
+    check(&module);
+
+    // Deserialized modules have the same stats.
+    let module = unsafe { Module::deserialize(&store, &module.serialize()?)? };
+    check(&module);
+    Ok(())
+}
+
+#[compiler_test(module_stats)]
+fn modules_without_memories_have_no_pages(config: crate::Config) -> Result<()> {
+    let info = Module::new(&config.store(), "(module)")?.info();
+    assert_eq!(info.memories, 0);
+    assert_eq!(info.max_memory_pages, 0);
+    assert!(info.table_minimums.is_empty());
+    assert_eq!(info.data_size, 0);
+    Ok(())
+}
@@ -0,0 +1,99 @@
+//! Function names from the name section of binaries.
+
+use anyhow::Result;
+use wasmer::*;
+
+/// The `add_one` module of the documentation, with a function trapping when called with a
+/// negative value.
+const WAT: &str = r#"
+    (module
+        (type $t0 (func (param i32) (result i32)))
+        (func $add_one (export "add_one") (type $t0) (param $value i32) (result i32)
+            (if (i32.lt_s (local.get $value) (i32.const 0))
+                (then (call $reject)))
+            (i32.add (local.get $value) (i32.const 1)))
+        (func $reject
+            unreachable))
+"#;
+
+#[compiler_test(name_section)]
+fn function_names_are_read_from_the_name_section(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    assert_eq!(
+        module.function_name(FunctionIndex::from_u32(0)),
+        Some("add_one")
+    );
+    assert_eq!(
+        module.function_name(FunctionIndex::from_u32(1)),
+        Some("reject")
+    );
+    assert_eq!(module.function_name(FunctionIndex::from_u32(2)), None);
+
+    let instance = Instance::new(&module, &imports! {})?;
+    let add_one = instance.get_native_function::<i32, i32>("add_one")?;
+    assert_eq!(add_one.call(1)?, 2);
+    let error = add_one.call(-1).unwrap_err();
+    // Singlepass only unwinds to the frame raising the trap, the callers being named too
+    // when there.
+    let trace = error.trace();
+    assert_eq!(trace[0].function_name(), Some("reject"));
+    for frame in &trace[1..] {
+        assert_eq!(frame.function_name(), Some("add_one"));
+    }
+    let message = error.to_string();
+    assert!(message.contains("\n    at reject ("), "{}", message);
+    assert_eq!(message.contains("\n    at add_one ("), trace.len() > 1);
+    Ok(())
+}
+
+/// A module of two functions, with a function name subsection holding `names`.
+fn module_with_names(count: u8, names: &[(u8, &str)]) -> Vec<u8> {
+    let mut subsection = vec![count];
+    for (index, name) in names {
+        subsection.push(*index);
+        subsection.push(name.len() as u8);
+        subsection.extend(name.as_bytes());
+    }
+    let mut section = vec![4];
+    section.extend(b"name");
+    section.push(1);
+    section.push(subsection.len() as u8);
+    section.extend(subsection);
+
+    let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+    // One type, `[] -> []`, of two functions, each with an empty body.
+    wasm.extend(&[0x01, 0x04, 0x01, 0x60, 0x00, 0x00]);
+    wasm.extend(&[0x03, 0x03, 0x02, 0x00, 0x00]);
+    wasm.extend(&[0x0a, 0x07, 0x02, 0x02, 0x00, 0x0b, 0x02, 0x00, 0x0b]);
+    wasm.push(0);
+    wasm.push(section.len() as u8);
+    wasm.extend(section);
+    wasm
+}
+
+#[compiler_test(name_section)]
+fn damaged_name_sections_keep_what_they_can(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let names = |wasm: Vec<u8>| -> Result<Vec<Option<String>>> {
+        let module = Module::new(&store, wasm)?;
+        Ok((0..2)
+            .map(|i| {
+                module
+                    .function_name(FunctionIndex::from_u32(i))
+                    .map(str::to_string)
+            })
+            .collect())
+    };
+    let some = |name: &str| Some(name.to_string());
+
+    let wasm = module_with_names(2, &[(0, "first"), (1, "second")]);
+    assert_eq!(names(wasm)?, [some("first"), some("second")]);
+    // The first name of a function given several wins, and the other names are kept.
+    let wasm = module_with_names(3, &[(0, "first"), (0, "again"), (1, "second")]);
+    assert_eq!(names(wasm)?, [some("first"), some("second")]);
+    // The names before a truncation are kept.
+    let wasm = module_with_names(2, &[(1, "second")]);
+    assert_eq!(names(wasm)?, [None, some("second")]);
+    Ok(())
+}
@@ -80,6 +80,33 @@ fn native_function_works_for_wasm(config: crate::Config) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[compiler_test(native_functions)]
+fn native_function_signature_is_checked_at_lookup(config: crate::Config) -> anyhow::Result<()> {
+    let store = config.store();
+    let wat = r#"(module
+        (memory (export "memory") 1)
+        (func (export "add") (param i32 i32) (result i32)
+           (i32.add (local.get 0) (local.get 1)))
+)"#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+
+    let mismatch = instance.get_native_function::<(i32, i32), i64>("add");
+    assert!(matches!(mismatch, Err(ExportError::IncompatibleType)));
+    let mismatch = instance.get_native_function::<(i64, i32), i32>("add");
+    assert!(matches!(mismatch, Err(ExportError::IncompatibleType)));
+    let not_a_function = instance.get_native_function::<(), ()>("memory");
+    assert!(matches!(not_a_function, Err(ExportError::IncompatibleType)));
+    match instance.get_native_function::<(), ()>("sub") {
+        Err(ExportError::Missing(name)) => assert_eq!(name, "sub"),
+        _ => panic!("`sub` should be missing"),
+    }
+
+    let add = instance.get_native_function::<(i32, i32), i32>("add")?;
+    assert_eq!(add.call(i32::MAX, 1)?, i32::MIN);
+    Ok(())
+}
+
 #[should_panic(
     expected = "Closures (functions with captured environments) are currently unsupported with native functions. See: https://github.com/wasmerio/wasmer/issues/1840"
 )]
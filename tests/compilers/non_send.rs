@@ -0,0 +1,123 @@
+//! Host functions and instances bound to the thread that created them.
+
+use anyhow::Result;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasmer::*;
+
+#[compiler_test(non_send)]
+fn fn_mut_state_without_locks(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (import "env" "record" (func $record (param i32) (result i32)))
+            (func (export "record") (param i32) (result i32)
+                (call $record (local.get 0))))
+        "#,
+    )?;
+    // Neither the counter nor the `Rc` could be captured by a `Send + Sync` closure.
+    let log = Rc::new(RefCell::new(vec![]));
+    let mut count = 0;
+    let record = Function::new_st(&store, ([Type::I32], [Type::I32]), {
+        let log = log.clone();
+        move |args| {
+            count += 1;
+            log.borrow_mut().push(args[0].unwrap_i32());
+            Ok(vec![Value::I32(count)])
+        }
+    });
+    assert_eq!(
+        record.ty(),
+        FunctionType::new(vec![Type::I32], vec![Type::I32])
+    );
+    let mut imports = NonSendImports::new(imports! {});
+    imports.define("env", "record", record);
+    let instance = NonSendInstance::new(&module, &imports)?;
+    let record = instance.get_native_function::<i32, i32>("record")?;
+    assert_eq!(record.call(10)?, 1);
+    assert_eq!(record.call(20)?, 2);
+    assert_eq!(record.call(30)?, 3);
+    assert_eq!(*log.borrow(), [10, 20, 30]);
+    Ok(())
+}
+
+#[compiler_test(non_send)]
+fn mixed_imports(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (import "env" "double" (func $double (param i32) (result i32)))
+            (import "env" "add" (func $add (param i32) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "run") (param i32) (result i32)
+                (call $add (call $double (local.get 0)))))
+        "#,
+    )?;
+    let mut total = 0;
+    let add = Function::new_st(&store, ([Type::I32], [Type::I32]), move |args| {
+        total += args[0].unwrap_i32();
+        Ok(vec![Value::I32(total)])
+    });
+    let mut imports = NonSendImports::new(imports! {
+        "env" => {
+            "double" => Function::new_native(&store, |x: i32| x * 2),
+            "add" => Function::new_native(&store, |_: i32| -> i32 { unreachable!() }),
+        }
+    });
+    // Single-threaded functions take precedence over the imports of the same name.
+    assert!(imports.define("env", "add", add).is_none());
+    let instance = NonSendInstance::new(&module, &imports)?;
+    let run = instance.get_native_function::<i32, i32>("run")?;
+    assert_eq!(run.call(1)?, 2);
+    assert_eq!(run.call(2)?, 6);
+    assert_eq!(instance.call_sequence(), 2);
+    assert_eq!(instance.lookup_memory("memory").unwrap().size(), Pages(1));
+    Ok(())
+}
+
+#[compiler_test(non_send)]
+fn reentrant_calls_trap(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (import "env" "callback" (func $callback))
+            (func (export "run") (call $callback)))
+        "#,
+    )?;
+    let slot: Rc<RefCell<Option<Rc<NonSendInstance>>>> = Rc::new(RefCell::new(None));
+    let callback = Function::new_st(&store, ([], []), {
+        let slot = slot.clone();
+        move |_| {
+            let instance = slot.borrow().clone();
+            if let Some(instance) = instance {
+                let run = instance
+                    .get_native_function::<(), ()>("run")
+                    .map_err(|e| RuntimeError::new(e.to_string()))?;
+                run.call()?;
+            }
+            Ok(vec![])
+        }
+    });
+    let mut imports = NonSendImports::new(imports! {});
+    imports.define("env", "callback", callback);
+    let instance = Rc::new(NonSendInstance::new(&module, &imports)?);
+    let run = instance.get_native_function::<(), ()>("run")?;
+    run.call()?;
+
+    *slot.borrow_mut() = Some(instance.clone());
+    let error = run.call().unwrap_err();
+    assert_eq!(
+        error.message(),
+        "single-threaded host function called while it is running"
+    );
+    // The failed call leaves the function callable again.
+    slot.borrow_mut().take();
+    run.call()?;
+    Ok(())
+}
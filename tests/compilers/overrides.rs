@@ -0,0 +1,176 @@
+//! Overriding imported host functions for the duration of a single call.
+
+use anyhow::Result;
+use std::sync::{Arc, Barrier, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (import "host" "now" (func $now (result i64)))
+        (import "host" "reenter" (func $reenter (result i64)))
+        (func (export "now") (result i64) (call $now))
+        (func (export "outer") (result i64) (call $reenter)))
+"#;
+
+/// An instance of `WAT` whose `now` import returns 7 and may be overridden. Its `reenter`
+/// import calls `now` back plainly then with an override returning 2, and returns the first
+/// result times 100 plus the second.
+fn instance(config: &crate::Config) -> Result<(Store, Instance)> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let slot: Arc<Mutex<Option<Instance>>> = Arc::new(Mutex::new(None));
+    let reenter = {
+        let slot = slot.clone();
+        let inner_store = store.clone();
+        let ty = FunctionType::new(vec![], vec![Type::I64]);
+        Function::new(&store, ty, move |_| {
+            let instance = slot.lock().unwrap().clone().unwrap();
+            let now = |overrides: &[(&str, &str, Function)]| {
+                let results = instance
+                    .guarded_call_with_overrides("now", &[], Guards::default(), overrides)
+                    .unwrap();
+                results[0].unwrap_i64()
+            };
+            let two = Function::new_native(&inner_store, || 2i64);
+            let result = now(&[]) * 100 + now(&[("host", "now", two)]);
+            Ok(vec![Value::I64(result)])
+        })
+    };
+    let imports = imports! {
+        "host" => {
+            "now" => Function::new_native(&store, || 7i64),
+            "reenter" => reenter,
+        },
+    };
+    let instance = Instance::new_with_config(
+        &module,
+        InstanceConfig::default().with_overridable_import("host", "now"),
+        &imports,
+    )?;
+    *slot.lock().unwrap() = Some(instance.clone());
+    Ok((store, instance))
+}
+
+fn call(
+    instance: &Instance,
+    entry: &str,
+    overrides: &[(&str, &str, Function)],
+) -> Result<i64, OverrideError> {
+    let results = instance.guarded_call_with_overrides(entry, &[], Guards::default(), overrides)?;
+    Ok(results[0].unwrap_i64())
+}
+
+#[compiler_test(overrides)]
+fn overrides_apply_to_one_call(config: crate::Config) -> Result<()> {
+    let (store, instance) = instance(&config)?;
+    let now = instance.lookup_function("now").unwrap();
+    assert_eq!(call(&instance, "now", &[])?, 7);
+    let one = Function::new_native(&store, || 1i64);
+    assert_eq!(call(&instance, "now", &[("host", "now", one)])?, 1);
+    // Neither the next call nor plain calls see the override.
+    assert_eq!(call(&instance, "now", &[])?, 7);
+    assert_eq!(now.call(&[])?[0].unwrap_i64(), 7);
+    Ok(())
+}
+
+#[compiler_test(overrides)]
+fn nested_calls_see_the_overrides_of_the_outer_call(config: crate::Config) -> Result<()> {
+    let (store, instance) = instance(&config)?;
+    assert_eq!(call(&instance, "outer", &[])?, 702);
+    let one = Function::new_native(&store, || 1i64);
+    assert_eq!(call(&instance, "outer", &[("host", "now", one)])?, 102);
+    assert_eq!(call(&instance, "now", &[])?, 7);
+    Ok(())
+}
+
+#[compiler_test(overrides)]
+fn overrides_do_not_apply_to_other_threads(config: crate::Config) -> Result<()> {
+    let (store, instance) = instance(&config)?;
+    // The override waits in the middle of its call for the other thread to call plainly.
+    let barrier = Arc::new(Barrier::new(2));
+    let one = {
+        let barrier = barrier.clone();
+        let ty = FunctionType::new(vec![], vec![Type::I64]);
+        Function::new(&store, ty, move |_| {
+            barrier.wait();
+            barrier.wait();
+            Ok(vec![Value::I64(1)])
+        })
+    };
+    let other = {
+        let instance = instance.clone();
+        let barrier = barrier.clone();
+        thread::spawn(move || {
+            barrier.wait();
+            let result = call(&instance, "now", &[]).unwrap();
+            barrier.wait();
+            result
+        })
+    };
+    assert_eq!(call(&instance, "now", &[("host", "now", one)])?, 1);
+    assert_eq!(other.join().unwrap(), 7);
+    Ok(())
+}
+
+#[compiler_test(overrides)]
+fn overrides_are_checked_before_the_call(config: crate::Config) -> Result<()> {
+    let (store, instance) = instance(&config)?;
+    let one = Function::new_native(&store, || 1i64);
+
+    let error = call(&instance, "now", &[("host", "reenter", one.clone())]).unwrap_err();
+    assert!(matches!(
+        &error,
+        OverrideError::NotOverridable { module, field } if module == "host" && field == "reenter"
+    ));
+    assert_eq!(error.code(), ErrorCode::OverrideNotOverridable);
+
+    let wrong = Function::new_native(&store, |x: i32| x);
+    let error = call(&instance, "now", &[("host", "now", wrong)]).unwrap_err();
+    match &error {
+        OverrideError::SignatureMismatch {
+            expected, given, ..
+        } => {
+            assert_eq!(*expected, FunctionType::new(vec![], vec![Type::I64]));
+            assert_eq!(*given, FunctionType::new(vec![Type::I32], vec![Type::I32]));
+        }
+        error => panic!("unexpected error: {}", error),
+    }
+    assert_eq!(error.code(), ErrorCode::OverrideSignatureMismatch);
+
+    let error = call(&instance, "missing", &[("host", "now", one.clone())]).unwrap_err();
+    assert!(matches!(
+        error,
+        OverrideError::Export(ExportError::Missing(_))
+    ));
+
+    let guards = Guards {
+        gas: None,
+        deadline: Some(Instant::now() - Duration::from_secs(1)),
+    };
+    let overrides = [("host", "now", one)];
+    let error = instance
+        .guarded_call_with_overrides("now", &[], guards, &overrides)
+        .unwrap_err();
+    assert!(matches!(error, OverrideError::Call(_)));
+    Ok(())
+}
+
+#[compiler_test(overrides)]
+fn imports_are_only_overridable_when_declared(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let imports = imports! {
+        "host" => {
+            "now" => Function::new_native(&store, || 7i64),
+            "reenter" => Function::new_native(&store, || 0i64),
+        },
+    };
+    let instance = Instance::new(&module, &imports)?;
+    let one = Function::new_native(&store, || 1i64);
+    let error = call(&instance, "now", &[("host", "now", one)]).unwrap_err();
+    assert!(matches!(error, OverrideError::NotOverridable { .. }));
+    assert_eq!(call(&instance, "now", &[])?, 7);
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! Loading artifacts into pages supplied by the embedder.
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::Arc;
+use wasmer::*;
+
+/// Hands out the pages of the system, counting what it is asked.
+#[derive(Debug, Default)]
+struct CountingAllocator {
+    system: SystemPageAllocator,
+    allocated: AtomicUsize,
+    freed: AtomicUsize,
+    protected: AtomicUsize,
+}
+
+unsafe impl PageAllocator for CountingAllocator {
+    fn page_size(&self) -> usize {
+        self.system.page_size()
+    }
+
+    fn alloc_rx(&self, len: usize) -> Result<*mut u8, String> {
+        assert_eq!(len % self.page_size(), 0);
+        self.allocated.fetch_add(len, SeqCst);
+        self.system.alloc_rx(len)
+    }
+
+    unsafe fn protect(
+        &self,
+        ptr: *mut u8,
+        len: usize,
+        protection: PageProtection,
+    ) -> Result<(), String> {
+        assert_eq!(ptr as usize % self.page_size(), 0);
+        assert_eq!(len % self.page_size(), 0);
+        assert_eq!(protection, PageProtection::ReadExecute);
+        self.protected.fetch_add(len, SeqCst);
+        self.system.protect(ptr, len, protection)
+    }
+
+    unsafe fn free(&self, ptr: *mut u8, len: usize) {
+        self.freed.fetch_add(len, SeqCst);
+        self.system.free(ptr, len)
+    }
+}
+
+#[compiler_test(page_allocator)]
+fn artifacts_are_loaded_into_supplied_pages(config: crate::Config) -> Result<()> {
+    let wat = r#"
+        (module
+            (func (export "add") (param i32 i32) (result i32)
+                (i32.add (local.get 0) (local.get 1))))
+    "#;
+    let serialized = Module::new(&config.store(), wat)?.serialize()?;
+
+    let allocator = Arc::new(CountingAllocator::default());
+    let engine = Universal::headless().engine();
+    engine.set_page_allocator(allocator.clone());
+    let store = Store::new(&engine);
+    let module = unsafe { Module::deserialize(&store, &serialized)? };
+    let instance = Instance::new(&module, &imports! {})?;
+    let add = instance.get_native_function::<(i32, i32), i32>("add")?;
+    assert_eq!(add.call(2, 3)?, 5);
+
+    let allocated = allocator.allocated.load(SeqCst);
+    assert!(allocated > 0);
+    let protected = allocator.protected.load(SeqCst);
+    assert!(protected > 0 && protected <= allocated);
+    assert_eq!(allocator.freed.load(SeqCst), 0);
+
+    drop((add, instance, module, store, engine));
+    assert_eq!(allocator.freed.load(SeqCst), allocated);
+    Ok(())
+}
@@ -0,0 +1,274 @@
+//! Placing functions into code memory while the others are compiling.
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_compiler::{
+    Compilation, CompileModuleInfo, CompiledFunction, Compiler as WasmCompiler, FunctionBodyData,
+    ModuleTranslationState, Target,
+};
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{Universal, UniversalEngine};
+use wasmer_types::entity::PrimaryMap;
+use wasmer_types::LocalFunctionIndex;
+
+/// A module of `count` functions each calling the next one through a `br_table`, and a
+/// function trapping from the last one.
+fn chain(count: usize) -> Vec<u8> {
+    let mut wat = String::from("(module\n");
+    for i in 0..count {
+        let next = if i + 1 < count {
+            format!("(call $f{} (local.get 0))", i + 1)
+        } else {
+            "(call $trap (local.get 0))".to_string()
+        };
+        wat += &format!(
+            r#"(func $f{0} (export "f{0}") (param i32) (result i32)
+                (block $sub (block $mul (block $add
+                    (br_table $add $mul $sub (i32.rem_u (local.get 0) (i32.const 3))))
+                    (return (i32.add (i32.const {0}) {1})))
+                    (return (i32.mul (i32.const 3) {1})))
+                (i32.sub {1} (i32.const 1)))
+            "#,
+            i, next
+        );
+    }
+    wat += r#"(func $trap (param i32) (result i32)
+        (if (i32.eq (local.get 0) (i32.const 1000)) (then unreachable))
+        (local.get 0)))"#;
+    wat2wasm(wat.as_bytes()).unwrap().into_owned()
+}
+
+fn engine(
+    config: &crate::Config,
+    parallel: bool,
+    pipelined: bool,
+) -> (UniversalEngine, Arc<AtomicMetricsSink>) {
+    let mut compiler_config = config.compiler_config(false);
+    compiler_config.enable_parallel_compilation(parallel);
+    let engine = Universal::new(compiler_config)
+        .pipelined_loading(pipelined)
+        .engine();
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    (engine, sink)
+}
+
+/// The results of calling the first function of `chain` on a few inputs, and the index of
+/// the function raising the trap of the last one.
+fn results(module: &Module) -> Result<(Vec<i32>, u32)> {
+    let instance = Instance::new(module, &imports! {})?;
+    let first = instance.get_native_function::<i32, i32>("f0")?;
+    let results = (0..6)
+        .map(|input| Ok(first.call(input)?))
+        .collect::<Result<_>>()?;
+    let last = instance.get_native_function::<i32, i32>("f199")?;
+    let error = last.call(1000).unwrap_err();
+    Ok((results, error.trace()[0].func_index()))
+}
+
+#[compiler_test(pipelined_loading)]
+fn pipelined_loading_matches_serial_loading(config: crate::Config) -> Result<()> {
+    let binary = chain(200);
+    for &frame_pointer in &[true, false] {
+        let mut config = config.clone();
+        config.set_frame_pointer(frame_pointer);
+        let (serial, serial_sink) = engine(&config, false, false);
+        let store = Store::new(&serial);
+        let expected = serial.compile_universal(&binary, store.tunables())?;
+        let expected_results = results(&Module::new(&store, &binary)?)?;
+        // `$trap` comes after the 200 functions of the chain.
+        assert_eq!(expected_results.1, 200);
+        let code_bytes = serial_sink.snapshot().code_bytes;
+
+        // One loader takes the functions from each compiling thread.
+        for &parallel in &[false, true] {
+            let (engine, sink) = engine(&config, parallel, true);
+            let store = Store::new(&engine);
+            let (executable, artifact) =
+                engine.compile_and_load_universal(&binary, store.tunables())?;
+            assert_eq!(
+                executable.serialize().unwrap(),
+                expected.serialize().unwrap()
+            );
+            let snapshot = sink.snapshot();
+            assert_eq!(snapshot.pipelined_loads, 1);
+            assert_eq!(snapshot.compilations_succeeded, 1);
+            // The code memory reserved and not used is given back.
+            assert_eq!(snapshot.code_bytes, code_bytes);
+            drop(artifact);
+
+            let module = Module::new(&store, &binary)?;
+            assert_eq!(sink.snapshot().pipelined_loads, 2);
+            assert_eq!(results(&module)?, expected_results);
+        }
+    }
+    Ok(())
+}
+
+/// The bytes of code memory serial loading maps for `binary`.
+fn serial_code_bytes(config: &crate::Config, binary: &[u8]) -> Result<i64> {
+    let (engine, sink) = engine(config, false, false);
+    let store = Store::new(&engine);
+    let _module = Module::new(&store, binary)?;
+    Ok(sink.snapshot().code_bytes)
+}
+
+#[compiler_test(pipelined_loading)]
+fn modules_use_pipelined_loading(config: crate::Config) -> Result<()> {
+    let (engine, sink) = engine(&config, true, true);
+    let store = Store::new(&engine);
+    let module = Module::new(&store, chain(20))?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let first = instance.get_native_function::<i32, i32>("f0")?;
+    assert_eq!(first.call(4)?, first.call(4)?);
+    assert_eq!(sink.snapshot().pipelined_loads, 1);
+    Ok(())
+}
+
+#[compiler_test(pipelined_loading)]
+fn hot_functions_are_loaded_serially(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(config.compiler_config(false))
+        .pipelined_loading(true)
+        .hot_functions(vec!["f1".to_string()])
+        .engine();
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    let store = Store::new(&engine);
+    let (_, artifact) = engine.compile_and_load_universal(&chain(20), store.tunables())?;
+    assert!(artifact.hot_code().is_some());
+    let snapshot = sink.snapshot();
+    assert_eq!(snapshot.artifacts_loaded, 1);
+    assert_eq!(snapshot.pipelined_loads, 0);
+    Ok(())
+}
+
+#[compiler_test(pipelined_loading)]
+fn loading_falls_back_to_serial_without_room(config: crate::Config) -> Result<()> {
+    let binary = chain(50);
+    let size = serial_code_bytes(&config, &binary)?;
+    let (engine, sink) = engine(&config, true, true);
+    let store = Store::new(&engine);
+
+    // Too little code memory to reserve for the functions, but enough for the module.
+    engine.set_code_memory_limit(Some(size as usize));
+    let (_, artifact) = engine.compile_and_load_universal(&binary, store.tunables())?;
+    let snapshot = sink.snapshot();
+    assert_eq!(snapshot.pipelined_loads, 0);
+    assert_eq!(snapshot.code_bytes, size);
+    drop(artifact);
+
+    // Not enough for the module either: nothing stays mapped.
+    engine.set_code_memory_limit(Some(size as usize - 1));
+    assert!(matches!(
+        engine.compile_and_load_universal(&binary, store.tunables()),
+        Err(CompileError::CodeMemoryExhausted { .. })
+    ));
+    assert_eq!(sink.snapshot().code_bytes, 0);
+    Ok(())
+}
+
+/// A compiler failing once it handed all the functions over.
+struct FailingCompiler(Box<dyn WasmCompiler>);
+
+impl WasmCompiler for FailingCompiler {
+    fn compile_module<'data, 'module>(
+        &self,
+        _target: &Target,
+        _module: &'module CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        _function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+    ) -> Result<Compilation, CompileError> {
+        Err(CompileError::Codegen("failing compiler".to_string()))
+    }
+
+    fn compile_module_streaming<'data, 'module>(
+        &self,
+        target: &Target,
+        module: &'module CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+        sink: &(dyn Fn(LocalFunctionIndex, &CompiledFunction) + Sync),
+    ) -> Result<Compilation, CompileError> {
+        self.0.compile_module_streaming(
+            target,
+            module,
+            module_translation,
+            function_body_inputs,
+            sink,
+        )?;
+        Err(CompileError::Codegen("failing compiler".to_string()))
+    }
+}
+
+struct FailingConfig(Box<dyn CompilerConfig>);
+
+/// Hands out the pages of the system, counting those allocated and not freed yet.
+#[derive(Debug, Default)]
+struct LiveAllocator {
+    system: SystemPageAllocator,
+    allocated: AtomicUsize,
+    live: AtomicUsize,
+}
+
+unsafe impl PageAllocator for LiveAllocator {
+    fn page_size(&self) -> usize {
+        self.system.page_size()
+    }
+
+    fn alloc_rx(&self, len: usize) -> Result<*mut u8, String> {
+        self.allocated.fetch_add(len, SeqCst);
+        self.live.fetch_add(len, SeqCst);
+        self.system.alloc_rx(len)
+    }
+
+    unsafe fn protect(
+        &self,
+        ptr: *mut u8,
+        len: usize,
+        protection: PageProtection,
+    ) -> Result<(), String> {
+        self.system.protect(ptr, len, protection)
+    }
+
+    unsafe fn free(&self, ptr: *mut u8, len: usize) {
+        self.live.fetch_sub(len, SeqCst);
+        self.system.free(ptr, len)
+    }
+}
+
+impl CompilerConfig for FailingConfig {
+    fn compiler(self: Box<Self>) -> Box<dyn WasmCompiler> {
+        Box::new(FailingCompiler(self.0.compiler()))
+    }
+
+    fn default_features_for_target(&self, target: &Target) -> Features {
+        self.0.default_features_for_target(target)
+    }
+}
+
+#[compiler_test(pipelined_loading)]
+fn failed_compilations_release_the_streamed_code(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(FailingConfig(config.compiler_config(false)))
+        .pipelined_loading(true)
+        .engine();
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    let allocator = Arc::new(LiveAllocator::default());
+    engine.set_page_allocator(allocator.clone());
+    let store = Store::new(&engine);
+    match engine.compile_and_load_universal(&chain(50), store.tunables()) {
+        Err(CompileError::Codegen(_)) => {}
+        Err(error) => panic!("unexpected error: {}", error),
+        Ok(_) => panic!("the module compiled"),
+    }
+    let snapshot = sink.snapshot();
+    assert_eq!(snapshot.compilations_failed, 1);
+    assert_eq!(snapshot.artifacts_loaded, 0);
+    assert_eq!(snapshot.code_bytes, 0);
+    // The code memory was reserved, and released with the streamed functions.
+    assert!(allocator.allocated.load(SeqCst) > 0);
+    assert_eq!(allocator.live.load(SeqCst), 0);
+    Ok(())
+}
@@ -0,0 +1,146 @@
+//! Touching the pages of the memories and tables of instances at instantiation.
+
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use wasmer::*;
+use wasmer_engine_universal::Universal;
+
+/// A module with `pages` pages of memory, the first byte of the first two of which are written
+/// by data segments, and a table.
+fn module(pages: u32) -> String {
+    format!(
+        r#"
+        (module
+            (memory (export "memory") {})
+            (table 4 funcref)
+            (data (i32.const 0) "a")
+            (data (i32.const 1) "b")
+            (data (i32.const 65536) "c")
+            (func (export "load") (param i32) (result i32)
+                local.get 0
+                i32.load8_u))
+        "#,
+        pages
+    )
+}
+
+/// Instantiate a module with `pages` pages of memory with `policy`, returning the instance and
+/// the metrics of its engine.
+fn instantiate(
+    config: &crate::Config,
+    pages: u32,
+    policy: PretouchPolicy,
+) -> Result<(Instance, MetricsSnapshot)> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    let store = Store::new(&engine);
+    let module = Module::new(&store, module(pages))?;
+    let config = InstanceConfig::default().with_pretouch(policy);
+    let instance = Instance::new_with_config(&module, config, &imports! {})?;
+    Ok((instance, sink.snapshot()))
+}
+
+fn memory(mode: PretouchMode) -> PretouchPolicy {
+    PretouchPolicy {
+        memory: mode,
+        tables: false,
+    }
+}
+
+#[compiler_test(pretouch)]
+fn pages_touched_are_counted(config: crate::Config) -> Result<()> {
+    let (_, metrics) = instantiate(&config, 2, PretouchPolicy::default())?;
+    assert_eq!(metrics.pages_pretouched, 0);
+    assert_eq!(metrics.pretouch_time, Duration::from_secs(0));
+
+    // The first two segments share a page, whatever the page size.
+    let (_, metrics) = instantiate(&config, 2, memory(PretouchMode::WrittenPagesOnly))?;
+    assert_eq!(metrics.pages_pretouched, 2);
+
+    let (_, one) = instantiate(&config, 2, memory(PretouchMode::AllMinimumPages))?;
+    let (_, two) = instantiate(&config, 4, memory(PretouchMode::AllMinimumPages))?;
+    assert!(one.pages_pretouched >= 2);
+    assert_eq!(two.pages_pretouched, 2 * one.pages_pretouched);
+
+    let policy = PretouchPolicy {
+        memory: PretouchMode::AllMinimumPages,
+        tables: true,
+    };
+    let (_, with_tables) = instantiate(&config, 2, policy)?;
+    assert!(with_tables.pages_pretouched > one.pages_pretouched);
+    Ok(())
+}
+
+#[compiler_test(pretouch)]
+fn contents_are_preserved(config: crate::Config) -> Result<()> {
+    let policy = PretouchPolicy {
+        memory: PretouchMode::AllMinimumPages,
+        tables: true,
+    };
+    let (instance, _) = instantiate(&config, 2, policy)?;
+    let load = instance.get_native_function::<i32, i32>("load")?;
+    assert_eq!(load.call(0)?, b'a' as i32);
+    assert_eq!(load.call(1)?, b'b' as i32);
+    assert_eq!(load.call(65536)?, b'c' as i32);
+    assert_eq!(load.call(4096)?, 0);
+    Ok(())
+}
+
+/// Return the resident size of the mapping containing `addr`, in bytes, if known.
+#[cfg(target_os = "linux")]
+fn resident_bytes(addr: *const u8) -> Option<usize> {
+    let smaps = std::fs::read_to_string("/proc/self/smaps").ok()?;
+    let mut in_mapping = false;
+    for line in smaps.lines() {
+        let mut fields = line.split_whitespace();
+        let first = fields.next()?;
+        if let Some((start, end)) = first.split_once('-') {
+            if let (Ok(start), Ok(end)) = (
+                usize::from_str_radix(start, 16),
+                usize::from_str_radix(end, 16),
+            ) {
+                in_mapping = (start..end).contains(&(addr as usize));
+                continue;
+            }
+        }
+        if in_mapping && first == "Rss:" {
+            return Some(fields.next()?.parse::<usize>().ok()? * 1024);
+        }
+    }
+    None
+}
+
+#[cfg(target_os = "linux")]
+#[compiler_test(pretouch)]
+fn minimum_pages_are_resident(config: crate::Config) -> Result<()> {
+    let size = 256 * 64 * 1024;
+    let (instance, _) = instantiate(&config, 256, memory(PretouchMode::AllMinimumPages))?;
+    let memory = instance.lookup_memory("memory").unwrap();
+    // Residency is best effort: the kernel may not tell, or reclaim pages in the meantime.
+    if let Some(resident) = resident_bytes(memory.data_ptr()) {
+        assert!(resident >= size, "{} bytes resident", resident);
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[compiler_test(pretouch)]
+fn readonly_instances_are_only_read(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    let store = Store::new(&engine);
+    let module = Module::new(&store, module(2))?;
+    let policy = PretouchPolicy {
+        memory: PretouchMode::AllMinimumPages,
+        tables: true,
+    };
+    let config = InstanceConfig::default().with_pretouch(policy);
+    let instance = Instance::new_readonly_with_config(&module, config, &imports! {})?;
+    assert!(sink.snapshot().pages_pretouched > 0);
+    let load = instance.get_native_function::<i32, i32>("load")?;
+    assert_eq!(load.call(65536)?, b'c' as i32);
+    Ok(())
+}
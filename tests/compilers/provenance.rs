@@ -0,0 +1,128 @@
+//! How executables were produced, recorded alongside them.
+
+use anyhow::Result;
+use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{
+    ArtifactEditor, SerializeOptions, Universal, UniversalEngine, UniversalExecutable,
+    UniversalExecutableRef,
+};
+
+const WAT: &str = r#"
+    (module
+        (import "host" "gas" (func $gas (param i32)))
+        (func (export "add") (param i32 i32) (result i32)
+            (call $gas (i32.const 1))
+            (i32.add (local.get 0) (local.get 1))))
+"#;
+
+fn compile(config: &crate::Config) -> Result<(UniversalEngine, UniversalExecutable)> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let store = Store::new(&engine);
+    let executable = engine.compile_universal(&wat2wasm(WAT.as_bytes())?, store.tunables())?;
+    Ok((engine, executable))
+}
+
+fn options() -> SerializeOptions {
+    SerializeOptions::new()
+        .provenance(&[("requested_by", "alice"), ("ticket", "OPS-12")])
+        .built_at(1_700_000_000)
+}
+
+#[compiler_test(provenance)]
+fn provenance_round_trips(config: crate::Config) -> Result<()> {
+    let (engine, mut executable) = compile(&config)?;
+    let compiled = executable.provenance()?;
+    assert!(compiled.engine.starts_with("wasmer-engine-universal-near "));
+    assert_eq!(compiled.compiler.name, "singlepass");
+    assert!(!compiled.compiler.version.is_empty());
+    assert!(compiled
+        .compiler
+        .flags
+        .iter()
+        .any(|flag| flag.starts_with("canonicalize_nans=")));
+    assert_eq!(compiled.compiler.middlewares, ["intrinsic:gas"]);
+    assert!(compiled.features.contains(&"bulk_memory".to_string()));
+    assert_eq!((compiled.built_at, compiled.custom.len()), (None, 0));
+
+    let bytes = executable.serialize_with(&options()).unwrap();
+    assert_eq!(executable.serialize().unwrap(), bytes);
+    let reference = unsafe { UniversalExecutableRef::deserialize(&bytes)? };
+    let provenance = reference.provenance()?;
+    assert_eq!(provenance.built_at, Some(1_700_000_000));
+    assert_eq!(provenance.custom("requested_by"), Some("alice"));
+    assert_eq!(provenance.custom("ticket"), Some("OPS-12"));
+    assert_eq!(provenance.custom("missing"), None);
+    assert_eq!(
+        (
+            &provenance.engine,
+            provenance.engine_id,
+            &provenance.compiler
+        ),
+        (&compiled.engine, compiled.engine_id, &compiled.compiler)
+    );
+
+    // Loaded artifacts have the provenance of their executable.
+    let artifact = engine.load_universal_executable_ref(&reference)?;
+    assert_eq!(artifact.provenance(), &provenance);
+
+    // Later entries come after the earlier ones, and the current time is recorded by default.
+    let mut executable = reference.to_owned()?;
+    let bytes = executable
+        .serialize_with(&SerializeOptions::new().provenance(&[("requested_by", "bob")]))
+        .unwrap();
+    let provenance = unsafe { UniversalExecutableRef::deserialize(&bytes)? }.provenance()?;
+    assert_eq!(provenance.custom.len(), 3);
+    assert_eq!(provenance.custom("requested_by"), Some("bob"));
+    assert!(provenance.built_at.unwrap() > 1_700_000_000);
+
+    // Engines configured the same way have the same id.
+    let (_, other) = compile(&config)?;
+    assert_eq!(other.provenance()?.engine_id, compiled.engine_id);
+    Ok(())
+}
+
+#[compiler_test(provenance)]
+fn provenance_is_not_in_the_consensus_hash(config: crate::Config) -> Result<()> {
+    let (engine, mut executable) = compile(&config)?;
+    let plain = executable.serialize().unwrap();
+    let recorded = executable.serialize_with(&options()).unwrap();
+    assert_ne!(plain, recorded);
+
+    let load = |bytes: &[u8]| -> Result<_> {
+        let reference = unsafe { UniversalExecutableRef::deserialize(bytes)? };
+        Ok(engine.load_universal_executable_ref(&reference)?)
+    };
+    let (plain, recorded) = (load(&plain)?, load(&recorded)?);
+    assert_ne!(plain.provenance(), recorded.provenance());
+    assert_eq!(plain.consensus_hash(), recorded.consensus_hash());
+    Ok(())
+}
+
+#[compiler_test(provenance)]
+fn signatures_cover_the_provenance(config: crate::Config) -> Result<()> {
+    let (_, mut executable) = compile(&config)?;
+    let bytes = executable.serialize_with(&options()).unwrap();
+    let mut editor = unsafe { ArtifactEditor::open(&bytes)? };
+    let digest = editor.signing_digest()?;
+    editor.replace_signature_block(digest.to_vec());
+    let signed = editor.finish()?;
+
+    let verify = |bytes: &[u8]| -> Result<bool> {
+        let editor = unsafe { ArtifactEditor::open(bytes)? };
+        Ok(editor.signature_block() == Some(&editor.signing_digest()?[..]))
+    };
+    assert!(verify(&signed)?);
+
+    // Claim someone else requested the compilation.
+    let mut tampered = signed.clone();
+    let at = tampered
+        .windows(5)
+        .position(|window| window == b"alice")
+        .unwrap();
+    tampered[at..at + 5].copy_from_slice(b"mallo");
+    let provenance = unsafe { UniversalExecutableRef::deserialize(&tampered)? }.provenance()?;
+    assert_eq!(provenance.custom("requested_by"), Some("mallo"));
+    assert!(!verify(&tampered)?);
+    Ok(())
+}
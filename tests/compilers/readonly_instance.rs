@@ -0,0 +1,186 @@
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (memory (export "memory") 1)
+        (global $calls (mut i32) (i32.const 0))
+        (data (i32.const 0) "shared")
+        (func (export "load") (param i32) (result i32)
+            local.get 0
+            i32.load8_u)
+        (func (export "count") (result i32)
+            global.get $calls
+            i32.const 1
+            i32.add
+            global.set $calls
+            global.get $calls)
+        (func (export "store") (param i32)
+            local.get 0
+            i32.const 42
+            i32.store8)
+        (func (export "grow") (result i32)
+            i32.const 1
+            memory.grow)
+        (func (export "fill")
+            i32.const 0
+            i32.const 42
+            i32.const 4
+            memory.fill))
+"#;
+
+/// Return the inode of the file mapped at `addr`, if any.
+fn mapped_inode(addr: *const u8) -> Option<u64> {
+    let maps = std::fs::read_to_string("/proc/self/maps").ok()?;
+    maps.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        let (start, end) = fields.next()?.split_once('-')?;
+        let start = usize::from_str_radix(start, 16).ok()?;
+        let end = usize::from_str_radix(end, 16).ok()?;
+        if !(start..end).contains(&(addr as usize)) {
+            return None;
+        }
+        fields.nth(3)?.parse().ok()
+    })
+}
+
+#[compiler_test(readonly_instance)]
+fn concurrent_view_calls(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+
+    let threads = (0..8)
+        .map(|_| {
+            let module = module.clone();
+            std::thread::spawn(move || -> Result<()> {
+                let instance = Instance::new_readonly(&module, &imports! {})?;
+                let load = instance.get_native_function::<i32, i32>("load")?;
+                let count = instance.get_native_function::<(), i32>("count")?;
+                for (offset, byte) in b"shared".iter().enumerate() {
+                    assert_eq!(load.call(offset as i32)?, *byte as i32);
+                }
+                // Globals are not shared.
+                assert_eq!(count.call()?, 1);
+                assert_eq!(count.call()?, 2);
+                let memory = instance.lookup_memory("memory").unwrap();
+                assert_eq!(memory.size(), Pages(1));
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap()?;
+    }
+
+    // Best effort: all the memories map the same file.
+    let first = Instance::new_readonly(&module, &imports! {})?;
+    let second = Instance::new_readonly(&module, &imports! {})?;
+    let first = first.lookup_memory("memory").unwrap().data_ptr();
+    let second = second.lookup_memory("memory").unwrap().data_ptr();
+    assert_ne!(first, second);
+    if let Some(inode) = mapped_inode(first) {
+        assert_eq!(mapped_inode(second), Some(inode));
+    }
+    Ok(())
+}
+
+#[compiler_test(readonly_instance)]
+fn writes_trap(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let instance = Instance::new_readonly(&module, &imports! {})?;
+
+    let store8 = instance.get_native_function::<i32, ()>("store")?;
+    let error = store8.call(0).unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::ReadOnlyInstance));
+    // Out of bounds accesses are still reported as such.
+    let error = store8.call(65536).unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::HeapAccessOutOfBounds));
+
+    let fill = instance.get_native_function::<(), ()>("fill")?;
+    let error = fill.call().unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::ReadOnlyInstance));
+
+    let grow = instance.get_native_function::<(), i32>("grow")?;
+    assert_eq!(grow.call()?, -1);
+
+    // The instance is still usable, and writable instances are unaffected.
+    let load = instance.get_native_function::<i32, i32>("load")?;
+    assert_eq!(load.call(0)?, b's' as i32);
+    let writable = Instance::new(&module, &imports! {})?;
+    writable.get_native_function::<i32, ()>("store")?.call(0)?;
+    let load = writable.get_native_function::<i32, i32>("load")?;
+    assert_eq!(load.call(0)?, 42);
+    assert_eq!(
+        instance.get_native_function::<i32, i32>("load")?.call(0)?,
+        b's' as i32
+    );
+    Ok(())
+}
+
+#[compiler_test(readonly_instance)]
+fn start_function_writing_memory(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"(module
+            (memory 1)
+            (func $start
+                i32.const 0
+                i32.const 1
+                i32.store)
+            (start $start))"#,
+    )?;
+    match Instance::new_readonly(&module, &imports! {}) {
+        Err(InstantiationError::Start(e)) => {
+            assert_eq!(e.trap_code(), Some(TrapCode::ReadOnlyInstance))
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("instantiation should have failed"),
+    }
+    Instance::new(&module, &imports! {})?;
+    Ok(())
+}
+
+#[compiler_test(readonly_instance)]
+fn unsupported_modules(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let memory = Memory::new(&store, MemoryType::new(1, None, false))?;
+    let imports = imports! { "env" => { "memory" => memory } };
+    let module = Module::new(&store, r#"(module (import "env" "memory" (memory 1)))"#)?;
+    match Instance::new_readonly(&module, &imports) {
+        Err(error @ InstantiationError::ReadOnlyUnsupported(_)) => {
+            assert_eq!(error.failure_kind(), FailureKind::Permanent)
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("instantiation should have failed"),
+    }
+
+    let module = Module::new(
+        &store,
+        r#"(module
+            (import "env" "base" (global i32))
+            (memory 1)
+            (data (global.get 0) "placed"))"#,
+    )?;
+    let imports = imports! {
+        "env" => { "base" => Global::new(&store, Value::I32(0)) },
+    };
+    assert!(matches!(
+        Instance::new_readonly(&module, &imports),
+        Err(InstantiationError::ReadOnlyUnsupported(_))
+    ));
+
+    let module = Module::new(
+        &store,
+        r#"(module (memory 1) (data (i32.const 65535) "ab"))"#,
+    )?;
+    match Instance::new_readonly(&module, &imports! {}) {
+        Err(InstantiationError::Start(e)) => {
+            assert_eq!(e.trap_code(), Some(TrapCode::HeapAccessOutOfBounds))
+        }
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("instantiation should have failed"),
+    }
+    Ok(())
+}
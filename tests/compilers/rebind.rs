@@ -0,0 +1,146 @@
+//! Rebinding an instance to the code of the same binary compiled anew.
+
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use wasmer::*;
+use wasmer_engine_universal::Universal;
+
+const COUNTER: &str = r#"
+    (module
+        (import "env" "tick" (func $tick))
+        (memory (export "memory") 1)
+        (global $count (export "count") (mut i32) (i32.const 0))
+        (table 1 funcref)
+        (elem (i32.const 0) $increment)
+        (type $nullary (func (result i32)))
+        (func $increment (export "increment") (result i32)
+            (call $tick)
+            (global.set $count (i32.add (global.get $count) (i32.const 1)))
+            (i32.store (i32.const 8) (global.get $count))
+            (global.get $count))
+        (func (export "increment_indirect") (result i32)
+            (call_indirect (type $nullary) (i32.const 0))))
+"#;
+
+/// The address of the code of the exported function `name`.
+fn address(instance: &Instance, name: &str) -> usize {
+    match instance.lookup(name) {
+        Some(Export::Function(function)) => function.vm_function.address as usize,
+        _ => panic!("`{}` should be an exported function", name),
+    }
+}
+
+fn instantiate(module: &Module) -> Result<Instance> {
+    let tick = Function::new_native(module.store(), || {});
+    Ok(Instance::new(
+        module,
+        &imports! { "env" => { "tick" => tick } },
+    )?)
+}
+
+#[compiler_test(rebind)]
+fn rebound_instances_keep_their_state(mut config: crate::Config) -> Result<()> {
+    let wasm = wat2wasm(COUNTER.as_bytes())?;
+    config.set_bounds_check_elimination(false);
+    let old_module = Module::new(&config.store(), &wasm)?;
+    config.set_bounds_check_elimination(true);
+    let new_module = Module::new(&config.store(), &wasm)?;
+    assert_eq!(old_module.wasm_hash(), new_module.wasm_hash());
+
+    let instance = instantiate(&old_module)?;
+    let increment = instance.get_native_function::<(), i32>("increment")?;
+    assert_eq!(increment.call()?, 1);
+    assert_eq!(increment.call()?, 2);
+    let old_address = address(&instance, "increment");
+
+    instance.rebind(&new_module)?;
+    // The instance now runs the code of the new module, as its instances do.
+    let new_address = address(&instance, "increment");
+    assert_ne!(new_address, old_address);
+    assert_eq!(
+        new_address,
+        address(&instantiate(&new_module)?, "increment")
+    );
+
+    // The memory, the global and the table are those of before, the table calling the new
+    // code.
+    let increment = instance.get_native_function::<(), i32>("increment")?;
+    let increment_indirect = instance.get_native_function::<(), i32>("increment_indirect")?;
+    assert_eq!(increment.call()?, 3);
+    assert_eq!(increment_indirect.call()?, 4);
+    let memory = instance.lookup_memory("memory").unwrap();
+    assert_eq!(memory.view::<u32>()[2].get(), 4);
+
+    // Rebinding back and forth is allowed.
+    instance.rebind(&old_module)?;
+    assert_eq!(address(&instance, "increment"), old_address);
+    assert_eq!(increment.call()?, 5);
+    Ok(())
+}
+
+#[compiler_test(rebind)]
+fn mismatched_modules_are_rejected(config: crate::Config) -> Result<()> {
+    let wasm = wat2wasm(COUNTER.as_bytes())?;
+    let store = config.store();
+    let module = Module::new(&store, &wasm)?;
+    let instance = instantiate(&module)?;
+
+    let other = Module::new(&store, COUNTER.replace("(i32.const 1)", "(i32.const 2)"))?;
+    let error = instance.rebind(&other).unwrap_err();
+    assert_eq!(error, RebindError::WasmMismatch);
+    assert_eq!(error.code(), ErrorCode::RebindWasmMismatch);
+
+    // The same binary compiled for memories with other guard pages would access the memory
+    // of the instance out of its bounds.
+    let base = BaseTunables::for_target(&Target::default());
+    let tunables = BaseTunables {
+        static_memory_offset_guard_size: base.static_memory_offset_guard_size / 2,
+        dynamic_memory_offset_guard_size: base.dynamic_memory_offset_guard_size / 2,
+        ..base
+    };
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let guarded = Module::new(&Store::new_with_tunables(&engine, tunables), &wasm)?;
+    let error = instance.rebind(&guarded).unwrap_err();
+    assert!(matches!(error, RebindError::LayoutMismatch(_)), "{}", error);
+    assert_eq!(error.code(), ErrorCode::RebindLayoutMismatch);
+
+    let increment = instance.get_native_function::<(), i32>("increment")?;
+    assert_eq!(increment.call()?, 1);
+    Ok(())
+}
+
+#[compiler_test(rebind)]
+fn calls_in_flight_prevent_rebinding(config: crate::Config) -> Result<()> {
+    let wasm = wat2wasm(COUNTER.as_bytes())?;
+    let store = config.store();
+    let module = Module::new(&store, &wasm)?;
+    let new_module = Module::new(&store, &wasm)?;
+
+    // The instance rebinds itself from an import it calls.
+    let slot = Arc::new(Mutex::new(None::<Instance>));
+    let outcome = Arc::new(Mutex::new(None));
+    let tick = {
+        let (slot, outcome, new_module) = (slot.clone(), outcome.clone(), new_module.clone());
+        let ty = FunctionType::new(vec![], vec![]);
+        Function::new(&store, ty, move |_| {
+            if let Some(instance) = &*slot.lock().unwrap() {
+                *outcome.lock().unwrap() = Some(instance.rebind(&new_module));
+            }
+            Ok(vec![])
+        })
+    };
+    let instance = Instance::new(&module, &imports! { "env" => { "tick" => tick } })?;
+    *slot.lock().unwrap() = Some(instance.clone());
+    let increment = instance.get_native_function::<(), i32>("increment")?;
+    assert_eq!(increment.call()?, 1);
+    let error = outcome.lock().unwrap().take().unwrap().unwrap_err();
+    assert_eq!(error, RebindError::CallInFlight { active_calls: 1 });
+    assert_eq!(error.code(), ErrorCode::RebindCallInFlight);
+    assert!(error.failure_kind().is_transient());
+
+    // Once the call returned, the instance can be rebound.
+    slot.lock().unwrap().take();
+    instance.rebind(&new_module)?;
+    assert_eq!(increment.call()?, 2);
+    Ok(())
+}
@@ -0,0 +1,273 @@
+//! Reclaiming the memory of the engine and of the caches registered with it.
+
+use anyhow::Result;
+use std::sync::Arc;
+use std::thread;
+use wasmer::*;
+use wasmer_engine_universal::Universal;
+
+const WAT: &str = r#"
+    (module
+        (memory 2)
+        (data (i32.const 16) "\2a")
+        (func (export "load") (param i32) (result i32)
+            (i32.add (i32.load8_u (i32.const 16)) (local.get 0))))
+"#;
+
+struct Setup {
+    engine: UniversalEngine,
+    sink: Arc<AtomicMetricsSink>,
+    module: Module,
+    scope: InstanceScope,
+    arena: Arc<dyn Reclaimable>,
+    cache: Arc<ViewCallCache>,
+}
+
+/// An engine with a registered instance arena and view call cache, both empty.
+fn setup(config: &crate::Config) -> Result<Setup> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    let store = Store::new(&engine);
+    let module = Module::new(&store, WAT)?;
+    let scope = InstanceScope::new();
+    let arena = scope.reclaimable();
+    let cache = Arc::new(ViewCallCache::new(1 << 20));
+    engine.register_reclaimable(arena.clone());
+    engine.register_reclaimable(cache.clone());
+    Ok(Setup {
+        engine,
+        sink,
+        module,
+        scope,
+        arena,
+        cache,
+    })
+}
+
+fn load(instance: &Instance, arg: i32) -> Result<i32> {
+    Ok(instance
+        .get_native_function::<i32, i32>("load")?
+        .call(arg)?)
+}
+
+fn names(report: &ReclaimReport) -> Vec<&str> {
+    report
+        .components
+        .iter()
+        .map(|component| &*component.name)
+        .collect()
+}
+
+#[compiler_test(reclaim)]
+fn components_are_reclaimed_by_priority(config: crate::Config) -> Result<()> {
+    let Setup {
+        engine,
+        sink,
+        module,
+        scope,
+        arena,
+        cache,
+    } = setup(&config)?;
+
+    // Fill the code of a dropped artifact, three arena buffers, the cache and the images.
+    drop(Module::new(
+        module.store(),
+        "(module (func (export \"f\")))",
+    )?);
+    let code_bytes = sink.snapshot().code_bytes;
+    let scoped = (0..3)
+        .map(|_| module.instantiate_scoped(&imports! {}, InstanceConfig::default(), &scope))
+        .collect::<Result<Vec<_>, _>>()?;
+    drop(scoped);
+    let arena_bytes = arena.reclaimable_bytes();
+    assert!(arena_bytes > 0);
+    for arg in 0..10 {
+        cache.call(&module, &imports! {}, 1, "load", &[Value::I32(arg)])?;
+    }
+    let cache_bytes = cache.stats().bytes;
+    drop(Instance::new_readonly(&module, &imports! {})?);
+
+    // The cheapest component is asked first, and the reclamation stops at the target.
+    let report = engine.reclaim(1, ReclaimPriority::Expensive);
+    assert_eq!(names(&report), ["code memory"]);
+    assert!(report.reached_target());
+    let released_code = code_bytes - sink.snapshot().code_bytes;
+    assert!(released_code > 0);
+    assert_eq!(report.components[0].released_bytes as i64, released_code);
+    assert_eq!(report.components[0].reclaimable_bytes as i64, released_code);
+
+    // Nothing above the maximum priority is asked.
+    let report = engine.reclaim(usize::MAX, ReclaimPriority::Unused);
+    assert_eq!(names(&report), ["instance arena"]);
+    assert_eq!(report.released_bytes(), arena_bytes);
+    assert!(!report.reached_target());
+    assert_eq!(arena.reclaimable_bytes(), 0);
+    assert_eq!(cache.stats().bytes, cache_bytes);
+
+    // Caches release what they are asked for, least recently used first.
+    let report = engine.reclaim(cache_bytes / 2, ReclaimPriority::Cached);
+    assert_eq!(names(&report), ["view call cache"]);
+    let released = report.released_bytes();
+    assert!(released >= cache_bytes / 2 && released < cache_bytes);
+    assert_eq!(cache.stats().bytes, cache_bytes - released);
+    cache.call(&module, &imports! {}, 1, "load", &[Value::I32(9)])?;
+    assert_eq!(cache.stats().hits, 1);
+
+    let report = engine.reclaim(usize::MAX, ReclaimPriority::Expensive);
+    assert_eq!(names(&report), ["view call cache", "memory images"]);
+    assert_eq!(report.components[1].priority, ReclaimPriority::Expensive);
+    assert_eq!(report.components[1].released_bytes, 2 * 65536);
+    assert_eq!(cache.stats().bytes, 0);
+    assert_eq!(
+        engine.reclaim(usize::MAX, ReclaimPriority::Expensive),
+        ReclaimReport {
+            target_bytes: usize::MAX,
+            components: Vec::new(),
+        }
+    );
+
+    // The released memory is recreated on use.
+    let instance = Instance::new_readonly(&module, &imports! {})?;
+    assert_eq!(load(&instance, 1)?, 43);
+    Ok(())
+}
+
+#[compiler_test(reclaim)]
+fn memory_in_use_is_kept(config: crate::Config) -> Result<()> {
+    let Setup {
+        engine,
+        module,
+        scope,
+        arena,
+        cache,
+        ..
+    } = setup(&config)?;
+    let readonly = Instance::new_readonly(&module, &imports! {})?;
+    let scoped = module.instantiate_scoped(&imports! {}, InstanceConfig::default(), &scope)?;
+    cache.call(&module, &imports! {}, 1, "load", &[Value::I32(1)])?;
+
+    let report = engine.reclaim(usize::MAX, ReclaimPriority::Expensive);
+    assert_eq!(names(&report), ["view call cache"]);
+    assert_eq!(load(&readonly, 2)?, 44);
+    let answer = scoped.get_native_function::<i32, i32>("load")?;
+    assert_eq!(answer.call(3)?, 45);
+    drop(answer);
+    drop(scoped);
+    assert!(arena.reclaimable_bytes() > 0);
+
+    // Components dropped by their owner are no longer asked.
+    drop(scope);
+    drop(arena);
+    drop(cache);
+    drop(readonly);
+    let report = engine.reclaim(usize::MAX, ReclaimPriority::Expensive);
+    assert_eq!(names(&report), ["memory images"]);
+    Ok(())
+}
+
+#[compiler_test(reclaim)]
+fn reclaiming_concurrently_with_use(config: crate::Config) -> Result<()> {
+    let Setup {
+        engine,
+        module,
+        scope,
+        cache,
+        ..
+    } = setup(&config)?;
+    let scope = Arc::new(scope);
+    let threads = (0..4)
+        .map(|thread| {
+            let (module, scope, cache) = (module.clone(), scope.clone(), cache.clone());
+            thread::spawn(move || -> Result<()> {
+                for arg in 0..50 {
+                    let result =
+                        cache.call(&module, &imports! {}, 1, "load", &[Value::I32(arg)])?;
+                    assert_eq!(result[..], [Value::I32(42 + arg)]);
+                    let scoped = module.instantiate_scoped(
+                        &imports! {},
+                        InstanceConfig::default(),
+                        &scope,
+                    )?;
+                    let scoped_load = scoped.get_native_function::<i32, i32>("load")?;
+                    assert_eq!(scoped_load.call(thread)?, 42 + thread);
+                    let readonly = Instance::new_readonly(&module, &imports! {})?;
+                    assert_eq!(load(&readonly, arg)?, 42 + arg);
+                }
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for _ in 0..50 {
+        engine.reclaim(usize::MAX, ReclaimPriority::Expensive);
+    }
+    for thread in threads {
+        thread.join().unwrap()?;
+    }
+    Ok(())
+}
+
+#[cfg(all(feature = "pressure-watcher", target_os = "linux"))]
+#[compiler_test(reclaim)]
+fn pressure_triggers_reclamation(config: crate::Config) -> Result<()> {
+    use std::fs;
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use wasmer_engine_universal::{PressureWatcher, PressureWatcherConfig};
+
+    let Setup {
+        engine,
+        module,
+        cache,
+        ..
+    } = setup(&config)?;
+    cache.call(&module, &imports! {}, 1, "load", &[Value::I32(1)])?;
+    let dir = tempfile::tempdir()?;
+    let psi = dir.path().join("memory.pressure");
+    fs::write(
+        &psi,
+        "some avg10=1.00 avg60=0.00 avg300=0.00 total=0\n\
+         full avg10=0.00 avg60=0.00 avg300=0.00 total=0\n",
+    )?;
+    fs::write(dir.path().join("memory.current"), "900\n")?;
+    fs::write(dir.path().join("memory.max"), "max\n")?;
+
+    let (sender, reports) = mpsc::channel();
+    let sender = std::sync::Mutex::new(sender);
+    let watcher_config = PressureWatcherConfig::new(ReclaimPriority::Cached)
+        .with_interval(Duration::from_millis(10))
+        .with_psi_path(&psi)
+        .with_psi_threshold(10.0, 100)
+        .with_cgroup_dir(dir.path())
+        .with_cgroup_headroom(300)
+        .with_reclaim_handler(move |report| {
+            let _ = sender.lock().unwrap().send(report.clone());
+        });
+    let watcher = PressureWatcher::start(&engine, watcher_config)?;
+
+    // Below the thresholds, or without a memory limit, nothing is reclaimed.
+    assert!(reports.recv_timeout(Duration::from_millis(100)).is_err());
+    assert!(cache.stats().bytes > 0);
+
+    fs::write(dir.path().join("memory.max"), "1000\n")?;
+    let report = reports.recv_timeout(Duration::from_secs(10))?;
+    assert_eq!(report.target_bytes, 200);
+    assert_eq!(names(&report), ["view call cache"]);
+    assert_eq!(cache.stats().bytes, 0);
+
+    fs::write(dir.path().join("memory.max"), "max\n")?;
+    fs::write(
+        &psi,
+        "some avg10=25.00 avg60=0.00 avg300=0.00 total=0\n\
+         full avg10=0.00 avg60=0.00 avg300=0.00 total=0\n",
+    )?;
+    let report = loop {
+        let report = reports.recv_timeout(Duration::from_secs(10))?;
+        if report.target_bytes == 100 {
+            break report;
+        }
+    };
+    assert!(report.components.is_empty());
+    drop(watcher);
+    Ok(())
+}
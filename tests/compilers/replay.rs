@@ -0,0 +1,221 @@
+//! Recording the import calls of an execution and replaying them without the host.
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (import "env" "read" (func $read (param i32 i32) (result i32)))
+        (import "env" "random" (func $random (result i64)))
+        (import "env" "log" (func $log (param i32 i32)))
+        (import "env" "abort" (func $abort (param i32)))
+        (memory (export "memory") 1)
+        (global $total (export "total") (mut i64) (i64.const 0))
+        (func (export "run") (result i64)
+            (local $len i32) (local $i i32)
+            (local.set $len (call $read (i32.const 16) (i32.const 8)))
+            (block $done
+                (loop $next
+                    (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+                    (global.set $total
+                        (i64.add (global.get $total) (i64.load8_u offset=16 (local.get $i))))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br $next)))
+            (global.set $total (i64.add (global.get $total) (call $random)))
+            (call $log (i32.const 16) (local.get $len))
+            (global.get $total))
+        (func (export "fail")
+            (call $abort (i32.const 7))))
+"#;
+
+#[derive(Clone, Default)]
+struct Env {
+    memory: LazyInit<Memory>,
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl WasmerEnv for Env {
+    fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
+        let memory = instance
+            .lookup_memory("memory")
+            .ok_or_else(|| HostEnvInitError::MissingMemory("memory".into()))?;
+        self.memory.initialize(memory);
+        Ok(())
+    }
+}
+
+/// The host of the contract, which only the recording needs.
+fn imports(store: &Store, env: &Env) -> ImportObject {
+    let read = |env: &Env, ptr: u32, len: u32| {
+        let input = b"replayed";
+        let len = input.len().min(len as usize);
+        let memory = env.memory.get_ref().unwrap();
+        let data = unsafe { memory.data_unchecked_mut() };
+        data[ptr as usize..][..len].copy_from_slice(&input[..len]);
+        len as u32
+    };
+    let random = Arc::new(AtomicI64::new(1000));
+    let log = |env: &Env, ptr: u32, len: u32| {
+        let memory = env.memory.get_ref().unwrap();
+        let bytes = unsafe { &memory.data_unchecked()[ptr as usize..][..len as usize] };
+        let line = String::from_utf8_lossy(bytes).into_owned();
+        env.lines.lock().unwrap().push(line);
+    };
+    let abort = |code: i32| -> Result<(), RuntimeError> {
+        Err(RuntimeError::new(format!("aborted with {}", code)))
+    };
+    imports! {
+        "env" => {
+            "read" => Function::new_native_with_env(store, env.clone(), read),
+            "random" => Function::new(store, ([], [Type::I64]), move |_| {
+                Ok(vec![Val::I64(random.fetch_add(1, Ordering::SeqCst))])
+            }),
+            "log" => Function::new_native_with_env(store, env.clone(), log),
+            "abort" => Function::new_native(store, abort),
+        },
+    }
+}
+
+fn total(store: &Store, instance: &Instance) -> Val {
+    match Extern::from_vm_export(store, instance.lookup("total").unwrap()) {
+        Extern::Global(global) => global.get(),
+        other => panic!("unexpected export: {:?}", other),
+    }
+}
+
+fn memory(instance: &Instance) -> Vec<u8> {
+    let memory = instance.lookup_memory("memory").unwrap();
+    unsafe { memory.data_unchecked() }.to_vec()
+}
+
+/// Record a call to `run`, returning the log with its result.
+fn record(store: &Store, module: &Module, env: &Env) -> Result<(ReplayLog, i64)> {
+    let recorder = ReplayRecorder::wrap(store, &imports(store, env));
+    let instance = Instance::new(module, &recorder)?;
+    let result = instance.get_native_function::<(), i64>("run")?.call()?;
+    Ok((recorder.log(), result))
+}
+
+#[compiler_test(replay)]
+fn replays_without_the_host(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let env = Env::default();
+    let recorder = ReplayRecorder::wrap(&store, &imports(&store, &env));
+    let recorded = Instance::new(&module, &recorder)?;
+    let result = recorded.get_native_function::<(), i64>("run")?.call()?;
+    let sum = b"replayed".iter().map(|byte| i64::from(*byte)).sum::<i64>();
+    assert_eq!(result, sum + 1000);
+    let error = recorded
+        .get_native_function::<(), ()>("fail")?
+        .call()
+        .unwrap_err();
+    assert_eq!(error.message(), "aborted with 7");
+    assert_eq!(*env.lines.lock().unwrap(), ["replayed"]);
+
+    let log = recorder.log();
+    assert_eq!(log.len(), 4);
+    let log = ReplayLog::from_bytes(&log.to_bytes())?;
+    let player = ReplayPlayer::from_log(&store, log);
+    let replayed = Instance::new(&module, &player)?;
+    let result = replayed.get_native_function::<(), i64>("run")?.call()?;
+    assert_eq!(result, sum + 1000);
+    let error = replayed
+        .get_native_function::<(), ()>("fail")?
+        .call()
+        .unwrap_err();
+    assert_eq!(error.message(), "aborted with 7");
+    player.finish()?;
+
+    // The replayed instance ends up in the same state, without calling the host.
+    assert_eq!(memory(&replayed), memory(&recorded));
+    assert_eq!(total(&store, &replayed), total(&store, &recorded));
+    assert_eq!(env.lines.lock().unwrap().len(), 1);
+    Ok(())
+}
+
+#[compiler_test(replay)]
+fn divergences_are_reported(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let (log, _) = record(&store, &module, &Env::default())?;
+    assert_eq!(log.len(), 3);
+
+    // A module reading its input elsewhere.
+    let modified = Module::new(&store, WAT.replacen("i32.const 16", "i32.const 32", 1))?;
+    let player = ReplayPlayer::from_log(&store, log.clone());
+    let instance = Instance::new(&modified, &player)?;
+    let error = instance
+        .get_native_function::<(), i64>("run")?
+        .call()
+        .unwrap_err();
+    let divergence = error.downcast::<ReplayDivergence>().unwrap();
+    assert_eq!(
+        divergence,
+        ReplayDivergence {
+            call_index: 0,
+            field: DivergenceField::Arguments,
+            expected: "[I32(16), I32(8)]".to_string(),
+            actual: "[I32(32), I32(8)]".to_string(),
+        }
+    );
+    assert_eq!(divergence.code(), ErrorCode::ReplayDivergence);
+    assert_eq!(player.divergence(), Some(divergence.clone()));
+    assert_eq!(player.finish(), Err(divergence));
+
+    // Running once more than recorded.
+    let player = ReplayPlayer::from_log(&store, log.clone());
+    let instance = Instance::new(&module, &player)?;
+    let run = instance.get_native_function::<(), i64>("run")?;
+    run.call()?;
+    let divergence = run
+        .call()
+        .unwrap_err()
+        .downcast::<ReplayDivergence>()
+        .unwrap();
+    assert_eq!(divergence.call_index, 3);
+    assert_eq!(divergence.field, DivergenceField::Import);
+    assert_eq!(
+        divergence.to_string(),
+        "replay diverged at call 3: expected import the end of the log, found env.read [W0642]"
+    );
+
+    // Running less than recorded.
+    let player = ReplayPlayer::from_log(&store, log);
+    Instance::new(&module, &player)?;
+    let divergence = player.finish().unwrap_err();
+    assert_eq!(divergence.call_index, 0);
+    assert_eq!(divergence.expected, "env.read");
+    assert_eq!(divergence.actual, "the end of the execution");
+    Ok(())
+}
+
+#[compiler_test(replay)]
+fn damaged_logs_are_rejected(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let (log, _) = record(&store, &module, &Env::default())?;
+    let bytes = log.to_bytes();
+
+    let error = ReplayLog::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert!(matches!(error, ReplayLogError::Corrupted { .. }));
+    assert_eq!(error.code(), ErrorCode::ReplayLogCorrupted);
+    assert_eq!(error.failure_kind(), FailureKind::Corrupt);
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(
+        ReplayLog::from_bytes(&trailing).unwrap_err(),
+        ReplayLogError::Corrupted {
+            offset: bytes.len()
+        }
+    );
+    let mut newer = bytes;
+    newer[4] = 2;
+    assert_eq!(
+        ReplayLog::from_bytes(&newer).unwrap_err(),
+        ReplayLogError::UnsupportedVersion(2)
+    );
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (import "env" "double" (func $double (param i32) (result i32)))
+        (memory (export "memory") 1)
+        (global $calls (mut i32) (i32.const 0))
+        (data (i32.const 0) "scoped")
+        (func (export "count") (result i32)
+            global.get $calls
+            i32.const 1
+            i32.add
+            global.set $calls
+            global.get $calls)
+        (func (export "store") (param i32)
+            local.get 0
+            i32.const 42
+            i32.store8)
+        (func (export "double") (param i32) (result i32)
+            local.get 0
+            call $double))
+"#;
+
+#[compiler_test(scoped_instance)]
+fn reused_storage_starts_fresh(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let imports = imports! {
+        "env" => { "double" => Function::new_native(&store, |x: i32| x * 2) },
+    };
+    let scope = InstanceScope::new();
+    for _ in 0..4 {
+        let instance = module.instantiate_scoped(&imports, InstanceConfig::default(), &scope)?;
+        let memory = instance.lookup_memory("memory").unwrap();
+        let bytes = memory.view::<u8>()[..6]
+            .iter()
+            .map(|cell| cell.get())
+            .collect::<Vec<_>>();
+        assert_eq!(bytes, b"scoped");
+
+        let count = instance.get_native_function::<(), i32>("count")?;
+        assert_eq!(count.call()?, 1);
+        assert_eq!(count.call()?, 2);
+        instance.get_native_function::<i32, ()>("store")?.call(0)?;
+        assert_eq!(memory.view::<u8>()[0].get(), 42);
+        let double = instance.get_native_function::<i32, i32>("double")?;
+        assert_eq!(double.call(21)?, 42);
+        assert_eq!(instance.call_sequence(), 4);
+    }
+    Ok(())
+}
+
+#[compiler_test(scoped_instance)]
+fn interleaved_with_owned_instances(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(&store, WAT)?;
+    let other = Module::new(
+        &store,
+        r#"(module (func (export "count") (result i32) i32.const 7))"#,
+    )?;
+    let imports = imports! {
+        "env" => { "double" => Function::new_native(&store, |x: i32| x * 2) },
+    };
+    let mut scope = InstanceScope::new();
+    let owned = Instance::new(&module, &imports)?;
+    {
+        // Instances of different modules may share a scope.
+        let first = module.instantiate_scoped(&imports, InstanceConfig::default(), &scope)?;
+        let second = other.instantiate_scoped(&imports! {}, InstanceConfig::default(), &scope)?;
+        assert_eq!(first.get_native_function::<(), i32>("count")?.call()?, 1);
+        assert_eq!(second.get_native_function::<(), i32>("count")?.call()?, 7);
+    }
+    scope.reset();
+    let scoped = module.instantiate_scoped(&imports, InstanceConfig::default(), &scope)?;
+    assert_eq!(scoped.get_native_function::<(), i32>("count")?.call()?, 1);
+    assert_eq!(owned.get_native_function::<(), i32>("count")?.call()?, 1);
+    Ok(())
+}
+
+#[compiler_test(scoped_instance)]
+fn start_trap(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let module = Module::new(
+        &store,
+        r#"(module (func $start unreachable) (start $start))"#,
+    )?;
+    let scope = InstanceScope::new();
+    for _ in 0..2 {
+        match module.instantiate_scoped(&imports! {}, InstanceConfig::default(), &scope) {
+            Err(InstantiationError::Start(e)) => {
+                assert_eq!(e.trap_code(), Some(TrapCode::UnreachableCodeReached))
+            }
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("instantiation should have failed"),
+        }
+    }
+    Ok(())
+}
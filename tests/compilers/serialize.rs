@@ -1,5 +1,9 @@
 use anyhow::Result;
+use std::convert::TryInto;
 use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{ArtifactEditor, UniversalExecutableRef};
+use wasmer_vm::Artifact;
 
 #[compiler_test(serialize)]
 fn test_serialize(config: crate::Config) -> Result<()> {
@@ -22,66 +26,377 @@ fn test_serialize(config: crate::Config) -> Result<()> {
     Ok(())
 }
 
-// #[compiler_test(serialize)]
-// fn test_deserialize(config: crate::Config) -> Result<()> {
-//     let store = config.store();
-//     let wasm = wat2wasm(r#"
-//         (module $name
-//             (import "host" "sum_part" (func (param i32 i64 i32 f32 f64) (result i64)))
-//             (func (export "test_call") (result i64)
-//                 i32.const 100
-//                 i64.const 200
-//                 i32.const 300
-//                 f32.const 400
-//                 f64.const 500
-//                 call 0
-//             )
-//         )
-//     "#.as_bytes()).unwrap();
-//
-//     let engine = store.engine();
-//     let tunables = BaseTunables::for_target(engine.target());
-//     let executable = engine.compile(&wasm, &tunables).unwrap();
-//     let writer = std::io::Cursor::new(vec![]);
-//     executable.serialize(&mut writer).unwrap();
-//     let serialized_bytes = writer.into_inner();
-//
-//     let headless_store = config.headless_store();
-//
-//
-//     let deserialized_module = unsafe { Module::deserialize(&headless_store, &serialized_bytes)? };
-//     assert_eq!(deserialized_module.name(), Some("name"));
-//     assert_eq!(
-//         deserialized_module.artifact().module_ref().exports().collect::<Vec<_>>(),
-//         module.exports().collect::<Vec<_>>()
-//     );
-//     assert_eq!(
-//         deserialized_module.artifact().module_ref().imports().collect::<Vec<_>>(),
-//         module.imports().collect::<Vec<_>>()
-//     );
-//
-//     let func_type = FunctionType::new(
-//         vec![Type::I32, Type::I64, Type::I32, Type::F32, Type::F64],
-//         vec![Type::I64],
-//     );
-//     let instance = Instance::new(
-//         &module,
-//         &imports! {
-//             "host" => {
-//                 "sum_part" => Function::new(&store, &func_type, |params| {
-//                     let param_0: i64 = params[0].unwrap_i32() as i64;
-//                     let param_1: i64 = params[1].unwrap_i64() as i64;
-//                     let param_2: i64 = params[2].unwrap_i32() as i64;
-//                     let param_3: i64 = params[3].unwrap_f32() as i64;
-//                     let param_4: i64 = params[4].unwrap_f64() as i64;
-//                     Ok(vec![Value::I64(param_0 + param_1 + param_2 + param_3 + param_4)])
-//                 })
-//             }
-//         },
-//     )?;
-//
-//     let test_call = instance.exports.get_function("test_call")?;
-//     let result = test_call.call(&[])?;
-//     assert_eq!(result.to_vec(), vec![Value::I64(1500)]);
-//     Ok(())
-// }
+const SUM: &str = r#"
+    (module $name
+        (import "host" "sum_part" (func (param i32 i64 i32 f32 f64) (result i64)))
+        (func (export "test_call") (result i64)
+            i32.const 100
+            i64.const 200
+            i32.const 300
+            f32.const 400
+            f64.const 500
+            call 0
+        )
+    )
+"#;
+
+#[compiler_test(serialize)]
+fn test_deserialize(config: crate::Config) -> Result<()> {
+    let serialized = {
+        let store = config.store();
+        let module = Module::new(&store, SUM)?;
+        module.serialize()?
+    };
+
+    let headless_store = config.headless_store();
+    let module = unsafe { Module::deserialize(&headless_store, &serialized)? };
+    assert_eq!(
+        module.exports().map(|(name, _)| name).collect::<Vec<_>>(),
+        ["test_call"]
+    );
+
+    let func_type = FunctionType::new(
+        vec![Type::I32, Type::I64, Type::I32, Type::F32, Type::F64],
+        vec![Type::I64],
+    );
+    let instance = Instance::new(
+        &module,
+        &imports! {
+            "host" => {
+                "sum_part" => Function::new(&headless_store, &func_type, |params| {
+                    let param_0: i64 = params[0].unwrap_i32() as i64;
+                    let param_1: i64 = params[1].unwrap_i64() as i64;
+                    let param_2: i64 = params[2].unwrap_i32() as i64;
+                    let param_3: i64 = params[3].unwrap_f32() as i64;
+                    let param_4: i64 = params[4].unwrap_f64() as i64;
+                    Ok(vec![Value::I64(param_0 + param_1 + param_2 + param_3 + param_4)])
+                })
+            }
+        },
+    )?;
+
+    let test_call = instance.get_native_function::<(), i64>("test_call")?;
+    assert_eq!(test_call.call()?, 1500);
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_mismatched(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let serialized = Module::new(&store, SUM)?.serialize()?;
+    let incompatible = |bytes: &[u8]| match unsafe { Module::deserialize(&store, bytes) } {
+        Err(DeserializeError::Incompatible(message)) => message,
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("deserialized a mismatched module"),
+    };
+
+    // The header is 16 bytes of magic, then the format version.
+    let mut other_format = serialized.clone();
+    other_format[16] += 1;
+    assert!(incompatible(&other_format).contains("serialized in format 3"));
+    let mut other_engine = serialized.clone();
+    let version_end = 24 + wasmer_engine_universal::VERSION.len();
+    other_engine[version_end - 1] = b'x';
+    assert!(incompatible(&other_engine).contains("by engine"));
+    assert_eq!(
+        incompatible(b"\0asm\x01\0\0\0"),
+        "the bytes are not a serialized module"
+    );
+
+    // Code compiled for features the target lacks is not loaded.
+    let mut features = serialized[..version_end].to_vec();
+    features.extend(&8u32.to_le_bytes());
+    features.extend(b"QUANTUM1");
+    // The header is padded to the alignment of the executable.
+    features.resize((features.len() + 15) / 16 * 16, 0);
+    let message = incompatible(&features);
+    assert!(
+        message.ends_with("the target lacks: QUANTUM1"),
+        "{}",
+        message
+    );
+
+    let error = unsafe { Module::deserialize(&store, &serialized[..20]) }.unwrap_err();
+    assert!(
+        matches!(error, DeserializeError::CorruptedBinary(_)),
+        "{}",
+        error
+    );
+
+    // Only modules compiled from a binary can be compiled again to be serialized.
+    let module = unsafe { Module::deserialize(&store, &serialized)? };
+    assert!(matches!(
+        module.serialize(),
+        Err(SerializeError::BinaryUnavailable)
+    ));
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_from_file(config: crate::Config) -> Result<()> {
+    let serialized = Module::new(&config.store(), SUM)?.serialize()?;
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("sum.wasmu");
+    std::fs::write(&path, &serialized)?;
+
+    let store = config.headless_store();
+    let module = unsafe { Module::deserialize_from_file(&store, &path)? };
+    let instance = Instance::new(
+        &module,
+        &imports! {
+            "host" => {
+                "sum_part" => Function::new_native(&store, |a: i32, b: i64, c: i32, d: f32, e: f64| {
+                    a as i64 + b + c as i64 + d as i64 + e as i64
+                })
+            }
+        },
+    )?;
+    let test_call = instance.get_native_function::<(), i64>("test_call")?;
+    assert_eq!(test_call.call()?, 1500);
+
+    // Truncated files fail to load rather than being read past their end.
+    for len in &[
+        0,
+        20,
+        serialized.len() / 2,
+        serialized.len() - 8,
+        serialized.len() - 1,
+    ] {
+        std::fs::write(&path, &serialized[..*len])?;
+        assert!(unsafe { Module::deserialize_from_file(&store, &path) }.is_err());
+    }
+    let missing = dir.path().join("missing.wasmu");
+    assert!(matches!(
+        unsafe { Module::deserialize_from_file(&store, &missing) },
+        Err(DeserializeError::Io(_))
+    ));
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_load_executable_from_file(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let store = Store::new(&engine);
+    let wasm = wat2wasm(SUM.as_bytes())?;
+    let executable = engine.compile_universal(&wasm, store.tunables())?;
+    let serialized = Executable::serialize(&executable).unwrap();
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("sum.wasmu");
+    std::fs::write(&path, &serialized)?;
+
+    let artifact = unsafe { engine.load_universal_executable_from_file(&path)? };
+    assert!(artifact.export_field("test_call").is_some());
+
+    for len in &[serialized.len() / 2, serialized.len() - 1] {
+        std::fs::write(&path, &serialized[..*len])?;
+        let error = unsafe { engine.load_universal_executable_from_file(&path) }
+            .err()
+            .unwrap();
+        assert!(
+            matches!(error, DeserializeError::CorruptedBinary(_)),
+            "{}",
+            error
+        );
+    }
+    std::fs::write(&path, b"\0asm\x01\0\0\0")?;
+    let error = unsafe { engine.load_universal_executable_from_file(&path) }
+        .err()
+        .unwrap();
+    assert!(
+        matches!(error, DeserializeError::Incompatible(_)),
+        "{}",
+        error
+    );
+    Ok(())
+}
+
+/// 37 functions calling each other, with relocations for the calls.
+fn many_functions() -> String {
+    let mut wat = String::from("(module (func $f0)");
+    for i in 1..37 {
+        wat.push_str(&format!(" (func $f{} (call $f{}))", i, i - 1));
+    }
+    wat.push(')');
+    wat
+}
+
+fn compile_universal(config: &crate::Config, wat: &str) -> Result<Vec<u8>> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let store = Store::new(&engine);
+    let executable = engine.compile_universal(&wat2wasm(wat.as_bytes())?, store.tunables())?;
+    Ok(Executable::serialize(&executable).unwrap())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_truncated(config: crate::Config) -> Result<()> {
+    let serialized = compile_universal(&config, &many_functions())?;
+    for len in 0..serialized.len() {
+        let error = match unsafe { UniversalExecutableRef::deserialize(&serialized[..len]) } {
+            Err(error) => error,
+            Ok(_) => panic!("deserialized an executable truncated to {} bytes", len),
+        };
+        // Past the magic header, the bytes are those of a damaged executable.
+        if len >= 32 {
+            assert!(
+                matches!(error, DeserializeError::CorruptedBinary(_)),
+                "{}: {}",
+                len,
+                error
+            );
+        }
+    }
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_maximal_lengths(config: crate::Config) -> Result<()> {
+    let serialized = compile_universal(&config, &many_functions())?;
+    let mut position = [0u8; 8];
+    position.copy_from_slice(&serialized[serialized.len() - 8..]);
+    let root = 32 + u64::from_le_bytes(position) as usize;
+
+    // The lengths of the maps of functions in the root are 37: claiming the most a length can
+    // fails before anything is read through the length.
+    let mut patched = 0;
+    for at in (root..serialized.len() - 8).step_by(4) {
+        if serialized[at..at + 4] != 37u32.to_le_bytes() {
+            continue;
+        }
+        for length in &[u32::MAX, 1000] {
+            let mut bytes = serialized.clone();
+            bytes[at..at + 4].copy_from_slice(&length.to_le_bytes());
+            match unsafe { UniversalExecutableRef::deserialize(&bytes) } {
+                Ok(_) => continue,
+                Err(DeserializeError::LimitExceeded(_)) => assert_eq!(*length, u32::MAX),
+                Err(DeserializeError::CorruptedBinary(message)) => {
+                    assert!(message.starts_with("the executable is malformed"))
+                }
+                Err(error) => panic!("unexpected error: {}", error),
+            }
+            patched += 1;
+        }
+    }
+    assert!(patched >= 2);
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_huge_lengths(config: crate::Config) -> Result<()> {
+    let serialized = compile_universal(&config, &many_functions())?;
+
+    // Any length in the executable, down to those of the module info and frame info, that
+    // claims more than the buffer holds fails validation, rather than being allocated for.
+    let mut rejected = 0;
+    for at in (32..serialized.len() - 8).step_by(4) {
+        let value = u32::from_le_bytes(serialized[at..at + 4].try_into().unwrap());
+        if value == 0 || value > 64 {
+            continue;
+        }
+        let mut bytes = serialized.clone();
+        bytes[at..at + 4].copy_from_slice(&0x7fff_ffffu32.to_le_bytes());
+        match unsafe { UniversalExecutableRef::deserialize(&bytes) } {
+            // Not a length, and still valid.
+            Ok(executable) => drop(executable.to_owned()),
+            Err(DeserializeError::CorruptedBinary(_)) | Err(DeserializeError::LimitExceeded(_)) => {
+                rejected += 1
+            }
+            Err(error) => panic!("unexpected error: {}", error),
+        }
+    }
+    assert!(rejected > 0);
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_overlapping(config: crate::Config) -> Result<()> {
+    let serialized = compile_universal(&config, &many_functions())?;
+    let mut editor = unsafe { ArtifactEditor::open(&serialized)? };
+    editor.add_custom_metadata("a", vec![0xaa; 24]);
+    editor.add_custom_metadata("b", vec![0xbb; 24]);
+    let edited = editor.finish()?;
+    unsafe { UniversalExecutableRef::deserialize(&edited)? };
+
+    // Point the bytes of `b` at those of `a`: two values may not claim the same bytes.
+    let find = |byte| edited.windows(24).position(|w| w == [byte; 24]).unwrap();
+    let (a, b) = (find(0xaa), find(0xbb));
+    let at = (32..edited.len() - 8)
+        .step_by(4)
+        .find(|&at| {
+            let offset = i32::from_le_bytes(edited[at..at + 4].try_into().unwrap());
+            at as i64 + offset as i64 == b as i64 && edited[at + 4..at + 8] == 24u32.to_le_bytes()
+        })
+        .expect("the bytes of `b` are pointed to");
+    let mut bytes = edited.clone();
+    bytes[at..at + 4].copy_from_slice(&((a as i64 - at as i64) as i32).to_le_bytes());
+    let error = unsafe { UniversalExecutableRef::deserialize(&bytes) }
+        .err()
+        .expect("deserialized overlapping values");
+    assert!(
+        matches!(error, DeserializeError::CorruptedBinary(_)),
+        "{}",
+        error
+    );
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_limits(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let serialized = Module::new(&store, many_functions())?.serialize()?;
+    let limits = DeserializeLimits::default();
+    unsafe { Module::deserialize_with_limits(&store, &serialized, &limits)? };
+
+    let exceeded = |limits: DeserializeLimits| match unsafe {
+        Module::deserialize_with_limits(&store, &serialized, &limits)
+    } {
+        Err(error @ DeserializeError::LimitExceeded(_)) => {
+            assert_eq!(error.code(), ErrorCode::DeserializeLimitExceeded);
+            error.to_string()
+        }
+        Err(error) => panic!("unexpected error: {}", error),
+        Ok(_) => panic!("deserialized a module over the limits"),
+    };
+    let message = exceeded(DeserializeLimits {
+        max_functions: 36,
+        ..limits
+    });
+    assert_eq!(
+        message,
+        "binary too large: 37 functions, more than the limit of 36 [W0214]"
+    );
+    assert!(exceeded(DeserializeLimits {
+        max_total_size: 64,
+        ..limits
+    })
+    .contains("more than the limit of 64"));
+    assert!(exceeded(DeserializeLimits {
+        max_relocations: 0,
+        ..limits
+    })
+    .contains("relocations"));
+    Ok(())
+}
+
+#[compiler_test(serialize)]
+fn test_deserialize_custom_metadata_limit(config: crate::Config) -> Result<()> {
+    let serialized = compile_universal(&config, &many_functions())?;
+    let mut editor = unsafe { ArtifactEditor::open(&serialized)? };
+    editor.add_custom_metadata("build", b"2024-05-01".to_vec());
+    editor.add_custom_metadata("commit", b"4fe2a1".to_vec());
+    let edited = editor.finish()?;
+
+    let limits = DeserializeLimits {
+        max_custom_metadata: 1,
+        ..DeserializeLimits::default()
+    };
+    unsafe { UniversalExecutableRef::deserialize_with_limits(&serialized, &limits)? };
+    let error = unsafe { UniversalExecutableRef::deserialize_with_limits(&edited, &limits) }
+        .err()
+        .expect("deserialized an executable over the limits");
+    assert!(
+        error.to_string().contains("2 custom metadata entries"),
+        "{}",
+        error
+    );
+    Ok(())
+}
@@ -0,0 +1,276 @@
+//! Snapshots of the state of instances, streamed to and from bytes.
+
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (type $nullary (func (result i32)))
+        (memory (export "memory") 1 4096)
+        (global $count (mut i32) (i32.const 0))
+        (global $wide (mut i64) (i64.const 0))
+        (global $float (mut f64) (f64.const 0))
+        (global $callee (mut funcref) (ref.null func))
+        (table $t 2 8 funcref)
+        (table $scratch 1 funcref)
+        (elem (i32.const 0) $one)
+        (elem declare func $two)
+        (func $one (result i32) (i32.const 1))
+        (func $two (result i32) (i32.const 2))
+        (func (export "grow") (param i32) (result i32)
+            (memory.grow (local.get 0)))
+        (func (export "touch") (param $page i32) (param $value i64)
+            (global.set $count (i32.add (global.get $count) (i32.const 1)))
+            (global.set $wide (local.get $value))
+            (global.set $float (f64.convert_i64_s (local.get $value)))
+            (global.set $callee (ref.func $two))
+            (drop (table.grow $t (ref.func $two) (i32.const 1)))
+            (i64.store (i32.mul (local.get $page) (i32.const 65536)) (local.get $value)))
+        (func (export "count") (result i32) (global.get $count))
+        (func (export "wide") (result i64) (global.get $wide))
+        (func (export "float") (result f64) (global.get $float))
+        (func (export "callee") (result i32)
+            (if (ref.is_null (global.get $callee)) (then (return (i32.const -1))))
+            (table.set $scratch (i32.const 0) (global.get $callee))
+            (call_indirect $scratch (type $nullary) (i32.const 0)))
+        (func (export "table_size") (result i32) (table.size $t))
+        (func (export "call") (param i32) (result i32)
+            (call_indirect $t (type $nullary) (local.get 0))))
+"#;
+
+fn instantiate(store: &Store) -> Result<Instance> {
+    Ok(Instance::new(&Module::new(store, WAT)?, &imports! {})?)
+}
+
+/// The state of an instance, as seen through its exports.
+#[derive(Debug, PartialEq)]
+struct State {
+    count: i32,
+    wide: i64,
+    float: f64,
+    callee: i32,
+    table: Vec<Result<i32, String>>,
+    pages: Pages,
+}
+
+fn state(instance: &Instance) -> Result<State> {
+    let call = instance.get_native_function::<i32, i32>("call")?;
+    let size = instance
+        .get_native_function::<(), i32>("table_size")?
+        .call()?;
+    Ok(State {
+        count: instance.get_native_function::<(), i32>("count")?.call()?,
+        wide: instance.get_native_function::<(), i64>("wide")?.call()?,
+        float: instance.get_native_function::<(), f64>("float")?.call()?,
+        callee: instance.get_native_function::<(), i32>("callee")?.call()?,
+        table: (0..size)
+            .map(|index| call.call(index).map_err(|error| error.message()))
+            .collect(),
+        pages: instance.lookup_memory("memory").unwrap().size(),
+    })
+}
+
+/// Assert that the memories, tables and globals of `a` and `b` hold the same values.
+fn compare_instances(a: &Instance, b: &Instance) -> Result<()> {
+    assert_eq!(state(a)?, state(b)?);
+    let (a, b) = (
+        a.lookup_memory("memory").unwrap(),
+        b.lookup_memory("memory").unwrap(),
+    );
+    // SAFETY: no call into the instances is running.
+    assert!(unsafe { a.data_unchecked() == b.data_unchecked() });
+    Ok(())
+}
+
+fn snapshot(instance: &Instance) -> Result<(Vec<u8>, SnapshotStats)> {
+    let mut bytes = vec![];
+    let stats = instance.snapshot_to(&mut bytes, SnapshotOptions::new())?;
+    assert_eq!(stats.bytes, bytes.len() as u64);
+    Ok((bytes, stats))
+}
+
+#[compiler_test(snapshot)]
+fn snapshots_round_trip(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let instance = instantiate(&store)?;
+    assert_eq!(
+        instance.get_native_function::<i32, i32>("grow")?.call(2)?,
+        1
+    );
+    let touch = instance.get_native_function::<(i32, i64), ()>("touch")?;
+    touch.call(0, 7)?;
+    touch.call(2, -1)?;
+
+    let (bytes, stats) = snapshot(&instance)?;
+    assert_eq!((stats.pages, stats.zero_pages), (2, 1));
+    let restored = instantiate(&store)?;
+    restored.restore_from(&bytes[..])?;
+    compare_instances(&instance, &restored)?;
+    assert_eq!(
+        state(&restored)?.table,
+        vec![
+            Ok(1),
+            Err("uninitialized element".to_string()),
+            Ok(2),
+            Ok(2)
+        ]
+    );
+
+    // Restoring over a modified instance brings it back to the snapshot, including the pages
+    // written since, which the snapshot leaves out.
+    let memory = restored.lookup_memory("memory").unwrap();
+    memory.view::<u8>()[WASM_PAGE_SIZE + 3].set(9);
+    restored.restore_from(&bytes[..])?;
+    compare_instances(&instance, &restored)?;
+
+    // Tables cannot shrink back to the size they had in the snapshot.
+    let touch = restored.get_native_function::<(i32, i64), ()>("touch")?;
+    touch.call(1, 3)?;
+    let error = restored.restore_from(&bytes[..]).unwrap_err();
+    assert!(matches!(error, SnapshotError::Mismatch(_)), "{}", error);
+    assert!(!restored.is_poisoned());
+
+    // Buffer sizes do not change the snapshot.
+    let mut small = vec![];
+    instance.snapshot_to(&mut small, SnapshotOptions::new().with_buffer_size(16))?;
+    assert!(small == snapshot(&instance)?.0);
+    Ok(())
+}
+
+#[compiler_test(snapshot)]
+fn sparse_memories_snapshot_the_pages_written(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let instance = instantiate(&store)?;
+    // A memory of 256 MiB, three pages of which are written to.
+    assert_eq!(
+        instance
+            .get_native_function::<i32, i32>("grow")?
+            .call(4095)?,
+        1
+    );
+    let touch = instance.get_native_function::<(i32, i64), ()>("touch")?;
+    for &page in &[0, 1234, 4095] {
+        touch.call(page, i64::from(page) + 1)?;
+    }
+
+    let (bytes, stats) = snapshot(&instance)?;
+    assert_eq!((stats.pages, stats.zero_pages), (3, 4093));
+    // Each page written takes its index, its checksum and its contents, which dwarf the rest.
+    let page_bytes = 3 * (4 + 32 + WASM_PAGE_SIZE);
+    assert!(
+        page_bytes < bytes.len() && bytes.len() < page_bytes + 256,
+        "{} bytes",
+        bytes.len()
+    );
+
+    let restored = instantiate(&store)?;
+    restored.restore_from(&bytes[..])?;
+    compare_instances(&instance, &restored)?;
+    Ok(())
+}
+
+#[compiler_test(snapshot)]
+fn corrupt_pages_are_reported(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let instance = instantiate(&store)?;
+    instance.get_native_function::<i32, i32>("grow")?.call(9)?;
+    let touch = instance.get_native_function::<(i32, i64), ()>("touch")?;
+    for &page in &[0, 5, 9] {
+        touch.call(page, 0x0123_4567_89ab_cd00 + i64::from(page))?;
+    }
+    let (mut bytes, _) = snapshot(&instance)?;
+    let value = 0x0123_4567_89ab_cd05_i64.to_le_bytes();
+    let offset = bytes
+        .windows(value.len())
+        .position(|window| window == value)
+        .unwrap();
+    bytes[offset + 1] ^= 1;
+
+    let restored = instantiate(&store)?;
+    let error = restored.restore_from(&bytes[..]).unwrap_err();
+    assert!(
+        matches!(error, SnapshotError::CorruptPage { memory: 0, page: 5 }),
+        "{}",
+        error
+    );
+    assert_eq!(error.code(), ErrorCode::SnapshotCorruptPage);
+    assert_eq!(error.failure_kind(), FailureKind::Corrupt);
+    assert!(restored.is_poisoned());
+
+    // Corruption outside of the pages shows in the hash of the snapshot.
+    let (mut bytes, _) = snapshot(&instance)?;
+    let count = 3_i32.to_le_bytes();
+    let offset = bytes
+        .windows(count.len())
+        .position(|window| window == count)
+        .unwrap();
+    bytes[offset] ^= 1;
+    let error = restored.restore_from(&bytes[..]).unwrap_err();
+    assert!(matches!(error, SnapshotError::Corrupt(_)), "{}", error);
+    assert!(restored.is_poisoned());
+
+    // So does truncation.
+    let (bytes, _) = snapshot(&instance)?;
+    let error = restored
+        .restore_from(&bytes[..bytes.len() - 1])
+        .unwrap_err();
+    assert!(matches!(error, SnapshotError::Corrupt(_)), "{}", error);
+    Ok(())
+}
+
+#[compiler_test(snapshot)]
+fn poisoned_instances_must_be_restored(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let instance = instantiate(&store)?;
+    instance
+        .get_native_function::<(i32, i64), ()>("touch")?
+        .call(0, 42)?;
+    let (bytes, _) = snapshot(&instance)?;
+
+    let restored = instantiate(&store)?;
+    let count = restored.get_native_function::<(), i32>("count")?;
+    let error = restored
+        .restore_from(&bytes[..bytes.len() / 2])
+        .unwrap_err();
+    assert!(matches!(error, SnapshotError::Corrupt(_)), "{}", error);
+
+    // Calls into the poisoned instance trap, whether through a native or a dynamic function,
+    // and it has no state to snapshot.
+    let error = count.call().unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::PoisonedInstance));
+    assert_eq!(error.code(), ErrorCode::TrapPoisonedInstance);
+    let error = restored
+        .lookup_function("count")
+        .unwrap()
+        .call(&[])
+        .unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::PoisonedInstance));
+    let error = restored
+        .snapshot_to(vec![], SnapshotOptions::new())
+        .unwrap_err();
+    assert!(matches!(error, SnapshotError::Poisoned), "{}", error);
+    assert_eq!(error.code(), ErrorCode::SnapshotPoisoned);
+
+    // Snapshots that do not fit the instance are rejected before it is modified, which
+    // leaves it poisoned.
+    let other = Instance::new(&Module::new(&store, "(module (memory 1))")?, &imports! {})?;
+    let (other, _) = snapshot(&other)?;
+    let error = restored.restore_from(&other[..]).unwrap_err();
+    assert!(matches!(error, SnapshotError::Mismatch(_)), "{}", error);
+    assert_eq!(error.code(), ErrorCode::SnapshotMismatch);
+    let error = restored.restore_from(&b"not a snapshot"[..]).unwrap_err();
+    assert!(matches!(error, SnapshotError::Format(_)), "{}", error);
+    assert!(restored.is_poisoned());
+
+    // A successful restore lifts the poison.
+    restored.restore_from(&bytes[..])?;
+    assert!(!restored.is_poisoned());
+    assert_eq!(count.call()?, 1);
+    compare_instances(&instance, &restored)?;
+
+    // Instances that were not poisoned are left as they were by rejected snapshots.
+    let error = instance.restore_from(&other[..]).unwrap_err();
+    assert!(matches!(error, SnapshotError::Mismatch(_)), "{}", error);
+    assert!(!instance.is_poisoned());
+    Ok(())
+}
@@ -0,0 +1,14 @@
+;; module contract
+;; interface sha256:14ed7a02727fbcc7927146c97ba15cdddb727de6d920ae2982fbc9e64653aa84
+(import "env" "log" (func $log (param $p0 i32) (param $p1 i32)))
+(import "env" "memory" (memory 1 16))
+(import "host" "now" (func $now (result i64)))
+(import "env" "gas_price" (global i64))
+(import "host" "counter" (global (mut i32)))
+(export "log" (func $log (param $p0 i32) (param $p1 i32)))
+(export "transfer" (func $transfer (param $to i32) (param $amount i64) (result i32)))
+(export "scaled" (func $f3 (param $x i32) (param $p1 f32) (result f32)))
+(export "table" (table 2 10 funcref))
+(export "memory" (memory 1 16))
+(export "total" (global (mut i64)))
+(export "scale" (global f64))
@@ -0,0 +1,162 @@
+//! Source locations of module offsets, from the debug information embedded in the module.
+//!
+//! Both fixtures contain the code of:
+//!
+//! ```wat
+//! (module
+//!     (func (export "run") call 1)      ;; `call` at 45
+//!     (func nop unreachable)            ;; at 50 and 51, `end` at 52
+//!     (func (export "div") (param i32) (result i32)
+//!         i32.const 10                  ;; at 55
+//!         local.get 0                   ;; at 57
+//!         i32.div_s))                   ;; at 59
+//! ```
+//!
+//! `source_map.wasm` embeds a source map with `sourceRoot` "src" as a `data:` URL, and
+//! `dwarf.wasm` has a DWARF 4 line table unit for the first two functions and a DWARF 5 one for
+//! the last. The source map is only read with the `source-map-json` feature.
+
+use anyhow::Result;
+use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{Universal, UniversalExecutableRef};
+
+const SOURCE_MAP_WASM: &[u8] = include_bytes!("fixtures/source_map.wasm");
+const DWARF_WASM: &[u8] = include_bytes!("fixtures/dwarf.wasm");
+
+fn engine(config: &crate::Config, retain: bool) -> UniversalEngine {
+    Universal::new(config.compiler_config(false))
+        .retain_source_maps(retain)
+        .engine()
+}
+
+fn resolve(engine: &UniversalEngine, wasm: &[u8], offset: usize) -> Result<Option<String>> {
+    let executable = engine.compile_universal(wasm, &BaseTunables::for_target(engine.target()))?;
+    let artifact = engine.load_universal_executable(&executable)?;
+    Ok(artifact.resolve_source(offset).map(|l| l.to_string()))
+}
+
+fn trap(store: &Store, wasm: &[u8], function: &str) -> Result<RuntimeError> {
+    let module = Module::new(store, wasm)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let error = match function {
+        "div" => instance
+            .get_native_function::<i32, i32>("div")?
+            .call(0)
+            .unwrap_err(),
+        _ => instance
+            .get_native_function::<(), ()>(function)?
+            .call()
+            .unwrap_err(),
+    };
+    Ok(error)
+}
+
+#[cfg(feature = "source-map-json")]
+#[compiler_test(source_maps)]
+fn embedded_source_map(config: crate::Config) -> Result<()> {
+    let engine = engine(&config, true);
+    let resolve = |offset| resolve(&engine, SOURCE_MAP_WASM, offset).unwrap();
+    assert_eq!(resolve(0), None);
+    assert_eq!(resolve(44), None);
+    assert_eq!(resolve(45).as_deref(), Some("src/lib.rs:10:5"));
+    assert_eq!(resolve(50).as_deref(), Some("src/util.rs:3:5"));
+    assert_eq!(resolve(51).as_deref(), Some("src/lib.rs:142:9"));
+    // The end of the second function is explicitly unmapped.
+    assert_eq!(resolve(52), None);
+    assert_eq!(resolve(55).as_deref(), Some("src/lib.rs:20:1"));
+    assert_eq!(resolve(57).as_deref(), Some("src/lib.rs:20:1"));
+    assert_eq!(resolve(59).as_deref(), Some("src/lib.rs:21:14"));
+
+    let store = Store::new(&engine);
+    let error = trap(&store, SOURCE_MAP_WASM, "run")?;
+    let location = error.trace()[0].source_location().unwrap();
+    assert_eq!(
+        (location.file(), location.line(), location.column()),
+        ("src/lib.rs", 142, Some(9))
+    );
+    let message = error.to_string();
+    assert!(
+        message.contains("[1]:0x33) at src/lib.rs:142:9"),
+        "{}",
+        message
+    );
+    Ok(())
+}
+
+#[compiler_test(source_maps)]
+fn dwarf_line_tables(config: crate::Config) -> Result<()> {
+    let engine = engine(&config, true);
+    let resolve = |offset| resolve(&engine, DWARF_WASM, offset).unwrap();
+    assert_eq!(resolve(44), None);
+    assert_eq!(resolve(45).as_deref(), Some("/work/src/lib.rs:10:5"));
+    assert_eq!(resolve(50).as_deref(), Some("/work/src/util.rs:3:5"));
+    assert_eq!(resolve(51).as_deref(), Some("/work/src/lib.rs:142:9"));
+    // Line 0 has no source, and the sequences end after each function.
+    assert_eq!(resolve(52), None);
+    assert_eq!(resolve(53), None);
+    assert_eq!(resolve(55).as_deref(), Some("/work/src/div.rs:20"));
+    assert_eq!(resolve(59).as_deref(), Some("/work/src/div.rs:21:14"));
+    assert_eq!(resolve(60), None);
+
+    let store = Store::new(&engine);
+    let error = trap(&store, DWARF_WASM, "div")?;
+    assert_eq!(
+        error.trace()[0]
+            .source_location()
+            .map(|l| l.to_string())
+            .as_deref(),
+        Some("/work/src/div.rs:21:14")
+    );
+    Ok(())
+}
+
+#[compiler_test(source_maps)]
+fn disabled_by_default(config: crate::Config) -> Result<()> {
+    let engine = engine(&config, false);
+    assert_eq!(resolve(&engine, SOURCE_MAP_WASM, 51)?, None);
+
+    let store = Store::new(&engine);
+    let error = trap(&store, SOURCE_MAP_WASM, "run")?;
+    assert!(error.trace().iter().all(|f| f.source_location().is_none()));
+    assert!(!error.to_string().contains(" at src/"));
+    Ok(())
+}
+
+#[compiler_test(source_maps)]
+fn size_limit(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(config.compiler_config(false))
+        .retain_source_maps(true)
+        .source_map_size_limit(64)
+        .engine();
+    let executable =
+        engine.compile_universal(DWARF_WASM, &BaseTunables::for_target(engine.target()))?;
+    assert!(executable.source_map().is_none());
+
+    let engine = Universal::new(config.compiler_config(false))
+        .retain_source_maps(true)
+        .source_map_size_limit(4096)
+        .engine();
+    let executable =
+        engine.compile_universal(DWARF_WASM, &BaseTunables::for_target(engine.target()))?;
+    assert!(executable.source_map().is_some());
+    Ok(())
+}
+
+#[compiler_test(source_maps)]
+fn survives_serialization(config: crate::Config) -> Result<()> {
+    let engine = engine(&config, true);
+    let executable =
+        engine.compile_universal(DWARF_WASM, &BaseTunables::for_target(engine.target()))?;
+    let serialized = executable.serialize().unwrap();
+    let deserialized = unsafe { UniversalExecutableRef::deserialize(&serialized)? };
+    let artifact = engine.load_universal_executable_ref(&deserialized)?;
+    assert_eq!(
+        artifact
+            .resolve_source(51)
+            .map(|l| l.to_string())
+            .as_deref(),
+        Some("/work/src/lib.rs:142:9")
+    );
+    Ok(())
+}
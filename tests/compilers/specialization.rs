@@ -0,0 +1,204 @@
+//! Modules specialized for the values of their immutable imported globals.
+
+use anyhow::Result;
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{Universal, UniversalEngine, UniversalExecutableRef};
+
+/// Sums the integers below `env.max_items`, and returns `env.scale`.
+const WAT: &str = r#"
+    (module
+        (import "env" "max_items" (global $max_items i32))
+        (import "env" "scale" (global $scale f64))
+        (import "env" "counter" (global $counter (mut i32)))
+        (func (export "sum") (result i32)
+            (local $i i32) (local $sum i32)
+            (block $done
+                (loop $loop
+                    (br_if $done (i32.ge_u (local.get $i) (global.get $max_items)))
+                    (local.set $sum (i32.add (local.get $sum) (local.get $i)))
+                    (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                    (br $loop)))
+            (local.get $sum))
+        (func (export "scale") (result f64)
+            global.get $scale))
+"#;
+
+const BINDINGS: &[(&str, &str, Value)] = &[
+    ("env", "max_items", Value::I32(1000)),
+    ("env", "scale", Value::F64(2.5)),
+];
+
+fn engine(config: &crate::Config) -> (UniversalEngine, Store) {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let store = Store::new(&engine);
+    (engine, store)
+}
+
+fn imports(store: &Store, max_items: i32) -> ImportObject {
+    imports! {
+        "env" => {
+            "max_items" => Global::new(store, Value::I32(max_items)),
+            "scale" => Global::new(store, Value::F64(2.5)),
+            "counter" => Global::new_mut(store, Value::I32(0)),
+        },
+    }
+}
+
+#[compiler_test(specialization)]
+fn bound_globals_are_folded(config: crate::Config) -> Result<()> {
+    let (engine, store) = engine(&config);
+    let wasm = wat2wasm(WAT.as_bytes())?;
+    let sum_length = |executable: &wasmer_engine_universal::UniversalExecutable| {
+        executable.function_bodies()[LocalFunctionIndex::from_u32(0)]
+            .body
+            .len()
+    };
+    let generic = engine.compile_universal(&wasm, store.tunables())?;
+    let specialized = engine.compile_universal_specialized(
+        &wasm,
+        store.tunables(),
+        &[("env", "max_items", GlobalInit::I32Const(1000))],
+    )?;
+    // The loop compares with a constant rather than loading the global on every iteration.
+    assert!(sum_length(&specialized) < sum_length(&generic));
+
+    let generic = Module::new(&store, WAT)?;
+    for module in &[generic.clone(), generic.specialize(BINDINGS)?] {
+        let instance = Instance::new(module, &imports(&store, 1000))?;
+        assert_eq!(
+            instance.get_native_function::<(), i32>("sum")?.call()?,
+            499500
+        );
+        assert_eq!(
+            instance.get_native_function::<(), f64>("scale")?.call()?,
+            2.5
+        );
+    }
+    Ok(())
+}
+
+#[compiler_test(specialization)]
+fn mismatched_global_is_rejected(config: crate::Config) -> Result<()> {
+    let (_, store) = engine(&config);
+    let module = Module::new(&store, WAT)?.specialize(BINDINGS)?;
+    match Instance::new(&module, &imports(&store, 7)) {
+        Err(error @ InstantiationError::SpecializationMismatch(_)) => {
+            let message = error.to_string();
+            assert!(message.contains("env.max_items is bound to I32Const(1000)"));
+            assert!(message.contains("given I32Const(7)"));
+            assert_eq!(error.code(), ErrorCode::InstantiationSpecializationMismatch);
+        }
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+
+    // The runtime can provide the bound globals instead, whatever the resolver has.
+    let config = InstanceConfig::default().with_bound_globals_provided(true);
+    let instance = Instance::new_with_config(&module, config.clone(), &imports(&store, 7))?;
+    assert_eq!(
+        instance.get_native_function::<(), i32>("sum")?.call()?,
+        499500
+    );
+    let imports = imports! {
+        "env" => { "counter" => Global::new_mut(&store, Value::I32(0)) },
+    };
+    let instance = Instance::new_with_config(&module, config, &imports)?;
+    assert_eq!(
+        instance.get_native_function::<(), f64>("scale")?.call()?,
+        2.5
+    );
+    Ok(())
+}
+
+#[compiler_test(specialization)]
+fn bindings_survive_serialization(config: crate::Config) -> Result<()> {
+    let (engine, store) = engine(&config);
+    let wasm = wat2wasm(WAT.as_bytes())?;
+    let executable = engine.compile_universal_specialized(
+        &wasm,
+        store.tunables(),
+        &[("env", "max_items", GlobalInit::I32Const(10))],
+    )?;
+    let serialized = executable.serialize().unwrap();
+    let deserialized = unsafe { UniversalExecutableRef::deserialize(&serialized)? };
+    let module = Module::from_executable(&store, &deserialized)?;
+
+    assert_eq!(
+        module.global_bindings(),
+        [("env", "max_items", Value::I32(10))]
+    );
+    assert_eq!(
+        module.specialization_hash(),
+        executable.specialization_hash()
+    );
+    assert!(matches!(
+        Instance::new(&module, &imports(&store, 1000)),
+        Err(InstantiationError::SpecializationMismatch(_))
+    ));
+    let instance = Instance::new(&module, &imports(&store, 10))?;
+    assert_eq!(instance.get_native_function::<(), i32>("sum")?.call()?, 45);
+    Ok(())
+}
+
+#[compiler_test(specialization)]
+fn specializations_are_cached_by_bindings(config: crate::Config) -> Result<()> {
+    let (engine, store) = engine(&config);
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    let module = Module::new(&store, WAT)?;
+    assert_eq!(module.specialization_hash(), None);
+    assert_eq!(module.global_bindings(), []);
+
+    let specialized = module.specialize(BINDINGS)?;
+    let reordered = module.specialize(&[BINDINGS[1].clone(), BINDINGS[0].clone()])?;
+    let other = module.specialize(&[("env", "max_items", Value::I32(1001))])?;
+    assert_eq!(sink.snapshot().compilations_started, 3);
+
+    let hash = specialized.specialization_hash().unwrap();
+    assert_eq!(reordered.specialization_hash(), Some(hash));
+    assert_ne!(other.specialization_hash(), Some(hash));
+    assert_eq!(specialized.global_bindings(), BINDINGS);
+
+    // Nothing is cached once the specialized modules are dropped.
+    drop((specialized, reordered));
+    module.specialize(BINDINGS)?;
+    assert_eq!(sink.snapshot().compilations_started, 4);
+    Ok(())
+}
+
+#[compiler_test(specialization)]
+fn invalid_bindings_are_rejected(config: crate::Config) -> Result<()> {
+    let (engine, store) = engine(&config);
+    let module = Module::new(&store, WAT)?;
+    for (binding, reason) in &[
+        (
+            ("env", "missing", Value::I32(1)),
+            "is not an imported global",
+        ),
+        (("env", "counter", Value::I32(1)), "is mutable"),
+        (("env", "max_items", Value::I64(1)), "is of type I32"),
+        (("env", "scale", Value::FuncRef(None)), "cannot be bound"),
+    ] {
+        match module.specialize(&[binding.clone()]) {
+            Err(error @ CompileError::Specialization(_)) => {
+                assert!(error.to_string().contains(reason), "{}", error);
+                assert_eq!(error.code(), ErrorCode::CompileSpecialization);
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+    assert!(matches!(
+        module.specialize(&[BINDINGS[0].clone(), BINDINGS[0].clone()]),
+        Err(CompileError::Specialization(_))
+    ));
+
+    // Modules loaded from executables do not have their binary.
+    let executable = engine.compile_universal(&wat2wasm(WAT.as_bytes())?, store.tunables())?;
+    let module = Module::from_executable(&store, &executable)?;
+    assert!(matches!(
+        module.specialize(BINDINGS),
+        Err(CompileError::Specialization(_))
+    ));
+    Ok(())
+}
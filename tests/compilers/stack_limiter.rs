@@ -232,3 +232,49 @@ fn deep_but_sane() {
     let e = main_func.call(&[]);
     assert!(e.is_ok());
 }
+
+/// The depth at which a recursion traps with a `bytes` stack limit, on a thread with
+/// `native_stack` bytes of native stack.
+fn recursion_depth(bytes: usize, native_stack: usize) -> i32 {
+    let wat = r#"
+        (global $depth (export "depth") (mut i32) (i32.const 0))
+        (func $recurse (export "recurse") (param i64 i64)
+            (global.set $depth (i32.add (global.get $depth) (i32.const 1)))
+            (call $recurse (local.get 0) (local.get 1))
+        )
+    "#;
+    std::thread::Builder::new()
+        .stack_size(native_stack)
+        .spawn(move || {
+            let store = get_store();
+            let module = Module::new(&store, &wat).unwrap();
+            let instance = Instance::new_with_config(
+                &module,
+                unsafe { InstanceConfig::default().with_max_wasm_stack(bytes) },
+                &imports! {},
+            )
+            .unwrap();
+            let recurse = instance
+                .get_native_function::<(i64, i64), ()>("recurse")
+                .unwrap();
+            let error = recurse.call(1, 2).unwrap_err();
+            assert_eq!(error.to_trap(), Some(TrapCode::StackOverflow));
+            match Extern::from_vm_export(&store, instance.lookup("depth").unwrap()) {
+                Extern::Global(global) => global.get().unwrap_i32(),
+                _ => panic!("the contract should export its depth"),
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap()
+}
+
+#[test]
+fn max_wasm_stack_is_deterministic() {
+    let small = recursion_depth(64 * 1024, 1024 * 1024);
+    assert!(small > 100);
+    assert_eq!(recursion_depth(64 * 1024, 32 * 1024 * 1024), small);
+    // The limit is shared by the frames, whatever their number.
+    let large = recursion_depth(128 * 1024, 1024 * 1024);
+    assert!((2 * small - 1..=2 * small + 1).contains(&large));
+}
@@ -0,0 +1,140 @@
+//! Compiling modules read in chunks, validating them as they arrive.
+
+use anyhow::Result;
+use std::io::{self, Read};
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (memory (export "memory") 1)
+        (data (i32.const 0) "hello")
+        (func $double (param i32) (result i32)
+            (i32.mul (local.get 0) (i32.const 2)))
+        (func (export "quadruple") (param i32) (result i32)
+            (call $double (call $double (local.get 0))))
+        (func (export "first_byte") (result i32)
+            (i32.load8_u (i32.const 0))))
+"#;
+
+/// Reads at most `chunk` bytes at a time from `bytes`, then fails with `error` if any.
+struct Trickle<'a> {
+    bytes: &'a [u8],
+    chunk: usize,
+    error: Option<io::ErrorKind>,
+}
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.bytes.is_empty() {
+            return match self.error {
+                Some(kind) => Err(io::Error::new(kind, "connection lost")),
+                None => Ok(0),
+            };
+        }
+        let len = self.chunk.min(buf.len()).min(self.bytes.len());
+        let (read, rest) = self.bytes.split_at(len);
+        buf[..len].copy_from_slice(read);
+        self.bytes = rest;
+        Ok(len)
+    }
+}
+
+fn trickle(bytes: &[u8], chunk: usize) -> Trickle<'_> {
+    Trickle {
+        bytes,
+        chunk,
+        error: None,
+    }
+}
+
+#[compiler_test(streaming)]
+fn modules_can_be_read_in_tiny_chunks(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wasm = wat2wasm(WAT.as_bytes())?;
+    for chunk in &[1, 2, 3, 7, wasm.len()] {
+        let module = Module::from_reader(&store, trickle(&wasm, *chunk))?;
+        let instance = Instance::new(&module, &imports! {})?;
+        let quadruple = instance.get_native_function::<i32, i32>("quadruple")?;
+        assert_eq!(quadruple.call(5)?, 20);
+        let first_byte = instance.get_native_function::<(), i32>("first_byte")?;
+        assert_eq!(first_byte.call()?, i32::from(b'h'));
+    }
+    Ok(())
+}
+
+/// The offsets at which the header and each section of `wasm` end.
+fn section_ends(wasm: &[u8]) -> Vec<usize> {
+    let mut ends = vec![8];
+    let mut offset = 8;
+    while offset < wasm.len() {
+        // The id of the section, then its size as a LEB128.
+        offset += 1;
+        let (mut size, mut shift) = (0, 0);
+        loop {
+            let byte = wasm[offset];
+            offset += 1;
+            size |= usize::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        offset += size;
+        ends.push(offset);
+    }
+    ends
+}
+
+#[compiler_test(streaming)]
+fn truncated_modules_are_malformed(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wasm = wat2wasm(WAT.as_bytes())?;
+    // Modules may end after any section, such as the empty module ending after the header.
+    let ends = section_ends(&wasm);
+    for len in (0..wasm.len()).filter(|len| !ends.contains(len)) {
+        match Module::from_reader(&store, trickle(&wasm[..len], 3)) {
+            Err(IoCompileError::Compile(CompileError::Wasm(_))) => {}
+            Err(other) => panic!("unexpected error for {} bytes: {}", len, other),
+            Ok(_) => panic!("{} bytes of the module were accepted", len),
+        }
+    }
+    Ok(())
+}
+
+#[compiler_test(streaming)]
+fn invalid_bodies_fail_before_the_end(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let mut wasm = wat2wasm(
+        br#"
+        (module
+            (func (result i32) (i64.const 0))
+            (func (result i32) (i32.const 0)))
+        "#,
+    )?
+    .into_owned();
+    // The first function body does not validate, and the module never ends: the reader
+    // failing after the bytes of the module shows that they were validated first.
+    wasm.truncate(wasm.len() - 1);
+    let reader = Trickle {
+        bytes: &wasm,
+        chunk: 1,
+        error: Some(io::ErrorKind::ConnectionReset),
+    };
+    match Module::from_reader(&store, reader) {
+        Err(IoCompileError::Compile(CompileError::Validate(_))) => {}
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+
+    // Without validation errors, reading errors are reported as such.
+    let valid = wat2wasm(WAT.as_bytes())?;
+    let reader = Trickle {
+        bytes: &valid[..valid.len() / 2],
+        chunk: 4,
+        error: Some(io::ErrorKind::ConnectionReset),
+    };
+    match Module::from_reader(&store, reader) {
+        Err(IoCompileError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::ConnectionReset),
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    Ok(())
+}
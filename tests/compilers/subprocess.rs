@@ -0,0 +1,160 @@
+use anyhow::Result;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+use std::time::Duration;
+use wasmer::*;
+use wasmer_engine_universal::{SubprocessCompiler, SubprocessLimits};
+
+const HELPER: &str = env!("CARGO_BIN_EXE_wasmer-compile-helper");
+
+const WAT: &str = r#"(module
+    (func (export "add") (param i32 i32) (result i32)
+        (i32.add (local.get 0) (local.get 1))))"#;
+
+fn scripts_dir() -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join("subprocess-helpers")
+}
+
+/// Path of the fake helper `name`, which writes its pid to `<name>.pid` next to it.
+///
+/// All the scripts are written at once, before any of them runs: executing a file while
+/// another thread holds it open for writing fails.
+fn script(name: &str) -> PathBuf {
+    static WRITE: Once = Once::new();
+    WRITE.call_once(|| {
+        let scripts = [
+            ("crash", "cat > /dev/null\nkill -KILL $$"),
+            ("hang", "exec sleep 60"),
+            ("garbage", "cat > /dev/null\nprintf 'garbage'"),
+        ];
+        fs::create_dir_all(scripts_dir()).unwrap();
+        for (name, body) in scripts.iter() {
+            let path = scripts_dir().join(name);
+            let pid_file = scripts_dir().join(format!("{}.pid", name));
+            let content = format!("#!/bin/sh\necho $$ > '{}'\n{}\n", pid_file.display(), body);
+            fs::write(&path, content).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+    });
+    scripts_dir().join(name)
+}
+
+/// Check that the fake helper `name` does not linger as a zombie child of this process.
+fn assert_reaped(name: &str) {
+    let pid = fs::read_to_string(scripts_dir().join(format!("{}.pid", name))).unwrap();
+    let pid = pid.trim();
+    if let Ok(stat) = fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        // The pid may have been reused since, but not by one of our zombies.
+        let fields = stat.rsplit(')').next().unwrap();
+        let fields = fields.split_whitespace().collect::<Vec<_>>();
+        let parent = std::process::id().to_string();
+        assert!(
+            fields[0] != "Z" || fields[1] != parent,
+            "helper {} was not reaped",
+            pid
+        );
+    }
+}
+
+fn subprocess_error(result: Result<impl Sized, CompileError>) -> SubprocessError {
+    match result {
+        Err(CompileError::Subprocess(e)) => e,
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("compilation should have failed"),
+    }
+}
+
+#[compiler_test(subprocess)]
+fn compile_valid_module(config: crate::Config) -> Result<()> {
+    let compiler = SubprocessCompiler::new(HELPER, SubprocessLimits::default());
+    let executable = compiler.compile_universal(&wat2wasm(WAT.as_bytes())?)?;
+    let store = config.headless_store();
+    let module = Module::from_executable(&store, &executable)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let add = instance.get_native_function::<(i32, i32), i32>("add")?;
+    assert_eq!(add.call(1, 2)?, 3);
+
+    // Compilation errors are reported as if the compilation happened in process.
+    match compiler.compile_universal(b"\0asm\x01\0\0\0\xff") {
+        Err(CompileError::Wasm(_)) => {}
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("compilation should have failed"),
+    }
+    Ok(())
+}
+
+#[compiler_test(subprocess)]
+fn module_exceeding_limits(_config: crate::Config) -> Result<()> {
+    let limits = SubprocessLimits {
+        max_wasm_size: Some(16),
+        ..SubprocessLimits::default()
+    };
+    let compiler = SubprocessCompiler::new(HELPER, limits);
+    let error = subprocess_error(compiler.compile_universal(&wat2wasm(WAT.as_bytes())?));
+    assert!(
+        matches!(error, SubprocessError::LimitExceeded(_)),
+        "{}",
+        error
+    );
+
+    let limits = SubprocessLimits {
+        max_response_size: 16,
+        ..SubprocessLimits::default()
+    };
+    let compiler = SubprocessCompiler::new(HELPER, limits);
+    let error = subprocess_error(compiler.compile_universal(&wat2wasm(WAT.as_bytes())?));
+    assert!(
+        matches!(error, SubprocessError::LimitExceeded(_)),
+        "{}",
+        error
+    );
+    assert_eq!(error.failure_kind(), FailureKind::Permanent);
+    Ok(())
+}
+
+#[compiler_test(subprocess)]
+fn helper_crash(_config: crate::Config) -> Result<()> {
+    let compiler = SubprocessCompiler::new(script("crash"), SubprocessLimits::default());
+    let error = subprocess_error(compiler.compile_universal(&wat2wasm(WAT.as_bytes())?));
+    assert!(matches!(error, SubprocessError::Crashed(_)), "{}", error);
+    assert_eq!(error.failure_kind(), FailureKind::TransientResource);
+    assert_reaped("crash");
+    Ok(())
+}
+
+#[compiler_test(subprocess)]
+fn helper_timeout(_config: crate::Config) -> Result<()> {
+    let limits = SubprocessLimits {
+        timeout: Duration::from_millis(200),
+        ..SubprocessLimits::default()
+    };
+    let compiler = SubprocessCompiler::new(script("hang"), limits);
+    let error = subprocess_error(compiler.compile_universal(&wat2wasm(WAT.as_bytes())?));
+    assert!(matches!(error, SubprocessError::Timeout), "{}", error);
+    assert_eq!(
+        CompileError::Subprocess(error).failure_kind(),
+        FailureKind::TransientInterrupted
+    );
+    assert_reaped("hang");
+    Ok(())
+}
+
+#[compiler_test(subprocess)]
+fn malformed_response(_config: crate::Config) -> Result<()> {
+    let compiler = SubprocessCompiler::new(script("garbage"), SubprocessLimits::default());
+    let error = subprocess_error(compiler.compile_universal(&wat2wasm(WAT.as_bytes())?));
+    assert!(
+        matches!(error, SubprocessError::MalformedResponse(_)),
+        "{}",
+        error
+    );
+    assert_eq!(error.failure_kind(), FailureKind::Corrupt);
+    assert_reaped("garbage");
+
+    let compiler = SubprocessCompiler::new(scripts_dir().join("missing"), Default::default());
+    let error = subprocess_error(compiler.compile_universal(&wat2wasm(WAT.as_bytes())?));
+    assert!(matches!(error, SubprocessError::Spawn(_)), "{}", error);
+    Ok(())
+}
@@ -0,0 +1,158 @@
+//! Caching the frames symbolicated for the backtraces of traps.
+
+use anyhow::Result;
+use std::sync::Arc;
+use std::thread;
+use wasmer::*;
+use wasmer_engine_universal::Universal;
+
+const WAT: &str = r#"
+    (module
+        (func (export "a") unreachable)
+        (func (export "b") (call $inner))
+        (func $inner unreachable))
+"#;
+
+fn engine_with_sink(config: &crate::Config) -> (UniversalEngine, Arc<AtomicMetricsSink>) {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    (engine, sink)
+}
+
+fn trap(instance: &Instance, name: &str) -> RuntimeError {
+    let function = instance.lookup_function(name).unwrap();
+    function.call(&[]).unwrap_err()
+}
+
+/// The frames of the trace of `error`, without their source locations.
+fn frames(error: &RuntimeError) -> Vec<(String, u32, Option<String>, usize, usize)> {
+    error
+        .trace()
+        .iter()
+        .map(|frame| {
+            (
+                frame.module_name().to_string(),
+                frame.func_index(),
+                frame.function_name().map(str::to_string),
+                frame.module_offset(),
+                frame.func_offset(),
+            )
+        })
+        .collect()
+}
+
+#[compiler_test(symbolication)]
+fn repeated_traps_hit_the_cache(config: crate::Config) -> Result<()> {
+    let (engine, sink) = engine_with_sink(&config);
+    let store = Store::new(&engine);
+    let module = Module::new(&store, WAT)?;
+    let instance = Instance::new(&module, &imports! {})?;
+
+    let first = trap(&instance, "a");
+    let misses = sink.snapshot().symbolication_cache_misses;
+    assert_eq!(misses, first.trace().len() as u64);
+    assert!(misses > 0);
+    assert_eq!(sink.snapshot().symbolication_cache_hits, 0);
+
+    let second = trap(&instance, "a");
+    assert_eq!(sink.snapshot().symbolication_cache_hits, misses);
+    assert_eq!(sink.snapshot().symbolication_cache_misses, misses);
+    assert_eq!(frames(&second), frames(&first));
+
+    // Trapping somewhere else misses.
+    trap(&instance, "b");
+    assert!(sink.snapshot().symbolication_cache_misses > misses);
+    assert_eq!(sink.snapshot().symbolication_cache_hits, misses);
+    Ok(())
+}
+
+#[compiler_test(symbolication)]
+fn cached_frames_are_the_uncached_ones(config: crate::Config) -> Result<()> {
+    let (engine, sink) = engine_with_sink(&config);
+    let cached = Store::new(&engine);
+    let uncached = Store::new(&engine);
+    uncached.set_symbolication_cache_size(0);
+    assert_eq!(
+        cached.symbolication_cache().capacity(),
+        DEFAULT_SYMBOLICATION_CACHE_SIZE
+    );
+
+    let instance = Instance::new(&Module::new(&uncached, WAT)?, &imports! {})?;
+    let expected = frames(&trap(&instance, "b"));
+    assert!(uncached.symbolication_cache().is_empty());
+    assert_eq!(sink.snapshot().symbolication_cache_misses, 0);
+    assert_eq!(expected[0].1, 2);
+
+    let instance = Instance::new(&Module::new(&cached, WAT)?, &imports! {})?;
+    assert_eq!(frames(&trap(&instance, "b")), expected);
+    assert_eq!(frames(&trap(&instance, "b")), expected);
+    let hits = sink.snapshot().symbolication_cache_hits;
+    assert_eq!(hits, expected.len() as u64);
+    Ok(())
+}
+
+#[compiler_test(symbolication)]
+fn unloading_the_artifact_purges_its_frames(config: crate::Config) -> Result<()> {
+    let (engine, _) = engine_with_sink(&config);
+    let store = Store::new(&engine);
+    let kept = Instance::new(&Module::new(&store, WAT)?, &imports! {})?;
+    let module = Module::new(&store, WAT)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    trap(&kept, "a");
+    let kept_frames = store.symbolication_cache().len();
+    trap(&instance, "a");
+    trap(&instance, "b");
+    assert!(store.symbolication_cache().len() > kept_frames);
+
+    drop(instance);
+    drop(module);
+    assert_eq!(store.symbolication_cache().len(), kept_frames);
+    Ok(())
+}
+
+#[compiler_test(symbolication)]
+fn the_cache_is_bounded(config: crate::Config) -> Result<()> {
+    let (engine, sink) = engine_with_sink(&config);
+    let store = Store::new(&engine);
+    store.set_symbolication_cache_size(1);
+    let instance = Instance::new(&Module::new(&store, WAT)?, &imports! {})?;
+    // The frames of `a` and `b` keep evicting each other.
+    let mut frames = 0;
+    for name in &["a", "b", "a"] {
+        frames += trap(&instance, name).trace().len();
+        assert_eq!(store.symbolication_cache().len(), 1);
+    }
+    assert_eq!(sink.snapshot().symbolication_cache_misses, frames as u64);
+    assert_eq!(sink.snapshot().symbolication_cache_hits, 0);
+    Ok(())
+}
+
+#[compiler_test(symbolication)]
+fn concurrent_traps_share_the_cache(config: crate::Config) -> Result<()> {
+    let (engine, sink) = engine_with_sink(&config);
+    let store = Store::new(&engine);
+    let instance = Instance::new(&Module::new(&store, WAT)?, &imports! {})?;
+    let expected = frames(&trap(&instance, "a"));
+    let threads = (0..8)
+        .map(|_| {
+            let instance = instance.clone();
+            let expected = expected.clone();
+            thread::spawn(move || {
+                for _ in 0..50 {
+                    assert_eq!(frames(&trap(&instance, "a")), expected);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let snapshot = sink.snapshot();
+    assert_eq!(snapshot.symbolication_cache_misses, expected.len() as u64);
+    assert_eq!(
+        snapshot.symbolication_cache_hits,
+        400 * expected.len() as u64
+    );
+    Ok(())
+}
@@ -0,0 +1,175 @@
+//! Tracking where the function references installed into tables come from.
+
+use anyhow::Result;
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_engine_universal::Universal;
+
+/// Exports a function that instance B should never be able to call.
+const A: &str = r#"(module (func (export "seven") (result i32) i32.const 7))"#;
+
+/// Installs whatever it is given into its table.
+const B: &str = r#"
+    (module
+        (type $ret_i32 (func (result i32)))
+        (import "env" "host" (func $host (result i32)))
+        (table $t (export "table") 4 funcref)
+        (func $own (result i32) i32.const 1)
+        (elem declare func $own $host)
+        (func (export "install") (param i32 funcref)
+            local.get 0
+            local.get 1
+            table.set $t)
+        (func (export "install_own") (param i32)
+            local.get 0
+            ref.func $own
+            table.set $t)
+        (func (export "install_host") (param i32)
+            local.get 0
+            ref.func $host
+            table.set $t)
+        (func (export "copy") (param i32 i32 i32)
+            local.get 0
+            local.get 1
+            local.get 2
+            table.copy $t $t)
+        (func (export "call") (param i32) (result i32)
+            local.get 0
+            call_indirect $t (type $ret_i32)))
+"#;
+
+struct Instances {
+    a: Instance,
+    b: Instance,
+    sink: Arc<AtomicMetricsSink>,
+}
+
+fn instantiate(config: &crate::Config, policy: TableProvenancePolicy) -> Result<Instances> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    let store = Store::new(&engine);
+    let a = Instance::new(&Module::new(&store, A)?, &imports! {})?;
+    let imports = imports! {
+        "env" => {
+            "host" => Function::new_native(&store, || -> i32 { 3 }),
+        },
+    };
+    let config = InstanceConfig::default().with_table_provenance_policy(policy);
+    let b = Instance::new_with_config(&Module::new(&store, B)?, config, &imports)?;
+    Ok(Instances { a, b, sink })
+}
+
+/// Have instance B install the export of instance A at `index` of its table.
+fn install_foreign(instances: &Instances, index: i32) -> Result<(), RuntimeError> {
+    let seven = instances.a.lookup_function("seven").unwrap();
+    let install = instances.b.lookup_function("install").unwrap();
+    install.call(&[Val::I32(index), Val::FuncRef(Some(seven))])?;
+    Ok(())
+}
+
+#[compiler_test(table_provenance)]
+fn log_mode_counts_foreign_funcrefs(config: crate::Config) -> Result<()> {
+    let instances = instantiate(&config, TableProvenancePolicy::Log)?;
+    let b = &instances.b;
+    let call = b.get_native_function::<i32, i32>("call")?;
+    let table = b.lookup_table("table").unwrap();
+
+    // What B defines or imports is its own.
+    b.get_native_function::<i32, ()>("install_own")?.call(1)?;
+    b.get_native_function::<i32, ()>("install_host")?.call(2)?;
+    assert_eq!(instances.sink.snapshot().foreign_funcrefs_installed, 0);
+    assert_eq!(table.origin(1), Some(FuncOrigin::Instance(b.id())));
+    assert_eq!(table.origin(2), Some(FuncOrigin::Host));
+
+    install_foreign(&instances, 0)?;
+    assert_eq!(instances.sink.snapshot().foreign_funcrefs_installed, 1);
+    assert_eq!(
+        table.origin(0),
+        Some(FuncOrigin::Instance(instances.a.id()))
+    );
+    assert_eq!(call.call(0)?, 7);
+    assert_eq!(call.call(1)?, 1);
+    assert_eq!(call.call(2)?, 3);
+    Ok(())
+}
+
+#[compiler_test(table_provenance)]
+fn reject_mode_traps(config: crate::Config) -> Result<()> {
+    let instances = instantiate(&config, TableProvenancePolicy::Reject)?;
+    let b = &instances.b;
+    let table = b.lookup_table("table").unwrap();
+
+    let error = install_foreign(&instances, 0).unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::ForeignFuncref));
+    assert_eq!(error.code(), ErrorCode::TrapForeignFuncref);
+    assert_eq!(table.origin(0), None);
+
+    // B's own functions are still accepted.
+    b.get_native_function::<i32, ()>("install_own")?.call(0)?;
+    b.get_native_function::<i32, ()>("install_host")?.call(1)?;
+    assert_eq!(b.get_native_function::<i32, i32>("call")?.call(0)?, 1);
+    Ok(())
+}
+
+#[compiler_test(table_provenance)]
+fn origin_follows_table_copy(config: crate::Config) -> Result<()> {
+    let instances = instantiate(&config, TableProvenancePolicy::Log)?;
+    let b = &instances.b;
+    let copy = b.get_native_function::<(i32, i32, i32), ()>("copy")?;
+    let table = b.lookup_table("table").unwrap();
+
+    install_foreign(&instances, 0)?;
+    b.get_native_function::<i32, ()>("install_own")?.call(1)?;
+    copy.call(2, 0, 2)?;
+    assert_eq!(
+        table.origin(2),
+        Some(FuncOrigin::Instance(instances.a.id()))
+    );
+    assert_eq!(table.origin(3), Some(FuncOrigin::Instance(b.id())));
+    // Copying the foreign reference installs it once more.
+    assert_eq!(instances.sink.snapshot().foreign_funcrefs_installed, 2);
+    assert_eq!(b.get_native_function::<i32, i32>("call")?.call(2)?, 7);
+    Ok(())
+}
+
+#[compiler_test(table_provenance)]
+fn reject_mode_leaves_table_untouched_on_copy(config: crate::Config) -> Result<()> {
+    // A table exported by an instance with the default policy, then copied by one rejecting
+    // foreign references.
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let store = Store::new(&engine);
+    let a = Instance::new(&Module::new(&store, A)?, &imports! {})?;
+    let lax = Instance::new(
+        &Module::new(&store, B)?,
+        &imports! { "env" => { "host" => Function::new_native(&store, || -> i32 { 3 }) } },
+    )?;
+    let seven = a.lookup_function("seven").unwrap();
+    lax.lookup_function("install")
+        .unwrap()
+        .call(&[Val::I32(1), Val::FuncRef(Some(seven))])?;
+    let table = lax.lookup_table("table").unwrap();
+
+    let strict = Instance::new_with_config(
+        &Module::new(
+            &store,
+            r#"(module
+                (import "env" "table" (table $t 4 funcref))
+                (func (export "copy") (param i32 i32 i32)
+                    local.get 0
+                    local.get 1
+                    local.get 2
+                    table.copy $t $t))"#,
+        )?,
+        InstanceConfig::default().with_table_provenance_policy(TableProvenancePolicy::Reject),
+        &imports! { "env" => { "table" => table.clone() } },
+    )?;
+    let copy = strict.get_native_function::<(i32, i32, i32), ()>("copy")?;
+    let error = copy.call(2, 0, 2).unwrap_err();
+    assert_eq!(error.trap_code(), Some(TrapCode::ForeignFuncref));
+    assert_eq!(table.origin(2), None);
+    assert_eq!(table.origin(3), None);
+    // Null references are not foreign.
+    copy.call(2, 0, 1)?;
+    Ok(())
+}
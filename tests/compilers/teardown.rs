@@ -0,0 +1,201 @@
+//! Releasing the memories of dropped instances on the thread of a `TeardownQueue`.
+
+use anyhow::Result;
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use wasmer::vm::{self, MemoryStyle, TableStyle, VMMemoryDefinition, VMTableDefinition};
+use wasmer::*;
+
+/// The names of the threads that dropped the tracked memories, in order.
+type Drops = Arc<Mutex<Vec<Option<String>>>>;
+
+/// A memory recording the thread it is dropped on.
+#[derive(Debug)]
+struct TrackedMemory {
+    memory: Arc<dyn vm::Memory>,
+    drops: Drops,
+}
+
+impl vm::Memory for TrackedMemory {
+    fn ty(&self) -> MemoryType {
+        self.memory.ty()
+    }
+
+    fn style(&self) -> &MemoryStyle {
+        self.memory.style()
+    }
+
+    fn size(&self) -> Pages {
+        self.memory.size()
+    }
+
+    fn grow(&self, delta: Pages) -> Result<Pages, MemoryError> {
+        self.memory.grow(delta)
+    }
+
+    fn vmmemory(&self) -> NonNull<VMMemoryDefinition> {
+        self.memory.vmmemory()
+    }
+}
+
+impl Drop for TrackedMemory {
+    fn drop(&mut self) {
+        let name = thread::current().name().map(str::to_string);
+        self.drops.lock().unwrap().push(name);
+    }
+}
+
+/// Tunables tracking the memories of the instances.
+struct TrackingTunables {
+    base: BaseTunables,
+    drops: Drops,
+}
+
+impl Tunables for TrackingTunables {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.base.memory_style(memory)
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.base.table_style(table)
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<Arc<dyn vm::Memory>, MemoryError> {
+        self.base.create_host_memory(ty, style)
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<Arc<dyn vm::Memory>, MemoryError> {
+        Ok(Arc::new(TrackedMemory {
+            memory: self
+                .base
+                .create_vm_memory(ty, style, vm_definition_location)?,
+            drops: Arc::clone(&self.drops),
+        }))
+    }
+
+    fn create_host_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+    ) -> Result<Arc<dyn vm::Table>, String> {
+        self.base.create_host_table(ty, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<Arc<dyn vm::Table>, String> {
+        self.base.create_vm_table(ty, style, vm_definition_location)
+    }
+}
+
+fn tracking_store(config: &crate::Config, drops: &Drops) -> Store {
+    let engine = config.engine(config.compiler_config(false));
+    let tunables = TrackingTunables {
+        base: BaseTunables::for_target(engine.target()),
+        drops: Arc::clone(drops),
+    };
+    Store::new_with_tunables(&*engine, tunables)
+}
+
+fn instantiate(module: &Module, queue: Option<&TeardownQueue>) -> Result<Instance> {
+    let mut config = InstanceConfig::default();
+    if let Some(queue) = queue {
+        config = config.with_deferred_teardown(Arc::new(queue.clone()));
+    }
+    Ok(Instance::new_with_config(module, config, &imports! {})?)
+}
+
+#[compiler_test(teardown)]
+fn memories_are_released_by_the_queue(config: crate::Config) -> Result<()> {
+    let drops = Drops::default();
+    let store = tracking_store(&config, &drops);
+    let module = Module::new(&store, "(module (memory 2))")?;
+    let queue = TeardownQueue::new(1 << 20)?;
+
+    drop(instantiate(&module, Some(&queue))?);
+    queue.drain();
+    assert_eq!(queue.backlog(), 0);
+    assert_eq!(
+        *drops.lock().unwrap(),
+        [Some("wasmer-teardown".to_string())]
+    );
+
+    // Without a queue, the memory is released by the thread dropping the instance.
+    drop(instantiate(&module, None)?);
+    let caller = thread::current().name().map(str::to_string);
+    assert_eq!(drops.lock().unwrap()[1], caller);
+    Ok(())
+}
+
+#[compiler_test(teardown)]
+fn full_backlog_falls_back_to_synchronous_teardown(config: crate::Config) -> Result<()> {
+    let drops = Drops::default();
+    let store = tracking_store(&config, &drops);
+    let module = Module::new(&store, "(module (memory 2))")?;
+    let caller = thread::current().name().map(str::to_string);
+
+    // Two pages do not fit in a backlog of one.
+    let queue = TeardownQueue::new(WASM_PAGE_SIZE)?;
+    drop(instantiate(&module, Some(&queue))?);
+    assert_eq!(queue.backlog(), 0);
+    assert_eq!(*drops.lock().unwrap(), [caller.clone()]);
+
+    // But they fit in a backlog of two.
+    let queue = TeardownQueue::new(2 * WASM_PAGE_SIZE)?;
+    drop(instantiate(&module, Some(&queue))?);
+    queue.drain();
+    assert_eq!(drops.lock().unwrap()[1].as_deref(), Some("wasmer-teardown"));
+    Ok(())
+}
+
+#[compiler_test(teardown)]
+fn large_memories_are_released_off_the_caller_thread(config: crate::Config) -> Result<()> {
+    let drops = Drops::default();
+    let store = tracking_store(&config, &drops);
+    // 256 MiB, all touched so that unmapping them has to release every page.
+    let module = Module::new(
+        &store,
+        r#"(module
+            (memory 4096)
+            (func (export "touch")
+                i32.const 0
+                i32.const 1
+                i32.const 0x1000_0000
+                memory.fill))"#,
+    )?;
+    let queue = TeardownQueue::new(1 << 30)?;
+
+    let teardown = |queue: Option<&TeardownQueue>| -> Result<Duration> {
+        let instance = instantiate(&module, queue)?;
+        instance.get_native_function::<(), ()>("touch")?.call()?;
+        let start = Instant::now();
+        drop(instance);
+        Ok(start.elapsed())
+    };
+    let synchronous = teardown(None)?;
+    let deferred = teardown(Some(&queue))?;
+    assert!(
+        deferred < synchronous,
+        "deferred: {:?}, synchronous: {:?}",
+        deferred,
+        synchronous
+    );
+    queue.drain();
+    assert_eq!(queue.backlog(), 0);
+    assert_eq!(drops.lock().unwrap()[1].as_deref(), Some("wasmer-teardown"));
+    Ok(())
+}
@@ -0,0 +1,200 @@
+//! Attribution of compilations and loaded artifacts to tenants, and their quotas.
+
+use anyhow::Result;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use wasmer::*;
+use wasmer_engine_universal::UniversalExecutable;
+
+const WAT: &str = r#"
+    (module
+        (func (export "add") (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1))))
+"#;
+
+const ALICE: TenantId = TenantId(1);
+const BOB: TenantId = TenantId(2);
+
+fn engine(config: &crate::Config) -> UniversalEngine {
+    Universal::new(config.compiler_config(false)).engine()
+}
+
+fn compile(
+    engine: &UniversalEngine,
+    tenant: TenantId,
+) -> Result<UniversalExecutable, CompileError> {
+    let store = Store::new(engine);
+    engine.compile_universal_as(tenant, &wat2wasm(WAT.as_bytes()).unwrap(), store.tunables())
+}
+
+/// Return the dimension of the quota `result` exceeded, if it failed that way.
+fn exceeded<T>(result: Result<T, CompileError>) -> Option<QuotaDimension> {
+    let error = result.err()?;
+    match &error {
+        CompileError::QuotaExceeded { dimension, .. } => {
+            assert_eq!(error.failure_kind(), FailureKind::TransientResource);
+            Some(*dimension)
+        }
+        _ => panic!("unexpected error: {}", error),
+    }
+}
+
+#[compiler_test(tenants)]
+fn usage_follows_compile_load_and_drop(config: crate::Config) -> Result<()> {
+    let engine = engine(&config);
+    assert_eq!(engine.tenant_usage(ALICE), TenantUsage::default());
+
+    let executable = compile(&engine, ALICE)?;
+    let usage = engine.tenant_usage(ALICE);
+    assert_eq!(usage.compilations_submitted, 1);
+    assert!(usage.compile_time > Duration::from_secs(0));
+    assert_eq!((usage.artifacts_loaded, usage.code_bytes), (0, 0));
+
+    let first = engine.load_universal_executable_as(ALICE, &executable)?;
+    let code_bytes = engine.tenant_usage(ALICE).code_bytes;
+    assert!(code_bytes > 0);
+    let store = Store::new(&engine);
+    let second = Module::new_for_tenant(&store, ALICE, WAT)?;
+    let usage = engine.tenant_usage(ALICE);
+    assert_eq!(usage.compilations_submitted, 2);
+    assert_eq!(usage.artifacts_loaded, 2);
+    assert_eq!(usage.code_bytes, 2 * code_bytes);
+
+    // Work done without a tenant is not attributed to any.
+    let _untenanted = Module::new(&store, WAT)?;
+    assert_eq!(engine.tenant_usage(ALICE), usage);
+    assert_eq!(engine.tenant_usage(BOB), TenantUsage::default());
+
+    drop(first);
+    assert_eq!(engine.tenant_usage(ALICE).artifacts_loaded, 1);
+    drop(second);
+    let usage = engine.tenant_usage(ALICE);
+    assert_eq!((usage.artifacts_loaded, usage.code_bytes), (0, 0));
+    assert_eq!(usage.compilations_submitted, 2);
+    Ok(())
+}
+
+#[compiler_test(tenants)]
+fn artifact_quota(config: crate::Config) -> Result<()> {
+    let engine = engine(&config);
+    engine.set_tenant_quota(
+        ALICE,
+        Quota {
+            max_artifacts: Some(1),
+            ..Quota::default()
+        },
+    );
+    let executable = compile(&engine, ALICE)?;
+    let first = engine.load_universal_executable_as(ALICE, &executable)?;
+    assert_eq!(
+        exceeded(engine.load_universal_executable_as(ALICE, &executable)),
+        Some(QuotaDimension::Artifacts)
+    );
+    assert_eq!(engine.tenant_usage(ALICE).artifacts_loaded, 1);
+    // Other tenants, and work done without a tenant, are not limited.
+    engine.load_universal_executable_as(BOB, &executable)?;
+    engine.load_universal_executable(&executable)?;
+
+    drop(first);
+    engine.load_universal_executable_as(ALICE, &executable)?;
+    Ok(())
+}
+
+#[compiler_test(tenants)]
+fn code_bytes_quota_is_enforced_before_mapping(config: crate::Config) -> Result<()> {
+    let engine = engine(&config);
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    let executable = compile(&engine, ALICE)?;
+    let code_bytes = {
+        let _artifact = engine.load_universal_executable_as(ALICE, &executable)?;
+        engine.tenant_usage(ALICE).code_bytes
+    };
+
+    engine.set_tenant_quota(
+        ALICE,
+        Quota {
+            max_code_bytes: Some(code_bytes + code_bytes / 2),
+            ..Quota::default()
+        },
+    );
+    let _first = engine.load_universal_executable_as(ALICE, &executable)?;
+    let mapped = sink.snapshot().code_bytes;
+    assert_eq!(
+        exceeded(engine.load_universal_executable_as(ALICE, &executable)),
+        Some(QuotaDimension::CodeBytes)
+    );
+    assert_eq!(sink.snapshot().code_bytes, mapped);
+    assert_eq!(engine.tenant_usage(ALICE).code_bytes, code_bytes);
+    Ok(())
+}
+
+#[compiler_test(tenants)]
+fn compile_time_quota(config: crate::Config) -> Result<()> {
+    let engine = engine(&config);
+    compile(&engine, ALICE)?;
+    let spent = engine.tenant_usage(ALICE).compile_time;
+
+    // A quota of zero refuses every compilation, whatever was spent in the last minute.
+    engine.set_tenant_quota(
+        ALICE,
+        Quota {
+            max_compile_ms_per_minute: Some(0),
+            ..Quota::default()
+        },
+    );
+    assert_eq!(
+        exceeded(compile(&engine, ALICE)),
+        Some(QuotaDimension::CompileMsPerMinute)
+    );
+    let store = Store::new(&engine);
+    assert_eq!(
+        exceeded(Module::new_for_tenant(&store, ALICE, WAT)),
+        Some(QuotaDimension::CompileMsPerMinute)
+    );
+    let usage = engine.tenant_usage(ALICE);
+    assert_eq!(usage.compilations_submitted, 3);
+    assert_eq!(usage.compile_time, spent);
+    compile(&engine, BOB)?;
+    Ok(())
+}
+
+#[compiler_test(tenants)]
+fn rejections_do_not_affect_other_tenants(config: crate::Config) -> Result<()> {
+    let engine = engine(&config);
+    engine.set_tenant_quota(
+        ALICE,
+        Quota {
+            max_artifacts: Some(0),
+            ..Quota::default()
+        },
+    );
+    let rejected = {
+        let engine = engine.clone();
+        thread::spawn(move || {
+            let store = Store::new(&engine);
+            (0..20)
+                .filter(|_| exceeded(Module::new_for_tenant(&store, ALICE, WAT)).is_some())
+                .count()
+        })
+    };
+    let admitted = {
+        let engine = engine.clone();
+        thread::spawn(move || -> Result<Vec<Module>> {
+            let store = Store::new(&engine);
+            (0..20)
+                .map(|_| Ok(Module::new_for_tenant(&store, BOB, WAT)?))
+                .collect()
+        })
+    };
+    assert_eq!(rejected.join().unwrap(), 20);
+    let modules = admitted.join().unwrap()?;
+    let add = Instance::new(&modules[19], &imports! {})?
+        .get_native_function::<(i32, i32), i32>("add")?
+        .call(1, 2)?;
+    assert_eq!(add, 3);
+    assert_eq!(engine.tenant_usage(BOB).artifacts_loaded, 20);
+    assert_eq!(engine.tenant_usage(ALICE).artifacts_loaded, 0);
+    Ok(())
+}
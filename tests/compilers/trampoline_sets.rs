@@ -0,0 +1,93 @@
+//! Trampolines compiled once and shared with headless engines.
+
+use anyhow::Result;
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{Universal, UniversalExecutableRef};
+
+const WAT: &str = r#"
+    (module
+        (import "host" "add" (func $add (param i32 i32) (result i32)))
+        (export "add" (func $add))
+        (func (export "add_one") (param i32) (result i32)
+            (call $add (local.get 0) (i32.const 1))))
+"#;
+
+fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+fn signatures() -> Vec<FunctionType> {
+    vec![
+        FunctionType::new(vec![Type::I32, Type::I32], vec![Type::I32]),
+        FunctionType::new(vec![Type::I64], vec![Type::I64]),
+    ]
+}
+
+#[compiler_test(trampoline_sets)]
+fn headless_engines_use_installed_trampolines(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let store = Store::new(&engine);
+    let set = engine
+        .compile_trampoline_set(&signatures())?
+        .serialize()
+        .unwrap();
+    let executable = engine
+        .compile_universal(&wat2wasm(WAT.as_bytes())?, store.tunables())?
+        .serialize()
+        .unwrap();
+
+    let headless = Universal::headless().engine();
+    let sink = Arc::new(AtomicMetricsSink::new());
+    headless.set_metrics_sink(sink.clone());
+    let set = unsafe { TrampolineSetExecutable::deserialize(&set)? };
+    assert_eq!(set.signatures(), &signatures()[..]);
+    headless.install_trampoline_set(&set)?;
+    // Installing the same signatures again keeps the installed trampolines.
+    headless.install_trampoline_set(&set)?;
+    let store = Store::new(&headless);
+    let executable = unsafe { UniversalExecutableRef::deserialize(&executable)? };
+    let module = Module::from_executable(&store, &executable)?;
+
+    let host = Function::new_native(&store, add);
+    assert_eq!(host.call(&[Val::I32(1), Val::I32(2)])?[..], [Val::I32(3)]);
+    let double = Function::new_native(&store, |a: i64| a * 2);
+    assert_eq!(double.call(&[Val::I64(21)])?[..], [Val::I64(42)]);
+
+    let instance = Instance::new(&module, &imports! { "host" => { "add" => host } })?;
+    let add_one = instance.get_native_function::<i32, i32>("add_one")?;
+    assert_eq!(add_one.call(41)?, 42);
+    let reexported = instance.lookup_function("add").unwrap();
+    assert_eq!(
+        reexported.call(&[Val::I32(40), Val::I32(2)])?[..],
+        [Val::I32(42)]
+    );
+
+    assert_eq!(sink.snapshot().trampolines_compiled, 0);
+    Ok(())
+}
+
+#[compiler_test(trampoline_sets)]
+fn missing_trampolines_are_compiled_on_demand(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let sink = Arc::new(AtomicMetricsSink::new());
+    engine.set_metrics_sink(sink.clone());
+    let store = Store::new(&engine);
+
+    let host = Function::new_native(&store, add);
+    assert_eq!(host.call(&[Val::I32(1), Val::I32(2)])?[..], [Val::I32(3)]);
+    assert_eq!(sink.snapshot().trampolines_compiled, 1);
+    assert_eq!(host.call(&[Val::I32(3), Val::I32(4)])?[..], [Val::I32(7)]);
+    let other = Function::new_native(&store, |a: i32, b: i32| a * b);
+    assert_eq!(other.call(&[Val::I32(3), Val::I32(4)])?[..], [Val::I32(12)]);
+    assert_eq!(sink.snapshot().trampolines_compiled, 1);
+
+    // Engines without a compiler report the missing trampoline instead.
+    let headless = Universal::headless().engine();
+    let store = Store::new(&headless);
+    let host = Function::new_native(&store, add);
+    let error = host.call(&[Val::I32(1), Val::I32(2)]).unwrap_err();
+    assert!(error.message().contains("no trampoline"));
+    Ok(())
+}
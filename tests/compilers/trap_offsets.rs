@@ -0,0 +1,238 @@
+//! Every trap must be attributed to the byte offset of the operator that trapped.
+
+use anyhow::Result;
+use wasmer::*;
+use wasmer_compiler::wasmparser::{Operator, Parser, Payload};
+use wasmer_vm::TrapCode;
+
+/// Offset of the first operator matching `pred` in the body of the function `func`
+/// (counting local functions only).
+fn operator_offset(wasm: &[u8], func: usize, mut pred: impl FnMut(&Operator) -> bool) -> usize {
+    let mut bodies = Parser::new(0)
+        .parse_all(wasm)
+        .filter_map(|payload| match payload.unwrap() {
+            Payload::CodeSectionEntry(body) => Some(body),
+            _ => None,
+        });
+    let body = bodies.nth(func).expect("no such function");
+    let mut operators = body
+        .get_operators_reader()
+        .unwrap()
+        .into_iter_with_offsets();
+    operators
+        .find_map(|op| {
+            let (op, offset) = op.unwrap();
+            if pred(&op) {
+                Some(offset)
+            } else {
+                None
+            }
+        })
+        .expect("no matching operator")
+}
+
+/// Offset of the body of the function `func` (counting local functions only).
+fn body_offset(wasm: &[u8], func: usize) -> usize {
+    let mut bodies = Parser::new(0)
+        .parse_all(wasm)
+        .filter_map(|payload| match payload.unwrap() {
+            Payload::CodeSectionEntry(body) => Some(body),
+            _ => None,
+        });
+    let body = bodies.nth(func).expect("no such function");
+    body.get_binary_reader().original_position()
+}
+
+fn run(config: &crate::Config, wasm: &[u8]) -> Result<RuntimeError> {
+    let store = config.store();
+    let module = Module::new(&store, wasm)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.get_native_function::<(), ()>("run")?;
+    Ok(run.call().expect_err("run should trap"))
+}
+
+fn check(
+    config: &crate::Config,
+    wat: &str,
+    code: TrapCode,
+    pred: impl FnMut(&Operator) -> bool,
+) -> Result<()> {
+    let wasm = wat2wasm(wat.as_bytes())?;
+    let expected = operator_offset(&wasm, 0, pred);
+    let error = run(config, &wasm)?;
+    let trace = error.trace().to_vec();
+    assert!(!trace.is_empty(), "no trace for {:?}", code);
+    assert_eq!(trace[0].func_index(), 0);
+    assert_eq!(trace[0].module_offset(), expected, "offset for {:?}", code);
+    assert_eq!(error.to_trap(), Some(code));
+    Ok(())
+}
+
+#[compiler_test(trap_offsets)]
+fn memory_out_of_bounds_load(config: crate::Config) -> Result<()> {
+    let wat = r#"(module
+        (memory 1)
+        (func (export "run")
+            i32.const 16
+            i32.load
+            i32.const 65536
+            i32.load
+            i32.add
+            drop))"#;
+    let mut seen = 0;
+    check(&config, wat, TrapCode::HeapAccessOutOfBounds, |op| {
+        matches!(op, Operator::I32Load { .. }) && {
+            seen += 1;
+            seen == 2
+        }
+    })
+}
+
+#[compiler_test(trap_offsets)]
+fn memory_out_of_bounds_store(config: crate::Config) -> Result<()> {
+    let wat = r#"(module
+        (memory 1)
+        (func (export "run")
+            i32.const 65535
+            i64.const 0
+            i64.store offset=1))"#;
+    check(&config, wat, TrapCode::HeapAccessOutOfBounds, |op| {
+        matches!(op, Operator::I64Store { .. })
+    })
+}
+
+#[compiler_test(trap_offsets)]
+fn table_out_of_bounds(config: crate::Config) -> Result<()> {
+    let wat = r#"(module
+        (table 1 funcref)
+        (func (export "run")
+            i32.const 1
+            call_indirect))"#;
+    check(&config, wat, TrapCode::TableAccessOutOfBounds, |op| {
+        matches!(op, Operator::CallIndirect { .. })
+    })
+}
+
+#[compiler_test(trap_offsets)]
+fn indirect_call_to_null(config: crate::Config) -> Result<()> {
+    let wat = r#"(module
+        (table 1 funcref)
+        (func (export "run")
+            i32.const 0
+            call_indirect))"#;
+    check(&config, wat, TrapCode::IndirectCallToNull, |op| {
+        matches!(op, Operator::CallIndirect { .. })
+    })
+}
+
+#[compiler_test(trap_offsets)]
+fn indirect_call_bad_signature(config: crate::Config) -> Result<()> {
+    let wat = r#"(module
+        (table funcref (elem $f))
+        (func (export "run")
+            i32.const 0
+            call_indirect)
+        (func $f (param i32)))"#;
+    check(&config, wat, TrapCode::BadSignature, |op| {
+        matches!(op, Operator::CallIndirect { .. })
+    })
+}
+
+#[compiler_test(trap_offsets)]
+fn division_by_zero(config: crate::Config) -> Result<()> {
+    let wat = r#"(module
+        (func (export "run")
+            i32.const 7
+            i32.const 1
+            i32.div_u
+            i32.const 0
+            i32.div_u
+            drop))"#;
+    let mut seen = 0;
+    check(&config, wat, TrapCode::IntegerDivisionByZero, |op| {
+        matches!(op, Operator::I32DivU) && {
+            seen += 1;
+            seen == 2
+        }
+    })
+}
+
+#[compiler_test(trap_offsets)]
+fn integer_overflow(config: crate::Config) -> Result<()> {
+    let wat = r#"(module
+        (func (export "run")
+            i64.const 0x8000000000000000
+            i64.const -1
+            i64.div_s
+            drop))"#;
+    check(&config, wat, TrapCode::IntegerOverflow, |op| {
+        matches!(op, Operator::I64DivS)
+    })
+}
+
+#[compiler_test(trap_offsets)]
+fn bad_conversion_to_integer(config: crate::Config) -> Result<()> {
+    let wat = r#"(module
+        (func (export "run")
+            f32.const nan
+            i32.trunc_f32_s
+            drop))"#;
+    check(&config, wat, TrapCode::BadConversionToInteger, |op| {
+        matches!(op, Operator::I32TruncF32S)
+    })
+}
+
+#[compiler_test(trap_offsets)]
+fn unreachable(config: crate::Config) -> Result<()> {
+    let wat = r#"(module
+        (func (export "run")
+            nop
+            block
+                unreachable
+            end))"#;
+    check(&config, wat, TrapCode::UnreachableCodeReached, |op| {
+        matches!(op, Operator::Unreachable)
+    })
+}
+
+#[compiler_test(trap_offsets)]
+fn stack_overflow_at_entry(config: crate::Config) -> Result<()> {
+    let wat = r#"(module
+        (func $run (export "run")
+            nop
+            call $run))"#;
+    let wasm = wat2wasm(wat.as_bytes())?;
+    let error = run(&config, &wasm)?;
+    let trace = error.trace();
+    assert!(!trace.is_empty());
+    assert_eq!(trace[0].module_offset(), body_offset(&wasm, 0));
+    assert_eq!(trace[0].func_offset(), 0);
+    assert_eq!(error.to_trap(), Some(TrapCode::StackOverflow));
+    Ok(())
+}
+
+#[compiler_test(trap_offsets)]
+fn offsets_survive_serialization(config: crate::Config) -> Result<()> {
+    use wasmer_engine::Executable;
+    use wasmer_engine_universal::UniversalExecutableRef;
+
+    let wat = r#"(module
+        (func (export "run")
+            nop
+            unreachable))"#;
+    let wasm = wat2wasm(wat.as_bytes())?;
+    let expected = operator_offset(&wasm, 0, |op| matches!(op, Operator::Unreachable));
+    let store = config.store();
+    let serialized = store
+        .engine()
+        .compile(&wasm, store.tunables())?
+        .serialize()
+        .unwrap();
+    let executable = unsafe { UniversalExecutableRef::deserialize(&serialized)? };
+    let module = Module::from_executable(&store, &executable)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.get_native_function::<(), ()>("run")?;
+    let error = run.call().expect_err("run should trap");
+    assert_eq!(error.trace()[0].module_offset(), expected);
+    Ok(())
+}
@@ -262,6 +262,57 @@ fn trap_start_function_import(config: crate::Config) -> Result<()> {
     Ok(())
 }
 
+#[derive(Debug, PartialEq)]
+struct Denied(i32);
+
+impl std::fmt::Display for Denied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "denied with code {}", self.0)
+    }
+}
+
+impl std::error::Error for Denied {}
+
+#[compiler_test(traps)]
+fn host_errors_are_returned_as_they_are(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let wat = r#"
+        (module
+            (import "" "native" (func $native (param i32) (result i32)))
+            (import "" "dynamic" (func $dynamic (param i32) (result i32)))
+            (func (export "native") (param i32) (result i32)
+                (i32.add (call $native (local.get 0)) (i32.const 1)))
+            (func (export "dynamic") (param i32) (result i32)
+                (i32.add (call $dynamic (local.get 0)) (i32.const 1)))
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let sig = FunctionType::new(vec![Type::I32], vec![Type::I32]);
+    let instance = Instance::new(
+        &module,
+        &imports! {
+            "" => {
+                "native" => Function::new_native(&store, |code: i32| -> Result<i32, Denied> {
+                    if code < 0 { Err(Denied(code)) } else { Ok(code) }
+                }),
+                "dynamic" => Function::new(&store, &sig, |args| match args[0].unwrap_i32() {
+                    code if code < 0 => Err(RuntimeError::user(Box::new(Denied(code)))),
+                    code => Ok(vec![Val::I32(code)]),
+                }),
+            }
+        },
+    )?;
+    for name in ["native", "dynamic"] {
+        let function = instance.get_native_function::<i32, i32>(name)?;
+        assert_eq!(function.call(2)?, 3);
+        let error = function.call(-7).unwrap_err();
+        assert!(error.is::<Denied>());
+        assert_eq!(error.message(), "denied with code -7");
+        assert_eq!(error.downcast::<Denied>().unwrap(), Denied(-7));
+    }
+    Ok(())
+}
+
 #[compiler_test(traps)]
 fn rust_panic_import(config: crate::Config) -> Result<()> {
     let store = config.store();
@@ -465,3 +516,42 @@ fn present_after_module_drop(config: crate::Config) -> Result<()> {
         // assert_eq!(t.trace()[0].func_index(), 0);
     }
 }
+
+#[compiler_test(traps)]
+fn module_named_after_its_file(config: crate::Config) -> Result<()> {
+    let store = config.store();
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("hello.wasm");
+    std::fs::write(
+        &path,
+        wat2wasm(br#"(module $hello_mod (func (export "run") unreachable))"#)?,
+    )?;
+
+    let module = Module::from_file(&store, &path)?;
+    let name = path.canonicalize()?.to_string_lossy().into_owned();
+    assert_eq!(module.name(), Some(&name[..]));
+    let instance = Instance::new(&module, &imports! {})?;
+    let e = instance
+        .get_native_function::<(), ()>("run")?
+        .call()
+        .unwrap_err();
+    assert_eq!(e.trace()[0].module_name(), name);
+
+    // Renaming only works while the module is the only owner of its code.
+    let mut module = Module::from_file(&store, &path)?;
+    assert!(module.set_name("renamed"));
+    let instance = Instance::new(&module, &imports! {})?;
+    assert!(!module.set_name("again"));
+    let e = instance
+        .get_native_function::<(), ()>("run")?
+        .call()
+        .unwrap_err();
+    assert_eq!(e.trace()[0].module_name(), "renamed");
+    assert_eq!(module.name(), Some("renamed"));
+
+    assert!(matches!(
+        Module::from_file(&store, dir.path().join("missing.wasm")),
+        Err(IoCompileError::Io(_))
+    ));
+    Ok(())
+}
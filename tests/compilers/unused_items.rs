@@ -0,0 +1,106 @@
+//! Finding the imports and globals a module never uses, and stripping the unused imports.
+
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+        (import "env" "a" (func $a (result i32)))
+        (import "env" "b" (func $b (result i32)))
+        (import "env" "c" (func $c (result i32)))
+        (import "env" "d" (func $d (result i32)))
+        (import "env" "e" (func $e (result i32)))
+        (import "env" "g" (global $g i32))
+        (import "env" "h" (global $h i32))
+        (global $used (mut i32) (i32.const 0))
+        (global $unused i32 (i32.const 1))
+        (table 1 funcref)
+        (elem (i32.const 0) $via_table)
+        (func $via_table (result i32) (call $c))
+        (func $dead (result i32) (call $d))
+        (func (export "run") (result i32)
+            (global.set $used (global.get $g))
+            (i32.add (call $a) (call_indirect (result i32) (i32.const 0)))))
+"#;
+
+fn store(config: &crate::Config, strip: bool) -> Store {
+    let mut compiler_config = config.compiler_config(false);
+    compiler_config.strip_unused_imports(strip);
+    Store::new(&*config.engine(compiler_config))
+}
+
+fn imports(store: &Store) -> ImportObject {
+    imports! {
+        "env" => {
+            "a" => Function::new_native(store, || 1),
+            "c" => Function::new_native(store, || 2),
+            "g" => Global::new(store, Val::I32(3)),
+            "h" => Global::new(store, Val::I32(4)),
+        },
+    }
+}
+
+#[compiler_test(unused_items)]
+fn unused_items_are_reported(config: crate::Config) -> Result<()> {
+    let module = Module::new(&store(&config, false), WAT)?;
+    let report = module.unused_items();
+    let names = |names: &[&str]| -> Vec<(String, String)> {
+        names
+            .iter()
+            .map(|name| ("env".to_string(), name.to_string()))
+            .collect()
+    };
+    // `c` is called from a function only reachable through the table, `d` from a function
+    // nothing reaches.
+    assert_eq!(report.functions, names(&["b", "d", "e"]));
+    assert_eq!(report.imported_globals, names(&["h"]));
+    assert_eq!(report.globals, [GlobalIndex::from_u32(3)]);
+    assert!(!report.imports_stripped);
+    Ok(())
+}
+
+#[compiler_test(unused_items)]
+fn stripped_imports_need_not_be_given(config: crate::Config) -> Result<()> {
+    let kept = store(&config, false);
+    let module = Module::new(&kept, WAT)?;
+    assert!(Instance::new(&module, &imports(&kept)).is_err());
+
+    let stripped = store(&config, true);
+    let module = Module::new(&stripped, WAT)?;
+    assert!(module.unused_items().imports_stripped);
+    let instance = Instance::new(&module, &imports(&stripped))?;
+    let run = instance.lookup_function("run").unwrap();
+    assert_eq!(run.call(&[])?[0].unwrap_i32(), 3);
+    Ok(())
+}
+
+#[compiler_test(unused_items)]
+fn stripped_imports_are_still_used_when_given(config: crate::Config) -> Result<()> {
+    let store = store(&config, true);
+    let module = Module::new(
+        &store,
+        r#"
+        (module
+            (import "env" "a" (func (result i32)))
+            (func (export "run") (result i32) (i32.const 0)))
+        "#,
+    )?;
+    assert_eq!(module.unused_items().functions.len(), 1);
+    let imports = imports! {
+        "env" => { "a" => Function::new_native(&store, || 1) },
+    };
+    Instance::new(&module, &imports)?;
+    Ok(())
+}
+
+#[compiler_test(unused_items)]
+fn stripping_changes_the_id(config: crate::Config) -> Result<()> {
+    let kept = Module::new(&store(&config, false), WAT)?;
+    let stripped = Module::new(&store(&config, true), WAT)?;
+    assert_ne!(kept.artifact_id(), stripped.artifact_id());
+    assert_eq!(
+        kept.unused_items().functions,
+        stripped.unused_items().functions
+    );
+    Ok(())
+}
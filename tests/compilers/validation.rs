@@ -0,0 +1,84 @@
+//! Telling where and why modules are invalid, against features chosen by the caller.
+
+use anyhow::Result;
+use wasmer::*;
+
+/// A module with a single function of type `[] -> []`, whose code section has `code` as its
+/// contents.
+fn module(code: &[u8]) -> Vec<u8> {
+    let mut binary = b"\0asm\x01\0\0\0".to_vec();
+    binary.extend_from_slice(&[0x01, 0x04, 0x01, 0x60, 0x00, 0x00]);
+    binary.extend_from_slice(&[0x03, 0x02, 0x01, 0x00]);
+    binary.extend_from_slice(&[0x0a, code.len() as u8]);
+    binary.extend_from_slice(code);
+    binary
+}
+
+/// The offset of the contents of the code section of [`module`].
+const CODE: usize = 20;
+
+/// One body of 3 bytes: no locals, the unassigned opcode 0x27, and `end`.
+const INVALID_OPCODE: &[u8] = &[0x01, 0x03, 0x00, 0x27, 0x0b];
+
+/// One body claiming 16 bytes, while the section only has 3 more.
+const OVERSIZED_BODY: &[u8] = &[0x01, 0x10, 0x00, 0x01, 0x0b];
+
+fn report(binary: &[u8], features: &Features) -> ValidationReport {
+    Module::validate_with_features(binary, features).unwrap_err()
+}
+
+#[test]
+fn invalid_opcodes_are_located() {
+    let binary = module(INVALID_OPCODE);
+    let report = report(&binary, &Features::default());
+    assert_eq!(report.offset, CODE + 3);
+    assert_eq!(report.section, Some(SectionKind::Code));
+    assert_eq!(report.function, Some(LocalFunctionIndex::from_u32(0)));
+    assert_eq!(report.operator, None);
+}
+
+#[test]
+fn oversized_function_bodies_are_located() {
+    let binary = module(OVERSIZED_BODY);
+    let report = report(&binary, &Features::default());
+    assert!(report.offset > CODE && report.offset <= binary.len());
+    assert_eq!(report.section, Some(SectionKind::Code));
+    assert_eq!(report.function, None);
+    assert_eq!(report.operator, None);
+}
+
+#[test]
+fn disabled_proposals_are_located() -> Result<()> {
+    let binary = wat2wasm(
+        br#"
+        (module
+            (func)
+            (func (drop (v128.const i32x4 0 0 0 0))))
+        "#,
+    )?;
+    let mut features = Features::default();
+    features.simd(false);
+    let report = report(&binary, &features);
+    assert_eq!(binary[report.offset], 0xfd);
+    assert_eq!(report.section, Some(SectionKind::Code));
+    assert_eq!(report.function, Some(LocalFunctionIndex::from_u32(1)));
+    assert_eq!(report.operator.as_deref(), Some("V128Const"));
+    assert!(report.to_string().contains("V128Const"));
+
+    // The same module is valid against features enabling SIMD, whatever those of the stores.
+    features.simd(true);
+    Module::validate_with_features(&binary, &features)?;
+    Ok(())
+}
+
+#[test]
+fn reports_are_distinct() {
+    let features = Features::default();
+    let opcode = report(&module(INVALID_OPCODE), &features);
+    let oversized = report(&module(OVERSIZED_BODY), &features);
+    assert_ne!(opcode, oversized);
+    assert_ne!(opcode.message, oversized.message);
+
+    let valid = module(&[0x01, 0x02, 0x00, 0x0b]);
+    assert_eq!(Module::validate_with_features(&valid, &features), Ok(()));
+}
@@ -0,0 +1,239 @@
+//! Caching the results of view calls.
+
+use anyhow::Result;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use wasmer::*;
+use wasmer_engine::Executable;
+use wasmer_engine_universal::{Universal, UniversalEngine, UniversalExecutableRef};
+
+const WAT: &str = r#"
+    (module
+        (import "env" "balance" (func $balance (param i32) (result i64)))
+        (import "env" "now" (func $now (result i64)))
+        (func (export "balance") (param i32) (result i64)
+            (call $balance (local.get 0)))
+        (func (export "age") (param i64) (result i64)
+            (i64.sub (call $now) (local.get 0)))
+        (func (export "ratio") (param f64 f64) (result f64)
+            (drop (call $balance (i32.const 0)))
+            (f64.div (local.get 0) (local.get 1)))
+        (func (export "fail") (param i32) (result i64)
+            (drop (call $balance (local.get 0)))
+            unreachable))
+"#;
+
+/// A contract whose imports count how many times the calls were executed.
+struct Contract {
+    sink: Arc<AtomicMetricsSink>,
+    module: Module,
+    imports: ImportObject,
+    executions: Arc<AtomicU32>,
+}
+
+impl Contract {
+    fn new(config: &crate::Config) -> Result<Self> {
+        let engine: UniversalEngine = Universal::new(config.compiler_config(false)).engine();
+        let sink = Arc::new(AtomicMetricsSink::new());
+        engine.set_metrics_sink(sink.clone());
+        let store = Store::new(&engine);
+        let module = Module::new(&store, WAT)?;
+        let executions = Arc::new(AtomicU32::new(0));
+        let counter = executions.clone();
+        let balance = Function::new(&store, ([Type::I32], [Type::I64]), move |args| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Ok(vec![Val::I64(i64::from(args[0].unwrap_i32()) * 100)])
+        });
+        let now = Function::new_native(&store, || -> i64 {
+            if let Some(context) = CallContext::current() {
+                if let Some(nondeterminism) = context.extension::<Nondeterminism>() {
+                    nondeterminism.record();
+                }
+            }
+            1_700_000_000
+        });
+        let imports = imports! {
+            "env" => {
+                "balance" => balance,
+                "now" => now,
+            },
+        };
+        Ok(Self {
+            sink,
+            module,
+            imports,
+            executions,
+        })
+    }
+
+    fn call(
+        &self,
+        cache: &ViewCallCache,
+        generation: u64,
+        entry: &str,
+        args: &[Val],
+    ) -> Result<Box<[Val]>, ViewCallError> {
+        cache.call(&self.module, &self.imports, generation, entry, args)
+    }
+
+    fn balance(&self, cache: &ViewCallCache, generation: u64, account: i32) -> Result<i64> {
+        let result = self.call(cache, generation, "balance", &[Val::I32(account)])?;
+        Ok(result[0].unwrap_i64())
+    }
+
+    fn executions(&self) -> u32 {
+        self.executions.load(Ordering::SeqCst)
+    }
+}
+
+#[compiler_test(view_cache)]
+fn identical_calls_hit(config: crate::Config) -> Result<()> {
+    let contract = Contract::new(&config)?;
+    let cache = ViewCallCache::new(1 << 20);
+    assert_eq!(contract.balance(&cache, 1, 7)?, 700);
+    assert_eq!(contract.balance(&cache, 1, 7)?, 700);
+    assert_eq!(contract.executions(), 1);
+    // Other arguments and entry points are other calls.
+    assert_eq!(contract.balance(&cache, 1, 8)?, 800);
+    let result = contract.call(&cache, 1, "ratio", &[Val::F64(1.0), Val::F64(4.0)])?;
+    assert_eq!(result[..], [Val::F64(0.25)]);
+    assert_eq!(contract.executions(), 3);
+    let result = contract.call(&cache, 1, "ratio", &[Val::F64(1.0), Val::F64(4.0)])?;
+    assert_eq!(result[..], [Val::F64(0.25)]);
+    assert_eq!(contract.executions(), 3);
+
+    let stats = cache.stats();
+    assert_eq!(stats.hits, 2);
+    assert_eq!(stats.misses, 3);
+    assert_eq!(stats.entries, 3);
+    assert_eq!(stats.evictions, 0);
+
+    let error = contract
+        .call(&cache, 1, "missing", &[])
+        .expect_err("missing is not exported");
+    assert_eq!(error.code(), ErrorCode::ExportMissing);
+    assert_eq!(error.failure_kind(), FailureKind::Permanent);
+    Ok(())
+}
+
+#[compiler_test(view_cache)]
+fn traps_are_cached_when_enabled(config: crate::Config) -> Result<()> {
+    let contract = Contract::new(&config)?;
+    let cache = ViewCallCache::new(1 << 20);
+    for _ in 0..2 {
+        let error = contract
+            .call(&cache, 1, "fail", &[Val::I32(1)])
+            .unwrap_err();
+        assert_eq!(error.code(), ErrorCode::TrapUnreachableCodeReached);
+    }
+    assert_eq!(contract.executions(), 2);
+    assert_eq!(cache.stats().entries, 0);
+
+    let cache = ViewCallCache::new(1 << 20).with_trap_caching(true);
+    for _ in 0..2 {
+        match contract.call(&cache, 1, "fail", &[Val::I32(1)]) {
+            Err(ViewCallError::Runtime(error)) => {
+                assert_eq!(error.trap_code(), Some(TrapCode::UnreachableCodeReached))
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+    assert_eq!(contract.executions(), 3);
+    assert_eq!(cache.stats().hits, 1);
+    Ok(())
+}
+
+#[compiler_test(view_cache)]
+fn bumping_the_generation_invalidates(config: crate::Config) -> Result<()> {
+    let contract = Contract::new(&config)?;
+    let cache = ViewCallCache::new(1 << 20);
+    contract.balance(&cache, 1, 7)?;
+    contract.balance(&cache, 1, 7)?;
+    assert_eq!(contract.executions(), 1);
+    contract.balance(&cache, 2, 7)?;
+    contract.balance(&cache, 2, 7)?;
+    assert_eq!(contract.executions(), 2);
+    // The results of the previous generation stay until they are evicted.
+    assert_eq!(cache.stats().entries, 2);
+    Ok(())
+}
+
+#[compiler_test(view_cache)]
+fn least_recently_used_entries_are_evicted(config: crate::Config) -> Result<()> {
+    let contract = Contract::new(&config)?;
+    // The entry name, an encoded `i32` argument and an encoded `i64` result.
+    let entry_bytes = "balance".len() + 5 + 9;
+    let cache = ViewCallCache::new(2 * entry_bytes);
+    contract.balance(&cache, 1, 1)?;
+    contract.balance(&cache, 1, 2)?;
+    assert_eq!(cache.stats().bytes, 2 * entry_bytes);
+    // Using the first entry makes the second the least recently used.
+    contract.balance(&cache, 1, 1)?;
+    contract.balance(&cache, 1, 3)?;
+    let stats = cache.stats();
+    assert_eq!(stats.evictions, 1);
+    assert_eq!(stats.entries, 2);
+    assert_eq!(contract.executions(), 3);
+    contract.balance(&cache, 1, 1)?;
+    contract.balance(&cache, 1, 3)?;
+    assert_eq!(contract.executions(), 3);
+    contract.balance(&cache, 1, 2)?;
+    assert_eq!(contract.executions(), 4);
+
+    // Results larger than the cache are not cached.
+    let cache = ViewCallCache::new(entry_bytes - 1);
+    contract.balance(&cache, 1, 1)?;
+    contract.balance(&cache, 1, 1)?;
+    assert_eq!(contract.executions(), 6);
+    assert_eq!(cache.stats().entries, 0);
+    Ok(())
+}
+
+#[compiler_test(view_cache)]
+fn nondeterministic_calls_are_not_cached(config: crate::Config) -> Result<()> {
+    let contract = Contract::new(&config)?;
+    let cache = ViewCallCache::new(1 << 20);
+    for _ in 0..2 {
+        let result = contract.call(&cache, 1, "age", &[Val::I64(1_600_000_000)])?;
+        assert_eq!(result[..], [Val::I64(100_000_000)]);
+    }
+    for _ in 0..2 {
+        let result = contract.call(&cache, 1, "ratio", &[Val::F64(0.0), Val::F64(0.0)])?;
+        assert!(result[0].unwrap_f64().is_nan());
+    }
+    assert_eq!(contract.executions(), 2);
+    let stats = cache.stats();
+    assert_eq!(stats.hits, 0);
+    assert_eq!(stats.misses, 4);
+    assert_eq!(stats.nondeterministic, 4);
+    assert_eq!(stats.entries, 0);
+    assert_eq!(contract.sink.snapshot().nondeterministic_view_calls, 4);
+    Ok(())
+}
+
+#[compiler_test(view_cache)]
+fn consensus_hash_does_not_depend_on_loading(config: crate::Config) -> Result<()> {
+    let engine = Universal::new(config.compiler_config(false)).engine();
+    let store = Store::new(&engine);
+    let executable = engine.compile_universal(&wat2wasm(WAT.as_bytes())?, store.tunables())?;
+    let serialized = executable.serialize().unwrap();
+    let deserialized = unsafe { UniversalExecutableRef::deserialize(&serialized)? };
+    let hash = Module::new(&store, WAT)?.consensus_hash();
+    assert_eq!(
+        Module::from_executable(&store, &executable)?.consensus_hash(),
+        hash
+    );
+    assert_eq!(
+        Module::from_executable(&store, &deserialized)?.consensus_hash(),
+        hash
+    );
+
+    // Modules with the same code but other data compute other results.
+    let with_data = |data: &str| -> Result<[u8; 32]> {
+        let wat = format!("(module (memory 1) (data (i32.const 0) {:?}))", data);
+        Ok(Module::new(&store, wat)?.consensus_hash())
+    };
+    assert_ne!(with_data("a")?, with_data("b")?);
+    assert_ne!(with_data("a")?, hash);
+    Ok(())
+}
@@ -56,7 +56,7 @@ pub fn run_wast(mut config: crate::Config, wast_path: &str) -> anyhow::Result<()
     if config.compiler == crate::Compiler::Singlepass {
         // We don't support multivalue yet in singlepass
         wast.allow_instantiation_failures(&[
-            "Validation error: invalid result arity: func type returns multiple values",
+            "needs the multi_value feature",
             "Validation error: blocks, loops, and ifs accept no parameters when multi-value is not enabled",
         ]);
     }